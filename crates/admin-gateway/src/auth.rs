@@ -0,0 +1,345 @@
+//! Caller authentication and route-level authorization.
+//!
+//! Every request is matched against [`route_policies`] by method and route
+//! pattern. Non-public routes need a bearer token that verifies against
+//! auth-service's JWKS (or the shared HS256 secret) and carries the route's
+//! permission. The verified identity is then forwarded to upstream services
+//! as a signed `x-quadrant-identity` header (`common::gateway_identity`);
+//! identity headers sent by clients are always dropped.
+//!
+//! Routes without a policy require authentication but no permission.
+//! Browsers cannot set headers on WebSocket or EventSource requests, so
+//! `/v1/live/` routes also accept the token as an `access_token` query
+//! parameter.
+
+use crate::{
+  config::GatewayAuthConfig,
+  error::ApiError,
+  realtime::{ACCESS_TOKEN_PARAM, LIVE_PREFIX},
+};
+use axum::{
+  extract::{MatchedPath, Request, State},
+  http::{HeaderMap, Method, Uri, header::AUTHORIZATION},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use common::{auth_middleware::AuthContext, gateway_identity, jwks::JwksVerifier};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tracing::{debug, error};
+
+/// Lifetime of identity tokens minted for upstream calls
+const IDENTITY_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+  Public,
+  Authenticated,
+  Permission(&'static str),
+  SystemAdmin,
+}
+
+#[derive(Debug, Clone)]
+pub struct RoutePolicy {
+  pub method: Method,
+  /// Route pattern as registered with the router, e.g. `/v1/streams/:id`
+  pub path: &'static str,
+  pub access: Access,
+}
+
+impl RoutePolicy {
+  fn new(method: Method, path: &'static str, access: Access) -> Self {
+    Self { method, path, access }
+  }
+}
+
+/// Access rules for the gateway's routes
+pub fn route_policies() -> Vec<RoutePolicy> {
+  use Access::*;
+  vec![
+    RoutePolicy::new(Method::GET, "/healthz", Public),
+    RoutePolicy::new(Method::GET, "/metrics", Public),
+    RoutePolicy::new(Method::GET, "/openapi.json", Public),
+    RoutePolicy::new(Method::GET, "/docs", Public),
+    RoutePolicy::new(Method::GET, "/v1/openapi.json", Public),
+    RoutePolicy::new(Method::GET, "/v1/docs", Public),
+    RoutePolicy::new(Method::GET, "/v1/streams", Permission("stream:read")),
+    RoutePolicy::new(Method::POST, "/v1/streams", Permission("stream:create")),
+    RoutePolicy::new(Method::DELETE, "/v1/streams/:id", Permission("stream:delete")),
+    RoutePolicy::new(Method::GET, "/v1/recordings", Permission("recording:read")),
+    RoutePolicy::new(Method::POST, "/v1/recordings", Permission("recording:create")),
+    RoutePolicy::new(Method::DELETE, "/v1/recordings/:id", Permission("recording:delete")),
+    RoutePolicy::new(Method::GET, "/v1/nodes", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/canary", SystemAdmin),
+    RoutePolicy::new(Method::PUT, "/v1/canary/:kind", SystemAdmin),
+    RoutePolicy::new(Method::DELETE, "/v1/canary/:kind", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/system/overview", Authenticated),
+    RoutePolicy::new(Method::POST, "/v1/onboarding", Permission("device:create")),
+    RoutePolicy::new(Method::GET, "/v1/analytics/cameras", Permission("device:read")),
+    RoutePolicy::new(Method::GET, "/v1/analytics/cameras/:camera_id", Permission("device:read")),
+    RoutePolicy::new(Method::PUT, "/v1/analytics/cameras/:camera_id", Permission("device:update")),
+    RoutePolicy::new(Method::DELETE, "/v1/analytics/cameras/:camera_id", Permission("device:update")),
+    RoutePolicy::new(Method::GET, "/v1/live/:service/*path", Authenticated),
+    RoutePolicy::new(Method::GET, "/v1/config/:service", SystemAdmin),
+    RoutePolicy::new(Method::PUT, "/v1/config/:service", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/config/:service/versions", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/config/:service/versions/:version", SystemAdmin),
+    RoutePolicy::new(Method::POST, "/v1/config/:service/rollback", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/usage", Authenticated),
+    RoutePolicy::new(Method::GET, "/v1/usage/tenants", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/timeline", Permission("audit:read")),
+    // Edge sites authenticate with FEDERATION_TOKEN, checked by the handler
+    RoutePolicy::new(Method::PUT, "/v1/federation/sites/:site_id", Public),
+    RoutePolicy::new(Method::GET, "/v1/federation/sites", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/federation/sites/:site_id", SystemAdmin),
+    RoutePolicy::new(Method::DELETE, "/v1/federation/sites/:site_id", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/federation/devices", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/federation/alerts", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/federation/health", SystemAdmin),
+    RoutePolicy::new(Method::GET, "/v1/federation/sites/:site_id/playback/*path", Permission("recording:read")),
+    RoutePolicy::new(Method::POST, "/v1/federation/sites/:site_id/playback/*path", Permission("recording:read")),
+    RoutePolicy::new(Method::DELETE, "/v1/federation/sites/:site_id/playback/*path", Permission("recording:read")),
+  ]
+}
+
+pub struct GatewayAuth {
+  verifier: JwksVerifier,
+  jwt_secret: String,
+  policies: HashMap<(Method, &'static str), Access>,
+}
+
+impl GatewayAuth {
+  pub fn new(config: &GatewayAuthConfig) -> Self {
+    let verifier = JwksVerifier::new(
+      config.jwks_url.as_str(),
+      Duration::from_secs(config.jwks_max_age_secs),
+    )
+    .with_hs256_secret(config.jwt_secret.clone());
+    Self::with_verifier(verifier, config.jwt_secret.clone(), route_policies())
+  }
+
+  pub fn with_verifier(verifier: JwksVerifier, jwt_secret: String, policies: Vec<RoutePolicy>) -> Self {
+    Self {
+      verifier,
+      jwt_secret,
+      policies: policies
+        .into_iter()
+        .map(|p| ((p.method, p.path), p.access))
+        .collect(),
+    }
+  }
+
+  pub fn access(&self, method: &Method, path: &str) -> Access {
+    self
+      .policies
+      .get(&(method.clone(), path))
+      .copied()
+      .unwrap_or(Access::Authenticated)
+  }
+
+  async fn authenticate(&self, headers: &HeaderMap, query_token: Option<&str>) -> Result<AuthContext, ApiError> {
+    let token = headers
+      .get(AUTHORIZATION)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| v.strip_prefix("Bearer "))
+      .or(query_token)
+      .ok_or_else(|| ApiError::unauthorized("missing or invalid Authorization header"))?;
+    let claims = self.verifier.verify(token).await.map_err(|e| {
+      debug!(error = %e, "rejected access token");
+      ApiError::unauthorized("invalid or expired token")
+    })?;
+    Ok(AuthContext::from(claims))
+  }
+}
+
+/// `access_token` query parameter of a live (WebSocket/SSE) request
+fn query_token(method: &Method, uri: &Uri) -> Option<String> {
+  if method != Method::GET || !uri.path().starts_with(LIVE_PREFIX) {
+    return None;
+  }
+  url::form_urlencoded::parse(uri.query()?.as_bytes())
+    .find(|(key, _)| key == ACCESS_TOKEN_PARAM)
+    .map(|(_, value)| value.into_owned())
+}
+
+fn authorize(ctx: &AuthContext, access: Access) -> Result<(), ApiError> {
+  match access {
+    Access::Public | Access::Authenticated => Ok(()),
+    Access::Permission(permission) if ctx.has_permission(permission) => Ok(()),
+    Access::Permission(permission) => Err(ApiError::forbidden(format!(
+      "permission '{permission}' required"
+    ))),
+    Access::SystemAdmin if ctx.is_system_admin => Ok(()),
+    Access::SystemAdmin => Err(ApiError::forbidden("system administrator required")),
+  }
+}
+
+pub async fn gateway_auth_middleware(
+  State(auth): State<Arc<GatewayAuth>>,
+  mut req: Request,
+  next: Next,
+) -> Response {
+  gateway_identity::strip(req.headers_mut());
+
+  let access = {
+    let path = req
+      .extensions()
+      .get::<MatchedPath>()
+      .map(|p| p.as_str().to_string())
+      .unwrap_or_else(|| req.uri().path().to_string());
+    auth.access(req.method(), &path)
+  };
+  if access == Access::Public {
+    return next.run(req).await;
+  }
+
+  let query_token = query_token(req.method(), req.uri());
+  let ctx = match auth.authenticate(req.headers(), query_token.as_deref()).await {
+    Ok(ctx) => ctx,
+    Err(e) => return e.into_response(),
+  };
+  if let Err(e) = authorize(&ctx, access) {
+    return e.into_response();
+  }
+
+  let identity = match gateway_identity::headers(&ctx, &auth.jwt_secret, IDENTITY_TTL) {
+    Ok(headers) => headers,
+    Err(e) => {
+      error!(error = %e, "failed to mint identity headers");
+      return ApiError::internal("failed to forward identity").into_response();
+    }
+  };
+  req.extensions_mut().insert(ctx);
+  gateway_identity::scope(identity, next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    middleware,
+    routing::get,
+  };
+  use common::auth_middleware::AuthClaims;
+  use jsonwebtoken::{EncodingKey, Header, encode};
+  use tower::ServiceExt;
+
+  const SECRET: &str = "test-secret";
+
+  fn token(permissions: &[&str], is_system_admin: bool) -> String {
+    let claims = AuthClaims {
+      sub: "user-1".into(),
+      tenant_id: "tenant-a".into(),
+      username: "alice".into(),
+      is_system_admin,
+      roles: vec![],
+      permissions: permissions.iter().map(|p| p.to_string()).collect(),
+      exp: i64::MAX / 2,
+      iat: 0,
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(SECRET.as_bytes())).unwrap()
+  }
+
+  /// `/v1/streams` echoes the identity tenant it would forward upstream
+  fn app() -> Router {
+    let verifier = JwksVerifier::new("http://127.0.0.1:9/.well-known/jwks.json", Duration::from_secs(60))
+      .with_hs256_secret(SECRET);
+    let auth = Arc::new(GatewayAuth::with_verifier(verifier, SECRET.into(), route_policies()));
+    Router::new()
+      .route("/healthz", get(|| async { "ok" }))
+      .route(
+        "/v1/streams",
+        get(|| async {
+          gateway_identity::current()
+            .and_then(|h| h.get(gateway_identity::TENANT_ID_HEADER).cloned())
+            .map(|v| v.to_str().unwrap_or_default().to_string())
+            .unwrap_or_default()
+        }),
+      )
+      .route("/v1/nodes", get(|| async { "nodes" }))
+      .route("/v1/live/:service/*path", get(|| async { "live" }))
+      .layer(middleware::from_fn_with_state(auth, gateway_auth_middleware))
+  }
+
+  async fn call(path: &str, bearer: Option<String>) -> (StatusCode, String) {
+    let mut req = Request::builder().uri(path);
+    if let Some(token) = bearer {
+      req = req.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let resp = app()
+      .oneshot(req.body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8_lossy(&body).to_string())
+  }
+
+  #[tokio::test]
+  async fn public_routes_skip_authentication() {
+    assert_eq!(call("/healthz", None).await.0, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn protected_routes_require_valid_token_and_permission() {
+    assert_eq!(call("/v1/streams", None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(call("/v1/streams", Some("not-a-jwt".into())).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+      call("/v1/streams", Some(token(&["recording:read"], false))).await.0,
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+      call("/v1/nodes", Some(token(&["stream:read"], false))).await.0,
+      StatusCode::FORBIDDEN
+    );
+    assert_eq!(call("/v1/nodes", Some(token(&[], true))).await.0, StatusCode::OK);
+  }
+
+  #[tokio::test]
+  async fn live_routes_accept_query_token() {
+    let query = format!("?access_token={}", token(&[], false));
+    assert_eq!(
+      call(&format!("/v1/live/alert-service/v1/events/ws{query}"), None).await,
+      (StatusCode::OK, "live".to_string())
+    );
+    assert_eq!(
+      call(&format!("/v1/streams{query}"), None).await.0,
+      StatusCode::UNAUTHORIZED
+    );
+  }
+
+  #[tokio::test]
+  async fn verified_identity_is_forwarded_upstream() {
+    let (status, tenant) = call("/v1/streams", Some(token(&["stream:read"], false))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tenant, "tenant-a");
+  }
+
+  #[tokio::test]
+  async fn client_supplied_identity_headers_are_dropped() {
+    let forged = gateway_identity::mint(
+      &AuthContext::from(AuthClaims {
+        sub: "root".into(),
+        tenant_id: "tenant-b".into(),
+        username: "root".into(),
+        is_system_admin: true,
+        roles: vec![],
+        permissions: vec![],
+        exp: 0,
+        iat: 0,
+      }),
+      SECRET,
+      IDENTITY_TTL,
+    )
+    .unwrap();
+    let req = Request::builder()
+      .uri("/v1/nodes")
+      .header(gateway_identity::IDENTITY_HEADER, forged)
+      .body(Body::empty())
+      .unwrap();
+    let resp = app().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+  }
+}
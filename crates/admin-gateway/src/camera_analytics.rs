@@ -0,0 +1,371 @@
+//! Zones and alarm classes per camera, for operators.
+//!
+//! operator-ui draws zones and tripwires over a camera's picture and picks
+//! the classes that raise alarms. `/v1/analytics/cameras/:camera_id` keeps
+//! them as the camera's entry of the central configuration document
+//! `camera-analytics`, which AI nodes follow and apply to the camera's tasks.
+//! Every change publishes a new version, so the history and rollback of
+//! `/v1/config/camera-analytics` cover these edits too. Edits racing on
+//! different cameras are retried against the newer version.
+
+use crate::{error::ApiError, state::AppState};
+use axum::{
+  Json,
+  extract::{Path, State},
+  http::StatusCode,
+};
+use common::{
+  ai_tasks::{CAMERA_ANALYTICS_SERVICE, CameraAnalytics, CameraAnalyticsDocument},
+  service_config::{ServiceConfig, ServiceConfigUpdate},
+};
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::Value;
+use std::{sync::LazyLock, time::Duration};
+use tracing::{info, warn};
+
+/// Publishes tried before a racing edit is reported as a conflict
+const MAX_ATTEMPTS: usize = 3;
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(3))
+    .timeout(Duration::from_secs(15))
+    .build()
+    .unwrap_or_default()
+});
+
+/// One camera's analytics and the document version they are part of
+#[derive(Debug, Clone, Serialize)]
+pub struct CameraAnalyticsEntry {
+  pub camera_id: String,
+  pub version: u64,
+  #[serde(flatten)]
+  pub analytics: CameraAnalytics,
+}
+
+fn config_url(state: &AppState) -> Result<Url, ApiError> {
+  state
+    .config()
+    .coordinator_base_url
+    .join(&format!("v1/config/{CAMERA_ANALYTICS_SERVICE}"))
+    .map_err(|_| ApiError::internal("invalid coordinator URL"))
+}
+
+fn validate_camera_id(camera_id: &str) -> Result<(), ApiError> {
+  common::validation::validate_id(camera_id, "camera_id")
+    .map_err(|e| ApiError::bad_request(format!("invalid camera_id: {e}")))
+}
+
+fn unavailable(e: reqwest::Error) -> ApiError {
+  warn!(error = %e, "camera analytics request to coordinator failed");
+  ApiError::new(StatusCode::BAD_GATEWAY, "coordinator unavailable")
+}
+
+/// Coordinator refusals are passed on; anything else is a gateway error
+async fn upstream_error(resp: reqwest::Response) -> ApiError {
+  let status = resp.status();
+  let body: Value = resp.json().await.unwrap_or(Value::Null);
+  let message = body["error"].as_str().unwrap_or("coordinator request failed").to_string();
+  if status.is_client_error() {
+    ApiError::new(status, message)
+  } else {
+    ApiError::new(StatusCode::BAD_GATEWAY, message)
+  }
+}
+
+/// Latest version of the document; version 0 and no cameras before the
+/// first publish
+async fn latest(state: &AppState) -> Result<(u64, CameraAnalyticsDocument), ApiError> {
+  let mut request = CLIENT.get(config_url(state)?);
+  if let Some(identity) = common::gateway_identity::current() {
+    request = request.headers(identity);
+  }
+  let resp = request.send().await.map_err(unavailable)?;
+  if resp.status() == StatusCode::NOT_FOUND {
+    return Ok((0, CameraAnalyticsDocument::default()));
+  }
+  if !resp.status().is_success() {
+    return Err(upstream_error(resp).await);
+  }
+  let config: ServiceConfig = resp.json().await.map_err(unavailable)?;
+  let document = serde_json::from_value(config.document)
+    .map_err(|e| ApiError::internal(format!("invalid camera analytics document: {e}")))?;
+  Ok((config.version, document))
+}
+
+/// Publish `document` unless a version after `expected_version` exists;
+/// `None` when one does
+async fn publish(
+  state: &AppState,
+  expected_version: u64,
+  document: &CameraAnalyticsDocument,
+  comment: String,
+) -> Result<Option<u64>, ApiError> {
+  let update = ServiceConfigUpdate {
+    document: serde_json::to_value(document).map_err(|e| ApiError::internal(e.to_string()))?,
+    comment: Some(comment),
+    expected_version: Some(expected_version),
+  };
+  let mut request = CLIENT.put(config_url(state)?).json(&update);
+  if let Some(identity) = common::gateway_identity::current() {
+    request = request.headers(identity);
+  }
+  let resp = request.send().await.map_err(unavailable)?;
+  if resp.status() == StatusCode::CONFLICT {
+    return Ok(None);
+  }
+  if !resp.status().is_success() {
+    return Err(upstream_error(resp).await);
+  }
+  let config: ServiceConfig = resp.json().await.map_err(unavailable)?;
+  Ok(Some(config.version))
+}
+
+/// Apply `change` to the latest document and publish it, retrying when
+/// another edit got in first; returns the new version
+async fn update(
+  state: &AppState,
+  comment: String,
+  change: impl Fn(&mut CameraAnalyticsDocument) -> Result<(), ApiError>,
+) -> Result<u64, ApiError> {
+  for _ in 0..MAX_ATTEMPTS {
+    let (version, mut document) = latest(state).await?;
+    change(&mut document)?;
+    if let Some(version) = publish(state, version, &document, comment.clone()).await? {
+      return Ok(version);
+    }
+  }
+  Err(ApiError::new(
+    StatusCode::CONFLICT,
+    "camera analytics kept changing concurrently, try again",
+  ))
+}
+
+/// Analytics of every configured camera
+pub async fn list_camera_analytics(State(state): State<AppState>) -> Result<Json<Vec<CameraAnalyticsEntry>>, ApiError> {
+  let (version, document) = latest(&state).await?;
+  Ok(Json(
+    document
+      .cameras
+      .into_iter()
+      .map(|(camera_id, analytics)| CameraAnalyticsEntry {
+        camera_id,
+        version,
+        analytics,
+      })
+      .collect(),
+  ))
+}
+
+pub async fn get_camera_analytics(
+  State(state): State<AppState>,
+  Path(camera_id): Path<String>,
+) -> Result<Json<CameraAnalyticsEntry>, ApiError> {
+  let (version, mut document) = latest(&state).await?;
+  let analytics = document
+    .cameras
+    .remove(&camera_id)
+    .ok_or_else(|| ApiError::not_found(format!("no analytics for camera '{camera_id}'")))?;
+  Ok(Json(CameraAnalyticsEntry {
+    camera_id,
+    version,
+    analytics,
+  }))
+}
+
+/// Replace a camera's zones, tripwires and alarm classes
+pub async fn put_camera_analytics(
+  State(state): State<AppState>,
+  Path(camera_id): Path<String>,
+  Json(analytics): Json<CameraAnalytics>,
+) -> Result<Json<CameraAnalyticsEntry>, ApiError> {
+  validate_camera_id(&camera_id)?;
+  analytics
+    .validate()
+    .map_err(|e| ApiError::bad_request(format!("invalid camera analytics: {e}")))?;
+  let version = update(&state, format!("analytics of camera {camera_id} updated"), |document| {
+    document.cameras.insert(camera_id.clone(), analytics.clone());
+    Ok(())
+  })
+  .await?;
+  info!(
+    camera_id = %camera_id,
+    version,
+    zones = analytics.zones.len(),
+    tripwires = analytics.tripwires.len(),
+    alarm_classes = analytics.alarm_classes.len(),
+    "camera analytics published"
+  );
+  Ok(Json(CameraAnalyticsEntry {
+    camera_id,
+    version,
+    analytics,
+  }))
+}
+
+/// Drop a camera's entry; its tasks lose their zones and alarm classes
+pub async fn delete_camera_analytics(
+  State(state): State<AppState>,
+  Path(camera_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+  validate_camera_id(&camera_id)?;
+  let version = update(&state, format!("analytics of camera {camera_id} removed"), |document| {
+    document
+      .cameras
+      .remove(&camera_id)
+      .map(drop)
+      .ok_or_else(|| ApiError::not_found(format!("no analytics for camera '{camera_id}'")))
+  })
+  .await?;
+  info!(camera_id = %camera_id, version, "camera analytics removed");
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::GatewayConfig,
+    coordinator::HttpCoordinatorClient,
+    routing::RoutingTable,
+    worker::{HttpRecorderClient, HttpWorkerClient},
+  };
+  use axum::{Router, routing::get};
+  use serde_json::json;
+  use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+  };
+  use tokio::net::TcpListener;
+
+  /// Coordinator stand-in keeping the `camera-analytics` versions; with
+  /// `race` set, another edit adding `cam-2` lands before the first publish
+  #[derive(Default)]
+  struct Coordinator {
+    versions: Mutex<Vec<Value>>,
+    race: Mutex<bool>,
+  }
+
+  impl Coordinator {
+    fn latest(&self) -> Option<(u64, Value)> {
+      let versions = self.versions.lock().unwrap();
+      versions.last().map(|document| (versions.len() as u64, document.clone()))
+    }
+
+    fn config(&self) -> Value {
+      let (version, document) = self.latest().unwrap();
+      json!({"service": CAMERA_ANALYTICS_SERVICE, "version": version, "created_at": 0, "document": document})
+    }
+  }
+
+  async fn spawn_coordinator(coordinator: Arc<Coordinator>) -> Url {
+    let app = Router::new()
+      .route(
+        "/v1/config/camera-analytics",
+        get(|State(c): State<Arc<Coordinator>>| async move {
+          match c.latest() {
+            Some(_) => (StatusCode::OK, Json(c.config())),
+            None => (StatusCode::NOT_FOUND, Json(json!({"error": "no configuration"}))),
+          }
+        })
+        .put(|State(c): State<Arc<Coordinator>>, Json(update): Json<ServiceConfigUpdate>| async move {
+          if std::mem::take(&mut *c.race.lock().unwrap()) {
+            let mut document = c.latest().map(|(_, d)| d).unwrap_or_else(|| json!({}));
+            document["cam-2"] = json!({"alarm_classes": ["car"]});
+            c.versions.lock().unwrap().push(document);
+          }
+          let current = c.latest().map_or(0, |(version, _)| version);
+          if update.expected_version != Some(current) {
+            return (StatusCode::CONFLICT, Json(json!({"error": "version conflict"})));
+          }
+          c.versions.lock().unwrap().push(update.document);
+          (StatusCode::CREATED, Json(c.config()))
+        }),
+      )
+      .with_state(coordinator);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      axum::serve(listener, app).await.unwrap();
+    });
+    Url::parse(&format!("http://{addr}/")).unwrap()
+  }
+
+  async fn state_for(backend: Url) -> AppState {
+    let config = GatewayConfig {
+      bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+      coordinator_base_url: backend.clone(),
+      node_id: "test-node".into(),
+      worker_base_url: backend.clone(),
+      recorder_base_url: backend.clone(),
+      node_discovery_interval_secs: 0,
+      node_unhealthy_threshold: 3,
+      overview_timeout_ms: 300,
+      auth: None,
+      device_manager_base_url: None,
+      ai_service_base_url: None,
+      playback_base_url: None,
+      alert_service_base_url: None,
+      federation_token: None,
+    };
+    let routing = Arc::new(RoutingTable::new(3));
+    let coordinator = Arc::new(HttpCoordinatorClient::new(backend).await.unwrap());
+    AppState::new(
+      config,
+      coordinator,
+      Arc::new(HttpWorkerClient::new(routing.clone())),
+      Arc::new(HttpRecorderClient::new(routing.clone())),
+      routing,
+    )
+  }
+
+  fn dock() -> CameraAnalytics {
+    serde_json::from_value(json!({
+      "zones": [{"id": "dock", "polygon": [{"x": 0, "y": 0}, {"x": 10, "y": 0}, {"x": 10, "y": 10}]}],
+      "alarm_classes": ["person"]
+    }))
+    .unwrap()
+  }
+
+  #[tokio::test]
+  async fn edits_keep_other_cameras_and_retry_racing_publishes() {
+    let coordinator = Arc::new(Coordinator::default());
+    let state = state_for(spawn_coordinator(coordinator.clone()).await).await;
+
+    let Json(entry) = put_camera_analytics(State(state.clone()), Path("cam-1".into()), Json(dock()))
+      .await
+      .unwrap();
+    assert_eq!(entry.version, 1);
+
+    *coordinator.race.lock().unwrap() = true;
+    let mut changed = dock();
+    changed.alarm_classes.push("forklift".into());
+    let Json(entry) = put_camera_analytics(State(state.clone()), Path("cam-1".into()), Json(changed))
+      .await
+      .unwrap();
+    assert_eq!(entry.version, 3);
+
+    let Json(cameras) = list_camera_analytics(State(state.clone())).await.unwrap();
+    let ids: Vec<_> = cameras.iter().map(|c| c.camera_id.as_str()).collect();
+    assert_eq!(ids, ["cam-1", "cam-2"]);
+    assert_eq!(cameras[0].analytics.alarm_classes, ["person", "forklift"]);
+
+    let status = delete_camera_analytics(State(state.clone()), Path("cam-2".into())).await.unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let missing = get_camera_analytics(State(state.clone()), Path("cam-2".into())).await;
+    assert!(missing.is_err());
+  }
+
+  #[tokio::test]
+  async fn invalid_zones_are_refused_before_publishing() {
+    let coordinator = Arc::new(Coordinator::default());
+    let state = state_for(spawn_coordinator(coordinator.clone()).await).await;
+
+    let mut broken = dock();
+    broken.zones[0].polygon.truncate(2);
+    let result = put_camera_analytics(State(state), Path("cam-1".into()), Json(broken)).await;
+    assert!(result.unwrap_err().to_string().contains("400"));
+    assert!(coordinator.latest().is_none());
+  }
+}
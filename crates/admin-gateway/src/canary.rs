@@ -0,0 +1,481 @@
+//! Weighted canary routing between node versions.
+//!
+//! A canary rule sends `weight` percent of new stream/recorder/AI requests of
+//! one node kind to nodes registered with the rule's `version` (see
+//! `NodeRegisterRequest::version`); the rest go to the other nodes of that
+//! kind. Call outcomes are tallied per group over a sliding window, and when
+//! the canary group's error rate crosses `max_error_rate` (and is worse than
+//! the baseline's) the rule's weight drops to 0 so new requests stay on the
+//! baseline until an operator re-arms it.
+//!
+//! Rules come from `CANARY_RULES` at startup and can be changed at runtime
+//! through `/v1/canary`.
+
+use anyhow::{Context, Result, bail};
+use common::nodes::NodeKind;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::HashMap,
+  env,
+  str::FromStr,
+  sync::atomic::{AtomicU64, Ordering},
+  time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Thresholds for automatic rollback
+#[derive(Debug, Clone)]
+pub struct CanaryPolicy {
+  pub rules: Vec<CanaryRule>,
+  /// Canary error rate (0.0-1.0) that triggers a rollback
+  pub max_error_rate: f64,
+  /// Canary calls needed in a window before the error rate is trusted
+  pub min_requests: u64,
+  /// Length of the outcome window
+  pub window: Duration,
+}
+
+impl Default for CanaryPolicy {
+  fn default() -> Self {
+    Self {
+      rules: Vec::new(),
+      max_error_rate: 0.25,
+      min_requests: 20,
+      window: Duration::from_secs(300),
+    }
+  }
+}
+
+impl CanaryPolicy {
+  /// Read `CANARY_RULES` (`stream:1.5.0=10,recorder:1.5.0=5`),
+  /// `CANARY_MAX_ERROR_RATE`, `CANARY_MIN_REQUESTS` and `CANARY_WINDOW_SECS`
+  pub fn from_env() -> Result<Self> {
+    let defaults = Self::default();
+    let rules = match env::var("CANARY_RULES") {
+      Ok(rules) => rules
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<CanaryRule>>>()
+        .context("invalid CANARY_RULES")?,
+      Err(_) => Vec::new(),
+    };
+    let max_error_rate = env::var("CANARY_MAX_ERROR_RATE")
+      .ok()
+      .and_then(|v| v.parse::<f64>().ok())
+      .filter(|v| (0.0..=1.0).contains(v))
+      .unwrap_or(defaults.max_error_rate);
+    let min_requests = env::var("CANARY_MIN_REQUESTS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(defaults.min_requests)
+      .max(1);
+    let window = env::var("CANARY_WINDOW_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .map(|secs| Duration::from_secs(secs.max(1)))
+      .unwrap_or(defaults.window);
+
+    Ok(Self {
+      rules,
+      max_error_rate,
+      min_requests,
+      window,
+    })
+  }
+}
+
+/// Route `weight` percent of new requests of `kind` to nodes of `version`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CanaryRule {
+  pub kind: NodeKind,
+  pub version: String,
+  pub weight: u8,
+}
+
+impl CanaryRule {
+  pub fn new(kind: NodeKind, version: impl Into<String>, weight: u8) -> Result<Self> {
+    let version = version.into();
+    if version.trim().is_empty() {
+      bail!("canary version must not be empty");
+    }
+    if weight > 100 {
+      bail!("canary weight must be between 0 and 100");
+    }
+    Ok(Self { kind, version, weight })
+  }
+}
+
+impl FromStr for CanaryRule {
+  type Err = anyhow::Error;
+
+  /// `kind:version=weight`
+  fn from_str(s: &str) -> Result<Self> {
+    let (target, weight) = s
+      .rsplit_once('=')
+      .with_context(|| format!("expected kind:version=weight, got '{s}'"))?;
+    let (kind, version) = target
+      .split_once(':')
+      .with_context(|| format!("expected kind:version=weight, got '{s}'"))?;
+    let kind = kind.trim().parse::<NodeKind>().map_err(anyhow::Error::msg)?;
+    let weight = weight
+      .trim()
+      .parse::<u8>()
+      .with_context(|| format!("invalid canary weight in '{s}'"))?;
+    Self::new(kind, version.trim(), weight)
+  }
+}
+
+/// Which side of a rule a node is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanaryGroup {
+  Canary,
+  Baseline,
+}
+
+impl CanaryGroup {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CanaryGroup::Canary => "canary",
+      CanaryGroup::Baseline => "baseline",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct Outcomes {
+  pub requests: u64,
+  pub errors: u64,
+}
+
+impl Outcomes {
+  fn record(&mut self, success: bool) {
+    self.requests += 1;
+    if !success {
+      self.errors += 1;
+    }
+  }
+
+  pub fn error_rate(&self) -> f64 {
+    if self.requests == 0 {
+      0.0
+    } else {
+      self.errors as f64 / self.requests as f64
+    }
+  }
+}
+
+/// Rule state as reported by `GET /v1/canary`
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryStatus {
+  pub kind: NodeKind,
+  pub version: String,
+  /// Weight currently applied; 0 after a rollback
+  pub weight: u8,
+  /// Weight the rule was configured with
+  pub configured_weight: u8,
+  pub rolled_back: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub rollback_reason: Option<String>,
+  pub canary: Outcomes,
+  pub baseline: Outcomes,
+}
+
+struct RuleState {
+  rule: CanaryRule,
+  weight: u8,
+  rollback_reason: Option<String>,
+  window_started: Instant,
+  canary: Outcomes,
+  baseline: Outcomes,
+}
+
+impl RuleState {
+  fn new(rule: CanaryRule) -> Self {
+    Self {
+      weight: rule.weight,
+      rule,
+      rollback_reason: None,
+      window_started: Instant::now(),
+      canary: Outcomes::default(),
+      baseline: Outcomes::default(),
+    }
+  }
+
+  fn status(&self) -> CanaryStatus {
+    CanaryStatus {
+      kind: self.rule.kind,
+      version: self.rule.version.clone(),
+      weight: self.weight,
+      configured_weight: self.rule.weight,
+      rolled_back: self.rollback_reason.is_some(),
+      rollback_reason: self.rollback_reason.clone(),
+      canary: self.canary,
+      baseline: self.baseline,
+    }
+  }
+}
+
+/// Canary rules per node kind plus their outcome windows
+pub struct CanaryRouter {
+  rules: RwLock<HashMap<NodeKind, RuleState>>,
+  policy: CanaryPolicy,
+  /// Per-kind request counters driving the weighted split
+  sequence: [AtomicU64; 3],
+}
+
+impl Default for CanaryRouter {
+  fn default() -> Self {
+    Self::new(CanaryPolicy::default())
+  }
+}
+
+impl CanaryRouter {
+  pub fn new(policy: CanaryPolicy) -> Self {
+    let rules = policy
+      .rules
+      .iter()
+      .cloned()
+      .map(|rule| (rule.kind, RuleState::new(rule)))
+      .collect();
+    let router = Self {
+      rules: RwLock::new(rules),
+      policy,
+      sequence: Default::default(),
+    };
+    for rule in &router.policy.rules {
+      set_weight_metric(rule.kind, &rule.version, rule.weight);
+    }
+    router
+  }
+
+  fn sequence(&self, kind: NodeKind) -> &AtomicU64 {
+    match kind {
+      NodeKind::Stream => &self.sequence[0],
+      NodeKind::Recorder => &self.sequence[1],
+      NodeKind::Ai => &self.sequence[2],
+    }
+  }
+
+  /// Install or replace the rule for `rule.kind`, clearing any rollback
+  pub async fn set_rule(&self, rule: CanaryRule) {
+    info!(kind = %rule.kind, version = %rule.version, weight = rule.weight, "canary rule set");
+    let (kind, version, weight) = (rule.kind, rule.version.clone(), rule.weight);
+    let previous = self.rules.write().await.insert(kind, RuleState::new(rule));
+    if let Some(previous) = previous.filter(|p| p.rule.version != version) {
+      telemetry::metrics::ADMIN_GATEWAY_CANARY_WEIGHT
+        .remove_label_values(&[kind.as_str(), &previous.rule.version])
+        .ok();
+    }
+    set_weight_metric(kind, &version, weight);
+  }
+
+  pub async fn remove_rule(&self, kind: NodeKind) -> bool {
+    let removed = self.rules.write().await.remove(&kind);
+    if let Some(state) = &removed {
+      info!(kind = %kind, version = %state.rule.version, "canary rule removed");
+      telemetry::metrics::ADMIN_GATEWAY_CANARY_WEIGHT
+        .remove_label_values(&[kind.as_str(), &state.rule.version])
+        .ok();
+    }
+    removed.is_some()
+  }
+
+  pub async fn status(&self) -> Vec<CanaryStatus> {
+    let rules = self.rules.read().await;
+    NodeKind::ALL
+      .into_iter()
+      .filter_map(|kind| rules.get(&kind).map(RuleState::status))
+      .collect()
+  }
+
+  /// Restrict `nodes` (with their versions) to the group the next request of
+  /// `kind` should go to. Nodes are returned unchanged when there is no rule
+  /// or only one group has nodes.
+  pub async fn select<T>(&self, kind: NodeKind, nodes: Vec<(T, Option<String>)>) -> Vec<T> {
+    let rules = self.rules.read().await;
+    let Some(state) = rules.get(&kind) else {
+      return nodes.into_iter().map(|(node, _)| node).collect();
+    };
+
+    let (canary, baseline): (Vec<_>, Vec<_>) = nodes
+      .into_iter()
+      .partition(|(_, version)| group_of(&state.rule, version.as_deref()) == CanaryGroup::Canary);
+    let canary: Vec<T> = canary.into_iter().map(|(node, _)| node).collect();
+    let baseline: Vec<T> = baseline.into_iter().map(|(node, _)| node).collect();
+    if canary.is_empty() {
+      return baseline;
+    }
+    if baseline.is_empty() {
+      return canary;
+    }
+
+    // Spread canary picks evenly: request n goes to the canary when
+    // floor((n + 1) * w / 100) advances
+    let weight = u64::from(state.weight);
+    let n = self.sequence(kind).fetch_add(1, Ordering::Relaxed);
+    if (n + 1) * weight / 100 > n * weight / 100 {
+      canary
+    } else {
+      baseline
+    }
+  }
+
+  /// Record the outcome of a request served by a node running `version`,
+  /// rolling the canary back when its error rate spikes
+  pub async fn observe(&self, kind: NodeKind, version: Option<&str>, success: bool) {
+    let mut rules = self.rules.write().await;
+    let Some(state) = rules.get_mut(&kind) else {
+      return;
+    };
+
+    if state.window_started.elapsed() >= self.policy.window {
+      state.window_started = Instant::now();
+      state.canary = Outcomes::default();
+      state.baseline = Outcomes::default();
+    }
+
+    let group = group_of(&state.rule, version);
+    match group {
+      CanaryGroup::Canary => state.canary.record(success),
+      CanaryGroup::Baseline => state.baseline.record(success),
+    }
+    telemetry::metrics::ADMIN_GATEWAY_CANARY_REQUESTS
+      .with_label_values(&[kind.as_str(), group.as_str(), if success { "success" } else { "error" }])
+      .inc();
+
+    let canary_rate = state.canary.error_rate();
+    if state.weight > 0
+      && state.canary.requests >= self.policy.min_requests
+      && canary_rate > self.policy.max_error_rate
+      && canary_rate > state.baseline.error_rate()
+    {
+      let reason = format!(
+        "canary error rate {:.0}% over {} requests (baseline {:.0}%)",
+        canary_rate * 100.0,
+        state.canary.requests,
+        state.baseline.error_rate() * 100.0
+      );
+      warn!(kind = %kind, version = %state.rule.version, reason = %reason, "rolling back canary");
+      state.weight = 0;
+      state.rollback_reason = Some(reason);
+      set_weight_metric(kind, &state.rule.version, 0);
+      telemetry::metrics::ADMIN_GATEWAY_CANARY_ROLLBACKS
+        .with_label_values(&[kind.as_str()])
+        .inc();
+    }
+  }
+}
+
+fn group_of(rule: &CanaryRule, version: Option<&str>) -> CanaryGroup {
+  if version == Some(rule.version.as_str()) {
+    CanaryGroup::Canary
+  } else {
+    CanaryGroup::Baseline
+  }
+}
+
+fn set_weight_metric(kind: NodeKind, version: &str, weight: u8) {
+  telemetry::metrics::ADMIN_GATEWAY_CANARY_WEIGHT
+    .with_label_values(&[kind.as_str(), version])
+    .set(i64::from(weight));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn nodes() -> Vec<(&'static str, Option<String>)> {
+    vec![
+      ("sn-1", Some("1.4.0".to_string())),
+      ("sn-2", None),
+      ("sn-3", Some("1.5.0".to_string())),
+    ]
+  }
+
+  fn policy(rule: &str) -> CanaryPolicy {
+    CanaryPolicy {
+      rules: vec![rule.parse().unwrap()],
+      min_requests: 4,
+      max_error_rate: 0.5,
+      ..CanaryPolicy::default()
+    }
+  }
+
+  #[test]
+  fn parses_rules() {
+    let rule: CanaryRule = "stream:1.5.0-rc.1=15".parse().unwrap();
+    assert_eq!(rule, CanaryRule::new(NodeKind::Stream, "1.5.0-rc.1", 15).unwrap());
+    for bad in ["stream=10", "stream:1.5.0", "pipeline:1.0=5", "stream:1.0=101", "stream:=5"] {
+      assert!(bad.parse::<CanaryRule>().is_err(), "{bad}");
+    }
+  }
+
+  #[tokio::test]
+  async fn splits_requests_by_weight() {
+    let router = CanaryRouter::new(policy("stream:1.5.0=20"));
+    let mut canary = 0;
+    for _ in 0..100 {
+      let picked = router.select(NodeKind::Stream, nodes()).await;
+      if picked == ["sn-3"] {
+        canary += 1;
+      } else {
+        assert_eq!(picked, ["sn-1", "sn-2"]);
+      }
+    }
+    assert_eq!(canary, 20);
+
+    // No rule for recorders: every node stays eligible
+    assert_eq!(router.select(NodeKind::Recorder, nodes()).await.len(), 3);
+  }
+
+  #[tokio::test]
+  async fn single_group_gets_everything() {
+    let router = CanaryRouter::new(policy("stream:9.9.9=50"));
+    for _ in 0..4 {
+      assert_eq!(router.select(NodeKind::Stream, nodes()).await.len(), 3);
+    }
+  }
+
+  #[tokio::test]
+  async fn rolls_back_when_canary_errors_spike() {
+    let router = CanaryRouter::new(policy("stream:1.5.0=50"));
+    for _ in 0..4 {
+      router.observe(NodeKind::Stream, None, true).await;
+    }
+    router.observe(NodeKind::Stream, Some("1.5.0"), true).await;
+    for _ in 0..2 {
+      router.observe(NodeKind::Stream, Some("1.5.0"), false).await;
+    }
+    // Too few canary calls to judge yet
+    assert_eq!(router.status().await[0].weight, 50);
+
+    router.observe(NodeKind::Stream, Some("1.5.0"), false).await;
+    let status = &router.status().await[0];
+    assert_eq!(status.weight, 0);
+    assert!(status.rolled_back);
+    assert_eq!(status.canary, Outcomes { requests: 4, errors: 3 });
+    for _ in 0..10 {
+      assert_eq!(router.select(NodeKind::Stream, nodes()).await, ["sn-1", "sn-2"]);
+    }
+
+    // Re-arming clears the rollback and the window
+    router.set_rule(CanaryRule::new(NodeKind::Stream, "1.5.0", 50).unwrap()).await;
+    let status = &router.status().await[0];
+    assert_eq!(status.weight, 50);
+    assert!(!status.rolled_back);
+    assert_eq!(status.canary, Outcomes::default());
+  }
+
+  #[tokio::test]
+  async fn no_rollback_when_baseline_is_as_bad() {
+    let router = CanaryRouter::new(policy("stream:1.5.0=50"));
+    for _ in 0..4 {
+      router.observe(NodeKind::Stream, Some("1.5.0"), false).await;
+      router.observe(NodeKind::Stream, Some("1.4.0"), false).await;
+    }
+    assert_eq!(router.status().await[0].weight, 50);
+  }
+}
@@ -9,6 +9,66 @@ pub struct GatewayConfig {
   pub node_id: String,
   pub worker_base_url: Url,
   pub recorder_base_url: Url,
+  /// How often to pull node registrations from the coordinator; 0 disables
+  /// discovery and routes only to the static worker/recorder endpoints
+  pub node_discovery_interval_secs: u64,
+  /// Consecutive failures before a node is taken out of rotation
+  pub node_unhealthy_threshold: u32,
+  /// Per-source timeout for `/v1/system/overview` fan-out calls
+  pub overview_timeout_ms: u64,
+  /// Caller authentication; `None` serves every route unauthenticated
+  pub auth: Option<GatewayAuthConfig>,
+  pub device_manager_base_url: Option<Url>,
+  pub ai_service_base_url: Option<Url>,
+  pub playback_base_url: Option<Url>,
+  pub alert_service_base_url: Option<Url>,
+  /// Shared secret edge sites present when reporting to this (central)
+  /// gateway, and that this gateway presents when reporting as an edge site
+  pub federation_token: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct GatewayAuthConfig {
+  /// auth-service key set used to verify RS256 access tokens
+  pub jwks_url: Url,
+  /// Shared `JWT_SECRET`: verifies HS256 tokens and signs identity headers
+  pub jwt_secret: String,
+  /// How long a fetched key set is trusted before it is refreshed
+  pub jwks_max_age_secs: u64,
+}
+
+impl GatewayAuthConfig {
+  fn from_env() -> Result<Option<Self>> {
+    let enabled = env::var("GATEWAY_AUTH_ENABLED")
+      .map(|v| !matches!(v.to_lowercase().as_str(), "false" | "0" | "no"))
+      .unwrap_or(true);
+    if !enabled {
+      tracing::warn!("GATEWAY_AUTH_ENABLED=false, gateway routes are unauthenticated");
+      return Ok(None);
+    }
+
+    let auth = env::var("AUTH_SERVICE_ENDPOINT")
+      .unwrap_or_else(|_| "http://127.0.0.1:8087/".to_string());
+    let jwks_url = Url::parse(&auth)
+      .and_then(|base| base.join(common::jwks::JWKS_PATH))
+      .context("invalid AUTH_SERVICE_ENDPOINT")?;
+
+    let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| {
+      tracing::warn!("JWT_SECRET not set, using default (INSECURE for production!)");
+      "default-jwt-secret-CHANGE-IN-PRODUCTION".to_string()
+    });
+
+    let jwks_max_age_secs = env::var("JWKS_MAX_AGE_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(300);
+
+    Ok(Some(Self {
+      jwks_url,
+      jwt_secret,
+      jwks_max_age_secs,
+    }))
+  }
 }
 
 impl GatewayConfig {
@@ -28,14 +88,71 @@ impl GatewayConfig {
       env::var("RECORDER_WORKER_ENDPOINT").unwrap_or_else(|_| "http://127.0.0.1:8083/".to_string());
     let recorder_base_url = Url::parse(&recorder).context("invalid RECORDER_WORKER_ENDPOINT")?;
 
+    let node_discovery_interval_secs = env::var("NODE_DISCOVERY_INTERVAL_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(10);
+
+    let node_unhealthy_threshold = env::var("NODE_UNHEALTHY_THRESHOLD")
+      .ok()
+      .and_then(|v| v.parse::<u32>().ok())
+      .unwrap_or(3);
+
+    let overview_timeout_ms = env::var("OVERVIEW_TIMEOUT_MS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(2000);
+
+    let auth = GatewayAuthConfig::from_env()?;
+
     let node_id = env::var("NODE_ID").unwrap_or_else(|_| uuid::Uuid::new_v4().to_string());
 
+    let device_manager_base_url = optional_url("DEVICE_MANAGER_ENDPOINT")?;
+    let ai_service_base_url = optional_url("AI_SERVICE_ENDPOINT")?;
+    let playback_base_url = optional_url("PLAYBACK_SERVICE_ENDPOINT")?;
+    let alert_service_base_url = optional_url("ALERT_SERVICE_ENDPOINT")?;
+    let federation_token = env::var("FEDERATION_TOKEN").ok().filter(|v| !v.trim().is_empty());
+
     Ok(Self {
       bind_addr,
       coordinator_base_url,
       node_id,
       worker_base_url,
       recorder_base_url,
+      node_discovery_interval_secs,
+      node_unhealthy_threshold,
+      overview_timeout_ms,
+      auth,
+      device_manager_base_url,
+      ai_service_base_url,
+      playback_base_url,
+      alert_service_base_url,
+      federation_token,
     })
   }
+
+  /// Base URLs of the backend services the gateway knows about, by service name
+  pub fn service_endpoints(&self) -> Vec<(&'static str, Url)> {
+    let mut endpoints = vec![("coordinator", self.coordinator_base_url.clone())];
+    let optional = [
+      ("device-manager", &self.device_manager_base_url),
+      ("ai-service", &self.ai_service_base_url),
+      ("playback-service", &self.playback_base_url),
+      ("alert-service", &self.alert_service_base_url),
+    ];
+    for (name, url) in optional {
+      if let Some(url) = url {
+        endpoints.push((name, url.clone()));
+      }
+    }
+    endpoints
+  }
+}
+
+fn optional_url(var: &str) -> Result<Option<Url>> {
+  env::var(var)
+    .ok()
+    .filter(|v| !v.trim().is_empty())
+    .map(|v| Url::parse(&v).with_context(|| format!("invalid {var}")))
+    .transpose()
 }
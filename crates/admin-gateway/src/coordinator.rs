@@ -4,8 +4,9 @@ use common::leases::{
   LeaseAcquireRequest, LeaseAcquireResponse, LeaseReleaseRequest, LeaseReleaseResponse,
   LeaseRenewRequest, LeaseRenewResponse,
 };
+use common::nodes::{NodeKind, NodeRecord};
+use common::resilient_http::ResilientClient;
 use reqwest::Url;
-use std::time::Duration;
 use tracing::instrument;
 
 #[async_trait]
@@ -13,19 +14,17 @@ pub trait CoordinatorClient: Send + Sync {
   async fn acquire(&self, request: &LeaseAcquireRequest) -> Result<LeaseAcquireResponse>;
   async fn renew(&self, request: &LeaseRenewRequest) -> Result<LeaseRenewResponse>;
   async fn release(&self, request: &LeaseReleaseRequest) -> Result<LeaseReleaseResponse>;
+  async fn list_nodes(&self, kind: Option<NodeKind>) -> Result<Vec<NodeRecord>>;
 }
 
 pub struct HttpCoordinatorClient {
   base: Url,
-  client: reqwest::Client,
+  client: ResilientClient,
 }
 
 impl HttpCoordinatorClient {
-  pub fn new(base: Url) -> Result<Self> {
-    let client = reqwest::Client::builder()
-      .connect_timeout(Duration::from_secs(3))
-      .timeout(Duration::from_secs(10))
-      .build()?;
+  pub async fn new(base: Url) -> Result<Self> {
+    let client = ResilientClient::builder("coordinator").build().await?;
     Ok(Self { base, client })
   }
 
@@ -41,9 +40,7 @@ impl CoordinatorClient for HttpCoordinatorClient {
     let url = self.endpoint("v1/leases/acquire")?;
     let resp = self
       .client
-      .post(url)
-      .json(request)
-      .send()
+      .send(|c| c.post(url.clone()).json(request))
       .await
       .context("coordinator acquire request failed")?;
     let resp = resp
@@ -62,9 +59,7 @@ impl CoordinatorClient for HttpCoordinatorClient {
     let url = self.endpoint("v1/leases/renew")?;
     let resp = self
       .client
-      .post(url)
-      .json(request)
-      .send()
+      .send(|c| c.post(url.clone()).json(request))
       .await
       .context("coordinator renew request failed")?;
     let resp = resp
@@ -83,9 +78,7 @@ impl CoordinatorClient for HttpCoordinatorClient {
     let url = self.endpoint("v1/leases/release")?;
     let resp = self
       .client
-      .post(url)
-      .json(request)
-      .send()
+      .send(|c| c.post(url.clone()).json(request))
       .await
       .context("coordinator release request failed")?;
     let resp = resp
@@ -98,4 +91,26 @@ impl CoordinatorClient for HttpCoordinatorClient {
         .context("failed to parse release response")?,
     )
   }
+
+  #[instrument(skip_all)]
+  async fn list_nodes(&self, kind: Option<NodeKind>) -> Result<Vec<NodeRecord>> {
+    let mut url = self.endpoint("v1/nodes")?;
+    if let Some(kind) = kind {
+      url.query_pairs_mut().append_pair("kind", kind.as_str());
+    }
+    let resp = self
+      .client
+      .send(|c| c.get(url.clone()))
+      .await
+      .context("coordinator list nodes request failed")?;
+    let resp = resp
+      .error_for_status()
+      .context("coordinator list nodes returned error status")?;
+    Ok(
+      resp
+        .json()
+        .await
+        .context("failed to parse node list response")?,
+    )
+  }
 }
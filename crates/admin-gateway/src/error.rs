@@ -24,6 +24,14 @@ impl ApiError {
     Self::new(StatusCode::BAD_REQUEST, message)
   }
 
+  pub fn unauthorized(message: impl Into<String>) -> Self {
+    Self::new(StatusCode::UNAUTHORIZED, message)
+  }
+
+  pub fn forbidden(message: impl Into<String>) -> Self {
+    Self::new(StatusCode::FORBIDDEN, message)
+  }
+
   pub fn not_found(message: impl Into<String>) -> Self {
     Self::new(StatusCode::NOT_FOUND, message)
   }
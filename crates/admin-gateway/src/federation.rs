@@ -0,0 +1,498 @@
+//! Multi-site federation.
+//!
+//! A gateway can play either role, or both:
+//!
+//! * **Edge**: with `FEDERATION_CENTRAL_URL` and `FEDERATION_SITE_ID` set,
+//!   [`SiteReporter`] periodically sends the central gateway a report with
+//!   this site's health (from the system overview), device inventory and
+//!   recent alerts (from the coordinator's event timeline). Failed reports are
+//!   only logged; the site keeps operating on its own while the WAN is down
+//!   and the next report catches the central instance up.
+//! * **Central**: sites `PUT /v1/federation/sites/:site_id` with the shared
+//!   `FEDERATION_TOKEN`. Reports are kept by the coordinator's site registry
+//!   and operators read the aggregated `/v1/federation/*` views through this
+//!   gateway. `/v1/federation/sites/:site_id/playback/*path` proxies playback
+//!   to the site that owns the recording, using the playback URL it reported.
+
+use crate::{config::GatewayConfig, error::ApiError, overview, state::AppState};
+use anyhow::{Context, Result};
+use axum::{
+  Json,
+  body::{Body, Bytes},
+  extract::{OriginalUri, Path, RawQuery, State},
+  http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+  response::Response,
+};
+use common::{
+  auth_middleware::AuthContext,
+  federation::{
+    MAX_REPORT_ALERTS, MAX_REPORT_DEVICES, SiteAlert, SiteDevice, SiteHealth, SiteRecord, SiteReport, SiteStatus,
+  },
+  gateway_identity,
+  timeline::{TimelineEvent, TimelineEventKind},
+};
+use reqwest::Url;
+use serde_json::json;
+use std::{
+  env,
+  sync::LazyLock,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Route edge sites report to; not recorded as an operator action
+pub const REPORT_ROUTE: &str = "/v1/federation/sites/:site_id";
+
+/// How far back the alert section of a report looks
+const ALERT_WINDOW: Duration = Duration::from_secs(24 * 3600);
+
+/// Request headers passed on to a site's playback service
+const PLAYBACK_REQUEST_HEADERS: [header::HeaderName; 5] = [
+  header::ACCEPT,
+  header::CONTENT_TYPE,
+  header::RANGE,
+  header::IF_NONE_MATCH,
+  header::IF_MODIFIED_SINCE,
+];
+
+/// Response headers passed back from a site's playback service
+const PLAYBACK_RESPONSE_HEADERS: [header::HeaderName; 7] = [
+  header::CONTENT_TYPE,
+  header::CONTENT_LENGTH,
+  header::CONTENT_RANGE,
+  header::ACCEPT_RANGES,
+  header::CACHE_CONTROL,
+  header::ETAG,
+  header::LAST_MODIFIED,
+];
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(5))
+    .timeout(Duration::from_secs(30))
+    .build()
+    .unwrap_or_default()
+});
+
+fn now_epoch_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default()
+}
+
+/// Compare without an early exit so response timing does not leak the token
+fn token_matches(expected: &str, presented: &str) -> bool {
+  expected.len() == presented.len()
+    && expected
+      .bytes()
+      .zip(presented.bytes())
+      .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+      == 0
+}
+
+fn coordinator_url(state: &AppState, path: &str) -> Result<Url, ApiError> {
+  state
+    .config()
+    .coordinator_base_url
+    .join(path)
+    .map_err(|_| ApiError::internal("invalid coordinator URL"))
+}
+
+/// Relay an upstream answer as-is
+async fn passthrough(upstream: reqwest::Response) -> Result<Response, ApiError> {
+  let status = upstream.status();
+  let body = upstream
+    .bytes()
+    .await
+    .map_err(|_| ApiError::new(StatusCode::BAD_GATEWAY, "coordinator response failed"))?;
+  let mut response = Response::new(Body::from(body));
+  *response.status_mut() = status;
+  response
+    .headers_mut()
+    .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+  Ok(response)
+}
+
+fn coordinator_unavailable(e: reqwest::Error) -> ApiError {
+  warn!(error = %e, "federation request to coordinator failed");
+  ApiError::new(StatusCode::BAD_GATEWAY, "coordinator unavailable")
+}
+
+/// Accept a report from an edge site (central role)
+pub async fn receive_report(
+  State(state): State<AppState>,
+  Path(site_id): Path<String>,
+  headers: HeaderMap,
+  Json(report): Json<SiteReport>,
+) -> Result<Response, ApiError> {
+  let Some(expected) = &state.config().federation_token else {
+    return Err(ApiError::not_found("federation is not enabled on this gateway"));
+  };
+  let presented = headers
+    .get(header::AUTHORIZATION)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.strip_prefix("Bearer "))
+    .unwrap_or_default();
+  if !token_matches(expected, presented) {
+    return Err(ApiError::unauthorized("invalid federation token"));
+  }
+  if report.site_id != site_id {
+    return Err(ApiError::bad_request("site_id in the report does not match the path"));
+  }
+
+  let upstream = CLIENT
+    .post(coordinator_url(&state, "v1/federation/sites/report")?)
+    .json(&report)
+    .send()
+    .await
+    .map_err(coordinator_unavailable)?;
+  passthrough(upstream).await
+}
+
+/// Aggregated federation reads; gateway and coordinator share the paths
+pub async fn forward_read(State(state): State<AppState>, OriginalUri(uri): OriginalUri) -> Result<Response, ApiError> {
+  let path_and_query = uri.path_and_query().map(|p| p.as_str()).unwrap_or(uri.path());
+  let upstream = CLIENT
+    .get(coordinator_url(&state, path_and_query.trim_start_matches('/'))?)
+    .send()
+    .await
+    .map_err(coordinator_unavailable)?;
+  passthrough(upstream).await
+}
+
+/// Forget a site; it reappears if it reports again
+pub async fn remove_site(State(state): State<AppState>, Path(site_id): Path<String>) -> Result<Response, ApiError> {
+  let upstream = CLIENT
+    .post(coordinator_url(&state, "v1/federation/sites/remove")?)
+    .json(&json!({ "site_id": site_id }))
+    .send()
+    .await
+    .map_err(coordinator_unavailable)?;
+  passthrough(upstream).await
+}
+
+/// Site registration as known to the coordinator
+async fn site(state: &AppState, site_id: &str) -> Result<SiteRecord, ApiError> {
+  common::validation::validate_id(site_id, "site_id")
+    .map_err(|e| ApiError::bad_request(format!("invalid site_id: {e}")))?;
+  let upstream = CLIENT
+    .get(coordinator_url(state, &format!("v1/federation/sites/{site_id}"))?)
+    .send()
+    .await
+    .map_err(coordinator_unavailable)?;
+  match upstream.status() {
+    StatusCode::NOT_FOUND => Err(ApiError::not_found(format!("site '{site_id}' not found"))),
+    status if !status.is_success() => Err(ApiError::new(StatusCode::BAD_GATEWAY, "site lookup failed")),
+    _ => upstream
+      .json()
+      .await
+      .map_err(|_| ApiError::new(StatusCode::BAD_GATEWAY, "invalid site record")),
+  }
+}
+
+/// Playback URL on `site` for `path`, refusing path traversal
+fn playback_url(site: &SiteRecord, path: &str, query: Option<&str>) -> Result<Url, ApiError> {
+  if path.split('/').any(|segment| segment == ".." || segment == ".") {
+    return Err(ApiError::bad_request("invalid path"));
+  }
+  let base = site
+    .playback_url
+    .as_deref()
+    .ok_or_else(|| ApiError::not_found(format!("site '{}' does not expose playback", site.site_id)))?;
+  let mut url = Url::parse(base)
+    .and_then(|base| base.join(path.trim_start_matches('/')))
+    .map_err(|_| ApiError::internal("invalid site playback URL"))?;
+  url.set_query(query);
+  Ok(url)
+}
+
+/// Proxy a playback request to the site that owns the recording
+pub async fn playback(
+  State(state): State<AppState>,
+  Path((site_id, path)): Path<(String, String)>,
+  RawQuery(query): RawQuery,
+  method: Method,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Result<Response, ApiError> {
+  let site = site(&state, &site_id).await?;
+  let url = playback_url(&site, &path, query.as_deref())?;
+
+  let mut request = CLIENT.request(method, url).body(body);
+  for name in PLAYBACK_REQUEST_HEADERS {
+    if let Some(value) = headers.get(&name) {
+      request = request.header(name, value);
+    }
+  }
+  if let Some(identity) = gateway_identity::current() {
+    request = request.headers(identity);
+  }
+  let upstream = request.send().await.map_err(|e| {
+    warn!(site_id = %site_id, status = ?site.status, error = %e, "site playback request failed");
+    let message = match site.status {
+      SiteStatus::Unreachable => format!("site '{site_id}' is unreachable"),
+      SiteStatus::Online => format!("playback on site '{site_id}' is unavailable"),
+    };
+    ApiError::new(StatusCode::BAD_GATEWAY, message)
+  })?;
+
+  let status = upstream.status();
+  let mut response_headers = HeaderMap::new();
+  for name in PLAYBACK_RESPONSE_HEADERS {
+    if let Some(value) = upstream.headers().get(&name) {
+      response_headers.insert(name, value.clone());
+    }
+  }
+  let mut response = Response::new(Body::from_stream(upstream.bytes_stream()));
+  *response.status_mut() = status;
+  *response.headers_mut() = response_headers;
+  Ok(response)
+}
+
+/// Sends this site's reports to the central gateway (edge role)
+pub struct SiteReporter {
+  central: Url,
+  token: Option<String>,
+  site_id: String,
+  name: String,
+  playback_url: Option<Url>,
+  interval: Duration,
+  jwt_secret: Option<String>,
+}
+
+impl SiteReporter {
+  /// Reporter configured from `FEDERATION_CENTRAL_URL`, `FEDERATION_SITE_ID`,
+  /// `FEDERATION_SITE_NAME`, `FEDERATION_PLAYBACK_URL` and
+  /// `FEDERATION_REPORT_INTERVAL_SECS`; `None` unless the central URL and the
+  /// site id are both set
+  pub fn from_env(config: &GatewayConfig) -> Result<Option<Self>> {
+    let (Some(central), Some(site_id)) = (non_empty_var("FEDERATION_CENTRAL_URL"), non_empty_var("FEDERATION_SITE_ID"))
+    else {
+      return Ok(None);
+    };
+    let central = Url::parse(&central).context("invalid FEDERATION_CENTRAL_URL")?;
+    common::validation::validate_id(&site_id, "FEDERATION_SITE_ID").context("invalid FEDERATION_SITE_ID")?;
+    let playback_url = match non_empty_var("FEDERATION_PLAYBACK_URL") {
+      Some(url) => Some(Url::parse(&url).context("invalid FEDERATION_PLAYBACK_URL")?),
+      None => config.playback_base_url.clone(),
+    };
+    let interval_secs = env::var("FEDERATION_REPORT_INTERVAL_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(30)
+      .max(5);
+    if config.federation_token.is_none() {
+      warn!("FEDERATION_TOKEN not set, the central gateway will reject this site's reports");
+    }
+
+    Ok(Some(Self {
+      central,
+      token: config.federation_token.clone(),
+      name: non_empty_var("FEDERATION_SITE_NAME").unwrap_or_else(|| site_id.clone()),
+      site_id,
+      playback_url,
+      interval: Duration::from_secs(interval_secs),
+      jwt_secret: config.auth.as_ref().map(|auth| auth.jwt_secret.clone()),
+    }))
+  }
+
+  /// Identity used towards this site's own services: a system administrator
+  /// outside every tenant
+  fn identity(&self) -> Option<HeaderMap> {
+    let secret = self.jwt_secret.as_ref()?;
+    let ctx = AuthContext {
+      user_id: format!("federation-{}", self.site_id),
+      tenant_id: uuid::Uuid::nil().to_string(),
+      username: "federation".to_string(),
+      is_system_admin: true,
+      roles: vec![],
+      permissions: vec![],
+    };
+    gateway_identity::headers(&ctx, secret, self.interval * 2)
+      .inspect_err(|e| warn!(error = %e, "failed to mint federation identity"))
+      .ok()
+  }
+
+  /// Collect the current report. Sections whose source is unavailable are
+  /// left empty and show up in `health.failing_sources`.
+  pub async fn collect(&self, state: &AppState) -> SiteReport {
+    let collect = async {
+      let overview = overview::build_overview(state, None).await;
+      let (devices, alerts) = tokio::join!(self.devices(state), self.alerts(state));
+      (overview, devices, alerts)
+    };
+    let (overview, devices, alerts) = match self.identity() {
+      Some(identity) => gateway_identity::scope(identity, collect).await,
+      None => collect.await,
+    };
+
+    let mut health = SiteHealth {
+      status: json!(overview.status).as_str().unwrap_or_default().to_string(),
+      coordinator_ready: overview.health.coordinator_ready,
+      nodes_total: overview.health.nodes.iter().map(|n| n.total).sum(),
+      nodes_healthy: overview.health.nodes.iter().map(|n| n.healthy).sum(),
+      failing_sources: overview
+        .sources
+        .iter()
+        .filter(|s| !matches!(s.state, overview::SourceState::Ok | overview::SourceState::NotConfigured))
+        .map(|s| s.name.clone())
+        .collect(),
+    };
+    let devices = devices.unwrap_or_else(|e| {
+      health.failing_sources.push("federation-devices".to_string());
+      warn!(error = %e, "failed to collect device inventory for federation report");
+      Vec::new()
+    });
+    let alerts = alerts.unwrap_or_else(|e| {
+      health.failing_sources.push("federation-alerts".to_string());
+      warn!(error = %e, "failed to collect alerts for federation report");
+      Vec::new()
+    });
+
+    SiteReport {
+      site_id: self.site_id.clone(),
+      name: self.name.clone(),
+      playback_url: self.playback_url.as_ref().map(Url::to_string),
+      version: Some(common::VERSION.to_string()),
+      report_interval_secs: self.interval.as_secs(),
+      generated_at_epoch_secs: now_epoch_secs(),
+      health,
+      devices,
+      alerts,
+    }
+  }
+
+  async fn devices(&self, state: &AppState) -> Result<Vec<SiteDevice>> {
+    let Some(base) = &state.config().device_manager_base_url else {
+      return Ok(Vec::new());
+    };
+    let path = format!("v1/devices?limit={MAX_REPORT_DEVICES}");
+    let mut devices: Vec<SiteDevice> = overview::get_json(&CLIENT, base, &path, None).await?;
+    devices.truncate(MAX_REPORT_DEVICES);
+    Ok(devices)
+  }
+
+  /// Newest alerts from the coordinator's event timeline
+  async fn alerts(&self, state: &AppState) -> Result<Vec<SiteAlert>> {
+    let from = (now_epoch_secs().saturating_sub(ALERT_WINDOW.as_secs())) * 1000;
+    let path = format!("v1/timeline?kind=alert&from={from}&limit={}", common::timeline::MAX_QUERY_LIMIT);
+    let events: Vec<TimelineEvent> = overview::get_json(&CLIENT, &state.config().coordinator_base_url, &path, None).await?;
+    Ok(events.into_iter().rev().take(MAX_REPORT_ALERTS).filter_map(site_alert).collect())
+  }
+
+  async fn send(&self, report: &SiteReport) -> Result<()> {
+    let url = self
+      .central
+      .join(&format!("v1/federation/sites/{}", self.site_id))
+      .context("invalid central URL")?;
+    let mut request = CLIENT.put(url).json(report);
+    if let Some(token) = &self.token {
+      request = request.bearer_auth(token);
+    }
+    request
+      .send()
+      .await
+      .context("site report request failed")?
+      .error_for_status()
+      .context("central gateway rejected the site report")?;
+    Ok(())
+  }
+
+  /// Report now and then every interval until the process exits
+  pub fn spawn(self, state: AppState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(self.interval);
+      ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+      let mut connected = false;
+      loop {
+        ticker.tick().await;
+        let report = self.collect(&state).await;
+        match self.send(&report).await {
+          Ok(()) if !connected => {
+            connected = true;
+            info!(site_id = %self.site_id, central = %self.central, "reporting to central gateway");
+          }
+          Ok(()) => debug!(site_id = %self.site_id, devices = report.devices.len(), "site report sent"),
+          Err(e) if connected => {
+            connected = false;
+            warn!(site_id = %self.site_id, error = %e, "central gateway unreachable, continuing autonomously");
+          }
+          Err(e) => debug!(site_id = %self.site_id, error = %e, "site report failed"),
+        }
+      }
+    })
+  }
+}
+
+fn site_alert(event: TimelineEvent) -> Option<SiteAlert> {
+  if event.kind != TimelineEventKind::Alert {
+    return None;
+  }
+  Some(SiteAlert {
+    severity: event.details.get("severity").and_then(|v| v.as_str()).map(str::to_string),
+    id: event.id,
+    tenant_id: event.tenant_id,
+    camera_id: event.camera_id,
+    message: event.summary,
+    fired_at_ms: event.timestamp_ms,
+  })
+}
+
+fn non_empty_var(var: &str) -> Option<String> {
+  env::var(var).ok().filter(|v| !v.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn site_record(playback_url: Option<&str>) -> SiteRecord {
+    SiteRecord {
+      site_id: "east".into(),
+      name: "East".into(),
+      status: SiteStatus::Online,
+      playback_url: playback_url.map(str::to_string),
+      version: None,
+      first_seen_epoch_secs: 0,
+      last_report_epoch_secs: 0,
+      health: SiteHealth::default(),
+      device_count: 0,
+      alert_count: 0,
+    }
+  }
+
+  #[test]
+  fn playback_urls_stay_on_the_site() {
+    let east = site_record(Some("https://east.example.com:8086/"));
+    let url = playback_url(&east, "v1/playback/sessions/s1/index.m3u8", Some("token=x")).unwrap();
+    assert_eq!(url.as_str(), "https://east.example.com:8086/v1/playback/sessions/s1/index.m3u8?token=x");
+    assert!(playback_url(&east, "v1/../../admin", None).is_err());
+    assert!(playback_url(&site_record(None), "v1/playback/sessions", None).is_err());
+  }
+
+  #[test]
+  fn tokens_must_match_exactly() {
+    assert!(token_matches("s3cret", "s3cret"));
+    assert!(!token_matches("s3cret", "s3cre"));
+    assert!(!token_matches("s3cret", "S3cret"));
+    assert!(!token_matches("s3cret", ""));
+  }
+
+  #[test]
+  fn alerts_come_from_alert_timeline_events() {
+    let alert = TimelineEvent::new(TimelineEventKind::Alert, "alert-service", "Tamper: lens covered")
+      .at(1_700_000_000_000)
+      .camera("cam-1")
+      .tenant("tenant-a")
+      .details(json!({ "severity": "critical" }));
+    let converted = site_alert(alert).unwrap();
+    assert_eq!(converted.severity.as_deref(), Some("critical"));
+    assert_eq!(converted.camera_id.as_deref(), Some("cam-1"));
+    assert_eq!(converted.fired_at_ms, 1_700_000_000_000);
+
+    let status = TimelineEvent::new(TimelineEventKind::DeviceStatus, "device-manager", "cam-1 is offline");
+    assert!(site_alert(status).is_none());
+  }
+}
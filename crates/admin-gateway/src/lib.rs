@@ -1,6 +1,18 @@
+pub mod auth;
+pub mod camera_analytics;
+pub mod canary;
 pub mod config;
 pub mod coordinator;
 pub mod error;
+pub mod federation;
+pub mod onboarding;
+pub mod openapi;
+pub mod overview;
+pub mod realtime;
 pub mod routes;
+pub mod routing;
+pub mod service_config;
 pub mod state;
+pub mod timeline;
+pub mod usage;
 pub mod worker;
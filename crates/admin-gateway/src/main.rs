@@ -1,13 +1,20 @@
 use admin_gateway::{
+  canary::CanaryPolicy,
   config::GatewayConfig,
   coordinator::{CoordinatorClient, HttpCoordinatorClient},
+  federation,
   routes,
+  routing::RoutingTable,
+  service_config,
   state::AppState,
   worker::{HttpRecorderClient, HttpWorkerClient, RecorderClient, WorkerClient},
 };
 use anyhow::Result;
+use common::nodes::NodeKind;
+use common::service_config::ConfigWatcher;
 use common::state_store::StateStore;
 use common::state_store_client::StateStoreClient;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tracing::{info, warn};
@@ -27,13 +34,53 @@ async fn main() -> Result<()> {
   }
 
   let config = GatewayConfig::from_env()?;
-  let coordinator: Arc<dyn CoordinatorClient> = Arc::new(HttpCoordinatorClient::new(
-    config.coordinator_base_url.clone(),
-  )?);
-  let worker: Arc<dyn WorkerClient> =
-    Arc::new(HttpWorkerClient::new(config.worker_base_url.clone())?);
-  let recorder: Arc<dyn RecorderClient> =
-    Arc::new(HttpRecorderClient::new(config.recorder_base_url.clone())?);
+  let coordinator: Arc<dyn CoordinatorClient> = Arc::new(
+    HttpCoordinatorClient::new(config.coordinator_base_url.clone()).await?,
+  );
+
+  // Static endpoints serve until nodes register with the coordinator
+  let routing = Arc::new(
+    RoutingTable::new(config.node_unhealthy_threshold).with_canary(CanaryPolicy::from_env()?),
+  );
+  routing
+    .add_static(NodeKind::Stream, config.worker_base_url.clone())
+    .await?;
+  routing
+    .add_static(NodeKind::Recorder, config.recorder_base_url.clone())
+    .await?;
+  if let Some(ai) = &config.ai_service_base_url {
+    routing.add_static(NodeKind::Ai, ai.clone()).await?;
+  }
+
+  if config.node_discovery_interval_secs > 0 {
+    let discovery_routing = routing.clone();
+    let discovery_coordinator = coordinator.clone();
+    let interval_secs = config.node_discovery_interval_secs;
+    tokio::spawn(async move {
+      let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+      loop {
+        interval.tick().await;
+        if let Err(e) = discovery_routing.refresh(discovery_coordinator.as_ref()).await {
+          warn!(error = %e, "node discovery failed; keeping current routing table");
+        }
+        discovery_routing
+          .probe(std::time::Duration::from_secs(2))
+          .await;
+      }
+    });
+  } else {
+    info!("node discovery disabled, routing to static worker/recorder endpoints");
+  }
+
+  // Canary rules may also be managed centrally through /v1/config/admin-gateway
+  if let Some(watcher) = ConfigWatcher::from_env(config.coordinator_base_url.clone(), service_config::SERVICE_NAME).await? {
+    service_config::follow(watcher, routing.clone());
+  }
+
+  common::timeline::init_from_env(Some(config.coordinator_base_url.clone())).await?;
+
+  let worker: Arc<dyn WorkerClient> = Arc::new(HttpWorkerClient::new(routing.clone()));
+  let recorder: Arc<dyn RecorderClient> = Arc::new(HttpRecorderClient::new(routing.clone()));
 
   // Initialize StateStore client (optional, enabled via env var)
   let state_store_enabled = std::env::var("ENABLE_STATE_STORE")
@@ -49,6 +96,7 @@ async fn main() -> Result<()> {
       coordinator,
       worker,
       recorder,
+      routing.clone(),
       state_store_client,
     );
 
@@ -86,9 +134,13 @@ async fn main() -> Result<()> {
 
     state
   } else {
-    AppState::new(config.clone(), coordinator, worker, recorder)
+    AppState::new(config.clone(), coordinator, worker, recorder, routing)
   };
 
+  if let Some(reporter) = federation::SiteReporter::from_env(&config)? {
+    reporter.spawn(state.clone());
+  }
+
   let app = routes::router(state.clone());
   let listener = TcpListener::bind(config.bind_addr).await?;
 
@@ -99,7 +151,7 @@ async fn main() -> Result<()> {
     "admin-gateway listening"
   );
 
-  axum::serve(listener, app.into_make_service())
+  axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
     .with_graceful_shutdown(shutdown_signal())
     .await?;
 
@@ -0,0 +1,716 @@
+//! `POST /v1/onboarding`: add a camera in one call.
+//!
+//! Runs the steps an operator would otherwise drive by hand and reports each
+//! of them:
+//!
+//! 1. resolve the device template, if one is named, from the central
+//!    configuration document `device-templates` (one object per template)
+//! 2. create the device in device-manager
+//! 3. probe it through device-manager; a failed probe deletes it again
+//! 4. start a verification stream through the gateway's stream API
+//! 5. capture a snapshot from the camera
+//!
+//! The verification stream is stopped afterwards unless `keep_stream` is
+//! set. Its source URI carries the camera credentials, like any stream
+//! started with an authenticated URI.
+
+use crate::{error::ApiError, routes, state::AppState};
+use anyhow::{Context, Result, anyhow, bail};
+use axum::{
+  Json,
+  extract::{Path, State},
+  http::{HeaderMap, HeaderValue, StatusCode, header::AUTHORIZATION},
+};
+use base64::Engine;
+use common::{
+  service_config::ServiceConfig,
+  streams::{StreamConfig, StreamStartRequest, ThermalPalette},
+};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+  sync::LazyLock,
+  time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// Central configuration document holding the device templates
+pub const TEMPLATES_SERVICE: &str = "device-templates";
+
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+const SNAPSHOT_WIDTH: u32 = 640;
+
+/// Probes can take several seconds against slow cameras
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(3))
+    .timeout(Duration::from_secs(30))
+    .build()
+    .unwrap_or_default()
+});
+
+fn default_true() -> bool {
+  true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OnboardingRequest {
+  pub name: String,
+  /// `host[:port]`, completed from the template, or a full media URI
+  pub address: String,
+  #[serde(default)]
+  pub username: Option<String>,
+  #[serde(default)]
+  pub password: Option<String>,
+  #[serde(default)]
+  pub template: Option<String>,
+  #[serde(default)]
+  pub location: Option<String>,
+  /// Overrides the template's zone
+  #[serde(default)]
+  pub zone: Option<String>,
+  /// Added to the template's tags
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// Leave the verification stream running
+  #[serde(default)]
+  pub keep_stream: bool,
+  #[serde(default = "default_true")]
+  pub snapshot: bool,
+}
+
+/// Device defaults for a camera model or site standard
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DeviceTemplate {
+  pub device_type: Option<String>,
+  pub manufacturer: Option<String>,
+  pub model: Option<String>,
+  /// device-manager connection protocol; derived from the address when unset
+  pub protocol: Option<String>,
+  /// URI scheme used for bare addresses (default `rtsp`)
+  pub scheme: Option<String>,
+  pub port: Option<u16>,
+  /// Media path used for bare addresses, e.g. `/Streaming/Channels/101`
+  pub path: Option<String>,
+  pub zone: Option<String>,
+  pub tags: Vec<String>,
+  pub recording_enabled: Option<bool>,
+  pub ai_enabled: Option<bool>,
+  pub auto_start: Option<bool>,
+  pub health_check_interval_secs: Option<i32>,
+  pub metadata: Option<Value>,
+  /// Palette the verification stream of a thermal camera is rendered in;
+  /// `thermal` devices default to white-hot
+  pub thermal_palette: Option<ThermalPalette>,
+}
+
+impl DeviceTemplate {
+  fn stream_palette(&self) -> Option<ThermalPalette> {
+    match self.device_type.as_deref() {
+      Some("thermal") => Some(self.thermal_palette.unwrap_or(ThermalPalette::WhiteHot)),
+      _ => self.thermal_palette,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+  Template,
+  CreateDevice,
+  Probe,
+  VerifyStream,
+  Snapshot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+  Ok,
+  Failed,
+  Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+  pub step: OnboardingStep,
+  pub status: StepStatus,
+  pub duration_ms: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OnboardingResult {
+  /// True when every step that ran succeeded
+  pub success: bool,
+  /// Set when the device exists after onboarding
+  pub device_id: Option<String>,
+  pub device: Option<Value>,
+  pub probe: Option<Value>,
+  /// Verification stream, when kept running
+  pub stream_id: Option<String>,
+  /// Base64-encoded JPEG
+  pub snapshot: Option<String>,
+  pub steps: Vec<StepResult>,
+}
+
+impl OnboardingResult {
+  fn record(&mut self, step: OnboardingStep, started: Instant, outcome: Result<Option<String>>) -> bool {
+    let (status, message) = match outcome {
+      Ok(message) => (StepStatus::Ok, message),
+      Err(e) => (StepStatus::Failed, Some(format!("{e:#}"))),
+    };
+    self.steps.push(StepResult {
+      step,
+      status,
+      duration_ms: started.elapsed().as_millis() as u64,
+      message,
+    });
+    status == StepStatus::Ok
+  }
+
+  fn skip(&mut self, step: OnboardingStep, reason: &str) {
+    self.steps.push(StepResult {
+      step,
+      status: StepStatus::Skipped,
+      duration_ms: 0,
+      message: Some(reason.to_string()),
+    });
+  }
+
+  fn finish(mut self, status: StatusCode) -> (StatusCode, Json<OnboardingResult>) {
+    self.success = self.steps.iter().all(|s| s.status != StepStatus::Failed);
+    (status, Json(self))
+  }
+}
+
+/// Media URI for `address`: full URIs are kept, bare hosts are completed
+/// from the template
+pub fn media_uri(address: &str, template: &DeviceTemplate) -> Result<Url> {
+  let address = address.trim();
+  if address.is_empty() {
+    bail!("address must not be empty");
+  }
+  let mut url = if address.contains("://") {
+    Url::parse(address).context("invalid address")?
+  } else {
+    let scheme = template.scheme.as_deref().unwrap_or("rtsp");
+    let mut url = Url::parse(&format!("{scheme}://{address}")).context("invalid address")?;
+    if let Some(port) = template.port.filter(|_| url.port().is_none()) {
+      url.set_port(Some(port)).map_err(|_| anyhow!("invalid port"))?;
+    }
+    if let Some(path) = &template.path {
+      url.set_path(path);
+    }
+    url
+  };
+  if url.host_str().is_none_or(str::is_empty) {
+    bail!("address has no host");
+  }
+  if !url.username().is_empty() || url.password().is_some() {
+    bail!("pass credentials as username/password, not in the address");
+  }
+  if !matches!(url.scheme(), "rtsp" | "rtsps" | "rtmp" | "http" | "https") {
+    bail!("unsupported address scheme '{}'", url.scheme());
+  }
+  url.set_fragment(None);
+  Ok(url)
+}
+
+fn protocol_for(url: &Url, template: &DeviceTemplate) -> String {
+  template.protocol.clone().unwrap_or_else(|| {
+    match url.scheme() {
+      "rtmp" => "rtmp",
+      "http" | "https" => "http",
+      _ => "rtsp",
+    }
+    .to_string()
+  })
+}
+
+/// device-manager `CreateDeviceRequest` body
+fn device_body(req: &OnboardingRequest, template: &DeviceTemplate, uri: &Url) -> Value {
+  let mut tags = template.tags.clone();
+  for tag in &req.tags {
+    if !tags.contains(tag) {
+      tags.push(tag.clone());
+    }
+  }
+  let mut metadata = match &template.metadata {
+    Some(Value::Object(map)) => map.clone(),
+    _ => Default::default(),
+  };
+  metadata.insert("onboarded_by".into(), json!("admin-gateway"));
+  if let Some(name) = &req.template {
+    metadata.insert("template".into(), json!(name));
+  }
+
+  let mut body = json!({
+    "name": req.name,
+    "device_type": template.device_type.as_deref().unwrap_or("camera"),
+    "primary_uri": uri.as_str(),
+    "protocol": protocol_for(uri, template),
+    "tags": tags,
+    "metadata": metadata,
+  });
+  let optional = [
+    ("manufacturer", template.manufacturer.clone().map(Value::from)),
+    ("model", template.model.clone().map(Value::from)),
+    ("username", req.username.clone().map(Value::from)),
+    ("password", req.password.clone().map(Value::from)),
+    ("location", req.location.clone().map(Value::from)),
+    ("zone", req.zone.clone().or_else(|| template.zone.clone()).map(Value::from)),
+    ("recording_enabled", template.recording_enabled.map(Value::from)),
+    ("ai_enabled", template.ai_enabled.map(Value::from)),
+    ("auto_start", template.auto_start.map(Value::from)),
+    ("health_check_interval_secs", template.health_check_interval_secs.map(Value::from)),
+  ];
+  for (key, value) in optional {
+    if let Some(value) = value {
+      body[key] = value;
+    }
+  }
+  body
+}
+
+/// Calls to device-manager and the coordinator on behalf of the caller
+struct Upstream {
+  authorization: Option<HeaderValue>,
+}
+
+impl Upstream {
+  async fn call(&self, method: Method, url: Url, body: Option<&Value>) -> Result<Value> {
+    let mut request = CLIENT.request(method, url);
+    if let Some(auth) = &self.authorization {
+      request = request.header(AUTHORIZATION, auth.clone());
+    }
+    if let Some(identity) = common::gateway_identity::current() {
+      request = request.headers(identity);
+    }
+    if let Some(body) = body {
+      request = request.json(body);
+    }
+    let resp = request.send().await?;
+    let status = resp.status();
+    let body: Value = resp.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+      let message = body["error"].as_str().unwrap_or("request failed");
+      return Err(UpstreamError { status, message: message.to_string() }.into());
+    }
+    Ok(body)
+  }
+}
+
+#[derive(Debug)]
+struct UpstreamError {
+  status: StatusCode,
+  message: String,
+}
+
+impl std::fmt::Display for UpstreamError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} ({})", self.message, self.status)
+  }
+}
+
+impl std::error::Error for UpstreamError {}
+
+/// Response status when onboarding stops before a device exists: upstream
+/// outages are 502, anything wrong with the request or camera is 422
+fn failure_status(error: &anyhow::Error) -> StatusCode {
+  match error.downcast_ref::<UpstreamError>() {
+    Some(e) if matches!(e.status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => e.status,
+    Some(e) if e.status.is_client_error() => StatusCode::UNPROCESSABLE_ENTITY,
+    Some(_) => StatusCode::BAD_GATEWAY,
+    None if error.downcast_ref::<reqwest::Error>().is_some() => StatusCode::BAD_GATEWAY,
+    None => StatusCode::UNPROCESSABLE_ENTITY,
+  }
+}
+
+async fn load_template(state: &AppState, upstream: &Upstream, name: &str) -> Result<DeviceTemplate> {
+  let url = state
+    .config()
+    .coordinator_base_url
+    .join(&format!("v1/config/{TEMPLATES_SERVICE}"))?;
+  let config: ServiceConfig = serde_json::from_value(
+    upstream
+      .call(Method::GET, url, None)
+      .await
+      .context("device templates unavailable")?,
+  )?;
+  let template = config
+    .document
+    .get(name)
+    .with_context(|| format!("device template '{name}' not found"))?;
+  serde_json::from_value(template.clone()).with_context(|| format!("invalid device template '{name}'"))
+}
+
+pub async fn onboard_camera(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  Json(req): Json<OnboardingRequest>,
+) -> Result<(StatusCode, Json<OnboardingResult>), ApiError> {
+  common::validation::validate_name(&req.name, "name")
+    .map_err(|e| ApiError::bad_request(format!("invalid name: {e}")))?;
+  let device_manager = state
+    .config()
+    .device_manager_base_url
+    .clone()
+    .ok_or_else(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "device-manager is not configured"))?;
+  let upstream = Upstream {
+    authorization: headers.get(AUTHORIZATION).cloned(),
+  };
+  let mut result = OnboardingResult::default();
+
+  let started = Instant::now();
+  let template = match &req.template {
+    None => {
+      result.skip(OnboardingStep::Template, "no template requested");
+      DeviceTemplate::default()
+    }
+    Some(name) => match load_template(&state, &upstream, name).await {
+      Ok(template) => {
+        result.record(OnboardingStep::Template, started, Ok(Some(name.clone())));
+        template
+      }
+      Err(e) => {
+        let status = failure_status(&e);
+        result.record(OnboardingStep::Template, started, Err(e));
+        return Ok(result.finish(status));
+      }
+    },
+  };
+  let uri = media_uri(&req.address, &template).map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+  let started = Instant::now();
+  let devices_url = device_manager.join("v1/devices").map_err(|e| ApiError::internal(e.to_string()))?;
+  let device = match upstream
+    .call(Method::POST, devices_url, Some(&device_body(&req, &template, &uri)))
+    .await
+  {
+    Ok(device) => device,
+    Err(e) => {
+      let status = failure_status(&e);
+      result.record(OnboardingStep::CreateDevice, started, Err(e));
+      return Ok(result.finish(status));
+    }
+  };
+  let Some(device_id) = device["device_id"].as_str().map(str::to_string) else {
+    result.record(OnboardingStep::CreateDevice, started, Err(anyhow!("device-manager returned no device_id")));
+    return Ok(result.finish(StatusCode::BAD_GATEWAY));
+  };
+  result.record(OnboardingStep::CreateDevice, started, Ok(Some(device_id.clone())));
+  let device_url = |path: &str| {
+    device_manager
+      .join(&format!("v1/devices/{device_id}{path}"))
+      .map_err(|e| ApiError::internal(e.to_string()))
+  };
+
+  // A camera that cannot be probed is removed again so the wizard can be
+  // retried with corrected details
+  let started = Instant::now();
+  let probe = upstream
+    .call(Method::POST, device_url("/probe")?, None)
+    .await
+    .and_then(|probe| match probe["success"].as_bool() {
+      Some(true) => Ok(probe),
+      _ => Err(anyhow!(
+        "probe failed: {}",
+        probe["error_message"].as_str().unwrap_or("camera did not respond")
+      )),
+    });
+  match probe {
+    Ok(probe) => {
+      result.record(OnboardingStep::Probe, started, Ok(None));
+      result.probe = Some(probe);
+    }
+    Err(e) => {
+      let status = failure_status(&e);
+      result.record(OnboardingStep::Probe, started, Err(e));
+      if let Err(e) = upstream.call(Method::DELETE, device_url("")?, None).await {
+        warn!(device_id = %device_id, error = %e, "failed to remove unreachable device");
+        result.device_id = Some(device_id);
+        result.device = Some(device);
+      }
+      return Ok(result.finish(status));
+    }
+  }
+  result.device_id = Some(device_id.clone());
+  result.device = Some(device);
+
+  let mut source = uri.clone();
+  if let Some(username) = &req.username {
+    let _ = source.set_username(username);
+    let _ = source.set_password(req.password.as_deref());
+  }
+
+  let stream_id = format!("onboard-{device_id}");
+  let started = Instant::now();
+  let start = StreamStartRequest {
+    config: StreamConfig {
+      id: stream_id.clone(),
+      camera_id: Some(device_id.clone()),
+      uri: source.to_string(),
+      codec: None,
+      container: None,
+      substream_uri: None,
+      thermal_palette: template.stream_palette(),
+    },
+    lease_ttl_secs: None,
+  };
+  let streaming = match routes::start_stream(State(state.clone()), Json(start)).await {
+    Ok(Json(resp)) if resp.accepted => result.record(OnboardingStep::VerifyStream, started, Ok(Some(stream_id.clone()))),
+    Ok(Json(resp)) => {
+      let message = resp.message.unwrap_or_else(|| "stream not accepted".into());
+      result.record(OnboardingStep::VerifyStream, started, Err(anyhow!(message)))
+    }
+    Err(e) => result.record(OnboardingStep::VerifyStream, started, Err(anyhow!(e.to_string()))),
+  };
+
+  if req.snapshot {
+    let started = Instant::now();
+    match common::frame_extractor::capture_snapshot(source.as_str(), SNAPSHOT_WIDTH, SNAPSHOT_TIMEOUT).await {
+      Ok(jpeg) => {
+        result.snapshot = Some(base64::engine::general_purpose::STANDARD.encode(&jpeg));
+        result.record(OnboardingStep::Snapshot, started, Ok(None));
+      }
+      Err(e) => {
+        result.record(OnboardingStep::Snapshot, started, Err(e));
+      }
+    }
+  } else {
+    result.skip(OnboardingStep::Snapshot, "snapshot not requested");
+  }
+
+  if streaming && req.keep_stream {
+    result.stream_id = Some(stream_id);
+  } else if streaming
+    && let Err(e) = routes::stop_stream(State(state.clone()), Path(stream_id.clone())).await
+  {
+    warn!(stream_id = %stream_id, error = %e, "failed to stop verification stream");
+    result.stream_id = Some(stream_id);
+  }
+
+  info!(device_id = %device_id, steps = result.steps.len(), "camera onboarded");
+  Ok(result.finish(StatusCode::CREATED))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::GatewayConfig,
+    coordinator::HttpCoordinatorClient,
+    routing::RoutingTable,
+    worker::{HttpRecorderClient, HttpWorkerClient},
+  };
+  use axum::{Router, routing::{delete, get, post}};
+  use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+  };
+  use tokio::net::TcpListener;
+
+  #[derive(Default)]
+  struct Backend {
+    probe_ok: bool,
+    created: Mutex<Option<Value>>,
+    deleted: Mutex<Vec<String>>,
+  }
+
+  /// Coordinator and device-manager stand-in. Stream leases are always
+  /// refused, so the verification stream step fails.
+  async fn spawn_backend(backend: Arc<Backend>) -> Url {
+    let app = Router::new()
+      .route(
+        "/v1/config/device-templates",
+        get(|| async {
+          Json(json!({
+            "service": "device-templates",
+            "version": 1,
+            "created_at": 0,
+            "document": {
+              "hik-bullet": {
+                "manufacturer": "Hikvision",
+                "port": 554,
+                "path": "/Streaming/Channels/101",
+                "zone": "perimeter",
+                "tags": ["outdoor"]
+              }
+            }
+          }))
+        }),
+      )
+      .route(
+        "/v1/devices",
+        post(|State(backend): State<Arc<Backend>>, Json(body): Json<Value>| async move {
+          let mut device = body.clone();
+          device["device_id"] = json!("dev-1");
+          *backend.created.lock().unwrap() = Some(body);
+          (StatusCode::CREATED, Json(device))
+        }),
+      )
+      .route(
+        "/v1/devices/:id/probe",
+        post(|State(backend): State<Arc<Backend>>| async move {
+          Json(json!({"success": backend.probe_ok, "error_message": "connection refused"}))
+        }),
+      )
+      .route(
+        "/v1/devices/:id",
+        delete(|State(backend): State<Arc<Backend>>, Path(id): Path<String>| async move {
+          backend.deleted.lock().unwrap().push(id);
+          StatusCode::NO_CONTENT
+        }),
+      )
+      .route(
+        "/v1/leases/acquire",
+        post(|| async { Json(json!({"granted": false, "record": null})) }),
+      )
+      .with_state(backend);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      axum::serve(listener, app).await.unwrap();
+    });
+    Url::parse(&format!("http://{addr}/")).unwrap()
+  }
+
+  async fn state_for(backend: Url) -> AppState {
+    let config = GatewayConfig {
+      bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+      coordinator_base_url: backend.clone(),
+      node_id: "test-node".into(),
+      worker_base_url: backend.clone(),
+      recorder_base_url: backend.clone(),
+      node_discovery_interval_secs: 0,
+      node_unhealthy_threshold: 3,
+      overview_timeout_ms: 300,
+      auth: None,
+      device_manager_base_url: Some(backend.clone()),
+      ai_service_base_url: None,
+      playback_base_url: None,
+      alert_service_base_url: None,
+      federation_token: None,
+    };
+    let routing = Arc::new(RoutingTable::new(3));
+    let coordinator = Arc::new(HttpCoordinatorClient::new(backend).await.unwrap());
+    AppState::new(
+      config,
+      coordinator,
+      Arc::new(HttpWorkerClient::new(routing.clone())),
+      Arc::new(HttpRecorderClient::new(routing.clone())),
+      routing,
+    )
+  }
+
+  fn request() -> OnboardingRequest {
+    serde_json::from_value(json!({
+      "name": "Gate camera",
+      "address": "10.0.0.5",
+      "username": "admin",
+      "password": "secret",
+      "template": "hik-bullet",
+      "tags": ["gate", "outdoor"],
+      "snapshot": false
+    }))
+    .unwrap()
+  }
+
+  fn statuses(result: &OnboardingResult) -> Vec<(OnboardingStep, StepStatus)> {
+    result.steps.iter().map(|s| (s.step, s.status)).collect()
+  }
+
+  #[test]
+  fn bare_addresses_are_completed_from_the_template() {
+    let template = DeviceTemplate {
+      port: Some(554),
+      path: Some("/Streaming/Channels/101".into()),
+      ..Default::default()
+    };
+    assert_eq!(
+      media_uri("10.0.0.5", &template).unwrap().as_str(),
+      "rtsp://10.0.0.5:554/Streaming/Channels/101"
+    );
+    assert_eq!(
+      media_uri("10.0.0.5:8554", &template).unwrap().as_str(),
+      "rtsp://10.0.0.5:8554/Streaming/Channels/101"
+    );
+    assert_eq!(
+      media_uri("rtsp://cam.local/live", &template).unwrap().as_str(),
+      "rtsp://cam.local/live"
+    );
+    assert!(media_uri("rtsp://admin:pw@cam.local/live", &template).is_err());
+    assert!(media_uri("file:///etc/passwd", &template).is_err());
+    assert!(media_uri(" ", &template).is_err());
+  }
+
+  #[tokio::test]
+  async fn creates_and_probes_device_from_template() {
+    let backend = Arc::new(Backend {
+      probe_ok: true,
+      ..Default::default()
+    });
+    let state = state_for(spawn_backend(backend.clone()).await).await;
+
+    let (status, Json(result)) = onboard_camera(State(state), HeaderMap::new(), Json(request()))
+      .await
+      .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(result.device_id.as_deref(), Some("dev-1"));
+    assert_eq!(
+      statuses(&result),
+      [
+        (OnboardingStep::Template, StepStatus::Ok),
+        (OnboardingStep::CreateDevice, StepStatus::Ok),
+        (OnboardingStep::Probe, StepStatus::Ok),
+        (OnboardingStep::VerifyStream, StepStatus::Failed),
+        (OnboardingStep::Snapshot, StepStatus::Skipped),
+      ]
+    );
+    assert!(!result.success);
+
+    let created = backend.created.lock().unwrap().clone().unwrap();
+    assert_eq!(created["primary_uri"], "rtsp://10.0.0.5:554/Streaming/Channels/101");
+    assert_eq!(created["protocol"], "rtsp");
+    assert_eq!(created["manufacturer"], "Hikvision");
+    assert_eq!(created["zone"], "perimeter");
+    assert_eq!(created["tags"], json!(["outdoor", "gate"]));
+    assert_eq!(created["password"], "secret");
+    assert_eq!(created["metadata"]["template"], "hik-bullet");
+  }
+
+  #[tokio::test]
+  async fn unreachable_camera_is_removed_again() {
+    let backend = Arc::new(Backend::default());
+    let state = state_for(spawn_backend(backend.clone()).await).await;
+
+    let (status, Json(result)) = onboard_camera(State(state), HeaderMap::new(), Json(request()))
+      .await
+      .unwrap();
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(!result.success);
+    assert!(result.device_id.is_none());
+    assert_eq!(result.steps.last().unwrap().step, OnboardingStep::Probe);
+    assert!(result.steps.last().unwrap().message.as_deref().unwrap().contains("connection refused"));
+    assert_eq!(*backend.deleted.lock().unwrap(), ["dev-1"]);
+  }
+
+  #[tokio::test]
+  async fn unknown_template_stops_before_creating_device() {
+    let backend = Arc::new(Backend::default());
+    let state = state_for(spawn_backend(backend.clone()).await).await;
+    let mut req = request();
+    req.template = Some("ptz-dome".into());
+
+    let (status, Json(result)) = onboard_camera(State(state), HeaderMap::new(), Json(req))
+      .await
+      .unwrap();
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(statuses(&result), [(OnboardingStep::Template, StepStatus::Failed)]);
+    assert!(backend.created.lock().unwrap().is_none());
+  }
+}
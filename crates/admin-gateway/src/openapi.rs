@@ -0,0 +1,113 @@
+use crate::state::AppState;
+use axum::{Json, extract::State, response::Html};
+use common::openapi::{OpenApiSpec, merge_documents, swagger_ui_html};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::warn;
+
+const SPEC_FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// OpenAPI description of the gateway's own routes
+pub fn openapi() -> OpenApiSpec {
+  OpenApiSpec::new("admin-gateway", env!("CARGO_PKG_VERSION"))
+    .with_description("REST facade for orchestrating streams and recordings")
+    .operations(&[
+      ("GET", "/healthz", "health", "Liveness probe"),
+      ("GET", "/metrics", "health", "Prometheus metrics"),
+      ("GET", "/v1/streams", "streams", "List streams"),
+      ("POST", "/v1/streams", "streams", "Start stream"),
+      ("DELETE", "/v1/streams/:id", "streams", "Stop stream"),
+      ("GET", "/v1/recordings", "recordings", "List recordings"),
+      ("POST", "/v1/recordings", "recordings", "Start recording"),
+      ("DELETE", "/v1/recordings/:id", "recordings", "Stop recording"),
+      ("GET", "/v1/nodes", "nodes", "Routing table of stream, recorder and AI nodes"),
+      ("GET", "/v1/canary", "nodes", "Canary routing rules and their error rates"),
+      ("PUT", "/v1/canary/:kind", "nodes", "Set or re-arm the canary rule for a node kind"),
+      ("DELETE", "/v1/canary/:kind", "nodes", "Remove the canary rule for a node kind"),
+      ("GET", "/v1/system/overview", "system", "Health, capacity and alarm summary across services"),
+      ("POST", "/v1/onboarding", "devices", "Create, probe and verify a camera in one call"),
+      ("GET", "/v1/analytics/cameras", "analytics", "Zones and alarm classes of every configured camera"),
+      ("GET", "/v1/analytics/cameras/:camera_id", "analytics", "Zones and alarm classes of a camera"),
+      ("PUT", "/v1/analytics/cameras/:camera_id", "analytics", "Replace a camera's zones and alarm classes; applied to its AI tasks"),
+      ("DELETE", "/v1/analytics/cameras/:camera_id", "analytics", "Remove a camera's zones and alarm classes"),
+      ("GET", "/v1/live/:service/*path", "system", "WebSocket or SSE proxy to a backend real-time endpoint"),
+      ("GET", "/v1/config/:service", "config", "Latest configuration of a service"),
+      ("PUT", "/v1/config/:service", "config", "Publish a new configuration version"),
+      ("GET", "/v1/config/:service/versions", "config", "List configuration versions"),
+      ("GET", "/v1/config/:service/versions/:version", "config", "Get a configuration version"),
+      ("POST", "/v1/config/:service/rollback", "config", "Republish an earlier configuration version"),
+      ("GET", "/v1/usage", "usage", "Monthly API usage and quota of a tenant"),
+      ("GET", "/v1/usage/tenants", "usage", "Monthly API usage of every tenant"),
+      ("GET", "/v1/timeline", "timeline", "Cluster events by time, camera and kind"),
+      ("PUT", "/v1/federation/sites/:site_id", "federation", "Report from an edge site (federation token)"),
+      ("GET", "/v1/federation/sites", "federation", "Edge sites and their connectivity"),
+      ("GET", "/v1/federation/sites/:site_id", "federation", "Get an edge site"),
+      ("DELETE", "/v1/federation/sites/:site_id", "federation", "Forget an edge site"),
+      ("GET", "/v1/federation/devices", "federation", "Device inventory across sites"),
+      ("GET", "/v1/federation/alerts", "federation", "Recent alerts across sites"),
+      ("GET", "/v1/federation/health", "federation", "Site, device and alert counts"),
+      ("GET", "/v1/federation/sites/:site_id/playback/*path", "federation", "Playback proxied to the owning site"),
+      ("POST", "/v1/federation/sites/:site_id/playback/*path", "federation", "Playback proxied to the owning site"),
+      ("DELETE", "/v1/federation/sites/:site_id/playback/*path", "federation", "Playback proxied to the owning site"),
+      ("GET", "/v1/openapi.json", "docs", "Aggregated OpenAPI document"),
+    ])
+}
+
+/// Aggregated spec: the gateway's own document merged with every reachable
+/// backend's `/openapi.json`. Unreachable services are skipped.
+pub async fn aggregated_openapi(State(state): State<AppState>) -> Json<Value> {
+  let mut documents = vec![("admin-gateway".to_string(), openapi().to_json())];
+
+  let client = match reqwest::Client::builder()
+    .timeout(SPEC_FETCH_TIMEOUT)
+    .build()
+  {
+    Ok(client) => client,
+    Err(e) => {
+      warn!(error = %e, "failed to build HTTP client for OpenAPI aggregation");
+      return Json(merge_documents("quadrant-vms", common::VERSION, &documents));
+    }
+  };
+
+  let mut fetches = JoinSet::new();
+  for (index, (name, base)) in state.config().service_endpoints().into_iter().enumerate() {
+    let client = client.clone();
+    fetches.spawn(async move { (index, name, fetch_spec(&client, &base).await) });
+  }
+
+  // Keep configuration order so merge precedence is deterministic
+  let mut fetched = Vec::new();
+  while let Some(joined) = fetches.join_next().await {
+    match joined {
+      Ok(result) => fetched.push(result),
+      Err(e) => warn!(error = %e, "OpenAPI fetch task failed"),
+    }
+  }
+  fetched.sort_by_key(|(index, _, _)| *index);
+
+  for (_, name, result) in fetched {
+    match result {
+      Ok(doc) => documents.push((name.to_string(), doc)),
+      Err(e) => warn!(service = name, error = %e, "skipping service in aggregated OpenAPI"),
+    }
+  }
+
+  Json(merge_documents("quadrant-vms", common::VERSION, &documents))
+}
+
+pub async fn aggregated_docs() -> Html<String> {
+  Html(swagger_ui_html("quadrant-vms", "/v1/openapi.json"))
+}
+
+async fn fetch_spec(client: &reqwest::Client, base: &reqwest::Url) -> anyhow::Result<Value> {
+  let url = base.join("openapi.json")?;
+  let doc = client
+    .get(url)
+    .send()
+    .await?
+    .error_for_status()?
+    .json::<Value>()
+    .await?;
+  Ok(doc)
+}
@@ -0,0 +1,585 @@
+//! `GET /v1/system/overview`: one health/capacity/alarm document for
+//! dashboards.
+//!
+//! The gateway fans out to the coordinator, device-manager, every routed
+//! recorder and alert-service in parallel. Each source gets its own timeout;
+//! a slow or failing source is reported in `sources` and its section is left
+//! empty instead of failing the whole request.
+
+use crate::{routing::RouteStatus, state::AppState};
+use anyhow::{Context, Result};
+use axum::{
+  Json,
+  extract::State,
+  http::{HeaderMap, HeaderValue, header::AUTHORIZATION},
+};
+use common::{leases::LeaseRecord, nodes::NodeKind, recordings::RecordingListResponse};
+use reqwest::Url;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+  collections::BTreeMap,
+  future::Future,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinSet;
+use tracing::warn;
+
+/// Alert events sampled for the alarm summary (newest first)
+const ALARM_SAMPLE: usize = 100;
+/// Unsuppressed alarms listed individually in the summary
+const LATEST_ALARMS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverallStatus {
+  Healthy,
+  Degraded,
+  Unavailable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceState {
+  Ok,
+  Error,
+  Timeout,
+  NotConfigured,
+}
+
+/// Outcome of the call to one backend
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceReport {
+  pub name: String,
+  pub state: SourceState,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub latency_ms: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeKindSummary {
+  pub kind: NodeKind,
+  pub total: usize,
+  pub healthy: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthSummary {
+  /// `None` when the coordinator could not be reached
+  pub coordinator_ready: Option<bool>,
+  pub nodes: Vec<NodeKindSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecorderSummary {
+  pub node_id: String,
+  pub healthy: bool,
+  /// `None` when the recorder did not answer in time
+  pub active_recordings: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeviceSummary {
+  pub total: usize,
+  pub by_status: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapacitySummary {
+  /// Active coordinator leases by kind
+  pub leases: Option<BTreeMap<String, usize>>,
+  pub gateway_streams: usize,
+  pub gateway_recordings: usize,
+  pub recorders: Vec<RecorderSummary>,
+  pub devices: Option<DeviceSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlarmEntry {
+  pub severity: String,
+  pub message: String,
+  pub fired_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AlarmSummary {
+  /// Number of events sampled (at most `ALARM_SAMPLE`, newest first)
+  pub sampled: usize,
+  /// Unsuppressed events in the sample by severity
+  pub active_by_severity: BTreeMap<String, usize>,
+  pub latest: Vec<AlarmEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemOverview {
+  pub status: OverallStatus,
+  pub generated_at_epoch_secs: u64,
+  pub sources: Vec<SourceReport>,
+  pub health: HealthSummary,
+  pub capacity: CapacitySummary,
+  pub alarms: Option<AlarmSummary>,
+}
+
+#[derive(Deserialize)]
+struct DeviceRow {
+  status: String,
+}
+
+#[derive(Deserialize)]
+struct AlertRow {
+  severity: String,
+  message: String,
+  fired_at: String,
+  #[serde(default)]
+  suppressed: bool,
+}
+
+pub async fn system_overview(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+) -> Json<SystemOverview> {
+  Json(build_overview(&state, headers.get(AUTHORIZATION).cloned()).await)
+}
+
+/// Collect the overview. `auth` is forwarded to device-manager and
+/// alert-service so they scope their answers to the caller's tenant.
+pub async fn build_overview(state: &AppState, auth: Option<HeaderValue>) -> SystemOverview {
+  let config = state.config();
+  let timeout = Duration::from_millis(config.overview_timeout_ms.max(1));
+  let client = match reqwest::Client::builder().timeout(timeout).build() {
+    Ok(client) => client,
+    Err(e) => {
+      warn!(error = %e, "failed to build HTTP client for system overview");
+      reqwest::Client::new()
+    }
+  };
+
+  let coordinator_base = config.coordinator_base_url.clone();
+  let coordinator = async {
+    let ready = timed("coordinator", timeout, async {
+      let resp = client.get(coordinator_base.join("readyz")?).send().await?;
+      Ok(resp.status().is_success())
+    });
+    let leases = timed(
+      "coordinator-leases",
+      timeout,
+      get_json::<Vec<LeaseRecord>>(&client, &coordinator_base, "v1/leases", None),
+    );
+    tokio::join!(ready, leases)
+  };
+
+  let devices = optional_source(
+    "device-manager",
+    config.device_manager_base_url.clone(),
+    timeout,
+    |base| {
+      let client = client.clone();
+      let auth = auth.clone();
+      async move { get_json::<Vec<DeviceRow>>(&client, &base, "v1/devices", auth).await }
+    },
+  );
+
+  let alarms = optional_source(
+    "alert-service",
+    config.alert_service_base_url.clone(),
+    timeout,
+    |base| {
+      let client = client.clone();
+      let auth = auth.clone();
+      async move {
+        let path = format!("v1/events?limit={ALARM_SAMPLE}");
+        get_json::<Vec<AlertRow>>(&client, &base, &path, auth).await
+      }
+    },
+  );
+
+  let recorders = recorder_reports(state, timeout);
+
+  let (
+    ((ready_report, ready), (leases_report, leases)),
+    (devices_report, devices),
+    (alarms_report, alarms),
+    (recorder_sources, recorders),
+  ) = tokio::join!(coordinator, devices, alarms, recorders);
+
+  let routes = state.routing().status().await;
+  let (gateway_streams, gateway_recordings) = (
+    state.streams().read().await.len(),
+    state.recordings().read().await.len(),
+  );
+
+  let mut sources = vec![ready_report, leases_report, devices_report, alarms_report];
+  sources.extend(recorder_sources);
+
+  let health = HealthSummary {
+    coordinator_ready: ready,
+    nodes: summarize_nodes(&routes),
+  };
+  let status = overall_status(&health, &sources);
+
+  SystemOverview {
+    status,
+    generated_at_epoch_secs: SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default(),
+    sources,
+    health,
+    capacity: CapacitySummary {
+      leases: leases.map(|records| summarize_leases(&records)),
+      gateway_streams,
+      gateway_recordings,
+      recorders,
+      devices: devices.map(|rows| summarize_devices(&rows)),
+    },
+    alarms: alarms.map(|rows| summarize_alarms(&rows)),
+  }
+}
+
+/// Active recordings on every routed recorder, queried concurrently
+async fn recorder_reports(
+  state: &AppState,
+  timeout: Duration,
+) -> (Vec<SourceReport>, Vec<RecorderSummary>) {
+  let routing = state.routing();
+  let health: BTreeMap<String, bool> = routing
+    .status()
+    .await
+    .into_iter()
+    .filter(|r| r.kind == NodeKind::Recorder)
+    .map(|r| (r.node_id, r.healthy))
+    .collect();
+
+  let mut calls = JoinSet::new();
+  for (index, endpoint) in routing.endpoints(NodeKind::Recorder).await.into_iter().enumerate() {
+    calls.spawn(async move {
+      let name = format!("recorder/{}", endpoint.node_id);
+      let result = timed(&name, timeout, async {
+        let resp = endpoint
+          .client
+          .inner()
+          .get(endpoint.url("recordings")?)
+          .timeout(timeout)
+          .send()
+          .await?
+          .error_for_status()?;
+        Ok(resp.json::<RecordingListResponse>().await?)
+      })
+      .await;
+      (index, endpoint.node_id.clone(), result)
+    });
+  }
+
+  let mut results = Vec::new();
+  while let Some(joined) = calls.join_next().await {
+    match joined {
+      Ok(result) => results.push(result),
+      Err(e) => warn!(error = %e, "recorder overview task failed"),
+    }
+  }
+  results.sort_by_key(|(index, _, _)| *index);
+
+  let mut sources = Vec::with_capacity(results.len());
+  let mut recorders = Vec::with_capacity(results.len());
+  for (_, node_id, (report, list)) in results {
+    sources.push(report);
+    recorders.push(RecorderSummary {
+      healthy: health.get(&node_id).copied().unwrap_or(false),
+      node_id,
+      active_recordings: list
+        .map(|l| l.recordings.iter().filter(|r| r.state.is_active()).count()),
+    });
+  }
+  (sources, recorders)
+}
+
+async fn optional_source<T, F, Fut>(
+  name: &str,
+  base: Option<Url>,
+  timeout: Duration,
+  call: F,
+) -> (SourceReport, Option<T>)
+where
+  F: FnOnce(Url) -> Fut,
+  Fut: Future<Output = Result<T>>,
+{
+  match base {
+    Some(base) => timed(name, timeout, call(base)).await,
+    None => (
+      SourceReport {
+        name: name.to_string(),
+        state: SourceState::NotConfigured,
+        latency_ms: None,
+        error: None,
+      },
+      None,
+    ),
+  }
+}
+
+/// Run one source call under `timeout` and describe how it went
+async fn timed<T>(
+  name: &str,
+  timeout: Duration,
+  call: impl Future<Output = Result<T>>,
+) -> (SourceReport, Option<T>) {
+  let started = Instant::now();
+  let outcome = tokio::time::timeout(timeout, call).await;
+  let latency_ms = Some(u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX));
+  let (state, error, value) = match outcome {
+    Ok(Ok(value)) => (SourceState::Ok, None, Some(value)),
+    Ok(Err(e)) if is_timeout(&e) => (SourceState::Timeout, Some(format!("{e:#}")), None),
+    Ok(Err(e)) => (SourceState::Error, Some(format!("{e:#}")), None),
+    Err(_) => (
+      SourceState::Timeout,
+      Some(format!("no answer within {}ms", timeout.as_millis())),
+      None,
+    ),
+  };
+  if let Some(error) = &error {
+    warn!(source = name, error = %error, "system overview source unavailable");
+  }
+  (
+    SourceReport {
+      name: name.to_string(),
+      state,
+      latency_ms,
+      error,
+    },
+    value,
+  )
+}
+
+fn is_timeout(error: &anyhow::Error) -> bool {
+  error
+    .downcast_ref::<reqwest::Error>()
+    .is_some_and(reqwest::Error::is_timeout)
+}
+
+pub(crate) async fn get_json<T: DeserializeOwned>(
+  client: &reqwest::Client,
+  base: &Url,
+  path: &str,
+  auth: Option<HeaderValue>,
+) -> Result<T> {
+  let url = base.join(path).context("invalid overview source URL")?;
+  let mut request = client.get(url);
+  if let Some(auth) = auth {
+    request = request.header(AUTHORIZATION, auth);
+  }
+  if let Some(identity) = common::gateway_identity::current() {
+    request = request.headers(identity);
+  }
+  Ok(request.send().await?.error_for_status()?.json::<T>().await?)
+}
+
+fn summarize_nodes(routes: &[RouteStatus]) -> Vec<NodeKindSummary> {
+  NodeKind::ALL
+    .into_iter()
+    .map(|kind| {
+      let of_kind = routes.iter().filter(|r| r.kind == kind);
+      NodeKindSummary {
+        kind,
+        total: of_kind.clone().count(),
+        healthy: of_kind.filter(|r| r.healthy).count(),
+      }
+    })
+    .collect()
+}
+
+fn summarize_leases(records: &[LeaseRecord]) -> BTreeMap<String, usize> {
+  let mut by_kind = BTreeMap::new();
+  for record in records {
+    *by_kind.entry(record.kind.as_str().to_string()).or_default() += 1;
+  }
+  by_kind
+}
+
+fn summarize_devices(rows: &[DeviceRow]) -> DeviceSummary {
+  let mut summary = DeviceSummary {
+    total: rows.len(),
+    ..Default::default()
+  };
+  for row in rows {
+    *summary.by_status.entry(row.status.clone()).or_default() += 1;
+  }
+  summary
+}
+
+fn summarize_alarms(rows: &[AlertRow]) -> AlarmSummary {
+  let mut summary = AlarmSummary {
+    sampled: rows.len(),
+    ..Default::default()
+  };
+  for row in rows.iter().filter(|r| !r.suppressed) {
+    *summary.active_by_severity.entry(row.severity.clone()).or_default() += 1;
+    if summary.latest.len() < LATEST_ALARMS {
+      summary.latest.push(AlarmEntry {
+        severity: row.severity.clone(),
+        message: row.message.clone(),
+        fired_at: row.fired_at.clone(),
+      });
+    }
+  }
+  summary
+}
+
+/// Unavailable when the coordinator is down or not ready (nothing can be
+/// scheduled); degraded when any source failed or any routed node is unhealthy.
+fn overall_status(health: &HealthSummary, sources: &[SourceReport]) -> OverallStatus {
+  if health.coordinator_ready != Some(true) {
+    return OverallStatus::Unavailable;
+  }
+  let source_failed = sources
+    .iter()
+    .any(|s| matches!(s.state, SourceState::Error | SourceState::Timeout));
+  let node_unhealthy = health.nodes.iter().any(|n| n.healthy < n.total);
+  if source_failed || node_unhealthy {
+    OverallStatus::Degraded
+  } else {
+    OverallStatus::Healthy
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::GatewayConfig,
+    coordinator::HttpCoordinatorClient,
+    routing::RoutingTable,
+    worker::{HttpRecorderClient, HttpWorkerClient},
+  };
+  use axum::{Router, routing::get};
+  use serde_json::json;
+  use std::{net::SocketAddr, sync::Arc};
+  use tokio::net::TcpListener;
+
+  /// One server standing in for the coordinator, a recorder and a slow alert-service
+  async fn spawn_backend() -> Url {
+    let app = Router::new()
+      .route("/readyz", get(|| async { "ready" }))
+      .route(
+        "/v1/leases",
+        get(|| async {
+          Json(json!([
+            {"lease_id": "l1", "resource_id": "cam-1", "holder_id": "gw", "kind": "stream", "expires_at_epoch_secs": 0, "version": 1},
+            {"lease_id": "l2", "resource_id": "rec-1", "holder_id": "gw", "kind": "recorder", "expires_at_epoch_secs": 0, "version": 1},
+            {"lease_id": "l3", "resource_id": "rec-2", "holder_id": "gw", "kind": "recorder", "expires_at_epoch_secs": 0, "version": 1}
+          ]))
+        }),
+      )
+      .route(
+        "/recordings",
+        get(|| async {
+          Json(json!({"recordings": [
+            {"config": {"id": "rec-1"}, "state": "recording"},
+            {"config": {"id": "rec-0"}, "state": "stopped"}
+          ]}))
+        }),
+      )
+      .route(
+        "/v1/events",
+        get(|| async {
+          tokio::time::sleep(Duration::from_secs(5)).await;
+          Json(json!([]))
+        }),
+      );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      axum::serve(listener, app).await.unwrap();
+    });
+    Url::parse(&format!("http://{addr}/")).unwrap()
+  }
+
+  async fn state_for(backend: Url) -> AppState {
+    let config = GatewayConfig {
+      bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+      coordinator_base_url: backend.clone(),
+      node_id: "test-node".into(),
+      worker_base_url: backend.clone(),
+      recorder_base_url: backend.clone(),
+      node_discovery_interval_secs: 0,
+      node_unhealthy_threshold: 3,
+      overview_timeout_ms: 300,
+      auth: None,
+      device_manager_base_url: None,
+      ai_service_base_url: None,
+      playback_base_url: None,
+      alert_service_base_url: Some(backend.clone()),
+      federation_token: None,
+    };
+    let routing = Arc::new(RoutingTable::new(3));
+    routing.add_static(NodeKind::Recorder, backend.clone()).await.unwrap();
+    let coordinator = Arc::new(HttpCoordinatorClient::new(backend).await.unwrap());
+    AppState::new(
+      config,
+      coordinator,
+      Arc::new(HttpWorkerClient::new(routing.clone())),
+      Arc::new(HttpRecorderClient::new(routing.clone())),
+      routing,
+    )
+  }
+
+  fn source<'a>(overview: &'a SystemOverview, name: &str) -> &'a SourceReport {
+    overview.sources.iter().find(|s| s.name == name).unwrap()
+  }
+
+  #[tokio::test]
+  async fn slow_and_unconfigured_sources_yield_partial_overview() {
+    let state = state_for(spawn_backend().await).await;
+
+    let overview = build_overview(&state, None).await;
+
+    assert_eq!(overview.status, OverallStatus::Degraded);
+    assert_eq!(overview.health.coordinator_ready, Some(true));
+    assert_eq!(source(&overview, "coordinator").state, SourceState::Ok);
+    assert_eq!(source(&overview, "device-manager").state, SourceState::NotConfigured);
+    assert_eq!(source(&overview, "alert-service").state, SourceState::Timeout);
+    assert_eq!(source(&overview, "recorder/static-recorder").state, SourceState::Ok);
+
+    let leases = overview.capacity.leases.unwrap();
+    assert_eq!(leases.get("stream"), Some(&1));
+    assert_eq!(leases.get("recorder"), Some(&2));
+    assert_eq!(overview.capacity.recorders.len(), 1);
+    assert_eq!(overview.capacity.recorders[0].active_recordings, Some(1));
+    assert!(overview.capacity.devices.is_none());
+    assert!(overview.alarms.is_none());
+  }
+
+  #[tokio::test]
+  async fn unreachable_coordinator_marks_overview_unavailable() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let state = state_for(Url::parse(&format!("http://{addr}/")).unwrap()).await;
+
+    let overview = build_overview(&state, None).await;
+
+    assert_eq!(overview.status, OverallStatus::Unavailable);
+    assert_eq!(overview.health.coordinator_ready, None);
+    assert_eq!(source(&overview, "coordinator").state, SourceState::Error);
+    assert!(overview.capacity.leases.is_none());
+    assert_eq!(overview.capacity.recorders[0].active_recordings, None);
+  }
+
+  #[test]
+  fn alarm_summary_skips_suppressed_events() {
+    let rows: Vec<AlertRow> = serde_json::from_value(json!([
+      {"severity": "critical", "message": "camera offline", "fired_at": "2026-01-01T00:00:02Z", "suppressed": false},
+      {"severity": "warning", "message": "disk 80%", "fired_at": "2026-01-01T00:00:01Z", "suppressed": true},
+      {"severity": "critical", "message": "camera offline", "fired_at": "2026-01-01T00:00:00Z", "suppressed": false}
+    ]))
+    .unwrap();
+
+    let summary = summarize_alarms(&rows);
+
+    assert_eq!(summary.sampled, 3);
+    assert_eq!(summary.active_by_severity.get("critical"), Some(&2));
+    assert!(!summary.active_by_severity.contains_key("warning"));
+    assert_eq!(summary.latest.len(), 2);
+    assert_eq!(summary.latest[0].fired_at, "2026-01-01T00:00:02Z");
+  }
+}
@@ -0,0 +1,406 @@
+//! WebSocket and Server-Sent Events proxying.
+//!
+//! `GET /v1/live/:service/*path` forwards a WebSocket upgrade or an
+//! `Accept: text/event-stream` request to `path` on a configured backend
+//! (device-manager, ai-service, playback-service, alert-service), so operator
+//! clients reach every real-time endpoint through the gateway origin. Plain
+//! requests are rejected; regular APIs keep their own routes.
+//!
+//! The caller is authenticated by the gateway (browsers may pass the token as
+//! `?access_token=`, see `auth`) and forwarded with the signed identity
+//! headers; the token itself is never sent upstream in the query string.
+
+use crate::{error::ApiError, state::AppState};
+use axum::{
+  body::Body,
+  extract::{
+    Path, RawQuery, State, WebSocketUpgrade,
+    ws::{self, WebSocket},
+  },
+  http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
+  response::{IntoResponse, Response},
+};
+use common::gateway_identity;
+use futures::{SinkExt, StreamExt};
+use reqwest::Url;
+use std::{sync::LazyLock, time::Duration};
+use tokio_tungstenite::{
+  MaybeTlsStream, WebSocketStream, connect_async,
+  tungstenite::{self, client::IntoClientRequest, protocol::frame::coding::CloseCode},
+};
+use tracing::{debug, warn};
+
+/// Query parameter browsers use to authenticate WebSocket/EventSource requests
+pub const ACCESS_TOKEN_PARAM: &str = "access_token";
+
+/// Path prefix of proxied routes
+pub const LIVE_PREFIX: &str = "/v1/live/";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// No overall timeout: event streams stay open as long as both sides want
+static SSE_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  reqwest::Client::builder()
+    .connect_timeout(CONNECT_TIMEOUT)
+    .build()
+    .unwrap_or_default()
+});
+
+type Upstream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Proxy one WebSocket or SSE request to a backend service
+pub async fn proxy(
+  State(state): State<AppState>,
+  Path((service, path)): Path<(String, String)>,
+  RawQuery(query): RawQuery,
+  ws: Option<WebSocketUpgrade>,
+  headers: HeaderMap,
+) -> Result<Response, ApiError> {
+  let url = upstream_url(&state, &service, &path, query.as_deref())?;
+  let forwarded = forwarded_headers(&headers);
+
+  if let Some(ws) = ws {
+    return websocket(ws, url, forwarded, &headers).await;
+  }
+  let wants_events = headers
+    .get(header::ACCEPT)
+    .and_then(|v| v.to_str().ok())
+    .is_some_and(|v| v.contains("text/event-stream"));
+  if wants_events {
+    return event_stream(url, forwarded).await;
+  }
+  Err(ApiError::bad_request(
+    "only WebSocket upgrades and text/event-stream requests are proxied",
+  ))
+}
+
+/// Backend URL for `path` on `service`, without the gateway access token
+fn upstream_url(state: &AppState, service: &str, path: &str, query: Option<&str>) -> Result<Url, ApiError> {
+  if path.split('/').any(|segment| segment == ".." || segment == ".") {
+    return Err(ApiError::bad_request("invalid path"));
+  }
+  let base = state
+    .config()
+    .service_endpoints()
+    .into_iter()
+    .filter(|(name, _)| *name != "coordinator")
+    .find(|(name, _)| *name == service)
+    .map(|(_, url)| url)
+    .ok_or_else(|| ApiError::not_found(format!("no live endpoints for service '{service}'")))?;
+
+  let mut url = base
+    .join(path.trim_start_matches('/'))
+    .map_err(|_| ApiError::bad_request("invalid path"))?;
+  url.set_query(None);
+  if let Some(query) = query {
+    let pairs: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+      .filter(|(key, _)| key != ACCESS_TOKEN_PARAM)
+      .map(|(key, value)| (key.into_owned(), value.into_owned()))
+      .collect();
+    if !pairs.is_empty() {
+      url.query_pairs_mut().extend_pairs(pairs);
+    }
+  }
+  Ok(url)
+}
+
+/// Headers sent upstream: the caller's credentials and the gateway identity
+fn forwarded_headers(headers: &HeaderMap) -> HeaderMap {
+  let mut forwarded = HeaderMap::new();
+  for name in [header::AUTHORIZATION, HeaderName::from_static("last-event-id")] {
+    if let Some(value) = headers.get(&name) {
+      forwarded.insert(name, value.clone());
+    }
+  }
+  if let Some(identity) = gateway_identity::current() {
+    forwarded.extend(identity);
+  }
+  forwarded
+}
+
+async fn event_stream(url: Url, forwarded: HeaderMap) -> Result<Response, ApiError> {
+  let upstream = SSE_CLIENT
+    .get(url)
+    .headers(forwarded)
+    .header(header::ACCEPT, "text/event-stream")
+    .send()
+    .await
+    .map_err(|e| {
+      warn!(error = %e, "live event stream connection failed");
+      ApiError::new(StatusCode::BAD_GATEWAY, "upstream unavailable")
+    })?;
+
+  let status = upstream.status();
+  let content_type = upstream.headers().get(header::CONTENT_TYPE).cloned();
+  let mut response = Response::new(Body::from_stream(upstream.bytes_stream()));
+  *response.status_mut() = status;
+  let headers = response.headers_mut();
+  if let Some(content_type) = content_type {
+    headers.insert(header::CONTENT_TYPE, content_type);
+  }
+  headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+  // Stop reverse proxies in front of the gateway from buffering events
+  headers.insert("x-accel-buffering", HeaderValue::from_static("no"));
+  Ok(response)
+}
+
+async fn websocket(
+  ws: WebSocketUpgrade,
+  mut url: Url,
+  forwarded: HeaderMap,
+  headers: &HeaderMap,
+) -> Result<Response, ApiError> {
+  let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+  url
+    .set_scheme(scheme)
+    .map_err(|_| ApiError::internal("invalid upstream URL"))?;
+
+  let mut request = url
+    .as_str()
+    .into_client_request()
+    .map_err(|e| ApiError::internal(format!("invalid upstream URL: {e}")))?;
+  request.headers_mut().extend(forwarded);
+  if let Some(protocols) = headers.get(header::SEC_WEBSOCKET_PROTOCOL) {
+    request
+      .headers_mut()
+      .insert(header::SEC_WEBSOCKET_PROTOCOL, protocols.clone());
+  }
+
+  // Connect before accepting the client so upstream failures surface as a
+  // plain HTTP error instead of an immediately closed socket
+  let (upstream, handshake) = tokio::time::timeout(CONNECT_TIMEOUT, connect_async(request))
+    .await
+    .map_err(|_| ApiError::new(StatusCode::GATEWAY_TIMEOUT, "upstream connect timed out"))?
+    .map_err(|e| match e {
+      tungstenite::Error::Http(resp) => ApiError::new(
+        StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+        "upstream rejected the WebSocket upgrade",
+      ),
+      e => {
+        warn!(error = %e, "live WebSocket connection failed");
+        ApiError::new(StatusCode::BAD_GATEWAY, "upstream unavailable")
+      }
+    })?;
+
+  let ws = match handshake
+    .headers()
+    .get(header::SEC_WEBSOCKET_PROTOCOL)
+    .and_then(|v| v.to_str().ok())
+  {
+    Some(protocol) => ws.protocols([protocol.to_string()]),
+    None => ws,
+  };
+  Ok(ws.on_upgrade(move |client| pump(client, upstream)).into_response())
+}
+
+/// Relay frames both ways until either side closes
+async fn pump(client: WebSocket, upstream: Upstream) {
+  let (mut client_tx, mut client_rx) = client.split();
+  let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+  let to_upstream = async {
+    while let Some(Ok(message)) = client_rx.next().await {
+      let close = matches!(message, ws::Message::Close(_));
+      if upstream_tx.send(to_tungstenite(message)).await.is_err() || close {
+        break;
+      }
+    }
+    let _ = upstream_tx.close().await;
+  };
+  let to_client = async {
+    while let Some(Ok(message)) = upstream_rx.next().await {
+      let Some(message) = from_tungstenite(message) else {
+        continue;
+      };
+      let close = matches!(message, ws::Message::Close(_));
+      if client_tx.send(message).await.is_err() || close {
+        break;
+      }
+    }
+    let _ = client_tx.close().await;
+  };
+
+  tokio::select! {
+    _ = to_upstream => {},
+    _ = to_client => {},
+  }
+  debug!("live WebSocket proxy closed");
+}
+
+fn to_tungstenite(message: ws::Message) -> tungstenite::Message {
+  match message {
+    ws::Message::Text(text) => tungstenite::Message::Text(text),
+    ws::Message::Binary(data) => tungstenite::Message::Binary(data),
+    ws::Message::Ping(data) => tungstenite::Message::Ping(data),
+    ws::Message::Pong(data) => tungstenite::Message::Pong(data),
+    ws::Message::Close(frame) => {
+      tungstenite::Message::Close(frame.map(|frame| tungstenite::protocol::CloseFrame {
+        code: CloseCode::from(frame.code),
+        reason: frame.reason,
+      }))
+    }
+  }
+}
+
+fn from_tungstenite(message: tungstenite::Message) -> Option<ws::Message> {
+  Some(match message {
+    tungstenite::Message::Text(text) => ws::Message::Text(text),
+    tungstenite::Message::Binary(data) => ws::Message::Binary(data),
+    tungstenite::Message::Ping(data) => ws::Message::Ping(data),
+    tungstenite::Message::Pong(data) => ws::Message::Pong(data),
+    tungstenite::Message::Close(frame) => ws::Message::Close(frame.map(|frame| ws::CloseFrame {
+      code: frame.code.into(),
+      reason: frame.reason,
+    })),
+    tungstenite::Message::Frame(_) => return None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    config::GatewayConfig,
+    coordinator::HttpCoordinatorClient,
+    routes,
+    routing::RoutingTable,
+    worker::{HttpRecorderClient, HttpWorkerClient},
+  };
+  use axum::{
+    Router,
+    response::sse::{Event, Sse},
+    routing::get,
+  };
+  use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+  use tokio::net::TcpListener;
+
+  async fn serve(app: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      axum::serve(listener, app).await.unwrap();
+    });
+    addr
+  }
+
+  /// Alert-service stand-in: an SSE feed and a WebSocket that greets with the
+  /// identity it received, then echoes
+  async fn spawn_backend() -> Url {
+    let app = Router::new()
+      .route(
+        "/v1/events/stream",
+        get(|RawQuery(query): RawQuery| async move {
+          let events = futures::stream::iter([
+            Ok::<_, Infallible>(Event::default().event("alert").data("motion")),
+            Ok(Event::default().data(query.unwrap_or_default())),
+          ]);
+          Sse::new(events)
+        }),
+      )
+      .route(
+        "/v1/events/ws",
+        get(|ws: WebSocketUpgrade, headers: HeaderMap| async move {
+          let user = headers
+            .get(gateway_identity::USER_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("anonymous")
+            .to_string();
+          ws.on_upgrade(|mut socket| async move {
+            let _ = socket.send(ws::Message::Text(format!("hello {user}"))).await;
+            while let Some(Ok(message)) = socket.recv().await {
+              if socket.send(message).await.is_err() {
+                break;
+              }
+            }
+          })
+        }),
+      );
+    Url::parse(&format!("http://{}/", serve(app).await)).unwrap()
+  }
+
+  async fn spawn_gateway(backend: Url) -> SocketAddr {
+    let config = GatewayConfig {
+      bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+      coordinator_base_url: backend.clone(),
+      node_id: "test-node".into(),
+      worker_base_url: backend.clone(),
+      recorder_base_url: backend.clone(),
+      node_discovery_interval_secs: 0,
+      node_unhealthy_threshold: 3,
+      overview_timeout_ms: 300,
+      auth: None,
+      device_manager_base_url: None,
+      ai_service_base_url: None,
+      playback_base_url: None,
+      alert_service_base_url: Some(backend.clone()),
+      federation_token: None,
+    };
+    let routing = Arc::new(RoutingTable::new(3));
+    let coordinator = Arc::new(HttpCoordinatorClient::new(backend).await.unwrap());
+    let state = AppState::new(
+      config,
+      coordinator,
+      Arc::new(HttpWorkerClient::new(routing.clone())),
+      Arc::new(HttpRecorderClient::new(routing.clone())),
+      routing,
+    );
+    serve(routes::router(state)).await
+  }
+
+  #[tokio::test]
+  async fn proxies_server_sent_events_without_access_token() {
+    let gateway = spawn_gateway(spawn_backend().await).await;
+    let resp = reqwest::Client::new()
+      .get(format!(
+        "http://{gateway}/v1/live/alert-service/v1/events/stream?camera=cam-1&access_token=secret"
+      ))
+      .header(header::ACCEPT, "text/event-stream")
+      .send()
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/event-stream");
+    let body = resp.text().await.unwrap();
+    assert!(body.contains("event: alert\ndata: motion"));
+    assert!(body.contains("data: camera=cam-1\n"));
+    assert!(!body.contains("secret"));
+  }
+
+  #[tokio::test]
+  async fn proxies_websocket_frames_both_ways() {
+    let gateway = spawn_gateway(spawn_backend().await).await;
+    let (mut socket, _) = connect_async(format!("ws://{gateway}/v1/live/alert-service/v1/events/ws"))
+      .await
+      .unwrap();
+
+    let greeting = socket.next().await.unwrap().unwrap();
+    assert_eq!(greeting, tungstenite::Message::Text("hello anonymous".into()));
+    socket.send(tungstenite::Message::Text("ping".into())).await.unwrap();
+    assert_eq!(
+      socket.next().await.unwrap().unwrap(),
+      tungstenite::Message::Text("ping".into())
+    );
+    socket.close(None).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn rejects_plain_requests_and_unknown_services() {
+    let gateway = spawn_gateway(spawn_backend().await).await;
+    let client = reqwest::Client::new();
+    let status = |path: &str| {
+      let request = client
+        .get(format!("http://{gateway}{path}"))
+        .header(header::ACCEPT, "text/event-stream");
+      async move { request.send().await.unwrap().status() }
+    };
+
+    let plain = client
+      .get(format!("http://{gateway}/v1/live/alert-service/v1/events/stream"))
+      .send()
+      .await
+      .unwrap();
+    assert_eq!(plain.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(status("/v1/live/coordinator/v1/state/streams").await, StatusCode::NOT_FOUND);
+    assert_eq!(status("/v1/live/device-manager/v1/events").await, StatusCode::NOT_FOUND);
+  }
+}
@@ -1,27 +1,105 @@
-use crate::{error::ApiError, state::AppState};
+use crate::{auth::{GatewayAuth, gateway_auth_middleware}, camera_analytics, canary::{CanaryRule, CanaryStatus}, error::ApiError, federation, onboarding, openapi, overview, realtime, routing::RouteStatus, service_config, state::AppState, timeline, usage};
 use axum::{
   Json, Router,
-  extract::{Path, State},
+  extract::{DefaultBodyLimit, Path, State},
   middleware,
-  routing::{delete, get},
+  routing::{delete, get, post, put},
 };
 use common::{
+  federation::MAX_REPORT_BYTES,
+  idempotency::{Idempotency, StateStoreIdempotencyStore, idempotency_middleware},
   leases::{LeaseAcquireRequest, LeaseKind, LeaseReleaseRequest},
+  nodes::NodeKind,
+  quota::{Quota, QuotaConfig, StateStoreUsageStore, quota_middleware},
+  rate_limit::{RateLimitConfig, RateLimitKey, RateLimiter, rate_limit_middleware},
   recordings::{RecordingInfo, RecordingStartRequest, RecordingStartResponse, RecordingState, RecordingStopRequest, RecordingStopResponse},
   streams::{StreamInfo, StreamStartRequest, StreamStartResponse, StreamState, StreamStopResponse},
+  timeline::TimelineEventKind,
 };
+use serde::Deserialize;
+use std::sync::Arc;
 use telemetry::trace_http_request;
 use tower::ServiceBuilder;
 use tracing::info;
 
 pub fn router(state: AppState) -> Router {
-  Router::new()
-    .route("/healthz", get(healthz))
-    .route("/metrics", get(metrics))
-    .route("/v1/streams", get(list_streams).post(start_stream))
+  // Share Idempotency-Key records across gateway replicas when the StateStore is enabled
+  let idempotency = match state.state_store() {
+    Some(store) => Idempotency::new(Arc::new(StateStoreIdempotencyStore::new(store))),
+    None => Idempotency::in_memory(),
+  };
+  let idempotent = middleware::from_fn_with_state(idempotency, idempotency_middleware);
+  let limiter = RateLimiter::new(RateLimitConfig::from_env("GATEWAY", 100, 50.0, RateLimitKey::ApiKey));
+  // Tenant limits and monthly quotas rely on the AuthContext set by `auth`;
+  // quota counters are shared across replicas through the StateStore
+  let tenant_limiter = RateLimiter::new(RateLimitConfig::from_env("GATEWAY_TENANT", 200, 100.0, RateLimitKey::Tenant));
+  let quota_config = QuotaConfig::from_env("GATEWAY", 0);
+  let quota = match state.state_store() {
+    Some(store) => Quota::new(quota_config, Arc::new(StateStoreUsageStore::new(store))),
+    None => Quota::in_memory(quota_config),
+  };
+
+  let api = Router::new()
+    .route("/v1/streams", get(list_streams).post(start_stream).layer(idempotent.clone()))
     .route("/v1/streams/:id", delete(stop_stream))
-    .route("/v1/recordings", get(list_recordings).post(start_recording))
+    .route("/v1/recordings", get(list_recordings).post(start_recording).layer(idempotent))
     .route("/v1/recordings/:id", delete(stop_recording))
+    .layer(middleware::from_fn_with_state(quota.clone(), quota_middleware))
+    .layer(middleware::from_fn_with_state(tenant_limiter, rate_limit_middleware))
+    .layer(middleware::from_fn_with_state(limiter, rate_limit_middleware));
+
+  let auth = state.config().auth.as_ref().map(|config| Arc::new(GatewayAuth::new(config)));
+
+  let router = Router::new()
+    .route("/healthz", get(healthz))
+    .route("/metrics", get(metrics))
+    .route("/v1/nodes", get(list_nodes))
+    .route("/v1/canary", get(list_canaries))
+    .route("/v1/canary/:kind", put(set_canary).delete(remove_canary))
+    .route("/v1/system/overview", get(overview::system_overview))
+    .route("/v1/onboarding", post(onboarding::onboard_camera))
+    .route("/v1/analytics/cameras", get(camera_analytics::list_camera_analytics))
+    .route(
+      "/v1/analytics/cameras/:camera_id",
+      get(camera_analytics::get_camera_analytics)
+        .put(camera_analytics::put_camera_analytics)
+        .delete(camera_analytics::delete_camera_analytics),
+    )
+    .route("/v1/live/:service/*path", get(realtime::proxy))
+    .route("/v1/config/:service", get(service_config::forward).put(service_config::forward))
+    .route("/v1/config/:service/versions", get(service_config::forward))
+    .route("/v1/config/:service/versions/:version", get(service_config::forward))
+    .route("/v1/config/:service/rollback", post(service_config::forward))
+    .route("/v1/timeline", get(timeline::query_timeline))
+    .route("/v1/federation/sites", get(federation::forward_read))
+    .route(
+      federation::REPORT_ROUTE,
+      put(federation::receive_report)
+        .layer(DefaultBodyLimit::max(MAX_REPORT_BYTES))
+        .get(federation::forward_read)
+        .delete(federation::remove_site),
+    )
+    .route(
+      "/v1/federation/sites/:site_id/playback/*path",
+      get(federation::playback)
+        .post(federation::playback)
+        .delete(federation::playback),
+    )
+    .route("/v1/federation/devices", get(federation::forward_read))
+    .route("/v1/federation/alerts", get(federation::forward_read))
+    .route("/v1/federation/health", get(federation::forward_read))
+    .merge(api)
+    .merge(usage::router(quota))
+    .route("/v1/openapi.json", get(openapi::aggregated_openapi))
+    .route("/v1/docs", get(openapi::aggregated_docs))
+    .merge(common::openapi::openapi_routes(&openapi::openapi()))
+    .layer(middleware::from_fn(timeline::record_operator_actions));
+  let router = match auth {
+    Some(auth) => router.layer(middleware::from_fn_with_state(auth, gateway_auth_middleware)),
+    None => router,
+  };
+
+  router
     .layer(
       ServiceBuilder::new()
         .layer(middleware::from_fn(trace_http_request))
@@ -34,17 +112,72 @@ async fn healthz() -> &'static str {
 }
 
 async fn metrics() -> Result<String, ApiError> {
+  for health in common::resilient_http::target_health().await {
+    telemetry::metrics::set_upstream_health(
+      &health.target,
+      health.circuit.as_gauge(),
+      i64::from(health.consecutive_failures),
+      i64::try_from(health.last_latency_ms).unwrap_or(i64::MAX),
+      &health.event_totals(),
+    );
+  }
   telemetry::metrics::encode_metrics()
     .map_err(|e| ApiError::internal(format!("failed to encode metrics: {}", e)))
 }
 
+/// The gateway's routing table: discovered and static nodes with their health
+async fn list_nodes(State(state): State<AppState>) -> Json<Vec<RouteStatus>> {
+  Json(state.routing().status().await)
+}
+
+/// Canary rules with their current weights and outcome windows
+async fn list_canaries(State(state): State<AppState>) -> Json<Vec<CanaryStatus>> {
+  Json(state.routing().canary().status().await)
+}
+
+#[derive(Debug, Deserialize)]
+struct CanaryRequest {
+  version: String,
+  weight: u8,
+}
+
+/// Install or re-arm the canary rule for a node kind
+async fn set_canary(
+  State(state): State<AppState>,
+  Path(kind): Path<String>,
+  Json(request): Json<CanaryRequest>,
+) -> Result<Json<Vec<CanaryStatus>>, ApiError> {
+  let kind = kind.parse::<NodeKind>().map_err(ApiError::bad_request)?;
+  common::validation::validate_id(&request.version, "version")
+    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+  let rule = CanaryRule::new(kind, request.version, request.weight)
+    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+  let routing = state.routing();
+  let canary = routing.canary();
+  canary.set_rule(rule).await;
+  Ok(Json(canary.status().await))
+}
+
+async fn remove_canary(
+  State(state): State<AppState>,
+  Path(kind): Path<String>,
+) -> Result<Json<Vec<CanaryStatus>>, ApiError> {
+  let kind = kind.parse::<NodeKind>().map_err(ApiError::bad_request)?;
+  let routing = state.routing();
+  let canary = routing.canary();
+  if !canary.remove_rule(kind).await {
+    return Err(ApiError::not_found(format!("no canary rule for {kind} nodes")));
+  }
+  Ok(Json(canary.status().await))
+}
+
 async fn list_streams(State(state): State<AppState>) -> Result<Json<Vec<StreamInfo>>, ApiError> {
   let streams = state.streams().read().await;
   let list = streams.values().cloned().collect();
   Ok(Json(list))
 }
 
-async fn start_stream(
+pub(crate) async fn start_stream(
   State(state): State<AppState>,
   Json(payload): Json<StreamStartRequest>,
 ) -> Result<Json<StreamStartResponse>, ApiError> {
@@ -57,6 +190,10 @@ async fn start_stream(
   // Validate source URI (prevent command injection, URL attacks)
   common::validation::validate_uri(&config.uri, "source_uri")
     .map_err(|e| ApiError::bad_request(format!("invalid source_uri: {}", e)))?;
+  if let Some(substream_uri) = &config.substream_uri {
+    common::validation::validate_uri(substream_uri, "substream_uri")
+      .map_err(|e| ApiError::bad_request(format!("invalid substream_uri: {}", e)))?;
+  }
 
   {
     let streams = state.streams().read().await;
@@ -169,7 +306,7 @@ async fn start_stream(
   }))
 }
 
-async fn stop_stream(
+pub(crate) async fn stop_stream(
   State(state): State<AppState>,
   Path(stream_id): Path<String>,
 ) -> Result<Json<StreamStopResponse>, ApiError> {
@@ -405,6 +542,7 @@ async fn start_recording(
     .await;
 
   info!(recording_id = %payload.config.id, lease = %record.lease_id, "recording start accepted");
+  timeline::record_recording(&state, TimelineEventKind::RecordingStarted, &payload.config).await;
 
   Ok(Json(RecordingStartResponse {
     accepted: true,
@@ -475,6 +613,7 @@ async fn stop_recording(
     };
 
     info!(recording_id = %recording_id, lease = %lease_id, released = release_resp.released, "recording stop requested");
+    timeline::record_recording(&state, TimelineEventKind::RecordingStopped, &info.config).await;
 
     Ok(Json(RecordingStopResponse {
       stopped: true,
@@ -498,7 +637,7 @@ async fn stop_recording(
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::{config::GatewayConfig, coordinator::CoordinatorClient, worker::{RecorderClient, WorkerClient}};
+  use crate::{config::GatewayConfig, coordinator::CoordinatorClient, routing::RoutingTable, worker::{RecorderClient, WorkerClient}};
   use anyhow::{Result, anyhow};
   use axum::{
     body::Body,
@@ -509,6 +648,7 @@ mod tests {
       LeaseAcquireRequest, LeaseAcquireResponse, LeaseKind, LeaseRecord, LeaseReleaseRequest,
       LeaseReleaseResponse, LeaseRenewRequest, LeaseRenewResponse,
     },
+    nodes::{NodeKind, NodeRecord},
     streams::{StreamConfig, StreamInfo, StreamState},
   };
   use reqwest::Url;
@@ -585,6 +725,10 @@ mod tests {
         .unwrap_or(LeaseReleaseResponse { released: true });
       Ok(resp)
     }
+
+    async fn list_nodes(&self, _kind: Option<NodeKind>) -> Result<Vec<NodeRecord>> {
+      Ok(vec![])
+    }
   }
 
   #[derive(Default)]
@@ -655,6 +799,10 @@ mod tests {
     }
   }
 
+  fn routing() -> Arc<RoutingTable> {
+    Arc::new(RoutingTable::new(3))
+  }
+
   fn base_config() -> GatewayConfig {
     GatewayConfig {
       bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
@@ -662,6 +810,15 @@ mod tests {
       node_id: "test-node".into(),
       worker_base_url: Url::parse("http://127.0.0.1:8080").unwrap(),
       recorder_base_url: Url::parse("http://127.0.0.1:8083").unwrap(),
+      node_discovery_interval_secs: 0,
+      node_unhealthy_threshold: 3,
+      overview_timeout_ms: 2000,
+      auth: None,
+      device_manager_base_url: None,
+      ai_service_base_url: None,
+      playback_base_url: None,
+      alert_service_base_url: None,
+      federation_token: None,
     }
   }
 
@@ -685,7 +842,7 @@ mod tests {
     let worker = Arc::new(StubWorker::new());
     let worker_client: Arc<dyn WorkerClient> = worker.clone();
     let recorder: Arc<dyn RecorderClient> = Arc::new(StubRecorder::new());
-    let state = AppState::new(base_config(), coordinator.clone(), worker_client, recorder);
+    let state = AppState::new(base_config(), coordinator.clone(), worker_client, recorder, routing());
     let app = router(state.clone());
 
     let start_body = json!({
@@ -763,7 +920,7 @@ mod tests {
     let worker = Arc::new(StubWorker::new());
     let worker_client: Arc<dyn WorkerClient> = worker.clone();
     let recorder: Arc<dyn RecorderClient> = Arc::new(StubRecorder::new());
-    let state = AppState::new(base_config(), coordinator, worker_client, recorder);
+    let state = AppState::new(base_config(), coordinator, worker_client, recorder, routing());
     let app = router(state);
     let body = json!({
         "config": {
@@ -807,7 +964,7 @@ mod tests {
     let worker = Arc::new(StubWorker::new());
     let worker_client: Arc<dyn WorkerClient> = worker.clone();
     let recorder: Arc<dyn RecorderClient> = Arc::new(StubRecorder::new());
-    let state = AppState::new(base_config(), coordinator.clone(), worker_client, recorder);
+    let state = AppState::new(base_config(), coordinator.clone(), worker_client, recorder, routing());
     {
       // seed state directly with a running stream
       let mut streams = state.streams().write().await;
@@ -820,6 +977,8 @@ mod tests {
             uri: "rtsp://example".into(),
             codec: Some("h264".into()),
             container: Some("ts".into()),
+            substream_uri: None,
+            thermal_palette: None,
           },
           state: StreamState::Running,
           lease_id: Some("lease-abc".into()),
@@ -903,6 +1062,7 @@ mod tests {
       coordinator.clone(),
       worker.clone() as Arc<dyn WorkerClient>,
       recorder,
+      routing(),
     );
     let app = router(state.clone());
 
@@ -975,6 +1135,7 @@ mod tests {
       coordinator.clone(),
       worker.clone() as Arc<dyn WorkerClient>,
       recorder,
+      routing(),
     );
     let app = router(state.clone());
 
@@ -0,0 +1,567 @@
+//! Routing table for stream, recorder and AI nodes.
+//!
+//! Nodes register with the coordinator (`common::nodes`); the gateway polls
+//! `GET /v1/nodes`, load-balances requests round-robin across healthy nodes
+//! and drops nodes whose registration expired. The statically configured
+//! endpoints are only used while no node of that kind is registered. Canary
+//! rules (`canary`) can steer a share of new requests to one node version.
+//! Draining nodes get no new requests but still serve their existing
+//! resources.
+
+use crate::{
+  canary::{CanaryPolicy, CanaryRouter},
+  coordinator::CoordinatorClient,
+};
+use anyhow::{Context, Result};
+use common::{
+  nodes::{NodeKind, NodeRecord},
+  resilient_http::{CircuitState, ResilientClient},
+};
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+  },
+  time::Duration,
+};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Upper bound on remembered resource-to-node assignments
+const MAX_ASSIGNMENTS: usize = 10_000;
+
+/// Service name used for a node's client in logs and upstream metrics
+fn target_name(kind: NodeKind) -> &'static str {
+  match kind {
+    NodeKind::Stream => "stream-node",
+    NodeKind::Recorder => "recorder-node",
+    NodeKind::Ai => "ai-service",
+  }
+}
+
+/// A routable node and the client used to reach it
+pub struct NodeEndpoint {
+  pub node_id: String,
+  pub base_url: Url,
+  /// Version the node registered with; `None` for static endpoints
+  pub version: Option<String>,
+  pub client: ResilientClient,
+}
+
+impl NodeEndpoint {
+  pub fn url(&self, path: &str) -> Result<Url> {
+    self
+      .base_url
+      .join(path)
+      .with_context(|| format!("invalid endpoint for node {}", self.node_id))
+  }
+}
+
+struct Route {
+  endpoint: Arc<NodeEndpoint>,
+  discovered: bool,
+  healthy: bool,
+  draining: bool,
+  consecutive_failures: u32,
+}
+
+/// Routing table entry as reported by `GET /v1/nodes`
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteStatus {
+  pub kind: NodeKind,
+  pub node_id: String,
+  pub base_url: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  pub discovered: bool,
+  pub healthy: bool,
+  pub draining: bool,
+  pub consecutive_failures: u32,
+  pub circuit: String,
+}
+
+pub struct RoutingTable {
+  routes: RwLock<HashMap<NodeKind, Vec<Route>>>,
+  assignments: RwLock<HashMap<(NodeKind, String), String>>,
+  cursors: [AtomicUsize; 3],
+  unhealthy_threshold: u32,
+  canary: CanaryRouter,
+}
+
+impl RoutingTable {
+  /// `unhealthy_threshold` consecutive failures take a node out of rotation
+  pub fn new(unhealthy_threshold: u32) -> Self {
+    Self {
+      routes: RwLock::new(HashMap::new()),
+      assignments: RwLock::new(HashMap::new()),
+      cursors: Default::default(),
+      unhealthy_threshold: unhealthy_threshold.max(1),
+      canary: CanaryRouter::default(),
+    }
+  }
+
+  pub fn with_canary(mut self, policy: CanaryPolicy) -> Self {
+    self.canary = CanaryRouter::new(policy);
+    self
+  }
+
+  pub fn canary(&self) -> &CanaryRouter {
+    &self.canary
+  }
+
+  fn cursor(&self, kind: NodeKind) -> &AtomicUsize {
+    match kind {
+      NodeKind::Stream => &self.cursors[0],
+      NodeKind::Recorder => &self.cursors[1],
+      NodeKind::Ai => &self.cursors[2],
+    }
+  }
+
+  /// Add a statically configured endpoint, used when discovery finds nothing
+  pub async fn add_static(&self, kind: NodeKind, base_url: Url) -> Result<()> {
+    let client = ResilientClient::builder(target_name(kind)).build().await?;
+    let endpoint = NodeEndpoint {
+      node_id: format!("static-{}", kind),
+      base_url,
+      version: None,
+      client,
+    };
+    self.routes.write().await.entry(kind).or_default().push(Route {
+      endpoint: Arc::new(endpoint),
+      discovered: false,
+      healthy: true,
+      draining: false,
+      consecutive_failures: 0,
+    });
+    Ok(())
+  }
+
+  /// Replace the discovered nodes of `kind` with the coordinator's current
+  /// registrations. Health state survives for nodes that are still present.
+  pub async fn apply_registrations(&self, kind: NodeKind, records: &[NodeRecord]) {
+    let mut routes = self.routes.write().await;
+    let list = routes.entry(kind).or_default();
+
+    let mut previous: HashMap<String, Route> = HashMap::new();
+    let mut next = Vec::with_capacity(records.len() + 1);
+    for route in list.drain(..) {
+      if route.discovered {
+        previous.insert(route.endpoint.node_id.clone(), route);
+      } else {
+        next.push(route);
+      }
+    }
+
+    for record in records.iter().filter(|r| r.kind == kind) {
+      let base_url = match Url::parse(&record.base_url) {
+        Ok(url) => url,
+        Err(e) => {
+          warn!(node_id = %record.node_id, error = %e, "ignoring node with invalid base_url");
+          continue;
+        }
+      };
+
+      if let Some(mut route) = previous.remove(&record.node_id) {
+        if route.endpoint.base_url == base_url && route.endpoint.version == record.version {
+          if route.draining != record.draining {
+            info!(kind = %kind, node_id = %record.node_id, draining = record.draining, "node drain state changed");
+          }
+          route.draining = record.draining;
+          next.push(route);
+          continue;
+        }
+      }
+
+      // Per-node target so one failing node cannot open the breaker for all
+      let target = format!("{}/{}", target_name(kind), record.node_id);
+      let client = match ResilientClient::builder(target).build().await {
+        Ok(client) => client,
+        Err(e) => {
+          warn!(node_id = %record.node_id, error = %e, "failed to build client for node");
+          continue;
+        }
+      };
+      info!(
+        kind = %kind,
+        node_id = %record.node_id,
+        base_url = %base_url,
+        version = record.version.as_deref().unwrap_or("-"),
+        "node added to routing table"
+      );
+      next.push(Route {
+        endpoint: Arc::new(NodeEndpoint {
+          node_id: record.node_id.clone(),
+          base_url,
+          version: record.version.clone(),
+          client,
+        }),
+        discovered: true,
+        healthy: true,
+        draining: record.draining,
+        consecutive_failures: 0,
+      });
+    }
+
+    for node_id in previous.keys() {
+      info!(kind = %kind, node_id = %node_id, "node removed from routing table");
+    }
+    *list = next;
+  }
+
+  /// Candidates for `kind`: discovered nodes if any, otherwise static ones
+  async fn candidates(&self, kind: NodeKind) -> Vec<(Arc<NodeEndpoint>, bool, bool)> {
+    let routes = self.routes.read().await;
+    let Some(list) = routes.get(&kind) else {
+      return Vec::new();
+    };
+    let discovered = list.iter().any(|r| r.discovered);
+    list
+      .iter()
+      .filter(|r| r.discovered == discovered)
+      .map(|r| (r.endpoint.clone(), r.healthy, r.draining))
+      .collect()
+  }
+
+  /// Next node for a new request, round-robin over healthy nodes within the
+  /// canary group chosen for it. When every node is unhealthy all are tried
+  /// anyway rather than failing outright; draining nodes are never picked.
+  pub async fn pick(&self, kind: NodeKind) -> Option<Arc<NodeEndpoint>> {
+    let candidates: Vec<_> = self
+      .candidates(kind)
+      .await
+      .into_iter()
+      .filter(|(_, _, draining)| !draining)
+      .collect();
+    let mut healthy = Vec::with_capacity(candidates.len());
+    for (endpoint, is_healthy, _) in &candidates {
+      if *is_healthy && endpoint.client.circuit_state().await != CircuitState::Open {
+        healthy.push(endpoint.clone());
+      }
+    }
+    let pool: Vec<Arc<NodeEndpoint>> = if healthy.is_empty() {
+      candidates.into_iter().map(|(endpoint, _, _)| endpoint).collect()
+    } else {
+      healthy
+    };
+    let pool = self
+      .canary
+      .select(
+        kind,
+        pool
+          .into_iter()
+          .map(|endpoint| {
+            let version = endpoint.version.clone();
+            (endpoint, version)
+          })
+          .collect(),
+      )
+      .await;
+    if pool.is_empty() {
+      return None;
+    }
+    let index = self.cursor(kind).fetch_add(1, Ordering::Relaxed) % pool.len();
+    Some(pool[index].clone())
+  }
+
+  /// Every routable node of `kind`, healthy ones first
+  pub async fn endpoints(&self, kind: NodeKind) -> Vec<Arc<NodeEndpoint>> {
+    let mut candidates = self.candidates(kind).await;
+    candidates.sort_by_key(|(_, healthy, _)| !*healthy);
+    candidates.into_iter().map(|(endpoint, _, _)| endpoint).collect()
+  }
+
+  /// Nodes to try for a new request: the round-robin pick first, then the
+  /// other non-draining nodes as failover targets
+  pub async fn route_order(&self, kind: NodeKind) -> Vec<Arc<NodeEndpoint>> {
+    let Some(first) = self.pick(kind).await else {
+      return Vec::new();
+    };
+    let mut candidates = self.candidates(kind).await;
+    candidates.sort_by_key(|(_, healthy, _)| !*healthy);
+    let rest: Vec<_> = candidates
+      .into_iter()
+      .filter(|(e, _, draining)| !draining && e.node_id != first.node_id)
+      .map(|(e, _, _)| e)
+      .collect();
+    let mut order = vec![first];
+    order.extend(rest);
+    order
+  }
+
+  /// Nodes to try for a call about an existing resource: its assigned node
+  /// first, then every other node (assignments do not survive a restart)
+  pub async fn resource_order(&self, kind: NodeKind, resource_id: &str) -> Vec<Arc<NodeEndpoint>> {
+    let assigned = self.assigned(kind, resource_id).await;
+    let mut order: Vec<_> = assigned.iter().cloned().collect();
+    order.extend(
+      self
+        .endpoints(kind)
+        .await
+        .into_iter()
+        .filter(|e| assigned.as_ref().is_none_or(|a| a.node_id != e.node_id)),
+    );
+    order
+  }
+
+  /// Remember which node serves a resource so follow-up calls reach it
+  pub async fn assign(&self, kind: NodeKind, resource_id: &str, node_id: &str) {
+    let mut assignments = self.assignments.write().await;
+    let key = (kind, resource_id.to_string());
+    if assignments.len() >= MAX_ASSIGNMENTS && !assignments.contains_key(&key) {
+      warn!(kind = %kind, resource_id, "assignment table full; follow-up calls will search all nodes");
+      return;
+    }
+    assignments.insert(key, node_id.to_string());
+  }
+
+  pub async fn unassign(&self, kind: NodeKind, resource_id: &str) {
+    self
+      .assignments
+      .write()
+      .await
+      .remove(&(kind, resource_id.to_string()));
+  }
+
+  /// Node a resource was assigned to, if it is still in the table
+  pub async fn assigned(&self, kind: NodeKind, resource_id: &str) -> Option<Arc<NodeEndpoint>> {
+    let node_id = self
+      .assignments
+      .read()
+      .await
+      .get(&(kind, resource_id.to_string()))
+      .cloned()?;
+    let routes = self.routes.read().await;
+    routes
+      .get(&kind)?
+      .iter()
+      .find(|r| r.endpoint.node_id == node_id)
+      .map(|r| r.endpoint.clone())
+  }
+
+  /// Record the outcome of a call to a node (passive health checking)
+  pub async fn report(&self, kind: NodeKind, node_id: &str, success: bool) {
+    let mut routes = self.routes.write().await;
+    let Some(route) = routes
+      .get_mut(&kind)
+      .and_then(|list| list.iter_mut().find(|r| r.endpoint.node_id == node_id))
+    else {
+      return;
+    };
+
+    if success {
+      if !route.healthy {
+        info!(kind = %kind, node_id, "node healthy again, back in rotation");
+      }
+      route.healthy = true;
+      route.consecutive_failures = 0;
+    } else {
+      route.consecutive_failures = route.consecutive_failures.saturating_add(1);
+      if route.healthy && route.consecutive_failures >= self.unhealthy_threshold {
+        route.healthy = false;
+        warn!(
+          kind = %kind,
+          node_id,
+          failures = route.consecutive_failures,
+          "node unhealthy, removed from rotation"
+        );
+      }
+    }
+  }
+
+  /// Pull registrations from the coordinator. On failure the current table
+  /// is kept so a coordinator outage does not empty it.
+  pub async fn refresh(&self, coordinator: &dyn CoordinatorClient) -> Result<()> {
+    let records = coordinator.list_nodes(None).await?;
+    for kind in NodeKind::ALL {
+      self.apply_registrations(kind, &records).await;
+    }
+    Ok(())
+  }
+
+  /// Probe `/healthz` on every node and feed the results into `report`
+  pub async fn probe(&self, timeout: Duration) {
+    for kind in NodeKind::ALL {
+      for endpoint in self.endpoints(kind).await {
+        let healthy = match endpoint.url("healthz") {
+          Ok(url) => endpoint
+            .client
+            .inner()
+            .get(url)
+            .timeout(timeout)
+            .send()
+            .await
+            .map(|resp| resp.status().is_success())
+            .unwrap_or(false),
+          Err(_) => false,
+        };
+        self.report(kind, &endpoint.node_id, healthy).await;
+      }
+    }
+  }
+
+  pub async fn status(&self) -> Vec<RouteStatus> {
+    let routes = self.routes.read().await;
+    let mut out = Vec::new();
+    for kind in NodeKind::ALL {
+      for route in routes.get(&kind).into_iter().flatten() {
+        out.push(RouteStatus {
+          kind,
+          node_id: route.endpoint.node_id.clone(),
+          base_url: route.endpoint.base_url.to_string(),
+          version: route.endpoint.version.clone(),
+          discovered: route.discovered,
+          healthy: route.healthy,
+          draining: route.draining,
+          consecutive_failures: route.consecutive_failures,
+          circuit: route.endpoint.client.circuit_state().await.to_string(),
+        });
+      }
+    }
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(node_id: &str, kind: NodeKind) -> NodeRecord {
+    NodeRecord {
+      node_id: node_id.to_string(),
+      kind,
+      base_url: format!("http://{node_id}:8080/"),
+      registered_at_epoch_secs: 0,
+      last_seen_epoch_secs: 0,
+      expires_at_epoch_secs: u64::MAX,
+      version: None,
+      draining: false,
+    }
+  }
+
+  async fn picked(table: &RoutingTable, kind: NodeKind, n: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for _ in 0..n {
+      out.push(table.pick(kind).await.unwrap().node_id.clone());
+    }
+    out
+  }
+
+  #[tokio::test]
+  async fn discovered_nodes_replace_static_fallback() {
+    let table = RoutingTable::new(3);
+    table
+      .add_static(NodeKind::Stream, Url::parse("http://127.0.0.1:8080/").unwrap())
+      .await
+      .unwrap();
+    assert_eq!(picked(&table, NodeKind::Stream, 2).await, ["static-stream", "static-stream"]);
+
+    table
+      .apply_registrations(
+        NodeKind::Stream,
+        &[
+          record("sn-1", NodeKind::Stream),
+          record("sn-2", NodeKind::Stream),
+          record("rec-1", NodeKind::Recorder),
+        ],
+      )
+      .await;
+    let mut nodes = picked(&table, NodeKind::Stream, 4).await;
+    nodes.sort();
+    assert_eq!(nodes, ["sn-1", "sn-1", "sn-2", "sn-2"]);
+
+    // Registrations expired: fall back to the static endpoint again
+    table.apply_registrations(NodeKind::Stream, &[]).await;
+    assert_eq!(picked(&table, NodeKind::Stream, 1).await, ["static-stream"]);
+  }
+
+  #[tokio::test]
+  async fn unhealthy_nodes_leave_rotation_until_they_recover() {
+    let table = RoutingTable::new(2);
+    table
+      .apply_registrations(
+        NodeKind::Recorder,
+        &[record("rec-1", NodeKind::Recorder), record("rec-2", NodeKind::Recorder)],
+      )
+      .await;
+
+    table.report(NodeKind::Recorder, "rec-1", false).await;
+    assert!(picked(&table, NodeKind::Recorder, 4).await.contains(&"rec-1".to_string()));
+
+    table.report(NodeKind::Recorder, "rec-1", false).await;
+    assert_eq!(picked(&table, NodeKind::Recorder, 3).await, ["rec-2", "rec-2", "rec-2"]);
+
+    table.report(NodeKind::Recorder, "rec-1", true).await;
+    assert!(picked(&table, NodeKind::Recorder, 4).await.contains(&"rec-1".to_string()));
+  }
+
+  #[tokio::test]
+  async fn all_unhealthy_fails_open() {
+    let table = RoutingTable::new(1);
+    table
+      .apply_registrations(NodeKind::Ai, &[record("ai-1", NodeKind::Ai)])
+      .await;
+    table.report(NodeKind::Ai, "ai-1", false).await;
+    assert_eq!(picked(&table, NodeKind::Ai, 1).await, ["ai-1"]);
+  }
+
+  #[tokio::test]
+  async fn draining_nodes_get_no_new_requests() {
+    let table = RoutingTable::new(3);
+    let mut draining = record("sn-2", NodeKind::Stream);
+    draining.draining = true;
+    table
+      .apply_registrations(NodeKind::Stream, &[record("sn-1", NodeKind::Stream), draining.clone()])
+      .await;
+    assert_eq!(picked(&table, NodeKind::Stream, 3).await, ["sn-1", "sn-1", "sn-1"]);
+    let order: Vec<_> = table.route_order(NodeKind::Stream).await.iter().map(|e| e.node_id.clone()).collect();
+    assert_eq!(order, ["sn-1"]);
+
+    // Existing resources on the draining node are still reachable
+    table.assign(NodeKind::Stream, "cam-1", "sn-2").await;
+    assert_eq!(table.resource_order(NodeKind::Stream, "cam-1").await[0].node_id, "sn-2");
+
+    // Every node draining: nothing to route new work to
+    draining.node_id = "sn-1".to_string();
+    let mut second = record("sn-2", NodeKind::Stream);
+    second.draining = true;
+    table.apply_registrations(NodeKind::Stream, &[draining, second]).await;
+    assert!(table.pick(NodeKind::Stream).await.is_none());
+  }
+
+  #[tokio::test]
+  async fn assignments_follow_the_table() {
+    let table = RoutingTable::new(3);
+    table
+      .apply_registrations(NodeKind::Stream, &[record("sn-1", NodeKind::Stream)])
+      .await;
+    table.assign(NodeKind::Stream, "cam-1", "sn-1").await;
+    assert_eq!(table.assigned(NodeKind::Stream, "cam-1").await.unwrap().node_id, "sn-1");
+
+    table.apply_registrations(NodeKind::Stream, &[]).await;
+    assert!(table.assigned(NodeKind::Stream, "cam-1").await.is_none());
+
+    table.unassign(NodeKind::Stream, "cam-1").await;
+    assert!(table.assignments.read().await.is_empty());
+  }
+
+  #[tokio::test]
+  async fn canary_rule_steers_share_of_new_requests() {
+    let table = RoutingTable::new(3).with_canary(CanaryPolicy {
+      rules: vec!["recorder:2.0.0=25".parse().unwrap()],
+      ..CanaryPolicy::default()
+    });
+    let mut canary = record("rec-2", NodeKind::Recorder);
+    canary.version = Some("2.0.0".to_string());
+    table
+      .apply_registrations(NodeKind::Recorder, &[record("rec-1", NodeKind::Recorder), canary])
+      .await;
+
+    let nodes = picked(&table, NodeKind::Recorder, 8).await;
+    assert_eq!(nodes.iter().filter(|n| *n == "rec-2").count(), 2);
+    let status = table.status().await;
+    assert_eq!(status[1].version.as_deref(), Some("2.0.0"));
+  }
+}
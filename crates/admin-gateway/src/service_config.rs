@@ -0,0 +1,172 @@
+//! Central service configuration.
+//!
+//! Operators manage configuration documents through the gateway's
+//! `/v1/config/*` routes, which forward to the coordinator with the caller's
+//! identity so versions record their author. The gateway also follows its
+//! own document (service name `admin-gateway`) and applies it live:
+//!
+//! ```json
+//! { "canary": [{ "kind": "stream", "version": "1.4.0", "weight": 10 }] }
+//! ```
+//!
+//! When `canary` is present it replaces the canary rules; kinds it does not
+//! list lose their rule.
+
+use crate::{canary::CanaryRule, error::ApiError, routing::RoutingTable, state::AppState};
+use anyhow::{Context, Result};
+use axum::{
+  body::{Body, Bytes},
+  extract::{Request, State},
+  http::{HeaderValue, Method, header::CONTENT_TYPE},
+  response::Response,
+};
+use common::{
+  nodes::NodeKind,
+  service_config::{ConfigWatcher, MAX_WAIT_SECS, ServiceConfig},
+};
+use serde::Deserialize;
+use std::{sync::Arc, sync::LazyLock, time::Duration};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Name the gateway's own configuration is published under
+pub const SERVICE_NAME: &str = "admin-gateway";
+
+const MAX_BODY_BYTES: usize = 512 * 1024;
+
+/// Long-polls are held open for up to `MAX_WAIT_SECS`
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(3))
+    .timeout(Duration::from_secs(MAX_WAIT_SECS + 10))
+    .build()
+    .unwrap_or_default()
+});
+
+/// Forward a `/v1/config/*` request to the coordinator
+pub async fn forward(State(state): State<AppState>, req: Request) -> Result<Response, ApiError> {
+  let (parts, body) = req.into_parts();
+  let mut url = state
+    .config()
+    .coordinator_base_url
+    .join(parts.uri.path().trim_start_matches('/'))
+    .map_err(|_| ApiError::bad_request("invalid path"))?;
+  url.set_query(parts.uri.query());
+
+  let mut request = CLIENT.request(parts.method.clone(), url);
+  if parts.method != Method::GET {
+    let body = axum::body::to_bytes(body, MAX_BODY_BYTES)
+      .await
+      .map_err(|_| ApiError::bad_request("request body too large"))?;
+    request = request
+      .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+      .body(body);
+  }
+  if let Some(identity) = common::gateway_identity::current() {
+    request = request.headers(identity);
+  }
+
+  let upstream = request.send().await.map_err(|e| {
+    warn!(error = %e, "config request to coordinator failed");
+    ApiError::new(axum::http::StatusCode::BAD_GATEWAY, "coordinator unavailable")
+  })?;
+  let status = upstream.status();
+  let content_type = upstream.headers().get(CONTENT_TYPE).cloned();
+  let body: Bytes = upstream
+    .bytes()
+    .await
+    .map_err(|_| ApiError::new(axum::http::StatusCode::BAD_GATEWAY, "coordinator response failed"))?;
+
+  let mut response = Response::new(Body::from(body));
+  *response.status_mut() = status;
+  if let Some(content_type) = content_type {
+    response.headers_mut().insert(CONTENT_TYPE, content_type);
+  }
+  Ok(response)
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct GatewayDocument {
+  canary: Option<Vec<CanaryRule>>,
+}
+
+/// Apply a version of the gateway's own configuration
+pub async fn apply(routing: &RoutingTable, config: &ServiceConfig) -> Result<()> {
+  let document: GatewayDocument =
+    serde_json::from_value(config.document.clone()).context("invalid admin-gateway configuration")?;
+
+  if let Some(rules) = document.canary {
+    let rules = rules
+      .into_iter()
+      .map(|rule| CanaryRule::new(rule.kind, rule.version, rule.weight))
+      .collect::<Result<Vec<_>>>()?;
+    let canary = routing.canary();
+    for kind in NodeKind::ALL {
+      match rules.iter().find(|rule| rule.kind == kind) {
+        Some(rule) => canary.set_rule(rule.clone()).await,
+        None => {
+          canary.remove_rule(kind).await;
+        }
+      }
+    }
+  }
+  info!(version = config.version, "applied admin-gateway configuration");
+  Ok(())
+}
+
+/// Apply every new version `watcher` sees until the process exits
+pub fn follow(watcher: ConfigWatcher, routing: Arc<RoutingTable>) -> JoinHandle<()> {
+  let (mut configs, _) = watcher.spawn();
+  tokio::spawn(async move {
+    while configs.changed().await.is_ok() {
+      let config = configs.borrow_and_update().clone();
+      if let Some(config) = config {
+        if let Err(e) = apply(&routing, &config).await {
+          warn!(version = config.version, error = %e, "rejected admin-gateway configuration");
+        }
+      }
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn config(document: serde_json::Value) -> ServiceConfig {
+    ServiceConfig {
+      service: SERVICE_NAME.into(),
+      version: 1,
+      document,
+      created_at: 0,
+      created_by: None,
+      comment: None,
+      rollback_of: None,
+    }
+  }
+
+  #[tokio::test]
+  async fn applies_canary_rules_and_removes_unlisted_kinds() {
+    let routing = RoutingTable::new(3);
+    let canary = routing.canary();
+    canary
+      .set_rule(CanaryRule::new(NodeKind::Recorder, "2.0.0", 50).unwrap())
+      .await;
+
+    let doc = json!({"canary": [{"kind": "stream", "version": "1.4.0", "weight": 10}]});
+    apply(&routing, &config(doc)).await.unwrap();
+    let status = canary.status().await;
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].kind, NodeKind::Stream);
+    assert_eq!(status[0].weight, 10);
+
+    // Documents without a canary section leave the rules alone
+    apply(&routing, &config(json!({"other": true}))).await.unwrap();
+    assert_eq!(canary.status().await.len(), 1);
+
+    let bad = json!({"canary": [{"kind": "stream", "version": "1.4.0", "weight": 101}]});
+    assert!(apply(&routing, &config(bad)).await.is_err());
+    assert_eq!(canary.status().await[0].weight, 10);
+  }
+}
@@ -1,4 +1,4 @@
-use crate::{config::GatewayConfig, coordinator::CoordinatorClient, worker::{RecorderClient, WorkerClient}};
+use crate::{config::GatewayConfig, coordinator::CoordinatorClient, routing::RoutingTable, worker::{RecorderClient, WorkerClient}};
 use common::{
   leases::LeaseRenewRequest,
   recordings::RecordingInfo,
@@ -20,6 +20,7 @@ struct AppStateInner {
   coordinator: Arc<dyn CoordinatorClient>,
   worker: Arc<dyn WorkerClient>,
   recorder: Arc<dyn RecorderClient>,
+  routing: Arc<RoutingTable>,
   state_store: Option<Arc<dyn StateStore>>,
   streams: RwLock<HashMap<String, StreamInfo>>,
   recordings: RwLock<HashMap<String, RecordingInfo>>,
@@ -32,12 +33,14 @@ impl AppState {
     coordinator: Arc<dyn CoordinatorClient>,
     worker: Arc<dyn WorkerClient>,
     recorder: Arc<dyn RecorderClient>,
+    routing: Arc<RoutingTable>,
   ) -> Self {
     let inner = AppStateInner {
       config,
       coordinator,
       worker,
       recorder,
+      routing,
       state_store: None,
       streams: RwLock::new(HashMap::new()),
       recordings: RwLock::new(HashMap::new()),
@@ -53,6 +56,7 @@ impl AppState {
     coordinator: Arc<dyn CoordinatorClient>,
     worker: Arc<dyn WorkerClient>,
     recorder: Arc<dyn RecorderClient>,
+    routing: Arc<RoutingTable>,
     state_store: Arc<dyn StateStore>,
   ) -> Self {
     let inner = AppStateInner {
@@ -60,6 +64,7 @@ impl AppState {
       coordinator,
       worker,
       recorder,
+      routing,
       state_store: Some(state_store),
       streams: RwLock::new(HashMap::new()),
       recordings: RwLock::new(HashMap::new()),
@@ -70,6 +75,10 @@ impl AppState {
     }
   }
 
+  pub fn config(&self) -> &GatewayConfig {
+    &self.inner.config
+  }
+
   pub fn node_id(&self) -> &str {
     &self.inner.config.node_id
   }
@@ -86,6 +95,10 @@ impl AppState {
     self.inner.recorder.clone()
   }
 
+  pub fn routing(&self) -> Arc<RoutingTable> {
+    self.inner.routing.clone()
+  }
+
   pub fn streams(&self) -> &RwLock<HashMap<String, StreamInfo>> {
     &self.inner.streams
   }
@@ -298,6 +311,28 @@ impl AppState {
                 };
 
                 match coordinator.renew(&req).await {
+                    Ok(resp) if !resp.renewed => {
+                        // Expired or taken over: another gateway may run the
+                        // stream now, so this one stops its worker
+                        tracing::warn!(stream_id = %stream_id, "stream lease lost, stopping stream");
+                        if let Err(e) = worker.stop_stream(&stream_id).await {
+                            tracing::warn!(stream_id = %stream_id, error = %e, "failed to stop stream after lease loss");
+                        }
+                        let info = {
+                            let mut streams = state.streams().write().await;
+                            if let Some(entry) = streams.get_mut(&stream_id) {
+                                entry.state = StreamState::Error;
+                                entry.last_error = Some("Lease lost to another node".to_string());
+                                Some(entry.clone())
+                            } else {
+                                None
+                            }
+                        };
+                        if let Some(info) = info {
+                            state.persist_stream(&info).await;
+                        }
+                        break;
+                    }
                     Ok(_) => {
                         consecutive_failures = 0;
                         let info = {
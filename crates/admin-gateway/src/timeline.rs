@@ -0,0 +1,205 @@
+//! Event timeline at the gateway.
+//!
+//! `GET /v1/timeline` reads the coordinator's cluster timeline, limited to
+//! the caller's tenant unless they are a system administrator.
+//! [`record_operator_actions`] puts every successful mutating API call on the
+//! timeline with the caller as actor, and the recording handlers add
+//! start/stop events for the camera being recorded.
+
+use crate::{error::ApiError, state::AppState};
+use axum::{
+  Extension,
+  body::Body,
+  extract::{MatchedPath, Query, Request, State},
+  http::{Method, StatusCode, header::CONTENT_TYPE},
+  middleware::Next,
+  response::Response,
+};
+use common::{
+  auth_middleware::AuthContext,
+  recordings::RecordingConfig,
+  timeline::{self, TimelineEvent, TimelineEventKind, TimelineQuery},
+};
+use serde_json::json;
+use std::{sync::LazyLock, time::Duration};
+use tracing::warn;
+
+/// `source` of events recorded by the gateway
+pub const SOURCE: &str = "admin-gateway";
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+  reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(3))
+    .timeout(Duration::from_secs(15))
+    .build()
+    .unwrap_or_default()
+});
+
+/// Restrict `query` to what `caller` may read
+fn scope(caller: Option<&AuthContext>, query: &mut TimelineQuery) -> Result<(), ApiError> {
+  let Some(ctx) = caller else {
+    return Ok(());
+  };
+  match &query.tenant_id {
+    Some(tenant_id) if *tenant_id != ctx.tenant_id && !ctx.is_system_admin => {
+      Err(ApiError::forbidden("cannot read another tenant's timeline"))
+    }
+    Some(_) => Ok(()),
+    None if ctx.is_system_admin => Ok(()),
+    None => {
+      query.tenant_id = Some(ctx.tenant_id.clone());
+      Ok(())
+    }
+  }
+}
+
+/// Timeline events from the coordinator, oldest first
+pub async fn query_timeline(
+  State(state): State<AppState>,
+  caller: Option<Extension<AuthContext>>,
+  Query(mut query): Query<TimelineQuery>,
+) -> Result<Response, ApiError> {
+  scope(caller.as_ref().map(|Extension(ctx)| ctx), &mut query)?;
+  let url = state
+    .config()
+    .coordinator_base_url
+    .join("v1/timeline")
+    .map_err(|_| ApiError::internal("invalid coordinator URL"))?;
+
+  let mut request = CLIENT.get(url).query(&query);
+  if let Some(identity) = common::gateway_identity::current() {
+    request = request.headers(identity);
+  }
+  let upstream = request.send().await.map_err(|e| {
+    warn!(error = %e, "timeline request to coordinator failed");
+    ApiError::new(StatusCode::BAD_GATEWAY, "coordinator unavailable")
+  })?;
+  let status = upstream.status();
+  let body = upstream
+    .bytes()
+    .await
+    .map_err(|_| ApiError::new(StatusCode::BAD_GATEWAY, "coordinator response failed"))?;
+
+  let mut response = Response::new(Body::from(body));
+  *response.status_mut() = status;
+  response
+    .headers_mut()
+    .insert(CONTENT_TYPE, axum::http::HeaderValue::from_static("application/json"));
+  Ok(response)
+}
+
+/// Operator action for a successful mutating request
+fn operator_action(method: &Method, route: &str, path: &str, status: StatusCode, caller: Option<&AuthContext>) -> TimelineEvent {
+  let mut event = TimelineEvent::new(TimelineEventKind::OperatorAction, SOURCE, format!("{method} {path}")).details(json!({
+    "method": method.as_str(),
+    "route": route,
+    "path": path,
+    "status": status.as_u16(),
+  }));
+  if let Some(ctx) = caller {
+    event = event.actor(ctx.username.clone()).tenant(ctx.tenant_id.clone());
+  }
+  event
+}
+
+/// Record successful non-GET requests as operator actions, except periodic
+/// federation reports. Runs inside the gateway auth layer so the caller is
+/// known.
+pub async fn record_operator_actions(req: Request, next: Next) -> Response {
+  if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+    return next.run(req).await;
+  }
+  let method = req.method().clone();
+  let path = req.uri().path().to_string();
+  let route = req
+    .extensions()
+    .get::<MatchedPath>()
+    .map(|p| p.as_str().to_string())
+    .unwrap_or_else(|| path.clone());
+  if method == Method::PUT && route == crate::federation::REPORT_ROUTE {
+    return next.run(req).await;
+  }
+  let caller = req.extensions().get::<AuthContext>().cloned();
+
+  let response = next.run(req).await;
+  if response.status().is_success() {
+    timeline::record(operator_action(&method, &route, &path, response.status(), caller.as_ref()));
+  }
+  response
+}
+
+/// Recording start/stop event, attributed to the source stream's camera
+pub(crate) async fn record_recording(state: &AppState, kind: TimelineEventKind, config: &RecordingConfig) {
+  let camera_id = match &config.source_stream_id {
+    Some(stream_id) => {
+      let streams = state.streams().read().await;
+      Some(
+        streams
+          .get(stream_id)
+          .and_then(|stream| stream.config.camera_id.clone())
+          .unwrap_or_else(|| stream_id.clone()),
+      )
+    }
+    None => None,
+  };
+  let verb = if kind == TimelineEventKind::RecordingStarted { "started" } else { "stopped" };
+  let mut event = TimelineEvent::new(kind, SOURCE, format!("Recording {} {}", config.id, verb)).details(json!({
+    "recording_id": config.id,
+    "source_stream_id": config.source_stream_id,
+  }));
+  if let Some(camera_id) = camera_id {
+    event = event.camera(camera_id);
+  }
+  timeline::record(event);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn caller(tenant_id: &str, is_system_admin: bool) -> AuthContext {
+    AuthContext {
+      user_id: "user-1".into(),
+      tenant_id: tenant_id.into(),
+      username: "alice".into(),
+      is_system_admin,
+      roles: vec![],
+      permissions: vec![],
+    }
+  }
+
+  #[test]
+  fn queries_are_scoped_to_the_callers_tenant() {
+    let mut query = TimelineQuery::default();
+    scope(Some(&caller("tenant-a", false)), &mut query).unwrap();
+    assert_eq!(query.tenant_id.as_deref(), Some("tenant-a"));
+
+    let mut other = TimelineQuery {
+      tenant_id: Some("tenant-b".into()),
+      ..Default::default()
+    };
+    assert!(scope(Some(&caller("tenant-a", false)), &mut other).is_err());
+    assert!(scope(Some(&caller("tenant-a", true)), &mut other).is_ok());
+
+    let mut all = TimelineQuery::default();
+    scope(Some(&caller("tenant-a", true)), &mut all).unwrap();
+    assert_eq!(all.tenant_id, None);
+  }
+
+  #[test]
+  fn operator_actions_name_the_caller() {
+    let event = operator_action(
+      &Method::DELETE,
+      "/v1/streams/:id",
+      "/v1/streams/lobby",
+      StatusCode::OK,
+      Some(&caller("tenant-a", false)),
+    );
+    assert_eq!(event.kind, TimelineEventKind::OperatorAction);
+    assert_eq!(event.summary, "DELETE /v1/streams/lobby");
+    assert_eq!(event.actor.as_deref(), Some("alice"));
+    assert_eq!(event.tenant_id.as_deref(), Some("tenant-a"));
+    assert_eq!(event.details["route"], "/v1/streams/:id");
+    assert!(event.validate().is_ok());
+  }
+}
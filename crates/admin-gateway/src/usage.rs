@@ -0,0 +1,197 @@
+//! Per-tenant API usage reports for billing.
+//!
+//! Counters are the ones `common::quota::quota_middleware` maintains for the
+//! metered API routes; see `routes::router`.
+
+use crate::error::ApiError;
+use axum::{
+  Extension, Json, Router,
+  extract::{Query, State},
+  routing::get,
+};
+use common::{
+  auth_middleware::AuthContext,
+  quota::{Quota, TenantUsage, current_period, validate_period},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+  /// `YYYY-MM`; defaults to the current month
+  pub period: Option<String>,
+  /// Tenant to report on; only system administrators may name another tenant
+  pub tenant_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UsageReport {
+  pub tenant_id: String,
+  pub period: String,
+  pub requests: u64,
+  pub rejected: u64,
+  /// Monthly allowance; `None` when unlimited
+  pub monthly_quota: Option<u64>,
+  pub remaining: Option<u64>,
+}
+
+impl UsageReport {
+  fn new(usage: TenantUsage, quota: &Quota) -> Self {
+    let monthly_quota = quota.config().limit_for(&usage.tenant_id);
+    Self {
+      remaining: monthly_quota.map(|limit| limit.saturating_sub(usage.requests)),
+      monthly_quota,
+      tenant_id: usage.tenant_id,
+      period: usage.period,
+      requests: usage.requests,
+      rejected: usage.rejected,
+    }
+  }
+}
+
+pub fn router<S>(quota: Quota) -> Router<S> {
+  Router::new()
+    .route("/v1/usage", get(tenant_usage))
+    .route("/v1/usage/tenants", get(all_tenant_usage))
+    .with_state(quota)
+}
+
+fn period(query: &UsageQuery) -> Result<String, ApiError> {
+  match &query.period {
+    Some(period) => {
+      validate_period(period).map_err(|e| ApiError::bad_request(e.to_string()))?;
+      Ok(period.clone())
+    }
+    None => Ok(current_period()),
+  }
+}
+
+/// Usage of the caller's tenant. Without gateway authentication there is no
+/// caller, so `tenant_id` must be given.
+async fn tenant_usage(
+  State(quota): State<Quota>,
+  caller: Option<Extension<AuthContext>>,
+  Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageReport>, ApiError> {
+  let period = period(&query)?;
+  let tenant_id = match (caller, query.tenant_id) {
+    (Some(Extension(ctx)), Some(tenant_id)) if tenant_id != ctx.tenant_id && !ctx.is_system_admin => {
+      return Err(ApiError::forbidden("cannot read another tenant's usage"));
+    }
+    (_, Some(tenant_id)) => tenant_id,
+    (Some(Extension(ctx)), None) => ctx.tenant_id,
+    (None, None) => return Err(ApiError::bad_request("tenant_id is required")),
+  };
+
+  let usage = quota
+    .store()
+    .list(&period, Some(&tenant_id))
+    .await?
+    .into_iter()
+    .next()
+    .unwrap_or(TenantUsage {
+      tenant_id,
+      period,
+      requests: 0,
+      rejected: 0,
+    });
+  Ok(Json(UsageReport::new(usage, &quota)))
+}
+
+/// Usage of every tenant that made metered requests in the period
+async fn all_tenant_usage(
+  State(quota): State<Quota>,
+  Query(query): Query<UsageQuery>,
+) -> Result<Json<Vec<UsageReport>>, ApiError> {
+  let period = period(&query)?;
+  let usage = quota.store().list(&period, query.tenant_id.as_deref()).await?;
+  Ok(Json(
+    usage
+      .into_iter()
+      .map(|usage| UsageReport::new(usage, &quota))
+      .collect(),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+  };
+  use common::quota::QuotaConfig;
+  use tower::ServiceExt;
+
+  fn caller(tenant_id: &str, is_system_admin: bool) -> AuthContext {
+    AuthContext {
+      user_id: "user-1".into(),
+      tenant_id: tenant_id.into(),
+      username: "alice".into(),
+      is_system_admin,
+      roles: vec![],
+      permissions: vec![],
+    }
+  }
+
+  async fn get_json(
+    app: &Router,
+    uri: &str,
+    caller: Option<AuthContext>,
+  ) -> (StatusCode, serde_json::Value) {
+    let mut req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    if let Some(ctx) = caller {
+      req.extensions_mut().insert(ctx);
+    }
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+  }
+
+  #[tokio::test]
+  async fn reports_caller_tenant_usage_against_quota() {
+    let quota = Quota::in_memory(QuotaConfig::new(10).with_tenant_quota("t2", 0));
+    for tenant in ["t1", "t1", "t2"] {
+      quota.record(tenant).await;
+    }
+    let app = router(quota);
+
+    let (status, body) = get_json(&app, "/v1/usage", Some(caller("t1", false))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tenant_id"], "t1");
+    assert_eq!(body["requests"], 2);
+    assert_eq!(body["monthly_quota"], 10);
+    assert_eq!(body["remaining"], 8);
+
+    let (status, _) = get_json(&app, "/v1/usage?tenant_id=t2", Some(caller("t1", false))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = get_json(&app, "/v1/usage?tenant_id=t2", Some(caller("t1", true))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["monthly_quota"].is_null());
+
+    let (status, body) = get_json(&app, "/v1/usage?period=2001-01", Some(caller("t1", false))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["requests"], 0);
+    let (status, _) = get_json(&app, "/v1/usage?period=2001-1", Some(caller("t1", false))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn lists_all_tenants_for_billing() {
+    let quota = Quota::in_memory(QuotaConfig::default());
+    for tenant in ["t2", "t1"] {
+      quota.record(tenant).await;
+    }
+    let app = router(quota);
+
+    let (status, body) = get_json(&app, "/v1/usage/tenants", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let tenants: Vec<&str> = body
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|r| r["tenant_id"].as_str().unwrap())
+      .collect();
+    assert_eq!(tenants, ["t1", "t2"]);
+  }
+}
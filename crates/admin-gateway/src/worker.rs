@@ -1,12 +1,15 @@
-use anyhow::{Context, Result};
+use crate::routing::{NodeEndpoint, RoutingTable};
+use anyhow::{Context, Result, anyhow};
 use async_trait::async_trait;
 use common::{
+  nodes::NodeKind,
   recordings::{RecordingStartRequest, RecordingStartResponse, RecordingStopRequest, RecordingStopResponse},
+  resilient_http::ResilientError,
   streams::StreamConfig,
 };
-use reqwest::Url;
-use std::time::Duration;
-use tracing::instrument;
+use reqwest::Response;
+use std::sync::Arc;
+use tracing::{instrument, warn};
 
 #[async_trait]
 pub trait WorkerClient: Send + Sync {
@@ -22,22 +25,46 @@ pub trait RecorderClient: Send + Sync {
   async fn health_check(&self) -> Result<bool>;
 }
 
-pub struct HttpWorkerClient {
-  base: Url,
-  client: reqwest::Client,
+/// Record the outcome of a call against the routing table. Only transport
+/// errors and 5xx count against the node; 4xx are the caller's problem.
+async fn report(
+  routing: &RoutingTable,
+  kind: NodeKind,
+  endpoint: &NodeEndpoint,
+  result: &Result<Response, ResilientError>,
+) {
+  let healthy = matches!(result, Ok(resp) if !resp.status().is_server_error());
+  routing.report(kind, &endpoint.node_id, healthy).await;
+  routing
+    .canary()
+    .observe(kind, endpoint.version.as_deref(), healthy)
+    .await;
 }
 
-impl HttpWorkerClient {
-  pub fn new(base: Url) -> Result<Self> {
-    let client = reqwest::Client::builder()
-      .connect_timeout(Duration::from_secs(3))
-      .timeout(Duration::from_secs(10))
-      .build()?;
-    Ok(Self { base, client })
+/// True if any node of `kind` answers its health check
+async fn any_healthy(routing: &RoutingTable, kind: NodeKind) -> Result<bool> {
+  for endpoint in routing.endpoints(kind).await {
+    let url = endpoint.url("healthz")?;
+    let healthy = match endpoint.client.send(|c| c.get(url.clone())).await {
+      Ok(resp) => resp.status().is_success(),
+      Err(_) => false,
+    };
+    routing.report(kind, &endpoint.node_id, healthy).await;
+    if healthy {
+      return Ok(true);
+    }
   }
+  Ok(false)
+}
 
-  fn endpoint(&self, path: &str) -> Result<Url> {
-    self.base.join(path).context("invalid worker endpoint")
+/// Routes stream requests to stream nodes from the [`RoutingTable`]
+pub struct HttpWorkerClient {
+  routing: Arc<RoutingTable>,
+}
+
+impl HttpWorkerClient {
+  pub fn new(routing: Arc<RoutingTable>) -> Self {
+    Self { routing }
   }
 }
 
@@ -45,76 +72,87 @@ impl HttpWorkerClient {
 impl WorkerClient for HttpWorkerClient {
   #[instrument(skip_all, fields(stream = %config.id))]
   async fn start_stream(&self, config: &StreamConfig) -> Result<()> {
-    let mut url = self.endpoint("start")?;
-    {
-      let mut pairs = url.query_pairs_mut();
-      pairs.append_pair("id", &config.id);
-      pairs.append_pair("uri", &config.uri);
-      if let Some(codec) = &config.codec {
-        pairs.append_pair("codec", codec);
+    let mut last_error = anyhow!("no stream nodes available");
+    for endpoint in self.routing.route_order(NodeKind::Stream).await {
+      let mut url = endpoint.url("start")?;
+      {
+        let mut pairs = url.query_pairs_mut();
+        pairs.append_pair("id", &config.id);
+        pairs.append_pair("uri", &config.uri);
+        if let Some(codec) = &config.codec {
+          pairs.append_pair("codec", codec);
+        }
+        if let Some(container) = &config.container {
+          pairs.append_pair("container", container);
+        }
+        if let Some(substream_uri) = &config.substream_uri {
+          pairs.append_pair("substream_uri", substream_uri);
+        }
+        if let Some(palette) = config.thermal_palette {
+          pairs.append_pair("thermal_palette", palette.as_str());
+        }
       }
-      if let Some(container) = &config.container {
-        pairs.append_pair("container", container);
+
+      let result = endpoint.client.send(|c| c.get(url.clone())).await;
+      report(&self.routing, NodeKind::Stream, &endpoint, &result).await;
+      match result {
+        Ok(resp) => {
+          resp
+            .error_for_status()
+            .context("worker start returned error status")?;
+          self
+            .routing
+            .assign(NodeKind::Stream, &config.id, &endpoint.node_id)
+            .await;
+          return Ok(());
+        }
+        // Only fail over when the node never saw the request, otherwise the
+        // stream could end up running twice
+        Err(e) if e.is_unsent() => {
+          warn!(node_id = %endpoint.node_id, error = %e, "stream node unreachable, trying next node");
+          last_error = anyhow::Error::new(e);
+        }
+        Err(e) => return Err(e).context("worker start request failed"),
       }
     }
-
-    let resp = self
-      .client
-      .get(url)
-      .send()
-      .await
-      .context("worker start request failed")?;
-    resp
-      .error_for_status()
-      .context("worker start returned error status")?;
-    Ok(())
+    Err(last_error).context("worker start request failed")
   }
 
   #[instrument(skip_all, fields(stream = stream_id))]
   async fn stop_stream(&self, stream_id: &str) -> Result<()> {
-    let mut url = self.endpoint("stop")?;
-    {
-      let mut pairs = url.query_pairs_mut();
-      pairs.append_pair("id", stream_id);
+    let mut last_error = anyhow!("no stream nodes available");
+    for endpoint in self.routing.resource_order(NodeKind::Stream, stream_id).await {
+      let mut url = endpoint.url("stop")?;
+      url.query_pairs_mut().append_pair("id", stream_id);
+
+      let result = endpoint.client.send(|c| c.get(url.clone())).await;
+      report(&self.routing, NodeKind::Stream, &endpoint, &result).await;
+      match result.map(Response::error_for_status) {
+        Ok(Ok(_)) => {
+          self.routing.unassign(NodeKind::Stream, stream_id).await;
+          return Ok(());
+        }
+        Ok(Err(e)) => last_error = anyhow::Error::new(e).context("worker stop returned error status"),
+        Err(e) => last_error = anyhow::Error::new(e).context("worker stop request failed"),
+      }
     }
-    let resp = self
-      .client
-      .get(url)
-      .send()
-      .await
-      .context("worker stop request failed")?;
-    resp
-      .error_for_status()
-      .context("worker stop returned error status")?;
-    Ok(())
+    Err(last_error)
   }
 
   #[instrument(skip_all)]
   async fn health_check(&self) -> Result<bool> {
-    let url = self.endpoint("healthz")?;
-    match self.client.get(url).send().await {
-      Ok(resp) => Ok(resp.status().is_success()),
-      Err(_) => Ok(false),
-    }
+    any_healthy(&self.routing, NodeKind::Stream).await
   }
 }
 
+/// Routes recording requests to recorder nodes from the [`RoutingTable`]
 pub struct HttpRecorderClient {
-  base: Url,
-  client: reqwest::Client,
+  routing: Arc<RoutingTable>,
 }
 
 impl HttpRecorderClient {
-  pub fn new(base: Url) -> Result<Self> {
-    let client = reqwest::Client::builder()
-      .connect_timeout(Duration::from_secs(3))
-      .timeout(Duration::from_secs(10))
-      .build()?;
-    Ok(Self { base, client })
-  }
-
-  fn endpoint(&self, path: &str) -> Result<Url> {
-    self.base.join(path).context("invalid recorder endpoint")
+  pub fn new(routing: Arc<RoutingTable>) -> Self {
+    Self { routing }
   }
 }
 
@@ -122,52 +160,72 @@ impl HttpRecorderClient {
 impl RecorderClient for HttpRecorderClient {
   #[instrument(skip_all, fields(recording_id = %request.config.id))]
   async fn start_recording(&self, request: &RecordingStartRequest) -> Result<RecordingStartResponse> {
-    let url = self.endpoint("start")?;
-    let resp = self
-      .client
-      .post(url)
-      .json(request)
-      .send()
-      .await
-      .context("recorder start request failed")?;
-
-    let response = resp
-      .error_for_status()
-      .context("recorder start returned error status")?
-      .json::<RecordingStartResponse>()
-      .await
-      .context("failed to parse recorder start response")?;
-
-    Ok(response)
+    let mut last_error = anyhow!("no recorder nodes available");
+    for endpoint in self.routing.route_order(NodeKind::Recorder).await {
+      let url = endpoint.url("start")?;
+      let result = endpoint
+        .client
+        .send(|c| c.post(url.clone()).json(request))
+        .await;
+      report(&self.routing, NodeKind::Recorder, &endpoint, &result).await;
+      match result {
+        Ok(resp) => {
+          let response = resp
+            .error_for_status()
+            .context("recorder start returned error status")?
+            .json::<RecordingStartResponse>()
+            .await
+            .context("failed to parse recorder start response")?;
+          self
+            .routing
+            .assign(NodeKind::Recorder, &request.config.id, &endpoint.node_id)
+            .await;
+          return Ok(response);
+        }
+        Err(e) if e.is_unsent() => {
+          warn!(node_id = %endpoint.node_id, error = %e, "recorder node unreachable, trying next node");
+          last_error = anyhow::Error::new(e);
+        }
+        Err(e) => return Err(e).context("recorder start request failed"),
+      }
+    }
+    Err(last_error).context("recorder start request failed")
   }
 
   #[instrument(skip_all, fields(recording_id = %request.id))]
   async fn stop_recording(&self, request: &RecordingStopRequest) -> Result<RecordingStopResponse> {
-    let url = self.endpoint("stop")?;
-    let resp = self
-      .client
-      .post(url)
-      .json(request)
-      .send()
-      .await
-      .context("recorder stop request failed")?;
-
-    let response = resp
-      .error_for_status()
-      .context("recorder stop returned error status")?
-      .json::<RecordingStopResponse>()
-      .await
-      .context("failed to parse recorder stop response")?;
-
-    Ok(response)
+    let mut last_error = anyhow!("no recorder nodes available");
+    for endpoint in self.routing.resource_order(NodeKind::Recorder, &request.id).await {
+      let url = endpoint.url("stop")?;
+      let result = endpoint
+        .client
+        .send(|c| c.post(url.clone()).json(request))
+        .await;
+      report(&self.routing, NodeKind::Recorder, &endpoint, &result).await;
+      let resp = match result.map(Response::error_for_status) {
+        Ok(Ok(resp)) => resp,
+        Ok(Err(e)) => {
+          last_error = anyhow::Error::new(e).context("recorder stop returned error status");
+          continue;
+        }
+        Err(e) => {
+          last_error = anyhow::Error::new(e).context("recorder stop request failed");
+          continue;
+        }
+      };
+
+      let response = resp
+        .json::<RecordingStopResponse>()
+        .await
+        .context("failed to parse recorder stop response")?;
+      self.routing.unassign(NodeKind::Recorder, &request.id).await;
+      return Ok(response);
+    }
+    Err(last_error)
   }
 
   #[instrument(skip_all)]
   async fn health_check(&self) -> Result<bool> {
-    let url = self.endpoint("healthz")?;
-    match self.client.get(url).send().await {
-      Ok(resp) => Ok(resp.status().is_success()),
-      Err(_) => Ok(false),
-    }
+    any_healthy(&self.routing, NodeKind::Recorder).await
   }
 }
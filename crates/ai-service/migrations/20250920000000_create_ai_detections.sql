@@ -0,0 +1,28 @@
+-- Detections of AI tasks, one row per detected object, queried through
+-- ai-service GET /v1/detections
+CREATE TABLE IF NOT EXISTS ai_detections (
+    id BIGSERIAL PRIMARY KEY,
+    task_id VARCHAR(255) NOT NULL,
+    plugin_type VARCHAR(100) NOT NULL,
+    -- Source stream of the task (or the frame's source for recording tasks)
+    camera_id VARCHAR(255),
+    -- output.config.tenant_id of the task, else AI_ALERT_TENANT_ID
+    tenant_id VARCHAR(255),
+    class VARCHAR(100) NOT NULL,
+    confidence REAL NOT NULL,
+    bbox_x INTEGER NOT NULL,
+    bbox_y INTEGER NOT NULL,
+    bbox_width INTEGER NOT NULL,
+    bbox_height INTEGER NOT NULL,
+    -- Plugin metadata of the detection (track_id, plate, watchlist hit, ...)
+    metadata JSONB,
+    -- Frame time, Unix milliseconds
+    detected_at BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_ai_detections_detected_at ON ai_detections(detected_at DESC);
+CREATE INDEX idx_ai_detections_task ON ai_detections(task_id, detected_at DESC);
+CREATE INDEX idx_ai_detections_class ON ai_detections(class, detected_at DESC);
+CREATE INDEX idx_ai_detections_camera ON ai_detections(camera_id, detected_at DESC);
+CREATE INDEX idx_ai_detections_tenant ON ai_detections(tenant_id, detected_at DESC);
@@ -0,0 +1,400 @@
+//! Violation and zone alerts
+//!
+//! Detections a plugin marks as violations (`metadata.violation == true`,
+//! e.g. `ppe_violation` from the PPE plugin), plates found on an LPR
+//! watchlist (`metadata.watchlist_hit == true`) and the zone events of tasks
+//! with zones (`zone_event` entered/exited/crossed) are raised as
+//! `ai_detection` triggers at alert-service, where alert rules match on the
+//! trigger context (`class`, `zone_id`, `missing_ppe`, `watchlist`,
+//! `zone_event`, ...) and escalate them. Tasks with `alarm_classes` raise
+//! only those classes.
+
+use anyhow::{Context, Result};
+use common::ai_tasks::{AiTaskInfo, Detection, ZoneEvent, ZoneEventKind};
+use common::auth_middleware::AuthContext;
+use common::gateway_identity;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+const DEFAULT_COOLDOWN_SECS: u64 = 60;
+const MAX_COOLDOWN_KEYS: usize = 4096;
+const IDENTITY_TTL: Duration = Duration::from_secs(60);
+
+/// Raises `ai_detection` alerts for violation detections, on behalf of the
+/// task's tenant
+pub struct ViolationAlerter {
+    client: reqwest::Client,
+    trigger_url: String,
+    jwt_secret: String,
+    default_tenant_id: Option<String>,
+    cooldown: Duration,
+    last_raised: Mutex<HashMap<String, Instant>>,
+}
+
+impl ViolationAlerter {
+    /// Alerting is enabled when `ALERT_SERVICE_URL` and `JWT_SECRET` are
+    /// both set; triggers carry an identity token signed with the secret
+    pub fn from_env() -> Option<Self> {
+        let base = std::env::var("ALERT_SERVICE_URL").ok()?;
+        let jwt_secret = std::env::var("JWT_SECRET").ok()?;
+        let cooldown = std::env::var("AI_ALERT_COOLDOWN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_COOLDOWN_SECS);
+        Some(Self {
+            client: reqwest::Client::new(),
+            trigger_url: format!("{}/v1/trigger", base.trim_end_matches('/')),
+            jwt_secret,
+            default_tenant_id: std::env::var("AI_ALERT_TENANT_ID").ok(),
+            cooldown: Duration::from_secs(cooldown),
+            last_raised: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Raise the violations and watchlist hits among `detections` in the
+    /// background; the same violation (task, class, zone, missing items) or
+    /// plate on a watchlist is raised at most once per cooldown
+    pub async fn raise_violations(self: &std::sync::Arc<Self>, task: &AiTaskInfo, detections: &[Detection]) {
+        let violations = detections
+            .iter()
+            .filter(|d| is_violation(d) && is_alarm_class(task, &d.class))
+            .map(|detection| {
+                let context = trigger_context(task, detection);
+                (cooldown_key(&task.config.id, detection), violation_message(&context), context)
+            })
+            .collect();
+        self.raise_due(task, violations).await;
+    }
+
+    /// Raise zone entries, exits and tripwire crossings in the background;
+    /// the same event of an object at a zone is raised at most once per
+    /// cooldown, so an object lingering on a zone's edge does not flood
+    /// alert-service
+    pub async fn raise_zone_events(self: &std::sync::Arc<Self>, task: &AiTaskInfo, events: &[ZoneEvent]) {
+        let events = events
+            .iter()
+            .filter(|event| is_alarm_class(task, &event.class))
+            .map(|event| {
+                let context = zone_context(task, event);
+                (zone_cooldown_key(&task.config.id, event), zone_message(event), context)
+            })
+            .collect();
+        self.raise_due(task, events).await;
+    }
+
+    /// Raise the triggers (cooldown key, message, context) not raised within
+    /// the cooldown
+    async fn raise_due(self: &std::sync::Arc<Self>, task: &AiTaskInfo, triggers: Vec<(String, String, Map<String, Value>)>) {
+        if triggers.is_empty() {
+            return;
+        }
+        let Some(tenant_id) = tenant_for(task).or_else(|| self.default_tenant_id.clone()) else {
+            debug!(task_id = %task.config.id, "no tenant for task, alerts skipped");
+            return;
+        };
+
+        let mut due = Vec::new();
+        {
+            let mut last_raised = self.last_raised.lock().await;
+            let now = Instant::now();
+            if last_raised.len() >= MAX_COOLDOWN_KEYS {
+                last_raised.retain(|_, at| now.duration_since(*at) < self.cooldown);
+            }
+            for (key, message, context) in triggers {
+                if last_raised
+                    .get(&key)
+                    .is_some_and(|at| now.duration_since(*at) < self.cooldown)
+                {
+                    continue;
+                }
+                last_raised.insert(key, now);
+                due.push((message, context));
+            }
+        }
+
+        for (message, context) in due {
+            let alerter = std::sync::Arc::clone(self);
+            let tenant_id = tenant_id.clone();
+            let task_id = task.config.id.clone();
+            tokio::spawn(async move {
+                if let Err(e) = alerter.raise(&tenant_id, message, context).await {
+                    warn!(task_id = %task_id, error = %e, "failed to raise AI alert");
+                }
+            });
+        }
+    }
+
+    async fn raise(&self, tenant_id: &str, message: String, context: Map<String, Value>) -> Result<()> {
+        let identity = AuthContext {
+            user_id: "ai-service".into(),
+            tenant_id: tenant_id.to_string(),
+            username: "ai-service".into(),
+            is_system_admin: false,
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        };
+        let headers = gateway_identity::headers(&identity, &self.jwt_secret, IDENTITY_TTL)?;
+        self.client
+            .post(&self.trigger_url)
+            .headers(headers)
+            .json(&json!({
+                "trigger_type": "ai_detection",
+                "message": message,
+                "context": context,
+            }))
+            .send()
+            .await
+            .context("alert service unreachable")?
+            .error_for_status()
+            .context("alert service rejected trigger")?;
+        Ok(())
+    }
+}
+
+fn is_alarm_class(task: &AiTaskInfo, class: &str) -> bool {
+    let classes = &task.config.alarm_classes;
+    classes.is_empty() || classes.iter().any(|c| c == class)
+}
+
+fn is_violation(detection: &Detection) -> bool {
+    flag(detection, "violation") || flag(detection, "watchlist_hit")
+}
+
+fn flag(detection: &Detection, name: &str) -> bool {
+    detection
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get(name))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Tenant the task's alerts are raised for (`output.config.tenant_id`)
+pub(crate) fn tenant_for(task: &AiTaskInfo) -> Option<String> {
+    task.config
+        .output
+        .config
+        .get("tenant_id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+fn violation_message(context: &Map<String, Value>) -> String {
+    let class = context["class"].as_str().unwrap_or("violation");
+    if let Some(watchlist) = context.get("watchlist").and_then(Value::as_str) {
+        let plate = context.get("plate_number").and_then(Value::as_str).unwrap_or("plate");
+        return format!("{} {} on watchlist {}", class, plate, watchlist);
+    }
+    match context.get("zone_name").and_then(Value::as_str) {
+        Some(zone) => format!("{} in {}", class, zone),
+        None => class.to_string(),
+    }
+}
+
+fn cooldown_key(task_id: &str, detection: &Detection) -> String {
+    let metadata = detection.metadata.as_ref();
+    let field = |name: &str| metadata.and_then(|m| m.get(name)).map(Value::to_string).unwrap_or_default();
+    if flag(detection, "watchlist_hit") {
+        return format!("{}|{}|{}|{}", task_id, detection.class, field("watchlist_id"), field("watchlist_plate"));
+    }
+    format!("{}|{}|{}|{}", task_id, detection.class, field("zone_id"), field("missing"))
+}
+
+/// Trigger context: the detection's metadata fields, plus the task, camera,
+/// class, confidence and box, so rule conditions can match any of them
+fn trigger_context(task: &AiTaskInfo, detection: &Detection) -> Map<String, Value> {
+    let mut context = detection
+        .metadata
+        .as_ref()
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    context.remove("violation");
+    context.insert("task_id".into(), json!(task.config.id));
+    context.insert("plugin_type".into(), json!(task.config.plugin_type));
+    if let Some(camera) = &task.config.source_stream_id {
+        context.insert("camera_id".into(), json!(camera));
+    }
+    context.insert("class".into(), json!(detection.class));
+    context.insert("confidence".into(), json!(detection.confidence));
+    context.insert("bbox".into(), json!(detection.bbox));
+    context
+}
+
+fn zone_cooldown_key(task_id: &str, event: &ZoneEvent) -> String {
+    let object = match event.track_id {
+        Some(track_id) => track_id.to_string(),
+        None => event.class.clone(),
+    };
+    format!("{}|{}|{}|{}", task_id, event.zone_id, event.event.as_str(), object)
+}
+
+fn zone_message(event: &ZoneEvent) -> String {
+    let zone = event.zone_name.as_deref().unwrap_or(&event.zone_id);
+    match event.event {
+        ZoneEventKind::Entered => format!("{} entered {}", event.class, zone),
+        ZoneEventKind::Exited => format!("{} left {}", event.class, zone),
+        ZoneEventKind::Crossed => format!("{} crossed {}", event.class, zone),
+    }
+}
+
+/// Trigger context of a zone event: the event's fields plus the task and
+/// camera
+fn zone_context(task: &AiTaskInfo, event: &ZoneEvent) -> Map<String, Value> {
+    let mut context = Map::new();
+    context.insert("task_id".into(), json!(task.config.id));
+    context.insert("plugin_type".into(), json!(task.config.plugin_type));
+    if let Some(camera) = &task.config.source_stream_id {
+        context.insert("camera_id".into(), json!(camera));
+    }
+    context.insert("zone_event".into(), json!(event.event));
+    context.insert("zone_id".into(), json!(event.zone_id));
+    if let Some(name) = &event.zone_name {
+        context.insert("zone_name".into(), json!(name));
+    }
+    context.insert("class".into(), json!(event.class));
+    if let Some(track_id) = event.track_id {
+        context.insert("track_id".into(), json!(track_id));
+    }
+    if let Some(direction) = event.direction {
+        context.insert("direction".into(), json!(direction));
+    }
+    context.insert("bbox".into(), json!(event.bbox));
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::{AiFrameConfig, AiOutputConfig, AiTaskConfig, AiTaskState, BoundingBox};
+
+    fn task(output_config: Value) -> AiTaskInfo {
+        AiTaskInfo {
+            config: AiTaskConfig {
+                id: "task-1".into(),
+                plugin_type: "ppe_detection".into(),
+                source_stream_id: Some("cam-1".into()),
+                source_recording_id: None,
+                model_config: Value::Null,
+                secondary_plugins: Vec::new(),
+                pipeline: None,
+                classes: None,
+                tracking: None,
+                zones: None,
+                dedup: None,
+                alarm_classes: Vec::new(),
+                schedule: None,
+                frame_config: AiFrameConfig::default(),
+                output: AiOutputConfig {
+                    output_type: "webhook".into(),
+                    config: output_config,
+                },
+            },
+            state: AiTaskState::Processing,
+            node_id: None,
+            lease_id: None,
+            last_error: None,
+            last_error_kind: None,
+            started_at: None,
+            stopped_at: None,
+            last_processed_frame: None,
+            frames_processed: 0,
+            detections_made: 0,
+        }
+    }
+
+    #[test]
+    fn context_flattens_violation_metadata() {
+        let detection = Detection {
+            class: "ppe_violation".into(),
+            confidence: 0.8,
+            bbox: BoundingBox { x: 1, y: 2, width: 3, height: 4 },
+            metadata: Some(json!({
+                "violation": true,
+                "zone_id": "zone-b",
+                "missing": ["hard_hat"],
+                "missing_ppe": "hard_hat"
+            })),
+        };
+        assert!(is_violation(&detection));
+
+        let task = task(json!({"tenant_id": "t-1"}));
+        assert_eq!(tenant_for(&task).as_deref(), Some("t-1"));
+        let context = trigger_context(&task, &detection);
+        assert_eq!(context["class"], "ppe_violation");
+        assert_eq!(context["zone_id"], "zone-b");
+        assert_eq!(context["missing_ppe"], "hard_hat");
+        assert_eq!(context["camera_id"], "cam-1");
+        assert!(!context.contains_key("violation"));
+        assert_eq!(
+            cooldown_key("task-1", &detection),
+            r#"task-1|ppe_violation|"zone-b"|["hard_hat"]"#
+        );
+        assert_eq!(violation_message(&context), "ppe_violation");
+    }
+
+    #[test]
+    fn alarm_classes_limit_raised_classes() {
+        let mut task = task(json!({}));
+        assert!(is_alarm_class(&task, "car"));
+        task.config.alarm_classes = vec!["person".into()];
+        assert!(is_alarm_class(&task, "person"));
+        assert!(!is_alarm_class(&task, "car"));
+    }
+
+    #[test]
+    fn watchlist_hits_are_raised_per_plate() {
+        let detection = Detection {
+            class: "license_plate".into(),
+            confidence: 0.9,
+            bbox: BoundingBox { x: 1, y: 2, width: 3, height: 4 },
+            metadata: Some(json!({
+                "plate_number": "A8123C",
+                "watchlist_hit": true,
+                "watchlist": "Stolen vehicles",
+                "watchlist_id": "stolen",
+                "watchlist_plate": "AB123C"
+            })),
+        };
+        assert!(is_violation(&detection));
+        let context = trigger_context(&task(json!({"tenant_id": "t-1"})), &detection);
+        assert_eq!(context["watchlist_hit"], true);
+        assert_eq!(violation_message(&context), "license_plate A8123C on watchlist Stolen vehicles");
+        assert_eq!(
+            cooldown_key("task-1", &detection),
+            r#"task-1|license_plate|"stolen"|"AB123C""#
+        );
+
+        let miss = Detection {
+            metadata: Some(json!({"plate_number": "XY987", "watchlist_hit": false})),
+            ..detection
+        };
+        assert!(!is_violation(&miss));
+    }
+
+    #[test]
+    fn zone_events_carry_zone_and_object() {
+        let event = ZoneEvent {
+            event: ZoneEventKind::Crossed,
+            zone_id: "gate".into(),
+            zone_name: Some("North gate".into()),
+            class: "person".into(),
+            track_id: Some(12),
+            direction: Some(common::ai_tasks::CrossingDirection::LeftToRight),
+            bbox: BoundingBox { x: 1, y: 2, width: 3, height: 4 },
+            timestamp: 1000,
+        };
+
+        let context = zone_context(&task(json!({"tenant_id": "t-1"})), &event);
+        assert_eq!(context["zone_event"], "crossed");
+        assert_eq!(context["zone_id"], "gate");
+        assert_eq!(context["direction"], "left_to_right");
+        assert_eq!(context["track_id"], 12);
+        assert_eq!(context["camera_id"], "cam-1");
+        assert_eq!(zone_message(&event), "person crossed North gate");
+        assert_eq!(zone_cooldown_key("task-1", &event), "task-1|gate|crossed|12");
+    }
+}
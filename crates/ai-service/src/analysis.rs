@@ -0,0 +1,666 @@
+//! Batch analysis of stored recordings.
+//!
+//! A job runs selected plugins over a recording fetched through
+//! playback-service's clip export, for forensic re-analysis of historical
+//! footage, e.g. after enrolling a new face or adding a plate to a watch
+//! list. Frames are sampled at a fixed rate and every detection lands in a
+//! timestamped index (one JSON object per line) that can be queried while the
+//! job runs and after it finished.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use common::ai_tasks::{BoundingBox, PluginErrorKind, VideoFrame};
+use common::playback::{ClipExportQuery, MAX_CLIP_SECS};
+use common::state_store::StateStore;
+use common::timeline::{self, TimelineEvent, TimelineEventKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{error, info, warn};
+
+use crate::anonymize::{download_clip, ffmpeg};
+use crate::plugin::registry::PluginRegistry;
+use crate::plugin::PluginError;
+
+const MAX_FPS: u32 = 30;
+
+const MAX_PLUGINS: usize = 8;
+
+const MAX_CLASSES: usize = 32;
+
+/// Most index entries one detections query returns
+pub const MAX_DETECTIONS_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisRequest {
+    pub recording_id: String,
+    /// Plugins to run on every sampled frame
+    pub plugins: Vec<String>,
+    /// Range of the recording, in seconds from its start; without `end_secs`
+    /// the whole remainder of the recording is analyzed
+    #[serde(default)]
+    pub start_secs: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_secs: Option<f64>,
+    /// Frames sampled per second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<u32>,
+    /// Detections below this confidence are left out of the index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f32>,
+    /// Only index these classes; all when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+    /// Reference of the investigation, e.g. a case number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+impl AnalysisRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        common::validation::validate_id(&self.recording_id, "recording_id")
+            .map_err(|e| e.to_string())?;
+        if self.plugins.is_empty() {
+            return Err("at least one plugin is required".to_string());
+        }
+        if self.plugins.len() > MAX_PLUGINS {
+            return Err(format!("at most {} plugins per job", MAX_PLUGINS));
+        }
+        if !self.start_secs.is_finite() || self.start_secs < 0.0 {
+            return Err("start_secs must be a non-negative number".to_string());
+        }
+        if let Some(end_secs) = self.end_secs {
+            if !end_secs.is_finite() || end_secs <= self.start_secs {
+                return Err("end_secs must be after start_secs".to_string());
+            }
+        }
+        if let Some(fps) = self.fps {
+            if !(1..=MAX_FPS).contains(&fps) {
+                return Err(format!("fps must be between 1 and {}", MAX_FPS));
+            }
+        }
+        if self
+            .min_confidence
+            .is_some_and(|c| !(0.0..=1.0).contains(&c))
+        {
+            return Err("min_confidence must be between 0 and 1".to_string());
+        }
+        if self.classes.len() > MAX_CLASSES {
+            return Err(format!("at most {} classes per job", MAX_CLASSES));
+        }
+        if self.reference.as_ref().is_some_and(|r| r.len() > 255) {
+            return Err("reference must be at most 255 characters".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a detection belongs in the index
+    fn keeps(&self, class: &str, confidence: f32) -> bool {
+        (self.classes.is_empty() || self.classes.iter().any(|c| c == class))
+            && self.min_confidence.is_none_or(|min| confidence >= min)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisJob {
+    pub id: String,
+    #[serde(flatten)]
+    pub request: AnalysisRequest,
+    pub state: AnalysisState,
+    pub frames_processed: u64,
+    /// Frames a plugin failed on with a recoverable error
+    pub frames_skipped: u64,
+    pub detections: u64,
+    /// Indexed detections per class
+    pub classes: BTreeMap<String, u64>,
+    /// Unix timestamp in seconds of the start of the recording, when known;
+    /// index entries are timestamped from it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recording_started_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamps in milliseconds
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<u64>,
+}
+
+/// One detection in the index of a job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Milliseconds from the start of the recording
+    pub offset_ms: u64,
+    /// Unix timestamp in milliseconds, when the recording's start is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    pub plugin: String,
+    pub class: String,
+    pub confidence: f32,
+    pub bbox: BoundingBox,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Filter of `GET /v1/analysis/jobs/:id/detections`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DetectionQuery {
+    pub plugin: Option<String>,
+    pub class: Option<String>,
+    pub min_confidence: Option<f32>,
+    /// Range of offsets from the start of the recording, in milliseconds
+    pub from_ms: Option<u64>,
+    pub to_ms: Option<u64>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl DetectionQuery {
+    fn matches(&self, entry: &IndexEntry) -> bool {
+        self.plugin.as_ref().is_none_or(|p| *p == entry.plugin)
+            && self.class.as_ref().is_none_or(|c| *c == entry.class)
+            && self.min_confidence.is_none_or(|min| entry.confidence >= min)
+            && self.from_ms.is_none_or(|from| entry.offset_ms >= from)
+            && self.to_ms.is_none_or(|to| entry.offset_ms < to)
+    }
+
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(100).clamp(1, MAX_DETECTIONS_LIMIT)
+    }
+}
+
+/// Runs analysis jobs one at a time and keeps track of them. Jobs are held
+/// in memory; their indexes stay in the output directory until deleted.
+pub struct Analyzer {
+    plugins: PluginRegistry,
+    state_store: Option<Arc<dyn StateStore>>,
+    client: reqwest::Client,
+    playback_url: Option<String>,
+    output_dir: PathBuf,
+    default_fps: u32,
+    jobs: RwLock<HashMap<String, AnalysisJob>>,
+    running: Semaphore,
+}
+
+impl Analyzer {
+    /// Configured from `PLAYBACK_SERVICE_URL`, `ANALYSIS_OUTPUT_DIR` and
+    /// `ANALYSIS_FPS`. The StateStore, when there is one, gives the start
+    /// and length of recordings.
+    pub fn from_env(plugins: PluginRegistry, state_store: Option<Arc<dyn StateStore>>) -> Self {
+        let playback_url = std::env::var("PLAYBACK_SERVICE_URL")
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty());
+        let output_dir =
+            std::env::var("ANALYSIS_OUTPUT_DIR").unwrap_or_else(|_| "./data/analysis".to_string());
+        let default_fps = std::env::var("ANALYSIS_FPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|fps| (1..=MAX_FPS).contains(fps))
+            .unwrap_or(2);
+
+        Self {
+            plugins,
+            state_store,
+            client: reqwest::Client::new(),
+            playback_url,
+            output_dir: PathBuf::from(output_dir),
+            default_fps,
+            jobs: RwLock::new(HashMap::new()),
+            running: Semaphore::new(1),
+        }
+    }
+
+    /// Queue a job; it runs in the background
+    pub async fn submit(self: &Arc<Self>, request: AnalysisRequest) -> Result<AnalysisJob> {
+        request.validate().map_err(|e| anyhow!(e))?;
+        if self.playback_url.is_none() {
+            bail!("analysis needs PLAYBACK_SERVICE_URL to fetch recordings");
+        }
+        for plugin in &request.plugins {
+            if !self.plugins.has_plugin(plugin).await {
+                bail!("plugin '{}' is not registered", plugin);
+            }
+        }
+
+        let job = AnalysisJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            request,
+            state: AnalysisState::Queued,
+            frames_processed: 0,
+            frames_skipped: 0,
+            detections: 0,
+            classes: BTreeMap::new(),
+            recording_started_at: None,
+            error: None,
+            created_at: timeline::now_ms(),
+            completed_at: None,
+        };
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+
+        let analyzer = self.clone();
+        let id = job.id.clone();
+        tokio::spawn(async move { analyzer.run(&id).await });
+
+        Ok(job)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<AnalysisJob> {
+        self.jobs.read().await.get(id).cloned()
+    }
+
+    /// Newest first
+    pub async fn list(&self) -> Vec<AnalysisJob> {
+        let mut jobs: Vec<_> = self.jobs.read().await.values().cloned().collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    /// Index entries of a job matching `query`, in recording order; `None`
+    /// when the job is unknown
+    pub async fn detections(&self, id: &str, query: &DetectionQuery) -> Result<Option<Vec<IndexEntry>>> {
+        if self.get(id).await.is_none() {
+            return Ok(None);
+        }
+        let index = match tokio::fs::read_to_string(self.index_path(id)).await {
+            Ok(index) => index,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Some(Vec::new())),
+            Err(e) => return Err(e).context("failed to read detection index"),
+        };
+        Ok(Some(filter_index(&index, query)))
+    }
+
+    /// Forget a finished job and delete its index
+    pub async fn remove(&self, id: &str) -> Result<bool> {
+        let mut jobs = self.jobs.write().await;
+        match jobs.get(id).map(|job| job.state) {
+            None => return Ok(false),
+            Some(AnalysisState::Queued | AnalysisState::Running) => {
+                bail!("job '{}' has not finished", id)
+            }
+            Some(_) => {}
+        }
+        jobs.remove(id);
+        match tokio::fs::remove_file(self.index_path(id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).context("failed to delete detection index")
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn index_path(&self, id: &str) -> PathBuf {
+        self.output_dir.join(format!("{}.jsonl", id))
+    }
+
+    async fn update(&self, id: &str, f: impl FnOnce(&mut AnalysisJob)) -> Option<AnalysisJob> {
+        let mut jobs = self.jobs.write().await;
+        let job = jobs.get_mut(id)?;
+        f(job);
+        Some(job.clone())
+    }
+
+    async fn run(&self, id: &str) {
+        let Ok(_permit) = self.running.acquire().await else {
+            return;
+        };
+        let Some(job) = self.update(id, |job| job.state = AnalysisState::Running).await else {
+            return;
+        };
+
+        let work_dir = std::env::temp_dir().join(format!("analysis-{}", id));
+        let result = self.analyze(id, &job.request, &work_dir).await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+
+        let job = self
+            .update(id, |job| {
+                job.completed_at = Some(timeline::now_ms());
+                match &result {
+                    Ok(()) => job.state = AnalysisState::Completed,
+                    Err(e) => {
+                        job.state = AnalysisState::Failed;
+                        job.error = Some(format!("{:#}", e));
+                    }
+                }
+            })
+            .await;
+
+        match (result, job) {
+            (Ok(()), Some(job)) => {
+                info!(
+                    job_id = %id,
+                    recording_id = %job.request.recording_id,
+                    frames = job.frames_processed,
+                    detections = job.detections,
+                    "recording analysis completed"
+                );
+                timeline::record(
+                    TimelineEvent::new(
+                        TimelineEventKind::OperatorAction,
+                        "ai-service",
+                        format!(
+                            "Recording {} analyzed with {}",
+                            job.request.recording_id,
+                            job.request.plugins.join(", ")
+                        ),
+                    )
+                    .details(serde_json::json!({
+                        "analysis_id": job.id,
+                        "recording_id": job.request.recording_id,
+                        "plugins": job.request.plugins,
+                        "reference": job.request.reference,
+                        "frames_processed": job.frames_processed,
+                        "detections": job.detections,
+                        "classes": job.classes,
+                    })),
+                );
+            }
+            (Err(e), _) => error!(job_id = %id, error = %e, "recording analysis failed"),
+            (Ok(()), None) => {}
+        }
+    }
+
+    /// Start (Unix seconds) and length (seconds) of a recording, as far as
+    /// the StateStore knows them
+    async fn recording_bounds(&self, recording_id: &str) -> (Option<u64>, Option<f64>) {
+        let Some(store) = &self.state_store else {
+            return (None, None);
+        };
+        match store.get_recording(recording_id).await {
+            Ok(Some(recording)) => {
+                let duration = recording
+                    .metadata
+                    .as_ref()
+                    .and_then(|m| m.duration_secs)
+                    .or_else(|| {
+                        recording
+                            .started_at
+                            .zip(recording.stopped_at)
+                            .map(|(start, stop)| stop.saturating_sub(start))
+                    });
+                (recording.started_at, duration.map(|d| d as f64))
+            }
+            Ok(None) => (None, None),
+            Err(e) => {
+                warn!(recording_id, error = %e, "failed to look up recording for analysis");
+                (None, None)
+            }
+        }
+    }
+
+    /// Run the plugins over the requested range, chunk by chunk, appending
+    /// to the index as detections come in
+    async fn analyze(&self, id: &str, request: &AnalysisRequest, work_dir: &Path) -> Result<()> {
+        let (started_at, duration) = self.recording_bounds(&request.recording_id).await;
+        let end_secs = request
+            .end_secs
+            .or(duration)
+            .context("end_secs is required when the length of the recording is unknown")?;
+        if end_secs <= request.start_secs {
+            bail!("recording is only {} seconds long", end_secs);
+        }
+        self.update(id, |job| job.recording_started_at = started_at).await;
+
+        let mut plugins = Vec::new();
+        for name in &request.plugins {
+            plugins.push((name.clone(), self.plugins.get(name).await?));
+        }
+
+        tokio::fs::create_dir_all(&self.output_dir)
+            .await
+            .context("failed to create output directory")?;
+        let mut index = tokio::fs::File::create(self.index_path(id))
+            .await
+            .context("failed to create detection index")?;
+
+        let base = self
+            .playback_url
+            .as_deref()
+            .context("PLAYBACK_SERVICE_URL is not set")?;
+        let fps = request.fps.unwrap_or(self.default_fps);
+        let mut sequence = 0u64;
+        // Clip exports are limited in length, so long ranges are fetched in parts
+        for range in chunks(request.start_secs, end_secs) {
+            let frames_dir = work_dir.join("frames");
+            let _ = tokio::fs::remove_dir_all(&frames_dir).await;
+            tokio::fs::create_dir_all(&frames_dir)
+                .await
+                .context("failed to create work directory")?;
+            let source = work_dir.join("source.mp4");
+            download_clip(&self.client, base, &request.recording_id, &range, &source).await?;
+
+            ffmpeg(&[
+                "-i".to_string(),
+                source.display().to_string(),
+                "-vf".to_string(),
+                format!("fps={}", fps),
+                "-q:v".to_string(),
+                "2".to_string(),
+                frames_dir.join("%06d.jpg").display().to_string(),
+            ])
+            .await
+            .context("failed to extract frames")?;
+
+            let mut frames = Vec::new();
+            let mut entries = tokio::fs::read_dir(&frames_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                frames.push(entry.path());
+            }
+            frames.sort();
+
+            for (index_in_chunk, path) in frames.iter().enumerate() {
+                let offset_ms = (range.start_secs * 1000.0) as u64 + index_in_chunk as u64 * 1000 / fps as u64;
+                let data = tokio::fs::read(path).await?;
+                let (width, height) = image::image_dimensions(path)
+                    .with_context(|| format!("failed to decode {}", path.display()))?;
+                let frame = VideoFrame {
+                    source_id: request.recording_id.clone(),
+                    timestamp: offset_ms,
+                    sequence,
+                    width,
+                    height,
+                    format: "jpeg".to_string(),
+                    data: base64::prelude::BASE64_STANDARD.encode(&data),
+                    audio: None,
+                };
+                sequence += 1;
+
+                let mut found = Vec::new();
+                let mut skipped = false;
+                for (name, plugin) in &plugins {
+                    let result = match plugin.read().await.process_frame(&frame).await {
+                        Ok(result) => result,
+                        Err(e) if PluginError::kind_of(&e) == PluginErrorKind::Recoverable => {
+                            warn!(job_id = %id, plugin = %name, offset_ms, error = %e, "skipping frame");
+                            skipped = true;
+                            continue;
+                        }
+                        Err(e) => return Err(e.context(format!("{} failed at {} ms", name, offset_ms))),
+                    };
+                    found.extend(
+                        result
+                            .detections
+                            .into_iter()
+                            .filter(|d| request.keeps(&d.class, d.confidence))
+                            .map(|d| IndexEntry {
+                                offset_ms,
+                                timestamp: started_at.map(|s| s * 1000 + offset_ms),
+                                plugin: name.clone(),
+                                class: d.class,
+                                confidence: d.confidence,
+                                bbox: d.bbox,
+                                metadata: d.metadata,
+                            }),
+                    );
+                }
+
+                let mut lines = Vec::new();
+                for entry in &found {
+                    serde_json::to_writer(&mut lines, entry)?;
+                    lines.push(b'\n');
+                }
+                index.write_all(&lines).await?;
+
+                self.update(id, |job| {
+                    job.frames_processed += 1;
+                    job.frames_skipped += u64::from(skipped);
+                    job.detections += found.len() as u64;
+                    for entry in &found {
+                        *job.classes.entry(entry.class.clone()).or_default() += 1;
+                    }
+                })
+                .await;
+            }
+            index.flush().await?;
+
+            // A short chunk means the recording ended before `end_secs`
+            let expected = ((range.end_secs - range.start_secs) * fps as f64) as usize;
+            if frames.len() + (fps as usize) < expected {
+                break;
+            }
+        }
+
+        if sequence == 0 {
+            bail!("recording range has no frames");
+        }
+        Ok(())
+    }
+}
+
+/// `start..end` split into ranges a single clip export may cover
+fn chunks(start_secs: f64, end_secs: f64) -> Vec<ClipExportQuery> {
+    let mut ranges = Vec::new();
+    let mut start = start_secs;
+    while start < end_secs {
+        let end = (start + MAX_CLIP_SECS).min(end_secs);
+        ranges.push(ClipExportQuery {
+            start_secs: start,
+            end_secs: end,
+        });
+        start = end;
+    }
+    ranges
+}
+
+/// Entries of a JSON lines index matching `query`, after skipping
+/// `query.offset` of them
+fn filter_index(index: &str, query: &DetectionQuery) -> Vec<IndexEntry> {
+    index
+        .lines()
+        .filter_map(|line| serde_json::from_str::<IndexEntry>(line).ok())
+        .filter(|entry| query.matches(entry))
+        .skip(query.offset)
+        .take(query.limit())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_validation() {
+        let request: AnalysisRequest = serde_json::from_value(serde_json::json!({
+            "recording_id": "rec-1",
+            "plugins": ["facial_recognition"],
+        }))
+        .unwrap();
+        assert_eq!(request.start_secs, 0.0);
+        assert!(request.end_secs.is_none());
+        assert!(request.validate().is_ok());
+
+        let invalid = |f: fn(&mut AnalysisRequest)| {
+            let mut request = request.clone();
+            f(&mut request);
+            request.validate().is_err()
+        };
+        assert!(invalid(|r| r.plugins.clear()));
+        assert!(invalid(|r| r.fps = Some(MAX_FPS + 1)));
+        assert!(invalid(|r| r.min_confidence = Some(1.5)));
+        assert!(invalid(|r| r.start_secs = -1.0));
+        assert!(invalid(|r| {
+            r.start_secs = 60.0;
+            r.end_secs = Some(30.0);
+        }));
+        assert!(invalid(|r| r.recording_id.clear()));
+    }
+
+    #[test]
+    fn test_long_ranges_are_split_into_clip_exports() {
+        let ranges = chunks(100.0, 100.0 + 2.5 * MAX_CLIP_SECS);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].start_secs, 100.0);
+        assert_eq!(ranges[1].start_secs, ranges[0].end_secs);
+        assert_eq!(ranges[2].end_secs, 100.0 + 2.5 * MAX_CLIP_SECS);
+        assert!(ranges.iter().all(|r| r.validate().is_ok()));
+        assert!(chunks(10.0, 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_index_queries() {
+        let entry = |offset_ms: u64, plugin: &str, class: &str, confidence: f32| IndexEntry {
+            offset_ms,
+            timestamp: None,
+            plugin: plugin.to_string(),
+            class: class.to_string(),
+            confidence,
+            bbox: BoundingBox {
+                x: 0,
+                y: 0,
+                width: 10,
+                height: 10,
+            },
+            metadata: None,
+        };
+        let index: String = [
+            entry(0, "lpr", "license_plate", 0.9),
+            entry(500, "facial_recognition", "alice", 0.8),
+            entry(1000, "lpr", "license_plate", 0.4),
+            entry(1500, "facial_recognition", "bob", 0.95),
+        ]
+        .iter()
+        .map(|e| serde_json::to_string(e).unwrap() + "\n")
+        .collect();
+
+        let offsets = |query: DetectionQuery| -> Vec<u64> {
+            filter_index(&index, &query).iter().map(|e| e.offset_ms).collect()
+        };
+        assert_eq!(offsets(DetectionQuery::default()), vec![0, 500, 1000, 1500]);
+        assert_eq!(
+            offsets(DetectionQuery {
+                plugin: Some("lpr".to_string()),
+                min_confidence: Some(0.5),
+                ..Default::default()
+            }),
+            vec![0]
+        );
+        assert_eq!(
+            offsets(DetectionQuery {
+                from_ms: Some(500),
+                to_ms: Some(1500),
+                ..Default::default()
+            }),
+            vec![500, 1000]
+        );
+        assert_eq!(
+            offsets(DetectionQuery {
+                offset: 1,
+                limit: Some(2),
+                ..Default::default()
+            }),
+            vec![500, 1000]
+        );
+    }
+}
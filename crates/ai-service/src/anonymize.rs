@@ -0,0 +1,579 @@
+//! Batch video anonymization.
+//!
+//! A job takes a time range of a recording, fetched through playback-service's
+//! clip export, and produces a redacted copy for disclosure to third parties.
+//! Frames are sampled at a fixed rate, the facial recognition and LPR plugins
+//! find faces and plates, and every region found is blurred before the frames
+//! are encoded back to MP4. Audio is dropped: voices are personal data too.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use common::ai_tasks::{BoundingBox, VideoFrame};
+use common::playback::ClipExportQuery;
+use common::timeline::{self, TimelineEvent, TimelineEventKind};
+use image::{imageops, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{error, info};
+
+use crate::plugin::registry::PluginRegistry;
+
+/// ffmpeg gets this long per step before it is killed
+const FFMPEG_TIMEOUT: Duration = Duration::from_secs(900);
+
+const MAX_FPS: u32 = 30;
+
+/// Regions are grown by this fraction of their size on each side, so hair
+/// and plate surrounds are covered too
+const REGION_MARGIN: f32 = 0.15;
+
+/// What to blur
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionTarget {
+    Faces,
+    Plates,
+}
+
+impl RedactionTarget {
+    /// Plugin that detects the target
+    fn plugin(self) -> &'static str {
+        match self {
+            RedactionTarget::Faces => "facial_recognition",
+            RedactionTarget::Plates => "lpr",
+        }
+    }
+
+    /// Whether a detection of `class` from the target's plugin is the target.
+    /// Facial recognition reports the matched name, so every class is a face.
+    fn matches(self, class: &str) -> bool {
+        match self {
+            RedactionTarget::Faces => true,
+            RedactionTarget::Plates => class == "license_plate",
+        }
+    }
+}
+
+fn default_targets() -> Vec<RedactionTarget> {
+    vec![RedactionTarget::Faces, RedactionTarget::Plates]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnonymizationRequest {
+    pub recording_id: String,
+    /// Range of the recording, in seconds from its start
+    pub start_secs: f64,
+    pub end_secs: f64,
+    #[serde(default = "default_targets")]
+    pub targets: Vec<RedactionTarget>,
+    /// Frames sampled per second; the copy plays back at this rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<u32>,
+    /// Reference of the disclosure request, e.g. a case number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+impl AnonymizationRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        common::validation::validate_id(&self.recording_id, "recording_id")
+            .map_err(|e| e.to_string())?;
+        self.range().validate()?;
+        if self.targets.is_empty() {
+            return Err("at least one target is required".to_string());
+        }
+        if let Some(fps) = self.fps {
+            if !(1..=MAX_FPS).contains(&fps) {
+                return Err(format!("fps must be between 1 and {}", MAX_FPS));
+            }
+        }
+        if self.reference.as_ref().is_some_and(|r| r.len() > 255) {
+            return Err("reference must be at most 255 characters".to_string());
+        }
+        Ok(())
+    }
+
+    fn range(&self) -> ClipExportQuery {
+        ClipExportQuery {
+            start_secs: self.start_secs,
+            end_secs: self.end_secs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnonymizationState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnonymizationJob {
+    pub id: String,
+    #[serde(flatten)]
+    pub request: AnonymizationRequest,
+    pub state: AnonymizationState,
+    pub frames_total: u64,
+    pub frames_processed: u64,
+    /// Regions blurred, counted once per frame they were detected in
+    pub regions_redacted: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamps in milliseconds
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<u64>,
+}
+
+/// Runs anonymization jobs one at a time and keeps track of them. Jobs are
+/// held in memory; redacted copies stay in the output directory until deleted.
+pub struct Anonymizer {
+    plugins: PluginRegistry,
+    client: reqwest::Client,
+    playback_url: Option<String>,
+    output_dir: PathBuf,
+    default_fps: u32,
+    jobs: RwLock<HashMap<String, AnonymizationJob>>,
+    running: Semaphore,
+}
+
+impl Anonymizer {
+    /// Configured from `PLAYBACK_SERVICE_URL`, `ANONYMIZATION_OUTPUT_DIR` and
+    /// `ANONYMIZATION_FPS`
+    pub fn from_env(plugins: PluginRegistry) -> Self {
+        let playback_url = std::env::var("PLAYBACK_SERVICE_URL")
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty());
+        let output_dir = std::env::var("ANONYMIZATION_OUTPUT_DIR")
+            .unwrap_or_else(|_| "./data/anonymized".to_string());
+        let default_fps = std::env::var("ANONYMIZATION_FPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|fps| (1..=MAX_FPS).contains(fps))
+            .unwrap_or(15);
+
+        Self {
+            plugins,
+            client: reqwest::Client::new(),
+            playback_url,
+            output_dir: PathBuf::from(output_dir),
+            default_fps,
+            jobs: RwLock::new(HashMap::new()),
+            running: Semaphore::new(1),
+        }
+    }
+
+    /// Queue a job; it runs in the background
+    pub async fn submit(self: &Arc<Self>, request: AnonymizationRequest) -> Result<AnonymizationJob> {
+        request.validate().map_err(|e| anyhow!(e))?;
+        if self.playback_url.is_none() {
+            bail!("anonymization needs PLAYBACK_SERVICE_URL to fetch recordings");
+        }
+        for target in &request.targets {
+            if !self.plugins.has_plugin(target.plugin()).await {
+                bail!("plugin '{}' is not registered", target.plugin());
+            }
+        }
+
+        let job = AnonymizationJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            request,
+            state: AnonymizationState::Queued,
+            frames_total: 0,
+            frames_processed: 0,
+            regions_redacted: 0,
+            output_bytes: None,
+            error: None,
+            created_at: timeline::now_ms(),
+            completed_at: None,
+        };
+        self.jobs.write().await.insert(job.id.clone(), job.clone());
+
+        let anonymizer = self.clone();
+        let id = job.id.clone();
+        tokio::spawn(async move { anonymizer.run(&id).await });
+
+        Ok(job)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<AnonymizationJob> {
+        self.jobs.read().await.get(id).cloned()
+    }
+
+    /// Newest first
+    pub async fn list(&self) -> Vec<AnonymizationJob> {
+        let mut jobs: Vec<_> = self.jobs.read().await.values().cloned().collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    /// Redacted copy of a completed job
+    pub async fn output(&self, id: &str) -> Option<PathBuf> {
+        let job = self.get(id).await?;
+        (job.state == AnonymizationState::Completed).then(|| self.output_path(id))
+    }
+
+    /// Forget a finished job and delete its copy
+    pub async fn remove(&self, id: &str) -> Result<bool> {
+        let mut jobs = self.jobs.write().await;
+        match jobs.get(id).map(|job| job.state) {
+            None => return Ok(false),
+            Some(AnonymizationState::Queued | AnonymizationState::Running) => {
+                bail!("job '{}' has not finished", id)
+            }
+            Some(_) => {}
+        }
+        jobs.remove(id);
+        match tokio::fs::remove_file(self.output_path(id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).context("failed to delete redacted copy")
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn output_path(&self, id: &str) -> PathBuf {
+        self.output_dir.join(format!("{}.mp4", id))
+    }
+
+    async fn update(&self, id: &str, f: impl FnOnce(&mut AnonymizationJob)) -> Option<AnonymizationJob> {
+        let mut jobs = self.jobs.write().await;
+        let job = jobs.get_mut(id)?;
+        f(job);
+        Some(job.clone())
+    }
+
+    async fn run(&self, id: &str) {
+        let Ok(_permit) = self.running.acquire().await else {
+            return;
+        };
+        let Some(job) = self.update(id, |job| job.state = AnonymizationState::Running).await else {
+            return;
+        };
+
+        let work_dir = std::env::temp_dir().join(format!("anonymize-{}", id));
+        let result = self.anonymize(id, &job.request, &work_dir).await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+
+        let job = self
+            .update(id, |job| {
+                job.completed_at = Some(timeline::now_ms());
+                match &result {
+                    Ok(bytes) => {
+                        job.state = AnonymizationState::Completed;
+                        job.output_bytes = Some(*bytes);
+                    }
+                    Err(e) => {
+                        job.state = AnonymizationState::Failed;
+                        job.error = Some(format!("{:#}", e));
+                    }
+                }
+            })
+            .await;
+
+        match (result, job) {
+            (Ok(_), Some(job)) => {
+                info!(
+                    job_id = %id,
+                    recording_id = %job.request.recording_id,
+                    frames = job.frames_processed,
+                    regions = job.regions_redacted,
+                    "anonymization completed"
+                );
+                timeline::record(
+                    TimelineEvent::new(
+                        TimelineEventKind::OperatorAction,
+                        "ai-service",
+                        format!("Redacted copy of recording {} created", job.request.recording_id),
+                    )
+                    .details(serde_json::json!({
+                        "anonymization_id": job.id,
+                        "recording_id": job.request.recording_id,
+                        "start_secs": job.request.start_secs,
+                        "end_secs": job.request.end_secs,
+                        "reference": job.request.reference,
+                        "regions_redacted": job.regions_redacted,
+                    })),
+                );
+            }
+            (Err(e), _) => error!(job_id = %id, error = %e, "anonymization failed"),
+            (Ok(_), None) => {}
+        }
+    }
+
+    /// Produce the redacted copy and return its size
+    async fn anonymize(&self, id: &str, request: &AnonymizationRequest, work_dir: &Path) -> Result<u64> {
+        let frames_dir = work_dir.join("frames");
+        tokio::fs::create_dir_all(&frames_dir)
+            .await
+            .context("failed to create work directory")?;
+
+        let source = work_dir.join("source.mp4");
+        self.download(request, &source).await?;
+
+        let fps = request.fps.unwrap_or(self.default_fps);
+        let pattern = frames_dir.join("%06d.jpg").display().to_string();
+        ffmpeg(&[
+            "-i".to_string(),
+            source.display().to_string(),
+            "-vf".to_string(),
+            format!("fps={}", fps),
+            "-q:v".to_string(),
+            "2".to_string(),
+            pattern.clone(),
+        ])
+        .await
+        .context("failed to extract frames")?;
+
+        let mut frames = Vec::new();
+        let mut entries = tokio::fs::read_dir(&frames_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            frames.push(entry.path());
+        }
+        frames.sort();
+        if frames.is_empty() {
+            bail!("recording range has no frames");
+        }
+        self.update(id, |job| job.frames_total = frames.len() as u64).await;
+
+        let mut detectors = Vec::new();
+        for target in &request.targets {
+            detectors.push((*target, self.plugins.get(target.plugin()).await?));
+        }
+
+        // Regions found in a frame are blurred in the next one too, so a
+        // single missed detection does not reveal a face
+        let mut previous: Vec<BoundingBox> = Vec::new();
+        for (index, path) in frames.iter().enumerate() {
+            let data = tokio::fs::read(path).await?;
+            let image = image::load_from_memory(&data)
+                .with_context(|| format!("failed to decode {}", path.display()))?
+                .to_rgb8();
+            let frame = VideoFrame {
+                source_id: request.recording_id.clone(),
+                timestamp: index as u64 * 1000 / fps as u64,
+                sequence: index as u64,
+                width: image.width(),
+                height: image.height(),
+                format: "jpeg".to_string(),
+                data: base64::prelude::BASE64_STANDARD.encode(&data),
+                audio: None,
+            };
+
+            let mut regions = Vec::new();
+            for (target, plugin) in &detectors {
+                let result = plugin
+                    .read()
+                    .await
+                    .process_frame(&frame)
+                    .await
+                    .with_context(|| format!("{} failed on frame {}", target.plugin(), index))?;
+                regions.extend(
+                    result
+                        .detections
+                        .into_iter()
+                        .filter(|d| target.matches(&d.class))
+                        .map(|d| d.bbox),
+                );
+            }
+
+            let found = regions.len() as u64;
+            let blurred: Vec<BoundingBox> = regions.iter().chain(&previous).cloned().collect();
+            let encoded = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+                let image = blur_regions(image, &blurred);
+                let mut out = Cursor::new(Vec::new());
+                image
+                    .write_to(&mut out, image::ImageFormat::Jpeg)
+                    .context("failed to encode frame")?;
+                Ok(out.into_inner())
+            })
+            .await??;
+            tokio::fs::write(path, encoded).await?;
+            previous = regions;
+
+            self.update(id, |job| {
+                job.frames_processed = index as u64 + 1;
+                job.regions_redacted += found;
+            })
+            .await;
+        }
+
+        tokio::fs::create_dir_all(&self.output_dir)
+            .await
+            .context("failed to create output directory")?;
+        let output = self.output_path(id);
+        ffmpeg(&[
+            "-framerate".to_string(),
+            fps.to_string(),
+            "-i".to_string(),
+            pattern,
+            "-c:v".to_string(),
+            "libx264".to_string(),
+            "-pix_fmt".to_string(),
+            "yuv420p".to_string(),
+            "-movflags".to_string(),
+            "+faststart".to_string(),
+            "-y".to_string(),
+            output.display().to_string(),
+        ])
+        .await
+        .context("failed to encode redacted copy")?;
+
+        Ok(tokio::fs::metadata(&output).await?.len())
+    }
+
+    /// Fetch the requested range as MP4 from playback-service
+    async fn download(&self, request: &AnonymizationRequest, dest: &Path) -> Result<()> {
+        let base = self
+            .playback_url
+            .as_deref()
+            .context("PLAYBACK_SERVICE_URL is not set")?;
+        download_clip(&self.client, base, &request.recording_id, &request.range(), dest).await
+    }
+}
+
+/// Fetch `range` of a recording as MP4 from playback-service at `base`
+pub(crate) async fn download_clip(
+    client: &reqwest::Client,
+    base: &str,
+    recording_id: &str,
+    range: &ClipExportQuery,
+    dest: &Path,
+) -> Result<()> {
+    let mut response = client
+        .get(format!("{}/v1/recordings/{}/clip", base, recording_id))
+        .query(range)
+        .send()
+        .await
+        .context("failed to reach playback-service")?
+        .error_for_status()
+        .context("clip export failed")?;
+
+    let mut file = tokio::fs::File::create(dest).await?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+pub(crate) async fn ffmpeg(args: &[String]) -> Result<()> {
+    let status = tokio::time::timeout(
+        FFMPEG_TIMEOUT,
+        tokio::process::Command::new("ffmpeg")
+            .args(["-loglevel", "error"])
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .status(),
+    )
+    .await
+    .map_err(|_| anyhow!("ffmpeg exceeded {}s", FFMPEG_TIMEOUT.as_secs()))?
+    .context("failed to execute ffmpeg")?;
+
+    if !status.success() {
+        bail!("ffmpeg exited with error: {:?}", status);
+    }
+    Ok(())
+}
+
+/// `bbox` grown by [`REGION_MARGIN`] and clipped to the image, as
+/// (x, y, width, height); `None` when nothing of it is inside
+fn expand(bbox: &BoundingBox, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let margin_x = (bbox.width as f32 * REGION_MARGIN) as u32;
+    let margin_y = (bbox.height as f32 * REGION_MARGIN) as u32;
+    let x0 = bbox.x.saturating_sub(margin_x);
+    let y0 = bbox.y.saturating_sub(margin_y);
+    let x1 = bbox.x.saturating_add(bbox.width).saturating_add(margin_x).min(width);
+    let y1 = bbox.y.saturating_add(bbox.height).saturating_add(margin_y).min(height);
+    if x0 < x1 && y0 < y1 {
+        Some((x0, y0, x1 - x0, y1 - y0))
+    } else {
+        None
+    }
+}
+
+/// Blur every region hard enough that faces and plate text are unreadable
+fn blur_regions(mut image: RgbImage, regions: &[BoundingBox]) -> RgbImage {
+    let (width, height) = image.dimensions();
+    for bbox in regions {
+        let Some((x, y, w, h)) = expand(bbox, width, height) else {
+            continue;
+        };
+        let sigma = (w.max(h) as f32 / 4.0).max(4.0);
+        let region = imageops::crop_imm(&image, x, y, w, h).to_image();
+        let blurred = imageops::blur(&region, sigma);
+        imageops::replace(&mut image, &blurred, x as i64, y as i64);
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbox(x: u32, y: u32, width: u32, height: u32) -> BoundingBox {
+        BoundingBox { x, y, width, height }
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let request: AnonymizationRequest = serde_json::from_value(serde_json::json!({
+            "recording_id": "rec-1",
+            "start_secs": 10.0,
+            "end_secs": 40.0,
+        }))
+        .unwrap();
+        assert_eq!(request.targets, default_targets());
+        assert!(request.validate().is_ok());
+
+        let invalid = |f: fn(&mut AnonymizationRequest)| {
+            let mut request = request.clone();
+            f(&mut request);
+            request.validate().is_err()
+        };
+        assert!(invalid(|r| r.targets.clear()));
+        assert!(invalid(|r| r.fps = Some(0)));
+        assert!(invalid(|r| r.fps = Some(MAX_FPS + 1)));
+        assert!(invalid(|r| r.end_secs = r.start_secs));
+        assert!(invalid(|r| r.recording_id.clear()));
+    }
+
+    #[test]
+    fn test_regions_are_expanded_within_the_frame() {
+        assert_eq!(expand(&bbox(100, 100, 20, 40), 640, 480), Some((97, 94, 26, 52)));
+        assert_eq!(expand(&bbox(0, 470, 40, 40), 640, 480), Some((0, 464, 46, 16)));
+        assert_eq!(expand(&bbox(700, 10, 20, 20), 640, 480), None);
+    }
+
+    #[test]
+    fn test_only_regions_are_blurred() {
+        // Checkerboard, so any blur changes pixels
+        let image = RgbImage::from_fn(64, 64, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgb([255, 255, 255])
+            } else {
+                image::Rgb([0, 0, 0])
+            }
+        });
+        let blurred = blur_regions(image.clone(), &[bbox(20, 20, 10, 10)]);
+        assert_ne!(blurred.get_pixel(25, 25), image.get_pixel(25, 25));
+        assert_eq!(blurred.get_pixel(5, 5), image.get_pixel(5, 5));
+        assert_eq!(blurred.get_pixel(60, 60), image.get_pixel(60, 60));
+    }
+}
@@ -0,0 +1,174 @@
+//! Live detection events at `/v1/events/ws`: every processed frame's
+//! `AiResult` as a JSON text message, filtered by `task_id`, `plugin` and
+//! `class` (comma-separated) from the query string. With a class filter
+//! only the matching detections are sent, and frames without any are
+//! skipped. Clients may replace the filter by sending a JSON message with
+//! the same fields.
+
+use crate::state::AiServiceState;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        Query, State, WebSocketUpgrade,
+    },
+    response::Response,
+};
+use common::ai_tasks::AiResult;
+use futures::{sink::SinkExt, stream::StreamExt};
+use serde::Deserialize;
+use std::collections::HashSet;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Which results a subscriber receives; empty fields match everything
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventFilter {
+    #[serde(default)]
+    pub task_id: Option<String>,
+    #[serde(default)]
+    pub plugin: Option<String>,
+    /// Comma-separated classes
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+impl EventFilter {
+    fn classes(&self) -> HashSet<&str> {
+        self.class
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|class| !class.is_empty())
+            .collect()
+    }
+
+    /// The part of a result this subscriber receives, if any
+    pub fn apply(&self, result: &AiResult) -> Option<AiResult> {
+        if self.task_id.as_deref().is_some_and(|id| id != result.task_id) {
+            return None;
+        }
+        if self.plugin.as_deref().is_some_and(|plugin| plugin != result.plugin_type) {
+            return None;
+        }
+        let classes = self.classes();
+        if classes.is_empty() {
+            return Some(result.clone());
+        }
+        let detections: Vec<_> = result
+            .detections
+            .iter()
+            .filter(|d| classes.contains(d.class.as_str()))
+            .cloned()
+            .collect();
+        if detections.is_empty() {
+            return None;
+        }
+        Some(AiResult {
+            detections,
+            ..result.clone()
+        })
+    }
+}
+
+pub async fn events_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AiServiceState>,
+    Query(filter): Query<EventFilter>,
+) -> Response {
+    ws.on_upgrade(move |socket| stream_events(socket, state, filter))
+}
+
+async fn stream_events(socket: WebSocket, state: AiServiceState, mut filter: EventFilter) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut frames = state.metadata().subscribe();
+    tracing::debug!(?filter, "detection event subscriber connected");
+
+    loop {
+        tokio::select! {
+            frame = frames.recv() => {
+                let frame = match frame {
+                    Ok(frame) => frame,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "detection event subscriber lagging, results skipped");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let Some(result) = filter.apply(&frame.result) else {
+                    continue;
+                };
+                let Ok(json) = serde_json::to_string(&result) else {
+                    continue;
+                };
+                if sender.send(Message::Text(json)).await.is_err() {
+                    break;
+                }
+            }
+            message = receiver.next() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<EventFilter>(&text) {
+                    Ok(update) => filter = update,
+                    Err(e) => tracing::debug!(error = %e, "ignoring invalid detection event filter"),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    tracing::debug!("detection event subscriber disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::{BoundingBox, Detection};
+
+    fn result() -> AiResult {
+        let detection = |class: &str| Detection {
+            class: class.to_string(),
+            confidence: 0.9,
+            bbox: BoundingBox { x: 0, y: 0, width: 10, height: 10 },
+            metadata: None,
+        };
+        AiResult {
+            task_id: "task-1".to_string(),
+            timestamp: 1,
+            plugin_type: "yolov8".to_string(),
+            detections: vec![detection("person"), detection("car")],
+            confidence: None,
+            processing_time_ms: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn filters_by_task_plugin_and_class() {
+        let result = result();
+        assert!(EventFilter::default().apply(&result).is_some());
+
+        let other_task = EventFilter {
+            task_id: Some("task-2".to_string()),
+            ..Default::default()
+        };
+        assert!(other_task.apply(&result).is_none());
+        let other_plugin = EventFilter {
+            plugin: Some("lpr".to_string()),
+            ..Default::default()
+        };
+        assert!(other_plugin.apply(&result).is_none());
+
+        let cars = EventFilter {
+            task_id: Some("task-1".to_string()),
+            class: Some(" car, truck".to_string()),
+            ..Default::default()
+        };
+        let filtered = cars.apply(&result).unwrap();
+        assert_eq!(filtered.detections.len(), 1);
+        assert_eq!(filtered.detections[0].class, "car");
+
+        let dogs = EventFilter {
+            class: Some("dog".to_string()),
+            ..Default::default()
+        };
+        assert!(dogs.apply(&result).is_none());
+    }
+}
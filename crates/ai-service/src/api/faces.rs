@@ -0,0 +1,283 @@
+//! Face enrollment for the facial recognition plugin at
+//! `/v1/plugins/facial_recognition/faces`: enroll from an uploaded image
+//! (multipart), list, get, rename or change metadata, and remove. Faces are
+//! returned without their embeddings.
+
+use crate::plugin::facial_recognition::{EnrolledFace, FaceUpdate, FacialRecognitionPlugin};
+use crate::plugin::AiPlugin;
+use crate::state::AiServiceState;
+use axum::{
+    extract::{Multipart, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const PLUGIN_ID: &str = "facial_recognition";
+
+/// An enrolled face as the API returns it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FaceRecord {
+    pub face_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    /// Unix timestamp in milliseconds
+    pub enrolled_at: u64,
+    /// Length of the stored embedding
+    pub embedding_size: usize,
+}
+
+impl From<EnrolledFace> for FaceRecord {
+    fn from(face: EnrolledFace) -> Self {
+        Self {
+            face_id: face.face_id,
+            name: face.name,
+            metadata: face.metadata,
+            enrolled_at: face.enrolled_at,
+            embedding_size: face.embedding.len(),
+        }
+    }
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+fn face_not_found(face_id: &str) -> Response {
+    error(StatusCode::NOT_FOUND, format!("Face '{}' not enrolled", face_id))
+}
+
+/// The registered facial recognition plugin; 404 when it is not registered
+async fn face_plugin(state: &AiServiceState) -> Result<Arc<RwLock<dyn AiPlugin>>, Response> {
+    state.plugins().get(PLUGIN_ID).await.map_err(|e| {
+        error(
+            StatusCode::NOT_FOUND,
+            format!("Facial recognition plugin not available: {}", e),
+        )
+    })
+}
+
+/// Downcast a plugin handle to the facial recognition plugin
+fn downcast(plugin: &dyn AiPlugin) -> Option<&FacialRecognitionPlugin> {
+    plugin.as_any().downcast_ref::<FacialRecognitionPlugin>()
+}
+
+fn plugin_inaccessible() -> Response {
+    error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to access facial recognition plugin",
+    )
+}
+
+/// Fields of a multipart enrollment
+#[derive(Debug, Default)]
+struct EnrollForm {
+    face_id: Option<String>,
+    name: Option<String>,
+    metadata: Option<serde_json::Value>,
+    image: Option<Vec<u8>>,
+}
+
+async fn read_enroll_form(mut multipart: Multipart) -> Result<EnrollForm, Response> {
+    let bad_request = |e: String| error(StatusCode::BAD_REQUEST, e);
+    let mut form = EnrollForm::default();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| bad_request(format!("Invalid multipart body: {}", e)))?
+    {
+        let Some(field_name) = field.name().map(str::to_string) else {
+            continue;
+        };
+        match field_name.as_str() {
+            "image" => {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| bad_request(format!("Failed to read image: {}", e)))?;
+                form.image = Some(bytes.to_vec());
+            }
+            "face_id" | "name" | "metadata" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| bad_request(format!("Failed to read {}: {}", field_name, e)))?;
+                let text = text.trim().to_string();
+                if text.is_empty() {
+                    continue;
+                }
+                match field_name.as_str() {
+                    "face_id" => form.face_id = Some(text),
+                    "name" => form.name = Some(text),
+                    _ => {
+                        let metadata = serde_json::from_str(&text)
+                            .map_err(|e| bad_request(format!("metadata is not valid JSON: {}", e)))?;
+                        form.metadata = Some(metadata);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(form)
+}
+
+/// List enrolled faces
+pub async fn list_faces(State(state): State<AiServiceState>) -> Response {
+    let plugin = match face_plugin(&state).await {
+        Ok(plugin) => plugin,
+        Err(response) => return response,
+    };
+    let plugin = plugin.read().await;
+    let faces = match downcast(&*plugin) {
+        Some(face_plugin) => face_plugin.list_faces(),
+        None => return plugin_inaccessible(),
+    };
+
+    match faces {
+        Ok(faces) => {
+            let mut faces: Vec<FaceRecord> = faces.into_iter().map(FaceRecord::from).collect();
+            faces.sort_by(|a, b| a.face_id.cmp(&b.face_id));
+            Json(json!({ "count": faces.len(), "faces": faces })).into_response()
+        }
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list faces: {}", e),
+        ),
+    }
+}
+
+/// Enroll a face from a multipart upload: `image` (JPEG/PNG of the face),
+/// `name`, and optional `face_id` (generated when missing) and `metadata`
+/// (JSON). An enrolled face_id is not replaced; remove it first.
+pub async fn enroll_face(State(state): State<AiServiceState>, multipart: Multipart) -> Response {
+    let form = match read_enroll_form(multipart).await {
+        Ok(form) => form,
+        Err(response) => return response,
+    };
+    let Some(name) = form.name else {
+        return error(StatusCode::BAD_REQUEST, "name is required");
+    };
+    let Some(image_data) = form.image else {
+        return error(StatusCode::BAD_REQUEST, "image is required");
+    };
+    let image = match image::load_from_memory(&image_data) {
+        Ok(image) => image,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid image format: {}", e)),
+    };
+    let face_id = form.face_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let plugin = match face_plugin(&state).await {
+        Ok(plugin) => plugin,
+        Err(response) => return response,
+    };
+    let plugin = plugin.read().await;
+    let face_plugin = match downcast(&*plugin) {
+        Some(face_plugin) => face_plugin,
+        None => return plugin_inaccessible(),
+    };
+
+    match face_plugin.get_face(&face_id) {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return error(
+                StatusCode::CONFLICT,
+                format!("Face '{}' is already enrolled", face_id),
+            )
+        }
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    match face_plugin
+        .enroll_face(face_id.clone(), name, &image, form.metadata)
+        .await
+    {
+        Ok(face) => {
+            tracing::info!(face_id = %face_id, "enrolled face");
+            (StatusCode::CREATED, Json(FaceRecord::from(face))).into_response()
+        }
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to enroll face: {}", e),
+        ),
+    }
+}
+
+/// Get an enrolled face
+pub async fn get_face(
+    State(state): State<AiServiceState>,
+    Path(face_id): Path<String>,
+) -> Response {
+    let plugin = match face_plugin(&state).await {
+        Ok(plugin) => plugin,
+        Err(response) => return response,
+    };
+    let plugin = plugin.read().await;
+    let face = match downcast(&*plugin) {
+        Some(face_plugin) => face_plugin.get_face(&face_id),
+        None => return plugin_inaccessible(),
+    };
+
+    match face {
+        Ok(Some(face)) => Json(FaceRecord::from(face)).into_response(),
+        Ok(None) => face_not_found(&face_id),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Rename an enrolled face or replace its metadata
+pub async fn update_face(
+    State(state): State<AiServiceState>,
+    Path(face_id): Path<String>,
+    Json(update): Json<FaceUpdate>,
+) -> Response {
+    if update.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+        return error(StatusCode::BAD_REQUEST, "name must not be empty");
+    }
+
+    let plugin = match face_plugin(&state).await {
+        Ok(plugin) => plugin,
+        Err(response) => return response,
+    };
+    let plugin = plugin.read().await;
+    let face = match downcast(&*plugin) {
+        Some(face_plugin) => face_plugin.update_face(&face_id, update),
+        None => return plugin_inaccessible(),
+    };
+
+    match face {
+        Ok(Some(face)) => Json(FaceRecord::from(face)).into_response(),
+        Ok(None) => face_not_found(&face_id),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Remove an enrolled face
+pub async fn delete_face(
+    State(state): State<AiServiceState>,
+    Path(face_id): Path<String>,
+) -> Response {
+    let plugin = match face_plugin(&state).await {
+        Ok(plugin) => plugin,
+        Err(response) => return response,
+    };
+    let plugin = plugin.read().await;
+    let removed = match downcast(&*plugin) {
+        Some(face_plugin) => face_plugin.remove_face(&face_id),
+        None => return plugin_inaccessible(),
+    };
+
+    match removed {
+        Ok(true) => {
+            tracing::info!(face_id = %face_id, "removed enrolled face");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => face_not_found(&face_id),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
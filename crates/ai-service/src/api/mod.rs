@@ -1,12 +1,26 @@
+pub mod events;
+pub mod faces;
+pub mod models;
 pub mod routes;
+pub mod watchlists;
 
 use crate::state::AiServiceState;
-use axum::{routing::{delete, get, post}, Router};
+use axum::{extract::DefaultBodyLimit, middleware, routing::{delete, get, post}, Router};
+use common::api_version::{self, Deprecation};
+use common::openapi::{openapi_routes, OpenApiSpec};
+use common::rate_limit::{rate_limit_middleware, RateLimitConfig, RateLimitKey, RateLimiter};
 use tower_http::trace::TraceLayer;
 
 /// Build the API router
 pub fn router(state: AiServiceState) -> Router {
-    Router::new()
+    let frame_limit = RateLimiter::new(RateLimitConfig::from_env(
+        "AI_FRAME",
+        60,
+        30.0,
+        RateLimitKey::ApiKey,
+    ));
+
+    let router = Router::new()
         // Health and metrics endpoints
         .route("/healthz", get(routes::healthz))
         .route("/readyz", get(routes::readyz))
@@ -14,13 +28,175 @@ pub fn router(state: AiServiceState) -> Router {
         // Plugin endpoints
         .route("/v1/plugins", get(routes::list_plugins))
         .route("/v1/plugins/:id", get(routes::get_plugin))
+        .route(
+            "/v1/plugins/:id/config",
+            get(routes::get_plugin_config).put(routes::update_plugin_config),
+        )
+        .route(
+            "/v1/plugins/:id/model",
+            get(models::get_plugin_model).put(models::set_plugin_model),
+        )
+        // Model registry
+        .route("/v1/models", get(models::list_models))
+        .route(
+            "/v1/models/:name",
+            get(models::get_model).post(models::upload_model).layer(DefaultBodyLimit::max(
+                crate::models::max_upload_bytes(),
+            )),
+        )
+        .route(
+            "/v1/models/:name/versions/:version",
+            get(models::get_model_version).delete(models::delete_model_version),
+        )
+        .route(
+            "/v1/models/:name/versions/:version/file",
+            get(models::download_model_version),
+        )
         // Task endpoints
         .route("/v1/tasks", get(routes::list_tasks).post(routes::start_task))
         .route("/v1/tasks/:id", get(routes::get_task).delete(routes::stop_task))
-        .route("/v1/tasks/:id/frames", post(routes::submit_frame))
+        .route(
+            "/v1/tasks/:id/frames",
+            post(routes::submit_frame)
+                .layer(middleware::from_fn_with_state(frame_limit, rate_limit_middleware)),
+        )
+        .route(
+            "/v1/tasks/:id/zones",
+            get(routes::get_task_zones)
+                .put(routes::put_task_zones)
+                .delete(routes::delete_task_zones),
+        )
+        // ONVIF analytics export
+        .route("/v1/tasks/:id/onvif/metadata", get(routes::onvif_metadata))
+        .route("/v1/onvif/topics", get(routes::onvif_topics))
+        .route("/v1/sharding", get(routes::get_sharding))
+        .route("/v1/scheduler", get(routes::get_scheduler))
+        .route("/v1/detections", get(routes::list_detections))
+        .route("/v1/events/ws", get(events::events_ws))
         // Facial recognition endpoints
         .route("/v1/faces", get(routes::list_faces).post(routes::enroll_face))
         .route("/v1/faces/:id", delete(routes::remove_face))
+        .route(
+            "/v1/plugins/facial_recognition/faces",
+            get(faces::list_faces).post(faces::enroll_face),
+        )
+        .route(
+            "/v1/plugins/facial_recognition/faces/:face_id",
+            get(faces::get_face)
+                .patch(faces::update_face)
+                .delete(faces::delete_face),
+        )
+        // LPR plate watchlists
+        .route(
+            "/v1/plugins/lpr/watchlists",
+            get(watchlists::list_watchlists).post(watchlists::create_watchlist),
+        )
+        .route(
+            "/v1/plugins/lpr/watchlists/:list_id",
+            get(watchlists::get_watchlist)
+                .patch(watchlists::update_watchlist)
+                .delete(watchlists::delete_watchlist),
+        )
+        .route(
+            "/v1/plugins/lpr/watchlists/:list_id/plates",
+            post(watchlists::add_watchlist_plates),
+        )
+        .route(
+            "/v1/plugins/lpr/watchlists/:list_id/plates/:plate",
+            delete(watchlists::remove_watchlist_plate),
+        )
+        // Data subject requests, coordinated by auth-service
+        .route("/v1/privacy/export", post(routes::privacy_export))
+        .route("/v1/privacy/erase", post(routes::privacy_erase))
+        // Redacted copies of recordings for disclosure
+        .route(
+            "/v1/anonymizations",
+            get(routes::list_anonymizations).post(routes::start_anonymization),
+        )
+        .route(
+            "/v1/anonymizations/:id",
+            get(routes::get_anonymization).delete(routes::delete_anonymization),
+        )
+        .route("/v1/anonymizations/:id/output", get(routes::download_anonymization))
+        // Forensic re-analysis of stored recordings
+        .route(
+            "/v1/analysis/jobs",
+            get(routes::list_analysis_jobs).post(routes::start_analysis_job),
+        )
+        .route(
+            "/v1/analysis/jobs/:id",
+            get(routes::get_analysis_job).delete(routes::delete_analysis_job),
+        )
+        .route("/v1/analysis/jobs/:id/detections", get(routes::get_analysis_detections))
+        .merge(openapi_routes(&openapi()))
         .layer(TraceLayer::new_for_http())
-        .with_state(state)
+        .with_state(state);
+    api_version::versioned(router, "ai-service", DEPRECATIONS)
+}
+
+/// Deprecated ai-service routes (see `common::api_version`)
+pub const DEPRECATIONS: &[Deprecation] = &[];
+
+/// OpenAPI description of the ai-service API
+pub fn openapi() -> OpenApiSpec {
+    OpenApiSpec::new("ai-service", env!("CARGO_PKG_VERSION"))
+        .with_description("AI plugin registry and task processing")
+        .operations(&[
+            ("GET", "/healthz", "health", "Liveness probe"),
+            ("GET", "/readyz", "health", "Readiness probe (includes plugin health)"),
+            ("GET", "/metrics", "health", "Prometheus metrics"),
+            ("GET", "/api/versions", "health", "Supported API versions and deprecated routes"),
+            ("GET", "/v1/plugins", "plugins", "List registered plugins"),
+            ("GET", "/v1/plugins/:id", "plugins", "Get plugin info"),
+            ("GET", "/v1/plugins/:id/config", "plugins", "Configuration the plugin runs with"),
+            ("PUT", "/v1/plugins/:id/config", "plugins", "Change plugin settings without a restart (merge patch, checked against the config schema)"),
+            ("GET", "/v1/plugins/:id/model", "models", "Registry model version the plugin runs"),
+            ("PUT", "/v1/plugins/:id/model", "models", "Hot-swap the plugin's model to a stored version (name, version, config_key)"),
+            ("GET", "/v1/models", "models", "List models with their versions and active plugins"),
+            ("GET", "/v1/models/:name", "models", "Get a model's versions"),
+            ("POST", "/v1/models/:name", "models", "Upload the next version of a model (multipart file, description, sha256)"),
+            ("GET", "/v1/models/:name/versions/:version", "models", "Get a model version's checksum and size"),
+            ("DELETE", "/v1/models/:name/versions/:version", "models", "Delete a model version no plugin runs"),
+            ("GET", "/v1/models/:name/versions/:version/file", "models", "Download a model version (ONNX)"),
+            ("GET", "/v1/tasks", "tasks", "List AI tasks"),
+            ("POST", "/v1/tasks", "tasks", "Start AI task"),
+            ("GET", "/v1/tasks/:id", "tasks", "Get AI task"),
+            ("DELETE", "/v1/tasks/:id", "tasks", "Stop AI task"),
+            ("POST", "/v1/tasks/:id/frames", "tasks", "Submit frame for processing; responses carry the queue depth and a suggested delay before the next frame"),
+            ("GET", "/v1/tasks/:id/zones", "tasks", "Get a task's zones and tripwires"),
+            ("PUT", "/v1/tasks/:id/zones", "tasks", "Replace a task's zones and tripwires"),
+            ("DELETE", "/v1/tasks/:id/zones", "tasks", "Remove a task's zones and tripwires"),
+            ("GET", "/v1/tasks/:id/onvif/metadata", "onvif", "Stream task results as ONVIF metadata (SSE)"),
+            ("GET", "/v1/onvif/topics", "onvif", "ONVIF event topics of the metadata stream"),
+            ("GET", "/v1/sharding", "tasks", "Camera shard membership of this node"),
+            ("GET", "/v1/detections", "detections", "Stored detections (task_id, camera_id, tenant_id, class, from_ms, to_ms, min_confidence, offset, limit)"),
+            ("GET", "/v1/events/ws", "events", "Live results as AiResult JSON over WebSocket (task_id, plugin, class filters)"),
+            ("GET", "/v1/faces", "faces", "List enrolled faces"),
+            ("POST", "/v1/faces", "faces", "Enroll face"),
+            ("DELETE", "/v1/faces/:id", "faces", "Remove enrolled face"),
+            ("GET", "/v1/plugins/facial_recognition/faces", "faces", "List enrolled faces (without embeddings)"),
+            ("POST", "/v1/plugins/facial_recognition/faces", "faces", "Enroll a face from a multipart image upload (image, name, face_id, metadata)"),
+            ("GET", "/v1/plugins/facial_recognition/faces/:face_id", "faces", "Get enrolled face"),
+            ("PATCH", "/v1/plugins/facial_recognition/faces/:face_id", "faces", "Rename an enrolled face or replace its metadata"),
+            ("DELETE", "/v1/plugins/facial_recognition/faces/:face_id", "faces", "Remove enrolled face"),
+            ("GET", "/v1/plugins/lpr/watchlists", "watchlists", "List plate watchlists"),
+            ("POST", "/v1/plugins/lpr/watchlists", "watchlists", "Create a plate watchlist (name, category, max_edit_distance, plates)"),
+            ("GET", "/v1/plugins/lpr/watchlists/:list_id", "watchlists", "Get a plate watchlist"),
+            ("PATCH", "/v1/plugins/lpr/watchlists/:list_id", "watchlists", "Update a plate watchlist or replace its plates"),
+            ("DELETE", "/v1/plugins/lpr/watchlists/:list_id", "watchlists", "Delete a plate watchlist"),
+            ("POST", "/v1/plugins/lpr/watchlists/:list_id/plates", "watchlists", "Add plates to a watchlist"),
+            ("DELETE", "/v1/plugins/lpr/watchlists/:list_id/plates/:plate", "watchlists", "Remove a plate from a watchlist"),
+            ("POST", "/v1/privacy/export", "privacy", "Export a data subject's face enrollments"),
+            ("POST", "/v1/privacy/erase", "privacy", "Erase a data subject's face enrollments"),
+            ("GET", "/v1/anonymizations", "anonymization", "List anonymization jobs"),
+            ("POST", "/v1/anonymizations", "anonymization", "Blur faces/plates in a recording range"),
+            ("GET", "/v1/anonymizations/:id", "anonymization", "Get anonymization job"),
+            ("DELETE", "/v1/anonymizations/:id", "anonymization", "Delete anonymization job and its copy"),
+            ("GET", "/v1/anonymizations/:id/output", "anonymization", "Download redacted copy (MP4)"),
+            ("GET", "/v1/analysis/jobs", "analysis", "List recording analysis jobs"),
+            ("POST", "/v1/analysis/jobs", "analysis", "Run plugins over a stored recording"),
+            ("GET", "/v1/analysis/jobs/:id", "analysis", "Get recording analysis job"),
+            ("DELETE", "/v1/analysis/jobs/:id", "analysis", "Delete analysis job and its index"),
+            ("GET", "/v1/analysis/jobs/:id/detections", "analysis", "Query the detection index of a job"),
+        ])
 }
@@ -0,0 +1,261 @@
+//! Model registry API at `/v1/models`: upload ONNX models as new versions
+//! (multipart `file`, optional `description` and `sha256`), list and
+//! download them, and put a version into service in a plugin at
+//! `/v1/plugins/:id/model` without restarting the service.
+
+use crate::models::{ModelError, ModelRegistry};
+use crate::state::AiServiceState;
+use axum::{
+    body::Body,
+    extract::{Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+impl IntoResponse for ModelError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ModelError::NotFound(_) => StatusCode::NOT_FOUND,
+            ModelError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ModelError::Conflict(_) => StatusCode::CONFLICT,
+            ModelError::LoadFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ModelError::Internal(e) => {
+                tracing::error!(error = %e, "model registry failure");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        error(status, self.to_string())
+    }
+}
+
+fn registry(state: &AiServiceState) -> Result<&Arc<ModelRegistry>, Response> {
+    state.models().ok_or_else(|| {
+        error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "model registry is disabled (AI_MODEL_DIR)",
+        )
+    })
+}
+
+fn version_not_found(name: &str, version: u32) -> Response {
+    error(
+        StatusCode::NOT_FOUND,
+        format!("Model '{}' has no version {}", name, version),
+    )
+}
+
+/// List models with their versions and the plugins running them
+pub async fn list_models(State(state): State<AiServiceState>) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    Json(json!({ "models": models.list().await })).into_response()
+}
+
+pub async fn get_model(State(state): State<AiServiceState>, Path(name): Path<String>) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    match models.get(&name).await {
+        Some(model) => Json(model).into_response(),
+        None => error(StatusCode::NOT_FOUND, format!("Model '{}' not found", name)),
+    }
+}
+
+/// Upload the next version of a model
+pub async fn upload_model(
+    State(state): State<AiServiceState>,
+    Path(name): Path<String>,
+    mut multipart: Multipart,
+) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    if let Err(e) = crate::models::validate_name(&name) {
+        return e.into_response();
+    }
+
+    let mut staged = None;
+    let mut description = None;
+    let mut sha256 = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                if let Some(staged) = staged.take() {
+                    models.discard(staged).await;
+                }
+                return error(StatusCode::BAD_REQUEST, format!("Invalid multipart body: {}", e));
+            }
+        };
+        match field.name() {
+            Some("file") if staged.is_none() => match models.stage(field).await {
+                Ok(upload) => staged = Some(upload),
+                Err(e) => return e.into_response(),
+            },
+            Some(field_name @ ("description" | "sha256")) => {
+                let field_name = field_name.to_string();
+                let text = match field.text().await {
+                    Ok(text) => text.trim().to_string(),
+                    Err(e) => {
+                        if let Some(staged) = staged.take() {
+                            models.discard(staged).await;
+                        }
+                        return error(
+                            StatusCode::BAD_REQUEST,
+                            format!("Failed to read {}: {}", field_name, e),
+                        );
+                    }
+                };
+                if text.is_empty() {
+                    continue;
+                }
+                if field_name == "sha256" {
+                    sha256 = Some(text);
+                } else {
+                    description = Some(text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(staged) = staged else {
+        return error(StatusCode::BAD_REQUEST, "Missing 'file' field");
+    };
+    match models
+        .add_version(&name, staged, description, sha256.as_deref())
+        .await
+    {
+        Ok(version) => (StatusCode::CREATED, Json(version)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_model_version(
+    State(state): State<AiServiceState>,
+    Path((name, version)): Path<(String, u32)>,
+) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    match models.version(&name, version).await {
+        Some(model) => Json(model).into_response(),
+        None => version_not_found(&name, version),
+    }
+}
+
+/// Download a version's ONNX file
+pub async fn download_model_version(
+    State(state): State<AiServiceState>,
+    Path((name, version)): Path<(String, u32)>,
+) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    let Some(model) = models.version(&name, version).await else {
+        return version_not_found(&name, version);
+    };
+    let Some(path) = models.version_file(&name, version).await else {
+        return version_not_found(&name, version);
+    };
+    match tokio::fs::File::open(&path).await {
+        Ok(file) => (
+            [
+                (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                (header::CONTENT_LENGTH, model.size_bytes.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}-v{}.onnx\"", name, version),
+                ),
+                (header::ETAG, format!("\"{}\"", model.sha256)),
+            ],
+            Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+        )
+            .into_response(),
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to open model file: {}", e),
+        ),
+    }
+}
+
+pub async fn delete_model_version(
+    State(state): State<AiServiceState>,
+    Path((name, version)): Path<(String, u32)>,
+) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    match models.delete_version(&name, version).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivateModelRequest {
+    pub name: String,
+    pub version: u32,
+    /// Plugin setting to put the model in; `model_path` (or
+    /// `detection_model_path`) when absent
+    #[serde(default)]
+    pub config_key: Option<String>,
+}
+
+/// The registry model a plugin runs
+pub async fn get_plugin_model(
+    State(state): State<AiServiceState>,
+    Path(plugin_id): Path<String>,
+) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    match models.active().await.remove(&plugin_id) {
+        Some(active) => Json(active).into_response(),
+        None => error(
+            StatusCode::NOT_FOUND,
+            format!("Plugin '{}' runs no registry model", plugin_id),
+        ),
+    }
+}
+
+/// Hot-swap the model of a plugin to a stored version
+pub async fn set_plugin_model(
+    State(state): State<AiServiceState>,
+    Path(plugin_id): Path<String>,
+    Json(request): Json<ActivateModelRequest>,
+) -> Response {
+    let models = match registry(&state) {
+        Ok(models) => models,
+        Err(response) => return response,
+    };
+    match models
+        .activate(
+            &plugin_id,
+            &request.name,
+            request.version,
+            request.config_key.as_deref(),
+        )
+        .await
+    {
+        Ok(active) => Json(active).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
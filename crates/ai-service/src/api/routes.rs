@@ -1,34 +1,129 @@
+use crate::analysis::{self, AnalysisRequest};
+use crate::anonymize::AnonymizationRequest;
+use crate::detections::DetectionQuery;
+use crate::onvif::{self, PresenceTracker};
 use crate::state::AiServiceState;
 use crate::plugin::facial_recognition::FacialRecognitionPlugin;
+use crate::plugin::{schema, PluginError};
+use crate::scheduler::Shed;
+use crate::sharding::{shard_key, Route, HANDOFF_HEADER, OWNER_HEADER};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
 use common::ai_tasks::{
-    AiTaskStartRequest, AiTaskStartResponse, AiTaskStopResponse, PluginListResponse,
-    VideoFrame,
+    AiTaskStartRequest, AiTaskStartResponse, AiTaskStopResponse, PluginErrorKind,
+    PluginListResponse, TaskZones, VideoFrame,
 };
+use common::privacy::{DataSubject, ErasureOutcome, ServiceExport};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+/// 307 to the same path on the node owning the camera; the method and body
+/// are kept, so submitters only need to follow redirects
+fn redirect_to_owner(node_id: &str, base_url: &str, path: &str) -> Response {
+    (
+        StatusCode::TEMPORARY_REDIRECT,
+        [
+            (header::LOCATION, format!("{}{}", base_url, path)),
+            (header::HeaderName::from_static(OWNER_HEADER), node_id.to_string()),
+        ],
+    )
+        .into_response()
+}
+
+/// 307 to `path` on the node holding a task that does not run here: the
+/// node this one handed it off to, or wherever the StateStore places it.
+/// With work sharing, a local copy left behind after the lease moved to
+/// another node does not count as running here.
+async fn redirect_to_holder(state: &AiServiceState, task_id: &str, path: &str) -> Option<Response> {
+    if state.sharding().is_none() && state.work_sharing().is_none() {
+        return None;
+    }
+    if state.get_task(task_id).await.is_some()
+        && (state.work_sharing().is_none() || state.holds_lease(task_id).await)
+    {
+        return None;
+    }
+
+    let moved = match state.sharding() {
+        Some(sharding) => sharding.moved_to(task_id).await,
+        None => None,
+    };
+    let node_id = match moved {
+        Some(node_id) => node_id,
+        None => state.stored_task_node(task_id).await?,
+    };
+    let mut base_url = None;
+    if let Some(sharding) = state.sharding() {
+        base_url = sharding.member_url(&node_id).await;
+    }
+    if let (None, Some(work_sharing)) = (&base_url, state.work_sharing()) {
+        base_url = work_sharing.member_url(&node_id).await;
+    }
+    Some(redirect_to_owner(&node_id, &base_url?, path))
+}
 
 /// Start a new AI task
 pub async fn start_task(
     State(state): State<AiServiceState>,
+    headers: HeaderMap,
     Json(request): Json<AiTaskStartRequest>,
 ) -> impl IntoResponse {
-    match state
-        .start_task(request.config, request.lease_ttl_secs)
-        .await
-    {
+    // Hand-offs are accepted as sent: the sender already routed them
+    if let Some(sharding) = state.sharding().filter(|_| !headers.contains_key(HANDOFF_HEADER)) {
+        if let Route::Redirect { node_id, base_url } = sharding.route(shard_key(&request.config)).await {
+            return redirect_to_owner(&node_id, &base_url, "/v1/tasks");
+        }
+    }
+
+    // With work sharing a node at its share leaves new tasks to the others,
+    // and leases are kept short so the tasks of a failed node move quickly
+    let queue = match state.work_sharing().filter(|_| !headers.contains_key(HANDOFF_HEADER)) {
+        Some(work_sharing) => work_sharing.should_queue(&state).await,
+        None => false,
+    };
+    if queue {
+        return match state.queue_task(request.config).await {
+            Ok(task_id) => {
+                let response = AiTaskStartResponse {
+                    accepted: true,
+                    lease_id: None,
+                    message: Some(format!("AI task '{}' queued for a less busy node", task_id)),
+                };
+                (StatusCode::OK, Json(response)).into_response()
+            }
+            Err(e) => {
+                tracing::error!("Failed to queue AI task: {}", e);
+                let response = AiTaskStartResponse {
+                    accepted: false,
+                    lease_id: None,
+                    message: Some(format!("Failed to start task: {}", e)),
+                };
+                (StatusCode::BAD_REQUEST, Json(response)).into_response()
+            }
+        };
+    }
+    let lease_ttl_secs = request
+        .lease_ttl_secs
+        .or_else(|| state.work_sharing().map(|w| w.lease_ttl_secs()));
+
+    match state.start_task(request.config, lease_ttl_secs).await {
         Ok(task_id) => {
             let response = AiTaskStartResponse {
                 accepted: true,
                 lease_id: Some(task_id.clone()),
                 message: Some(format!("AI task '{}' started successfully", task_id)),
             };
-            (StatusCode::OK, Json(response))
+            (StatusCode::OK, Json(response)).into_response()
         }
         Err(e) => {
             tracing::error!("Failed to start AI task: {}", e);
@@ -37,7 +132,7 @@ pub async fn start_task(
                 lease_id: None,
                 message: Some(format!("Failed to start task: {}", e)),
             };
-            (StatusCode::BAD_REQUEST, Json(response))
+            (StatusCode::BAD_REQUEST, Json(response)).into_response()
         }
     }
 }
@@ -47,13 +142,17 @@ pub async fn stop_task(
     State(state): State<AiServiceState>,
     Path(task_id): Path<String>,
 ) -> impl IntoResponse {
+    if let Some(redirect) = redirect_to_holder(&state, &task_id, &format!("/v1/tasks/{}", task_id)).await {
+        return redirect;
+    }
+
     match state.stop_task(&task_id).await {
         Ok(_) => {
             let response = AiTaskStopResponse {
                 success: true,
                 message: Some(format!("AI task '{}' stopped successfully", task_id)),
             };
-            (StatusCode::OK, Json(response))
+            (StatusCode::OK, Json(response)).into_response()
         }
         Err(e) => {
             tracing::error!("Failed to stop AI task {}: {}", task_id, e);
@@ -61,7 +160,7 @@ pub async fn stop_task(
                 success: false,
                 message: Some(format!("Failed to stop task: {}", e)),
             };
-            (StatusCode::NOT_FOUND, Json(response))
+            (StatusCode::NOT_FOUND, Json(response)).into_response()
         }
     }
 }
@@ -83,6 +182,59 @@ pub async fn get_task(
     }
 }
 
+/// Zones and tripwires of a task
+pub async fn get_task_zones(
+    State(state): State<AiServiceState>,
+    Path(task_id): Path<String>,
+) -> impl IntoResponse {
+    match state.get_task(&task_id).await {
+        Some(task_info) => (StatusCode::OK, Json(task_info.config.zones.unwrap_or_default())).into_response(),
+        None => task_not_found(&task_id),
+    }
+}
+
+/// Replace the zones and tripwires of a task; objects already in a zone
+/// enter it again
+pub async fn put_task_zones(
+    State(state): State<AiServiceState>,
+    Path(task_id): Path<String>,
+    Json(zones): Json<TaskZones>,
+) -> impl IntoResponse {
+    set_task_zones(&state, &task_id, Some(zones)).await
+}
+
+/// Remove the zones and tripwires of a task
+pub async fn delete_task_zones(
+    State(state): State<AiServiceState>,
+    Path(task_id): Path<String>,
+) -> impl IntoResponse {
+    set_task_zones(&state, &task_id, None).await
+}
+
+async fn set_task_zones(state: &AiServiceState, task_id: &str, zones: Option<TaskZones>) -> Response {
+    match state.set_task_zones(task_id, zones).await {
+        Ok(Some(task_info)) => (StatusCode::OK, Json(task_info.config.zones.unwrap_or_default())).into_response(),
+        Ok(None) => task_not_found(task_id),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!("Invalid zones: {}", e)
+            })),
+        )
+            .into_response(),
+    }
+}
+
+fn task_not_found(task_id: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "error": format!("Task '{}' not found", task_id)
+        })),
+    )
+        .into_response()
+}
+
 /// List all AI tasks
 pub async fn list_tasks(State(state): State<AiServiceState>) -> impl IntoResponse {
     let tasks = state.list_tasks().await;
@@ -118,6 +270,86 @@ pub async fn get_plugin(
     }
 }
 
+/// Configuration a plugin currently runs with
+pub async fn get_plugin_config(
+    State(state): State<AiServiceState>,
+    Path(plugin_id): Path<String>,
+) -> Response {
+    if !state.plugins().has_plugin(&plugin_id).await {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Plugin '{}' not found", plugin_id) })),
+        )
+            .into_response();
+    }
+    match state.plugins().init_config(&plugin_id).await {
+        Some(config) => Json(json!({ "plugin_id": plugin_id, "config": config })).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Plugin '{}' has no configuration", plugin_id) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Merge a partial configuration into the plugin's current one, check it
+/// against the plugin's config schema and apply it without a restart
+pub async fn update_plugin_config(
+    State(state): State<AiServiceState>,
+    Path(plugin_id): Path<String>,
+    Json(patch): Json<serde_json::Value>,
+) -> Response {
+    let plugin = match state.plugins().get(&plugin_id).await {
+        Ok(plugin) => plugin,
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": format!("Plugin '{}' not found", plugin_id) })),
+            )
+                .into_response();
+        }
+    };
+    if !patch.is_object() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Configuration must be a JSON object" })),
+        )
+            .into_response();
+    }
+    let Some(mut config) = state.plugins().init_config(&plugin_id).await else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": format!("Plugin '{}' was registered without a configuration", plugin_id)
+            })),
+        )
+            .into_response();
+    };
+
+    let schema = plugin.read().await.config_schema();
+    schema::merge_patch(&mut config, &patch);
+    if let Some(schema) = schema {
+        let mut errors = schema::unknown_settings(&schema, &patch);
+        errors.extend(schema::validate(&schema, &config));
+        if !errors.is_empty() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Invalid plugin configuration", "details": errors })),
+            )
+                .into_response();
+        }
+    }
+
+    match state.plugins().reconfigure(&plugin_id, config.clone()).await {
+        Ok(()) => Json(json!({ "plugin_id": plugin_id, "config": config })).into_response(),
+        Err(e) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": format!("{:#}", e) })),
+        )
+            .into_response(),
+    }
+}
+
 /// Health check endpoint
 pub async fn healthz() -> impl IntoResponse {
     (
@@ -160,14 +392,58 @@ pub async fn submit_frame(
     Path(task_id): Path<String>,
     Json(frame): Json<VideoFrame>,
 ) -> impl IntoResponse {
-    match state.process_frame(&task_id, frame).await {
-        Ok(result) => (StatusCode::OK, Json(result)).into_response(),
+    // Frames for a task held by another node go to that node
+    let path = format!("/v1/tasks/{}/frames", task_id);
+    if let Some(redirect) = redirect_to_holder(&state, &task_id, &path).await {
+        return redirect;
+    }
+
+    let outcome = state.process_frame(&task_id, frame).await;
+    // Feeders pace the task's next frame by the node's load
+    let backpressure = state.scheduler().map(|scheduler| scheduler.backpressure(&task_id));
+    let headers = backpressure.map(|b| b.to_headers()).unwrap_or_default();
+    match outcome {
+        Ok(result) => (StatusCode::OK, headers, Json(result)).into_response(),
         Err(e) => {
             tracing::error!("Failed to process frame for task {}: {}", task_id, e);
+            // Frames shed on a saturated node can be retried later
+            if let Some(shed) = e.downcast_ref::<Shed>() {
+                let backpressure = backpressure.unwrap_or_default();
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    headers,
+                    [(header::RETRY_AFTER, backpressure.retry_after_secs().to_string())],
+                    Json(json!({
+                        "error": shed.to_string(),
+                        "priority": shed.priority,
+                        "shed_reason": shed.reason.as_str(),
+                        "queue_depth": backpressure.queue_depth,
+                        "next_frame_delay_ms": backpressure.next_frame_delay_ms,
+                    })),
+                )
+                    .into_response();
+            }
+            // Frames failed by the plugin say why; anything else (unknown
+            // task, task not processing) is the caller's
+            let Some(plugin_error) = e.downcast_ref::<PluginError>() else {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({
+                        "error": format!("Failed to process frame: {}", e)
+                    })),
+                )
+                    .into_response();
+            };
+            let status = match plugin_error.kind() {
+                PluginErrorKind::Recoverable => StatusCode::BAD_REQUEST,
+                PluginErrorKind::Config => StatusCode::UNPROCESSABLE_ENTITY,
+                PluginErrorKind::Fatal | PluginErrorKind::Resource => StatusCode::SERVICE_UNAVAILABLE,
+            };
             (
-                StatusCode::BAD_REQUEST,
+                status,
                 Json(json!({
-                    "error": format!("Failed to process frame: {}", e)
+                    "error": format!("Failed to process frame: {}", plugin_error),
+                    "error_kind": plugin_error.kind(),
                 })),
             )
                 .into_response()
@@ -175,8 +451,117 @@ pub async fn submit_frame(
     }
 }
 
+/// Stream a task's results as ONVIF `tt:MetadataStream` documents, one
+/// `metadata` server-sent event per processed frame
+pub async fn onvif_metadata(
+    State(state): State<AiServiceState>,
+    Path(task_id): Path<String>,
+) -> impl IntoResponse {
+    if state.get_task(&task_id).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": format!("Task '{}' not found", task_id)
+            })),
+        )
+            .into_response();
+    }
+
+    let receiver = state.metadata().subscribe();
+    let stream = futures::stream::unfold(
+        (receiver, PresenceTracker::default()),
+        move |(mut receiver, mut tracker)| {
+            let task_id = task_id.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(frame) if frame.result.task_id == task_id => {
+                            let changes = tracker.update(&frame.result);
+                            let event = Event::default()
+                                .event("metadata")
+                                .data(onvif::render(&frame, &changes));
+                            return Some((Ok::<_, Infallible>(event), (receiver, tracker)));
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(skipped)) => {
+                            tracing::warn!(task_id = %task_id, skipped, "ONVIF metadata subscriber lagging, frames skipped");
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        },
+    );
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Event topics carried in the ONVIF metadata stream
+pub async fn onvif_topics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/xml")],
+        onvif::topic_set(),
+    )
+}
+
+/// Shard membership as seen by this node
+pub async fn get_sharding(State(state): State<AiServiceState>) -> impl IntoResponse {
+    match state.sharding() {
+        Some(sharding) => (StatusCode::OK, Json(json!(sharding.status().await))),
+        None => (StatusCode::OK, Json(json!({ "enabled": false }))),
+    }
+}
+
+/// Inference slots and waiting frames per task
+pub async fn get_scheduler(State(state): State<AiServiceState>) -> impl IntoResponse {
+    match state.scheduler() {
+        Some(scheduler) => (StatusCode::OK, Json(json!(scheduler.status()))),
+        None => (StatusCode::OK, Json(json!({ "enabled": false }))),
+    }
+}
+
+/// Stored detections, newest first, filtered by task, camera, tenant,
+/// classes, time range (Unix milliseconds) and confidence
+pub async fn list_detections(
+    State(state): State<AiServiceState>,
+    Query(query): Query<DetectionQuery>,
+) -> Response {
+    if let Err(e) = query.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response();
+    }
+    let Some(recorder) = state.detections() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "detection history is disabled" })),
+        )
+            .into_response();
+    };
+    match recorder.store().query(&query).await {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to query detections");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": format!("Failed to query detections: {}", e) })),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Metrics endpoint (Prometheus format)
-pub async fn metrics() -> impl IntoResponse {
+pub async fn metrics(State(state): State<AiServiceState>) -> impl IntoResponse {
+    state.export_gpu_metrics().await;
+    for health in common::resilient_http::target_health().await {
+        telemetry::metrics::set_upstream_health(
+            &health.target,
+            health.circuit.as_gauge(),
+            i64::from(health.consecutive_failures),
+            i64::try_from(health.last_latency_ms).unwrap_or(i64::MAX),
+            &health.event_totals(),
+        );
+    }
     use prometheus::Encoder;
     let encoder = prometheus::TextEncoder::new();
     let metric_families = telemetry::metrics::REGISTRY.gather();
@@ -430,3 +815,221 @@ pub async fn list_faces(State(state): State<AiServiceState>) -> impl IntoRespons
             .into_response(),
     }
 }
+
+/// Run `f` on the facial recognition plugin, or `None` when it is not
+/// registered (then no faces are enrolled)
+async fn with_face_plugin<T>(
+    state: &AiServiceState,
+    f: impl FnOnce(&FacialRecognitionPlugin) -> anyhow::Result<T>,
+) -> anyhow::Result<Option<T>> {
+    let Ok(plugin) = state.plugins().get("facial_recognition").await else {
+        return Ok(None);
+    };
+    let plugin = plugin.read().await;
+    let face_plugin = plugin
+        .as_any()
+        .downcast_ref::<FacialRecognitionPlugin>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to access facial recognition plugin"))?;
+    f(face_plugin).map(Some)
+}
+
+/// Everything held about a data subject: their face enrollments
+pub async fn privacy_export(
+    State(state): State<AiServiceState>,
+    Json(subject): Json<DataSubject>,
+) -> impl IntoResponse {
+    if let Err(e) = subject.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response();
+    }
+
+    let faces = with_face_plugin(&state, |plugin| plugin.faces_of(&subject)).await;
+    let mut export = ServiceExport::default();
+    match faces.and_then(|faces| export.add("face_enrollments", &faces.unwrap_or_default())) {
+        Ok(()) => (StatusCode::OK, Json(export)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to export subject data: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Remove a data subject's face enrollments
+pub async fn privacy_erase(
+    State(state): State<AiServiceState>,
+    Json(subject): Json<DataSubject>,
+) -> impl IntoResponse {
+    if let Err(e) = subject.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response();
+    }
+
+    match with_face_plugin(&state, |plugin| plugin.erase_subject(&subject)).await {
+        Ok(removed) => {
+            let removed = removed.unwrap_or(0);
+            tracing::info!(tenant_id = %subject.tenant_id, removed, "erased data subject face enrollments");
+            let outcome = ErasureOutcome::default().deleted("face_enrollments", removed);
+            (StatusCode::OK, Json(outcome)).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to erase subject data: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Queue a redacted copy of a recording range
+pub async fn start_anonymization(
+    State(state): State<AiServiceState>,
+    Json(request): Json<AnonymizationRequest>,
+) -> impl IntoResponse {
+    match state.anonymizer().submit(request).await {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Failed to start anonymization: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_anonymizations(State(state): State<AiServiceState>) -> impl IntoResponse {
+    Json(state.anonymizer().list().await)
+}
+
+pub async fn get_anonymization(
+    State(state): State<AiServiceState>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    match state.anonymizer().get(&job_id).await {
+        Some(job) => (StatusCode::OK, Json(job)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Anonymization '{}' not found", job_id) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn delete_anonymization(
+    State(state): State<AiServiceState>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    match state.anonymizer().remove(&job_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Anonymization '{}' not found", job_id) })),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::CONFLICT, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+/// Download the redacted copy of a completed job
+pub async fn download_anonymization(
+    State(state): State<AiServiceState>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    let Some(path) = state.anonymizer().output(&job_id).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("No completed anonymization '{}'", job_id) })),
+        )
+            .into_response();
+    };
+    match tokio::fs::File::open(&path).await {
+        Ok(file) => (
+            [
+                (header::CONTENT_TYPE, "video/mp4".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"redacted-{}.mp4\"", job_id),
+                ),
+            ],
+            Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to open redacted copy: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Queue a plugin run over a stored recording
+pub async fn start_analysis_job(
+    State(state): State<AiServiceState>,
+    Json(request): Json<AnalysisRequest>,
+) -> impl IntoResponse {
+    match state.analyzer().submit(request).await {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Failed to start analysis: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_analysis_jobs(State(state): State<AiServiceState>) -> impl IntoResponse {
+    Json(state.analyzer().list().await)
+}
+
+pub async fn get_analysis_job(
+    State(state): State<AiServiceState>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    match state.analyzer().get(&job_id).await {
+        Some(job) => (StatusCode::OK, Json(job)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Analysis job '{}' not found", job_id) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn delete_analysis_job(
+    State(state): State<AiServiceState>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    match state.analyzer().remove(&job_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Analysis job '{}' not found", job_id) })),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::CONFLICT, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+/// Detections indexed by a job so far, filtered by plugin, class,
+/// confidence and offset range
+pub async fn get_analysis_detections(
+    State(state): State<AiServiceState>,
+    Path(job_id): Path<String>,
+    Query(query): Query<analysis::DetectionQuery>,
+) -> impl IntoResponse {
+    match state.analyzer().detections(&job_id, &query).await {
+        Ok(Some(detections)) => Json(json!({
+            "job_id": job_id,
+            "offset": query.offset,
+            "count": detections.len(),
+            "detections": detections,
+        }))
+        .into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Analysis job '{}' not found", job_id) })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to read detections: {}", e) })),
+        )
+            .into_response(),
+    }
+}
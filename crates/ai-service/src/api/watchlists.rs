@@ -0,0 +1,192 @@
+//! Plate watchlists of the LPR plugin at `/v1/plugins/lpr/watchlists`:
+//! create, list, get, update and delete lists, and add or remove single
+//! plates. Plates are stored normalized (uppercase letters and digits).
+
+use crate::plugin::lpr::LprPlugin;
+use crate::plugin::lpr_watchlist::{self, NewWatchlist, Watchlist, WatchlistUpdate, Watchlists};
+use crate::state::AiServiceState;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+const PLUGIN_ID: &str = "lpr";
+
+/// Plates to add to a watchlist
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchlistPlates {
+    pub plates: Vec<String>,
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+fn watchlist_not_found(list_id: &str) -> Response {
+    error(StatusCode::NOT_FOUND, format!("Watchlist '{}' not found", list_id))
+}
+
+/// The watchlists of the registered LPR plugin; 404 when it is not
+/// registered
+async fn watchlists(state: &AiServiceState) -> Result<Watchlists, Response> {
+    let plugin = state.plugins().get(PLUGIN_ID).await.map_err(|e| {
+        error(
+            StatusCode::NOT_FOUND,
+            format!("LPR plugin not available: {}", e),
+        )
+    })?;
+    let plugin = plugin.read().await;
+    plugin
+        .as_any()
+        .downcast_ref::<LprPlugin>()
+        .map(|lpr| lpr.watchlists().clone())
+        .ok_or_else(|| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to access LPR plugin",
+            )
+        })
+}
+
+fn list_response(list: anyhow::Result<Option<Watchlist>>, list_id: &str) -> Response {
+    match list {
+        Ok(Some(list)) => Json(list).into_response(),
+        Ok(None) => watchlist_not_found(list_id),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// List watchlists
+pub async fn list_watchlists(State(state): State<AiServiceState>) -> Response {
+    let watchlists = match watchlists(&state).await {
+        Ok(watchlists) => watchlists,
+        Err(response) => return response,
+    };
+    match watchlists.list() {
+        Ok(lists) => Json(json!({ "count": lists.len(), "watchlists": lists })).into_response(),
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list watchlists: {}", e),
+        ),
+    }
+}
+
+/// Create a watchlist; `list_id` is generated when missing. An existing
+/// list_id is not replaced.
+pub async fn create_watchlist(
+    State(state): State<AiServiceState>,
+    Json(new): Json<NewWatchlist>,
+) -> Response {
+    if let Err(e) = new.validate() {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let watchlists = match watchlists(&state).await {
+        Ok(watchlists) => watchlists,
+        Err(response) => return response,
+    };
+    let list_id = new.list_id.clone();
+    match watchlists.create(new) {
+        Ok(Some(list)) => {
+            tracing::info!(list_id = %list.list_id, plates = list.plates.len(), "created plate watchlist");
+            (StatusCode::CREATED, Json(list)).into_response()
+        }
+        Ok(None) => error(
+            StatusCode::CONFLICT,
+            format!("Watchlist '{}' already exists", list_id.unwrap_or_default()),
+        ),
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to create watchlist: {}", e),
+        ),
+    }
+}
+
+/// Get a watchlist with its plates
+pub async fn get_watchlist(
+    State(state): State<AiServiceState>,
+    Path(list_id): Path<String>,
+) -> Response {
+    match watchlists(&state).await {
+        Ok(watchlists) => list_response(watchlists.get(&list_id), &list_id),
+        Err(response) => response,
+    }
+}
+
+/// Rename a watchlist, change its category or edit distance, or replace
+/// its plates
+pub async fn update_watchlist(
+    State(state): State<AiServiceState>,
+    Path(list_id): Path<String>,
+    Json(update): Json<WatchlistUpdate>,
+) -> Response {
+    if let Err(e) = update.validate() {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    match watchlists(&state).await {
+        Ok(watchlists) => list_response(watchlists.update(&list_id, update), &list_id),
+        Err(response) => response,
+    }
+}
+
+/// Delete a watchlist
+pub async fn delete_watchlist(
+    State(state): State<AiServiceState>,
+    Path(list_id): Path<String>,
+) -> Response {
+    let watchlists = match watchlists(&state).await {
+        Ok(watchlists) => watchlists,
+        Err(response) => return response,
+    };
+    match watchlists.delete(&list_id) {
+        Ok(true) => {
+            tracing::info!(list_id = %list_id, "deleted plate watchlist");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => watchlist_not_found(&list_id),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Add plates to a watchlist
+pub async fn add_watchlist_plates(
+    State(state): State<AiServiceState>,
+    Path(list_id): Path<String>,
+    Json(body): Json<WatchlistPlates>,
+) -> Response {
+    if let Err(e) = lpr_watchlist::validate_plates(&body.plates) {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let watchlists = match watchlists(&state).await {
+        Ok(watchlists) => watchlists,
+        Err(response) => return response,
+    };
+    match watchlists.add_plates(&list_id, &body.plates) {
+        Ok(Some(list)) => Json(list).into_response(),
+        Ok(None) => watchlist_not_found(&list_id),
+        Err(e) => error(StatusCode::BAD_REQUEST, e.to_string()),
+    }
+}
+
+/// Remove a plate from a watchlist
+pub async fn remove_watchlist_plate(
+    State(state): State<AiServiceState>,
+    Path((list_id, plate)): Path<(String, String)>,
+) -> Response {
+    let watchlists = match watchlists(&state).await {
+        Ok(watchlists) => watchlists,
+        Err(response) => return response,
+    };
+    match watchlists.remove_plate(&list_id, &plate) {
+        Ok(Some(true)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Some(false)) => error(
+            StatusCode::NOT_FOUND,
+            format!("Plate '{}' is not on watchlist '{}'", plate, list_id),
+        ),
+        Ok(None) => watchlist_not_found(&list_id),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
@@ -0,0 +1,374 @@
+//! Micro-batching of frames for plugins with batched inference.
+//!
+//! Frames for a plugin that reports a `max_batch_size` above one are queued
+//! per plugin instead of being run one by one. A worker per plugin takes the
+//! frames waiting in its queue (up to the batch size), optionally waits
+//! `max_wait` for more, and runs them through `AiPlugin::process_batch` as
+//! one inference. Frames of all tasks using the plugin share the queue, so
+//! many cameras on one GPU model fill batches even though every task sends
+//! its frames one at a time.
+//!
+//! With `max_wait` at zero nothing is delayed: a frame arriving while the
+//! plugin is idle runs alone, and frames arriving while a batch is in flight
+//! are taken together once it finishes.
+
+use crate::plugin::{AiPlugin, PluginError};
+use anyhow::{anyhow, Result};
+use common::ai_tasks::{AiResult, VideoFrame};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tracing::warn;
+
+/// Frames queued per plugin before submitters wait
+const QUEUE_DEPTH: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// Upper bound on any plugin's batch size; 1 disables batching
+    pub max_batch_size: usize,
+    /// How long a worker waits for a batch to fill once it has frames
+    pub max_wait: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 8,
+            max_wait: Duration::ZERO,
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Reads `AI_BATCH_MAX_SIZE` and `AI_BATCH_MAX_WAIT_MS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_batch_size: std::env::var("AI_BATCH_MAX_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|size| *size >= 1)
+                .unwrap_or(defaults.max_batch_size),
+            max_wait: std::env::var("AI_BATCH_MAX_WAIT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_wait),
+        }
+    }
+}
+
+struct BatchJob {
+    frame: VideoFrame,
+    reply: oneshot::Sender<Result<AiResult, PluginError>>,
+}
+
+/// A plugin's queue, with the plugin instance its worker runs
+type PluginQueue = (Arc<RwLock<dyn AiPlugin>>, mpsc::Sender<BatchJob>);
+
+/// Per-plugin frame queues feeding batched inference
+pub struct FrameBatcher {
+    config: BatchConfig,
+    queues: Mutex<HashMap<String, PluginQueue>>,
+}
+
+impl FrameBatcher {
+    pub fn new(config: BatchConfig) -> Self {
+        Self {
+            config,
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &BatchConfig {
+        &self.config
+    }
+
+    /// Batch size used for a plugin supporting `plugin_max` frames at once
+    pub fn batch_size(&self, plugin_max: usize) -> usize {
+        plugin_max.min(self.config.max_batch_size).max(1)
+    }
+
+    /// Queue a frame for the plugin's next batch and wait for its result
+    pub async fn submit(
+        &self,
+        plugin_id: &str,
+        plugin: Arc<RwLock<dyn AiPlugin>>,
+        batch_size: usize,
+        frame: VideoFrame,
+    ) -> Result<AiResult> {
+        let queue = self.queue(plugin_id, plugin, batch_size)?;
+        let (reply, response) = oneshot::channel();
+        queue
+            .send(BatchJob { frame, reply })
+            .await
+            .map_err(|_| anyhow!("batch worker for plugin '{}' stopped", plugin_id))?;
+        response
+            .await
+            .map_err(|_| anyhow!("batch worker for plugin '{}' stopped", plugin_id))?
+            .map_err(anyhow::Error::from)
+    }
+
+    fn queue(
+        &self,
+        plugin_id: &str,
+        plugin: Arc<RwLock<dyn AiPlugin>>,
+        batch_size: usize,
+    ) -> Result<mpsc::Sender<BatchJob>> {
+        let mut queues = self
+            .queues
+            .lock()
+            .map_err(|_| anyhow!("batch queues poisoned"))?;
+        // A plugin replaced in the registry gets a new worker
+        if let Some((_, queue)) = queues
+            .get(plugin_id)
+            .filter(|(running, queue)| Arc::ptr_eq(running, &plugin) && !queue.is_closed())
+        {
+            return Ok(queue.clone());
+        }
+        let (queue, receiver) = mpsc::channel(QUEUE_DEPTH);
+        tokio::spawn(run_worker(
+            plugin_id.to_string(),
+            plugin.clone(),
+            batch_size,
+            self.config.max_wait,
+            receiver,
+        ));
+        queues.insert(plugin_id.to_string(), (plugin, queue.clone()));
+        Ok(queue)
+    }
+}
+
+async fn run_worker(
+    plugin_id: String,
+    plugin: Arc<RwLock<dyn AiPlugin>>,
+    batch_size: usize,
+    max_wait: Duration,
+    mut receiver: mpsc::Receiver<BatchJob>,
+) {
+    while let Some(first) = receiver.recv().await {
+        let mut jobs = vec![first];
+        collect(&mut receiver, &mut jobs, batch_size, max_wait).await;
+        let (frames, replies): (Vec<VideoFrame>, Vec<_>) =
+            jobs.into_iter().map(|job| (job.frame, job.reply)).unzip();
+
+        telemetry::metrics::AI_SERVICE_BATCH_SIZE
+            .with_label_values(&[&plugin_id])
+            .observe(frames.len() as f64);
+        let plugin = plugin.read().await;
+        let results = match plugin.process_batch(&frames).await {
+            Ok(results) if results.len() == frames.len() => results.into_iter().map(Ok).collect(),
+            outcome => {
+                if let Err(e) = &outcome {
+                    warn!(plugin = %plugin_id, frames = frames.len(), "Batch failed: {:#}", e);
+                } else {
+                    warn!(plugin = %plugin_id, "Batch returned the wrong number of results");
+                }
+                // Retry frame by frame so one bad frame only fails itself
+                let mut results = Vec::with_capacity(frames.len());
+                for frame in &frames {
+                    results.push(
+                        plugin
+                            .process_frame(frame)
+                            .await
+                            .map_err(|e| PluginError::from_anyhow(&e)),
+                    );
+                }
+                results
+            }
+        };
+        drop(plugin);
+
+        for (reply, result) in replies.into_iter().zip(results) {
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Add the frames already queued, then those arriving within `max_wait`,
+/// until the batch is full
+async fn collect(
+    receiver: &mut mpsc::Receiver<BatchJob>,
+    jobs: &mut Vec<BatchJob>,
+    batch_size: usize,
+    max_wait: Duration,
+) {
+    while jobs.len() < batch_size {
+        match receiver.try_recv() {
+            Ok(job) => jobs.push(job),
+            Err(_) => break,
+        }
+    }
+    if max_wait.is_zero() {
+        return;
+    }
+    let deadline = tokio::time::Instant::now() + max_wait;
+    while jobs.len() < batch_size {
+        match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(job)) => jobs.push(job),
+            _ => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// Records batch sizes; frames with `format` "bad" or "oom" fail
+    #[derive(Default)]
+    struct BatchingPlugin {
+        batches: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl AiPlugin for BatchingPlugin {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+
+        fn id(&self) -> &'static str {
+            "batching"
+        }
+
+        fn name(&self) -> &'static str {
+            "Batching"
+        }
+
+        fn description(&self) -> &'static str {
+            "Batches frames"
+        }
+
+        fn version(&self) -> &'static str {
+            "1.0.0"
+        }
+
+        fn max_batch_size(&self) -> usize {
+            4
+        }
+
+        async fn init(&mut self, _config: serde_json::Value) -> Result<()> {
+            Ok(())
+        }
+
+        async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+            match frame.format.as_str() {
+                "bad" => return Err(PluginError::recoverable("cannot decode frame").into()),
+                "oom" => return Err(PluginError::inference("CUDA failure 2: out of memory").into()),
+                _ => {}
+            }
+            Ok(AiResult {
+                task_id: frame.source_id.clone(),
+                timestamp: frame.timestamp,
+                plugin_type: "batching".to_string(),
+                detections: vec![],
+                confidence: None,
+                processing_time_ms: None,
+                metadata: None,
+            })
+        }
+
+        async fn process_batch(&self, frames: &[VideoFrame]) -> Result<Vec<AiResult>> {
+            self.batches.lock().unwrap().push(frames.len());
+            // Let the other submissions queue up behind this batch
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let mut results = Vec::new();
+            for frame in frames {
+                results.push(self.process_frame(frame).await?);
+            }
+            Ok(results)
+        }
+    }
+
+    fn frame(camera: &str, format: &str) -> VideoFrame {
+        VideoFrame {
+            source_id: camera.to_string(),
+            timestamp: 0,
+            sequence: 0,
+            width: 640,
+            height: 480,
+            format: format.to_string(),
+            data: String::new(),
+            audio: None,
+        }
+    }
+
+    async fn submit_all(
+        batcher: &Arc<FrameBatcher>,
+        plugin: &Arc<RwLock<BatchingPlugin>>,
+        frames: Vec<VideoFrame>,
+    ) -> Vec<Result<AiResult>> {
+        let submissions = frames.into_iter().map(|frame| {
+            let batcher = batcher.clone();
+            let plugin: Arc<RwLock<dyn AiPlugin>> = plugin.clone();
+            tokio::spawn(async move { batcher.submit("batching", plugin, 4, frame).await })
+        });
+        let mut results = Vec::new();
+        for submission in submissions.collect::<Vec<_>>() {
+            results.push(submission.await.unwrap());
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn batches_frames_of_several_cameras() {
+        let batcher = Arc::new(FrameBatcher::new(BatchConfig {
+            max_batch_size: 8,
+            max_wait: Duration::from_millis(50),
+        }));
+        assert_eq!(batcher.batch_size(4), 4);
+        assert_eq!(batcher.batch_size(32), 8);
+        assert_eq!(batcher.batch_size(0), 1);
+
+        let plugin = Arc::new(RwLock::new(BatchingPlugin::default()));
+        let frames = (0..6)
+            .map(|i| frame(&format!("cam-{}", i), "jpeg"))
+            .collect();
+        let results = submit_all(&batcher, &plugin, frames).await;
+
+        // Every frame gets its own result back
+        for (i, result) in results.into_iter().enumerate() {
+            assert_eq!(result.unwrap().task_id, format!("cam-{}", i));
+        }
+        let batches = plugin.read().await.batches.lock().unwrap().clone();
+        assert_eq!(batches.iter().sum::<usize>(), 6);
+        assert!(batches.iter().all(|size| *size <= 4));
+        assert!(batches.len() < 6);
+    }
+
+    #[tokio::test]
+    async fn a_bad_frame_fails_only_itself() {
+        let batcher = Arc::new(FrameBatcher::new(BatchConfig {
+            max_batch_size: 8,
+            max_wait: Duration::from_millis(50),
+        }));
+        let plugin = Arc::new(RwLock::new(BatchingPlugin::default()));
+        let frames = vec![
+            frame("cam-1", "jpeg"),
+            frame("cam-2", "bad"),
+            frame("cam-3", "jpeg"),
+            frame("cam-4", "oom"),
+        ];
+        let results = submit_all(&batcher, &plugin, frames).await;
+
+        assert!(results[0].is_ok());
+        assert!(results[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("cannot decode"));
+        assert!(results[2].is_ok());
+        // The error kind survives the trip back from the worker
+        assert_eq!(
+            PluginError::kind_of(results[3].as_ref().unwrap_err()),
+            common::ai_tasks::PluginErrorKind::Resource
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Zones and alarm classes operators set up per camera.
+//!
+//! operator-ui saves them through admin-gateway into the central
+//! configuration document `camera-analytics`
+//! ([`CameraAnalyticsDocument`], one entry per camera ID). With
+//! `CONFIG_SYNC_ENABLED`, [`CameraAnalyticsSync`] follows the document and
+//! applies each camera's entry to the tasks whose `source_stream_id` is the
+//! camera: to running tasks when a version is published, and to new tasks
+//! as they start. An entry replaces the zones and alarm classes of the
+//! camera's tasks (tripwires only reach tasks with tracking); removing it
+//! clears them. Tasks of cameras that never had an entry keep their own.
+
+use common::ai_tasks::{AiTaskConfig, CameraAnalytics, CameraAnalyticsDocument};
+use common::service_config::ConfigWatcher;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::state::AiServiceState;
+
+#[derive(Default)]
+pub struct CameraAnalyticsSync {
+    document: std::sync::RwLock<CameraAnalyticsDocument>,
+}
+
+impl CameraAnalyticsSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn camera(&self, camera_id: &str) -> Option<CameraAnalytics> {
+        let document = self.document.read().unwrap_or_else(|e| e.into_inner());
+        document.cameras.get(camera_id).cloned()
+    }
+
+    /// Give a task about to start its camera's zones and alarm classes
+    pub fn apply(&self, config: &mut AiTaskConfig) {
+        let Some(camera) = config.source_stream_id.as_deref().and_then(|id| self.camera(id)) else {
+            return;
+        };
+        config.zones = Some(camera.task_zones(config.tracking.is_some())).filter(|z| !z.is_empty());
+        config.alarm_classes = camera.alarm_classes;
+    }
+
+    /// Take a new version of the document; returns the cameras whose entry
+    /// changed, with `None` for removed ones
+    fn update(&self, next: CameraAnalyticsDocument) -> Vec<(String, Option<CameraAnalytics>)> {
+        let mut document = self.document.write().unwrap_or_else(|e| e.into_inner());
+        let removed = document
+            .cameras
+            .keys()
+            .filter(|id| !next.cameras.contains_key(*id))
+            .map(|id| (id.clone(), None));
+        let changed = next
+            .cameras
+            .iter()
+            .filter(|(id, analytics)| document.cameras.get(*id) != Some(*analytics))
+            .map(|(id, analytics)| (id.clone(), Some(analytics.clone())));
+        let changes = removed.chain(changed).collect();
+        *document = next;
+        changes
+    }
+
+    /// Apply every version of the `camera-analytics` document to the tasks
+    /// on this node; invalid versions are skipped
+    pub fn follow(self: Arc<Self>, watcher: ConfigWatcher, state: AiServiceState) {
+        let (mut configs, _) = watcher.spawn();
+        tokio::spawn(async move {
+            while configs.changed().await.is_ok() {
+                let Some(config) = configs.borrow_and_update().clone() else {
+                    continue;
+                };
+                let document = serde_json::from_value::<CameraAnalyticsDocument>(config.document)
+                    .map_err(|e| e.to_string())
+                    .and_then(|document| document.validate().map(|_| document));
+                let document = match document {
+                    Ok(document) => document,
+                    Err(e) => {
+                        warn!(version = config.version, error = %e, "ignoring invalid camera analytics");
+                        continue;
+                    }
+                };
+                let changes = self.update(document);
+                info!(version = config.version, cameras = changes.len(), "camera analytics updated");
+                for (camera_id, analytics) in changes {
+                    state.apply_camera_analytics(&camera_id, analytics.as_ref()).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::{AiFrameConfig, AiOutputConfig, TrackingConfig};
+    use serde_json::json;
+
+    fn document(value: serde_json::Value) -> CameraAnalyticsDocument {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn config(camera: &str, tracking: bool) -> AiTaskConfig {
+        AiTaskConfig {
+            id: "task-1".into(),
+            plugin_type: "yolov8_detector".into(),
+            source_stream_id: Some(camera.into()),
+            source_recording_id: None,
+            model_config: serde_json::Value::Null,
+            secondary_plugins: Vec::new(),
+            pipeline: None,
+            classes: None,
+            tracking: tracking.then(TrackingConfig::default),
+            zones: None,
+            dedup: None,
+            alarm_classes: Vec::new(),
+            schedule: None,
+            frame_config: AiFrameConfig::default(),
+            output: AiOutputConfig {
+                output_type: "webhook".into(),
+                config: serde_json::Value::Null,
+            },
+        }
+    }
+
+    fn dock() -> serde_json::Value {
+        json!({
+            "zones": [{"id": "dock", "polygon": [{"x": 0, "y": 0}, {"x": 10, "y": 0}, {"x": 10, "y": 10}]}],
+            "tripwires": [{"id": "gate", "from": {"x": 0, "y": 5}, "to": {"x": 10, "y": 5}}],
+            "alarm_classes": ["person"]
+        })
+    }
+
+    #[test]
+    fn updates_report_changed_and_removed_cameras() {
+        let sync = CameraAnalyticsSync::new();
+        let changes = sync.update(document(json!({"cam-1": dock(), "cam-2": {}})));
+        assert_eq!(changes.len(), 2);
+
+        let changes = sync.update(document(json!({"cam-1": dock(), "cam-3": {"alarm_classes": ["car"]}})));
+        let ids: Vec<_> = changes.iter().map(|(id, a)| (id.as_str(), a.is_some())).collect();
+        assert_eq!(ids, vec![("cam-2", false), ("cam-3", true)]);
+    }
+
+    #[test]
+    fn starting_tasks_get_their_cameras_analytics() {
+        let sync = CameraAnalyticsSync::new();
+        sync.update(document(json!({"cam-1": dock()})));
+
+        let mut tracked = config("cam-1", true);
+        sync.apply(&mut tracked);
+        let zones = tracked.zones.unwrap();
+        assert_eq!((zones.zones.len(), zones.tripwires.len()), (1, 1));
+        assert_eq!(tracked.alarm_classes, vec!["person".to_string()]);
+
+        let mut untracked = config("cam-1", false);
+        sync.apply(&mut untracked);
+        assert!(untracked.zones.unwrap().tripwires.is_empty());
+
+        let mut other = config("cam-9", false);
+        sync.apply(&mut other);
+        assert!(other.zones.is_none() && other.alarm_classes.is_empty());
+    }
+}
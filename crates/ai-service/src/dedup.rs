@@ -0,0 +1,288 @@
+//! Deduplication of detections of objects that stay in view.
+//!
+//! A parked car or a person standing at a counter is detected in every
+//! frame, and each of those detections would otherwise raise an alert, be
+//! written to the database and land on the timeline. A [`Deduplicator`] per
+//! task remembers the objects it reported and drops later detections of the
+//! same object until `window_secs` after the report; an object still there
+//! then is reported again. Tracked detections are the same object when they
+//! share the track ID, others when they are of the same class and overlap
+//! the reported box by `iou_threshold`.
+//!
+//! Objects not seen for a whole window are forgotten, so an object leaving
+//! and coming back is reported on its return. The result keeps the count of
+//! dropped detections under `metadata.dedup.suppressed`.
+
+use anyhow::{bail, Result};
+use common::ai_tasks::{AiResult, BoundingBox, DedupConfig, Detection};
+
+/// Check a task's dedup settings before it starts
+pub fn validate(config: &DedupConfig) -> Result<()> {
+    if !(config.window_secs.is_finite() && config.window_secs > 0.0) {
+        bail!("dedup.window_secs must be above 0");
+    }
+    if !(config.iou_threshold > 0.0 && config.iou_threshold <= 1.0) {
+        bail!("dedup.iou_threshold must be in (0, 1]");
+    }
+    Ok(())
+}
+
+fn iou(a: &BoundingBox, b: &BoundingBox) -> f32 {
+    let overlap_w = (a.x + a.width).min(b.x + b.width).saturating_sub(a.x.max(b.x));
+    let overlap_h = (a.y + a.height).min(b.y + b.height).saturating_sub(a.y.max(b.y));
+    let intersection = overlap_w as f32 * overlap_h as f32;
+    if intersection == 0.0 {
+        return 0.0;
+    }
+    let union = a.width as f32 * a.height as f32 + b.width as f32 * b.height as f32 - intersection;
+    intersection / union
+}
+
+fn track_id(detection: &Detection) -> Option<u64> {
+    detection.metadata.as_ref()?.get("track_id")?.as_u64()
+}
+
+/// An object reported within the window
+struct Reported {
+    class: String,
+    track_id: Option<u64>,
+    /// Where it was last seen, so a slowly drifting box still matches
+    bbox: BoundingBox,
+    reported_at: u64,
+    last_seen: u64,
+}
+
+impl Reported {
+    fn matches(&self, detection: &Detection, track_id: Option<u64>, iou_threshold: f32) -> bool {
+        if self.class != detection.class {
+            return false;
+        }
+        match (self.track_id, track_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => iou(&self.bbox, &detection.bbox) >= iou_threshold,
+        }
+    }
+}
+
+/// Objects one task reported recently
+pub struct Deduplicator {
+    config: DedupConfig,
+    window_ms: u64,
+    reported: Vec<Reported>,
+}
+
+impl Deduplicator {
+    pub fn new(config: DedupConfig) -> Self {
+        Self {
+            window_ms: (config.window_secs as f64 * 1000.0) as u64,
+            config,
+            reported: Vec::new(),
+        }
+    }
+
+    /// Objects currently remembered
+    pub fn len(&self) -> usize {
+        self.reported.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reported.is_empty()
+    }
+
+    /// Drop the result's detections of objects reported within the window
+    /// and return how many were dropped
+    pub fn update(&mut self, result: &mut AiResult) -> usize {
+        let now = result.timestamp;
+        let window_ms = self.window_ms;
+        self.reported.retain(|r| now.saturating_sub(r.last_seen) < window_ms);
+
+        let deduplicated = |class: &str| {
+            self.config.classes.is_empty() || self.config.classes.iter().any(|c| c == class)
+        };
+        let before = result.detections.len();
+        let mut matched = vec![false; self.reported.len()];
+        let mut kept = Vec::with_capacity(before);
+        for detection in std::mem::take(&mut result.detections) {
+            if !deduplicated(&detection.class) {
+                kept.push(detection);
+                continue;
+            }
+            let track_id = track_id(&detection);
+            // Each remembered object absorbs one detection per frame, so two
+            // cars parked side by side are not folded into one
+            let existing = self
+                .reported
+                .iter()
+                .enumerate()
+                .find(|(i, r)| !matched[*i] && r.matches(&detection, track_id, self.config.iou_threshold))
+                .map(|(i, _)| i);
+            match existing {
+                Some(i) => {
+                    matched[i] = true;
+                    let reported = &mut self.reported[i];
+                    reported.bbox = detection.bbox.clone();
+                    reported.last_seen = reported.last_seen.max(now);
+                    reported.track_id = reported.track_id.or(track_id);
+                    if now.saturating_sub(reported.reported_at) >= window_ms {
+                        reported.reported_at = now;
+                        kept.push(detection);
+                    }
+                }
+                None => {
+                    self.reported.push(Reported {
+                        class: detection.class.clone(),
+                        track_id,
+                        bbox: detection.bbox.clone(),
+                        reported_at: now,
+                        last_seen: now,
+                    });
+                    matched.push(true);
+                    kept.push(detection);
+                }
+            }
+        }
+        result.detections = kept;
+
+        let suppressed = before - result.detections.len();
+        let dedup = serde_json::json!({ "suppressed": suppressed });
+        match &mut result.metadata {
+            Some(serde_json::Value::Object(metadata)) => {
+                metadata.insert("dedup".to_string(), dedup);
+            }
+            None => result.metadata = Some(serde_json::json!({ "dedup": dedup })),
+            Some(_) => {}
+        }
+        suppressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(class: &str, x: u32, y: u32) -> Detection {
+        Detection {
+            class: class.to_string(),
+            confidence: 0.9,
+            bbox: BoundingBox {
+                x,
+                y,
+                width: 100,
+                height: 50,
+            },
+            metadata: None,
+        }
+    }
+
+    fn tracked(class: &str, x: u32, track_id: u64) -> Detection {
+        Detection {
+            metadata: Some(serde_json::json!({ "track_id": track_id })),
+            ..detection(class, x, 0)
+        }
+    }
+
+    fn frame(timestamp: u64, detections: Vec<Detection>) -> AiResult {
+        AiResult {
+            task_id: "task-1".to_string(),
+            timestamp,
+            plugin_type: "mock_object_detector".to_string(),
+            detections,
+            confidence: None,
+            processing_time_ms: None,
+            metadata: None,
+        }
+    }
+
+    fn classes(result: &AiResult) -> Vec<&str> {
+        result.detections.iter().map(|d| d.class.as_str()).collect()
+    }
+
+    fn dedup(window_secs: f32) -> Deduplicator {
+        Deduplicator::new(DedupConfig {
+            window_secs,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn reports_a_parked_car_once_per_window() {
+        let mut dedup = dedup(10.0);
+
+        let mut first = frame(0, vec![detection("car", 100, 100)]);
+        assert_eq!(dedup.update(&mut first), 0);
+        assert_eq!(classes(&first), ["car"]);
+
+        // Jitter of the box does not make it a new car
+        for timestamp in [1_000, 5_000, 9_999] {
+            let mut result = frame(timestamp, vec![detection("car", 103, 98)]);
+            assert_eq!(dedup.update(&mut result), 1);
+            assert!(result.detections.is_empty());
+            assert_eq!(result.metadata.as_ref().unwrap()["dedup"]["suppressed"], 1);
+        }
+
+        // Still there after the window: reported again
+        let mut later = frame(10_000, vec![detection("car", 100, 100)]);
+        assert_eq!(dedup.update(&mut later), 0);
+        assert_eq!(dedup.len(), 1);
+    }
+
+    #[test]
+    fn new_objects_and_other_classes_pass() {
+        let mut dedup = dedup(10.0);
+        dedup.update(&mut frame(0, vec![detection("car", 100, 100)]));
+
+        // A person where the car is, and a second car next to it
+        let mut result = frame(
+            1_000,
+            vec![detection("car", 100, 100), detection("person", 100, 100), detection("car", 400, 100)],
+        );
+        assert_eq!(dedup.update(&mut result), 1);
+        assert_eq!(classes(&result), ["person", "car"]);
+    }
+
+    #[test]
+    fn forgets_objects_that_left() {
+        let mut dedup = dedup(10.0);
+        dedup.update(&mut frame(0, vec![detection("person", 0, 0)]));
+        dedup.update(&mut frame(2_000, vec![]));
+        assert_eq!(dedup.len(), 1);
+
+        // Gone for a whole window, then back
+        let mut result = frame(11_000, vec![detection("person", 0, 0)]);
+        assert_eq!(dedup.update(&mut result), 0);
+        assert_eq!(classes(&result), ["person"]);
+    }
+
+    #[test]
+    fn tracked_objects_match_by_track_id() {
+        let mut dedup = dedup(30.0);
+        dedup.update(&mut frame(0, vec![tracked("person", 0, 7)]));
+
+        // Track 7 walked away from its first box; track 8 stands where 7 was
+        let mut result = frame(1_000, vec![tracked("person", 500, 7), tracked("person", 0, 8)]);
+        assert_eq!(dedup.update(&mut result), 1);
+        assert_eq!(result.detections[0].metadata.as_ref().unwrap()["track_id"], 8);
+    }
+
+    #[test]
+    fn only_configured_classes_are_deduplicated() {
+        let mut dedup = Deduplicator::new(DedupConfig {
+            classes: vec!["car".to_string()],
+            ..Default::default()
+        });
+        let detections = || vec![detection("car", 0, 0), detection("person", 300, 0)];
+        dedup.update(&mut frame(0, detections()));
+
+        let mut result = frame(1_000, detections());
+        assert_eq!(dedup.update(&mut result), 1);
+        assert_eq!(classes(&result), ["person"]);
+    }
+
+    #[test]
+    fn validates_config() {
+        assert!(validate(&DedupConfig::default()).is_ok());
+        assert!(validate(&DedupConfig { window_secs: 0.0, ..Default::default() }).is_err());
+        assert!(validate(&DedupConfig { window_secs: f32::NAN, ..Default::default() }).is_err());
+        assert!(validate(&DedupConfig { iou_threshold: 1.5, ..Default::default() }).is_err());
+    }
+}
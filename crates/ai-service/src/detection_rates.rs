@@ -0,0 +1,174 @@
+//! Detection rate samples for anomaly detection
+//!
+//! Detections of every analyzed camera are counted per reporting window and
+//! sent to alert-service (`POST /v1/anomalies/samples`) as `detection_rate`
+//! samples, in detections per minute. alert-service learns each camera's
+//! normal rate per hour of day and raises an `anomaly` trigger when a
+//! camera suddenly sees far more or far less than usual. Recording tasks are
+//! not reported; only cameras with at least one analyzed frame in a window
+//! get a sample, so a stopped task does not read as silence.
+
+use crate::alerts::tenant_for;
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use common::ai_tasks::AiTaskInfo;
+use common::auth_middleware::AuthContext;
+use common::gateway_identity;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Cameras counted per window; further cameras wait for the next one
+const MAX_CAMERAS: usize = 4096;
+const IDENTITY_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct CameraWindow {
+    tenant_id: String,
+    detections: u64,
+}
+
+/// Counts detections per camera and reports the rates every interval
+pub struct DetectionRateReporter {
+    client: reqwest::Client,
+    samples_url: String,
+    jwt_secret: String,
+    default_tenant_id: Option<String>,
+    interval: Duration,
+    windows: Mutex<HashMap<String, CameraWindow>>,
+}
+
+impl DetectionRateReporter {
+    /// Reporting is enabled when `ANOMALY_REPORT_INTERVAL_SECS`,
+    /// `ALERT_SERVICE_URL` and `JWT_SECRET` are set
+    pub fn from_env() -> Option<Self> {
+        let interval = std::env::var("ANOMALY_REPORT_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)?;
+        let base = std::env::var("ALERT_SERVICE_URL").ok()?;
+        let jwt_secret = std::env::var("JWT_SECRET").ok()?;
+        Some(Self {
+            client: reqwest::Client::new(),
+            samples_url: format!("{}/v1/anomalies/samples", base.trim_end_matches('/')),
+            jwt_secret,
+            default_tenant_id: std::env::var("AI_ALERT_TENANT_ID").ok(),
+            interval: Duration::from_secs(interval.max(10)),
+            windows: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Count one analyzed frame of a live camera task
+    pub async fn record(&self, task: &AiTaskInfo, detections: usize) {
+        let Some(camera_id) = &task.config.source_stream_id else {
+            return;
+        };
+        let Some(tenant_id) = tenant_for(task).or_else(|| self.default_tenant_id.clone()) else {
+            return;
+        };
+        let mut windows = self.windows.lock().await;
+        if windows.len() >= MAX_CAMERAS && !windows.contains_key(camera_id) {
+            return;
+        }
+        let window = windows.entry(camera_id.clone()).or_default();
+        window.tenant_id = tenant_id;
+        window.detections += detections as u64;
+    }
+
+    /// Report every interval until the task is aborted
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let windows = std::mem::take(&mut *self.windows.lock().await);
+                for (tenant_id, samples) in samples_by_tenant(windows, self.interval) {
+                    if let Err(e) = self.report(&tenant_id, samples).await {
+                        warn!(tenant_id = %tenant_id, error = %e, "failed to report detection rates");
+                    }
+                }
+            }
+        })
+    }
+
+    async fn report(&self, tenant_id: &str, samples: Vec<Value>) -> Result<()> {
+        debug!(tenant_id, samples = samples.len(), "reporting detection rates");
+        let identity = AuthContext {
+            user_id: "ai-service".into(),
+            tenant_id: tenant_id.to_string(),
+            username: "ai-service".into(),
+            is_system_admin: false,
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        };
+        let headers = gateway_identity::headers(&identity, &self.jwt_secret, IDENTITY_TTL)?;
+        self.client
+            .post(&self.samples_url)
+            .headers(headers)
+            .json(&json!({ "samples": samples }))
+            .send()
+            .await
+            .context("alert service unreachable")?
+            .error_for_status()
+            .context("alert service rejected samples")?;
+        Ok(())
+    }
+}
+
+/// `detection_rate` samples (detections per minute) of the cameras analyzed
+/// in a window, grouped by tenant; a camera has a window once a frame of it
+/// was analyzed
+fn samples_by_tenant(windows: HashMap<String, CameraWindow>, interval: Duration) -> HashMap<String, Vec<Value>> {
+    let minutes = interval.as_secs_f64() / 60.0;
+    let observed_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut by_tenant: HashMap<String, Vec<Value>> = HashMap::new();
+    for (camera_id, window) in windows {
+        by_tenant.entry(window.tenant_id).or_default().push(json!({
+            "camera_id": camera_id,
+            "metric": "detection_rate",
+            "value": window.detections as f64 / minutes,
+            "observed_at": observed_at,
+        }));
+    }
+    by_tenant
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_are_per_minute_and_grouped_by_tenant() {
+        let windows = HashMap::from([
+            (
+                "cam-1".to_string(),
+                CameraWindow {
+                    tenant_id: "t-1".into(),
+                    detections: 30,
+                },
+            ),
+            (
+                "cam-2".to_string(),
+                CameraWindow {
+                    tenant_id: "t-2".into(),
+                    detections: 0,
+                },
+            ),
+        ]);
+        let samples = samples_by_tenant(windows, Duration::from_secs(120));
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples["t-1"][0]["camera_id"], "cam-1");
+        assert_eq!(samples["t-1"][0]["value"], 15.0);
+        // A camera analyzed without detections reports a zero rate
+        assert_eq!(samples["t-2"][0]["value"], 0.0);
+    }
+}
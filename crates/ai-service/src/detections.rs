@@ -0,0 +1,578 @@
+//! Detection history
+//!
+//! Every detection of a processed frame is kept as one row (task, camera,
+//! tenant, class, confidence, box, plugin metadata and frame time) and served
+//! at `GET /v1/detections`. Rows are queued in memory and written in batches
+//! every second, so frame processing never waits for the database; while the
+//! database is unreachable the queue keeps up to `MAX_PENDING` rows and drops
+//! the oldest beyond that. With `AI_DETECTIONS_DATABASE_URL` rows go to the
+//! `ai_detections` table (`migrations/`), pruned after
+//! `AI_DETECTIONS_RETENTION_DAYS`; otherwise the latest
+//! `AI_DETECTIONS_MEMORY_LIMIT` rows are kept in memory.
+
+use crate::alerts::tenant_for;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use common::ai_tasks::{AiResult, AiTaskInfo, BoundingBox};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Rows queued for the store at most; the oldest are dropped beyond
+const MAX_PENDING: usize = 50_000;
+/// Rows written per INSERT
+const BATCH_SIZE: usize = 500;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+pub const DEFAULT_LIMIT: u32 = 100;
+pub const MAX_LIMIT: u32 = 1000;
+
+/// One detected object of a processed frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionRecord {
+    pub task_id: String,
+    pub plugin_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub class: String,
+    pub confidence: f32,
+    pub bbox: BoundingBox,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    /// Frame time, Unix milliseconds
+    pub detected_at: i64,
+}
+
+/// A stored detection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredDetection {
+    pub id: i64,
+    #[serde(flatten)]
+    pub record: DetectionRecord,
+}
+
+/// Filters of `GET /v1/detections`; newest detections first
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DetectionQuery {
+    pub task_id: Option<String>,
+    pub camera_id: Option<String>,
+    pub tenant_id: Option<String>,
+    /// Comma-separated classes, any of which matches
+    pub class: Option<String>,
+    /// Unix milliseconds, inclusive
+    pub from_ms: Option<i64>,
+    /// Unix milliseconds, exclusive
+    pub to_ms: Option<i64>,
+    pub min_confidence: Option<f32>,
+    #[serde(default)]
+    pub offset: u32,
+    pub limit: Option<u32>,
+}
+
+impl DetectionQuery {
+    pub fn validate(&self) -> Result<()> {
+        if let (Some(from), Some(to)) = (self.from_ms, self.to_ms) {
+            if from >= to {
+                bail!("from_ms must be before to_ms");
+            }
+        }
+        if self.min_confidence.is_some_and(|c| !(0.0..=1.0).contains(&c)) {
+            bail!("min_confidence must be between 0 and 1");
+        }
+        if self.limit.is_some_and(|limit| limit == 0 || limit > MAX_LIMIT) {
+            bail!("limit must be between 1 and {}", MAX_LIMIT);
+        }
+        Ok(())
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_LIMIT)
+    }
+
+    /// Lowercase classes of the `class` filter
+    pub fn classes(&self) -> Vec<String> {
+        self.class
+            .iter()
+            .flat_map(|c| c.split(','))
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .collect()
+    }
+
+    fn matches(&self, record: &DetectionRecord, classes: &[String]) -> bool {
+        let eq = |filter: &Option<String>, value: &Option<String>| {
+            filter.is_none() || filter.as_ref() == value.as_ref()
+        };
+        self.task_id.as_ref().is_none_or(|t| *t == record.task_id)
+            && eq(&self.camera_id, &record.camera_id)
+            && eq(&self.tenant_id, &record.tenant_id)
+            && (classes.is_empty() || classes.contains(&record.class))
+            && self.from_ms.is_none_or(|from| record.detected_at >= from)
+            && self.to_ms.is_none_or(|to| record.detected_at < to)
+            && self.min_confidence.is_none_or(|c| record.confidence >= c)
+    }
+}
+
+/// One page of detections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionPage {
+    pub detections: Vec<StoredDetection>,
+    /// Detections matching the filters across all pages
+    pub total: u64,
+    pub offset: u32,
+    pub limit: u32,
+}
+
+#[async_trait]
+pub trait DetectionStore: Send + Sync {
+    async fn insert(&self, records: &[DetectionRecord]) -> Result<()>;
+    async fn query(&self, query: &DetectionQuery) -> Result<DetectionPage>;
+    /// Delete detections older than `before_ms`; returns how many
+    async fn purge_before(&self, before_ms: i64) -> Result<u64>;
+}
+
+pub struct PostgresDetectionStore {
+    pool: PgPool,
+}
+
+impl PostgresDetectionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DetectionStore for PostgresDetectionStore {
+    async fn insert(&self, records: &[DetectionRecord]) -> Result<()> {
+        for chunk in records.chunks(BATCH_SIZE) {
+            let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+                "INSERT INTO ai_detections (task_id, plugin_type, camera_id, tenant_id, class, confidence, \
+                 bbox_x, bbox_y, bbox_width, bbox_height, metadata, detected_at) ",
+            );
+            builder.push_values(chunk, |mut row, record| {
+                row.push_bind(&record.task_id)
+                    .push_bind(&record.plugin_type)
+                    .push_bind(&record.camera_id)
+                    .push_bind(&record.tenant_id)
+                    .push_bind(&record.class)
+                    .push_bind(record.confidence)
+                    .push_bind(record.bbox.x as i32)
+                    .push_bind(record.bbox.y as i32)
+                    .push_bind(record.bbox.width as i32)
+                    .push_bind(record.bbox.height as i32)
+                    .push_bind(&record.metadata)
+                    .push_bind(record.detected_at);
+            });
+            builder.build().execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn query(&self, query: &DetectionQuery) -> Result<DetectionPage> {
+        let classes = query.classes();
+        let rows = sqlx::query(
+            r#"
+            SELECT *, COUNT(*) OVER () AS total_count
+            FROM ai_detections
+            WHERE ($1::text IS NULL OR task_id = $1)
+              AND ($2::text IS NULL OR camera_id = $2)
+              AND ($3::text IS NULL OR tenant_id = $3)
+              AND (cardinality($4::text[]) = 0 OR class = ANY($4))
+              AND ($5::bigint IS NULL OR detected_at >= $5)
+              AND ($6::bigint IS NULL OR detected_at < $6)
+              AND ($7::real IS NULL OR confidence >= $7)
+            ORDER BY detected_at DESC, id DESC
+            LIMIT $8 OFFSET $9
+            "#,
+        )
+        .bind(&query.task_id)
+        .bind(&query.camera_id)
+        .bind(&query.tenant_id)
+        .bind(&classes)
+        .bind(query.from_ms)
+        .bind(query.to_ms)
+        .bind(query.min_confidence)
+        .bind(query.limit() as i64)
+        .bind(query.offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let total = match rows.first() {
+            Some(row) => row.try_get::<i64, _>("total_count")? as u64,
+            // Past the last page the window count is unavailable
+            None if query.offset > 0 => self.count(query, &classes).await?,
+            None => 0,
+        };
+        let detections = rows
+            .iter()
+            .map(|row| {
+                Ok(StoredDetection {
+                    id: row.try_get("id")?,
+                    record: DetectionRecord {
+                        task_id: row.try_get("task_id")?,
+                        plugin_type: row.try_get("plugin_type")?,
+                        camera_id: row.try_get("camera_id")?,
+                        tenant_id: row.try_get("tenant_id")?,
+                        class: row.try_get("class")?,
+                        confidence: row.try_get("confidence")?,
+                        bbox: BoundingBox {
+                            x: row.try_get::<i32, _>("bbox_x")?.max(0) as u32,
+                            y: row.try_get::<i32, _>("bbox_y")?.max(0) as u32,
+                            width: row.try_get::<i32, _>("bbox_width")?.max(0) as u32,
+                            height: row.try_get::<i32, _>("bbox_height")?.max(0) as u32,
+                        },
+                        metadata: row.try_get("metadata")?,
+                        detected_at: row.try_get("detected_at")?,
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DetectionPage {
+            detections,
+            total,
+            offset: query.offset,
+            limit: query.limit(),
+        })
+    }
+
+    async fn purge_before(&self, before_ms: i64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM ai_detections WHERE detected_at < $1")
+            .bind(before_ms)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+impl PostgresDetectionStore {
+    async fn count(&self, query: &DetectionQuery, classes: &[String]) -> Result<u64> {
+        let count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM ai_detections
+            WHERE ($1::text IS NULL OR task_id = $1)
+              AND ($2::text IS NULL OR camera_id = $2)
+              AND ($3::text IS NULL OR tenant_id = $3)
+              AND (cardinality($4::text[]) = 0 OR class = ANY($4))
+              AND ($5::bigint IS NULL OR detected_at >= $5)
+              AND ($6::bigint IS NULL OR detected_at < $6)
+              AND ($7::real IS NULL OR confidence >= $7)
+            "#,
+        )
+        .bind(&query.task_id)
+        .bind(&query.camera_id)
+        .bind(&query.tenant_id)
+        .bind(classes)
+        .bind(query.from_ms)
+        .bind(query.to_ms)
+        .bind(query.min_confidence)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count as u64)
+    }
+}
+
+/// The latest detections in memory, for nodes without a database
+pub struct MemoryDetectionStore {
+    capacity: usize,
+    rows: Mutex<(i64, VecDeque<StoredDetection>)>,
+}
+
+impl MemoryDetectionStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            rows: Mutex::new((0, VecDeque::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl DetectionStore for MemoryDetectionStore {
+    async fn insert(&self, records: &[DetectionRecord]) -> Result<()> {
+        let mut guard = self.rows.lock().await;
+        let (next_id, rows) = &mut *guard;
+        for record in records {
+            *next_id += 1;
+            rows.push_back(StoredDetection {
+                id: *next_id,
+                record: record.clone(),
+            });
+        }
+        let excess = rows.len().saturating_sub(self.capacity);
+        rows.drain(..excess);
+        Ok(())
+    }
+
+    async fn query(&self, query: &DetectionQuery) -> Result<DetectionPage> {
+        let classes = query.classes();
+        let guard = self.rows.lock().await;
+        let mut matching: Vec<&StoredDetection> = guard
+            .1
+            .iter()
+            .filter(|row| query.matches(&row.record, &classes))
+            .collect();
+        matching.sort_by(|a, b| {
+            b.record
+                .detected_at
+                .cmp(&a.record.detected_at)
+                .then(b.id.cmp(&a.id))
+        });
+        Ok(DetectionPage {
+            total: matching.len() as u64,
+            detections: matching
+                .into_iter()
+                .skip(query.offset as usize)
+                .take(query.limit() as usize)
+                .cloned()
+                .collect(),
+            offset: query.offset,
+            limit: query.limit(),
+        })
+    }
+
+    async fn purge_before(&self, before_ms: i64) -> Result<u64> {
+        let mut guard = self.rows.lock().await;
+        let before = guard.1.len();
+        guard.1.retain(|row| row.record.detected_at >= before_ms);
+        Ok((before - guard.1.len()) as u64)
+    }
+}
+
+/// Rows of one processed frame; `camera_id` is the task's source stream or
+/// the frame's source
+pub fn records(
+    task: &AiTaskInfo,
+    result: &AiResult,
+    camera_id: Option<String>,
+    tenant_id: Option<String>,
+) -> Vec<DetectionRecord> {
+    let detected_at = if result.timestamp > 0 {
+        result.timestamp as i64
+    } else {
+        chrono::Utc::now().timestamp_millis()
+    };
+    result
+        .detections
+        .iter()
+        .map(|detection| DetectionRecord {
+            task_id: result.task_id.clone(),
+            plugin_type: task.config.plugin_type.clone(),
+            camera_id: camera_id.clone(),
+            tenant_id: tenant_id.clone(),
+            class: detection.class.to_lowercase(),
+            confidence: detection.confidence,
+            bbox: detection.bbox.clone(),
+            metadata: detection.metadata.clone(),
+            detected_at,
+        })
+        .collect()
+}
+
+/// Queues detections of processed frames and writes them to a store
+pub struct DetectionRecorder {
+    store: Arc<dyn DetectionStore>,
+    default_tenant_id: Option<String>,
+    retention: Option<Duration>,
+    pending: Mutex<VecDeque<DetectionRecord>>,
+}
+
+impl DetectionRecorder {
+    pub fn new(store: Arc<dyn DetectionStore>) -> Self {
+        Self {
+            store,
+            default_tenant_id: std::env::var("AI_ALERT_TENANT_ID").ok(),
+            retention: None,
+            pending: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Postgres when `AI_DETECTIONS_DATABASE_URL` is set, else memory
+    /// (`AI_DETECTIONS_MEMORY_LIMIT`, default 10000; 0 disables the history)
+    pub async fn from_env() -> Result<Option<Self>> {
+        if let Ok(url) = std::env::var("AI_DETECTIONS_DATABASE_URL") {
+            let pool = PgPoolOptions::new().max_connections(5).connect(&url).await?;
+            let retention_days = std::env::var("AI_DETECTIONS_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(30);
+            let mut recorder = Self::new(Arc::new(PostgresDetectionStore::new(pool)));
+            recorder.retention = (retention_days > 0).then(|| Duration::from_secs(retention_days * 86_400));
+            return Ok(Some(recorder));
+        }
+        let capacity = std::env::var("AI_DETECTIONS_MEMORY_LIMIT")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(10_000);
+        Ok((capacity > 0).then(|| Self::new(Arc::new(MemoryDetectionStore::new(capacity)))))
+    }
+
+    pub fn store(&self) -> &Arc<dyn DetectionStore> {
+        &self.store
+    }
+
+    /// Queue the detections of one processed frame
+    pub async fn record(&self, task: &AiTaskInfo, result: &AiResult, camera_id: String) {
+        if result.detections.is_empty() {
+            return;
+        }
+        let tenant_id = tenant_for(task).or_else(|| self.default_tenant_id.clone());
+        let mut pending = self.pending.lock().await;
+        pending.extend(records(task, result, Some(camera_id), tenant_id));
+        let excess = pending.len().saturating_sub(MAX_PENDING);
+        if excess > 0 {
+            pending.drain(..excess);
+            telemetry::metrics::AI_SERVICE_DETECTIONS_STORED
+                .with_label_values(&["dropped"])
+                .inc_by(excess as u64);
+        }
+    }
+
+    /// Write queued detections; on failure they stay queued for the next
+    /// flush
+    pub async fn flush(&self) -> Result<usize> {
+        let batch: Vec<DetectionRecord> = {
+            let mut pending = self.pending.lock().await;
+            pending.drain(..).collect()
+        };
+        if batch.is_empty() {
+            return Ok(0);
+        }
+        if let Err(e) = self.store.insert(&batch).await {
+            let mut pending = self.pending.lock().await;
+            for record in batch.into_iter().rev() {
+                pending.push_front(record);
+            }
+            let excess = pending.len().saturating_sub(MAX_PENDING);
+            if excess > 0 {
+                pending.drain(..excess);
+                telemetry::metrics::AI_SERVICE_DETECTIONS_STORED
+                    .with_label_values(&["dropped"])
+                    .inc_by(excess as u64);
+            }
+            return Err(e);
+        }
+        telemetry::metrics::AI_SERVICE_DETECTIONS_STORED
+            .with_label_values(&["stored"])
+            .inc_by(batch.len() as u64);
+        Ok(batch.len())
+    }
+
+    /// Flush every second and purge detections past the retention hourly
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut failing = false;
+            let mut last_purge: Option<tokio::time::Instant> = None;
+            loop {
+                tokio::time::sleep(FLUSH_INTERVAL).await;
+                match self.flush().await {
+                    Ok(_) if failing => {
+                        info!("detection store reachable again");
+                        failing = false;
+                    }
+                    Ok(_) => {}
+                    Err(e) if !failing => {
+                        warn!(error = %e, "failed to store detections, keeping them queued");
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+                let Some(retention) = self.retention else {
+                    continue;
+                };
+                if last_purge.is_some_and(|at| at.elapsed() < PURGE_INTERVAL) {
+                    continue;
+                }
+                last_purge = Some(tokio::time::Instant::now());
+                let before = chrono::Utc::now().timestamp_millis() - retention.as_millis() as i64;
+                match self.store.purge_before(before).await {
+                    Ok(0) => {}
+                    Ok(purged) => info!(purged, "purged detections past retention"),
+                    Err(e) => warn!(error = %e, "failed to purge detections"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(task: &str, class: &str, confidence: f32, detected_at: i64) -> DetectionRecord {
+        DetectionRecord {
+            task_id: task.to_string(),
+            plugin_type: "object_detection".to_string(),
+            camera_id: Some("cam-1".to_string()),
+            tenant_id: None,
+            class: class.to_string(),
+            confidence,
+            bbox: BoundingBox { x: 1, y: 2, width: 3, height: 4 },
+            metadata: None,
+            detected_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_store_filters_and_pages_newest_first() {
+        let store = MemoryDetectionStore::new(100);
+        store
+            .insert(&[
+                record("t1", "person", 0.9, 1_000),
+                record("t1", "car", 0.8, 2_000),
+                record("t2", "person", 0.4, 3_000),
+                record("t1", "person", 0.7, 4_000),
+            ])
+            .await
+            .unwrap();
+
+        let query = DetectionQuery {
+            class: Some("Person, truck".to_string()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = store.query(&query).await.unwrap();
+        assert_eq!(page.total, 3);
+        let times: Vec<i64> = page.detections.iter().map(|d| d.record.detected_at).collect();
+        assert_eq!(times, vec![4_000, 3_000]);
+
+        let query = DetectionQuery {
+            task_id: Some("t1".to_string()),
+            from_ms: Some(1_000),
+            to_ms: Some(4_000),
+            min_confidence: Some(0.75),
+            ..Default::default()
+        };
+        let page = store.query(&query).await.unwrap();
+        let times: Vec<i64> = page.detections.iter().map(|d| d.record.detected_at).collect();
+        assert_eq!(times, vec![2_000, 1_000]);
+    }
+
+    #[tokio::test]
+    async fn memory_store_keeps_latest_rows() {
+        let store = MemoryDetectionStore::new(2);
+        for at in 1..=3 {
+            store.insert(&[record("t1", "car", 0.5, at)]).await.unwrap();
+        }
+        let page = store.query(&DetectionQuery::default()).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(store.purge_before(3).await.unwrap(), 1);
+    }
+
+    #[test]
+    fn query_validation() {
+        assert!(DetectionQuery::default().validate().is_ok());
+        let query = DetectionQuery { from_ms: Some(5), to_ms: Some(5), ..Default::default() };
+        assert!(query.validate().is_err());
+        let query = DetectionQuery { limit: Some(MAX_LIMIT + 1), ..Default::default() };
+        assert!(query.validate().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! GPU metrics through NVML.
+//!
+//! On every `/metrics` scrape the [`GpuMonitor`] reads each NVIDIA GPU's
+//! utilization, memory in use and installed, and the memory this process
+//! has allocated on it. Plugins report the device their session runs on
+//! (`AiPlugin::gpu_device`), so `ai_service_gpu_utilization_percent` is
+//! labelled with the plugins sharing a GPU. NVML counts devices in PCI bus
+//! order, which matches CUDA device ids with `CUDA_DEVICE_ORDER=PCI_BUS_ID`.
+//!
+//! NVML is loaded at runtime: without the NVIDIA driver (or with
+//! `AI_GPU_METRICS=false`) the monitor is not created and the GPU gauges
+//! stay empty.
+
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use nvml_wrapper::Nvml;
+use telemetry::metrics::{
+    AI_SERVICE_GPU_MEMORY_TOTAL, AI_SERVICE_GPU_MEMORY_USED, AI_SERVICE_GPU_PROCESS_MEMORY,
+    AI_SERVICE_GPU_UTILIZATION,
+};
+use tracing::{info, warn};
+
+/// One GPU as of a scrape
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuSample {
+    pub device_id: u32,
+    pub utilization_percent: u32,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+    /// Memory allocated by this process; `None` when the driver does not
+    /// report it
+    pub process_memory_bytes: Option<u64>,
+}
+
+pub struct GpuMonitor {
+    nvml: Nvml,
+}
+
+impl GpuMonitor {
+    /// Load NVML unless `AI_GPU_METRICS` is `false`; `None` without an
+    /// NVIDIA driver
+    pub fn from_env() -> Option<Self> {
+        let enabled = std::env::var("AI_GPU_METRICS")
+            .map(|v| v.to_lowercase() != "false")
+            .unwrap_or(true);
+        if !enabled {
+            return None;
+        }
+        match Nvml::init() {
+            Ok(nvml) => {
+                let devices = nvml.device_count().unwrap_or(0);
+                info!(devices, "NVML loaded, exporting GPU metrics");
+                Some(Self { nvml })
+            }
+            Err(e) => {
+                info!("NVML not available, GPU metrics disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Read every GPU; devices NVML fails to read are skipped
+    pub fn sample(&self) -> Vec<GpuSample> {
+        let count = match self.nvml.device_count() {
+            Ok(count) => count,
+            Err(e) => {
+                warn!("Failed to count GPUs: {}", e);
+                return Vec::new();
+            }
+        };
+        let pid = std::process::id();
+        let mut samples = Vec::new();
+        for device_id in 0..count {
+            let read = || -> Result<GpuSample, nvml_wrapper::error::NvmlError> {
+                let device = self.nvml.device_by_index(device_id)?;
+                let memory = device.memory_info()?;
+                let process_memory_bytes = device
+                    .running_compute_processes()
+                    .ok()
+                    .and_then(|processes| processes.into_iter().find(|p| p.pid == pid))
+                    .map(|process| match process.used_gpu_memory {
+                        UsedGpuMemory::Used(bytes) => Some(bytes),
+                        UsedGpuMemory::Unavailable => None,
+                    })
+                    // Not among the device's compute processes: nothing allocated
+                    .unwrap_or(Some(0));
+                Ok(GpuSample {
+                    device_id,
+                    utilization_percent: device.utilization_rates()?.gpu,
+                    memory_used_bytes: memory.used,
+                    memory_total_bytes: memory.total,
+                    process_memory_bytes,
+                })
+            };
+            match read() {
+                Ok(sample) => samples.push(sample),
+                Err(e) => warn!(device_id, "Failed to read GPU: {}", e),
+            }
+        }
+        samples
+    }
+}
+
+/// Set the GPU gauges from `samples`; `plugins` maps plugin ids to the GPU
+/// they run on
+pub fn export(samples: &[GpuSample], plugins: &[(String, u32)]) {
+    // Plugins can move to another device or stop using one
+    AI_SERVICE_GPU_UTILIZATION.reset();
+    for sample in samples {
+        let device = sample.device_id.to_string();
+        AI_SERVICE_GPU_MEMORY_USED
+            .with_label_values(&[&device])
+            .set(gauge(sample.memory_used_bytes));
+        AI_SERVICE_GPU_MEMORY_TOTAL
+            .with_label_values(&[&device])
+            .set(gauge(sample.memory_total_bytes));
+        match sample.process_memory_bytes {
+            Some(bytes) => AI_SERVICE_GPU_PROCESS_MEMORY
+                .with_label_values(&[&device])
+                .set(gauge(bytes)),
+            None => {
+                let _ = AI_SERVICE_GPU_PROCESS_MEMORY.remove_label_values(&[&device]);
+            }
+        }
+        for (plugin_id, _) in plugins.iter().filter(|(_, d)| *d == sample.device_id) {
+            AI_SERVICE_GPU_UTILIZATION
+                .with_label_values(&[plugin_id, &device])
+                .set(i64::from(sample.utilization_percent));
+        }
+    }
+}
+
+fn gauge(bytes: u64) -> i64 {
+    i64::try_from(bytes).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_device_gauges_and_plugin_utilization() {
+        let samples = vec![
+            GpuSample {
+                device_id: 70,
+                utilization_percent: 85,
+                memory_used_bytes: 6 << 30,
+                memory_total_bytes: 16 << 30,
+                process_memory_bytes: Some(2 << 30),
+            },
+            GpuSample {
+                device_id: 71,
+                utilization_percent: 10,
+                memory_used_bytes: 1 << 30,
+                memory_total_bytes: 16 << 30,
+                process_memory_bytes: None,
+            },
+        ];
+        let plugins = vec![
+            ("gpu_test_detector".to_string(), 70),
+            ("gpu_test_faces".to_string(), 70),
+            ("gpu_test_lpr".to_string(), 71),
+        ];
+        export(&samples, &plugins);
+
+        assert_eq!(AI_SERVICE_GPU_MEMORY_USED.with_label_values(&["70"]).get(), 6 << 30);
+        assert_eq!(AI_SERVICE_GPU_MEMORY_TOTAL.with_label_values(&["71"]).get(), 16 << 30);
+        assert_eq!(AI_SERVICE_GPU_PROCESS_MEMORY.with_label_values(&["70"]).get(), 2 << 30);
+        assert!(!series(&AI_SERVICE_GPU_PROCESS_MEMORY).contains(&vec!["71".to_string()]));
+        assert_eq!(AI_SERVICE_GPU_UTILIZATION.with_label_values(&["gpu_test_faces", "70"]).get(), 85);
+        assert_eq!(AI_SERVICE_GPU_UTILIZATION.with_label_values(&["gpu_test_lpr", "71"]).get(), 10);
+
+        // A plugin that moved to device 71 leaves no series on 70
+        export(&samples, &[("gpu_test_detector".to_string(), 71)]);
+        assert_eq!(
+            series(&AI_SERVICE_GPU_UTILIZATION),
+            vec![vec!["71".to_string(), "gpu_test_detector".to_string()]]
+        );
+    }
+
+    fn series(gauge: &prometheus::IntGaugeVec) -> Vec<Vec<String>> {
+        use prometheus::core::Collector;
+        gauge
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric().to_vec())
+            .map(|m| m.get_label().iter().map(|l| l.get_value().to_string()).collect())
+            .collect()
+    }
+}
@@ -1,8 +1,28 @@
+pub mod alerts;
+pub mod analysis;
+pub mod anonymize;
 pub mod api;
+pub mod batching;
+pub mod camera_analytics;
 pub mod config;
 pub mod coordinator;
+pub mod dedup;
+pub mod detection_rates;
+pub mod detections;
+pub mod gpu;
+pub mod models;
+pub mod motion;
+pub mod mqtt;
+pub mod onvif;
+pub mod outbox;
+pub mod pipeline;
 pub mod plugin;
+pub mod scheduler;
+pub mod sharding;
 pub mod state;
+pub mod tracking;
+pub mod work_sharing;
+pub mod zones;
 
 pub use config::AiServiceConfig;
 pub use plugin::registry::PluginRegistry;
@@ -1,16 +1,33 @@
 use ai_service::{
-    api, config::AiServiceConfig, coordinator::HttpCoordinatorClient,
+    alerts::ViolationAlerter, api,
+    batching::{BatchConfig, FrameBatcher}, camera_analytics::CameraAnalyticsSync,
+    config::AiServiceConfig, coordinator::HttpCoordinatorClient,
+    detection_rates::DetectionRateReporter, detections::DetectionRecorder, gpu::GpuMonitor, models::ModelRegistry, mqtt::DetectionPublisher,
     plugin::action_recognition::ActionRecognitionPlugin,
     plugin::anomaly_detection::AnomalyDetectorPlugin,
+    plugin::audio_events::AudioEventsPlugin,
     plugin::crowd_analytics::CrowdAnalyticsPlugin,
-    plugin::facial_recognition::FacialRecognitionPlugin, plugin::lpr::LprPlugin,
+    plugin::facial_recognition::FacialRecognitionPlugin,
+    plugin::grpc_backend::{self, GrpcBackendConfig, GrpcBackendPlugin},
+    plugin::lpr::LprPlugin,
     plugin::mock_detector::MockDetectorPlugin, plugin::pose_estimation::PoseEstimationPlugin,
-    plugin::registry::PluginRegistry, plugin::yolov8_detector::YoloV8DetectorPlugin,
-    plugin::AiPlugin, AiServiceState,
+    plugin::ppe_detection::PpeDetectionPlugin,
+    plugin::vision::{NmsConfig, ResizeMode},
+    plugin::registry::{PluginRegistry, RestartPolicy}, plugin::thermal_analytics::ThermalAnalyticsPlugin,
+    plugin::vehicle_attributes::VehicleAttributesPlugin,
+    plugin::yolov8_detector::YoloV8DetectorPlugin,
+    plugin::yolov8_segmentation::YoloV8SegmentationPlugin,
+    plugin::wasm_plugin::{WasmPlugin, WasmPluginConfig},
+    outbox::{Outbox, OutboxConfig},
+    plugin::AiPlugin, scheduler::{InferenceScheduler, SchedulerConfig}, sharding::Sharding, work_sharing::WorkSharing, AiServiceState,
 };
 use anyhow::Result;
+use common::ai_tasks::CAMERA_ANALYTICS_SERVICE;
+use common::nodes::{NodeAnnouncer, NodeKind};
+use common::service_config::ConfigWatcher;
 use common::state_store::StateStore;
 use common::state_store_client::StateStoreClient;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::{net::TcpListener, sync::RwLock};
 use tracing::{info, warn};
@@ -29,8 +46,8 @@ async fn main() -> Result<()> {
         config.bind_addr, config.node_id
     );
 
-    // Initialize plugin registry
-    let registry = PluginRegistry::new();
+    // Initialize plugin registry; plugins failing fatally are restarted
+    let registry = PluginRegistry::with_restart_policy(RestartPolicy::from_env());
 
     // Register built-in plugins
     info!("Registering built-in plugins...");
@@ -45,6 +62,10 @@ async fn main() -> Result<()> {
     registry.register(anomaly_detector).await?;
     info!("Registered anomaly_detector plugin");
 
+    // Letterboxing and NMS shared by the detection plugins
+    let resize_mode = ResizeMode::from_env();
+    let nms = NmsConfig::from_env();
+
     // Register YOLOv8 detector if model file exists
     let yolov8_model_path = std::env::var("YOLOV8_MODEL_PATH")
         .unwrap_or_else(|_| "models/yolov8n.onnx".to_string());
@@ -53,15 +74,21 @@ async fn main() -> Result<()> {
         let mut yolov8 = YoloV8DetectorPlugin::new();
         let yolov8_config = serde_json::json!({
             "model_path": yolov8_model_path,
+            "resize_mode": resize_mode,
+            "nms": nms,
+            // Only report these classes, e.g. "person,car"
+            "classes": std::env::var("YOLOV8_CLASSES")
+                .map(|s| s.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect())
+                .unwrap_or_else(|_| Vec::<String>::new()),
             "confidence_threshold": std::env::var("YOLOV8_CONFIDENCE")
                 .ok()
                 .and_then(|s| s.parse::<f32>().ok())
                 .unwrap_or(0.5)
         });
-        if let Err(e) = yolov8.init(yolov8_config).await {
+        if let Err(e) = yolov8.init(yolov8_config.clone()).await {
             tracing::warn!("Failed to initialize YOLOv8 plugin: {}", e);
         } else {
-            registry.register(Arc::new(RwLock::new(yolov8))).await?;
+            registry.register_with_config(Arc::new(RwLock::new(yolov8)), yolov8_config).await?;
             info!("Registered yolov8_detector plugin with model: {}", yolov8_model_path);
         }
     } else {
@@ -72,6 +99,39 @@ async fn main() -> Result<()> {
         );
     }
 
+    // Register YOLOv8 instance segmentation if model file exists
+    let seg_model_path = std::env::var("YOLOV8_SEG_MODEL_PATH")
+        .unwrap_or_else(|_| "models/yolov8n-seg.onnx".to_string());
+
+    if std::path::Path::new(&seg_model_path).exists() {
+        let mut seg_plugin = YoloV8SegmentationPlugin::new();
+        let seg_config = serde_json::json!({
+            "model_path": seg_model_path,
+            "resize_mode": resize_mode,
+            "nms": nms,
+            "confidence_threshold": std::env::var("YOLOV8_SEG_CONFIDENCE")
+                .ok()
+                .and_then(|s| s.parse::<f32>().ok())
+                .unwrap_or(0.5),
+            "mask_threshold": std::env::var("YOLOV8_SEG_MASK_THRESHOLD")
+                .ok()
+                .and_then(|s| s.parse::<f32>().ok())
+                .unwrap_or(0.5)
+        });
+        if let Err(e) = seg_plugin.init(seg_config.clone()).await {
+            tracing::warn!("Failed to initialize YOLOv8 segmentation plugin: {}", e);
+        } else {
+            registry.register_with_config(Arc::new(RwLock::new(seg_plugin)), seg_config).await?;
+            info!("Registered yolov8_segmentation plugin with model: {}", seg_model_path);
+        }
+    } else {
+        info!(
+            "YOLOv8-seg model not found at '{}', skipping yolov8_segmentation plugin registration. \
+            Set YOLOV8_SEG_MODEL_PATH environment variable to enable.",
+            seg_model_path
+        );
+    }
+
     // Register Pose Estimation plugin if model file exists
     let pose_model_path = std::env::var("POSE_MODEL_PATH")
         .unwrap_or_else(|_| "models/movenet.onnx".to_string());
@@ -89,10 +149,10 @@ async fn main() -> Result<()> {
                 .and_then(|s| s.parse::<f32>().ok())
                 .unwrap_or(0.3)
         });
-        if let Err(e) = pose_plugin.init(pose_config).await {
+        if let Err(e) = pose_plugin.init(pose_config.clone()).await {
             tracing::warn!("Failed to initialize Pose Estimation plugin: {}", e);
         } else {
-            registry.register(Arc::new(RwLock::new(pose_plugin))).await?;
+            registry.register_with_config(Arc::new(RwLock::new(pose_plugin)), pose_config).await?;
             info!("Registered pose_estimation plugin with model: {}", pose_model_path);
         }
     } else {
@@ -114,15 +174,22 @@ async fn main() -> Result<()> {
         let lpr_config = serde_json::json!({
             "detection_model_path": lpr_detection_model,
             "ocr_model_path": lpr_ocr_model,
+            "resize_mode": resize_mode,
+            "nms": nms,
             "confidence_threshold": std::env::var("LPR_CONFIDENCE")
                 .ok()
                 .and_then(|s| s.parse::<f32>().ok())
-                .unwrap_or(0.6)
+                .unwrap_or(0.6),
+            "watchlist_max_edit_distance": std::env::var("LPR_WATCHLIST_MAX_EDIT_DISTANCE")
+                .ok()
+                .and_then(|s| s.parse::<usize>().ok())
+                .unwrap_or(1)
+                .min(ai_service::plugin::lpr_watchlist::MAX_EDIT_DISTANCE)
         });
-        if let Err(e) = lpr_plugin.init(lpr_config).await {
+        if let Err(e) = lpr_plugin.init(lpr_config.clone()).await {
             tracing::warn!("Failed to initialize LPR plugin: {}", e);
         } else {
-            registry.register(Arc::new(RwLock::new(lpr_plugin))).await?;
+            registry.register_with_config(Arc::new(RwLock::new(lpr_plugin)), lpr_config).await?;
             info!("Registered lpr plugin with detection model: {}", lpr_detection_model);
         }
     } else {
@@ -144,6 +211,8 @@ async fn main() -> Result<()> {
         let face_recognition_config = serde_json::json!({
             "detection_model_path": face_detection_model,
             "embedding_model_path": face_embedding_model,
+            "resize_mode": resize_mode,
+            "nms": nms,
             "confidence_threshold": std::env::var("FACE_CONFIDENCE")
                 .ok()
                 .and_then(|s| s.parse::<f32>().ok())
@@ -153,10 +222,10 @@ async fn main() -> Result<()> {
                 .and_then(|s| s.parse::<f32>().ok())
                 .unwrap_or(0.5)
         });
-        if let Err(e) = face_recognition_plugin.init(face_recognition_config).await {
+        if let Err(e) = face_recognition_plugin.init(face_recognition_config.clone()).await {
             tracing::warn!("Failed to initialize Facial Recognition plugin: {}", e);
         } else {
-            registry.register(Arc::new(RwLock::new(face_recognition_plugin))).await?;
+            registry.register_with_config(Arc::new(RwLock::new(face_recognition_plugin)), face_recognition_config).await?;
             info!("Registered facial_recognition plugin with detection model: {}", face_detection_model);
         }
     } else {
@@ -184,10 +253,10 @@ async fn main() -> Result<()> {
                 .and_then(|s| s.parse::<usize>().ok())
                 .unwrap_or(16)
         });
-        if let Err(e) = action_plugin.init(action_config).await {
+        if let Err(e) = action_plugin.init(action_config.clone()).await {
             tracing::warn!("Failed to initialize Action Recognition plugin: {}", e);
         } else {
-            registry.register(Arc::new(RwLock::new(action_plugin))).await?;
+            registry.register_with_config(Arc::new(RwLock::new(action_plugin)), action_config).await?;
             info!("Registered action_recognition plugin with model: {}", action_model_path);
         }
     } else {
@@ -206,6 +275,8 @@ async fn main() -> Result<()> {
         let mut crowd_plugin = CrowdAnalyticsPlugin::new();
         let crowd_config = serde_json::json!({
             "model_path": crowd_model_path,
+            "resize_mode": resize_mode,
+            "nms": nms,
             "confidence_threshold": std::env::var("CROWD_CONFIDENCE")
                 .ok()
                 .and_then(|s| s.parse::<f32>().ok())
@@ -227,10 +298,10 @@ async fn main() -> Result<()> {
                 .and_then(|s| s.parse::<f32>().ok())
                 .unwrap_or(100.0)
         });
-        if let Err(e) = crowd_plugin.init(crowd_config).await {
+        if let Err(e) = crowd_plugin.init(crowd_config.clone()).await {
             tracing::warn!("Failed to initialize Crowd Analytics plugin: {}", e);
         } else {
-            registry.register(Arc::new(RwLock::new(crowd_plugin))).await?;
+            registry.register_with_config(Arc::new(RwLock::new(crowd_plugin)), crowd_config).await?;
             info!("Registered crowd_analytics plugin with model: {}", crowd_model_path);
         }
     } else {
@@ -241,35 +312,233 @@ async fn main() -> Result<()> {
         );
     }
 
+    // Register PPE compliance plugin if model file exists
+    let ppe_model_path = std::env::var("PPE_MODEL_PATH")
+        .unwrap_or_else(|_| "models/ppe_detector.onnx".to_string());
+
+    if std::path::Path::new(&ppe_model_path).exists() {
+        let mut ppe_plugin = PpeDetectionPlugin::new();
+        // Site-wide requirement for tasks without their own zones, e.g. "hard_hat,hi_vis_vest"
+        let required: Vec<String> = std::env::var("PPE_REQUIRED")
+            .map(|s| s.split(',').map(|i| i.trim().to_string()).filter(|i| !i.is_empty()).collect())
+            .unwrap_or_default();
+        let zones = if required.is_empty() {
+            serde_json::json!([])
+        } else {
+            serde_json::json!([{ "id": "site", "name": "Site", "required": required }])
+        };
+
+        let ppe_config = serde_json::json!({
+            "model_path": ppe_model_path,
+            "zones": zones,
+            "resize_mode": resize_mode,
+            "nms": nms,
+            "confidence_threshold": std::env::var("PPE_CONFIDENCE")
+                .ok()
+                .and_then(|s| s.parse::<f32>().ok())
+                .unwrap_or(0.4)
+        });
+        if let Err(e) = ppe_plugin.init(ppe_config.clone()).await {
+            tracing::warn!("Failed to initialize PPE detection plugin: {}", e);
+        } else {
+            registry.register_with_config(Arc::new(RwLock::new(ppe_plugin)), ppe_config).await?;
+            info!("Registered ppe_detection plugin with model: {}", ppe_model_path);
+        }
+    } else {
+        info!(
+            "PPE model not found at '{}', skipping ppe_detection plugin registration. \
+            Set PPE_MODEL_PATH environment variable to enable.",
+            ppe_model_path
+        );
+    }
+
+    // Always register vehicle attributes; the classifier model only adds make
+    // and sharper color/type on top of the built-in heuristics
+    let mut vehicle_plugin = VehicleAttributesPlugin::new();
+    let mut vehicle_config = serde_json::json!({
+        "confidence_threshold": std::env::var("VEHICLE_ATTRIBUTES_CONFIDENCE")
+            .ok()
+            .and_then(|s| s.parse::<f32>().ok())
+            .unwrap_or(0.5)
+    });
+    if let Ok(model_path) = std::env::var("VEHICLE_ATTRIBUTES_MODEL") {
+        if std::path::Path::new(&model_path).exists() {
+            vehicle_config["model_path"] = serde_json::json!(model_path);
+        } else {
+            tracing::warn!("Vehicle attributes model not found at '{}', using heuristics only", model_path);
+        }
+    }
+    if let Ok(labels_path) = std::env::var("VEHICLE_ATTRIBUTES_LABELS") {
+        // JSON object with make_labels, color_labels and type_labels arrays
+        match std::fs::read_to_string(&labels_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|s| serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&s).map_err(Into::into))
+        {
+            Ok(labels) => {
+                for (key, value) in labels {
+                    vehicle_config[key] = value;
+                }
+            }
+            Err(e) => tracing::warn!("Failed to read vehicle attribute labels from '{}': {}", labels_path, e),
+        }
+    }
+    if let Err(e) = vehicle_plugin.init(vehicle_config.clone()).await {
+        tracing::warn!("Failed to initialize Vehicle Attributes plugin: {}", e);
+    } else {
+        registry.register_with_config(Arc::new(RwLock::new(vehicle_plugin)), vehicle_config).await?;
+        info!("Registered vehicle_attributes plugin");
+    }
+
+    // Always register thermal analytics; it needs no model, only the
+    // calibration of the radiometric cameras (centi-Kelvin by default)
+    let mut thermal_plugin = ThermalAnalyticsPlugin::new();
+    let mut thermal_config = serde_json::json!({});
+    for (var, key) in [("THERMAL_RAW_SCALE", "raw_scale"), ("THERMAL_RAW_OFFSET", "raw_offset")] {
+        if let Some(value) = std::env::var(var).ok().and_then(|s| s.parse::<f32>().ok()) {
+            thermal_config[key] = serde_json::json!(value);
+        }
+    }
+    if let Err(e) = thermal_plugin.init(thermal_config.clone()).await {
+        tracing::warn!("Failed to initialize Thermal Analytics plugin: {}", e);
+    } else {
+        registry.register_with_config(Arc::new(RwLock::new(thermal_plugin)), thermal_config).await?;
+        info!("Registered thermal_analytics plugin");
+    }
+
+    // Always register audio events; without a classifier model it falls back
+    // to level-based detection of impulses and screams
+    let mut audio_plugin = AudioEventsPlugin::new();
+    let mut audio_config = serde_json::json!({
+        "confidence_threshold": std::env::var("AUDIO_EVENTS_CONFIDENCE")
+            .ok()
+            .and_then(|s| s.parse::<f32>().ok())
+            .unwrap_or(0.5)
+    });
+    if let Ok(model_path) = std::env::var("AUDIO_EVENTS_MODEL") {
+        if std::path::Path::new(&model_path).exists() {
+            audio_config["model_path"] = serde_json::json!(model_path);
+        } else {
+            tracing::warn!("Audio events model not found at '{}', using level-based detection", model_path);
+        }
+    }
+    if let Ok(labels_path) = std::env::var("AUDIO_EVENTS_LABELS") {
+        // JSON array with the classifier's labels, in output order
+        match std::fs::read_to_string(&labels_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|s| serde_json::from_str::<Vec<String>>(&s).map_err(Into::into))
+        {
+            Ok(labels) => audio_config["labels"] = serde_json::json!(labels),
+            Err(e) => tracing::warn!("Failed to read audio event labels from '{}': {}", labels_path, e),
+        }
+    }
+    if let Err(e) = audio_plugin.init(audio_config.clone()).await {
+        tracing::warn!("Failed to initialize Audio Events plugin: {}", e);
+    } else {
+        registry.register_with_config(Arc::new(RwLock::new(audio_plugin)), audio_config).await?;
+        info!("Registered audio_events plugin");
+    }
+
+    // Register plugins served by remote inference servers over gRPC
+    let grpc_health_interval = grpc_backend::health_interval_from_env();
+    for backend_config in GrpcBackendConfig::from_env() {
+        let id = backend_config.id.clone();
+        let mut backend = match GrpcBackendPlugin::new(backend_config) {
+            Ok(backend) => backend,
+            Err(e) => {
+                tracing::warn!("Failed to set up gRPC backend '{}': {:#}", id, e);
+                continue;
+            }
+        };
+        backend.init(serde_json::Value::Null).await?;
+        let health_checks = backend.spawn_health_checks(grpc_health_interval);
+        if let Err(e) = registry
+            .register_with_config(Arc::new(RwLock::new(backend)), serde_json::Value::Null)
+            .await
+        {
+            tracing::warn!("Failed to register gRPC backend '{}': {}", id, e);
+            health_checks.abort();
+        } else {
+            info!("Registered gRPC backend plugin {}", id);
+        }
+    }
+
+    // Register third-party analytics shipped as WebAssembly components
+    let wasm_config = WasmPluginConfig::from_env();
+    match wasm_config.components() {
+        Ok(components) if !components.is_empty() => {
+            let engine = wasm_config.engine()?;
+            for path in components {
+                let mut wasm_plugin = match WasmPlugin::load(&engine, &path, &wasm_config) {
+                    Ok(wasm_plugin) => wasm_plugin,
+                    Err(e) => {
+                        tracing::warn!("Failed to load WASM plugin {}: {:#}", path.display(), e);
+                        continue;
+                    }
+                };
+                let plugin_config = match ai_service::plugin::sidecar_config(&path) {
+                    Ok(plugin_config) => plugin_config,
+                    Err(e) => {
+                        tracing::warn!("Skipping WASM plugin {}: {:#}", path.display(), e);
+                        continue;
+                    }
+                };
+                let id = wasm_plugin.id();
+                if let Err(e) = wasm_plugin.init(plugin_config.clone()).await {
+                    tracing::warn!("Failed to initialize WASM plugin '{}': {:#}", id, e);
+                } else if let Err(e) = registry
+                    .register_with_config(Arc::new(RwLock::new(wasm_plugin)), plugin_config)
+                    .await
+                {
+                    tracing::warn!("Failed to register WASM plugin '{}': {}", id, e);
+                } else {
+                    info!("Registered WASM plugin {} from {}", id, path.display());
+                }
+            }
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("{:#}", e),
+    }
+
+    // Register plugins shipped as shared objects (`export_dynamic_plugin!`)
+    let dynamic_dir = std::env::var("AI_DYNAMIC_PLUGIN_DIR").unwrap_or_else(|_| "plugins/native".to_string());
+    if let Ok(entries) = std::fs::read_dir(&dynamic_dir) {
+        let mut libraries = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION))
+            .collect::<Vec<_>>();
+        libraries.sort();
+        for path in libraries {
+            match registry.load_dynamic(&path).await {
+                Ok(id) => info!("Registered dynamic plugin {} from {}", id, path.display()),
+                Err(e) => tracing::warn!("Failed to load dynamic plugin {}: {:#}", path.display(), e),
+            }
+        }
+    }
+
     let plugin_count = registry.count().await;
     info!("Plugin registry initialized with {} plugins", plugin_count);
 
+    common::timeline::init_from_env(config.coordinator_url.clone()).await?;
+
     // Create application state
     let state_store_enabled = std::env::var("ENABLE_STATE_STORE")
         .unwrap_or_else(|_| "false".to_string())
         .to_lowercase() == "true";
 
-    let state = if let Some(coordinator_url) = config.coordinator_url {
+    let mut state_store: Option<Arc<dyn StateStore>> = None;
+    let state = if let Some(coordinator_url) = &config.coordinator_url {
         info!("Connecting to coordinator at: {}", coordinator_url);
-        let coordinator = Arc::new(HttpCoordinatorClient::new(coordinator_url.clone())?);
+        let coordinator = Arc::new(HttpCoordinatorClient::new(coordinator_url.clone()).await?);
 
         if state_store_enabled {
-            let state_store: Arc<dyn StateStore> = Arc::new(StateStoreClient::new(coordinator_url.to_string()));
-            let state = AiServiceState::with_coordinator_and_state_store(
+            let store: Arc<dyn StateStore> = Arc::new(StateStoreClient::new(coordinator_url.to_string()));
+            state_store = Some(Arc::clone(&store));
+            AiServiceState::with_coordinator_and_state_store(
                 config.node_id.clone(),
                 coordinator,
                 registry,
-                state_store,
-            );
-
-            // Bootstrap: restore state from StateStore
-            if let Err(e) = state.bootstrap().await {
-                warn!(error = %e, "failed to bootstrap state from StateStore");
-            } else {
-                info!("state store enabled and bootstrapped");
-            }
-
-            state
+                store,
+            )
         } else {
             AiServiceState::with_coordinator(config.node_id.clone(), coordinator, registry)
         }
@@ -278,6 +547,111 @@ async fn main() -> Result<()> {
         AiServiceState::new(config.node_id.clone(), registry)
     };
 
+    // Buffer task states and detection events on disk during coordinator outages
+    if let Some(outbox_config) = OutboxConfig::from_env() {
+        let dir = outbox_config.dir.clone();
+        let timeline = common::timeline::coordinator_from_env(config.coordinator_url.clone())?;
+        let outbox = Arc::new(Outbox::open(outbox_config, state_store.clone(), timeline).await?);
+        state.set_outbox(Arc::clone(&outbox));
+        outbox.spawn();
+        info!(dir = %dir.display(), "AI result outbox enabled");
+    }
+
+    // Bootstrap: restore state from StateStore
+    if state_store.is_some() {
+        if let Err(e) = state.bootstrap().await {
+            warn!(error = %e, "failed to bootstrap state from StateStore");
+        } else {
+            info!("state store enabled and bootstrapped");
+        }
+    }
+
+    if let Some(alerter) = ViolationAlerter::from_env() {
+        state.set_alerter(Arc::new(alerter));
+        info!("violation alerts enabled");
+    }
+
+    // Zones and alarm classes operators draw per camera in operator-ui
+    if let Some(coordinator_url) = &config.coordinator_url {
+        if let Some(watcher) = ConfigWatcher::from_env(coordinator_url.clone(), CAMERA_ANALYTICS_SERVICE).await? {
+            let sync = Arc::new(CameraAnalyticsSync::new());
+            state.set_camera_analytics(Arc::clone(&sync));
+            sync.follow(watcher, state.clone());
+            info!("camera analytics sync enabled");
+        }
+    }
+
+    let batch_config = BatchConfig::from_env();
+    if batch_config.max_batch_size > 1 {
+        info!(
+            max_batch_size = batch_config.max_batch_size,
+            max_wait_ms = batch_config.max_wait.as_millis() as u64,
+            "micro-batching enabled for plugins with batched inference"
+        );
+        state.set_batcher(Arc::new(FrameBatcher::new(batch_config)));
+    }
+
+    let scheduler_config = SchedulerConfig::from_env();
+    info!(
+        max_in_flight = scheduler_config.max_in_flight,
+        max_waiting = scheduler_config.max_waiting,
+        max_wait_ms = scheduler_config.max_wait.as_millis() as u64,
+        "inference scheduling enabled"
+    );
+    state.set_scheduler(Arc::new(InferenceScheduler::new(scheduler_config)));
+
+    if let Some(monitor) = GpuMonitor::from_env() {
+        state.set_gpu_monitor(Arc::new(monitor));
+    }
+
+    // Detection history served at /v1/detections
+    if let Some(recorder) = DetectionRecorder::from_env().await? {
+        let recorder = Arc::new(recorder);
+        state.set_detections(Arc::clone(&recorder));
+        recorder.spawn();
+        info!("detection history enabled");
+    }
+
+    // Uploaded model versions; plugins switched to one keep it across restarts
+    if let Some(models) = ModelRegistry::from_env(state.plugins().clone()).await? {
+        models.restore().await;
+        info!(dir = %models.dir().display(), "model registry enabled");
+        state.set_models(Arc::new(models));
+    }
+
+    // Detections published to an MQTT broker
+    if let Some(publisher) = DetectionPublisher::from_env()? {
+        info!(topic = publisher.topic(), "MQTT detection publishing enabled");
+        publisher.spawn(state.metadata());
+    }
+
+    if let Some(reporter) = DetectionRateReporter::from_env() {
+        let reporter = Arc::new(reporter);
+        info!(interval_secs = reporter.interval().as_secs(), "detection rate reporting enabled");
+        state.set_detection_rates(Arc::clone(&reporter));
+        reporter.spawn();
+    }
+
+    // Shard cameras across the AI nodes registered with the coordinator
+    if let Some(sharding) = Sharding::from_env(&config.node_id, config.coordinator_url.as_ref()) {
+        let sharding = Arc::new(sharding);
+        state.set_sharding(Arc::clone(&sharding));
+        sharding.spawn(state.clone());
+        info!("camera sharding enabled");
+    }
+
+    // Adopt tasks of failed nodes, and spread new ones, through their leases
+    if let Some(work_sharing) = WorkSharing::from_env(&config.node_id, config.coordinator_url.as_ref()) {
+        if state_store.is_none() {
+            warn!("AI_WORK_SHARING_ENABLED needs ENABLE_STATE_STORE for other nodes to see tasks; work sharing disabled");
+        } else {
+            let work_sharing = Arc::new(work_sharing);
+            state.set_work_sharing(Arc::clone(&work_sharing));
+            info!(lease_ttl_secs = work_sharing.lease_ttl_secs(), "AI work sharing enabled");
+            work_sharing.spawn(state.clone());
+        }
+    }
+
     // Build HTTP router
     let app = api::router(state.clone());
 
@@ -286,8 +660,13 @@ async fn main() -> Result<()> {
     let listener = TcpListener::bind(&config.bind_addr).await?;
     info!("AI Service listening on {}", config.bind_addr);
 
+    // Register with the coordinator so gateways can discover this node
+    if let Some(announcer) = NodeAnnouncer::from_env(NodeKind::Ai, &config.node_id).await? {
+        announcer.spawn();
+    }
+
     // Run with graceful shutdown
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal(state))
         .await?;
 
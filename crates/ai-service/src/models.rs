@@ -0,0 +1,568 @@
+//! Model registry
+//!
+//! ONNX models uploaded at `/v1/models/:name` are stored under
+//! `AI_MODEL_DIR` as numbered versions (`<dir>/<name>/<version>/model.onnx`)
+//! with their SHA-256 and size, indexed in `<dir>/models.json`. A version
+//! is put into service by re-initializing a plugin with the version's path
+//! in its model setting (`model_path`, or e.g. `ocr_model_path` for the LPR
+//! recognizer), without restarting the service; the plugin keeps its
+//! previous model when the new one fails to load. Activations are recorded
+//! in the index and re-applied at startup, after the plugins registered
+//! with their env-var models.
+
+use crate::plugin::registry::PluginRegistry;
+use anyhow::Context;
+use axum::body::Bytes;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+const INDEX_FILE: &str = "models.json";
+const MODEL_FILE: &str = "model.onnx";
+const STAGING_DIR: &str = ".staging";
+/// Model settings a version may be put in when the caller names none
+const DEFAULT_CONFIG_KEYS: &[&str] = &["model_path", "detection_model_path"];
+
+/// Largest model upload, `AI_MODEL_MAX_UPLOAD_MB` (default 2048)
+pub fn max_upload_bytes() -> usize {
+    std::env::var("AI_MODEL_MAX_UPLOAD_MB")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|mb| *mb > 0)
+        .unwrap_or(2048)
+        .saturating_mul(1024 * 1024)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModelError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Conflict(String),
+    /// The plugin could not load the model and kept its previous one
+    #[error("{0}")]
+    LoadFailed(String),
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl From<std::io::Error> for ModelError {
+    fn from(e: std::io::Error) -> Self {
+        ModelError::Internal(e.into())
+    }
+}
+
+/// One uploaded version of a model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelVersion {
+    pub name: String,
+    pub version: u32,
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    pub size_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub uploaded_at: u64,
+}
+
+/// A plugin running a registry model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActiveModel {
+    pub name: String,
+    pub version: u32,
+    /// Plugin setting holding the model path
+    pub config_key: String,
+    /// Unix timestamp in milliseconds
+    pub activated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSummary {
+    pub name: String,
+    pub versions: Vec<ModelVersion>,
+    /// Plugins running one of the versions, by plugin ID
+    pub active: BTreeMap<String, ActiveModel>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModelIndex {
+    #[serde(default)]
+    models: BTreeMap<String, Vec<ModelVersion>>,
+    /// By plugin ID
+    #[serde(default)]
+    active: BTreeMap<String, ActiveModel>,
+}
+
+/// An upload written to the staging directory, not yet a version
+pub struct StagedModel {
+    path: PathBuf,
+    sha256: String,
+    size_bytes: u64,
+}
+
+pub struct ModelRegistry {
+    dir: PathBuf,
+    plugins: PluginRegistry,
+    index: RwLock<ModelIndex>,
+    /// Serializes activations, so the index matches what plugins run
+    activation: Mutex<()>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Model names become directory names: letters, digits, `.`, `_` and `-`
+pub fn validate_name(name: &str) -> Result<(), ModelError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(ModelError::Invalid(format!(
+            "Invalid model name '{}': use up to 64 letters, digits, '.', '_' or '-'",
+            name
+        )))
+    }
+}
+
+impl ModelRegistry {
+    /// Enabled when `AI_MODEL_DIR` is set
+    pub async fn from_env(plugins: PluginRegistry) -> anyhow::Result<Option<Self>> {
+        let Some(dir) = std::env::var("AI_MODEL_DIR").ok().filter(|v| !v.trim().is_empty()) else {
+            return Ok(None);
+        };
+        Self::open(PathBuf::from(dir), plugins).await.map(Some)
+    }
+
+    pub async fn open(dir: PathBuf, plugins: PluginRegistry) -> anyhow::Result<Self> {
+        // Uploads interrupted by a restart are never completed
+        let _ = tokio::fs::remove_dir_all(dir.join(STAGING_DIR)).await;
+        tokio::fs::create_dir_all(dir.join(STAGING_DIR))
+            .await
+            .with_context(|| format!("failed to create model directory {}", dir.display()))?;
+        let index = match tokio::fs::read(dir.join(INDEX_FILE)).await {
+            Ok(data) => serde_json::from_slice(&data).context("invalid model index")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ModelIndex::default(),
+            Err(e) => return Err(e).context("failed to read model index"),
+        };
+        Ok(Self {
+            dir,
+            plugins,
+            index: RwLock::new(index),
+            activation: Mutex::new(()),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn file(&self, name: &str, version: u32) -> PathBuf {
+        self.dir.join(name).join(version.to_string()).join(MODEL_FILE)
+    }
+
+    async fn save(&self, index: &ModelIndex) -> anyhow::Result<()> {
+        let data = serde_json::to_vec_pretty(index)?;
+        let tmp = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, self.dir.join(INDEX_FILE)).await?;
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Vec<ModelSummary> {
+        let index = self.index.read().await;
+        index
+            .models
+            .iter()
+            .map(|(name, versions)| ModelSummary {
+                name: name.clone(),
+                versions: versions.clone(),
+                active: index
+                    .active
+                    .iter()
+                    .filter(|(_, active)| &active.name == name)
+                    .map(|(plugin_id, active)| (plugin_id.clone(), active.clone()))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub async fn get(&self, name: &str) -> Option<ModelSummary> {
+        self.list().await.into_iter().find(|model| model.name == name)
+    }
+
+    pub async fn version(&self, name: &str, version: u32) -> Option<ModelVersion> {
+        let index = self.index.read().await;
+        index
+            .models
+            .get(name)?
+            .iter()
+            .find(|v| v.version == version)
+            .cloned()
+    }
+
+    /// File of a stored version
+    pub async fn version_file(&self, name: &str, version: u32) -> Option<PathBuf> {
+        self.version(name, version).await?;
+        Some(self.file(name, version))
+    }
+
+    /// Plugins running registry models, by plugin ID
+    pub async fn active(&self) -> BTreeMap<String, ActiveModel> {
+        self.index.read().await.active.clone()
+    }
+
+    /// Write an upload to the staging directory, hashing it on the way
+    pub async fn stage<S, E>(&self, mut chunks: S) -> Result<StagedModel, ModelError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: std::fmt::Display,
+    {
+        let path = self
+            .dir
+            .join(STAGING_DIR)
+            .join(uuid::Uuid::new_v4().to_string());
+        let mut file = tokio::fs::File::create(&path).await?;
+        let mut hasher = Sha256::new();
+        let mut size_bytes = 0u64;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    drop(file);
+                    let _ = tokio::fs::remove_file(&path).await;
+                    return Err(ModelError::Invalid(format!("Failed to read model upload: {}", e)));
+                }
+            };
+            hasher.update(&chunk);
+            size_bytes += chunk.len() as u64;
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok(StagedModel {
+            path,
+            sha256: hex::encode(hasher.finalize()),
+            size_bytes,
+        })
+    }
+
+    /// Drop a staged upload that will not be added
+    pub async fn discard(&self, staged: StagedModel) {
+        let _ = tokio::fs::remove_file(&staged.path).await;
+    }
+
+    /// Store a staged upload as the model's next version. With
+    /// `expected_sha256`, an upload with another checksum is rejected.
+    pub async fn add_version(
+        &self,
+        name: &str,
+        staged: StagedModel,
+        description: Option<String>,
+        expected_sha256: Option<&str>,
+    ) -> Result<ModelVersion, ModelError> {
+        if let Err(e) = self.check_upload(name, &staged, expected_sha256) {
+            self.discard(staged).await;
+            return Err(e);
+        }
+
+        let mut index = self.index.write().await;
+        let versions = index.models.entry(name.to_string()).or_default();
+        let version = versions.iter().map(|v| v.version).max().unwrap_or(0) + 1;
+        let file = self.file(name, version);
+        if let Some(parent) = file.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::rename(&staged.path, &file).await?;
+
+        let model = ModelVersion {
+            name: name.to_string(),
+            version,
+            sha256: staged.sha256,
+            size_bytes: staged.size_bytes,
+            description,
+            uploaded_at: now_ms(),
+        };
+        versions.push(model.clone());
+        self.save(&index).await?;
+        info!(model = %name, version, sha256 = %model.sha256, "stored model version");
+        Ok(model)
+    }
+
+    fn check_upload(
+        &self,
+        name: &str,
+        staged: &StagedModel,
+        expected_sha256: Option<&str>,
+    ) -> Result<(), ModelError> {
+        validate_name(name)?;
+        if staged.size_bytes == 0 {
+            return Err(ModelError::Invalid("Model file is empty".to_string()));
+        }
+        if let Some(expected) = expected_sha256 {
+            if !expected.trim().eq_ignore_ascii_case(&staged.sha256) {
+                return Err(ModelError::Invalid(format!(
+                    "Checksum mismatch: upload has SHA-256 {}",
+                    staged.sha256
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a version that no plugin runs
+    pub async fn delete_version(&self, name: &str, version: u32) -> Result<(), ModelError> {
+        let _activation = self.activation.lock().await;
+        let mut index = self.index.write().await;
+        if let Some((plugin_id, _)) = index
+            .active
+            .iter()
+            .find(|(_, active)| active.name == name && active.version == version)
+        {
+            return Err(ModelError::Conflict(format!(
+                "Model '{}' version {} is active in plugin '{}'",
+                name, version, plugin_id
+            )));
+        }
+        let versions = index
+            .models
+            .get_mut(name)
+            .ok_or_else(|| ModelError::NotFound(format!("Model '{}' not found", name)))?;
+        let Some(position) = versions.iter().position(|v| v.version == version) else {
+            return Err(ModelError::NotFound(format!(
+                "Model '{}' has no version {}",
+                name, version
+            )));
+        };
+        versions.remove(position);
+        if versions.is_empty() {
+            index.models.remove(name);
+        }
+        self.save(&index).await?;
+        drop(index);
+
+        let version_dir = self.dir.join(name).join(version.to_string());
+        if let Err(e) = tokio::fs::remove_dir_all(&version_dir).await {
+            warn!(model = %name, version, error = %e, "failed to remove model files");
+        }
+        let _ = tokio::fs::remove_dir(self.dir.join(name)).await;
+        Ok(())
+    }
+
+    /// Re-initialize `plugin_id` with a stored version in its `config_key`
+    /// setting (by default `model_path`, else `detection_model_path`)
+    pub async fn activate(
+        &self,
+        plugin_id: &str,
+        name: &str,
+        version: u32,
+        config_key: Option<&str>,
+    ) -> Result<ActiveModel, ModelError> {
+        let _activation = self.activation.lock().await;
+        let file = self.version_file(name, version).await.ok_or_else(|| {
+            ModelError::NotFound(format!("Model '{}' has no version {}", name, version))
+        })?;
+        if !self.plugins.has_plugin(plugin_id).await {
+            return Err(ModelError::NotFound(format!("Plugin '{}' not found", plugin_id)));
+        }
+        let mut config = self.plugins.init_config(plugin_id).await.ok_or_else(|| {
+            ModelError::Invalid(format!("Plugin '{}' does not load models", plugin_id))
+        })?;
+        let Some(settings) = config.as_object_mut() else {
+            return Err(ModelError::Invalid(format!(
+                "Plugin '{}' does not load models",
+                plugin_id
+            )));
+        };
+        let config_key = match config_key {
+            Some(key) if key == "model_path" || key.ends_with("_model_path") => key.to_string(),
+            Some(key) => {
+                return Err(ModelError::Invalid(format!(
+                    "'{}' is not a model setting (model_path or *_model_path)",
+                    key
+                )))
+            }
+            None => DEFAULT_CONFIG_KEYS
+                .iter()
+                .find(|key| settings.contains_key(**key))
+                .map(|key| key.to_string())
+                .ok_or_else(|| {
+                    ModelError::Invalid(format!(
+                        "Plugin '{}' has no model_path setting; name the config_key",
+                        plugin_id
+                    ))
+                })?,
+        };
+        settings.insert(
+            config_key.clone(),
+            serde_json::Value::String(file.to_string_lossy().into_owned()),
+        );
+
+        self.plugins
+            .reconfigure(plugin_id, config)
+            .await
+            .map_err(|e| ModelError::LoadFailed(format!("{:#}", e)))?;
+
+        let active = ActiveModel {
+            name: name.to_string(),
+            version,
+            config_key,
+            activated_at: now_ms(),
+        };
+        let mut index = self.index.write().await;
+        index.active.insert(plugin_id.to_string(), active.clone());
+        self.save(&index).await?;
+        info!(plugin = %plugin_id, model = %name, version, "activated model version");
+        Ok(active)
+    }
+
+    /// Put the recorded activations back into service at startup
+    pub async fn restore(&self) {
+        for (plugin_id, active) in self.active().await {
+            let result = self
+                .activate(&plugin_id, &active.name, active.version, Some(&active.config_key))
+                .await;
+            if let Err(e) = result {
+                warn!(
+                    plugin = %plugin_id,
+                    model = %active.name,
+                    version = active.version,
+                    error = %e,
+                    "failed to restore active model"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::AiPlugin;
+    use async_trait::async_trait;
+    use common::ai_tasks::{AiResult, VideoFrame};
+    use std::sync::Arc;
+
+    /// Loads the file at `model_path`; fails on files starting with "bad"
+    #[derive(Default)]
+    struct LoadingPlugin {
+        loaded: Vec<u8>,
+    }
+
+    #[async_trait]
+    impl AiPlugin for LoadingPlugin {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+
+        fn id(&self) -> &'static str {
+            "loader"
+        }
+
+        fn name(&self) -> &'static str {
+            "Loader"
+        }
+
+        fn description(&self) -> &'static str {
+            "Reads its model file"
+        }
+
+        fn version(&self) -> &'static str {
+            "1.0.0"
+        }
+
+        async fn init(&mut self, config: serde_json::Value) -> anyhow::Result<()> {
+            let path = config["model_path"].as_str().unwrap_or_default();
+            let data = std::fs::read(path)?;
+            anyhow::ensure!(!data.starts_with(b"bad"), "invalid model");
+            self.loaded = data;
+            Ok(())
+        }
+
+        async fn process_frame(&self, _frame: &VideoFrame) -> anyhow::Result<AiResult> {
+            anyhow::bail!("not used")
+        }
+    }
+
+    fn chunks(data: &'static [u8]) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Unpin {
+        futures::stream::iter(data.chunks(3).map(|chunk| Ok(Bytes::from_static(chunk))))
+    }
+
+    #[tokio::test]
+    async fn stores_versions_and_swaps_plugin_models() {
+        let dir = std::env::temp_dir().join(format!("ai-models-{}", uuid::Uuid::new_v4()));
+        let initial = dir.join("initial.onnx");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&initial, b"initial").unwrap();
+
+        let plugins = PluginRegistry::new();
+        let plugin = Arc::new(tokio::sync::RwLock::new(LoadingPlugin::default()));
+        let config = serde_json::json!({ "model_path": initial });
+        plugin.write().await.init(config.clone()).await.unwrap();
+        plugins.register_with_config(plugin.clone(), config).await.unwrap();
+
+        let registry = ModelRegistry::open(dir.clone(), plugins.clone()).await.unwrap();
+        let staged = registry.stage(chunks(b"model-v1")).await.unwrap();
+        let v1 = registry.add_version("detector", staged, None, None).await.unwrap();
+        assert_eq!(v1.version, 1);
+        assert_eq!(v1.size_bytes, 8);
+        assert_eq!(v1.sha256, hex::encode(Sha256::digest(b"model-v1")));
+
+        let staged = registry.stage(chunks(b"model-v2")).await.unwrap();
+        let mismatch = registry.add_version("detector", staged, None, Some(&v1.sha256)).await;
+        assert!(matches!(mismatch, Err(ModelError::Invalid(_))));
+        let staged = registry.stage(chunks(b"bad-model")).await.unwrap();
+        let broken = registry.add_version("detector", staged, None, None).await.unwrap();
+        assert_eq!(broken.version, 2);
+
+        registry.activate("loader", "detector", 1, None).await.unwrap();
+        assert_eq!(plugin.read().await.loaded, b"model-v1");
+
+        // A model the plugin cannot load leaves the running one in place
+        assert!(registry.activate("loader", "detector", 2, None).await.is_err());
+        assert_eq!(plugin.read().await.loaded, b"model-v1");
+        assert_eq!(registry.active().await["loader"].version, 1);
+
+        assert!(matches!(
+            registry.delete_version("detector", 1).await,
+            Err(ModelError::Conflict(_))
+        ));
+        registry.delete_version("detector", 2).await.unwrap();
+
+        // The index survives a restart
+        let reopened = ModelRegistry::open(dir.clone(), plugins).await.unwrap();
+        let model = reopened.get("detector").await.unwrap();
+        assert_eq!(model.versions.len(), 1);
+        assert_eq!(model.active["loader"].version, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_path_like_names() {
+        assert!(validate_name("yolov8n-coco_2.1").is_ok());
+        for name in ["", "../etc", ".staging", "a/b", "a b"] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+    }
+}
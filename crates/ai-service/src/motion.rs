@@ -0,0 +1,193 @@
+//! Motion-gated inference.
+//!
+//! A task with `frame_config.motion_gate` keeps a [`MotionGate`] that
+//! compares each frame with the last frame its plugin analyzed. Both are
+//! reduced to a small grayscale image first, which also smooths out sensor
+//! noise and compression artifacts; the motion score is the fraction of
+//! pixels whose luma changed by more than `pixel_threshold`. Frames scoring
+//! below `min_changed_fraction` skip inference, so a static scene costs one
+//! small image comparison per frame instead of a model run.
+//!
+//! The reference only moves on analyzed frames, so slow changes (a car
+//! creeping into view) add up until a frame is analyzed. After
+//! `max_skipped_frames` skipped frames one is analyzed regardless, which
+//! keeps results of static scenes fresh.
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use common::ai_tasks::{MotionGateConfig, VideoFrame};
+use image::{imageops::FilterType, GrayImage};
+
+/// Width frames are reduced to before they are compared
+const COMPARE_WIDTH: u32 = 160;
+
+/// Check a task's motion gate settings before it starts
+pub fn validate(config: &MotionGateConfig) -> Result<()> {
+    if !(0.0..=1.0).contains(&config.min_changed_fraction) {
+        bail!("frame_config.motion_gate.min_changed_fraction must be in [0, 1]");
+    }
+    Ok(())
+}
+
+/// Outcome of gating one frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionCheck {
+    /// Whether the frame goes to the plugin
+    pub analyze: bool,
+    /// Fraction of changed pixels; `None` for the first frame, or one that
+    /// could not be compared
+    pub score: Option<f32>,
+}
+
+pub struct MotionGate {
+    config: MotionGateConfig,
+    /// Reduced last analyzed frame
+    reference: Option<GrayImage>,
+    /// Frames skipped since the last analyzed one
+    skipped: u32,
+}
+
+impl MotionGate {
+    pub fn new(config: MotionGateConfig) -> Self {
+        Self {
+            config,
+            reference: None,
+            skipped: 0,
+        }
+    }
+
+    /// Decide whether `frame` is analyzed. Frames that cannot be decoded
+    /// are passed on, for the plugin to report.
+    pub fn check(&mut self, frame: &VideoFrame) -> MotionCheck {
+        match reduce(frame) {
+            Ok(image) => self.check_image(image),
+            Err(e) => {
+                tracing::debug!(source_id = %frame.source_id, "Motion gate cannot compare frame: {:#}", e);
+                self.reference = None;
+                self.skipped = 0;
+                MotionCheck { analyze: true, score: None }
+            }
+        }
+    }
+
+    fn check_image(&mut self, image: GrayImage) -> MotionCheck {
+        let score = self
+            .reference
+            .as_ref()
+            .filter(|reference| reference.dimensions() == image.dimensions())
+            .map(|reference| changed_fraction(reference, &image, self.config.pixel_threshold));
+
+        let forced = self.config.max_skipped_frames > 0 && self.skipped >= self.config.max_skipped_frames;
+        let analyze = match score {
+            Some(score) => forced || score >= self.config.min_changed_fraction,
+            None => true,
+        };
+        if analyze {
+            self.reference = Some(image);
+            self.skipped = 0;
+        } else {
+            self.skipped += 1;
+        }
+        MotionCheck { analyze, score }
+    }
+}
+
+/// Decode a frame into a small grayscale image
+fn reduce(frame: &VideoFrame) -> Result<GrayImage> {
+    let data = base64::prelude::BASE64_STANDARD
+        .decode(&frame.data)
+        .context("Failed to decode base64 image")?;
+    let image = image::load_from_memory(&data).context("Failed to load image")?;
+    let width = image.width().clamp(1, COMPARE_WIDTH);
+    let height = ((image.height() as u64 * width as u64) / image.width().max(1) as u64).max(1) as u32;
+    Ok(image::imageops::resize(&image.to_luma8(), width, height, FilterType::Triangle))
+}
+
+fn changed_fraction(a: &GrayImage, b: &GrayImage, pixel_threshold: u8) -> f32 {
+    let total = a.as_raw().len();
+    if total == 0 {
+        return 0.0;
+    }
+    let changed = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .filter(|(x, y)| x.abs_diff(**y) > pixel_threshold)
+        .count();
+    changed as f32 / total as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Luma, Rgb, RgbImage};
+
+    fn frame(image: RgbImage) -> VideoFrame {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        VideoFrame {
+            source_id: "cam-1".to_string(),
+            timestamp: 0,
+            sequence: 0,
+            width: 320,
+            height: 240,
+            format: "png".to_string(),
+            data: base64::prelude::BASE64_STANDARD.encode(bytes.into_inner()),
+            audio: None,
+        }
+    }
+
+    fn scene(object_x: Option<u32>) -> RgbImage {
+        let mut image = RgbImage::from_pixel(320, 240, Rgb([80, 80, 80]));
+        if let Some(x) = object_x {
+            for py in 100..160 {
+                for px in x..x + 40 {
+                    image.put_pixel(px, py, Rgb([230, 230, 230]));
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn skips_static_frames_until_something_moves() {
+        let mut gate = MotionGate::new(MotionGateConfig { max_skipped_frames: 0, ..Default::default() });
+
+        let first = gate.check(&frame(scene(None)));
+        assert_eq!(first, MotionCheck { analyze: true, score: None });
+
+        let still = gate.check(&frame(scene(None)));
+        assert!(!still.analyze);
+        assert_eq!(still.score, Some(0.0));
+
+        let moved = gate.check(&frame(scene(Some(100))));
+        assert!(moved.analyze);
+        assert!(moved.score.unwrap() > 0.02);
+
+        // The object stays put: compared with the frame it appeared in
+        assert!(!gate.check(&frame(scene(Some(100)))).analyze);
+    }
+
+    #[test]
+    fn forces_a_frame_after_max_skipped_frames() {
+        let mut gate = MotionGate::new(MotionGateConfig { max_skipped_frames: 2, ..Default::default() });
+        let analyzed: Vec<bool> = (0..7).map(|_| gate.check(&frame(scene(None))).analyze).collect();
+        assert_eq!(analyzed, vec![true, false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn counts_only_pixels_beyond_the_threshold() {
+        let a = GrayImage::from_pixel(10, 10, Luma([100]));
+        let mut b = GrayImage::from_pixel(10, 10, Luma([110]));
+        assert_eq!(changed_fraction(&a, &b, 25), 0.0);
+        for x in 0..10 {
+            b.put_pixel(x, 0, Luma([200]));
+        }
+        assert_eq!(changed_fraction(&a, &b, 25), 0.1);
+
+        assert!(validate(&MotionGateConfig { min_changed_fraction: 1.5, ..Default::default() }).is_err());
+        assert!(validate(&MotionGateConfig::default()).is_ok());
+    }
+}
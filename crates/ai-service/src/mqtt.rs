@@ -0,0 +1,156 @@
+//! Detections published to MQTT
+//!
+//! With `MQTT_SINK_URL` set, every processed frame with detections is
+//! published as JSON (the `AiResult` plus its `camera`) to a topic rendered
+//! from `MQTT_SINK_DETECTION_TOPIC`, with `{camera}`, `{task_id}` and
+//! `{plugin}` placeholders. When the template uses `{class}`, the frame is
+//! split into one message per class with only that class's detections.
+
+use crate::onvif::{MetadataFrame, MetadataHub};
+use anyhow::Result;
+use common::ai_tasks::AiResult;
+use common::mqtt::{render_topic, MqttSink, MqttSinkConfig};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+const DEFAULT_TOPIC: &str = "quadrant/ai/{camera}/{plugin}";
+
+#[derive(Serialize)]
+struct DetectionMessage<'a> {
+    camera: &'a str,
+    #[serde(flatten)]
+    result: &'a AiResult,
+}
+
+pub struct DetectionPublisher {
+    sink: MqttSink,
+    topic: String,
+}
+
+impl DetectionPublisher {
+    /// Enabled when `MQTT_SINK_URL` is set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(config) = MqttSinkConfig::from_env("ai")? else {
+            return Ok(None);
+        };
+        let topic = std::env::var("MQTT_SINK_DETECTION_TOPIC")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_TOPIC.to_string());
+        Ok(Some(Self {
+            sink: MqttSink::connect(&config),
+            topic,
+        }))
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Publishes the frames processed from now on
+    pub fn spawn(self, hub: &MetadataHub) -> JoinHandle<()> {
+        let mut frames = hub.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match frames.recv().await {
+                    Ok(frame) => {
+                        for (topic, result) in messages(&self.topic, &frame) {
+                            let message = DetectionMessage {
+                                camera: &frame.camera,
+                                result: &result,
+                            };
+                            self.sink.publish(&topic, &message);
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "MQTT detection publisher lagging, results skipped");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+/// Topic and payload of each message for a frame; none without detections
+fn messages(template: &str, frame: &MetadataFrame) -> Vec<(String, AiResult)> {
+    let result = &frame.result;
+    if result.detections.is_empty() {
+        return Vec::new();
+    }
+    let values = [
+        ("camera", frame.camera.as_str()),
+        ("task_id", result.task_id.as_str()),
+        ("plugin", result.plugin_type.as_str()),
+    ];
+    if !template.contains("{class}") {
+        return vec![(render_topic(template, &values), result.clone())];
+    }
+
+    let mut by_class: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+    for detection in &result.detections {
+        by_class.entry(detection.class.as_str()).or_default().push(detection.clone());
+    }
+    by_class
+        .into_iter()
+        .map(|(class, detections)| {
+            let mut values = values.to_vec();
+            values.push(("class", class));
+            let result = AiResult {
+                detections,
+                ..result.clone()
+            };
+            (render_topic(template, &values), result)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::{BoundingBox, Detection};
+
+    fn frame(classes: &[&str]) -> MetadataFrame {
+        let detections = classes
+            .iter()
+            .map(|class| Detection {
+                class: class.to_string(),
+                confidence: 0.9,
+                bbox: BoundingBox { x: 0, y: 0, width: 10, height: 10 },
+                metadata: None,
+            })
+            .collect();
+        MetadataFrame {
+            camera: "gate".to_string(),
+            width: 640,
+            height: 480,
+            result: AiResult {
+                task_id: "task-1".to_string(),
+                timestamp: 1,
+                plugin_type: "yolov8".to_string(),
+                detections,
+                confidence: None,
+                processing_time_ms: None,
+                metadata: None,
+            },
+        }
+    }
+
+    #[test]
+    fn renders_one_message_per_frame_or_class() {
+        assert!(messages(DEFAULT_TOPIC, &frame(&[])).is_empty());
+
+        let frame = frame(&["car", "person", "car"]);
+        let per_frame = messages(DEFAULT_TOPIC, &frame);
+        assert_eq!(per_frame.len(), 1);
+        assert_eq!(per_frame[0].0, "quadrant/ai/gate/yolov8");
+        assert_eq!(per_frame[0].1.detections.len(), 3);
+
+        let per_class = messages("vms/{task_id}/{class}", &frame);
+        let topics: Vec<_> = per_class.iter().map(|(topic, r)| (topic.as_str(), r.detections.len())).collect();
+        assert_eq!(topics, vec![("vms/task-1/car", 2), ("vms/task-1/person", 1)]);
+    }
+}
@@ -0,0 +1,374 @@
+//! ONVIF analytics metadata export.
+//!
+//! Every processed frame is rendered as an ONVIF `tt:MetadataStream`
+//! document: detections become `tt:Object`s in a `tt:VideoAnalytics` frame,
+//! with bounding boxes in ONVIF's normalized coordinates (-1..1, y up) and
+//! classes mapped onto ONVIF object types. Changes in which object types are
+//! present are carried as property events on the
+//! `tns1:RuleEngine/ObjectDetection/Object` topic in the same document, one
+//! property instance per task and object type, so clients that follow ONVIF
+//! analytics events see "Human present: true/false" style state.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use common::ai_tasks::{AiResult, Detection};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Topic of the object presence events
+pub const OBJECT_TOPIC: &str = "tns1:RuleEngine/ObjectDetection/Object";
+
+/// Frames buffered per subscriber before slow subscribers skip ahead
+const CHANNEL_CAPACITY: usize = 256;
+
+const NAMESPACES: &str = concat!(
+    r#"xmlns:tt="http://www.onvif.org/ver10/schema" "#,
+    r#"xmlns:wsnt="http://docs.oasis-open.org/wsn/b-2" "#,
+    r#"xmlns:tns1="http://www.onvif.org/ver10/topics""#,
+);
+
+/// One processed frame and where it came from
+#[derive(Debug, Clone)]
+pub struct MetadataFrame {
+    /// Camera or stream the frame belongs to; the video source token
+    pub camera: String,
+    pub width: u32,
+    pub height: u32,
+    pub result: AiResult,
+}
+
+/// Fans processed frames out to metadata subscribers
+#[derive(Debug, Clone)]
+pub struct MetadataHub {
+    sender: broadcast::Sender<Arc<MetadataFrame>>,
+}
+
+impl Default for MetadataHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetadataHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// Frames are dropped when nobody is subscribed
+    pub fn publish(&self, frame: MetadataFrame) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(Arc::new(frame));
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<MetadataFrame>> {
+        self.sender.subscribe()
+    }
+}
+
+/// ONVIF object type of a plugin class label
+pub fn object_type(class: &str) -> &'static str {
+    match class.to_ascii_lowercase().as_str() {
+        "person" | "human" | "pedestrian" => "Human",
+        "face" => "Face",
+        "car" | "truck" | "bus" | "train" | "vehicle" => "Vehicle",
+        "bicycle" | "motorcycle" | "motorbike" | "bike" => "Bike",
+        "license_plate" | "licenseplate" | "plate" => "LicensePlate",
+        "bird" | "cat" | "dog" | "horse" | "sheep" | "cow" | "elephant" | "bear" | "zebra"
+        | "giraffe" | "animal" => "Animal",
+        _ => "Other",
+    }
+}
+
+/// Whether an object type appeared or disappeared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresenceChange {
+    pub object_type: &'static str,
+    pub present: bool,
+    /// First state sent to this subscriber rather than a change
+    pub initialized: bool,
+}
+
+/// Object types present in the previous frame of one task, per subscriber
+#[derive(Debug, Default)]
+pub struct PresenceTracker {
+    present: Option<BTreeSet<&'static str>>,
+}
+
+impl PresenceTracker {
+    pub fn update(&mut self, result: &AiResult) -> Vec<PresenceChange> {
+        let now: BTreeSet<&'static str> = result
+            .detections
+            .iter()
+            .map(|d| object_type(&d.class))
+            .collect();
+        let changes = match &self.present {
+            None => now
+                .iter()
+                .map(|&object_type| PresenceChange {
+                    object_type,
+                    present: true,
+                    initialized: true,
+                })
+                .collect(),
+            Some(before) => {
+                let appeared = now.difference(before).map(|&object_type| PresenceChange {
+                    object_type,
+                    present: true,
+                    initialized: false,
+                });
+                let gone = before.difference(&now).map(|&object_type| PresenceChange {
+                    object_type,
+                    present: false,
+                    initialized: false,
+                });
+                appeared.chain(gone).collect()
+            }
+        };
+        self.present = Some(now);
+        changes
+    }
+}
+
+/// The frame as a `tt:MetadataStream` document, with `changes` as events
+pub fn render(frame: &MetadataFrame, changes: &[PresenceChange]) -> String {
+    let time = utc_time(frame.result.timestamp);
+    let mut xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?><tt:MetadataStream {NAMESPACES}>"#);
+    let _ = write!(xml, r#"<tt:VideoAnalytics><tt:Frame UtcTime="{time}">"#);
+    for (index, detection) in frame.result.detections.iter().enumerate() {
+        write_object(&mut xml, frame, index, detection);
+    }
+    xml.push_str("</tt:Frame></tt:VideoAnalytics>");
+    if !changes.is_empty() {
+        xml.push_str("<tt:Event>");
+        for change in changes {
+            write_event(&mut xml, frame, &time, change);
+        }
+        xml.push_str("</tt:Event>");
+    }
+    xml.push_str("</tt:MetadataStream>");
+    xml
+}
+
+fn write_object(xml: &mut String, frame: &MetadataFrame, index: usize, detection: &Detection) {
+    // Plugins that track objects report a stable id; otherwise ids are only
+    // unique within the frame
+    let object_id = detection
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("track_id"))
+        .and_then(|id| id.as_u64())
+        .unwrap_or(index as u64);
+    let width = f64::from(frame.width.max(1));
+    let height = f64::from(frame.height.max(1));
+    let bbox = &detection.bbox;
+    let x = |px: u32| (f64::from(px) / width * 2.0 - 1.0).clamp(-1.0, 1.0);
+    let y = |px: u32| (1.0 - f64::from(px) / height * 2.0).clamp(-1.0, 1.0);
+    let (left, right) = (x(bbox.x), x(bbox.x.saturating_add(bbox.width)));
+    let (top, bottom) = (y(bbox.y), y(bbox.y.saturating_add(bbox.height)));
+    let _ = write!(
+        xml,
+        concat!(
+            r#"<tt:Object ObjectId="{}"><tt:Appearance><tt:Shape>"#,
+            r#"<tt:BoundingBox left="{:.4}" top="{:.4}" right="{:.4}" bottom="{:.4}"/>"#,
+            r#"<tt:CenterOfGravity x="{:.4}" y="{:.4}"/></tt:Shape>"#,
+            r#"<tt:Class><tt:Type Likelihood="{:.2}">{}</tt:Type></tt:Class>"#,
+        ),
+        object_id,
+        left,
+        top,
+        right,
+        bottom,
+        (left + right) / 2.0,
+        (top + bottom) / 2.0,
+        detection.confidence.clamp(0.0, 1.0),
+        object_type(&detection.class),
+    );
+    write_vehicle_info(xml, detection);
+    xml.push_str("</tt:Appearance></tt:Object>");
+}
+
+/// Vehicle make and type (from the vehicle_attributes secondary plugin) and
+/// plate number of a detection, as ONVIF `VehicleInfo`/`LicensePlateInfo`
+fn write_vehicle_info(xml: &mut String, detection: &Detection) {
+    let Some(metadata) = &detection.metadata else {
+        return;
+    };
+    let vehicle = metadata.get("vehicle");
+    let text = |value: Option<&serde_json::Value>| value.and_then(|v| v.as_str()).map(|v| escape(v).into_owned());
+    let brand = text(vehicle.and_then(|v| v.get("make")));
+    let kind = text(vehicle.and_then(|v| v.get("type")));
+    if brand.is_some() || kind.is_some() {
+        xml.push_str("<tt:VehicleInfo>");
+        if let Some(kind) = kind {
+            let _ = write!(xml, "<tt:Type>{kind}</tt:Type>");
+        }
+        if let Some(brand) = brand {
+            let _ = write!(xml, "<tt:Brand>{brand}</tt:Brand>");
+        }
+        xml.push_str("</tt:VehicleInfo>");
+    }
+    if let Some(plate) = text(metadata.get("plate_number")) {
+        let _ = write!(xml, "<tt:LicensePlateInfo><tt:PlateNumber>{plate}</tt:PlateNumber></tt:LicensePlateInfo>");
+    }
+}
+
+fn write_event(xml: &mut String, frame: &MetadataFrame, time: &str, change: &PresenceChange) {
+    let operation = if change.initialized { "Initialized" } else { "Changed" };
+    let _ = write!(
+        xml,
+        concat!(
+            r#"<wsnt:NotificationMessage>"#,
+            r#"<wsnt:Topic Dialect="http://www.onvif.org/ver10/tev/topicExpression/ConcreteSet">{}</wsnt:Topic>"#,
+            r#"<wsnt:Message><tt:Message UtcTime="{}" PropertyOperation="{}"><tt:Source>"#,
+            r#"<tt:SimpleItem Name="VideoSourceConfigurationToken" Value="{}"/>"#,
+            r#"<tt:SimpleItem Name="Rule" Value="{}"/>"#,
+            r#"<tt:SimpleItem Name="ClassType" Value="{}"/>"#,
+            r#"</tt:Source><tt:Data><tt:SimpleItem Name="State" Value="{}"/></tt:Data>"#,
+            r#"</tt:Message></wsnt:Message></wsnt:NotificationMessage>"#,
+        ),
+        OBJECT_TOPIC,
+        time,
+        operation,
+        escape(&frame.camera),
+        escape(&frame.result.task_id),
+        change.object_type,
+        change.present,
+    );
+}
+
+/// Description of the exported event topics, shaped like the `TopicSet` of
+/// an ONVIF `GetEventPropertiesResponse`
+pub fn topic_set() -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<wstop:TopicSet xmlns:wstop="http://docs.oasis-open.org/wsn/t-1" "#,
+            r#"xmlns:xs="http://www.w3.org/2001/XMLSchema" {}>"#,
+            r#"<tns1:RuleEngine><ObjectDetection><Object wstop:topic="true">"#,
+            r#"<tt:MessageDescription IsProperty="true"><tt:Source>"#,
+            r#"<tt:SimpleItemDescription Name="VideoSourceConfigurationToken" Type="tt:ReferenceToken"/>"#,
+            r#"<tt:SimpleItemDescription Name="Rule" Type="xs:string"/>"#,
+            r#"<tt:SimpleItemDescription Name="ClassType" Type="xs:string"/>"#,
+            r#"</tt:Source><tt:Data><tt:SimpleItemDescription Name="State" Type="xs:boolean"/></tt:Data>"#,
+            r#"</tt:MessageDescription></Object></ObjectDetection></tns1:RuleEngine>"#,
+            r#"</wstop:TopicSet>"#,
+        ),
+        NAMESPACES,
+    )
+}
+
+fn utc_time(timestamp_ms: u64) -> String {
+    let time = i64::try_from(timestamp_ms)
+        .ok()
+        .and_then(DateTime::<Utc>::from_timestamp_millis)
+        .unwrap_or_default();
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::BoundingBox;
+
+    fn detection(class: &str, x: u32, y: u32, width: u32, height: u32) -> Detection {
+        Detection {
+            class: class.to_string(),
+            confidence: 0.9,
+            bbox: BoundingBox { x, y, width, height },
+            metadata: None,
+        }
+    }
+
+    fn frame(detections: Vec<Detection>) -> MetadataFrame {
+        MetadataFrame {
+            camera: "cam-<1>".to_string(),
+            width: 640,
+            height: 480,
+            result: AiResult {
+                task_id: "task-1".to_string(),
+                timestamp: 1_700_000_000_123,
+                plugin_type: "yolov8_detector".to_string(),
+                detections,
+                confidence: None,
+                processing_time_ms: None,
+                metadata: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_render_normalizes_boxes_and_maps_classes() {
+        let frame = frame(vec![detection("person", 0, 0, 320, 240)]);
+        let xml = render(&frame, &[]);
+
+        assert!(xml.contains(r#"<tt:Frame UtcTime="2023-11-14T22:13:20.123Z">"#));
+        assert!(xml.contains(
+            r#"<tt:BoundingBox left="-1.0000" top="1.0000" right="0.0000" bottom="0.0000"/>"#
+        ));
+        assert!(xml.contains(r#"<tt:CenterOfGravity x="-0.5000" y="0.5000"/>"#));
+        assert!(xml.contains(r#"<tt:Type Likelihood="0.90">Human</tt:Type>"#));
+        assert!(!xml.contains("<tt:Event>"));
+    }
+
+    #[test]
+    fn test_render_carries_vehicle_attributes_and_plate() {
+        let mut plate = detection("license_plate", 0, 0, 40, 20);
+        plate.metadata = Some(serde_json::json!({
+            "plate_number": "AB<123>",
+            "vehicle": {"color": "white", "type": "car"}
+        }));
+        let xml = render(&frame(vec![plate]), &[]);
+
+        assert!(xml.contains("<tt:VehicleInfo><tt:Type>car</tt:Type></tt:VehicleInfo>"));
+        assert!(xml.contains("<tt:PlateNumber>AB&lt;123&gt;</tt:PlateNumber>"));
+        assert!(!xml.contains("<tt:Brand>"));
+        assert!(xml.contains("</tt:LicensePlateInfo></tt:Appearance></tt:Object>"));
+    }
+
+    #[test]
+    fn test_presence_events_on_change_only() {
+        let mut tracker = PresenceTracker::default();
+
+        let first = frame(vec![detection("person", 0, 0, 10, 10), detection("car", 0, 0, 10, 10)]);
+        let changes = tracker.update(&first.result);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.initialized && c.present));
+        let xml = render(&first, &changes);
+        assert!(xml.contains(r#"PropertyOperation="Initialized""#));
+        assert!(xml.contains(r#"Value="cam-&lt;1&gt;""#));
+
+        assert!(tracker.update(&first.result).is_empty());
+
+        let second = frame(vec![detection("person", 0, 0, 10, 10)]);
+        assert_eq!(
+            tracker.update(&second.result),
+            vec![PresenceChange {
+                object_type: "Vehicle",
+                present: false,
+                initialized: false,
+            }]
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Local outbox for coordinator outages
+//!
+//! Edge sites keep analyzing while the coordinator is unreachable. Task
+//! state changes the StateStore could not take and the sampled detection
+//! events of the timeline are written to a bounded queue under
+//! `AI_OUTBOX_DIR` and replayed once the coordinator answers again, also
+//! across ai-service restarts. Only the latest state of a task is kept;
+//! detection events beyond `AI_OUTBOX_MAX_EVENTS` drop the oldest.
+
+use anyhow::{Context, Result};
+use common::ai_tasks::AiTaskInfo;
+use common::state_store::StateStore;
+use common::timeline::{TimelineEvent, MAX_BATCH};
+use reqwest::Url;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const TASKS_FILE: &str = "tasks.json";
+const EVENTS_FILE: &str = "events.jsonl";
+
+#[derive(Debug, Clone)]
+pub struct OutboxConfig {
+    pub dir: PathBuf,
+    /// Detection events kept before the oldest are dropped
+    pub max_events: usize,
+    /// How often buffered entries are sent to the coordinator
+    pub replay_interval: Duration,
+}
+
+impl OutboxConfig {
+    /// Buffering is enabled when `AI_OUTBOX_DIR` is set
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var("AI_OUTBOX_DIR").ok().filter(|v| !v.is_empty())?;
+        let max_events = std::env::var("AI_OUTBOX_MAX_EVENTS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(10_000)
+            .max(1);
+        let replay_secs = std::env::var("AI_OUTBOX_REPLAY_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(5)
+            .max(1);
+        Some(Self {
+            dir: PathBuf::from(dir),
+            max_events,
+            replay_interval: Duration::from_secs(replay_secs),
+        })
+    }
+}
+
+/// Disk-backed queue of task states and detection events for the coordinator
+pub struct Outbox {
+    config: OutboxConfig,
+    store: Option<Arc<dyn StateStore>>,
+    client: reqwest::Client,
+    /// `POST /v1/timeline/events` of the coordinator, when the timeline is on
+    events_url: Option<Url>,
+    /// Latest unsaved state per task ID
+    tasks: Mutex<BTreeMap<String, AiTaskInfo>>,
+    events: Mutex<VecDeque<TimelineEvent>>,
+    reachable: AtomicBool,
+}
+
+impl Outbox {
+    /// Open the outbox in `config.dir`, loading what a previous run left
+    /// behind. Task states go to `store`; detection events are only buffered
+    /// when `timeline` (see `common::timeline::coordinator_from_env`) is set.
+    pub async fn open(
+        config: OutboxConfig,
+        store: Option<Arc<dyn StateStore>>,
+        timeline: Option<Url>,
+    ) -> Result<Self> {
+        tokio::fs::create_dir_all(&config.dir)
+            .await
+            .with_context(|| format!("failed to create outbox directory {}", config.dir.display()))?;
+        let events_url = timeline
+            .map(|url| url.join("v1/timeline/events"))
+            .transpose()
+            .context("invalid timeline endpoint")?;
+        let tasks = load_tasks(&config.dir.join(TASKS_FILE)).await?;
+        let mut events = load_events(&config.dir.join(EVENTS_FILE)).await?;
+        let excess = events.len().saturating_sub(config.max_events);
+        events.drain(..excess);
+        let outbox = Self {
+            config,
+            store,
+            client: reqwest::Client::new(),
+            events_url,
+            tasks: Mutex::new(tasks),
+            events: Mutex::new(events),
+            reachable: AtomicBool::new(true),
+        };
+        set_pending("task_state", outbox.tasks.lock().await.len());
+        set_pending("detection", outbox.events.lock().await.len());
+        Ok(outbox)
+    }
+
+    /// Whether detection events should be handed to [`Outbox::push_event`]
+    /// instead of the timeline publisher
+    pub fn buffers_events(&self) -> bool {
+        self.events_url.is_some()
+    }
+
+    /// Save a task state to the StateStore, buffering it while the store is
+    /// unreachable. Saves are serialized with the replay so an older
+    /// buffered state never overwrites a newer one.
+    pub async fn save_task(&self, info: &AiTaskInfo) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let mut tasks = self.tasks.lock().await;
+        match store.save_ai_task(info).await {
+            Ok(()) => {
+                if tasks.remove(&info.config.id).is_some() {
+                    self.write_tasks(&tasks).await;
+                }
+            }
+            Err(e) => {
+                if self.reachable.swap(false, Ordering::Relaxed) {
+                    warn!(error = %e, "coordinator unreachable, buffering AI results locally");
+                }
+                tasks.insert(info.config.id.clone(), info.clone());
+                self.write_tasks(&tasks).await;
+            }
+        }
+        set_pending("task_state", tasks.len());
+    }
+
+    /// Buffered task states, newer than what the StateStore holds
+    pub async fn buffered_tasks(&self) -> Vec<AiTaskInfo> {
+        self.tasks.lock().await.values().cloned().collect()
+    }
+
+    /// Queue a detection event for the timeline
+    pub async fn push_event(&self, event: TimelineEvent) {
+        let mut events = self.events.lock().await;
+        events.push_back(event);
+        if events.len() > self.config.max_events {
+            let dropped = events.len() - self.config.max_events;
+            events.drain(..dropped);
+            telemetry::metrics::AI_SERVICE_OUTBOX_DROPPED.inc_by(dropped as u64);
+            self.write_events(&events).await;
+        } else if let Some(event) = events.back() {
+            self.append_event(event).await;
+        }
+        set_pending("detection", events.len());
+    }
+
+    /// Replay every interval until the task is aborted
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.replay_interval);
+            loop {
+                ticker.tick().await;
+                let replayed = match self.replay_tasks().await {
+                    Ok(tasks) => self.deliver_events().await.map(|events| (tasks, events)),
+                    Err(e) => Err(e),
+                };
+                match replayed {
+                    Ok((tasks, events)) => {
+                        if !self.reachable.swap(true, Ordering::Relaxed) {
+                            info!(tasks, events, "coordinator reachable again, replayed buffered AI results");
+                        }
+                    }
+                    Err(e) => {
+                        if self.reachable.swap(false, Ordering::Relaxed) {
+                            warn!(error = %e, "coordinator unreachable, buffering AI results locally");
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Save buffered task states; stops at the first failure
+    async fn replay_tasks(&self) -> Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let mut tasks = self.tasks.lock().await;
+        let mut replayed = 0;
+        let mut result = Ok(());
+        while let Some((task_id, info)) = tasks.first_key_value() {
+            if let Err(e) = store.save_ai_task(info).await {
+                result = Err(e.context(format!("failed to replay state of AI task {task_id}")));
+                break;
+            }
+            tasks.pop_first();
+            replayed += 1;
+        }
+        if replayed > 0 {
+            self.write_tasks(&tasks).await;
+            set_pending("task_state", tasks.len());
+        }
+        result.map(|()| replayed)
+    }
+
+    /// Send buffered detection events in batches, oldest first; stops at
+    /// the first failure. The queue is not held during a request, so frame
+    /// processing keeps pushing events meanwhile.
+    async fn deliver_events(&self) -> Result<usize> {
+        let Some(url) = &self.events_url else {
+            return Ok(0);
+        };
+        let mut delivered = 0;
+        loop {
+            let batch: Vec<TimelineEvent> = self.events.lock().await.iter().take(MAX_BATCH).cloned().collect();
+            if batch.is_empty() {
+                return Ok(delivered);
+            }
+            self.client
+                .post(url.clone())
+                .json(&batch)
+                .send()
+                .await
+                .context("coordinator unreachable")?
+                .error_for_status()
+                .context("coordinator rejected timeline events")?;
+            // Events carry their own ids, so a batch retried after a lost
+            // response is stored once
+            let sent: HashSet<&str> = batch.iter().map(|e| e.id.as_str()).collect();
+            let mut events = self.events.lock().await;
+            events.retain(|e| !sent.contains(e.id.as_str()));
+            self.write_events(&events).await;
+            set_pending("detection", events.len());
+            delivered += batch.len();
+        }
+    }
+
+    async fn write_tasks(&self, tasks: &BTreeMap<String, AiTaskInfo>) {
+        let path = self.config.dir.join(TASKS_FILE);
+        let written = match serde_json::to_vec(tasks) {
+            Ok(json) => write_atomic(&path, &json).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            warn!(path = %path.display(), error = %e, "failed to write AI outbox");
+        }
+    }
+
+    async fn write_events(&self, events: &VecDeque<TimelineEvent>) {
+        let path = self.config.dir.join(EVENTS_FILE);
+        let mut lines = Vec::new();
+        for event in events {
+            if serde_json::to_writer(&mut lines, event).is_ok() {
+                lines.push(b'\n');
+            }
+        }
+        if let Err(e) = write_atomic(&path, &lines).await {
+            warn!(path = %path.display(), error = %e, "failed to write AI outbox");
+        }
+    }
+
+    async fn append_event(&self, event: &TimelineEvent) {
+        let path = self.config.dir.join(EVENTS_FILE);
+        let appended = async {
+            let mut line = serde_json::to_vec(event)?;
+            line.push(b'\n');
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await?;
+            file.write_all(&line).await?;
+            anyhow::Ok(())
+        };
+        if let Err(e) = appended.await {
+            warn!(path = %path.display(), error = %e, "failed to write AI outbox");
+        }
+    }
+}
+
+fn set_pending(kind: &str, pending: usize) {
+    telemetry::metrics::AI_SERVICE_OUTBOX_PENDING
+        .with_label_values(&[kind])
+        .set(pending as i64);
+}
+
+async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, contents).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+async fn load_tasks(path: &Path) -> Result<BTreeMap<String, AiTaskInfo>> {
+    match tokio::fs::read(path).await {
+        Ok(json) => serde_json::from_slice(&json)
+            .with_context(|| format!("corrupt AI outbox {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("failed to read AI outbox {}", path.display())),
+    }
+}
+
+/// Events of a previous run; a line torn by a crash mid-append is skipped
+async fn load_events(path: &Path) -> Result<VecDeque<TimelineEvent>> {
+    let lines = match tokio::fs::read_to_string(path).await {
+        Ok(lines) => lines,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(VecDeque::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read AI outbox {}", path.display())),
+    };
+    let mut events = VecDeque::new();
+    for line in lines.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(event) => events.push_back(event),
+            Err(e) => warn!(path = %path.display(), error = %e, "skipping unreadable AI outbox entry"),
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::timeline::TimelineEventKind;
+
+    fn config(max_events: usize) -> OutboxConfig {
+        OutboxConfig {
+            dir: std::env::temp_dir().join(format!("ai-outbox-{}", uuid::Uuid::new_v4())),
+            max_events,
+            replay_interval: Duration::from_secs(5),
+        }
+    }
+
+    fn timeline() -> Option<Url> {
+        Url::parse("http://127.0.0.1:9").ok()
+    }
+
+    #[tokio::test]
+    async fn events_survive_a_restart_and_drop_the_oldest_when_full() -> Result<()> {
+        let config = config(3);
+        let outbox = Outbox::open(config.clone(), None, timeline()).await?;
+        assert!(outbox.buffers_events());
+        for i in 0..5 {
+            outbox
+                .push_event(TimelineEvent::new(TimelineEventKind::Detection, "ai-service", format!("{i} person")))
+                .await;
+        }
+
+        let reopened = Outbox::open(config.clone(), None, timeline()).await?;
+        let summaries: Vec<String> = reopened.events.lock().await.iter().map(|e| e.summary.clone()).collect();
+        assert_eq!(summaries, ["2 person", "3 person", "4 person"]);
+
+        // Nothing is delivered while the coordinator is unreachable
+        assert!(reopened.deliver_events().await.is_err());
+        assert_eq!(reopened.events.lock().await.len(), 3);
+
+        tokio::fs::remove_dir_all(&config.dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_torn_event_line_is_skipped() -> Result<()> {
+        let config = config(10);
+        let outbox = Outbox::open(config.clone(), None, timeline()).await?;
+        outbox
+            .push_event(TimelineEvent::new(TimelineEventKind::Detection, "ai-service", "1 car"))
+            .await;
+        let path = config.dir.join(EVENTS_FILE);
+        let mut file = tokio::fs::OpenOptions::new().append(true).open(&path).await?;
+        file.write_all(b"{\"id\":\"tor").await?;
+
+        let reopened = Outbox::open(config.clone(), None, None).await?;
+        assert!(!reopened.buffers_events());
+        assert_eq!(reopened.events.lock().await.len(), 1);
+
+        tokio::fs::remove_dir_all(&config.dir).await?;
+        Ok(())
+    }
+}
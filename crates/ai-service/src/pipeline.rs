@@ -0,0 +1,407 @@
+//! Plugin pipelines: a task's `pipeline` (e.g.
+//! `yolov8_detector -> facial_recognition[person]`) chains plugins so that
+//! each stage after the first runs on crops of the previous stage's
+//! detections, optionally only those of the bracketed classes.
+//!
+//! The first stage is the task's `plugin_type` and runs on the full frame as
+//! usual. [`PipelineExecutor`] then runs the later stages: their detections
+//! are mapped back to frame coordinates, tagged with `metadata.pipeline`
+//! (`stage`, `plugin` and the index of the `parent` detection they were
+//! found in) and appended to the result. The result's `metadata.pipeline`
+//! lists the timing of every stage. A failing stage ends the chain and
+//! leaves the result as the previous stages produced it.
+
+use crate::plugin::registry::PluginRegistry;
+use crate::plugin::PluginError;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use common::ai_tasks::{AiResult, BoundingBox, VideoFrame};
+use image::DynamicImage;
+use serde::Serialize;
+use std::time::Instant;
+use tracing::warn;
+
+/// Crops smaller than this (in either dimension) are not passed on
+const MIN_CROP_SIZE: u32 = 8;
+
+/// Most crops one stage runs on per frame, `AI_PIPELINE_MAX_CROPS`
+/// (default 16); the most confident detections go first
+pub fn max_crops() -> usize {
+    std::env::var("AI_PIPELINE_MAX_CROPS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(16)
+}
+
+/// One plugin of a pipeline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    pub plugin: String,
+    /// Classes of the previous stage's detections this stage runs on; all
+    /// when empty
+    pub classes: Vec<String>,
+}
+
+impl Stage {
+    fn accepts(&self, class: &str) -> bool {
+        self.classes.is_empty() || self.classes.iter().any(|c| c == class)
+    }
+}
+
+/// Parse `plugin -> plugin[class, class] -> ...` into its stages
+pub fn parse(pipeline: &str) -> Result<Vec<Stage>> {
+    let stages = pipeline
+        .split("->")
+        .map(|stage| {
+            let stage = stage.trim();
+            let (plugin, classes) = match stage.split_once('[') {
+                Some((plugin, classes)) => {
+                    let classes = classes
+                        .strip_suffix(']')
+                        .ok_or_else(|| anyhow!("Pipeline stage '{}' is missing ']'", stage))?;
+                    let classes: Vec<String> = classes
+                        .split(',')
+                        .map(|class| class.trim().to_string())
+                        .filter(|class| !class.is_empty())
+                        .collect();
+                    if classes.is_empty() {
+                        bail!("Pipeline stage '{}' lists no classes", stage);
+                    }
+                    (plugin.trim(), classes)
+                }
+                None => (stage, Vec::new()),
+            };
+            if plugin.is_empty() {
+                bail!("Pipeline '{}' has an empty stage", pipeline);
+            }
+            Ok(Stage {
+                plugin: plugin.to_string(),
+                classes,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if stages.len() < 2 {
+        bail!("Pipeline '{}' needs at least two stages", pipeline);
+    }
+    if !stages[0].classes.is_empty() {
+        bail!("The first pipeline stage runs on the full frame and takes no classes");
+    }
+    Ok(stages)
+}
+
+/// Timing of one pipeline stage, as listed in the result's
+/// `metadata.pipeline`
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub plugin: String,
+    pub processing_time_ms: u64,
+    /// Crops the stage ran on (the first stage runs on the frame)
+    pub inputs: usize,
+    pub detections: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Runs the stages of a task pipeline after its first plugin
+pub struct PipelineExecutor<'a> {
+    plugins: &'a PluginRegistry,
+    stages: Vec<Stage>,
+}
+
+impl<'a> PipelineExecutor<'a> {
+    pub fn new(plugins: &'a PluginRegistry, pipeline: &str) -> Result<Self> {
+        Ok(Self {
+            plugins,
+            stages: parse(pipeline)?,
+        })
+    }
+
+    /// Check that the pipeline starts with the task's plugin and that every
+    /// stage is registered
+    pub async fn validate(&self, plugin_type: &str) -> Result<()> {
+        if self.stages[0].plugin != plugin_type {
+            bail!(
+                "Pipeline must start with the task plugin '{}', not '{}'",
+                plugin_type,
+                self.stages[0].plugin
+            );
+        }
+        for stage in &self.stages[1..] {
+            if !self.plugins.has_plugin(&stage.plugin).await {
+                bail!("Pipeline plugin '{}' not found", stage.plugin);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the later stages over `result`, the first stage's result for
+    /// `frame`, and record the timing of all stages in its metadata
+    pub async fn run(&self, frame: &VideoFrame, result: &mut AiResult, first_stage_ms: u64) {
+        let mut timings = vec![StageTiming {
+            plugin: self.stages[0].plugin.clone(),
+            processing_time_ms: first_stage_ms,
+            inputs: 1,
+            detections: result.detections.len(),
+            error: None,
+        }];
+        let mut image = None;
+        let mut parents: Vec<usize> = (0..result.detections.len()).collect();
+
+        for (index, stage) in self.stages.iter().enumerate().skip(1) {
+            let start = Instant::now();
+            let mut timing = StageTiming {
+                plugin: stage.plugin.clone(),
+                processing_time_ms: 0,
+                inputs: 0,
+                detections: 0,
+                error: None,
+            };
+            match self.run_stage(index, stage, frame, &mut image, result, &parents).await {
+                Ok((inputs, children)) => {
+                    timing.inputs = inputs;
+                    timing.detections = children.len();
+                    parents = children;
+                }
+                Err(e) => {
+                    let kind = PluginError::kind_of(&e);
+                    telemetry::metrics::AI_SERVICE_PLUGIN_ERRORS
+                        .with_label_values(&[&stage.plugin, kind.as_str()])
+                        .inc();
+                    warn!(task_id = %result.task_id, plugin = %stage.plugin, kind = kind.as_str(), "Pipeline stage failed: {:#}", e);
+                    timing.error = Some(format!("{:#}", e));
+                    parents.clear();
+                }
+            }
+            timing.processing_time_ms = start.elapsed().as_millis() as u64;
+            let failed = timing.error.is_some();
+            timings.push(timing);
+            if failed {
+                break;
+            }
+        }
+
+        let timings = serde_json::json!(timings);
+        match result.metadata.as_mut().and_then(|m| m.as_object_mut()) {
+            Some(metadata) => {
+                metadata.insert("pipeline".to_string(), timings);
+            }
+            None => result.metadata = Some(serde_json::json!({ "pipeline": timings })),
+        }
+    }
+
+    /// Run one stage on crops of the `parents` detections; returns the
+    /// number of crops and the indices of the detections it added
+    async fn run_stage(
+        &self,
+        index: usize,
+        stage: &Stage,
+        frame: &VideoFrame,
+        image: &mut Option<DynamicImage>,
+        result: &mut AiResult,
+        parents: &[usize],
+    ) -> Result<(usize, Vec<usize>)> {
+        let mut inputs: Vec<(usize, BoundingBox)> = parents
+            .iter()
+            .filter(|&&parent| stage.accepts(&result.detections[parent].class))
+            .filter_map(|&parent| {
+                clamp(&result.detections[parent].bbox, frame.width, frame.height)
+                    .map(|region| (parent, region))
+            })
+            .collect();
+        inputs.sort_by(|a, b| {
+            result.detections[b.0]
+                .confidence
+                .total_cmp(&result.detections[a.0].confidence)
+        });
+        inputs.truncate(max_crops());
+        if inputs.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+
+        if image.is_none() {
+            *image = Some(decode_frame(frame)?);
+        }
+        let image = image.as_ref().context("frame not decoded")?;
+        let crops = inputs
+            .iter()
+            .map(|(_, region)| crop_frame(frame, image, region))
+            .collect::<Result<Vec<_>>>()?;
+
+        let plugin = self.plugins.get(&stage.plugin).await?;
+        let outputs = plugin.read().await.process_batch(&crops).await?;
+
+        let mut children = Vec::new();
+        for ((parent, region), output) in inputs.iter().zip(outputs) {
+            for mut detection in output.detections {
+                let Some(bbox) = to_frame(&detection.bbox, region) else {
+                    continue;
+                };
+                detection.bbox = bbox;
+                let tag = serde_json::json!({
+                    "stage": index,
+                    "plugin": stage.plugin,
+                    "parent": parent,
+                });
+                match detection.metadata.as_mut().and_then(|m| m.as_object_mut()) {
+                    Some(metadata) => {
+                        metadata.insert("pipeline".to_string(), tag);
+                    }
+                    None => detection.metadata = Some(serde_json::json!({ "pipeline": tag })),
+                }
+                children.push(result.detections.len());
+                result.detections.push(detection);
+            }
+        }
+        Ok((inputs.len(), children))
+    }
+}
+
+fn decode_frame(frame: &VideoFrame) -> Result<DynamicImage> {
+    let image_data = base64::prelude::BASE64_STANDARD
+        .decode(&frame.data)
+        .context(PluginError::recoverable("Failed to decode base64 image"))?;
+    image::load_from_memory(&image_data).context(PluginError::recoverable("Failed to load image"))
+}
+
+/// The part of `bbox` inside the frame, unless it is too small to crop
+fn clamp(bbox: &BoundingBox, width: u32, height: u32) -> Option<BoundingBox> {
+    let x = bbox.x.min(width);
+    let y = bbox.y.min(height);
+    let region = BoundingBox {
+        x,
+        y,
+        width: bbox.width.min(width - x),
+        height: bbox.height.min(height - y),
+    };
+    (region.width >= MIN_CROP_SIZE && region.height >= MIN_CROP_SIZE).then_some(region)
+}
+
+/// A box found in a crop of `region`, in frame coordinates and cut to the
+/// region; `None` when it lies outside the crop
+fn to_frame(bbox: &BoundingBox, region: &BoundingBox) -> Option<BoundingBox> {
+    let x = bbox.x.min(region.width);
+    let y = bbox.y.min(region.height);
+    let width = bbox.width.min(region.width - x);
+    let height = bbox.height.min(region.height - y);
+    (width > 0 && height > 0).then(|| BoundingBox {
+        x: region.x + x,
+        y: region.y + y,
+        width,
+        height,
+    })
+}
+
+/// A JPEG frame of `region`, for the next stage
+fn crop_frame(frame: &VideoFrame, image: &DynamicImage, region: &BoundingBox) -> Result<VideoFrame> {
+    let crop = image.crop_imm(region.x, region.y, region.width, region.height).to_rgb8();
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(crop)
+        .write_to(&mut bytes, image::ImageFormat::Jpeg)
+        .context(PluginError::recoverable("Failed to encode pipeline crop"))?;
+    Ok(VideoFrame {
+        source_id: frame.source_id.clone(),
+        timestamp: frame.timestamp,
+        sequence: frame.sequence,
+        width: region.width,
+        height: region.height,
+        format: "jpeg".to_string(),
+        data: base64::prelude::BASE64_STANDARD.encode(bytes.into_inner()),
+        audio: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::mock_detector::MockDetectorPlugin;
+    use image::{Rgb, RgbImage};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    #[test]
+    fn parses_stages_and_class_filters() {
+        let stages = parse("yolov8_detector -> facial_recognition[person] -> vehicle_attributes[car, truck]").unwrap();
+        assert_eq!(
+            stages,
+            vec![
+                Stage { plugin: "yolov8_detector".to_string(), classes: vec![] },
+                Stage { plugin: "facial_recognition".to_string(), classes: vec!["person".to_string()] },
+                Stage {
+                    plugin: "vehicle_attributes".to_string(),
+                    classes: vec!["car".to_string(), "truck".to_string()],
+                },
+            ]
+        );
+
+        assert!(parse("yolov8_detector").is_err());
+        assert!(parse("yolov8_detector -> ").is_err());
+        assert!(parse("yolov8_detector -> lpr[car").is_err());
+        assert!(parse("yolov8_detector -> lpr[]").is_err());
+        assert!(parse("yolov8_detector[person] -> lpr").is_err());
+    }
+
+    #[test]
+    fn maps_crop_boxes_into_the_frame() {
+        let region = clamp(&BoundingBox { x: 600, y: 20, width: 100, height: 50 }, 640, 480).unwrap();
+        assert_eq!(region, BoundingBox { x: 600, y: 20, width: 40, height: 50 });
+        assert!(clamp(&BoundingBox { x: 636, y: 20, width: 100, height: 50 }, 640, 480).is_none());
+
+        let inner = to_frame(&BoundingBox { x: 10, y: 5, width: 100, height: 20 }, &region).unwrap();
+        assert_eq!(inner, BoundingBox { x: 610, y: 25, width: 30, height: 20 });
+        assert!(to_frame(&BoundingBox { x: 45, y: 5, width: 10, height: 10 }, &region).is_none());
+    }
+
+    #[tokio::test]
+    async fn runs_later_stages_on_crops_of_earlier_detections() {
+        let registry = PluginRegistry::new();
+        registry
+            .register(Arc::new(RwLock::new(MockDetectorPlugin::new())))
+            .await
+            .unwrap();
+
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(640, 480, Rgb([90, 90, 90])))
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        let frame = VideoFrame {
+            source_id: "cam-1".to_string(),
+            timestamp: 1000,
+            sequence: 5,
+            width: 640,
+            height: 480,
+            format: "png".to_string(),
+            data: base64::prelude::BASE64_STANDARD.encode(bytes.into_inner()),
+            audio: None,
+        };
+
+        let plugin = registry.get("mock_object_detector").await.unwrap();
+        let mut result = plugin.read().await.process_frame(&frame).await.unwrap();
+        let first = result.detections.len();
+        let persons = result.detections.iter().filter(|d| d.class == "person").count();
+        assert!(persons > 0);
+
+        let executor = PipelineExecutor::new(&registry, "mock_object_detector -> mock_object_detector[person]").unwrap();
+        executor.validate("mock_object_detector").await.unwrap();
+        assert!(executor.validate("yolov8_detector").await.is_err());
+        executor.run(&frame, &mut result, 3).await;
+
+        let stages = &result.metadata.as_ref().unwrap()["pipeline"];
+        assert_eq!(stages[0]["processing_time_ms"], 3);
+        assert_eq!(stages[0]["detections"], first);
+        assert_eq!(stages[1]["inputs"], persons);
+        assert_eq!(stages[1]["detections"], result.detections.len() - first);
+        assert!(stages[1].get("error").is_none());
+
+        assert!(result.detections.len() > first);
+        for child in &result.detections[first..] {
+            let tag = &child.metadata.as_ref().unwrap()["pipeline"];
+            assert_eq!(tag["stage"], 1);
+            let parent = &result.detections[tag["parent"].as_u64().unwrap() as usize];
+            assert_eq!(parent.class, "person");
+            assert!(child.bbox.x >= parent.bbox.x && child.bbox.y >= parent.bbox.y);
+            assert!(child.bbox.x + child.bbox.width <= parent.bbox.x + parent.bbox.width);
+            assert!(child.bbox.y + child.bbox.height <= parent.bbox.y + parent.bbox.height);
+        }
+    }
+}
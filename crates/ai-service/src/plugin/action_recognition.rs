@@ -2,7 +2,7 @@
 ///
 /// This plugin analyzes sequences of video frames to detect human actions.
 /// Supported actions: walking, running, sitting, standing, waving, jumping, etc.
-use super::AiPlugin;
+use super::{AiPlugin, PluginError};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use base64::Engine;
@@ -252,10 +252,10 @@ impl ActionRecognitionPlugin {
         let session_arc = self
             .session
             .as_ref()
-            .context("Model session not initialized")?;
+            .context(PluginError::fatal("Model session not initialized"))?;
 
         let mut session = session_arc.lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock session: {}", e))?;
+            .map_err(|e| PluginError::poisoned("session", e))?;
 
         // Create input tensor value
         let input_value = Value::from_array(input)?;
@@ -263,7 +263,7 @@ impl ActionRecognitionPlugin {
         // Run inference
         let outputs = session
             .run(ort::inputs![input_value])
-            .context("Failed to run inference")?;
+            .map_err(PluginError::inference)?;
 
         // Get output tensor
         let output_tensor = outputs[0].try_extract_tensor::<f32>()?;
@@ -404,7 +404,8 @@ impl AiPlugin for ActionRecognitionPlugin {
 
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         // Parse configuration
-        self.config = serde_json::from_value(config).context("Invalid configuration")?;
+        self.config = serde_json::from_value(config)
+            .context(PluginError::config("Invalid configuration"))?;
 
         // Update frame buffer capacity
         let temporal_window = self.config.temporal_window;
@@ -451,17 +452,18 @@ impl AiPlugin for ActionRecognitionPlugin {
                     session_builder.with_execution_providers([CPUExecutionProvider::default().build()])?;
             }
             _ => {
-                return Err(anyhow::anyhow!(
+                return Err(PluginError::config(format!(
                     "Unsupported execution provider: {}",
                     self.config.execution_provider
-                ));
+                ))
+                .into());
             }
         }
 
         // Load the model
         let session = session_builder
             .commit_from_file(&self.config.model_path)
-            .context("Failed to load ONNX model")?;
+            .context(PluginError::config("Failed to load ONNX model"))?;
 
         self.session = Some(Arc::new(Mutex::new(session)));
         self.initialized = true;
@@ -472,7 +474,7 @@ impl AiPlugin for ActionRecognitionPlugin {
 
     async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
         if !self.initialized {
-            return Err(anyhow::anyhow!("Plugin not initialized"));
+            return Err(PluginError::fatal("Plugin not initialized").into());
         }
 
         let start_time = std::time::Instant::now();
@@ -480,19 +482,20 @@ impl AiPlugin for ActionRecognitionPlugin {
         // Decode frame
         let img_data = base64::engine::general_purpose::STANDARD
             .decode(&frame.data)
-            .context("Failed to decode base64 frame data")?;
-        let img = image::load_from_memory(&img_data).context("Failed to load image")?;
+            .context(PluginError::recoverable("Failed to decode base64 frame data"))?;
+        let img = image::load_from_memory(&img_data)
+            .context(PluginError::recoverable("Failed to load image"))?;
 
         // Add frame to buffer
         {
             let mut buffer = self.frame_buffer.lock()
-                .map_err(|e| anyhow::anyhow!("Failed to lock frame buffer: {}", e))?;
+                .map_err(|e| PluginError::poisoned("frame buffer", e))?;
             buffer.push(img);
         }
 
         // Check if we have enough frames for inference
         let buffer = self.frame_buffer.lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock frame buffer: {}", e))?;
+            .map_err(|e| PluginError::poisoned("frame buffer", e))?;
 
         let detections = if buffer.is_ready() {
             // Preprocess sequence
@@ -3,8 +3,8 @@
 /// This plugin provides two types of anomaly detection:
 /// 1. Temporal: Detects unusual patterns in time-series metrics (object counts, activity times)
 /// 2. Spatial: Detects unusual objects or behaviors in specific zones
-use super::AiPlugin;
-use anyhow::{anyhow, Result};
+use super::{AiPlugin, PluginError};
+use anyhow::Result;
 use async_trait::async_trait;
 use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
 use serde::{Deserialize, Serialize};
@@ -477,7 +477,7 @@ impl AiPlugin for AnomalyDetectorPlugin {
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         if !config.is_null() {
             self.config = serde_json::from_value(config)
-                .map_err(|e| anyhow!("Failed to parse anomaly detector config: {}", e))?;
+                .map_err(|e| PluginError::config(format!("Failed to parse anomaly detector config: {}", e)))?;
         }
 
         // Reset temporal metrics with new history size
@@ -614,6 +614,7 @@ mod tests {
                     }
                 ]
             }).to_string(),
+            audio: None,
         };
 
         let result = plugin.process_frame(&frame).await.unwrap();
@@ -651,6 +652,7 @@ mod tests {
                         }
                     ]
                 }).to_string(),
+                audio: None,
             };
 
             plugin.process_frame(&frame).await.unwrap();
@@ -672,6 +674,7 @@ mod tests {
                     "metadata": null
                 })).collect::<Vec<_>>()
             }).to_string(),
+            audio: None,
         };
 
         let result = plugin.process_frame(&anomalous_frame).await.unwrap();
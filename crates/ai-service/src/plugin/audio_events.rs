@@ -0,0 +1,648 @@
+/// Audio event classification plugin
+///
+/// Classifies the audio submitted with frames (`VideoFrame::audio`) and
+/// reports security-relevant sounds as `glass_break`, `gunshot`, `scream`
+/// and `aggression` (shouting) detections:
+/// 1. With an ONNX sound classifier (mono waveform in, one score per label
+///    out, e.g. YAMNet-style models), events come from the classifier labels
+///    that `event_labels` maps to them
+/// 2. Without one, loud sounds are picked out against the chunk's background
+///    level: short ones are impulses, split by brightness into `glass_break`
+///    and `gunshot`, and sustained ones in the voice band are `scream`. The
+///    fallback is coarse and does not report `aggression`
+///
+/// Events carry `metadata.violation = true`, which the AI service raises as
+/// `ai_detection` alerts (see `crate::alerts`).
+use super::{AiPlugin, PluginError};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use common::ai_tasks::{AiResult, AudioChunk, BoundingBox, Detection, VideoFrame};
+use ndarray::Array2;
+use ort::{
+    session::{builder::GraphOptimizationLevel, Session},
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Length of the windows the fallback measures
+const WINDOW_MS: usize = 10;
+
+/// Sounds staying loud this long are sustained rather than impulses
+const SUSTAINED_MS: usize = 300;
+
+/// Jump over the previous window that makes a loud sound an impulse
+const ONSET_DB: f32 = 10.0;
+
+/// Impulses brighter than this are glass breaking, duller ones shots
+const GLASS_MIN_HZ: f32 = 3000.0;
+
+/// Band of the dominant frequency of screams
+const SCREAM_HZ: std::ops::RangeInclusive<f32> = 700.0..=3500.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioEventsConfig {
+    /// Path to the sound classifier ONNX model (optional - without it the
+    /// level-based fallback is used)
+    pub model_path: Option<String>,
+
+    /// Labels of the classifier's scores
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// Sample rate the classifier expects; audio is resampled to it
+    #[serde(default = "default_model_sample_rate")]
+    pub model_sample_rate: u32,
+
+    /// "none" (scores are probabilities), "sigmoid" or "softmax"
+    #[serde(default = "default_output_activation")]
+    pub output_activation: String,
+
+    /// Classifier labels reported as each event, matched case-insensitively
+    /// as substrings
+    #[serde(default = "default_event_labels")]
+    pub event_labels: HashMap<String, Vec<String>>,
+
+    /// Minimum confidence for an event to be reported
+    #[serde(default = "default_confidence")]
+    pub confidence_threshold: f32,
+
+    /// Fallback: level (dBFS) a sound needs to count
+    #[serde(default = "default_min_level_dbfs")]
+    pub min_level_dbfs: f32,
+
+    /// Fallback: how far (dB) a sound must rise above the background
+    #[serde(default = "default_min_rise_db")]
+    pub min_rise_db: f32,
+}
+
+fn default_model_sample_rate() -> u32 {
+    16000
+}
+
+fn default_output_activation() -> String {
+    "none".to_string()
+}
+
+fn default_event_labels() -> HashMap<String, Vec<String>> {
+    [
+        ("glass_break", &["glass", "shatter"][..]),
+        ("gunshot", &["gunshot", "gunfire"][..]),
+        ("scream", &["scream"][..]),
+        ("aggression", &["shout", "yell", "aggress"][..]),
+    ]
+    .into_iter()
+    .map(|(event, labels)| (event.to_string(), labels.iter().map(|l| l.to_string()).collect()))
+    .collect()
+}
+
+fn default_confidence() -> f32 {
+    0.5
+}
+
+fn default_min_level_dbfs() -> f32 {
+    -20.0
+}
+
+fn default_min_rise_db() -> f32 {
+    20.0
+}
+
+impl Default for AudioEventsConfig {
+    fn default() -> Self {
+        Self {
+            model_path: None,
+            labels: Vec::new(),
+            model_sample_rate: default_model_sample_rate(),
+            output_activation: default_output_activation(),
+            event_labels: default_event_labels(),
+            confidence_threshold: default_confidence(),
+            min_level_dbfs: default_min_level_dbfs(),
+            min_rise_db: default_min_rise_db(),
+        }
+    }
+}
+
+/// Per-task settings, read from the task's `model_config`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct AudioTaskConfig {
+    /// Events the task reports; all when unset
+    #[serde(default)]
+    events: Option<Vec<String>>,
+    #[serde(default)]
+    confidence_threshold: Option<f32>,
+}
+
+/// Mono samples in [-1, 1]
+#[derive(Debug, Clone)]
+struct Audio {
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+/// Decode and downmix an audio chunk
+fn decode_audio(chunk: &AudioChunk) -> Result<Audio> {
+    if chunk.sample_rate == 0 || chunk.channels == 0 {
+        return Err(PluginError::recoverable("audio needs a sample_rate and channels").into());
+    }
+    let data = base64::prelude::BASE64_STANDARD
+        .decode(&chunk.data)
+        .context(PluginError::recoverable("Failed to decode base64 audio"))?;
+    let interleaved: Vec<f32> = match chunk.format.as_str() {
+        "s16le" => data
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+        "f32le" => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).clamp(-1.0, 1.0))
+            .collect(),
+        other => {
+            return Err(PluginError::recoverable(format!("unsupported audio format '{}'", other)).into());
+        }
+    };
+    let sample_bytes = if chunk.format == "s16le" { 2 } else { 4 };
+    if data.len() % (sample_bytes * chunk.channels as usize) != 0 {
+        return Err(PluginError::recoverable("audio data ends in a partial sample").into());
+    }
+
+    let channels = chunk.channels as usize;
+    let samples = interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok(Audio {
+        sample_rate: chunk.sample_rate,
+        samples,
+    })
+}
+
+/// Linear resampling to `rate`
+fn resample(audio: &Audio, rate: u32) -> Vec<f32> {
+    if audio.sample_rate == rate || audio.samples.is_empty() {
+        return audio.samples.clone();
+    }
+    let len = (audio.samples.len() as u64 * rate as u64 / audio.sample_rate as u64) as usize;
+    let step = audio.sample_rate as f64 / rate as f64;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = audio.samples[index.min(audio.samples.len() - 1)];
+            let b = audio.samples[(index + 1).min(audio.samples.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// A sound found in a chunk
+#[derive(Debug, Clone, PartialEq)]
+struct AudioEvent {
+    event: String,
+    confidence: f32,
+    /// Start within the chunk
+    offset_ms: i64,
+    level_dbfs: Option<f32>,
+    frequency_hz: Option<f32>,
+    label: Option<String>,
+}
+
+/// Level and dominant frequency of one window
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    level_db: f32,
+    frequency_hz: f32,
+}
+
+fn windows(audio: &Audio) -> Vec<Window> {
+    let size = (audio.sample_rate as usize * WINDOW_MS / 1000).max(2);
+    audio
+        .samples
+        .chunks_exact(size)
+        .map(|w| {
+            let energy: f32 = w.iter().map(|s| s * s).sum();
+            let diff: f32 = w.windows(2).map(|p| (p[1] - p[0]).powi(2)).sum();
+            let rms = (energy / w.len() as f32).sqrt();
+            // A sine of angular frequency ω has diff/energy = 2(1 - cos ω)
+            let ratio = if energy > 0.0 { diff / energy } else { 0.0 };
+            let omega = (1.0 - ratio / 2.0).clamp(-1.0, 1.0).acos();
+            Window {
+                level_db: 20.0 * rms.max(1e-6).log10(),
+                frequency_hz: omega * audio.sample_rate as f32 / std::f32::consts::TAU,
+            }
+        })
+        .collect()
+}
+
+/// Level-based fallback detection
+fn detect_by_level(audio: &Audio, min_level_dbfs: f32, min_rise_db: f32) -> Vec<AudioEvent> {
+    let windows = windows(audio);
+    if windows.is_empty() {
+        return Vec::new();
+    }
+    let mut levels: Vec<f32> = windows.iter().map(|w| w.level_db).collect();
+    levels.sort_by(f32::total_cmp);
+    let background = levels[levels.len() / 5];
+
+    let mut events = Vec::new();
+    let mut i = 0;
+    while i < windows.len() {
+        let window = windows[i];
+        let rise = window.level_db - background;
+        if window.level_db < min_level_dbfs || rise < min_rise_db {
+            i += 1;
+            continue;
+        }
+
+        // The sound lasts until it falls 10 dB under its peak
+        let mut peak = window.level_db;
+        let mut end = i + 1;
+        while end < windows.len() && windows[end].level_db >= peak - 10.0 {
+            peak = peak.max(windows[end].level_db);
+            end += 1;
+        }
+        let sound = &windows[i..end];
+        let frequency_hz = sound.iter().map(|w| w.frequency_hz).sum::<f32>() / sound.len() as f32;
+        let previous = if i > 0 { windows[i - 1].level_db } else { background };
+
+        let event = if sound.len() * WINDOW_MS < SUSTAINED_MS {
+            (window.level_db - previous >= ONSET_DB)
+                .then_some(if frequency_hz >= GLASS_MIN_HZ { "glass_break" } else { "gunshot" })
+        } else {
+            SCREAM_HZ.contains(&frequency_hz).then_some("scream")
+        };
+        if let Some(event) = event {
+            events.push(AudioEvent {
+                event: event.to_string(),
+                confidence: (0.5 + (rise - min_rise_db) / 40.0).min(0.9),
+                offset_ms: (i * WINDOW_MS) as i64,
+                level_dbfs: Some(peak),
+                frequency_hz: Some(frequency_hz),
+                label: None,
+            });
+        }
+        i = end;
+    }
+    events
+}
+
+/// Strongest classifier label of each event
+fn events_from_scores(
+    scores: &[f32],
+    labels: &[String],
+    event_labels: &HashMap<String, Vec<String>>,
+) -> Vec<AudioEvent> {
+    let mut events: Vec<AudioEvent> = Vec::new();
+    for (label, &score) in labels.iter().zip(scores) {
+        let lower = label.to_lowercase();
+        for (event, patterns) in event_labels {
+            if !patterns.iter().any(|p| lower.contains(&p.to_lowercase())) {
+                continue;
+            }
+            match events.iter_mut().find(|e| &e.event == event) {
+                Some(existing) if existing.confidence >= score => {}
+                Some(existing) => {
+                    existing.confidence = score;
+                    existing.label = Some(label.clone());
+                }
+                None => events.push(AudioEvent {
+                    event: event.clone(),
+                    confidence: score,
+                    offset_ms: 0,
+                    level_dbfs: None,
+                    frequency_hz: None,
+                    label: Some(label.clone()),
+                }),
+            }
+        }
+    }
+    events.sort_by(|a, b| a.event.cmp(&b.event));
+    events
+}
+
+/// Audio event classification plugin
+pub struct AudioEventsPlugin {
+    config: AudioEventsConfig,
+    session: Option<Arc<Mutex<Session>>>,
+}
+
+impl AudioEventsPlugin {
+    pub fn new() -> Self {
+        Self {
+            config: AudioEventsConfig::default(),
+            session: None,
+        }
+    }
+
+    /// Scores of each label over the chunk; the highest per label when the
+    /// model scores several patches
+    fn run_classifier(&self, session: &Mutex<Session>, audio: &Audio) -> Result<Vec<f32>> {
+        let samples = resample(audio, self.config.model_sample_rate);
+        let input = Array2::from_shape_vec((1, samples.len()), samples)?;
+        let input_tensor = Value::from_array(input)?;
+
+        let mut session = session
+            .lock()
+            .map_err(|e| PluginError::poisoned("classifier session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
+        let (_, data) = outputs[0].try_extract_tensor::<f32>()?;
+
+        let classes = self.config.labels.len();
+        if classes == 0 || data.len() % classes != 0 {
+            return Err(PluginError::config(format!(
+                "classifier output of {} values does not match {} labels",
+                data.len(),
+                classes
+            ))
+            .into());
+        }
+        let mut scores = vec![f32::NEG_INFINITY; classes];
+        for patch in data.chunks_exact(classes) {
+            let patch = match self.config.output_activation.as_str() {
+                "sigmoid" => patch.iter().map(|v| 1.0 / (1.0 + (-v).exp())).collect(),
+                "softmax" => softmax(patch),
+                _ => patch.to_vec(),
+            };
+            for (score, value) in scores.iter_mut().zip(patch) {
+                *score = score.max(value);
+            }
+        }
+        Ok(scores)
+    }
+}
+
+impl Default for AudioEventsPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let exp: Vec<f32> = logits.iter().map(|v| (v - max).exp()).collect();
+    let sum: f32 = exp.iter().sum();
+    exp.into_iter().map(|v| v / sum).collect()
+}
+
+#[async_trait]
+impl AiPlugin for AudioEventsPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        "audio_events"
+    }
+
+    fn name(&self) -> &'static str {
+        "Audio Events"
+    }
+
+    fn description(&self) -> &'static str {
+        "Detects glass breaking, gunshots, screams and shouting in the audio submitted with frames"
+    }
+
+    fn version(&self) -> &'static str {
+        "1.0.0"
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "events": {
+                    "type": "array",
+                    "items": {"type": "string", "enum": ["glass_break", "gunshot", "scream", "aggression"]},
+                    "description": "Events the task reports (task model_config); all when unset"
+                },
+                "confidence_threshold": {
+                    "type": "number",
+                    "minimum": 0.0,
+                    "maximum": 1.0,
+                    "default": 0.5
+                },
+                "model_path": {
+                    "type": "string",
+                    "description": "Sound classifier ONNX model: [1, samples] mono waveform in, label scores out"
+                },
+                "labels": {
+                    "type": "array",
+                    "items": {"type": "string"}
+                },
+                "model_sample_rate": {"type": "integer", "default": 16000},
+                "output_activation": {"type": "string", "enum": ["none", "sigmoid", "softmax"], "default": "none"},
+                "event_labels": {
+                    "type": "object",
+                    "description": "Classifier label substrings reported as each event"
+                },
+                "min_level_dbfs": {"type": "number", "default": -20.0},
+                "min_rise_db": {"type": "number", "default": 20.0}
+            }
+        }))
+    }
+
+    fn supported_formats(&self) -> Vec<String> {
+        vec!["s16le".to_string(), "f32le".to_string()]
+    }
+
+    async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+        self.config = serde_json::from_value(config)
+            .context(PluginError::config("Invalid audio events config"))?;
+
+        if let Some(model_path) = self.config.model_path.clone() {
+            super::require_model_file(&model_path)?;
+            let session = Session::builder()
+                .context("Failed to create session builder")?
+                .with_optimization_level(GraphOptimizationLevel::Level3)
+                .context("Failed to set optimization level")?
+                .commit_from_file(&model_path)
+                .context(PluginError::config("Failed to load model from file"))?;
+            self.session = Some(Arc::new(Mutex::new(session)));
+            tracing::info!("Audio event classifier loaded from {}", model_path);
+        }
+        Ok(())
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        self.process_task_frame(frame, &serde_json::Value::Null).await
+    }
+
+    async fn process_task_frame(&self, frame: &VideoFrame, task_config: &serde_json::Value) -> Result<AiResult> {
+        let start = std::time::Instant::now();
+        let task_config: AudioTaskConfig = if task_config.is_null() {
+            AudioTaskConfig::default()
+        } else {
+            serde_json::from_value(task_config.clone())
+                .context(PluginError::config("Invalid audio events task config"))?
+        };
+        let chunk = frame
+            .audio
+            .as_ref()
+            .ok_or_else(|| PluginError::recoverable("frame carries no audio"))?;
+        let audio = decode_audio(chunk)?;
+
+        let (events, source) = match &self.session {
+            Some(session) => {
+                let scores = self.run_classifier(session, &audio)?;
+                (events_from_scores(&scores, &self.config.labels, &self.config.event_labels), "model")
+            }
+            None => (
+                detect_by_level(&audio, self.config.min_level_dbfs, self.config.min_rise_db),
+                "level",
+            ),
+        };
+
+        let threshold = task_config
+            .confidence_threshold
+            .unwrap_or(self.config.confidence_threshold);
+        let detections = events
+            .into_iter()
+            .filter(|e| e.confidence >= threshold)
+            .filter(|e| task_config.events.as_ref().is_none_or(|wanted| wanted.contains(&e.event)))
+            .map(|e| Detection {
+                class: e.event.clone(),
+                confidence: e.confidence,
+                bbox: BoundingBox {
+                    x: 0,
+                    y: 0,
+                    width: frame.width,
+                    height: frame.height,
+                },
+                metadata: Some(serde_json::json!({
+                    "violation": true,
+                    "audio_event": e.event,
+                    "source": source,
+                    "label": e.label,
+                    "offset_ms": chunk.offset_ms + e.offset_ms,
+                    "level_dbfs": e.level_dbfs,
+                    "frequency_hz": e.frequency_hz,
+                })),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(AiResult {
+            task_id: frame.source_id.clone(),
+            timestamp: frame.timestamp,
+            plugin_type: self.id().to_string(),
+            confidence: detections.iter().map(|d| d.confidence).reduce(f32::max),
+            detections,
+            processing_time_ms: Some(start.elapsed().as_millis() as u64),
+            metadata: Some(serde_json::json!({
+                "audio_ms": audio.samples.len() as u64 * 1000 / audio.sample_rate as u64,
+                "source": source,
+            })),
+        })
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(self.config.model_path.is_none() || self.session.is_some())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        tracing::info!("Shutting down audio events plugin");
+        self.session = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    /// One second of quiet noise (about -50 dBFS) with `sound` mixed in at
+    /// 500 ms
+    fn chunk(sound: impl Fn(f32) -> f32, sound_ms: u32) -> Audio {
+        let mut seed = 12345u32;
+        let samples = (0..RATE)
+            .map(|i| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = ((seed >> 16) as f32 / 32768.0 - 1.0) * 0.005;
+                let t = i as f32 / RATE as f32 - 0.5;
+                let in_sound = t >= 0.0 && t < sound_ms as f32 / 1000.0;
+                noise + if in_sound { sound(t) } else { 0.0 }
+            })
+            .collect();
+        Audio {
+            sample_rate: RATE,
+            samples,
+        }
+    }
+
+    fn tone(hz: f32, amplitude: f32) -> impl Fn(f32) -> f32 {
+        move |t| amplitude * (t * hz * std::f32::consts::TAU).sin()
+    }
+
+    #[test]
+    fn test_stereo_s16le_is_downmixed() {
+        let samples: [i16; 4] = [16384, 0, -16384, -16384];
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let chunk = AudioChunk {
+            sample_rate: 8000,
+            channels: 2,
+            format: "s16le".into(),
+            offset_ms: 0,
+            data: base64::prelude::BASE64_STANDARD.encode(&data),
+        };
+        let audio = decode_audio(&chunk).unwrap();
+        assert_eq!(audio.samples, vec![0.25, -0.5]);
+        assert_eq!(resample(&audio, 16000).len(), 4);
+
+        let odd = AudioChunk {
+            data: base64::prelude::BASE64_STANDARD.encode(&data[..6]),
+            ..chunk
+        };
+        assert!(decode_audio(&odd).is_err());
+    }
+
+    #[test]
+    fn test_level_fallback_tells_impulses_and_screams_apart() {
+        let shot = chunk(|t| 0.8 * (-t / 0.01).exp() * (t * 300.0 * std::f32::consts::TAU).sin(), 40);
+        let events = detect_by_level(&shot, -20.0, 20.0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "gunshot");
+        assert_eq!(events[0].offset_ms, 500);
+
+        let glass = chunk(tone(6000.0, 0.5), 50);
+        let events = detect_by_level(&glass, -20.0, 20.0);
+        assert_eq!(events.iter().map(|e| e.event.as_str()).collect::<Vec<_>>(), ["glass_break"]);
+
+        let scream = chunk(tone(1500.0, 0.3), 400);
+        let events = detect_by_level(&scream, -20.0, 20.0);
+        assert_eq!(events.iter().map(|e| e.event.as_str()).collect::<Vec<_>>(), ["scream"]);
+
+        let quiet = chunk(tone(1500.0, 0.02), 400);
+        assert!(detect_by_level(&quiet, -20.0, 20.0).is_empty());
+    }
+
+    #[test]
+    fn test_classifier_labels_map_to_events() {
+        let labels: Vec<String> = ["Speech", "Glass", "Shatter", "Gunshot, gunfire", "Yell"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        let events = events_from_scores(&[0.9, 0.3, 0.7, 0.1, 0.6], &labels, &default_event_labels());
+        let found: Vec<(&str, f32, Option<&str>)> = events
+            .iter()
+            .map(|e| (e.event.as_str(), e.confidence, e.label.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("aggression", 0.6, Some("Yell")),
+                ("glass_break", 0.7, Some("Shatter")),
+                ("gunshot", 0.1, Some("Gunshot, gunfire")),
+            ]
+        );
+    }
+}
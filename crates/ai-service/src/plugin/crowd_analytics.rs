@@ -1,5 +1,6 @@
 /// Crowd analytics plugin for person counting and density analysis
-use super::AiPlugin;
+use super::vision::{self, Candidate, Letterbox, NmsConfig, Normalization, ResizeMode};
+use super::{AiPlugin, PluginError};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
@@ -31,6 +32,14 @@ pub struct CrowdAnalyticsConfig {
     #[serde(default = "default_input_size")]
     pub input_size: u32,
 
+    /// How frames are fitted into the model input
+    #[serde(default)]
+    pub resize_mode: ResizeMode,
+
+    /// Non-maximum suppression variant
+    #[serde(default)]
+    pub nms: NmsConfig,
+
     /// Grid size for density heatmap (e.g., 10x10 grid)
     #[serde(default = "default_grid_size")]
     pub grid_size: usize,
@@ -111,6 +120,8 @@ impl Default for CrowdAnalyticsConfig {
             confidence_threshold: 0.5,
             iou_threshold: 0.45,
             input_size: 640,
+            resize_mode: ResizeMode::default(),
+            nms: NmsConfig::default(),
             grid_size: 10,
             coverage_area_sqm: 100.0,
             min_cluster_size: 3,
@@ -162,72 +173,16 @@ impl CrowdAnalyticsPlugin {
     /// Preprocess image to YOLOv8 input format
     fn preprocess_image(&self, img: &DynamicImage) -> Result<Array<f32, IxDyn>> {
         let size = self.config.input_size;
-        let resized = img.resize_exact(size, size, image::imageops::FilterType::Triangle);
-        let rgb_img = resized.to_rgb8();
-
-        let mut input = Array::zeros(IxDyn(&[1, 3, size as usize, size as usize]));
-
-        for (x, y, pixel) in rgb_img.enumerate_pixels() {
-            let r = pixel[0] as f32 / 255.0;
-            let g = pixel[1] as f32 / 255.0;
-            let b = pixel[2] as f32 / 255.0;
-
-            input[[0, 0, y as usize, x as usize]] = r;
-            input[[0, 1, y as usize, x as usize]] = g;
-            input[[0, 2, y as usize, x as usize]] = b;
-        }
-
+        let (input, _) = vision::to_nchw(
+            std::slice::from_ref(img),
+            size,
+            size,
+            self.config.resize_mode,
+            Normalization::Unit,
+        );
         Ok(input)
     }
 
-    /// Apply Non-Maximum Suppression
-    fn nms(&self, boxes: Vec<(BoundingBox, f32)>) -> Vec<(BoundingBox, f32)> {
-        if boxes.is_empty() {
-            return vec![];
-        }
-
-        let mut sorted_boxes = boxes.clone();
-        sorted_boxes.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-
-        let mut keep = Vec::new();
-
-        while !sorted_boxes.is_empty() {
-            let current = sorted_boxes.remove(0);
-            keep.push(current.clone());
-
-            sorted_boxes.retain(|box_item| {
-                let iou = self.calculate_iou(&current.0, &box_item.0);
-                iou < self.config.iou_threshold
-            });
-        }
-
-        keep
-    }
-
-    /// Calculate IoU between two bounding boxes
-    fn calculate_iou(&self, box1: &BoundingBox, box2: &BoundingBox) -> f32 {
-        let x1 = box1.x.max(box2.x);
-        let y1 = box1.y.max(box2.y);
-        let x2 = (box1.x + box1.width).min(box2.x + box2.width);
-        let y2 = (box1.y + box1.height).min(box2.y + box2.height);
-
-        let intersection = if x2 > x1 && y2 > y1 {
-            ((x2 - x1) * (y2 - y1)) as f32
-        } else {
-            0.0
-        };
-
-        let area1 = (box1.width * box1.height) as f32;
-        let area2 = (box2.width * box2.height) as f32;
-        let union = area1 + area2 - intersection;
-
-        if union > 0.0 {
-            intersection / union
-        } else {
-            0.0
-        }
-    }
-
     /// Post-process YOLOv8 output to detect people (class 0 in COCO)
     fn detect_people(
         &self,
@@ -235,8 +190,8 @@ impl CrowdAnalyticsPlugin {
         original_width: u32,
         original_height: u32,
     ) -> Result<Vec<BoundingBox>> {
-        let scale_x = original_width as f32 / self.config.input_size as f32;
-        let scale_y = original_height as f32 / self.config.input_size as f32;
+        let size = self.config.input_size;
+        let letterbox = Letterbox::new(self.config.resize_mode, original_width, original_height, size, size);
 
         let mut boxes = Vec::new();
 
@@ -251,32 +206,28 @@ impl CrowdAnalyticsPlugin {
                 continue;
             }
 
-            // Extract bounding box (cx, cy, w, h)
-            let cx = output[[0, 0, i]];
-            let cy = output[[0, 1, i]];
-            let w = output[[0, 2, i]];
-            let h = output[[0, 3, i]];
-
-            // Convert to (x, y, w, h) and scale to original image
-            let x = ((cx - w / 2.0) * scale_x).max(0.0) as u32;
-            let y = ((cy - h / 2.0) * scale_y).max(0.0) as u32;
-            let width = (w * scale_x).min(original_width as f32) as u32;
-            let height = (h * scale_y).min(original_height as f32) as u32;
-
-            boxes.push((
-                BoundingBox {
-                    x,
-                    y,
-                    width,
-                    height,
-                },
-                person_score,
-            ));
+            // Extract bounding box (cx, cy, w, h) and map it to the frame
+            let bbox = letterbox.to_frame(
+                output[[0, 0, i]],
+                output[[0, 1, i]],
+                output[[0, 2, i]],
+                output[[0, 3, i]],
+            );
+            boxes.push(Candidate {
+                bbox,
+                score: person_score,
+                class_id: 0,
+            });
         }
 
         // Apply NMS
-        let filtered_boxes = self.nms(boxes);
-        Ok(filtered_boxes.into_iter().map(|(bbox, _)| bbox).collect())
+        let people = vision::nms(
+            boxes,
+            self.config.iou_threshold,
+            self.config.confidence_threshold,
+            &self.config.nms,
+        );
+        Ok(people.into_iter().map(|c| c.bbox).collect())
     }
 
     /// Calculate density heatmap based on person locations
@@ -476,6 +427,20 @@ impl AiPlugin for CrowdAnalyticsPlugin {
                     "default": 0.5,
                     "description": "Confidence threshold for person detections"
                 },
+                "resize_mode": {
+                    "type": "string",
+                    "enum": ["letterbox", "stretch"],
+                    "default": "letterbox",
+                    "description": "Fit frames into the model input keeping their aspect ratio (letterbox) or stretch them"
+                },
+                "nms": {
+                    "type": "object",
+                    "properties": {
+                        "method": {"type": "string", "enum": ["hard", "soft", "diou"], "default": "hard"},
+                        "soft_sigma": {"type": "number", "default": 0.5}
+                    },
+                    "description": "Non-maximum suppression variant"
+                },
                 "grid_size": {
                     "type": "integer",
                     "minimum": 5,
@@ -514,9 +479,15 @@ impl AiPlugin for CrowdAnalyticsPlugin {
         false
     }
 
+    fn gpu_device(&self) -> Option<u32> {
+        let provider = self.execution_provider_used.lock().ok()?;
+        super::gpu_device(&provider, self.config.device_id)
+    }
+
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         if !config.is_null() {
-            self.config = serde_json::from_value(config)?;
+            self.config = serde_json::from_value(config)
+                .context(PluginError::config("Invalid crowd analytics configuration"))?;
         }
 
         // Override from environment variables
@@ -529,6 +500,8 @@ impl AiPlugin for CrowdAnalyticsPlugin {
             }
         }
 
+        super::require_model_file(&self.config.model_path)?;
+
         // Initialize ONNX session with execution provider fallback
         let provider_preference = self.config.execution_provider.to_uppercase();
         let (session, actual_provider) = match provider_preference.as_str() {
@@ -628,7 +601,7 @@ impl AiPlugin for CrowdAnalyticsPlugin {
         };
 
         self.session = Some(Arc::new(Mutex::new(session)));
-        *self.execution_provider_used.lock().map_err(|e| anyhow::anyhow!("Lock error: {}", e))? = actual_provider.clone();
+        *self.execution_provider_used.lock().map_err(|e| PluginError::poisoned("execution provider", e))? = actual_provider.clone();
 
         tracing::info!(
             "Initialized Crowd Analytics - model: {}, provider: {}, grid: {}x{}",
@@ -647,14 +620,15 @@ impl AiPlugin for CrowdAnalyticsPlugin {
         let session_lock = self
             .session
             .as_ref()
-            .context("Model not initialized - call init() first")?;
+            .context(PluginError::fatal("Model not initialized - call init() first"))?;
 
         // Decode base64 image
         let image_data = base64::prelude::BASE64_STANDARD
             .decode(&frame.data)
-            .context("Failed to decode base64 image")?;
+            .context(PluginError::recoverable("Failed to decode base64 image"))?;
 
-        let img = image::load_from_memory(&image_data).context("Failed to load image")?;
+        let img = image::load_from_memory(&image_data)
+            .context(PluginError::recoverable("Failed to load image"))?;
 
         let original_width = img.width();
         let original_height = img.height();
@@ -669,12 +643,16 @@ impl AiPlugin for CrowdAnalyticsPlugin {
         let inference_start = std::time::Instant::now();
         let mut session = session_lock
             .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock session: {}", e))?;
-        let outputs = session.run(ort::inputs![input_tensor])?;
+            .map_err(|e| PluginError::poisoned("session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
         let inference_time = inference_start.elapsed();
 
         // Get output tensor
-        let output_value = outputs.get("output0").context("No output tensor found")?;
+        let output_value = outputs
+            .get("output0")
+            .context(PluginError::config("No output tensor found"))?;
         let (shape, data) = output_value.try_extract_tensor::<f32>()?;
 
         let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
@@ -712,7 +690,7 @@ impl AiPlugin for CrowdAnalyticsPlugin {
         let execution_provider = self
             .execution_provider_used
             .lock()
-            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?
+            .map_err(|e| PluginError::poisoned("execution provider", e))?
             .clone();
 
         // Track metrics
@@ -0,0 +1,728 @@
+//! Plugins loaded from shared objects (`cdylib`) at runtime.
+//!
+//! A plugin crate depends on `ai-service`, implements `AiPlugin` as usual
+//! and exports it with [`export_dynamic_plugin!`](crate::export_dynamic_plugin),
+//! which generates a C ABI shim: `quadrant_plugin_entry` returns a
+//! [`DynamicPluginApi`] table of `extern "C"` functions that pass JSON
+//! (plugin info, configuration, frames, results) as C strings. Nothing Rust
+//! specific crosses the boundary, so the plugin does not have to be built by
+//! the same compiler as the service.
+//!
+//! - **Version handshake**: the table starts with `abi_version`, which is
+//!   checked against [`DYNAMIC_PLUGIN_ABI_VERSION`] before anything else in
+//!   it is used; a plugin built for another version is refused.
+//! - **Crash isolation**: every shim call runs under `catch_unwind`, so a
+//!   panicking plugin fails the call instead of unwinding into the service
+//!   (which would abort it). A panic is reported as a fatal plugin error and
+//!   the registry restarts the plugin with a fresh instance. Plugins must be
+//!   built with `panic = "unwind"` (the default); segfaults and aborts are
+//!   not caught.
+//!
+//! The shim drives the plugin's futures with a plain executor, outside any
+//! tokio runtime: plugins do their (blocking) inference directly, like the
+//! built-in ONNX plugins, and must not rely on tokio timers or I/O.
+//! Libraries stay loaded for the life of the process.
+
+use super::{AiPlugin, PluginError};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use common::ai_tasks::{AiResult, PluginErrorKind, PluginInfo, VideoFrame};
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+
+/// Version of the C ABI; bumped on any change to [`DynamicPluginApi`]
+pub const DYNAMIC_PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin library exports, returning its [`DynamicPluginApi`]
+pub const ENTRY_SYMBOL: &str = "quadrant_plugin_entry";
+
+/// Outcome of a shim call; errors carry a message in the out string
+pub const STATUS_OK: i32 = 0;
+pub const STATUS_RECOVERABLE: i32 = 1;
+pub const STATUS_FATAL: i32 = 2;
+pub const STATUS_CONFIG: i32 = 3;
+pub const STATUS_RESOURCE: i32 = 4;
+pub const STATUS_PANIC: i32 = 5;
+
+/// Function table a plugin library exports. Strings are NUL-terminated
+/// UTF-8; strings handed out by the plugin (`out`) are released with its
+/// `free_string`.
+#[repr(C)]
+pub struct DynamicPluginApi {
+    /// Must stay the first field, for the version handshake
+    pub abi_version: u32,
+    /// New plugin instance, or null when it could not be created
+    pub create: unsafe extern "C" fn() -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(instance: *mut c_void),
+    /// `PluginInfo` as JSON
+    pub info: unsafe extern "C" fn(instance: *mut c_void, out: *mut *mut c_char) -> i32,
+    /// `AiPlugin::init` with the configuration as JSON
+    pub init: unsafe extern "C" fn(instance: *mut c_void, config: *const c_char, out: *mut *mut c_char) -> i32,
+    /// `AiPlugin::process_task_frame` with a `VideoFrame` and the task's
+    /// model config as JSON; `AiResult` as JSON on success
+    pub process_frame: unsafe extern "C" fn(
+        instance: *mut c_void,
+        frame: *const c_char,
+        task_config: *const c_char,
+        out: *mut *mut c_char,
+    ) -> i32,
+    /// 1 when healthy, 0 when not, or an error status
+    pub health_check: unsafe extern "C" fn(instance: *mut c_void, out: *mut *mut c_char) -> i32,
+    pub shutdown: unsafe extern "C" fn(instance: *mut c_void, out: *mut *mut c_char) -> i32,
+    pub free_string: unsafe extern "C" fn(value: *mut c_char),
+}
+
+/// Creates the plugin instances a library serves; implemented by
+/// `export_dynamic_plugin!`
+pub trait DynamicPluginFactory {
+    fn create() -> Box<dyn AiPlugin>;
+}
+
+/// Plugin side of the ABI
+impl DynamicPluginApi {
+    /// Table of shim functions around the plugins `F` creates
+    pub const fn of<F: DynamicPluginFactory>() -> Self {
+        Self {
+            abi_version: DYNAMIC_PLUGIN_ABI_VERSION,
+            create: shim::create::<F>,
+            destroy: shim::destroy,
+            info: shim::info,
+            init: shim::init,
+            process_frame: shim::process_frame,
+            health_check: shim::health_check,
+            shutdown: shim::shutdown,
+            free_string: shim::free_string,
+        }
+    }
+}
+
+/// Export an `AiPlugin` from a `cdylib` crate:
+///
+/// ```ignore
+/// ai_service::export_dynamic_plugin!(MyDetector::new);
+/// ```
+///
+/// The expression is called for every instance the service creates.
+#[macro_export]
+macro_rules! export_dynamic_plugin {
+    ($constructor:expr) => {
+        struct __QuadrantPluginFactory;
+
+        impl $crate::plugin::dynamic::DynamicPluginFactory for __QuadrantPluginFactory {
+            fn create() -> Box<dyn $crate::plugin::AiPlugin> {
+                Box::new(($constructor)())
+            }
+        }
+
+        static __QUADRANT_PLUGIN_API: $crate::plugin::dynamic::DynamicPluginApi =
+            $crate::plugin::dynamic::DynamicPluginApi::of::<__QuadrantPluginFactory>();
+
+        #[no_mangle]
+        pub extern "C" fn quadrant_plugin_entry() -> *const $crate::plugin::dynamic::DynamicPluginApi {
+            &__QUADRANT_PLUGIN_API
+        }
+    };
+}
+
+/// The `extern "C"` functions of `DynamicPluginApi::of`, run inside the
+/// plugin library
+mod shim {
+    use super::*;
+
+    type Instance = Box<dyn AiPlugin>;
+
+    /// Message of a panic payload
+    fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+        payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string())
+    }
+
+    fn status_of(error: &anyhow::Error) -> i32 {
+        match PluginError::kind_of(error) {
+            PluginErrorKind::Recoverable => STATUS_RECOVERABLE,
+            PluginErrorKind::Fatal => STATUS_FATAL,
+            PluginErrorKind::Config => STATUS_CONFIG,
+            PluginErrorKind::Resource => STATUS_RESOURCE,
+        }
+    }
+
+    /// Hand `value` to the host; interior NULs cannot cross as a C string
+    unsafe fn write_out(out: *mut *mut c_char, value: String) {
+        if out.is_null() {
+            return;
+        }
+        let value = CString::new(value.replace('\0', " ")).unwrap_or_default();
+        *out = value.into_raw();
+    }
+
+    unsafe fn read_json<T: serde::de::DeserializeOwned>(value: *const c_char) -> Result<T> {
+        if value.is_null() {
+            return Ok(serde_json::from_str("null")?);
+        }
+        let text = CStr::from_ptr(value)
+            .to_str()
+            .context(PluginError::recoverable("Argument is not UTF-8"))?;
+        serde_json::from_str(text).context(PluginError::recoverable("Argument is not valid JSON"))
+    }
+
+    /// Run `call` on the instance, catching panics; `Ok` output goes to
+    /// `out` with `STATUS_OK`
+    unsafe fn guarded(
+        out: *mut *mut c_char,
+        call: impl FnOnce() -> Result<(i32, Option<String>)>,
+    ) -> i32 {
+        match catch_unwind(AssertUnwindSafe(call)) {
+            Ok(Ok((status, value))) => {
+                if let Some(value) = value {
+                    write_out(out, value);
+                }
+                status
+            }
+            Ok(Err(e)) => {
+                write_out(out, format!("{:#}", e));
+                status_of(&e)
+            }
+            Err(payload) => {
+                write_out(out, panic_message(payload.as_ref()));
+                STATUS_PANIC
+            }
+        }
+    }
+
+    pub(super) unsafe extern "C" fn create<F: DynamicPluginFactory>() -> *mut c_void {
+        match catch_unwind(F::create) {
+            Ok(plugin) => Box::into_raw(Box::new(plugin)) as *mut c_void,
+            Err(_) => std::ptr::null_mut(),
+        }
+    }
+
+    pub(super) unsafe extern "C" fn destroy(instance: *mut c_void) {
+        if instance.is_null() {
+            return;
+        }
+        // A panicking destructor only leaks the instance
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(instance as *mut Instance))));
+    }
+
+    pub(super) unsafe extern "C" fn info(instance: *mut c_void, out: *mut *mut c_char) -> i32 {
+        guarded(out, || {
+            let plugin = &*(instance as *const Instance);
+            Ok((STATUS_OK, Some(serde_json::to_string(&plugin.info())?)))
+        })
+    }
+
+    pub(super) unsafe extern "C" fn init(instance: *mut c_void, config: *const c_char, out: *mut *mut c_char) -> i32 {
+        guarded(out, || {
+            let plugin = &mut *(instance as *mut Instance);
+            let config = read_json::<serde_json::Value>(config)?;
+            futures::executor::block_on(plugin.init(config))?;
+            Ok((STATUS_OK, None))
+        })
+    }
+
+    pub(super) unsafe extern "C" fn process_frame(
+        instance: *mut c_void,
+        frame: *const c_char,
+        task_config: *const c_char,
+        out: *mut *mut c_char,
+    ) -> i32 {
+        guarded(out, || {
+            let plugin = &*(instance as *const Instance);
+            let frame = read_json::<VideoFrame>(frame)?;
+            let task_config = read_json::<serde_json::Value>(task_config)?;
+            let result = futures::executor::block_on(plugin.process_task_frame(&frame, &task_config))?;
+            Ok((STATUS_OK, Some(serde_json::to_string(&result)?)))
+        })
+    }
+
+    pub(super) unsafe extern "C" fn health_check(instance: *mut c_void, out: *mut *mut c_char) -> i32 {
+        guarded(out, || {
+            let plugin = &*(instance as *const Instance);
+            let healthy = futures::executor::block_on(plugin.health_check())?;
+            Ok((i32::from(healthy), None))
+        })
+    }
+
+    pub(super) unsafe extern "C" fn shutdown(instance: *mut c_void, out: *mut *mut c_char) -> i32 {
+        guarded(out, || {
+            let plugin = &mut *(instance as *mut Instance);
+            futures::executor::block_on(plugin.shutdown())?;
+            Ok((STATUS_OK, None))
+        })
+    }
+
+    pub(super) unsafe extern "C" fn free_string(value: *mut c_char) {
+        if !value.is_null() {
+            drop(CString::from_raw(value));
+        }
+    }
+}
+
+/// A plugin instance inside a loaded library
+struct Instance(NonNull<c_void>);
+
+// SAFETY: the instance is the shim's `Box<dyn AiPlugin>`, and `AiPlugin`
+// is `Send + Sync`; calls that mutate it (`init`, `shutdown`, `destroy`)
+// are only made through `&mut DynamicPlugin`
+unsafe impl Send for Instance {}
+unsafe impl Sync for Instance {}
+
+/// Host side: a plugin served by a shared object
+pub struct DynamicPlugin {
+    path: PathBuf,
+    api: &'static DynamicPluginApi,
+    instance: Option<Instance>,
+    info: PluginInfo,
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    version: &'static str,
+}
+
+/// Plugin metadata is `&'static str`; libraries are loaded once and never
+/// unloaded
+fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+fn error_for(plugin_id: &str, status: i32, message: String) -> PluginError {
+    match status {
+        STATUS_RECOVERABLE => PluginError::recoverable(format!("Plugin '{}': {}", plugin_id, message)),
+        STATUS_CONFIG => PluginError::config(format!("Plugin '{}': {}", plugin_id, message)),
+        STATUS_RESOURCE => PluginError::resource(format!("Plugin '{}': {}", plugin_id, message)),
+        STATUS_PANIC => PluginError::fatal(format!("Plugin '{}' panicked: {}", plugin_id, message)),
+        _ => PluginError::fatal(format!("Plugin '{}': {}", plugin_id, message)),
+    }
+}
+
+impl DynamicPlugin {
+    /// Load the library at `path` and check its ABI version
+    pub fn load(path: &Path) -> Result<Self> {
+        // SAFETY: loading runs the library's initializers; plugin libraries
+        // are trusted like the service binary itself
+        let library = unsafe { libloading::Library::new(path) }
+            .with_context(|| format!("Failed to load plugin library {}", path.display()))?;
+        let api = {
+            // SAFETY: the entry point has this signature in every ABI version
+            let entry = unsafe {
+                library.get::<unsafe extern "C" fn() -> *const DynamicPluginApi>(ENTRY_SYMBOL.as_bytes())
+            }
+            .with_context(|| format!("{} is not a plugin library (no `{}`)", path.display(), ENTRY_SYMBOL))?;
+            // SAFETY: see above
+            unsafe { entry() }
+        };
+        // SAFETY: the table is a static of the library, which is never
+        // unloaded once the handshake succeeds
+        let plugin = unsafe { Self::from_api(api, path) }?;
+        // Threads or destructors of the plugin may still run its code
+        std::mem::forget(library);
+        Ok(plugin)
+    }
+
+    /// Handshake with an entry table and read the plugin's metadata
+    ///
+    /// # Safety
+    ///
+    /// `api` is null or points to an entry table (of any ABI version) that
+    /// lives for the rest of the process
+    unsafe fn from_api(api: *const DynamicPluginApi, path: &Path) -> Result<Self> {
+        if api.is_null() {
+            bail!("Plugin library {} returned no entry table", path.display());
+        }
+        // SAFETY: `abi_version` is the first field in every ABI version, so
+        // it can be read before the layout of the rest is known
+        let abi_version = unsafe { std::ptr::addr_of!((*api).abi_version).read() };
+        if abi_version != DYNAMIC_PLUGIN_ABI_VERSION {
+            return Err(PluginError::config(format!(
+                "Plugin library {} was built for plugin ABI v{}, this ai-service supports v{}",
+                path.display(),
+                abi_version,
+                DYNAMIC_PLUGIN_ABI_VERSION
+            ))
+            .into());
+        }
+        // SAFETY: the versions match, so the table has this layout, and it
+        // lives as long as the (never unloaded) library
+        let api = unsafe { &*api };
+
+        let mut plugin = Self {
+            path: path.to_path_buf(),
+            api,
+            instance: None,
+            info: PluginInfo {
+                id: String::new(),
+                name: String::new(),
+                description: String::new(),
+                version: String::new(),
+                config_schema: None,
+                supported_formats: Vec::new(),
+                requires_gpu: false,
+                backend: None,
+            },
+            id: "",
+            name: "",
+            description: "",
+            version: "",
+        };
+        let instance = plugin.create()?;
+        let info = plugin.call(&instance, |instance, out| {
+            // SAFETY: a live instance of this library
+            unsafe { (api.info)(instance, out) }
+        });
+        plugin.destroy(instance);
+        let info = info
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("Plugin library {} failed to report its info", path.display()))?;
+        let info: PluginInfo = serde_json::from_str(&info.unwrap_or_default())
+            .with_context(|| format!("Plugin library {} reported invalid info", path.display()))?;
+        if info.id.trim().is_empty() {
+            bail!("Plugin library {} reports an empty plugin id", path.display());
+        }
+        plugin.id = leak(info.id.clone());
+        plugin.name = leak(info.name.clone());
+        plugin.description = leak(info.description.clone());
+        plugin.version = leak(info.version.clone());
+        plugin.info = info;
+        Ok(plugin)
+    }
+
+    fn create(&self) -> Result<Instance> {
+        // SAFETY: `create` takes no arguments
+        let instance = unsafe { (self.api.create)() };
+        NonNull::new(instance).map(Instance).ok_or_else(|| {
+            PluginError::fatal(format!("Plugin library {} failed to create an instance", self.path.display())).into()
+        })
+    }
+
+    fn destroy(&self, instance: Instance) {
+        // SAFETY: the instance came from this library's `create` and is not
+        // used again
+        unsafe { (self.api.destroy)(instance.0.as_ptr()) }
+    }
+
+    /// Make a shim call, taking ownership of the string it hands out: the
+    /// output on `STATUS_OK`, the error message otherwise
+    fn call(
+        &self,
+        instance: &Instance,
+        call: impl FnOnce(*mut c_void, *mut *mut c_char) -> i32,
+    ) -> Result<Option<String>, PluginError> {
+        let mut out: *mut c_char = std::ptr::null_mut();
+        let status = call(instance.0.as_ptr(), &mut out);
+        let value = if out.is_null() {
+            None
+        } else {
+            // SAFETY: the shim wrote a C string it allocated; it is copied
+            // and handed back to be freed by the library
+            let value = unsafe { CStr::from_ptr(out) }.to_string_lossy().into_owned();
+            unsafe { (self.api.free_string)(out) };
+            Some(value)
+        };
+        if status == STATUS_OK {
+            Ok(value)
+        } else {
+            let message = value.unwrap_or_else(|| format!("plugin call failed with status {}", status));
+            Err(error_for(self.id, status, message))
+        }
+    }
+
+    fn instance(&self) -> Result<&Instance> {
+        self.instance
+            .as_ref()
+            .ok_or_else(|| PluginError::fatal(format!("Plugin '{}' not initialized", self.id)).into())
+    }
+
+    /// Shared object the plugin was loaded from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DynamicPlugin {
+    fn drop(&mut self) {
+        if let Some(instance) = self.instance.take() {
+            self.destroy(instance);
+        }
+    }
+}
+
+#[async_trait]
+impl AiPlugin for DynamicPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn version(&self) -> &'static str {
+        self.version
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        self.info.config_schema.clone()
+    }
+
+    fn supported_formats(&self) -> Vec<String> {
+        self.info.supported_formats.clone()
+    }
+
+    fn requires_gpu(&self) -> bool {
+        self.info.requires_gpu
+    }
+
+    /// Initialize a fresh instance, so a restart after a panic does not
+    /// reuse the state the panic left behind
+    async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+        if let Some(old) = self.instance.take() {
+            self.destroy(old);
+        }
+        let instance = self.create()?;
+        let config = CString::new(serde_json::to_string(&config)?)?;
+        let api = self.api;
+        let outcome = self.call(&instance, |instance, out| {
+            // SAFETY: a live instance and a valid C string
+            unsafe { (api.init)(instance, config.as_ptr(), out) }
+        });
+        match outcome {
+            Ok(_) => {
+                self.instance = Some(instance);
+                tracing::info!(plugin = self.id, path = %self.path.display(), "Dynamic plugin initialized");
+                Ok(())
+            }
+            Err(e) => {
+                self.destroy(instance);
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        self.process_task_frame(frame, &serde_json::Value::Null).await
+    }
+
+    async fn process_task_frame(&self, frame: &VideoFrame, task_config: &serde_json::Value) -> Result<AiResult> {
+        let instance = self.instance()?;
+        let frame = CString::new(serde_json::to_string(frame)?)?;
+        let task_config = CString::new(serde_json::to_string(task_config)?)?;
+        let api = self.api;
+        let result = self.call(instance, |instance, out| {
+            // SAFETY: a live instance and valid C strings
+            unsafe { (api.process_frame)(instance, frame.as_ptr(), task_config.as_ptr(), out) }
+        })?;
+        serde_json::from_str(&result.unwrap_or_default())
+            .context(PluginError::recoverable(format!("Plugin '{}' returned an invalid result", self.id)))
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        let Ok(instance) = self.instance() else {
+            return Ok(false);
+        };
+        let api = self.api;
+        let mut out: *mut c_char = std::ptr::null_mut();
+        // SAFETY: a live instance; any string handed out is freed below
+        let status = unsafe { (api.health_check)(instance.0.as_ptr(), &mut out) };
+        if !out.is_null() {
+            // SAFETY: allocated by the shim
+            unsafe { (api.free_string)(out) };
+        }
+        Ok(status == 1)
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        tracing::info!("Shutting down dynamic plugin {}", self.id);
+        let Some(instance) = self.instance.take() else {
+            return Ok(());
+        };
+        let api = self.api;
+        let outcome = self.call(&instance, |instance, out| {
+            // SAFETY: a live instance, destroyed right after
+            unsafe { (api.shutdown)(instance, out) }
+        });
+        self.destroy(instance);
+        outcome.map(|_| ()).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::mock_detector::MockDetectorPlugin;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    crate::export_dynamic_plugin!(MockDetectorPlugin::new);
+
+    /// Panics on frame 13 and on `{"panic": true}`; counts the frames of
+    /// its instance
+    #[derive(Default)]
+    struct Flaky {
+        frames: AtomicU64,
+    }
+
+    #[async_trait]
+    impl AiPlugin for Flaky {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+
+        fn id(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn name(&self) -> &'static str {
+            "Flaky"
+        }
+
+        fn description(&self) -> &'static str {
+            "Panics on request"
+        }
+
+        fn version(&self) -> &'static str {
+            "0.1.0"
+        }
+
+        async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+            if config["panic"] == true {
+                panic!("bad config");
+            }
+            if config["reject"] == true {
+                return Err(PluginError::config("rejected").into());
+            }
+            Ok(())
+        }
+
+        async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+            if frame.sequence == 13 {
+                panic!("unlucky frame");
+            }
+            let frames = self.frames.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(AiResult {
+                task_id: frame.source_id.clone(),
+                timestamp: frame.timestamp,
+                plugin_type: self.id().to_string(),
+                detections: Vec::new(),
+                confidence: None,
+                processing_time_ms: None,
+                metadata: Some(serde_json::json!({"frames": frames})),
+            })
+        }
+    }
+
+    struct FlakyFactory;
+
+    impl DynamicPluginFactory for FlakyFactory {
+        fn create() -> Box<dyn AiPlugin> {
+            Box::new(Flaky::default())
+        }
+    }
+
+    static FLAKY: DynamicPluginApi = DynamicPluginApi::of::<FlakyFactory>();
+    static FUTURE: DynamicPluginApi = DynamicPluginApi {
+        abi_version: DYNAMIC_PLUGIN_ABI_VERSION + 1,
+        ..DynamicPluginApi::of::<FlakyFactory>()
+    };
+
+    fn frame(sequence: u64) -> VideoFrame {
+        VideoFrame {
+            source_id: "cam-1".to_string(),
+            timestamp: 1000,
+            sequence,
+            width: 1920,
+            height: 1080,
+            format: "jpeg".to_string(),
+            data: "AAEC".to_string(),
+            audio: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_a_plugin_through_the_c_abi() {
+        let mut plugin = unsafe { DynamicPlugin::from_api(quadrant_plugin_entry(), Path::new("libmock.so")) }.unwrap();
+        let info = plugin.info();
+        assert_eq!(info.id, "mock_object_detector");
+        assert_eq!(info.version, "1.0.0");
+        assert_eq!(info.supported_formats, vec!["jpeg", "png", "raw"]);
+        assert!(info.config_schema.is_some());
+        assert!(!plugin.health_check().await.unwrap());
+
+        plugin.init(serde_json::json!({"classes": ["person"]})).await.unwrap();
+        assert!(plugin.health_check().await.unwrap());
+        let result = plugin.process_frame(&frame(42)).await.unwrap();
+        let mut direct = MockDetectorPlugin::new();
+        direct.init(serde_json::json!({"classes": ["person"]})).await.unwrap();
+        let expected = direct.process_frame(&frame(42)).await.unwrap();
+        assert_eq!(result.plugin_type, "mock_object_detector");
+        assert_eq!(
+            serde_json::to_value(&result.detections).unwrap(),
+            serde_json::to_value(&expected.detections).unwrap()
+        );
+
+        plugin.shutdown().await.unwrap();
+        assert!(!plugin.health_check().await.unwrap());
+        assert!(plugin.process_frame(&frame(42)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn panics_are_fatal_and_restarts_get_a_fresh_instance() {
+        let mut plugin = unsafe { DynamicPlugin::from_api(&FLAKY, Path::new("libflaky.so")) }.unwrap();
+        let error = plugin.init(serde_json::json!({"panic": true})).await.unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), PluginErrorKind::Fatal);
+        assert!(error.to_string().contains("Plugin 'flaky' panicked: bad config"));
+        let error = plugin.init(serde_json::json!({"reject": true})).await.unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), PluginErrorKind::Config);
+
+        plugin.init(serde_json::json!({})).await.unwrap();
+        plugin.process_frame(&frame(1)).await.unwrap();
+        let result = plugin.process_frame(&frame(2)).await.unwrap();
+        assert_eq!(result.metadata, Some(serde_json::json!({"frames": 2})));
+
+        let error = plugin.process_frame(&frame(13)).await.unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), PluginErrorKind::Fatal);
+        assert!(error.to_string().contains("unlucky frame"));
+
+        // What the registry does on a fatal failure
+        plugin.shutdown().await.unwrap();
+        plugin.init(serde_json::json!({})).await.unwrap();
+        let result = plugin.process_frame(&frame(3)).await.unwrap();
+        assert_eq!(result.metadata, Some(serde_json::json!({"frames": 1})));
+    }
+
+    #[test]
+    fn refuses_other_abi_versions_and_other_libraries() {
+        let error = unsafe { DynamicPlugin::from_api(&FUTURE, Path::new("libnext.so")) }
+            .err()
+            .unwrap();
+        assert_eq!(PluginError::kind_of(&error), PluginErrorKind::Config);
+        assert!(error.to_string().contains("plugin ABI v2"));
+        assert!(unsafe { DynamicPlugin::from_api(std::ptr::null(), Path::new("libnull.so")) }.is_err());
+
+        assert!(DynamicPlugin::load(Path::new("/nonexistent/libplugin.so")).is_err());
+        #[cfg(target_os = "linux")]
+        {
+            let error = DynamicPlugin::load(Path::new("libc.so.6")).err().unwrap();
+            assert!(error.to_string().contains("not a plugin library"));
+        }
+    }
+}
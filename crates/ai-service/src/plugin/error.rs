@@ -0,0 +1,160 @@
+//! Typed plugin failures.
+//!
+//! Plugins keep returning `anyhow::Result`, and attach a `PluginError` to the
+//! failures they can classify, usually as context:
+//! `.context(PluginError::recoverable("Failed to decode base64 image"))`.
+//! `PluginError::kind_of` finds it anywhere in the error chain. A failure
+//! without one counts as recoverable, so an unclassified error only drops
+//! its frame and never restarts a plugin or fails a task.
+
+use common::ai_tasks::PluginErrorKind;
+
+/// Substrings of inference errors reporting exhausted memory (ONNX Runtime,
+/// CUDA and TensorRT word it differently)
+const RESOURCE_MARKERS: &[&str] = &[
+    "out of memory",
+    "out_of_memory",
+    "failed to allocate",
+    "bad_alloc",
+    "resource exhausted",
+];
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum PluginError {
+    /// Only this frame failed
+    #[error("{0}")]
+    Recoverable(String),
+
+    /// The plugin instance must be restarted
+    #[error("{0}")]
+    Fatal(String),
+
+    /// The plugin or task configuration is wrong
+    #[error("{0}")]
+    Config(String),
+
+    /// Out of memory, or the backend is unreachable
+    #[error("{0}")]
+    Resource(String),
+}
+
+impl PluginError {
+    pub fn recoverable(message: impl Into<String>) -> Self {
+        PluginError::Recoverable(message.into())
+    }
+
+    pub fn fatal(message: impl Into<String>) -> Self {
+        PluginError::Fatal(message.into())
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        PluginError::Config(message.into())
+    }
+
+    pub fn resource(message: impl Into<String>) -> Self {
+        PluginError::Resource(message.into())
+    }
+
+    /// Failure of an inference run: exhausted memory is a resource error,
+    /// anything else leaves the session unusable
+    pub fn inference(error: impl std::fmt::Display) -> Self {
+        let message = format!("Inference failed: {}", error);
+        let lower = message.to_ascii_lowercase();
+        if RESOURCE_MARKERS.iter().any(|marker| lower.contains(marker)) {
+            PluginError::Resource(message)
+        } else {
+            PluginError::Fatal(message)
+        }
+    }
+
+    /// A poisoned lock around plugin state
+    pub fn poisoned(what: &str, error: impl std::fmt::Display) -> Self {
+        PluginError::Fatal(format!("Failed to lock {}: {}", what, error))
+    }
+
+    pub fn kind(&self) -> PluginErrorKind {
+        match self {
+            PluginError::Recoverable(_) => PluginErrorKind::Recoverable,
+            PluginError::Fatal(_) => PluginErrorKind::Fatal,
+            PluginError::Config(_) => PluginErrorKind::Config,
+            PluginError::Resource(_) => PluginErrorKind::Resource,
+        }
+    }
+
+    /// Kind of a plugin failure; failures the plugin did not classify are
+    /// recoverable
+    pub fn kind_of(error: &anyhow::Error) -> PluginErrorKind {
+        error
+            .downcast_ref::<PluginError>()
+            .map(PluginError::kind)
+            .unwrap_or(PluginErrorKind::Recoverable)
+    }
+
+    /// A failure as a `PluginError` of its kind with the whole error chain as
+    /// message, e.g. to hand it to another task
+    pub fn from_anyhow(error: &anyhow::Error) -> Self {
+        let message = format!("{:#}", error);
+        match Self::kind_of(error) {
+            PluginErrorKind::Recoverable => PluginError::Recoverable(message),
+            PluginErrorKind::Fatal => PluginError::Fatal(message),
+            PluginErrorKind::Config => PluginError::Config(message),
+            PluginErrorKind::Resource => PluginError::Resource(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn finds_the_kind_anywhere_in_the_chain() {
+        let decode: anyhow::Result<()> = Err(anyhow!("invalid base64"));
+        let error = decode
+            .context(PluginError::recoverable("Failed to decode base64 image"))
+            .context("Failed to process frame with plugin")
+            .unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), PluginErrorKind::Recoverable);
+
+        let missing: Option<()> = None;
+        let error = missing
+            .context(PluginError::config("No output tensor found"))
+            .unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), PluginErrorKind::Config);
+
+        let error = anyhow::Error::from(PluginError::fatal("Model not initialized"));
+        assert_eq!(PluginError::kind_of(&error), PluginErrorKind::Fatal);
+
+        assert_eq!(
+            PluginError::kind_of(&anyhow!("something else")),
+            PluginErrorKind::Recoverable
+        );
+    }
+
+    #[test]
+    fn classifies_inference_failures() {
+        let oom = PluginError::inference(
+            "Non-zero status code returned while running Conv node. CUDA failure 2: out of memory",
+        );
+        assert_eq!(oom.kind(), PluginErrorKind::Resource);
+        let bad_alloc = PluginError::inference("Failed to allocate memory for requested buffer of size 6422528");
+        assert_eq!(bad_alloc.kind(), PluginErrorKind::Resource);
+        let crashed = PluginError::inference("Non-zero status code returned while running Reshape node");
+        assert_eq!(crashed.kind(), PluginErrorKind::Fatal);
+    }
+
+    #[test]
+    fn keeps_kind_and_chain_when_converted() {
+        let error = Err::<(), _>(anyhow!("CUDA failure 2: out of memory"))
+            .map_err(PluginError::inference)
+            .context("Batch failed")
+            .unwrap_err();
+        let converted = PluginError::from_anyhow(&error);
+        assert_eq!(converted.kind(), PluginErrorKind::Resource);
+        assert_eq!(
+            converted.to_string(),
+            "Batch failed: Inference failed: CUDA failure 2: out of memory"
+        );
+    }
+}
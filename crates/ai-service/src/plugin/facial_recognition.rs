@@ -4,10 +4,12 @@
 /// 1. Detection stage: Locates faces in the image using RetinaFace/SCRFD
 /// 2. Embedding stage: Extracts facial embeddings using ArcFace/FaceNet
 /// 3. Matching stage: Compares embeddings against enrolled face database
-use super::AiPlugin;
+use super::vision::{self, Candidate, Letterbox, NmsConfig, Normalization, ResizeMode};
+use super::{AiPlugin, PluginError};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
+use common::privacy::DataSubject;
 use base64::Engine;
 use image::DynamicImage;
 use ndarray::{Array, IxDyn};
@@ -44,6 +46,14 @@ pub struct FacialRecognitionConfig {
     #[serde(default = "default_detection_input_size")]
     pub detection_input_size: u32,
 
+    /// How frames are fitted into the detection model input
+    #[serde(default)]
+    pub resize_mode: ResizeMode,
+
+    /// Non-maximum suppression variant
+    #[serde(default)]
+    pub nms: NmsConfig,
+
     /// Embedding model input size (width and height)
     #[serde(default = "default_embedding_input_size")]
     pub embedding_input_size: u32,
@@ -67,6 +77,11 @@ pub struct FacialRecognitionConfig {
     /// Number of inter-operation threads
     #[serde(default = "default_inter_threads")]
     pub inter_threads: usize,
+
+    /// Largest number of frames (detection) or faces (embedding) run as one
+    /// inference; only used for models with a dynamic batch dimension
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
 }
 
 fn default_confidence() -> f32 {
@@ -109,6 +124,10 @@ fn default_inter_threads() -> usize {
     1
 }
 
+fn default_max_batch_size() -> usize {
+    8
+}
+
 impl Default for FacialRecognitionConfig {
     fn default() -> Self {
         Self {
@@ -118,12 +137,15 @@ impl Default for FacialRecognitionConfig {
             iou_threshold: default_iou_threshold(),
             max_detections: default_max_detections(),
             detection_input_size: default_detection_input_size(),
+            resize_mode: ResizeMode::default(),
+            nms: NmsConfig::default(),
             embedding_input_size: default_embedding_input_size(),
             similarity_threshold: default_similarity_threshold(),
             execution_provider: default_execution_provider(),
             device_id: default_device_id(),
             intra_threads: default_intra_threads(),
             inter_threads: default_inter_threads(),
+            max_batch_size: default_max_batch_size(),
         }
     }
 }
@@ -165,11 +187,38 @@ pub struct FaceMatch {
     pub metadata: Option<serde_json::Value>,
 }
 
+/// Changes to an enrolled face; fields left out are kept. The embedding only
+/// changes by enrolling the face again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FaceUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Replaces the metadata; `null` clears it
+    #[serde(default, deserialize_with = "present")]
+    pub metadata: Option<Option<serde_json::Value>>,
+}
+
+/// Tells a field sent as `null` (Some(None)) from one left out (None)
+fn present<'de, D>(deserializer: D) -> std::result::Result<Option<Option<serde_json::Value>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<serde_json::Value>::deserialize(deserializer).map(Some)
+}
+
+fn is_subject_face(face: &EnrolledFace, subject: &DataSubject) -> bool {
+    subject.face_ids.contains(&face.face_id) || subject.has_name(&face.name)
+}
+
 /// Facial Recognition plugin
 pub struct FacialRecognitionPlugin {
     config: FacialRecognitionConfig,
     detection_session: Option<Arc<tokio::sync::Mutex<Session>>>,
     embedding_session: Option<Arc<tokio::sync::Mutex<Session>>>,
+    /// Frames per detection inference the loaded model accepts
+    detection_batch: usize,
+    /// Faces per embedding inference the loaded model accepts
+    embedding_batch: usize,
     execution_provider_used: Arc<RwLock<String>>,
     /// In-memory face database: face_id -> EnrolledFace
     face_database: Arc<RwLock<HashMap<String, EnrolledFace>>>,
@@ -181,6 +230,8 @@ impl FacialRecognitionPlugin {
             config: FacialRecognitionConfig::default(),
             detection_session: None,
             embedding_session: None,
+            detection_batch: 1,
+            embedding_batch: 1,
             execution_provider_used: Arc::new(RwLock::new("CPU".to_string())),
             face_database: Arc::new(RwLock::new(HashMap::new())),
         }
@@ -208,7 +259,7 @@ impl FacialRecognitionPlugin {
         // Store in database
         self.face_database
             .write()
-            .map_err(|e| anyhow::anyhow!("Failed to lock face database: {}", e))?
+            .map_err(|e| PluginError::poisoned("face database", e))?
             .insert(face_id, enrolled_face.clone());
 
         Ok(enrolled_face)
@@ -219,133 +270,140 @@ impl FacialRecognitionPlugin {
         let removed = self
             .face_database
             .write()
-            .map_err(|e| anyhow::anyhow!("Failed to lock face database: {}", e))?
+            .map_err(|e| PluginError::poisoned("face database", e))?
             .remove(face_id)
             .is_some();
         Ok(removed)
     }
 
+    /// Get one enrolled face
+    pub fn get_face(&self, face_id: &str) -> Result<Option<EnrolledFace>> {
+        Ok(self
+            .face_database
+            .read()
+            .map_err(|e| PluginError::poisoned("face database", e))?
+            .get(face_id)
+            .cloned())
+    }
+
+    /// Change the name or metadata of an enrolled face, returning it or
+    /// `None` when it is not enrolled
+    pub fn update_face(&self, face_id: &str, update: FaceUpdate) -> Result<Option<EnrolledFace>> {
+        let mut database = self
+            .face_database
+            .write()
+            .map_err(|e| PluginError::poisoned("face database", e))?;
+        let Some(face) = database.get_mut(face_id) else {
+            return Ok(None);
+        };
+        if let Some(name) = update.name {
+            face.name = name;
+        }
+        if let Some(metadata) = update.metadata {
+            face.metadata = metadata;
+        }
+        Ok(Some(face.clone()))
+    }
+
     /// List all enrolled faces
     pub fn list_faces(&self) -> Result<Vec<EnrolledFace>> {
         Ok(self
             .face_database
             .read()
-            .map_err(|e| anyhow::anyhow!("Failed to lock face database: {}", e))?
+            .map_err(|e| PluginError::poisoned("face database", e))?
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    /// Enrolled faces of a data subject, by face id or enrolled name
+    pub fn faces_of(&self, subject: &DataSubject) -> Result<Vec<EnrolledFace>> {
+        Ok(self
+            .face_database
+            .read()
+            .map_err(|e| PluginError::poisoned("face database", e))?
             .values()
+            .filter(|face| is_subject_face(face, subject))
             .cloned()
             .collect())
     }
 
+    /// Remove every enrolled face of a data subject, returning how many
+    pub fn erase_subject(&self, subject: &DataSubject) -> Result<u64> {
+        let mut database = self
+            .face_database
+            .write()
+            .map_err(|e| PluginError::poisoned("face database", e))?;
+        let before = database.len();
+        database.retain(|_, face| !is_subject_face(face, subject));
+        Ok((before - database.len()) as u64)
+    }
+
     /// Get face database size
     pub fn database_size(&self) -> Result<usize> {
         Ok(self
             .face_database
             .read()
-            .map_err(|e| anyhow::anyhow!("Failed to lock face database: {}", e))?
+            .map_err(|e| PluginError::poisoned("face database", e))?
             .len())
     }
 
-    /// Preprocess image for detection model
-    fn preprocess_for_detection(&self, img: &DynamicImage) -> Result<Array<f32, IxDyn>> {
+    /// Preprocess images to one detection model input batch
+    fn preprocess_for_detection(&self, images: &[DynamicImage]) -> Result<Array<f32, IxDyn>> {
         let size = self.config.detection_input_size;
-        let resized = img.resize_exact(size, size, image::imageops::FilterType::Triangle);
-        let rgb_img = resized.to_rgb8();
-
-        // Convert to NCHW format and normalize to [0, 1]
-        let mut input = Array::zeros(IxDyn(&[1, 3, size as usize, size as usize]));
-
-        for (x, y, pixel) in rgb_img.enumerate_pixels() {
-            let r = pixel[0] as f32 / 255.0;
-            let g = pixel[1] as f32 / 255.0;
-            let b = pixel[2] as f32 / 255.0;
-
-            input[[0, 0, y as usize, x as usize]] = r;
-            input[[0, 1, y as usize, x as usize]] = g;
-            input[[0, 2, y as usize, x as usize]] = b;
-        }
-
+        let (input, _) = vision::to_nchw(
+            images,
+            size,
+            size,
+            self.config.resize_mode,
+            Normalization::Unit,
+        );
         Ok(input)
     }
 
-    /// Preprocess cropped face image for embedding model
-    fn preprocess_for_embedding(&self, img: &DynamicImage) -> Result<Array<f32, IxDyn>> {
+    /// Preprocess cropped face images to one embedding model input batch
+    fn preprocess_for_embedding(&self, faces: &[DynamicImage]) -> Result<Array<f32, IxDyn>> {
         let size = self.config.embedding_input_size;
-        let resized = img.resize_exact(size, size, image::imageops::FilterType::Triangle);
-        let rgb_img = resized.to_rgb8();
-
-        // Convert to NCHW format and normalize to [-1, 1] (typical for ArcFace)
-        let mut input = Array::zeros(IxDyn(&[1, 3, size as usize, size as usize]));
-
-        for (x, y, pixel) in rgb_img.enumerate_pixels() {
-            let r = (pixel[0] as f32 / 127.5) - 1.0;
-            let g = (pixel[1] as f32 / 127.5) - 1.0;
-            let b = (pixel[2] as f32 / 127.5) - 1.0;
-
-            input[[0, 0, y as usize, x as usize]] = r;
-            input[[0, 1, y as usize, x as usize]] = g;
-            input[[0, 2, y as usize, x as usize]] = b;
-        }
 
+        // Face crops are stretched and normalized to [-1, 1] (typical for ArcFace)
+        let (input, _) = vision::to_nchw(
+            faces,
+            size,
+            size,
+            ResizeMode::Stretch,
+            Normalization::Symmetric,
+        );
         Ok(input)
     }
 
     /// Apply Non-Maximum Suppression (NMS)
     fn nms(&self, boxes: Vec<(BoundingBox, f32)>) -> Vec<(BoundingBox, f32)> {
-        if boxes.is_empty() {
-            return vec![];
-        }
-
-        let mut sorted_boxes = boxes.clone();
-        sorted_boxes.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-
-        let mut keep = Vec::new();
-
-        while !sorted_boxes.is_empty() {
-            let current = sorted_boxes.remove(0);
-            keep.push(current.clone());
-
-            sorted_boxes.retain(|box_item| {
-                let iou = self.calculate_iou(&current.0, &box_item.0);
-                iou < self.config.iou_threshold
-            });
-        }
-
-        keep
+        let candidates = boxes
+            .into_iter()
+            .map(|(bbox, score)| Candidate { bbox, score, class_id: 0 })
+            .collect();
+        vision::nms(
+            candidates,
+            self.config.iou_threshold,
+            self.config.confidence_threshold,
+            &self.config.nms,
+        )
+        .into_iter()
+        .map(|c| (c.bbox, c.score))
+        .collect()
     }
 
-    /// Calculate Intersection over Union (IoU)
-    fn calculate_iou(&self, box1: &BoundingBox, box2: &BoundingBox) -> f32 {
-        let x1 = box1.x.max(box2.x);
-        let y1 = box1.y.max(box2.y);
-        let x2 = (box1.x + box1.width).min(box2.x + box2.width);
-        let y2 = (box1.y + box1.height).min(box2.y + box2.height);
-
-        let intersection = if x2 > x1 && y2 > y1 {
-            ((x2 - x1) * (y2 - y1)) as f32
-        } else {
-            0.0
-        };
-
-        let area1 = (box1.width * box1.height) as f32;
-        let area2 = (box2.width * box2.height) as f32;
-        let union = area1 + area2 - intersection;
-
-        if union > 0.0 {
-            intersection / union
-        } else {
-            0.0
-        }
-    }
-
-    /// Post-process detection output (YOLO/RetinaFace format)
+    /// Post-process the detection output (YOLO/RetinaFace format) of one
+    /// image of the batch
     fn postprocess_detection(
         &self,
-        output: Array<f32, IxDyn>,
+        output: &Array<f32, IxDyn>,
+        batch_index: usize,
         original_width: u32,
         original_height: u32,
     ) -> Result<Vec<(BoundingBox, f32)>> {
-        let scale_x = original_width as f32 / self.config.detection_input_size as f32;
-        let scale_y = original_height as f32 / self.config.detection_input_size as f32;
+        let size = self.config.detection_input_size;
+        let letterbox = Letterbox::new(self.config.resize_mode, original_width, original_height, size, size);
 
         let mut boxes = Vec::new();
 
@@ -354,34 +412,21 @@ impl FacialRecognitionPlugin {
 
         for i in 0..num_predictions {
             // Get confidence score (index 4)
-            let confidence = output[[0, 4, i]];
+            let confidence = output[[batch_index, 4, i]];
 
             // Filter by confidence threshold
             if confidence < self.config.confidence_threshold {
                 continue;
             }
 
-            // Extract bounding box (cx, cy, w, h)
-            let cx = output[[0, 0, i]];
-            let cy = output[[0, 1, i]];
-            let w = output[[0, 2, i]];
-            let h = output[[0, 3, i]];
-
-            // Convert to (x, y, w, h) and scale to original image
-            let x = ((cx - w / 2.0) * scale_x).max(0.0) as u32;
-            let y = ((cy - h / 2.0) * scale_y).max(0.0) as u32;
-            let width = (w * scale_x).min(original_width as f32) as u32;
-            let height = (h * scale_y).min(original_height as f32) as u32;
-
-            boxes.push((
-                BoundingBox {
-                    x,
-                    y,
-                    width,
-                    height,
-                },
-                confidence,
-            ));
+            // Extract bounding box (cx, cy, w, h) and map it to the frame
+            let bbox = letterbox.to_frame(
+                output[[batch_index, 0, i]],
+                output[[batch_index, 1, i]],
+                output[[batch_index, 2, i]],
+                output[[batch_index, 3, i]],
+            );
+            boxes.push((bbox, confidence));
         }
 
         // Apply NMS
@@ -392,54 +437,62 @@ impl FacialRecognitionPlugin {
 
     /// Extract face embedding vector
     async fn extract_embedding(&self, face_img: &DynamicImage) -> Result<Vec<f32>> {
-        if self.embedding_session.is_none() {
-            return Err(anyhow::anyhow!("Embedding model not initialized"));
-        }
+        self.extract_embeddings(std::slice::from_ref(face_img))
+            .await?
+            .pop()
+            .context("No embedding extracted")
+    }
 
+    /// Extract the embedding vectors of several faces, batched as far as
+    /// the embedding model allows
+    async fn extract_embeddings(&self, faces: &[DynamicImage]) -> Result<Vec<Vec<f32>>> {
         let session_lock = self
             .embedding_session
             .as_ref()
-            .context("Embedding model not initialized")?;
-
-        // Preprocess face image
-        let input_array = self.preprocess_for_embedding(face_img)?;
-
-        // Convert to ort Value
-        let input_tensor = Value::from_array(input_array)?;
-
-        // Run embedding inference
-        let mut session = session_lock.lock().await;
-        let outputs = session.run(ort::inputs![input_tensor])?;
-
-        // Get output tensor (embedding vector)
-        // Expected shape: [batch, embedding_dim] (e.g., [1, 512])
-        let output_value = outputs
-            .get("output")
-            .or_else(|| outputs.get("output0"))
-            .or_else(|| outputs.get("embedding"))
-            .context("No embedding output tensor found")?;
-        let (shape, data) = output_value.try_extract_tensor::<f32>()?;
-
-        let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
-        let output = Array::from_shape_vec(IxDyn(&shape_usize), data.to_vec())?;
-
-        // Extract embedding vector and normalize (L2 normalization)
-        let embedding_dim = output.shape()[1];
-        let mut embedding: Vec<f32> = Vec::with_capacity(embedding_dim);
-
-        for i in 0..embedding_dim {
-            embedding.push(output[[0, i]]);
-        }
-
-        // L2 normalization
-        let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-        if norm > 0.0 {
-            for val in &mut embedding {
-                *val /= norm;
+            .context(PluginError::fatal("Embedding model not initialized"))?;
+
+        let mut embeddings = Vec::with_capacity(faces.len());
+        for batch in faces.chunks(self.embedding_batch.max(1)) {
+            // Preprocess face images
+            let input_array = self.preprocess_for_embedding(batch)?;
+
+            // Convert to ort Value
+            let input_tensor = Value::from_array(input_array)?;
+
+            // Run embedding inference
+            let mut session = session_lock.lock().await;
+            let outputs = session
+                .run(ort::inputs![input_tensor])
+                .map_err(PluginError::inference)?;
+
+            // Get output tensor (embedding vectors)
+            // Expected shape: [batch, embedding_dim] (e.g., [1, 512])
+            let output_value = outputs
+                .get("output")
+                .or_else(|| outputs.get("output0"))
+                .or_else(|| outputs.get("embedding"))
+                .context(PluginError::config("No embedding output tensor found"))?;
+            let (shape, data) = output_value.try_extract_tensor::<f32>()?;
+
+            let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
+            let output = Array::from_shape_vec(IxDyn(&shape_usize), data.to_vec())?;
+
+            // Extract embedding vectors and normalize (L2 normalization)
+            let embedding_dim = output.shape()[1];
+            for n in 0..batch.len() {
+                let mut embedding: Vec<f32> = (0..embedding_dim).map(|i| output[[n, i]]).collect();
+
+                let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+                if norm > 0.0 {
+                    for val in &mut embedding {
+                        *val /= norm;
+                    }
+                }
+                embeddings.push(embedding);
             }
         }
 
-        Ok(embedding)
+        Ok(embeddings)
     }
 
     /// Calculate cosine similarity between two embeddings
@@ -463,7 +516,7 @@ impl FacialRecognitionPlugin {
         let database = self
             .face_database
             .read()
-            .map_err(|e| anyhow::anyhow!("Failed to lock face database: {}", e))?;
+            .map_err(|e| PluginError::poisoned("face database", e))?;
 
         let mut best_match: Option<FaceMatch> = None;
         let mut best_similarity = self.config.similarity_threshold;
@@ -487,6 +540,7 @@ impl FacialRecognitionPlugin {
 
     /// Create ONNX session with execution provider fallback
     fn create_session(&self, model_path: &str) -> Result<(Session, String)> {
+        super::require_model_file(model_path)?;
         let provider_preference = self.config.execution_provider.to_uppercase();
 
         match provider_preference.as_str() {
@@ -570,7 +624,7 @@ impl FacialRecognitionPlugin {
             .with_inter_threads(self.config.inter_threads)
             .context("Failed to set inter threads")?
             .commit_from_file(model_path)
-            .context("Failed to load model from file")?;
+            .context(PluginError::config("Failed to load model from file"))?;
         Ok((session, "CPU".to_string()))
     }
 }
@@ -645,6 +699,20 @@ impl AiPlugin for FacialRecognitionPlugin {
                     "default": 640,
                     "description": "Detection model input size"
                 },
+                "resize_mode": {
+                    "type": "string",
+                    "enum": ["letterbox", "stretch"],
+                    "default": "letterbox",
+                    "description": "Fit frames into the detection input keeping their aspect ratio (letterbox) or stretch them"
+                },
+                "nms": {
+                    "type": "object",
+                    "properties": {
+                        "method": {"type": "string", "enum": ["hard", "soft", "diou"], "default": "hard"},
+                        "soft_sigma": {"type": "number", "default": 0.5}
+                    },
+                    "description": "Non-maximum suppression variant"
+                },
                 "embedding_input_size": {
                     "type": "integer",
                     "default": 112,
@@ -680,6 +748,12 @@ impl AiPlugin for FacialRecognitionPlugin {
                     "minimum": 1,
                     "default": 1,
                     "description": "Number of inter-operation threads"
+                },
+                "max_batch_size": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "default": 8,
+                    "description": "Frames or faces per inference for models exported with a dynamic batch dimension"
                 }
             },
             "required": ["detection_model_path"]
@@ -694,9 +768,15 @@ impl AiPlugin for FacialRecognitionPlugin {
         false // Can run on CPU, GPU is optional
     }
 
+    fn gpu_device(&self) -> Option<u32> {
+        let provider = self.execution_provider_used.read().ok()?;
+        super::gpu_device(&provider, self.config.device_id)
+    }
+
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         if !config.is_null() {
-            self.config = serde_json::from_value(config)?;
+            self.config = serde_json::from_value(config)
+                .context(PluginError::config("Invalid face recognition configuration"))?;
         }
 
         // Read GPU configuration from environment variables if set
@@ -712,8 +792,10 @@ impl AiPlugin for FacialRecognitionPlugin {
         // Initialize detection model
         let (detection_session, actual_provider) =
             self.create_session(&self.config.detection_model_path)?;
+        self.detection_batch =
+            super::batch_capacity(&detection_session, self.config.max_batch_size);
         self.detection_session = Some(Arc::new(tokio::sync::Mutex::new(detection_session)));
-        *self.execution_provider_used.write().map_err(|e| anyhow::anyhow!("Failed to lock execution provider: {}", e))? = actual_provider.clone();
+        *self.execution_provider_used.write().map_err(|e| PluginError::poisoned("execution provider", e))? = actual_provider.clone();
 
         tracing::info!(
             "Initialized face detection model - path: {}, provider: {}, device: {}",
@@ -725,6 +807,8 @@ impl AiPlugin for FacialRecognitionPlugin {
         // Initialize embedding model if provided
         if let Some(ref embedding_path) = self.config.embedding_model_path {
             let (embedding_session, embedding_provider) = self.create_session(embedding_path)?;
+            self.embedding_batch =
+                super::batch_capacity(&embedding_session, self.config.max_batch_size);
             self.embedding_session = Some(Arc::new(tokio::sync::Mutex::new(embedding_session)));
 
             tracing::info!(
@@ -739,133 +823,180 @@ impl AiPlugin for FacialRecognitionPlugin {
         Ok(())
     }
 
+    fn max_batch_size(&self) -> usize {
+        self.detection_batch
+    }
+
     async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        self.process_batch(std::slice::from_ref(frame))
+            .await?
+            .pop()
+            .context("No result for frame")
+    }
+
+    async fn process_batch(&self, frames: &[VideoFrame]) -> Result<Vec<AiResult>> {
         let start = std::time::Instant::now();
 
         let detection_session_lock = self
             .detection_session
             .as_ref()
-            .context("Detection model not initialized - call init() first")?;
-
-        // Decode base64 image
-        let image_data = base64::prelude::BASE64_STANDARD
-            .decode(&frame.data)
-            .context("Failed to decode base64 image")?;
-
-        let img = image::load_from_memory(&image_data).context("Failed to load image")?;
-
-        let original_width = img.width();
-        let original_height = img.height();
-
-        // Stage 1: Detect faces
-        let input_array = self.preprocess_for_detection(&img)?;
-        let input_tensor = Value::from_array(input_array)?;
-
-        let inference_start = std::time::Instant::now();
-        let mut detection_session = detection_session_lock.lock().await;
-        let outputs = detection_session.run(ort::inputs![input_tensor])?;
-        let detection_time = inference_start.elapsed();
-
-        // Get detection output
-        let output_value = outputs
-            .get("output0")
-            .or_else(|| outputs.get("output"))
-            .or_else(|| outputs.get("boxes"))
-            .context("No detection output tensor found")?;
-        let (shape, data) = output_value.try_extract_tensor::<f32>()?;
-
-        let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
-        let output = Array::from_shape_vec(IxDyn(&shape_usize), data.to_vec())?;
-
-        // Post-process detections
-        let face_boxes = self.postprocess_detection(output, original_width, original_height)?;
-
-        // Stage 2: Extract embeddings and match faces
-        let mut detections = Vec::new();
-        for (bbox, confidence) in face_boxes {
-            // Crop face region
-            let face_img = img.crop_imm(bbox.x, bbox.y, bbox.width, bbox.height);
-
-            // Extract embedding and try to match
-            let match_result = if self.embedding_session.is_some() {
-                match self.extract_embedding(&face_img).await {
-                    Ok(embedding) => self.match_face(&embedding).ok().flatten(),
+            .context(PluginError::fatal("Detection model not initialized - call init() first"))?;
+
+        // Decode base64 images
+        let images = frames
+            .iter()
+            .map(|frame| {
+                let image_data = base64::prelude::BASE64_STANDARD
+                    .decode(&frame.data)
+                    .context(PluginError::recoverable("Failed to decode base64 image"))?;
+                image::load_from_memory(&image_data)
+                    .context(PluginError::recoverable("Failed to load image"))
+            })
+            .collect::<Result<Vec<DynamicImage>>>()?;
+
+        let execution_provider = self
+            .execution_provider_used
+            .read()
+            .map_err(|e| PluginError::poisoned("execution provider", e))?
+            .clone();
+
+        let mut results = Vec::with_capacity(frames.len());
+        let detection_batch = self.detection_batch.max(1);
+        for (batch_frames, batch_images) in frames
+            .chunks(detection_batch)
+            .zip(images.chunks(detection_batch))
+        {
+            // Stage 1: Detect faces in every image of the batch
+            let input_array = self.preprocess_for_detection(batch_images)?;
+            let input_tensor = Value::from_array(input_array)?;
+
+            let inference_start = std::time::Instant::now();
+            let mut detection_session = detection_session_lock.lock().await;
+            let outputs = detection_session
+                .run(ort::inputs![input_tensor])
+                .map_err(PluginError::inference)?;
+            let detection_time = inference_start.elapsed();
+
+            // Get detection output
+            let output_value = outputs
+                .get("output0")
+                .or_else(|| outputs.get("output"))
+                .or_else(|| outputs.get("boxes"))
+                .context(PluginError::config("No detection output tensor found"))?;
+            let (shape, data) = output_value.try_extract_tensor::<f32>()?;
+
+            let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
+            let output = Array::from_shape_vec(IxDyn(&shape_usize), data.to_vec())?;
+            drop(outputs);
+            drop(detection_session);
+
+            // Post-process detections
+            let face_boxes = batch_images
+                .iter()
+                .enumerate()
+                .map(|(n, img)| self.postprocess_detection(&output, n, img.width(), img.height()))
+                .collect::<Result<Vec<_>>>()?;
+
+            // Stage 2: Extract the embeddings of all faces of the batch at once
+            let faces: Vec<DynamicImage> = batch_images
+                .iter()
+                .zip(&face_boxes)
+                .flat_map(|(img, boxes)| {
+                    boxes
+                        .iter()
+                        .map(|(bbox, _)| img.crop_imm(bbox.x, bbox.y, bbox.width, bbox.height))
+                })
+                .collect();
+            let mut embeddings = if self.embedding_session.is_some() && !faces.is_empty() {
+                match self.extract_embeddings(&faces).await {
+                    Ok(embeddings) => embeddings.into_iter().map(Some).collect(),
                     Err(e) => {
                         tracing::warn!("Embedding extraction failed: {}", e);
-                        None
+                        vec![None; faces.len()]
                     }
                 }
             } else {
-                None
-            };
-
-            // Create detection result
-            let (class, metadata) = if let Some(face_match) = match_result {
-                (
-                    face_match.name.clone(),
-                    Some(serde_json::json!({
-                        "face_id": face_match.face_id,
-                        "similarity": face_match.similarity,
-                        "matched": true,
-                        "metadata": face_match.metadata,
-                    })),
-                )
-            } else {
-                (
-                    "unknown".to_string(),
-                    Some(serde_json::json!({
-                        "matched": false,
+                vec![None; faces.len()]
+            }
+            .into_iter();
+
+            // Track metrics
+            telemetry::metrics::AI_SERVICE_GPU_INFERENCE
+                .with_label_values(&[self.id(), &execution_provider])
+                .inc_by(batch_frames.len() as u64);
+
+            telemetry::metrics::AI_SERVICE_INFERENCE_TIME
+                .with_label_values(&[self.id(), &execution_provider])
+                .observe(detection_time.as_secs_f64());
+
+            for ((frame, img), boxes) in batch_frames.iter().zip(batch_images).zip(face_boxes) {
+                // Match faces against the database
+                let mut detections = Vec::new();
+                for (bbox, confidence) in boxes {
+                    let match_result = embeddings
+                        .next()
+                        .flatten()
+                        .and_then(|embedding| self.match_face(&embedding).ok().flatten());
+
+                    // Create detection result
+                    let (class, metadata) = if let Some(face_match) = match_result {
+                        (
+                            face_match.name.clone(),
+                            Some(serde_json::json!({
+                                "face_id": face_match.face_id,
+                                "similarity": face_match.similarity,
+                                "matched": true,
+                                "metadata": face_match.metadata,
+                            })),
+                        )
+                    } else {
+                        (
+                            "unknown".to_string(),
+                            Some(serde_json::json!({
+                                "matched": false,
+                            })),
+                        )
+                    };
+
+                    detections.push(Detection {
+                        class,
+                        confidence,
+                        bbox,
+                        metadata,
+                    });
+                }
+
+                // Calculate average confidence
+                let avg_confidence = if !detections.is_empty() {
+                    detections.iter().map(|d| d.confidence).sum::<f32>() / detections.len() as f32
+                } else {
+                    0.0
+                };
+
+                results.push(AiResult {
+                    task_id: frame.source_id.clone(),
+                    timestamp: frame.timestamp,
+                    plugin_type: self.id().to_string(),
+                    detections,
+                    confidence: Some(avg_confidence),
+                    processing_time_ms: Some(start.elapsed().as_millis() as u64),
+                    metadata: Some(serde_json::json!({
+                        "frame_width": img.width(),
+                        "frame_height": img.height(),
+                        "frame_sequence": frame.sequence,
+                        "detection_model": self.config.detection_model_path,
+                        "embedding_model": self.config.embedding_model_path,
+                        "execution_provider": execution_provider,
+                        "device_id": self.config.device_id,
+                        "detection_time_ms": detection_time.as_millis() as u64,
+                        "database_size": self.database_size().unwrap_or(0),
+                        "batch_size": batch_frames.len()
                     })),
-                )
-            };
-
-            detections.push(Detection {
-                class,
-                confidence,
-                bbox,
-                metadata,
-            });
+                });
+            }
         }
 
-        let processing_time_ms = start.elapsed().as_millis() as u64;
-
-        // Calculate average confidence
-        let avg_confidence = if !detections.is_empty() {
-            detections.iter().map(|d| d.confidence).sum::<f32>() / detections.len() as f32
-        } else {
-            0.0
-        };
-
-        let execution_provider = self.execution_provider_used.read().map_err(|e| anyhow::anyhow!("Failed to lock execution provider: {}", e))?.clone();
-
-        // Track metrics
-        telemetry::metrics::AI_SERVICE_GPU_INFERENCE
-            .with_label_values(&[self.id(), &execution_provider])
-            .inc();
-
-        telemetry::metrics::AI_SERVICE_INFERENCE_TIME
-            .with_label_values(&[self.id(), &execution_provider])
-            .observe(detection_time.as_secs_f64());
-
-        Ok(AiResult {
-            task_id: frame.source_id.clone(),
-            timestamp: frame.timestamp,
-            plugin_type: self.id().to_string(),
-            detections,
-            confidence: Some(avg_confidence),
-            processing_time_ms: Some(processing_time_ms),
-            metadata: Some(serde_json::json!({
-                "frame_width": original_width,
-                "frame_height": original_height,
-                "frame_sequence": frame.sequence,
-                "detection_model": self.config.detection_model_path,
-                "embedding_model": self.config.embedding_model_path,
-                "execution_provider": execution_provider,
-                "device_id": self.config.device_id,
-                "detection_time_ms": detection_time.as_millis() as u64,
-                "database_size": self.database_size().unwrap_or(0)
-            })),
-        })
+        Ok(results)
     }
 
     async fn health_check(&self) -> Result<bool> {
@@ -897,8 +1028,6 @@ mod tests {
 
     #[test]
     fn test_calculate_iou() {
-        let plugin = FacialRecognitionPlugin::new();
-
         let box1 = BoundingBox {
             x: 10,
             y: 10,
@@ -912,11 +1041,11 @@ mod tests {
             height: 50,
         };
 
-        let iou = plugin.calculate_iou(&box1, &box2);
+        let iou = vision::iou(&box1, &box2);
         assert!(iou > 0.0 && iou < 1.0);
 
         // Identical boxes
-        let iou_same = plugin.calculate_iou(&box1, &box1);
+        let iou_same = vision::iou(&box1, &box1);
         assert!((iou_same - 1.0).abs() < 0.001);
 
         // Non-overlapping boxes
@@ -926,7 +1055,7 @@ mod tests {
             width: 50,
             height: 50,
         };
-        let iou_none = plugin.calculate_iou(&box1, &box3);
+        let iou_none = vision::iou(&box1, &box3);
         assert_eq!(iou_none, 0.0);
     }
 
@@ -956,4 +1085,69 @@ mod tests {
         let faces = plugin.list_faces().ok();
         assert_eq!(faces, Some(vec![]));
     }
+
+    #[test]
+    fn test_subject_faces_match_by_id_or_name() {
+        let plugin = FacialRecognitionPlugin::new();
+        {
+            let mut database = plugin.face_database.write().unwrap();
+            for (face_id, name) in [("f1", "Jane Doe"), ("f2", "John Roe"), ("f3", "jane doe")] {
+                database.insert(
+                    face_id.to_string(),
+                    EnrolledFace {
+                        face_id: face_id.to_string(),
+                        name: name.to_string(),
+                        embedding: vec![0.0; 4],
+                        metadata: None,
+                        enrolled_at: 0,
+                    },
+                );
+            }
+        }
+
+        let subject = DataSubject {
+            tenant_id: "acme".to_string(),
+            face_ids: vec!["f2".to_string()],
+            names: vec!["Jane Doe".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(plugin.faces_of(&subject).unwrap().len(), 3);
+
+        let jane = DataSubject {
+            face_ids: Vec::new(),
+            ..subject
+        };
+        assert_eq!(plugin.erase_subject(&jane).unwrap(), 2);
+        assert_eq!(plugin.database_size().unwrap(), 1);
+        assert_eq!(plugin.erase_subject(&jane).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_update_face_keeps_left_out_fields() {
+        let plugin = FacialRecognitionPlugin::new();
+        plugin.face_database.write().unwrap().insert(
+            "f1".to_string(),
+            EnrolledFace {
+                face_id: "f1".to_string(),
+                name: "Jane Doe".to_string(),
+                embedding: vec![0.6, 0.8],
+                metadata: Some(serde_json::json!({"badge": "1234"})),
+                enrolled_at: 7,
+            },
+        );
+
+        let rename: FaceUpdate = serde_json::from_str(r#"{"name": "Jane Roe"}"#).unwrap();
+        let face = plugin.update_face("f1", rename).unwrap().unwrap();
+        assert_eq!(face.name, "Jane Roe");
+        assert_eq!(face.metadata, Some(serde_json::json!({"badge": "1234"})));
+        assert_eq!(face.embedding, vec![0.6, 0.8]);
+
+        let clear: FaceUpdate = serde_json::from_str(r#"{"metadata": null}"#).unwrap();
+        let face = plugin.update_face("f1", clear).unwrap().unwrap();
+        assert_eq!(face.name, "Jane Roe");
+        assert_eq!(face.metadata, None);
+        assert_eq!(plugin.get_face("f1").unwrap(), Some(face));
+
+        assert!(plugin.update_face("missing", FaceUpdate::default()).unwrap().is_none());
+    }
 }
@@ -0,0 +1,558 @@
+//! Plugins served by a remote inference server over gRPC.
+//!
+//! A backend is any server implementing the `quadrant.v1.AiService`
+//! contract: a Triton front end, a Python sidecar wrapping a PyTorch model,
+//! another ai-service. Each backend is registered as an ordinary plugin, so
+//! tasks, secondary stages and `/v1/plugins/:id/detect` use it like an
+//! in-process ONNX plugin; `process_frame` forwards the frame to the remote
+//! plugin and returns its result under the local plugin id.
+//!
+//! Frames are spread round-robin over a small pool of HTTP/2 connections so a
+//! large frame in flight does not hold up the rest. Connections are opened
+//! lazily and re-established by tonic, so a backend that is down at startup
+//! is registered anyway and picked up once it answers. A periodic probe
+//! (`ListPlugins`) records health and latency, shown in `/v1/plugins`.
+
+use super::{AiPlugin, PluginError};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use common::ai_tasks::{AiResult, PluginBackendStatus, VideoFrame};
+use proto::v1::{ListPluginsRequest, ProcessFrameRequest};
+use proto::{AiServiceClient, ConversionError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tonic::transport::{Channel, Endpoint};
+
+const DEFAULT_POOL_SIZE: usize = 2;
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_HEALTH_INTERVAL_SECS: u64 = 15;
+
+/// One remote backend, as configured
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrpcBackendConfig {
+    /// Plugin id the backend is registered under
+    pub id: String,
+    /// Plugin id on the remote server
+    pub remote_plugin: String,
+    pub endpoint: String,
+    /// Connections frames are spread over
+    pub pool_size: usize,
+    /// Deadline of one request, connecting included
+    pub timeout: Duration,
+}
+
+impl GrpcBackendConfig {
+    /// Parse one `id[:remote_plugin]=endpoint` entry; the remote plugin id
+    /// defaults to the local one
+    pub fn parse(spec: &str, pool_size: usize, timeout: Duration) -> Result<Self> {
+        let (name, endpoint) = spec
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected id[:remote_plugin]=endpoint, got '{}'", spec))?;
+        let (id, remote_plugin) = name.split_once(':').unwrap_or((name, name));
+        let (id, remote_plugin, endpoint) = (id.trim(), remote_plugin.trim(), endpoint.trim());
+        if id.is_empty() || remote_plugin.is_empty() {
+            bail!("backend '{}' has an empty plugin id", spec);
+        }
+        // Built without TLS support; backends are reached over plaintext
+        if !endpoint.starts_with("http://") {
+            bail!("backend '{}' endpoint must be an http:// URL", id);
+        }
+        Ok(Self {
+            id: id.to_string(),
+            remote_plugin: remote_plugin.to_string(),
+            endpoint: endpoint.to_string(),
+            pool_size: pool_size.max(1),
+            timeout,
+        })
+    }
+
+    /// Backends listed in `AI_GRPC_BACKENDS` (comma separated), with
+    /// `AI_GRPC_POOL_SIZE` and `AI_GRPC_TIMEOUT_MS`. Invalid entries are
+    /// logged and skipped.
+    pub fn from_env() -> Vec<Self> {
+        let Ok(specs) = std::env::var("AI_GRPC_BACKENDS") else {
+            return Vec::new();
+        };
+        let pool_size = std::env::var("AI_GRPC_POOL_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_POOL_SIZE);
+        let timeout = Duration::from_millis(
+            std::env::var("AI_GRPC_TIMEOUT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|ms| *ms > 0)
+                .unwrap_or(DEFAULT_TIMEOUT_MS),
+        );
+        specs
+            .split(',')
+            .filter(|spec| !spec.trim().is_empty())
+            .filter_map(|spec| match Self::parse(spec, pool_size, timeout) {
+                Ok(config) => Some(config),
+                Err(e) => {
+                    tracing::warn!("Ignoring gRPC backend: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// How often backends are probed, from `AI_GRPC_HEALTH_INTERVAL_SECS`
+pub fn health_interval_from_env() -> Duration {
+    Duration::from_secs(
+        std::env::var("AI_GRPC_HEALTH_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_HEALTH_INTERVAL_SECS),
+    )
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    healthy: bool,
+    last_checked_ms: Option<u64>,
+    latency_ms: Option<u64>,
+    last_error: Option<String>,
+}
+
+/// Connection pool and health of one backend, shared with its probe task
+struct Backend {
+    config: GrpcBackendConfig,
+    pool: Vec<AiServiceClient<Channel>>,
+    next: AtomicUsize,
+    health: Mutex<Health>,
+}
+
+impl Backend {
+    fn connect(config: GrpcBackendConfig) -> Result<Self> {
+        let endpoint = Endpoint::from_shared(config.endpoint.clone())
+            .with_context(|| format!("invalid endpoint for gRPC backend '{}'", config.id))?
+            .connect_timeout(config.timeout)
+            .timeout(config.timeout)
+            .tcp_nodelay(true);
+        // Every lazy channel is a connection of its own
+        let pool = (0..config.pool_size)
+            .map(|_| AiServiceClient::new(endpoint.connect_lazy()))
+            .collect();
+        Ok(Self {
+            config,
+            pool,
+            next: AtomicUsize::new(0),
+            health: Mutex::new(Health::default()),
+        })
+    }
+
+    fn client(&self) -> AiServiceClient<Channel> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.pool.len();
+        self.pool[index].clone()
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut Health)) {
+        if let Ok(mut health) = self.health.lock() {
+            apply(&mut health);
+        }
+    }
+
+    /// Probe the backend and record the outcome
+    async fn check(&self) -> Result<proto::v1::PluginInfo> {
+        let started = Instant::now();
+        let outcome = self.list_remote_plugin().await;
+        let checked_ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        match &outcome {
+            Ok(_) => self.update(|health| {
+                health.healthy = true;
+                health.last_checked_ms = Some(checked_ms);
+                health.latency_ms = Some(started.elapsed().as_millis() as u64);
+                health.last_error = None;
+            }),
+            Err(e) => self.update(|health| {
+                health.healthy = false;
+                health.last_checked_ms = Some(checked_ms);
+                health.last_error = Some(e.to_string());
+            }),
+        }
+        outcome
+    }
+
+    async fn list_remote_plugin(&self) -> Result<proto::v1::PluginInfo> {
+        let plugins = self
+            .client()
+            .list_plugins(ListPluginsRequest {})
+            .await
+            .map_err(|status| anyhow!("ListPlugins failed: {}", status.message()))?
+            .into_inner()
+            .plugins;
+        plugins
+            .into_iter()
+            .find(|p| p.id == self.config.remote_plugin)
+            .ok_or_else(|| {
+                anyhow!(
+                    "remote plugin '{}' is not served by {}",
+                    self.config.remote_plugin,
+                    self.config.endpoint
+                )
+            })
+    }
+
+    fn status(&self) -> PluginBackendStatus {
+        let (healthy, last_checked_ms, latency_ms, last_error) = match self.health.lock() {
+            Ok(health) => (
+                health.healthy,
+                health.last_checked_ms,
+                health.latency_ms,
+                health.last_error.clone(),
+            ),
+            Err(_) => (false, None, None, None),
+        };
+        PluginBackendStatus {
+            kind: "grpc".to_string(),
+            endpoint: self.config.endpoint.clone(),
+            remote_plugin: self.config.remote_plugin.clone(),
+            pool_size: self.pool.len(),
+            healthy,
+            last_checked_ms,
+            latency_ms,
+            last_error,
+        }
+    }
+}
+
+/// A plugin whose inference runs on a remote gRPC server
+pub struct GrpcBackendPlugin {
+    backend: Arc<Backend>,
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    version: &'static str,
+    supported_formats: Vec<String>,
+    requires_gpu: bool,
+    config_schema: Option<serde_json::Value>,
+}
+
+/// Plugin metadata is `&'static str`; backends are created once at startup
+fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+impl GrpcBackendPlugin {
+    /// Set up the connection pool; nothing is dialled until the first
+    /// request or probe
+    pub fn new(config: GrpcBackendConfig) -> Result<Self> {
+        let id = leak(config.id.clone());
+        let name = leak(format!("{} (gRPC)", config.remote_plugin));
+        let description = leak(format!("Remote plugin served by {}", config.endpoint));
+        Ok(Self {
+            backend: Arc::new(Backend::connect(config)?),
+            id,
+            name,
+            description,
+            version: "remote",
+            supported_formats: vec!["jpeg".to_string()],
+            requires_gpu: false,
+            config_schema: None,
+        })
+    }
+
+    /// Probe the backend every `interval` so `/v1/plugins` shows its health
+    pub fn spawn_health_checks(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = backend.check().await {
+                    tracing::warn!(backend = %backend.config.id, "gRPC backend unhealthy: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl AiPlugin for GrpcBackendPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn version(&self) -> &'static str {
+        self.version
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        self.config_schema.clone()
+    }
+
+    fn supported_formats(&self) -> Vec<String> {
+        self.supported_formats.clone()
+    }
+
+    fn requires_gpu(&self) -> bool {
+        self.requires_gpu
+    }
+
+    fn backend_status(&self) -> Option<PluginBackendStatus> {
+        Some(self.backend.status())
+    }
+
+    /// Probe the backend and adopt the remote plugin's metadata. A backend
+    /// that does not answer yet is not an error: it is registered unhealthy
+    /// and the metadata stays generic.
+    async fn init(&mut self, _config: serde_json::Value) -> Result<()> {
+        match self.backend.check().await {
+            Ok(remote) => {
+                self.name = leak(format!("{} (gRPC)", remote.name));
+                self.description = leak(remote.description);
+                self.version = leak(remote.version);
+                self.supported_formats = remote.supported_formats;
+                self.requires_gpu = remote.requires_gpu;
+                self.config_schema = remote
+                    .config_schema_json
+                    .and_then(|schema| serde_json::from_str(&schema).ok());
+                tracing::info!(
+                    "Connected gRPC backend '{}' to {} at {}",
+                    self.id,
+                    self.backend.config.remote_plugin,
+                    self.backend.config.endpoint
+                );
+            }
+            Err(e) => tracing::warn!("gRPC backend '{}' is not reachable yet: {}", self.id, e),
+        }
+        Ok(())
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        let request = ProcessFrameRequest {
+            plugin_id: self.backend.config.remote_plugin.clone(),
+            frame: Some(frame.clone().try_into()?),
+        };
+        let response = match self.backend.client().process_frame(request).await {
+            Ok(response) => response.into_inner(),
+            Err(status) => {
+                // The backend is gone rather than rejecting this frame
+                if matches!(
+                    status.code(),
+                    tonic::Code::Unavailable | tonic::Code::DeadlineExceeded
+                ) {
+                    self.backend.update(|health| {
+                        health.healthy = false;
+                        health.last_error = Some(status.message().to_string());
+                    });
+                }
+                let message = format!("gRPC backend '{}' failed: {}", self.id, status.message());
+                return Err(match status.code() {
+                    tonic::Code::Unavailable
+                    | tonic::Code::DeadlineExceeded
+                    | tonic::Code::ResourceExhausted => PluginError::resource(message),
+                    tonic::Code::NotFound
+                    | tonic::Code::Unimplemented
+                    | tonic::Code::FailedPrecondition
+                    | tonic::Code::PermissionDenied
+                    | tonic::Code::Unauthenticated => PluginError::config(message),
+                    _ => PluginError::recoverable(message),
+                }
+                .into());
+            }
+        };
+        let result = response
+            .result
+            .ok_or(ConversionError::MissingField("result"))?;
+        let mut result = AiResult::try_from(result)?;
+        result.plugin_type = self.id.to_string();
+        Ok(result)
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(self.backend.check().await.is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Stream;
+    use proto::v1::{
+        AiResult as ProtoResult, Detection as ProtoDetection, ListPluginsResponse,
+        PluginInfo as ProtoPluginInfo, ProcessFrameResponse,
+    };
+    use proto::{AiService, AiServiceServer};
+    use std::pin::Pin;
+    use tonic::{Request, Response, Status, Streaming};
+
+    struct FakeServer;
+
+    #[tonic::async_trait]
+    impl AiService for FakeServer {
+        type StreamFramesStream =
+            Pin<Box<dyn Stream<Item = Result<ProcessFrameResponse, Status>> + Send>>;
+
+        async fn list_plugins(
+            &self,
+            _request: Request<ListPluginsRequest>,
+        ) -> Result<Response<ListPluginsResponse>, Status> {
+            Ok(Response::new(ListPluginsResponse {
+                plugins: vec![ProtoPluginInfo {
+                    id: "triton_yolo".to_string(),
+                    name: "YOLO on Triton".to_string(),
+                    description: "Object detection".to_string(),
+                    version: "2.1.0".to_string(),
+                    config_schema_json: None,
+                    supported_formats: vec!["jpeg".to_string(), "png".to_string()],
+                    requires_gpu: true,
+                }],
+            }))
+        }
+
+        async fn process_frame(
+            &self,
+            request: Request<ProcessFrameRequest>,
+        ) -> Result<Response<ProcessFrameResponse>, Status> {
+            let request = request.into_inner();
+            let frame = request
+                .frame
+                .ok_or_else(|| Status::invalid_argument("no frame"))?;
+            Ok(Response::new(ProcessFrameResponse {
+                result: Some(ProtoResult {
+                    task_id: frame.source_id,
+                    timestamp: frame.timestamp,
+                    plugin_type: request.plugin_id,
+                    detections: vec![ProtoDetection {
+                        class: "person".to_string(),
+                        confidence: 0.9,
+                        bbox: Some(proto::v1::BoundingBox {
+                            x: 1,
+                            y: 2,
+                            width: 3,
+                            height: 4,
+                        }),
+                        metadata_json: None,
+                    }],
+                    confidence: Some(0.9),
+                    processing_time_ms: Some(frame.data.len() as u64),
+                    metadata_json: None,
+                }),
+            }))
+        }
+
+        async fn stream_frames(
+            &self,
+            _request: Request<Streaming<ProcessFrameRequest>>,
+        ) -> Result<Response<Self::StreamFramesStream>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+    }
+
+    async fn serve() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(AiServiceServer::new(FakeServer))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+        format!("http://{}", addr)
+    }
+
+    fn frame() -> VideoFrame {
+        VideoFrame {
+            source_id: "cam-1".to_string(),
+            timestamp: 1000,
+            sequence: 7,
+            width: 640,
+            height: 480,
+            format: "jpeg".to_string(),
+            data: "AAEC".to_string(),
+            audio: None,
+        }
+    }
+
+    #[test]
+    fn parses_backend_specs() {
+        let timeout = Duration::from_secs(1);
+        let config = GrpcBackendConfig::parse("yolo=http://triton:8001", 0, timeout).unwrap();
+        assert_eq!(config.id, "yolo");
+        assert_eq!(config.remote_plugin, "yolo");
+        assert_eq!(config.pool_size, 1);
+
+        let config =
+            GrpcBackendConfig::parse(" faces:arcface = http://sidecar:50051 ", 4, timeout).unwrap();
+        assert_eq!(config.id, "faces");
+        assert_eq!(config.remote_plugin, "arcface");
+        assert_eq!(config.endpoint, "http://sidecar:50051");
+
+        assert!(GrpcBackendConfig::parse("http://triton:8001", 2, timeout).is_err());
+        assert!(GrpcBackendConfig::parse("yolo=triton:8001", 2, timeout).is_err());
+        assert!(GrpcBackendConfig::parse("yolo=https://triton:8001", 2, timeout).is_err());
+        assert!(GrpcBackendConfig::parse(":x=http://triton:8001", 2, timeout).is_err());
+    }
+
+    #[tokio::test]
+    async fn forwards_frames_to_the_remote_plugin() {
+        let endpoint = serve().await;
+        let spec = format!("detector:triton_yolo={}", endpoint);
+        let config = GrpcBackendConfig::parse(&spec, 2, Duration::from_secs(5)).unwrap();
+        let mut plugin = GrpcBackendPlugin::new(config).unwrap();
+        plugin.init(serde_json::Value::Null).await.unwrap();
+
+        let info = plugin.info();
+        assert_eq!(info.id, "detector");
+        assert_eq!(info.version, "2.1.0");
+        assert!(info.requires_gpu);
+        let backend = info.backend.unwrap();
+        assert!(backend.healthy);
+        assert_eq!(backend.pool_size, 2);
+        assert!(backend.latency_ms.is_some());
+
+        // Both pooled connections serve frames
+        for _ in 0..2 {
+            let result = plugin.process_frame(&frame()).await.unwrap();
+            assert_eq!(result.plugin_type, "detector");
+            assert_eq!(result.task_id, "cam-1");
+            assert_eq!(result.detections[0].class, "person");
+            // The frame reached the server decoded
+            assert_eq!(result.processing_time_ms, Some(3));
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_unhealthy_backends() {
+        let endpoint = serve().await;
+        let spec = format!("missing={}", endpoint);
+        let config = GrpcBackendConfig::parse(&spec, 1, Duration::from_secs(5)).unwrap();
+        let mut plugin = GrpcBackendPlugin::new(config).unwrap();
+        plugin.init(serde_json::Value::Null).await.unwrap();
+        assert!(!plugin.health_check().await.unwrap());
+        let backend = plugin.backend_status().unwrap();
+        assert!(!backend.healthy);
+        assert!(backend.last_error.unwrap().contains("not served"));
+
+        // Nothing listening: registered anyway, unhealthy until it answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("down=http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let config = GrpcBackendConfig::parse(&closed, 1, Duration::from_secs(2)).unwrap();
+        let mut plugin = GrpcBackendPlugin::new(config).unwrap();
+        plugin.init(serde_json::Value::Null).await.unwrap();
+        assert!(!plugin.backend_status().unwrap().healthy);
+        assert!(plugin.process_frame(&frame()).await.is_err());
+    }
+}
@@ -3,7 +3,9 @@
 /// This plugin performs two-stage license plate recognition:
 /// 1. Detection stage: Locates license plates in the image using YOLOv8
 /// 2. OCR stage: Reads the text from detected plates using CRNN/LSTM model
-use super::AiPlugin;
+use super::lpr_watchlist::{self, Watchlists};
+use super::vision::{self, Candidate, Letterbox, NmsConfig, Normalization, ResizeMode};
+use super::{AiPlugin, PluginError};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
@@ -42,6 +44,14 @@ pub struct LprConfig {
     #[serde(default = "default_detection_input_size")]
     pub detection_input_size: u32,
 
+    /// How frames are fitted into the detection model input
+    #[serde(default)]
+    pub resize_mode: ResizeMode,
+
+    /// Non-maximum suppression variant
+    #[serde(default)]
+    pub nms: NmsConfig,
+
     /// OCR model input width
     #[serde(default = "default_ocr_input_width")]
     pub ocr_input_width: u32,
@@ -69,6 +79,11 @@ pub struct LprConfig {
     /// Number of inter-operation threads
     #[serde(default = "default_inter_threads")]
     pub inter_threads: usize,
+
+    /// OCR misreads tolerated when matching plates against watchlists that
+    /// set no edit distance of their own
+    #[serde(default = "default_watchlist_max_edit_distance")]
+    pub watchlist_max_edit_distance: usize,
 }
 
 fn default_confidence() -> f32 {
@@ -117,6 +132,10 @@ fn default_inter_threads() -> usize {
     1
 }
 
+fn default_watchlist_max_edit_distance() -> usize {
+    1
+}
+
 impl Default for LprConfig {
     fn default() -> Self {
         Self {
@@ -126,6 +145,8 @@ impl Default for LprConfig {
             iou_threshold: default_iou_threshold(),
             max_detections: default_max_detections(),
             detection_input_size: default_detection_input_size(),
+            resize_mode: ResizeMode::default(),
+            nms: NmsConfig::default(),
             ocr_input_width: default_ocr_input_width(),
             ocr_input_height: default_ocr_input_height(),
             char_vocab: default_char_vocab(),
@@ -133,6 +154,7 @@ impl Default for LprConfig {
             device_id: default_device_id(),
             intra_threads: default_intra_threads(),
             inter_threads: default_inter_threads(),
+            watchlist_max_edit_distance: default_watchlist_max_edit_distance(),
         }
     }
 }
@@ -143,6 +165,8 @@ pub struct LprPlugin {
     detection_session: Option<Arc<Mutex<Session>>>,
     ocr_session: Option<Arc<Mutex<Session>>>,
     execution_provider_used: Arc<Mutex<String>>,
+    /// Kept across restarts of the plugin
+    watchlists: Watchlists,
 }
 
 impl LprPlugin {
@@ -152,28 +176,25 @@ impl LprPlugin {
             detection_session: None,
             ocr_session: None,
             execution_provider_used: Arc::new(Mutex::new("CPU".to_string())),
+            watchlists: Watchlists::new(),
         }
     }
 
+    /// Plate watchlists read plates are matched against
+    pub fn watchlists(&self) -> &Watchlists {
+        &self.watchlists
+    }
+
     /// Preprocess image for detection model
     fn preprocess_for_detection(&self, img: &DynamicImage) -> Result<Array<f32, IxDyn>> {
         let size = self.config.detection_input_size;
-        let resized = img.resize_exact(size, size, image::imageops::FilterType::Triangle);
-        let rgb_img = resized.to_rgb8();
-
-        // Convert to NCHW format and normalize to [0, 1]
-        let mut input = Array::zeros(IxDyn(&[1, 3, size as usize, size as usize]));
-
-        for (x, y, pixel) in rgb_img.enumerate_pixels() {
-            let r = pixel[0] as f32 / 255.0;
-            let g = pixel[1] as f32 / 255.0;
-            let b = pixel[2] as f32 / 255.0;
-
-            input[[0, 0, y as usize, x as usize]] = r;
-            input[[0, 1, y as usize, x as usize]] = g;
-            input[[0, 2, y as usize, x as usize]] = b;
-        }
-
+        let (input, _) = vision::to_nchw(
+            std::slice::from_ref(img),
+            size,
+            size,
+            self.config.resize_mode,
+            Normalization::Unit,
+        );
         Ok(input)
     }
 
@@ -197,50 +218,19 @@ impl LprPlugin {
 
     /// Apply Non-Maximum Suppression (NMS)
     fn nms(&self, boxes: Vec<(BoundingBox, f32)>) -> Vec<(BoundingBox, f32)> {
-        if boxes.is_empty() {
-            return vec![];
-        }
-
-        let mut sorted_boxes = boxes.clone();
-        sorted_boxes.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-
-        let mut keep = Vec::new();
-
-        while !sorted_boxes.is_empty() {
-            let current = sorted_boxes.remove(0);
-            keep.push(current.clone());
-
-            sorted_boxes.retain(|box_item| {
-                let iou = self.calculate_iou(&current.0, &box_item.0);
-                iou < self.config.iou_threshold
-            });
-        }
-
-        keep
-    }
-
-    /// Calculate Intersection over Union (IoU)
-    fn calculate_iou(&self, box1: &BoundingBox, box2: &BoundingBox) -> f32 {
-        let x1 = box1.x.max(box2.x);
-        let y1 = box1.y.max(box2.y);
-        let x2 = (box1.x + box1.width).min(box2.x + box2.width);
-        let y2 = (box1.y + box1.height).min(box2.y + box2.height);
-
-        let intersection = if x2 > x1 && y2 > y1 {
-            ((x2 - x1) * (y2 - y1)) as f32
-        } else {
-            0.0
-        };
-
-        let area1 = (box1.width * box1.height) as f32;
-        let area2 = (box2.width * box2.height) as f32;
-        let union = area1 + area2 - intersection;
-
-        if union > 0.0 {
-            intersection / union
-        } else {
-            0.0
-        }
+        let candidates = boxes
+            .into_iter()
+            .map(|(bbox, score)| Candidate { bbox, score, class_id: 0 })
+            .collect();
+        vision::nms(
+            candidates,
+            self.config.iou_threshold,
+            self.config.confidence_threshold,
+            &self.config.nms,
+        )
+        .into_iter()
+        .map(|c| (c.bbox, c.score))
+        .collect()
     }
 
     /// Post-process detection output (YOLOv8 format)
@@ -250,8 +240,8 @@ impl LprPlugin {
         original_width: u32,
         original_height: u32,
     ) -> Result<Vec<(BoundingBox, f32)>> {
-        let scale_x = original_width as f32 / self.config.detection_input_size as f32;
-        let scale_y = original_height as f32 / self.config.detection_input_size as f32;
+        let size = self.config.detection_input_size;
+        let letterbox = Letterbox::new(self.config.resize_mode, original_width, original_height, size, size);
 
         let mut boxes = Vec::new();
 
@@ -267,27 +257,14 @@ impl LprPlugin {
                 continue;
             }
 
-            // Extract bounding box (cx, cy, w, h)
-            let cx = output[[0, 0, i]];
-            let cy = output[[0, 1, i]];
-            let w = output[[0, 2, i]];
-            let h = output[[0, 3, i]];
-
-            // Convert to (x, y, w, h) and scale to original image
-            let x = ((cx - w / 2.0) * scale_x).max(0.0) as u32;
-            let y = ((cy - h / 2.0) * scale_y).max(0.0) as u32;
-            let width = (w * scale_x).min(original_width as f32) as u32;
-            let height = (h * scale_y).min(original_height as f32) as u32;
-
-            boxes.push((
-                BoundingBox {
-                    x,
-                    y,
-                    width,
-                    height,
-                },
-                confidence,
-            ));
+            // Extract bounding box (cx, cy, w, h) and map it to the frame
+            let bbox = letterbox.to_frame(
+                output[[0, 0, i]],
+                output[[0, 1, i]],
+                output[[0, 2, i]],
+                output[[0, 3, i]],
+            );
+            boxes.push((bbox, confidence));
         }
 
         // Apply NMS
@@ -305,7 +282,7 @@ impl LprPlugin {
         let session_lock = self
             .ocr_session
             .as_ref()
-            .context("OCR model not initialized")?;
+            .context(PluginError::fatal("OCR model not initialized"))?;
 
         // Preprocess plate image
         let input_array = self.preprocess_for_ocr(plate_img)?;
@@ -316,8 +293,10 @@ impl LprPlugin {
         // Run OCR inference
         let mut session = session_lock
             .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock OCR session: {}", e))?;
-        let outputs = session.run(ort::inputs![input_tensor])?;
+            .map_err(|e| PluginError::poisoned("OCR session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
 
         // Get output tensor (softmax probabilities over vocabulary)
         // Expected shape: [batch, sequence_length, vocab_size]
@@ -326,7 +305,7 @@ impl LprPlugin {
             .get("output")
             .or_else(|| outputs.get("output0"))
             .or_else(|| outputs.get("logits"))
-            .context("No OCR output tensor found (tried: output, output0, logits)")?;
+            .context(PluginError::config("No OCR output tensor found (tried: output, output0, logits)"))?;
         let (shape, data) = output_value.try_extract_tensor::<f32>()?;
 
         let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
@@ -377,6 +356,7 @@ impl LprPlugin {
 
     /// Create ONNX session with execution provider fallback
     fn create_session(&self, model_path: &str) -> Result<(Session, String)> {
+        super::require_model_file(model_path)?;
         let provider_preference = self.config.execution_provider.to_uppercase();
 
         match provider_preference.as_str() {
@@ -460,7 +440,7 @@ impl LprPlugin {
             .with_inter_threads(self.config.inter_threads)
             .context("Failed to set inter threads")?
             .commit_from_file(model_path)
-            .context("Failed to load model from file")?;
+            .context(PluginError::config("Failed to load model from file"))?;
         Ok((session, "CPU".to_string()))
     }
 }
@@ -535,6 +515,20 @@ impl AiPlugin for LprPlugin {
                     "default": 640,
                     "description": "Detection model input size"
                 },
+                "resize_mode": {
+                    "type": "string",
+                    "enum": ["letterbox", "stretch"],
+                    "default": "letterbox",
+                    "description": "Fit frames into the detection input keeping their aspect ratio (letterbox) or stretch them"
+                },
+                "nms": {
+                    "type": "object",
+                    "properties": {
+                        "method": {"type": "string", "enum": ["hard", "soft", "diou"], "default": "hard"},
+                        "soft_sigma": {"type": "number", "default": 0.5}
+                    },
+                    "description": "Non-maximum suppression variant"
+                },
                 "ocr_input_width": {
                     "type": "integer",
                     "default": 200,
@@ -573,6 +567,13 @@ impl AiPlugin for LprPlugin {
                     "minimum": 1,
                     "default": 1,
                     "description": "Number of inter-operation threads"
+                },
+                "watchlist_max_edit_distance": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": lpr_watchlist::MAX_EDIT_DISTANCE,
+                    "default": 1,
+                    "description": "OCR misreads tolerated when matching watchlists without their own edit distance"
                 }
             },
             "required": ["detection_model_path"]
@@ -587,9 +588,15 @@ impl AiPlugin for LprPlugin {
         false // Can run on CPU, GPU is optional
     }
 
+    fn gpu_device(&self) -> Option<u32> {
+        let provider = self.execution_provider_used.lock().ok()?;
+        super::gpu_device(&provider, self.config.device_id)
+    }
+
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         if !config.is_null() {
-            self.config = serde_json::from_value(config)?;
+            self.config = serde_json::from_value(config)
+                .context(PluginError::config("Invalid LPR configuration"))?;
         }
 
         // Read GPU configuration from environment variables if set
@@ -606,7 +613,7 @@ impl AiPlugin for LprPlugin {
         let (detection_session, actual_provider) =
             self.create_session(&self.config.detection_model_path)?;
         self.detection_session = Some(Arc::new(Mutex::new(detection_session)));
-        *self.execution_provider_used.lock().map_err(|e| anyhow::anyhow!("Failed to lock execution provider: {}", e))? = actual_provider.clone();
+        *self.execution_provider_used.lock().map_err(|e| PluginError::poisoned("execution provider", e))? = actual_provider.clone();
 
         tracing::info!(
             "Initialized LPR detection model - path: {}, provider: {}, device: {}",
@@ -638,14 +645,15 @@ impl AiPlugin for LprPlugin {
         let detection_session_lock = self
             .detection_session
             .as_ref()
-            .context("Detection model not initialized - call init() first")?;
+            .context(PluginError::fatal("Detection model not initialized - call init() first"))?;
 
         // Decode base64 image
         let image_data = base64::prelude::BASE64_STANDARD
             .decode(&frame.data)
-            .context("Failed to decode base64 image")?;
+            .context(PluginError::recoverable("Failed to decode base64 image"))?;
 
-        let img = image::load_from_memory(&image_data).context("Failed to load image")?;
+        let img = image::load_from_memory(&image_data)
+            .context(PluginError::recoverable("Failed to load image"))?;
 
         let original_width = img.width();
         let original_height = img.height();
@@ -657,8 +665,10 @@ impl AiPlugin for LprPlugin {
         let inference_start = std::time::Instant::now();
         let mut detection_session = detection_session_lock
             .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock detection session: {}", e))?;
-        let outputs = detection_session.run(ort::inputs![input_tensor])?;
+            .map_err(|e| PluginError::poisoned("detection session", e))?;
+        let outputs = detection_session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
         let detection_time = inference_start.elapsed();
 
         // Get detection output - try common YOLO output names
@@ -666,7 +676,7 @@ impl AiPlugin for LprPlugin {
             .get("output0")
             .or_else(|| outputs.get("output"))
             .or_else(|| outputs.get("boxes"))
-            .context("No detection output tensor found (tried: output0, output, boxes)")?;
+            .context(PluginError::config("No detection output tensor found (tried: output0, output, boxes)"))?;
         let (shape, data) = output_value.try_extract_tensor::<f32>()?;
 
         let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
@@ -687,13 +697,21 @@ impl AiPlugin for LprPlugin {
                 "UNKNOWN".to_string()
             });
 
+            let mut metadata = serde_json::json!({
+                "plate_number": plate_text,
+            });
+            if plate_text != "UNKNOWN" {
+                let hit = self
+                    .watchlists
+                    .best_match(&plate_text, self.config.watchlist_max_edit_distance)?;
+                lpr_watchlist::annotate(&mut metadata, hit.as_ref());
+            }
+
             detections.push(Detection {
                 class: "license_plate".to_string(),
                 confidence,
                 bbox,
-                metadata: Some(serde_json::json!({
-                    "plate_number": plate_text,
-                })),
+                metadata: Some(metadata),
             });
         }
 
@@ -706,7 +724,7 @@ impl AiPlugin for LprPlugin {
             0.0
         };
 
-        let execution_provider = self.execution_provider_used.lock().map_err(|e| anyhow::anyhow!("Failed to lock execution provider: {}", e))?.clone();
+        let execution_provider = self.execution_provider_used.lock().map_err(|e| PluginError::poisoned("execution provider", e))?.clone();
 
         // Track metrics
         telemetry::metrics::AI_SERVICE_GPU_INFERENCE
@@ -768,8 +786,6 @@ mod tests {
 
     #[test]
     fn test_calculate_iou() {
-        let plugin = LprPlugin::new();
-
         let box1 = BoundingBox {
             x: 10,
             y: 10,
@@ -783,11 +799,11 @@ mod tests {
             height: 20,
         };
 
-        let iou = plugin.calculate_iou(&box1, &box2);
+        let iou = vision::iou(&box1, &box2);
         assert!(iou > 0.0 && iou < 1.0);
 
         // Identical boxes
-        let iou_same = plugin.calculate_iou(&box1, &box1);
+        let iou_same = vision::iou(&box1, &box1);
         assert!((iou_same - 1.0).abs() < 0.001);
 
         // Non-overlapping boxes
@@ -797,7 +813,7 @@ mod tests {
             width: 50,
             height: 20,
         };
-        let iou_none = plugin.calculate_iou(&box1, &box3);
+        let iou_none = vision::iou(&box1, &box3);
         assert_eq!(iou_none, 0.0);
     }
 
@@ -0,0 +1,463 @@
+//! Plate watchlists of the LPR plugin
+//!
+//! Operators keep lists of plates (stolen, VIP, blocked, ...) at
+//! `/v1/plugins/lpr/watchlists`. Every plate the LPR plugin reads is matched
+//! against them after normalization (uppercase letters and digits only),
+//! tolerating OCR misreads up to the list's edit distance. A hit marks the
+//! plate detection with `watchlist_hit` and the list, which `alerts.rs`
+//! raises as an `ai_detection` trigger for alert rules to match on.
+
+use super::PluginError;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, RwLock};
+
+/// Largest edit distance a list may tolerate
+pub const MAX_EDIT_DISTANCE: usize = 3;
+
+/// Most plates one list holds
+pub const MAX_PLATES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchlistCategory {
+    Stolen,
+    Vip,
+    Blocked,
+    #[default]
+    Other,
+}
+
+impl WatchlistCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WatchlistCategory::Stolen => "stolen",
+            WatchlistCategory::Vip => "vip",
+            WatchlistCategory::Blocked => "blocked",
+            WatchlistCategory::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Watchlist {
+    pub list_id: String,
+    pub name: String,
+    pub category: WatchlistCategory,
+    /// Most character edits between a read plate and a listed one; the
+    /// plugin's `watchlist_max_edit_distance` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_edit_distance: Option<usize>,
+    /// Normalized plates
+    pub plates: BTreeSet<String>,
+    /// Unix timestamps in seconds
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// A watchlist to create
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewWatchlist {
+    /// Generated when missing
+    #[serde(default)]
+    pub list_id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub category: WatchlistCategory,
+    #[serde(default)]
+    pub max_edit_distance: Option<usize>,
+    #[serde(default)]
+    pub plates: Vec<String>,
+}
+
+impl NewWatchlist {
+    pub fn validate(&self) -> Result<()> {
+        if self.list_id.as_deref().is_some_and(|id| id.trim().is_empty()) {
+            bail!("list_id must not be empty");
+        }
+        if self.name.trim().is_empty() {
+            bail!("name is required");
+        }
+        validate_edit_distance(self.max_edit_distance)?;
+        validate_plates(&self.plates)
+    }
+}
+
+/// Changes to a watchlist; fields left out are kept
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchlistUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub category: Option<WatchlistCategory>,
+    /// `null` falls back to the plugin's default
+    #[serde(default, deserialize_with = "present")]
+    pub max_edit_distance: Option<Option<usize>>,
+    /// Replaces every plate of the list
+    #[serde(default)]
+    pub plates: Option<Vec<String>>,
+}
+
+impl WatchlistUpdate {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            bail!("name must not be empty");
+        }
+        validate_edit_distance(self.max_edit_distance.flatten())?;
+        match &self.plates {
+            Some(plates) => validate_plates(plates),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Tells a field sent as `null` (Some(None)) from one left out (None)
+fn present<'de, D, T>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn validate_edit_distance(distance: Option<usize>) -> Result<()> {
+    if distance.is_some_and(|d| d > MAX_EDIT_DISTANCE) {
+        bail!("max_edit_distance must be at most {}", MAX_EDIT_DISTANCE);
+    }
+    Ok(())
+}
+
+/// Plates must keep at least one letter or digit once normalized
+pub fn validate_plates(plates: &[String]) -> Result<()> {
+    if plates.len() > MAX_PLATES {
+        bail!("a watchlist holds at most {} plates", MAX_PLATES);
+    }
+    if let Some(plate) = plates.iter().find(|p| normalize_plate(p).is_empty()) {
+        bail!("'{}' is not a plate number", plate);
+    }
+    Ok(())
+}
+
+/// A read plate found on a watchlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchlistMatch {
+    pub list_id: String,
+    pub name: String,
+    pub category: WatchlistCategory,
+    /// The listed plate that matched
+    pub plate: String,
+    pub distance: usize,
+}
+
+/// Uppercase letters and digits of a plate, so "ab-123 c" matches "AB123C"
+pub fn normalize_plate(plate: &str) -> String {
+    plate
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Levenshtein distance between two plates
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Edits tolerated for a read plate: at most a third of its characters, so
+/// a short or partial read does not match unrelated plates
+fn tolerance(read: &str, max_edit_distance: usize) -> usize {
+    max_edit_distance.min(read.chars().count() / 3)
+}
+
+/// Plate watchlists by list ID, shared by the LPR plugin and its API
+#[derive(Debug, Clone, Default)]
+pub struct Watchlists {
+    lists: Arc<RwLock<BTreeMap<String, Watchlist>>>,
+}
+
+impl Watchlists {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn list(&self) -> Result<Vec<Watchlist>> {
+        Ok(self.read()?.values().cloned().collect())
+    }
+
+    pub fn get(&self, list_id: &str) -> Result<Option<Watchlist>> {
+        Ok(self.read()?.get(list_id).cloned())
+    }
+
+    /// Create a validated watchlist; `None` when its ID is taken
+    pub fn create(&self, new: NewWatchlist) -> Result<Option<Watchlist>> {
+        let list_id = new
+            .list_id
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let mut lists = self.write()?;
+        if lists.contains_key(&list_id) {
+            return Ok(None);
+        }
+        let now = common::validation::safe_unix_timestamp();
+        let list = Watchlist {
+            list_id: list_id.clone(),
+            name: new.name.trim().to_string(),
+            category: new.category,
+            max_edit_distance: new.max_edit_distance,
+            plates: new.plates.iter().map(|p| normalize_plate(p)).collect(),
+            created_at: now,
+            updated_at: now,
+        };
+        lists.insert(list_id, list.clone());
+        Ok(Some(list))
+    }
+
+    /// Apply a validated update, returning the list or `None` when it does
+    /// not exist
+    pub fn update(&self, list_id: &str, update: WatchlistUpdate) -> Result<Option<Watchlist>> {
+        let mut lists = self.write()?;
+        let Some(list) = lists.get_mut(list_id) else {
+            return Ok(None);
+        };
+        if let Some(name) = update.name {
+            list.name = name.trim().to_string();
+        }
+        if let Some(category) = update.category {
+            list.category = category;
+        }
+        if let Some(max_edit_distance) = update.max_edit_distance {
+            list.max_edit_distance = max_edit_distance;
+        }
+        if let Some(plates) = update.plates {
+            list.plates = plates.iter().map(|p| normalize_plate(p)).collect();
+        }
+        list.updated_at = common::validation::safe_unix_timestamp();
+        Ok(Some(list.clone()))
+    }
+
+    /// Add validated plates to a list; `None` when it does not exist
+    pub fn add_plates(&self, list_id: &str, plates: &[String]) -> Result<Option<Watchlist>> {
+        let mut lists = self.write()?;
+        let Some(list) = lists.get_mut(list_id) else {
+            return Ok(None);
+        };
+        let mut merged = list.plates.clone();
+        merged.extend(plates.iter().map(|p| normalize_plate(p)));
+        if merged.len() > MAX_PLATES {
+            bail!("a watchlist holds at most {} plates", MAX_PLATES);
+        }
+        list.plates = merged;
+        list.updated_at = common::validation::safe_unix_timestamp();
+        Ok(Some(list.clone()))
+    }
+
+    /// Remove a plate from a list; `None` when the list does not exist,
+    /// false when the plate is not on it
+    pub fn remove_plate(&self, list_id: &str, plate: &str) -> Result<Option<bool>> {
+        let mut lists = self.write()?;
+        let Some(list) = lists.get_mut(list_id) else {
+            return Ok(None);
+        };
+        let removed = list.plates.remove(&normalize_plate(plate));
+        if removed {
+            list.updated_at = common::validation::safe_unix_timestamp();
+        }
+        Ok(Some(removed))
+    }
+
+    pub fn delete(&self, list_id: &str) -> Result<bool> {
+        Ok(self.write()?.remove(list_id).is_some())
+    }
+
+    /// Closest listed plate to a read one across all lists; exact matches
+    /// win, then the fewest edits, then the first list by ID
+    pub fn best_match(&self, read: &str, default_edit_distance: usize) -> Result<Option<WatchlistMatch>> {
+        let read = normalize_plate(read);
+        if read.is_empty() {
+            return Ok(None);
+        }
+        let lists = self.read()?;
+        let mut best: Option<WatchlistMatch> = None;
+        for list in lists.values() {
+            let hit = |plate: &str, distance| WatchlistMatch {
+                list_id: list.list_id.clone(),
+                name: list.name.clone(),
+                category: list.category,
+                plate: plate.to_string(),
+                distance,
+            };
+            if list.plates.contains(&read) {
+                return Ok(Some(hit(&read, 0)));
+            }
+            let allowed = tolerance(&read, list.max_edit_distance.unwrap_or(default_edit_distance));
+            let allowed = match &best {
+                Some(best) => allowed.min(best.distance.saturating_sub(1)),
+                None => allowed,
+            };
+            if allowed == 0 {
+                continue;
+            }
+            let read_len = read.chars().count();
+            for plate in &list.plates {
+                if plate.chars().count().abs_diff(read_len) > allowed {
+                    continue;
+                }
+                let distance = edit_distance(&read, plate);
+                if distance <= allowed && best.as_ref().is_none_or(|b| distance < b.distance) {
+                    best = Some(hit(plate, distance));
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    fn read(&self) -> Result<std::sync::RwLockReadGuard<'_, BTreeMap<String, Watchlist>>> {
+        Ok(self.lists.read().map_err(|e| PluginError::poisoned("watchlists", e))?)
+    }
+
+    fn write(&self) -> Result<std::sync::RwLockWriteGuard<'_, BTreeMap<String, Watchlist>>> {
+        Ok(self.lists.write().map_err(|e| PluginError::poisoned("watchlists", e))?)
+    }
+}
+
+/// Detection metadata of a read plate: `watchlist_hit`, and on a hit the
+/// list (`watchlist`, `watchlist_id`, `watchlist_category`) and the listed
+/// plate with its distance
+pub fn annotate(metadata: &mut serde_json::Value, hit: Option<&WatchlistMatch>) {
+    let Some(metadata) = metadata.as_object_mut() else {
+        return;
+    };
+    metadata.insert("watchlist_hit".into(), serde_json::json!(hit.is_some()));
+    if let Some(hit) = hit {
+        metadata.insert("watchlist".into(), serde_json::json!(hit.name));
+        metadata.insert("watchlist_id".into(), serde_json::json!(hit.list_id));
+        metadata.insert("watchlist_category".into(), serde_json::json!(hit.category.as_str()));
+        metadata.insert("watchlist_plate".into(), serde_json::json!(hit.plate));
+        metadata.insert("watchlist_distance".into(), serde_json::json!(hit.distance));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(list_id: &str, category: WatchlistCategory, plates: &[&str]) -> NewWatchlist {
+        NewWatchlist {
+            list_id: Some(list_id.to_string()),
+            name: list_id.to_string(),
+            category,
+            max_edit_distance: None,
+            plates: plates.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn plates_are_normalized_and_compared_by_edits() {
+        assert_eq!(normalize_plate("ab-123 c"), "AB123C");
+        assert_eq!(edit_distance("AB123C", "AB123C"), 0);
+        assert_eq!(edit_distance("AB123C", "A8123C"), 1);
+        assert_eq!(edit_distance("AB123C", "AB123"), 1);
+        assert_eq!(edit_distance("AB123C", "BA123C"), 2);
+    }
+
+    #[test]
+    fn reads_match_within_the_edit_distance() {
+        let watchlists = Watchlists::new();
+        watchlists
+            .create(list("stolen", WatchlistCategory::Stolen, &["AB123C", "XY987"]))
+            .unwrap();
+        watchlists
+            .create(list("vip", WatchlistCategory::Vip, &["ab-123-d"]))
+            .unwrap();
+
+        // Exact reads win over closer lists checked first
+        let hit = watchlists.best_match("AB123D", 1).unwrap().unwrap();
+        assert_eq!((hit.list_id.as_str(), hit.distance), ("vip", 0));
+
+        // One misread character
+        let hit = watchlists.best_match("A8123C", 1).unwrap().unwrap();
+        assert_eq!(hit.list_id, "stolen");
+        assert_eq!(hit.plate, "AB123C");
+        assert_eq!(hit.category, WatchlistCategory::Stolen);
+        assert_eq!(hit.distance, 1);
+
+        assert!(watchlists.best_match("A8I23C", 1).unwrap().is_none());
+        assert!(watchlists.best_match("A8I23C", 2).unwrap().is_some());
+        // A short read tolerates fewer edits than configured
+        assert!(watchlists.best_match("XY98", 3).unwrap().is_some());
+        assert!(watchlists.best_match("XY", 3).unwrap().is_none());
+        assert!(watchlists.best_match("---", 3).unwrap().is_none());
+    }
+
+    #[test]
+    fn updates_keep_left_out_fields() {
+        let watchlists = Watchlists::new();
+        let mut new = list("blocked", WatchlistCategory::Blocked, &["AB123C"]);
+        new.max_edit_distance = Some(2);
+        watchlists.create(new.clone()).unwrap().unwrap();
+        assert!(watchlists.create(new).unwrap().is_none());
+
+        let rename: WatchlistUpdate = serde_json::from_str(r#"{"name": "Banned"}"#).unwrap();
+        let updated = watchlists.update("blocked", rename).unwrap().unwrap();
+        assert_eq!(updated.name, "Banned");
+        assert_eq!(updated.max_edit_distance, Some(2));
+
+        let reset: WatchlistUpdate = serde_json::from_str(r#"{"max_edit_distance": null}"#).unwrap();
+        let updated = watchlists.update("blocked", reset).unwrap().unwrap();
+        assert_eq!(updated.max_edit_distance, None);
+        assert_eq!(updated.category, WatchlistCategory::Blocked);
+
+        let added = watchlists
+            .add_plates("blocked", &["xy 987".to_string()])
+            .unwrap()
+            .unwrap();
+        assert!(added.plates.contains("XY987"));
+        assert_eq!(watchlists.remove_plate("blocked", "AB-123-C").unwrap(), Some(true));
+        assert_eq!(watchlists.remove_plate("blocked", "AB123C").unwrap(), Some(false));
+        assert_eq!(watchlists.remove_plate("missing", "AB123C").unwrap(), None);
+        assert!(watchlists.delete("blocked").unwrap());
+        assert!(!watchlists.delete("blocked").unwrap());
+    }
+
+    #[test]
+    fn invalid_lists_are_rejected() {
+        assert!(list("stolen", WatchlistCategory::Stolen, &["AB123C"]).validate().is_ok());
+        assert!(list("stolen", WatchlistCategory::Stolen, &["--"]).validate().is_err());
+        let mut far = list("stolen", WatchlistCategory::Stolen, &[]);
+        far.max_edit_distance = Some(MAX_EDIT_DISTANCE + 1);
+        assert!(far.validate().is_err());
+        let unnamed = NewWatchlist::default();
+        assert!(unnamed.validate().is_err());
+    }
+
+    #[test]
+    fn hits_are_annotated_on_the_detection() {
+        let mut metadata = serde_json::json!({"plate_number": "A8123C"});
+        annotate(&mut metadata, None);
+        assert_eq!(metadata["watchlist_hit"], false);
+
+        let hit = WatchlistMatch {
+            list_id: "stolen".into(),
+            name: "Stolen vehicles".into(),
+            category: WatchlistCategory::Stolen,
+            plate: "AB123C".into(),
+            distance: 1,
+        };
+        annotate(&mut metadata, Some(&hit));
+        assert_eq!(metadata["watchlist_hit"], true);
+        assert_eq!(metadata["watchlist"], "Stolen vehicles");
+        assert_eq!(metadata["watchlist_category"], "stolen");
+        assert_eq!(metadata["watchlist_plate"], "AB123C");
+        assert_eq!(metadata["plate_number"], "A8123C");
+    }
+}
@@ -1,6 +1,6 @@
 /// Mock object detection plugin for testing and demonstration purposes
-use super::AiPlugin;
-use anyhow::Result;
+use super::{AiPlugin, PluginError};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
 use serde::{Deserialize, Serialize};
@@ -116,7 +116,8 @@ impl AiPlugin for MockDetectorPlugin {
 
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         if !config.is_null() {
-            self.config = serde_json::from_value(config)?;
+            self.config = serde_json::from_value(config)
+                .context(PluginError::config("Invalid mock detector config"))?;
         }
         tracing::info!(
             "Initialized MockDetectorPlugin with confidence threshold: {}",
@@ -233,6 +234,7 @@ mod tests {
             height: 1080,
             format: "jpeg".to_string(),
             data: "base64encodeddata".to_string(),
+            audio: None,
         };
 
         let result = plugin.process_frame(&frame).await.unwrap();
@@ -254,6 +256,7 @@ mod tests {
             height: 1080,
             format: "jpeg".to_string(),
             data: "base64encodeddata".to_string(),
+            audio: None,
         };
 
         let result1 = plugin.process_frame(&frame).await.unwrap();
@@ -1,16 +1,78 @@
 pub mod action_recognition;
 pub mod anomaly_detection;
+pub mod audio_events;
 pub mod crowd_analytics;
+pub mod dynamic;
+pub mod error;
 pub mod facial_recognition;
+pub mod grpc_backend;
 pub mod lpr;
+pub mod lpr_watchlist;
 pub mod mock_detector;
 pub mod pose_estimation;
+pub mod ppe_detection;
 pub mod registry;
+pub mod schema;
+pub mod thermal_analytics;
+pub mod vehicle_attributes;
+pub mod vision;
+pub mod wasm_plugin;
 pub mod yolov8_detector;
+pub mod yolov8_segmentation;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use common::ai_tasks::{AiResult, PluginInfo, VideoFrame};
+use common::ai_tasks::{AiResult, PluginBackendStatus, PluginInfo, VideoFrame};
+
+pub use error::PluginError;
+
+/// Frames per inference an ONNX session accepts: up to `max_batch_size`
+/// when the first input has a dynamic batch dimension, otherwise one (a model
+/// exported for a fixed batch of one rejects anything else)
+pub(crate) fn batch_capacity(session: &ort::session::Session, max_batch_size: usize) -> usize {
+    let dynamic_batch = session
+        .inputs
+        .first()
+        .and_then(|input| input.input_type.tensor_shape())
+        .and_then(|shape| shape.first().copied())
+        == Some(-1);
+    if dynamic_batch {
+        max_batch_size.max(1)
+    } else {
+        1
+    }
+}
+
+/// Fail with a config error unless the model file exists, so a missing
+/// model is not mistaken for a failing execution provider
+/// GPU of a session initialized on `provider`; `None` for the CPU provider
+pub(crate) fn gpu_device(provider: &str, device_id: i32) -> Option<u32> {
+    if provider.eq_ignore_ascii_case("CPU") {
+        return None;
+    }
+    u32::try_from(device_id).ok()
+}
+
+/// Configuration of a plugin loaded from a file (a WASM component, a shared
+/// object): `<name>.json` next to it, or an empty object
+pub fn sidecar_config(path: &std::path::Path) -> Result<serde_json::Value> {
+    use anyhow::Context;
+    let config_path = path.with_extension("json");
+    if !config_path.exists() {
+        return Ok(serde_json::json!({}));
+    }
+    let text = std::fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read {}", config_path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Invalid JSON in {}", config_path.display()))
+}
+
+pub(crate) fn require_model_file(model_path: &str) -> Result<()> {
+    if std::path::Path::new(model_path).exists() {
+        Ok(())
+    } else {
+        Err(PluginError::config(format!("Model file not found: {}", model_path)).into())
+    }
+}
 
 /// Core trait that all AI plugins must implement
 #[async_trait]
@@ -42,6 +104,7 @@ pub trait AiPlugin: Send + Sync {
             config_schema: self.config_schema(),
             supported_formats: self.supported_formats(),
             requires_gpu: self.requires_gpu(),
+            backend: self.backend_status(),
         }
     }
 
@@ -60,12 +123,71 @@ pub trait AiPlugin: Send + Sync {
         false
     }
 
-    /// Initialize plugin with configuration
+    /// GPU the plugin runs inference on, once it is initialized on CUDA or
+    /// TensorRT; `None` on the CPU
+    fn gpu_device(&self) -> Option<u32> {
+        None
+    }
+
+    /// Connection and health of the remote backend, for plugins that
+    /// delegate inference to another server
+    fn backend_status(&self) -> Option<PluginBackendStatus> {
+        None
+    }
+
+    /// Whether the plugin can run as a secondary stage after another plugin
+    fn is_secondary(&self) -> bool {
+        false
+    }
+
+    /// Initialize plugin with configuration; the registry calls it again
+    /// with the same configuration to restart the plugin after a fatal or
+    /// resource failure (see `PluginError`)
     async fn init(&mut self, config: serde_json::Value) -> Result<()>;
 
+    /// Apply a new configuration to the running plugin. The default shuts
+    /// the plugin down and initializes it again; plugins override it to
+    /// apply settings that leave the loaded model untouched (thresholds,
+    /// class filters) in place.
+    async fn reconfigure(&mut self, config: serde_json::Value) -> Result<()> {
+        if let Err(e) = self.shutdown().await {
+            tracing::warn!("Error shutting down plugin '{}' for reconfiguration: {}", self.id(), e);
+        }
+        self.init(config).await
+    }
+
     /// Process a video frame and return detection results
     async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult>;
 
+    /// Largest number of frames `process_batch` runs as one inference;
+    /// plugins without batched inference keep 1 and get frames one by one
+    fn max_batch_size(&self) -> usize {
+        1
+    }
+
+    /// Process several frames, possibly of different cameras, returning one
+    /// result per frame in the same order. Plugins with batched tensors
+    /// override this; the default processes the frames one after another.
+    async fn process_batch(&self, frames: &[VideoFrame]) -> Result<Vec<AiResult>> {
+        let mut results = Vec::with_capacity(frames.len());
+        for frame in frames {
+            results.push(self.process_frame(frame).await?);
+        }
+        Ok(results)
+    }
+
+    /// Process a frame of a task, with the task's `model_config` (e.g.
+    /// per-camera rules); plugins without per-task settings ignore it
+    async fn process_task_frame(&self, frame: &VideoFrame, _task_config: &serde_json::Value) -> Result<AiResult> {
+        self.process_frame(frame).await
+    }
+
+    /// Enrich the result of a primary plugin for the same frame (secondary
+    /// plugins only)
+    async fn enrich(&self, _frame: &VideoFrame, _result: &mut AiResult) -> Result<()> {
+        Ok(())
+    }
+
     /// Health check - verify the plugin is operational
     async fn health_check(&self) -> Result<bool> {
         Ok(true)
@@ -2,7 +2,7 @@
 ///
 /// This plugin detects human poses and keypoints (e.g., shoulders, elbows, wrists, hips, knees, ankles).
 /// Compatible with MoveNet, MediaPipe Pose, or similar ONNX models.
-use super::AiPlugin;
+use super::{AiPlugin, PluginError};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
@@ -355,10 +355,11 @@ impl PoseEstimationPlugin {
                 }
             }
             _ => {
-                return Err(anyhow::anyhow!(
+                return Err(PluginError::config(format!(
                     "Unsupported output shape: {:?}. Expected [1, num_keypoints, 3] or [1, num_poses, num_keypoints, 3]",
                     shape
-                ));
+                ))
+                .into());
             }
         }
 
@@ -503,9 +504,15 @@ impl AiPlugin for PoseEstimationPlugin {
         false // Can run on CPU, GPU support is optional
     }
 
+    fn gpu_device(&self) -> Option<u32> {
+        let provider = self.execution_provider_used.lock().ok()?;
+        super::gpu_device(&provider, self.config.device_id)
+    }
+
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         if !config.is_null() {
-            self.config = serde_json::from_value(config)?;
+            self.config = serde_json::from_value(config)
+                .context(PluginError::config("Invalid pose estimation configuration"))?;
         }
 
         // Read configuration from environment variables if set
@@ -521,6 +528,8 @@ impl AiPlugin for PoseEstimationPlugin {
             }
         }
 
+        super::require_model_file(&self.config.model_path)?;
+
         // Configure execution providers with fallback
         let provider_preference = self.config.execution_provider.to_uppercase();
         let (session, actual_provider) = match provider_preference.as_str() {
@@ -589,14 +598,15 @@ impl AiPlugin for PoseEstimationPlugin {
         let session_lock = self
             .session
             .as_ref()
-            .context("Model not initialized - call init() first")?;
+            .context(PluginError::fatal("Model not initialized - call init() first"))?;
 
         // Decode base64 image
         let image_data = base64::prelude::BASE64_STANDARD
             .decode(&frame.data)
-            .context("Failed to decode base64 image")?;
+            .context(PluginError::recoverable("Failed to decode base64 image"))?;
 
-        let img = image::load_from_memory(&image_data).context("Failed to load image")?;
+        let img = image::load_from_memory(&image_data)
+            .context(PluginError::recoverable("Failed to load image"))?;
 
         let original_width = img.width();
         let original_height = img.height();
@@ -611,15 +621,17 @@ impl AiPlugin for PoseEstimationPlugin {
         let inference_start = std::time::Instant::now();
         let mut session = session_lock
             .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock session: {}", e))?;
-        let outputs = session.run(ort::inputs![input_tensor])?;
+            .map_err(|e| PluginError::poisoned("session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
         let inference_time = inference_start.elapsed();
 
         // Get output tensor - try common output names
         let output_value = outputs
             .get("output")
             .or_else(|| outputs.get("output0"))
-            .context("No output tensor found")?;
+            .context(PluginError::config("No output tensor found"))?;
 
         let (shape, data) = output_value.try_extract_tensor::<f32>()?;
 
@@ -0,0 +1,624 @@
+/// PPE and safety-compliance detection plugin
+///
+/// Runs a YOLOv8-format detector trained on people and protective
+/// equipment, then:
+/// 1. Assigns hard hats, hi-vis vests and masks to the person wearing them,
+///    by where the item sits within the person's box
+/// 2. Checks each person against the compliance zones they stand in (e.g.
+///    "hard hat required in zone B") and adds a `ppe_violation` detection
+///    listing the missing items
+///
+/// Violations carry `metadata.violation = true`, which the AI service
+/// raises as `ai_detection` alerts (see `crate::alerts`).
+use super::vision::{self, Candidate, Letterbox, NmsConfig, Normalization, ResizeMode};
+use super::{AiPlugin, PluginError};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
+use image::DynamicImage;
+use ndarray::{Array, IxDyn};
+use ort::{
+    execution_providers::{CPUExecutionProvider, CUDAExecutionProvider},
+    session::{builder::GraphOptimizationLevel, Session},
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Protective equipment the plugin tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PpeItem {
+    HardHat,
+    HiVisVest,
+    Mask,
+}
+
+impl PpeItem {
+    pub const ALL: [PpeItem; 3] = [PpeItem::HardHat, PpeItem::HiVisVest, PpeItem::Mask];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PpeItem::HardHat => "hard_hat",
+            PpeItem::HiVisVest => "hi_vis_vest",
+            PpeItem::Mask => "mask",
+        }
+    }
+
+    /// Item for a model label, accepting common dataset spellings
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().replace(['-', ' '], "_").as_str() {
+            "hard_hat" | "hardhat" | "helmet" => Some(PpeItem::HardHat),
+            "hi_vis_vest" | "vest" | "safety_vest" | "hi_vis" => Some(PpeItem::HiVisVest),
+            "mask" | "face_mask" => Some(PpeItem::Mask),
+            _ => None,
+        }
+    }
+
+    /// Vertical band of the person's box (fractions of its height, from the
+    /// top) the item's center must fall in; hard hats may stick out above
+    fn band(&self) -> (f32, f32) {
+        match self {
+            PpeItem::HardHat => (-0.2, 0.35),
+            PpeItem::Mask => (0.0, 0.45),
+            PpeItem::HiVisVest => (0.15, 0.85),
+        }
+    }
+}
+
+/// Area of the frame with required equipment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceZone {
+    /// Zone identifier
+    pub id: String,
+
+    /// Zone name
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Zone area in frame pixels; the whole frame when absent. A person is
+    /// in the zone when their feet (bottom center of the box) are.
+    #[serde(default)]
+    pub bbox: Option<BoundingBox>,
+
+    /// Equipment every person in the zone must wear
+    pub required: Vec<PpeItem>,
+}
+
+impl ComplianceZone {
+    fn contains_person(&self, person: &BoundingBox) -> bool {
+        let Some(zone) = &self.bbox else {
+            return true;
+        };
+        let feet_x = person.x + person.width / 2;
+        let feet_y = person.y + person.height;
+        feet_x >= zone.x && feet_x <= zone.x + zone.width && feet_y >= zone.y && feet_y <= zone.y + zone.height
+    }
+}
+
+/// Per-task settings, read from the task's `model_config`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct PpeTaskConfig {
+    #[serde(default)]
+    zones: Vec<ComplianceZone>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PpeConfig {
+    /// Path to the PPE detector ONNX model (YOLOv8 output format)
+    pub model_path: String,
+
+    /// Model class labels, in output order
+    #[serde(default = "default_class_names")]
+    pub class_names: Vec<String>,
+
+    /// Label of the person class
+    #[serde(default = "default_person_class")]
+    pub person_class: String,
+
+    /// Model input size (width and height)
+    #[serde(default = "default_input_size")]
+    pub input_size: u32,
+
+    /// Confidence threshold for detections
+    #[serde(default = "default_confidence")]
+    pub confidence_threshold: f32,
+
+    /// IoU threshold for NMS
+    #[serde(default = "default_iou_threshold")]
+    pub iou_threshold: f32,
+
+    /// How frames are fitted into the model input
+    #[serde(default)]
+    pub resize_mode: ResizeMode,
+
+    /// Non-maximum suppression variant (per class by default)
+    #[serde(default)]
+    pub nms: NmsConfig,
+
+    /// Zones for tasks that do not set their own
+    #[serde(default)]
+    pub zones: Vec<ComplianceZone>,
+
+    /// Execution provider preference (CPU, CUDA)
+    #[serde(default = "default_execution_provider")]
+    pub execution_provider: String,
+
+    /// GPU device ID (0, 1, 2, etc.)
+    #[serde(default)]
+    pub device_id: i32,
+}
+
+fn default_class_names() -> Vec<String> {
+    ["person", "hard_hat", "hi_vis_vest", "mask"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect()
+}
+
+fn default_person_class() -> String {
+    "person".to_string()
+}
+
+fn default_input_size() -> u32 {
+    640
+}
+
+fn default_confidence() -> f32 {
+    0.4
+}
+
+fn default_iou_threshold() -> f32 {
+    0.45
+}
+
+fn default_execution_provider() -> String {
+    "CPU".to_string()
+}
+
+impl Default for PpeConfig {
+    fn default() -> Self {
+        Self {
+            model_path: String::new(),
+            class_names: default_class_names(),
+            person_class: default_person_class(),
+            input_size: default_input_size(),
+            confidence_threshold: default_confidence(),
+            iou_threshold: default_iou_threshold(),
+            resize_mode: ResizeMode::default(),
+            nms: NmsConfig::default(),
+            zones: Vec::new(),
+            execution_provider: default_execution_provider(),
+            device_id: 0,
+        }
+    }
+}
+
+/// PPE and safety-compliance detection plugin
+pub struct PpeDetectionPlugin {
+    config: PpeConfig,
+    session: Option<Arc<Mutex<Session>>>,
+}
+
+impl PpeDetectionPlugin {
+    pub fn new() -> Self {
+        Self {
+            config: PpeConfig::default(),
+            session: None,
+        }
+    }
+
+    /// Run the detector over a frame
+    fn detect(&self, img: &DynamicImage) -> Result<Vec<Detection>> {
+        let session = self
+            .session
+            .as_ref()
+            .context(PluginError::fatal("Model not initialized - call init() first"))?;
+
+        let size = self.config.input_size;
+        let (input, _) = vision::to_nchw(
+            std::slice::from_ref(img),
+            size,
+            size,
+            self.config.resize_mode,
+            Normalization::Unit,
+        );
+        let input_tensor = Value::from_array(input)?;
+
+        let mut session = session
+            .lock()
+            .map_err(|e| PluginError::poisoned("session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
+        let output_value = outputs
+            .get("output0")
+            .context(PluginError::config("No output tensor found"))?;
+        let (shape, data) = output_value.try_extract_tensor::<f32>()?;
+        let shape: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
+        let output = Array::from_shape_vec(IxDyn(&shape), data.to_vec())?;
+
+        Ok(self.postprocess(&output, img.width(), img.height()))
+    }
+
+    /// Decode YOLOv8 output ([1, 4 + classes, predictions]) with per-class NMS
+    fn postprocess(&self, output: &Array<f32, IxDyn>, width: u32, height: u32) -> Vec<Detection> {
+        let size = self.config.input_size;
+        let letterbox = Letterbox::new(self.config.resize_mode, width, height, size, size);
+        let num_predictions = output.shape()[2];
+        let num_classes = output.shape()[1] - 4;
+
+        let mut candidates = Vec::new();
+        for i in 0..num_predictions {
+            let (class_idx, score) = (0..num_classes)
+                .map(|c| (c, output[[0, 4 + c, i]]))
+                .fold((0, 0.0f32), |best, cur| if cur.1 > best.1 { cur } else { best });
+            if score < self.config.confidence_threshold {
+                continue;
+            }
+            let bbox = letterbox.to_frame(output[[0, 0, i]], output[[0, 1, i]], output[[0, 2, i]], output[[0, 3, i]]);
+            candidates.push(Candidate {
+                bbox,
+                score,
+                class_id: class_idx,
+            });
+        }
+
+        vision::nms(
+            candidates,
+            self.config.iou_threshold,
+            self.config.confidence_threshold,
+            &self.config.nms,
+        )
+        .into_iter()
+        .map(|candidate| Detection {
+            class: self
+                .config
+                .class_names
+                .get(candidate.class_id)
+                .cloned()
+                .unwrap_or_else(|| format!("class_{}", candidate.class_id)),
+            confidence: candidate.score,
+            bbox: candidate.bbox,
+            metadata: None,
+        })
+        .collect()
+    }
+
+    fn create_session(&self) -> Result<Session> {
+        let model_path = &self.config.model_path;
+        super::require_model_file(model_path)?;
+        if self.config.execution_provider.eq_ignore_ascii_case("CUDA") {
+            let result = Session::builder()
+                .context("Failed to create session builder")?
+                .with_optimization_level(GraphOptimizationLevel::Level3)
+                .context("Failed to set optimization level")?
+                .with_execution_providers([
+                    CUDAExecutionProvider::default()
+                        .with_device_id(self.config.device_id)
+                        .build(),
+                    CPUExecutionProvider::default().build(),
+                ])
+                .context("Failed to set execution providers")?
+                .commit_from_file(model_path);
+            match result {
+                Ok(session) => return Ok(session),
+                Err(e) => tracing::warn!("CUDA failed for {}, falling back to CPU: {}", model_path, e),
+            }
+        }
+
+        Session::builder()
+            .context("Failed to create session builder")?
+            .with_optimization_level(GraphOptimizationLevel::Level3)
+            .context("Failed to set optimization level")?
+            .commit_from_file(model_path)
+            .context(PluginError::config("Failed to load model from file"))
+    }
+}
+
+impl Default for PpeDetectionPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `item` is worn by the person in `person`
+fn worn_by(item: PpeItem, item_box: &BoundingBox, person: &BoundingBox) -> bool {
+    let cx = (item_box.x + item_box.width / 2) as f32;
+    let cy = (item_box.y + item_box.height / 2) as f32;
+    let (top, bottom) = item.band();
+    let height = person.height as f32;
+    cx >= person.x as f32
+        && cx <= (person.x + person.width) as f32
+        && cy >= person.y as f32 + top * height
+        && cy <= person.y as f32 + bottom * height
+}
+
+/// Assign equipment to people and add a `ppe_violation` detection for each
+/// person missing equipment their zone requires
+fn evaluate_compliance(detections: Vec<Detection>, person_class: &str, zones: &[ComplianceZone]) -> Vec<Detection> {
+    let items: Vec<(PpeItem, BoundingBox)> = detections
+        .iter()
+        .filter_map(|d| PpeItem::from_label(&d.class).map(|item| (item, d.bbox.clone())))
+        .collect();
+
+    let mut output = Vec::with_capacity(detections.len());
+    let mut violations = Vec::new();
+    for mut detection in detections {
+        if detection.class != person_class {
+            output.push(detection);
+            continue;
+        }
+
+        let worn: Vec<PpeItem> = PpeItem::ALL
+            .into_iter()
+            .filter(|item| {
+                items
+                    .iter()
+                    .any(|(i, bbox)| i == item && worn_by(*item, bbox, &detection.bbox))
+            })
+            .collect();
+        let ppe: serde_json::Map<String, serde_json::Value> = PpeItem::ALL
+            .iter()
+            .map(|item| (item.as_str().to_string(), serde_json::json!(worn.contains(item))))
+            .collect();
+        detection.metadata = Some(serde_json::json!({ "ppe": ppe }));
+
+        for zone in zones.iter().filter(|z| z.contains_person(&detection.bbox)) {
+            let missing: Vec<&str> = zone
+                .required
+                .iter()
+                .filter(|item| !worn.contains(item))
+                .map(|item| item.as_str())
+                .collect();
+            if missing.is_empty() {
+                continue;
+            }
+            violations.push(Detection {
+                class: "ppe_violation".to_string(),
+                confidence: detection.confidence,
+                bbox: detection.bbox.clone(),
+                metadata: Some(serde_json::json!({
+                    "violation": true,
+                    "zone_id": zone.id,
+                    "zone_name": zone.name.as_deref().unwrap_or(&zone.id),
+                    "missing": missing,
+                    "missing_ppe": missing.join(","),
+                })),
+            });
+        }
+        output.push(detection);
+    }
+
+    output.extend(violations);
+    output
+}
+
+#[async_trait]
+impl AiPlugin for PpeDetectionPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        "ppe_detection"
+    }
+
+    fn name(&self) -> &'static str {
+        "PPE Compliance"
+    }
+
+    fn description(&self) -> &'static str {
+        "Detects hard hats, hi-vis vests and masks on people and reports per-zone compliance violations"
+    }
+
+    fn version(&self) -> &'static str {
+        "1.0.0"
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "zones": {
+                    "type": "array",
+                    "description": "Compliance zones (task model_config, or plugin default)",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "required"],
+                        "properties": {
+                            "id": {"type": "string"},
+                            "name": {"type": "string"},
+                            "bbox": {
+                                "type": "object",
+                                "description": "Zone area in pixels; whole frame when absent",
+                                "properties": {
+                                    "x": {"type": "integer"},
+                                    "y": {"type": "integer"},
+                                    "width": {"type": "integer"},
+                                    "height": {"type": "integer"}
+                                }
+                            },
+                            "required": {
+                                "type": "array",
+                                "items": {"type": "string", "enum": ["hard_hat", "hi_vis_vest", "mask"]}
+                            }
+                        }
+                    }
+                },
+                "confidence_threshold": {
+                    "type": "number",
+                    "minimum": 0.0,
+                    "maximum": 1.0,
+                    "default": 0.4
+                },
+                "resize_mode": {
+                    "type": "string",
+                    "enum": ["letterbox", "stretch"],
+                    "default": "letterbox"
+                },
+                "nms": {
+                    "type": "object",
+                    "properties": {
+                        "method": {"type": "string", "enum": ["hard", "soft", "diou"], "default": "hard"},
+                        "class_agnostic": {"type": "boolean", "default": false},
+                        "soft_sigma": {"type": "number", "default": 0.5}
+                    }
+                }
+            }
+        }))
+    }
+
+    fn supported_formats(&self) -> Vec<String> {
+        vec!["jpeg".to_string(), "png".to_string()]
+    }
+
+    async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+        self.config = serde_json::from_value(config)
+            .context(PluginError::config("Invalid PPE detection config"))?;
+        let session = self.create_session()?;
+        self.session = Some(Arc::new(Mutex::new(session)));
+        tracing::info!(
+            model = %self.config.model_path,
+            zones = self.config.zones.len(),
+            "PPE detection plugin initialized"
+        );
+        Ok(())
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        self.process_task_frame(frame, &serde_json::Value::Null).await
+    }
+
+    async fn process_task_frame(&self, frame: &VideoFrame, task_config: &serde_json::Value) -> Result<AiResult> {
+        let start = std::time::Instant::now();
+        let task_config: PpeTaskConfig = if task_config.is_null() {
+            PpeTaskConfig::default()
+        } else {
+            serde_json::from_value(task_config.clone())
+                .context(PluginError::config("Invalid PPE task config"))?
+        };
+        let zones = if task_config.zones.is_empty() {
+            &self.config.zones
+        } else {
+            &task_config.zones
+        };
+
+        let image_data = base64::prelude::BASE64_STANDARD
+            .decode(&frame.data)
+            .context(PluginError::recoverable("Failed to decode base64 image"))?;
+        let img = image::load_from_memory(&image_data)
+            .context(PluginError::recoverable("Failed to load image"))?;
+
+        let detections = evaluate_compliance(self.detect(&img)?, &self.config.person_class, zones);
+        let violations = detections.iter().filter(|d| d.class == "ppe_violation").count();
+
+        Ok(AiResult {
+            task_id: frame.source_id.clone(),
+            timestamp: frame.timestamp,
+            plugin_type: self.id().to_string(),
+            confidence: detections
+                .iter()
+                .map(|d| d.confidence)
+                .reduce(f32::max),
+            detections,
+            processing_time_ms: Some(start.elapsed().as_millis() as u64),
+            metadata: Some(serde_json::json!({
+                "zones": zones.len(),
+                "violations": violations,
+            })),
+        })
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(self.session.is_some())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        tracing::info!("Shutting down PPE detection plugin");
+        self.session = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(class: &str, x: u32, y: u32, width: u32, height: u32) -> Detection {
+        Detection {
+            class: class.to_string(),
+            confidence: 0.8,
+            bbox: BoundingBox { x, y, width, height },
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_item_labels() {
+        assert_eq!(PpeItem::from_label("Hard-Hat"), Some(PpeItem::HardHat));
+        assert_eq!(PpeItem::from_label("helmet"), Some(PpeItem::HardHat));
+        assert_eq!(PpeItem::from_label("safety vest"), Some(PpeItem::HiVisVest));
+        assert_eq!(PpeItem::from_label("person"), None);
+    }
+
+    #[test]
+    fn test_zone_violations() {
+        let zones = vec![
+            ComplianceZone {
+                id: "zone-b".into(),
+                name: Some("Zone B".into()),
+                bbox: Some(BoundingBox { x: 0, y: 0, width: 300, height: 400 }),
+                required: vec![PpeItem::HardHat, PpeItem::HiVisVest],
+            },
+            ComplianceZone {
+                id: "clean-room".into(),
+                name: None,
+                bbox: Some(BoundingBox { x: 300, y: 0, width: 300, height: 400 }),
+                required: vec![PpeItem::Mask],
+            },
+        ];
+        let detections = vec![
+            // Worker in zone B wearing a helmet (slightly above the box) but no vest
+            detection("person", 100, 100, 60, 200),
+            detection("hard_hat", 115, 85, 30, 20),
+            // A vest lying on the floor next to them does not count
+            detection("hi_vis_vest", 200, 330, 40, 40),
+            // Worker in the clean room with a mask
+            detection("person", 400, 100, 60, 200),
+            detection("mask", 420, 140, 20, 15),
+        ];
+
+        let output = evaluate_compliance(detections, "person", &zones);
+        let violations: Vec<&Detection> = output.iter().filter(|d| d.class == "ppe_violation").collect();
+        assert_eq!(violations.len(), 1);
+        let metadata = violations[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata["zone_id"], "zone-b");
+        assert_eq!(metadata["zone_name"], "Zone B");
+        assert_eq!(metadata["missing"], serde_json::json!(["hi_vis_vest"]));
+        assert_eq!(metadata["violation"], true);
+
+        let first = output[0].metadata.as_ref().unwrap();
+        assert_eq!(first["ppe"]["hard_hat"], true);
+        assert_eq!(first["ppe"]["hi_vis_vest"], false);
+        assert_eq!(output.iter().filter(|d| d.class == "person").count(), 2);
+    }
+
+    #[test]
+    fn test_zone_without_box_covers_frame() {
+        let zone = ComplianceZone {
+            id: "site".into(),
+            name: None,
+            bbox: None,
+            required: vec![PpeItem::HardHat],
+        };
+        let output = evaluate_compliance(vec![detection("person", 5000, 5000, 10, 30)], "person", &[zone]);
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[1].metadata.as_ref().unwrap()["missing_ppe"], "hard_hat");
+    }
+}
@@ -1,21 +1,101 @@
+use super::dynamic::DynamicPlugin;
 use super::AiPlugin;
-use anyhow::{anyhow, Result};
-use common::ai_tasks::PluginInfo;
-use std::collections::HashMap;
+use anyhow::{anyhow, Context, Result};
+use common::ai_tasks::{PluginErrorKind, PluginInfo};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+/// When the registry restarts (re-initializes) a failing plugin
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Failure kinds that restart the plugin
+    pub restart_on: Vec<PluginErrorKind>,
+    /// Restarts allowed per plugin within `window`; 0 disables restarts
+    pub max_restarts: usize,
+    pub window: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            restart_on: vec![PluginErrorKind::Fatal, PluginErrorKind::Resource],
+            max_restarts: 3,
+            window: Duration::from_secs(600),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Reads `AI_PLUGIN_RESTART_ON` (comma-separated kinds, `none` for
+    /// none), `AI_PLUGIN_MAX_RESTARTS` and `AI_PLUGIN_RESTART_WINDOW_SECS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            restart_on: std::env::var("AI_PLUGIN_RESTART_ON")
+                .ok()
+                .map(|kinds| {
+                    kinds
+                        .split(',')
+                        .filter_map(|kind| match kind.trim() {
+                            "fatal" => Some(PluginErrorKind::Fatal),
+                            "resource" => Some(PluginErrorKind::Resource),
+                            "recoverable" => Some(PluginErrorKind::Recoverable),
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .unwrap_or(defaults.restart_on),
+            max_restarts: std::env::var("AI_PLUGIN_MAX_RESTARTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_restarts),
+            window: std::env::var("AI_PLUGIN_RESTART_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.window),
+        }
+    }
+}
+
+/// What the registry did about a plugin failure
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestartOutcome {
+    /// The policy does not restart the plugin for this kind of failure
+    NotRestarted,
+    /// The plugin was re-initialized, by this failure or by another one
+    /// reported since `failed_at`
+    Restarted,
+    /// The plugin needed a restart but could not get one
+    GaveUp(String),
+}
 
 /// Registry for AI plugins
 #[derive(Clone)]
 pub struct PluginRegistry {
     plugins: Arc<RwLock<HashMap<String, Arc<RwLock<dyn AiPlugin>>>>>,
+    /// Configuration each plugin was initialized with, to restart it
+    init_configs: Arc<RwLock<HashMap<String, serde_json::Value>>>,
+    /// Recent restarts per plugin; the lock also serializes restarts
+    restarts: Arc<Mutex<HashMap<String, VecDeque<Instant>>>>,
+    restart_policy: RestartPolicy,
 }
 
 impl PluginRegistry {
     /// Create a new empty plugin registry
     pub fn new() -> Self {
+        Self::with_restart_policy(RestartPolicy::default())
+    }
+
+    pub fn with_restart_policy(restart_policy: RestartPolicy) -> Self {
         Self {
             plugins: Arc::new(RwLock::new(HashMap::new())),
+            init_configs: Arc::new(RwLock::new(HashMap::new())),
+            restarts: Arc::new(Mutex::new(HashMap::new())),
+            restart_policy,
         }
     }
 
@@ -35,6 +115,130 @@ impl PluginRegistry {
         Ok(())
     }
 
+    /// Register a plugin initialized with `init_config`, which it is
+    /// initialized with again when restarted; plugins registered without one
+    /// are never restarted
+    pub async fn register_with_config(
+        &self,
+        plugin: Arc<RwLock<dyn AiPlugin>>,
+        init_config: serde_json::Value,
+    ) -> Result<()> {
+        let id = plugin.read().await.id().to_string();
+        self.register(plugin).await?;
+        self.init_configs.write().await.insert(id, init_config);
+        Ok(())
+    }
+
+    /// Load a plugin from a shared object exporting the C ABI shim
+    /// (`export_dynamic_plugin!`), initialize it with the `<name>.json`
+    /// beside it and register it, restartable like a built-in plugin.
+    /// Returns the plugin id.
+    pub async fn load_dynamic(&self, path: impl AsRef<Path>) -> Result<String> {
+        let path = path.as_ref();
+        let mut plugin = DynamicPlugin::load(path)?;
+        let config = super::sidecar_config(path)?;
+        let id = plugin.id().to_string();
+        plugin
+            .init(config.clone())
+            .await
+            .with_context(|| format!("Failed to initialize plugin '{}' from {}", id, path.display()))?;
+        self.register_with_config(Arc::new(RwLock::new(plugin)), config).await?;
+        Ok(id)
+    }
+
+    /// Apply the restart policy to a failure of kind `kind` of a frame that
+    /// started processing at `failed_at`. Frames failing together restart
+    /// the plugin once: a failure that started before the last restart is
+    /// taken as handled by it.
+    pub async fn handle_failure(
+        &self,
+        plugin_id: &str,
+        kind: PluginErrorKind,
+        failed_at: Instant,
+    ) -> RestartOutcome {
+        if !self.restart_policy.restart_on.contains(&kind) {
+            return RestartOutcome::NotRestarted;
+        }
+        let outcome = self.restart(plugin_id, failed_at).await;
+        let label = if outcome == RestartOutcome::Restarted {
+            "restarted"
+        } else {
+            "gave_up"
+        };
+        telemetry::metrics::AI_SERVICE_PLUGIN_RESTARTS
+            .with_label_values(&[plugin_id, label])
+            .inc();
+        outcome
+    }
+
+    async fn restart(&self, plugin_id: &str, failed_at: Instant) -> RestartOutcome {
+        let Ok(plugin) = self.get(plugin_id).await else {
+            return RestartOutcome::GaveUp(format!("plugin '{}' is not registered", plugin_id));
+        };
+        let Some(init_config) = self.init_configs.read().await.get(plugin_id).cloned() else {
+            return RestartOutcome::GaveUp("plugin was registered without an init config".to_string());
+        };
+
+        let mut restarts = self.restarts.lock().await;
+        let history = restarts.entry(plugin_id.to_string()).or_default();
+        if history.back().is_some_and(|restarted_at| *restarted_at >= failed_at) {
+            return RestartOutcome::Restarted;
+        }
+        let now = Instant::now();
+        history.retain(|restarted_at| now.duration_since(*restarted_at) < self.restart_policy.window);
+        if history.len() >= self.restart_policy.max_restarts {
+            return RestartOutcome::GaveUp(format!(
+                "restarted {} times within {}s",
+                history.len(),
+                self.restart_policy.window.as_secs()
+            ));
+        }
+        history.push_back(now);
+
+        tracing::warn!("Restarting AI plugin '{}'", plugin_id);
+        let mut plugin = plugin.write().await;
+        if let Err(e) = plugin.shutdown().await {
+            tracing::warn!("Error shutting down plugin '{}' for restart: {}", plugin_id, e);
+        }
+        match plugin.init(init_config).await {
+            Ok(()) => {
+                tracing::info!("Restarted AI plugin '{}'", plugin_id);
+                RestartOutcome::Restarted
+            }
+            Err(e) => RestartOutcome::GaveUp(format!("re-initialization failed: {:#}", e)),
+        }
+    }
+
+    /// Configuration a plugin was registered (or last reconfigured) with
+    pub async fn init_config(&self, plugin_id: &str) -> Option<serde_json::Value> {
+        self.init_configs.read().await.get(plugin_id).cloned()
+    }
+
+    /// Apply `config` to a plugin (`AiPlugin::reconfigure`), e.g. to swap its
+    /// model or change thresholds. Frames wait for the switch; when the new
+    /// configuration fails, the previous one is applied again and the error
+    /// returned.
+    pub async fn reconfigure(&self, plugin_id: &str, config: serde_json::Value) -> Result<()> {
+        let plugin = self.get(plugin_id).await?;
+        let previous = self
+            .init_config(plugin_id)
+            .await
+            .ok_or_else(|| anyhow!("Plugin '{}' was registered without an init config", plugin_id))?;
+
+        // Serialized with restarts, which would re-apply a stale config
+        let _restarts = self.restarts.lock().await;
+        let mut plugin = plugin.write().await;
+        if let Err(e) = plugin.reconfigure(config.clone()).await {
+            if let Err(restore) = plugin.reconfigure(previous).await {
+                tracing::error!("Failed to restore plugin '{}' after reconfiguration: {:#}", plugin_id, restore);
+            }
+            return Err(e.context(format!("Failed to reconfigure plugin '{}'", plugin_id)));
+        }
+        self.init_configs.write().await.insert(plugin_id.to_string(), config);
+        tracing::info!("Reconfigured AI plugin '{}'", plugin_id);
+        Ok(())
+    }
+
     /// Get a plugin by ID
     pub async fn get(&self, plugin_id: &str) -> Result<Arc<RwLock<dyn AiPlugin>>> {
         let plugins = self.plugins.read().await;
@@ -57,6 +261,18 @@ impl PluginRegistry {
         infos
     }
 
+    /// GPU of each plugin running on one, by plugin id
+    pub async fn gpu_devices(&self) -> Vec<(String, u32)> {
+        let plugins = self.plugins.read().await;
+        let mut devices = Vec::new();
+        for (plugin_id, plugin) in plugins.iter() {
+            if let Some(device) = plugin.read().await.gpu_device() {
+                devices.push((plugin_id.clone(), device));
+            }
+        }
+        devices
+    }
+
     /// Check if a plugin is registered
     pub async fn has_plugin(&self, plugin_id: &str) -> bool {
         let plugins = self.plugins.read().await;
@@ -197,4 +413,162 @@ mod tests {
         let result = registry.register(plugin2).await;
         assert!(result.is_err());
     }
+
+    /// Counts `init` calls; fails to initialize once `fail_init` is set or
+    /// with a `broken` config
+    #[derive(Default)]
+    struct RestartablePlugin {
+        inits: usize,
+        fail_init: bool,
+        config: serde_json::Value,
+    }
+
+    #[async_trait]
+    impl AiPlugin for RestartablePlugin {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+
+        fn id(&self) -> &'static str {
+            "restartable"
+        }
+
+        fn name(&self) -> &'static str {
+            "Restartable"
+        }
+
+        fn description(&self) -> &'static str {
+            "Counts initializations"
+        }
+
+        fn version(&self) -> &'static str {
+            "1.0.0"
+        }
+
+        async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+            if self.fail_init || config.get("broken").is_some() {
+                return Err(anyhow!("model file missing"));
+            }
+            self.inits += 1;
+            self.config = config;
+            Ok(())
+        }
+
+        async fn process_frame(&self, _frame: &VideoFrame) -> Result<AiResult> {
+            Err(anyhow!("not used"))
+        }
+    }
+
+    fn inits(plugin: &Arc<RwLock<RestartablePlugin>>) -> usize {
+        plugin.try_read().unwrap().inits
+    }
+
+    #[tokio::test]
+    async fn restarts_plugins_on_fatal_failures() {
+        let registry = PluginRegistry::new();
+        let plugin = Arc::new(RwLock::new(RestartablePlugin::default()));
+        registry
+            .register_with_config(plugin.clone(), serde_json::json!({}))
+            .await
+            .unwrap();
+
+        let failed_at = Instant::now();
+        assert_eq!(
+            registry.handle_failure("restartable", PluginErrorKind::Recoverable, failed_at).await,
+            RestartOutcome::NotRestarted
+        );
+        assert_eq!(
+            registry.handle_failure("restartable", PluginErrorKind::Config, failed_at).await,
+            RestartOutcome::NotRestarted
+        );
+        assert_eq!(inits(&plugin), 0);
+
+        assert_eq!(
+            registry.handle_failure("restartable", PluginErrorKind::Fatal, failed_at).await,
+            RestartOutcome::Restarted
+        );
+        assert_eq!(inits(&plugin), 1);
+
+        // A frame that failed before that restart does not restart again
+        assert_eq!(
+            registry.handle_failure("restartable", PluginErrorKind::Resource, failed_at).await,
+            RestartOutcome::Restarted
+        );
+        assert_eq!(inits(&plugin), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_restarts_are_exhausted_or_fail() {
+        let registry = PluginRegistry::with_restart_policy(RestartPolicy {
+            max_restarts: 1,
+            ..RestartPolicy::default()
+        });
+        let plugin = Arc::new(RwLock::new(RestartablePlugin::default()));
+        registry
+            .register_with_config(plugin.clone(), serde_json::json!({}))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            registry.handle_failure("restartable", PluginErrorKind::Fatal, Instant::now()).await,
+            RestartOutcome::Restarted
+        );
+        assert!(matches!(
+            registry.handle_failure("restartable", PluginErrorKind::Fatal, Instant::now()).await,
+            RestartOutcome::GaveUp(_)
+        ));
+        assert_eq!(inits(&plugin), 1);
+
+        let registry = PluginRegistry::new();
+        let plugin = Arc::new(RwLock::new(RestartablePlugin {
+            fail_init: true,
+            ..RestartablePlugin::default()
+        }));
+        registry
+            .register_with_config(plugin.clone(), serde_json::json!({}))
+            .await
+            .unwrap();
+        assert!(matches!(
+            registry.handle_failure("restartable", PluginErrorKind::Fatal, Instant::now()).await,
+            RestartOutcome::GaveUp(reason) if reason.contains("model file missing")
+        ));
+    }
+
+    #[tokio::test]
+    async fn does_not_restart_plugins_without_init_config() {
+        let registry = PluginRegistry::new();
+        registry
+            .register(Arc::new(RwLock::new(RestartablePlugin::default())))
+            .await
+            .unwrap();
+        assert!(matches!(
+            registry.handle_failure("restartable", PluginErrorKind::Fatal, Instant::now()).await,
+            RestartOutcome::GaveUp(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn reconfigures_and_rolls_back_failed_configs() {
+        let registry = PluginRegistry::new();
+        let plugin = Arc::new(RwLock::new(RestartablePlugin::default()));
+        registry
+            .register_with_config(plugin.clone(), serde_json::json!({ "model_path": "v1.onnx" }))
+            .await
+            .unwrap();
+
+        let v2 = serde_json::json!({ "model_path": "v2.onnx" });
+        registry.reconfigure("restartable", v2.clone()).await.unwrap();
+        assert_eq!(plugin.try_read().unwrap().config, v2);
+        assert_eq!(registry.init_config("restartable").await, Some(v2.clone()));
+
+        let broken = serde_json::json!({ "model_path": "v3.onnx", "broken": true });
+        assert!(registry.reconfigure("restartable", broken).await.is_err());
+        assert_eq!(plugin.try_read().unwrap().config, v2);
+        assert_eq!(registry.init_config("restartable").await, Some(v2));
+        assert_eq!(inits(&plugin), 2);
+    }
 }
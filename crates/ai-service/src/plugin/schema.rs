@@ -0,0 +1,202 @@
+//! Checks plugin configurations against their `config_schema()`
+//!
+//! Plugin schemas use a small JSON Schema subset: `type` (a name or a list
+//! of names), `enum`, `minimum`/`maximum`, `items`, `properties` and
+//! `required`. Other keywords (`default`, `description`, ...) are ignored.
+
+use serde_json::{Map, Value};
+
+/// Apply a JSON merge patch (RFC 7386): objects are merged key by key,
+/// `null` removes a key and anything else replaces the target value
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Top-level settings of `patch` that the schema does not declare, so typos
+/// are refused rather than silently ignored by the plugin
+pub fn unknown_settings(schema: &Value, patch: &Value) -> Vec<String> {
+    let (Some(properties), Some(patch)) = (
+        schema.get("properties").and_then(Value::as_object),
+        patch.as_object(),
+    ) else {
+        return Vec::new();
+    };
+    patch
+        .keys()
+        .filter(|key| !properties.contains_key(*key))
+        .map(|key| format!("{}: unknown setting", key))
+        .collect()
+}
+
+/// Violations of `schema` by `value`, each prefixed with the setting's path
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, "", &mut errors);
+    errors
+}
+
+fn type_matches(name: &str, value: &Value) -> bool {
+    match name {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let at = if path.is_empty() { "config" } else { path };
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|name| type_matches(name, value)) {
+        errors.push(format!("{}: expected {}", at, types.join(" or ")));
+        return;
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            errors.push(format!("{}: must be one of {}", at, allowed.join(", ")));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                errors.push(format!("{}: must be at least {}", at, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                errors.push(format!("{}: must be at most {}", at, maximum));
+            }
+        }
+    }
+
+    if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in values.iter().enumerate() {
+            check(items, item, &format!("{}[{}]", at, index), errors);
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    errors.push(format!("{}: missing required setting", join(path, key)));
+                }
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (key, property) in properties {
+                if let Some(value) = object.get(key) {
+                    check(property, value, &join(path, key), errors);
+                }
+            }
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["model_path"],
+            "properties": {
+                "model_path": {"type": "string"},
+                "confidence_threshold": {"type": "number", "minimum": 0.0, "maximum": 1.0},
+                "max_detections": {"type": "integer", "minimum": 1},
+                "classes": {"type": "array", "items": {"type": "string"}},
+                "execution_provider": {"type": "string", "enum": ["CPU", "CUDA", "TensorRT"]},
+                "nms": {
+                    "type": "object",
+                    "properties": {"method": {"type": "string", "enum": ["hard", "soft"]}}
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn merges_patches_into_the_current_config() {
+        let mut config = json!({"model_path": "a.onnx", "confidence_threshold": 0.5, "nms": {"method": "hard", "class_agnostic": true}});
+        merge_patch(
+            &mut config,
+            &json!({"confidence_threshold": 0.7, "nms": {"method": "soft"}, "model_path": null}),
+        );
+        assert_eq!(
+            config,
+            json!({"confidence_threshold": 0.7, "nms": {"method": "soft", "class_agnostic": true}})
+        );
+    }
+
+    #[test]
+    fn reports_every_violation_with_its_path() {
+        let valid = json!({"model_path": "a.onnx", "confidence_threshold": 0.4, "classes": ["car"], "nms": {"method": "soft"}});
+        assert!(validate(&schema(), &valid).is_empty());
+
+        let invalid = json!({
+            "confidence_threshold": 1.5,
+            "max_detections": 2.5,
+            "classes": ["car", 3],
+            "execution_provider": "ROCm",
+            "nms": {"method": "fast"}
+        });
+        let mut errors = validate(&schema(), &invalid);
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                "classes[1]: expected string",
+                "confidence_threshold: must be at most 1",
+                "execution_provider: must be one of \"CPU\", \"CUDA\", \"TensorRT\"",
+                "max_detections: expected integer",
+                "model_path: missing required setting",
+                "nms.method: must be one of \"hard\", \"soft\"",
+            ]
+        );
+        assert_eq!(validate(&schema(), &json!([])), vec!["config: expected object"]);
+    }
+
+    #[test]
+    fn flags_undeclared_settings() {
+        let patch = json!({"confidence_treshold": 0.7, "max_detections": 10});
+        assert_eq!(unknown_settings(&schema(), &patch), vec!["confidence_treshold: unknown setting"]);
+        assert!(unknown_settings(&json!({"type": "object"}), &patch).is_empty());
+    }
+}
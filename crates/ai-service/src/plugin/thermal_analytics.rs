@@ -0,0 +1,526 @@
+/// Radiometric thermal analytics plugin
+///
+/// Reads the per-pixel temperatures of radiometric thermal frames and checks
+/// the regions configured for a task (e.g. "bearing housing must stay below
+/// 80 °C", "cold store must stay below -18 °C") against their limits:
+/// 1. Every region reports its hottest, coldest and mean temperature as a
+///    `temperature` detection, with the hottest pixel's position
+/// 2. A region with at least `min_alarm_pixels` pixels beyond a limit adds a
+///    `temperature_alarm` detection boxing those pixels
+///
+/// Frames are `gray16le` (raw little-endian 16-bit values, row by row) or
+/// 16-bit grayscale PNG/TIFF; raw values are converted with
+/// `celsius = raw * raw_scale + raw_offset` (centi-Kelvin by default, the
+/// usual linear radiometric output). 8-bit frames carry no temperatures and
+/// are rejected. Alarms carry `metadata.violation = true`, which the AI
+/// service raises as `ai_detection` alerts (see `crate::alerts`).
+use super::{AiPlugin, PluginError};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
+use serde::{Deserialize, Serialize};
+
+/// Area of the frame with temperature limits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalRegion {
+    /// Region identifier
+    pub id: String,
+
+    /// Region name
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Region area in frame pixels; the whole frame when absent
+    #[serde(default)]
+    pub bbox: Option<BoundingBox>,
+
+    /// Alarm when the region gets hotter than this (°C)
+    #[serde(default)]
+    pub max_celsius: Option<f32>,
+
+    /// Alarm when the region gets colder than this (°C)
+    #[serde(default)]
+    pub min_celsius: Option<f32>,
+}
+
+/// Per-task settings, read from the task's `model_config`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ThermalTaskConfig {
+    #[serde(default)]
+    regions: Vec<ThermalRegion>,
+    #[serde(default)]
+    raw_scale: Option<f32>,
+    #[serde(default)]
+    raw_offset: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalConfig {
+    /// Degrees per raw unit
+    #[serde(default = "default_raw_scale")]
+    pub raw_scale: f32,
+
+    /// Temperature of raw value 0 (°C)
+    #[serde(default = "default_raw_offset")]
+    pub raw_offset: f32,
+
+    /// Pixels beyond a limit before a region alarms, so single hot pixels
+    /// (sensor noise, reflections) do not
+    #[serde(default = "default_min_alarm_pixels")]
+    pub min_alarm_pixels: usize,
+
+    /// Regions for tasks that do not set their own
+    #[serde(default)]
+    pub regions: Vec<ThermalRegion>,
+}
+
+fn default_raw_scale() -> f32 {
+    0.01
+}
+
+fn default_raw_offset() -> f32 {
+    -273.15
+}
+
+fn default_min_alarm_pixels() -> usize {
+    4
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self {
+            raw_scale: default_raw_scale(),
+            raw_offset: default_raw_offset(),
+            min_alarm_pixels: default_min_alarm_pixels(),
+            regions: Vec::new(),
+        }
+    }
+}
+
+/// Temperatures of a frame in °C, row by row
+#[derive(Debug, Clone)]
+struct ThermalImage {
+    width: u32,
+    height: u32,
+    celsius: Vec<f32>,
+}
+
+impl ThermalImage {
+    /// Decode the raw radiometric values of a frame
+    fn decode(frame: &VideoFrame, scale: f32, offset: f32) -> Result<Self> {
+        let data = base64::prelude::BASE64_STANDARD
+            .decode(&frame.data)
+            .context(PluginError::recoverable("Failed to decode base64 frame"))?;
+        let (width, height, raw) = match frame.format.as_str() {
+            "gray16le" => {
+                let expected = frame.width as usize * frame.height as usize * 2;
+                if frame.width == 0 || frame.height == 0 || data.len() != expected {
+                    return Err(PluginError::recoverable(format!(
+                        "gray16le frame of {}x{} needs {} bytes, got {}",
+                        frame.width,
+                        frame.height,
+                        expected,
+                        data.len()
+                    ))
+                    .into());
+                }
+                let raw = data
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                (frame.width, frame.height, raw)
+            }
+            _ => {
+                let img = image::load_from_memory(&data)
+                    .context(PluginError::recoverable("Failed to load image"))?;
+                if !matches!(img.color(), image::ColorType::L16 | image::ColorType::La16) {
+                    return Err(PluginError::recoverable(
+                        "frame is not radiometric (16-bit grayscale expected)",
+                    )
+                    .into());
+                }
+                let luma = img.to_luma16();
+                (luma.width(), luma.height(), luma.into_raw())
+            }
+        };
+        Ok(Self {
+            width,
+            height,
+            celsius: raw.into_iter().map(|v| v as f32 * scale + offset).collect(),
+        })
+    }
+
+    /// Region area clamped to the frame; `None` when it lies outside
+    fn clamp(&self, bbox: Option<&BoundingBox>) -> Option<BoundingBox> {
+        let Some(bbox) = bbox else {
+            return Some(BoundingBox {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+            });
+        };
+        let x = bbox.x.min(self.width);
+        let y = bbox.y.min(self.height);
+        let width = bbox.width.min(self.width - x);
+        let height = bbox.height.min(self.height - y);
+        (width > 0 && height > 0).then_some(BoundingBox { x, y, width, height })
+    }
+}
+
+/// Box around the pixels of `area` for which `beyond` holds, and how many
+/// there are
+fn pixels_beyond(image: &ThermalImage, area: &BoundingBox, beyond: impl Fn(f32) -> bool) -> (usize, BoundingBox) {
+    let (mut count, mut x0, mut y0, mut x1, mut y1) = (0, u32::MAX, u32::MAX, 0, 0);
+    for y in area.y..area.y + area.height {
+        for x in area.x..area.x + area.width {
+            if beyond(image.celsius[(y * image.width + x) as usize]) {
+                count += 1;
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x);
+                y1 = y1.max(y);
+            }
+        }
+    }
+    let bbox = if count == 0 {
+        area.clone()
+    } else {
+        BoundingBox {
+            x: x0,
+            y: y0,
+            width: x1 - x0 + 1,
+            height: y1 - y0 + 1,
+        }
+    };
+    (count, bbox)
+}
+
+/// Region temperatures and alarms of a frame
+fn analyze_regions(image: &ThermalImage, regions: &[ThermalRegion], min_alarm_pixels: usize) -> Vec<Detection> {
+    let mut output = Vec::new();
+    let mut alarms = Vec::new();
+    for region in regions {
+        let Some(area) = image.clamp(region.bbox.as_ref()) else {
+            continue;
+        };
+        let name = region.name.as_deref().unwrap_or(&region.id);
+
+        let (mut max, mut min, mut sum, mut hotspot) = (f32::MIN, f32::MAX, 0.0f64, (area.x, area.y));
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                let t = image.celsius[(y * image.width + x) as usize];
+                if t > max {
+                    max = t;
+                    hotspot = (x, y);
+                }
+                min = min.min(t);
+                sum += t as f64;
+            }
+        }
+        let mean = sum / (area.width as f64 * area.height as f64);
+
+        let limits = [
+            ("above", region.max_celsius, max),
+            ("below", region.min_celsius, min),
+        ];
+        for (condition, limit, extreme) in limits {
+            let Some(limit) = limit else {
+                continue;
+            };
+            let (pixels, bbox) = if condition == "above" {
+                pixels_beyond(image, &area, |t| t > limit)
+            } else {
+                pixels_beyond(image, &area, |t| t < limit)
+            };
+            if pixels < min_alarm_pixels.max(1) {
+                continue;
+            }
+            alarms.push(Detection {
+                class: "temperature_alarm".to_string(),
+                confidence: 1.0,
+                bbox,
+                metadata: Some(serde_json::json!({
+                    "violation": true,
+                    "zone_id": region.id,
+                    "zone_name": name,
+                    "condition": condition,
+                    "threshold_celsius": limit,
+                    "temperature_celsius": extreme,
+                    "pixels": pixels,
+                })),
+            });
+        }
+
+        output.push(Detection {
+            class: "temperature".to_string(),
+            confidence: 1.0,
+            bbox: area,
+            metadata: Some(serde_json::json!({
+                "zone_id": region.id,
+                "zone_name": name,
+                "max_celsius": max,
+                "min_celsius": min,
+                "mean_celsius": mean,
+                "hotspot": { "x": hotspot.0, "y": hotspot.1 },
+            })),
+        });
+    }
+    output.extend(alarms);
+    output
+}
+
+/// Radiometric thermal analytics plugin
+pub struct ThermalAnalyticsPlugin {
+    config: ThermalConfig,
+}
+
+impl ThermalAnalyticsPlugin {
+    pub fn new() -> Self {
+        Self {
+            config: ThermalConfig::default(),
+        }
+    }
+}
+
+impl Default for ThermalAnalyticsPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl AiPlugin for ThermalAnalyticsPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        "thermal_analytics"
+    }
+
+    fn name(&self) -> &'static str {
+        "Thermal Analytics"
+    }
+
+    fn description(&self) -> &'static str {
+        "Measures temperatures in regions of radiometric thermal frames and alarms when they leave their limits"
+    }
+
+    fn version(&self) -> &'static str {
+        "1.0.0"
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "regions": {
+                    "type": "array",
+                    "description": "Measured regions (task model_config, or plugin default)",
+                    "items": {
+                        "type": "object",
+                        "required": ["id"],
+                        "properties": {
+                            "id": {"type": "string"},
+                            "name": {"type": "string"},
+                            "bbox": {
+                                "type": "object",
+                                "description": "Region area in pixels; whole frame when absent",
+                                "properties": {
+                                    "x": {"type": "integer"},
+                                    "y": {"type": "integer"},
+                                    "width": {"type": "integer"},
+                                    "height": {"type": "integer"}
+                                }
+                            },
+                            "max_celsius": {"type": "number"},
+                            "min_celsius": {"type": "number"}
+                        }
+                    }
+                },
+                "raw_scale": {
+                    "type": "number",
+                    "description": "Degrees per raw unit",
+                    "default": 0.01
+                },
+                "raw_offset": {
+                    "type": "number",
+                    "description": "Temperature of raw value 0 in °C",
+                    "default": -273.15
+                },
+                "min_alarm_pixels": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "default": 4
+                }
+            }
+        }))
+    }
+
+    fn supported_formats(&self) -> Vec<String> {
+        vec!["gray16le".to_string(), "png".to_string(), "tiff".to_string()]
+    }
+
+    async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+        self.config = if config.is_null() {
+            ThermalConfig::default()
+        } else {
+            serde_json::from_value(config).context(PluginError::config("Invalid thermal analytics config"))?
+        };
+        tracing::info!(
+            raw_scale = self.config.raw_scale,
+            raw_offset = self.config.raw_offset,
+            regions = self.config.regions.len(),
+            "Thermal analytics plugin initialized"
+        );
+        Ok(())
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        self.process_task_frame(frame, &serde_json::Value::Null).await
+    }
+
+    async fn process_task_frame(&self, frame: &VideoFrame, task_config: &serde_json::Value) -> Result<AiResult> {
+        let start = std::time::Instant::now();
+        let task_config: ThermalTaskConfig = if task_config.is_null() {
+            ThermalTaskConfig::default()
+        } else {
+            serde_json::from_value(task_config.clone())
+                .context(PluginError::config("Invalid thermal analytics task config"))?
+        };
+        let regions = if task_config.regions.is_empty() {
+            &self.config.regions
+        } else {
+            &task_config.regions
+        };
+        let image = ThermalImage::decode(
+            frame,
+            task_config.raw_scale.unwrap_or(self.config.raw_scale),
+            task_config.raw_offset.unwrap_or(self.config.raw_offset),
+        )?;
+
+        // Without regions the whole frame is measured
+        let whole_frame = [ThermalRegion {
+            id: "frame".to_string(),
+            name: None,
+            bbox: None,
+            max_celsius: None,
+            min_celsius: None,
+        }];
+        let regions = if regions.is_empty() { &whole_frame[..] } else { &regions[..] };
+        let detections = analyze_regions(&image, regions, self.config.min_alarm_pixels);
+        let alarms = detections.iter().filter(|d| d.class == "temperature_alarm").count();
+
+        Ok(AiResult {
+            task_id: frame.source_id.clone(),
+            timestamp: frame.timestamp,
+            plugin_type: self.id().to_string(),
+            confidence: None,
+            detections,
+            processing_time_ms: Some(start.elapsed().as_millis() as u64),
+            metadata: Some(serde_json::json!({
+                "regions": regions.len(),
+                "alarms": alarms,
+            })),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 20x10 frame at 20 °C with a 3x2 spot at 95 °C in its top-left
+    /// quarter, in centi-Kelvin
+    fn frame() -> VideoFrame {
+        let mut data = Vec::new();
+        for y in 0..10u32 {
+            for x in 0..20u32 {
+                let celsius = if (2..5).contains(&x) && (3..5).contains(&y) { 95.0 } else { 20.0 };
+                let raw = ((celsius + 273.15f32) * 100.0).round() as u16;
+                data.extend_from_slice(&raw.to_le_bytes());
+            }
+        }
+        VideoFrame {
+            source_id: "thermal-1".into(),
+            timestamp: 0,
+            sequence: 0,
+            width: 20,
+            height: 10,
+            format: "gray16le".into(),
+            data: base64::prelude::BASE64_STANDARD.encode(data),
+            audio: None,
+        }
+    }
+
+    fn region(id: &str, bbox: Option<BoundingBox>, max_celsius: Option<f32>, min_celsius: Option<f32>) -> ThermalRegion {
+        ThermalRegion {
+            id: id.into(),
+            name: None,
+            bbox,
+            max_celsius,
+            min_celsius,
+        }
+    }
+
+    #[test]
+    fn test_raw_frames_decode_to_celsius() {
+        let image = ThermalImage::decode(&frame(), 0.01, -273.15).unwrap();
+        assert_eq!(image.celsius.len(), 200);
+        assert!((image.celsius[0] - 20.0).abs() < 0.01);
+        assert!((image.celsius[3 * 20 + 2] - 95.0).abs() < 0.01);
+
+        let mut short = frame();
+        short.height = 11;
+        assert!(ThermalImage::decode(&short, 0.01, -273.15).is_err());
+    }
+
+    #[test]
+    fn test_regions_alarm_beyond_their_limits() {
+        let image = ThermalImage::decode(&frame(), 0.01, -273.15).unwrap();
+        let left = BoundingBox { x: 0, y: 0, width: 10, height: 10 };
+        let right = BoundingBox { x: 10, y: 0, width: 10, height: 10 };
+        let regions = [
+            region("motor", Some(left), Some(80.0), None),
+            region("cold-store", Some(right), Some(80.0), Some(25.0)),
+        ];
+        let detections = analyze_regions(&image, &regions, 4);
+
+        let motor = &detections[0].metadata.as_ref().unwrap();
+        assert!((motor["max_celsius"].as_f64().unwrap() - 95.0).abs() < 0.01);
+        assert_eq!(motor["hotspot"], serde_json::json!({"x": 2, "y": 3}));
+
+        let alarms: Vec<&Detection> = detections.iter().filter(|d| d.class == "temperature_alarm").collect();
+        assert_eq!(alarms.len(), 2);
+        let hot = alarms[0].metadata.as_ref().unwrap();
+        assert_eq!(hot["zone_id"], "motor");
+        assert_eq!(hot["condition"], "above");
+        assert_eq!(hot["pixels"], 6);
+        assert_eq!(alarms[0].bbox, BoundingBox { x: 2, y: 3, width: 3, height: 2 });
+        let cold = alarms[1].metadata.as_ref().unwrap();
+        assert_eq!(cold["zone_id"], "cold-store");
+        assert_eq!(cold["condition"], "below");
+        assert_eq!(cold["violation"], true);
+
+        // A spot smaller than the alarm minimum is measured but not raised
+        let detections = analyze_regions(&image, &regions[..1], 10);
+        assert!(detections.iter().all(|d| d.class == "temperature"));
+    }
+
+    #[test]
+    fn test_eight_bit_frames_are_not_radiometric() {
+        let img = image::DynamicImage::new_luma8(4, 4);
+        let mut png = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let mut frame = frame();
+        frame.format = "png".into();
+        frame.data = base64::prelude::BASE64_STANDARD.encode(png.into_inner());
+        assert!(ThermalImage::decode(&frame, 0.01, -273.15).is_err());
+    }
+}
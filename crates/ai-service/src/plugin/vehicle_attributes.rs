@@ -0,0 +1,654 @@
+/// Vehicle attribute classification plugin
+///
+/// A secondary plugin: it runs after a detector in a task pipeline
+/// (`secondary_plugins`) and describes the vehicles the detector found:
+/// 1. Vehicle detections (car, truck, bus, ...) get make, color and type
+/// 2. License plates from the LPR plugin get the attributes of the vehicle
+///    carrying them: the enclosing vehicle detection, or the region around
+///    the plate when the task only detects plates
+///
+/// Color comes from the pixels of the vehicle crop and type from the
+/// detector class; an optional ONNX classifier adds make and overrides
+/// both with its `make`, `color` and `type` output heads.
+use super::{AiPlugin, PluginError};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
+use image::{DynamicImage, RgbImage};
+use ndarray::{Array, IxDyn};
+use ort::{
+    execution_providers::{CPUExecutionProvider, CUDAExecutionProvider},
+    session::{builder::GraphOptimizationLevel, Session},
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VehicleAttributesConfig {
+    /// Path to the attribute classifier ONNX model (optional - without it
+    /// only color and type are reported)
+    pub model_path: Option<String>,
+
+    /// Labels of the classifier's `make` output
+    #[serde(default)]
+    pub make_labels: Vec<String>,
+
+    /// Labels of the classifier's `color` output
+    #[serde(default)]
+    pub color_labels: Vec<String>,
+
+    /// Labels of the classifier's `type` output
+    #[serde(default)]
+    pub type_labels: Vec<String>,
+
+    /// Classifier input size (width and height)
+    #[serde(default = "default_input_size")]
+    pub input_size: u32,
+
+    /// Minimum classifier confidence for an attribute to be reported
+    #[serde(default = "default_confidence")]
+    pub confidence_threshold: f32,
+
+    /// Detector classes treated as vehicles
+    #[serde(default = "default_vehicle_classes")]
+    pub vehicle_classes: Vec<String>,
+
+    /// Describe the vehicle around license plates that have no enclosing
+    /// vehicle detection
+    #[serde(default = "default_plate_context")]
+    pub plate_context: bool,
+
+    /// Execution provider preference (CPU, CUDA)
+    #[serde(default = "default_execution_provider")]
+    pub execution_provider: String,
+
+    /// GPU device ID (0, 1, 2, etc.)
+    #[serde(default)]
+    pub device_id: i32,
+}
+
+fn default_input_size() -> u32 {
+    224
+}
+
+fn default_confidence() -> f32 {
+    0.5
+}
+
+fn default_vehicle_classes() -> Vec<String> {
+    ["car", "truck", "bus", "van", "motorcycle", "motorbike", "vehicle"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect()
+}
+
+fn default_plate_context() -> bool {
+    true
+}
+
+fn default_execution_provider() -> String {
+    "CPU".to_string()
+}
+
+impl Default for VehicleAttributesConfig {
+    fn default() -> Self {
+        Self {
+            model_path: None,
+            make_labels: Vec::new(),
+            color_labels: Vec::new(),
+            type_labels: Vec::new(),
+            input_size: default_input_size(),
+            confidence_threshold: default_confidence(),
+            vehicle_classes: default_vehicle_classes(),
+            plate_context: default_plate_context(),
+            execution_provider: default_execution_provider(),
+            device_id: 0,
+        }
+    }
+}
+
+/// Attributes attached under `metadata.vehicle` of a detection
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VehicleAttributes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make_confidence: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_confidence: Option<f32>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub vehicle_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_confidence: Option<f32>,
+}
+
+impl VehicleAttributes {
+    /// One-line description, e.g. "white Toyota car"
+    pub fn describe(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.color, &self.make, &self.vehicle_type]
+            .into_iter()
+            .filter_map(|p| p.as_deref())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+/// Vehicle attribute classification plugin
+pub struct VehicleAttributesPlugin {
+    config: VehicleAttributesConfig,
+    session: Option<Arc<Mutex<Session>>>,
+}
+
+impl VehicleAttributesPlugin {
+    pub fn new() -> Self {
+        Self {
+            config: VehicleAttributesConfig::default(),
+            session: None,
+        }
+    }
+
+    fn is_vehicle(&self, class: &str) -> bool {
+        self.config
+            .vehicle_classes
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(class))
+    }
+
+    /// Classify one vehicle crop; `detected_class` is the detector's label
+    fn classify(&self, crop: &DynamicImage, detected_class: Option<&str>) -> Result<VehicleAttributes> {
+        let rgb = crop.to_rgb8();
+        let (color, color_confidence) = dominant_color(&rgb);
+        let mut attributes = VehicleAttributes {
+            color: Some(color.to_string()),
+            color_confidence: Some(color_confidence),
+            vehicle_type: detected_class
+                .filter(|c| !c.eq_ignore_ascii_case("vehicle"))
+                .map(|c| c.to_ascii_lowercase()),
+            type_confidence: None,
+            ..Default::default()
+        };
+
+        if let Some(session) = &self.session {
+            let outputs = self.run_classifier(session, crop)?;
+            let threshold = self.config.confidence_threshold;
+            if let Some((make, confidence)) = top_label(outputs.get("make"), &self.config.make_labels) {
+                if confidence >= threshold {
+                    attributes.make = Some(make);
+                    attributes.make_confidence = Some(confidence);
+                }
+            }
+            if let Some((color, confidence)) = top_label(outputs.get("color"), &self.config.color_labels) {
+                if confidence >= threshold {
+                    attributes.color = Some(color);
+                    attributes.color_confidence = Some(confidence);
+                }
+            }
+            if let Some((vehicle_type, confidence)) = top_label(outputs.get("type"), &self.config.type_labels) {
+                if confidence >= threshold {
+                    attributes.vehicle_type = Some(vehicle_type);
+                    attributes.type_confidence = Some(confidence);
+                }
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Run the classifier and return the scores of each output head
+    fn run_classifier(&self, session: &Mutex<Session>, crop: &DynamicImage) -> Result<HashMap<String, Vec<f32>>> {
+        let size = self.config.input_size;
+        let resized = crop.resize_exact(size, size, image::imageops::FilterType::Triangle);
+        let rgb_img = resized.to_rgb8();
+
+        // NCHW, normalized with ImageNet mean/std as classifier backbones expect
+        let mean = [0.485, 0.456, 0.406];
+        let std = [0.229, 0.224, 0.225];
+        let mut input = Array::zeros(IxDyn(&[1, 3, size as usize, size as usize]));
+        for (x, y, pixel) in rgb_img.enumerate_pixels() {
+            for c in 0..3 {
+                input[[0, c, y as usize, x as usize]] = (pixel[c] as f32 / 255.0 - mean[c]) / std[c];
+            }
+        }
+        let input_tensor = Value::from_array(input)?;
+
+        let mut session = session
+            .lock()
+            .map_err(|e| PluginError::poisoned("classifier session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
+
+        let mut heads = HashMap::new();
+        for head in ["make", "color", "type"] {
+            if let Some(value) = outputs.get(head) {
+                let (_, data) = value.try_extract_tensor::<f32>()?;
+                heads.insert(head.to_string(), softmax(data));
+            }
+        }
+        Ok(heads)
+    }
+
+    fn create_session(&self, model_path: &str) -> Result<Session> {
+        super::require_model_file(model_path)?;
+        if self.config.execution_provider.eq_ignore_ascii_case("CUDA") {
+            let result = Session::builder()
+                .context("Failed to create session builder")?
+                .with_optimization_level(GraphOptimizationLevel::Level3)
+                .context("Failed to set optimization level")?
+                .with_execution_providers([
+                    CUDAExecutionProvider::default()
+                        .with_device_id(self.config.device_id)
+                        .build(),
+                    CPUExecutionProvider::default().build(),
+                ])
+                .context("Failed to set execution providers")?
+                .commit_from_file(model_path);
+            match result {
+                Ok(session) => return Ok(session),
+                Err(e) => tracing::warn!("CUDA failed for {}, falling back to CPU: {}", model_path, e),
+            }
+        }
+
+        Session::builder()
+            .context("Failed to create session builder")?
+            .with_optimization_level(GraphOptimizationLevel::Level3)
+            .context("Failed to set optimization level")?
+            .commit_from_file(model_path)
+            .context(PluginError::config("Failed to load model from file"))
+    }
+}
+
+impl Default for VehicleAttributesPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Region a vehicle carrying `plate` likely covers: plates sit low and
+/// centered, on a vehicle about four plate widths wide
+fn plate_context_region(plate: &BoundingBox, frame_width: u32, frame_height: u32) -> BoundingBox {
+    let width = plate.width.saturating_mul(4).min(frame_width);
+    let height = plate.width.saturating_mul(3).min(frame_height);
+    let center_x = plate.x + plate.width / 2;
+    let bottom = (plate.y + plate.height * 2).min(frame_height);
+
+    let x = center_x.saturating_sub(width / 2).min(frame_width - width);
+    let y = bottom.saturating_sub(height);
+    BoundingBox { x, y, width, height }
+}
+
+/// Whether the center of `inner` lies within `outer`
+fn contains_center(outer: &BoundingBox, inner: &BoundingBox) -> bool {
+    let (cx, cy) = (inner.x + inner.width / 2, inner.y + inner.height / 2);
+    cx >= outer.x && cx < outer.x + outer.width && cy >= outer.y && cy < outer.y + outer.height
+}
+
+/// Named body color of a vehicle crop and the share of pixels that have it.
+/// Only the middle of the crop is sampled, which is mostly body panels
+/// rather than road, windows or background.
+fn dominant_color(img: &RgbImage) -> (&'static str, f32) {
+    let (w, h) = img.dimensions();
+    let (x0, x1) = (w / 5, w - w / 5);
+    let (y0, y1) = (h / 4, h - h / 4);
+
+    let mut counts: HashMap<&'static str, u32> = HashMap::new();
+    let mut total = 0u32;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let p = img.get_pixel(x, y);
+            *counts.entry(color_name(p[0], p[1], p[2])).or_insert(0) += 1;
+            total += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .max_by_key(|(name, count)| (*count, *name))
+        .map(|(name, count)| (name, count as f32 / total.max(1) as f32))
+        .unwrap_or(("unknown", 0.0))
+}
+
+/// Color name of one pixel, by HSV buckets
+fn color_name(r: u8, g: u8, b: u8) -> &'static str {
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let value = max;
+    let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+
+    if value < 0.2 {
+        return "black";
+    }
+    if saturation < 0.2 {
+        return match value {
+            v if v > 0.8 => "white",
+            v if v > 0.55 => "silver",
+            _ => "gray",
+        };
+    }
+
+    let delta = max - min;
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    match hue {
+        h if !(15.0..330.0).contains(&h) => "red",
+        h if h < 45.0 && value < 0.6 => "brown",
+        h if h < 45.0 => "orange",
+        h if h < 70.0 => "yellow",
+        h if h < 165.0 => "green",
+        h if h < 260.0 => "blue",
+        _ => "purple",
+    }
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let exp: Vec<f32> = logits.iter().map(|v| (v - max).exp()).collect();
+    let sum: f32 = exp.iter().sum();
+    exp.into_iter().map(|v| v / sum).collect()
+}
+
+/// Highest scoring label of a classifier head
+fn top_label(scores: Option<&Vec<f32>>, labels: &[String]) -> Option<(String, f32)> {
+    let (index, score) = scores?
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    labels.get(index).map(|label| (label.clone(), *score))
+}
+
+fn set_vehicle(detection: &mut Detection, attributes: &VehicleAttributes) {
+    let metadata = detection
+        .metadata
+        .get_or_insert_with(|| serde_json::json!({}));
+    if let Some(object) = metadata.as_object_mut() {
+        object.insert("vehicle".to_string(), serde_json::json!(attributes));
+        if let Some(description) = attributes.describe() {
+            object.insert("vehicle_description".to_string(), serde_json::json!(description));
+        }
+    }
+}
+
+fn decode_frame(frame: &VideoFrame) -> Result<DynamicImage> {
+    let image_data = base64::prelude::BASE64_STANDARD
+        .decode(&frame.data)
+        .context(PluginError::recoverable("Failed to decode base64 image"))?;
+    image::load_from_memory(&image_data).context(PluginError::recoverable("Failed to load image"))
+}
+
+#[async_trait]
+impl AiPlugin for VehicleAttributesPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        "vehicle_attributes"
+    }
+
+    fn name(&self) -> &'static str {
+        "Vehicle Attributes"
+    }
+
+    fn description(&self) -> &'static str {
+        "Secondary classifier adding make, color and type to vehicle and license plate detections"
+    }
+
+    fn version(&self) -> &'static str {
+        "1.0.0"
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "model_path": {
+                    "type": "string",
+                    "description": "Path to the attribute classifier ONNX model with make/color/type outputs (optional)"
+                },
+                "make_labels": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Labels of the make output"
+                },
+                "color_labels": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Labels of the color output"
+                },
+                "type_labels": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Labels of the type output"
+                },
+                "confidence_threshold": {
+                    "type": "number",
+                    "minimum": 0.0,
+                    "maximum": 1.0,
+                    "default": 0.5,
+                    "description": "Minimum classifier confidence for an attribute"
+                },
+                "vehicle_classes": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Detector classes treated as vehicles"
+                },
+                "plate_context": {
+                    "type": "boolean",
+                    "default": true,
+                    "description": "Describe the vehicle around license plates without a vehicle detection"
+                }
+            }
+        }))
+    }
+
+    fn is_secondary(&self) -> bool {
+        true
+    }
+
+    async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+        self.config = serde_json::from_value(config)
+            .context(PluginError::config("Invalid vehicle attributes config"))?;
+
+        if let Some(model_path) = self.config.model_path.clone() {
+            let session = self.create_session(&model_path)?;
+            self.session = Some(Arc::new(Mutex::new(session)));
+            tracing::info!("Vehicle attribute classifier loaded from {}", model_path);
+        }
+        Ok(())
+    }
+
+    /// Classify a frame holding a single vehicle, e.g. a crop
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        let start = std::time::Instant::now();
+        let img = decode_frame(frame)?;
+        let attributes = self.classify(&img, None)?;
+
+        let mut detection = Detection {
+            class: "vehicle".to_string(),
+            confidence: attributes.color_confidence.unwrap_or(0.0),
+            bbox: BoundingBox {
+                x: 0,
+                y: 0,
+                width: img.width(),
+                height: img.height(),
+            },
+            metadata: None,
+        };
+        set_vehicle(&mut detection, &attributes);
+
+        Ok(AiResult {
+            task_id: frame.source_id.clone(),
+            timestamp: frame.timestamp,
+            plugin_type: self.id().to_string(),
+            detections: vec![detection],
+            confidence: attributes.color_confidence,
+            processing_time_ms: Some(start.elapsed().as_millis() as u64),
+            metadata: None,
+        })
+    }
+
+    async fn enrich(&self, frame: &VideoFrame, result: &mut AiResult) -> Result<()> {
+        let wants_plates = self.config.plate_context;
+        let has_work = result
+            .detections
+            .iter()
+            .any(|d| self.is_vehicle(&d.class) || (wants_plates && d.class == "license_plate"));
+        if !has_work {
+            return Ok(());
+        }
+
+        let img = decode_frame(frame)?;
+        let (frame_width, frame_height) = (img.width(), img.height());
+        let crop = |bbox: &BoundingBox| {
+            let x = bbox.x.min(frame_width.saturating_sub(1));
+            let y = bbox.y.min(frame_height.saturating_sub(1));
+            let width = bbox.width.min(frame_width - x).max(1);
+            let height = bbox.height.min(frame_height - y).max(1);
+            img.crop_imm(x, y, width, height)
+        };
+
+        // Vehicles the primary plugin found
+        let mut vehicles: Vec<(BoundingBox, VehicleAttributes)> = Vec::new();
+        for detection in result.detections.iter_mut().filter(|d| self.is_vehicle(&d.class)) {
+            let attributes = self.classify(&crop(&detection.bbox), Some(&detection.class))?;
+            set_vehicle(detection, &attributes);
+            vehicles.push((detection.bbox.clone(), attributes));
+        }
+
+        // Plates take the vehicle they sit on, or the region around them
+        if wants_plates {
+            for plate in result.detections.iter_mut().filter(|d| d.class == "license_plate") {
+                let attributes = match vehicles.iter().find(|(bbox, _)| contains_center(bbox, &plate.bbox)) {
+                    Some((_, attributes)) => attributes.clone(),
+                    None => {
+                        let region = plate_context_region(&plate.bbox, frame_width, frame_height);
+                        self.classify(&crop(&region), None)?
+                    }
+                };
+                set_vehicle(plate, &attributes);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(self.config.model_path.is_none() || self.session.is_some())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        tracing::info!("Shutting down vehicle attributes plugin");
+        self.session = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn encode(img: RgbImage) -> String {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        base64::prelude::BASE64_STANDARD.encode(bytes.into_inner())
+    }
+
+    fn detection(class: &str, x: u32, y: u32, width: u32, height: u32) -> Detection {
+        Detection {
+            class: class.to_string(),
+            confidence: 0.9,
+            bbox: BoundingBox { x, y, width, height },
+            metadata: Some(serde_json::json!({"plate_number": "AB123"})),
+        }
+    }
+
+    #[test]
+    fn test_color_names() {
+        assert_eq!(color_name(250, 250, 250), "white");
+        assert_eq!(color_name(10, 10, 12), "black");
+        assert_eq!(color_name(170, 170, 175), "silver");
+        assert_eq!(color_name(200, 20, 30), "red");
+        assert_eq!(color_name(20, 40, 200), "blue");
+        assert_eq!(color_name(120, 70, 20), "brown");
+
+        let img = RgbImage::from_pixel(40, 20, Rgb([200, 20, 30]));
+        assert_eq!(dominant_color(&img), ("red", 1.0));
+    }
+
+    #[test]
+    fn test_plate_context_region_stays_in_frame() {
+        let plate = BoundingBox { x: 300, y: 400, width: 80, height: 20 };
+        let region = plate_context_region(&plate, 640, 480);
+        assert_eq!((region.width, region.height), (320, 240));
+        assert_eq!(region.x, 180);
+        assert_eq!(region.y + region.height, 440);
+
+        let corner = BoundingBox { x: 620, y: 470, width: 20, height: 10 };
+        let region = plate_context_region(&corner, 640, 480);
+        assert!(region.x + region.width <= 640 && region.y + region.height <= 480);
+    }
+
+    #[tokio::test]
+    async fn test_enrich_describes_vehicles_and_plates() {
+        // A blue car on the left, a plate on its own on the right
+        let mut img = RgbImage::from_pixel(200, 100, Rgb([128, 128, 128]));
+        for x in 0..100 {
+            for y in 0..100 {
+                img.put_pixel(x, y, Rgb([20, 40, 200]));
+            }
+        }
+        let frame = VideoFrame {
+            source_id: "cam-1".into(),
+            timestamp: 0,
+            sequence: 1,
+            width: 200,
+            height: 100,
+            format: "png".into(),
+            data: encode(img),
+            audio: None,
+        };
+        let mut result = AiResult {
+            task_id: "task-1".into(),
+            timestamp: 0,
+            plugin_type: "lpr".into(),
+            detections: vec![
+                detection("car", 0, 0, 100, 100),
+                detection("license_plate", 40, 80, 20, 8),
+                detection("license_plate", 150, 80, 20, 8),
+            ],
+            confidence: None,
+            processing_time_ms: None,
+            metadata: None,
+        };
+
+        VehicleAttributesPlugin::new().enrich(&frame, &mut result).await.unwrap();
+
+        let vehicle = |i: usize| result.detections[i].metadata.as_ref().unwrap()["vehicle"].clone();
+        assert_eq!(vehicle(0)["color"], "blue");
+        assert_eq!(vehicle(0)["type"], "car");
+        assert_eq!(vehicle(1), vehicle(0));
+        assert_eq!(vehicle(2)["color"], "gray");
+        assert!(vehicle(2).get("type").is_none());
+        assert_eq!(result.detections[1].metadata.as_ref().unwrap()["plate_number"], "AB123");
+        assert_eq!(result.detections[1].metadata.as_ref().unwrap()["vehicle_description"], "blue car");
+    }
+}
@@ -0,0 +1,387 @@
+//! Pre- and postprocessing shared by the ONNX detection plugins.
+//!
+//! Frames are letterboxed into the model input by default: scaled to fit
+//! with their aspect ratio kept, centered and padded with gray (114, as in
+//! YOLO training), so a 16:9 frame is not squashed into a square input.
+//! [`Letterbox`] maps the model's boxes back to frame pixels. Candidate
+//! boxes then go through [`nms`]: greedy ("hard"), Gaussian soft-NMS or
+//! DIoU-NMS, per class or across classes.
+
+use common::ai_tasks::BoundingBox;
+use image::{imageops::FilterType, DynamicImage, Rgb, RgbImage};
+use ndarray::{Array, IxDyn};
+use serde::{Deserialize, Serialize};
+
+/// Gray the letterbox border is filled with
+pub const PAD_VALUE: u8 = 114;
+
+/// How frames are fitted into the model input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeMode {
+    /// Keep the aspect ratio and pad
+    #[default]
+    Letterbox,
+    /// Scale each axis independently; for models trained on stretched input
+    Stretch,
+}
+
+impl ResizeMode {
+    /// Reads `AI_RESIZE_MODE` (`letterbox` or `stretch`)
+    pub fn from_env() -> Self {
+        std::env::var("AI_RESIZE_MODE")
+            .ok()
+            .and_then(|v| serde_json::from_value(serde_json::Value::String(v.trim().to_ascii_lowercase())).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Pixel value range of the model input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// 0..1
+    Unit,
+    /// -1..1
+    Symmetric,
+}
+
+impl Normalization {
+    fn apply(self, value: u8) -> f32 {
+        match self {
+            Normalization::Unit => value as f32 / 255.0,
+            Normalization::Symmetric => value as f32 / 127.5 - 1.0,
+        }
+    }
+}
+
+/// Where a frame sits inside the model input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub pad_x: f32,
+    pub pad_y: f32,
+    /// Frame size
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Letterbox {
+    pub fn new(mode: ResizeMode, width: u32, height: u32, input_width: u32, input_height: u32) -> Self {
+        let scale_x = input_width as f32 / width.max(1) as f32;
+        let scale_y = input_height as f32 / height.max(1) as f32;
+        match mode {
+            ResizeMode::Stretch => Self {
+                scale_x,
+                scale_y,
+                pad_x: 0.0,
+                pad_y: 0.0,
+                width,
+                height,
+            },
+            ResizeMode::Letterbox => {
+                let scale = scale_x.min(scale_y);
+                Self {
+                    scale_x: scale,
+                    scale_y: scale,
+                    pad_x: (input_width as f32 - width as f32 * scale) / 2.0,
+                    pad_y: (input_height as f32 - height as f32 * scale) / 2.0,
+                    width,
+                    height,
+                }
+            }
+        }
+    }
+
+    /// Size of the scaled frame inside the input
+    fn scaled_size(&self) -> (u32, u32) {
+        (
+            ((self.width as f32 * self.scale_x).round() as u32).max(1),
+            ((self.height as f32 * self.scale_y).round() as u32).max(1),
+        )
+    }
+
+    /// A model box (center and size in input pixels) in frame pixels,
+    /// clipped to the frame
+    pub fn to_frame(&self, cx: f32, cy: f32, w: f32, h: f32) -> BoundingBox {
+        let clip = |value: f32, max: u32| value.clamp(0.0, max as f32);
+        let x1 = clip((cx - w / 2.0 - self.pad_x) / self.scale_x, self.width);
+        let y1 = clip((cy - h / 2.0 - self.pad_y) / self.scale_y, self.height);
+        let x2 = clip((cx + w / 2.0 - self.pad_x) / self.scale_x, self.width);
+        let y2 = clip((cy + h / 2.0 - self.pad_y) / self.scale_y, self.height);
+        BoundingBox {
+            x: x1 as u32,
+            y: y1 as u32,
+            width: (x2 - x1) as u32,
+            height: (y2 - y1) as u32,
+        }
+    }
+}
+
+/// Fit a frame into a `width`×`height` model input
+pub fn resize(img: &DynamicImage, width: u32, height: u32, mode: ResizeMode) -> (RgbImage, Letterbox) {
+    let letterbox = Letterbox::new(mode, img.width(), img.height(), width, height);
+    let (scaled_width, scaled_height) = letterbox.scaled_size();
+    let scaled = img.resize_exact(scaled_width, scaled_height, FilterType::Triangle).to_rgb8();
+    if scaled_width == width && scaled_height == height {
+        return (scaled, letterbox);
+    }
+    let mut canvas = RgbImage::from_pixel(width, height, Rgb([PAD_VALUE; 3]));
+    image::imageops::overlay(
+        &mut canvas,
+        &scaled,
+        letterbox.pad_x.round() as i64,
+        letterbox.pad_y.round() as i64,
+    );
+    (canvas, letterbox)
+}
+
+/// One NCHW input batch of RGB frames, with each frame's placement
+pub fn to_nchw(
+    images: &[DynamicImage],
+    width: u32,
+    height: u32,
+    mode: ResizeMode,
+    normalization: Normalization,
+) -> (Array<f32, IxDyn>, Vec<Letterbox>) {
+    let mut input = Array::zeros(IxDyn(&[images.len(), 3, height as usize, width as usize]));
+    let mut letterboxes = Vec::with_capacity(images.len());
+    for (n, img) in images.iter().enumerate() {
+        let (rgb, letterbox) = resize(img, width, height, mode);
+        for (x, y, pixel) in rgb.enumerate_pixels() {
+            for channel in 0..3 {
+                input[[n, channel, y as usize, x as usize]] = normalization.apply(pixel[channel]);
+            }
+        }
+        letterboxes.push(letterbox);
+    }
+    (input, letterboxes)
+}
+
+/// A box the model proposed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub bbox: BoundingBox,
+    pub score: f32,
+    pub class_id: usize,
+}
+
+/// Suppression method of [`nms`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NmsMethod {
+    /// Drop boxes overlapping a better one by the IoU threshold or more
+    #[default]
+    Hard,
+    /// Lower overlapping boxes' scores instead (Gaussian soft-NMS), which
+    /// keeps more of a dense crowd
+    Soft,
+    /// Hard NMS on distance-IoU: overlapping boxes with distant centers,
+    /// such as occluded neighbours, survive
+    Diou,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NmsConfig {
+    #[serde(default)]
+    pub method: NmsMethod,
+    /// Suppress overlapping boxes of different classes too
+    #[serde(default)]
+    pub class_agnostic: bool,
+    /// Gaussian sigma of soft-NMS; smaller decays overlapping scores faster
+    #[serde(default = "default_soft_sigma")]
+    pub soft_sigma: f32,
+}
+
+fn default_soft_sigma() -> f32 {
+    0.5
+}
+
+impl Default for NmsConfig {
+    fn default() -> Self {
+        Self {
+            method: NmsMethod::default(),
+            class_agnostic: false,
+            soft_sigma: default_soft_sigma(),
+        }
+    }
+}
+
+impl NmsConfig {
+    /// Reads `AI_NMS_METHOD` (`hard`, `soft` or `diou`),
+    /// `AI_NMS_CLASS_AGNOSTIC` and `AI_NMS_SOFT_SIGMA`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            method: std::env::var("AI_NMS_METHOD")
+                .ok()
+                .and_then(|v| serde_json::from_value(serde_json::Value::String(v.trim().to_ascii_lowercase())).ok())
+                .unwrap_or(defaults.method),
+            class_agnostic: std::env::var("AI_NMS_CLASS_AGNOSTIC")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.class_agnostic),
+            soft_sigma: std::env::var("AI_NMS_SOFT_SIGMA")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|sigma: &f32| *sigma > 0.0)
+                .unwrap_or(defaults.soft_sigma),
+        }
+    }
+}
+
+/// Intersection over union
+pub fn iou(a: &BoundingBox, b: &BoundingBox) -> f32 {
+    let x1 = a.x.max(b.x);
+    let y1 = a.y.max(b.y);
+    let x2 = (a.x + a.width).min(b.x + b.width);
+    let y2 = (a.y + a.height).min(b.y + b.height);
+    let intersection = if x2 > x1 && y2 > y1 { ((x2 - x1) * (y2 - y1)) as f32 } else { 0.0 };
+    let union = (a.width * a.height) as f32 + (b.width * b.height) as f32 - intersection;
+    if union > 0.0 {
+        intersection / union
+    } else {
+        0.0
+    }
+}
+
+/// IoU less the squared center distance over the squared diagonal of the
+/// smallest box enclosing both
+pub fn diou(a: &BoundingBox, b: &BoundingBox) -> f32 {
+    let center = |r: &BoundingBox| (r.x as f32 + r.width as f32 / 2.0, r.y as f32 + r.height as f32 / 2.0);
+    let (ax, ay) = center(a);
+    let (bx, by) = center(b);
+    let enclosing_w = (a.x + a.width).max(b.x + b.width) as f32 - a.x.min(b.x) as f32;
+    let enclosing_h = (a.y + a.height).max(b.y + b.height) as f32 - a.y.min(b.y) as f32;
+    let diagonal = enclosing_w * enclosing_w + enclosing_h * enclosing_h;
+    if diagonal <= 0.0 {
+        return iou(a, b);
+    }
+    iou(a, b) - ((ax - bx).powi(2) + (ay - by).powi(2)) / diagonal
+}
+
+/// Non-maximum suppression; the kept boxes come out best first. Soft-NMS
+/// drops boxes whose decayed score falls below `score_threshold`.
+pub fn nms(
+    mut candidates: Vec<Candidate>,
+    iou_threshold: f32,
+    score_threshold: f32,
+    config: &NmsConfig,
+) -> Vec<Candidate> {
+    let by_score = |a: &Candidate, b: &Candidate| b.score.total_cmp(&a.score);
+    let competes = |a: &Candidate, b: &Candidate| config.class_agnostic || a.class_id == b.class_id;
+    let mut keep = Vec::new();
+
+    candidates.sort_by(by_score);
+    while !candidates.is_empty() {
+        let best = candidates.remove(0);
+        match config.method {
+            NmsMethod::Hard => {
+                candidates.retain(|c| !competes(&best, c) || iou(&best.bbox, &c.bbox) < iou_threshold)
+            }
+            NmsMethod::Diou => {
+                candidates.retain(|c| !competes(&best, c) || diou(&best.bbox, &c.bbox) < iou_threshold)
+            }
+            NmsMethod::Soft => {
+                let sigma = config.soft_sigma.max(f32::EPSILON);
+                for candidate in candidates.iter_mut().filter(|c| competes(&best, c)) {
+                    let overlap = iou(&best.bbox, &candidate.bbox);
+                    candidate.score *= (-(overlap * overlap) / sigma).exp();
+                }
+                candidates.retain(|c| c.score >= score_threshold);
+                candidates.sort_by(by_score);
+            }
+        }
+        keep.push(best);
+    }
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbox(x: u32, y: u32, width: u32, height: u32) -> BoundingBox {
+        BoundingBox { x, y, width, height }
+    }
+
+    fn candidate(bbox: BoundingBox, score: f32, class_id: usize) -> Candidate {
+        Candidate { bbox, score, class_id }
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect_ratio() {
+        let img = DynamicImage::new_rgb8(1280, 720);
+        let (input, letterbox) = resize(&img, 640, 640, ResizeMode::Letterbox);
+        assert_eq!(input.dimensions(), (640, 640));
+        assert_eq!(letterbox.scale_x, 0.5);
+        assert_eq!(letterbox.pad_y, 140.0);
+        assert_eq!(input.get_pixel(0, 0), &Rgb([PAD_VALUE; 3]));
+        assert_eq!(input.get_pixel(320, 320), &Rgb([0, 0, 0]));
+
+        // The input's center box maps back to the frame's center, unsquashed
+        let frame_box = letterbox.to_frame(320.0, 320.0, 64.0, 64.0);
+        assert_eq!(frame_box, bbox(576, 296, 128, 128));
+
+        // Boxes reaching into the padding are clipped to the frame
+        let clipped = letterbox.to_frame(20.0, 150.0, 80.0, 40.0);
+        assert_eq!((clipped.x, clipped.y), (0, 0));
+
+        let stretched = Letterbox::new(ResizeMode::Stretch, 1280, 640, 640, 640);
+        assert_eq!(stretched.to_frame(320.0, 320.0, 64.0, 64.0), bbox(576, 288, 128, 64));
+    }
+
+    #[test]
+    fn nchw_batches_are_normalized() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])));
+        let (input, letterboxes) = to_nchw(&[img.clone(), img], 4, 4, ResizeMode::Letterbox, Normalization::Symmetric);
+        assert_eq!(input.shape(), &[2, 3, 4, 4]);
+        assert_eq!(letterboxes.len(), 2);
+        assert_eq!(input[[1, 0, 2, 2]], 1.0);
+        assert_eq!(input[[1, 1, 2, 2]], -1.0);
+    }
+
+    #[test]
+    fn iou_variants() {
+        let a = bbox(10, 10, 50, 50);
+        assert!((iou(&a, &a) - 1.0).abs() < 0.001);
+        assert_eq!(iou(&a, &bbox(100, 100, 50, 50)), 0.0);
+        let b = bbox(30, 30, 50, 50);
+        assert!(iou(&a, &b) > 0.0 && iou(&a, &b) < 1.0);
+        assert!(diou(&a, &b) < iou(&a, &b));
+    }
+
+    #[test]
+    fn hard_nms_is_per_class_unless_agnostic() {
+        let boxes = vec![
+            candidate(bbox(10, 10, 50, 50), 0.9, 0),
+            candidate(bbox(15, 15, 50, 50), 0.8, 0),
+            candidate(bbox(12, 12, 50, 50), 0.7, 1),
+        ];
+        let kept = nms(boxes.clone(), 0.45, 0.5, &NmsConfig::default());
+        assert_eq!(kept.iter().map(|c| c.score).collect::<Vec<_>>(), vec![0.9, 0.7]);
+
+        let agnostic = NmsConfig {
+            class_agnostic: true,
+            ..Default::default()
+        };
+        assert_eq!(nms(boxes, 0.45, 0.5, &agnostic).len(), 1);
+    }
+
+    #[test]
+    fn soft_nms_decays_instead_of_dropping() {
+        let boxes = vec![
+            candidate(bbox(10, 10, 50, 50), 0.9, 0),
+            candidate(bbox(20, 10, 50, 50), 0.85, 0),
+        ];
+        let soft = NmsConfig {
+            method: NmsMethod::Soft,
+            ..Default::default()
+        };
+        let kept = nms(boxes.clone(), 0.45, 0.3, &soft);
+        assert_eq!(kept.len(), 2);
+        assert!(kept[1].score < 0.85);
+        // Decayed below the score threshold
+        assert_eq!(nms(boxes, 0.45, 0.8, &soft).len(), 1);
+    }
+}
@@ -0,0 +1,543 @@
+//! Third-party analytics compiled to WebAssembly components.
+//!
+//! Partners build their logic against `wit/analytics.wit` (frame in,
+//! detections out) and drop the `.wasm` component into the plugins
+//! directory; each one is registered as an ordinary plugin under the id it
+//! reports, without recompiling the service. A `<name>.json` next to
+//! `<name>.wasm` is the plugin's configuration (`super::sidecar_config`),
+//! handed to its `init`.
+//!
+//! Components run sandboxed: they get no imports (no WASI, so no files,
+//! network or clock), their linear memory is capped, and every frame has a
+//! fuel budget so a component stuck in a loop traps instead of holding a
+//! worker. A trap leaves the instance unusable; it is dropped and the
+//! failure reported as fatal, so the registry restarts the plugin with a
+//! fresh instance.
+
+use super::{AiPlugin, PluginError};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use base64::Engine as _;
+use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wasmtime::component::{Component, Linker};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "wit/analytics.wit",
+        world: "analytics",
+    });
+}
+
+const DEFAULT_PLUGIN_DIR: &str = "plugins/wasm";
+/// Roughly a few seconds of compute; decoding a 1080p frame pixel by pixel
+/// takes a small fraction of it
+const DEFAULT_FUEL_PER_FRAME: u64 = 10_000_000_000;
+const DEFAULT_MAX_MEMORY_MB: usize = 256;
+
+/// Where components are loaded from and the sandbox they run in
+#[derive(Debug, Clone, PartialEq)]
+pub struct WasmPluginConfig {
+    pub dir: PathBuf,
+    /// Instructions (roughly) one frame may take before the call traps
+    pub fuel_per_frame: u64,
+    /// Cap on a component's linear memory
+    pub max_memory_mb: usize,
+}
+
+impl Default for WasmPluginConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(DEFAULT_PLUGIN_DIR),
+            fuel_per_frame: DEFAULT_FUEL_PER_FRAME,
+            max_memory_mb: DEFAULT_MAX_MEMORY_MB,
+        }
+    }
+}
+
+impl WasmPluginConfig {
+    /// `AI_WASM_PLUGIN_DIR`, `AI_WASM_FUEL_PER_FRAME` and
+    /// `AI_WASM_MAX_MEMORY_MB`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            dir: std::env::var("AI_WASM_PLUGIN_DIR")
+                .map(PathBuf::from)
+                .unwrap_or(defaults.dir),
+            fuel_per_frame: std::env::var("AI_WASM_FUEL_PER_FRAME")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|fuel| *fuel > 0)
+                .unwrap_or(defaults.fuel_per_frame),
+            max_memory_mb: std::env::var("AI_WASM_MAX_MEMORY_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|mb| *mb > 0)
+                .unwrap_or(defaults.max_memory_mb),
+        }
+    }
+
+    /// Engine shared by all components, with fuel metering on
+    pub fn engine(&self) -> Result<Engine> {
+        let mut config = Config::new();
+        config.wasm_component_model(true).consume_fuel(true);
+        Engine::new(&config)
+    }
+
+    /// `.wasm` files in the plugins directory, by name; none when the
+    /// directory does not exist
+    pub fn components(&self) -> Result<Vec<PathBuf>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut paths = std::fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read WASM plugin directory {}", self.dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "wasm"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// A running instance of the component
+struct Guest {
+    store: Store<StoreLimits>,
+    bindings: bindings::Analytics,
+}
+
+/// A plugin implemented by a WebAssembly component
+pub struct WasmPlugin {
+    path: PathBuf,
+    engine: Engine,
+    component: Component,
+    linker: Linker<StoreLimits>,
+    fuel_per_frame: u64,
+    max_memory_bytes: usize,
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    version: &'static str,
+    /// Set by `init`; dropped after a trap
+    guest: Mutex<Option<Guest>>,
+}
+
+/// Plugin metadata is `&'static str`; components are loaded once at startup
+fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+impl WasmPlugin {
+    /// Compile the component at `path` and read its metadata
+    pub fn load(engine: &Engine, path: &Path, config: &WasmPluginConfig) -> Result<Self> {
+        let component = Component::from_file(engine, path)
+            .with_context(|| format!("Failed to compile WASM component {}", path.display()))?;
+        Self::from_component(engine, component, path, config)
+    }
+
+    fn from_component(
+        engine: &Engine,
+        component: Component,
+        path: &Path,
+        config: &WasmPluginConfig,
+    ) -> Result<Self> {
+        let mut plugin = Self {
+            path: path.to_path_buf(),
+            engine: engine.clone(),
+            component,
+            // Nothing is provided to components; one importing anything
+            // fails to instantiate here
+            linker: Linker::new(engine),
+            fuel_per_frame: config.fuel_per_frame,
+            max_memory_bytes: config.max_memory_mb.saturating_mul(1024 * 1024),
+            id: "",
+            name: "",
+            description: "",
+            version: "",
+            guest: Mutex::new(None),
+        };
+        let mut guest = plugin.instantiate()?;
+        let info = guest
+            .bindings
+            .call_info(&mut guest.store)
+            .with_context(|| format!("WASM component {} failed to report its info", path.display()))?;
+        if info.id.trim().is_empty() {
+            bail!("WASM component {} reports an empty plugin id", path.display());
+        }
+        plugin.id = leak(info.id);
+        plugin.name = leak(info.name);
+        plugin.description = leak(info.description);
+        plugin.version = leak(info.version);
+        Ok(plugin)
+    }
+
+    fn instantiate(&self) -> Result<Guest> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory_bytes)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel_per_frame)?;
+        let bindings = bindings::Analytics::instantiate(&mut store, &self.component, &self.linker)
+            .with_context(|| format!("Failed to instantiate WASM component {}", self.path.display()))?;
+        Ok(Guest { store, bindings })
+    }
+
+    /// Detections in the service's form; metadata that is not JSON is kept
+    /// as a string
+    fn to_detection(detection: bindings::Detection) -> Detection {
+        Detection {
+            class: detection.class,
+            confidence: detection.confidence.clamp(0.0, 1.0),
+            bbox: BoundingBox {
+                x: detection.bbox.x,
+                y: detection.bbox.y,
+                width: detection.bbox.width,
+                height: detection.bbox.height,
+            },
+            metadata: detection.metadata.map(|metadata| {
+                serde_json::from_str(&metadata).unwrap_or(serde_json::Value::String(metadata))
+            }),
+        }
+    }
+
+    /// A trap (including running out of fuel) leaves the instance unusable
+    fn trap(&self, error: anyhow::Error) -> anyhow::Error {
+        let message = match error.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => format!(
+                "WASM plugin '{}' exceeded its fuel budget of {} per frame",
+                self.id, self.fuel_per_frame
+            ),
+            _ => format!("WASM plugin '{}' trapped: {:#}", self.id, error),
+        };
+        PluginError::fatal(message).into()
+    }
+}
+
+#[async_trait]
+impl AiPlugin for WasmPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn version(&self) -> &'static str {
+        self.version
+    }
+
+    fn supported_formats(&self) -> Vec<String> {
+        vec!["jpeg".to_string(), "png".to_string()]
+    }
+
+    /// Start a fresh instance and hand it the configuration; the registry
+    /// comes back here to restart the plugin after a trap
+    async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+        let mut guest = self.instantiate().map_err(|e| PluginError::config(format!("{:#}", e)))?;
+        let config_json = serde_json::to_string(&config)?;
+        guest
+            .bindings
+            .call_init(&mut guest.store, &config_json)
+            .map_err(|e| self.trap(e))?
+            .map_err(|e| PluginError::config(format!("WASM plugin '{}' rejected its configuration: {}", self.id, e)))?;
+        *self
+            .guest
+            .get_mut()
+            .map_err(|e| PluginError::poisoned("WASM instance", e))? = Some(guest);
+        tracing::info!(plugin = self.id, path = %self.path.display(), "WASM plugin initialized");
+        Ok(())
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        let start = std::time::Instant::now();
+        let image_data = base64::prelude::BASE64_STANDARD
+            .decode(&frame.data)
+            .context(PluginError::recoverable("Failed to decode base64 image"))?;
+        let img = image::load_from_memory(&image_data)
+            .context(PluginError::recoverable("Failed to load image"))?
+            .to_rgb8();
+        let (width, height) = img.dimensions();
+        let input = bindings::Frame {
+            source_id: frame.source_id.clone(),
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+            width,
+            height,
+            pixels: img.into_raw(),
+        };
+
+        let outcome = {
+            let mut slot = self
+                .guest
+                .lock()
+                .map_err(|e| PluginError::poisoned("WASM instance", e))?;
+            let guest = slot
+                .as_mut()
+                .ok_or_else(|| PluginError::fatal(format!("WASM plugin '{}' not initialized", self.id)))?;
+            guest.store.set_fuel(self.fuel_per_frame)?;
+            let outcome = guest.bindings.call_process_frame(&mut guest.store, &input);
+            if outcome.is_err() {
+                *slot = None;
+            }
+            outcome
+        };
+        let detections = outcome
+            .map_err(|e| self.trap(e))?
+            .map_err(|e| PluginError::recoverable(format!("WASM plugin '{}' failed: {}", self.id, e)))?
+            .into_iter()
+            .map(Self::to_detection)
+            .collect::<Vec<_>>();
+
+        Ok(AiResult {
+            task_id: frame.source_id.clone(),
+            timestamp: frame.timestamp,
+            plugin_type: self.id.to_string(),
+            confidence: detections.iter().map(|d| d.confidence).reduce(f32::max),
+            detections,
+            processing_time_ms: Some(start.elapsed().as_millis() as u64),
+            metadata: Some(serde_json::json!({
+                "frame_width": width,
+                "frame_height": height,
+                "frame_sequence": frame.sequence,
+                "component": self.path.display().to_string(),
+            })),
+        })
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        let guest = self
+            .guest
+            .lock()
+            .map_err(|e| PluginError::poisoned("WASM instance", e))?;
+        Ok(guest.is_some())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        tracing::info!("Shutting down WASM plugin {}", self.id);
+        if let Ok(guest) = self.guest.get_mut() {
+            *guest = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A component written by hand against the contract. It reports the
+    /// first pixel's red value as the box's x and the frame sequence as its
+    /// y; a red of 255 fails the frame, 254 traps and 253 spins forever.
+    const COMPONENT: &str = r#"
+(component
+  (core module $m
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (data (i32.const 0) "wat_sample")
+    (data (i32.const 16) "WAT sample")
+    (data (i32.const 32) "Test component")
+    (data (i32.const 48) "1.0.0")
+    (data (i32.const 112) "object")
+    (data (i32.const 128) "{\"source\":\"wat\"}")
+    (data (i32.const 144) "too bright")
+    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ptr i32)
+      (local.set $ptr (i32.and (i32.add (global.get $heap) (i32.const 7)) (i32.const -8)))
+      (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+      (if (i32.gt_u (global.get $heap) (i32.mul (memory.size) (i32.const 65536)))
+        (then (drop (memory.grow (i32.add (i32.shr_u (local.get 3) (i32.const 16)) (i32.const 1))))))
+      (local.get $ptr))
+    (func (export "info") (result i32)
+      (i32.store (i32.const 64) (i32.const 0))
+      (i32.store (i32.const 68) (i32.const 10))
+      (i32.store (i32.const 72) (i32.const 16))
+      (i32.store (i32.const 76) (i32.const 10))
+      (i32.store (i32.const 80) (i32.const 32))
+      (i32.store (i32.const 84) (i32.const 14))
+      (i32.store (i32.const 88) (i32.const 48))
+      (i32.store (i32.const 92) (i32.const 5))
+      (i32.const 64))
+    (func (export "init") (param i32 i32) (result i32)
+      (i32.store8 (i32.const 96) (i32.const 0))
+      (i32.const 96))
+    (func (export "process-frame")
+      (param $sid i32) (param $sid_len i32) (param $ts i64) (param $seq i64)
+      (param $w i32) (param $h i32) (param $px i32) (param $px_len i32)
+      (result i32)
+      (local $red i32)
+      (local.set $red (i32.load8_u (local.get $px)))
+      (if (i32.eq (local.get $red) (i32.const 253)) (then (loop $spin (br $spin))))
+      (if (i32.eq (local.get $red) (i32.const 254)) (then unreachable))
+      (if (i32.eq (local.get $red) (i32.const 255))
+        (then
+          (i32.store8 (i32.const 160) (i32.const 1))
+          (i32.store (i32.const 164) (i32.const 144))
+          (i32.store (i32.const 168) (i32.const 10))
+          (return (i32.const 160))))
+      (i32.store (i32.const 176) (i32.const 112))
+      (i32.store (i32.const 180) (i32.const 6))
+      (f32.store (i32.const 184) (f32.const 0.75))
+      (i32.store (i32.const 188) (local.get $red))
+      (i32.store (i32.const 192) (i32.wrap_i64 (local.get $seq)))
+      (i32.store (i32.const 196) (local.get $w))
+      (i32.store (i32.const 200) (local.get $h))
+      (i32.store8 (i32.const 204) (i32.const 1))
+      (i32.store (i32.const 208) (i32.const 128))
+      (i32.store (i32.const 212) (i32.const 16))
+      (i32.store8 (i32.const 160) (i32.const 0))
+      (i32.store (i32.const 164) (i32.const 176))
+      (i32.store (i32.const 168) (i32.const 1))
+      (i32.const 160)))
+  (core instance $i (instantiate $m))
+
+  (type $plugin-info-t (record
+    (field "id" string) (field "name" string) (field "description" string) (field "version" string)))
+  (export $plugin-info "plugin-info" (type $plugin-info-t))
+  (type $bbox-t (record (field "x" u32) (field "y" u32) (field "width" u32) (field "height" u32)))
+  (export $bbox "bbox" (type $bbox-t))
+  (type $detection-t (record
+    (field "class" string) (field "confidence" f32) (field "bbox" $bbox) (field "metadata" (option string))))
+  (export $detection "detection" (type $detection-t))
+  (type $frame-t (record
+    (field "source-id" string) (field "timestamp" u64) (field "sequence" u64)
+    (field "width" u32) (field "height" u32) (field "pixels" (list u8))))
+  (export $frame "frame" (type $frame-t))
+
+  (func $info (result $plugin-info)
+    (canon lift (core func $i "info") (memory $i "memory")))
+  (export "info" (func $info))
+  (func $init (param "config" string) (result (result (error string)))
+    (canon lift (core func $i "init") (memory $i "memory") (realloc (func $i "cabi_realloc"))))
+  (export "init" (func $init))
+  (func $process-frame (param "frame" $frame) (result (result (list $detection) (error string)))
+    (canon lift (core func $i "process-frame") (memory $i "memory") (realloc (func $i "cabi_realloc"))))
+  (export "process-frame" (func $process-frame))
+)
+"#;
+
+    fn plugin(fuel_per_frame: u64) -> WasmPlugin {
+        let config = WasmPluginConfig {
+            fuel_per_frame,
+            ..WasmPluginConfig::default()
+        };
+        let engine = config.engine().unwrap();
+        let component = Component::new(&engine, wat::parse_str(COMPONENT).unwrap()).unwrap();
+        WasmPlugin::from_component(&engine, component, Path::new("sample.wasm"), &config).unwrap()
+    }
+
+    /// A 4x2 PNG (lossless, so the first pixel arrives as sent)
+    fn frame(red: u8) -> VideoFrame {
+        let img = image::RgbImage::from_pixel(4, 2, image::Rgb([red, 0, 0]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        VideoFrame {
+            source_id: "cam-1".to_string(),
+            timestamp: 1000,
+            sequence: 7,
+            width: 4,
+            height: 2,
+            format: "png".to_string(),
+            data: base64::prelude::BASE64_STANDARD.encode(png.into_inner()),
+            audio: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_frames_through_the_component() {
+        let mut plugin = plugin(DEFAULT_FUEL_PER_FRAME);
+        let info = plugin.info();
+        assert_eq!(info.id, "wat_sample");
+        assert_eq!(info.name, "WAT sample");
+        assert_eq!(info.version, "1.0.0");
+        assert!(!plugin.health_check().await.unwrap());
+
+        plugin.init(serde_json::json!({"threshold": 0.5})).await.unwrap();
+        assert!(plugin.health_check().await.unwrap());
+        let result = plugin.process_frame(&frame(42)).await.unwrap();
+        assert_eq!(result.plugin_type, "wat_sample");
+        assert_eq!(result.task_id, "cam-1");
+        assert_eq!(result.confidence, Some(0.75));
+        let detection = &result.detections[0];
+        assert_eq!(detection.class, "object");
+        assert_eq!(
+            detection.bbox,
+            BoundingBox {
+                x: 42,
+                y: 7,
+                width: 4,
+                height: 2
+            }
+        );
+        assert_eq!(detection.metadata, Some(serde_json::json!({"source": "wat"})));
+
+        // A refused frame fails alone
+        let error = plugin.process_frame(&frame(255)).await.unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), common::ai_tasks::PluginErrorKind::Recoverable);
+        assert!(error.to_string().contains("too bright"));
+        assert!(plugin.process_frame(&frame(1)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn traps_are_fatal_until_restarted() {
+        let mut plugin = plugin(1_000_000);
+        plugin.init(serde_json::json!({})).await.unwrap();
+
+        let error = plugin.process_frame(&frame(254)).await.unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), common::ai_tasks::PluginErrorKind::Fatal);
+        assert!(!plugin.health_check().await.unwrap());
+        assert!(plugin.process_frame(&frame(1)).await.is_err());
+
+        // The restart gets a fresh instance
+        plugin.init(serde_json::json!({})).await.unwrap();
+        let error = plugin.process_frame(&frame(253)).await.unwrap_err();
+        assert_eq!(PluginError::kind_of(&error), common::ai_tasks::PluginErrorKind::Fatal);
+        assert!(error.to_string().contains("fuel budget"));
+
+        plugin.init(serde_json::json!({})).await.unwrap();
+        assert!(plugin.process_frame(&frame(1)).await.is_ok());
+    }
+
+    #[test]
+    fn lists_components_and_their_configs() {
+        let dir = std::env::temp_dir().join(format!("wasm-plugins-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.wasm", "a.wasm", "notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        std::fs::write(dir.join("a.json"), br#"{"zone": "gate"}"#).unwrap();
+        let config = WasmPluginConfig {
+            dir: dir.clone(),
+            ..WasmPluginConfig::default()
+        };
+        let components = config.components().unwrap();
+        assert_eq!(components, vec![dir.join("a.wasm"), dir.join("b.wasm")]);
+        assert_eq!(
+            crate::plugin::sidecar_config(&components[0]).unwrap(),
+            serde_json::json!({"zone": "gate"})
+        );
+        assert_eq!(crate::plugin::sidecar_config(&components[1]).unwrap(), serde_json::json!({}));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let missing = WasmPluginConfig {
+            dir: dir.join("missing"),
+            ..WasmPluginConfig::default()
+        };
+        assert!(missing.components().unwrap().is_empty());
+    }
+}
@@ -1,8 +1,9 @@
 /// YOLOv8 object detection plugin using ONNX Runtime
-use super::AiPlugin;
+use super::vision::{self, Candidate, Letterbox, NmsConfig, Normalization, ResizeMode};
+use super::{AiPlugin, PluginError};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use common::ai_tasks::{AiResult, BoundingBox, Detection, VideoFrame};
+use common::ai_tasks::{AiResult, Detection, VideoFrame};
 use base64::Engine;
 use image::DynamicImage;
 use ndarray::{Array, IxDyn};
@@ -39,6 +40,18 @@ pub struct YoloV8Config {
     #[serde(default = "default_coco_classes")]
     pub class_names: Vec<String>,
 
+    /// Only report these classes (empty = all)
+    #[serde(default)]
+    pub classes: Vec<String>,
+
+    /// How frames are fitted into the model input
+    #[serde(default)]
+    pub resize_mode: ResizeMode,
+
+    /// Non-maximum suppression variant
+    #[serde(default)]
+    pub nms: NmsConfig,
+
     /// Execution provider preference (CPU, CUDA, TensorRT)
     #[serde(default = "default_execution_provider")]
     pub execution_provider: String,
@@ -58,6 +71,11 @@ pub struct YoloV8Config {
     /// GPU memory limit in bytes (0 = unlimited)
     #[serde(default = "default_gpu_mem_limit")]
     pub gpu_mem_limit: usize,
+
+    /// Largest number of frames run as one inference; only used when the
+    /// model has a dynamic batch dimension
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
 }
 
 fn default_confidence() -> f32 {
@@ -76,7 +94,7 @@ fn default_input_size() -> u32 {
     640
 }
 
-fn default_coco_classes() -> Vec<String> {
+pub(crate) fn default_coco_classes() -> Vec<String> {
     vec![
         "person", "bicycle", "car", "motorcycle", "airplane", "bus", "train", "truck", "boat",
         "traffic light", "fire hydrant", "stop sign", "parking meter", "bench", "bird", "cat",
@@ -115,6 +133,10 @@ fn default_gpu_mem_limit() -> usize {
     0 // unlimited
 }
 
+fn default_max_batch_size() -> usize {
+    8
+}
+
 impl Default for YoloV8Config {
     fn default() -> Self {
         Self {
@@ -124,20 +146,58 @@ impl Default for YoloV8Config {
             max_detections: 100,
             input_size: 640,
             class_names: default_coco_classes(),
+            classes: Vec::new(),
+            resize_mode: ResizeMode::default(),
+            nms: NmsConfig::default(),
             execution_provider: default_execution_provider(),
             device_id: default_device_id(),
             intra_threads: default_intra_threads(),
             inter_threads: default_inter_threads(),
             gpu_mem_limit: default_gpu_mem_limit(),
+            max_batch_size: default_max_batch_size(),
         }
     }
 }
 
+impl YoloV8Config {
+    /// Read GPU configuration from environment variables if set
+    fn apply_env_overrides(&mut self) {
+        if let Ok(provider) = std::env::var("YOLOV8_EXECUTION_PROVIDER") {
+            self.execution_provider = provider;
+        }
+        if let Ok(device_id) = std::env::var("YOLOV8_DEVICE_ID") {
+            if let Ok(id) = device_id.parse::<i32>() {
+                self.device_id = id;
+            }
+        }
+        if let Ok(gpu_mem) = std::env::var("YOLOV8_GPU_MEM_LIMIT") {
+            if let Ok(limit) = gpu_mem.parse::<usize>() {
+                self.gpu_mem_limit = limit;
+            }
+        }
+    }
+
+    /// Whether `other` runs on the session built for this configuration,
+    /// i.e. only post-processing settings differ
+    fn same_session(&self, other: &Self) -> bool {
+        self.model_path == other.model_path
+            && self.input_size == other.input_size
+            && self.execution_provider.eq_ignore_ascii_case(&other.execution_provider)
+            && self.device_id == other.device_id
+            && self.intra_threads == other.intra_threads
+            && self.inter_threads == other.inter_threads
+            && self.gpu_mem_limit == other.gpu_mem_limit
+            && self.max_batch_size == other.max_batch_size
+    }
+}
+
 /// YOLOv8 object detection plugin
 pub struct YoloV8DetectorPlugin {
     config: YoloV8Config,
     session: Option<Arc<Mutex<Session>>>,
     execution_provider_used: Arc<Mutex<String>>,
+    /// Frames per inference the loaded model accepts
+    batch_capacity: usize,
 }
 
 impl YoloV8DetectorPlugin {
@@ -146,88 +206,70 @@ impl YoloV8DetectorPlugin {
             config: YoloV8Config::default(),
             session: None,
             execution_provider_used: Arc::new(Mutex::new("CPU".to_string())),
+            batch_capacity: 1,
         }
     }
 
-    /// Preprocess image to YOLOv8 input format
-    fn preprocess_image(&self, img: &DynamicImage) -> Result<Array<f32, IxDyn>> {
+    /// Preprocess images to one YOLOv8 input batch
+    fn preprocess_images(&self, images: &[DynamicImage]) -> Result<Array<f32, IxDyn>> {
         let size = self.config.input_size;
-        let resized = img.resize_exact(size, size, image::imageops::FilterType::Triangle);
-        let rgb_img = resized.to_rgb8();
-
-        // Convert to NCHW format and normalize to [0, 1]
-        let mut input = Array::zeros(IxDyn(&[1, 3, size as usize, size as usize]));
-
-        for (x, y, pixel) in rgb_img.enumerate_pixels() {
-            let r = pixel[0] as f32 / 255.0;
-            let g = pixel[1] as f32 / 255.0;
-            let b = pixel[2] as f32 / 255.0;
-
-            input[[0, 0, y as usize, x as usize]] = r;
-            input[[0, 1, y as usize, x as usize]] = g;
-            input[[0, 2, y as usize, x as usize]] = b;
-        }
-
+        let (input, _) = vision::to_nchw(images, size, size, self.config.resize_mode, Normalization::Unit);
         Ok(input)
     }
 
-    /// Apply Non-Maximum Suppression (NMS)
-    fn nms(&self, boxes: Vec<(BoundingBox, f32, usize)>) -> Vec<(BoundingBox, f32, usize)> {
-        if boxes.is_empty() {
-            return vec![];
-        }
-
-        let mut sorted_boxes = boxes.clone();
-        sorted_boxes.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-
-        let mut keep = Vec::new();
-
-        while !sorted_boxes.is_empty() {
-            let current = sorted_boxes.remove(0);
-            keep.push(current.clone());
+    /// Whether a class index is reported
+    fn class_wanted(&self, class_idx: usize) -> bool {
+        self.config.classes.is_empty()
+            || self
+                .config
+                .class_names
+                .get(class_idx)
+                .is_some_and(|name| self.config.classes.contains(name))
+    }
 
-            sorted_boxes.retain(|box_item| {
-                let iou = self.calculate_iou(&current.0, &box_item.0);
-                iou < self.config.iou_threshold
-            });
-        }
+    /// Run the model over a batch of images; returns the raw output and the
+    /// inference time
+    fn infer(&self, images: &[DynamicImage]) -> Result<(Array<f32, IxDyn>, std::time::Duration)> {
+        let session_lock = self
+            .session
+            .as_ref()
+            .context(PluginError::fatal("Model not initialized - call init() first"))?;
 
-        keep
-    }
+        // Convert ndarray to ort Value
+        let input_tensor = Value::from_array(self.preprocess_images(images)?)?;
 
-    /// Calculate Intersection over Union (IoU)
-    fn calculate_iou(&self, box1: &BoundingBox, box2: &BoundingBox) -> f32 {
-        let x1 = box1.x.max(box2.x);
-        let y1 = box1.y.max(box2.y);
-        let x2 = (box1.x + box1.width).min(box2.x + box2.width);
-        let y2 = (box1.y + box1.height).min(box2.y + box2.height);
+        // Run inference - acquire lock for session and measure inference time
+        let inference_start = std::time::Instant::now();
+        let mut session = session_lock.lock().map_err(|e| PluginError::poisoned("session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
+        let inference_time = inference_start.elapsed();
 
-        let intersection = if x2 > x1 && y2 > y1 {
-            ((x2 - x1) * (y2 - y1)) as f32
-        } else {
-            0.0
-        };
+        // Get output tensor - output is at index 0 (use string key for named outputs)
+        let output_value = outputs
+            .get("output0")
+            .context(PluginError::config("No output tensor found"))?;
+        let (shape, data) = output_value.try_extract_tensor::<f32>()?;
 
-        let area1 = (box1.width * box1.height) as f32;
-        let area2 = (box2.width * box2.height) as f32;
-        let union = area1 + area2 - intersection;
+        // Convert shape from i64 to usize
+        let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
 
-        if union > 0.0 {
-            intersection / union
-        } else {
-            0.0
-        }
+        // Convert to ndarray
+        let output = Array::from_shape_vec(IxDyn(&shape_usize), data.to_vec())?;
+        Ok((output, inference_time))
     }
 
-    /// Post-process YOLOv8 output
+    /// Post-process the YOLOv8 output of one image of the batch
     fn postprocess_output(
         &self,
-        output: Array<f32, IxDyn>,
+        output: &Array<f32, IxDyn>,
+        batch_index: usize,
         original_width: u32,
         original_height: u32,
     ) -> Result<Vec<Detection>> {
-        let scale_x = original_width as f32 / self.config.input_size as f32;
-        let scale_y = original_height as f32 / self.config.input_size as f32;
+        let size = self.config.input_size;
+        let letterbox = Letterbox::new(self.config.resize_mode, original_width, original_height, size, size);
 
         let mut boxes = Vec::new();
 
@@ -241,50 +283,44 @@ impl YoloV8DetectorPlugin {
 
             // Find the class with highest score
             for class_idx in 0..num_classes {
-                let score = output[[0, 4 + class_idx, i]];
+                let score = output[[batch_index, 4 + class_idx, i]];
                 if score > max_class_score {
                     max_class_score = score;
                     max_class_idx = class_idx;
                 }
             }
 
-            // Filter by confidence threshold
-            if max_class_score < self.config.confidence_threshold {
+            // Filter by confidence threshold and class
+            if max_class_score < self.config.confidence_threshold || !self.class_wanted(max_class_idx) {
                 continue;
             }
 
-            // Extract bounding box (cx, cy, w, h)
-            let cx = output[[0, 0, i]];
-            let cy = output[[0, 1, i]];
-            let w = output[[0, 2, i]];
-            let h = output[[0, 3, i]];
-
-            // Convert to (x, y, w, h) and scale to original image
-            let x = ((cx - w / 2.0) * scale_x).max(0.0) as u32;
-            let y = ((cy - h / 2.0) * scale_y).max(0.0) as u32;
-            let width = (w * scale_x).min(original_width as f32) as u32;
-            let height = (h * scale_y).min(original_height as f32) as u32;
-
-            boxes.push((
-                BoundingBox {
-                    x,
-                    y,
-                    width,
-                    height,
-                },
-                max_class_score,
-                max_class_idx,
-            ));
+            // Extract bounding box (cx, cy, w, h) and map it to the frame
+            boxes.push(Candidate {
+                bbox: letterbox.to_frame(
+                    output[[batch_index, 0, i]],
+                    output[[batch_index, 1, i]],
+                    output[[batch_index, 2, i]],
+                    output[[batch_index, 3, i]],
+                ),
+                score: max_class_score,
+                class_id: max_class_idx,
+            });
         }
 
         // Apply NMS
-        let filtered_boxes = self.nms(boxes);
+        let filtered_boxes = vision::nms(
+            boxes,
+            self.config.iou_threshold,
+            self.config.confidence_threshold,
+            &self.config.nms,
+        );
 
         // Convert to Detection objects
         let detections: Vec<Detection> = filtered_boxes
             .into_iter()
             .take(self.config.max_detections)
-            .map(|(bbox, confidence, class_idx)| {
+            .map(|Candidate { bbox, score: confidence, class_id: class_idx }| {
                 let class = if class_idx < self.config.class_names.len() {
                     self.config.class_names[class_idx].clone()
                 } else {
@@ -377,6 +413,26 @@ impl AiPlugin for YoloV8DetectorPlugin {
                     "items": {"type": "string"},
                     "description": "List of class names (default: COCO 80 classes)"
                 },
+                "classes": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Only report these classes (default: all)"
+                },
+                "resize_mode": {
+                    "type": "string",
+                    "enum": ["letterbox", "stretch"],
+                    "default": "letterbox",
+                    "description": "Fit frames into the input keeping their aspect ratio (letterbox) or stretched"
+                },
+                "nms": {
+                    "type": "object",
+                    "properties": {
+                        "method": {"type": "string", "enum": ["hard", "soft", "diou"], "default": "hard"},
+                        "class_agnostic": {"type": "boolean", "default": false},
+                        "soft_sigma": {"type": "number", "default": 0.5}
+                    },
+                    "description": "Non-maximum suppression variant; per class unless class_agnostic"
+                },
                 "execution_provider": {
                     "type": "string",
                     "enum": ["CPU", "CUDA", "TensorRT"],
@@ -406,6 +462,12 @@ impl AiPlugin for YoloV8DetectorPlugin {
                     "minimum": 0,
                     "default": 0,
                     "description": "GPU memory limit in bytes (0 = unlimited)"
+                },
+                "max_batch_size": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "default": 8,
+                    "description": "Frames per inference for models exported with a dynamic batch dimension"
                 }
             },
             "required": ["model_path"]
@@ -420,25 +482,20 @@ impl AiPlugin for YoloV8DetectorPlugin {
         false // Can run on CPU, GPU support is optional
     }
 
+    fn gpu_device(&self) -> Option<u32> {
+        let provider = self.execution_provider_used.lock().ok()?;
+        super::gpu_device(&provider, self.config.device_id)
+    }
+
     async fn init(&mut self, config: serde_json::Value) -> Result<()> {
         if !config.is_null() {
-            self.config = serde_json::from_value(config)?;
+            self.config = serde_json::from_value(config)
+                .context(PluginError::config("Invalid YOLOv8 configuration"))?;
         }
 
-        // Read GPU configuration from environment variables if set
-        if let Ok(provider) = std::env::var("YOLOV8_EXECUTION_PROVIDER") {
-            self.config.execution_provider = provider;
-        }
-        if let Ok(device_id) = std::env::var("YOLOV8_DEVICE_ID") {
-            if let Ok(id) = device_id.parse::<i32>() {
-                self.config.device_id = id;
-            }
-        }
-        if let Ok(gpu_mem) = std::env::var("YOLOV8_GPU_MEM_LIMIT") {
-            if let Ok(limit) = gpu_mem.parse::<usize>() {
-                self.config.gpu_mem_limit = limit;
-            }
-        }
+        self.config.apply_env_overrides();
+
+        super::require_model_file(&self.config.model_path)?;
 
         // Try to configure execution providers with fallback
         let provider_preference = self.config.execution_provider.to_uppercase();
@@ -539,105 +596,128 @@ impl AiPlugin for YoloV8DetectorPlugin {
             }
         };
 
+        self.batch_capacity = super::batch_capacity(&session, self.config.max_batch_size);
         self.session = Some(Arc::new(Mutex::new(session)));
         *self.execution_provider_used.lock()
             .expect("BUG: execution_provider_used mutex poisoned during initialization") = actual_provider.clone();
 
         tracing::info!(
-            "Initialized YOLOv8 detector - model: {}, provider: {}, device: {}, confidence: {}, input_size: {}",
+            "Initialized YOLOv8 detector - model: {}, provider: {}, device: {}, confidence: {}, input_size: {}, batch: {}",
             self.config.model_path,
             actual_provider,
             self.config.device_id,
             self.config.confidence_threshold,
-            self.config.input_size
+            self.config.input_size,
+            self.batch_capacity
         );
 
         Ok(())
     }
 
-    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
-        let start = std::time::Instant::now();
-
-        let session_lock = self
-            .session
-            .as_ref()
-            .context("Model not initialized - call init() first")?;
-
-        // Decode base64 image
-        let image_data = base64::prelude::BASE64_STANDARD
-            .decode(&frame.data)
-            .context("Failed to decode base64 image")?;
-
-        let img = image::load_from_memory(&image_data).context("Failed to load image")?;
-
-        let original_width = img.width();
-        let original_height = img.height();
-
-        // Preprocess image
-        let input_array = self.preprocess_image(&img)?;
-
-        // Convert ndarray to ort Value
-        let input_tensor = Value::from_array(input_array)?;
-
-        // Run inference - acquire lock for session and measure inference time
-        let inference_start = std::time::Instant::now();
-        let mut session = session_lock.lock().map_err(|e| anyhow::anyhow!("Failed to lock session: {}", e))?;
-        let outputs = session.run(ort::inputs![input_tensor])?;
-        let inference_time = inference_start.elapsed();
-
-        // Get output tensor - output is at index 0 (use string key for named outputs)
-        let output_value = outputs.get("output0").context("No output tensor found")?;
-        let (shape, data) = output_value.try_extract_tensor::<f32>()?;
-
-        // Convert shape from i64 to usize
-        let shape_usize: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
+    /// Thresholds, class filters, resizing and NMS apply to the loaded
+    /// session; other changes rebuild it
+    async fn reconfigure(&mut self, config: serde_json::Value) -> Result<()> {
+        let mut next: YoloV8Config = serde_json::from_value(config.clone())
+            .context(PluginError::config("Invalid YOLOv8 configuration"))?;
+        next.apply_env_overrides();
+        if self.session.is_some() && self.config.same_session(&next) {
+            tracing::info!(
+                "Reconfigured YOLOv8 detector in place - confidence: {}, iou: {}",
+                next.confidence_threshold,
+                next.iou_threshold
+            );
+            self.config = next;
+            return Ok(());
+        }
+        self.shutdown().await?;
+        self.init(config).await
+    }
 
-        // Convert to ndarray
-        let output = Array::from_shape_vec(IxDyn(&shape_usize), data.to_vec())?;
+    fn max_batch_size(&self) -> usize {
+        self.batch_capacity
+    }
 
-        // Post-process results
-        let detections = self.postprocess_output(output, original_width, original_height)?;
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        self.process_batch(std::slice::from_ref(frame))
+            .await?
+            .pop()
+            .context("No result for frame")
+    }
 
-        let processing_time_ms = start.elapsed().as_millis() as u64;
+    async fn process_batch(&self, frames: &[VideoFrame]) -> Result<Vec<AiResult>> {
+        let start = std::time::Instant::now();
 
-        // Calculate average confidence
-        let avg_confidence = if !detections.is_empty() {
-            detections.iter().map(|d| d.confidence).sum::<f32>() / detections.len() as f32
-        } else {
-            0.0
-        };
+        // Decode base64 images
+        let images = frames
+            .iter()
+            .map(|frame| {
+                let image_data = base64::prelude::BASE64_STANDARD
+                    .decode(&frame.data)
+                    .context(PluginError::recoverable("Failed to decode base64 image"))?;
+                image::load_from_memory(&image_data)
+                    .context(PluginError::recoverable("Failed to load image"))
+            })
+            .collect::<Result<Vec<DynamicImage>>>()?;
 
         let execution_provider = self.execution_provider_used.lock()
             .expect("BUG: execution_provider_used mutex poisoned")
             .clone();
 
-        // Track GPU/CPU inference metrics
-        telemetry::metrics::AI_SERVICE_GPU_INFERENCE
-            .with_label_values(&[self.id(), &execution_provider])
-            .inc();
-
-        telemetry::metrics::AI_SERVICE_INFERENCE_TIME
-            .with_label_values(&[self.id(), &execution_provider])
-            .observe(inference_time.as_secs_f64());
-
-        Ok(AiResult {
-            task_id: frame.source_id.clone(),
-            timestamp: frame.timestamp,
-            plugin_type: self.id().to_string(),
-            detections,
-            confidence: Some(avg_confidence),
-            processing_time_ms: Some(processing_time_ms),
-            metadata: Some(serde_json::json!({
-                "frame_width": original_width,
-                "frame_height": original_height,
-                "frame_sequence": frame.sequence,
-                "model_path": self.config.model_path,
-                "input_size": self.config.input_size,
-                "execution_provider": execution_provider,
-                "device_id": self.config.device_id,
-                "inference_time_ms": inference_time.as_millis() as u64
-            })),
-        })
+        let mut results = Vec::with_capacity(frames.len());
+        let batch_capacity = self.batch_capacity.max(1);
+        for (batch_frames, batch_images) in frames
+            .chunks(batch_capacity)
+            .zip(images.chunks(batch_capacity))
+        {
+            let (output, inference_time) = self.infer(batch_images)?;
+
+            // Track GPU/CPU inference metrics
+            telemetry::metrics::AI_SERVICE_GPU_INFERENCE
+                .with_label_values(&[self.id(), &execution_provider])
+                .inc_by(batch_frames.len() as u64);
+
+            telemetry::metrics::AI_SERVICE_INFERENCE_TIME
+                .with_label_values(&[self.id(), &execution_provider])
+                .observe(inference_time.as_secs_f64());
+
+            for (batch_index, (frame, img)) in batch_frames.iter().zip(batch_images).enumerate() {
+                let original_width = img.width();
+                let original_height = img.height();
+
+                // Post-process results
+                let detections =
+                    self.postprocess_output(&output, batch_index, original_width, original_height)?;
+
+                // Calculate average confidence
+                let avg_confidence = if !detections.is_empty() {
+                    detections.iter().map(|d| d.confidence).sum::<f32>() / detections.len() as f32
+                } else {
+                    0.0
+                };
+
+                results.push(AiResult {
+                    task_id: frame.source_id.clone(),
+                    timestamp: frame.timestamp,
+                    plugin_type: self.id().to_string(),
+                    detections,
+                    confidence: Some(avg_confidence),
+                    processing_time_ms: Some(start.elapsed().as_millis() as u64),
+                    metadata: Some(serde_json::json!({
+                        "frame_width": original_width,
+                        "frame_height": original_height,
+                        "frame_sequence": frame.sequence,
+                        "model_path": self.config.model_path,
+                        "input_size": self.config.input_size,
+                        "execution_provider": execution_provider,
+                        "device_id": self.config.device_id,
+                        "inference_time_ms": inference_time.as_millis() as u64,
+                        "batch_size": batch_frames.len()
+                    })),
+                });
+            }
+        }
+
+        Ok(results)
     }
 
     async fn health_check(&self) -> Result<bool> {
@@ -654,6 +734,7 @@ impl AiPlugin for YoloV8DetectorPlugin {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::ai_tasks::BoundingBox;
 
     #[test]
     fn test_config_defaults() {
@@ -666,9 +747,25 @@ mod tests {
     }
 
     #[test]
-    fn test_calculate_iou() {
-        let plugin = YoloV8DetectorPlugin::new();
+    fn test_threshold_changes_keep_the_session() {
+        let config = YoloV8Config::default();
+        let thresholds = YoloV8Config {
+            confidence_threshold: 0.7,
+            classes: vec!["person".to_string()],
+            ..config.clone()
+        };
+        assert!(config.same_session(&thresholds));
+
+        let provider = YoloV8Config {
+            execution_provider: "cpu".to_string(),
+            ..config.clone()
+        };
+        assert!(!config.same_session(&provider));
+        assert!(!config.same_session(&YoloV8Config { input_size: 320, ..config.clone() }));
+    }
 
+    #[test]
+    fn test_calculate_iou() {
         let box1 = BoundingBox {
             x: 10,
             y: 10,
@@ -682,11 +779,11 @@ mod tests {
             height: 50,
         };
 
-        let iou = plugin.calculate_iou(&box1, &box2);
+        let iou = vision::iou(&box1, &box2);
         assert!(iou > 0.0 && iou < 1.0);
 
         // Identical boxes
-        let iou_same = plugin.calculate_iou(&box1, &box1);
+        let iou_same = vision::iou(&box1, &box1);
         assert!((iou_same - 1.0).abs() < 0.001);
 
         // Non-overlapping boxes
@@ -696,7 +793,7 @@ mod tests {
             width: 50,
             height: 50,
         };
-        let iou_none = plugin.calculate_iou(&box1, &box3);
+        let iou_none = vision::iou(&box1, &box3);
         assert_eq!(iou_none, 0.0);
     }
 
@@ -704,42 +801,77 @@ mod tests {
     fn test_nms() {
         let plugin = YoloV8DetectorPlugin::new();
 
+        let candidate = |x: u32, y: u32, score: f32, class_id: usize| Candidate {
+            bbox: BoundingBox {
+                x,
+                y,
+                width: 50,
+                height: 50,
+            },
+            score,
+            class_id,
+        };
         let boxes = vec![
-            (
-                BoundingBox {
-                    x: 10,
-                    y: 10,
-                    width: 50,
-                    height: 50,
-                },
-                0.9,
-                0,
-            ),
-            (
-                BoundingBox {
-                    x: 15,
-                    y: 15,
-                    width: 50,
-                    height: 50,
-                },
-                0.8,
-                0,
-            ),
-            (
-                BoundingBox {
-                    x: 100,
-                    y: 100,
-                    width: 50,
-                    height: 50,
-                },
-                0.85,
-                1,
-            ),
+            candidate(10, 10, 0.9, 0),
+            candidate(15, 15, 0.8, 0),
+            candidate(100, 100, 0.85, 1),
         ];
 
-        let filtered = plugin.nms(boxes);
+        let filtered = vision::nms(
+            boxes,
+            plugin.config.iou_threshold,
+            plugin.config.confidence_threshold,
+            &plugin.config.nms,
+        );
         // Should keep the highest confidence box from overlapping ones + non-overlapping box
         assert_eq!(filtered.len(), 2);
-        assert_eq!(filtered[0].1, 0.9); // Highest confidence kept first
+        assert_eq!(filtered[0].score, 0.9); // Highest confidence kept first
+    }
+
+    #[test]
+    fn test_postprocess_batch_index() {
+        let plugin = YoloV8DetectorPlugin::new();
+
+        // Two images, three predictions each; only the second image has a
+        // confident "car" (class 2) centered at (320, 320)
+        let mut output = Array::zeros(IxDyn(&[2, 84, 3]));
+        output[[1, 0, 1]] = 320.0;
+        output[[1, 1, 1]] = 320.0;
+        output[[1, 2, 1]] = 64.0;
+        output[[1, 3, 1]] = 32.0;
+        output[[1, 4 + 2, 1]] = 0.9;
+
+        assert!(plugin
+            .postprocess_output(&output, 0, 640, 640)
+            .unwrap()
+            .is_empty());
+
+        // Scaled to the second image's own size
+        let detections = plugin.postprocess_output(&output, 1, 1280, 1280).unwrap();
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].class, "car");
+        assert_eq!(detections[0].bbox.x, 576);
+        assert_eq!(detections[0].bbox.width, 128);
+    }
+
+    #[test]
+    fn test_postprocess_letterbox_and_class_filter() {
+        let mut plugin = YoloV8DetectorPlugin::new();
+
+        // A person (class 0) at the input's center
+        let mut output = Array::zeros(IxDyn(&[1, 84, 1]));
+        output[[0, 0, 0]] = 320.0;
+        output[[0, 1, 0]] = 320.0;
+        output[[0, 2, 0]] = 64.0;
+        output[[0, 3, 0]] = 64.0;
+        output[[0, 4, 0]] = 0.9;
+
+        // A 16:9 frame is letterboxed, so the box stays square
+        let detections = plugin.postprocess_output(&output, 0, 1280, 720).unwrap();
+        assert_eq!(detections[0].bbox.y, 296);
+        assert_eq!(detections[0].bbox.height, 128);
+
+        plugin.config.classes = vec!["car".to_string()];
+        assert!(plugin.postprocess_output(&output, 0, 1280, 720).unwrap().is_empty());
     }
 }
@@ -0,0 +1,542 @@
+/// YOLOv8-seg instance segmentation plugin
+///
+/// Runs a YOLOv8-seg ONNX model, which adds mask coefficients to each
+/// detection (`output0`, `[1, 4 + classes + coefficients, predictions]`) and
+/// a set of prototype masks (`output1`, `[1, coefficients, height, width]`).
+/// Each kept detection's mask is the sigmoid of its coefficients over the
+/// prototypes, sampled at frame resolution inside its box and stored
+/// run-length encoded under `metadata.mask`
+/// ([`common::ai_tasks::SegmentationMask`]), for zone occupancy and privacy
+/// masking downstream.
+use super::vision::{self, Candidate, Letterbox, NmsConfig, Normalization, ResizeMode};
+use super::yolov8_detector::default_coco_classes;
+use super::{AiPlugin, PluginError};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use common::ai_tasks::{AiResult, BoundingBox, Detection, SegmentationMask, VideoFrame};
+use image::DynamicImage;
+use ndarray::{Array, IxDyn};
+use ort::{
+    execution_providers::{CPUExecutionProvider, CUDAExecutionProvider},
+    session::{builder::GraphOptimizationLevel, Session},
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YoloV8SegConfig {
+    /// Path to the YOLOv8-seg ONNX model file
+    pub model_path: String,
+
+    /// Confidence threshold for detections (0.0 to 1.0)
+    #[serde(default = "default_confidence")]
+    pub confidence_threshold: f32,
+
+    /// IoU threshold for NMS
+    #[serde(default = "default_iou_threshold")]
+    pub iou_threshold: f32,
+
+    /// Mask probability above which a pixel belongs to the object
+    #[serde(default = "default_mask_threshold")]
+    pub mask_threshold: f32,
+
+    /// Maximum number of detections per frame
+    #[serde(default = "default_max_detections")]
+    pub max_detections: usize,
+
+    /// Model input size (width and height)
+    #[serde(default = "default_input_size")]
+    pub input_size: u32,
+
+    /// Model class labels, in output order (default COCO 80 classes)
+    #[serde(default = "default_coco_classes")]
+    pub class_names: Vec<String>,
+
+    /// Only report these classes (empty = all)
+    #[serde(default)]
+    pub classes: Vec<String>,
+
+    /// How frames are fitted into the model input
+    #[serde(default)]
+    pub resize_mode: ResizeMode,
+
+    /// Non-maximum suppression variant
+    #[serde(default)]
+    pub nms: NmsConfig,
+
+    /// Execution provider preference (CPU, CUDA)
+    #[serde(default = "default_execution_provider")]
+    pub execution_provider: String,
+
+    /// GPU device ID (0, 1, 2, etc.)
+    #[serde(default)]
+    pub device_id: i32,
+}
+
+fn default_confidence() -> f32 {
+    0.5
+}
+
+fn default_iou_threshold() -> f32 {
+    0.45
+}
+
+fn default_mask_threshold() -> f32 {
+    0.5
+}
+
+fn default_max_detections() -> usize {
+    100
+}
+
+fn default_input_size() -> u32 {
+    640
+}
+
+fn default_execution_provider() -> String {
+    "CPU".to_string()
+}
+
+impl Default for YoloV8SegConfig {
+    fn default() -> Self {
+        Self {
+            model_path: "models/yolov8n-seg.onnx".to_string(),
+            confidence_threshold: default_confidence(),
+            iou_threshold: default_iou_threshold(),
+            mask_threshold: default_mask_threshold(),
+            max_detections: default_max_detections(),
+            input_size: default_input_size(),
+            class_names: default_coco_classes(),
+            classes: Vec::new(),
+            resize_mode: ResizeMode::default(),
+            nms: NmsConfig::default(),
+            execution_provider: default_execution_provider(),
+            device_id: 0,
+        }
+    }
+}
+
+/// YOLOv8-seg instance segmentation plugin
+pub struct YoloV8SegmentationPlugin {
+    config: YoloV8SegConfig,
+    session: Option<Arc<Mutex<Session>>>,
+}
+
+impl YoloV8SegmentationPlugin {
+    pub fn new() -> Self {
+        Self {
+            config: YoloV8SegConfig::default(),
+            session: None,
+        }
+    }
+
+    /// Whether a class index is reported
+    fn class_wanted(&self, class_idx: usize) -> bool {
+        self.config.classes.is_empty()
+            || self
+                .config
+                .class_names
+                .get(class_idx)
+                .is_some_and(|name| self.config.classes.contains(name))
+    }
+
+    /// Run the model over a frame; returns the detections and prototype
+    /// masks
+    fn infer(&self, img: &DynamicImage) -> Result<(Array<f32, IxDyn>, Array<f32, IxDyn>)> {
+        let session = self
+            .session
+            .as_ref()
+            .context(PluginError::fatal("Model not initialized - call init() first"))?;
+
+        let size = self.config.input_size;
+        let (input, _) = vision::to_nchw(
+            std::slice::from_ref(img),
+            size,
+            size,
+            self.config.resize_mode,
+            Normalization::Unit,
+        );
+        let input_tensor = Value::from_array(input)?;
+
+        let mut session = session
+            .lock()
+            .map_err(|e| PluginError::poisoned("session", e))?;
+        let outputs = session
+            .run(ort::inputs![input_tensor])
+            .map_err(PluginError::inference)?;
+        let tensor = |name: &str| -> Result<Array<f32, IxDyn>> {
+            let value = outputs
+                .get(name)
+                .with_context(|| PluginError::config(format!("No {} tensor found; not a segmentation model?", name)))?;
+            let (shape, data) = value.try_extract_tensor::<f32>()?;
+            let shape: Vec<usize> = shape.as_ref().iter().map(|&x| x as usize).collect();
+            Ok(Array::from_shape_vec(IxDyn(&shape), data.to_vec())?)
+        };
+        Ok((tensor("output0")?, tensor("output1")?))
+    }
+
+    /// Decode detections and their masks from the YOLOv8-seg outputs of a
+    /// `width` x `height` frame
+    fn postprocess(
+        &self,
+        output: &Array<f32, IxDyn>,
+        protos: &Array<f32, IxDyn>,
+        width: u32,
+        height: u32,
+    ) -> Vec<Detection> {
+        let size = self.config.input_size;
+        let letterbox = Letterbox::new(self.config.resize_mode, width, height, size, size);
+        let num_coefficients = protos.shape()[1];
+        let num_predictions = output.shape()[2];
+        let num_classes = output.shape()[1].saturating_sub(4 + num_coefficients);
+
+        // Candidates with their mask coefficients
+        let mut proposals = Vec::new();
+        for i in 0..num_predictions {
+            let (class_idx, score) = (0..num_classes)
+                .map(|c| (c, output[[0, 4 + c, i]]))
+                .fold((0, 0.0f32), |best, cur| if cur.1 > best.1 { cur } else { best });
+            if score < self.config.confidence_threshold || !self.class_wanted(class_idx) {
+                continue;
+            }
+            let bbox = letterbox.to_frame(output[[0, 0, i]], output[[0, 1, i]], output[[0, 2, i]], output[[0, 3, i]]);
+            let coefficients: Vec<f32> = (0..num_coefficients)
+                .map(|k| output[[0, 4 + num_classes + k, i]])
+                .collect();
+            proposals.push((
+                Candidate {
+                    bbox,
+                    score,
+                    class_id: class_idx,
+                },
+                coefficients,
+            ));
+        }
+
+        let kept = vision::nms(
+            proposals.iter().map(|(candidate, _)| candidate.clone()).collect(),
+            self.config.iou_threshold,
+            self.config.confidence_threshold,
+            &self.config.nms,
+        );
+
+        kept.into_iter()
+            .take(self.config.max_detections)
+            .map(|candidate| {
+                let coefficients = proposals
+                    .iter()
+                    .find(|(c, _)| c.bbox == candidate.bbox && c.class_id == candidate.class_id)
+                    .map(|(_, coefficients)| coefficients.as_slice())
+                    .unwrap_or_default();
+                let mask = self.mask(protos, coefficients, &letterbox, &candidate.bbox);
+                let mut detection = Detection {
+                    class: self
+                        .config
+                        .class_names
+                        .get(candidate.class_id)
+                        .cloned()
+                        .unwrap_or_else(|| format!("class_{}", candidate.class_id)),
+                    confidence: candidate.score,
+                    bbox: candidate.bbox,
+                    metadata: Some(serde_json::json!({
+                        "class_id": candidate.class_id,
+                        "mask_area": mask.area(),
+                    })),
+                };
+                mask.attach(&mut detection);
+                detection
+            })
+            .collect()
+    }
+
+    /// A detection's mask over its box: each frame pixel takes the
+    /// prototype cell it falls in, and is part of the object when the
+    /// sigmoid of the coefficients over that cell exceeds the threshold
+    fn mask(
+        &self,
+        protos: &Array<f32, IxDyn>,
+        coefficients: &[f32],
+        letterbox: &Letterbox,
+        bbox: &BoundingBox,
+    ) -> SegmentationMask {
+        let (proto_height, proto_width) = (protos.shape()[2], protos.shape()[3]);
+        let input_size = self.config.input_size.max(1) as f32;
+        let cell = |frame: u32, scale: f32, pad: f32, cells: usize| {
+            let input = (frame as f32 + 0.5) * scale + pad;
+            ((input * cells as f32 / input_size).max(0.0) as usize).min(cells - 1)
+        };
+        let columns: Vec<usize> = (bbox.x..bbox.x + bbox.width)
+            .map(|x| cell(x, letterbox.scale_x, letterbox.pad_x, proto_width))
+            .collect();
+        let rows: Vec<usize> = (bbox.y..bbox.y + bbox.height)
+            .map(|y| cell(y, letterbox.scale_y, letterbox.pad_y, proto_height))
+            .collect();
+
+        // Decide each prototype cell the box touches once
+        let (first_column, first_row) = (columns.first().copied().unwrap_or(0), rows.first().copied().unwrap_or(0));
+        let cells_wide = columns.last().map_or(0, |last| last - first_column + 1);
+        let cells_high = rows.last().map_or(0, |last| last - first_row + 1);
+        let threshold_logit = (self.config.mask_threshold / (1.0 - self.config.mask_threshold).max(f32::EPSILON)).ln();
+        let mut inside = vec![false; cells_wide * cells_high];
+        for row in 0..cells_high {
+            for column in 0..cells_wide {
+                let logit: f32 = coefficients
+                    .iter()
+                    .enumerate()
+                    .map(|(k, c)| c * protos[[0, k, first_row + row, first_column + column]])
+                    .sum();
+                inside[row * cells_wide + column] = logit > threshold_logit;
+            }
+        }
+
+        let pixels = rows.iter().flat_map(|&row| {
+            let inside = &inside;
+            columns
+                .iter()
+                .map(move |&column| inside[(row - first_row) * cells_wide + column - first_column])
+        });
+        SegmentationMask::encode(bbox.clone(), pixels)
+    }
+
+    fn create_session(&self) -> Result<Session> {
+        let model_path = &self.config.model_path;
+        super::require_model_file(model_path)?;
+        if self.config.execution_provider.eq_ignore_ascii_case("CUDA") {
+            let result = Session::builder()
+                .context("Failed to create session builder")?
+                .with_optimization_level(GraphOptimizationLevel::Level3)
+                .context("Failed to set optimization level")?
+                .with_execution_providers([
+                    CUDAExecutionProvider::default()
+                        .with_device_id(self.config.device_id)
+                        .build(),
+                    CPUExecutionProvider::default().build(),
+                ])
+                .context("Failed to set execution providers")?
+                .commit_from_file(model_path);
+            match result {
+                Ok(session) => return Ok(session),
+                Err(e) => tracing::warn!("CUDA failed for {}, falling back to CPU: {}", model_path, e),
+            }
+        }
+
+        Session::builder()
+            .context("Failed to create session builder")?
+            .with_optimization_level(GraphOptimizationLevel::Level3)
+            .context("Failed to set optimization level")?
+            .commit_from_file(model_path)
+            .context(PluginError::config("Failed to load model from file"))
+    }
+}
+
+impl Default for YoloV8SegmentationPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl AiPlugin for YoloV8SegmentationPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        "yolov8_segmentation"
+    }
+
+    fn name(&self) -> &'static str {
+        "YOLOv8 Instance Segmentation"
+    }
+
+    fn description(&self) -> &'static str {
+        "Object detection with per-instance masks (run-length encoded) using a YOLOv8-seg ONNX model"
+    }
+
+    fn version(&self) -> &'static str {
+        "1.0.0"
+    }
+
+    fn config_schema(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "model_path": {
+                    "type": "string",
+                    "default": "models/yolov8n-seg.onnx",
+                    "description": "Path to the YOLOv8-seg ONNX model file"
+                },
+                "confidence_threshold": {
+                    "type": "number",
+                    "minimum": 0.0,
+                    "maximum": 1.0,
+                    "default": 0.5
+                },
+                "iou_threshold": {
+                    "type": "number",
+                    "minimum": 0.0,
+                    "maximum": 1.0,
+                    "default": 0.45
+                },
+                "mask_threshold": {
+                    "type": "number",
+                    "minimum": 0.0,
+                    "maximum": 1.0,
+                    "default": 0.5,
+                    "description": "Mask probability above which a pixel belongs to the object"
+                },
+                "max_detections": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "default": 100
+                },
+                "classes": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Only report these classes (default: all)"
+                },
+                "resize_mode": {
+                    "type": "string",
+                    "enum": ["letterbox", "stretch"],
+                    "default": "letterbox"
+                },
+                "nms": {
+                    "type": "object",
+                    "properties": {
+                        "method": {"type": "string", "enum": ["hard", "soft", "diou"], "default": "hard"},
+                        "class_agnostic": {"type": "boolean", "default": false},
+                        "soft_sigma": {"type": "number", "default": 0.5}
+                    }
+                }
+            },
+            "required": ["model_path"]
+        }))
+    }
+
+    fn supported_formats(&self) -> Vec<String> {
+        vec!["jpeg".to_string(), "png".to_string()]
+    }
+
+    async fn init(&mut self, config: serde_json::Value) -> Result<()> {
+        if !config.is_null() {
+            self.config = serde_json::from_value(config)
+                .context(PluginError::config("Invalid YOLOv8 segmentation config"))?;
+        }
+        let session = self.create_session()?;
+        self.session = Some(Arc::new(Mutex::new(session)));
+        tracing::info!(
+            model = %self.config.model_path,
+            confidence = self.config.confidence_threshold,
+            mask_threshold = self.config.mask_threshold,
+            "YOLOv8 segmentation plugin initialized"
+        );
+        Ok(())
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> Result<AiResult> {
+        let start = std::time::Instant::now();
+        let image_data = base64::prelude::BASE64_STANDARD
+            .decode(&frame.data)
+            .context(PluginError::recoverable("Failed to decode base64 image"))?;
+        let img = image::load_from_memory(&image_data)
+            .context(PluginError::recoverable("Failed to load image"))?;
+
+        let inference_start = std::time::Instant::now();
+        let (output, protos) = self.infer(&img)?;
+        telemetry::metrics::AI_SERVICE_INFERENCE_TIME
+            .with_label_values(&[self.id(), &self.config.execution_provider])
+            .observe(inference_start.elapsed().as_secs_f64());
+
+        let detections = self.postprocess(&output, &protos, img.width(), img.height());
+        Ok(AiResult {
+            task_id: frame.source_id.clone(),
+            timestamp: frame.timestamp,
+            plugin_type: self.id().to_string(),
+            confidence: detections
+                .iter()
+                .map(|d| d.confidence)
+                .reduce(f32::max),
+            detections,
+            processing_time_ms: Some(start.elapsed().as_millis() as u64),
+            metadata: Some(serde_json::json!({
+                "frame_width": img.width(),
+                "frame_height": img.height(),
+                "frame_sequence": frame.sequence,
+                "model_path": self.config.model_path,
+            })),
+        })
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(self.session.is_some())
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        tracing::info!("Shutting down YOLOv8 segmentation plugin");
+        self.session = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16x16 input with one 4x4 prototype whose left half is the object,
+    /// and one "car" (class 2 of 3) covering the whole input
+    fn outputs() -> (YoloV8SegmentationPlugin, Array<f32, IxDyn>, Array<f32, IxDyn>) {
+        let mut plugin = YoloV8SegmentationPlugin::new();
+        plugin.config.input_size = 16;
+        plugin.config.class_names = vec!["person".into(), "bicycle".into(), "car".into()];
+
+        let mut output = Array::zeros(IxDyn(&[1, 4 + 3 + 1, 2]));
+        output[[0, 0, 0]] = 8.0;
+        output[[0, 1, 0]] = 8.0;
+        output[[0, 2, 0]] = 16.0;
+        output[[0, 3, 0]] = 16.0;
+        output[[0, 4 + 2, 0]] = 0.9;
+        output[[0, 4 + 3, 0]] = 10.0;
+
+        let mut protos = Array::zeros(IxDyn(&[1, 1, 4, 4]));
+        for row in 0..4 {
+            for column in 0..4 {
+                protos[[0, 0, row, column]] = if column < 2 { 1.0 } else { -1.0 };
+            }
+        }
+        (plugin, output, protos)
+    }
+
+    #[test]
+    fn masks_follow_the_prototypes_at_frame_resolution() {
+        let (plugin, output, protos) = outputs();
+
+        // A 32x32 frame is scaled down twice into the input
+        let detections = plugin.postprocess(&output, &protos, 32, 32);
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].class, "car");
+        let mask = SegmentationMask::from_detection(&detections[0]).unwrap();
+        assert_eq!(mask.bbox, BoundingBox { x: 0, y: 0, width: 32, height: 32 });
+        assert_eq!(mask.area(), 16 * 32);
+        assert!(mask.contains(15, 31) && !mask.contains(16, 0));
+        assert_eq!(detections[0].metadata.as_ref().unwrap()["mask_area"], 16 * 32);
+    }
+
+    #[test]
+    fn class_filter_and_mask_threshold() {
+        let (mut plugin, output, protos) = outputs();
+
+        // sigmoid(10) is just below 0.99996, so nothing is left of the mask
+        plugin.config.mask_threshold = 0.99996;
+        let detections = plugin.postprocess(&output, &protos, 16, 16);
+        assert_eq!(SegmentationMask::from_detection(&detections[0]).unwrap().area(), 0);
+
+        plugin.config.classes = vec!["person".to_string()];
+        assert!(plugin.postprocess(&output, &protos, 16, 16).is_empty());
+    }
+}
@@ -0,0 +1,520 @@
+//! Inference scheduling across the tasks of a node.
+//!
+//! Every analyzed frame holds one of `AI_SCHED_MAX_IN_FLIGHT` inference
+//! slots while it runs through the task's plugins. Tasks ask for a share of
+//! the node with `schedule.priority` and cap their own rate with
+//! `schedule.max_inference_fps` (or `frame_config.max_fps`):
+//!
+//! - frames arriving faster than the task's rate are skipped before they
+//!   cost anything
+//! - while slots are free, frames run right away
+//! - once the node is saturated, `low` priority frames are shed; others wait
+//!   up to `AI_SCHED_MAX_WAIT_MS` for a slot, at most
+//!   `AI_SCHED_MAX_WAITING` of them. A full waiting list sheds its lowest
+//!   priority frame for a higher priority one, or the new frame.
+//!
+//! A freed slot goes to the waiting task with the fewest running frames per
+//! unit of priority weight (low 1, normal 2, high 4), so a busy camera
+//! cannot starve the others and a `high` task gets up to four times the
+//! slots of a `low` one. Shed frames are answered with `429` instead of
+//! piling up behind the plugins.
+//!
+//! Every frame response also carries [`InferenceScheduler::backpressure`]:
+//! the waiting frames and how long the sender should hold the task's next
+//! frame, from the average time frames hold a slot and the task's own rate.
+
+use common::ai_tasks::{FrameBackpressure, TaskPriority};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Frames in inference at once
+    pub max_in_flight: usize,
+    /// Frames waiting for a slot at once
+    pub max_waiting: usize,
+    /// How long a frame waits for a slot before it is shed
+    pub max_wait: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 16,
+            max_waiting: 16,
+            max_wait: Duration::from_millis(500),
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// Reads `AI_SCHED_MAX_IN_FLIGHT`, `AI_SCHED_MAX_WAITING` (default: the
+    /// in-flight limit) and `AI_SCHED_MAX_WAIT_MS`
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let defaults = Self::default();
+        let max_in_flight = var("AI_SCHED_MAX_IN_FLIGHT")
+            .filter(|v| *v >= 1)
+            .map_or(defaults.max_in_flight, |v| v as usize);
+        Self {
+            max_in_flight,
+            max_waiting: var("AI_SCHED_MAX_WAITING").map_or(max_in_flight, |v| v as usize),
+            max_wait: var("AI_SCHED_MAX_WAIT_MS").map_or(defaults.max_wait, Duration::from_millis),
+        }
+    }
+}
+
+/// Why a frame was shed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShedReason {
+    /// The node was saturated and the task has `low` priority
+    Saturated,
+    /// The waiting list was full of frames of the same or higher priority
+    QueueFull,
+    /// A higher priority frame took its place in the waiting list
+    Preempted,
+    /// No slot freed up within `AI_SCHED_MAX_WAIT_MS`
+    Timeout,
+}
+
+impl ShedReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShedReason::Saturated => "saturated",
+            ShedReason::QueueFull => "queue_full",
+            ShedReason::Preempted => "preempted",
+            ShedReason::Timeout => "timeout",
+        }
+    }
+}
+
+/// A frame the node had no capacity for
+#[derive(Debug, thiserror::Error)]
+#[error("AI node saturated, {} priority frame shed ({})", priority.as_str(), reason.as_str())]
+pub struct Shed {
+    pub priority: TaskPriority,
+    pub reason: ShedReason,
+}
+
+/// An inference slot, freed on drop
+#[derive(Debug)]
+pub struct InferencePermit {
+    scheduler: Arc<InferenceScheduler>,
+    task_id: String,
+    granted_at: Instant,
+}
+
+impl Drop for InferencePermit {
+    fn drop(&mut self) {
+        self.scheduler.release(&self.task_id, self.granted_at.elapsed());
+    }
+}
+
+/// Scheduler state of one task, served at `GET /v1/scheduler`
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskLoad {
+    pub task_id: String,
+    pub priority: TaskPriority,
+    pub in_flight: usize,
+    pub waiting: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SchedulerStatus {
+    pub max_in_flight: usize,
+    pub max_waiting: usize,
+    pub in_flight: usize,
+    pub waiting: usize,
+    pub tasks: Vec<TaskLoad>,
+}
+
+#[derive(Debug)]
+struct TaskSlot {
+    priority: TaskPriority,
+    in_flight: usize,
+    /// Earliest time the next frame is due under the task's rate
+    next_at: Option<Instant>,
+}
+
+type Handoff = oneshot::Sender<Result<InferencePermit, ShedReason>>;
+
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    task_id: String,
+    priority: TaskPriority,
+    handoff: Handoff,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    in_flight: usize,
+    tasks: HashMap<String, TaskSlot>,
+    waiters: Vec<Waiter>,
+    next_waiter: u64,
+    /// Moving average of how long frames hold a slot, in milliseconds
+    avg_hold_ms: f64,
+}
+
+/// Weight of the latest frame in the hold time average
+const HOLD_SMOOTHING: f64 = 0.2;
+
+#[derive(Debug)]
+pub struct InferenceScheduler {
+    config: SchedulerConfig,
+    inner: Mutex<Inner>,
+}
+
+impl InferenceScheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn slot<'a>(inner: &'a mut Inner, task_id: &str, priority: TaskPriority) -> &'a mut TaskSlot {
+        let slot = inner.tasks.entry(task_id.to_string()).or_insert(TaskSlot {
+            priority,
+            in_flight: 0,
+            next_at: None,
+        });
+        slot.priority = priority;
+        slot
+    }
+
+    /// Whether a frame of `task_id` arriving now is within its rate
+    pub fn due(&self, task_id: &str, priority: TaskPriority, max_fps: Option<f32>) -> bool {
+        let mut inner = self.lock();
+        due(&mut Self::slot(&mut inner, task_id, priority).next_at, Instant::now(), max_fps)
+    }
+
+    /// Take an inference slot for a frame of `task_id`, waiting for one
+    /// when the node is saturated; hold the permit until the frame's
+    /// plugins are done
+    pub async fn acquire(self: &Arc<Self>, task_id: &str, priority: TaskPriority) -> Result<InferencePermit, Shed> {
+        let shed = |reason: ShedReason| {
+            telemetry::metrics::AI_SERVICE_FRAMES_SHED
+                .with_label_values(&[priority.as_str(), reason.as_str()])
+                .inc();
+            Shed { priority, reason }
+        };
+
+        let (id, handoff) = {
+            let mut inner = self.lock();
+            Self::slot(&mut inner, task_id, priority);
+            if inner.in_flight < self.config.max_in_flight {
+                return Ok(self.grant(&mut inner, task_id));
+            }
+            if priority == TaskPriority::Low {
+                return Err(shed(ShedReason::Saturated));
+            }
+            if inner.waiters.len() >= self.config.max_waiting {
+                // Make room by shedding the newest frame of the lowest priority
+                let lowest = inner
+                    .waiters
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, w)| (w.priority, std::cmp::Reverse(w.id)))
+                    .filter(|(_, w)| w.priority < priority)
+                    .map(|(i, _)| i);
+                let Some(lowest) = lowest else {
+                    return Err(shed(ShedReason::QueueFull));
+                };
+                let evicted = inner.waiters.remove(lowest);
+                telemetry::metrics::AI_SERVICE_FRAMES_SHED
+                    .with_label_values(&[evicted.priority.as_str(), ShedReason::Preempted.as_str()])
+                    .inc();
+                let _ = evicted.handoff.send(Err(ShedReason::Preempted));
+            }
+
+            let (handoff, receiver) = oneshot::channel();
+            let id = inner.next_waiter;
+            inner.next_waiter += 1;
+            inner.waiters.push(Waiter {
+                id,
+                task_id: task_id.to_string(),
+                priority,
+                handoff,
+            });
+            telemetry::metrics::AI_SERVICE_SCHEDULER_WAITING.set(inner.waiters.len() as i64);
+            (id, receiver)
+        };
+
+        match tokio::time::timeout(self.config.max_wait, handoff).await {
+            Ok(Ok(Ok(permit))) => Ok(permit),
+            // Preemption is counted by the frame that caused it
+            Ok(Ok(Err(reason))) => Err(Shed { priority, reason }),
+            Ok(Err(_)) | Err(_) => {
+                let mut inner = self.lock();
+                inner.waiters.retain(|w| w.id != id);
+                telemetry::metrics::AI_SERVICE_SCHEDULER_WAITING.set(inner.waiters.len() as i64);
+                Err(shed(ShedReason::Timeout))
+            }
+        }
+    }
+
+    fn grant(self: &Arc<Self>, inner: &mut Inner, task_id: &str) -> InferencePermit {
+        inner.in_flight += 1;
+        if let Some(slot) = inner.tasks.get_mut(task_id) {
+            slot.in_flight += 1;
+        }
+        telemetry::metrics::AI_SERVICE_SCHEDULER_IN_FLIGHT.set(inner.in_flight as i64);
+        InferencePermit {
+            scheduler: Arc::clone(self),
+            task_id: task_id.to_string(),
+            granted_at: Instant::now(),
+        }
+    }
+
+    /// Free a slot and hand it to the waiting frame whose task is furthest
+    /// below its share
+    fn release(self: &Arc<Self>, task_id: &str, held: Duration) {
+        let handoff = {
+            let mut inner = self.lock();
+            let held_ms = held.as_secs_f64() * 1000.0;
+            inner.avg_hold_ms = if inner.avg_hold_ms == 0.0 {
+                held_ms
+            } else {
+                inner.avg_hold_ms + HOLD_SMOOTHING * (held_ms - inner.avg_hold_ms)
+            };
+            inner.in_flight = inner.in_flight.saturating_sub(1);
+            if let Some(slot) = inner.tasks.get_mut(task_id) {
+                slot.in_flight = slot.in_flight.saturating_sub(1);
+            }
+            let next = next_waiter(&inner.waiters, &inner.tasks);
+            let handoff = next.map(|i| {
+                let waiter = inner.waiters.remove(i);
+                let permit = self.grant(&mut inner, &waiter.task_id);
+                (waiter.handoff, permit)
+            });
+            telemetry::metrics::AI_SERVICE_SCHEDULER_IN_FLIGHT.set(inner.in_flight as i64);
+            telemetry::metrics::AI_SERVICE_SCHEDULER_WAITING.set(inner.waiters.len() as i64);
+            handoff
+        };
+        // Sent without the lock: a waiter that already gave up drops the
+        // permit, which frees the slot again for the next one
+        if let Some((handoff, permit)) = handoff {
+            let _ = handoff.send(Ok(permit));
+        }
+    }
+
+    /// Forget a stopped task's share and rate
+    pub fn remove_task(&self, task_id: &str) {
+        let mut inner = self.lock();
+        if inner.tasks.get(task_id).is_some_and(|slot| slot.in_flight == 0) {
+            inner.tasks.remove(task_id);
+        }
+    }
+
+    /// Waiting frames and how long the sender of `task_id` should wait
+    /// before its next frame: until the frames queued ahead of it (and this
+    /// one) have had a slot on a saturated node, and at least until the
+    /// task's rate admits another frame
+    pub fn backpressure(&self, task_id: &str) -> FrameBackpressure {
+        let inner = self.lock();
+        let now = Instant::now();
+        let saturated = inner.in_flight >= self.config.max_in_flight;
+        let queue_ms = if saturated || !inner.waiters.is_empty() {
+            let rounds = (inner.waiters.len() + 1) as f64 / self.config.max_in_flight.max(1) as f64;
+            (inner.avg_hold_ms * rounds).ceil() as u64
+        } else {
+            0
+        };
+        let rate_ms = inner
+            .tasks
+            .get(task_id)
+            .and_then(|slot| slot.next_at)
+            .map_or(0, |next| next.saturating_duration_since(now).as_millis() as u64);
+        FrameBackpressure {
+            queue_depth: inner.waiters.len(),
+            next_frame_delay_ms: queue_ms.max(rate_ms),
+        }
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        let inner = self.lock();
+        let mut tasks: Vec<TaskLoad> = inner
+            .tasks
+            .iter()
+            .map(|(task_id, slot)| TaskLoad {
+                task_id: task_id.clone(),
+                priority: slot.priority,
+                in_flight: slot.in_flight,
+                waiting: inner.waiters.iter().filter(|w| w.task_id == *task_id).count(),
+            })
+            .collect();
+        tasks.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+        SchedulerStatus {
+            max_in_flight: self.config.max_in_flight,
+            max_waiting: self.config.max_waiting,
+            in_flight: inner.in_flight,
+            waiting: inner.waiters.len(),
+            tasks,
+        }
+    }
+}
+
+/// Whether a frame arriving `now` is within the task's rate; frames up to
+/// a tenth of the interval early pass, so a camera sending exactly at the
+/// limit is not halved by jitter
+fn due(next_at: &mut Option<Instant>, now: Instant, max_fps: Option<f32>) -> bool {
+    let Some(fps) = max_fps.filter(|fps| *fps > 0.0) else {
+        return true;
+    };
+    let interval = Duration::from_secs_f32(1.0 / fps);
+    if next_at.is_some_and(|next| now + interval / 10 < next) {
+        return false;
+    }
+    // Keep to the schedule unless the task fell more than a frame behind
+    *next_at = Some(match *next_at {
+        Some(next) if next + interval > now => next + interval,
+        _ => now + interval,
+    });
+    true
+}
+
+/// Waiting frame to run next: the task with the fewest running frames per
+/// unit of priority weight, then the higher priority, then the oldest frame
+fn next_waiter(waiters: &[Waiter], tasks: &HashMap<String, TaskSlot>) -> Option<usize> {
+    let load = |w: &Waiter| tasks.get(&w.task_id).map_or(0, |slot| slot.in_flight) as u64;
+    waiters
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            // in_flight / weight, compared without division
+            (load(a) * u64::from(b.priority.weight()))
+                .cmp(&(load(b) * u64::from(a.priority.weight())))
+                .then(b.priority.cmp(&a.priority))
+                .then(a.id.cmp(&b.id))
+        })
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(max_in_flight: usize, max_waiting: usize) -> Arc<InferenceScheduler> {
+        Arc::new(InferenceScheduler::new(SchedulerConfig {
+            max_in_flight,
+            max_waiting,
+            max_wait: Duration::from_secs(5),
+        }))
+    }
+
+    async fn run(scheduler: &Arc<InferenceScheduler>, task_id: &str, priority: TaskPriority) -> InferencePermit {
+        scheduler.acquire(task_id, priority).await.unwrap()
+    }
+
+    #[test]
+    fn throttles_to_the_task_rate() {
+        let start = Instant::now();
+        let mut next_at = None;
+        let ms = Duration::from_millis;
+        // 10 fps camera capped at 5 fps, with jitter
+        let admitted: Vec<u64> = [0, 98, 201, 299, 402, 500, 601]
+            .into_iter()
+            .filter(|t| due(&mut next_at, start + ms(*t), Some(5.0)))
+            .collect();
+        assert_eq!(admitted, [0, 201, 402, 601]);
+        // Uncapped tasks are never throttled
+        assert!(due(&mut None, start, None));
+    }
+
+    #[tokio::test]
+    async fn sheds_low_priority_and_preempts_waiting_frames_when_saturated() {
+        let scheduler = scheduler(1, 1);
+        let running = run(&scheduler, "busy", TaskPriority::Normal).await;
+
+        let low = scheduler.acquire("yard", TaskPriority::Low).await.unwrap_err();
+        assert_eq!(low.reason, ShedReason::Saturated);
+
+        let waiting = tokio::spawn({
+            let scheduler = Arc::clone(&scheduler);
+            async move { scheduler.acquire("lobby", TaskPriority::Normal).await.map(drop) }
+        });
+        while scheduler.status().waiting == 0 {
+            tokio::task::yield_now().await;
+        }
+        // A normal frame cannot push out another normal frame
+        let full = scheduler.acquire("dock", TaskPriority::Normal).await.unwrap_err();
+        assert_eq!(full.reason, ShedReason::QueueFull);
+
+        // A high priority frame can, and gets the next slot
+        let high = tokio::spawn({
+            let scheduler = Arc::clone(&scheduler);
+            async move { scheduler.acquire("vault", TaskPriority::High).await.map(drop) }
+        });
+        let preempted = waiting.await.unwrap().unwrap_err();
+        assert_eq!(preempted.reason, ShedReason::Preempted);
+        drop(running);
+        high.await.unwrap().unwrap();
+        assert_eq!(scheduler.status().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn freed_slots_go_to_the_task_furthest_below_its_share() {
+        let scheduler = scheduler(4, 8);
+        let busy_a = run(&scheduler, "busy", TaskPriority::Normal).await;
+        let _busy_b = run(&scheduler, "busy", TaskPriority::Normal).await;
+        let _busy_c = run(&scheduler, "busy", TaskPriority::Normal).await;
+        let _quiet = run(&scheduler, "quiet", TaskPriority::Normal).await;
+
+        let (order_tx, mut order) = tokio::sync::mpsc::unbounded_channel();
+        for (queued, task_id) in ["busy", "quiet"].into_iter().enumerate() {
+            let waiting = Arc::clone(&scheduler);
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let admission = waiting.acquire(task_id, TaskPriority::Normal).await;
+                let _ = order_tx.send((task_id, admission));
+            });
+            while scheduler.status().waiting <= queued {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        // "busy" queued first, but still runs two frames to "quiet"'s one
+        drop(busy_a);
+        let (first, admission) = order.recv().await.unwrap();
+        assert_eq!(first, "quiet");
+        assert!(admission.is_ok());
+    }
+
+    #[tokio::test]
+    async fn backpressure_asks_for_a_delay_once_saturated() {
+        let scheduler = scheduler(1, 4);
+        assert_eq!(scheduler.backpressure("lobby"), FrameBackpressure::default());
+
+        // Frames hold their slot for about 40 ms
+        let first = run(&scheduler, "lobby", TaskPriority::Normal).await;
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        drop(first);
+        assert_eq!(scheduler.backpressure("lobby").next_frame_delay_ms, 0);
+
+        let _running = run(&scheduler, "lobby", TaskPriority::Normal).await;
+        let waiting = tokio::spawn({
+            let scheduler = Arc::clone(&scheduler);
+            async move { scheduler.acquire("dock", TaskPriority::Normal).await.map(drop) }
+        });
+        while scheduler.status().waiting == 0 {
+            tokio::task::yield_now().await;
+        }
+        // One frame ahead plus this one, on a single slot
+        let backpressure = scheduler.backpressure("lobby");
+        assert_eq!(backpressure.queue_depth, 1);
+        assert!(backpressure.next_frame_delay_ms >= 80, "{backpressure:?}");
+        waiting.abort();
+
+        // A capped task is asked to wait for its next admitted frame
+        assert!(scheduler.due("yard", TaskPriority::Normal, Some(1.0)));
+        assert!(scheduler.backpressure("yard").next_frame_delay_ms > 500);
+    }
+}
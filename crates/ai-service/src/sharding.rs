@@ -0,0 +1,372 @@
+//! Camera sharding across ai-service nodes.
+//!
+//! Every node builds the same consistent-hash ring from the AI nodes
+//! registered with the coordinator and owns the cameras that hash to it.
+//! Tasks started on a node that does not own their camera, and frames for
+//! tasks held elsewhere, are redirected (307) to the owner. When membership
+//! changes each node hands the tasks it no longer owns to their new owner;
+//! the ring moves only the cameras of the node that joined or left.
+
+use crate::state::AiServiceState;
+use anyhow::{Context, Result};
+use common::ai_tasks::{AiTaskConfig, AiTaskInfo, AiTaskStartRequest, AiTaskState};
+use common::nodes::{NodeKind, NodeRecord};
+use reqwest::Url;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Points each node gets on the ring; more points even out camera counts
+const VIRTUAL_NODES: usize = 64;
+
+/// Handed-off tasks remembered for redirecting late frames
+const MAX_MOVED_TASKS: usize = 4096;
+
+/// Set on task hand-offs so the receiving node accepts the task even when
+/// its view of the membership lags behind the sender's
+pub const HANDOFF_HEADER: &str = "x-ai-shard-handoff";
+
+/// Names the node a redirect points at
+pub const OWNER_HEADER: &str = "x-ai-shard-owner";
+
+/// Consistent-hash ring over node ids
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HashRing {
+    points: Vec<(u64, String)>,
+}
+
+impl HashRing {
+    pub fn new<'a>(nodes: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut points = Vec::new();
+        for node in nodes {
+            for replica in 0..VIRTUAL_NODES {
+                points.push((hash(&format!("{}#{}", node, replica)), node.to_string()));
+            }
+        }
+        points.sort();
+        points.dedup();
+        Self { points }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Node owning `key`: the first point at or after its hash, wrapping
+    pub fn owner(&self, key: &str) -> Option<&str> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let h = hash(key);
+        let idx = self.points.partition_point(|(point, _)| *point < h);
+        Some(&self.points[idx % self.points.len()].1)
+    }
+}
+
+/// FNV-1a with a splitmix64 finalizer: stable across processes and
+/// releases (unlike `DefaultHasher`), and spreads similar ids apart
+pub(crate) fn hash(key: &str) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.as_bytes() {
+        h ^= u64::from(*byte);
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// Base URL of every AI node registered with the coordinator that takes new
+/// work (not draining, registration not expired), by node id
+pub(crate) async fn ai_members(client: &reqwest::Client, coordinator: &Url) -> Result<BTreeMap<String, String>> {
+    let url = coordinator
+        .join(&format!("v1/nodes?kind={}", NodeKind::Ai))
+        .context("invalid coordinator endpoint")?;
+    let records: Vec<NodeRecord> = client
+        .get(url)
+        .send()
+        .await
+        .context("node list request failed")?
+        .error_for_status()
+        .context("node list returned error status")?
+        .json()
+        .await
+        .context("failed to parse node list")?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(records
+        .into_iter()
+        .filter(|r| r.kind == NodeKind::Ai && !r.draining && !r.is_expired_at(now))
+        .map(|r| (r.node_id, r.base_url.trim_end_matches('/').to_string()))
+        .collect())
+}
+
+/// Camera a task is sharded by: its source stream, or the task itself when
+/// it has none (e.g. tasks over recordings)
+pub fn shard_key(config: &AiTaskConfig) -> &str {
+    config.source_stream_id.as_deref().unwrap_or(&config.id)
+}
+
+/// Where a request belongs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Route {
+    Local,
+    Redirect { node_id: String, base_url: String },
+}
+
+#[derive(Default)]
+struct Membership {
+    ring: HashRing,
+    /// Base URL of each member, ordered for stable reporting
+    base_urls: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShardMember {
+    pub node_id: String,
+    pub base_url: String,
+}
+
+/// Membership as seen by this node
+#[derive(Debug, Clone, Serialize)]
+pub struct ShardingStatus {
+    pub enabled: bool,
+    pub node_id: String,
+    pub members: Vec<ShardMember>,
+    /// Tasks this node handed to another node, by task id
+    pub moved_tasks: HashMap<String, String>,
+}
+
+/// This node's view of the AI node membership
+pub struct Sharding {
+    node_id: String,
+    coordinator: Url,
+    client: reqwest::Client,
+    refresh_interval: Duration,
+    membership: RwLock<Membership>,
+    /// Node each handed-off task went to
+    moved: RwLock<HashMap<String, String>>,
+}
+
+impl Sharding {
+    pub fn new(node_id: String, coordinator: Url, refresh_interval: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            node_id,
+            coordinator,
+            client,
+            refresh_interval,
+            membership: RwLock::new(Membership::default()),
+            moved: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Sharding configured from `AI_SHARDING_ENABLED` and
+    /// `AI_SHARD_REFRESH_SECS`; it needs a coordinator to read membership from
+    pub fn from_env(node_id: &str, coordinator: Option<&Url>) -> Option<Self> {
+        let enabled = std::env::var("AI_SHARDING_ENABLED")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let coordinator = coordinator.filter(|_| enabled)?;
+        let refresh_secs = std::env::var("AI_SHARD_REFRESH_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10)
+            .max(1);
+        Some(Self::new(
+            node_id.to_string(),
+            coordinator.clone(),
+            Duration::from_secs(refresh_secs),
+        ))
+    }
+
+    /// Where work for camera `key` belongs. Local while the membership is
+    /// unknown, so a node keeps working when the coordinator is unreachable
+    /// at startup.
+    pub async fn route(&self, key: &str) -> Route {
+        let membership = self.membership.read().await;
+        match membership.ring.owner(key) {
+            Some(owner) if owner != self.node_id => match membership.base_urls.get(owner) {
+                Some(base_url) => Route::Redirect {
+                    node_id: owner.to_string(),
+                    base_url: base_url.clone(),
+                },
+                None => Route::Local,
+            },
+            _ => Route::Local,
+        }
+    }
+
+    /// Base URL of another member
+    pub async fn member_url(&self, node_id: &str) -> Option<String> {
+        if node_id == self.node_id {
+            return None;
+        }
+        self.membership.read().await.base_urls.get(node_id).cloned()
+    }
+
+    /// Node a task was handed to by this node
+    pub async fn moved_to(&self, task_id: &str) -> Option<String> {
+        self.moved.read().await.get(task_id).cloned()
+    }
+
+    pub async fn status(&self) -> ShardingStatus {
+        let membership = self.membership.read().await;
+        ShardingStatus {
+            enabled: true,
+            node_id: self.node_id.clone(),
+            members: membership
+                .base_urls
+                .iter()
+                .map(|(node_id, base_url)| ShardMember {
+                    node_id: node_id.clone(),
+                    base_url: base_url.clone(),
+                })
+                .collect(),
+            moved_tasks: self.moved.read().await.clone(),
+        }
+    }
+
+    /// Reload membership from the coordinator's AI node registrations.
+    /// Draining nodes take no new cameras and so leave the ring. Returns
+    /// whether the membership changed.
+    pub async fn refresh(&self) -> Result<bool> {
+        let base_urls = ai_members(&self.client, &self.coordinator).await?;
+        let mut membership = self.membership.write().await;
+        if membership.base_urls == base_urls {
+            return Ok(false);
+        }
+        membership.ring = HashRing::new(base_urls.keys().map(String::as_str));
+        membership.base_urls = base_urls;
+        Ok(true)
+    }
+
+    /// Refresh membership periodically and hand off the tasks this node no
+    /// longer owns after each change
+    pub fn spawn(self: Arc<Self>, state: AiServiceState) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.refresh_interval);
+            loop {
+                ticker.tick().await;
+                match self.refresh().await {
+                    Ok(true) => {
+                        let members: Vec<String> =
+                            self.membership.read().await.base_urls.keys().cloned().collect();
+                        info!(node_id = %self.node_id, members = ?members, "AI shard membership changed, resharding");
+                        self.rebalance(&state).await;
+                    }
+                    Ok(false) => {}
+                    Err(e) => warn!(node_id = %self.node_id, error = %e, "failed to refresh AI shard membership"),
+                }
+            }
+        })
+    }
+
+    async fn rebalance(&self, state: &AiServiceState) {
+        for task in state.list_tasks().await {
+            if task.state != AiTaskState::Processing {
+                continue;
+            }
+            let Route::Redirect { node_id, base_url } = self.route(shard_key(&task.config)).await else {
+                continue;
+            };
+            match self.hand_off(state, &task, &base_url).await {
+                Ok(()) => {
+                    info!(task_id = %task.config.id, to = %node_id, "handed AI task to its new owner");
+                    let mut moved = self.moved.write().await;
+                    if moved.len() >= MAX_MOVED_TASKS {
+                        moved.clear();
+                    }
+                    moved.insert(task.config.id.clone(), node_id);
+                }
+                Err(e) => warn!(task_id = %task.config.id, to = %node_id, error = %e, "AI task hand-off failed, keeping it here"),
+            }
+        }
+    }
+
+    /// Stop `task` here and start it on `base_url`. The lease has to be
+    /// released first for the new owner to acquire it; if the new owner
+    /// refuses, the task is restarted here.
+    async fn hand_off(&self, state: &AiServiceState, task: &AiTaskInfo, base_url: &str) -> Result<()> {
+        let task_id = &task.config.id;
+        state.stop_task(task_id).await?;
+        state.forget_task(task_id).await;
+
+        let request = AiTaskStartRequest {
+            config: task.config.clone(),
+            lease_ttl_secs: None,
+        };
+        let sent = self
+            .client
+            .post(format!("{}/v1/tasks", base_url))
+            .header(HANDOFF_HEADER, &self.node_id)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+
+        match sent {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                state
+                    .start_task(task.config.clone(), None)
+                    .await
+                    .context("failed to restart task after hand-off failure")?;
+                Err(e).context("new owner refused the task")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_owner_is_stable() {
+        let ring = HashRing::new(["ai-1", "ai-2", "ai-3"]);
+        let reordered = HashRing::new(["ai-3", "ai-1", "ai-2"]);
+        assert_eq!(ring, reordered);
+        for camera in ["cam-1", "cam-2", "lobby", "parking-east"] {
+            assert!(ring.owner(camera).is_some());
+            assert_eq!(ring.owner(camera), reordered.owner(camera));
+        }
+        assert_eq!(HashRing::default().owner("cam-1"), None);
+    }
+
+    #[test]
+    fn test_join_moves_only_cameras_to_new_node() {
+        let before = HashRing::new(["ai-1", "ai-2", "ai-3"]);
+        let after = HashRing::new(["ai-1", "ai-2", "ai-3", "ai-4"]);
+
+        let cameras: Vec<String> = (0..1000).map(|i| format!("camera-{}", i)).collect();
+        let mut moved = 0;
+        for camera in &cameras {
+            let (old, new) = (before.owner(camera).unwrap(), after.owner(camera).unwrap());
+            if old != new {
+                assert_eq!(new, "ai-4");
+                moved += 1;
+            }
+        }
+        // Roughly a quarter of the cameras move to the new node
+        assert!((150..350).contains(&moved), "moved {}", moved);
+
+        let counts = cameras.iter().fold(HashMap::new(), |mut counts, camera| {
+            *counts.entry(after.owner(camera).unwrap()).or_insert(0) += 1;
+            counts
+        });
+        assert!(counts.values().all(|&n| (150..350).contains(&n)), "{:?}", counts);
+    }
+}
@@ -1,11 +1,35 @@
+use crate::alerts::ViolationAlerter;
+use crate::analysis::Analyzer;
+use crate::anonymize::Anonymizer;
+use crate::batching::FrameBatcher;
+use crate::camera_analytics::CameraAnalyticsSync;
 use crate::coordinator::CoordinatorClient;
-use crate::plugin::registry::PluginRegistry;
+use crate::dedup::{self, Deduplicator};
+use crate::detection_rates::DetectionRateReporter;
+use crate::detections::DetectionRecorder;
+use crate::gpu::{self, GpuMonitor};
+use crate::models::ModelRegistry;
+use crate::motion::{self, MotionGate};
+use crate::onvif::{MetadataFrame, MetadataHub};
+use crate::outbox::Outbox;
+use crate::pipeline::PipelineExecutor;
+use crate::plugin::registry::{PluginRegistry, RestartOutcome};
+use crate::plugin::{AiPlugin, PluginError};
+use crate::scheduler::InferenceScheduler;
+use crate::sharding::Sharding;
+use crate::tracking::{self, Tracker};
+use crate::work_sharing::WorkSharing;
+use crate::zones::{self, ZoneAnalyzer};
 use anyhow::{anyhow, Context, Result};
-use common::ai_tasks::{AiResult, AiTaskConfig, AiTaskInfo, AiTaskState, VideoFrame};
+use common::ai_tasks::{
+    AiResult, AiTaskConfig, AiTaskInfo, AiTaskState, CameraAnalytics, PluginErrorKind, TaskZones,
+    TrackEventKind, VideoFrame,
+};
 use common::leases::{LeaseAcquireRequest, LeaseKind, LeaseReleaseRequest, LeaseRenewRequest};
 use common::state_store::StateStore;
-use std::collections::HashMap;
-use std::sync::Arc;
+use common::timeline::{self, TimelineEvent, TimelineEventKind};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
@@ -14,6 +38,9 @@ use tracing::{error, info, warn};
 const MAX_RENEWAL_RETRIES: u32 = 3;
 const RENEWAL_BACKOFF_BASE_MS: u64 = 100;
 
+/// At most one timeline detection event per task in this interval
+const TIMELINE_DETECTION_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Clone)]
 pub struct AiServiceState {
     inner: Arc<AiServiceStateInner>,
@@ -26,6 +53,28 @@ struct AiServiceStateInner {
     tasks: RwLock<HashMap<String, AiTaskInfo>>,
     renewals: RwLock<HashMap<String, CancellationToken>>,
     state_store: Option<Arc<dyn StateStore>>,
+    metadata: MetadataHub,
+    anonymizer: Arc<Anonymizer>,
+    analyzer: Arc<Analyzer>,
+    sharding: OnceLock<Arc<Sharding>>,
+    work_sharing: OnceLock<Arc<WorkSharing>>,
+    alerter: OnceLock<Arc<ViolationAlerter>>,
+    detection_rates: OnceLock<Arc<DetectionRateReporter>>,
+    batcher: OnceLock<Arc<FrameBatcher>>,
+    outbox: OnceLock<Arc<Outbox>>,
+    detections: OnceLock<Arc<DetectionRecorder>>,
+    models: OnceLock<Arc<ModelRegistry>>,
+    gpu: OnceLock<Arc<GpuMonitor>>,
+    scheduler: OnceLock<Arc<InferenceScheduler>>,
+    camera_analytics: OnceLock<Arc<CameraAnalyticsSync>>,
+    /// Object trackers of the tasks with tracking, by task ID
+    trackers: RwLock<HashMap<String, Tracker>>,
+    /// Zone analytics of the tasks with zones, by task ID
+    zone_analyzers: RwLock<HashMap<String, ZoneAnalyzer>>,
+    /// Recently reported objects of the tasks with dedup, by task ID
+    deduplicators: RwLock<HashMap<String, Deduplicator>>,
+    /// Motion gates of the tasks with `frame_config.motion_gate`, by task ID
+    motion_gates: RwLock<HashMap<String, Arc<std::sync::Mutex<MotionGate>>>>,
 }
 
 impl AiServiceState {
@@ -34,10 +83,28 @@ impl AiServiceState {
             inner: Arc::new(AiServiceStateInner {
                 node_id,
                 coordinator: None,
+                anonymizer: Arc::new(Anonymizer::from_env(plugins.clone())),
+                analyzer: Arc::new(Analyzer::from_env(plugins.clone(), None)),
                 plugins,
                 tasks: RwLock::new(HashMap::new()),
                 renewals: RwLock::new(HashMap::new()),
                 state_store: None,
+                metadata: MetadataHub::new(),
+                sharding: OnceLock::new(),
+                work_sharing: OnceLock::new(),
+                alerter: OnceLock::new(),
+                detection_rates: OnceLock::new(),
+                batcher: OnceLock::new(),
+                outbox: OnceLock::new(),
+                detections: OnceLock::new(),
+                models: OnceLock::new(),
+                gpu: OnceLock::new(),
+                scheduler: OnceLock::new(),
+                camera_analytics: OnceLock::new(),
+                trackers: RwLock::new(HashMap::new()),
+                zone_analyzers: RwLock::new(HashMap::new()),
+                deduplicators: RwLock::new(HashMap::new()),
+                motion_gates: RwLock::new(HashMap::new()),
             }),
         }
     }
@@ -51,10 +118,28 @@ impl AiServiceState {
             inner: Arc::new(AiServiceStateInner {
                 node_id,
                 coordinator: Some(coordinator),
+                anonymizer: Arc::new(Anonymizer::from_env(plugins.clone())),
+                analyzer: Arc::new(Analyzer::from_env(plugins.clone(), None)),
                 plugins,
                 tasks: RwLock::new(HashMap::new()),
                 renewals: RwLock::new(HashMap::new()),
                 state_store: None,
+                metadata: MetadataHub::new(),
+                sharding: OnceLock::new(),
+                work_sharing: OnceLock::new(),
+                alerter: OnceLock::new(),
+                detection_rates: OnceLock::new(),
+                batcher: OnceLock::new(),
+                outbox: OnceLock::new(),
+                detections: OnceLock::new(),
+                models: OnceLock::new(),
+                gpu: OnceLock::new(),
+                scheduler: OnceLock::new(),
+                camera_analytics: OnceLock::new(),
+                trackers: RwLock::new(HashMap::new()),
+                zone_analyzers: RwLock::new(HashMap::new()),
+                deduplicators: RwLock::new(HashMap::new()),
+                motion_gates: RwLock::new(HashMap::new()),
             }),
         }
     }
@@ -69,17 +154,159 @@ impl AiServiceState {
             inner: Arc::new(AiServiceStateInner {
                 node_id,
                 coordinator: Some(coordinator),
+                anonymizer: Arc::new(Anonymizer::from_env(plugins.clone())),
+                analyzer: Arc::new(Analyzer::from_env(plugins.clone(), Some(state_store.clone()))),
                 plugins,
                 tasks: RwLock::new(HashMap::new()),
                 renewals: RwLock::new(HashMap::new()),
                 state_store: Some(state_store),
+                metadata: MetadataHub::new(),
+                sharding: OnceLock::new(),
+                work_sharing: OnceLock::new(),
+                alerter: OnceLock::new(),
+                detection_rates: OnceLock::new(),
+                batcher: OnceLock::new(),
+                outbox: OnceLock::new(),
+                detections: OnceLock::new(),
+                models: OnceLock::new(),
+                gpu: OnceLock::new(),
+                scheduler: OnceLock::new(),
+                camera_analytics: OnceLock::new(),
+                trackers: RwLock::new(HashMap::new()),
+                zone_analyzers: RwLock::new(HashMap::new()),
+                deduplicators: RwLock::new(HashMap::new()),
+                motion_gates: RwLock::new(HashMap::new()),
             }),
         }
     }
 
+    /// Shard cameras across AI nodes; only the first sharding set is kept
+    pub fn set_sharding(&self, sharding: Arc<Sharding>) {
+        let _ = self.inner.sharding.set(sharding);
+    }
+
+    pub fn sharding(&self) -> Option<&Arc<Sharding>> {
+        self.inner.sharding.get()
+    }
+
+    /// Share tasks with the other AI nodes through their leases; only the
+    /// first one set is kept
+    pub fn set_work_sharing(&self, work_sharing: Arc<WorkSharing>) {
+        let _ = self.inner.work_sharing.set(work_sharing);
+    }
+
+    pub fn work_sharing(&self) -> Option<&Arc<WorkSharing>> {
+        self.inner.work_sharing.get()
+    }
+
+    /// Raise violation detections as alert-service triggers; only the first
+    /// alerter set is kept
+    pub fn set_alerter(&self, alerter: Arc<ViolationAlerter>) {
+        let _ = self.inner.alerter.set(alerter);
+    }
+
+    /// Count detections per camera for anomaly detection; only the first
+    /// reporter set is kept
+    pub fn set_detection_rates(&self, reporter: Arc<DetectionRateReporter>) {
+        let _ = self.inner.detection_rates.set(reporter);
+    }
+
+    /// Run frames of plugins with batched inference in micro-batches; only
+    /// the first batcher set is kept
+    pub fn set_batcher(&self, batcher: Arc<FrameBatcher>) {
+        let _ = self.inner.batcher.set(batcher);
+    }
+
+    /// Buffer task states and detection events while the coordinator is
+    /// unreachable; only the first outbox set is kept
+    pub fn set_outbox(&self, outbox: Arc<Outbox>) {
+        let _ = self.inner.outbox.set(outbox);
+    }
+
+    /// Keep the detections of processed frames for `/v1/detections`; only
+    /// the first recorder set is kept
+    pub fn set_detections(&self, recorder: Arc<DetectionRecorder>) {
+        let _ = self.inner.detections.set(recorder);
+    }
+
+    pub fn detections(&self) -> Option<&Arc<DetectionRecorder>> {
+        self.inner.detections.get()
+    }
+
+    /// Serve uploaded model versions at `/v1/models`; only the first
+    /// registry set is kept
+    pub fn set_models(&self, models: Arc<ModelRegistry>) {
+        let _ = self.inner.models.set(models);
+    }
+
+    pub fn models(&self) -> Option<&Arc<ModelRegistry>> {
+        self.inner.models.get()
+    }
+
+    /// Export GPU gauges on `/metrics`; only the first monitor set is kept
+    pub fn set_gpu_monitor(&self, monitor: Arc<GpuMonitor>) {
+        let _ = self.inner.gpu.set(monitor);
+    }
+
+    /// Schedule frames across tasks by priority and rate; without a
+    /// scheduler every frame runs right away. Only the first one set is kept
+    pub fn set_scheduler(&self, scheduler: Arc<InferenceScheduler>) {
+        let _ = self.inner.scheduler.set(scheduler);
+    }
+
+    pub fn scheduler(&self) -> Option<&Arc<InferenceScheduler>> {
+        self.inner.scheduler.get()
+    }
+
+    /// Give tasks the zones and alarm classes operators set up for their
+    /// camera; only the first sync set is kept
+    pub fn set_camera_analytics(&self, sync: Arc<CameraAnalyticsSync>) {
+        let _ = self.inner.camera_analytics.set(sync);
+    }
+
+    /// Refresh the GPU gauges before a scrape
+    pub async fn export_gpu_metrics(&self) {
+        let Some(monitor) = self.inner.gpu.get().cloned() else {
+            return;
+        };
+        let samples = match tokio::task::spawn_blocking(move || monitor.sample()).await {
+            Ok(samples) => samples,
+            Err(e) => {
+                warn!(error = %e, "GPU sampling task failed");
+                return;
+            }
+        };
+        gpu::export(&samples, &self.inner.plugins.gpu_devices().await);
+    }
+
+    /// Run a plugin over one frame, through the plugin's batch queue when it
+    /// batches; `task_config` only reaches unbatched plugins
+    async fn run_plugin(
+        &self,
+        plugin_id: &str,
+        plugin: Arc<RwLock<dyn AiPlugin>>,
+        frame: &VideoFrame,
+        task_config: &serde_json::Value,
+    ) -> Result<AiResult> {
+        let _queued = QueueDepthGuard::enter(plugin_id);
+        let plugin_read = plugin.read().await;
+        if let Some(batcher) = self.inner.batcher.get() {
+            let batch_size = batcher.batch_size(plugin_read.max_batch_size());
+            if batch_size > 1 {
+                drop(plugin_read);
+                return batcher
+                    .submit(plugin_id, plugin, batch_size, frame.clone())
+                    .await;
+            }
+        }
+        plugin_read.process_task_frame(frame, task_config).await
+    }
+
     /// Persist AI task state to StateStore if configured
     async fn persist_task(&self, info: &AiTaskInfo) {
-        if let Some(store) = &self.inner.state_store {
+        if let Some(outbox) = self.inner.outbox.get() {
+            outbox.save_task(info).await;
+        } else if let Some(store) = &self.inner.state_store {
             if let Err(e) = store.save_ai_task(info).await {
                 warn!(task_id = %info.config.id, error = %e, "failed to persist AI task state");
             }
@@ -89,11 +316,26 @@ impl AiServiceState {
     /// Bootstrap: restore state from StateStore on startup
     pub async fn bootstrap(&self) -> Result<()> {
         if let Some(store) = &self.inner.state_store {
-            let tasks = store.list_ai_tasks(Some(&self.inner.node_id)).await?;
+            let outbox = self.inner.outbox.get();
+            let tasks = match store.list_ai_tasks(Some(&self.inner.node_id)).await {
+                Ok(tasks) => tasks,
+                // An edge node restarted during an outage resumes from its outbox
+                Err(e) if outbox.is_some() => {
+                    warn!(error = %e, "StateStore unreachable, restoring AI tasks from the outbox only");
+                    Vec::new()
+                }
+                Err(e) => return Err(e),
+            };
             let mut tasks_map = self.inner.tasks.write().await;
             for task in tasks {
                 tasks_map.insert(task.config.id.clone(), task);
             }
+            // Buffered states are newer than what the StateStore holds
+            if let Some(outbox) = outbox {
+                for task in outbox.buffered_tasks().await {
+                    tasks_map.insert(task.config.id.clone(), task);
+                }
+            }
             info!(node_id = %self.inner.node_id, count = tasks_map.len(), "restored AI tasks from StateStore");
         }
         Ok(())
@@ -107,22 +349,160 @@ impl AiServiceState {
         &self.inner.plugins
     }
 
+    /// Batch anonymization jobs
+    pub fn anonymizer(&self) -> &Arc<Anonymizer> {
+        &self.inner.anonymizer
+    }
+
+    /// Batch analysis jobs over stored recordings
+    pub fn analyzer(&self) -> &Arc<Analyzer> {
+        &self.inner.analyzer
+    }
+
+    /// Processed frames, for ONVIF metadata and live event subscribers
+    pub fn metadata(&self) -> &MetadataHub {
+        &self.inner.metadata
+    }
+
     pub async fn get_task(&self, task_id: &str) -> Option<AiTaskInfo> {
         let tasks = self.inner.tasks.read().await;
         tasks.get(task_id).cloned()
     }
 
+    /// Node a task not held here runs on, according to the StateStore
+    pub async fn stored_task_node(&self, task_id: &str) -> Option<String> {
+        let store = self.inner.state_store.as_ref()?;
+        match store.get_ai_task(task_id).await {
+            Ok(task) => task.and_then(|t| t.node_id),
+            Err(e) => {
+                warn!(task_id = %task_id, error = %e, "failed to look up AI task in StateStore");
+                None
+            }
+        }
+    }
+
+    /// Drop a stopped task from this node, e.g. after handing it to another node
+    pub async fn forget_task(&self, task_id: &str) -> Option<AiTaskInfo> {
+        self.inner.trackers.write().await.remove(task_id);
+        self.inner.zone_analyzers.write().await.remove(task_id);
+        self.inner.deduplicators.write().await.remove(task_id);
+        self.inner.motion_gates.write().await.remove(task_id);
+        self.inner.tasks.write().await.remove(task_id)
+    }
+
     pub async fn list_tasks(&self) -> Vec<AiTaskInfo> {
         let tasks = self.inner.tasks.read().await;
         tasks.values().cloned().collect()
     }
 
+    /// Every task in the StateStore, whichever node holds it
+    pub async fn stored_tasks(&self) -> Result<Vec<AiTaskInfo>> {
+        let store = self.inner.state_store.as_ref().context("StateStore is not enabled")?;
+        store.list_ai_tasks(None).await
+    }
+
+    /// Whether this node holds the lease of a task and keeps renewing it
+    pub async fn holds_lease(&self, task_id: &str) -> bool {
+        self.inner.renewals.read().await.contains_key(task_id)
+    }
+
+    /// Number of tasks whose leases this node holds
+    pub async fn leased_task_count(&self) -> usize {
+        self.inner.renewals.read().await.len()
+    }
+
+    /// Start a stored task no node holds the lease of. Fails when another
+    /// node acquires the lease first.
+    pub async fn adopt_task(&self, config: AiTaskConfig, lease_ttl_secs: Option<u64>) -> Result<String> {
+        if !self.holds_lease(&config.id).await {
+            // A copy restored at startup or left behind when the lease was lost
+            self.forget_task(&config.id).await;
+        }
+        self.start_task(config, lease_ttl_secs).await
+    }
+
+    /// Store a task without a node or lease for a less busy node to adopt
+    pub async fn queue_task(&self, config: AiTaskConfig) -> Result<String> {
+        let store = self.inner.state_store.as_ref().context("StateStore is not enabled")?;
+        let task_id = config.id.clone();
+        if self.get_task(&task_id).await.is_some() {
+            return Err(anyhow!("Task '{}' already exists", task_id));
+        }
+        if let Some(existing) = store.get_ai_task(&task_id).await? {
+            if !matches!(existing.state, AiTaskState::Stopped | AiTaskState::Error) {
+                return Err(anyhow!("Task '{}' already exists", task_id));
+            }
+        }
+        self.validate_task(&config).await?;
+
+        let task_info = AiTaskInfo {
+            config,
+            state: AiTaskState::Pending,
+            node_id: None,
+            lease_id: None,
+            last_error: None,
+            last_error_kind: None,
+            started_at: None,
+            stopped_at: None,
+            last_processed_frame: None,
+            frames_processed: 0,
+            detections_made: 0,
+        };
+        store.save_ai_task(&task_info).await?;
+        info!("Queued AI task {} for another node", task_id);
+        Ok(task_id)
+    }
+
+    /// Stop a queued task no node has adopted yet; false when there is none
+    async fn unqueue_task(&self, task_id: &str) -> Result<bool> {
+        let Some(store) = &self.inner.state_store else {
+            return Ok(false);
+        };
+        let Some(mut task) = store.get_ai_task(task_id).await? else {
+            return Ok(false);
+        };
+        if task.node_id.is_some() || task.state != AiTaskState::Pending {
+            return Ok(false);
+        }
+        task.state = AiTaskState::Stopped;
+        task.stopped_at = Some(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        );
+        store.save_ai_task(&task).await?;
+        Ok(true)
+    }
+
+    /// Stop processing a task whose lease ended. `persist` records the
+    /// failure in the StateStore; it is skipped when another node may hold
+    /// the task now.
+    async fn lease_ended(&self, task_id: &str, reason: &str, persist: bool) {
+        self.inner.renewals.write().await.remove(task_id);
+        let info = {
+            let mut tasks = self.inner.tasks.write().await;
+            let Some(task) = tasks.get_mut(task_id) else {
+                return;
+            };
+            task.state = AiTaskState::Error;
+            task.last_error = Some(reason.to_string());
+            task.clone()
+        };
+        if persist {
+            self.persist_task(&info).await;
+        }
+    }
+
     pub async fn start_task(
         &self,
-        config: AiTaskConfig,
+        mut config: AiTaskConfig,
         lease_ttl_secs: Option<u64>,
     ) -> Result<String> {
         let task_id = config.id.clone();
+        if let Some(sync) = self.inner.camera_analytics.get() {
+            sync.apply(&mut config);
+        }
 
         // Check if task already exists
         {
@@ -132,10 +512,7 @@ impl AiServiceState {
             }
         }
 
-        // Verify plugin exists
-        if !self.inner.plugins.has_plugin(&config.plugin_type).await {
-            return Err(anyhow!("Plugin '{}' not found", config.plugin_type));
-        }
+        self.validate_task(&config).await?;
 
         // Acquire lease from coordinator if available
         let lease_id = if let Some(coordinator) = &self.inner.coordinator {
@@ -151,6 +528,10 @@ impl AiServiceState {
                 .acquire(&request)
                 .await
                 .context("Failed to acquire lease for AI task")?;
+            if !response.granted {
+                let holder = response.record.map(|r| r.holder_id).unwrap_or_default();
+                return Err(anyhow!("Task '{}' is leased to node '{}'", task_id, holder));
+            }
 
             response.record.map(|r| r.lease_id)
         } else {
@@ -164,6 +545,7 @@ impl AiServiceState {
             node_id: Some(self.inner.node_id.clone()),
             lease_id: lease_id.clone(),
             last_error: None,
+            last_error_kind: None,
             started_at: Some(
                 std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
@@ -200,6 +582,47 @@ impl AiServiceState {
         Ok(task_id)
     }
 
+    /// Check that a task's plugins exist and its settings are valid
+    async fn validate_task(&self, config: &AiTaskConfig) -> Result<()> {
+        // Verify plugin exists
+        if !self.inner.plugins.has_plugin(&config.plugin_type).await {
+            return Err(anyhow!("Plugin '{}' not found", config.plugin_type));
+        }
+        for secondary in &config.secondary_plugins {
+            let plugin = self.inner.plugins.get(secondary).await
+                .context(format!("Secondary plugin '{}' not found", secondary))?;
+            if !plugin.read().await.is_secondary() {
+                return Err(anyhow!("Plugin '{}' cannot run as a secondary plugin", secondary));
+            }
+        }
+        if let Some(pipeline) = &config.pipeline {
+            PipelineExecutor::new(&self.inner.plugins, pipeline)?
+                .validate(&config.plugin_type)
+                .await?;
+        }
+        if let Some(gate) = &config.frame_config.motion_gate {
+            motion::validate(gate)?;
+        }
+        if let Some(fps) = config.schedule.as_ref().and_then(|s| s.max_inference_fps) {
+            if !fps.is_finite() || fps <= 0.0 {
+                return Err(anyhow!("schedule.max_inference_fps must be above 0"));
+            }
+        }
+        if let Some(classes) = &config.classes {
+            classes.validate().map_err(anyhow::Error::msg)?;
+        }
+        if let Some(tracking) = &config.tracking {
+            tracking::validate(tracking)?;
+        }
+        if let Some(task_zones) = &config.zones {
+            zones::validate(task_zones, config.tracking.is_some())?;
+        }
+        if let Some(dedup) = &config.dedup {
+            dedup::validate(dedup)?;
+        }
+        Ok(())
+    }
+
     pub async fn stop_task(&self, task_id: &str) -> Result<()> {
         // Cancel renewal loop
         {
@@ -241,13 +664,89 @@ impl AiServiceState {
                 }
             }
 
+            // A restarted task starts its tracks, zone presence, reported
+            // objects and motion reference over
+            self.inner.trackers.write().await.remove(task_id);
+            self.inner.zone_analyzers.write().await.remove(task_id);
+            self.inner.deduplicators.write().await.remove(task_id);
+            self.inner.motion_gates.write().await.remove(task_id);
+            if let Some(scheduler) = self.inner.scheduler.get() {
+                scheduler.remove_task(task_id);
+            }
+
             info!("Stopped AI task: {}", task_id);
             Ok(())
+        } else if self.unqueue_task(task_id).await? {
+            info!("Stopped queued AI task: {}", task_id);
+            Ok(())
         } else {
             Err(anyhow!("Task '{}' not found", task_id))
         }
     }
 
+    /// Replace a task's zones and tripwires, or remove them with `None`;
+    /// objects are followed from scratch. `Ok(None)` when the task is not
+    /// on this node.
+    pub async fn set_task_zones(&self, task_id: &str, task_zones: Option<TaskZones>) -> Result<Option<AiTaskInfo>> {
+        let task_zones = task_zones.filter(|z| !z.is_empty());
+        let info = {
+            let mut tasks = self.inner.tasks.write().await;
+            let Some(task) = tasks.get_mut(task_id) else {
+                return Ok(None);
+            };
+            if let Some(task_zones) = &task_zones {
+                zones::validate(task_zones, task.config.tracking.is_some())?;
+            }
+            task.config.zones = task_zones;
+            task.clone()
+        };
+        self.inner.zone_analyzers.write().await.remove(task_id);
+        self.persist_task(&info).await;
+        info!(
+            task_id = %task_id,
+            zones = info.config.zones.as_ref().map_or(0, |z| z.zones.len()),
+            tripwires = info.config.zones.as_ref().map_or(0, |z| z.tripwires.len()),
+            "Updated task zones"
+        );
+        Ok(Some(info))
+    }
+
+    /// Replace the classes a task raises alerts for; empty raises every
+    /// class. `None` when the task is not on this node.
+    pub async fn set_task_alarm_classes(&self, task_id: &str, alarm_classes: Vec<String>) -> Option<AiTaskInfo> {
+        let info = {
+            let mut tasks = self.inner.tasks.write().await;
+            let task = tasks.get_mut(task_id)?;
+            task.config.alarm_classes = alarm_classes;
+            task.clone()
+        };
+        self.persist_task(&info).await;
+        Some(info)
+    }
+
+    /// Apply a camera's operator-set zones and alarm classes to its tasks on
+    /// this node, or clear them with `None`
+    pub async fn apply_camera_analytics(&self, camera_id: &str, analytics: Option<&CameraAnalytics>) {
+        let tasks: Vec<(String, bool)> = {
+            let tasks = self.inner.tasks.read().await;
+            tasks
+                .values()
+                .filter(|t| t.config.source_stream_id.as_deref() == Some(camera_id))
+                .map(|t| (t.config.id.clone(), t.config.tracking.is_some()))
+                .collect()
+        };
+        for (task_id, tracking) in tasks {
+            let task_zones = analytics.map(|a| a.task_zones(tracking));
+            if let Err(e) = self.set_task_zones(&task_id, task_zones).await {
+                warn!(task_id = %task_id, camera_id = %camera_id, error = %e, "failed to apply camera zones");
+                continue;
+            }
+            let alarm_classes = analytics.map(|a| a.alarm_classes.clone()).unwrap_or_default();
+            self.set_task_alarm_classes(&task_id, alarm_classes).await;
+            info!(task_id = %task_id, camera_id = %camera_id, "Applied camera analytics");
+        }
+    }
+
     async fn update_task_state(&self, task_id: &str, new_state: AiTaskState) -> Result<()> {
         let info_to_persist = {
             let mut tasks = self.inner.tasks.write().await;
@@ -279,6 +778,58 @@ impl AiServiceState {
         }
     }
 
+    /// Record a failure of the task's plugin on the task and apply the
+    /// registry's restart policy. The task goes to the error state when its
+    /// configuration is wrong, or when its plugin is unusable and could not
+    /// be restarted; a lack of resources or a bad frame only fails the frame.
+    async fn plugin_failed(
+        &self,
+        task_id: &str,
+        plugin_id: &str,
+        failed_at: std::time::Instant,
+        error: &anyhow::Error,
+    ) -> PluginError {
+        let error = PluginError::from_anyhow(error);
+        let kind = error.kind();
+        telemetry::metrics::AI_SERVICE_PLUGIN_ERRORS
+            .with_label_values(&[plugin_id, kind.as_str()])
+            .inc();
+        telemetry::metrics::AI_SERVICE_FRAMES_PROCESSED
+            .with_label_values(&[plugin_id, "error"])
+            .inc();
+
+        let mut last_error = error.to_string();
+        let mut fails_task = kind == PluginErrorKind::Config;
+        match self.inner.plugins.handle_failure(plugin_id, kind, failed_at).await {
+            RestartOutcome::GaveUp(reason) => {
+                error!(plugin = %plugin_id, "Could not restart AI plugin: {}", reason);
+                last_error = format!("{} (plugin not restarted: {})", last_error, reason);
+                fails_task |= kind == PluginErrorKind::Fatal;
+            }
+            RestartOutcome::Restarted | RestartOutcome::NotRestarted => {}
+        }
+        warn!(task_id = %task_id, plugin = %plugin_id, kind = kind.as_str(), "Plugin failed: {}", last_error);
+
+        let info_to_persist = {
+            let mut tasks = self.inner.tasks.write().await;
+            tasks.get_mut(task_id).and_then(|task| {
+                task.last_error = Some(last_error);
+                task.last_error_kind = Some(kind);
+                if fails_task && task.state == AiTaskState::Processing {
+                    task.state = AiTaskState::Error;
+                    Some(task.clone())
+                } else {
+                    None
+                }
+            })
+        };
+        if let Some(info) = info_to_persist {
+            error!(task_id = %task_id, kind = kind.as_str(), "AI task failed");
+            self.persist_task(&info).await;
+        }
+        error
+    }
+
     /// Process a video frame for a specific task
     pub async fn process_frame(&self, task_id: &str, frame: VideoFrame) -> Result<AiResult> {
         // Get task info
@@ -293,21 +844,209 @@ impl AiServiceState {
             return Err(anyhow!("Task '{}' is not in processing state (current: {:?})", task_id, task_info.state));
         }
 
+        // Frames over the task's rate are skipped
+        let schedule = task_info.config.schedule.clone().unwrap_or_default();
+        let scheduler = self.inner.scheduler.get();
+        let max_fps = schedule
+            .max_inference_fps
+            .or(task_info.config.frame_config.max_fps.map(|fps| fps as f32));
+        if scheduler.is_some_and(|s| !s.due(task_id, schedule.priority, max_fps)) {
+            telemetry::metrics::AI_SERVICE_FRAMES_PROCESSED
+                .with_label_values(&[&task_info.config.plugin_type, "throttled"])
+                .inc();
+            return Ok(AiResult {
+                task_id: task_id.to_string(),
+                timestamp: frame.timestamp,
+                plugin_type: task_info.config.plugin_type.clone(),
+                detections: Vec::new(),
+                confidence: None,
+                processing_time_ms: Some(0),
+                metadata: Some(serde_json::json!({ "schedule": {"throttled": true} })),
+            });
+        }
+
+        // Frames without motion since the last analyzed one skip inference;
+        // frames carrying audio are always analyzed, sounds need no motion
+        let mut motion_score = None;
+        let motion_gate = task_info.config.frame_config.motion_gate.as_ref().filter(|_| frame.audio.is_none());
+        if let Some(config) = motion_gate {
+            let gate_start = std::time::Instant::now();
+            let gate = self
+                .inner
+                .motion_gates
+                .write()
+                .await
+                .entry(task_id.to_string())
+                .or_insert_with(|| Arc::new(std::sync::Mutex::new(MotionGate::new(config.clone()))))
+                .clone();
+            let check = gate.lock().unwrap_or_else(|e| e.into_inner()).check(&frame);
+            if !check.analyze {
+                telemetry::metrics::AI_SERVICE_FRAMES_PROCESSED
+                    .with_label_values(&[&task_info.config.plugin_type, "skipped"])
+                    .inc();
+                return Ok(AiResult {
+                    task_id: task_id.to_string(),
+                    timestamp: frame.timestamp,
+                    plugin_type: task_info.config.plugin_type.clone(),
+                    detections: Vec::new(),
+                    confidence: None,
+                    processing_time_ms: Some(gate_start.elapsed().as_millis() as u64),
+                    metadata: Some(serde_json::json!({
+                        "motion_gate": {"skipped": true, "motion_score": check.score}
+                    })),
+                });
+            }
+            motion_score = check.score;
+        }
+
+        // Once the node is saturated, frames wait for an inference slot or
+        // are shed by priority
+        let permit = match scheduler {
+            Some(scheduler) => match scheduler.acquire(task_id, schedule.priority).await {
+                Ok(permit) => Some(permit),
+                Err(shed) => {
+                    telemetry::metrics::AI_SERVICE_FRAMES_PROCESSED
+                        .with_label_values(&[&task_info.config.plugin_type, "shed"])
+                        .inc();
+                    return Err(shed.into());
+                }
+            },
+            None => None,
+        };
+
         // Get the plugin
         let plugin = self.inner.plugins.get(&task_info.config.plugin_type).await
             .context(format!("Plugin '{}' not found", task_info.config.plugin_type))?;
 
         // Process frame with plugin
-        let plugin_read = plugin.read().await;
         let start_time = std::time::Instant::now();
-        let mut result = plugin_read.process_frame(&frame).await
-            .context("Failed to process frame with plugin")?;
+        let mut result = match self
+            .run_plugin(
+                &task_info.config.plugin_type,
+                plugin,
+                &frame,
+                &task_info.config.model_config,
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                let error = self
+                    .plugin_failed(task_id, &task_info.config.plugin_type, start_time, &e)
+                    .await;
+                return Err(anyhow::Error::from(error).context("Failed to process frame with plugin"));
+            }
+        };
+
+        // Later pipeline stages run on crops of the earlier stages' detections
+        if let Some(pipeline) = &task_info.config.pipeline {
+            match PipelineExecutor::new(&self.inner.plugins, pipeline) {
+                Ok(executor) => {
+                    let first_stage_ms = start_time.elapsed().as_millis() as u64;
+                    executor.run(&frame, &mut result, first_stage_ms).await;
+                }
+                Err(e) => warn!(task_id = %task_id, "Invalid pipeline: {:#}", e),
+            }
+        }
+
+        // Run the secondary plugins of the pipeline over the primary result;
+        // a failing stage leaves the result as the previous stage produced it
+        for secondary in &task_info.config.secondary_plugins {
+            let plugin = match self.inner.plugins.get(secondary).await {
+                Ok(plugin) => plugin,
+                Err(e) => {
+                    warn!(task_id = %task_id, plugin = %secondary, "Secondary plugin unavailable: {}", e);
+                    continue;
+                }
+            };
+            let enriched = plugin.read().await.enrich(&frame, &mut result).await;
+            if let Err(e) = enriched {
+                let kind = PluginError::kind_of(&e);
+                telemetry::metrics::AI_SERVICE_PLUGIN_ERRORS
+                    .with_label_values(&[secondary, kind.as_str()])
+                    .inc();
+                warn!(task_id = %task_id, plugin = %secondary, kind = kind.as_str(), "Secondary plugin failed: {:#}", e);
+            }
+        }
         let processing_time = start_time.elapsed().as_millis() as u64;
-        drop(plugin_read);
+        drop(permit);
+
+        // Rename and filter classes once, ahead of tracking and every output
+        if let Some(classes) = &task_info.config.classes {
+            let dropped = classes.apply(&mut result.detections);
+            telemetry::metrics::AI_SERVICE_DETECTIONS_FILTERED
+                .with_label_values(&[&task_info.config.plugin_type])
+                .inc_by(dropped as u64);
+        }
+
+        // Track objects over the final detections of the pipeline
+        let mut track_events = Vec::new();
+        if let Some(config) = &task_info.config.tracking {
+            track_events = self
+                .inner
+                .trackers
+                .write()
+                .await
+                .entry(task_id.to_string())
+                .or_insert_with(|| Tracker::new(config.clone()))
+                .update(&mut result);
+            for event in &track_events {
+                telemetry::metrics::AI_SERVICE_TRACK_EVENTS
+                    .with_label_values(&[&task_info.config.plugin_type, event_label(event.event)])
+                    .inc();
+            }
+        }
+
+        // Zone entries/exits and tripwire crossings of the tracked objects
+        let mut zone_events = Vec::new();
+        if let Some(task_zones) = &task_info.config.zones {
+            zone_events = self
+                .inner
+                .zone_analyzers
+                .write()
+                .await
+                .entry(task_id.to_string())
+                .or_insert_with(|| ZoneAnalyzer::new(task_zones.clone(), task_info.config.tracking.is_some()))
+                .update(&mut result, &track_events);
+            for event in &zone_events {
+                telemetry::metrics::AI_SERVICE_ZONE_EVENTS
+                    .with_label_values(&[&task_info.config.plugin_type, event.event.as_str()])
+                    .inc();
+            }
+        }
 
         // Override task_id to match the actual task (plugin may use frame.source_id)
         result.task_id = task_id.to_string();
 
+        if task_info.config.frame_config.motion_gate.is_some() {
+            let gate = serde_json::json!({"skipped": false, "motion_score": motion_score});
+            match result.metadata.as_mut().and_then(|m| m.as_object_mut()) {
+                Some(metadata) => {
+                    metadata.insert("motion_gate".to_string(), gate);
+                }
+                None => result.metadata = Some(serde_json::json!({ "motion_gate": gate })),
+            }
+        }
+
+        // Repeated detections of objects that stay in view are left out of
+        // alerts, records and the timeline; live overlays still show them
+        let detected = result.detections.len();
+        let mut overlay = None;
+        if let Some(config) = &task_info.config.dedup {
+            overlay = Some(result.clone());
+            let suppressed = self
+                .inner
+                .deduplicators
+                .write()
+                .await
+                .entry(task_id.to_string())
+                .or_insert_with(|| Deduplicator::new(config.clone()))
+                .update(&mut result);
+            telemetry::metrics::AI_SERVICE_DETECTIONS_DEDUPLICATED
+                .with_label_values(&[&task_info.config.plugin_type])
+                .inc_by(suppressed as u64);
+        }
+
         // Update task stats
         let detections_count = result.detections.len() as u64;
         self.update_task_stats(task_id, 1, detections_count).await;
@@ -327,6 +1066,43 @@ impl AiServiceState {
             "Processed frame"
         );
 
+        let camera = task_info
+            .config
+            .source_stream_id
+            .clone()
+            .unwrap_or_else(|| frame.source_id.clone());
+        if !result.detections.is_empty() {
+            match self.inner.outbox.get().filter(|outbox| outbox.buffers_events()) {
+                Some(outbox) => {
+                    if timeline::sample(task_id, TIMELINE_DETECTION_INTERVAL) {
+                        outbox.push_event(detection_event(&result, camera.clone())).await;
+                    }
+                }
+                None => timeline::record_sampled(
+                    task_id,
+                    TIMELINE_DETECTION_INTERVAL,
+                    detection_event(&result, camera.clone()),
+                ),
+            }
+        }
+        if let Some(alerter) = self.inner.alerter.get() {
+            alerter.raise_violations(&task_info, &result.detections).await;
+            alerter.raise_zone_events(&task_info, &zone_events).await;
+        }
+        // Rates measure scene activity, duplicates included
+        if let Some(reporter) = self.inner.detection_rates.get() {
+            reporter.record(&task_info, detected).await;
+        }
+        if let Some(recorder) = self.inner.detections.get() {
+            recorder.record(&task_info, &result, camera.clone()).await;
+        }
+        self.inner.metadata.publish(MetadataFrame {
+            camera,
+            width: frame.width,
+            height: frame.height,
+            result: overlay.unwrap_or_else(|| result.clone()),
+        });
+
         Ok(result)
     }
 
@@ -365,6 +1141,14 @@ impl AiServiceState {
                         };
 
                         match coordinator.renew(&request).await {
+                            Ok(response) if !response.renewed => {
+                                // Expired or taken over: another node may run
+                                // the task now, so this one stops processing it
+                                // and leaves the stored task to that node
+                                warn!("Lease lost for task {}, stopping it", task_id);
+                                state.lease_ended(&task_id, "lease lost to another node", false).await;
+                                break;
+                            }
                             Ok(_) => {
                                 consecutive_failures = 0;
                             }
@@ -377,7 +1161,10 @@ impl AiServiceState {
 
                                 if consecutive_failures >= MAX_RENEWAL_RETRIES {
                                     error!("Max renewal retries exceeded for task: {}", task_id);
-                                    let _ = state.update_task_state(&task_id, AiTaskState::Error).await;
+                                    // With work sharing the stored task stays
+                                    // claimable for when the lease lapses
+                                    let persist = state.work_sharing().is_none();
+                                    state.lease_ended(&task_id, "lease renewal failed", persist).await;
                                     break;
                                 }
 
@@ -424,3 +1211,67 @@ impl AiServiceState {
         Ok(())
     }
 }
+
+/// Counts a frame in `ai_service_plugin_queue_depth` while it waits for or
+/// runs through a plugin
+struct QueueDepthGuard(prometheus::IntGauge);
+
+impl QueueDepthGuard {
+    fn enter(plugin_id: &str) -> Self {
+        let gauge = telemetry::metrics::AI_SERVICE_PLUGIN_QUEUE_DEPTH.with_label_values(&[plugin_id]);
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for QueueDepthGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+fn event_label(event: TrackEventKind) -> &'static str {
+    match event {
+        TrackEventKind::Created => "created",
+        TrackEventKind::Updated => "updated",
+        TrackEventKind::Lost => "lost",
+    }
+}
+
+/// Timeline summary of a frame's detections, e.g. "2 person, 1 car"
+fn detection_event(result: &AiResult, camera: String) -> TimelineEvent {
+    let mut classes: BTreeMap<&str, usize> = BTreeMap::new();
+    for detection in &result.detections {
+        *classes.entry(detection.class.as_str()).or_default() += 1;
+    }
+    let summary = classes
+        .iter()
+        .map(|(class, count)| format!("{count} {class}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // Vehicle descriptions from the vehicle_attributes secondary plugin,
+    // paired with the plate number on LPR detections
+    let vehicles: Vec<serde_json::Value> = result
+        .detections
+        .iter()
+        .filter_map(|d| {
+            let metadata = d.metadata.as_ref()?;
+            let description = metadata.get("vehicle_description")?;
+            Some(serde_json::json!({
+                "description": description,
+                "plate_number": metadata.get("plate_number"),
+            }))
+        })
+        .collect();
+    let mut details = serde_json::json!({
+        "task_id": result.task_id,
+        "plugin_type": result.plugin_type,
+        "classes": classes,
+    });
+    if !vehicles.is_empty() {
+        details["vehicles"] = serde_json::json!(vehicles);
+    }
+    TimelineEvent::new(TimelineEventKind::Detection, "ai-service", summary)
+        .camera(camera)
+        .details(details)
+}
@@ -0,0 +1,478 @@
+//! Object tracking across the frames of a task.
+//!
+//! A [`Tracker`] per task keeps the objects of recent frames and gives each
+//! detection continuing one of them the same track ID, so downstream
+//! analytics (dwell time, counting, line crossing) can follow an object.
+//! Tracks move with a constant-velocity model; every frame the predicted
+//! boxes are matched to the detections of the same class by IoU, greedily
+//! from the best overlap down.
+//!
+//! With [`TrackingAlgorithm::ByteTrack`] the low-confidence detections left
+//! over by the first pass get a second chance to match a track, so an object
+//! partly hidden behind another keeps its ID instead of being lost and
+//! recreated. SORT ignores them.
+//!
+//! A track is reported once it was matched in `min_hits` consecutive frames,
+//! which filters out one-frame false positives, and lost after `max_age`
+//! frames without a match. Each tracked detection gets `metadata.track_id`
+//! and the result lists the lifecycle events under `metadata.tracks`.
+
+use anyhow::{bail, Result};
+use common::ai_tasks::{
+    AiResult, BoundingBox, TrackEvent, TrackEventKind, TrackingAlgorithm, TrackingConfig,
+};
+
+/// Check a task's tracking settings before it starts
+pub fn validate(config: &TrackingConfig) -> Result<()> {
+    if !(config.iou_threshold > 0.0 && config.iou_threshold <= 1.0) {
+        bail!("tracking.iou_threshold must be in (0, 1]");
+    }
+    if config.low_threshold > config.high_threshold {
+        bail!("tracking.low_threshold must not exceed tracking.high_threshold");
+    }
+    if config.min_hits == 0 {
+        bail!("tracking.min_hits must be at least 1");
+    }
+    Ok(())
+}
+
+/// Box by center and size, in pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct TrackBox {
+    cx: f32,
+    cy: f32,
+    w: f32,
+    h: f32,
+}
+
+impl TrackBox {
+    fn from_bbox(bbox: &BoundingBox) -> Self {
+        Self {
+            cx: bbox.x as f32 + bbox.width as f32 / 2.0,
+            cy: bbox.y as f32 + bbox.height as f32 / 2.0,
+            w: bbox.width as f32,
+            h: bbox.height as f32,
+        }
+    }
+
+    fn iou(&self, other: &TrackBox) -> f32 {
+        let overlap_w = (self.cx + self.w / 2.0).min(other.cx + other.w / 2.0)
+            - (self.cx - self.w / 2.0).max(other.cx - other.w / 2.0);
+        let overlap_h = (self.cy + self.h / 2.0).min(other.cy + other.h / 2.0)
+            - (self.cy - self.h / 2.0).max(other.cy - other.h / 2.0);
+        if overlap_w <= 0.0 || overlap_h <= 0.0 {
+            return 0.0;
+        }
+        let intersection = overlap_w * overlap_h;
+        let union = self.w * self.h + other.w * other.h - intersection;
+        if union <= 0.0 {
+            0.0
+        } else {
+            intersection / union
+        }
+    }
+}
+
+struct Track {
+    /// Assigned when the track is reported
+    id: Option<u64>,
+    class: String,
+    /// Last matched box
+    last: TrackBox,
+    bbox: BoundingBox,
+    /// Center movement per frame
+    velocity: (f32, f32),
+    confidence: f32,
+    hits: u32,
+    /// Frames since the last match
+    misses: u32,
+    first_seen: u64,
+    last_seen: u64,
+}
+
+impl Track {
+    fn new(class: &str, bbox: &BoundingBox, confidence: f32, timestamp: u64) -> Self {
+        Self {
+            id: None,
+            class: class.to_string(),
+            last: TrackBox::from_bbox(bbox),
+            bbox: bbox.clone(),
+            velocity: (0.0, 0.0),
+            confidence,
+            hits: 1,
+            misses: 0,
+            first_seen: timestamp,
+            last_seen: timestamp,
+        }
+    }
+
+    /// Where the track is expected in the current frame
+    fn predicted(&self) -> TrackBox {
+        let frames = (self.misses + 1) as f32;
+        TrackBox {
+            cx: self.last.cx + self.velocity.0 * frames,
+            cy: self.last.cy + self.velocity.1 * frames,
+            ..self.last
+        }
+    }
+
+    fn matched(&mut self, bbox: &BoundingBox, confidence: f32, timestamp: u64) {
+        let measured = TrackBox::from_bbox(bbox);
+        let frames = (self.misses + 1) as f32;
+        let velocity = (
+            (measured.cx - self.last.cx) / frames,
+            (measured.cy - self.last.cy) / frames,
+        );
+        // Smooth the velocity so one jittery box does not throw the
+        // prediction off; the first movement is taken as is
+        self.velocity = if self.hits == 1 {
+            velocity
+        } else {
+            (
+                (self.velocity.0 + velocity.0) / 2.0,
+                (self.velocity.1 + velocity.1) / 2.0,
+            )
+        };
+        self.last = measured;
+        self.bbox = bbox.clone();
+        self.confidence = confidence;
+        self.hits += 1;
+        self.misses = 0;
+        self.last_seen = timestamp;
+    }
+
+    fn event(&self, event: TrackEventKind) -> Option<TrackEvent> {
+        Some(TrackEvent {
+            track_id: self.id?,
+            event,
+            class: self.class.clone(),
+            bbox: self.bbox.clone(),
+            confidence: self.confidence,
+            first_seen: self.first_seen,
+            last_seen: self.last_seen,
+            hits: self.hits,
+        })
+    }
+}
+
+/// Tracks of one task
+pub struct Tracker {
+    config: TrackingConfig,
+    tracks: Vec<Track>,
+    next_id: u64,
+    last_timestamp: Option<u64>,
+}
+
+impl Tracker {
+    pub fn new(config: TrackingConfig) -> Self {
+        Self {
+            config,
+            tracks: Vec::new(),
+            next_id: 1,
+            last_timestamp: None,
+        }
+    }
+
+    /// Tracks currently followed, reported or not
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// Match the result's detections to the tracks, tag tracked detections
+    /// with `metadata.track_id` and add the track events under
+    /// `metadata.tracks`. A frame older than the last one is left untouched.
+    pub fn update(&mut self, result: &mut AiResult) -> Vec<TrackEvent> {
+        let timestamp = result.timestamp;
+        if self.last_timestamp.is_some_and(|last| timestamp < last) {
+            return Vec::new();
+        }
+        self.last_timestamp = Some(timestamp);
+
+        let tracked = |class: &str| {
+            self.config.classes.is_empty() || self.config.classes.iter().any(|c| c == class)
+        };
+        let mut high = Vec::new();
+        let mut low = Vec::new();
+        for (index, detection) in result.detections.iter().enumerate() {
+            if !tracked(&detection.class) {
+                continue;
+            }
+            if detection.confidence >= self.config.high_threshold {
+                high.push(index);
+            } else if self.config.algorithm == TrackingAlgorithm::ByteTrack
+                && detection.confidence >= self.config.low_threshold
+            {
+                low.push(index);
+            }
+        }
+
+        let predicted: Vec<TrackBox> = self.tracks.iter().map(Track::predicted).collect();
+        let mut track_match: Vec<Option<usize>> = vec![None; self.tracks.len()];
+        let mut detection_matched = vec![false; result.detections.len()];
+        for candidates in [&high, &low] {
+            // Best overlaps first among the tracks and detections still free
+            let mut pairs = Vec::new();
+            for (t, track) in self.tracks.iter().enumerate() {
+                if track_match[t].is_some() {
+                    continue;
+                }
+                for &d in candidates.iter() {
+                    let detection = &result.detections[d];
+                    if detection_matched[d] || detection.class != track.class {
+                        continue;
+                    }
+                    let iou = predicted[t].iou(&TrackBox::from_bbox(&detection.bbox));
+                    if iou >= self.config.iou_threshold {
+                        pairs.push((iou, t, d));
+                    }
+                }
+            }
+            pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+            for (_, t, d) in pairs {
+                if track_match[t].is_none() && !detection_matched[d] {
+                    track_match[t] = Some(d);
+                    detection_matched[d] = true;
+                }
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut assigned = Vec::new();
+        let mut kept = Vec::with_capacity(self.tracks.len());
+        for (mut track, matched) in std::mem::take(&mut self.tracks).into_iter().zip(track_match) {
+            match matched {
+                Some(d) => {
+                    let detection = &result.detections[d];
+                    track.matched(&detection.bbox, detection.confidence, timestamp);
+                    self.report(&mut track, d, &mut events, &mut assigned);
+                    kept.push(track);
+                }
+                // A track not reported yet must match in consecutive frames
+                None if track.id.is_none() => {}
+                None => {
+                    track.misses += 1;
+                    if track.misses > self.config.max_age {
+                        events.extend(track.event(TrackEventKind::Lost));
+                    } else {
+                        kept.push(track);
+                    }
+                }
+            }
+        }
+        for d in high {
+            if !detection_matched[d] {
+                let detection = &result.detections[d];
+                let mut track = Track::new(&detection.class, &detection.bbox, detection.confidence, timestamp);
+                self.report(&mut track, d, &mut events, &mut assigned);
+                kept.push(track);
+            }
+        }
+        self.tracks = kept;
+
+        for (d, track_id) in assigned {
+            let detection = &mut result.detections[d];
+            match &mut detection.metadata {
+                Some(serde_json::Value::Object(metadata)) => {
+                    metadata.insert("track_id".to_string(), track_id.into());
+                }
+                None => detection.metadata = Some(serde_json::json!({ "track_id": track_id })),
+                // Plugin metadata that is not an object is left as is
+                Some(_) => {}
+            }
+        }
+        if !events.is_empty() {
+            let tracks = serde_json::to_value(&events).unwrap_or_default();
+            match &mut result.metadata {
+                Some(serde_json::Value::Object(metadata)) => {
+                    metadata.insert("tracks".to_string(), tracks);
+                }
+                None => result.metadata = Some(serde_json::json!({ "tracks": tracks })),
+                Some(_) => {}
+            }
+        }
+        events
+    }
+
+    /// Give a matched track its ID once it has enough hits, noting the
+    /// event and the ID for detection `d`
+    fn report(
+        &mut self,
+        track: &mut Track,
+        d: usize,
+        events: &mut Vec<TrackEvent>,
+        assigned: &mut Vec<(usize, u64)>,
+    ) {
+        let kind = match track.id {
+            Some(_) => TrackEventKind::Updated,
+            None if track.hits >= self.config.min_hits => {
+                track.id = Some(self.next_id);
+                self.next_id += 1;
+                TrackEventKind::Created
+            }
+            None => return,
+        };
+        if let Some(event) = track.event(kind) {
+            assigned.push((d, event.track_id));
+            events.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::Detection;
+
+    fn detection(class: &str, x: u32, y: u32, confidence: f32) -> Detection {
+        Detection {
+            class: class.to_string(),
+            confidence,
+            bbox: BoundingBox {
+                x,
+                y,
+                width: 50,
+                height: 100,
+            },
+            metadata: None,
+        }
+    }
+
+    fn frame(timestamp: u64, detections: Vec<Detection>) -> AiResult {
+        AiResult {
+            task_id: "task-1".to_string(),
+            timestamp,
+            plugin_type: "mock_object_detector".to_string(),
+            detections,
+            confidence: None,
+            processing_time_ms: None,
+            metadata: None,
+        }
+    }
+
+    fn track_id(detection: &Detection) -> Option<u64> {
+        detection.metadata.as_ref()?.get("track_id")?.as_u64()
+    }
+
+    fn kinds(events: &[TrackEvent]) -> Vec<(u64, TrackEventKind)> {
+        events.iter().map(|e| (e.track_id, e.event)).collect()
+    }
+
+    #[test]
+    fn keeps_ids_of_moving_objects() {
+        let mut tracker = Tracker::new(TrackingConfig {
+            min_hits: 2,
+            ..Default::default()
+        });
+
+        // Two people walking in opposite directions
+        let mut first = frame(0, vec![detection("person", 100, 100, 0.9), detection("person", 400, 100, 0.9)]);
+        assert!(tracker.update(&mut first).is_empty());
+        assert_eq!(track_id(&first.detections[0]), None);
+
+        let mut ids = Vec::new();
+        for step in 1..6u32 {
+            let mut result = frame(
+                step as u64 * 100,
+                vec![
+                    detection("person", 400 - step * 20, 100, 0.9),
+                    detection("person", 100 + step * 20, 100, 0.9),
+                ],
+            );
+            let events = tracker.update(&mut result);
+            let expected = if step == 1 { TrackEventKind::Created } else { TrackEventKind::Updated };
+            assert!(events.iter().all(|e| e.event == expected));
+            ids.push((track_id(&result.detections[0]).unwrap(), track_id(&result.detections[1]).unwrap()));
+            assert!(result.metadata.as_ref().unwrap()["tracks"].is_array());
+        }
+        assert!(ids.windows(2).all(|w| w[0] == w[1]));
+        assert_ne!(ids[0].0, ids[0].1);
+    }
+
+    #[test]
+    fn loses_tracks_after_max_age() {
+        let mut tracker = Tracker::new(TrackingConfig {
+            min_hits: 1,
+            max_age: 2,
+            ..Default::default()
+        });
+
+        let mut result = frame(0, vec![detection("car", 10, 10, 0.8)]);
+        assert_eq!(kinds(&tracker.update(&mut result)), vec![(1, TrackEventKind::Created)]);
+
+        // Two empty frames are tolerated, the third loses the track
+        for timestamp in [1, 2] {
+            assert!(tracker.update(&mut frame(timestamp, vec![])).is_empty());
+        }
+        let events = tracker.update(&mut frame(3, vec![]));
+        assert_eq!(kinds(&events), vec![(1, TrackEventKind::Lost)]);
+        assert_eq!(events[0].last_seen, 0);
+        assert!(tracker.is_empty());
+
+        // The car coming back is a new track
+        let mut result = frame(4, vec![detection("car", 10, 10, 0.8)]);
+        assert_eq!(kinds(&tracker.update(&mut result)), vec![(2, TrackEventKind::Created)]);
+    }
+
+    #[test]
+    fn byte_track_keeps_tracks_through_low_confidence() {
+        for (algorithm, survives) in [(TrackingAlgorithm::ByteTrack, true), (TrackingAlgorithm::Sort, false)] {
+            let mut tracker = Tracker::new(TrackingConfig {
+                algorithm,
+                min_hits: 1,
+                max_age: 0,
+                ..Default::default()
+            });
+            tracker.update(&mut frame(0, vec![detection("person", 100, 100, 0.9)]));
+
+            // Partly occluded: the detector is unsure
+            let mut occluded = frame(1, vec![detection("person", 105, 100, 0.2)]);
+            let events = tracker.update(&mut occluded);
+            assert_eq!(track_id(&occluded.detections[0]).is_some(), survives, "{:?}", algorithm);
+            assert_eq!(events.iter().any(|e| e.event == TrackEventKind::Lost), !survives);
+        }
+    }
+
+    #[test]
+    fn tracks_only_configured_classes_and_matches_within_class() {
+        let mut tracker = Tracker::new(TrackingConfig {
+            min_hits: 1,
+            classes: vec!["person".to_string(), "dog".to_string()],
+            ..Default::default()
+        });
+
+        let mut result = frame(0, vec![detection("person", 100, 100, 0.9), detection("car", 100, 100, 0.9)]);
+        tracker.update(&mut result);
+        assert_eq!(track_id(&result.detections[0]), Some(1));
+        assert_eq!(track_id(&result.detections[1]), None);
+
+        // A dog where the person was does not take over the person's track
+        let mut result = frame(1, vec![detection("dog", 100, 100, 0.9)]);
+        tracker.update(&mut result);
+        assert_eq!(track_id(&result.detections[0]), Some(2));
+    }
+
+    #[test]
+    fn ignores_frames_out_of_order() {
+        let mut tracker = Tracker::new(TrackingConfig {
+            min_hits: 1,
+            ..Default::default()
+        });
+        tracker.update(&mut frame(10, vec![detection("person", 100, 100, 0.9)]));
+
+        let mut late = frame(5, vec![detection("person", 100, 100, 0.9)]);
+        assert!(tracker.update(&mut late).is_empty());
+        assert!(late.detections[0].metadata.is_none());
+    }
+
+    #[test]
+    fn validates_config() {
+        assert!(validate(&TrackingConfig::default()).is_ok());
+        assert!(validate(&TrackingConfig { iou_threshold: 0.0, ..Default::default() }).is_err());
+        assert!(validate(&TrackingConfig { low_threshold: 0.9, ..Default::default() }).is_err());
+        assert!(validate(&TrackingConfig { min_hits: 0, ..Default::default() }).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Work sharing across ai-service nodes through coordinator leases.
+//!
+//! A task runs where its lease is held, and the StateStore keeps every
+//! task's configuration. Each node periodically looks for stored tasks that
+//! should be running but whose lease nobody holds (their node died, was cut
+//! off from the coordinator or never adopted them) and adopts them by
+//! acquiring the lease. The coordinator grants it to one node only, so
+//! exactly one node processes each task.
+//!
+//! Only nodes registered with the coordinator (`NODE_ADVERTISE_URL`) and
+//! not draining adopt tasks, up to their fair share of the running tasks;
+//! a node at its share queues newly started tasks for the others instead of
+//! running them. With camera sharding on, the ring decides instead: only the
+//! owner of a task's camera adopts it.
+
+use crate::sharding::{self, shard_key, Route};
+use crate::state::AiServiceState;
+use anyhow::{Context, Result};
+use common::ai_tasks::{AiTaskInfo, AiTaskState};
+use common::leases::{LeaseKind, LeaseRecord};
+use reqwest::Url;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Whether a stored task is meant to be running somewhere
+pub fn wants_node(state: AiTaskState) -> bool {
+    matches!(
+        state,
+        AiTaskState::Pending | AiTaskState::Initializing | AiTaskState::Processing
+    )
+}
+
+/// Tasks each of `members` nodes should run of `total`, rounded up
+pub fn fair_share(total: usize, members: usize) -> usize {
+    total.div_ceil(members.max(1))
+}
+
+/// Stored tasks meant to be running that no node holds the lease of
+pub fn orphans<'a>(tasks: &'a [AiTaskInfo], leased: &HashSet<String>) -> Vec<&'a AiTaskInfo> {
+    tasks
+        .iter()
+        .filter(|t| wants_node(t.state) && !leased.contains(&t.config.id))
+        .collect()
+}
+
+/// This node's share of the AI work
+pub struct WorkSharing {
+    node_id: String,
+    coordinator: Url,
+    client: reqwest::Client,
+    interval: Duration,
+    lease_ttl_secs: u64,
+    /// Base URL of each node taking work, by node id
+    members: RwLock<BTreeMap<String, String>>,
+    /// Tasks meant to be running at the last pass, on any node
+    running: AtomicUsize,
+}
+
+impl WorkSharing {
+    pub fn new(node_id: String, coordinator: Url, interval: Duration, lease_ttl_secs: u64) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            node_id,
+            coordinator,
+            client,
+            interval,
+            lease_ttl_secs,
+            members: RwLock::new(BTreeMap::new()),
+            running: AtomicUsize::new(0),
+        }
+    }
+
+    /// Work sharing configured from `AI_WORK_SHARING_ENABLED`,
+    /// `AI_WORK_SHARING_INTERVAL_SECS` and `AI_WORK_SHARING_LEASE_TTL_SECS`;
+    /// it needs a coordinator to hold the leases
+    pub fn from_env(node_id: &str, coordinator: Option<&Url>) -> Option<Self> {
+        let enabled = std::env::var("AI_WORK_SHARING_ENABLED")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let coordinator = coordinator.filter(|_| enabled)?;
+        let secs = |var: &str, default: u64| {
+            std::env::var(var)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(default)
+                .max(1)
+        };
+        Some(Self::new(
+            node_id.to_string(),
+            coordinator.clone(),
+            Duration::from_secs(secs("AI_WORK_SHARING_INTERVAL_SECS", 10)),
+            secs("AI_WORK_SHARING_LEASE_TTL_SECS", 30),
+        ))
+    }
+
+    /// TTL of the leases of tasks started or adopted here: how long a task
+    /// of a failed node waits before another node takes it over
+    pub fn lease_ttl_secs(&self) -> u64 {
+        self.lease_ttl_secs
+    }
+
+    /// Base URL of another member
+    pub async fn member_url(&self, node_id: &str) -> Option<String> {
+        if node_id == self.node_id {
+            return None;
+        }
+        self.members.read().await.get(node_id).cloned()
+    }
+
+    /// Whether a task started on this node should be left to a less busy
+    /// one, judged by the membership and task count of the last pass
+    pub async fn should_queue(&self, state: &AiServiceState) -> bool {
+        if state.sharding().is_some() {
+            return false;
+        }
+        let members = self.members.read().await.len();
+        if members < 2 {
+            return false;
+        }
+        let share = fair_share(self.running.load(Ordering::Relaxed) + 1, members);
+        state.leased_task_count().await >= share
+    }
+
+    /// Live AI task leases, by task id
+    async fn leased(&self) -> Result<HashSet<String>> {
+        let url = self
+            .coordinator
+            .join(&format!("v1/leases?kind={}", LeaseKind::Ai))
+            .context("invalid coordinator endpoint")?;
+        let leases: Vec<LeaseRecord> = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("lease list request failed")?
+            .error_for_status()
+            .context("lease list returned error status")?
+            .json()
+            .await
+            .context("failed to parse lease list")?;
+        Ok(leases.into_iter().map(|l| l.resource_id).collect())
+    }
+
+    /// Adopt the orphaned tasks that fall to this node; returns how many
+    pub async fn run_pass(&self, state: &AiServiceState) -> Result<usize> {
+        let members = sharding::ai_members(&self.client, &self.coordinator).await?;
+        let member_count = members.len();
+        // Unregistered or draining nodes take no new work
+        let takes_work = members.contains_key(&self.node_id);
+        *self.members.write().await = members;
+
+        let tasks = state.stored_tasks().await?;
+        let leased = self.leased().await?;
+        let running = tasks.iter().filter(|t| wants_node(t.state)).count();
+        self.running.store(running, Ordering::Relaxed);
+
+        let mut orphans = orphans(&tasks, &leased);
+        if !takes_work || orphans.is_empty() {
+            return Ok(0);
+        }
+        // Nodes try the orphans in different orders so they rarely race for one
+        orphans.sort_by_key(|t| sharding::hash(&format!("{}/{}", self.node_id, t.config.id)));
+
+        let share = fair_share(running, member_count);
+        let mut held = state.leased_task_count().await;
+        let mut adopted = 0;
+        for task in orphans {
+            let falls_here = match state.sharding() {
+                Some(sharding) => sharding.route(shard_key(&task.config)).await == Route::Local,
+                None => held < share,
+            };
+            if !falls_here {
+                continue;
+            }
+            let task_id = &task.config.id;
+            match state.adopt_task(task.config.clone(), Some(self.lease_ttl_secs)).await {
+                Ok(_) => {
+                    info!(task_id = %task_id, previous_node = ?task.node_id, "adopted AI task without a lease holder");
+                    held += 1;
+                    adopted += 1;
+                }
+                // Usually another node got the lease first
+                Err(e) => debug!(task_id = %task_id, error = %e, "did not adopt AI task"),
+            }
+        }
+        Ok(adopted)
+    }
+
+    /// Look for orphaned tasks periodically
+    pub fn spawn(self: Arc<Self>, state: AiServiceState) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.run_pass(&state).await {
+                    warn!(node_id = %self.node_id, error = %e, "AI work sharing pass failed");
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::AiTaskConfig;
+
+    fn task(id: &str, state: AiTaskState) -> AiTaskInfo {
+        let config: AiTaskConfig = serde_json::from_value(serde_json::json!({
+            "id": id,
+            "plugin_type": "mock_object_detector",
+            "output": { "type": "file" },
+        }))
+        .unwrap();
+        AiTaskInfo {
+            config,
+            state,
+            node_id: None,
+            lease_id: None,
+            last_error: None,
+            last_error_kind: None,
+            started_at: None,
+            stopped_at: None,
+            last_processed_frame: None,
+            frames_processed: 0,
+            detections_made: 0,
+        }
+    }
+
+    #[test]
+    fn test_orphans_are_unleased_tasks_meant_to_run() {
+        let tasks = vec![
+            task("leased", AiTaskState::Processing),
+            task("dead-node", AiTaskState::Processing),
+            task("queued", AiTaskState::Pending),
+            task("stopped", AiTaskState::Stopped),
+            task("failed", AiTaskState::Error),
+        ];
+        let leased = HashSet::from(["leased".to_string()]);
+        let ids: Vec<&str> = orphans(&tasks, &leased).iter().map(|t| t.config.id.as_str()).collect();
+        assert_eq!(ids, vec!["dead-node", "queued"]);
+    }
+
+    #[test]
+    fn test_fair_share_rounds_up() {
+        assert_eq!(fair_share(0, 3), 0);
+        assert_eq!(fair_share(7, 3), 3);
+        assert_eq!(fair_share(6, 3), 2);
+        // No registered members yet: this node takes everything
+        assert_eq!(fair_share(4, 0), 4);
+    }
+}
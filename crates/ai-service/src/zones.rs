@@ -0,0 +1,450 @@
+//! Zone and line-crossing analytics over the detections of a task.
+//!
+//! A [`ZoneAnalyzer`] per task follows where each object stands (the bottom
+//! center of its box) and reports it entering and leaving the task's
+//! polygon zones and crossing its tripwires. Objects are told apart by the
+//! track IDs of [`crate::tracking`]: a track leaves its zones when it is
+//! lost, not when it is missed in one frame. Without tracking, zones follow
+//! each class as a whole (entered by its first object, exited when none is
+//! left) and tripwires are not available.
+//!
+//! The events are listed under `metadata.zone_events` of the result.
+
+use anyhow::Result;
+use common::ai_tasks::{
+    AiResult, AnalyticsZone, BoundingBox, CrossingDirection, Detection, TaskZones, TrackEvent,
+    TrackEventKind, ZoneEvent, ZoneEventKind, ZonePoint,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Check a task's zones before they are applied; `tracking` is whether the
+/// task tracks objects
+pub fn validate(zones: &TaskZones, tracking: bool) -> Result<()> {
+    zones.validate(tracking).map_err(anyhow::Error::msg)
+}
+
+/// Where an object stands: the bottom center of its box
+fn anchor(bbox: &BoundingBox) -> ZonePoint {
+    ZonePoint {
+        x: bbox.x as f32 + bbox.width as f32 / 2.0,
+        y: (bbox.y + bbox.height) as f32,
+    }
+}
+
+/// Even-odd rule
+fn contains(polygon: &[ZonePoint], point: ZonePoint) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Which side of the line through `from` and `to` a point is on: positive
+/// on the right looking from `from` to `to` on screen (y grows downwards)
+fn side(from: ZonePoint, to: ZonePoint, point: ZonePoint) -> f32 {
+    (to.x - from.x) * (point.y - from.y) - (to.y - from.y) * (point.x - from.x)
+}
+
+/// Direction an object moving from `previous` to `current` crossed the
+/// segment `from`-`to` in, if it did. A point on the line counts as right
+/// of it, so an object stopping on the line crosses once.
+fn crossing(from: ZonePoint, to: ZonePoint, previous: ZonePoint, current: ZonePoint) -> Option<CrossingDirection> {
+    let was_right = side(from, to, previous) >= 0.0;
+    let is_right = side(from, to, current) >= 0.0;
+    if was_right == is_right {
+        return None;
+    }
+    // The movement must pass between the ends of the wire
+    if side(previous, current, from) * side(previous, current, to) > 0.0 {
+        return None;
+    }
+    Some(if is_right {
+        CrossingDirection::LeftToRight
+    } else {
+        CrossingDirection::RightToLeft
+    })
+}
+
+fn reports(classes: &[String], class: &str) -> bool {
+    classes.is_empty() || classes.iter().any(|c| c == class)
+}
+
+/// Object a zone follows
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Object {
+    Track(u64),
+    /// Every object of a class, for tasks without tracking
+    Class(String),
+}
+
+struct Presence {
+    class: String,
+    bbox: BoundingBox,
+    /// IDs of the zones the object is in
+    zones: BTreeSet<String>,
+}
+
+/// Zones and tripwires of one task, and where its objects were
+pub struct ZoneAnalyzer {
+    zones: TaskZones,
+    tracking: bool,
+    inside: BTreeMap<Object, Presence>,
+    /// Last anchor of each track, for tripwires
+    anchors: HashMap<u64, ZonePoint>,
+    last_timestamp: Option<u64>,
+}
+
+impl ZoneAnalyzer {
+    pub fn new(zones: TaskZones, tracking: bool) -> Self {
+        Self {
+            zones,
+            tracking,
+            inside: BTreeMap::new(),
+            anchors: HashMap::new(),
+            last_timestamp: None,
+        }
+    }
+
+    /// Compare the objects of the result with the previous frames, add the
+    /// zone events under `metadata.zone_events` and return them.
+    /// `track_events` are the tracker's events of the same frame; lost
+    /// tracks leave their zones. A frame older than the last one is left
+    /// untouched.
+    pub fn update(&mut self, result: &mut AiResult, track_events: &[TrackEvent]) -> Vec<ZoneEvent> {
+        let timestamp = result.timestamp;
+        if self.last_timestamp.is_some_and(|last| timestamp < last) {
+            return Vec::new();
+        }
+        self.last_timestamp = Some(timestamp);
+
+        let mut events = Vec::new();
+        let mut seen: BTreeMap<Object, Presence> = BTreeMap::new();
+        for detection in &result.detections {
+            let object = match track_id(detection) {
+                Some(track_id) => Object::Track(track_id),
+                // Detections not (yet) tracked are left out of tracked tasks
+                None if self.tracking => continue,
+                None => Object::Class(detection.class.clone()),
+            };
+            let point = anchor(&detection.bbox);
+            if let Object::Track(track_id) = object {
+                if let Some(previous) = self.anchors.insert(track_id, point) {
+                    self.crossings(detection, track_id, previous, point, timestamp, &mut events);
+                }
+            }
+            let zones = self
+                .zones
+                .zones
+                .iter()
+                .filter(|z| reports(&z.classes, &detection.class) && contains(&z.polygon, point))
+                .map(|z| z.id.clone());
+            match seen.get_mut(&object) {
+                Some(presence) => presence.zones.extend(zones),
+                None => {
+                    seen.insert(
+                        object,
+                        Presence {
+                            class: detection.class.clone(),
+                            bbox: detection.bbox.clone(),
+                            zones: zones.collect(),
+                        },
+                    );
+                }
+            }
+        }
+
+        // Tracks missed in this frame stay where they were until lost
+        let lost: HashSet<u64> = track_events
+            .iter()
+            .filter(|e| e.event == TrackEventKind::Lost)
+            .map(|e| e.track_id)
+            .collect();
+        let mut previous = std::mem::take(&mut self.inside);
+        for (object, now) in seen {
+            let before = previous.remove(&object).map(|p| p.zones).unwrap_or_default();
+            for zone_id in before.difference(&now.zones) {
+                events.push(self.zone_event(ZoneEventKind::Exited, zone_id, &object, &now, timestamp));
+            }
+            for zone_id in now.zones.difference(&before) {
+                events.push(self.zone_event(ZoneEventKind::Entered, zone_id, &object, &now, timestamp));
+            }
+            self.inside.insert(object, now);
+        }
+        for (object, before) in previous {
+            let gone = match &object {
+                Object::Track(track_id) => lost.contains(track_id),
+                Object::Class(_) => true,
+            };
+            if !gone {
+                self.inside.insert(object, before);
+                continue;
+            }
+            for zone_id in &before.zones {
+                events.push(self.zone_event(ZoneEventKind::Exited, zone_id, &object, &before, timestamp));
+            }
+        }
+        for track_id in &lost {
+            self.anchors.remove(track_id);
+        }
+        // Objects outside every zone need no entry
+        self.inside.retain(|_, presence| !presence.zones.is_empty());
+
+        if !events.is_empty() {
+            let zone_events = serde_json::to_value(&events).unwrap_or_default();
+            match &mut result.metadata {
+                Some(serde_json::Value::Object(metadata)) => {
+                    metadata.insert("zone_events".to_string(), zone_events);
+                }
+                None => result.metadata = Some(serde_json::json!({ "zone_events": zone_events })),
+                // Plugin metadata that is not an object is left as is
+                Some(_) => {}
+            }
+        }
+        events
+    }
+
+    fn crossings(
+        &self,
+        detection: &Detection,
+        track_id: u64,
+        previous: ZonePoint,
+        current: ZonePoint,
+        timestamp: u64,
+        events: &mut Vec<ZoneEvent>,
+    ) {
+        for wire in &self.zones.tripwires {
+            if !reports(&wire.classes, &detection.class) {
+                continue;
+            }
+            let Some(direction) = crossing(wire.from, wire.to, previous, current) else {
+                continue;
+            };
+            if wire.direction.is_some_and(|d| d != direction) {
+                continue;
+            }
+            events.push(ZoneEvent {
+                event: ZoneEventKind::Crossed,
+                zone_id: wire.id.clone(),
+                zone_name: wire.name.clone(),
+                class: detection.class.clone(),
+                track_id: Some(track_id),
+                direction: Some(direction),
+                bbox: detection.bbox.clone(),
+                timestamp,
+            });
+        }
+    }
+
+    fn zone_event(
+        &self,
+        event: ZoneEventKind,
+        zone_id: &str,
+        object: &Object,
+        presence: &Presence,
+        timestamp: u64,
+    ) -> ZoneEvent {
+        ZoneEvent {
+            event,
+            zone_id: zone_id.to_string(),
+            zone_name: self.zone(zone_id).and_then(|z| z.name.clone()),
+            class: presence.class.clone(),
+            track_id: match object {
+                Object::Track(track_id) => Some(*track_id),
+                Object::Class(_) => None,
+            },
+            direction: None,
+            bbox: presence.bbox.clone(),
+            timestamp,
+        }
+    }
+
+    fn zone(&self, zone_id: &str) -> Option<&AnalyticsZone> {
+        self.zones.zones.iter().find(|z| z.id == zone_id)
+    }
+}
+
+fn track_id(detection: &Detection) -> Option<u64> {
+    detection.metadata.as_ref()?.get("track_id")?.as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ai_tasks::Tripwire;
+
+    fn point(x: f32, y: f32) -> ZonePoint {
+        ZonePoint { x, y }
+    }
+
+    fn square(id: &str, x: f32, y: f32, size: f32) -> AnalyticsZone {
+        AnalyticsZone {
+            id: id.to_string(),
+            name: Some(format!("Zone {}", id)),
+            polygon: vec![point(x, y), point(x + size, y), point(x + size, y + size), point(x, y + size)],
+            classes: Vec::new(),
+        }
+    }
+
+    /// A 20x40 box standing at (`x`, `y`)
+    fn detection(class: &str, x: u32, y: u32, track_id: Option<u64>) -> Detection {
+        Detection {
+            class: class.to_string(),
+            confidence: 0.9,
+            bbox: BoundingBox {
+                x: x - 10,
+                y: y - 40,
+                width: 20,
+                height: 40,
+            },
+            metadata: track_id.map(|id| serde_json::json!({ "track_id": id })),
+        }
+    }
+
+    fn frame(timestamp: u64, detections: Vec<Detection>) -> AiResult {
+        AiResult {
+            task_id: "task-1".to_string(),
+            timestamp,
+            plugin_type: "mock_object_detector".to_string(),
+            detections,
+            confidence: None,
+            processing_time_ms: None,
+            metadata: None,
+        }
+    }
+
+    fn lost(track_id: u64) -> TrackEvent {
+        TrackEvent {
+            track_id,
+            event: TrackEventKind::Lost,
+            class: "person".to_string(),
+            bbox: BoundingBox { x: 0, y: 0, width: 1, height: 1 },
+            confidence: 0.9,
+            first_seen: 0,
+            last_seen: 0,
+            hits: 1,
+        }
+    }
+
+    fn kinds(events: &[ZoneEvent]) -> Vec<(ZoneEventKind, &str, Option<u64>)> {
+        events.iter().map(|e| (e.event, e.zone_id.as_str(), e.track_id)).collect()
+    }
+
+    #[test]
+    fn tracks_enter_and_leave_zones() {
+        let zones = TaskZones {
+            zones: vec![square("dock", 100.0, 100.0, 100.0)],
+            tripwires: Vec::new(),
+        };
+        let mut analyzer = ZoneAnalyzer::new(zones, true);
+
+        assert!(analyzer.update(&mut frame(0, vec![detection("person", 50, 150, Some(1))]), &[]).is_empty());
+        let mut result = frame(1, vec![detection("person", 150, 150, Some(1))]);
+        let events = analyzer.update(&mut result, &[]);
+        assert_eq!(kinds(&events), vec![(ZoneEventKind::Entered, "dock", Some(1))]);
+        assert_eq!(events[0].zone_name.as_deref(), Some("Zone dock"));
+        assert!(result.metadata.as_ref().unwrap()["zone_events"].is_array());
+
+        // Missed for a frame is not leaving; walking out or being lost is
+        assert!(analyzer.update(&mut frame(2, vec![]), &[]).is_empty());
+        let events = analyzer.update(&mut frame(3, vec![detection("person", 250, 150, Some(1))]), &[]);
+        assert_eq!(kinds(&events), vec![(ZoneEventKind::Exited, "dock", Some(1))]);
+
+        analyzer.update(&mut frame(4, vec![detection("person", 150, 150, Some(2))]), &[]);
+        let events = analyzer.update(&mut frame(5, vec![]), &[lost(2)]);
+        assert_eq!(kinds(&events), vec![(ZoneEventKind::Exited, "dock", Some(2))]);
+
+        // Untracked detections of a tracked task are ignored
+        assert!(analyzer.update(&mut frame(6, vec![detection("person", 150, 150, None)]), &[]).is_empty());
+    }
+
+    #[test]
+    fn untracked_zones_follow_classes() {
+        let mut zone = square("yard", 0.0, 0.0, 100.0);
+        zone.classes = vec!["car".to_string()];
+        let mut analyzer = ZoneAnalyzer::new(TaskZones { zones: vec![zone], tripwires: Vec::new() }, false);
+
+        let events = analyzer.update(
+            &mut frame(0, vec![detection("car", 50, 50, None), detection("person", 50, 50, None)]),
+            &[],
+        );
+        assert_eq!(kinds(&events), vec![(ZoneEventKind::Entered, "yard", None)]);
+        // A second car changes nothing, the last one leaving exits
+        assert!(analyzer
+            .update(&mut frame(1, vec![detection("car", 50, 50, None), detection("car", 60, 60, None)]), &[])
+            .is_empty());
+        let events = analyzer.update(&mut frame(2, vec![]), &[]);
+        assert_eq!(kinds(&events), vec![(ZoneEventKind::Exited, "yard", None)]);
+    }
+
+    #[test]
+    fn reports_tripwire_crossings_by_direction() {
+        // Vertical wire pointing down the screen: its left is x > 100
+        let wire = |direction| Tripwire {
+            id: "gate".to_string(),
+            name: None,
+            from: point(100.0, 0.0),
+            to: point(100.0, 200.0),
+            direction,
+            classes: Vec::new(),
+        };
+        let zones = TaskZones {
+            zones: Vec::new(),
+            tripwires: vec![wire(None)],
+        };
+        let mut analyzer = ZoneAnalyzer::new(zones, true);
+
+        analyzer.update(&mut frame(0, vec![detection("person", 150, 100, Some(7))]), &[]);
+        let events = analyzer.update(&mut frame(1, vec![detection("person", 50, 100, Some(7))]), &[]);
+        assert_eq!(kinds(&events), vec![(ZoneEventKind::Crossed, "gate", Some(7))]);
+        assert_eq!(events[0].direction, Some(CrossingDirection::LeftToRight));
+
+        // Passing beyond the end of the wire is no crossing
+        analyzer.update(&mut frame(2, vec![detection("person", 50, 300, Some(7))]), &[]);
+        assert!(analyzer.update(&mut frame(3, vec![detection("person", 150, 300, Some(7))]), &[]).is_empty());
+
+        let one_way = TaskZones {
+            zones: Vec::new(),
+            tripwires: vec![wire(Some(CrossingDirection::LeftToRight))],
+        };
+        let mut analyzer = ZoneAnalyzer::new(one_way, true);
+        analyzer.update(&mut frame(0, vec![detection("person", 50, 100, Some(1))]), &[]);
+        assert!(analyzer.update(&mut frame(1, vec![detection("person", 150, 100, Some(1))]), &[]).is_empty());
+    }
+
+    #[test]
+    fn validates_zones() {
+        let zones = TaskZones {
+            zones: vec![square("a", 0.0, 0.0, 10.0)],
+            tripwires: vec![Tripwire {
+                id: "b".to_string(),
+                name: None,
+                from: point(0.0, 0.0),
+                to: point(10.0, 0.0),
+                direction: None,
+                classes: Vec::new(),
+            }],
+        };
+        assert!(validate(&zones, true).is_ok());
+        assert!(validate(&zones, false).is_err());
+
+        let mut duplicate = zones.clone();
+        duplicate.tripwires[0].id = "a".to_string();
+        assert!(validate(&duplicate, true).is_err());
+
+        let mut line = zones.clone();
+        line.zones[0].polygon.truncate(2);
+        assert!(validate(&line, true).is_err());
+
+        let mut outside = zones;
+        outside.zones[0].polygon[0].x = -1.0;
+        assert!(validate(&outside, true).is_err());
+    }
+}
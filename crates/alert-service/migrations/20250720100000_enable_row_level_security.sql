@@ -0,0 +1,36 @@
+-- Row-level security: each connection only sees the rows of the tenant in
+-- the app.tenant_id setting (see common::tenant_rls), or every row when it is
+-- '*'. Connections without the setting see nothing. FORCE applies the
+-- policies to the table owner too; superusers still bypass them.
+
+ALTER TABLE alert_rules ENABLE ROW LEVEL SECURITY;
+ALTER TABLE alert_rules FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON alert_rules
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id::TEXT = current_setting('app.tenant_id', true)
+    );
+
+ALTER TABLE alert_events ENABLE ROW LEVEL SECURITY;
+ALTER TABLE alert_events FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON alert_events
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id::TEXT = current_setting('app.tenant_id', true)
+    );
+
+-- Per-rule tables follow the visibility of their rule
+ALTER TABLE alert_actions ENABLE ROW LEVEL SECURITY;
+ALTER TABLE alert_actions FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON alert_actions
+    USING (EXISTS (SELECT 1 FROM alert_rules r WHERE r.id = alert_actions.rule_id));
+
+ALTER TABLE alert_notifications ENABLE ROW LEVEL SECURITY;
+ALTER TABLE alert_notifications FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON alert_notifications
+    USING (EXISTS (SELECT 1 FROM alert_events e WHERE e.id = alert_notifications.event_id));
+
+ALTER TABLE alert_suppression_state ENABLE ROW LEVEL SECURITY;
+ALTER TABLE alert_suppression_state FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON alert_suppression_state
+    USING (EXISTS (SELECT 1 FROM alert_rules r WHERE r.id = alert_suppression_state.rule_id));
@@ -0,0 +1,23 @@
+-- Learned baselines of camera metrics (detection rate, stream bitrate), one
+-- row per camera, metric and UTC hour of day. mean and variance are
+-- exponentially weighted; samples counts the observations folded in.
+CREATE TABLE IF NOT EXISTS anomaly_baselines (
+    tenant_id UUID NOT NULL,
+    camera_id VARCHAR(255) NOT NULL,
+    metric VARCHAR(50) NOT NULL,
+    hour_of_day SMALLINT NOT NULL CHECK (hour_of_day BETWEEN 0 AND 23),
+    mean DOUBLE PRECISION NOT NULL,
+    variance DOUBLE PRECISION NOT NULL,
+    samples BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (tenant_id, camera_id, metric, hour_of_day)
+);
+
+ALTER TABLE anomaly_baselines ENABLE ROW LEVEL SECURITY;
+ALTER TABLE anomaly_baselines FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON anomaly_baselines
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id::TEXT = current_setting('app.tenant_id', true)
+    );
@@ -0,0 +1,24 @@
+-- Per-tenant email and SMS senders. When a tenant has a row for a channel
+-- type, its alerts are sent through it instead of the global SMTP_*/TWILIO_*
+-- settings. config_json holds the non-secret settings (host, account,
+-- sender identity); the SMTP password or Twilio auth token is kept in
+-- secret_encrypted, sealed with ALERT_CHANNEL_MASTER_KEY.
+CREATE TABLE IF NOT EXISTS notification_channels (
+    tenant_id UUID NOT NULL,
+    channel_type VARCHAR(20) NOT NULL,
+    config_json JSONB NOT NULL,
+    secret_encrypted TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (tenant_id, channel_type)
+);
+
+ALTER TABLE notification_channels ENABLE ROW LEVEL SECURITY;
+ALTER TABLE notification_channels FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON notification_channels
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id::TEXT = current_setting('app.tenant_id', true)
+    );
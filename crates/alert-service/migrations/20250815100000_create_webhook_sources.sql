@@ -0,0 +1,31 @@
+-- External systems (access control, intrusion panels) posting events to
+-- POST /v1/inbox/events. Each source has its own token; only its SHA-256
+-- is stored, so a token is shown once when the source is created or its
+-- token is rotated. Events of a source are raised as external_event
+-- triggers in the source's tenant.
+CREATE TABLE IF NOT EXISTS webhook_sources (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    -- Free-form system type, e.g. access_control, intrusion
+    kind VARCHAR(64) NOT NULL,
+    -- Camera events are tied to when they do not name one
+    camera_id VARCHAR(255),
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    last_event_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    UNIQUE(tenant_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_sources_tenant ON webhook_sources(tenant_id);
+
+ALTER TABLE webhook_sources ENABLE ROW LEVEL SECURITY;
+ALTER TABLE webhook_sources FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON webhook_sources
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id::TEXT = current_setting('app.tenant_id', true)
+    );
@@ -0,0 +1,20 @@
+-- Per-tenant language and time zone of notifications. Recipients without a
+-- locale of their own in the action get `locale`; times in notifications
+-- are shown in `time_zone` (IANA name), formatted with `datetime_format`
+-- (strftime) when set and the recipient language's default otherwise.
+CREATE TABLE IF NOT EXISTS notification_settings (
+    tenant_id UUID PRIMARY KEY,
+    locale VARCHAR(35) NOT NULL DEFAULT 'en',
+    time_zone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+    datetime_format VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE notification_settings ENABLE ROW LEVEL SECURITY;
+ALTER TABLE notification_settings FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON notification_settings
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id::TEXT = current_setting('app.tenant_id', true)
+    );
@@ -0,0 +1,344 @@
+//! Anomaly detection on camera metrics
+//!
+//! Producers report per-camera samples of two metrics: the ai-service the
+//! detection rate (detections per minute of analyzed video) and stream nodes
+//! the ingest bitrate. Every camera, metric and UTC hour of day has its own
+//! baseline, an exponentially weighted mean and variance, so a busy lobby at
+//! noon and an empty one at 3am are both normal. Once a baseline has learned
+//! enough samples, a value too many standard deviations away raises an
+//! `anomaly` trigger: a bitrate collapse from a covered or blinded lens, a
+//! detection spike or silence after the camera was turned away.
+//!
+//! Anomalous samples are not learned, so sustained tampering keeps raising
+//! alerts (rule suppression limits how often). After an intended change to
+//! a camera its baselines are reset and learned again.
+
+use crate::store::AlertStore;
+use anyhow::Result;
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Samples accepted in one report
+pub const MAX_SAMPLES_PER_BATCH: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyMetric {
+    /// Detections per minute of analyzed video
+    DetectionRate,
+    /// Ingest bitrate of the camera stream
+    BitrateKbps,
+}
+
+impl AnomalyMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyMetric::DetectionRate => "detection_rate",
+            AnomalyMetric::BitrateKbps => "bitrate_kbps",
+        }
+    }
+
+    /// Smallest spread a baseline is scored with, so a metric that has been
+    /// flat (no detections all night) does not alert on the first change
+    pub fn min_spread(&self) -> f64 {
+        match self {
+            AnomalyMetric::DetectionRate => 1.0,
+            AnomalyMetric::BitrateKbps => 64.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct MetricSample {
+    #[validate(custom(function = "common::validated::id"))]
+    pub camera_id: String,
+    pub metric: AnomalyMetric,
+    #[validate(range(min = 0.0))]
+    pub value: f64,
+    /// When the sample was taken; defaults to now
+    pub observed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct ReportSamplesRequest {
+    #[validate(length(min = 1, max = "MAX_SAMPLES_PER_BATCH"), nested)]
+    pub samples: Vec<MetricSample>,
+}
+
+/// Learned baseline of one camera, metric and hour, as stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalyBaseline {
+    pub tenant_id: Uuid,
+    pub camera_id: String,
+    pub metric: String,
+    pub hour_of_day: i16,
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Running statistics of a baseline
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BaselineStats {
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: i64,
+}
+
+impl BaselineStats {
+    /// Fold a value in. The first samples are weighted equally (a plain
+    /// running mean) until their weight drops to `learning_rate`.
+    pub fn learn(&mut self, value: f64, learning_rate: f64) {
+        self.samples += 1;
+        let weight = (1.0 / self.samples as f64).max(learning_rate);
+        let delta = value - self.mean;
+        self.mean += weight * delta;
+        self.variance = (1.0 - weight) * (self.variance + weight * delta * delta);
+    }
+
+    /// Standard deviation scored against: at least the metric's minimum and
+    /// a tenth of the mean
+    pub fn spread(&self, metric: AnomalyMetric) -> f64 {
+        self.variance
+            .max(0.0)
+            .sqrt()
+            .max(metric.min_spread())
+            .max(self.mean.abs() * 0.1)
+    }
+
+    /// Signed distance of `value` from the mean, in spreads
+    pub fn deviation(&self, metric: AnomalyMetric, value: f64) -> f64 {
+        (value - self.mean) / self.spread(metric)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    /// Deviation, in standard deviations, that counts as anomalous
+    pub threshold_sigma: f64,
+    /// Samples a baseline needs before it is scored
+    pub min_samples: i64,
+    /// Weight of a new sample once the baseline is warm
+    pub learning_rate: f64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            threshold_sigma: 4.0,
+            min_samples: 120,
+            learning_rate: 0.01,
+        }
+    }
+}
+
+impl AnomalyConfig {
+    /// Reads `ANOMALY_THRESHOLD_SIGMA`, `ANOMALY_MIN_SAMPLES` and
+    /// `ANOMALY_LEARNING_RATE`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let parse = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok());
+        Self {
+            threshold_sigma: parse("ANOMALY_THRESHOLD_SIGMA")
+                .filter(|v| *v > 0.0)
+                .unwrap_or(defaults.threshold_sigma),
+            min_samples: parse("ANOMALY_MIN_SAMPLES")
+                .filter(|v| *v >= 1.0)
+                .map(|v| v as i64)
+                .unwrap_or(defaults.min_samples),
+            learning_rate: parse("ANOMALY_LEARNING_RATE")
+                .filter(|v| *v > 0.0 && *v <= 1.0)
+                .unwrap_or(defaults.learning_rate),
+        }
+    }
+}
+
+/// A sample that deviates sharply from its camera's baseline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub camera_id: String,
+    pub metric: AnomalyMetric,
+    pub value: f64,
+    pub expected: f64,
+    pub std_dev: f64,
+    /// Signed deviation in standard deviations
+    pub deviation_sigma: f64,
+    /// `spike` or `drop`
+    pub direction: String,
+    pub hour_of_day: i16,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl Anomaly {
+    pub fn message(&self) -> String {
+        format!(
+            "{} {} on {}: {:.1} against {:.1} expected",
+            self.metric.as_str(),
+            self.direction,
+            self.camera_id,
+            self.value,
+            self.expected,
+        )
+    }
+
+    /// Trigger context; rules match on any field (`metric`, `direction`,
+    /// `camera_id`, `deviation_sigma`, ...)
+    pub fn context(&self) -> HashMap<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields.into_iter().collect(),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+/// Score `value` against a baseline; `None` while the baseline is still
+/// learning or the value is within the threshold
+pub fn score(config: &AnomalyConfig, stats: &BaselineStats, sample: &MetricSample, hour_of_day: i16) -> Option<Anomaly> {
+    if stats.samples < config.min_samples {
+        return None;
+    }
+    let deviation = stats.deviation(sample.metric, sample.value);
+    if deviation.abs() < config.threshold_sigma {
+        return None;
+    }
+    Some(Anomaly {
+        camera_id: sample.camera_id.clone(),
+        metric: sample.metric,
+        value: sample.value,
+        expected: stats.mean,
+        std_dev: stats.spread(sample.metric),
+        deviation_sigma: deviation,
+        direction: if deviation > 0.0 { "spike" } else { "drop" }.to_string(),
+        hour_of_day,
+        observed_at: sample.observed_at.unwrap_or_else(Utc::now),
+    })
+}
+
+/// Scores samples against stored baselines and learns the normal ones
+pub struct AnomalyDetector {
+    store: AlertStore,
+    config: AnomalyConfig,
+}
+
+impl AnomalyDetector {
+    pub fn new(store: AlertStore, config: AnomalyConfig) -> Self {
+        Self { store, config }
+    }
+
+    pub fn config(&self) -> &AnomalyConfig {
+        &self.config
+    }
+
+    /// Score a sample, then fold it into its baseline unless it is anomalous
+    pub async fn observe(&self, tenant_id: Uuid, sample: &MetricSample) -> Result<Option<Anomaly>> {
+        let observed_at = sample.observed_at.unwrap_or_else(Utc::now);
+        let hour_of_day = observed_at.hour() as i16;
+        let metric = sample.metric.as_str();
+        let mut stats = self
+            .store
+            .get_anomaly_baseline(tenant_id, &sample.camera_id, metric, hour_of_day)
+            .await?
+            .unwrap_or_default();
+
+        let anomaly = score(&self.config, &stats, sample, hour_of_day);
+        if anomaly.is_none() {
+            stats.learn(sample.value, self.config.learning_rate);
+            self.store
+                .upsert_anomaly_baseline(tenant_id, &sample.camera_id, metric, hour_of_day, &stats)
+                .await?;
+        }
+        Ok(anomaly)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(metric: AnomalyMetric, value: f64) -> MetricSample {
+        MetricSample {
+            camera_id: "cam-1".to_string(),
+            metric,
+            value,
+            observed_at: None,
+        }
+    }
+
+    fn learned(values: &[f64], learning_rate: f64) -> BaselineStats {
+        let mut stats = BaselineStats::default();
+        for value in values {
+            stats.learn(*value, learning_rate);
+        }
+        stats
+    }
+
+    #[test]
+    fn learns_mean_and_variance() {
+        // Equal weights while warming up: the plain mean and variance
+        let stats = learned(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0], 0.01);
+        assert!((stats.mean - 5.0).abs() < 1e-9);
+        assert!((stats.variance - 4.0).abs() < 1e-9);
+        assert_eq!(stats.samples, 8);
+
+        // Once warm, recent samples dominate
+        let stats = learned(&[100.0; 200], 0.05);
+        let mut shifted = stats;
+        for _ in 0..100 {
+            shifted.learn(200.0, 0.05);
+        }
+        assert!(shifted.mean > 195.0);
+    }
+
+    #[test]
+    fn scores_only_warm_baselines() {
+        let config = AnomalyConfig {
+            min_samples: 10,
+            ..AnomalyConfig::default()
+        };
+        let cold = learned(&[2000.0; 5], config.learning_rate);
+        assert!(score(&config, &cold, &sample(AnomalyMetric::BitrateKbps, 10.0), 3).is_none());
+
+        let values: Vec<f64> = (0..50).map(|i| 2000.0 + f64::from(i % 5) * 50.0).collect();
+        let warm = learned(&values, config.learning_rate);
+        assert!(score(&config, &warm, &sample(AnomalyMetric::BitrateKbps, 2150.0), 3).is_none());
+
+        // A blinded lens: the encoder has almost nothing left to send
+        let anomaly = score(&config, &warm, &sample(AnomalyMetric::BitrateKbps, 150.0), 3).unwrap();
+        assert_eq!(anomaly.direction, "drop");
+        assert!(anomaly.deviation_sigma < -4.0);
+        assert_eq!(anomaly.hour_of_day, 3);
+        let context = anomaly.context();
+        assert_eq!(context["metric"], "bitrate_kbps");
+        assert_eq!(context["camera_id"], "cam-1");
+    }
+
+    #[test]
+    fn flat_baselines_need_a_real_change() {
+        let config = AnomalyConfig {
+            min_samples: 10,
+            ..AnomalyConfig::default()
+        };
+        // No detections all night: one passer-by is not an anomaly, a crowd is
+        let quiet = learned(&[0.0; 60], config.learning_rate);
+        assert!(score(&config, &quiet, &sample(AnomalyMetric::DetectionRate, 2.0), 2).is_none());
+        let anomaly = score(&config, &quiet, &sample(AnomalyMetric::DetectionRate, 12.0), 2).unwrap();
+        assert_eq!(anomaly.direction, "spike");
+    }
+
+    #[test]
+    fn rejects_oversized_reports() {
+        let request = ReportSamplesRequest {
+            samples: vec![sample(AnomalyMetric::DetectionRate, 1.0); MAX_SAMPLES_PER_BATCH as usize + 1],
+        };
+        assert!(request.validate().is_err());
+        let request = ReportSamplesRequest {
+            samples: vec![sample(AnomalyMetric::DetectionRate, -1.0)],
+        };
+        assert!(request.validate().is_err());
+    }
+}
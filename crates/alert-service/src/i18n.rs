@@ -0,0 +1,504 @@
+//! Language and time zone of alert notifications.
+//!
+//! Each recipient gets the built-in texts of their language (the tenant's
+//! default locale when the action does not name one), and the times in them
+//! are shown in the tenant's time zone. Actions may also carry their own
+//! templates per locale, which take precedence over the built-in texts.
+
+use crate::types::{AlertEvent, NotificationSettings, Severity, TriggerType};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Built-in texts of one language
+#[derive(Debug)]
+pub struct Catalog {
+    pub locale: &'static str,
+    /// Info, warning, error, critical
+    severities: [&'static str; 4],
+    /// In `TriggerType` declaration order
+    triggers: [&'static str; 16],
+    labels: Labels,
+    /// strftime pattern of times when the tenant does not set one
+    datetime_format: &'static str,
+    email_subject: &'static str,
+    /// Slack message text
+    chat_text: &'static str,
+    /// Discord embed title
+    chat_title: &'static str,
+}
+
+#[derive(Debug)]
+struct Labels {
+    title: &'static str,
+    severity: &'static str,
+    trigger: &'static str,
+    message: &'static str,
+    event_id: &'static str,
+    fired_at: &'static str,
+    context: &'static str,
+}
+
+/// Default SMS text, the same in every language
+const SMS_BODY: &str = "[{severity_label}] {trigger_label}: {message}";
+
+static CATALOGS: [Catalog; 4] = [
+    Catalog {
+        locale: "en",
+        severities: ["Info", "Warning", "Error", "Critical"],
+        triggers: [
+            "Device offline",
+            "Device online",
+            "Motion detected",
+            "AI detection",
+            "Recording started",
+            "Recording stopped",
+            "Recording failed",
+            "Stream started",
+            "Stream stopped",
+            "Stream failed",
+            "Health check failed",
+            "Anomaly",
+            "Storage capacity",
+            "Latency budget exceeded",
+            "External event",
+            "Custom alert",
+        ],
+        labels: Labels {
+            title: "Alert Notification",
+            severity: "Severity",
+            trigger: "Trigger",
+            message: "Message",
+            event_id: "Event ID",
+            fired_at: "Fired At",
+            context: "Context",
+        },
+        datetime_format: "%Y-%m-%d %H:%M:%S %Z",
+        email_subject: "Alert: {severity_label}",
+        chat_text: "*{severity_label}* alert triggered",
+        chat_title: "{severity_label} alert",
+    },
+    Catalog {
+        locale: "de",
+        severities: ["Info", "Warnung", "Fehler", "Kritisch"],
+        triggers: [
+            "Gerät offline",
+            "Gerät online",
+            "Bewegung erkannt",
+            "KI-Erkennung",
+            "Aufzeichnung gestartet",
+            "Aufzeichnung beendet",
+            "Aufzeichnung fehlgeschlagen",
+            "Stream gestartet",
+            "Stream beendet",
+            "Stream fehlgeschlagen",
+            "Zustandsprüfung fehlgeschlagen",
+            "Anomalie",
+            "Speicherkapazität",
+            "Latenzbudget überschritten",
+            "Externes Ereignis",
+            "Benutzerdefinierter Alarm",
+        ],
+        labels: Labels {
+            title: "Alarmmeldung",
+            severity: "Schweregrad",
+            trigger: "Auslöser",
+            message: "Meldung",
+            event_id: "Ereignis-ID",
+            fired_at: "Ausgelöst am",
+            context: "Kontext",
+        },
+        datetime_format: "%d.%m.%Y %H:%M:%S %Z",
+        email_subject: "Alarm: {severity_label}",
+        chat_text: "*{severity_label}*: Alarm ausgelöst",
+        chat_title: "Alarm: {severity_label}",
+    },
+    Catalog {
+        locale: "fr",
+        severities: ["Info", "Avertissement", "Erreur", "Critique"],
+        triggers: [
+            "Appareil hors ligne",
+            "Appareil en ligne",
+            "Mouvement détecté",
+            "Détection IA",
+            "Enregistrement démarré",
+            "Enregistrement arrêté",
+            "Échec de l'enregistrement",
+            "Flux démarré",
+            "Flux arrêté",
+            "Échec du flux",
+            "Échec du contrôle de santé",
+            "Anomalie",
+            "Capacité de stockage",
+            "Budget de latence dépassé",
+            "Événement externe",
+            "Alerte personnalisée",
+        ],
+        labels: Labels {
+            title: "Notification d'alerte",
+            severity: "Gravité",
+            trigger: "Déclencheur",
+            message: "Message",
+            event_id: "ID de l'événement",
+            fired_at: "Déclenchée le",
+            context: "Contexte",
+        },
+        datetime_format: "%d/%m/%Y %H:%M:%S %Z",
+        email_subject: "Alerte : {severity_label}",
+        chat_text: "Alerte *{severity_label}* déclenchée",
+        chat_title: "Alerte {severity_label}",
+    },
+    Catalog {
+        locale: "es",
+        severities: ["Información", "Advertencia", "Error", "Crítica"],
+        triggers: [
+            "Dispositivo desconectado",
+            "Dispositivo conectado",
+            "Movimiento detectado",
+            "Detección de IA",
+            "Grabación iniciada",
+            "Grabación detenida",
+            "Error de grabación",
+            "Transmisión iniciada",
+            "Transmisión detenida",
+            "Error de transmisión",
+            "Error en la comprobación de estado",
+            "Anomalía",
+            "Capacidad de almacenamiento",
+            "Presupuesto de latencia superado",
+            "Evento externo",
+            "Alerta personalizada",
+        ],
+        labels: Labels {
+            title: "Notificación de alerta",
+            severity: "Gravedad",
+            trigger: "Disparador",
+            message: "Mensaje",
+            event_id: "ID del evento",
+            fired_at: "Activada el",
+            context: "Contexto",
+        },
+        datetime_format: "%d/%m/%Y %H:%M:%S %Z",
+        email_subject: "Alerta: {severity_label}",
+        chat_text: "Alerta *{severity_label}* activada",
+        chat_title: "Alerta {severity_label}",
+    },
+];
+
+/// Locales with built-in texts
+pub fn supported_locales() -> impl Iterator<Item = &'static str> {
+    CATALOGS.iter().map(|c| c.locale)
+}
+
+/// Catalog of a BCP 47 tag: its exact language, else its primary language
+/// (`de-AT` gets `de`)
+pub fn catalog(locale: &str) -> Option<&'static Catalog> {
+    let locale = locale.trim().replace('_', "-").to_lowercase();
+    let language = locale.split('-').next().unwrap_or_default();
+    CATALOGS
+        .iter()
+        .find(|c| c.locale == locale)
+        .or_else(|| CATALOGS.iter().find(|c| c.locale == language))
+}
+
+pub fn parse_time_zone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>()
+        .map_err(|_| format!("Unknown time zone: {} (expected an IANA name such as Europe/Berlin)", name))
+}
+
+/// A strftime pattern chrono can format with (an invalid one would fail
+/// every notification)
+pub fn validate_datetime_format(format: &str) -> Result<(), String> {
+    if format.trim().is_empty() {
+        return Err("datetime_format must not be empty".to_string());
+    }
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid datetime_format: {}", format));
+    }
+    Ok(())
+}
+
+fn severity_index(severity: &Severity) -> usize {
+    match severity {
+        Severity::Info => 0,
+        Severity::Warning => 1,
+        Severity::Error => 2,
+        Severity::Critical => 3,
+    }
+}
+
+fn trigger_index(trigger: &TriggerType) -> usize {
+    match trigger {
+        TriggerType::DeviceOffline => 0,
+        TriggerType::DeviceOnline => 1,
+        TriggerType::MotionDetected => 2,
+        TriggerType::AiDetection => 3,
+        TriggerType::RecordingStarted => 4,
+        TriggerType::RecordingStopped => 5,
+        TriggerType::RecordingFailed => 6,
+        TriggerType::StreamStarted => 7,
+        TriggerType::StreamStopped => 8,
+        TriggerType::StreamFailed => 9,
+        TriggerType::HealthCheckFailed => 10,
+        TriggerType::Anomaly => 11,
+        TriggerType::StorageCapacity => 12,
+        TriggerType::LatencyBudget => 13,
+        TriggerType::ExternalEvent => 14,
+        TriggerType::Custom => 15,
+    }
+}
+
+/// Language and time zone one notification is rendered in
+#[derive(Debug, Clone)]
+pub struct NotificationLocale {
+    /// Locale as requested, used to pick action templates
+    tag: String,
+    catalog: &'static Catalog,
+    time_zone: Tz,
+    datetime_format: Option<String>,
+}
+
+impl Default for NotificationLocale {
+    fn default() -> Self {
+        Self::for_tenant(None)
+    }
+}
+
+impl NotificationLocale {
+    /// The tenant's default locale and time zone; English and UTC for
+    /// tenants without notification settings
+    pub fn for_tenant(settings: Option<&NotificationSettings>) -> Self {
+        let tag = settings.map_or(DEFAULT_LOCALE, |s| s.locale.as_str());
+        Self {
+            tag: tag.to_string(),
+            catalog: catalog(tag).unwrap_or(&CATALOGS[0]),
+            time_zone: settings
+                .and_then(|s| parse_time_zone(&s.time_zone).ok())
+                .unwrap_or(Tz::UTC),
+            datetime_format: settings.and_then(|s| s.datetime_format.clone()),
+        }
+    }
+
+    /// A recipient's own locale, in the tenant's time zone. Locales without
+    /// a catalog keep the tenant's texts but still pick their own action
+    /// templates.
+    pub fn for_recipient(&self, locale: Option<&str>) -> Self {
+        match locale.map(str::trim).filter(|l| !l.is_empty()) {
+            Some(tag) => Self {
+                tag: tag.to_string(),
+                catalog: catalog(tag).unwrap_or(self.catalog),
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn severity(&self, severity: &Severity) -> &'static str {
+        self.catalog.severities[severity_index(severity)]
+    }
+
+    pub fn trigger(&self, trigger: &TriggerType) -> &'static str {
+        self.catalog.triggers[trigger_index(trigger)]
+    }
+
+    pub fn format_time(&self, at: DateTime<Utc>) -> String {
+        let format = self.datetime_format.as_deref().unwrap_or(self.catalog.datetime_format);
+        at.with_timezone(&self.time_zone).format(format).to_string()
+    }
+
+    /// The action's template for this locale (exact tag, then primary
+    /// language), else its locale-independent one
+    pub fn pick<'a>(&self, localized: &'a HashMap<String, String>, fallback: Option<&'a str>) -> Option<&'a str> {
+        let tag = self.tag.replace('_', "-");
+        let language = tag.split('-').next().unwrap_or_default();
+        localized
+            .iter()
+            .find(|(k, _)| k.replace('_', "-").eq_ignore_ascii_case(&tag))
+            .or_else(|| localized.iter().find(|(k, _)| k.eq_ignore_ascii_case(language)))
+            .map(|(_, v)| v.as_str())
+            .or(fallback)
+    }
+
+    pub fn email_subject(&self) -> &'static str {
+        self.catalog.email_subject
+    }
+
+    pub fn email_body(&self) -> String {
+        let l = &self.catalog.labels;
+        format!(
+            "{}\n\n\
+            {}: {{severity_label}}\n\
+            {}: {{trigger_label}}\n\
+            {}: {{message}}\n\n\
+            {}: {{event_id}}\n\
+            {}: {{fired_at}}\n\n\
+            {}:\n{{context}}\n",
+            l.title, l.severity, l.trigger, l.message, l.event_id, l.fired_at, l.context
+        )
+    }
+
+    pub fn sms_body(&self) -> &'static str {
+        SMS_BODY
+    }
+
+    pub fn chat_text(&self) -> &'static str {
+        self.catalog.chat_text
+    }
+
+    pub fn chat_title(&self) -> &'static str {
+        self.catalog.chat_title
+    }
+
+    /// Slack/Discord field titles: message, trigger, severity, event ID,
+    /// fired at
+    pub fn field_labels(&self) -> [&'static str; 5] {
+        let l = &self.catalog.labels;
+        [l.message, l.trigger, l.severity, l.event_id, l.fired_at]
+    }
+
+    /// Fill in a template's placeholders: `{severity}` and `{trigger_type}`
+    /// (codes), `{severity_label}` and `{trigger_label}` (translated),
+    /// `{message}`, `{event_id}`, `{fired_at}` (tenant time zone),
+    /// `{fired_at_utc}` (RFC 3339), `{time_zone}` and `{context}`
+    pub fn render(&self, template: &str, event: &AlertEvent) -> String {
+        let context = serde_json::to_string_pretty(&event.context_json).unwrap_or_default();
+        template
+            .replace("{severity_label}", self.severity(&event.severity))
+            .replace("{severity}", &event.severity.to_string())
+            .replace("{trigger_label}", self.trigger(&event.trigger_type))
+            .replace("{trigger_type}", &event.trigger_type.to_string())
+            .replace("{event_id}", &event.id.to_string())
+            .replace("{fired_at_utc}", &event.fired_at.to_rfc3339())
+            .replace("{fired_at}", &self.format_time(event.fired_at))
+            .replace("{time_zone}", self.time_zone.name())
+            .replace("{context}", &context)
+            // Last, so placeholders in the message are left alone
+            .replace("{message}", &event.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn event() -> AlertEvent {
+        AlertEvent {
+            id: Uuid::nil(),
+            rule_id: Uuid::nil(),
+            tenant_id: Uuid::nil(),
+            fired_at: Utc.with_ymd_and_hms(2025, 3, 1, 23, 30, 0).unwrap(),
+            severity: Severity::Critical,
+            trigger_type: TriggerType::DeviceOffline,
+            message: "Gate camera lost {fired_at}".to_string(),
+            context_json: json!({"device_id": "gate"}),
+            suppressed: false,
+            suppressed_reason: None,
+            notifications_sent: 0,
+            notifications_failed: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn settings(locale: &str, time_zone: &str, datetime_format: Option<&str>) -> NotificationSettings {
+        NotificationSettings {
+            tenant_id: Uuid::nil(),
+            locale: locale.to_string(),
+            time_zone: time_zone.to_string(),
+            datetime_format: datetime_format.map(str::to_string),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn every_catalog_translates_every_trigger() {
+        let en = NotificationLocale::default();
+        for locale in supported_locales() {
+            let localized = en.for_recipient(Some(locale));
+            assert_eq!(localized.catalog.locale, locale);
+            assert!(localized.catalog.triggers.iter().all(|t| !t.is_empty()));
+        }
+        assert_eq!(en.trigger(&TriggerType::Custom), "Custom alert");
+        assert_eq!(en.trigger(&TriggerType::LatencyBudget), "Latency budget exceeded");
+    }
+
+    #[test]
+    fn locales_fall_back_to_their_language_then_the_tenant() {
+        assert_eq!(catalog("de-AT").map(|c| c.locale), Some("de"));
+        assert_eq!(catalog("FR_ca").map(|c| c.locale), Some("fr"));
+        assert!(catalog("ja").is_none());
+
+        let tenant = NotificationLocale::for_tenant(Some(&settings("es", "UTC", None)));
+        assert_eq!(tenant.severity(&Severity::Warning), "Advertencia");
+        assert_eq!(tenant.for_recipient(Some("de-CH")).severity(&Severity::Warning), "Warnung");
+        // No Japanese texts: the tenant's language is used
+        assert_eq!(tenant.for_recipient(Some("ja")).severity(&Severity::Warning), "Advertencia");
+        assert_eq!(tenant.for_recipient(None).tag(), "es");
+    }
+
+    #[test]
+    fn times_are_shown_in_the_tenant_time_zone() {
+        let event = event();
+        assert_eq!(NotificationLocale::default().format_time(event.fired_at), "2025-03-01 23:30:00 UTC");
+
+        let berlin = NotificationLocale::for_tenant(Some(&settings("de", "Europe/Berlin", None)));
+        assert_eq!(berlin.format_time(event.fired_at), "02.03.2025 00:30:00 CET");
+
+        let custom = NotificationLocale::for_tenant(Some(&settings("en", "America/New_York", Some("%b %e, %H:%M"))));
+        assert_eq!(custom.format_time(event.fired_at), "Mar  1, 18:30");
+        // The recipient's language does not override the tenant's pattern
+        assert_eq!(custom.for_recipient(Some("fr")).format_time(event.fired_at), "Mar  1, 18:30");
+    }
+
+    #[test]
+    fn templates_are_picked_by_locale() {
+        let templates: HashMap<String, String> = [
+            ("de".to_string(), "Alarm {severity_label}".to_string()),
+            ("pt-BR".to_string(), "Alerta {severity}".to_string()),
+        ]
+        .into();
+        let tenant = NotificationLocale::default();
+        assert_eq!(tenant.for_recipient(Some("de-AT")).pick(&templates, None), Some("Alarm {severity_label}"));
+        assert_eq!(tenant.for_recipient(Some("pt_br")).pick(&templates, None), Some("Alerta {severity}"));
+        assert_eq!(tenant.for_recipient(Some("pt")).pick(&templates, Some("x")), Some("x"));
+        assert_eq!(tenant.pick(&templates, None), None);
+    }
+
+    #[test]
+    fn rendering_fills_in_translated_placeholders() {
+        let event = event();
+        let berlin = NotificationLocale::for_tenant(Some(&settings("de", "Europe/Berlin", None)));
+
+        assert_eq!(
+            berlin.render(berlin.sms_body(), &event),
+            "[Kritisch] Gerät offline: Gate camera lost {fired_at}"
+        );
+        assert_eq!(
+            berlin.render("{severity}/{trigger_type} {fired_at} ({time_zone}) {fired_at_utc}", &event),
+            "critical/device_offline 02.03.2025 00:30:00 CET (Europe/Berlin) 2025-03-01T23:30:00+00:00"
+        );
+
+        let body = berlin.render(&berlin.email_body(), &event);
+        assert!(body.starts_with("Alarmmeldung\n\nSchweregrad: Kritisch\nAuslöser: Gerät offline\n"));
+        assert!(body.contains("\"device_id\": \"gate\""));
+    }
+
+    #[test]
+    fn datetime_formats_are_checked() {
+        assert!(validate_datetime_format("%d.%m.%Y %H:%M").is_ok());
+        assert!(validate_datetime_format("%Q").is_err());
+        assert!(validate_datetime_format(" ").is_err());
+        assert!(parse_time_zone("Asia/Tokyo").is_ok());
+        assert!(parse_time_zone("Mars/Olympus").is_err());
+    }
+}
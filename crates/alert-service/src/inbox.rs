@@ -0,0 +1,290 @@
+//! Webhook inbox for third-party events
+//!
+//! External systems (access control, intrusion panels) post normalized
+//! events to `POST /v1/inbox/events` with the token of their webhook source,
+//! in an `X-Webhook-Token` header or as a bearer token. A source belongs to
+//! one tenant and its token is only stored as a SHA-256, so it is shown once
+//! when the source is created or the token rotated.
+//!
+//! Each event raises an `external_event` trigger in the source's tenant. Its
+//! context holds the event's attributes next to `source`, `source_kind`,
+//! `event_type` and `camera_id`, so rule conditions match them like the
+//! fields of native detections, and the event goes on the system timeline as
+//! a detection of its camera.
+
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use common::timeline::{TimelineEvent, TimelineEventKind};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// Prefix of webhook source tokens, to tell them apart from other secrets
+pub const TOKEN_PREFIX: &str = "whk_";
+
+/// Header carrying the token for systems that cannot send bearer tokens
+pub const TOKEN_HEADER: &str = "x-webhook-token";
+
+/// Most attributes accepted per event
+pub const MAX_ATTRIBUTES: usize = 64;
+
+/// Largest serialized attributes object accepted per event
+pub const MAX_ATTRIBUTES_BYTES: usize = 8 * 1024;
+
+/// A system allowed to post events, as stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSource {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    /// Free-form system type, e.g. `access_control` or `intrusion`
+    pub kind: String,
+    /// Camera events are tied to when they do not name one
+    pub camera_id: Option<String>,
+    pub enabled: bool,
+    pub last_event_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct CreateWebhookSourceRequest {
+    #[validate(custom(function = "common::validated::name"))]
+    pub name: String,
+    #[validate(custom(function = "common::validated::id"), length(max = 64))]
+    pub kind: String,
+    #[validate(custom(function = "common::validated::id"))]
+    pub camera_id: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// A source with its token, returned only when the token is issued
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedWebhookSource {
+    #[serde(flatten)]
+    pub source: WebhookSource,
+    pub token: String,
+}
+
+/// Normalized event posted by an external system
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct InboxEvent {
+    /// What happened, e.g. `door_forced`, `access_denied`, `zone_alarm`
+    #[validate(custom(function = "event_type"))]
+    pub event_type: String,
+    /// The external system's id of the event; deliveries retried with the
+    /// same id are stored once on the timeline
+    #[validate(length(min = 1, max = 128))]
+    pub event_id: Option<String>,
+    /// When the event happened; defaults to when it was received
+    pub occurred_at: Option<DateTime<Utc>>,
+    /// Camera covering the event; defaults to the source's camera
+    #[validate(custom(function = "common::validated::id"))]
+    pub camera_id: Option<String>,
+    #[validate(length(min = 1, max = 512))]
+    pub message: Option<String>,
+    /// System-specific fields (door, zone, badge, ...), matched by rule
+    /// conditions like detection fields
+    #[serde(default)]
+    #[validate(custom(function = "attributes"))]
+    pub attributes: Map<String, Value>,
+}
+
+fn event_type(value: &str) -> Result<(), ValidationError> {
+    let valid = !value.is_empty()
+        && value.len() <= 64
+        && value
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '.' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("event_type")
+            .with_message("must be 1-64 lowercase letters, digits, '_', '.' or '-'".into()))
+    }
+}
+
+fn attributes(value: &Map<String, Value>) -> Result<(), ValidationError> {
+    if value.len() > MAX_ATTRIBUTES {
+        return Err(ValidationError::new("attributes")
+            .with_message(format!("at most {} attributes", MAX_ATTRIBUTES).into()));
+    }
+    let size = serde_json::to_vec(value).map(|bytes| bytes.len()).unwrap_or(usize::MAX);
+    if size > MAX_ATTRIBUTES_BYTES {
+        return Err(ValidationError::new("attributes")
+            .with_message(format!("at most {} bytes", MAX_ATTRIBUTES_BYTES).into()));
+    }
+    Ok(())
+}
+
+/// New random source token
+pub fn generate_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    format!("{}{}", TOKEN_PREFIX, general_purpose::URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Hex SHA-256 of a token, as stored
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Token of an inbox request: the `X-Webhook-Token` header, or a bearer
+/// token in `Authorization`
+pub fn token_from_headers(headers: &axum::http::HeaderMap) -> Option<&str> {
+    if let Some(token) = headers.get(TOKEN_HEADER).and_then(|v| v.to_str().ok()) {
+        return Some(token.trim()).filter(|t| !t.is_empty());
+    }
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+impl InboxEvent {
+    pub fn camera<'a>(&'a self, source: &'a WebhookSource) -> Option<&'a str> {
+        self.camera_id.as_deref().or(source.camera_id.as_deref())
+    }
+
+    pub fn message(&self, source: &WebhookSource) -> String {
+        self.message
+            .clone()
+            .unwrap_or_else(|| format!("{} from {}", self.event_type, source.name))
+    }
+
+    /// Trigger context: the attributes, overridden by the standard fields
+    pub fn context(&self, source: &WebhookSource) -> HashMap<String, Value> {
+        let mut context: HashMap<String, Value> = self.attributes.clone().into_iter().collect();
+        context.insert("source".to_string(), Value::from(source.name.clone()));
+        context.insert("source_id".to_string(), Value::from(source.id.to_string()));
+        context.insert("source_kind".to_string(), Value::from(source.kind.clone()));
+        context.insert("event_type".to_string(), Value::from(self.event_type.clone()));
+        match self.camera(source) {
+            Some(camera) => context.insert("camera_id".to_string(), Value::from(camera)),
+            None => context.remove("camera_id"),
+        };
+        if let Some(event_id) = &self.event_id {
+            context.insert("external_event_id".to_string(), Value::from(event_id.clone()));
+        }
+        if let Some(occurred_at) = self.occurred_at {
+            context.insert("occurred_at".to_string(), Value::from(occurred_at.to_rfc3339()));
+        }
+        context
+    }
+
+    /// Timeline entry of the event, a detection of its camera
+    pub fn timeline_event(&self, source: &WebhookSource) -> TimelineEvent {
+        let mut entry = TimelineEvent::new(
+            TimelineEventKind::Detection,
+            "alert-service",
+            format!("{}: {}", source.name, self.message(source)),
+        )
+        .tenant(source.tenant_id.to_string())
+        .details(serde_json::json!({
+            "external_source": source.name,
+            "source_id": source.id,
+            "source_kind": source.kind,
+            "event_type": self.event_type,
+            "attributes": self.attributes,
+        }));
+        if let Some(event_id) = &self.event_id {
+            entry.id = format!("inbox-{}-{}", source.id, event_id);
+        }
+        if let Some(occurred_at) = self.occurred_at {
+            entry = entry.at(occurred_at.timestamp_millis().max(0) as u64);
+        }
+        if let Some(camera) = self.camera(source) {
+            entry = entry.camera(camera);
+        }
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    fn source() -> WebhookSource {
+        WebhookSource {
+            id: Uuid::new_v4(),
+            tenant_id: Uuid::new_v4(),
+            name: "Lobby doors".to_string(),
+            kind: "access_control".to_string(),
+            camera_id: Some("cam-lobby".to_string()),
+            enabled: true,
+            last_event_at: None,
+            token_hash: String::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn event(value: Value) -> InboxEvent {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn tokens_are_prefixed_and_hashed() {
+        let token = generate_token();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_ne!(token, generate_token());
+        assert_eq!(hash_token(&token).len(), 64);
+        assert_eq!(hash_token(&token), hash_token(&token));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(token_from_headers(&headers), None);
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer whk_abc"));
+        assert_eq!(token_from_headers(&headers), Some("whk_abc"));
+        headers.insert(TOKEN_HEADER, HeaderValue::from_static("whk_def"));
+        assert_eq!(token_from_headers(&headers), Some("whk_def"));
+    }
+
+    #[test]
+    fn validates_events() {
+        assert!(event(serde_json::json!({"event_type": "door_forced"})).validate().is_ok());
+        assert!(event(serde_json::json!({"event_type": "Door Forced"})).validate().is_err());
+        assert!(event(serde_json::json!({"event_type": ""})).validate().is_err());
+
+        let attributes: Map<String, Value> = (0..=MAX_ATTRIBUTES)
+            .map(|i| (format!("a{}", i), Value::from(i)))
+            .collect();
+        let too_many = event(serde_json::json!({"event_type": "zone_alarm", "attributes": attributes}));
+        assert!(too_many.validate().is_err());
+    }
+
+    #[test]
+    fn builds_trigger_context_and_timeline_entry() {
+        let source = source();
+        let event = event(serde_json::json!({
+            "event_type": "access_denied",
+            "event_id": "evt-42",
+            "occurred_at": "2025-08-15T10:00:00Z",
+            "attributes": {"door": "north", "badge": "1234", "source": "spoofed"}
+        }));
+
+        let context = event.context(&source);
+        assert_eq!(context["source"], "Lobby doors");
+        assert_eq!(context["source_kind"], "access_control");
+        assert_eq!(context["event_type"], "access_denied");
+        assert_eq!(context["camera_id"], "cam-lobby");
+        assert_eq!(context["door"], "north");
+        assert_eq!(context["external_event_id"], "evt-42");
+        assert_eq!(event.message(&source), "access_denied from Lobby doors");
+
+        let entry = event.timeline_event(&source);
+        assert_eq!(entry.kind, TimelineEventKind::Detection);
+        assert_eq!(entry.id, format!("inbox-{}-evt-42", source.id));
+        assert_eq!(entry.timestamp_ms, 1_755_252_000_000);
+        assert_eq!(entry.camera_id.as_deref(), Some("cam-lobby"));
+        assert_eq!(entry.details["attributes"]["badge"], "1234");
+        assert!(entry.validate().is_ok());
+    }
+}
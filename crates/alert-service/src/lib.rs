@@ -1,10 +1,15 @@
+pub mod anomaly;
+pub mod i18n;
+pub mod inbox;
 pub mod notifier;
 pub mod routes;
 pub mod rule_engine;
+pub mod secrets;
 pub mod store;
 pub mod types;
 
 // Re-export commonly used types
+pub use anomaly::{AnomalyConfig, AnomalyDetector};
 pub use notifier::Notifier;
 pub use routes::{create_router, AppState};
 pub use rule_engine::RuleEngine;
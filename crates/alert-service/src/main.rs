@@ -1,7 +1,9 @@
-use alert_service::{create_router, AlertStore, AppState, Notifier, RuleEngine};
+use alert_service::{create_router, AlertStore, AnomalyConfig, AnomalyDetector, AppState, Notifier, RuleEngine};
 use anyhow::{Context, Result};
-use sqlx::postgres::PgPoolOptions;
+use common::evidence::EvidenceStore;
+use common::tenant_rls;
 use std::env;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tracing::info;
@@ -25,7 +27,7 @@ async fn main() -> Result<()> {
     info!("Bind address: {}", bind_addr);
 
     // Create database connection pool
-    let pool = PgPoolOptions::new()
+    let pool = tenant_rls::pool_options()
         .max_connections(10)
         .connect(&database_url)
         .await
@@ -41,6 +43,8 @@ async fn main() -> Result<()> {
 
     info!("Migrations complete");
 
+    common::timeline::init_from_env(None).await?;
+
     // Create store
     let store = AlertStore::new(pool);
 
@@ -48,59 +52,25 @@ async fn main() -> Result<()> {
     let engine = Arc::new(RuleEngine::new(store.clone()));
 
     // Create notifier
-    let mut notifier = Notifier::new(store.clone());
-
-    // Configure email channel if SMTP settings are provided
-    if let (Ok(smtp_host), Ok(smtp_username), Ok(smtp_password), Ok(from_address)) = (
-        env::var("SMTP_HOST"),
-        env::var("SMTP_USERNAME"),
-        env::var("SMTP_PASSWORD"),
-        env::var("SMTP_FROM"),
-    ) {
-        let smtp_port = env::var("SMTP_PORT")
-            .unwrap_or_else(|_| "587".to_string())
-            .parse()
-            .unwrap_or(587);
-
-        notifier.add_email_channel(
-            smtp_host.clone(),
-            smtp_port,
-            smtp_username,
-            smtp_password,
-            from_address,
-        );
-
-        info!("Email channel configured (SMTP: {}:{})", smtp_host, smtp_port);
-    } else {
-        info!("Email channel not configured (SMTP settings missing)");
-    }
+    let notifier = Arc::new(Notifier::from_env(store.clone()));
 
-    // Configure SMS channel if Twilio settings are provided
-    if let (Ok(account_sid), Ok(auth_token), Ok(from_number)) = (
-        env::var("TWILIO_ACCOUNT_SID"),
-        env::var("TWILIO_AUTH_TOKEN"),
-        env::var("TWILIO_FROM_NUMBER"),
-    ) {
-        notifier.add_sms_channel(
-            account_sid,
-            auth_token,
-            from_number.clone(),
-        );
-
-        info!("SMS channel configured (Twilio from: {})", from_number);
-    } else {
-        info!("SMS channel not configured (Twilio settings missing)");
-    }
+    // Baselines of camera metrics reported at /v1/anomalies/samples
+    let anomalies = Arc::new(AnomalyDetector::new(store.clone(), AnomalyConfig::from_env()));
 
-    info!("Slack and Discord channels configured (webhook-based)");
-
-    let notifier = Arc::new(notifier);
+    // Snapshot and clip evidence of fired alerts, kept apart from recordings
+    let evidence = EvidenceStore::from_env().map(Arc::new);
+    if let Some(evidence) = &evidence {
+        info!(dir = %evidence.config().dir.display(), "Alert evidence capture enabled");
+        evidence.spawn_retention();
+    }
 
     // Create app state
     let state = AppState {
         store,
         engine,
         notifier,
+        anomalies,
+        evidence,
     };
 
     // Create router
@@ -113,7 +83,8 @@ async fn main() -> Result<()> {
 
     info!("Alert service listening on {}", bind_addr);
 
-    axum::serve(listener, app)
+    // Peer addresses key the inbox rate limit of token-header senders
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .context("Server error")?;
 
@@ -1,12 +1,18 @@
+use crate::i18n::NotificationLocale;
+use crate::secrets::SecretCipher;
 use crate::store::AlertStore;
 use crate::types::*;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use common::auth_middleware::AuthContext;
+use common::gateway_identity;
+use common::mqtt::{render_topic, MqttSink, MqttSinkConfig};
 use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Message, SmtpTransport, Transport};
 use rumqttc::{AsyncClient, MqttOptions, QoS};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{error, info};
@@ -14,7 +20,9 @@ use uuid::Uuid;
 
 #[async_trait]
 pub trait NotificationChannel: Send + Sync {
-    async fn send(&self, event: &AlertEvent, action: &AlertAction) -> Result<()>;
+    /// `locale` is the tenant's; channels addressing people switch to each
+    /// recipient's own
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, locale: &NotificationLocale) -> Result<()>;
     fn channel_type(&self) -> ActionType;
 }
 
@@ -24,6 +32,7 @@ pub struct EmailChannel {
     smtp_username: String,
     smtp_password: String,
     from_address: String,
+    from_name: Option<String>,
 }
 
 impl EmailChannel {
@@ -40,76 +49,68 @@ impl EmailChannel {
             smtp_username,
             smtp_password,
             from_address,
+            from_name: None,
         }
     }
 
-    fn render_template(&self, template: &str, event: &AlertEvent) -> String {
-        template
-            .replace("{severity}", &event.severity.to_string())
-            .replace("{message}", &event.message)
-            .replace("{trigger_type}", &event.trigger_type.to_string())
-            .replace("{event_id}", &event.id.to_string())
-            .replace("{fired_at}", &event.fired_at.to_string())
+    /// Display name shown with the sender address
+    pub fn with_from_name(mut self, from_name: Option<String>) -> Self {
+        self.from_name = from_name;
+        self
+    }
+
+    fn sender(&self) -> Result<Mailbox> {
+        Ok(Mailbox::new(self.from_name.clone(), self.from_address.parse()?))
     }
 }
 
 #[async_trait]
 impl NotificationChannel for EmailChannel {
-    async fn send(&self, event: &AlertEvent, action: &AlertAction) -> Result<()> {
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, locale: &NotificationLocale) -> Result<()> {
         let config: EmailActionConfig = serde_json::from_value(action.config_json.clone())
             .context("Invalid email action config")?;
 
-        let subject = config
-            .subject
-            .unwrap_or_else(|| format!("Alert: {}", event.severity));
-
-        let body = if let Some(template) = config.template {
-            self.render_template(&template, event)
-        } else {
-            format!(
-                "Alert Notification\n\n\
-                Severity: {}\n\
-                Trigger: {}\n\
-                Message: {}\n\n\
-                Event ID: {}\n\
-                Fired At: {}\n\n\
-                Context:\n{}\n",
-                event.severity,
-                event.trigger_type,
-                event.message,
-                event.id,
-                event.fired_at,
-                serde_json::to_string_pretty(&event.context_json)?
-            )
-        };
-
-        // Build email message
-        let mut email_builder = Message::builder()
-            .from(self.from_address.parse()?)
-            .subject(subject)
-            .header(ContentType::TEXT_PLAIN);
-
+        // One message per language, so recipients don't see each other's
+        // addresses across more messages than needed
+        let mut by_locale: BTreeMap<Option<&str>, Vec<&String>> = BTreeMap::new();
         for to in &config.to {
-            email_builder = email_builder.to(to.parse()?);
+            by_locale.entry(config.locales.get(to).map(String::as_str)).or_default().push(to);
         }
 
-        let email = email_builder.body(body)?;
-
-        // Send via SMTP
         let creds = Credentials::new(self.smtp_username.clone(), self.smtp_password.clone());
-
         let mailer = SmtpTransport::relay(&self.smtp_host)?
             .port(self.smtp_port)
             .credentials(creds)
             .build();
 
-        mailer.send(&email)?;
+        for (recipient_locale, recipients) in by_locale {
+            let locale = locale.for_recipient(recipient_locale);
+            let subject = locale
+                .pick(&config.subjects, config.subject.as_deref())
+                .unwrap_or(locale.email_subject());
+            let body = match locale.pick(&config.templates, config.template.as_deref()) {
+                Some(template) => locale.render(template, event),
+                None => locale.render(&locale.email_body(), event),
+            };
 
-        info!(
-            event_id = %event.id,
-            recipients = ?config.to,
-            "Email notification sent"
-        );
+            let mut email_builder = Message::builder()
+                .from(self.sender()?)
+                .subject(locale.render(subject, event))
+                .header(ContentType::TEXT_PLAIN);
+
+            for to in &recipients {
+                email_builder = email_builder.to(to.parse()?);
+            }
+
+            mailer.send(&email_builder.body(body)?)?;
+
+            info!(
+                event_id = %event.id,
+                recipients = ?recipients,
+                locale = %locale.tag(),
+                "Email notification sent"
+            );
+        }
 
         Ok(())
     }
@@ -152,7 +153,7 @@ impl Default for WebhookChannel {
 
 #[async_trait]
 impl NotificationChannel for WebhookChannel {
-    async fn send(&self, event: &AlertEvent, action: &AlertAction) -> Result<()> {
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, _locale: &NotificationLocale) -> Result<()> {
         let config: WebhookActionConfig = serde_json::from_value(action.config_json.clone())
             .context("Invalid webhook action config")?;
 
@@ -294,6 +295,20 @@ impl MqttChannel {
     }
 }
 
+/// JSON body of MQTT alert messages
+fn mqtt_payload(event: &AlertEvent) -> serde_json::Value {
+    serde_json::json!({
+        "event_id": event.id,
+        "rule_id": event.rule_id,
+        "tenant_id": event.tenant_id,
+        "severity": event.severity,
+        "trigger_type": event.trigger_type,
+        "message": event.message,
+        "context": event.context_json,
+        "fired_at": event.fired_at,
+    })
+}
+
 impl Default for MqttChannel {
     fn default() -> Self {
         Self::new()
@@ -302,7 +317,7 @@ impl Default for MqttChannel {
 
 #[async_trait]
 impl NotificationChannel for MqttChannel {
-    async fn send(&self, event: &AlertEvent, action: &AlertAction) -> Result<()> {
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, _locale: &NotificationLocale) -> Result<()> {
         let config: MqttActionConfig = serde_json::from_value(action.config_json.clone())
             .context("Invalid MQTT action config")?;
 
@@ -315,17 +330,7 @@ impl NotificationChannel for MqttChannel {
             .replace("{trigger_type}", &event.trigger_type.to_string())
             .replace("{tenant_id}", &event.tenant_id.to_string());
 
-        // Build payload
-        let payload = serde_json::to_string(&serde_json::json!({
-            "event_id": event.id,
-            "rule_id": event.rule_id,
-            "tenant_id": event.tenant_id,
-            "severity": event.severity,
-            "trigger_type": event.trigger_type,
-            "message": event.message,
-            "context": event.context_json,
-            "fired_at": event.fired_at,
-        }))?;
+        let payload = serde_json::to_string(&mqtt_payload(event))?;
 
         let qos = match config.qos.unwrap_or(1) {
             0 => QoS::AtMostOnce,
@@ -365,15 +370,6 @@ impl SlackChannel {
         }
     }
 
-    fn render_template(&self, template: &str, event: &AlertEvent) -> String {
-        template
-            .replace("{severity}", &event.severity.to_string())
-            .replace("{message}", &event.message)
-            .replace("{trigger_type}", &event.trigger_type.to_string())
-            .replace("{event_id}", &event.id.to_string())
-            .replace("{fired_at}", &event.fired_at.to_string())
-    }
-
     fn severity_color(&self, severity: &Severity) -> &'static str {
         match severity {
             Severity::Info => "#36a64f",      // green
@@ -392,15 +388,16 @@ impl Default for SlackChannel {
 
 #[async_trait]
 impl NotificationChannel for SlackChannel {
-    async fn send(&self, event: &AlertEvent, action: &AlertAction) -> Result<()> {
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, locale: &NotificationLocale) -> Result<()> {
         let config: SlackActionConfig = serde_json::from_value(action.config_json.clone())
             .context("Invalid Slack action config")?;
 
-        let text = if let Some(template) = config.template {
-            self.render_template(&template, event)
-        } else {
-            format!("*{}* alert triggered", event.severity.to_string().to_uppercase())
-        };
+        let locale = locale.for_recipient(config.locale.as_deref());
+        let template = locale
+            .pick(&config.templates, config.template.as_deref())
+            .unwrap_or(locale.chat_text());
+        let text = locale.render(template, event);
+        let [message_label, trigger_label, severity_label, event_id_label, fired_at_label] = locale.field_labels();
 
         let mut payload = serde_json::json!({
             "text": text,
@@ -408,28 +405,28 @@ impl NotificationChannel for SlackChannel {
                 "color": self.severity_color(&event.severity),
                 "fields": [
                     {
-                        "title": "Message",
+                        "title": message_label,
                         "value": event.message,
                         "short": false
                     },
                     {
-                        "title": "Trigger Type",
-                        "value": event.trigger_type.to_string(),
+                        "title": trigger_label,
+                        "value": locale.trigger(&event.trigger_type),
                         "short": true
                     },
                     {
-                        "title": "Severity",
-                        "value": event.severity.to_string(),
+                        "title": severity_label,
+                        "value": locale.severity(&event.severity),
                         "short": true
                     },
                     {
-                        "title": "Event ID",
+                        "title": event_id_label,
                         "value": event.id.to_string(),
                         "short": true
                     },
                     {
-                        "title": "Fired At",
-                        "value": event.fired_at.to_rfc3339(),
+                        "title": fired_at_label,
+                        "value": locale.format_time(event.fired_at),
                         "short": true
                     }
                 ]
@@ -489,15 +486,6 @@ impl DiscordChannel {
         }
     }
 
-    fn render_template(&self, template: &str, event: &AlertEvent) -> String {
-        template
-            .replace("{severity}", &event.severity.to_string())
-            .replace("{message}", &event.message)
-            .replace("{trigger_type}", &event.trigger_type.to_string())
-            .replace("{event_id}", &event.id.to_string())
-            .replace("{fired_at}", &event.fired_at.to_string())
-    }
-
     fn severity_color(&self, severity: &Severity) -> u32 {
         match severity {
             Severity::Info => 0x36a64f,      // green
@@ -516,34 +504,35 @@ impl Default for DiscordChannel {
 
 #[async_trait]
 impl NotificationChannel for DiscordChannel {
-    async fn send(&self, event: &AlertEvent, action: &AlertAction) -> Result<()> {
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, locale: &NotificationLocale) -> Result<()> {
         let config: DiscordActionConfig = serde_json::from_value(action.config_json.clone())
             .context("Invalid Discord action config")?;
 
-        let description = if let Some(template) = config.template {
-            self.render_template(&template, event)
-        } else {
-            event.message.clone()
+        let locale = locale.for_recipient(config.locale.as_deref());
+        let description = match locale.pick(&config.templates, config.template.as_deref()) {
+            Some(template) => locale.render(template, event),
+            None => event.message.clone(),
         };
+        let [_, trigger_label, severity_label, event_id_label, _] = locale.field_labels();
 
         let mut payload = serde_json::json!({
             "embeds": [{
-                "title": format!("{} Alert", event.severity.to_string().to_uppercase()),
+                "title": locale.render(locale.chat_title(), event),
                 "description": description,
                 "color": self.severity_color(&event.severity),
                 "fields": [
                     {
-                        "name": "Trigger Type",
-                        "value": event.trigger_type.to_string(),
+                        "name": trigger_label,
+                        "value": locale.trigger(&event.trigger_type),
                         "inline": true
                     },
                     {
-                        "name": "Severity",
-                        "value": event.severity.to_string(),
+                        "name": severity_label,
+                        "value": locale.severity(&event.severity),
                         "inline": true
                     },
                     {
-                        "name": "Event ID",
+                        "name": event_id_label,
                         "value": event.id.to_string(),
                         "inline": false
                     }
@@ -610,42 +599,28 @@ impl SmsChannel {
             twilio_from_number,
         }
     }
-
-    fn render_template(&self, template: &str, event: &AlertEvent) -> String {
-        template
-            .replace("{severity}", &event.severity.to_string())
-            .replace("{message}", &event.message)
-            .replace("{trigger_type}", &event.trigger_type.to_string())
-            .replace("{event_id}", &event.id.to_string())
-            .replace("{fired_at}", &event.fired_at.to_string())
-    }
 }
 
 #[async_trait]
 impl NotificationChannel for SmsChannel {
-    async fn send(&self, event: &AlertEvent, action: &AlertAction) -> Result<()> {
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, locale: &NotificationLocale) -> Result<()> {
         let config: SmsActionConfig = serde_json::from_value(action.config_json.clone())
             .context("Invalid SMS action config")?;
 
-        let body = if let Some(template) = config.template {
-            self.render_template(&template, event)
-        } else {
-            format!(
-                "[{}] {}: {}",
-                event.severity.to_string().to_uppercase(),
-                event.trigger_type.to_string(),
-                event.message
-            )
-        };
-
         // Twilio API URL
         let url = format!(
             "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
             self.twilio_account_sid
         );
 
-        // Send SMS to each recipient
+        // Send SMS to each recipient, in their language
         for to in &config.to {
+            let locale = locale.for_recipient(config.locales.get(to).map(String::as_str));
+            let template = locale
+                .pick(&config.templates, config.template.as_deref())
+                .unwrap_or(locale.sms_body());
+            let body = locale.render(template, event);
+
             let params = [
                 ("From", self.twilio_from_number.as_str()),
                 ("To", to.as_str()),
@@ -685,11 +660,97 @@ impl NotificationChannel for SmsChannel {
     }
 }
 
+/// Activates relay outputs through device-manager
+/// (`POST /v1/devices/:device_id/io/relays/:token`), as the rule's tenant so
+/// only that tenant's devices can be switched
+pub struct DeviceOutputChannel {
+    client: reqwest::Client,
+    device_manager_url: url::Url,
+    jwt_secret: String,
+}
+
+impl DeviceOutputChannel {
+    pub fn new(device_manager_url: &str, jwt_secret: String) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(15))
+                .build()?,
+            device_manager_url: url::Url::parse(device_manager_url).context("Invalid DEVICE_MANAGER_URL")?,
+            jwt_secret,
+        })
+    }
+
+    fn relay_url(&self, device_id: &str, output: &str) -> Result<url::Url> {
+        let mut url = self.device_manager_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("DEVICE_MANAGER_URL cannot be a base URL"))?
+            .pop_if_empty()
+            .extend(["v1", "devices", device_id, "io", "relays", output]);
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for DeviceOutputChannel {
+    async fn send(&self, event: &AlertEvent, action: &AlertAction, _locale: &NotificationLocale) -> Result<()> {
+        let config: DeviceOutputActionConfig = serde_json::from_value(action.config_json.clone())
+            .context("Invalid device output action config")?;
+
+        let identity = AuthContext {
+            user_id: "alert-service".into(),
+            tenant_id: event.tenant_id.to_string(),
+            username: "alert-service".into(),
+            is_system_admin: false,
+            roles: Vec::new(),
+            permissions: vec!["device:output".into()],
+        };
+        let headers = gateway_identity::headers(&identity, &self.jwt_secret, Duration::from_secs(60))?;
+
+        let response = self
+            .client
+            .post(self.relay_url(&config.device_id, &config.output)?)
+            .headers(headers)
+            .json(&serde_json::json!({
+                "state": "active",
+                "duration_secs": config.duration_secs,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Device output request failed with status {}: {}", status, error_text);
+        }
+
+        info!(
+            event_id = %event.id,
+            device_id = %config.device_id,
+            output = %config.output,
+            "Device output triggered"
+        );
+
+        Ok(())
+    }
+
+    fn channel_type(&self) -> ActionType {
+        ActionType::DeviceOutput
+    }
+}
+
 pub struct Notifier {
     store: AlertStore,
     channels: HashMap<ActionType, Arc<dyn NotificationChannel>>,
+    secrets: SecretCipher,
+    /// Never fall back to the global email/SMS senders for tenants without
+    /// their own channel
+    tenant_channels_only: bool,
+    /// Every fired alert is also published here, whatever its rule's actions
+    mqtt_sink: Option<(MqttSink, String)>,
 }
 
+const DEFAULT_MQTT_ALERT_TOPIC: &str = "quadrant/alerts/{tenant_id}/{severity}";
+
 impl Notifier {
     pub fn new(store: AlertStore) -> Self {
         let mut channels: HashMap<ActionType, Arc<dyn NotificationChannel>> = HashMap::new();
@@ -706,7 +767,28 @@ impl Notifier {
         // Add Discord channel (always available - uses webhook URLs)
         channels.insert(ActionType::Discord, Arc::new(DiscordChannel::new()));
 
-        Self { store, channels }
+        Self {
+            store,
+            channels,
+            secrets: SecretCipher::from_env(),
+            tenant_channels_only: false,
+            mqtt_sink: None,
+        }
+    }
+
+    /// Cipher of the secrets in tenant notification channels
+    pub fn secrets(&self) -> &SecretCipher {
+        &self.secrets
+    }
+
+    pub fn set_tenant_channels_only(&mut self, tenant_channels_only: bool) {
+        self.tenant_channels_only = tenant_channels_only;
+    }
+
+    /// Publish every fired alert to `topic`, a template with `{tenant_id}`,
+    /// `{severity}`, `{trigger_type}` and `{rule_id}` placeholders
+    pub fn set_mqtt_sink(&mut self, sink: MqttSink, topic: String) {
+        self.mqtt_sink = Some((sink, topic));
     }
 
     pub fn add_email_channel(
@@ -742,15 +824,179 @@ impl Notifier {
         self.channels.insert(ActionType::Sms, Arc::new(channel));
     }
 
+    /// Notifier with the email and SMS channels configured from `SMTP_*` and
+    /// `TWILIO_*` environment variables when they are set
+    pub fn from_env(store: AlertStore) -> Self {
+        let mut notifier = Self::new(store);
+
+        // Configure email channel if SMTP settings are provided
+        if let (Ok(smtp_host), Ok(smtp_username), Ok(smtp_password), Ok(from_address)) = (
+            std::env::var("SMTP_HOST"),
+            std::env::var("SMTP_USERNAME"),
+            std::env::var("SMTP_PASSWORD"),
+            std::env::var("SMTP_FROM"),
+        ) {
+            let smtp_port = std::env::var("SMTP_PORT")
+                .unwrap_or_else(|_| "587".to_string())
+                .parse()
+                .unwrap_or(587);
+
+            notifier.add_email_channel(
+                smtp_host.clone(),
+                smtp_port,
+                smtp_username,
+                smtp_password,
+                from_address,
+            );
+
+            info!("Email channel configured (SMTP: {}:{})", smtp_host, smtp_port);
+        } else {
+            info!("Email channel not configured (SMTP settings missing)");
+        }
+
+        // Configure SMS channel if Twilio settings are provided
+        if let (Ok(account_sid), Ok(auth_token), Ok(from_number)) = (
+            std::env::var("TWILIO_ACCOUNT_SID"),
+            std::env::var("TWILIO_AUTH_TOKEN"),
+            std::env::var("TWILIO_FROM_NUMBER"),
+        ) {
+            notifier.add_sms_channel(
+                account_sid,
+                auth_token,
+                from_number.clone(),
+            );
+
+            info!("SMS channel configured (Twilio from: {})", from_number);
+        } else {
+            info!("SMS channel not configured (Twilio settings missing)");
+        }
+
+        info!("Slack and Discord channels configured (webhook-based)");
+
+        // Configure device outputs if device-manager is reachable
+        if let (Ok(device_manager_url), Ok(jwt_secret)) = (
+            std::env::var("DEVICE_MANAGER_URL"),
+            std::env::var("JWT_SECRET"),
+        ) {
+            match DeviceOutputChannel::new(&device_manager_url, jwt_secret) {
+                Ok(channel) => {
+                    notifier.channels.insert(ActionType::DeviceOutput, Arc::new(channel));
+                    info!("Device output channel configured (device-manager: {})", device_manager_url);
+                }
+                Err(e) => error!(error = %e, "Device output channel not configured"),
+            }
+        } else {
+            info!("Device output channel not configured (DEVICE_MANAGER_URL or JWT_SECRET missing)");
+        }
+
+        let tenant_channels_only = std::env::var("ALERT_TENANT_CHANNELS_ONLY")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        notifier.set_tenant_channels_only(tenant_channels_only);
+        if tenant_channels_only {
+            info!("Email and SMS are only sent through tenant notification channels");
+        }
+
+        match MqttSinkConfig::from_env("alerts") {
+            Ok(Some(config)) => {
+                let topic = std::env::var("MQTT_SINK_ALERT_TOPIC")
+                    .ok()
+                    .filter(|v| !v.trim().is_empty())
+                    .unwrap_or_else(|| DEFAULT_MQTT_ALERT_TOPIC.to_string());
+                info!(broker = %config.host, topic = %topic, "Alerts are published to MQTT");
+                notifier.set_mqtt_sink(MqttSink::connect(&config), topic);
+            }
+            Ok(None) => {}
+            Err(e) => error!(error = %e, "MQTT sink not configured"),
+        }
+
+        notifier
+    }
+
+    /// Channel that sends a tenant's `action_type` actions: the tenant's own
+    /// email/SMS sender when it has one, the global channel otherwise
+    async fn channel_for(
+        &self,
+        tenant_id: Uuid,
+        action_type: &ActionType,
+    ) -> Result<Option<Arc<dyn NotificationChannel>>> {
+        if matches!(action_type, ActionType::Email | ActionType::Sms) {
+            if let Some(channel) = self.store.get_notification_channel(tenant_id, action_type).await? {
+                if !channel.enabled {
+                    anyhow::bail!("{} channel disabled for tenant", action_type);
+                }
+                return self.tenant_channel(&channel).map(Some);
+            }
+            if self.tenant_channels_only {
+                return Ok(None);
+            }
+        }
+        Ok(self.channels.get(action_type).cloned())
+    }
+
+    fn tenant_channel(&self, channel: &TenantNotificationChannel) -> Result<Arc<dyn NotificationChannel>> {
+        let secret = self
+            .secrets
+            .decrypt(&channel.secret_encrypted)
+            .context("Failed to decrypt tenant channel secret")?;
+
+        match channel.channel_type {
+            ActionType::Email => {
+                let settings: TenantEmailSettings = serde_json::from_value(channel.config_json.clone())
+                    .context("Invalid tenant email channel config")?;
+                let email = EmailChannel::new(
+                    settings.smtp_host,
+                    settings.smtp_port.unwrap_or(587),
+                    settings.smtp_username,
+                    secret,
+                    settings.from_address,
+                )
+                .with_from_name(settings.from_name);
+                Ok(Arc::new(email))
+            }
+            ActionType::Sms => {
+                let settings: TenantSmsSettings = serde_json::from_value(channel.config_json.clone())
+                    .context("Invalid tenant SMS channel config")?;
+                Ok(Arc::new(SmsChannel::new(settings.account_sid, secret, settings.from_number)))
+            }
+            ref other => anyhow::bail!("{} channels are not configured per tenant", other),
+        }
+    }
+
     pub async fn notify(&self, event: &AlertEvent) -> Result<()> {
         if event.suppressed {
             info!(event_id = %event.id, "Event is suppressed, skipping notifications");
             return Ok(());
         }
 
+        if let Some((sink, topic)) = &self.mqtt_sink {
+            let topic = render_topic(
+                topic,
+                &[
+                    ("tenant_id", &event.tenant_id.to_string()),
+                    ("severity", &event.severity.to_string()),
+                    ("trigger_type", &event.trigger_type.to_string()),
+                    ("rule_id", &event.rule_id.to_string()),
+                ],
+            );
+            sink.publish(&topic, &mqtt_payload(event));
+        }
+
         // Get all actions for this rule
         let actions = self.store.list_actions(event.rule_id).await?;
 
+        // Without the tenant's settings, notifications still go out in the
+        // default language and UTC
+        let settings = self
+            .store
+            .get_notification_settings(event.tenant_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!(tenant_id = %event.tenant_id, error = %e, "Failed to load notification settings");
+                None
+            });
+        let locale = NotificationLocale::for_tenant(settings.as_ref());
+
         for action in actions {
             if !action.enabled {
                 continue;
@@ -760,18 +1006,24 @@ impl Notifier {
             let notification = self.store.create_notification(event.id, action.id).await?;
 
             // Get channel
-            let channel = match self.channels.get(&action.action_type) {
-                Some(c) => c,
-                None => {
+            let channel = match self.channel_for(event.tenant_id, &action.action_type).await {
+                Ok(Some(c)) => c,
+                unavailable => {
+                    let reason = match unavailable {
+                        Err(e) => e.to_string(),
+                        _ => "Channel not configured".to_string(),
+                    };
                     error!(
                         action_type = ?action.action_type,
+                        tenant_id = %event.tenant_id,
+                        reason = %reason,
                         "No channel available for action type"
                     );
                     self.store
                         .update_notification_status(
                             notification.id,
                             &NotificationStatus::Failed,
-                            Some("Channel not configured".to_string()),
+                            Some(reason),
                         )
                         .await?;
                     self.store.increment_notifications_failed(event.id).await?;
@@ -780,7 +1032,7 @@ impl Notifier {
             };
 
             // Send notification
-            match channel.send(event, &action).await {
+            match channel.send(event, &action, &locale).await {
                 Ok(_) => {
                     self.store
                         .update_notification_status(notification.id, &NotificationStatus::Sent, None)
@@ -1,14 +1,23 @@
+use crate::anomaly::{AnomalyDetector, ReportSamplesRequest};
+use crate::inbox::{self, CreateWebhookSourceRequest, InboxEvent, IssuedWebhookSource};
 use crate::notifier::Notifier;
 use crate::rule_engine::RuleEngine;
 use crate::store::AlertStore;
 use crate::types::*;
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::IntoResponse,
     Json, Router,
 };
-use common::auth_middleware::RequireAuth;
+use common::auth_middleware::{auth_middleware, AuthMiddlewareConfig, RequireAuth};
+use common::evidence::{self, EvidenceStore};
+use common::privacy::{DataSubject, ERASE_PERMISSION, EXPORT_PERMISSION};
+use common::rate_limit::{rate_limit_middleware, RateLimitConfig, RateLimitKey, RateLimiter};
+use common::openapi::{openapi_routes, OpenApiSpec};
+use common::tenant_rls;
+use common::validated::ValidatedJson;
 use common::validation;
 use serde::Deserialize;
 use serde_json::json;
@@ -38,9 +47,36 @@ pub struct AppState {
     pub store: AlertStore,
     pub engine: Arc<RuleEngine>,
     pub notifier: Arc<Notifier>,
+    /// Learns camera metric baselines and scores reported samples
+    pub anomalies: Arc<AnomalyDetector>,
+    /// Snapshot and clip of the camera at alert time; `None` when
+    /// `EVIDENCE_DIR` is not configured
+    pub evidence: Option<Arc<EvidenceStore>>,
 }
 
 pub fn create_router(state: AppState) -> Router {
+    // Data subject requests come from auth-service with the caller's
+    // identity header, which this layer verifies for `RequireAuth`
+    let privacy_routes = Router::new()
+        .route("/v1/privacy/export", axum::routing::post(privacy_export))
+        .route("/v1/privacy/erase", axum::routing::post(privacy_erase))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(AuthMiddlewareConfig::from_env()),
+            auth_middleware,
+        ));
+
+    // External systems authenticate with their webhook source token, so
+    // the inbox stays outside the tenant scope layer (which expects JWTs)
+    let inbox_limit = RateLimiter::new(RateLimitConfig::from_env(
+        "ALERT_INBOX",
+        60,
+        10.0,
+        RateLimitKey::ApiKey,
+    ));
+    let inbox_routes = Router::new()
+        .route("/v1/inbox/events", axum::routing::post(receive_inbox_event))
+        .route_layer(middleware::from_fn_with_state(inbox_limit, rate_limit_middleware));
+
     Router::new()
         // Health check
         .route("/healthz", axum::routing::get(health_check))
@@ -58,12 +94,82 @@ pub fn create_router(state: AppState) -> Router {
         // Alert Events
         .route("/v1/events", axum::routing::get(list_events))
         .route("/v1/events/:event_id", axum::routing::get(get_event))
+        .route("/v1/events/:event_id/evidence", axum::routing::get(get_event_evidence))
+        .route("/v1/events/:event_id/evidence/:file", axum::routing::get(get_event_evidence_file))
         // Trigger alerts (for integration)
         .route("/v1/trigger", axum::routing::post(trigger_alert))
+        // Camera metric anomalies
+        .route("/v1/anomalies/samples", axum::routing::post(report_samples))
+        .route("/v1/anomalies/baselines", axum::routing::get(list_baselines))
+        .route("/v1/anomalies/baselines/:camera_id", axum::routing::delete(reset_baselines))
+        // Tenant email/SMS senders
+        .route("/v1/notification-channels", axum::routing::get(list_notification_channels))
+        .route("/v1/notification-channels/:channel_type", axum::routing::get(get_notification_channel))
+        .route("/v1/notification-channels/:channel_type", axum::routing::put(put_notification_channel))
+        .route("/v1/notification-channels/:channel_type", axum::routing::delete(delete_notification_channel))
+        // Tenant notification language and time zone
+        .route("/v1/notification-settings", axum::routing::get(get_notification_settings))
+        .route("/v1/notification-settings", axum::routing::put(put_notification_settings))
+        .route("/v1/notification-settings", axum::routing::delete(delete_notification_settings))
+        // Webhook inbox sources
+        .route("/v1/webhook-sources", axum::routing::post(create_webhook_source))
+        .route("/v1/webhook-sources", axum::routing::get(list_webhook_sources))
+        .route("/v1/webhook-sources/:source_id", axum::routing::get(get_webhook_source))
+        .route("/v1/webhook-sources/:source_id", axum::routing::delete(delete_webhook_source))
+        .route("/v1/webhook-sources/:source_id/token", axum::routing::post(rotate_webhook_source_token))
+        .merge(privacy_routes)
+        .merge(openapi_routes(&openapi()))
+        // Limit queries to the caller's tenant whenever credentials are sent
+        .layer(middleware::from_fn_with_state(
+            Arc::new(AuthMiddlewareConfig::from_env()),
+            tenant_rls::tenant_scope_middleware,
+        ))
+        .merge(inbox_routes)
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
 
+/// OpenAPI description of the alert-service API
+pub fn openapi() -> OpenApiSpec {
+    OpenApiSpec::new("alert-service", env!("CARGO_PKG_VERSION"))
+        .with_description("Alert rules, actions and event history")
+        .operations(&[
+            ("GET", "/healthz", "health", "Liveness probe"),
+            ("GET", "/readyz", "health", "Readiness probe"),
+            ("POST", "/v1/rules", "rules", "Create alert rule"),
+            ("GET", "/v1/rules", "rules", "List alert rules"),
+            ("GET", "/v1/rules/:rule_id", "rules", "Get alert rule"),
+            ("PUT", "/v1/rules/:rule_id", "rules", "Update alert rule"),
+            ("DELETE", "/v1/rules/:rule_id", "rules", "Delete alert rule"),
+            ("POST", "/v1/rules/:rule_id/actions", "actions", "Create rule action"),
+            ("GET", "/v1/rules/:rule_id/actions", "actions", "List rule actions"),
+            ("DELETE", "/v1/actions/:action_id", "actions", "Delete action"),
+            ("GET", "/v1/events", "events", "List alert events"),
+            ("GET", "/v1/events/:event_id", "events", "Get alert event"),
+            ("GET", "/v1/events/:event_id/evidence", "events", "Get the snapshot and clip reference captured when the alert fired"),
+            ("GET", "/v1/events/:event_id/evidence/:file", "events", "Download the alert's snapshot.jpg or clip.mp4"),
+            ("POST", "/v1/trigger", "events", "Trigger alert evaluation"),
+            ("POST", "/v1/anomalies/samples", "anomalies", "Report camera metric samples; sharp deviations from the learned baseline raise anomaly triggers"),
+            ("GET", "/v1/anomalies/baselines", "anomalies", "List learned baselines (optionally of one camera_id)"),
+            ("DELETE", "/v1/anomalies/baselines/:camera_id", "anomalies", "Reset a camera's baselines so they are learned again"),
+            ("GET", "/v1/notification-channels", "channels", "List the tenant's email/SMS senders (secrets are never returned)"),
+            ("GET", "/v1/notification-channels/:channel_type", "channels", "Get the tenant's email or sms sender"),
+            ("PUT", "/v1/notification-channels/:channel_type", "channels", "Create or replace the tenant's email or sms sender; the secret is stored encrypted"),
+            ("DELETE", "/v1/notification-channels/:channel_type", "channels", "Remove the tenant's sender; alerts fall back to the global one"),
+            ("GET", "/v1/notification-settings", "channels", "Get the tenant's default notification locale and time zone"),
+            ("PUT", "/v1/notification-settings", "channels", "Set the tenant's default notification locale, time zone (IANA) and datetime_format (strftime)"),
+            ("DELETE", "/v1/notification-settings", "channels", "Remove the tenant's notification settings; notifications go out in English and UTC"),
+            ("GET", "/v1/webhook-sources", "inbox", "List the tenant's webhook inbox sources (tokens are never returned)"),
+            ("POST", "/v1/webhook-sources", "inbox", "Register an external system (name, kind, camera_id); returns its token once"),
+            ("GET", "/v1/webhook-sources/:source_id", "inbox", "Get a webhook inbox source"),
+            ("DELETE", "/v1/webhook-sources/:source_id", "inbox", "Remove a webhook inbox source; its token stops working"),
+            ("POST", "/v1/webhook-sources/:source_id/token", "inbox", "Issue a new token for a source, revoking the old one"),
+            ("POST", "/v1/inbox/events", "inbox", "Post an external event (event_type, event_id, occurred_at, camera_id, message, attributes) with a source token; raises external_event triggers"),
+            ("POST", "/v1/privacy/export", "privacy", "Export a data subject's alert data"),
+            ("POST", "/v1/privacy/erase", "privacy", "Erase a data subject's alert data"),
+        ])
+}
+
 async fn health_check() -> impl IntoResponse {
     Json(json!({
         "status": "healthy",
@@ -98,7 +204,7 @@ async fn ready_check(State(state): State<AppState>) -> impl IntoResponse {
 async fn create_rule(
     State(state): State<AppState>,
     RequireAuth(auth_ctx): RequireAuth,
-    Json(req): Json<CreateAlertRuleRequest>,
+    ValidatedJson(req): ValidatedJson<CreateAlertRuleRequest>,
 ) -> impl IntoResponse {
     // Check permission
     if !auth_ctx.has_permission("alert:create") {
@@ -197,7 +303,7 @@ async fn update_rule(
     State(state): State<AppState>,
     RequireAuth(auth_ctx): RequireAuth,
     Path(rule_id): Path<Uuid>,
-    Json(req): Json<UpdateAlertRuleRequest>,
+    ValidatedJson(req): ValidatedJson<UpdateAlertRuleRequest>,
 ) -> impl IntoResponse {
     // Check permission
     if !auth_ctx.has_permission("alert:update") {
@@ -273,6 +379,25 @@ async fn create_action(
             .into_response();
     }
 
+    // A rule switching a relay output acts for its author later on, so
+    // authors need the right to switch outputs themselves
+    if req.action_type == ActionType::DeviceOutput {
+        if !auth_ctx.has_permission("device:output") {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({"error": "device_output actions need the device:output permission"})),
+            )
+                .into_response();
+        }
+        if let Err(e) = serde_json::from_value::<DeviceOutputActionConfig>(req.config_json.clone()) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid device output action config: {}", e)})),
+            )
+                .into_response();
+        }
+    }
+
     let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
 
     // Verify rule exists and belongs to tenant
@@ -451,6 +576,105 @@ async fn get_event(
     }
 }
 
+/// Evidence captured for an alert event
+async fn get_event_evidence(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(event_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let store = match evidence_store(&state, &auth_ctx, event_id).await {
+        Ok(store) => store,
+        Err(response) => return response,
+    };
+    match store.get(&event_id.to_string()) {
+        Ok(Some(evidence)) => Json(evidence).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "no evidence for this event"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// The snapshot or clip of an alert event
+async fn get_event_evidence_file(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path((event_id, file)): Path<(Uuid, String)>,
+) -> impl IntoResponse {
+    let store = match evidence_store(&state, &auth_ctx, event_id).await {
+        Ok(store) => store,
+        Err(response) => return response,
+    };
+    let path = match store.file(&event_id.to_string(), &file) {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": format!("{} not captured for this event", file)})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    let content_type = if file == evidence::CLIP_FILE { "video/mp4" } else { "image/jpeg" };
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => ([(header::CONTENT_TYPE, content_type)], bytes).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// The evidence store, once the caller may read the event; the event lookup
+/// is tenant scoped, so other tenants' evidence is not found
+async fn evidence_store(
+    state: &AppState,
+    auth_ctx: &common::auth_middleware::AuthContext,
+    event_id: Uuid,
+) -> Result<Arc<EvidenceStore>, axum::response::Response> {
+    if !auth_ctx.has_permission("alert:read") {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response());
+    }
+    let Some(store) = state.evidence.clone() else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": "alert evidence is not enabled"})),
+        )
+            .into_response());
+    };
+    match state.store.get_event(event_id).await {
+        Ok(Some(_)) => Ok(store),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "event not found"})),
+        )
+            .into_response()),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response()),
+    }
+}
+
 // Trigger alert endpoint (for integration with other services)
 
 async fn trigger_alert(
@@ -476,8 +700,19 @@ async fn trigger_alert(
         }
     };
 
-    // Send notifications for each event
-    for event in &events {
+    notify_events(&state, &events).await;
+
+    Json(json!({
+        "fired_count": events.len(),
+        "events": events,
+    }))
+    .into_response()
+}
+
+/// Send the notifications of fired events
+async fn notify_events(state: &AppState, events: &[AlertEvent]) {
+    for event in events {
+        capture_evidence(state, event);
         if let Err(e) = state.notifier.notify(event).await {
             tracing::error!(
                 event_id = %event.id,
@@ -486,10 +721,710 @@ async fn trigger_alert(
             );
         }
     }
+}
+
+/// Keep a snapshot and clip of the event's camera, when the event names one
+fn capture_evidence(state: &AppState, event: &AlertEvent) {
+    let Some(store) = &state.evidence else {
+        return;
+    };
+    if event.suppressed {
+        return;
+    }
+    let Some(camera_id) = event.context_json.get("camera_id").and_then(|v| v.as_str()) else {
+        return;
+    };
+    store.capture(
+        event.id.to_string(),
+        event.tenant_id.to_string(),
+        camera_id.to_string(),
+    );
+}
+
+// Camera metric anomalies
+
+async fn report_samples(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    ValidatedJson(req): ValidatedJson<ReportSamplesRequest>,
+) -> impl IntoResponse {
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    let mut anomalies = Vec::new();
+    let mut events = Vec::new();
+    for sample in &req.samples {
+        let anomaly = match state.anomalies.observe(tenant_id, sample).await {
+            Ok(Some(anomaly)) => anomaly,
+            Ok(None) => continue,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response()
+            }
+        };
+        tracing::info!(
+            camera_id = %anomaly.camera_id,
+            metric = anomaly.metric.as_str(),
+            value = anomaly.value,
+            expected = anomaly.expected,
+            "camera metric anomaly"
+        );
+        match state
+            .engine
+            .evaluate_and_fire(tenant_id, &TriggerType::Anomaly, anomaly.message(), anomaly.context())
+            .await
+        {
+            Ok(fired) => events.extend(fired),
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response()
+            }
+        }
+        anomalies.push(anomaly);
+    }
+
+    notify_events(&state, &events).await;
 
     Json(json!({
+        "accepted": req.samples.len(),
+        "anomalies": anomalies,
         "fired_count": events.len(),
-        "events": events,
     }))
     .into_response()
 }
+
+#[derive(Debug, Deserialize)]
+struct ListBaselinesQuery {
+    camera_id: Option<String>,
+}
+
+async fn list_baselines(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Query(query): Query<ListBaselinesQuery>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:read") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state
+        .store
+        .list_anomaly_baselines(tenant_id, query.camera_id.as_deref())
+        .await
+    {
+        Ok(baselines) => Json(baselines).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn reset_baselines(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(camera_id): Path<String>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state.store.delete_anomaly_baselines(tenant_id, &camera_id).await {
+        Ok(deleted) => Json(json!({"camera_id": camera_id, "baselines_deleted": deleted})).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// Tenant notification channels
+
+/// Email or SMS, the channel types configured per tenant
+fn tenant_channel_type(channel_type: &str) -> Result<ActionType, (StatusCode, Json<serde_json::Value>)> {
+    match channel_type.parse::<ActionType>() {
+        Ok(t @ (ActionType::Email | ActionType::Sms)) => Ok(t),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("Invalid channel type: {} (expected email or sms)", channel_type)})),
+        )),
+    }
+}
+
+async fn list_notification_channels(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:read") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state.store.list_notification_channels(tenant_id).await {
+        Ok(channels) => Json(channels).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_notification_channel(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(channel_type): Path<String>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:read") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+    let channel_type = match tenant_channel_type(&channel_type) {
+        Ok(t) => t,
+        Err(err_response) => return err_response.into_response(),
+    };
+
+    match state.store.get_notification_channel(tenant_id, &channel_type).await {
+        Ok(Some(channel)) => Json(channel).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "notification channel not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn put_notification_channel(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(channel_type): Path<String>,
+    Json(req): Json<UpsertNotificationChannelRequest>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+    let channel_type = match tenant_channel_type(&channel_type) {
+        Ok(t) => t,
+        Err(err_response) => return err_response.into_response(),
+    };
+    if let Err(e) = req.validate_for(&channel_type) {
+        return (StatusCode::BAD_REQUEST, Json(json!({"error": e}))).into_response();
+    }
+
+    // A new channel needs its secret
+    if req.secret.is_none() {
+        match state.store.get_notification_channel(tenant_id, &channel_type).await {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "secret is required to create a notification channel"})),
+                )
+                    .into_response()
+            }
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    let secret_encrypted = match req.secret.as_deref().map(|s| state.notifier.secrets().encrypt(s)).transpose() {
+        Ok(sealed) => sealed,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+
+    match state
+        .store
+        .upsert_notification_channel(
+            tenant_id,
+            &channel_type,
+            &req.config_json,
+            secret_encrypted.as_deref(),
+            req.enabled,
+        )
+        .await
+    {
+        Ok(channel) => Json(channel).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn delete_notification_channel(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(channel_type): Path<String>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+    let channel_type = match tenant_channel_type(&channel_type) {
+        Ok(t) => t,
+        Err(err_response) => return err_response.into_response(),
+    };
+
+    match state.store.delete_notification_channel(tenant_id, &channel_type).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "notification channel not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// Tenant notification settings
+
+async fn get_notification_settings(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:read") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state.store.get_notification_settings(tenant_id).await {
+        Ok(Some(settings)) => Json(settings).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "notification settings not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn put_notification_settings(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Json(req): Json<UpsertNotificationSettingsRequest>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+    if let Err(e) = req.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({"error": e}))).into_response();
+    }
+
+    match state.store.upsert_notification_settings(tenant_id, &req).await {
+        Ok(settings) => Json(settings).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn delete_notification_settings(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state.store.delete_notification_settings(tenant_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "notification settings not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+// Webhook inbox
+
+async fn create_webhook_source(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    ValidatedJson(req): ValidatedJson<CreateWebhookSourceRequest>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    let token = inbox::generate_token();
+    match state.store.create_webhook_source(tenant_id, &req, &inbox::hash_token(&token)).await {
+        Ok(source) => (StatusCode::CREATED, Json(IssuedWebhookSource { source, token })).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn list_webhook_sources(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:read") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state.store.list_webhook_sources(tenant_id).await {
+        Ok(sources) => Json(sources).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_webhook_source(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(source_id): Path<Uuid>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:read") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state.store.get_webhook_source(source_id, tenant_id).await {
+        Ok(Some(source)) => Json(source).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "webhook source not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn rotate_webhook_source_token(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(source_id): Path<Uuid>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    let token = inbox::generate_token();
+    match state
+        .store
+        .rotate_webhook_source_token(source_id, tenant_id, &inbox::hash_token(&token))
+        .await
+    {
+        Ok(Some(source)) => Json(IssuedWebhookSource { source, token }).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "webhook source not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn delete_webhook_source(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(source_id): Path<Uuid>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("alert:update") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = match validation::parse_uuid(&auth_ctx.tenant_id, "tenant_id") { Ok(id) => id, Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": format!("Invalid tenant_id: {}", e)}))).into_response(), };
+
+    match state.store.delete_webhook_source(source_id, tenant_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "webhook source not found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+/// Event posted by an external system: evaluated against the source
+/// tenant's `external_event` rules and recorded on the timeline
+async fn receive_inbox_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ValidatedJson(event): ValidatedJson<InboxEvent>,
+) -> impl IntoResponse {
+    let Some(token) = inbox::token_from_headers(&headers) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"error": "missing webhook token"})),
+        )
+            .into_response();
+    };
+    let source = match state.store.find_webhook_source_by_token(&inbox::hash_token(token)).await {
+        Ok(Some(source)) if source.enabled => source,
+        Ok(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({"error": "invalid webhook token"})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+
+    tenant_rls::scope(source.tenant_id.to_string(), async move {
+        let events = match state
+            .engine
+            .evaluate_and_fire(
+                source.tenant_id,
+                &TriggerType::ExternalEvent,
+                event.message(&source),
+                event.context(&source),
+            )
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response()
+            }
+        };
+
+        common::timeline::record(event.timeline_event(&source));
+        if let Err(e) = state.store.touch_webhook_source(source.id).await {
+            tracing::warn!(source_id = %source.id, error = %e, "Failed to record webhook source activity");
+        }
+        tracing::info!(
+            source = %source.name,
+            event_type = %event.event_type,
+            fired = events.len(),
+            "external event received"
+        );
+
+        notify_events(&state, &events).await;
+
+        (
+            StatusCode::ACCEPTED,
+            Json(json!({
+                "source_id": source.id,
+                "fired_count": events.len(),
+                "events": events,
+            })),
+        )
+            .into_response()
+    })
+    .await
+}
+
+// Data subject requests
+
+/// Tenant a data subject request is limited to: the subject's tenant when
+/// alert-service knows it (tenants here are UUIDs), every tenant for a
+/// system admin otherwise. Other callers only reach their own tenant.
+fn subject_tenant(
+    auth_ctx: &common::auth_middleware::AuthContext,
+    subject: &DataSubject,
+) -> Result<Option<Uuid>, (StatusCode, Json<serde_json::Value>)> {
+    if let Err(e) = subject.validate() {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))));
+    }
+    if auth_ctx.is_system_admin {
+        return Ok(Uuid::parse_str(&subject.tenant_id).ok());
+    }
+    if subject.tenant_id != auth_ctx.tenant_id {
+        return Err((StatusCode::FORBIDDEN, Json(json!({"error": "permission denied"}))));
+    }
+    parse_auth_uuids(auth_ctx).map(|(tenant_id, _)| Some(tenant_id))
+}
+
+async fn privacy_export(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Json(subject): Json<DataSubject>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission(EXPORT_PERMISSION) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+    let tenant_id = match subject_tenant(&auth_ctx, &subject) {
+        Ok(tenant_id) => tenant_id,
+        Err(err_response) => return err_response.into_response(),
+    };
+
+    match state.store.export_subject(tenant_id, &subject).await {
+        Ok(export) => Json(export).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
+async fn privacy_erase(
+    State(state): State<AppState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Json(subject): Json<DataSubject>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission(ERASE_PERMISSION) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+    let tenant_id = match subject_tenant(&auth_ctx, &subject) {
+        Ok(tenant_id) => tenant_id,
+        Err(err_response) => return err_response.into_response(),
+    };
+
+    match state.store.erase_subject(tenant_id, &subject).await {
+        Ok(outcome) => {
+            tracing::info!(tenant_id = %subject.tenant_id, ?outcome, "erased data subject alert data");
+            Json(outcome).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
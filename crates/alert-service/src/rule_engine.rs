@@ -2,6 +2,7 @@ use crate::store::{AlertStore, SuppressionState};
 use crate::types::*;
 use anyhow::{Context, Result};
 use chrono::{Duration, Utc};
+use common::timeline::{self, TimelineEvent, TimelineEventKind};
 use std::collections::HashMap;
 use std::str::FromStr;
 use uuid::Uuid;
@@ -101,6 +102,8 @@ impl RuleEngine {
                 "Alert fired"
             );
 
+            timeline::record(timeline_event(&rule, &event));
+
             // Update suppression state
             self.update_suppression_state(&rule).await?;
 
@@ -315,6 +318,31 @@ impl RuleEngine {
     }
 }
 
+/// Timeline entry for a fired (unsuppressed) alert. The camera comes from
+/// the trigger context when the caller supplied one.
+fn timeline_event(rule: &AlertRule, event: &AlertEvent) -> TimelineEvent {
+    let mut entry = TimelineEvent::new(
+        TimelineEventKind::Alert,
+        "alert-service",
+        format!("{}: {}", rule.name, event.message),
+    )
+    .at(event.fired_at.timestamp_millis().max(0) as u64)
+    .tenant(event.tenant_id.to_string())
+    .details(serde_json::json!({
+        "event_id": event.id,
+        "rule_id": rule.id,
+        "severity": event.severity.to_string(),
+        "trigger_type": event.trigger_type.to_string(),
+    }));
+    let camera = ["camera_id", "device_id"]
+        .iter()
+        .find_map(|key| event.context_json.get(*key).and_then(|v| v.as_str()));
+    if let Some(camera) = camera {
+        entry = entry.camera(camera);
+    }
+    entry
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,118 @@
+//! Encryption of the secrets in tenant notification channels (SMTP
+//! passwords, Twilio auth tokens). Secrets are sealed with AES-256-GCM under
+//! a key derived with Argon2id from `ALERT_CHANNEL_MASTER_KEY`, the same
+//! `v1$salt$nonce$ciphertext` format device-manager uses for camera
+//! passwords. They are decrypted only to send a notification.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm,
+};
+use anyhow::{Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use rand::Rng;
+use tracing::warn;
+
+const INSECURE_DEFAULT_KEY: &str = "INSECURE_DEFAULT_KEY_CHANGE_IN_PRODUCTION";
+
+#[derive(Clone)]
+pub struct SecretCipher {
+    master_key: String,
+}
+
+impl SecretCipher {
+    pub fn new(master_key: impl Into<String>) -> Self {
+        Self {
+            master_key: master_key.into(),
+        }
+    }
+
+    /// Cipher keyed by `ALERT_CHANNEL_MASTER_KEY`
+    pub fn from_env() -> Self {
+        match std::env::var("ALERT_CHANNEL_MASTER_KEY") {
+            Ok(key) if !key.is_empty() => Self::new(key),
+            _ => {
+                warn!("ALERT_CHANNEL_MASTER_KEY not set, tenant channel secrets use an insecure default key");
+                Self::new(INSECURE_DEFAULT_KEY)
+            }
+        }
+    }
+
+    fn cipher(&self, salt: &[u8]) -> Result<Aes256Gcm> {
+        let params = Params::new(19456, 2, 1, Some(32))
+            .map_err(|e| anyhow::anyhow!("invalid Argon2 params: {}", e))?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+        let mut derived_key = [0u8; 32];
+        argon2
+            .hash_password_into(self.master_key.as_bytes(), salt, &mut derived_key)
+            .map_err(|e| anyhow::anyhow!("Argon2 key derivation failed: {}", e))?;
+
+        Ok(Aes256Gcm::new(&derived_key.into()))
+    }
+
+    pub fn encrypt(&self, secret: &str) -> Result<String> {
+        let salt: [u8; 32] = rand::thread_rng().gen();
+        let nonce_bytes: [u8; 12] = rand::thread_rng().gen();
+
+        let ciphertext = self
+            .cipher(&salt)?
+            .encrypt(&nonce_bytes.into(), secret.as_bytes())
+            .map_err(|e| anyhow::anyhow!("AES-GCM encryption failed: {}", e))?;
+
+        Ok(format!(
+            "v1${}${}${}",
+            general_purpose::STANDARD.encode(salt),
+            general_purpose::STANDARD.encode(nonce_bytes),
+            general_purpose::STANDARD.encode(ciphertext)
+        ))
+    }
+
+    pub fn decrypt(&self, encrypted: &str) -> Result<String> {
+        let parts: Vec<&str> = encrypted.split('$').collect();
+        if parts.len() != 4 || parts[0] != "v1" {
+            anyhow::bail!("invalid encrypted secret format");
+        }
+
+        let salt = general_purpose::STANDARD
+            .decode(parts[1])
+            .context("failed to decode salt")?;
+        let nonce_bytes = general_purpose::STANDARD
+            .decode(parts[2])
+            .context("failed to decode nonce")?;
+        let ciphertext = general_purpose::STANDARD
+            .decode(parts[3])
+            .context("failed to decode ciphertext")?;
+
+        let nonce: [u8; 12] = nonce_bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("invalid nonce length: expected 12 bytes, got {}", nonce_bytes.len()))?;
+        let plaintext = self
+            .cipher(&salt)?
+            .decrypt(&nonce.into(), ciphertext.as_ref())
+            .map_err(|e| anyhow::anyhow!("AES-GCM decryption failed: {}", e))?;
+
+        String::from_utf8(plaintext).context("invalid utf8 in secret")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_round_trip_only_under_their_key() {
+        let cipher = SecretCipher::new("tenant-channel-test-key");
+        let sealed = cipher.encrypt("smtp-password").unwrap();
+
+        assert!(sealed.starts_with("v1$"));
+        assert!(!sealed.contains("smtp-password"));
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "smtp-password");
+        assert_ne!(cipher.encrypt("smtp-password").unwrap(), sealed);
+
+        assert!(SecretCipher::new("another-key").decrypt(&sealed).is_err());
+        assert!(cipher.decrypt("plaintext").is_err());
+    }
+}
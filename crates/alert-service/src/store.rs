@@ -1,5 +1,8 @@
+use crate::anomaly::{AnomalyBaseline, BaselineStats};
+use crate::inbox::{CreateWebhookSourceRequest, WebhookSource};
 use crate::types::*;
 use anyhow::Result;
+use common::privacy::{normalize_plate, DataSubject, ErasureOutcome, ServiceExport};
 use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
@@ -473,6 +476,526 @@ impl AlertStore {
 
         Ok(rules)
     }
+
+    // Data Subject Requests
+
+    /// Rules, actions and events holding a data subject's personal data.
+    /// A `tenant_id` of `None` searches every tenant.
+    pub async fn export_subject(&self, tenant_id: Option<Uuid>, subject: &DataSubject) -> Result<ServiceExport> {
+        let keys = SubjectKeys::from(subject);
+        let mut export = ServiceExport::default();
+
+        if let Some(user_id) = keys.user_id {
+            let rules: Vec<serde_json::Value> = sqlx::query_scalar(
+                "SELECT to_jsonb(r) FROM alert_rules r WHERE ($1::uuid IS NULL OR r.tenant_id = $1) AND r.created_by = $2",
+            )
+            .bind(tenant_id)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+            export.add("alert_rules_created", &rules)?;
+        }
+
+        if let Some(email) = &subject.email {
+            let actions: Vec<serde_json::Value> = sqlx::query_scalar(
+                r#"
+                SELECT to_jsonb(a) FROM alert_actions a
+                JOIN alert_rules r ON r.id = a.rule_id
+                WHERE ($1::uuid IS NULL OR r.tenant_id = $1) AND a.config_json->'to' ? $2
+                "#,
+            )
+            .bind(tenant_id)
+            .bind(email)
+            .fetch_all(&self.pool)
+            .await?;
+            export.add("alert_action_recipients", &actions)?;
+        }
+
+        let events: Vec<serde_json::Value> = sqlx::query_scalar(&format!(
+            "SELECT to_jsonb(e) FROM alert_events e WHERE {SUBJECT_EVENTS} ORDER BY e.fired_at DESC"
+        ))
+        .bind(tenant_id)
+        .bind(&keys.face_ids)
+        .bind(&keys.names)
+        .bind(&keys.plates)
+        .bind(&subject.user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        export.add("alert_events", &events)?;
+
+        Ok(export)
+    }
+
+    /// Remove a data subject from rules, actions and events: their email
+    /// leaves action recipient lists and locales (actions left without
+    /// recipients are disabled), rules they created lose the creator, and
+    /// events about them lose the message and the identifying context fields.
+    pub async fn erase_subject(&self, tenant_id: Option<Uuid>, subject: &DataSubject) -> Result<ErasureOutcome> {
+        let keys = SubjectKeys::from(subject);
+        let mut tx = self.pool.begin().await?;
+        let mut outcome = ErasureOutcome::default();
+
+        if let Some(user_id) = keys.user_id {
+            let rules = sqlx::query(
+                "UPDATE alert_rules SET created_by = NULL WHERE ($1::uuid IS NULL OR tenant_id = $1) AND created_by = $2",
+            )
+            .bind(tenant_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+            outcome = outcome.anonymized("alert_rules_created", rules.rows_affected());
+        }
+
+        if let Some(email) = &subject.email {
+            let actions = sqlx::query(
+                r#"
+                UPDATE alert_actions a
+                SET config_json = jsonb_set(a.config_json, '{to}', (a.config_json->'to') - $2::text)
+                        #- ARRAY['locales', $2::text],
+                    enabled = a.enabled AND jsonb_array_length((a.config_json->'to') - $2::text) > 0
+                FROM alert_rules r
+                WHERE r.id = a.rule_id AND ($1::uuid IS NULL OR r.tenant_id = $1) AND a.config_json->'to' ? $2
+                "#,
+            )
+            .bind(tenant_id)
+            .bind(email)
+            .execute(&mut *tx)
+            .await?;
+            outcome = outcome.anonymized("alert_action_recipients", actions.rows_affected());
+        }
+
+        let events = sqlx::query(&format!(
+            r#"
+            UPDATE alert_events e
+            SET message = '[redacted]',
+                context_json = e.context_json - 'face_id' - 'face_name' - 'plate' - 'user_id'
+            WHERE {SUBJECT_EVENTS}
+            "#
+        ))
+        .bind(tenant_id)
+        .bind(&keys.face_ids)
+        .bind(&keys.names)
+        .bind(&keys.plates)
+        .bind(&subject.user_id)
+        .execute(&mut *tx)
+        .await?;
+        outcome = outcome.anonymized("alert_events", events.rows_affected());
+
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    // Anomaly baselines
+
+    pub async fn get_anomaly_baseline(
+        &self,
+        tenant_id: Uuid,
+        camera_id: &str,
+        metric: &str,
+        hour_of_day: i16,
+    ) -> Result<Option<BaselineStats>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT mean, variance, samples
+            FROM anomaly_baselines
+            WHERE tenant_id = $1 AND camera_id = $2 AND metric = $3 AND hour_of_day = $4
+            "#,
+            tenant_id,
+            camera_id,
+            metric,
+            hour_of_day
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| BaselineStats {
+            mean: r.mean,
+            variance: r.variance,
+            samples: r.samples,
+        }))
+    }
+
+    pub async fn upsert_anomaly_baseline(
+        &self,
+        tenant_id: Uuid,
+        camera_id: &str,
+        metric: &str,
+        hour_of_day: i16,
+        stats: &BaselineStats,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO anomaly_baselines (tenant_id, camera_id, metric, hour_of_day, mean, variance, samples, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+            ON CONFLICT (tenant_id, camera_id, metric, hour_of_day) DO UPDATE
+            SET mean = EXCLUDED.mean,
+                variance = EXCLUDED.variance,
+                samples = EXCLUDED.samples,
+                updated_at = NOW()
+            "#,
+            tenant_id,
+            camera_id,
+            metric,
+            hour_of_day,
+            stats.mean,
+            stats.variance,
+            stats.samples
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_anomaly_baselines(&self, tenant_id: Uuid, camera_id: Option<&str>) -> Result<Vec<AnomalyBaseline>> {
+        let baselines = sqlx::query_as!(
+            AnomalyBaseline,
+            r#"
+            SELECT tenant_id, camera_id, metric, hour_of_day, mean, variance, samples, updated_at
+            FROM anomaly_baselines
+            WHERE tenant_id = $1 AND ($2::text IS NULL OR camera_id = $2)
+            ORDER BY camera_id, metric, hour_of_day
+            "#,
+            tenant_id,
+            camera_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(baselines)
+    }
+
+    /// Forget a camera's baselines so they are learned again
+    pub async fn delete_anomaly_baselines(&self, tenant_id: Uuid, camera_id: &str) -> Result<u64> {
+        let result = sqlx::query!(
+            "DELETE FROM anomaly_baselines WHERE tenant_id = $1 AND camera_id = $2",
+            tenant_id,
+            camera_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Tenant notification channels
+
+    pub async fn list_notification_channels(&self, tenant_id: Uuid) -> Result<Vec<TenantNotificationChannel>> {
+        let channels = sqlx::query_as!(
+            TenantNotificationChannel,
+            r#"
+            SELECT tenant_id, channel_type as "channel_type: ActionType", config_json, enabled, secret_encrypted, created_at, updated_at
+            FROM notification_channels
+            WHERE tenant_id = $1
+            ORDER BY channel_type
+            "#,
+            tenant_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(channels)
+    }
+
+    pub async fn get_notification_channel(
+        &self,
+        tenant_id: Uuid,
+        channel_type: &ActionType,
+    ) -> Result<Option<TenantNotificationChannel>> {
+        let channel = sqlx::query_as!(
+            TenantNotificationChannel,
+            r#"
+            SELECT tenant_id, channel_type as "channel_type: ActionType", config_json, enabled, secret_encrypted, created_at, updated_at
+            FROM notification_channels
+            WHERE tenant_id = $1 AND channel_type = $2
+            "#,
+            tenant_id,
+            channel_type.to_string()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(channel)
+    }
+
+    /// Create or replace a tenant's channel settings. Without a new secret
+    /// the stored one is kept, so creating a channel needs one.
+    pub async fn upsert_notification_channel(
+        &self,
+        tenant_id: Uuid,
+        channel_type: &ActionType,
+        config_json: &serde_json::Value,
+        secret_encrypted: Option<&str>,
+        enabled: Option<bool>,
+    ) -> Result<TenantNotificationChannel> {
+        let updated = sqlx::query_as!(
+            TenantNotificationChannel,
+            r#"
+            UPDATE notification_channels
+            SET config_json = $3,
+                secret_encrypted = COALESCE($4, secret_encrypted),
+                enabled = COALESCE($5, enabled),
+                updated_at = NOW()
+            WHERE tenant_id = $1 AND channel_type = $2
+            RETURNING tenant_id, channel_type as "channel_type: ActionType", config_json, enabled, secret_encrypted, created_at, updated_at
+            "#,
+            tenant_id,
+            channel_type.to_string(),
+            config_json,
+            secret_encrypted,
+            enabled
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(channel) = updated {
+            return Ok(channel);
+        }
+
+        let secret_encrypted = secret_encrypted
+            .ok_or_else(|| anyhow::anyhow!("a secret is required to create a {} channel", channel_type))?;
+
+        let channel = sqlx::query_as!(
+            TenantNotificationChannel,
+            r#"
+            INSERT INTO notification_channels (tenant_id, channel_type, config_json, secret_encrypted, enabled)
+            VALUES ($1, $2::text, $3, $4, $5)
+            RETURNING tenant_id, channel_type as "channel_type: ActionType", config_json, enabled, secret_encrypted, created_at, updated_at
+            "#,
+            tenant_id,
+            channel_type.to_string(),
+            config_json,
+            secret_encrypted,
+            enabled.unwrap_or(true)
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(channel)
+    }
+
+    pub async fn delete_notification_channel(&self, tenant_id: Uuid, channel_type: &ActionType) -> Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM notification_channels WHERE tenant_id = $1 AND channel_type = $2",
+            tenant_id,
+            channel_type.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Tenant notification settings
+
+    pub async fn get_notification_settings(&self, tenant_id: Uuid) -> Result<Option<NotificationSettings>> {
+        let settings = sqlx::query_as!(
+            NotificationSettings,
+            r#"
+            SELECT tenant_id, locale, time_zone, datetime_format, created_at, updated_at
+            FROM notification_settings
+            WHERE tenant_id = $1
+            "#,
+            tenant_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(settings)
+    }
+
+    pub async fn upsert_notification_settings(
+        &self,
+        tenant_id: Uuid,
+        req: &UpsertNotificationSettingsRequest,
+    ) -> Result<NotificationSettings> {
+        let settings = sqlx::query_as!(
+            NotificationSettings,
+            r#"
+            INSERT INTO notification_settings (tenant_id, locale, time_zone, datetime_format)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (tenant_id) DO UPDATE
+            SET locale = EXCLUDED.locale,
+                time_zone = EXCLUDED.time_zone,
+                datetime_format = EXCLUDED.datetime_format,
+                updated_at = NOW()
+            RETURNING tenant_id, locale, time_zone, datetime_format, created_at, updated_at
+            "#,
+            tenant_id,
+            req.locale,
+            req.time_zone,
+            req.datetime_format
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(settings)
+    }
+
+    pub async fn delete_notification_settings(&self, tenant_id: Uuid) -> Result<bool> {
+        let result = sqlx::query!("DELETE FROM notification_settings WHERE tenant_id = $1", tenant_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Webhook inbox sources
+
+    pub async fn create_webhook_source(
+        &self,
+        tenant_id: Uuid,
+        req: &CreateWebhookSourceRequest,
+        token_hash: &str,
+    ) -> Result<WebhookSource> {
+        let source = sqlx::query_as!(
+            WebhookSource,
+            r#"
+            INSERT INTO webhook_sources (tenant_id, name, kind, camera_id, token_hash, enabled)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, tenant_id, name, kind, camera_id, enabled, last_event_at, token_hash, created_at, updated_at
+            "#,
+            tenant_id,
+            req.name,
+            req.kind,
+            req.camera_id,
+            token_hash,
+            req.enabled.unwrap_or(true)
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(source)
+    }
+
+    pub async fn list_webhook_sources(&self, tenant_id: Uuid) -> Result<Vec<WebhookSource>> {
+        let sources = sqlx::query_as!(
+            WebhookSource,
+            r#"
+            SELECT id, tenant_id, name, kind, camera_id, enabled, last_event_at, token_hash, created_at, updated_at
+            FROM webhook_sources
+            WHERE tenant_id = $1
+            ORDER BY name
+            "#,
+            tenant_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(sources)
+    }
+
+    pub async fn get_webhook_source(&self, id: Uuid, tenant_id: Uuid) -> Result<Option<WebhookSource>> {
+        let source = sqlx::query_as!(
+            WebhookSource,
+            r#"
+            SELECT id, tenant_id, name, kind, camera_id, enabled, last_event_at, token_hash, created_at, updated_at
+            FROM webhook_sources
+            WHERE id = $1 AND tenant_id = $2
+            "#,
+            id,
+            tenant_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(source)
+    }
+
+    /// Source a posted token belongs to, in any tenant
+    pub async fn find_webhook_source_by_token(&self, token_hash: &str) -> Result<Option<WebhookSource>> {
+        let source = sqlx::query_as!(
+            WebhookSource,
+            r#"
+            SELECT id, tenant_id, name, kind, camera_id, enabled, last_event_at, token_hash, created_at, updated_at
+            FROM webhook_sources
+            WHERE token_hash = $1
+            "#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(source)
+    }
+
+    /// Replace a source's token; the old one stops working at once
+    pub async fn rotate_webhook_source_token(
+        &self,
+        id: Uuid,
+        tenant_id: Uuid,
+        token_hash: &str,
+    ) -> Result<Option<WebhookSource>> {
+        let source = sqlx::query_as!(
+            WebhookSource,
+            r#"
+            UPDATE webhook_sources
+            SET token_hash = $3, updated_at = NOW()
+            WHERE id = $1 AND tenant_id = $2
+            RETURNING id, tenant_id, name, kind, camera_id, enabled, last_event_at, token_hash, created_at, updated_at
+            "#,
+            id,
+            tenant_id,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(source)
+    }
+
+    pub async fn touch_webhook_source(&self, id: Uuid) -> Result<()> {
+        sqlx::query!(
+            "UPDATE webhook_sources SET last_event_at = NOW() WHERE id = $1",
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_webhook_source(&self, id: Uuid, tenant_id: Uuid) -> Result<bool> {
+        let result = sqlx::query!(
+            "DELETE FROM webhook_sources WHERE id = $1 AND tenant_id = $2",
+            id,
+            tenant_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Events whose context names a data subject; binds the tenant ($1), face
+/// ids ($2), lower-case names ($3), normalized plates ($4) and user id ($5)
+const SUBJECT_EVENTS: &str = r#"
+    ($1::uuid IS NULL OR e.tenant_id = $1)
+    AND (e.context_json->>'face_id' = ANY($2)
+        OR lower(trim(e.context_json->>'face_name')) = ANY($3)
+        OR upper(regexp_replace(e.context_json->>'plate', '[^A-Za-z0-9]', '', 'g')) = ANY($4)
+        OR e.context_json->>'user_id' = $5)
+"#;
+
+/// A data subject's identifiers in the form alert-service stores them
+struct SubjectKeys {
+    user_id: Option<Uuid>,
+    face_ids: Vec<String>,
+    names: Vec<String>,
+    plates: Vec<String>,
+}
+
+impl From<&DataSubject> for SubjectKeys {
+    fn from(subject: &DataSubject) -> Self {
+        Self {
+            // Rule creators are stored as UUIDs; other user ids never match
+            user_id: subject.user_id.as_deref().and_then(|id| Uuid::parse_str(id).ok()),
+            face_ids: subject.face_ids.clone(),
+            names: subject.names.iter().map(|n| n.trim().to_lowercase()).collect(),
+            plates: subject.plates.iter().map(|p| normalize_plate(p)).collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -2,9 +2,10 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
+use validator::Validate;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type, Default)]
-#[sqlx(type_name = "text")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
 pub enum Severity {
     #[default]
@@ -40,7 +41,7 @@ impl std::str::FromStr for Severity {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type, Default)]
-#[sqlx(type_name = "text")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
 pub enum TriggerType {
     DeviceOffline,
@@ -54,6 +55,12 @@ pub enum TriggerType {
     StreamStopped,
     StreamFailed,
     HealthCheckFailed,
+    Anomaly,
+    StorageCapacity,
+    /// Live playback running behind its protocol's latency budget
+    LatencyBudget,
+    /// Event posted by an external system to the webhook inbox
+    ExternalEvent,
     #[default]
     Custom,
 }
@@ -72,6 +79,10 @@ impl std::fmt::Display for TriggerType {
             TriggerType::StreamStopped => "stream_stopped",
             TriggerType::StreamFailed => "stream_failed",
             TriggerType::HealthCheckFailed => "health_check_failed",
+            TriggerType::Anomaly => "anomaly",
+            TriggerType::StorageCapacity => "storage_capacity",
+            TriggerType::LatencyBudget => "latency_budget",
+            TriggerType::ExternalEvent => "external_event",
             TriggerType::Custom => "custom",
         };
         write!(f, "{}", s)
@@ -94,6 +105,10 @@ impl std::str::FromStr for TriggerType {
             "stream_stopped" => Ok(TriggerType::StreamStopped),
             "stream_failed" => Ok(TriggerType::StreamFailed),
             "health_check_failed" => Ok(TriggerType::HealthCheckFailed),
+            "anomaly" => Ok(TriggerType::Anomaly),
+            "storage_capacity" => Ok(TriggerType::StorageCapacity),
+            "latency_budget" => Ok(TriggerType::LatencyBudget),
+            "external_event" => Ok(TriggerType::ExternalEvent),
             "custom" => Ok(TriggerType::Custom),
             _ => Err(format!("Invalid trigger type: {}", s)),
         }
@@ -101,7 +116,7 @@ impl std::str::FromStr for TriggerType {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, sqlx::Type)]
-#[sqlx(type_name = "text")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
 pub enum ActionType {
     Email,
@@ -110,6 +125,8 @@ pub enum ActionType {
     Slack,
     Discord,
     Sms,
+    /// Activate a relay output of a device through device-manager
+    DeviceOutput,
 }
 
 impl std::fmt::Display for ActionType {
@@ -121,6 +138,7 @@ impl std::fmt::Display for ActionType {
             ActionType::Slack => write!(f, "slack"),
             ActionType::Discord => write!(f, "discord"),
             ActionType::Sms => write!(f, "sms"),
+            ActionType::DeviceOutput => write!(f, "device_output"),
         }
     }
 }
@@ -136,6 +154,7 @@ impl std::str::FromStr for ActionType {
             "slack" => Ok(ActionType::Slack),
             "discord" => Ok(ActionType::Discord),
             "sms" => Ok(ActionType::Sms),
+            "device_output" => Ok(ActionType::DeviceOutput),
             _ => Err(format!("Invalid action type: {}", s)),
         }
     }
@@ -160,29 +179,39 @@ pub struct AlertRule {
     pub created_by: Option<Uuid>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct CreateAlertRuleRequest {
+    #[validate(custom(function = "common::validated::name"))]
     pub name: String,
+    #[validate(length(max = 4096))]
     pub description: Option<String>,
     pub enabled: Option<bool>,
     pub severity: Severity,
     pub trigger_type: TriggerType,
     #[serde(default)]
     pub condition_json: serde_json::Value,
+    #[validate(range(min = 0, max = 604800))]
     pub suppress_duration_secs: Option<i32>,
+    #[validate(range(min = 1, max = 10000))]
     pub max_alerts_per_hour: Option<i32>,
+    #[validate(custom(function = "common::validated::cron"))]
     pub schedule_cron: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct UpdateAlertRuleRequest {
+    #[validate(custom(function = "common::validated::name"))]
     pub name: Option<String>,
+    #[validate(length(max = 4096))]
     pub description: Option<String>,
     pub enabled: Option<bool>,
     pub severity: Option<Severity>,
     pub condition_json: Option<serde_json::Value>,
+    #[validate(range(min = 0, max = 604800))]
     pub suppress_duration_secs: Option<i32>,
+    #[validate(range(min = 1, max = 10000))]
     pub max_alerts_per_hour: Option<i32>,
+    #[validate(custom(function = "common::validated::cron"))]
     pub schedule_cron: Option<String>,
 }
 
@@ -230,7 +259,7 @@ pub struct TriggerAlertRequest {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "text")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
 pub enum NotificationStatus {
     Pending,
@@ -266,6 +295,15 @@ pub struct EmailActionConfig {
     pub to: Vec<String>,
     pub subject: Option<String>,
     pub template: Option<String>,
+    /// Subject per locale (`de`, `pt-BR`), used before `subject`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub subjects: HashMap<String, String>,
+    /// Template per locale, used before `template`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub templates: HashMap<String, String>,
+    /// Locale of recipients in `to`; others get the tenant's default
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub locales: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -292,6 +330,11 @@ pub struct SlackActionConfig {
     pub username: Option<String>,
     pub icon_emoji: Option<String>,
     pub template: Option<String>,
+    /// Locale of the channel's readers; default: the tenant's
+    pub locale: Option<String>,
+    /// Template per locale, used before `template`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub templates: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -300,12 +343,156 @@ pub struct DiscordActionConfig {
     pub username: Option<String>,
     pub avatar_url: Option<String>,
     pub template: Option<String>,
+    /// Locale of the channel's readers; default: the tenant's
+    pub locale: Option<String>,
+    /// Template per locale, used before `template`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub templates: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SmsActionConfig {
     pub to: Vec<String>, // Phone numbers in E.164 format
     pub template: Option<String>,
+    /// Template per locale, used before `template`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub templates: HashMap<String, String>,
+    /// Locale of recipients in `to`; others get the tenant's default
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub locales: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceOutputActionConfig {
+    pub device_id: String,
+    /// Relay output token, as listed by `GET /v1/devices/:device_id/io/relays`
+    pub output: String,
+    /// Set the output back to inactive after this many seconds
+    pub duration_secs: Option<u32>,
+}
+
+// Tenant notification channels
+
+/// A tenant's own sender for email or SMS actions, used instead of the
+/// global `SMTP_*`/`TWILIO_*` settings for that tenant's alerts. The secret
+/// (SMTP password, Twilio auth token) is stored encrypted and never returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantNotificationChannel {
+    pub tenant_id: Uuid,
+    pub channel_type: ActionType,
+    /// `TenantEmailSettings` or `TenantSmsSettings`
+    pub config_json: serde_json::Value,
+    pub enabled: bool,
+    #[serde(skip)]
+    pub secret_encrypted: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantEmailSettings {
+    pub smtp_host: String,
+    pub smtp_port: Option<u16>, // Default: 587
+    pub smtp_username: String,
+    pub from_address: String,
+    pub from_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantSmsSettings {
+    pub account_sid: String,
+    pub from_number: String, // E.164
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsertNotificationChannelRequest {
+    pub config_json: serde_json::Value,
+    /// Required when the channel is created; kept when omitted on update
+    pub secret: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+impl UpsertNotificationChannelRequest {
+    /// Check the settings against the channel type (only email and SMS
+    /// senders are configured per tenant)
+    pub fn validate_for(&self, channel_type: &ActionType) -> Result<(), String> {
+        if self.secret.as_deref().is_some_and(|s| s.is_empty()) {
+            return Err("secret must not be empty".to_string());
+        }
+        match channel_type {
+            ActionType::Email => {
+                let settings: TenantEmailSettings = serde_json::from_value(self.config_json.clone())
+                    .map_err(|e| format!("Invalid email channel config: {}", e))?;
+                if settings.smtp_host.trim().is_empty() || settings.smtp_username.trim().is_empty() {
+                    return Err("smtp_host and smtp_username are required".to_string());
+                }
+                if !settings.from_address.contains('@') {
+                    return Err(format!("Invalid from_address: {}", settings.from_address));
+                }
+                Ok(())
+            }
+            ActionType::Sms => {
+                let settings: TenantSmsSettings = serde_json::from_value(self.config_json.clone())
+                    .map_err(|e| format!("Invalid SMS channel config: {}", e))?;
+                if settings.account_sid.trim().is_empty() {
+                    return Err("account_sid is required".to_string());
+                }
+                if !settings.from_number.starts_with('+') {
+                    return Err(format!("from_number must be in E.164 format: {}", settings.from_number));
+                }
+                Ok(())
+            }
+            other => Err(format!("{} channels are not configured per tenant", other)),
+        }
+    }
+}
+
+// Tenant notification settings
+
+/// A tenant's default notification language and the time zone times in
+/// notifications are shown in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    pub tenant_id: Uuid,
+    /// BCP 47 tag, e.g. `de` or `pt-BR`
+    pub locale: String,
+    /// IANA name, e.g. `Europe/Berlin`
+    pub time_zone: String,
+    /// strftime pattern; default: the recipient language's
+    pub datetime_format: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsertNotificationSettingsRequest {
+    pub locale: String,
+    #[serde(default = "default_time_zone")]
+    pub time_zone: String,
+    pub datetime_format: Option<String>,
+}
+
+fn default_time_zone() -> String {
+    "UTC".to_string()
+}
+
+impl UpsertNotificationSettingsRequest {
+    /// The default locale must have built-in texts, since recipients without
+    /// a locale of their own fall back on it
+    pub fn validate(&self) -> Result<(), String> {
+        if crate::i18n::catalog(&self.locale).is_none() {
+            return Err(format!(
+                "Unsupported locale: {} (expected one of {})",
+                self.locale,
+                crate::i18n::supported_locales().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        crate::i18n::parse_time_zone(&self.time_zone)?;
+        if let Some(format) = &self.datetime_format {
+            crate::i18n::validate_datetime_format(format)?;
+        }
+        Ok(())
+    }
 }
 
 // Alert context helpers
@@ -348,3 +535,84 @@ impl Default for AlertContext {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(config_json: serde_json::Value, secret: Option<&str>) -> UpsertNotificationChannelRequest {
+        UpsertNotificationChannelRequest {
+            config_json,
+            secret: secret.map(str::to_string),
+            enabled: None,
+        }
+    }
+
+    #[test]
+    fn tenant_channel_settings_match_their_type() {
+        let email = json!({
+            "smtp_host": "smtp.customer-a.example",
+            "smtp_username": "alerts",
+            "from_address": "alerts@customer-a.example",
+            "from_name": "Customer A Security"
+        });
+        assert!(request(email.clone(), Some("pw")).validate_for(&ActionType::Email).is_ok());
+        // The secret may be omitted to keep the stored one, but not blanked
+        assert!(request(email.clone(), None).validate_for(&ActionType::Email).is_ok());
+        assert!(request(email.clone(), Some("")).validate_for(&ActionType::Email).is_err());
+        assert!(request(email, Some("pw")).validate_for(&ActionType::Sms).is_err());
+
+        let sms = json!({"account_sid": "AC123", "from_number": "+15550100"});
+        assert!(request(sms, Some("token")).validate_for(&ActionType::Sms).is_ok());
+        let local = json!({"account_sid": "AC123", "from_number": "5550100"});
+        assert!(request(local, Some("token")).validate_for(&ActionType::Sms).is_err());
+
+        let webhook = json!({"url": "https://example.com"});
+        assert!(request(webhook, None).validate_for(&ActionType::Webhook).is_err());
+    }
+
+    #[test]
+    fn notification_settings_need_a_known_locale_and_time_zone() {
+        let settings = |locale: &str, time_zone: &str, datetime_format: Option<&str>| UpsertNotificationSettingsRequest {
+            locale: locale.to_string(),
+            time_zone: time_zone.to_string(),
+            datetime_format: datetime_format.map(str::to_string),
+        };
+        assert!(settings("de-AT", "Europe/Vienna", Some("%d.%m.%Y %H:%M")).validate().is_ok());
+        assert!(settings("xx", "UTC", None).validate().is_err());
+        assert!(settings("fr", "Paris", None).validate().is_err());
+        assert!(settings("fr", "Europe/Paris", Some("%Q")).validate().is_err());
+
+        let request: UpsertNotificationSettingsRequest = serde_json::from_value(json!({"locale": "es"})).unwrap();
+        assert_eq!(request.time_zone, "UTC");
+    }
+
+    #[test]
+    fn recipient_locales_are_optional_in_actions() {
+        let config: SmsActionConfig = serde_json::from_value(json!({"to": ["+4915550100"]})).unwrap();
+        assert!(config.locales.is_empty() && config.templates.is_empty());
+
+        let config: EmailActionConfig = serde_json::from_value(json!({
+            "to": ["ops@example.com", "lager@example.de"],
+            "locales": {"lager@example.de": "de"},
+            "subjects": {"de": "Alarm {severity_label}"}
+        }))
+        .unwrap();
+        assert_eq!(config.locales["lager@example.de"], "de");
+        // Unset maps stay out of stored configs
+        assert!(serde_json::to_value(&config).unwrap().get("templates").is_none());
+    }
+
+    #[test]
+    fn device_output_actions_name_a_device_and_output() {
+        assert_eq!("device_output".parse::<ActionType>(), Ok(ActionType::DeviceOutput));
+        assert_eq!(ActionType::DeviceOutput.to_string(), "device_output");
+        assert_eq!(json!(ActionType::DeviceOutput), json!("device_output"));
+
+        let config: DeviceOutputActionConfig =
+            serde_json::from_value(json!({"device_id": "gate-cam", "output": "AlarmOut_0", "duration_secs": 5})).unwrap();
+        assert_eq!(config.output, "AlarmOut_0");
+        assert!(serde_json::from_value::<DeviceOutputActionConfig>(json!({"device_id": "gate-cam"})).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+/// Loopback ports of the embedded services; only the operator UI listens on
+/// `EDGE_ADDR`
+#[derive(Clone, Debug)]
+pub struct ServicePorts {
+  pub coordinator: u16,
+  pub stream_node: u16,
+  pub recorder_node: u16,
+  pub playback: u16,
+  pub device_manager: u16,
+  pub alert_service: u16,
+}
+
+impl Default for ServicePorts {
+  fn default() -> Self {
+    Self {
+      coordinator: 8082,
+      stream_node: 8083,
+      recorder_node: 8085,
+      playback: 8086,
+      device_manager: 8088,
+      alert_service: 8089,
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct EdgeConfig {
+  /// Public address of the operator UI
+  pub ui_addr: SocketAddr,
+  /// Address the embedded services bind on
+  pub internal_host: IpAddr,
+  pub ports: ServicePorts,
+  /// Root for the state database, HLS output and recordings
+  pub data_dir: PathBuf,
+  /// SQLite database holding coordinator state and the event timeline
+  pub state_db_url: String,
+  /// PostgreSQL for device-manager, alert-service and recording retention;
+  /// those are left out when unset
+  pub database_url: Option<String>,
+  pub frontend_dir: PathBuf,
+}
+
+impl EdgeConfig {
+  pub fn from_env() -> Result<Self> {
+    let from_env = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+
+    let ui_addr = from_env("EDGE_ADDR")
+      .unwrap_or_else(|| "0.0.0.0:8090".to_string())
+      .parse()
+      .context("invalid EDGE_ADDR")?;
+    let internal_host = from_env("EDGE_INTERNAL_HOST")
+      .unwrap_or_else(|| "127.0.0.1".to_string())
+      .parse()
+      .context("invalid EDGE_INTERNAL_HOST")?;
+    let data_dir = PathBuf::from(from_env("EDGE_DATA_DIR").unwrap_or_else(|| "./data".to_string()));
+    let state_db_url = from_env("EDGE_STATE_DB")
+      .unwrap_or_else(|| format!("sqlite://{}", data_dir.join("state.db").display()));
+    let frontend_dir = from_env("FRONTEND_DIR")
+      .unwrap_or_else(|| "./crates/operator-ui/frontend/dist".to_string())
+      .into();
+
+    Ok(Self {
+      ui_addr,
+      internal_host,
+      ports: ServicePorts::default(),
+      data_dir,
+      state_db_url,
+      database_url: from_env("DATABASE_URL"),
+      frontend_dir,
+    })
+  }
+
+  pub fn addr(&self, port: u16) -> SocketAddr {
+    SocketAddr::new(self.internal_host, port)
+  }
+
+  /// Base URL the other embedded services reach `port` on
+  pub fn url(&self, port: u16) -> String {
+    format!("http://{}", self.addr(port))
+  }
+
+  /// Settings the embedded services read from the environment themselves,
+  /// as `(name, value)` pairs; the binary applies those the operator has not
+  /// set before starting the runtime
+  pub fn service_env(&self) -> Vec<(&'static str, String)> {
+    let data = |dir: &str| self.data_dir.join(dir).display().to_string();
+    vec![
+      ("COORDINATOR_URL", self.url(self.ports.coordinator)),
+      ("HLS_ROOT", data("hls")),
+      ("RECORDINGS_ROOT", data("recordings")),
+      ("RECORDING_STORAGE_ROOT", data("recordings")),
+      ("FIRMWARE_STORAGE_ROOT", data("firmware")),
+      ("PLAYBACK_SERVICE_URL", self.url(self.ports.playback)),
+    ]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn service_env_points_at_embedded_services() {
+    let config = EdgeConfig {
+      ui_addr: "0.0.0.0:8090".parse().unwrap(),
+      internal_host: "127.0.0.1".parse().unwrap(),
+      ports: ServicePorts::default(),
+      data_dir: PathBuf::from("/var/lib/quadrant"),
+      state_db_url: "sqlite::memory:".to_string(),
+      database_url: None,
+      frontend_dir: PathBuf::from("dist"),
+    };
+    let env: std::collections::HashMap<_, _> = config.service_env().into_iter().collect();
+    assert_eq!(env["COORDINATOR_URL"], "http://127.0.0.1:8082");
+    assert_eq!(env["HLS_ROOT"], "/var/lib/quadrant/hls");
+    assert_eq!(env["RECORDINGS_ROOT"], env["RECORDING_STORAGE_ROOT"]);
+    assert_eq!(env["PLAYBACK_SERVICE_URL"], "http://127.0.0.1:8086");
+  }
+}
@@ -0,0 +1,335 @@
+//! Single-process "edge appliance" for small installations.
+//!
+//! Runs the coordinator, stream node, recorder node, playback service,
+//! device-manager, alert-service and operator UI in one process. Each
+//! service keeps its own HTTP API on a loopback port so the existing
+//! inter-service clients work unchanged; only the operator UI is exposed.
+//! Timeline events travel over an in-memory channel straight into the
+//! embedded coordinator, whose state lives in a SQLite file instead of
+//! PostgreSQL.
+
+use alert_service::{AlertStore, AnomalyConfig, AnomalyDetector, Notifier, RuleEngine};
+use anyhow::{Context, Result};
+use axum::Router;
+use common::approvals::MemoryApprovalStore;
+use common::auth_middleware::AuthMiddlewareConfig;
+use common::evidence::EvidenceStore;
+use common::nodes::{NodeAnnouncer, NodeKind, NodeRegisterRequest};
+use common::state_store::StateStore;
+use common::tenant_rls;
+use common::timeline::{TimelinePublisher, MAX_BATCH};
+use coordinator::config::{CoordinatorConfig, LeaseStoreType};
+use coordinator::sqlite_state_store::SqliteStateStore;
+use coordinator::state::CoordinatorState;
+use coordinator::store::MemoryLeaseStore;
+use coordinator::timeline::Timeline;
+use device_manager::{
+  firmware_storage, DeviceManagerState, DeviceProber, DeviceStore, FirmwareExecutor,
+  FirmwareStorage, HealthMonitor, OnvifDiscoveryClient, TourExecutor,
+};
+use playback_service::approvals::Approvals;
+use playback_service::cache::{self, CacheConfig, EdgeCache};
+use playback_service::playback::PlaybackManager;
+use recorder_node::coordinator::HttpCoordinatorClient;
+use recorder_node::onvif::{OnvifConfig, OnvifService};
+use recorder_node::recording::manager::RECORDING_MANAGER;
+use recorder_node::recording::timelapse::Timelapser;
+use recorder_node::retention::api::RetentionApiState;
+use recorder_node::retention::store::RetentionStore;
+use recorder_node::retention::{PostgresRetentionStore, RetentionExecutor};
+use recorder_node::search::api::SearchApiState;
+use recorder_node::search::{self, PostgresSearchStore, SearchIndexer, SearchStore};
+use recorder_node::storage::capacity::{self, CapacityConfig, CapacityGuard};
+use reqwest::Url;
+use sqlx::postgres::PgPool;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use tower_http::cors::CorsLayer;
+use tower_http::services::ServeDir;
+use tracing::{info, warn};
+
+pub mod config;
+
+pub use config::EdgeConfig;
+
+const NODE_ID: &str = "edge";
+
+/// Start every embedded service and serve until one fails or a shutdown
+/// signal arrives
+pub async fn run(config: EdgeConfig) -> Result<()> {
+  tokio::fs::create_dir_all(&config.data_dir)
+    .await
+    .with_context(|| format!("failed to create data directory {}", config.data_dir.display()))?;
+
+  let coordinator = coordinator_state(&config).await?;
+  spawn_timeline_bus(coordinator.timeline());
+
+  let pool = match &config.database_url {
+    Some(url) => Some(
+      tenant_rls::pool_options()
+        .max_connections(5)
+        .connect(url)
+        .await
+        .context("failed to connect to DATABASE_URL")?,
+    ),
+    None => {
+      warn!("DATABASE_URL not set, device-manager, alert-service and retention policies disabled");
+      None
+    }
+  };
+
+  let ports = &config.ports;
+  let mut services = vec![
+    ("coordinator", config.addr(ports.coordinator), coordinator::routes::versioned(coordinator::routes::router(coordinator))),
+    ("stream-node", config.addr(ports.stream_node), stream_node::router()),
+    ("recorder-node", config.addr(ports.recorder_node), recorder_router(&config, pool.as_ref()).await?),
+    ("playback", config.addr(ports.playback), playback_router(&config)),
+  ];
+  if let Some(pool) = &pool {
+    services.push(("device-manager", config.addr(ports.device_manager), device_manager_router(pool.clone()).await?));
+    services.push(("alert-service", config.addr(ports.alert_service), alert_router(pool.clone())));
+  }
+  services.push(("operator-ui", config.ui_addr, operator_ui_router(&config, pool.is_some()).await?));
+
+  // Bind everything up front so a taken port fails startup, not a task
+  let mut servers = JoinSet::new();
+  for (name, addr, app) in services {
+    let listener = TcpListener::bind(addr)
+      .await
+      .with_context(|| format!("failed to bind {name} on {addr}"))?;
+    info!(service = name, %addr, "service listening");
+    servers.spawn(async move {
+      axum::serve(listener, app.into_make_service())
+        .await
+        .with_context(|| format!("{name} stopped"))
+    });
+  }
+
+  announce(&config, NodeKind::Stream, ports.stream_node).await?;
+  announce(&config, NodeKind::Recorder, ports.recorder_node).await?;
+
+  tokio::select! {
+    Some(result) = servers.join_next() => {
+      result.context("service task panicked")??;
+    }
+    _ = shutdown_signal() => {}
+  }
+  servers.shutdown().await;
+  Ok(())
+}
+
+async fn coordinator_state(config: &EdgeConfig) -> Result<CoordinatorState> {
+  let mut coordinator_config = CoordinatorConfig::from_env()?;
+  coordinator_config.bind_addr = config.addr(config.ports.coordinator);
+  coordinator_config.store_type = LeaseStoreType::Memory;
+  coordinator_config.cluster_enabled = false;
+
+  info!(url = %config.state_db_url, "using SQLite state store");
+  let state_store = Arc::new(SqliteStateStore::connect(&config.state_db_url).await?) as Arc<dyn StateStore>;
+  let lease_store = Arc::new(MemoryLeaseStore::new(
+    coordinator_config.default_ttl_secs,
+    coordinator_config.max_ttl_secs,
+  ));
+  let state = CoordinatorState::new(coordinator_config, lease_store, Some(state_store));
+
+  // Keep the event timeline bounded; TIMELINE_RETENTION_DAYS=0 keeps everything
+  let retention_days = std::env::var("TIMELINE_RETENTION_DAYS")
+    .ok()
+    .and_then(|v| v.parse::<u64>().ok())
+    .unwrap_or(30);
+  if retention_days > 0 {
+    state
+      .timeline()
+      .spawn_retention(Duration::from_secs(retention_days * 24 * 3600));
+  }
+  state.namespaces().spawn(state.timeline());
+  Ok(state)
+}
+
+/// Deliver timeline events recorded anywhere in the process to the embedded
+/// coordinator without going through HTTP
+fn spawn_timeline_bus(timeline: Arc<Timeline>) {
+  let (publisher, mut events) = TimelinePublisher::in_process();
+  if !common::timeline::install(publisher) {
+    warn!("timeline publisher already installed, in-process delivery disabled");
+    return;
+  }
+  tokio::spawn(async move {
+    while let Some(event) = events.recv().await {
+      let mut batch = vec![event];
+      while batch.len() < MAX_BATCH {
+        match events.try_recv() {
+          Ok(event) => batch.push(event),
+          Err(_) => break,
+        }
+      }
+      if let Err(e) = timeline.append(batch).await {
+        warn!(error = %e, "failed to store timeline events");
+      }
+    }
+  });
+}
+
+async fn recorder_router(config: &EdgeConfig, pool: Option<&PgPool>) -> Result<Router> {
+  let coordinator = Url::parse(&config.url(config.ports.coordinator))?;
+  let client = Arc::new(HttpCoordinatorClient::new(coordinator).await?);
+  RECORDING_MANAGER.set_coordinator(client, NODE_ID.to_string()).await;
+
+  let mut app = recorder_node::router().merge(recorder_node::import_router());
+  if let Some(pool) = pool {
+    let store = Arc::new(PostgresRetentionStore::new(pool.clone())) as Arc<dyn RetentionStore>;
+    let executor = Arc::new(RetentionExecutor::new(
+      Arc::clone(&store),
+      config.data_dir.join("recordings").display().to_string(),
+    ));
+    app = app.merge(recorder_node::retention_router(Arc::new(RetentionApiState { store, executor })));
+
+    let store = Arc::new(PostgresSearchStore::new(pool.clone())) as Arc<dyn SearchStore>;
+    let indexer = Arc::new(SearchIndexer::new(Arc::clone(&store)));
+    search::indexer::install(Arc::clone(&indexer));
+    app = app.merge(recorder_node::search_router(Arc::new(SearchApiState {
+      store: Arc::clone(&store),
+      indexer,
+    })));
+    app = app.merge(recorder_node::timelapse_router(Arc::new(Timelapser::from_env(Arc::clone(&store)))));
+    tokio::spawn(search::changes::run_change_compactor(Arc::clone(&store)));
+
+    if let Some(config) = OnvifConfig::from_env()? {
+      info!(replay_base_url = %config.replay_base_url, "ONVIF Profile G services enabled");
+      app = app.merge(recorder_node::onvif_router(Arc::new(OnvifService::new(config, store))));
+    }
+  }
+
+  let capacity_guard = Arc::new(CapacityGuard::new(CapacityConfig::from_env(), NODE_ID.to_string(), pool.cloned()));
+  capacity::install(Arc::clone(&capacity_guard));
+  capacity_guard.spawn();
+  Ok(recorder_node::versioned(app))
+}
+
+fn playback_router(config: &EdgeConfig) -> Router {
+  let playback_url = config.url(config.ports.playback);
+  let manager = Arc::new(PlaybackManager::new(
+    None,
+    NODE_ID.to_string(),
+    format!("{playback_url}/hls"),
+    std::env::var("RTSP_BASE_URL").unwrap_or_else(|_| "rtsp://localhost:8554".to_string()),
+  ));
+  let edge_cache = Arc::new(EdgeCache::new(CacheConfig::default()));
+  let approvals = Arc::new(Approvals::new(
+    Arc::new(MemoryApprovalStore::new()),
+    Arc::new(AuthMiddlewareConfig::from_env()),
+  ));
+
+  Router::new()
+    .nest("/api", playback_service::api::create_router(manager, edge_cache.clone(), approvals))
+    .nest_service("/hls/streams", ServeDir::new(config.data_dir.join("hls")))
+    .nest_service("/hls/recordings", ServeDir::new(config.data_dir.join("recordings")))
+    .layer(axum::middleware::from_fn_with_state(edge_cache, cache::middleware::cache_layer))
+    .layer(CorsLayer::permissive())
+}
+
+async fn device_manager_router(pool: PgPool) -> Result<Router> {
+  let store = Arc::new(DeviceStore::from_pool(pool));
+  let prober = Arc::new(DeviceProber::new(10));
+  let firmware_storage = Arc::new(
+    FirmwareStorage::new(std::env::var("FIRMWARE_STORAGE_ROOT").unwrap_or_else(|_| "./data/firmware".to_string()))
+      .context("failed to create firmware storage")?,
+  );
+  firmware_storage
+    .init()
+    .await
+    .context("failed to initialize firmware storage")?;
+  let upload_ttl_secs = std::env::var("FIRMWARE_UPLOAD_TTL_SECS")
+    .ok()
+    .and_then(|v| v.parse::<u64>().ok())
+    .unwrap_or(firmware_storage::DEFAULT_UPLOAD_TTL_SECS);
+  firmware_storage::spawn_upload_cleanup(Arc::clone(&firmware_storage), Arc::new(AtomicU64::new(upload_ttl_secs)));
+  let firmware_executor = Arc::new(FirmwareExecutor::new((*store).clone(), (*firmware_storage).clone()));
+
+  let state = DeviceManagerState::new(
+    Arc::clone(&store),
+    Arc::clone(&prober),
+    Arc::new(TourExecutor::new(Arc::clone(&store), 10)),
+    Arc::new(OnvifDiscoveryClient::new(5)),
+    firmware_executor,
+    firmware_storage,
+  );
+
+  let health_monitor = HealthMonitor::new(store, prober, 30, 3);
+  tokio::spawn(async move {
+    health_monitor.start().await;
+  });
+
+  Ok(device_manager::routes::router(state))
+}
+
+fn alert_router(pool: PgPool) -> Router {
+  let store = AlertStore::new(pool);
+  alert_service::create_router(alert_service::AppState {
+    engine: Arc::new(RuleEngine::new(store.clone())),
+    notifier: Arc::new(Notifier::from_env(store.clone())),
+    anomalies: Arc::new(AnomalyDetector::new(store.clone(), AnomalyConfig::from_env())),
+    evidence: EvidenceStore::from_env().map(|evidence| {
+      let evidence = Arc::new(evidence);
+      evidence.spawn_retention();
+      evidence
+    }),
+    store,
+  })
+}
+
+async fn operator_ui_router(config: &EdgeConfig, with_database: bool) -> Result<Router> {
+  let mut ui_config = operator_ui::config::Config::from_env()?;
+  ui_config.bind_addr = config.ui_addr.to_string();
+  ui_config.frontend_dir = config.frontend_dir.clone();
+  ui_config.recorder_node_url = config.url(config.ports.recorder_node);
+  ui_config.playback_service_url = config.url(config.ports.playback);
+  if with_database {
+    ui_config.device_manager_url = config.url(config.ports.device_manager);
+    ui_config.alert_service_url = config.url(config.ports.alert_service);
+  }
+  let state = operator_ui::state::AppState::new(ui_config).await?;
+  Ok(operator_ui::router(state, &config.frontend_dir))
+}
+
+/// Register an embedded node with the embedded coordinator so requests are
+/// routed to it
+async fn announce(config: &EdgeConfig, kind: NodeKind, port: u16) -> Result<()> {
+  let coordinator = Url::parse(&config.url(config.ports.coordinator))?;
+  let registration = NodeRegisterRequest {
+    node_id: format!("{NODE_ID}-{kind}"),
+    kind,
+    base_url: config.url(port),
+    ttl_secs: 30,
+    version: Some(env!("CARGO_PKG_VERSION").to_string()),
+  };
+  NodeAnnouncer::new(coordinator, registration).await?.spawn();
+  Ok(())
+}
+
+async fn shutdown_signal() {
+  let ctrl_c = async {
+    let _ = tokio::signal::ctrl_c().await;
+  };
+
+  #[cfg(unix)]
+  let terminate = async {
+    use tokio::signal::unix::{signal, SignalKind};
+    if let Ok(mut sigterm) = signal(SignalKind::terminate()) {
+      let _ = sigterm.recv().await;
+    }
+  };
+
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  tokio::select! {
+      _ = ctrl_c => {},
+      _ = terminate => {},
+  }
+
+  info!("shutdown signal received");
+}
@@ -0,0 +1,24 @@
+use all_in_one::EdgeConfig;
+
+fn main() -> anyhow::Result<()> {
+  let config = EdgeConfig::from_env()?;
+
+  // The embedded services read these themselves; set them while the process
+  // is still single-threaded, keeping any the operator exported
+  for (name, value) in config.service_env() {
+    if std::env::var_os(name).is_none() {
+      std::env::set_var(name, value);
+    }
+  }
+
+  tokio::runtime::Builder::new_multi_thread()
+    .enable_all()
+    .build()?
+    .block_on(async {
+      let log_config = telemetry::LogConfig::new("quadrant-edge")
+          .with_version(env!("CARGO_PKG_VERSION"));
+      telemetry::init_structured_logging(log_config);
+
+      all_in_one::run(config).await
+    })
+}
@@ -0,0 +1,26 @@
+-- Data subject erasure requests (GDPR article 17) and their signed
+-- completion reports. The subject itself is not stored, only a fingerprint
+-- of the identifiers it was requested with.
+CREATE TABLE IF NOT EXISTS privacy_requests (
+    request_id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    subject_fingerprint TEXT NOT NULL,
+    requested_by TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'completed', 'incomplete')),
+    report JSONB,
+    signature TEXT, -- Compact JWS of the report, signed with the token signing key
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    completed_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(tenant_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_privacy_requests_tenant ON privacy_requests(tenant_id, created_at DESC);
+
+INSERT INTO permissions (permission_id, resource, action, description) VALUES
+    ('privacy:export', 'privacy', 'export', 'Export all data held about a person'),
+    ('privacy:erase', 'privacy', 'erase', 'Erase or anonymize all data held about a person')
+ON CONFLICT (permission_id) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES ('system-admin', 'privacy:export'), ('system-admin', 'privacy:erase')
+ON CONFLICT (role_id, permission_id) DO NOTHING;
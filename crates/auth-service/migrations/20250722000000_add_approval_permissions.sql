@@ -0,0 +1,9 @@
+-- Permissions for four-eyes approval of playback and export (playback-service)
+INSERT INTO permissions (permission_id, resource, action, description) VALUES
+    ('approval:approve', 'approval', 'approve', 'Approve or reject held playback and export requests'),
+    ('approval:manage', 'approval', 'manage', 'Manage which sources require a second approver')
+ON CONFLICT (permission_id) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES ('system-admin', 'approval:approve'), ('system-admin', 'approval:manage')
+ON CONFLICT (role_id, permission_id) DO NOTHING;
@@ -0,0 +1,9 @@
+-- Permission to drive device relay outputs (sirens, door strikes) through
+-- device-manager, separate from device:update
+INSERT INTO permissions (permission_id, resource, action, description) VALUES
+    ('device:output', 'device', 'output', 'Trigger relay outputs (sirens, door strikes) on devices')
+ON CONFLICT (permission_id) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+VALUES ('system-admin', 'device:output'), ('operator', 'device:output')
+ON CONFLICT (role_id, permission_id) DO NOTHING;
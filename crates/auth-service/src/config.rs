@@ -1,13 +1,22 @@
 use anyhow::{Context, Result};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct AuthConfig {
     pub bind_addr: SocketAddr,
     pub database_url: String,
     pub jwt_secret: String,
+    /// PEM RSA private key; when set, tokens are signed RS256 and the public
+    /// key is published at `/.well-known/jwks.json`
+    pub jwt_private_key_path: Option<PathBuf>,
+    pub jwt_key_id: String,
     pub jwt_expiration_secs: i64,
     pub bcrypt_cost: u32,
+    /// Services holding personal data, called for data subject exports and
+    /// erasures; unset services are reported as not configured
+    pub ai_service_url: Option<String>,
+    pub alert_service_url: Option<String>,
 }
 
 impl AuthConfig {
@@ -26,6 +35,13 @@ impl AuthConfig {
                 "default-jwt-secret-CHANGE-IN-PRODUCTION".to_string()
             });
 
+        let jwt_private_key_path = std::env::var("JWT_PRIVATE_KEY_PATH")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from);
+
+        let jwt_key_id = std::env::var("JWT_KEY_ID").unwrap_or_else(|_| "quadrant-1".to_string());
+
         let jwt_expiration_secs = std::env::var("JWT_EXPIRATION_SECS")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -36,12 +52,23 @@ impl AuthConfig {
             .and_then(|v| v.parse().ok())
             .unwrap_or(10); // Default: 10
 
+        let service_url = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().trim_end_matches('/').to_string())
+                .filter(|v| !v.is_empty())
+        };
+
         Ok(Self {
             bind_addr,
             database_url,
             jwt_secret,
+            jwt_private_key_path,
+            jwt_key_id,
             jwt_expiration_secs,
             bcrypt_cost,
+            ai_service_url: service_url("AI_SERVICE_URL"),
+            alert_service_url: service_url("ALERT_SERVICE_URL"),
         })
     }
 }
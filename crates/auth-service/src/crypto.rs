@@ -4,10 +4,23 @@ use argon2::{
     Argon2,
 };
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use jsonwebtoken::{
+    decode, encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse,
+        RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use common::privacy::ErasureReport;
 use rand::Rng;
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts, RsaPrivateKey,
+};
 
-use crate::models::JwtClaims;
+use crate::{config::AuthConfig, models::JwtClaims};
 
 /// Hash a password using Argon2
 pub fn hash_password(password: &str) -> Result<String> {
@@ -30,19 +43,18 @@ pub fn verify_password(password: &str, password_hash: &str) -> Result<bool> {
         .is_ok())
 }
 
-/// Generate a JWT token
-pub fn generate_jwt(
+/// Claims for an access token expiring `expiration_secs` from now
+pub fn access_claims(
     user_id: &str,
     tenant_id: &str,
     username: &str,
     is_system_admin: bool,
     roles: Vec<String>,
     permissions: Vec<String>,
-    jwt_secret: &str,
     expiration_secs: i64,
-) -> Result<String> {
+) -> JwtClaims {
     let now = Utc::now().timestamp();
-    let claims = JwtClaims {
+    JwtClaims {
         sub: user_id.to_string(),
         tenant_id: tenant_id.to_string(),
         username: username.to_string(),
@@ -51,28 +63,155 @@ pub fn generate_jwt(
         permissions,
         exp: now + expiration_secs,
         iat: now,
-    };
+    }
+}
+
+/// Keys used to sign and verify access tokens: HS256 with the shared
+/// `JWT_SECRET`, or RS256 when a private key is configured. Only RS256 keys
+/// are published in the JWKS; the HS256 secret never leaves the services.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    key_id: Option<String>,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    pub fn hs256(secret: &str) -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            key_id: None,
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            jwks: JwkSet { keys: Vec::new() },
+        }
+    }
+
+    /// RS256 keys from a PKCS#8 or PKCS#1 PEM private key
+    pub fn rs256(private_key_pem: &str, key_id: &str) -> Result<Self> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(private_key_pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(private_key_pem))
+            .context("invalid RSA private key")?;
+        let public_key = private_key.to_public_key();
+        let n = URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be());
+        let e = URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be());
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::RS256),
+                key_id: Some(key_id.to_string()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: n.clone(),
+                e: e.clone(),
+            }),
+        };
 
-    let token = encode(
-        &Header::new(Algorithm::HS256),
-        &claims,
-        &EncodingKey::from_secret(jwt_secret.as_bytes()),
-    )
-    .context("failed to encode JWT")?;
+        Ok(Self {
+            algorithm: Algorithm::RS256,
+            key_id: Some(key_id.to_string()),
+            encoding: EncodingKey::from_rsa_pem(private_key_pem.as_bytes())
+                .context("invalid RSA private key")?,
+            decoding: DecodingKey::from_rsa_components(&n, &e)
+                .context("invalid RSA public key")?,
+            jwks: JwkSet { keys: vec![jwk] },
+        })
+    }
+
+    pub fn from_config(config: &AuthConfig) -> Result<Self> {
+        match &config.jwt_private_key_path {
+            Some(path) => {
+                let pem = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                Self::rs256(&pem, &config.jwt_key_id)
+            }
+            None => Ok(Self::hs256(&config.jwt_secret)),
+        }
+    }
+
+    /// Public keys for `/.well-known/jwks.json` (empty for HS256)
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    pub fn sign(&self, claims: &JwtClaims) -> Result<String> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.key_id.clone();
+        encode(&header, claims, &self.encoding).context("failed to encode JWT")
+    }
 
-    Ok(token)
+    pub fn verify(&self, token: &str) -> Result<JwtClaims> {
+        let token_data = decode::<JwtClaims>(token, &self.decoding, &Validation::new(self.algorithm))
+            .context("failed to decode JWT")?;
+        Ok(token_data.claims)
+    }
+
+    /// Sign an erasure report as a compact JWS. Reports never expire and
+    /// carry their own issuer, so they can't be replayed as access tokens.
+    pub fn sign_report(&self, report: &ErasureReport) -> Result<String> {
+        let claims = ReportClaims {
+            iss: REPORT_ISSUER.to_string(),
+            iat: Utc::now().timestamp(),
+            report: report.clone(),
+        };
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.key_id.clone();
+        encode(&header, &claims, &self.encoding).context("failed to encode report")
+    }
+
+    pub fn verify_report(&self, signature: &str) -> Result<ErasureReport> {
+        let mut validation = Validation::new(self.algorithm);
+        validation.validate_exp = false;
+        validation.required_spec_claims.clear();
+        validation.required_spec_claims.insert("iss".to_string());
+        validation.set_issuer(&[REPORT_ISSUER]);
+        let token_data = decode::<ReportClaims>(signature, &self.decoding, &validation)
+            .context("failed to decode report")?;
+        Ok(token_data.claims.report)
+    }
 }
 
-/// Verify and decode a JWT token
+/// Issuer of signed erasure reports
+pub const REPORT_ISSUER: &str = "quadrant-privacy-report";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ReportClaims {
+    iss: String,
+    iat: i64,
+    #[serde(flatten)]
+    report: ErasureReport,
+}
+
+/// Generate an HS256 JWT token
+pub fn generate_jwt(
+    user_id: &str,
+    tenant_id: &str,
+    username: &str,
+    is_system_admin: bool,
+    roles: Vec<String>,
+    permissions: Vec<String>,
+    jwt_secret: &str,
+    expiration_secs: i64,
+) -> Result<String> {
+    let claims = access_claims(
+        user_id,
+        tenant_id,
+        username,
+        is_system_admin,
+        roles,
+        permissions,
+        expiration_secs,
+    );
+    JwtKeys::hs256(jwt_secret).sign(&claims)
+}
+
+/// Verify and decode an HS256 JWT token
 pub fn verify_jwt(token: &str, jwt_secret: &str) -> Result<JwtClaims> {
-    let token_data = decode::<JwtClaims>(
-        token,
-        &DecodingKey::from_secret(jwt_secret.as_bytes()),
-        &Validation::new(Algorithm::HS256),
-    )
-    .context("failed to decode JWT")?;
-
-    Ok(token_data.claims)
+    JwtKeys::hs256(jwt_secret).verify(token)
 }
 
 /// Generate a random API token (cryptographically secure)
@@ -128,6 +267,45 @@ mod tests {
         assert_eq!(claims.permissions.len(), 2);
     }
 
+    #[test]
+    fn test_rs256_keys_publish_matching_jwks() {
+        let pem = include_str!("../../common/tests/fixtures/jwt_rs256.pem");
+        let published: JwkSet =
+            serde_json::from_str(include_str!("../../common/tests/fixtures/jwt_rs256.jwks.json"))
+                .unwrap();
+        let keys = JwtKeys::rs256(pem, "test-key-1").unwrap();
+        assert_eq!(keys.jwks(), &published);
+
+        let claims = access_claims("user_123", "tenant_123", "testuser", false, vec![], vec![], 60);
+        let token = keys.sign(&claims).unwrap();
+        assert_eq!(jsonwebtoken::decode_header(&token).unwrap().kid.as_deref(), Some("test-key-1"));
+        assert_eq!(keys.verify(&token).unwrap().sub, "user_123");
+        assert!(JwtKeys::hs256("test_secret").verify(&token).is_err());
+        assert!(JwtKeys::hs256("test_secret").jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_erasure_reports_are_not_access_tokens() {
+        let keys = JwtKeys::hs256("test_secret");
+        let report = ErasureReport {
+            request_id: "req-1".to_string(),
+            tenant_id: "tenant_123".to_string(),
+            subject_fingerprint: "abc".to_string(),
+            requested_by: "admin".to_string(),
+            requested_at_epoch_secs: 1,
+            completed_at_epoch_secs: 2,
+            services: vec![],
+        };
+        let signature = keys.sign_report(&report).unwrap();
+        assert_eq!(keys.verify_report(&signature).unwrap(), report);
+        assert!(keys.verify(&signature).is_err());
+        assert!(JwtKeys::hs256("other_secret").verify_report(&signature).is_err());
+
+        let claims = access_claims("user_123", "tenant_123", "testuser", true, vec![], vec![], 60);
+        let token = keys.sign(&claims).unwrap();
+        assert!(keys.verify_report(&token).is_err());
+    }
+
     #[test]
     fn test_api_token_generation() {
         let token = generate_api_token();
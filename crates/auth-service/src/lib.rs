@@ -3,6 +3,7 @@ pub mod crypto;
 pub mod error;
 pub mod models;
 pub mod oidc;
+pub mod privacy;
 pub mod repository;
 pub mod routes;
 pub mod service;
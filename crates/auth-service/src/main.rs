@@ -1,6 +1,9 @@
 use anyhow::{Context, Result};
-use auth_service::{AuthConfig, AuthRepository, AuthService, AuthState};
+use auth_service::{
+    crypto::JwtKeys, privacy::PrivacyServices, AuthConfig, AuthRepository, AuthService, AuthState,
+};
 use sqlx::postgres::PgPoolOptions;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tracing::info;
@@ -29,7 +32,9 @@ async fn main() -> Result<()> {
 
     // Create repository and service
     let repository = AuthRepository::new(pool);
-    let service = Arc::new(AuthService::new(repository, config.clone()));
+    let keys = JwtKeys::from_config(&config)?;
+    let privacy = PrivacyServices::from_config(&config)?;
+    let service = Arc::new(AuthService::new(repository, config.clone(), keys, privacy));
     let state = AuthState::new(service);
 
     // Build router
@@ -41,7 +46,7 @@ async fn main() -> Result<()> {
         "auth-service listening"
     );
 
-    axum::serve(listener, app.into_make_service())
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal())
         .await?;
 
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
+use common::privacy::{DataSubject, ServiceExport};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
 
 // ===== Tenant Models =====
 
@@ -18,7 +20,7 @@ pub struct Tenant {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTenantRequest {
     pub tenant_id: String,
     pub name: String,
@@ -47,7 +49,7 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateUserRequest {
     pub tenant_id: String,
     pub username: String,
@@ -57,7 +59,7 @@ pub struct CreateUserRequest {
     pub is_system_admin: Option<bool>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateUserRequest {
     pub email: Option<String>,
     pub password: Option<String>,
@@ -130,14 +132,14 @@ pub struct ApiToken {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateApiTokenRequest {
     pub name: String,
     pub description: Option<String>,
     pub expires_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateApiTokenResponse {
     pub token_id: String,
     pub token: String, // Plain text token (only returned once)
@@ -214,14 +216,14 @@ pub struct OidcCallbackRequest {
 
 // ===== Authentication Models =====
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
     pub tenant_id: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LoginResponse {
     pub access_token: String,
     pub token_type: String,
@@ -229,7 +231,7 @@ pub struct LoginResponse {
     pub user: UserInfo,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UserInfo {
     pub user_id: String,
     pub tenant_id: String,
@@ -286,3 +288,33 @@ pub struct CreateAuditLogRequest {
     pub error_message: Option<String>,
     pub metadata: Option<serde_json::Value>,
 }
+
+// ===== Privacy Models =====
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PrivacyRequest {
+    pub request_id: String,
+    pub tenant_id: String,
+    pub subject_fingerprint: String,
+    pub requested_by: String,
+    pub status: String, // running, completed, incomplete
+    pub report: Option<serde_json::Value>,
+    pub signature: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Everything held about a data subject, across services
+#[derive(Debug, Serialize)]
+pub struct SubjectExport {
+    pub subject: DataSubject,
+    pub generated_at: DateTime<Utc>,
+    pub services: BTreeMap<String, ServiceExport>,
+    /// Services that could not be exported from, with the reason
+    pub errors: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyReportRequest {
+    pub signature: String,
+}
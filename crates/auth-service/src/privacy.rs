@@ -0,0 +1,120 @@
+//! Fan-out of data subject exports and erasures to the services holding
+//! personal data outside auth-service.
+
+use anyhow::{Context, Result};
+use common::{
+    auth_middleware::AuthContext,
+    gateway_identity,
+    privacy::{
+        DataSubject, ErasureOutcome, ErasureStatus, ServiceErasure, ServiceExport, ERASE_PATH,
+        EXPORT_PATH,
+    },
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::config::AuthConfig;
+
+/// How long the identity forwarded to each service stays valid
+const IDENTITY_TTL: Duration = Duration::from_secs(300);
+
+pub struct PrivacyServices {
+    client: reqwest::Client,
+    jwt_secret: String,
+    endpoints: Vec<(&'static str, Option<String>)>,
+}
+
+impl PrivacyServices {
+    pub fn from_config(config: &AuthConfig) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(60))
+                .build()
+                .context("failed to build HTTP client")?,
+            jwt_secret: config.jwt_secret.clone(),
+            endpoints: vec![
+                ("ai-service", config.ai_service_url.clone()),
+                ("alert-service", config.alert_service_url.clone()),
+            ],
+        })
+    }
+
+    /// Exports from every configured service, and the error of each one
+    /// that failed
+    pub async fn export(
+        &self,
+        subject: &DataSubject,
+        caller: &AuthContext,
+    ) -> (BTreeMap<String, ServiceExport>, BTreeMap<String, String>) {
+        let mut exports = BTreeMap::new();
+        let mut errors = BTreeMap::new();
+        for (service, base) in &self.endpoints {
+            let Some(base) = base else { continue };
+            match self.call::<ServiceExport>(base, EXPORT_PATH, subject, caller).await {
+                Ok(export) => {
+                    exports.insert(service.to_string(), export);
+                }
+                Err(e) => {
+                    tracing::warn!(service, error = %e, "data subject export failed");
+                    errors.insert(service.to_string(), format!("{e:#}"));
+                }
+            }
+        }
+        (exports, errors)
+    }
+
+    pub async fn erase(&self, subject: &DataSubject, caller: &AuthContext) -> Vec<ServiceErasure> {
+        let mut erasures = Vec::new();
+        for (service, base) in &self.endpoints {
+            let erasure = match base {
+                None => ServiceErasure {
+                    service: service.to_string(),
+                    status: ErasureStatus::NotConfigured,
+                    outcome: ErasureOutcome::default(),
+                    error: None,
+                },
+                Some(base) => match self.call::<ErasureOutcome>(base, ERASE_PATH, subject, caller).await {
+                    Ok(outcome) => ServiceErasure {
+                        service: service.to_string(),
+                        status: ErasureStatus::Completed,
+                        outcome,
+                        error: None,
+                    },
+                    Err(e) => {
+                        tracing::warn!(service, error = %e, "data subject erasure failed");
+                        ServiceErasure {
+                            service: service.to_string(),
+                            status: ErasureStatus::Failed,
+                            outcome: ErasureOutcome::default(),
+                            error: Some(format!("{e:#}")),
+                        }
+                    }
+                },
+            };
+            erasures.push(erasure);
+        }
+        erasures
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        base: &str,
+        path: &str,
+        subject: &DataSubject,
+        caller: &AuthContext,
+    ) -> Result<T> {
+        let identity = gateway_identity::mint(caller, &self.jwt_secret, IDENTITY_TTL)?;
+        self.client
+            .post(format!("{base}/{path}"))
+            .header(gateway_identity::IDENTITY_HEADER, identity)
+            .json(subject)
+            .send()
+            .await
+            .with_context(|| format!("failed to reach {base}"))?
+            .error_for_status()?
+            .json()
+            .await
+            .context("invalid response")
+    }
+}
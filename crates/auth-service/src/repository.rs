@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use chrono::Utc;
+use common::privacy::ErasureOutcome;
 use sqlx::{Pool, Postgres};
 
 use crate::models::*;
@@ -676,4 +677,170 @@ impl AuthRepository {
 
         Ok(())
     }
+
+    // ===== Privacy Operations =====
+
+    /// Users in `tenant_id` matching the subject's user id or e-mail address
+    pub async fn find_subject_users(
+        &self,
+        tenant_id: &str,
+        user_id: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<Vec<User>> {
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT * FROM users
+            WHERE tenant_id = $1 AND (user_id = $2 OR lower(email) = lower($3))
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .bind(email)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to find subject users")?;
+
+        Ok(users)
+    }
+
+    pub async fn list_user_audit_logs(&self, user_id: &str) -> Result<Vec<AuditLog>> {
+        let logs = sqlx::query_as::<_, AuditLog>(
+            "SELECT * FROM audit_logs WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list user audit logs")?;
+
+        Ok(logs)
+    }
+
+    /// Delete a user with their tokens, identities and role grants. Their
+    /// audit entries are kept for accountability but stripped of anything
+    /// identifying them.
+    pub async fn erase_user(&self, user_id: &str) -> Result<ErasureOutcome> {
+        let mut tx = self.pool.begin().await.context("failed to begin transaction")?;
+
+        let tokens = sqlx::query("DELETE FROM api_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("failed to delete API tokens")?
+            .rows_affected();
+        let identities = sqlx::query("DELETE FROM oidc_user_identities WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("failed to delete OIDC identities")?
+            .rows_affected();
+        let roles = sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("failed to delete role grants")?
+            .rows_affected();
+        let audit = sqlx::query(
+            r#"
+            UPDATE audit_logs
+            SET user_id = NULL, ip_address = NULL, user_agent = NULL, metadata = NULL
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .context("failed to anonymize audit logs")?
+        .rows_affected();
+        let users = sqlx::query("DELETE FROM users WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("failed to delete user")?
+            .rows_affected();
+
+        tx.commit().await.context("failed to commit erasure")?;
+
+        Ok(ErasureOutcome::default()
+            .deleted("users", users)
+            .deleted("api_tokens", tokens)
+            .deleted("oidc_identities", identities)
+            .deleted("role_grants", roles)
+            .anonymized("audit_logs", audit))
+    }
+
+    pub async fn create_privacy_request(
+        &self,
+        request_id: &str,
+        tenant_id: &str,
+        subject_fingerprint: &str,
+        requested_by: &str,
+    ) -> Result<PrivacyRequest> {
+        let request = sqlx::query_as::<_, PrivacyRequest>(
+            r#"
+            INSERT INTO privacy_requests (request_id, tenant_id, subject_fingerprint, requested_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(request_id)
+        .bind(tenant_id)
+        .bind(subject_fingerprint)
+        .bind(requested_by)
+        .fetch_one(&self.pool)
+        .await
+        .context("failed to create privacy request")?;
+
+        Ok(request)
+    }
+
+    pub async fn complete_privacy_request(
+        &self,
+        request_id: &str,
+        status: &str,
+        report: serde_json::Value,
+        signature: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE privacy_requests
+            SET status = $2, report = $3, signature = $4, completed_at = $5
+            WHERE request_id = $1
+            "#,
+        )
+        .bind(request_id)
+        .bind(status)
+        .bind(report)
+        .bind(signature)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .context("failed to complete privacy request")?;
+
+        Ok(())
+    }
+
+    pub async fn get_privacy_request(&self, request_id: &str) -> Result<Option<PrivacyRequest>> {
+        let request = sqlx::query_as::<_, PrivacyRequest>(
+            "SELECT * FROM privacy_requests WHERE request_id = $1",
+        )
+        .bind(request_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to get privacy request")?;
+
+        Ok(request)
+    }
+
+    pub async fn list_privacy_requests(&self, tenant_id: &str, limit: i64) -> Result<Vec<PrivacyRequest>> {
+        let requests = sqlx::query_as::<_, PrivacyRequest>(
+            "SELECT * FROM privacy_requests WHERE tenant_id = $1 ORDER BY created_at DESC LIMIT $2",
+        )
+        .bind(tenant_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list privacy requests")?;
+
+        Ok(requests)
+    }
 }
@@ -1,8 +1,12 @@
 use axum::{
     extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     routing::{get, post},
     Json, Router,
 };
+use common::privacy::{DataSubject, ErasureReport};
+use common::rate_limit::{rate_limit_middleware, RateLimitConfig, RateLimitKey, RateLimiter};
 
 use crate::{
     error::ApiError,
@@ -11,16 +15,23 @@ use crate::{
 };
 
 pub fn router(state: AuthState) -> Router {
+    // Slow down credential stuffing: small burst, one attempt every 5s per IP
+    let login_limit = middleware::from_fn_with_state(
+        RateLimiter::new(RateLimitConfig::from_env("AUTH_LOGIN", 10, 0.2, RateLimitKey::Ip)),
+        rate_limit_middleware,
+    );
+
     Router::new()
         // Health and metrics
         .route("/healthz", get(healthz))
         .route("/metrics", get(metrics))
         // Authentication
-        .route("/v1/auth/login", post(login))
+        .route("/v1/auth/login", post(login).layer(login_limit.clone()))
         .route("/v1/auth/verify", post(verify_token))
+        .route("/.well-known/jwks.json", get(jwks))
         // OIDC Authentication
         .route("/v1/auth/oidc/:provider_id/login", get(oidc_login))
-        .route("/v1/auth/oidc/:provider_id/callback", post(oidc_callback))
+        .route("/v1/auth/oidc/:provider_id/callback", post(oidc_callback).layer(login_limit))
         // Users
         .route("/v1/users", get(list_users).post(create_user))
         .route("/v1/users/:id", get(get_user).put(update_user).delete(delete_user))
@@ -43,6 +54,11 @@ pub fn router(state: AuthState) -> Router {
         .route("/v1/oidc/providers/:id", get(get_oidc_provider).put(update_oidc_provider).delete(delete_oidc_provider))
         // Audit logs
         .route("/v1/audit-logs", get(list_audit_logs))
+        // Data subject requests
+        .route("/v1/privacy/exports", post(export_subject))
+        .route("/v1/privacy/erasures", get(list_erasures).post(start_erasure))
+        .route("/v1/privacy/erasures/:id", get(get_erasure))
+        .route("/v1/privacy/reports/verify", post(verify_erasure_report))
         .with_state(state)
 }
 
@@ -68,6 +84,11 @@ async fn login(
     Ok(Json(response))
 }
 
+/// Public keys for verifying access tokens (empty unless RS256 signing is configured)
+async fn jwks(State(state): State<AuthState>) -> Json<jsonwebtoken::jwk::JwkSet> {
+    Json(state.service().jwks().clone())
+}
+
 #[derive(serde::Deserialize)]
 struct VerifyTokenRequest {
     token: String,
@@ -434,3 +455,71 @@ async fn list_user_oidc_identities(
     let identities = service.list_user_oidc_identities(&user_id).await?;
     Ok(Json(identities))
 }
+
+// ===== Data Subject Requests =====
+
+/// Claims of the caller's bearer access token
+async fn caller_claims(state: &AuthState, headers: &HeaderMap) -> Result<JwtClaims, ApiError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::unauthorized("missing bearer token"))?;
+    state.service().verify_token(token).await
+}
+
+async fn export_subject(
+    State(state): State<AuthState>,
+    headers: HeaderMap,
+    Json(subject): Json<DataSubject>,
+) -> Result<Json<SubjectExport>, ApiError> {
+    let caller = caller_claims(&state, &headers).await?;
+    let export = state.service().export_subject(&caller, subject).await?;
+    Ok(Json(export))
+}
+
+async fn start_erasure(
+    State(state): State<AuthState>,
+    headers: HeaderMap,
+    Json(subject): Json<DataSubject>,
+) -> Result<(StatusCode, Json<PrivacyRequest>), ApiError> {
+    let caller = caller_claims(&state, &headers).await?;
+    let request = state.shared_service().start_erasure(caller, subject).await?;
+    Ok((StatusCode::ACCEPTED, Json(request)))
+}
+
+#[derive(serde::Deserialize)]
+struct ListErasuresQuery {
+    tenant_id: Option<String>,
+    limit: Option<i64>,
+}
+
+async fn list_erasures(
+    State(state): State<AuthState>,
+    headers: HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<ListErasuresQuery>,
+) -> Result<Json<Vec<PrivacyRequest>>, ApiError> {
+    let caller = caller_claims(&state, &headers).await?;
+    let limit = query.limit.unwrap_or(100).min(1000);
+    let requests = state.service().list_erasures(&caller, query.tenant_id, limit).await?;
+    Ok(Json(requests))
+}
+
+async fn get_erasure(
+    State(state): State<AuthState>,
+    headers: HeaderMap,
+    Path(request_id): Path<String>,
+) -> Result<Json<PrivacyRequest>, ApiError> {
+    let caller = caller_claims(&state, &headers).await?;
+    let request = state.service().get_erasure(&caller, &request_id).await?;
+    Ok(Json(request))
+}
+
+/// Anyone holding a report can check it; the signature is the proof
+async fn verify_erasure_report(
+    State(state): State<AuthState>,
+    Json(req): Json<VerifyReportRequest>,
+) -> Result<Json<ErasureReport>, ApiError> {
+    let report = state.service().verify_erasure_report(&req.signature)?;
+    Ok(Json(report))
+}
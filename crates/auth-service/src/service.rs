@@ -1,30 +1,54 @@
 use anyhow::Result;
+use chrono::Utc;
+use common::{
+    auth_middleware::AuthContext,
+    privacy::{
+        DataSubject, ErasureReport, ErasureStatus, ServiceErasure, ServiceExport,
+        ERASE_PERMISSION, EXPORT_PERMISSION,
+    },
+};
+use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
     config::AuthConfig,
-    crypto,
+    crypto::{self, JwtKeys},
     error::ApiError,
     models::*,
     oidc::{OidcClientManager, OidcUserInfo},
+    privacy::PrivacyServices,
     repository::AuthRepository,
 };
 
 pub struct AuthService {
     repo: AuthRepository,
     config: AuthConfig,
+    keys: JwtKeys,
     oidc_manager: OidcClientManager,
+    privacy: PrivacyServices,
 }
 
 impl AuthService {
-    pub fn new(repo: AuthRepository, config: AuthConfig) -> Self {
+    pub fn new(
+        repo: AuthRepository,
+        config: AuthConfig,
+        keys: JwtKeys,
+        privacy: PrivacyServices,
+    ) -> Self {
         Self {
             repo,
             config,
+            keys,
             oidc_manager: OidcClientManager::new(),
+            privacy,
         }
     }
 
+    /// Public signing keys for `/.well-known/jwks.json`
+    pub fn jwks(&self) -> &jsonwebtoken::jwk::JwkSet {
+        self.keys.jwks()
+    }
+
     // ===== Authentication =====
 
     pub async fn login(&self, req: LoginRequest) -> Result<LoginResponse, ApiError> {
@@ -64,17 +88,18 @@ impl AuthService {
         let permission_ids: Vec<String> = permissions.iter().map(|p| p.permission_id.clone()).collect();
 
         // Generate JWT token
-        let access_token = crypto::generate_jwt(
-            &user.user_id,
-            &user.tenant_id,
-            &user.username,
-            user.is_system_admin,
-            role_names.clone(),
-            permission_ids.clone(),
-            &self.config.jwt_secret,
-            self.config.jwt_expiration_secs,
-        )
-        .map_err(|e| ApiError::internal(format!("failed to generate JWT: {}", e)))?;
+        let access_token = self
+            .keys
+            .sign(&crypto::access_claims(
+                &user.user_id,
+                &user.tenant_id,
+                &user.username,
+                user.is_system_admin,
+                role_names.clone(),
+                permission_ids.clone(),
+                self.config.jwt_expiration_secs,
+            ))
+            .map_err(|e| ApiError::internal(format!("failed to generate JWT: {}", e)))?;
 
         Ok(LoginResponse {
             access_token,
@@ -94,7 +119,8 @@ impl AuthService {
     }
 
     pub async fn verify_token(&self, token: &str) -> Result<JwtClaims, ApiError> {
-        crypto::verify_jwt(token, &self.config.jwt_secret)
+        self.keys
+            .verify(token)
             .map_err(|_| ApiError::unauthorized("invalid or expired token"))
     }
 
@@ -457,17 +483,18 @@ impl AuthService {
         let permission_ids: Vec<String> = permissions.iter().map(|p| p.permission_id.clone()).collect();
 
         // Generate JWT token
-        let access_token = crypto::generate_jwt(
-            &user.user_id,
-            &user.tenant_id,
-            &user.username,
-            user.is_system_admin,
-            role_names.clone(),
-            permission_ids.clone(),
-            &self.config.jwt_secret,
-            self.config.jwt_expiration_secs,
-        )
-        .map_err(|e| ApiError::internal(format!("failed to generate JWT: {}", e)))?;
+        let access_token = self
+            .keys
+            .sign(&crypto::access_claims(
+                &user.user_id,
+                &user.tenant_id,
+                &user.username,
+                user.is_system_admin,
+                role_names.clone(),
+                permission_ids.clone(),
+                self.config.jwt_expiration_secs,
+            ))
+            .map_err(|e| ApiError::internal(format!("failed to generate JWT: {}", e)))?;
 
         // Update last login time
         self.repo.update_user_login(&user.user_id).await?;
@@ -566,4 +593,232 @@ impl AuthService {
     pub async fn delete_oidc_identity(&self, identity_id: &str) -> Result<(), ApiError> {
         self.repo.delete_oidc_identity(identity_id).await.map_err(Into::into)
     }
+
+    // ===== Data Subject Requests =====
+
+    /// Everything held about a person across auth-service, ai-service and
+    /// alert-service. Services that fail are listed in `errors` rather than
+    /// failing the whole export.
+    pub async fn export_subject(
+        &self,
+        caller: &JwtClaims,
+        subject: DataSubject,
+    ) -> Result<SubjectExport, ApiError> {
+        authorize_subject(caller, &subject, EXPORT_PERMISSION)?;
+        let users = self
+            .repo
+            .find_subject_users(&subject.tenant_id, subject.user_id.as_deref(), subject.email.as_deref())
+            .await?;
+        let subject = with_account(subject, &users);
+
+        let mut local = ServiceExport::default();
+        local.add("users", &users)?;
+        for user in &users {
+            local.add("roles", &self.repo.get_user_roles(&user.user_id).await?)?;
+            local.add("api_tokens", &self.repo.list_user_api_tokens(&user.user_id).await?)?;
+            local.add("oidc_identities", &self.repo.list_user_oidc_identities(&user.user_id).await?)?;
+            local.add("audit_logs", &self.repo.list_user_audit_logs(&user.user_id).await?)?;
+        }
+
+        let (mut services, errors) = self.privacy.export(&subject, &caller_context(caller)).await;
+        services.insert("auth-service".to_string(), local);
+
+        self.audit_privacy(caller, "privacy_export", &subject.fingerprint(), &subject.tenant_id)
+            .await;
+
+        Ok(SubjectExport {
+            subject,
+            generated_at: Utc::now(),
+            services,
+            errors,
+        })
+    }
+
+    /// Record an erasure request and run it in the background; poll
+    /// [`Self::get_erasure`] for the signed report
+    pub async fn start_erasure(
+        self: Arc<Self>,
+        caller: JwtClaims,
+        subject: DataSubject,
+    ) -> Result<PrivacyRequest, ApiError> {
+        authorize_subject(&caller, &subject, ERASE_PERMISSION)?;
+        let users = self
+            .repo
+            .find_subject_users(&subject.tenant_id, subject.user_id.as_deref(), subject.email.as_deref())
+            .await?;
+        if users.iter().any(|u| u.user_id == caller.sub) {
+            return Err(ApiError::bad_request("cannot erase your own account"));
+        }
+        let subject = with_account(subject, &users);
+
+        let request_id = Uuid::new_v4().to_string();
+        let request = self
+            .repo
+            .create_privacy_request(&request_id, &subject.tenant_id, &subject.fingerprint(), &caller.sub)
+            .await?;
+
+        let service = self.clone();
+        let user_ids = users.into_iter().map(|u| u.user_id).collect();
+        tokio::spawn(async move {
+            if let Err(e) = service.run_erasure(&request_id, &caller, &subject, user_ids).await {
+                tracing::error!(request_id, error = %e, "data subject erasure failed");
+            }
+        });
+
+        Ok(request)
+    }
+
+    async fn run_erasure(
+        &self,
+        request_id: &str,
+        caller: &JwtClaims,
+        subject: &DataSubject,
+        user_ids: Vec<String>,
+    ) -> Result<()> {
+        let requested_at = Utc::now().timestamp() as u64;
+
+        // Other services first: they match on the account's user id and
+        // e-mail, which are gone once the local account is erased.
+        let mut services = self.privacy.erase(subject, &caller_context(caller)).await;
+
+        let mut local = ServiceErasure {
+            service: "auth-service".to_string(),
+            status: ErasureStatus::Completed,
+            outcome: Default::default(),
+            error: None,
+        };
+        for user_id in &user_ids {
+            match self.repo.erase_user(user_id).await {
+                Ok(outcome) => {
+                    for (category, count) in outcome.deleted {
+                        local.outcome = local.outcome.deleted(&category, count);
+                    }
+                    for (category, count) in outcome.anonymized {
+                        local.outcome = local.outcome.anonymized(&category, count);
+                    }
+                }
+                Err(e) => {
+                    local.status = ErasureStatus::Failed;
+                    local.error = Some(format!("{e:#}"));
+                    break;
+                }
+            }
+        }
+        services.insert(0, local);
+
+        let report = ErasureReport {
+            request_id: request_id.to_string(),
+            tenant_id: subject.tenant_id.clone(),
+            subject_fingerprint: subject.fingerprint(),
+            requested_by: caller.sub.clone(),
+            requested_at_epoch_secs: requested_at,
+            completed_at_epoch_secs: Utc::now().timestamp() as u64,
+            services,
+        };
+        let status = if report.is_complete() { "completed" } else { "incomplete" };
+        let signature = self.keys.sign_report(&report)?;
+        self.repo
+            .complete_privacy_request(request_id, status, serde_json::to_value(&report)?, &signature)
+            .await?;
+
+        self.audit_privacy(caller, "privacy_erase", &report.subject_fingerprint, &report.tenant_id)
+            .await;
+        tracing::info!(request_id, status, "data subject erasure finished");
+        Ok(())
+    }
+
+    pub async fn get_erasure(&self, caller: &JwtClaims, request_id: &str) -> Result<PrivacyRequest, ApiError> {
+        let request = self
+            .repo
+            .get_privacy_request(request_id)
+            .await?
+            .filter(|r| caller.is_system_admin || r.tenant_id == caller.tenant_id)
+            .ok_or_else(|| ApiError::not_found("erasure request not found"))?;
+        require_permission(caller, ERASE_PERMISSION)?;
+        Ok(request)
+    }
+
+    pub async fn list_erasures(
+        &self,
+        caller: &JwtClaims,
+        tenant_id: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<PrivacyRequest>, ApiError> {
+        require_permission(caller, ERASE_PERMISSION)?;
+        let tenant_id = match tenant_id {
+            Some(tenant_id) if caller.is_system_admin => tenant_id,
+            _ => caller.tenant_id.clone(),
+        };
+        self.repo
+            .list_privacy_requests(&tenant_id, limit)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Check a completion report was signed by this deployment and return
+    /// its contents
+    pub fn verify_erasure_report(&self, signature: &str) -> Result<ErasureReport, ApiError> {
+        self.keys
+            .verify_report(signature)
+            .map_err(|_| ApiError::bad_request("invalid report signature"))
+    }
+
+    async fn audit_privacy(&self, caller: &JwtClaims, action: &str, fingerprint: &str, tenant_id: &str) {
+        let entry = CreateAuditLogRequest {
+            tenant_id: tenant_id.to_string(),
+            user_id: Some(caller.sub.clone()),
+            action: action.to_string(),
+            resource_type: Some("data_subject".to_string()),
+            resource_id: Some(fingerprint.to_string()),
+            ip_address: None,
+            user_agent: None,
+            status: "success".to_string(),
+            error_message: None,
+            metadata: None,
+        };
+        if let Err(e) = self.repo.create_audit_log(entry).await {
+            tracing::warn!(error = %e, action, "failed to write privacy audit log");
+        }
+    }
+}
+
+fn require_permission(caller: &JwtClaims, permission: &str) -> Result<(), ApiError> {
+    if caller.is_system_admin || caller.permissions.iter().any(|p| p == permission) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden(format!("missing permission {permission}")))
+    }
+}
+
+/// Only system admins may act on subjects outside their own tenant
+fn authorize_subject(caller: &JwtClaims, subject: &DataSubject, permission: &str) -> Result<(), ApiError> {
+    subject
+        .validate()
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    require_permission(caller, permission)?;
+    if !caller.is_system_admin && subject.tenant_id != caller.tenant_id {
+        return Err(ApiError::forbidden("subject belongs to another tenant"));
+    }
+    Ok(())
+}
+
+/// Fill in the account identifiers of a subject named by only one of them,
+/// so other services can match records keyed on either
+fn with_account(mut subject: DataSubject, users: &[User]) -> DataSubject {
+    if let [user] = users {
+        subject.user_id.get_or_insert_with(|| user.user_id.clone());
+        subject.email.get_or_insert_with(|| user.email.clone());
+    }
+    subject
+}
+
+fn caller_context(caller: &JwtClaims) -> AuthContext {
+    AuthContext {
+        user_id: caller.sub.clone(),
+        tenant_id: caller.tenant_id.clone(),
+        username: caller.username.clone(),
+        is_system_admin: caller.is_system_admin,
+        roles: caller.roles.clone(),
+        permissions: caller.permissions.clone(),
+    }
 }
@@ -15,4 +15,9 @@ impl AuthState {
     pub fn service(&self) -> &AuthService {
         &self.service
     }
+
+    /// Owned handle, for work that outlives a request
+    pub fn shared_service(&self) -> Arc<AuthService> {
+        self.service.clone()
+    }
 }
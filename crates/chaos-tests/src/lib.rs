@@ -0,0 +1,6 @@
+//! Failover tests for the services holding coordinator leases.
+//!
+//! The tests in `tests/` run a coordinator built with the `fault-injection`
+//! feature next to real recorder, gateway and ai-service state. They drop
+//! heartbeats, delay responses and expire leases, then check that the work
+//! moves to another holder and that the old holder stops its copy.
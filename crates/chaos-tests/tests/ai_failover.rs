@@ -0,0 +1,57 @@
+//! An AI task runs on one node at a time: others refuse it while its lease
+//! is held, and the old holder stops once another node has taken it over.
+
+mod harness;
+
+use ai_service::plugin::mock_detector::MockDetectorPlugin;
+use ai_service::{AiServiceState, PluginRegistry};
+use anyhow::Result;
+use common::ai_tasks::{AiTaskConfig, AiTaskState};
+use harness::{ChaosCoordinator, LEASE_TTL_SECS, eventually};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+async fn ai_node(coordinator: &ChaosCoordinator, node_id: &str) -> Result<AiServiceState> {
+  let plugins = PluginRegistry::new();
+  plugins.register(Arc::new(RwLock::new(MockDetectorPlugin::new()))).await?;
+  let client = Arc::new(ai_service::coordinator::HttpCoordinatorClient::new(coordinator.url.clone()).await?);
+  Ok(AiServiceState::with_coordinator(node_id.to_string(), client, plugins))
+}
+
+fn task(id: &str) -> Result<AiTaskConfig> {
+  Ok(serde_json::from_value(json!({
+    "id": id,
+    "plugin_type": "mock_object_detector",
+    "source_stream_id": "chaos-cam",
+    "output": { "type": "file" },
+  }))?)
+}
+
+async fn task_state(node: &AiServiceState, id: &str) -> Option<AiTaskState> {
+  node.get_task(id).await.map(|info| info.state)
+}
+
+#[tokio::test]
+async fn ai_task_moves_to_another_node_after_lease_expiry() -> Result<()> {
+  let _ = tracing_subscriber::fmt::try_init();
+  let coordinator = ChaosCoordinator::start().await?;
+  let a = ai_node(&coordinator, "ai-a").await?;
+  let b = ai_node(&coordinator, "ai-b").await?;
+
+  a.start_task(task("chaos-ai")?, Some(LEASE_TTL_SECS)).await?;
+  assert!(b.start_task(task("chaos-ai")?, Some(LEASE_TTL_SECS)).await.is_err());
+  assert!(b.get_task("chaos-ai").await.is_none());
+
+  assert!(coordinator.faults.expire_lease("chaos-ai").await?);
+  b.start_task(task("chaos-ai")?, Some(LEASE_TTL_SECS)).await?;
+  assert_eq!(coordinator.holder("chaos-ai").await.as_deref(), Some("ai-b"));
+
+  eventually("ai-a to stop the task", || async {
+    task_state(&a, "chaos-ai").await == Some(AiTaskState::Error)
+  })
+  .await?;
+  assert_eq!(task_state(&b, "chaos-ai").await, Some(AiTaskState::Processing));
+  assert_eq!(coordinator.holder("chaos-ai").await.as_deref(), Some("ai-b"));
+  Ok(())
+}
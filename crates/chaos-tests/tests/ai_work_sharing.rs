@@ -0,0 +1,200 @@
+//! AI nodes share the stored tasks: a failed node's tasks are adopted by the
+//! others once their leases expire, and a busy node queues new tasks for a
+//! less busy one.
+
+mod harness;
+
+use ai_service::coordinator::CoordinatorClient;
+use ai_service::plugin::mock_detector::MockDetectorPlugin;
+use ai_service::work_sharing::WorkSharing;
+use ai_service::{AiServiceState, PluginRegistry};
+use anyhow::Result;
+use common::ai_tasks::{AiTaskConfig, AiTaskStartResponse, AiTaskState, AiTaskStopResponse};
+use common::leases::{
+  LeaseAcquireRequest, LeaseAcquireResponse, LeaseReleaseRequest, LeaseReleaseResponse, LeaseRenewRequest,
+  LeaseRenewResponse,
+};
+use common::state_store_client::StateStoreClient;
+use harness::{ChaosCoordinator, eventually, serve};
+use reqwest::Url;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::json;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+
+/// Shortest lease the coordinator grants, so failed nodes are noticed fast
+const TASK_LEASE_TTL_SECS: u64 = 5;
+
+/// Lease calls without the shared circuit breaker of `HttpCoordinatorClient`:
+/// these nodes share one process, and the renewals dropped for one of them
+/// must not trip the breaker for the others
+struct PlainCoordinatorClient {
+  base: Url,
+  http: reqwest::Client,
+}
+
+impl PlainCoordinatorClient {
+  async fn post<T: Serialize + Sync, R: DeserializeOwned>(&self, path: &str, body: &T) -> Result<R> {
+    let response = self.http.post(self.base.join(path)?).json(body).send().await?;
+    Ok(response.error_for_status()?.json().await?)
+  }
+}
+
+#[async_trait::async_trait]
+impl CoordinatorClient for PlainCoordinatorClient {
+  async fn acquire(&self, request: &LeaseAcquireRequest) -> Result<LeaseAcquireResponse> {
+    self.post("v1/leases/acquire", request).await
+  }
+
+  async fn renew(&self, request: &LeaseRenewRequest) -> Result<LeaseRenewResponse> {
+    self.post("v1/leases/renew", request).await
+  }
+
+  async fn release(&self, request: &LeaseReleaseRequest) -> Result<LeaseReleaseResponse> {
+    self.post("v1/leases/release", request).await
+  }
+}
+
+struct AiNode {
+  state: AiServiceState,
+  sharing: Arc<WorkSharing>,
+}
+
+impl AiNode {
+  async fn start(coordinator: &ChaosCoordinator, node_id: &str) -> Result<Self> {
+    let plugins = PluginRegistry::new();
+    plugins.register(Arc::new(RwLock::new(MockDetectorPlugin::new()))).await?;
+    let client = Arc::new(PlainCoordinatorClient {
+      base: coordinator.url.clone(),
+      http: reqwest::Client::new(),
+    });
+    let store = Arc::new(StateStoreClient::new(
+      coordinator.url.as_str().trim_end_matches('/').to_string(),
+    ));
+    let state = AiServiceState::with_coordinator_and_state_store(node_id.to_string(), client, plugins, store);
+    let sharing = Arc::new(WorkSharing::new(
+      node_id.to_string(),
+      coordinator.url.clone(),
+      Duration::from_secs(1),
+      TASK_LEASE_TTL_SECS,
+    ));
+    state.set_work_sharing(sharing.clone());
+    Ok(Self { state, sharing })
+  }
+
+  async fn run_pass(&self) -> Result<usize> {
+    self.sharing.run_pass(&self.state).await
+  }
+
+  async fn task_state(&self, id: &str) -> Option<AiTaskState> {
+    self.state.get_task(id).await.map(|info| info.state)
+  }
+}
+
+fn task(id: &str) -> Result<AiTaskConfig> {
+  Ok(serde_json::from_value(json!({
+    "id": id,
+    "plugin_type": "mock_object_detector",
+    "source_stream_id": format!("{}-cam", id),
+    "output": { "type": "file" },
+  }))?)
+}
+
+#[tokio::test]
+async fn tasks_of_a_failed_node_are_adopted() -> Result<()> {
+  let _ = tracing_subscriber::fmt::try_init();
+  let coordinator = ChaosCoordinator::start_with_state_store().await?;
+  let a = AiNode::start(&coordinator, "ai-a").await?;
+  let b = AiNode::start(&coordinator, "ai-b").await?;
+  // ai-a stops announcing itself when it fails, so it drops out quickly
+  coordinator.register_ai_node("ai-a", &Url::parse("http://ai-a.local/")?, 3).await?;
+  coordinator.register_ai_node("ai-b", &Url::parse("http://ai-b.local/")?, 60).await?;
+
+  let ids = ["chaos-share-1", "chaos-share-2", "chaos-share-3"];
+  for id in ids {
+    a.state.start_task(task(id)?, Some(TASK_LEASE_TTL_SECS)).await?;
+  }
+  assert_eq!(b.run_pass().await?, 0);
+
+  coordinator.drop_renewals_of("ai-a").await;
+  eventually("ai-b to adopt every task of ai-a", || async {
+    let _ = b.run_pass().await;
+    for id in ids {
+      if coordinator.holder(id).await.as_deref() != Some("ai-b") {
+        return false;
+      }
+    }
+    true
+  })
+  .await?;
+
+  for id in ids {
+    assert_eq!(b.state.stored_task_node(id).await.as_deref(), Some("ai-b"));
+    assert_eq!(b.task_state(id).await, Some(AiTaskState::Processing));
+  }
+  // ai-a gave the tasks up without overwriting their stored records
+  eventually("ai-a to stop its copies", || async {
+    for id in ids {
+      if a.task_state(id).await != Some(AiTaskState::Error) {
+        return false;
+      }
+    }
+    true
+  })
+  .await?;
+  assert_eq!(b.state.stored_task_node(ids[0]).await.as_deref(), Some("ai-b"));
+  Ok(())
+}
+
+#[tokio::test]
+async fn busy_node_queues_new_tasks_for_idle_ones() -> Result<()> {
+  let _ = tracing_subscriber::fmt::try_init();
+  let coordinator = ChaosCoordinator::start_with_state_store().await?;
+  let a = AiNode::start(&coordinator, "ai-a").await?;
+  let b = AiNode::start(&coordinator, "ai-b").await?;
+  let (a_url, _a_server) = serve(ai_service::api::router(a.state.clone())).await?;
+  let (b_url, _b_server) = serve(ai_service::api::router(b.state.clone())).await?;
+  coordinator.register_ai_node("ai-a", &a_url, 60).await?;
+  coordinator.register_ai_node("ai-b", &b_url, 60).await?;
+  a.run_pass().await?;
+
+  let http = reqwest::Client::new();
+  let start = |id: &'static str| {
+    let (http, a_url) = (http.clone(), a_url.clone());
+    async move {
+      let response: AiTaskStartResponse = http
+        .post(a_url.join("v1/tasks")?)
+        .json(&json!({ "config": task(id)?, "lease_ttl_secs": TASK_LEASE_TTL_SECS }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+      anyhow::Ok(response)
+    }
+  };
+
+  // ai-a takes its share of one, then leaves the next task to ai-b
+  let first = start("chaos-busy-1").await?;
+  assert!(first.accepted && first.lease_id.is_some());
+  let second = start("chaos-busy-2").await?;
+  assert!(second.accepted && second.lease_id.is_none());
+  assert!(a.state.get_task("chaos-busy-2").await.is_none());
+
+  assert_eq!(b.run_pass().await?, 1);
+  assert_eq!(coordinator.holder("chaos-busy-1").await.as_deref(), Some("ai-a"));
+  assert_eq!(coordinator.holder("chaos-busy-2").await.as_deref(), Some("ai-b"));
+  assert_eq!(b.task_state("chaos-busy-2").await, Some(AiTaskState::Processing));
+
+  // Stopping it through ai-a is redirected to ai-b
+  let stopped: AiTaskStopResponse = http
+    .delete(a_url.join("v1/tasks/chaos-busy-2")?)
+    .send()
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+  assert!(stopped.success);
+  assert_eq!(b.task_state("chaos-busy-2").await, Some(AiTaskState::Stopped));
+  Ok(())
+}
@@ -0,0 +1,168 @@
+//! Shared harness: a coordinator with fault injection on an ephemeral port
+
+#![allow(dead_code)]
+
+use anyhow::{Result, bail};
+use common::leases::{LeaseHistoryEntry, LeaseHistoryQuery};
+use common::nodes::{NodeKind, NodeRegisterRequest};
+use common::state_store::StateStore;
+use coordinator::{
+  config::{CoordinatorConfig, LeaseStoreType},
+  faults::{self, FaultInjector, FaultPlan, HeartbeatDrop, HeartbeatKind},
+  routes,
+  sqlite_state_store::SqliteStateStore,
+  state::CoordinatorState,
+  store::{LeaseStore, MemoryLeaseStore},
+};
+use reqwest::Url;
+use std::{
+  future::Future,
+  net::SocketAddr,
+  sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+  },
+  time::Duration,
+};
+use tokio::{net::TcpListener, task::JoinHandle, time::Instant};
+
+/// Lease TTL the tests start work with; holders renew every 5s at the least
+pub const LEASE_TTL_SECS: u64 = 10;
+
+/// Longest a failover may take: a TTL to expire plus a renewal interval
+const FAILOVER_TIMEOUT: Duration = Duration::from_secs(LEASE_TTL_SECS + 10);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct ChaosCoordinator {
+  /// Base URL, with a trailing slash
+  pub url: Url,
+  pub faults: FaultInjector,
+  store: Arc<dyn LeaseStore>,
+  server: JoinHandle<()>,
+}
+
+impl ChaosCoordinator {
+  pub async fn start() -> Result<Self> {
+    Self::start_with(None).await
+  }
+
+  /// Also serve `/v1/state`, from a fresh SQLite StateStore
+  pub async fn start_with_state_store() -> Result<Self> {
+    static DATABASES: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+      "chaos-state-{}-{}.db",
+      std::process::id(),
+      DATABASES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_file(&path);
+    let state_store = SqliteStateStore::connect(&format!("sqlite://{}", path.display())).await?;
+    Self::start_with(Some(Arc::new(state_store))).await
+  }
+
+  async fn start_with(state_store: Option<Arc<dyn StateStore>>) -> Result<Self> {
+    let cfg = CoordinatorConfig {
+      bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+      default_ttl_secs: LEASE_TTL_SECS,
+      max_ttl_secs: 60,
+      store_type: LeaseStoreType::Memory,
+      database_url: None,
+      cluster_enabled: false,
+      node_id: None,
+      peer_addrs: vec![],
+      election_timeout_ms: 5000,
+      heartbeat_interval_ms: 1000,
+    };
+    let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new(cfg.default_ttl_secs, cfg.max_ttl_secs));
+    let faults = FaultInjector::new(store.clone());
+    let app = faults::wrap(routes::router(CoordinatorState::new(cfg, store.clone(), state_store)), &faults);
+    let (url, server) = serve(app).await?;
+    Ok(Self {
+      url,
+      faults,
+      store,
+      server,
+    })
+  }
+
+  /// Holder of the live lease on `resource_id`
+  pub async fn holder(&self, resource_id: &str) -> Option<String> {
+    let leases = self.store.list(None).await.ok()?;
+    leases
+      .into_iter()
+      .find(|l| l.resource_id == resource_id)
+      .map(|l| l.holder_id)
+  }
+
+  /// Lease history of `resource_id`, oldest first
+  pub async fn history(&self, resource_id: &str) -> Result<Vec<LeaseHistoryEntry>> {
+    let query = LeaseHistoryQuery {
+      resource_id: Some(resource_id.to_string()),
+      ..Default::default()
+    };
+    self.store.history(&query).await
+  }
+
+  /// Register an AI node, as its `NodeAnnouncer` would, for `ttl_secs`
+  pub async fn register_ai_node(&self, node_id: &str, base_url: &Url, ttl_secs: u64) -> Result<()> {
+    reqwest::Client::new()
+      .post(self.url.join("v1/nodes/register")?)
+      .json(&NodeRegisterRequest {
+        node_id: node_id.to_string(),
+        kind: NodeKind::Ai,
+        base_url: base_url.to_string(),
+        ttl_secs,
+        version: None,
+      })
+      .send()
+      .await?
+      .error_for_status()?;
+    Ok(())
+  }
+
+  /// Drop every lease renewal of `holder_id`, as if it lost its network
+  pub async fn drop_renewals_of(&self, holder_id: &str) {
+    self
+      .faults
+      .set(FaultPlan {
+        dropped_heartbeats: vec![HeartbeatDrop {
+          kind: HeartbeatKind::Lease,
+          id: Some(holder_id.to_string()),
+        }],
+        ..Default::default()
+      })
+      .await;
+  }
+}
+
+impl Drop for ChaosCoordinator {
+  fn drop(&mut self) {
+    self.server.abort();
+  }
+}
+
+/// Wait until `check` holds, for at most a lease TTL plus a renewal interval
+pub async fn eventually<F, Fut>(what: &str, mut check: F) -> Result<()>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = bool>,
+{
+  let deadline = Instant::now() + FAILOVER_TIMEOUT;
+  while Instant::now() < deadline {
+    if check().await {
+      return Ok(());
+    }
+    tokio::time::sleep(POLL_INTERVAL).await;
+  }
+  bail!("timed out waiting for {}", what)
+}
+
+/// Serve `app` on an ephemeral port, returning its base URL
+pub async fn serve(app: axum::Router) -> Result<(Url, JoinHandle<()>)> {
+  let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+  let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+  let server = tokio::spawn(async move {
+    let _ = axum::serve(listener, app.into_make_service()).await;
+  });
+  Ok((url, server))
+}
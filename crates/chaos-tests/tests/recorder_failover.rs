@@ -0,0 +1,125 @@
+//! A recording moves to another recorder when its lease holder goes away,
+//! and the old holder stops writing it.
+
+mod harness;
+
+use anyhow::Result;
+use common::leases::LeaseEvent;
+use common::recordings::{RecordingConfig, RecordingFormat, RecordingStartRequest, RecordingState};
+use coordinator::faults::{DelayFault, FaultPlan};
+use harness::{ChaosCoordinator, LEASE_TTL_SECS, eventually};
+use recorder_node::coordinator::HttpCoordinatorClient;
+use recorder_node::recording::manager::RecordingManager;
+use std::{sync::Arc, time::Duration};
+
+async fn recorder(coordinator: &ChaosCoordinator, node_id: &str) -> Result<RecordingManager> {
+  std::env::set_var("MOCK_RECORDING", "1");
+  let manager = RecordingManager::new();
+  let client = Arc::new(HttpCoordinatorClient::new(coordinator.url.clone()).await?);
+  manager.set_coordinator(client, node_id.to_string()).await;
+  Ok(manager)
+}
+
+fn start_request(id: &str) -> RecordingStartRequest {
+  RecordingStartRequest {
+    config: RecordingConfig {
+      id: id.to_string(),
+      source_stream_id: None,
+      source_uri: Some("rtsp://camera.local/stream".to_string()),
+      retention_hours: Some(1),
+      format: Some(RecordingFormat::Mp4),
+      encoding: None,
+    },
+    lease_ttl_secs: Some(LEASE_TTL_SECS),
+    ai_config: None,
+    motion_policy: None,
+    tags: Vec::new(),
+    labels: Default::default(),
+  }
+}
+
+async fn state_of(manager: &RecordingManager, id: &str) -> Option<RecordingState> {
+  manager.get(id).await.map(|info| info.state)
+}
+
+#[tokio::test]
+async fn recording_fails_over_when_renewals_are_dropped() -> Result<()> {
+  let _ = tracing_subscriber::fmt::try_init();
+  let coordinator = ChaosCoordinator::start().await?;
+  let a = recorder(&coordinator, "recorder-a").await?;
+  let b = recorder(&coordinator, "recorder-b").await?;
+
+  assert!(a.start(start_request("chaos-rec-drop")).await?.accepted);
+  assert!(!b.start(start_request("chaos-rec-drop")).await?.accepted);
+
+  coordinator.drop_renewals_of("recorder-a").await;
+  eventually("recorder-a to give up the recording", || async {
+    state_of(&a, "chaos-rec-drop").await == Some(RecordingState::Error)
+  })
+  .await?;
+  eventually("the lease of recorder-a to expire", || async {
+    coordinator.holder("chaos-rec-drop").await.is_none()
+  })
+  .await?;
+
+  assert!(b.start(start_request("chaos-rec-drop")).await?.accepted);
+  assert_eq!(coordinator.holder("chaos-rec-drop").await.as_deref(), Some("recorder-b"));
+  let history = coordinator.history("chaos-rec-drop").await?;
+  let takeover = history.last().map(|e| (e.event, e.previous_holder_id.as_deref()));
+  assert_eq!(takeover, Some((LeaseEvent::Transfer, Some("recorder-a"))));
+  Ok(())
+}
+
+#[tokio::test]
+async fn old_holder_stops_after_its_lease_is_taken_over() -> Result<()> {
+  let _ = tracing_subscriber::fmt::try_init();
+  let coordinator = ChaosCoordinator::start().await?;
+  let a = recorder(&coordinator, "recorder-a").await?;
+  let b = recorder(&coordinator, "recorder-b").await?;
+
+  assert!(a.start(start_request("chaos-rec-expire")).await?.accepted);
+  assert!(coordinator.faults.expire_lease("chaos-rec-expire").await?);
+  assert!(b.start(start_request("chaos-rec-expire")).await?.accepted);
+
+  // recorder-a learns on its next renewal that the lease is gone
+  eventually("recorder-a to stop the recording", || async {
+    state_of(&a, "chaos-rec-expire").await == Some(RecordingState::Error)
+  })
+  .await?;
+  let lost = a.get("chaos-rec-expire").await.and_then(|info| info.last_error);
+  assert_eq!(lost.as_deref(), Some("lease lost to another recorder"));
+  assert_eq!(coordinator.holder("chaos-rec-expire").await.as_deref(), Some("recorder-b"));
+  assert_ne!(state_of(&b, "chaos-rec-expire").await, Some(RecordingState::Error));
+  Ok(())
+}
+
+#[tokio::test]
+async fn slow_renewals_within_the_ttl_keep_the_lease() -> Result<()> {
+  let _ = tracing_subscriber::fmt::try_init();
+  let coordinator = ChaosCoordinator::start().await?;
+  let a = recorder(&coordinator, "recorder-a").await?;
+
+  assert!(a.start(start_request("chaos-rec-slow")).await?.accepted);
+  coordinator
+    .faults
+    .set(FaultPlan {
+      delays: vec![DelayFault {
+        path_prefix: "/v1/leases/renew".to_string(),
+        delay_ms: 2000,
+      }],
+      ..Default::default()
+    })
+    .await;
+
+  // Past the first renewal (at half the TTL) and its delayed response
+  tokio::time::sleep(Duration::from_secs(LEASE_TTL_SECS / 2 + 3)).await;
+  assert_eq!(coordinator.holder("chaos-rec-slow").await.as_deref(), Some("recorder-a"));
+  assert_eq!(state_of(&a, "chaos-rec-slow").await, Some(RecordingState::Recording));
+  let renewed = coordinator
+    .history("chaos-rec-slow")
+    .await?
+    .iter()
+    .any(|e| e.event == LeaseEvent::Renew);
+  assert!(renewed);
+  Ok(())
+}
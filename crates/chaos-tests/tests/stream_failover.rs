@@ -0,0 +1,157 @@
+//! A stream moves to another gateway when its lease holder cannot reach the
+//! coordinator, and the old gateway stops its worker.
+
+mod harness;
+
+use admin_gateway::{
+  config::GatewayConfig,
+  coordinator::HttpCoordinatorClient,
+  routes,
+  routing::RoutingTable,
+  state::AppState,
+  worker::{RecorderClient, WorkerClient},
+};
+use anyhow::{Result, bail};
+use common::recordings::{RecordingStartRequest, RecordingStartResponse, RecordingStopRequest, RecordingStopResponse};
+use common::streams::{StreamConfig, StreamState};
+use harness::{ChaosCoordinator, LEASE_TTL_SECS, eventually, serve};
+use reqwest::Url;
+use serde_json::json;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{sync::Mutex, task::JoinHandle};
+
+#[derive(Default)]
+struct StubWorker {
+  stopped: Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl WorkerClient for StubWorker {
+  async fn start_stream(&self, _config: &StreamConfig) -> Result<()> {
+    Ok(())
+  }
+
+  async fn stop_stream(&self, stream_id: &str) -> Result<()> {
+    self.stopped.lock().await.push(stream_id.to_string());
+    Ok(())
+  }
+
+  async fn health_check(&self) -> Result<bool> {
+    Ok(true)
+  }
+}
+
+struct NoRecorder;
+
+#[async_trait::async_trait]
+impl RecorderClient for NoRecorder {
+  async fn start_recording(&self, _request: &RecordingStartRequest) -> Result<RecordingStartResponse> {
+    bail!("no recorder in this test")
+  }
+
+  async fn stop_recording(&self, _request: &RecordingStopRequest) -> Result<RecordingStopResponse> {
+    bail!("no recorder in this test")
+  }
+
+  async fn health_check(&self) -> Result<bool> {
+    Ok(false)
+  }
+}
+
+struct Gateway {
+  url: Url,
+  state: AppState,
+  worker: Arc<StubWorker>,
+  server: JoinHandle<()>,
+}
+
+impl Gateway {
+  async fn start(coordinator: &ChaosCoordinator, node_id: &str) -> Result<Self> {
+    let config = GatewayConfig {
+      bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+      coordinator_base_url: coordinator.url.clone(),
+      node_id: node_id.to_string(),
+      worker_base_url: Url::parse("http://worker.local/")?,
+      recorder_base_url: Url::parse("http://recorder.local/")?,
+      node_discovery_interval_secs: 0,
+      node_unhealthy_threshold: 3,
+      overview_timeout_ms: 2000,
+      auth: None,
+      device_manager_base_url: None,
+      ai_service_base_url: None,
+      playback_base_url: None,
+      alert_service_base_url: None,
+      federation_token: None,
+    };
+    let client = Arc::new(HttpCoordinatorClient::new(coordinator.url.clone()).await?);
+    let worker = Arc::new(StubWorker::default());
+    let state = AppState::new(
+      config,
+      client,
+      worker.clone(),
+      Arc::new(NoRecorder),
+      Arc::new(RoutingTable::new(3)),
+    );
+    let (url, server) = serve(routes::router(state.clone())).await?;
+    Ok(Self {
+      url,
+      state,
+      worker,
+      server,
+    })
+  }
+
+  /// Whether the gateway accepted the stream
+  async fn start_stream(&self, id: &str) -> Result<bool> {
+    let response: serde_json::Value = reqwest::Client::new()
+      .post(self.url.join("v1/streams")?)
+      .json(&json!({
+        "config": { "id": id, "uri": "rtsp://camera.local/stream", "codec": "h264", "container": "ts" },
+        "lease_ttl_secs": LEASE_TTL_SECS,
+      }))
+      .send()
+      .await?
+      .json()
+      .await?;
+    Ok(response["accepted"] == true)
+  }
+
+  async fn stream_state(&self, id: &str) -> Option<StreamState> {
+    self.state.streams().read().await.get(id).map(|info| info.state.clone())
+  }
+}
+
+impl Drop for Gateway {
+  fn drop(&mut self) {
+    self.server.abort();
+  }
+}
+
+#[tokio::test]
+async fn stream_fails_over_when_renewals_are_dropped() -> Result<()> {
+  let _ = tracing_subscriber::fmt::try_init();
+  let coordinator = ChaosCoordinator::start().await?;
+  let a = Gateway::start(&coordinator, "gateway-a").await?;
+  let b = Gateway::start(&coordinator, "gateway-b").await?;
+
+  assert!(a.start_stream("chaos-cam").await?);
+  assert!(!b.start_stream("chaos-cam").await?);
+
+  coordinator.drop_renewals_of("gateway-a").await;
+  eventually("the lease of gateway-a to expire", || async {
+    coordinator.holder("chaos-cam").await.is_none()
+  })
+  .await?;
+  assert!(b.start_stream("chaos-cam").await?);
+
+  // gateway-a keeps retrying until it learns the lease is gone, then stops
+  // its worker so the camera is not streamed twice
+  eventually("gateway-a to stop the stream", || async {
+    a.stream_state("chaos-cam").await == Some(StreamState::Error)
+  })
+  .await?;
+  assert_eq!(*a.worker.stopped.lock().await, vec!["chaos-cam".to_string()]);
+  assert_eq!(b.stream_state("chaos-cam").await, Some(StreamState::Running));
+  assert_eq!(coordinator.holder("chaos-cam").await.as_deref(), Some("gateway-b"));
+  Ok(())
+}
@@ -4,6 +4,7 @@
 //! and result delivery.
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 
 /// Configuration for frame capture and processing
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +20,10 @@ pub struct AiFrameConfig {
     /// Skip first N seconds of stream (default: 0)
     #[serde(default)]
     pub skip_seconds: u32,
+
+    /// Skip inference on frames nearly identical to the last analyzed one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion_gate: Option<MotionGateConfig>,
 }
 
 impl Default for AiFrameConfig {
@@ -27,6 +32,7 @@ impl Default for AiFrameConfig {
             frame_interval: 1,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         }
     }
 }
@@ -35,6 +41,530 @@ fn default_frame_interval() -> u32 {
     1
 }
 
+/// Share of an AI node a task gets when the node is saturated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPriority {
+    /// Shed first when the node is saturated
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl TaskPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskPriority::Low => "low",
+            TaskPriority::Normal => "normal",
+            TaskPriority::High => "high",
+        }
+    }
+
+    /// Weight of the task's fair share of inference slots
+    pub fn weight(&self) -> u32 {
+        match self {
+            TaskPriority::Low => 1,
+            TaskPriority::Normal => 2,
+            TaskPriority::High => 4,
+        }
+    }
+}
+
+/// How a task's frames are scheduled on its AI node
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskSchedule {
+    #[serde(default)]
+    pub priority: TaskPriority,
+
+    /// Frames reaching the plugins per second; frames arriving faster are
+    /// skipped (default: `frame_config.max_fps`, else no limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_inference_fps: Option<f32>,
+}
+
+/// Motion gating of a task's frames: a frame only reaches the plugin when
+/// enough of it changed since the last analyzed frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MotionGateConfig {
+    /// Fraction of pixels that must change for a frame to be analyzed
+    /// (default: 0.005)
+    #[serde(default = "default_min_changed_fraction")]
+    pub min_changed_fraction: f32,
+
+    /// Luma difference (0-255) for a pixel to count as changed (default: 25)
+    #[serde(default = "default_pixel_threshold")]
+    pub pixel_threshold: u8,
+
+    /// Analyze a frame after this many skipped ones even without motion, so
+    /// results stay fresh on static scenes; 0 never forces one (default: 30)
+    #[serde(default = "default_max_skipped_frames")]
+    pub max_skipped_frames: u32,
+}
+
+impl Default for MotionGateConfig {
+    fn default() -> Self {
+        Self {
+            min_changed_fraction: default_min_changed_fraction(),
+            pixel_threshold: default_pixel_threshold(),
+            max_skipped_frames: default_max_skipped_frames(),
+        }
+    }
+}
+
+fn default_min_changed_fraction() -> f32 {
+    0.005
+}
+
+fn default_pixel_threshold() -> u8 {
+    25
+}
+
+fn default_max_skipped_frames() -> u32 {
+    30
+}
+
+/// Object tracking algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackingAlgorithm {
+    /// SORT: every detection above the threshold is matched to tracks by IoU
+    Sort,
+    /// ByteTrack: high-confidence detections are matched first, then
+    /// low-confidence ones keep existing tracks alive through occlusions
+    #[default]
+    ByteTrack,
+}
+
+/// Object tracking across the frames of a task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackingConfig {
+    #[serde(default)]
+    pub algorithm: TrackingAlgorithm,
+
+    /// Minimum IoU between a track's predicted box and a detection to match
+    #[serde(default = "default_tracking_iou_threshold")]
+    pub iou_threshold: f32,
+
+    /// Detections at or above this confidence start and match tracks
+    #[serde(default = "default_tracking_high_threshold")]
+    pub high_threshold: f32,
+
+    /// ByteTrack only: detections between this and `high_threshold` only
+    /// match existing tracks
+    #[serde(default = "default_tracking_low_threshold")]
+    pub low_threshold: f32,
+
+    /// Matched frames before a track is reported
+    #[serde(default = "default_tracking_min_hits")]
+    pub min_hits: u32,
+
+    /// Frames without a match before a track is lost
+    #[serde(default = "default_tracking_max_age")]
+    pub max_age: u32,
+
+    /// Classes to track; empty tracks every class
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: TrackingAlgorithm::default(),
+            iou_threshold: default_tracking_iou_threshold(),
+            high_threshold: default_tracking_high_threshold(),
+            low_threshold: default_tracking_low_threshold(),
+            min_hits: default_tracking_min_hits(),
+            max_age: default_tracking_max_age(),
+            classes: Vec::new(),
+        }
+    }
+}
+
+fn default_tracking_iou_threshold() -> f32 {
+    0.3
+}
+
+fn default_tracking_high_threshold() -> f32 {
+    0.5
+}
+
+fn default_tracking_low_threshold() -> f32 {
+    0.1
+}
+
+fn default_tracking_min_hits() -> u32 {
+    3
+}
+
+fn default_tracking_max_age() -> u32 {
+    30
+}
+
+/// Suppression of repeated detections of objects that stay in view
+/// (parked cars, people standing): within `window_secs` an object is only
+/// reported once; it is reported again when it is still there after the
+/// window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+    #[serde(default = "default_dedup_window_secs")]
+    pub window_secs: f32,
+
+    /// Minimum IoU with an object reported before for a detection of the
+    /// same class to be a duplicate. Tracked detections are matched by
+    /// track ID instead.
+    #[serde(default = "default_dedup_iou_threshold")]
+    pub iou_threshold: f32,
+
+    /// Classes to deduplicate; empty deduplicates every class
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: default_dedup_window_secs(),
+            iou_threshold: default_dedup_iou_threshold(),
+            classes: Vec::new(),
+        }
+    }
+}
+
+fn default_dedup_window_secs() -> f32 {
+    60.0
+}
+
+fn default_dedup_iou_threshold() -> f32 {
+    0.5
+}
+
+/// Most entries of each list and of the remapping table of a [`ClassFilter`]
+pub const MAX_CLASS_FILTER_ENTRIES: usize = 256;
+
+/// Class labels a task emits. Detections are first renamed by `remap`
+/// (e.g. `{"truck": "vehicle", "bus": "vehicle"}`), keeping the plugin's
+/// label as `metadata.original_class`; `allow` and `deny` then apply to the
+/// renamed classes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassFilter {
+    /// Classes to keep; empty keeps every class not denied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+
+    /// Classes to drop
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+
+    /// Plugin class to the class emitted instead
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub remap: BTreeMap<String, String>,
+}
+
+impl ClassFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, len) in [("allow", self.allow.len()), ("deny", self.deny.len()), ("remap", self.remap.len())] {
+            if len > MAX_CLASS_FILTER_ENTRIES {
+                return Err(format!("classes.{} may have at most {} entries", name, MAX_CLASS_FILTER_ENTRIES));
+            }
+        }
+        let classes = self
+            .allow
+            .iter()
+            .chain(&self.deny)
+            .chain(self.remap.keys())
+            .chain(self.remap.values());
+        if classes.into_iter().any(|c| c.trim().is_empty()) {
+            return Err("class names must not be empty".to_string());
+        }
+        if let Some(class) = self.allow.iter().find(|c| self.deny.contains(c)) {
+            return Err(format!("class '{}' is both allowed and denied", class));
+        }
+        Ok(())
+    }
+
+    /// Class emitted for a plugin's `class`, or `None` when it is dropped
+    pub fn class_of<'a>(&'a self, class: &'a str) -> Option<&'a str> {
+        let class = self.remap.get(class).map_or(class, String::as_str);
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|c| c == class);
+        (allowed && !self.deny.iter().any(|c| c == class)).then_some(class)
+    }
+
+    /// Rename and filter `detections`, returning how many were dropped
+    pub fn apply(&self, detections: &mut Vec<Detection>) -> usize {
+        let before = detections.len();
+        detections.retain_mut(|detection| {
+            let class = match self.class_of(&detection.class) {
+                None => return false,
+                Some(class) if class == detection.class => return true,
+                Some(class) => class.to_string(),
+            };
+            let original = std::mem::replace(&mut detection.class, class);
+            match &mut detection.metadata {
+                Some(serde_json::Value::Object(metadata)) => {
+                    metadata.insert("original_class".to_string(), original.into());
+                }
+                None => detection.metadata = Some(serde_json::json!({ "original_class": original })),
+                Some(_) => {}
+            }
+            true
+        });
+        before - detections.len()
+    }
+}
+
+/// Track lifecycle stage reported in a result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackEventKind {
+    /// The track reached `min_hits` and got its ID reported
+    Created,
+    /// A reported track was matched again
+    Updated,
+    /// The track went `max_age` frames without a match and was dropped
+    Lost,
+}
+
+/// Track lifecycle event, listed under `metadata.tracks` of a result of a
+/// task with tracking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackEvent {
+    pub track_id: u64,
+    pub event: TrackEventKind,
+    pub class: String,
+    /// Last matched box (for `lost`, the last box seen)
+    pub bbox: BoundingBox,
+    pub confidence: f32,
+    /// Frame timestamp of the first match
+    pub first_seen: u64,
+    /// Frame timestamp of the last match
+    pub last_seen: u64,
+    /// Frames the track was matched in
+    pub hits: u32,
+}
+
+/// Point in frame pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZonePoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl ZonePoint {
+    fn in_frame(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.x >= 0.0 && self.y >= 0.0
+    }
+}
+
+/// Polygon area of a task's frames. An object is in the zone while the
+/// bottom center of its box (where it stands) is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalyticsZone {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Corners in order, at least three
+    pub polygon: Vec<ZonePoint>,
+    /// Classes the zone reports; empty reports every class
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+}
+
+/// Side a tripwire is crossed from, looking along the wire from `from` to
+/// `to` on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossingDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// Line segment reporting the tracked objects that cross it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tripwire {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub from: ZonePoint,
+    pub to: ZonePoint,
+    /// Only crossings in this direction are reported; both when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<CrossingDirection>,
+    /// Classes the tripwire reports; empty reports every class
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+}
+
+/// Most zones and tripwires of one task, together
+pub const MAX_ZONES: usize = 64;
+
+/// Most corners of one zone
+pub const MAX_POLYGON_POINTS: usize = 64;
+
+/// Zones and tripwires of a task; body of `PUT /v1/tasks/:id/zones`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskZones {
+    #[serde(default)]
+    pub zones: Vec<AnalyticsZone>,
+    /// Need tracking, since a crossing is one object's movement
+    #[serde(default)]
+    pub tripwires: Vec<Tripwire>,
+}
+
+impl TaskZones {
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty() && self.tripwires.is_empty()
+    }
+
+    /// Check the zones before they are applied; `tracking` is whether the
+    /// task tracks objects
+    pub fn validate(&self, tracking: bool) -> Result<(), String> {
+        if self.zones.len() + self.tripwires.len() > MAX_ZONES {
+            return Err(format!("a task may have at most {} zones and tripwires", MAX_ZONES));
+        }
+        if !self.tripwires.is_empty() && !tracking {
+            return Err("tripwires need tracking to be enabled on the task".to_string());
+        }
+        let mut ids = HashSet::new();
+        let all_ids = self
+            .zones
+            .iter()
+            .map(|z| z.id.as_str())
+            .chain(self.tripwires.iter().map(|t| t.id.as_str()));
+        for id in all_ids {
+            if id.trim().is_empty() {
+                return Err("zone and tripwire IDs must not be empty".to_string());
+            }
+            if !ids.insert(id) {
+                return Err(format!("duplicate zone or tripwire ID '{}'", id));
+            }
+        }
+        for zone in &self.zones {
+            if !(3..=MAX_POLYGON_POINTS).contains(&zone.polygon.len()) {
+                return Err(format!("zone '{}' needs between 3 and {} points", zone.id, MAX_POLYGON_POINTS));
+            }
+            if !zone.polygon.iter().all(ZonePoint::in_frame) {
+                return Err(format!("zone '{}' has a point outside the frame", zone.id));
+            }
+        }
+        for wire in &self.tripwires {
+            if !wire.from.in_frame() || !wire.to.in_frame() {
+                return Err(format!("tripwire '{}' has a point outside the frame", wire.id));
+            }
+            if wire.from == wire.to {
+                return Err(format!("tripwire '{}' needs two different points", wire.id));
+            }
+        }
+        Ok(())
+    }
+}
+
+// === Camera Analytics ===
+
+/// Central configuration document holding the analytics operators set up
+/// per camera
+pub const CAMERA_ANALYTICS_SERVICE: &str = "camera-analytics";
+
+/// Most alarm classes of one camera
+pub const MAX_ALARM_CLASSES: usize = 256;
+
+/// Zones, tripwires and alarm classes of one camera, drawn in operator-ui;
+/// applied to every AI task whose `source_stream_id` is the camera
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraAnalytics {
+    #[serde(default)]
+    pub zones: Vec<AnalyticsZone>,
+    /// Applied to tasks with tracking only
+    #[serde(default)]
+    pub tripwires: Vec<Tripwire>,
+    /// Classes raised as alerts; empty raises every class
+    #[serde(default)]
+    pub alarm_classes: Vec<String>,
+}
+
+impl CameraAnalytics {
+    pub fn validate(&self) -> Result<(), String> {
+        self.task_zones(true).validate(true)?;
+        if self.alarm_classes.len() > MAX_ALARM_CLASSES {
+            return Err(format!("at most {} alarm classes", MAX_ALARM_CLASSES));
+        }
+        if self.alarm_classes.iter().any(|c| c.trim().is_empty()) {
+            return Err("alarm classes must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// Zones of a task of this camera; tripwires are left out unless the
+    /// task tracks objects
+    pub fn task_zones(&self, tracking: bool) -> TaskZones {
+        TaskZones {
+            zones: self.zones.clone(),
+            tripwires: if tracking { self.tripwires.clone() } else { Vec::new() },
+        }
+    }
+}
+
+/// The `camera-analytics` configuration document: analytics by camera ID
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CameraAnalyticsDocument {
+    pub cameras: BTreeMap<String, CameraAnalytics>,
+}
+
+impl CameraAnalyticsDocument {
+    pub fn validate(&self) -> Result<(), String> {
+        for (camera_id, analytics) in &self.cameras {
+            crate::validation::validate_id(camera_id, "camera_id").map_err(|e| e.to_string())?;
+            analytics
+                .validate()
+                .map_err(|e| format!("camera '{}': {}", camera_id, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// What happened at a zone or tripwire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneEventKind {
+    Entered,
+    Exited,
+    Crossed,
+}
+
+impl ZoneEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ZoneEventKind::Entered => "entered",
+            ZoneEventKind::Exited => "exited",
+            ZoneEventKind::Crossed => "crossed",
+        }
+    }
+}
+
+/// Zone entry/exit or tripwire crossing, listed under
+/// `metadata.zone_events` of a result of a task with zones
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneEvent {
+    pub event: ZoneEventKind,
+    /// Zone or tripwire ID
+    pub zone_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone_name: Option<String>,
+    pub class: String,
+    /// Object's track; without tracking, zones report a class entering
+    /// (its first object) and exiting (its last object)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<u64>,
+    /// Crossings only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<CrossingDirection>,
+    pub bbox: BoundingBox,
+    /// Frame timestamp
+    pub timestamp: u64,
+}
+
 /// Configuration for an AI task
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiTaskConfig {
@@ -56,10 +586,51 @@ pub struct AiTaskConfig {
     #[serde(default)]
     pub model_config: serde_json::Value,
 
+    /// Secondary plugins run in order on each result of the primary plugin
+    /// (e.g. "vehicle_attributes" after "lpr")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secondary_plugins: Vec<String>,
+
+    /// Plugin chain starting at `plugin_type`, e.g.
+    /// `"yolov8_detector -> facial_recognition[person]"`: each later stage
+    /// runs on crops of the previous stage's detections (of the bracketed
+    /// classes, or all), and its detections are added in frame coordinates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<String>,
+
+    /// Renaming and allow/deny lists applied to the detections of the
+    /// pipeline before tracking, so every consumer sees the same classes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classes: Option<ClassFilter>,
+
+    /// Object tracking over the final detections: each tracked detection
+    /// gets `metadata.track_id`, and results list track events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracking: Option<TrackingConfig>,
+
+    /// Zone and line-crossing analytics over the final detections; results
+    /// list the zone events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zones: Option<TaskZones>,
+
+    /// Drop repeated detections of the same object from what is alerted on,
+    /// recorded and put on the timeline; results list the suppressed count
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup: Option<DedupConfig>,
+
+    /// Classes whose violations and zone events are raised as alerts;
+    /// empty raises every class. Results and outputs are not filtered.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alarm_classes: Vec<String>,
+
     /// Frame capture and processing configuration
     #[serde(default)]
     pub frame_config: AiFrameConfig,
 
+    /// Priority and inference rate of the task on its node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<TaskSchedule>,
+
     /// Output format configuration
     pub output: AiOutputConfig,
 }
@@ -146,6 +717,40 @@ pub enum AiTaskState {
     Error,
 }
 
+/// Class of a plugin failure, deciding whether the frame, the plugin or the
+/// task is given up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginErrorKind {
+    /// Only this frame failed (undecodable image, unexpected input); the
+    /// next frame may succeed
+    Recoverable,
+
+    /// The plugin instance is unusable (inference session failed, poisoned
+    /// lock, not initialized) until it is restarted
+    Fatal,
+
+    /// The plugin or task configuration is wrong (missing model, invalid
+    /// settings, model outputs the plugin does not expect); restarting does
+    /// not help
+    Config,
+
+    /// The plugin ran out of a resource such as GPU memory, or its backend
+    /// is unreachable
+    Resource,
+}
+
+impl PluginErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PluginErrorKind::Recoverable => "recoverable",
+            PluginErrorKind::Fatal => "fatal",
+            PluginErrorKind::Config => "config",
+            PluginErrorKind::Resource => "resource",
+        }
+    }
+}
+
 /// AI task information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiTaskInfo {
@@ -167,6 +772,10 @@ pub struct AiTaskInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_error: Option<String>,
 
+    /// Kind of the last plugin failure, when `last_error` came from the plugin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error_kind: Option<PluginErrorKind>,
+
     /// Timestamp when task started (Unix timestamp in milliseconds)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub started_at: Option<u64>,
@@ -209,6 +818,37 @@ pub struct VideoFrame {
 
     /// Frame data (base64 encoded for JSON transport)
     pub data: String,
+
+    /// Audio captured with the frame, for audio plugins; an audio-only
+    /// submission has no image (`width`/`height` 0, empty `data`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioChunk>,
+}
+
+/// Audio samples submitted with a frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioChunk {
+    /// Samples per second
+    pub sample_rate: u32,
+
+    /// Interleaved channels
+    #[serde(default = "default_audio_channels")]
+    pub channels: u16,
+
+    /// Sample format: "s16le" (signed 16-bit) or "f32le" (32-bit float)
+    pub format: String,
+
+    /// Start of the chunk relative to the frame timestamp, in milliseconds
+    /// (negative when the audio precedes the frame)
+    #[serde(default)]
+    pub offset_ms: i64,
+
+    /// Little-endian samples (base64 encoded for JSON transport)
+    pub data: String,
+}
+
+fn default_audio_channels() -> u16 {
+    1
 }
 
 /// Detection result from AI plugin
@@ -229,7 +869,7 @@ pub struct Detection {
 }
 
 /// Bounding box coordinates
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BoundingBox {
     pub x: u32,
     pub y: u32,
@@ -237,6 +877,118 @@ pub struct BoundingBox {
     pub height: u32,
 }
 
+// === Segmentation Masks ===
+
+/// Key of a detection's mask in `Detection.metadata`
+pub const MASK_METADATA_KEY: &str = "mask";
+
+/// Instance mask of a detection, run-length encoded over the pixels of an
+/// area of the frame (usually the detection's box)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentationMask {
+    /// Area the mask covers, in frame pixels
+    pub bbox: BoundingBox,
+    /// Lengths of alternating runs of background and object pixels, row by
+    /// row from the top left; the first run is background and may be 0
+    pub counts: Vec<u32>,
+}
+
+impl SegmentationMask {
+    /// Encode the pixels of `bbox` in row order; `true` is the object
+    pub fn encode(bbox: BoundingBox, pixels: impl IntoIterator<Item = bool>) -> Self {
+        let mut counts = Vec::new();
+        let mut value = false;
+        let mut run = 0u32;
+        for pixel in pixels {
+            if pixel != value {
+                counts.push(run);
+                value = pixel;
+                run = 0;
+            }
+            run += 1;
+        }
+        counts.push(run);
+        Self { bbox, counts }
+    }
+
+    /// Pixels of the area in row order, `true` where the object is
+    pub fn decode(&self) -> Vec<bool> {
+        let mut pixels = Vec::with_capacity(self.pixel_count() as usize);
+        for (i, &run) in self.counts.iter().enumerate() {
+            pixels.extend(std::iter::repeat_n(i % 2 == 1, run as usize));
+        }
+        pixels
+    }
+
+    /// Pixels the object covers
+    pub fn area(&self) -> u64 {
+        self.counts.iter().skip(1).step_by(2).map(|&run| run as u64).sum()
+    }
+
+    /// Whether the frame pixel `(x, y)` is part of the object
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        let BoundingBox { x: left, y: top, width, height } = self.bbox;
+        if x < left || y < top || x - left >= width || y - top >= height {
+            return false;
+        }
+        let mut index = (y - top) as u64 * width as u64 + (x - left) as u64;
+        for (i, &run) in self.counts.iter().enumerate() {
+            if index < run as u64 {
+                return i % 2 == 1;
+            }
+            index -= run as u64;
+        }
+        false
+    }
+
+    /// Frame pixels of the object
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let BoundingBox { x, y, width, .. } = self.bbox;
+        self.decode()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, set)| *set)
+            .map(move |(i, _)| (x + (i as u64 % width as u64) as u32, y + (i as u64 / width as u64) as u32))
+    }
+
+    fn pixel_count(&self) -> u64 {
+        self.bbox.width as u64 * self.bbox.height as u64
+    }
+
+    /// Check that the runs cover the area exactly
+    pub fn validate(&self) -> Result<(), String> {
+        let covered: u64 = self.counts.iter().map(|&run| run as u64).sum();
+        if covered != self.pixel_count() {
+            return Err(format!(
+                "mask runs cover {covered} pixels, its {}x{} area has {}",
+                self.bbox.width,
+                self.bbox.height,
+                self.pixel_count()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The mask a detection carries in its metadata; `None` without one or
+    /// when it is invalid
+    pub fn from_detection(detection: &Detection) -> Option<Self> {
+        let value = detection.metadata.as_ref()?.get(MASK_METADATA_KEY)?;
+        let mask: Self = serde_json::from_value(value.clone()).ok()?;
+        mask.validate().ok().map(|_| mask)
+    }
+
+    /// Store the mask in a detection's metadata, keeping its other entries
+    pub fn attach(&self, detection: &mut Detection) {
+        let metadata = detection
+            .metadata
+            .get_or_insert_with(|| serde_json::Value::Object(Default::default()));
+        if !metadata.is_object() {
+            *metadata = serde_json::Value::Object(Default::default());
+        }
+        metadata[MASK_METADATA_KEY] = serde_json::to_value(self).unwrap_or_default();
+    }
+}
+
 /// AI processing result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiResult {
@@ -289,6 +1041,30 @@ pub struct PluginInfo {
 
     /// Whether the plugin requires GPU
     pub requires_gpu: bool,
+
+    /// Remote inference backend serving the plugin; absent for in-process
+    /// plugins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<PluginBackendStatus>,
+}
+
+/// Connection and health of a remote plugin backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginBackendStatus {
+    /// Transport, e.g. `grpc`
+    pub kind: String,
+    pub endpoint: String,
+    /// Plugin id on the remote server
+    pub remote_plugin: String,
+    /// Connections frames are spread over
+    pub pool_size: usize,
+    pub healthy: bool,
+    /// When the backend was last probed (Unix ms); `None` until the first
+    /// check
+    pub last_checked_ms: Option<u64>,
+    /// Round trip of the last successful probe
+    pub latency_ms: Option<u64>,
+    pub last_error: Option<String>,
 }
 
 /// List of available plugins
@@ -297,6 +1073,59 @@ pub struct PluginListResponse {
     pub plugins: Vec<PluginInfo>,
 }
 
+// === Frame Backpressure ===
+
+/// Frames waiting for an inference slot on the AI node
+pub const QUEUE_DEPTH_HEADER: &str = "x-ai-queue-depth";
+
+/// How long the AI node asks the sender to wait before the task's next frame
+pub const NEXT_FRAME_DELAY_HEADER: &str = "x-ai-next-frame-delay-ms";
+
+/// Load of the AI node a frame was submitted to, sent back with every
+/// frame response (also on `429`) so feeders slow down instead of letting
+/// latency grow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameBackpressure {
+    /// Frames waiting for an inference slot
+    pub queue_depth: usize,
+    /// Suggested wait before the task's next frame; 0 when the node has room
+    pub next_frame_delay_ms: u64,
+}
+
+impl FrameBackpressure {
+    pub fn next_frame_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.next_frame_delay_ms)
+    }
+
+    /// `Retry-After` of a shed frame, in whole seconds and at least one
+    pub fn retry_after_secs(&self) -> u64 {
+        self.next_frame_delay_ms.div_ceil(1000).max(1)
+    }
+
+    pub fn to_headers(&self) -> axum::http::HeaderMap {
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(QUEUE_DEPTH_HEADER, self.queue_depth.into());
+        headers.insert(NEXT_FRAME_DELAY_HEADER, self.next_frame_delay_ms.into());
+        headers
+    }
+
+    /// Read from a frame response; `None` when the node sent no hints.
+    /// Without the delay header a `Retry-After` is used instead
+    pub fn from_headers(headers: &axum::http::HeaderMap) -> Option<Self> {
+        let number = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+        let queue_depth = number(QUEUE_DEPTH_HEADER);
+        let next_frame_delay_ms = number(NEXT_FRAME_DELAY_HEADER)
+            .or_else(|| number(axum::http::header::RETRY_AFTER.as_str()).map(|secs| secs.saturating_mul(1000)));
+        if queue_depth.is_none() && next_frame_delay_ms.is_none() {
+            return None;
+        }
+        Some(Self {
+            queue_depth: queue_depth.unwrap_or_default() as usize,
+            next_frame_delay_ms: next_frame_delay_ms.unwrap_or_default(),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -312,10 +1141,19 @@ mod tests {
                 "model": "yolov8",
                 "confidence_threshold": 0.5
             }),
+            secondary_plugins: Vec::new(),
+            pipeline: None,
+            classes: None,
+            tracking: None,
+            zones: None,
+            dedup: None,
+            alarm_classes: Vec::new(),
+            schedule: None,
             frame_config: AiFrameConfig {
                 frame_interval: 5,
                 max_fps: Some(10),
                 skip_seconds: 0,
+                motion_gate: None,
             },
             output: AiOutputConfig {
                 output_type: "webhook".to_string(),
@@ -371,4 +1209,120 @@ mod tests {
         assert_eq!(deserialized.class, detection.class);
         assert_eq!(deserialized.confidence, detection.confidence);
     }
+
+    #[test]
+    fn test_camera_analytics_document() {
+        let document: CameraAnalyticsDocument = serde_json::from_value(serde_json::json!({
+            "cam-1": {
+                "zones": [{"id": "dock", "polygon": [{"x": 0, "y": 0}, {"x": 100, "y": 0}, {"x": 100, "y": 80}]}],
+                "tripwires": [{"id": "gate", "from": {"x": 0, "y": 50}, "to": {"x": 100, "y": 50}}],
+                "alarm_classes": ["person"]
+            }
+        }))
+        .unwrap();
+        assert!(document.validate().is_ok());
+        let camera = &document.cameras["cam-1"];
+        assert_eq!(camera.task_zones(true).tripwires.len(), 1);
+        assert!(camera.task_zones(false).tripwires.is_empty());
+
+        let mut broken = document.clone();
+        broken.cameras.get_mut("cam-1").unwrap().zones[0].polygon.truncate(2);
+        assert!(broken.validate().unwrap_err().contains("cam-1"));
+        let mut broken = document;
+        broken.cameras.get_mut("cam-1").unwrap().alarm_classes.push(" ".to_string());
+        assert!(broken.validate().is_err());
+    }
+
+    #[test]
+    fn test_frame_backpressure_headers() {
+        let backpressure = FrameBackpressure {
+            queue_depth: 3,
+            next_frame_delay_ms: 1200,
+        };
+        let headers = backpressure.to_headers();
+        assert_eq!(FrameBackpressure::from_headers(&headers), Some(backpressure));
+        assert_eq!(backpressure.retry_after_secs(), 2);
+        assert_eq!(FrameBackpressure::default().retry_after_secs(), 1);
+
+        let mut retry_only = axum::http::HeaderMap::new();
+        retry_only.insert(axum::http::header::RETRY_AFTER, 2.into());
+        assert_eq!(
+            FrameBackpressure::from_headers(&retry_only).map(|b| b.next_frame_delay_ms),
+            Some(2000)
+        );
+        assert_eq!(FrameBackpressure::from_headers(&axum::http::HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_segmentation_mask_rle() {
+        // A 4x3 area at (10, 20) whose middle two columns are the object
+        let bbox = BoundingBox { x: 10, y: 20, width: 4, height: 3 };
+        let pixels: Vec<bool> = (0..12).map(|i| matches!(i % 4, 1 | 2)).collect();
+        let mask = SegmentationMask::encode(bbox.clone(), pixels.clone());
+        assert_eq!(mask.counts, vec![1, 2, 2, 2, 2, 2, 1]);
+        assert_eq!(mask.decode(), pixels);
+        assert_eq!(mask.area(), 6);
+        assert!(mask.contains(11, 21) && !mask.contains(10, 21) && !mask.contains(11, 23));
+        assert_eq!(mask.pixels().next(), Some((11, 20)));
+
+        // Starting on the object gives an empty first run
+        let full = SegmentationMask::encode(bbox.clone(), vec![true; 12]);
+        assert_eq!((full.counts.clone(), full.area()), (vec![0, 12], 12));
+
+        let mut detection = Detection {
+            class: "person".to_string(),
+            confidence: 0.9,
+            bbox,
+            metadata: Some(serde_json::json!({"class_id": 0})),
+        };
+        mask.attach(&mut detection);
+        assert_eq!(detection.metadata.as_ref().unwrap()["class_id"], 0);
+        assert_eq!(SegmentationMask::from_detection(&detection), Some(mask));
+
+        detection.metadata.as_mut().unwrap()["mask"]["counts"] = serde_json::json!([1, 2]);
+        assert_eq!(SegmentationMask::from_detection(&detection), None);
+    }
+
+    #[test]
+    fn test_class_filter() {
+        let filter: ClassFilter = serde_json::from_value(serde_json::json!({
+            "allow": ["vehicle", "person"],
+            "remap": {"truck": "vehicle", "car": "vehicle"}
+        }))
+        .unwrap();
+        assert!(filter.validate().is_ok());
+
+        let detection = |class: &str, metadata| Detection {
+            class: class.to_string(),
+            confidence: 0.9,
+            bbox: BoundingBox { x: 0, y: 0, width: 10, height: 10 },
+            metadata,
+        };
+        let mut detections = vec![
+            detection("truck", Some(serde_json::json!({"track_id": 4}))),
+            detection("person", None),
+            detection("dog", None),
+            detection("car", None),
+        ];
+        assert_eq!(filter.apply(&mut detections), 1);
+        let classes: Vec<&str> = detections.iter().map(|d| d.class.as_str()).collect();
+        assert_eq!(classes, ["vehicle", "person", "vehicle"]);
+        let truck = detections[0].metadata.as_ref().unwrap();
+        assert_eq!((truck["original_class"].as_str(), truck["track_id"].as_u64()), (Some("truck"), Some(4)));
+        assert_eq!(detections[2].metadata.as_ref().unwrap()["original_class"], "car");
+        assert!(detections[1].metadata.is_none());
+
+        // Lists apply to the renamed classes
+        let deny = ClassFilter {
+            deny: vec!["vehicle".to_string()],
+            ..filter.clone()
+        };
+        assert_eq!(deny.class_of("truck"), None);
+        assert!(deny.validate().unwrap_err().contains("both allowed and denied"));
+        let empty = ClassFilter {
+            remap: BTreeMap::from([("truck".to_string(), " ".to_string())]),
+            ..Default::default()
+        };
+        assert!(empty.validate().is_err());
+    }
 }
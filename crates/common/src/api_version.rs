@@ -0,0 +1,381 @@
+//! REST API versioning and deprecation for the coordinator and node APIs.
+//!
+//! Routes carry an explicit version prefix (`/v1/...`, `/v2/...`). Wrapping
+//! a service's router with [`versioned`]:
+//! - serves `GET /api/versions` ([`VersionsInfo`]) for negotiation
+//! - answers `/v2/...` requests without a v2 route with the `/v1` handler, so
+//!   only endpoints whose contract changes need a v2 route
+//! - adds `Deprecation` (RFC 9745), `Sunset` (RFC 8594) and a
+//!   `successor-version` `Link` to responses of routes in the service's
+//!   deprecation table, and `x-api-version` to every versioned response
+//!
+//! Callers [`negotiate`] once per peer and build paths with
+//! [`ApiVersion::path`]. Peers from before versioning have no
+//! `/api/versions` and are spoken to in v1, so mixed-version clusters keep
+//! working through a rolling upgrade.
+
+use anyhow::{Context, Result};
+use axum::{
+  extract::{Request, State},
+  http::{header, HeaderValue, Method, StatusCode, Uri},
+  middleware::{self, Next},
+  response::{IntoResponse, Response},
+  routing::get,
+  Json, Router,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing::debug;
+
+/// Response header naming the API version that served the request
+pub const API_VERSION_HEADER: &str = "x-api-version";
+
+/// Path of the version discovery endpoint
+pub const VERSIONS_PATH: &str = "/api/versions";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+  V1,
+  V2,
+}
+
+impl ApiVersion {
+  /// Versions this build serves and speaks, oldest first
+  pub const SUPPORTED: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+  pub const LATEST: ApiVersion = ApiVersion::V2;
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ApiVersion::V1 => "v1",
+      ApiVersion::V2 => "v2",
+    }
+  }
+
+  /// Relative request path under this version, e.g. `v2/nodes/register`
+  pub fn path(&self, path: &str) -> String {
+    format!("{}/{}", self.as_str(), path.trim_start_matches('/'))
+  }
+
+  /// Version prefix of a request path
+  pub fn of_path(path: &str) -> Option<Self> {
+    path.trim_start_matches('/').split('/').next()?.parse().ok()
+  }
+}
+
+impl fmt::Display for ApiVersion {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for ApiVersion {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().trim_start_matches('v') {
+      "1" => Ok(ApiVersion::V1),
+      "2" => Ok(ApiVersion::V2),
+      other => Err(anyhow::anyhow!("unknown API version: {}", other)),
+    }
+  }
+}
+
+/// A route scheduled for removal
+#[derive(Debug, Clone, Copy)]
+pub struct Deprecation {
+  pub method: &'static str,
+  /// Route path in axum syntax (`/v1/nodes/:node_id`)
+  pub path: &'static str,
+  /// When the route was deprecated (Unix seconds)
+  pub deprecated_at: u64,
+  /// Earliest removal (Unix seconds)
+  pub sunset: u64,
+  /// Route to move to, e.g. `/v2/nodes`
+  pub successor: Option<&'static str>,
+}
+
+impl Deprecation {
+  fn matches(&self, method: &Method, path: &str) -> bool {
+    method.as_str().eq_ignore_ascii_case(self.method) && path_matches(self.path, path)
+  }
+}
+
+/// Deprecated route as published by `GET /api/versions`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeprecatedRoute {
+  pub method: String,
+  pub path: String,
+  pub deprecated_at_epoch_secs: u64,
+  pub sunset_epoch_secs: u64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub successor: Option<String>,
+}
+
+/// Body of `GET /api/versions`. Versions are strings so that a peer on a
+/// newer build can list versions this build does not know.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionsInfo {
+  pub service: String,
+  pub latest: String,
+  pub supported: Vec<String>,
+  #[serde(default)]
+  pub deprecated: Vec<DeprecatedRoute>,
+}
+
+impl VersionsInfo {
+  pub fn new(service: &str, deprecations: &[Deprecation]) -> Self {
+    Self {
+      service: service.to_string(),
+      latest: ApiVersion::LATEST.to_string(),
+      supported: ApiVersion::SUPPORTED.iter().map(|v| v.to_string()).collect(),
+      deprecated: deprecations
+        .iter()
+        .map(|d| DeprecatedRoute {
+          method: d.method.to_string(),
+          path: d.path.to_string(),
+          deprecated_at_epoch_secs: d.deprecated_at,
+          sunset_epoch_secs: d.sunset,
+          successor: d.successor.map(str::to_string),
+        })
+        .collect(),
+    }
+  }
+
+  /// The listed versions this build knows
+  pub fn versions(&self) -> Vec<ApiVersion> {
+    self.supported.iter().filter_map(|v| v.parse().ok()).collect()
+  }
+}
+
+/// Newest version both this build and a peer supporting `remote` speak
+pub fn pick(remote: &[ApiVersion]) -> Option<ApiVersion> {
+  ApiVersion::SUPPORTED.iter().rev().find(|v| remote.contains(v)).copied()
+}
+
+/// API version to use with the service at `base`: the newest both sides
+/// support, or v1 when the peer predates `/api/versions`. Errors only when
+/// the peer cannot be reached, so callers can retry later.
+pub async fn negotiate(client: &reqwest::Client, base: &Url) -> Result<ApiVersion> {
+  let url = base.join(VERSIONS_PATH.trim_start_matches('/')).context("invalid versions endpoint")?;
+  let resp = client.get(url).send().await.context("API version request failed")?;
+  if !resp.status().is_success() {
+    return Ok(ApiVersion::V1);
+  }
+  let info: VersionsInfo = match resp.json().await {
+    Ok(info) => info,
+    Err(_) => return Ok(ApiVersion::V1),
+  };
+  Ok(pick(&info.versions()).unwrap_or(ApiVersion::V1))
+}
+
+/// Add version discovery, the v2 compatibility shim and deprecation headers
+/// to a service router. Apply it last, after every route is merged in.
+pub fn versioned(router: Router, service: &'static str, deprecations: &'static [Deprecation]) -> Router {
+  let info = Arc::new(VersionsInfo::new(service, deprecations));
+  let shim_target = router.clone();
+  router
+    .route(
+      VERSIONS_PATH,
+      get(move || {
+        let info = Arc::clone(&info);
+        async move { Json((*info).clone()) }
+      }),
+    )
+    .fallback(move |request: Request| v2_shim(shim_target.clone(), request))
+    .layer(middleware::from_fn_with_state(deprecations, version_headers))
+}
+
+/// Serve a `/v2/...` request that has no v2 route with its v1 handler
+async fn v2_shim(router: Router, mut request: Request) -> Response {
+  let Some(rest) = request.uri().path().strip_prefix("/v2/") else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let target = match request.uri().query() {
+    Some(query) => format!("/v1/{}?{}", rest, query),
+    None => format!("/v1/{}", rest),
+  };
+  let Ok(uri) = target.parse::<Uri>() else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  *request.uri_mut() = uri;
+  match router.oneshot(request).await {
+    Ok(response) => response,
+    Err(never) => match never {},
+  }
+}
+
+async fn version_headers(
+  State(deprecations): State<&'static [Deprecation]>,
+  request: Request,
+  next: Next,
+) -> Response {
+  let method = request.method().clone();
+  let path = request.uri().path().to_string();
+  let deprecation = deprecations.iter().find(|d| d.matches(&method, &path));
+  if deprecation.is_some() {
+    let user_agent = request
+      .headers()
+      .get(header::USER_AGENT)
+      .and_then(|v| v.to_str().ok())
+      .unwrap_or("-");
+    debug!(%method, %path, user_agent, "deprecated API route called");
+  }
+
+  let mut response = next.run(request).await;
+  let headers = response.headers_mut();
+  if let Some(version) = ApiVersion::of_path(&path) {
+    headers.insert(API_VERSION_HEADER, HeaderValue::from_static(version.as_str()));
+  }
+  if let Some(deprecation) = deprecation {
+    if let Ok(value) = HeaderValue::from_str(&format!("@{}", deprecation.deprecated_at)) {
+      headers.insert("deprecation", value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&http_date(deprecation.sunset)) {
+      headers.insert("sunset", value);
+    }
+    if let Some(successor) = deprecation.successor {
+      if let Ok(value) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+        headers.append(header::LINK, value);
+      }
+    }
+  }
+  response
+}
+
+/// Whether a request path matches an axum route path (`:param` segments)
+fn path_matches(template: &str, path: &str) -> bool {
+  let mut template = template.trim_matches('/').split('/');
+  let mut path = path.trim_matches('/').split('/');
+  loop {
+    match (template.next(), path.next()) {
+      (None, None) => return true,
+      (Some(t), Some(p)) if t == p || (t.starts_with(':') && !p.is_empty()) => {}
+      _ => return false,
+    }
+  }
+}
+
+/// IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`) of a Unix timestamp
+fn http_date(epoch_secs: u64) -> String {
+  const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+  const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+  let days = (epoch_secs / 86_400) as i64;
+  let secs = epoch_secs % 86_400;
+
+  // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z.rem_euclid(146_097);
+  let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS[days.rem_euclid(7) as usize],
+    day,
+    MONTHS[(month - 1) as usize],
+    year,
+    secs / 3_600,
+    secs % 3_600 / 60,
+    secs % 60
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{body::Body, routing::post};
+
+  static DEPRECATIONS: &[Deprecation] = &[Deprecation {
+    method: "GET",
+    path: "/v1/items/:id",
+    deprecated_at: 1_792_108_800,
+    sunset: 1_807_920_000,
+    successor: Some("/v2/items/:id"),
+  }];
+
+  fn app() -> Router {
+    let router = Router::new()
+      .route("/v1/items/:id", get(|| async { "v1 item" }))
+      .route("/v1/items", post(|| async { "v1 create" }))
+      .route("/v2/items/:id", get(|| async { "v2 item" }));
+    versioned(router, "test", DEPRECATIONS)
+  }
+
+  async fn call(method: Method, uri: &str) -> Response {
+    let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+    app().oneshot(request).await.unwrap()
+  }
+
+  async fn text(response: Response) -> String {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+  }
+
+  #[tokio::test]
+  async fn deprecated_route_gets_headers() {
+    let response = call(Method::GET, "/v1/items/7").await;
+    assert_eq!(response.headers()["deprecation"], "@1792108800");
+    assert_eq!(response.headers()["sunset"], "Sat, 17 Apr 2027 00:00:00 GMT");
+    assert_eq!(response.headers()["link"], "</v2/items/:id>; rel=\"successor-version\"");
+    assert_eq!(response.headers()[API_VERSION_HEADER], "v1");
+
+    let response = call(Method::GET, "/v2/items/7").await;
+    assert!(response.headers().get("deprecation").is_none());
+    assert_eq!(text(response).await, "v2 item");
+  }
+
+  #[tokio::test]
+  async fn v2_falls_back_to_v1_handler() {
+    let response = call(Method::POST, "/v2/items?x=1").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[API_VERSION_HEADER], "v2");
+    assert_eq!(text(response).await, "v1 create");
+
+    assert_eq!(call(Method::GET, "/v2/missing").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(call(Method::GET, "/v1/missing").await.status(), StatusCode::NOT_FOUND);
+  }
+
+  #[tokio::test]
+  async fn versions_endpoint_lists_deprecations() {
+    let info: VersionsInfo = serde_json::from_str(&text(call(Method::GET, VERSIONS_PATH).await).await).unwrap();
+    assert_eq!(info.latest, "v2");
+    assert_eq!(info.versions(), ApiVersion::SUPPORTED.to_vec());
+    assert_eq!(info.deprecated[0].path, "/v1/items/:id");
+  }
+
+  #[test]
+  fn picks_newest_common_version() {
+    assert_eq!(pick(&[ApiVersion::V1, ApiVersion::V2]), Some(ApiVersion::V2));
+    assert_eq!(pick(&[ApiVersion::V1]), Some(ApiVersion::V1));
+    assert_eq!(pick(&[]), None);
+
+    let newer = VersionsInfo {
+      service: "coordinator".into(),
+      latest: "v3".into(),
+      supported: vec!["v2".into(), "v3".into()],
+      deprecated: Vec::new(),
+    };
+    assert_eq!(pick(&newer.versions()), Some(ApiVersion::V2));
+  }
+
+  #[test]
+  fn formats_http_dates() {
+    assert_eq!(http_date(784_111_777), "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert_eq!(http_date(951_782_400), "Tue, 29 Feb 2000 00:00:00 GMT");
+    assert_eq!(ApiVersion::V2.path("/nodes/register"), "v2/nodes/register");
+    assert_eq!(ApiVersion::of_path("/v1/nodes"), Some(ApiVersion::V1));
+    assert_eq!(ApiVersion::of_path("/healthz"), None);
+  }
+}
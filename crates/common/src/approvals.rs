@@ -0,0 +1,811 @@
+//! Four-eyes authorization for sensitive playback and export.
+//!
+//! [`ApprovalRule`]s mark streams or recordings (by id, with a trailing `*`
+//! as wildcard) whose playback or export needs a second person. When a
+//! caller asks for such an action, [`ApprovalGate::authorize`] holds it as a
+//! pending [`ApprovalRequest`] instead of serving it. Another user of the
+//! same tenant with [`APPROVE_PERMISSION`] approves or rejects the request;
+//! the requester then repeats the original call (optionally naming the
+//! approval in [`APPROVAL_HEADER`]) and the approval is used up.
+//!
+//! Every step is written to the request's audit trail and, as an operator
+//! action, to the cluster timeline.
+
+use crate::auth_middleware::AuthContext;
+use crate::timeline::{self, TimelineEvent, TimelineEventKind};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Request header naming the approval that covers a repeated call
+pub const APPROVAL_HEADER: &str = "x-approval-id";
+
+/// Permission needed to approve or reject other users' requests
+pub const APPROVE_PERMISSION: &str = "approval:approve";
+
+/// Permission needed to create and delete approval rules
+pub const MANAGE_PERMISSION: &str = "approval:manage";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalAction {
+  /// Live or recorded playback sessions
+  Playback,
+  /// Clip downloads
+  Export,
+}
+
+impl ApprovalAction {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      ApprovalAction::Playback => "playback",
+      ApprovalAction::Export => "export",
+    }
+  }
+}
+
+impl FromStr for ApprovalAction {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "playback" => Ok(ApprovalAction::Playback),
+      "export" => Ok(ApprovalAction::Export),
+      _ => Err(format!("unknown approval action '{s}'")),
+    }
+  }
+}
+
+/// Sources whose `actions` need a second approver
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApprovalRule {
+  pub id: String,
+  /// Global rules (no tenant) apply to every tenant
+  pub tenant_id: Option<String>,
+  /// Stream or recording id; a trailing `*` matches any suffix
+  pub source_pattern: String,
+  pub actions: Vec<ApprovalAction>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  pub created_by: String,
+  pub created_at_ms: u64,
+}
+
+impl ApprovalRule {
+  pub fn matches(&self, action: ApprovalAction, source_id: &str) -> bool {
+    if !self.actions.contains(&action) {
+      return false;
+    }
+    match self.source_pattern.strip_suffix('*') {
+      Some(prefix) => source_id.starts_with(prefix),
+      None => source_id == self.source_pattern,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateApprovalRuleRequest {
+  pub tenant_id: Option<String>,
+  pub source_pattern: String,
+  pub actions: Vec<ApprovalAction>,
+  #[serde(default)]
+  pub description: Option<String>,
+}
+
+impl CreateApprovalRuleRequest {
+  pub fn validate(&self) -> Result<(), String> {
+    let pattern = self.source_pattern.trim();
+    if pattern.is_empty() || pattern.len() > 255 {
+      return Err("source_pattern must be 1-255 characters".to_string());
+    }
+    if pattern.strip_suffix('*').unwrap_or(pattern).contains('*') {
+      return Err("source_pattern may only end with '*'".to_string());
+    }
+    if self.actions.is_empty() {
+      return Err("actions must not be empty".to_string());
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListApprovalRulesResponse {
+  pub rules: Vec<ApprovalRule>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+  /// Waiting for a second person
+  Pending,
+  /// Approved and not yet used
+  Approved,
+  Rejected,
+  /// The approved action was carried out
+  Used,
+  /// Not decided, or not used, in time
+  Expired,
+}
+
+impl ApprovalStatus {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      ApprovalStatus::Pending => "pending",
+      ApprovalStatus::Approved => "approved",
+      ApprovalStatus::Rejected => "rejected",
+      ApprovalStatus::Used => "used",
+      ApprovalStatus::Expired => "expired",
+    }
+  }
+}
+
+impl FromStr for ApprovalStatus {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "pending" => Ok(ApprovalStatus::Pending),
+      "approved" => Ok(ApprovalStatus::Approved),
+      "rejected" => Ok(ApprovalStatus::Rejected),
+      "used" => Ok(ApprovalStatus::Used),
+      "expired" => Ok(ApprovalStatus::Expired),
+      _ => Err(format!("unknown approval status '{s}'")),
+    }
+  }
+}
+
+/// A held playback or export, and its decision
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalRequest {
+  pub id: String,
+  pub tenant_id: String,
+  pub action: ApprovalAction,
+  /// Stream or recording id
+  pub source_id: String,
+  /// Action parameters the approval is limited to, e.g. the export range
+  #[serde(default, skip_serializing_if = "Value::is_null")]
+  pub details: Value,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub reason: Option<String>,
+  pub requested_by: String,
+  pub requested_by_name: String,
+  pub status: ApprovalStatus,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub decided_by: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub decided_by_name: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub decision_note: Option<String>,
+  pub created_at_ms: u64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub decided_at_ms: Option<u64>,
+  /// Deadline for the decision while pending, for the use once approved
+  pub expires_at_ms: u64,
+}
+
+impl ApprovalRequest {
+  /// Status with the deadline applied; stored statuses are only updated on
+  /// the next transition
+  pub fn effective_status(&self, now_ms: u64) -> ApprovalStatus {
+    match self.status {
+      ApprovalStatus::Pending | ApprovalStatus::Approved if now_ms >= self.expires_at_ms => ApprovalStatus::Expired,
+      status => status,
+    }
+  }
+
+  /// Copy with [`Self::effective_status`], for responses
+  pub fn current(mut self, now_ms: u64) -> Self {
+    self.status = self.effective_status(now_ms);
+    self
+  }
+
+  fn covers(&self, caller: &AuthContext, action: ApprovalAction, source_id: &str, details: &Value) -> bool {
+    self.requested_by == caller.user_id
+      && self.tenant_id == caller.tenant_id
+      && self.action == action
+      && self.source_id == source_id
+      && &self.details == details
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListApprovalRequestsResponse {
+  pub approvals: Vec<ApprovalRequest>,
+}
+
+/// Body of approve and reject calls
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApprovalDecisionRequest {
+  #[serde(default)]
+  pub note: Option<String>,
+}
+
+/// Response to a call held for approval (HTTP 202)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequiredResponse {
+  pub approval_required: bool,
+  pub approval: ApprovalRequest,
+  pub message: String,
+}
+
+/// One step of a request's audit trail
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalEvent {
+  pub approval_id: String,
+  /// requested, approved, rejected, used or denied
+  pub event: String,
+  pub actor: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub note: Option<String>,
+  pub at_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListApprovalEventsResponse {
+  pub events: Vec<ApprovalEvent>,
+}
+
+#[async_trait]
+pub trait ApprovalStore: Send + Sync {
+  async fn create_rule(&self, rule: &ApprovalRule) -> Result<()>;
+  async fn get_rule(&self, rule_id: &str) -> Result<Option<ApprovalRule>>;
+  /// Rules of `tenant_id` plus the global ones; every rule for `None`
+  async fn list_rules(&self, tenant_id: Option<&str>) -> Result<Vec<ApprovalRule>>;
+  async fn delete_rule(&self, rule_id: &str) -> Result<bool>;
+
+  async fn create_request(&self, request: &ApprovalRequest) -> Result<()>;
+  async fn get_request(&self, approval_id: &str) -> Result<Option<ApprovalRequest>>;
+  /// Newest first
+  async fn list_requests(&self, tenant_id: Option<&str>, status: Option<ApprovalStatus>) -> Result<Vec<ApprovalRequest>>;
+  /// Pending or approved requests of `user_id`, newest first
+  async fn open_requests(&self, user_id: &str) -> Result<Vec<ApprovalRequest>>;
+  /// Store `request` if its stored status is still `expected`; `false` when
+  /// another decision or use got there first
+  async fn update_request(&self, request: &ApprovalRequest, expected: ApprovalStatus) -> Result<bool>;
+
+  async fn add_event(&self, event: &ApprovalEvent) -> Result<()>;
+  /// Oldest first
+  async fn list_events(&self, approval_id: &str) -> Result<Vec<ApprovalEvent>>;
+}
+
+/// Store for services running without a database; requests are lost on
+/// restart
+#[derive(Default)]
+pub struct MemoryApprovalStore {
+  rules: RwLock<HashMap<String, ApprovalRule>>,
+  requests: RwLock<HashMap<String, ApprovalRequest>>,
+  events: RwLock<Vec<ApprovalEvent>>,
+}
+
+impl MemoryApprovalStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+#[async_trait]
+impl ApprovalStore for MemoryApprovalStore {
+  async fn create_rule(&self, rule: &ApprovalRule) -> Result<()> {
+    self.rules.write().await.insert(rule.id.clone(), rule.clone());
+    Ok(())
+  }
+
+  async fn get_rule(&self, rule_id: &str) -> Result<Option<ApprovalRule>> {
+    Ok(self.rules.read().await.get(rule_id).cloned())
+  }
+
+  async fn list_rules(&self, tenant_id: Option<&str>) -> Result<Vec<ApprovalRule>> {
+    let mut rules: Vec<_> = self
+      .rules
+      .read()
+      .await
+      .values()
+      .filter(|rule| tenant_id.is_none() || rule.tenant_id.is_none() || rule.tenant_id.as_deref() == tenant_id)
+      .cloned()
+      .collect();
+    rules.sort_by(|a, b| a.source_pattern.cmp(&b.source_pattern));
+    Ok(rules)
+  }
+
+  async fn delete_rule(&self, rule_id: &str) -> Result<bool> {
+    Ok(self.rules.write().await.remove(rule_id).is_some())
+  }
+
+  async fn create_request(&self, request: &ApprovalRequest) -> Result<()> {
+    self.requests.write().await.insert(request.id.clone(), request.clone());
+    Ok(())
+  }
+
+  async fn get_request(&self, approval_id: &str) -> Result<Option<ApprovalRequest>> {
+    Ok(self.requests.read().await.get(approval_id).cloned())
+  }
+
+  async fn list_requests(&self, tenant_id: Option<&str>, status: Option<ApprovalStatus>) -> Result<Vec<ApprovalRequest>> {
+    let mut requests: Vec<_> = self
+      .requests
+      .read()
+      .await
+      .values()
+      .filter(|r| tenant_id.is_none_or(|t| r.tenant_id == t))
+      .filter(|r| status.is_none_or(|s| r.status == s))
+      .cloned()
+      .collect();
+    requests.sort_by(|a, b| b.created_at_ms.cmp(&a.created_at_ms));
+    Ok(requests)
+  }
+
+  async fn open_requests(&self, user_id: &str) -> Result<Vec<ApprovalRequest>> {
+    let mut requests: Vec<_> = self
+      .requests
+      .read()
+      .await
+      .values()
+      .filter(|r| r.requested_by == user_id)
+      .filter(|r| matches!(r.status, ApprovalStatus::Pending | ApprovalStatus::Approved))
+      .cloned()
+      .collect();
+    requests.sort_by(|a, b| b.created_at_ms.cmp(&a.created_at_ms));
+    Ok(requests)
+  }
+
+  async fn update_request(&self, request: &ApprovalRequest, expected: ApprovalStatus) -> Result<bool> {
+    let mut requests = self.requests.write().await;
+    match requests.get_mut(&request.id) {
+      Some(stored) if stored.status == expected => {
+        *stored = request.clone();
+        Ok(true)
+      }
+      _ => Ok(false),
+    }
+  }
+
+  async fn add_event(&self, event: &ApprovalEvent) -> Result<()> {
+    self.events.write().await.push(event.clone());
+    Ok(())
+  }
+
+  async fn list_events(&self, approval_id: &str) -> Result<Vec<ApprovalEvent>> {
+    Ok(self
+      .events
+      .read()
+      .await
+      .iter()
+      .filter(|e| e.approval_id == approval_id)
+      .cloned()
+      .collect())
+  }
+}
+
+/// Outcome of [`ApprovalGate::authorize`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum GateDecision {
+  /// No rule applies, or an approval was used up for this call
+  Allowed,
+  /// Held until a second person decides
+  Pending(ApprovalRequest),
+  /// The covering approval was rejected, used or expired, or belongs to
+  /// someone else
+  Denied(String),
+}
+
+/// Why a decision could not be recorded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecisionError {
+  NotFound,
+  /// Requesters cannot decide their own requests
+  OwnRequest,
+  /// Already decided, used or expired
+  NotPending(ApprovalStatus),
+}
+
+/// Applies the approval rules to playback and export calls
+pub struct ApprovalGate {
+  store: std::sync::Arc<dyn ApprovalStore>,
+  service: String,
+  /// How long a request waits for a decision
+  request_ttl: Duration,
+  /// How long an approval may be used
+  grant_ttl: Duration,
+}
+
+impl ApprovalGate {
+  pub fn new(store: std::sync::Arc<dyn ApprovalStore>, service: &str, request_ttl: Duration, grant_ttl: Duration) -> Self {
+    Self {
+      store,
+      service: service.to_string(),
+      request_ttl,
+      grant_ttl,
+    }
+  }
+
+  /// Gate with `APPROVAL_REQUEST_TTL_SECS` (default a day) and
+  /// `APPROVAL_GRANT_TTL_SECS` (default an hour)
+  pub fn from_env(store: std::sync::Arc<dyn ApprovalStore>, service: &str) -> Self {
+    let secs = |name: &str, default: u64| {
+      std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(default)
+    };
+    Self::new(
+      store,
+      service,
+      Duration::from_secs(secs("APPROVAL_REQUEST_TTL_SECS", 24 * 3600)),
+      Duration::from_secs(secs("APPROVAL_GRANT_TTL_SECS", 3600)),
+    )
+  }
+
+  pub fn store(&self) -> &std::sync::Arc<dyn ApprovalStore> {
+    &self.store
+  }
+
+  /// Whether a rule marks `action` on `source_id` as needing approval
+  pub async fn requires_approval(&self, tenant_id: &str, action: ApprovalAction, source_id: &str) -> Result<bool> {
+    let rules = self.store.list_rules(Some(tenant_id)).await?;
+    Ok(rules.iter().any(|rule| rule.matches(action, source_id)))
+  }
+
+  /// Decide whether `caller` may carry out `action` on `source_id` now.
+  /// `details` are the parameters an approval is limited to; `approval_id`
+  /// names the approval the caller presents, otherwise the caller's open
+  /// request for the same action is used, or a new one is opened.
+  pub async fn authorize(
+    &self,
+    caller: &AuthContext,
+    action: ApprovalAction,
+    source_id: &str,
+    details: Value,
+    approval_id: Option<&str>,
+    reason: Option<String>,
+  ) -> Result<GateDecision> {
+    if !self.requires_approval(&caller.tenant_id, action, source_id).await? {
+      return Ok(GateDecision::Allowed);
+    }
+    let now = timeline::now_ms();
+
+    let existing = match approval_id {
+      Some(id) => match self.store.get_request(id).await? {
+        Some(request) if request.covers(caller, action, source_id, &details) => Some(request),
+        _ => return Ok(GateDecision::Denied("approval does not cover this request".to_string())),
+      },
+      None => self
+        .store
+        .open_requests(&caller.user_id)
+        .await?
+        .into_iter()
+        .find(|r| r.covers(caller, action, source_id, &details) && r.effective_status(now) != ApprovalStatus::Expired),
+    };
+
+    let Some(mut request) = existing else {
+      let request = ApprovalRequest {
+        id: uuid::Uuid::new_v4().to_string(),
+        tenant_id: caller.tenant_id.clone(),
+        action,
+        source_id: source_id.to_string(),
+        details,
+        reason,
+        requested_by: caller.user_id.clone(),
+        requested_by_name: caller.username.clone(),
+        status: ApprovalStatus::Pending,
+        decided_by: None,
+        decided_by_name: None,
+        decision_note: None,
+        created_at_ms: now,
+        decided_at_ms: None,
+        expires_at_ms: now + self.request_ttl.as_millis() as u64,
+      };
+      self.store.create_request(&request).await?;
+      self.audit(&request, "requested", &caller.username, request.reason.clone()).await;
+      return Ok(GateDecision::Pending(request));
+    };
+
+    match request.effective_status(now) {
+      ApprovalStatus::Pending => Ok(GateDecision::Pending(request)),
+      ApprovalStatus::Approved => {
+        request.status = ApprovalStatus::Used;
+        if !self.store.update_request(&request, ApprovalStatus::Approved).await? {
+          return Ok(GateDecision::Denied("approval has already been used".to_string()));
+        }
+        self.audit(&request, "used", &caller.username, None).await;
+        Ok(GateDecision::Allowed)
+      }
+      status => {
+        self
+          .audit(&request, "denied", &caller.username, Some(format!("approval is {}", status.as_str())))
+          .await;
+        Ok(GateDecision::Denied(format!("approval is {}", status.as_str())))
+      }
+    }
+  }
+
+  /// Approve or reject a pending request. The caller's permission and
+  /// tenant are checked by the API; this enforces the second person.
+  pub async fn decide(
+    &self,
+    approver: &AuthContext,
+    approval_id: &str,
+    approve: bool,
+    note: Option<String>,
+  ) -> Result<std::result::Result<ApprovalRequest, DecisionError>> {
+    let Some(mut request) = self.store.get_request(approval_id).await? else {
+      return Ok(Err(DecisionError::NotFound));
+    };
+    if request.requested_by == approver.user_id {
+      return Ok(Err(DecisionError::OwnRequest));
+    }
+    let now = timeline::now_ms();
+    match request.effective_status(now) {
+      ApprovalStatus::Pending => {}
+      status => return Ok(Err(DecisionError::NotPending(status))),
+    }
+
+    request.status = if approve {
+      ApprovalStatus::Approved
+    } else {
+      ApprovalStatus::Rejected
+    };
+    request.decided_by = Some(approver.user_id.clone());
+    request.decided_by_name = Some(approver.username.clone());
+    request.decision_note = note.clone();
+    request.decided_at_ms = Some(now);
+    if approve {
+      request.expires_at_ms = now + self.grant_ttl.as_millis() as u64;
+    }
+    if !self.store.update_request(&request, ApprovalStatus::Pending).await? {
+      let current = self.store.get_request(approval_id).await?;
+      let status = current.map_or(ApprovalStatus::Expired, |r| r.effective_status(now));
+      return Ok(Err(DecisionError::NotPending(status)));
+    }
+
+    self
+      .audit(&request, if approve { "approved" } else { "rejected" }, &approver.username, note)
+      .await;
+    Ok(Ok(request))
+  }
+
+  /// Append to the request's audit trail and the cluster timeline
+  async fn audit(&self, request: &ApprovalRequest, event: &str, actor: &str, note: Option<String>) {
+    let entry = ApprovalEvent {
+      approval_id: request.id.clone(),
+      event: event.to_string(),
+      actor: actor.to_string(),
+      note: note.clone(),
+      at_ms: timeline::now_ms(),
+    };
+    if let Err(e) = self.store.add_event(&entry).await {
+      warn!(approval_id = %request.id, event, error = %e, "failed to write approval audit event");
+    }
+    info!(
+      approval_id = %request.id,
+      action = request.action.as_str(),
+      source_id = %request.source_id,
+      event,
+      actor,
+      "approval audit"
+    );
+
+    timeline::record(
+      TimelineEvent::new(
+        TimelineEventKind::OperatorAction,
+        &self.service,
+        format!("{} {} approval {event}", request.source_id, request.action.as_str()),
+      )
+      .camera(request.source_id.clone())
+      .tenant(request.tenant_id.clone())
+      .actor(actor)
+      .details(serde_json::json!({
+        "approval_id": request.id,
+        "action": request.action.as_str(),
+        "event": event,
+        "requested_by": request.requested_by_name,
+        "note": note,
+      })),
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Arc;
+
+  fn user(user_id: &str, tenant_id: &str) -> AuthContext {
+    AuthContext {
+      user_id: user_id.to_string(),
+      tenant_id: tenant_id.to_string(),
+      username: user_id.to_string(),
+      is_system_admin: false,
+      roles: Vec::new(),
+      permissions: vec![APPROVE_PERMISSION.to_string()],
+    }
+  }
+
+  async fn gate_with_rule(pattern: &str, actions: Vec<ApprovalAction>) -> ApprovalGate {
+    let store = Arc::new(MemoryApprovalStore::new());
+    store
+      .create_rule(&ApprovalRule {
+        id: "rule-1".to_string(),
+        tenant_id: Some("t1".to_string()),
+        source_pattern: pattern.to_string(),
+        actions,
+        description: None,
+        created_by: "admin".to_string(),
+        created_at_ms: 0,
+      })
+      .await
+      .unwrap();
+    ApprovalGate::new(store, "test", Duration::from_secs(60), Duration::from_secs(60))
+  }
+
+  #[test]
+  fn rules_match_exact_ids_or_prefixes() {
+    let rule = ApprovalRule {
+      id: "r".to_string(),
+      tenant_id: None,
+      source_pattern: "vault-*".to_string(),
+      actions: vec![ApprovalAction::Export],
+      description: None,
+      created_by: "admin".to_string(),
+      created_at_ms: 0,
+    };
+    assert!(rule.matches(ApprovalAction::Export, "vault-cam-1"));
+    assert!(!rule.matches(ApprovalAction::Playback, "vault-cam-1"));
+    assert!(!rule.matches(ApprovalAction::Export, "lobby-cam-1"));
+
+    let exact = ApprovalRule {
+      source_pattern: "lobby".to_string(),
+      ..rule
+    };
+    assert!(exact.matches(ApprovalAction::Export, "lobby"));
+    assert!(!exact.matches(ApprovalAction::Export, "lobby-2"));
+  }
+
+  #[test]
+  fn rule_patterns_only_allow_a_trailing_wildcard() {
+    let request = |pattern: &str| CreateApprovalRuleRequest {
+      tenant_id: None,
+      source_pattern: pattern.to_string(),
+      actions: vec![ApprovalAction::Playback],
+      description: None,
+    };
+    assert!(request("cam-*").validate().is_ok());
+    assert!(request("*").validate().is_ok());
+    assert!(request("cam-*-hd").validate().is_err());
+    assert!(request("").validate().is_err());
+  }
+
+  #[tokio::test]
+  async fn unmatched_actions_pass_through() {
+    let gate = gate_with_rule("vault-*", vec![ApprovalAction::Export]).await;
+    let alice = user("alice", "t1");
+    let decision = gate
+      .authorize(&alice, ApprovalAction::Playback, "vault-1", Value::Null, None, None)
+      .await
+      .unwrap();
+    assert_eq!(decision, GateDecision::Allowed);
+    // Other tenants' rules do not apply
+    let decision = gate
+      .authorize(&user("bob", "t2"), ApprovalAction::Export, "vault-1", Value::Null, None, None)
+      .await
+      .unwrap();
+    assert_eq!(decision, GateDecision::Allowed);
+  }
+
+  #[tokio::test]
+  async fn held_requests_proceed_once_after_a_second_approval() {
+    let gate = gate_with_rule("vault-*", vec![ApprovalAction::Export]).await;
+    let alice = user("alice", "t1");
+    let bob = user("bob", "t1");
+    let range = serde_json::json!({"start_secs": 0.0, "end_secs": 60.0});
+
+    let GateDecision::Pending(request) = gate
+      .authorize(&alice, ApprovalAction::Export, "vault-1", range.clone(), None, Some("incident 42".into()))
+      .await
+      .unwrap()
+    else {
+      panic!("export should be held");
+    };
+
+    // Repeating the call keeps the same request open
+    let again = gate
+      .authorize(&alice, ApprovalAction::Export, "vault-1", range.clone(), None, None)
+      .await
+      .unwrap();
+    assert_eq!(again, GateDecision::Pending(request.clone()));
+
+    // Four eyes: the requester cannot approve
+    let own = gate.decide(&alice, &request.id, true, None).await.unwrap();
+    assert_eq!(own, Err(DecisionError::OwnRequest));
+    let approved = gate.decide(&bob, &request.id, true, Some("ok".into())).await.unwrap().unwrap();
+    assert_eq!(approved.status, ApprovalStatus::Approved);
+
+    // The approval covers only the requested range
+    let other_range = serde_json::json!({"start_secs": 0.0, "end_secs": 600.0});
+    let decision = gate
+      .authorize(&alice, ApprovalAction::Export, "vault-1", other_range, Some(&request.id), None)
+      .await
+      .unwrap();
+    assert!(matches!(decision, GateDecision::Denied(_)));
+
+    let decision = gate
+      .authorize(&alice, ApprovalAction::Export, "vault-1", range.clone(), Some(&request.id), None)
+      .await
+      .unwrap();
+    assert_eq!(decision, GateDecision::Allowed);
+    let decision = gate
+      .authorize(&alice, ApprovalAction::Export, "vault-1", range, Some(&request.id), None)
+      .await
+      .unwrap();
+    assert_eq!(decision, GateDecision::Denied("approval is used".to_string()));
+
+    let events: Vec<_> = gate
+      .store()
+      .list_events(&request.id)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|e| (e.event, e.actor))
+      .collect();
+    assert_eq!(
+      events,
+      vec![
+        ("requested".to_string(), "alice".to_string()),
+        ("approved".to_string(), "bob".to_string()),
+        ("used".to_string(), "alice".to_string()),
+        ("denied".to_string(), "alice".to_string()),
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn rejected_requests_stay_denied() {
+    let gate = gate_with_rule("lobby", vec![ApprovalAction::Playback]).await;
+    let alice = user("alice", "t1");
+    let GateDecision::Pending(request) = gate
+      .authorize(&alice, ApprovalAction::Playback, "lobby", Value::Null, None, None)
+      .await
+      .unwrap()
+    else {
+      panic!("playback should be held");
+    };
+    gate.decide(&user("bob", "t1"), &request.id, false, None).await.unwrap().unwrap();
+
+    let decision = gate
+      .authorize(&alice, ApprovalAction::Playback, "lobby", Value::Null, Some(&request.id), None)
+      .await
+      .unwrap();
+    assert_eq!(decision, GateDecision::Denied("approval is rejected".to_string()));
+    let twice = gate.decide(&user("carol", "t1"), &request.id, true, None).await.unwrap();
+    assert_eq!(twice, Err(DecisionError::NotPending(ApprovalStatus::Rejected)));
+  }
+
+  #[test]
+  fn undecided_requests_expire() {
+    let request = ApprovalRequest {
+      id: "a".to_string(),
+      tenant_id: "t1".to_string(),
+      action: ApprovalAction::Playback,
+      source_id: "lobby".to_string(),
+      details: Value::Null,
+      reason: None,
+      requested_by: "alice".to_string(),
+      requested_by_name: "alice".to_string(),
+      status: ApprovalStatus::Pending,
+      decided_by: None,
+      decided_by_name: None,
+      decision_note: None,
+      created_at_ms: 0,
+      decided_at_ms: None,
+      expires_at_ms: 1_000,
+    };
+    assert_eq!(request.effective_status(999), ApprovalStatus::Pending);
+    assert_eq!(request.effective_status(1_000), ApprovalStatus::Expired);
+  }
+}
@@ -0,0 +1,216 @@
+//! Cloud archive of recordings.
+//!
+//! Recorder nodes copy recordings selected by archive policies to object
+//! storage (S3, Azure Blob Storage or Google Cloud Storage) with resumable
+//! multipart uploads, and record where each copy went in the recording index.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Recording index tag that marks a recording as flagged by an operator
+pub const FLAGGED_TAG: &str = "flagged";
+
+/// Which recordings a policy archives
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ArchiveSelector {
+  /// Recordings tagged [`FLAGGED_TAG`]
+  Flagged,
+  /// Recordings with indexed events, limited to `event_types` when not empty
+  EventBased {
+    #[serde(default)]
+    event_types: Vec<String>,
+  },
+  /// Recordings that started more than `days` ago
+  OlderThan { days: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivePolicy {
+  pub id: String,
+  /// Global policies (no tenant) cover every tenant's recordings
+  pub tenant_id: Option<String>,
+  pub name: String,
+  pub enabled: bool,
+  pub selector: ArchiveSelector,
+  #[serde(default)]
+  pub created_at: Option<i64>,
+  #[serde(default)]
+  pub updated_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateArchivePolicyRequest {
+  pub tenant_id: Option<String>,
+  pub name: String,
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
+  pub selector: ArchiveSelector,
+}
+
+fn default_enabled() -> bool {
+  true
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateArchivePolicyRequest {
+  pub name: Option<String>,
+  pub enabled: Option<bool>,
+  pub selector: Option<ArchiveSelector>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListArchivePoliciesResponse {
+  pub policies: Vec<ArchivePolicy>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveBackend {
+  S3,
+  Azure,
+  Gcs,
+}
+
+impl ArchiveBackend {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ArchiveBackend::S3 => "s3",
+      ArchiveBackend::Azure => "azure",
+      ArchiveBackend::Gcs => "gcs",
+    }
+  }
+}
+
+impl fmt::Display for ArchiveBackend {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for ArchiveBackend {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "s3" => Ok(ArchiveBackend::S3),
+      "azure" => Ok(ArchiveBackend::Azure),
+      "gcs" => Ok(ArchiveBackend::Gcs),
+      _ => Err(format!("unknown archive backend '{s}'")),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveJobStatus {
+  Pending,
+  Uploading,
+  Completed,
+  Failed,
+}
+
+impl ArchiveJobStatus {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ArchiveJobStatus::Pending => "pending",
+      ArchiveJobStatus::Uploading => "uploading",
+      ArchiveJobStatus::Completed => "completed",
+      ArchiveJobStatus::Failed => "failed",
+    }
+  }
+}
+
+impl FromStr for ArchiveJobStatus {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "pending" => Ok(ArchiveJobStatus::Pending),
+      "uploading" => Ok(ArchiveJobStatus::Uploading),
+      "completed" => Ok(ArchiveJobStatus::Completed),
+      "failed" => Ok(ArchiveJobStatus::Failed),
+      _ => Err(format!("unknown archive job status '{s}'")),
+    }
+  }
+}
+
+/// A part already stored by the backend, with the tag needed to assemble it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArchivedPart {
+  pub part_number: i32,
+  pub tag: String,
+}
+
+/// Upload of one recording. Progress is saved after every part, so an
+/// interrupted upload resumes from the first part the backend has not stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveJob {
+  pub id: String,
+  pub policy_id: Option<String>,
+  pub recording_id: String,
+  pub tenant_id: Option<String>,
+  /// Recorder node holding the recording, which performs the upload
+  pub node_id: String,
+  pub backend: ArchiveBackend,
+  pub source_path: String,
+  pub object_key: String,
+  pub status: ArchiveJobStatus,
+  pub bytes_total: i64,
+  pub bytes_uploaded: i64,
+  pub part_size_bytes: i64,
+  pub upload_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub parts: Vec<ArchivedPart>,
+  /// Where the archived copy can be retrieved, once completed
+  pub location: Option<String>,
+  pub attempts: i32,
+  pub error_message: Option<String>,
+  pub created_at: i64,
+  pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListArchiveJobsResponse {
+  pub jobs: Vec<ArchiveJob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveRunResponse {
+  pub policy_id: String,
+  pub jobs_created: usize,
+}
+
+/// Archived copy of a recording, as recorded in the recording index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveLocation {
+  pub recording_id: String,
+  pub tenant_id: Option<String>,
+  pub location: String,
+  pub archived_at: i64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn selectors_are_tagged_by_kind() {
+    let selector: ArchiveSelector = serde_json::from_str(r#"{"kind":"event_based"}"#).unwrap();
+    assert_eq!(selector, ArchiveSelector::EventBased { event_types: vec![] });
+    let selector: ArchiveSelector = serde_json::from_str(r#"{"kind":"older_than","days":30}"#).unwrap();
+    assert_eq!(selector, ArchiveSelector::OlderThan { days: 30 });
+    assert_eq!(
+      serde_json::to_string(&ArchiveSelector::Flagged).unwrap(),
+      r#"{"kind":"flagged"}"#
+    );
+  }
+
+  #[test]
+  fn backend_round_trips() {
+    for backend in [ArchiveBackend::S3, ArchiveBackend::Azure, ArchiveBackend::Gcs] {
+      assert_eq!(backend.as_str().parse::<ArchiveBackend>().unwrap(), backend);
+    }
+    assert!("ftp".parse::<ArchiveBackend>().is_err());
+  }
+}
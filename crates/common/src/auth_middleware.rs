@@ -1,7 +1,7 @@
 use axum::{
     body::Body,
     extract::{Request, State},
-    http::{header, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
@@ -9,6 +9,8 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+use crate::{gateway_identity, tenant_rls};
+
 /// JWT Claims structure matching auth-service
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthClaims {
@@ -55,6 +57,19 @@ impl AuthContext {
     }
 }
 
+impl From<AuthClaims> for AuthContext {
+    fn from(claims: AuthClaims) -> Self {
+        Self {
+            user_id: claims.sub,
+            tenant_id: claims.tenant_id,
+            username: claims.username,
+            is_system_admin: claims.is_system_admin,
+            roles: claims.roles,
+            permissions: claims.permissions,
+        }
+    }
+}
+
 /// Auth middleware configuration
 #[derive(Clone)]
 pub struct AuthMiddlewareConfig {
@@ -72,6 +87,17 @@ impl AuthMiddlewareConfig {
         }
     }
 
+    /// Read `AUTH_SERVICE_URL` and `JWT_SECRET` (must match auth-service)
+    pub fn from_env() -> Self {
+        let auth_service_url = std::env::var("AUTH_SERVICE_URL")
+            .unwrap_or_else(|_| "http://127.0.0.1:8087".to_string());
+        let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| {
+            tracing::warn!("JWT_SECRET not set, using default (INSECURE for production!)");
+            "default-jwt-secret-CHANGE-IN-PRODUCTION".to_string()
+        });
+        Self::new(auth_service_url, jwt_secret)
+    }
+
     pub fn with_permissions(mut self, permissions: Vec<String>) -> Self {
         self.required_permissions = permissions;
         self
@@ -79,8 +105,8 @@ impl AuthMiddlewareConfig {
 }
 
 /// Extract auth token from request headers
-fn extract_token(req: &Request) -> Option<String> {
-    let auth_header = req.headers().get(header::AUTHORIZATION)?;
+fn extract_token(headers: &HeaderMap) -> Option<String> {
+    let auth_header = headers.get(header::AUTHORIZATION)?;
     let auth_str = auth_header.to_str().ok()?;
 
     if auth_str.starts_with("Bearer ") {
@@ -104,39 +130,57 @@ fn verify_jwt_local(token: &str, jwt_secret: &str) -> Result<AuthClaims, String>
     Ok(token_data.claims)
 }
 
-/// Authentication middleware that verifies JWT tokens
+/// Caller identity from the signed identity header added by the
+/// admin-gateway, or else from a bearer JWT
+pub fn authenticate(headers: &HeaderMap, jwt_secret: &str) -> Result<AuthContext, Response> {
+    match headers.get(gateway_identity::IDENTITY_HEADER) {
+        // Relayed by the admin-gateway, which already validated the caller
+        Some(identity) => identity
+            .to_str()
+            .map_err(|e| e.to_string())
+            .and_then(|token| {
+                gateway_identity::verify(token, jwt_secret).map_err(|e| e.to_string())
+            })
+            .map_err(|e| {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({ "error": format!("Invalid gateway identity: {}", e) })),
+                )
+                    .into_response()
+            }),
+        None => {
+            // Extract token from Authorization header
+            let token = extract_token(headers).ok_or_else(|| {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({ "error": "Missing or invalid Authorization header" })),
+                )
+                    .into_response()
+            })?;
+
+            // Verify JWT token locally
+            let claims = verify_jwt_local(&token, jwt_secret).map_err(|e| {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({ "error": e })),
+                )
+                    .into_response()
+            })?;
+
+            Ok(AuthContext::from(claims))
+        }
+    }
+}
+
+/// Authentication middleware that verifies JWT tokens, or the signed identity
+/// header added by the admin-gateway. Handlers run inside the caller's
+/// [`tenant_rls::scope`].
 pub async fn auth_middleware(
     State(config): State<Arc<AuthMiddlewareConfig>>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, Response> {
-    // Extract token from Authorization header
-    let token = extract_token(&req).ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": "Missing or invalid Authorization header" })),
-        )
-            .into_response()
-    })?;
-
-    // Verify JWT token locally
-    let claims = verify_jwt_local(&token, &config.jwt_secret).map_err(|e| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e })),
-        )
-            .into_response()
-    })?;
-
-    // Create auth context
-    let auth_ctx = AuthContext {
-        user_id: claims.sub.clone(),
-        tenant_id: claims.tenant_id.clone(),
-        username: claims.username.clone(),
-        is_system_admin: claims.is_system_admin,
-        roles: claims.roles.clone(),
-        permissions: claims.permissions.clone(),
-    };
+    let auth_ctx = authenticate(req.headers(), &config.jwt_secret)?;
 
     // Check required permissions
     if !config.required_permissions.is_empty() {
@@ -158,9 +202,10 @@ pub async fn auth_middleware(
     }
 
     // Add auth context to request extensions
+    let tenant = tenant_rls::scope_of(&auth_ctx);
     req.extensions_mut().insert(auth_ctx);
 
-    Ok(next.run(req).await)
+    Ok(tenant_rls::scope(tenant, next.run(req)).await)
 }
 
 /// Extract AuthContext from request extensions
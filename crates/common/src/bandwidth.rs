@@ -0,0 +1,343 @@
+//! Site-wide bandwidth management.
+//!
+//! Every uplink (a site's WAN link, or any shared network segment) can have a
+//! budget in kbit/s. Stream nodes report their camera ingest and playback
+//! nodes their viewing egress, tagged with the uplink they sit behind
+//! (`BANDWIDTH_UPLINK`). The coordinator sums the reports per uplink and
+//! answers each one with a [`BandwidthDirective`]: while an uplink is over
+//! budget, playback nodes throttle remote viewers and stream nodes switch
+//! cameras with a substream to it.
+//!
+//! Budgets are the central configuration document of the `bandwidth`
+//! service (`PUT /v1/config/bandwidth`), shaped as [`BandwidthBudgets`].
+
+use crate::resilient_http::ResilientClient;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{env, time::Duration};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Central configuration service holding the budgets
+pub const CONFIG_SERVICE: &str = "bandwidth";
+
+/// Reports missed before a node's usage no longer counts
+pub const MISSED_REPORTS_EXPIRED: u64 = 3;
+
+const MAX_UPLINKS: usize = 1000;
+
+fn default_true() -> bool {
+  true
+}
+
+fn default_recover_percent() -> u8 {
+  90
+}
+
+/// Which side of the traffic a reporting node carries
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BandwidthRole {
+  /// Stream node pulling camera video
+  Stream,
+  /// Playback service serving viewers
+  Playback,
+}
+
+/// What happens while an uplink is over budget
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthPolicy {
+  /// Cap the egress playback nodes send to viewers outside the site
+  #[serde(default = "default_true")]
+  pub throttle_remote_playback: bool,
+  /// Move cameras that have a substream to it
+  #[serde(default = "default_true")]
+  pub reduce_substream_quality: bool,
+  /// Remote playback keeps at least this much even when ingest alone fills
+  /// the budget
+  #[serde(default)]
+  pub min_remote_playback_kbps: u64,
+  /// The uplink counts as recovered once usage drops below this share of
+  /// the budget, so it does not flap around the limit
+  #[serde(default = "default_recover_percent")]
+  pub recover_below_percent: u8,
+}
+
+impl Default for BandwidthPolicy {
+  fn default() -> Self {
+    Self {
+      throttle_remote_playback: true,
+      reduce_substream_quality: true,
+      min_remote_playback_kbps: 0,
+      recover_below_percent: default_recover_percent(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UplinkBudget {
+  pub uplink: String,
+  pub limit_kbps: u64,
+  #[serde(default)]
+  pub policy: BandwidthPolicy,
+}
+
+/// The `bandwidth` configuration document
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthBudgets {
+  #[serde(default)]
+  pub uplinks: Vec<UplinkBudget>,
+}
+
+impl BandwidthBudgets {
+  pub fn validate(&self) -> Result<(), String> {
+    if self.uplinks.len() > MAX_UPLINKS {
+      return Err(format!("at most {MAX_UPLINKS} uplinks"));
+    }
+    let mut seen = std::collections::HashSet::new();
+    for budget in &self.uplinks {
+      crate::validation::validate_id(&budget.uplink, "uplink").map_err(|e| e.to_string())?;
+      if !seen.insert(budget.uplink.as_str()) {
+        return Err(format!("uplink '{}' listed more than once", budget.uplink));
+      }
+      if budget.limit_kbps == 0 {
+        return Err(format!("limit_kbps of '{}' must be positive", budget.uplink));
+      }
+      if !(1..=100).contains(&budget.policy.recover_below_percent) {
+        return Err(format!("recover_below_percent of '{}' must be 1-100", budget.uplink));
+      }
+    }
+    Ok(())
+  }
+
+  pub fn get(&self, uplink: &str) -> Option<&UplinkBudget> {
+    self.uplinks.iter().find(|b| b.uplink == uplink)
+  }
+}
+
+/// Usage a node measured over its last report interval
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthReport {
+  pub node_id: String,
+  pub role: BandwidthRole,
+  pub uplink: String,
+  /// Camera video received
+  #[serde(default)]
+  pub ingest_kbps: u64,
+  /// Video sent to viewers outside the site
+  #[serde(default)]
+  pub remote_egress_kbps: u64,
+  /// Video sent to viewers on the site's own network; reported for
+  /// visibility, it does not cross the uplink
+  #[serde(default)]
+  pub local_egress_kbps: u64,
+  pub interval_secs: u64,
+}
+
+/// The coordinator's answer to a report
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthDirective {
+  pub uplink: String,
+  /// Usage is over the budget (or has not yet recovered below it)
+  pub constrained: bool,
+  /// Remote playback egress this node may send; `None` is unlimited
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub remote_playback_limit_kbps: Option<u64>,
+  /// Start and keep cameras on their substream
+  #[serde(default)]
+  pub prefer_substream: bool,
+}
+
+/// Usage and budget of one uplink
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UplinkStatus {
+  pub uplink: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub limit_kbps: Option<u64>,
+  pub ingest_kbps: u64,
+  pub remote_egress_kbps: u64,
+  pub local_egress_kbps: u64,
+  /// Ingest plus remote egress
+  pub usage_kbps: u64,
+  pub constrained: bool,
+  /// Nodes with a current report, ordered by id
+  pub nodes: Vec<String>,
+}
+
+/// Measures a node's traffic and applies the coordinator's directives
+#[async_trait]
+pub trait BandwidthSource: Send + Sync + 'static {
+  /// Usage since the previous call, as `(ingest_kbps, remote_egress_kbps,
+  /// local_egress_kbps)`
+  async fn sample(&self, interval: Duration) -> (u64, u64, u64);
+
+  async fn apply(&self, directive: &BandwidthDirective);
+}
+
+/// Periodically reports this node's usage to the coordinator
+pub struct BandwidthReporter {
+  coordinator: Url,
+  node_id: String,
+  role: BandwidthRole,
+  uplink: String,
+  interval: Duration,
+  client: ResilientClient,
+}
+
+impl BandwidthReporter {
+  pub async fn new(coordinator: Url, node_id: &str, role: BandwidthRole, uplink: &str, interval: Duration) -> Result<Self> {
+    let client = ResilientClient::builder("coordinator").build().await?;
+    Ok(Self {
+      coordinator,
+      node_id: node_id.to_string(),
+      role,
+      uplink: uplink.to_string(),
+      interval: interval.max(Duration::from_secs(1)),
+      client,
+    })
+  }
+
+  /// Reporter configured from `COORDINATOR_URL`, `BANDWIDTH_UPLINK` and
+  /// `BANDWIDTH_REPORT_INTERVAL_SECS` (default 10); `None` unless both the
+  /// coordinator and the uplink are set
+  pub async fn from_env(role: BandwidthRole, node_id: &str) -> Result<Option<Self>> {
+    let (Some(coordinator), Some(uplink)) = (non_empty_var("COORDINATOR_URL"), non_empty_var("BANDWIDTH_UPLINK")) else {
+      return Ok(None);
+    };
+    let coordinator = Url::parse(&coordinator).context("invalid COORDINATOR_URL")?;
+    crate::validation::validate_id(&uplink, "BANDWIDTH_UPLINK")?;
+    let interval_secs = env::var("BANDWIDTH_REPORT_INTERVAL_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(10);
+    Ok(Some(
+      Self::new(coordinator, node_id, role, &uplink, Duration::from_secs(interval_secs)).await?,
+    ))
+  }
+
+  pub async fn report(&self, report: &BandwidthReport) -> Result<BandwidthDirective> {
+    let url = self
+      .coordinator
+      .join("v1/bandwidth/reports")
+      .context("invalid coordinator endpoint")?;
+    let resp = self
+      .client
+      .send(|c| c.post(url.clone()).json(report))
+      .await
+      .context("bandwidth report request failed")?
+      .error_for_status()
+      .context("bandwidth report returned error status")?;
+    resp.json().await.context("failed to parse bandwidth directive")
+  }
+
+  /// Sample `source` every interval, report it and hand the directive back.
+  /// When the coordinator cannot be reached the last directive is lifted
+  /// after the report would have expired, so a node never stays throttled
+  /// on its own.
+  pub fn spawn(self, source: impl BandwidthSource) -> JoinHandle<()> {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(self.interval);
+      ticker.tick().await;
+      let mut current = BandwidthDirective {
+        uplink: self.uplink.clone(),
+        ..Default::default()
+      };
+      let mut failures = 0u64;
+      loop {
+        ticker.tick().await;
+        let (ingest_kbps, remote_egress_kbps, local_egress_kbps) = source.sample(self.interval).await;
+        let report = BandwidthReport {
+          node_id: self.node_id.clone(),
+          role: self.role,
+          uplink: self.uplink.clone(),
+          ingest_kbps,
+          remote_egress_kbps,
+          local_egress_kbps,
+          interval_secs: self.interval.as_secs(),
+        };
+        let directive = match self.report(&report).await {
+          Ok(directive) => {
+            failures = 0;
+            directive
+          }
+          Err(e) => {
+            failures += 1;
+            warn!(node_id = %self.node_id, error = %e, "bandwidth report failed");
+            if failures < MISSED_REPORTS_EXPIRED {
+              continue;
+            }
+            BandwidthDirective {
+              uplink: self.uplink.clone(),
+              ..Default::default()
+            }
+          }
+        };
+        if directive != current {
+          info!(
+            node_id = %self.node_id,
+            uplink = %directive.uplink,
+            constrained = directive.constrained,
+            remote_playback_limit_kbps = ?directive.remote_playback_limit_kbps,
+            prefer_substream = directive.prefer_substream,
+            "bandwidth directive changed"
+          );
+          source.apply(&directive).await;
+          current = directive;
+        } else {
+          debug!(node_id = %self.node_id, ingest_kbps, remote_egress_kbps, "bandwidth reported");
+        }
+      }
+    })
+  }
+}
+
+/// kbit/s of `bytes` transferred over `interval`
+pub fn kbps(bytes: u64, interval: Duration) -> u64 {
+  let millis = interval.as_millis().max(1) as u64;
+  bytes.saturating_mul(8) / millis
+}
+
+fn non_empty_var(var: &str) -> Option<String> {
+  env::var(var).ok().filter(|v| !v.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn budgets_document_defaults_policy() {
+    let budgets: BandwidthBudgets =
+      serde_json::from_value(serde_json::json!({ "uplinks": [{ "uplink": "site-a", "limit_kbps": 50000 }] })).unwrap();
+    assert!(budgets.validate().is_ok());
+    let budget = budgets.get("site-a").unwrap();
+    assert!(budget.policy.throttle_remote_playback);
+    assert_eq!(budget.policy.recover_below_percent, 90);
+    assert!(budgets.get("site-b").is_none());
+  }
+
+  #[test]
+  fn budgets_reject_duplicates_and_zero_limits() {
+    let budget = |uplink: &str, limit_kbps| UplinkBudget {
+      uplink: uplink.to_string(),
+      limit_kbps,
+      policy: BandwidthPolicy::default(),
+    };
+    let duplicate = BandwidthBudgets {
+      uplinks: vec![budget("wan", 1000), budget("wan", 2000)],
+    };
+    assert!(duplicate.validate().is_err());
+    let zero = BandwidthBudgets {
+      uplinks: vec![budget("wan", 0)],
+    };
+    assert!(zero.validate().is_err());
+  }
+
+  #[test]
+  fn kbps_from_bytes() {
+    assert_eq!(kbps(1_250_000, Duration::from_secs(10)), 1000);
+    assert_eq!(kbps(0, Duration::ZERO), 0);
+  }
+}
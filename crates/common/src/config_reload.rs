@@ -0,0 +1,509 @@
+//! Reloading service settings without a restart.
+//!
+//! Services read their settings from the environment at startup. Each one
+//! declares the settings it knows ([`Setting`]) and whether a new value can be
+//! applied while running ([`Reload::Live`]) or only on the next start
+//! ([`Reload::Restart`]), then subscribes to a [`ConfigReloader`]. Values are
+//! layered, later sources winning:
+//!
+//! 1. the process environment at startup
+//! 2. `CONFIG_FILE`, `KEY=VALUE` lines re-read on SIGHUP and on
+//!    `POST /v1/settings/reload` ([`settings_routes`])
+//! 3. the `settings` object of the service's central configuration document
+//!    (see [`crate::service_config`]) when `CONFIG_SYNC_ENABLED=true`
+//!
+//! Changed live settings are published to subscribers as a new [`Settings`]
+//! generation. Changes to restart-only settings are reported and logged but
+//! keep their startup value. Declared values are readable by system
+//! administrators at `GET /v1/settings`, so secrets must not be declared.
+
+use crate::auth_middleware::{auth_middleware, AuthMiddlewareConfig, RequireAuth};
+use crate::service_config::ConfigWatcher;
+use anyhow::{Context, Result};
+use axum::{
+  extract::State,
+  http::StatusCode,
+  middleware,
+  response::{IntoResponse, Response},
+  routing::{get, post},
+  Json, Router,
+};
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+  collections::BTreeMap,
+  env,
+  path::{Path, PathBuf},
+  str::FromStr,
+  sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// When a new value of a setting takes effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reload {
+  /// Applied by the running service
+  Live,
+  /// Only read at startup
+  Restart,
+}
+
+/// A setting a service reads, named after its environment variable
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Setting {
+  pub name: &'static str,
+  pub reload: Reload,
+}
+
+impl Setting {
+  pub const fn live(name: &'static str) -> Self {
+    Self { name, reload: Reload::Live }
+  }
+
+  pub const fn restart(name: &'static str) -> Self {
+    Self {
+      name,
+      reload: Reload::Restart,
+    }
+  }
+}
+
+/// Current value of every declared setting that has one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+  /// Starts at 0 and increases by one per applied change
+  pub generation: u64,
+  values: BTreeMap<String, String>,
+}
+
+impl Settings {
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.values.get(name).map(String::as_str)
+  }
+
+  /// Parsed value of `name`, or `default` when unset or invalid
+  pub fn get_or<T: FromStr>(&self, name: &str, default: T) -> T {
+    match self.get(name).map(|v| v.trim().parse()) {
+      Some(Ok(value)) => value,
+      Some(Err(_)) => {
+        warn!(setting = name, value = self.get(name), "invalid setting value, using default");
+        default
+      }
+      None => default,
+    }
+  }
+}
+
+/// Outcome of a reload
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReloadReport {
+  /// Generation in effect after the reload
+  pub generation: u64,
+  /// Live settings whose new value was applied
+  pub applied: Vec<String>,
+  /// Settings whose value changed but only take effect on restart
+  pub requires_restart: Vec<String>,
+  /// Keys supplied by a source that the service does not declare
+  pub unknown: Vec<String>,
+}
+
+#[derive(Default)]
+struct Sources {
+  file: BTreeMap<String, String>,
+  central: BTreeMap<String, String>,
+}
+
+/// Publishes a service's settings and applies reloads
+pub struct ConfigReloader {
+  service: String,
+  declared: &'static [Setting],
+  file: Option<PathBuf>,
+  environment: BTreeMap<String, String>,
+  sources: Mutex<Sources>,
+  tx: watch::Sender<Arc<Settings>>,
+}
+
+impl ConfigReloader {
+  /// Reloader starting from `environment` and the contents of `file`
+  pub fn new(
+    service: impl Into<String>,
+    declared: &'static [Setting],
+    environment: BTreeMap<String, String>,
+    file: Option<PathBuf>,
+  ) -> Result<Self> {
+    let file_values = match &file {
+      Some(path) => read_file(path)?,
+      None => BTreeMap::new(),
+    };
+    let sources = Sources {
+      file: file_values,
+      central: BTreeMap::new(),
+    };
+    let (values, _) = merge(declared, &environment, &sources);
+    Ok(Self {
+      service: service.into(),
+      declared,
+      file,
+      environment,
+      sources: Mutex::new(sources),
+      tx: watch::channel(Arc::new(Settings { generation: 0, values })).0,
+    })
+  }
+
+  /// Reloader for the declared settings found in the environment and in
+  /// `CONFIG_FILE`
+  pub fn from_env(service: impl Into<String>, declared: &'static [Setting]) -> Result<Self> {
+    let environment = declared
+      .iter()
+      .filter_map(|s| env::var(s.name).ok().map(|v| (s.name.to_string(), v)))
+      .collect();
+    let file = env::var("CONFIG_FILE").ok().filter(|v| !v.trim().is_empty()).map(PathBuf::from);
+    Self::new(service, declared, environment, file)
+  }
+
+  /// [`Self::from_env`], reloading on SIGHUP and following the central
+  /// configuration document on `COORDINATOR_URL` when enabled
+  pub async fn start(service: &str, declared: &'static [Setting]) -> Result<Arc<Self>> {
+    let reloader = Arc::new(Self::from_env(service, declared)?);
+    reloader.reload_on_hangup()?;
+    if let Some(coordinator) = env::var("COORDINATOR_URL").ok().filter(|v| !v.trim().is_empty()) {
+      let coordinator = Url::parse(&coordinator).context("invalid COORDINATOR_URL")?;
+      if let Some(watcher) = ConfigWatcher::from_env(coordinator, service).await? {
+        reloader.follow(watcher);
+      }
+    }
+    Ok(reloader)
+  }
+
+  pub fn service(&self) -> &str {
+    &self.service
+  }
+
+  pub fn declared(&self) -> &'static [Setting] {
+    self.declared
+  }
+
+  pub fn current(&self) -> Arc<Settings> {
+    self.tx.borrow().clone()
+  }
+
+  /// Receiver notified whenever live settings change
+  pub fn subscribe(&self) -> watch::Receiver<Arc<Settings>> {
+    self.tx.subscribe()
+  }
+
+  /// Re-read `CONFIG_FILE` and apply what changed
+  pub fn reload(&self) -> Result<ReloadReport> {
+    let file = match &self.file {
+      Some(path) => read_file(path)?,
+      None => BTreeMap::new(),
+    };
+    Ok(self.update(|sources| sources.file = file))
+  }
+
+  /// Apply the `settings` object of a central configuration document
+  pub fn apply_document(&self, document: &Value) -> ReloadReport {
+    let central = document
+      .get("settings")
+      .and_then(Value::as_object)
+      .map(|settings| {
+        settings
+          .iter()
+          .filter_map(|(name, value)| {
+            let value = match value {
+              Value::String(s) => s.clone(),
+              Value::Number(n) => n.to_string(),
+              Value::Bool(b) => b.to_string(),
+              _ => {
+                warn!(setting = %name, "ignoring non-scalar setting in central configuration");
+                return None;
+              }
+            };
+            Some((name.clone(), value))
+          })
+          .collect()
+      })
+      .unwrap_or_default();
+    self.update(|sources| sources.central = central)
+  }
+
+  fn update(&self, change: impl FnOnce(&mut Sources)) -> ReloadReport {
+    let Ok(mut sources) = self.sources.lock() else {
+      warn!(service = %self.service, "settings lock poisoned, reload skipped");
+      return ReloadReport {
+        generation: self.current().generation,
+        ..Default::default()
+      };
+    };
+    change(&mut sources);
+    let (merged, unknown) = merge(self.declared, &self.environment, &sources);
+
+    let current = self.current();
+    let mut values = current.values.clone();
+    let mut report = ReloadReport {
+      unknown,
+      ..Default::default()
+    };
+    for setting in self.declared {
+      let next = merged.get(setting.name);
+      if next == current.values.get(setting.name) {
+        continue;
+      }
+      match setting.reload {
+        Reload::Live => {
+          match next {
+            Some(value) => values.insert(setting.name.to_string(), value.clone()),
+            None => values.remove(setting.name),
+          };
+          report.applied.push(setting.name.to_string());
+        }
+        Reload::Restart => report.requires_restart.push(setting.name.to_string()),
+      }
+    }
+
+    report.generation = current.generation;
+    if !report.applied.is_empty() {
+      report.generation += 1;
+      self.tx.send_replace(Arc::new(Settings {
+        generation: report.generation,
+        values,
+      }));
+      info!(service = %self.service, generation = report.generation, applied = ?report.applied, "settings reloaded");
+    }
+    if !report.requires_restart.is_empty() {
+      warn!(service = %self.service, settings = ?report.requires_restart, "changed settings take effect on restart");
+    }
+    if !report.unknown.is_empty() {
+      warn!(service = %self.service, settings = ?report.unknown, "ignoring unknown settings");
+    }
+    report
+  }
+
+  /// Reload whenever the process receives SIGHUP
+  #[cfg(unix)]
+  pub fn reload_on_hangup(self: &Arc<Self>) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).context("failed to install SIGHUP handler")?;
+    let reloader = Arc::clone(self);
+    tokio::spawn(async move {
+      while hangup.recv().await.is_some() {
+        info!(service = %reloader.service, "SIGHUP received, reloading settings");
+        if let Err(e) = reloader.reload() {
+          warn!(service = %reloader.service, error = %e, "settings reload failed");
+        }
+      }
+    });
+    Ok(())
+  }
+
+  #[cfg(not(unix))]
+  pub fn reload_on_hangup(self: &Arc<Self>) -> Result<()> {
+    Ok(())
+  }
+
+  /// Apply every version of the central configuration document
+  pub fn follow(self: &Arc<Self>, watcher: ConfigWatcher) {
+    let (mut configs, _) = watcher.spawn();
+    let reloader = Arc::clone(self);
+    tokio::spawn(async move {
+      while configs.changed().await.is_ok() {
+        let config = configs.borrow_and_update().clone();
+        if let Some(config) = config {
+          reloader.apply_document(&config.document);
+        }
+      }
+    });
+  }
+}
+
+/// Declared settings from all sources, and the undeclared keys supplied
+fn merge(
+  declared: &[Setting],
+  environment: &BTreeMap<String, String>,
+  sources: &Sources,
+) -> (BTreeMap<String, String>, Vec<String>) {
+  let mut merged = BTreeMap::new();
+  let mut unknown = Vec::new();
+  for layer in [environment, &sources.file, &sources.central] {
+    for (name, value) in layer {
+      if declared.iter().any(|s| s.name == name) {
+        merged.insert(name.clone(), value.clone());
+      } else if !unknown.contains(name) {
+        unknown.push(name.clone());
+      }
+    }
+  }
+  (merged, unknown)
+}
+
+/// `KEY=VALUE` lines; blank lines and lines starting with `#` are skipped
+fn parse_file(contents: &str) -> BTreeMap<String, String> {
+  contents
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| line.split_once('='))
+    .map(|(name, value)| {
+      let value = value.trim();
+      let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+      (name.trim().to_string(), value.to_string())
+    })
+    .collect()
+}
+
+fn read_file(path: &Path) -> Result<BTreeMap<String, String>> {
+  let contents =
+    std::fs::read_to_string(path).with_context(|| format!("failed to read CONFIG_FILE {}", path.display()))?;
+  Ok(parse_file(&contents))
+}
+
+/// `GET /v1/settings` and `POST /v1/settings/reload`, for system
+/// administrators
+pub fn settings_routes<S>(reloader: Arc<ConfigReloader>) -> Router<S>
+where
+  S: Clone + Send + Sync + 'static,
+{
+  Router::new()
+    .route("/v1/settings", get(list_settings))
+    .route("/v1/settings/reload", post(reload_settings))
+    .route_layer(middleware::from_fn_with_state(
+      Arc::new(AuthMiddlewareConfig::from_env()),
+      auth_middleware,
+    ))
+    .with_state(reloader)
+}
+
+fn forbidden() -> Response {
+  (
+    StatusCode::FORBIDDEN,
+    Json(json!({ "error": "system administrator required" })),
+  )
+    .into_response()
+}
+
+async fn list_settings(
+  RequireAuth(ctx): RequireAuth,
+  State(reloader): State<Arc<ConfigReloader>>,
+) -> Response {
+  if !ctx.is_system_admin {
+    return forbidden();
+  }
+  let current = reloader.current();
+  let settings: Vec<Value> = reloader
+    .declared()
+    .iter()
+    .map(|s| json!({ "name": s.name, "reload": s.reload, "value": current.get(s.name) }))
+    .collect();
+  Json(json!({
+    "service": reloader.service(),
+    "generation": current.generation,
+    "settings": settings,
+  }))
+  .into_response()
+}
+
+async fn reload_settings(
+  RequireAuth(ctx): RequireAuth,
+  State(reloader): State<Arc<ConfigReloader>>,
+) -> Response {
+  if !ctx.is_system_admin {
+    return forbidden();
+  }
+  match reloader.reload() {
+    Ok(report) => Json(report).into_response(),
+    Err(e) => (
+      StatusCode::UNPROCESSABLE_ENTITY,
+      Json(json!({ "error": format!("{e:#}") })),
+    )
+      .into_response(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  static DECLARED: &[Setting] = &[Setting::live("CACHE_ITEMS"), Setting::restart("BIND_ADDR")];
+
+  fn reloader(file: &Path) -> ConfigReloader {
+    let environment = BTreeMap::from([
+      ("CACHE_ITEMS".to_string(), "100".to_string()),
+      ("BIND_ADDR".to_string(), "0.0.0.0:80".to_string()),
+    ]);
+    ConfigReloader::new("test", DECLARED, environment, Some(file.to_path_buf())).unwrap()
+  }
+
+  fn write(file: &tempfile::NamedTempFile, contents: &str) {
+    std::fs::write(file.path(), contents).unwrap();
+  }
+
+  #[test]
+  fn file_lines_are_parsed() {
+    let values = parse_file("# comment\n\nA=1\n B = \"two\" \nnot a setting\n");
+    assert_eq!(values.get("A").map(String::as_str), Some("1"));
+    assert_eq!(values.get("B").map(String::as_str), Some("two"));
+    assert_eq!(values.len(), 2);
+  }
+
+  #[test]
+  fn only_live_settings_change_while_running() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    write(&file, "CACHE_ITEMS=200\n");
+    let reloader = reloader(file.path());
+    let changes = reloader.subscribe();
+    assert_eq!(reloader.current().get_or("CACHE_ITEMS", 0), 200);
+
+    write(&file, "CACHE_ITEMS=300\nBIND_ADDR=127.0.0.1:80\nTYPO=1\n");
+    let report = reloader.reload().unwrap();
+    assert_eq!(report.applied, ["CACHE_ITEMS"]);
+    assert_eq!(report.requires_restart, ["BIND_ADDR"]);
+    assert_eq!(report.unknown, ["TYPO"]);
+    assert_eq!(report.generation, 1);
+    assert!(changes.has_changed().unwrap());
+    let current = reloader.current();
+    assert_eq!(current.get_or("CACHE_ITEMS", 0), 300);
+    assert_eq!(current.get("BIND_ADDR"), Some("0.0.0.0:80"));
+
+    // Removing the line falls back to the environment
+    write(&file, "");
+    let report = reloader.reload().unwrap();
+    assert_eq!(report.applied, ["CACHE_ITEMS"]);
+    assert_eq!(reloader.current().get_or("CACHE_ITEMS", 0), 100);
+  }
+
+  #[test]
+  fn central_documents_override_the_file() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    write(&file, "CACHE_ITEMS=200\n");
+    let reloader = reloader(file.path());
+
+    let report = reloader.apply_document(&json!({ "settings": { "CACHE_ITEMS": 500 } }));
+    assert_eq!(report.applied, ["CACHE_ITEMS"]);
+    assert_eq!(reloader.current().get_or("CACHE_ITEMS", 0), 500);
+
+    // Unchanged reloads publish nothing
+    let report = reloader.reload().unwrap();
+    assert!(report.applied.is_empty());
+    assert_eq!(report.generation, 1);
+
+    reloader.apply_document(&json!({}));
+    assert_eq!(reloader.current().get_or("CACHE_ITEMS", 0), 200);
+  }
+
+  #[test]
+  fn invalid_values_use_the_default() {
+    let settings = Settings {
+      generation: 0,
+      values: BTreeMap::from([("CACHE_ITEMS".to_string(), "many".to_string())]),
+    };
+    assert_eq!(settings.get_or("CACHE_ITEMS", 10usize), 10);
+    assert_eq!(settings.get_or("MISSING", 5usize), 5);
+  }
+}
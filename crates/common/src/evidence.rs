@@ -0,0 +1,412 @@
+//! Alert evidence.
+//!
+//! When an alert fires for a camera, an [`EvidenceStore`] keeps what the
+//! camera showed: a JPEG snapshot of the live stream and a clip from
+//! `pre_secs` before to `post_secs` after the alert, both pulled from the
+//! camera's live HLS playlist (`<hls_base_url>/<camera_id>/index.m3u8`) and
+//! stream-copied. Each alert's evidence lives in `<dir>/<event_id>/` next to
+//! an `evidence.json` record with the clip reference (source, time range and
+//! file), and is deleted after its own retention period, independently of
+//! the recording retention policies, so it outlives the routine footage.
+//!
+//! The live playlist only holds the last few segments (stream-node keeps
+//! five of two seconds), which bounds how far back the clip can reach; the
+//! clip's `start_ms` records where it actually starts.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+  env, fs,
+  path::{Path, PathBuf},
+  process::Stdio,
+  sync::Arc,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+pub const SNAPSHOT_FILE: &str = "snapshot.jpg";
+pub const CLIP_FILE: &str = "clip.mp4";
+const RECORD_FILE: &str = "evidence.json";
+
+/// Segment length of stream-node's live HLS output (`-hls_time`)
+const HLS_SEGMENT_SECS: u32 = 2;
+const SNAPSHOT_WIDTH: u32 = 1280;
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone)]
+pub struct EvidenceConfig {
+  pub dir: PathBuf,
+  /// Base URL of the live HLS playlists, e.g.
+  /// `http://playback-service:8087/hls/streams`
+  pub hls_base_url: String,
+  pub pre_secs: u32,
+  pub post_secs: u32,
+  pub retention: Duration,
+}
+
+impl EvidenceConfig {
+  /// Enabled when `EVIDENCE_DIR` and `EVIDENCE_HLS_BASE_URL` are set;
+  /// `EVIDENCE_PRE_SECS` and `EVIDENCE_POST_SECS` default to 10,
+  /// `EVIDENCE_RETENTION_DAYS` to 90
+  pub fn from_env() -> Option<Self> {
+    let dir = env::var("EVIDENCE_DIR").ok().filter(|v| !v.trim().is_empty())?;
+    let hls_base_url = env::var("EVIDENCE_HLS_BASE_URL").ok().filter(|v| !v.trim().is_empty())?;
+    let number = |name: &str, default: u64| {
+      env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(default)
+    };
+    Some(Self {
+      dir: PathBuf::from(dir),
+      hls_base_url: hls_base_url.trim_end_matches('/').to_string(),
+      pre_secs: number("EVIDENCE_PRE_SECS", 10).min(60) as u32,
+      post_secs: number("EVIDENCE_POST_SECS", 10).min(60) as u32,
+      retention: Duration::from_secs(number("EVIDENCE_RETENTION_DAYS", 90).max(1) * 86_400),
+    })
+  }
+}
+
+/// Where an alert's clip comes from and which part of it was kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipReference {
+  /// Live playlist the clip was cut from
+  pub source: String,
+  /// Unix milliseconds
+  pub start_ms: u64,
+  pub end_ms: u64,
+  /// [`CLIP_FILE`] once stored
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub file: Option<String>,
+}
+
+/// Evidence kept for one alert event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+  pub event_id: String,
+  pub tenant_id: String,
+  pub camera_id: String,
+  /// When the alert fired, Unix milliseconds
+  pub alert_at_ms: u64,
+  /// [`SNAPSHOT_FILE`] once stored
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub snapshot: Option<String>,
+  pub clip: ClipReference,
+  /// Deleted after this time, Unix milliseconds
+  pub expires_at_ms: u64,
+  /// Why a snapshot or clip is missing
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub errors: Vec<String>,
+}
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+/// Event and camera ids become path components; anything that could leave
+/// the evidence directory is refused
+fn safe_component(value: &str) -> Result<&str> {
+  let valid = !value.is_empty()
+    && value.len() <= 255
+    && value != "."
+    && value != ".."
+    && value
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+  if !valid {
+    bail!("invalid evidence id '{}'", value);
+  }
+  Ok(value)
+}
+
+/// FFmpeg arguments cutting `pre_secs + post_secs` of video from a live
+/// playlist, starting enough segments back to cover `pre_secs`
+pub fn clip_args(playlist: &str, pre_secs: u32, post_secs: u32, output: &str) -> Vec<String> {
+  let segments_back = pre_secs.div_ceil(HLS_SEGMENT_SECS).max(1);
+  vec![
+    "-loglevel".into(),
+    "error".into(),
+    "-live_start_index".into(),
+    format!("-{}", segments_back),
+    "-i".into(),
+    playlist.into(),
+    "-t".into(),
+    (pre_secs + post_secs).to_string(),
+    "-map".into(),
+    "0:v:0".into(),
+    "-c".into(),
+    "copy".into(),
+    "-movflags".into(),
+    "+faststart".into(),
+    "-y".into(),
+    output.into(),
+  ]
+}
+
+pub struct EvidenceStore {
+  config: EvidenceConfig,
+}
+
+impl EvidenceStore {
+  pub fn new(config: EvidenceConfig) -> Self {
+    Self { config }
+  }
+
+  pub fn from_env() -> Option<Self> {
+    EvidenceConfig::from_env().map(Self::new)
+  }
+
+  pub fn config(&self) -> &EvidenceConfig {
+    &self.config
+  }
+
+  fn event_dir(&self, event_id: &str) -> Result<PathBuf> {
+    Ok(self.config.dir.join(safe_component(event_id)?))
+  }
+
+  fn playlist(&self, camera_id: &str) -> Result<String> {
+    Ok(format!(
+      "{}/{}/index.m3u8",
+      self.config.hls_base_url,
+      safe_component(camera_id)?
+    ))
+  }
+
+  /// Capture an alert's evidence in the background; returns at once, the
+  /// clip is complete `post_secs` later
+  pub fn capture(self: &Arc<Self>, event_id: String, tenant_id: String, camera_id: String) -> JoinHandle<()> {
+    let store = Arc::clone(self);
+    tokio::spawn(async move {
+      match store.capture_now(&event_id, &tenant_id, &camera_id).await {
+        Ok(evidence) if evidence.errors.is_empty() => {
+          info!(event_id = %event_id, camera_id = %camera_id, "alert evidence stored")
+        }
+        Ok(evidence) => warn!(
+          event_id = %event_id,
+          camera_id = %camera_id,
+          errors = ?evidence.errors,
+          "alert evidence incomplete"
+        ),
+        Err(e) => warn!(event_id = %event_id, camera_id = %camera_id, error = %e, "failed to store alert evidence"),
+      }
+    })
+  }
+
+  /// Snapshot now, then cut the clip; the record is written before the clip
+  /// so the snapshot is available while the clip is still recording
+  pub async fn capture_now(&self, event_id: &str, tenant_id: &str, camera_id: &str) -> Result<Evidence> {
+    let dir = self.event_dir(event_id)?;
+    let playlist = self.playlist(camera_id)?;
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let alert_at_ms = now_ms();
+    let pre_ms = u64::from(self.config.pre_secs) * 1000;
+    let mut evidence = Evidence {
+      event_id: event_id.to_string(),
+      tenant_id: tenant_id.to_string(),
+      camera_id: camera_id.to_string(),
+      alert_at_ms,
+      snapshot: None,
+      clip: ClipReference {
+        source: playlist.clone(),
+        start_ms: alert_at_ms.saturating_sub(pre_ms),
+        end_ms: alert_at_ms + u64::from(self.config.post_secs) * 1000,
+        file: None,
+      },
+      expires_at_ms: alert_at_ms + self.config.retention.as_millis() as u64,
+      errors: Vec::new(),
+    };
+
+    match crate::frame_extractor::capture_snapshot(&playlist, SNAPSHOT_WIDTH, SNAPSHOT_TIMEOUT).await {
+      Ok(jpeg) => {
+        fs::write(dir.join(SNAPSHOT_FILE), jpeg)?;
+        evidence.snapshot = Some(SNAPSHOT_FILE.to_string());
+      }
+      Err(e) => evidence.errors.push(format!("snapshot: {:#}", e)),
+    }
+    self.write_record(&dir, &evidence)?;
+
+    let clip_path = dir.join(CLIP_FILE);
+    match self.cut_clip(&playlist, &clip_path).await {
+      Ok(()) => {
+        // A short live window starts the clip later than asked for
+        let probe_path = clip_path.clone();
+        let probed = tokio::task::spawn_blocking(move || crate::thumbnail::probe_video_duration(&probe_path)).await;
+        if let Ok(Ok(duration)) = probed {
+          let total = u64::from(self.config.pre_secs + self.config.post_secs) * 1000;
+          let missing = total.saturating_sub((duration * 1000.0) as u64);
+          evidence.clip.start_ms += missing.min(pre_ms);
+        }
+        evidence.clip.file = Some(CLIP_FILE.to_string());
+      }
+      Err(e) => evidence.errors.push(format!("clip: {:#}", e)),
+    }
+    self.write_record(&dir, &evidence)?;
+    Ok(evidence)
+  }
+
+  async fn cut_clip(&self, playlist: &str, output: &Path) -> Result<()> {
+    let output_str = output.to_str().context("bad clip path")?;
+    let args = clip_args(playlist, self.config.pre_secs, self.config.post_secs, output_str);
+    let deadline = Duration::from_secs(u64::from(self.config.post_secs) + 30);
+    let status = tokio::time::timeout(
+      deadline,
+      tokio::process::Command::new("ffmpeg")
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status(),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("clip not finished within {}s", deadline.as_secs()))?
+    .context("failed to execute ffmpeg")?;
+    if !status.success() {
+      let _ = fs::remove_file(output);
+      bail!("ffmpeg exited with error: {:?}", status);
+    }
+    Ok(())
+  }
+
+  fn write_record(&self, dir: &Path, evidence: &Evidence) -> Result<()> {
+    let tmp = dir.join(format!("{}.tmp", RECORD_FILE));
+    fs::write(&tmp, serde_json::to_vec_pretty(evidence)?)?;
+    fs::rename(&tmp, dir.join(RECORD_FILE))?;
+    Ok(())
+  }
+
+  /// Evidence of an alert event, if any was captured
+  pub fn get(&self, event_id: &str) -> Result<Option<Evidence>> {
+    let path = self.event_dir(event_id)?.join(RECORD_FILE);
+    match fs::read(&path) {
+      Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  /// Path of a stored evidence file ([`SNAPSHOT_FILE`] or [`CLIP_FILE`])
+  pub fn file(&self, event_id: &str, name: &str) -> Result<Option<PathBuf>> {
+    if name != SNAPSHOT_FILE && name != CLIP_FILE {
+      bail!("unknown evidence file '{}'", name);
+    }
+    let path = self.event_dir(event_id)?.join(name);
+    Ok(path.is_file().then_some(path))
+  }
+
+  /// Delete the evidence of one event; returns whether there was any
+  pub fn delete(&self, event_id: &str) -> Result<bool> {
+    let dir = self.event_dir(event_id)?;
+    match fs::remove_dir_all(&dir) {
+      Ok(()) => Ok(true),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  /// Delete evidence past its retention; returns how many alerts' evidence
+  /// was removed. Directories without a readable record are left alone.
+  pub fn purge_expired(&self) -> Result<usize> {
+    let entries = match fs::read_dir(&self.config.dir) {
+      Ok(entries) => entries,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+      Err(e) => return Err(e.into()),
+    };
+    let now = now_ms();
+    let mut purged = 0;
+    for entry in entries.flatten() {
+      let Some(event_id) = entry.file_name().to_str().map(str::to_string) else {
+        continue;
+      };
+      let Ok(Some(evidence)) = self.get(&event_id) else {
+        continue;
+      };
+      if evidence.expires_at_ms <= now && self.delete(&event_id)? {
+        purged += 1;
+      }
+    }
+    Ok(purged)
+  }
+
+  /// Purge expired evidence every hour
+  pub fn spawn_retention(self: &Arc<Self>) -> JoinHandle<()> {
+    let store = Arc::clone(self);
+    tokio::spawn(async move {
+      loop {
+        match store.purge_expired() {
+          Ok(0) => {}
+          Ok(purged) => info!(purged, "purged expired alert evidence"),
+          Err(e) => warn!(error = %e, "failed to purge alert evidence"),
+        }
+        tokio::time::sleep(PURGE_INTERVAL).await;
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn store(dir: &Path) -> EvidenceStore {
+    EvidenceStore::new(EvidenceConfig {
+      dir: dir.to_path_buf(),
+      hls_base_url: "http://playback:8087/hls/streams".into(),
+      pre_secs: 10,
+      post_secs: 10,
+      retention: Duration::from_secs(86_400),
+    })
+  }
+
+  #[test]
+  fn clip_starts_enough_segments_back() {
+    let args = clip_args("http://p/cam/index.m3u8", 10, 10, "/e/clip.mp4");
+    let joined = args.join(" ");
+    assert!(joined.contains("-live_start_index -5 -i http://p/cam/index.m3u8 -t 20"));
+    assert!(joined.ends_with("-y /e/clip.mp4"));
+    assert!(clip_args("x", 3, 5, "o").join(" ").contains("-live_start_index -2"));
+  }
+
+  #[test]
+  fn ids_cannot_escape_the_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = store(dir.path());
+    assert!(store.playlist("../etc").is_err());
+    assert!(store.get("..").is_err());
+    assert!(store.file("event-1", "../../secret").is_err());
+    assert_eq!(
+      store.playlist("cam-1").unwrap(),
+      "http://playback:8087/hls/streams/cam-1/index.m3u8"
+    );
+  }
+
+  #[test]
+  fn expired_evidence_is_purged() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = store(dir.path());
+    for (event_id, expires_at_ms) in [("old", 1), ("new", u64::MAX)] {
+      let event_dir = store.event_dir(event_id).unwrap();
+      fs::create_dir_all(&event_dir).unwrap();
+      let evidence = Evidence {
+        event_id: event_id.into(),
+        tenant_id: "t".into(),
+        camera_id: "cam-1".into(),
+        alert_at_ms: 0,
+        snapshot: None,
+        clip: ClipReference { source: "s".into(), start_ms: 0, end_ms: 0, file: None },
+        expires_at_ms,
+        errors: Vec::new(),
+      };
+      store.write_record(&event_dir, &evidence).unwrap();
+    }
+    assert_eq!(store.purge_expired().unwrap(), 1);
+    assert!(store.get("old").unwrap().is_none());
+    assert!(store.get("new").unwrap().is_some());
+  }
+}
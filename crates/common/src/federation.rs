@@ -0,0 +1,263 @@
+//! Multi-site federation.
+//!
+//! Each edge site (its own coordinator, nodes and admin-gateway) periodically
+//! sends a [`SiteReport`] to a central gateway: overall health, the device
+//! inventory and recent alerts. The central coordinator keeps the latest
+//! report per site and serves aggregated views across sites; a site whose
+//! reports stop arriving is shown as unreachable with its last known data.
+//! Sites never depend on the central instance to operate.
+
+use serde::{Deserialize, Serialize};
+
+/// Devices accepted in one report
+pub const MAX_REPORT_DEVICES: usize = 10_000;
+
+/// Alerts accepted in one report
+pub const MAX_REPORT_ALERTS: usize = 100;
+
+/// Request body limit for reports, sized for a full device inventory
+pub const MAX_REPORT_BYTES: usize = 8 * 1024 * 1024;
+
+/// Reports missed before a site is shown as unreachable
+pub const MISSED_REPORTS_UNREACHABLE: u64 = 3;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SiteStatus {
+  Online,
+  Unreachable,
+}
+
+/// Health of a site as seen by its own gateway
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SiteHealth {
+  /// Overall status of the site's system overview (`healthy`, `degraded`, `unavailable`)
+  pub status: String,
+  pub coordinator_ready: Option<bool>,
+  pub nodes_total: usize,
+  pub nodes_healthy: usize,
+  /// Backends the site's gateway could not reach
+  #[serde(default)]
+  pub failing_sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SiteDevice {
+  pub device_id: String,
+  pub tenant_id: String,
+  pub name: String,
+  pub status: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub location: Option<String>,
+}
+
+/// Alert taken from the site's event timeline
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SiteAlert {
+  pub id: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tenant_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub camera_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub severity: Option<String>,
+  pub message: String,
+  pub fired_at_ms: u64,
+}
+
+/// Periodic snapshot an edge site sends to the central instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteReport {
+  pub site_id: String,
+  pub name: String,
+  /// Playback service of the site as reachable from the central gateway
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub playback_url: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  /// How often the site reports; the central instance derives staleness from it
+  pub report_interval_secs: u64,
+  pub generated_at_epoch_secs: u64,
+  pub health: SiteHealth,
+  pub devices: Vec<SiteDevice>,
+  /// Newest first
+  pub alerts: Vec<SiteAlert>,
+}
+
+/// A site as listed by the central instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteRecord {
+  pub site_id: String,
+  pub name: String,
+  pub status: SiteStatus,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub playback_url: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  pub first_seen_epoch_secs: u64,
+  pub last_report_epoch_secs: u64,
+  pub health: SiteHealth,
+  pub device_count: usize,
+  pub alert_count: usize,
+}
+
+/// Device from the aggregated inventory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederatedDevice {
+  pub site_id: String,
+  pub site_status: SiteStatus,
+  /// When the site last reported this device's status
+  pub as_of_epoch_secs: u64,
+  #[serde(flatten)]
+  pub device: SiteDevice,
+}
+
+/// Alert from the aggregated alert feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederatedAlert {
+  pub site_id: String,
+  #[serde(flatten)]
+  pub alert: SiteAlert,
+}
+
+/// Site counts for the central dashboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FederationHealth {
+  pub sites_total: usize,
+  pub sites_online: usize,
+  pub sites_unreachable: usize,
+  /// Online sites whose own overview is not `healthy`
+  pub sites_degraded: usize,
+  pub devices_total: usize,
+  pub alerts_total: usize,
+}
+
+/// Filters for the aggregated device and alert views
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FederationQuery {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub site_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tenant_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub status: Option<String>,
+}
+
+impl FederationQuery {
+  pub fn matches_device(&self, site_id: &str, device: &SiteDevice) -> bool {
+    self.site_id.as_deref().is_none_or(|s| s == site_id)
+      && self.tenant_id.as_deref().is_none_or(|t| t == device.tenant_id)
+      && self.status.as_deref().is_none_or(|s| s.eq_ignore_ascii_case(&device.status))
+  }
+
+  pub fn matches_alert(&self, site_id: &str, alert: &SiteAlert) -> bool {
+    self.site_id.as_deref().is_none_or(|s| s == site_id)
+      && self.tenant_id.as_deref().is_none_or(|t| alert.tenant_id.as_deref() == Some(t))
+      && self
+        .status
+        .as_deref()
+        .is_none_or(|s| alert.severity.as_deref().is_some_and(|severity| severity.eq_ignore_ascii_case(s)))
+  }
+}
+
+impl SiteReport {
+  /// Check identifiers and size limits before the report is stored
+  pub fn validate(&self) -> Result<(), String> {
+    crate::validation::validate_id(&self.site_id, "site_id").map_err(|e| e.to_string())?;
+    if self.name.trim().is_empty() {
+      return Err("name must not be empty".to_string());
+    }
+    if let Some(url) = &self.playback_url {
+      let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid playback_url: {e}"))?;
+      if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("playback_url must be http or https".to_string());
+      }
+    }
+    if self.report_interval_secs == 0 {
+      return Err("report_interval_secs must be positive".to_string());
+    }
+    if self.devices.len() > MAX_REPORT_DEVICES {
+      return Err(format!("at most {MAX_REPORT_DEVICES} devices per report"));
+    }
+    if self.alerts.len() > MAX_REPORT_ALERTS {
+      return Err(format!("at most {MAX_REPORT_ALERTS} alerts per report"));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn report() -> SiteReport {
+    SiteReport {
+      site_id: "warehouse-east".into(),
+      name: "Warehouse East".into(),
+      playback_url: Some("https://east.example.com:8086/".into()),
+      version: None,
+      report_interval_secs: 30,
+      generated_at_epoch_secs: 1_700_000_000,
+      health: SiteHealth::default(),
+      devices: vec![SiteDevice {
+        device_id: "cam-1".into(),
+        tenant_id: "tenant-a".into(),
+        name: "Dock door".into(),
+        status: "online".into(),
+        location: None,
+      }],
+      alerts: vec![],
+    }
+  }
+
+  #[test]
+  fn validates_reports() {
+    assert!(report().validate().is_ok());
+
+    let mut bad_id = report();
+    bad_id.site_id = "../east".into();
+    assert!(bad_id.validate().is_err());
+
+    let mut bad_url = report();
+    bad_url.playback_url = Some("file:///etc/passwd".into());
+    assert!(bad_url.validate().is_err());
+
+    let mut too_many = report();
+    too_many.alerts = vec![
+      SiteAlert {
+        id: "a".into(),
+        tenant_id: Some("tenant-a".into()),
+        camera_id: None,
+        severity: Some("critical".into()),
+        message: "tamper".into(),
+        fired_at_ms: 1_700_000_000_000,
+      };
+      MAX_REPORT_ALERTS + 1
+    ];
+    assert!(too_many.validate().is_err());
+  }
+
+  #[test]
+  fn federated_device_flattens_site_device() {
+    let device = FederatedDevice {
+      site_id: "warehouse-east".into(),
+      site_status: SiteStatus::Unreachable,
+      as_of_epoch_secs: 1_700_000_000,
+      device: report().devices.remove(0),
+    };
+    let value = serde_json::to_value(&device).unwrap();
+    assert_eq!(value["device_id"], "cam-1");
+    assert_eq!(value["site_status"], "unreachable");
+
+    let query = FederationQuery {
+      status: Some("ONLINE".into()),
+      ..Default::default()
+    };
+    assert!(query.matches_device("warehouse-east", &device.device));
+    let other_site = FederationQuery {
+      site_id: Some("hq".into()),
+      ..Default::default()
+    };
+    assert!(!other_site.matches_device("warehouse-east", &device.device));
+  }
+}
@@ -0,0 +1,216 @@
+//! FFmpeg pipeline progress.
+//!
+//! Started with [`PROGRESS_ARGS`], FFmpeg writes a block of `key=value`
+//! lines to stdout about twice a second, each ending with
+//! `progress=continue` (or `progress=end` on exit). The blocks carry the
+//! frame counters and the speed against real time that explain why a
+//! pipeline falls behind: dropped and duplicated frames, an encoder slower
+//! than the camera.
+//!
+//! FFmpeg does not report how many frames wait in front of the encoder, so
+//! the backlog is measured as lag instead: how far the output timestamps
+//! trail the wall clock since the first report. A live source produces one
+//! second of video per second; when the encoder keeps up the lag stays flat,
+//! when frames back up it grows by the same amount.
+
+use std::io::{BufRead, BufReader, Read};
+use std::time::Instant;
+
+/// Global options that make FFmpeg report progress on stdout instead of
+/// the stats line on stderr
+pub const PROGRESS_ARGS: [&str; 3] = ["-progress", "pipe:1", "-nostats"];
+
+/// One progress block
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PipelineProgress {
+  /// Frames written since the pipeline started
+  pub frame: u64,
+  pub fps: f64,
+  /// Output bitrate, unknown until the muxer has written something
+  pub bitrate_kbps: Option<f64>,
+  /// Timestamp of the last written frame, in microseconds
+  pub out_time_us: i64,
+  /// Frames duplicated to keep the output rate
+  pub dup_frames: u64,
+  /// Frames dropped, mostly because the encoder fell behind
+  pub drop_frames: u64,
+  /// Processing speed against real time (`1.0` keeps up with a live source)
+  pub speed: Option<f64>,
+  /// Last block, written when FFmpeg exits
+  pub ended: bool,
+}
+
+/// Collects `key=value` lines into [`PipelineProgress`] blocks
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+  current: PipelineProgress,
+}
+
+impl ProgressParser {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Feed one line; returns the block it completes. Unknown keys and
+  /// values FFmpeg leaves empty (`N/A`) are skipped.
+  pub fn feed(&mut self, line: &str) -> Option<PipelineProgress> {
+    let (key, value) = line.trim().split_once('=')?;
+    let value = value.trim();
+    match key {
+      "frame" => set(&mut self.current.frame, value),
+      "fps" => set(&mut self.current.fps, value),
+      "bitrate" => {
+        self.current.bitrate_kbps = value.trim_end_matches("kbits/s").parse().ok();
+      }
+      "out_time_us" | "out_time_ms" => set(&mut self.current.out_time_us, value),
+      "dup_frames" => set(&mut self.current.dup_frames, value),
+      "drop_frames" => set(&mut self.current.drop_frames, value),
+      "speed" => self.current.speed = value.trim_end_matches('x').parse().ok(),
+      "progress" => {
+        self.current.ended = value == "end";
+        return Some(std::mem::take(&mut self.current));
+      }
+      _ => {}
+    }
+    None
+  }
+}
+
+fn set<T: std::str::FromStr>(field: &mut T, value: &str) {
+  if let Ok(parsed) = value.parse() {
+    *field = parsed;
+  }
+}
+
+/// A progress block with what changed since the previous one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressSample {
+  pub progress: PipelineProgress,
+  /// Frames dropped since the previous block
+  pub new_drop_frames: u64,
+  /// Frames duplicated since the previous block
+  pub new_dup_frames: u64,
+  /// Seconds the output trails the wall clock, relative to the first block
+  pub lag_secs: f64,
+}
+
+/// Turns successive blocks of one FFmpeg process into samples
+#[derive(Debug, Default)]
+pub struct ProgressTracker {
+  previous: Option<PipelineProgress>,
+  /// Wall clock and output time of the first block
+  origin: Option<(Instant, i64)>,
+}
+
+impl ProgressTracker {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn observe(&mut self, progress: PipelineProgress, now: Instant) -> ProgressSample {
+    let (started, first_out_us) = *self.origin.get_or_insert((now, progress.out_time_us));
+    let wall_secs = now.duration_since(started).as_secs_f64();
+    let media_secs = (progress.out_time_us - first_out_us) as f64 / 1_000_000.0;
+
+    let (drops_before, dups_before) = self
+      .previous
+      .as_ref()
+      .map(|p| (p.drop_frames, p.dup_frames))
+      .unwrap_or_default();
+    let sample = ProgressSample {
+      new_drop_frames: progress.drop_frames.saturating_sub(drops_before),
+      new_dup_frames: progress.dup_frames.saturating_sub(dups_before),
+      lag_secs: (wall_secs - media_secs).max(0.0),
+      progress: progress.clone(),
+    };
+    self.previous = Some(progress);
+    sample
+  }
+}
+
+/// Read FFmpeg's progress output on a thread of its own until the process
+/// closes it. `on_sample` gets every sample, then `None` once the output is
+/// closed so per-pipeline state can be cleared.
+pub fn spawn_reader<R, F>(
+  name: String,
+  output: R,
+  mut on_sample: F,
+) -> std::io::Result<std::thread::JoinHandle<()>>
+where
+  R: Read + Send + 'static,
+  F: FnMut(Option<&ProgressSample>) + Send + 'static,
+{
+  std::thread::Builder::new().name(name).spawn(move || {
+    let mut parser = ProgressParser::new();
+    let mut tracker = ProgressTracker::new();
+    for line in BufReader::new(output).lines() {
+      let Ok(line) = line else {
+        break;
+      };
+      if let Some(progress) = parser.feed(&line) {
+        let sample = tracker.observe(progress, Instant::now());
+        on_sample(Some(&sample));
+      }
+    }
+    on_sample(None);
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  const BLOCK: &str = "frame=250\nfps=24.98\nstream_0_0_q=-1.0\nbitrate=1843.2kbits/s\n\
+    total_size=2301952\nout_time_us=10000000\nout_time_ms=10000000\nout_time=00:00:10.000000\n\
+    dup_frames=2\ndrop_frames=5\nspeed=0.998x\nprogress=continue\n";
+
+  #[test]
+  fn parses_a_progress_block() {
+    let mut parser = ProgressParser::new();
+    let blocks: Vec<PipelineProgress> = BLOCK.lines().filter_map(|l| parser.feed(l)).collect();
+    assert_eq!(blocks.len(), 1);
+    let block = &blocks[0];
+    assert_eq!(block.frame, 250);
+    assert!((block.fps - 24.98).abs() < 1e-9);
+    assert_eq!(block.bitrate_kbps, Some(1843.2));
+    assert_eq!(block.out_time_us, 10_000_000);
+    assert_eq!(block.dup_frames, 2);
+    assert_eq!(block.drop_frames, 5);
+    assert_eq!(block.speed, Some(0.998));
+    assert!(!block.ended);
+
+    // Before the first packet FFmpeg has no bitrate or speed yet
+    for line in ["frame=0", "bitrate=N/A", "speed=N/A", "progress=end"] {
+      if let Some(block) = parser.feed(line) {
+        assert_eq!(block.bitrate_kbps, None);
+        assert_eq!(block.speed, None);
+        assert!(block.ended);
+      }
+    }
+  }
+
+  #[test]
+  fn tracks_drops_and_lag() {
+    let start = Instant::now();
+    let block = |out_secs: i64, drops: u64| PipelineProgress {
+      out_time_us: out_secs * 1_000_000,
+      drop_frames: drops,
+      ..PipelineProgress::default()
+    };
+    let mut tracker = ProgressTracker::new();
+
+    // The first block sets the origin; connection time is not lag
+    let sample = tracker.observe(block(3, 0), start);
+    assert_eq!(sample.lag_secs, 0.0);
+
+    let sample = tracker.observe(block(13, 4), start + Duration::from_secs(10));
+    assert_eq!(sample.new_drop_frames, 4);
+    assert!(sample.lag_secs < 1e-9);
+
+    // The encoder managed 6s of video in 10s: 4s are backed up
+    let sample = tracker.observe(block(19, 10), start + Duration::from_secs(20));
+    assert_eq!(sample.new_drop_frames, 6);
+    assert!((sample.lag_secs - 4.0).abs() < 1e-9);
+  }
+}
@@ -6,6 +6,7 @@
 use anyhow::{Context, Result};
 use base64::Engine;
 use std::process::{Command, Stdio};
+use std::time::Duration;
 use tracing::{debug, error, warn};
 
 /// Extract a single JPEG frame from a video source
@@ -95,6 +96,41 @@ pub fn extract_frame_jpeg(
     Ok(output.stdout)
 }
 
+/// Capture one JPEG frame from a live source, giving up after `timeout`
+///
+/// Unlike [`extract_frame_jpeg`] this does not block the runtime, and ffmpeg
+/// is killed when the deadline passes, so an unresponsive camera cannot pin
+/// a thread.
+pub async fn capture_snapshot(source_uri: &str, width: u32, timeout: Duration) -> Result<Vec<u8>> {
+    let mut command = tokio::process::Command::new("ffmpeg");
+    command.args(["-loglevel", "error"]);
+    if source_uri.starts_with("rtsp://") {
+        command.args(["-rtsp_transport", "tcp"]);
+    }
+    command.args(["-i", source_uri, "-vframes", "1", "-f", "image2pipe"]);
+    if width > 0 {
+        command.args(["-vf", &format!("scale={}:-1", width)]);
+    }
+    command
+        .args(["-q:v", "5", "pipe:1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(timeout, command.output())
+        .await
+        .map_err(|_| anyhow::anyhow!("no frame within {}s", timeout.as_secs()))?
+        .context("failed to execute ffmpeg")?;
+    if !output.status.success() {
+        anyhow::bail!("ffmpeg exited with error: {:?}", output.status);
+    }
+    if output.stdout.is_empty() {
+        anyhow::bail!("ffmpeg returned no frame data");
+    }
+    Ok(output.stdout)
+}
+
 /// Extract a frame and encode it as base64 (for JSON transport)
 pub fn extract_frame_base64(
     source_uri: &str,
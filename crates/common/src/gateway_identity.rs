@@ -0,0 +1,158 @@
+//! Verified caller identity forwarded from the admin-gateway to internal
+//! services.
+//!
+//! After validating the caller's token the gateway mints a short-lived HS256
+//! token with the shared `JWT_SECRET` and an `iss` of [`ISSUER`], and sends it
+//! in [`IDENTITY_HEADER`] on every upstream call made while handling the
+//! request (see [`scope`]). [`crate::auth_middleware::auth_middleware`] accepts
+//! this header in place of a bearer token, so services trust the gateway's
+//! decision even when users hold RS256 tokens they cannot verify themselves.
+//!
+//! The plain [`USER_ID_HEADER`] and [`TENANT_ID_HEADER`] are informational
+//! (logs, tracing) and must never be used for authorization.
+
+use anyhow::{Context, Result};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::auth_middleware::{AuthClaims, AuthContext};
+
+pub const IDENTITY_HEADER: &str = "x-quadrant-identity";
+pub const USER_ID_HEADER: &str = "x-quadrant-user-id";
+pub const TENANT_ID_HEADER: &str = "x-quadrant-tenant-id";
+
+/// `iss` of identity tokens; user access tokens carry none, so they cannot be
+/// replayed as identity headers
+pub const ISSUER: &str = "quadrant-admin-gateway";
+
+tokio::task_local! {
+    static OUTBOUND: HeaderMap;
+}
+
+#[derive(Serialize)]
+struct IdentityClaims<'a> {
+    #[serde(flatten)]
+    claims: &'a AuthClaims,
+    iss: &'static str,
+}
+
+/// Identity token for `ctx`, valid for `ttl`
+pub fn mint(ctx: &AuthContext, secret: &str, ttl: Duration) -> Result<String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let claims = AuthClaims {
+        sub: ctx.user_id.clone(),
+        tenant_id: ctx.tenant_id.clone(),
+        username: ctx.username.clone(),
+        is_system_admin: ctx.is_system_admin,
+        roles: ctx.roles.clone(),
+        permissions: ctx.permissions.clone(),
+        exp: now + ttl.as_secs() as i64,
+        iat: now,
+    };
+    encode(
+        &Header::new(Algorithm::HS256),
+        &IdentityClaims {
+            claims: &claims,
+            iss: ISSUER,
+        },
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .context("failed to sign identity token")
+}
+
+/// Check an identity token minted by the gateway
+pub fn verify(token: &str, secret: &str) -> Result<AuthContext> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_issuer(&[ISSUER]);
+    validation.set_required_spec_claims(&["exp", "iss"]);
+    let claims = decode::<AuthClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)
+        .context("invalid identity token")?
+        .claims;
+    Ok(AuthContext::from(claims))
+}
+
+/// Headers to attach to upstream calls on behalf of `ctx`
+pub fn headers(ctx: &AuthContext, secret: &str, ttl: Duration) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static(IDENTITY_HEADER),
+        HeaderValue::from_str(&mint(ctx, secret, ttl)?)?,
+    );
+    headers.insert(HeaderName::from_static(USER_ID_HEADER), HeaderValue::from_str(&ctx.user_id)?);
+    headers.insert(HeaderName::from_static(TENANT_ID_HEADER), HeaderValue::from_str(&ctx.tenant_id)?);
+    Ok(headers)
+}
+
+/// Drop identity headers supplied by the client; only the gateway sets them
+pub fn strip(headers: &mut HeaderMap) {
+    for name in [IDENTITY_HEADER, USER_ID_HEADER, TENANT_ID_HEADER] {
+        headers.remove(name);
+    }
+}
+
+/// Run `fut` with `headers` attached to every
+/// [`crate::resilient_http::ResilientClient`] call it makes. Tasks spawned
+/// from `fut` do not inherit the scope.
+pub async fn scope<F: Future>(headers: HeaderMap, fut: F) -> F::Output {
+    OUTBOUND.scope(headers, fut).await
+}
+
+/// Identity headers of the enclosing [`scope`], if any
+pub fn current() -> Option<HeaderMap> {
+    OUTBOUND.try_with(HeaderMap::clone).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> AuthContext {
+        AuthContext {
+            user_id: "user-1".into(),
+            tenant_id: "tenant-a".into(),
+            username: "alice".into(),
+            is_system_admin: false,
+            roles: vec!["operator".into()],
+            permissions: vec!["stream:read".into()],
+        }
+    }
+
+    #[test]
+    fn identity_round_trips_and_rejects_access_tokens() {
+        let token = mint(&ctx(), "secret", Duration::from_secs(60)).unwrap();
+        let verified = verify(&token, "secret").unwrap();
+        assert_eq!(verified.user_id, "user-1");
+        assert_eq!(verified.permissions, ["stream:read"]);
+        assert!(verify(&token, "other-secret").is_err());
+
+        // A user's own HS256 access token has no issuer
+        let access = encode(
+            &Header::new(Algorithm::HS256),
+            &AuthClaims {
+                sub: "user-1".into(),
+                tenant_id: "tenant-a".into(),
+                username: "alice".into(),
+                is_system_admin: true,
+                roles: vec![],
+                permissions: vec![],
+                exp: i64::MAX / 2,
+                iat: 0,
+            },
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert!(verify(&access, "secret").is_err());
+    }
+
+    #[tokio::test]
+    async fn scope_exposes_headers_to_current_task_only() {
+        assert!(current().is_none());
+        let headers = headers(&ctx(), "secret", Duration::from_secs(60)).unwrap();
+        let seen = scope(headers, async { current() }).await.unwrap();
+        assert_eq!(seen.get(TENANT_ID_HEADER).unwrap(), "tenant-a");
+        assert!(seen.contains_key(IDENTITY_HEADER));
+    }
+}
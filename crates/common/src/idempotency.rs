@@ -0,0 +1,452 @@
+//! `Idempotency-Key` support for mutating endpoints.
+//!
+//! Clients that retry a POST (start recording, start stream, create device)
+//! send the same `Idempotency-Key` header on every attempt. The first request
+//! is executed and its response cached for a TTL; identical retries receive
+//! the cached response instead of creating a duplicate. Reusing a key with a
+//! different request body is rejected with 422.
+//!
+//! Responses are cached in an [`IdempotencyStore`]: [`MemoryIdempotencyStore`]
+//! for single-instance deployments, or [`StateStoreIdempotencyStore`] to share
+//! keys across replicas through the coordinator's Postgres StateStore.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::auth_middleware::AuthContext;
+use crate::state_store::StateStore;
+use crate::validation::safe_unix_timestamp;
+
+/// Request header carrying the client-chosen key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header set when a cached response is replayed
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Default time a response stays replayable
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Maximum accepted key length
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// Request/response bodies larger than this are not buffered or cached
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Default capacity of [`MemoryIdempotencyStore`]
+const DEFAULT_MEMORY_CAPACITY: usize = 10_000;
+
+/// A response recorded for an idempotency key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CachedResponse {
+    /// SHA-256 of method, path and request body
+    pub fingerprint: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Response body, base64 encoded for JSON transport
+    pub body: String,
+    pub expires_at_epoch_secs: u64,
+}
+
+impl CachedResponse {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at_epoch_secs <= now
+    }
+
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let body = BASE64.decode(self.body.as_bytes()).unwrap_or_default();
+
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = status;
+        if let Some(content_type) = self
+            .content_type
+            .and_then(|ct| HeaderValue::from_str(&ct).ok())
+        {
+            response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        }
+        response
+            .headers_mut()
+            .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+        response
+    }
+}
+
+/// Storage backend for cached responses
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>>;
+    async fn put(&self, key: &str, response: &CachedResponse) -> Result<()>;
+}
+
+/// Bounded in-process store; when full, the entry closest to expiry is evicted
+pub struct MemoryIdempotencyStore {
+    entries: RwLock<HashMap<String, CachedResponse>>,
+    capacity: usize,
+}
+
+impl MemoryIdempotencyStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub async fn len(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.entries.read().await.is_empty()
+    }
+}
+
+impl Default for MemoryIdempotencyStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_CAPACITY)
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let now = safe_unix_timestamp();
+        let entries = self.entries.read().await;
+        Ok(entries.get(key).filter(|r| !r.is_expired(now)).cloned())
+    }
+
+    async fn put(&self, key: &str, response: &CachedResponse) -> Result<()> {
+        let now = safe_unix_timestamp();
+        let mut entries = self.entries.write().await;
+
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            entries.retain(|_, r| !r.is_expired(now));
+        }
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, r)| r.expires_at_epoch_secs)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key.to_string(), response.clone());
+        Ok(())
+    }
+}
+
+/// Adapter persisting cached responses through a [`StateStore`]
+pub struct StateStoreIdempotencyStore {
+    store: Arc<dyn StateStore>,
+}
+
+impl StateStoreIdempotencyStore {
+    pub fn new(store: Arc<dyn StateStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for StateStoreIdempotencyStore {
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let now = safe_unix_timestamp();
+        Ok(self
+            .store
+            .get_idempotency_record(key)
+            .await?
+            .filter(|r| !r.is_expired(now)))
+    }
+
+    async fn put(&self, key: &str, response: &CachedResponse) -> Result<()> {
+        self.store.save_idempotency_record(key, response).await
+    }
+}
+
+/// Middleware state: the backing store plus keys currently being executed
+#[derive(Clone)]
+pub struct Idempotency {
+    store: Arc<dyn IdempotencyStore>,
+    ttl: Duration,
+    in_flight: Arc<RwLock<HashSet<String>>>,
+}
+
+impl Idempotency {
+    pub fn new(store: Arc<dyn IdempotencyStore>) -> Self {
+        Self {
+            store,
+            ttl: DEFAULT_IDEMPOTENCY_TTL,
+            in_flight: Arc::new(RwLock::new(HashSet::new())),
+        }
+    }
+
+    /// In-memory store with default capacity
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(MemoryIdempotencyStore::default()))
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+fn validate_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH
+        && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn fingerprint(method: &Method, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str().as_bytes());
+    hasher.update([0]);
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+/// Scope for keys: the tenant when auth middleware ran first, otherwise a
+/// digest of the Authorization header so callers never share a key space.
+fn principal(req: &Request) -> String {
+    if let Some(ctx) = req.extensions().get::<AuthContext>() {
+        return ctx.tenant_id.clone();
+    }
+    match req.headers().get(header::AUTHORIZATION) {
+        Some(value) => hex::encode(Sha256::digest(value.as_bytes())),
+        None => "-".to_string(),
+    }
+}
+
+/// Idempotency middleware; mount with
+/// `axum::middleware::from_fn_with_state(Idempotency::in_memory(), idempotency_middleware)`.
+///
+/// Requests without the header, and non-mutating methods, pass through
+/// untouched. Keys are scoped per caller (see `principal`).
+/// 5xx responses are not cached so the client can retry them.
+pub async fn idempotency_middleware(
+    State(idempotency): State<Idempotency>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !is_mutating(req.method()) {
+        return next.run(req).await;
+    }
+    let Some(raw_key) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(req).await;
+    };
+    let key = match raw_key.to_str() {
+        Ok(key) if validate_key(key) => key.to_string(),
+        _ => {
+            return error_response(StatusCode::BAD_REQUEST, "Invalid Idempotency-Key header");
+        }
+    };
+
+    let scoped_key = format!("{}:{}", principal(&req), key);
+
+    let (parts, body) = req.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large"),
+    };
+    let request_fingerprint = fingerprint(&parts.method, parts.uri.path(), &body);
+
+    match idempotency.store.get(&scoped_key).await {
+        Ok(Some(cached)) => {
+            if cached.fingerprint != request_fingerprint {
+                return error_response(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "Idempotency-Key was already used for a different request",
+                );
+            }
+            return cached.into_response();
+        }
+        Ok(None) => {}
+        Err(e) => warn!(error = %e, "idempotency store lookup failed; executing request"),
+    }
+
+    if !idempotency.in_flight.write().await.insert(scoped_key.clone()) {
+        return error_response(
+            StatusCode::CONFLICT,
+            "A request with this Idempotency-Key is already in progress",
+        );
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let response = record_response(&idempotency, &scoped_key, request_fingerprint, response).await;
+
+    idempotency.in_flight.write().await.remove(&scoped_key);
+    response
+}
+
+async fn record_response(
+    idempotency: &Idempotency,
+    key: &str,
+    request_fingerprint: String,
+    response: Response,
+) -> Response {
+    if response.status().is_server_error() {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "failed to buffer response for idempotency cache");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response");
+        }
+    };
+
+    let cached = CachedResponse {
+        fingerprint: request_fingerprint,
+        status: parts.status.as_u16(),
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        body: BASE64.encode(&body),
+        expires_at_epoch_secs: safe_unix_timestamp().saturating_add(idempotency.ttl.as_secs()),
+    };
+    if let Err(e) = idempotency.store.put(key, &cached).await {
+        warn!(error = %e, "failed to store idempotent response");
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    fn app(counter: Arc<AtomicUsize>) -> Router {
+        Router::new()
+            .route(
+                "/v1/things",
+                post(move |body: String| {
+                    let counter = counter.clone();
+                    async move {
+                        let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                        Json(serde_json::json!({ "n": n, "body": body }))
+                    }
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                Idempotency::in_memory(),
+                idempotency_middleware,
+            ))
+    }
+
+    fn request(key: Option<&str>, body: &str) -> Request {
+        let mut builder = Request::builder().method("POST").uri("/v1/things");
+        if let Some(key) = key {
+            builder = builder.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn body_json(resp: Response) -> serde_json::Value {
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_replays_cached_response() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(counter.clone());
+
+        let first = app.clone().oneshot(request(Some("abc"), "x")).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        assert!(first.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+        let first = body_json(first).await;
+
+        let second = app.oneshot(request(Some("abc"), "x")).await.unwrap();
+        assert_eq!(
+            second.headers().get(IDEMPOTENT_REPLAYED_HEADER).unwrap(),
+            "true"
+        );
+        assert_eq!(body_json(second).await, first);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_key_reuse_with_different_body_rejected() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(counter.clone());
+
+        app.clone().oneshot(request(Some("abc"), "x")).await.unwrap();
+        let resp = app.oneshot(request(Some("abc"), "y")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_key_pass_through() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(counter.clone());
+
+        app.clone().oneshot(request(None, "x")).await.unwrap();
+        app.oneshot(request(None, "x")).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key_rejected() {
+        let app = app(Arc::new(AtomicUsize::new(0)));
+        let resp = app.oneshot(request(Some("has space"), "x")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_is_bounded() {
+        let store = MemoryIdempotencyStore::new(2);
+        let now = safe_unix_timestamp();
+        for (i, key) in ["a", "b", "c"].iter().enumerate() {
+            let response = CachedResponse {
+                fingerprint: String::new(),
+                status: 200,
+                content_type: None,
+                body: String::new(),
+                expires_at_epoch_secs: now + 100 + i as u64,
+            };
+            store.put(key, &response).await.unwrap();
+        }
+        assert_eq!(store.len().await, 2);
+        assert!(store.get("a").await.unwrap().is_none());
+        assert!(store.get("c").await.unwrap().is_some());
+    }
+}
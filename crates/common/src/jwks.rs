@@ -0,0 +1,238 @@
+//! Access-token verification against auth-service's published keys.
+//!
+//! auth-service signs tokens with RS256 when a private key is configured and
+//! publishes the public half at `/.well-known/jwks.json`. [`JwksVerifier`]
+//! caches that key set, refetches it when a token names an unknown `kid`
+//! (key rotation) and falls back to the shared HS256 `JWT_SECRET` for tokens
+//! issued by deployments that have no signing key yet.
+
+use anyhow::{anyhow, bail, Context, Result};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::auth_middleware::AuthClaims;
+
+/// Path of the key set relative to the auth-service base URL
+pub const JWKS_PATH: &str = ".well-known/jwks.json";
+
+/// Minimum spacing between refetches triggered by unknown key ids, so tokens
+/// with made-up `kid`s cannot hammer auth-service
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+
+struct CachedKeys {
+    keys: JwkSet,
+    fetched_at: Option<Instant>,
+}
+
+pub struct JwksVerifier {
+    jwks_url: String,
+    client: reqwest::Client,
+    hs256_secret: Option<String>,
+    max_age: Duration,
+    cache: RwLock<CachedKeys>,
+}
+
+impl JwksVerifier {
+    /// `jwks_url` is the full key set URL; `max_age` bounds how long a fetched
+    /// key set is trusted before it is refreshed.
+    pub fn new(jwks_url: impl Into<String>, max_age: Duration) -> Self {
+        Self {
+            jwks_url: jwks_url.into(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            hs256_secret: None,
+            max_age,
+            cache: RwLock::new(CachedKeys {
+                keys: JwkSet { keys: Vec::new() },
+                fetched_at: None,
+            }),
+        }
+    }
+
+    /// Also accept HS256 tokens signed with the shared secret
+    pub fn with_hs256_secret(mut self, secret: impl Into<String>) -> Self {
+        self.hs256_secret = Some(secret.into());
+        self
+    }
+
+    /// Seed the cache, e.g. in tests or when keys are distributed out of band
+    pub async fn set_keys(&self, keys: JwkSet) {
+        *self.cache.write().await = CachedKeys {
+            keys,
+            fetched_at: Some(Instant::now()),
+        };
+    }
+
+    /// Verify signature and expiry and return the token's claims
+    pub async fn verify(&self, token: &str) -> Result<AuthClaims> {
+        let header = decode_header(token).context("malformed token")?;
+
+        if header.alg == Algorithm::HS256 {
+            let secret = self
+                .hs256_secret
+                .as_deref()
+                .ok_or_else(|| anyhow!("HS256 tokens are not accepted"))?;
+            return decode::<AuthClaims>(
+                token,
+                &DecodingKey::from_secret(secret.as_bytes()),
+                &Validation::new(Algorithm::HS256),
+            )
+            .map(|data| data.claims)
+            .context("invalid token");
+        }
+
+        let kid = header.kid.ok_or_else(|| anyhow!("token has no key id"))?;
+        let jwk = self.key(&kid).await?;
+        check_algorithm(&jwk, header.alg)?;
+        let key = DecodingKey::from_jwk(&jwk).context("unusable signing key")?;
+        decode::<AuthClaims>(token, &key, &Validation::new(header.alg))
+            .map(|data| data.claims)
+            .context("invalid token")
+    }
+
+    async fn key(&self, kid: &str) -> Result<Jwk> {
+        {
+            let cache = self.cache.read().await;
+            let fresh = cache.fetched_at.is_some_and(|at| at.elapsed() < self.max_age);
+            if let Some(jwk) = cache.keys.find(kid) {
+                if fresh {
+                    return Ok(jwk.clone());
+                }
+            } else if cache
+                .fetched_at
+                .is_some_and(|at| at.elapsed() < MIN_REFETCH_INTERVAL)
+            {
+                bail!("unknown signing key '{kid}'");
+            }
+        }
+
+        match self.fetch().await {
+            Ok(keys) => {
+                let found = keys.find(kid).cloned();
+                self.set_keys(keys).await;
+                found.ok_or_else(|| anyhow!("unknown signing key '{kid}'"))
+            }
+            Err(e) => {
+                // Keep serving from a stale cache while auth-service is unreachable
+                warn!(error = %e, "failed to refresh JWKS");
+                self.cache
+                    .read()
+                    .await
+                    .keys
+                    .find(kid)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("unknown signing key '{kid}'"))
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<JwkSet> {
+        debug!(url = %self.jwks_url, "fetching JWKS");
+        let keys = self
+            .client
+            .get(&self.jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await
+            .context("invalid JWKS document")?;
+        Ok(keys)
+    }
+}
+
+/// Only asymmetric keys are accepted from a key set, and the token's `alg`
+/// must match the key's declared algorithm
+fn check_algorithm(jwk: &Jwk, alg: Algorithm) -> Result<()> {
+    if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) {
+        bail!("symmetric keys are not accepted from JWKS");
+    }
+    if let Some(declared) = jwk.common.key_algorithm {
+        if declared.to_string() != format!("{alg:?}") {
+            bail!("token algorithm {alg:?} does not match key algorithm {declared}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    const PRIVATE_KEY: &str = include_str!("../tests/fixtures/jwt_rs256.pem");
+    const JWKS: &str = include_str!("../tests/fixtures/jwt_rs256.jwks.json");
+
+    fn claims() -> AuthClaims {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        AuthClaims {
+            sub: "user-1".into(),
+            tenant_id: "tenant-a".into(),
+            username: "alice".into(),
+            is_system_admin: false,
+            roles: vec!["operator".into()],
+            permissions: vec!["stream:read".into()],
+            exp: now + 300,
+            iat: now,
+        }
+    }
+
+    fn rs256_token(kid: &str) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let key = EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap();
+        encode(&header, &claims(), &key).unwrap()
+    }
+
+    async fn verifier() -> JwksVerifier {
+        // Unroutable URL: every key must come from the seeded cache
+        let verifier = JwksVerifier::new("http://127.0.0.1:9/.well-known/jwks.json", Duration::from_secs(300));
+        verifier.set_keys(serde_json::from_str(JWKS).unwrap()).await;
+        verifier
+    }
+
+    #[tokio::test]
+    async fn verifies_rs256_token_from_key_set() {
+        let claims = verifier().await.verify(&rs256_token("test-key-1")).await.unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.tenant_id, "tenant-a");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_kid_and_tampered_token() {
+        let verifier = verifier().await;
+        assert!(verifier.verify(&rs256_token("other-key")).await.is_err());
+
+        // Flip a character in the middle of the signature
+        let token = rs256_token("test-key-1");
+        let at = token.rfind('.').unwrap() + 10;
+        let flipped = if &token[at..=at] == "A" { "B" } else { "A" };
+        let token = format!("{}{}{}", &token[..at], flipped, &token[at + 1..]);
+        assert!(verifier.verify(&token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn hs256_only_accepted_with_shared_secret() {
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(),
+            &EncodingKey::from_secret(b"shared"),
+        )
+        .unwrap();
+
+        assert!(verifier().await.verify(&token).await.is_err());
+        let verifier = verifier().await.with_hs256_secret("shared");
+        assert_eq!(verifier.verify(&token).await.unwrap().username, "alice");
+    }
+}
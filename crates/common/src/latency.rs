@@ -0,0 +1,99 @@
+//! End-to-end latency of live video.
+//!
+//! Stream nodes write their HLS playlists with `program_date_time`, so every
+//! segment carries the wall-clock time its first frame was ingested. The
+//! time a segment is published (stream node) or delivered (playback-service)
+//! minus that timestamp is how far that point of the pipeline runs behind
+//! the camera. Clocks of the nodes involved are assumed to be in sync (NTP).
+
+/// Ingest time and length of one playlist segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentTiming {
+  /// Segment URI as written in the playlist
+  pub name: String,
+  /// Wall-clock time the segment's first frame was ingested (Unix ms)
+  pub ingest_ms: u64,
+  pub duration_ms: u64,
+}
+
+/// Segments of a playlist that carry ingest timestamps, oldest first.
+/// Segments after a `#EXT-X-PROGRAM-DATE-TIME` tag without their own follow
+/// on from the previous one.
+pub fn parse_segment_timings(playlist: &str) -> Vec<SegmentTiming> {
+  let mut segments = Vec::new();
+  let mut next_ingest: Option<u64> = None;
+  let mut duration_ms = None;
+  for line in playlist.lines().map(str::trim) {
+    if let Some(date) = line.strip_prefix("#EXT-X-PROGRAM-DATE-TIME:") {
+      next_ingest = parse_date_time_ms(date);
+    } else if let Some(rest) = line.strip_prefix("#EXTINF:") {
+      duration_ms = rest
+        .split(',')
+        .next()
+        .and_then(|d| d.parse::<f64>().ok())
+        .map(|d| (d * 1000.0) as u64);
+    } else if !line.is_empty() && !line.starts_with('#') {
+      if let (Some(ingest_ms), Some(duration_ms)) = (next_ingest, duration_ms.take()) {
+        segments.push(SegmentTiming {
+          name: line.to_string(),
+          ingest_ms,
+          duration_ms,
+        });
+        next_ingest = Some(ingest_ms + duration_ms);
+      }
+    }
+  }
+  segments
+}
+
+/// The newest segment of a playlist: the live edge players start from
+pub fn live_edge(playlist: &str) -> Option<SegmentTiming> {
+  parse_segment_timings(playlist).pop()
+}
+
+/// FFmpeg writes `2024-06-12T02:00:00.000+0000`; other packagers use RFC 3339
+fn parse_date_time_ms(date: &str) -> Option<u64> {
+  chrono::DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f%z")
+    .or_else(|_| chrono::DateTime::parse_from_rfc3339(date))
+    .ok()
+    .and_then(|at| u64::try_from(at.timestamp_millis()).ok())
+}
+
+/// Current wall-clock time (Unix ms)
+pub fn now_ms() -> u64 {
+  std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn segments_carry_their_ingest_time() {
+    let playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:7\n\
+      #EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:00.000+0000\n#EXTINF:2.000000,\nsegment_00007.ts\n\
+      #EXTINF:1.960000,\nsegment_00008.ts\n\
+      #EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:04.500Z\n#EXTINF:2.0,\nsegment_00009.ts\n";
+    let segments = parse_segment_timings(playlist);
+    let start = 1_718_157_600_000;
+    assert_eq!(
+      segments,
+      vec![
+        SegmentTiming { name: "segment_00007.ts".into(), ingest_ms: start, duration_ms: 2000 },
+        SegmentTiming { name: "segment_00008.ts".into(), ingest_ms: start + 2000, duration_ms: 1960 },
+        SegmentTiming { name: "segment_00009.ts".into(), ingest_ms: start + 4500, duration_ms: 2000 },
+      ]
+    );
+    assert_eq!(live_edge(playlist).map(|s| s.name), Some("segment_00009.ts".to_string()));
+  }
+
+  #[test]
+  fn playlists_without_timestamps_have_no_live_edge() {
+    let playlist = "#EXTM3U\n#EXTINF:2.0,\nsegment_00000.ts\n#EXTINF:2.0,\nsegment_00001.ts\n";
+    assert!(parse_segment_timings(playlist).is_empty());
+    assert_eq!(live_edge(playlist), None);
+  }
+}
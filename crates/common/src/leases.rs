@@ -111,3 +111,106 @@ pub struct LeaseRenewResponse {
 pub struct LeaseReleaseResponse {
   pub released: bool,
 }
+
+/// Results returned by one lease history query at most
+pub const MAX_HISTORY_LIMIT: usize = 1_000;
+
+/// What happened to a lease
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaseEvent {
+  /// Granted on a resource with no previous holder, or the same one
+  Acquire,
+  Renew,
+  /// Released by its holder
+  Release,
+  /// Not renewed in time and reclaimed
+  Expire,
+  /// Granted to a different holder than the resource's previous one
+  Transfer,
+}
+
+impl LeaseEvent {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      LeaseEvent::Acquire => "acquire",
+      LeaseEvent::Renew => "renew",
+      LeaseEvent::Release => "release",
+      LeaseEvent::Expire => "expire",
+      LeaseEvent::Transfer => "transfer",
+    }
+  }
+}
+
+impl fmt::Display for LeaseEvent {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for LeaseEvent {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "acquire" => Ok(LeaseEvent::Acquire),
+      "renew" => Ok(LeaseEvent::Renew),
+      "release" => Ok(LeaseEvent::Release),
+      "expire" => Ok(LeaseEvent::Expire),
+      "transfer" => Ok(LeaseEvent::Transfer),
+      _ => Err(format!("unknown lease event '{s}'")),
+    }
+  }
+}
+
+/// One append-only record of the lease history
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LeaseHistoryEntry {
+  /// Increases with every record
+  pub sequence: u64,
+  pub occurred_at_epoch_ms: u64,
+  pub event: LeaseEvent,
+  pub lease_id: String,
+  pub resource_id: String,
+  pub holder_id: String,
+  /// Holder the resource was taken over from, on `transfer`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub previous_holder_id: Option<String>,
+  pub kind: LeaseKind,
+  pub expires_at_epoch_secs: u64,
+  pub version: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LeaseHistoryQuery {
+  #[serde(default)]
+  pub resource_id: Option<String>,
+  #[serde(default)]
+  pub holder_id: Option<String>,
+  #[serde(default)]
+  pub event: Option<LeaseEvent>,
+  /// Inclusive lower bound
+  #[serde(default)]
+  pub from_epoch_ms: Option<u64>,
+  /// Exclusive upper bound
+  #[serde(default)]
+  pub to_epoch_ms: Option<u64>,
+  #[serde(default)]
+  pub limit: Option<usize>,
+}
+
+impl LeaseHistoryQuery {
+  pub fn effective_limit(&self) -> usize {
+    self.limit.unwrap_or(MAX_HISTORY_LIMIT).clamp(1, MAX_HISTORY_LIMIT)
+  }
+
+  pub fn matches(&self, entry: &LeaseHistoryEntry) -> bool {
+    self.from_epoch_ms.is_none_or(|from| entry.occurred_at_epoch_ms >= from)
+      && self.to_epoch_ms.is_none_or(|to| entry.occurred_at_epoch_ms < to)
+      && self.resource_id.as_ref().is_none_or(|r| &entry.resource_id == r)
+      && self.holder_id.as_ref().is_none_or(|h| {
+        &entry.holder_id == h || entry.previous_holder_id.as_ref() == Some(h)
+      })
+      && self.event.is_none_or(|e| entry.event == e)
+  }
+}
@@ -1,15 +1,40 @@
 pub mod ai_tasks;
+pub mod api_version;
+pub mod approvals;
+pub mod archive;
 pub mod auth_middleware;
+pub mod bandwidth;
+pub mod config_reload;
+pub mod evidence;
+pub mod federation;
+pub mod ffmpeg_progress;
 pub mod frame_extractor;
+pub mod gateway_identity;
+pub mod idempotency;
+pub mod jwks;
+pub mod latency;
 pub mod leases;
+pub mod motion;
+pub mod mqtt;
+pub mod nodes;
+pub mod openapi;
 pub mod playback;
+pub mod privacy;
+pub mod quota;
+pub mod rate_limit;
 pub mod recordings;
+pub mod resilient_http;
 pub mod retention;
 pub mod search;
+pub mod service_config;
 pub mod state_store;
 pub mod state_store_client;
 pub mod streams;
+pub mod tenancy;
+pub mod tenant_rls;
 pub mod thumbnail;
+pub mod timeline;
+pub mod validated;
 pub mod validation;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,108 @@
+//! Motion-based recording storage.
+//!
+//! Stream nodes with `STREAM_MOTION_DETECTION` enabled run a frame
+//! differencing detector over every HLS segment they produce and publish the
+//! result as [`SegmentActivity`] at `GET /streams/:id/motion`. A recording
+//! started with a [`MotionStoragePolicy`] looks up that activity for each of
+//! its own segments: segments with motion (plus pre/post roll) are kept as
+//! recorded, idle segments are re-encoded at a reduced bitrate or dropped.
+
+use serde::{Deserialize, Serialize};
+
+/// Motion activity of one stream segment, in wall-clock milliseconds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SegmentActivity {
+  /// Segment file name in the stream's HLS directory
+  pub segment: String,
+  pub start_ms: u64,
+  pub end_ms: u64,
+  /// Largest fraction of changed pixels between consecutive sampled frames
+  pub score: f32,
+  pub motion: bool,
+}
+
+/// Body of `GET /streams/:id/motion`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MotionActivityResponse {
+  pub stream_id: String,
+  /// Analysed segments ending after the requested `since_ms`, oldest first
+  pub segments: Vec<SegmentActivity>,
+}
+
+/// What happens to recorded segments without motion
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleSegmentAction {
+  /// Keep idle segments as recorded (activity is still tracked)
+  Keep,
+  /// Re-encode idle segments at `idle_bitrate_kbps`
+  #[default]
+  Reduce,
+  /// Delete idle segments; the playlist marks the gap as a discontinuity
+  Drop,
+}
+
+impl IdleSegmentAction {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      IdleSegmentAction::Keep => "keep",
+      IdleSegmentAction::Reduce => "reduce",
+      IdleSegmentAction::Drop => "drop",
+    }
+  }
+}
+
+/// Storage policy of a motion-only recording; always recorded as HLS
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MotionStoragePolicy {
+  #[serde(default)]
+  pub idle_action: IdleSegmentAction,
+  #[serde(default = "default_idle_bitrate_kbps")]
+  pub idle_bitrate_kbps: u32,
+  /// Idle time kept at full quality before motion starts
+  #[serde(default = "default_roll_secs")]
+  pub pre_roll_secs: u32,
+  /// Idle time kept at full quality after motion ends
+  #[serde(default = "default_roll_secs")]
+  pub post_roll_secs: u32,
+  /// Stream node serving the source stream's activity; defaults to the
+  /// recorder's `MOTION_ACTIVITY_URL`
+  #[serde(default)]
+  pub activity_url: Option<String>,
+}
+
+impl Default for MotionStoragePolicy {
+  fn default() -> Self {
+    Self {
+      idle_action: IdleSegmentAction::default(),
+      idle_bitrate_kbps: default_idle_bitrate_kbps(),
+      pre_roll_secs: default_roll_secs(),
+      post_roll_secs: default_roll_secs(),
+      activity_url: None,
+    }
+  }
+}
+
+fn default_idle_bitrate_kbps() -> u32 {
+  256
+}
+
+fn default_roll_secs() -> u32 {
+  4
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn policy_defaults_reduce_idle_segments() {
+    let policy: MotionStoragePolicy = serde_json::from_str("{}").unwrap();
+    assert_eq!(policy, MotionStoragePolicy::default());
+    assert_eq!(policy.idle_action, IdleSegmentAction::Reduce);
+    assert_eq!(policy.idle_bitrate_kbps, 256);
+
+    let policy: MotionStoragePolicy = serde_json::from_str(r#"{"idle_action": "drop"}"#).unwrap();
+    assert_eq!(policy.idle_action, IdleSegmentAction::Drop);
+  }
+}
@@ -0,0 +1,297 @@
+//! Node registrations.
+//!
+//! Stream, recorder and AI nodes announce their base URL to the coordinator
+//! and keep the registration alive with periodic re-registration. Gateways
+//! read `GET /v1/nodes` to build their routing tables; a node that stops
+//! heartbeating expires after its TTL. Operators can mark a node as draining
+//! (`POST /v1/nodes/drain`) before maintenance so gateways stop sending it
+//! new work. Nodes do the same themselves when shutting down, see
+//! [`NodeAnnouncer::drain`] and [`shutdown_grace`].
+
+use crate::api_version::{self, ApiVersion};
+use crate::resilient_http::ResilientClient;
+use anyhow::{Context, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+  env, fmt,
+  str::FromStr,
+  sync::{Arc, OnceLock},
+  time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+  Stream,
+  Recorder,
+  Ai,
+}
+
+impl NodeKind {
+  pub const ALL: [NodeKind; 3] = [NodeKind::Stream, NodeKind::Recorder, NodeKind::Ai];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      NodeKind::Stream => "stream",
+      NodeKind::Recorder => "recorder",
+      NodeKind::Ai => "ai",
+    }
+  }
+}
+
+impl fmt::Display for NodeKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for NodeKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "stream" => Ok(NodeKind::Stream),
+      "recorder" => Ok(NodeKind::Recorder),
+      "ai" => Ok(NodeKind::Ai),
+      _ => Err(format!("unknown node kind '{s}'")),
+    }
+  }
+}
+
+/// Register or refresh a node; re-sending the same `node_id` acts as a heartbeat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeRegisterRequest {
+  pub node_id: String,
+  pub kind: NodeKind,
+  /// Base URL the node serves its API on, as reachable from the gateways
+  pub base_url: String,
+  #[serde(default = "default_node_ttl_secs")]
+  pub ttl_secs: u64,
+  /// Deployed software version, used by gateways for canary routing
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+}
+
+fn default_node_ttl_secs() -> u64 {
+  30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDeregisterRequest {
+  pub node_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDeregisterResponse {
+  pub removed: bool,
+}
+
+/// Take a node out of (or back into) rotation for new work
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDrainRequest {
+  pub node_id: String,
+  #[serde(default = "default_draining")]
+  pub draining: bool,
+}
+
+fn default_draining() -> bool {
+  true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NodeRecord {
+  pub node_id: String,
+  pub kind: NodeKind,
+  pub base_url: String,
+  pub registered_at_epoch_secs: u64,
+  pub last_seen_epoch_secs: u64,
+  pub expires_at_epoch_secs: u64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  /// Draining nodes keep serving their existing streams and recordings but
+  /// receive no new ones
+  #[serde(default)]
+  pub draining: bool,
+}
+
+impl NodeRecord {
+  pub fn is_expired_at(&self, now_epoch_secs: u64) -> bool {
+    self.expires_at_epoch_secs <= now_epoch_secs
+  }
+}
+
+/// Keeps this process registered with the coordinator
+#[derive(Clone)]
+pub struct NodeAnnouncer {
+  coordinator: Url,
+  registration: NodeRegisterRequest,
+  client: ResilientClient,
+  api_version: Arc<OnceLock<ApiVersion>>,
+}
+
+impl NodeAnnouncer {
+  pub async fn new(coordinator: Url, registration: NodeRegisterRequest) -> Result<Self> {
+    let client = ResilientClient::builder("coordinator").build().await?;
+    Ok(Self {
+      coordinator,
+      registration,
+      client,
+      api_version: Arc::new(OnceLock::new()),
+    })
+  }
+
+  /// Announcer configured from `COORDINATOR_URL`, `NODE_ADVERTISE_URL`,
+  /// `NODE_REGISTRATION_TTL_SECS` and `NODE_VERSION`; `None` when either URL
+  /// is unset
+  pub async fn from_env(kind: NodeKind, node_id: &str) -> Result<Option<Self>> {
+    let (Some(coordinator), Some(advertise)) =
+      (non_empty_var("COORDINATOR_URL"), non_empty_var("NODE_ADVERTISE_URL"))
+    else {
+      return Ok(None);
+    };
+    let coordinator = Url::parse(&coordinator).context("invalid COORDINATOR_URL")?;
+    let base_url = Url::parse(&advertise).context("invalid NODE_ADVERTISE_URL")?;
+    let ttl_secs = env::var("NODE_REGISTRATION_TTL_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or_else(default_node_ttl_secs)
+      .max(3);
+
+    let registration = NodeRegisterRequest {
+      node_id: node_id.to_string(),
+      kind,
+      base_url: base_url.to_string(),
+      ttl_secs,
+      version: non_empty_var("NODE_VERSION"),
+    };
+    Ok(Some(Self::new(coordinator, registration).await?))
+  }
+
+  /// Coordinator URL for `path` under the API version negotiated on first use
+  async fn endpoint(&self, path: &str) -> Result<Url> {
+    let version = match self.api_version.get() {
+      Some(version) => *version,
+      None => {
+        let version = api_version::negotiate(self.client.inner(), &self.coordinator).await?;
+        debug!(%version, "negotiated coordinator API version");
+        *self.api_version.get_or_init(|| version)
+      }
+    };
+    self.coordinator.join(&version.path(path)).context("invalid coordinator endpoint")
+  }
+
+  pub async fn register(&self) -> Result<NodeRecord> {
+    let url = self.endpoint("nodes/register").await?;
+    let resp = self
+      .client
+      .send(|c| c.post(url.clone()).json(&self.registration))
+      .await
+      .context("node registration request failed")?
+      .error_for_status()
+      .context("node registration returned error status")?;
+    resp.json().await.context("failed to parse node registration response")
+  }
+
+  pub async fn deregister(&self) -> Result<()> {
+    let url = self.endpoint("nodes/deregister").await?;
+    let request = NodeDeregisterRequest {
+      node_id: self.registration.node_id.clone(),
+    };
+    self
+      .client
+      .send(|c| c.post(url.clone()).json(&request))
+      .await
+      .context("node deregistration request failed")?
+      .error_for_status()
+      .context("node deregistration returned error status")?;
+    Ok(())
+  }
+
+  /// Take this node out of rotation for new work, ahead of shutting down
+  pub async fn drain(&self) -> Result<()> {
+    let url = self.endpoint("nodes/drain").await?;
+    let request = NodeDrainRequest {
+      node_id: self.registration.node_id.clone(),
+      draining: true,
+    };
+    self
+      .client
+      .send(|c| c.post(url.clone()).json(&request))
+      .await
+      .context("node drain request failed")?
+      .error_for_status()
+      .context("node drain returned error status")?;
+    Ok(())
+  }
+
+  /// Register now and then re-register at a third of the TTL, so a single
+  /// missed heartbeat does not drop the node from routing
+  pub fn spawn(self) -> JoinHandle<()> {
+    let interval = Duration::from_secs((self.registration.ttl_secs / 3).max(1));
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(interval);
+      let mut registered = false;
+      loop {
+        ticker.tick().await;
+        match self.register().await {
+          Ok(record) if !registered => {
+            registered = true;
+            info!(
+              node_id = %record.node_id,
+              kind = %record.kind,
+              base_url = %record.base_url,
+              "registered with coordinator"
+            );
+          }
+          Ok(_) => debug!(node_id = %self.registration.node_id, "node registration refreshed"),
+          Err(e) => {
+            registered = false;
+            warn!(node_id = %self.registration.node_id, error = %e, "node registration failed");
+          }
+        }
+      }
+    })
+  }
+}
+
+/// How long a node may spend finalizing its work after SIGTERM before it
+/// gives up and exits, from `NODE_SHUTDOWN_GRACE_SECS` (default 30)
+pub fn shutdown_grace() -> Duration {
+  Duration::from_secs(
+    env::var("NODE_SHUTDOWN_GRACE_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(30),
+  )
+}
+
+fn non_empty_var(var: &str) -> Option<String> {
+  env::var(var).ok().filter(|v| !v.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn node_kind_round_trips() {
+    for kind in NodeKind::ALL {
+      assert_eq!(kind.as_str().parse::<NodeKind>().unwrap(), kind);
+    }
+    assert_eq!("AI".parse::<NodeKind>().unwrap(), NodeKind::Ai);
+    assert!("pipeline".parse::<NodeKind>().is_err());
+  }
+
+  #[test]
+  fn register_request_defaults_ttl() {
+    let req: NodeRegisterRequest = serde_json::from_str(
+      r#"{"node_id":"rec-1","kind":"recorder","base_url":"http://10.0.0.5:8083/"}"#,
+    )
+    .unwrap();
+    assert_eq!(req.kind, NodeKind::Recorder);
+    assert_eq!(req.ttl_secs, 30);
+  }
+}
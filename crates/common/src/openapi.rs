@@ -0,0 +1,367 @@
+//! OpenAPI 3 document generation shared by all services.
+//!
+//! Each service describes its routes with [`OpenApiSpec`] (using the same
+//! `:param` path syntax as the axum router) and mounts [`openapi_routes`] to
+//! expose `/openapi.json` and a Swagger UI page at `/docs`. The admin-gateway
+//! uses [`merge_documents`] to publish a single aggregated spec.
+
+use axum::{
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// OpenAPI version emitted by [`OpenApiSpec::to_json`]
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// A single documented operation (method + path)
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub tag: String,
+    pub summary: String,
+    pub path_params: Vec<String>,
+    pub has_body: bool,
+}
+
+/// Builder for a service's OpenAPI document
+#[derive(Debug, Clone)]
+pub struct OpenApiSpec {
+    title: String,
+    version: String,
+    description: Option<String>,
+    // path -> method -> operation (BTreeMap keeps the output stable)
+    paths: BTreeMap<String, BTreeMap<String, Operation>>,
+}
+
+impl OpenApiSpec {
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            version: version.into(),
+            description: None,
+            paths: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Document an operation. `path` uses axum syntax (`/v1/devices/:device_id`).
+    pub fn operation(mut self, method: &str, path: &str, tag: &str, summary: &str) -> Self {
+        let method = method.to_ascii_lowercase();
+        let (openapi_path, path_params) = convert_path(path);
+        let has_body = matches!(method.as_str(), "post" | "put" | "patch");
+        self.paths.entry(openapi_path).or_default().insert(
+            method,
+            Operation {
+                tag: tag.to_string(),
+                summary: summary.to_string(),
+                path_params,
+                has_body,
+            },
+        );
+        self
+    }
+
+    /// Document several operations at once: `(method, path, tag, summary)`
+    pub fn operations(self, ops: &[(&str, &str, &str, &str)]) -> Self {
+        ops.iter().fold(self, |spec, (method, path, tag, summary)| {
+            spec.operation(method, path, tag, summary)
+        })
+    }
+
+    /// Number of documented operations
+    pub fn operation_count(&self) -> usize {
+        self.paths.values().map(|methods| methods.len()).sum()
+    }
+
+    /// Render the document as OpenAPI 3 JSON
+    pub fn to_json(&self) -> Value {
+        let mut paths = Map::new();
+        let mut tags: Vec<String> = Vec::new();
+
+        for (path, methods) in &self.paths {
+            let mut item = Map::new();
+            for (method, op) in methods {
+                if !tags.contains(&op.tag) {
+                    tags.push(op.tag.clone());
+                }
+                item.insert(method.clone(), operation_json(method, path, op));
+            }
+            paths.insert(path.clone(), Value::Object(item));
+        }
+
+        tags.sort();
+
+        let mut info = Map::new();
+        info.insert("title".into(), Value::String(self.title.clone()));
+        info.insert("version".into(), Value::String(self.version.clone()));
+        if let Some(description) = &self.description {
+            info.insert("description".into(), Value::String(description.clone()));
+        }
+
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": info,
+            "tags": tags.iter().map(|t| json!({ "name": t })).collect::<Vec<_>>(),
+            "paths": paths,
+            "components": {
+                "schemas": {
+                    "Error": {
+                        "type": "object",
+                        "properties": { "error": { "type": "string" } }
+                    }
+                },
+                "securitySchemes": {
+                    "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" }
+                }
+            }
+        })
+    }
+}
+
+/// Convert an axum path (`/a/:id/*rest`) to OpenAPI syntax (`/a/{id}/{rest}`),
+/// returning the extracted parameter names.
+fn convert_path(path: &str) -> (String, Vec<String>) {
+    let mut params = Vec::new();
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| {
+            if let Some(name) = segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+                params.push(name.to_string());
+                format!("{{{}}}", name)
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect();
+    (segments.join("/"), params)
+}
+
+fn operation_json(method: &str, path: &str, op: &Operation) -> Value {
+    let operation_id = format!(
+        "{}{}",
+        method,
+        path.replace(['/', '{', '}', '-', '.'], "_")
+    );
+
+    let mut value = json!({
+        "tags": [op.tag],
+        "summary": op.summary,
+        "operationId": operation_id,
+        "responses": {
+            "200": { "description": "Success" },
+            "default": {
+                "description": "Error",
+                "content": {
+                    "application/json": { "schema": { "$ref": "#/components/schemas/Error" } }
+                }
+            }
+        }
+    });
+
+    if !op.path_params.is_empty() {
+        value["parameters"] = Value::Array(
+            op.path_params
+                .iter()
+                .map(|name| {
+                    json!({
+                        "name": name,
+                        "in": "path",
+                        "required": true,
+                        "schema": { "type": "string" }
+                    })
+                })
+                .collect(),
+        );
+    }
+
+    if op.has_body {
+        value["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": { "type": "object" } } }
+        });
+    }
+
+    value
+}
+
+/// Merge several service documents into one aggregated document.
+///
+/// Operations keep their original paths; each one is annotated with an
+/// `x-service` extension naming the service it came from. When two services
+/// document the same method + path, the first one wins.
+pub fn merge_documents(title: &str, version: &str, documents: &[(String, Value)]) -> Value {
+    let mut paths = Map::new();
+    let mut tags: Vec<String> = Vec::new();
+
+    for (service, doc) in documents {
+        let Some(service_paths) = doc.get("paths").and_then(Value::as_object) else {
+            continue;
+        };
+
+        for (path, methods) in service_paths {
+            let Some(methods) = methods.as_object() else {
+                continue;
+            };
+            let entry = paths
+                .entry(path.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            let Some(entry) = entry.as_object_mut() else {
+                continue;
+            };
+
+            for (method, op) in methods {
+                if entry.contains_key(method) {
+                    continue;
+                }
+                let mut op = op.clone();
+                if let Some(obj) = op.as_object_mut() {
+                    obj.insert("x-service".into(), Value::String(service.clone()));
+                    if let Some(op_tags) = obj.get("tags").and_then(Value::as_array) {
+                        for tag in op_tags.iter().filter_map(Value::as_str) {
+                            if !tags.iter().any(|t| t == tag) {
+                                tags.push(tag.to_string());
+                            }
+                        }
+                    }
+                }
+                entry.insert(method.clone(), op);
+            }
+        }
+    }
+
+    tags.sort();
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": title,
+            "version": version,
+            "x-services": documents.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>(),
+        },
+        "tags": tags.iter().map(|t| json!({ "name": t })).collect::<Vec<_>>(),
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": { "error": { "type": "string" } }
+                }
+            },
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" }
+            }
+        }
+    })
+}
+
+/// Swagger UI page that renders the document served at `spec_url`
+pub fn swagger_ui_html(title: &str, spec_url: &str) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8" />
+  <title>{title} - API docs</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {{
+      window.ui = SwaggerUIBundle({{ url: "{spec_url}", dom_id: "#swagger-ui" }});
+    }};
+  </script>
+</body>
+</html>"##
+    )
+}
+
+/// Routes serving `/openapi.json` and the Swagger UI at `/docs`
+pub fn openapi_routes<S>(spec: &OpenApiSpec) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let doc = Arc::new(spec.to_json());
+    let html = Arc::new(swagger_ui_html(spec.title(), "/openapi.json"));
+
+    Router::new()
+        .route(
+            "/openapi.json",
+            get(move || {
+                let doc = doc.clone();
+                async move { Json((*doc).clone()) }
+            }),
+        )
+        .route(
+            "/docs",
+            get(move || {
+                let html = html.clone();
+                async move { Html((*html).clone()).into_response() }
+            }),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_path_params() {
+        let (path, params) = convert_path("/v1/devices/:device_id/ptz/presets/:preset_id");
+        assert_eq!(path, "/v1/devices/{device_id}/ptz/presets/{preset_id}");
+        assert_eq!(params, vec!["device_id", "preset_id"]);
+
+        let (path, params) = convert_path("/healthz");
+        assert_eq!(path, "/healthz");
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_spec_to_json() {
+        let spec = OpenApiSpec::new("device-manager", "0.1.0").operations(&[
+            ("GET", "/v1/devices", "devices", "List devices"),
+            ("POST", "/v1/devices", "devices", "Create device"),
+            ("GET", "/v1/devices/:device_id", "devices", "Get device"),
+        ]);
+        assert_eq!(spec.operation_count(), 3);
+
+        let doc = spec.to_json();
+        assert_eq!(doc["openapi"], OPENAPI_VERSION);
+        assert_eq!(doc["info"]["title"], "device-manager");
+        assert!(doc["paths"]["/v1/devices"]["post"]["requestBody"].is_object());
+        assert!(doc["paths"]["/v1/devices"]["get"]["requestBody"].is_null());
+        assert_eq!(
+            doc["paths"]["/v1/devices/{device_id}"]["get"]["parameters"][0]["name"],
+            "device_id"
+        );
+    }
+
+    #[test]
+    fn test_merge_documents() {
+        let a = OpenApiSpec::new("a", "1")
+            .operation("GET", "/healthz", "health", "Health")
+            .operation("GET", "/v1/devices", "devices", "List devices")
+            .to_json();
+        let b = OpenApiSpec::new("b", "1")
+            .operation("GET", "/healthz", "health", "Health")
+            .operation("GET", "/v1/rules", "alerts", "List rules")
+            .to_json();
+
+        let merged = merge_documents("gateway", "1", &[("a".into(), a), ("b".into(), b)]);
+        assert_eq!(merged["paths"]["/healthz"]["get"]["x-service"], "a");
+        assert_eq!(merged["paths"]["/v1/rules"]["get"]["x-service"], "b");
+        assert_eq!(merged["tags"].as_array().map(|t| t.len()), Some(3));
+    }
+}
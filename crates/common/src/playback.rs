@@ -87,6 +87,13 @@ pub struct PlaybackInfo {
     /// DVR window information (only for DVR-enabled sessions)
     #[serde(default)]
     pub dvr_window: Option<DvrWindowInfo>,
+    /// Protocol negotiated for the session, when the client asked for
+    /// fallback instead of a fixed protocol
+    #[serde(default)]
+    pub delivery: Option<DeliveryRecord>,
+    /// Latest delivery latency of a live session; measured, not persisted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<SessionLatency>,
 }
 
 /// Request to start a playback session
@@ -95,6 +102,10 @@ pub struct PlaybackStartRequest {
     pub config: PlaybackConfig,
     #[serde(default)]
     pub lease_ttl_secs: Option<u64>,
+    /// Negotiate the protocol (WebRTC, then LL-HLS, then HLS) from what the
+    /// client reports instead of using `config.protocol`
+    #[serde(default)]
+    pub negotiate: Option<ClientReport>,
 }
 
 /// Response for playback start
@@ -105,6 +116,9 @@ pub struct PlaybackStartResponse {
     pub lease_id: Option<String>,
     pub playback_url: Option<String>,
     pub message: Option<String>,
+    /// Protocol served and the ones skipped, for negotiated sessions
+    #[serde(default)]
+    pub delivery: Option<DeliveryRecord>,
 }
 
 /// Request to stop a playback session
@@ -300,3 +314,473 @@ pub struct TimeAxisPreviewResponse {
     /// List of thumbnails evenly spaced along the timeline
     pub thumbnails: Vec<TimeAxisThumbnail>,
 }
+
+/// Longest clip a single export may cover
+pub const MAX_CLIP_SECS: f64 = 3600.0;
+
+/// Time range of a recording to export, in seconds from its start
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClipExportQuery {
+    pub start_secs: f64,
+    pub end_secs: f64,
+}
+
+impl ClipExportQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.start_secs.is_finite() || !self.end_secs.is_finite() || self.start_secs < 0.0 {
+            return Err("start_secs and end_secs must be non-negative numbers".to_string());
+        }
+        if self.end_secs <= self.start_secs {
+            return Err("end_secs must be after start_secs".to_string());
+        }
+        if self.end_secs - self.start_secs > MAX_CLIP_SECS {
+            return Err(format!("clips are limited to {} seconds", MAX_CLIP_SECS));
+        }
+        Ok(())
+    }
+}
+
+/// Most classes one overlay export may select
+pub const MAX_OVERLAY_CLASSES: usize = 32;
+
+/// AI detection overlays to burn into an exported clip. Query string of the
+/// clip export next to [`ClipExportQuery`], e.g.
+/// `?start_secs=10&end_secs=40&overlays=true&classes=person,car`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ClipOverlayQuery {
+    /// Draw the stored detections of the range onto the clip
+    #[serde(default)]
+    pub overlays: bool,
+    /// Comma-separated classes to draw; every class when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classes: Option<String>,
+    /// Boxes below this confidence are left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f32>,
+    /// Draw the class and confidence above each box (default true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<bool>,
+    /// How long a box stays up after the frame it was detected in; defaults
+    /// to the recorder's frame capture interval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold_secs: Option<f64>,
+}
+
+impl ClipOverlayQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .min_confidence
+            .is_some_and(|c| !(0.0..=1.0).contains(&c))
+        {
+            return Err("min_confidence must be between 0 and 1".to_string());
+        }
+        if self
+            .hold_secs
+            .is_some_and(|h| !h.is_finite() || !(0.1..=10.0).contains(&h))
+        {
+            return Err("hold_secs must be between 0.1 and 10".to_string());
+        }
+        if self.class_list().len() > MAX_OVERLAY_CLASSES {
+            return Err(format!("at most {} classes may be selected", MAX_OVERLAY_CLASSES));
+        }
+        Ok(())
+    }
+
+    /// Selected classes, lowercased as the search index stores them
+    pub fn class_list(&self) -> Vec<String> {
+        class_list(self.classes.as_deref())
+    }
+
+    pub fn show_labels(&self) -> bool {
+        self.labels.unwrap_or(true)
+    }
+}
+
+fn class_list(classes: Option<&str>) -> Vec<String> {
+    classes
+        .iter()
+        .flat_map(|c| c.split(','))
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+/// Most HLS segments one overlay window may cover
+pub const MAX_OVERLAY_SEGMENTS: u32 = 30;
+
+/// Query string of a playback session's overlay lookup, e.g.
+/// `?position_secs=42.7&segments=3&classes=person,car`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PlaybackOverlayQuery {
+    /// Where the player is, in seconds from the start of the recording;
+    /// estimated from the session's controls when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_secs: Option<f64>,
+    /// HLS segments to cover, starting with the one playing (default 3)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segments: Option<u32>,
+    /// Comma-separated classes to return; every class when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classes: Option<String>,
+    /// Boxes below this confidence are left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f32>,
+}
+
+impl PlaybackOverlayQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .position_secs
+            .is_some_and(|p| !p.is_finite() || p < 0.0)
+        {
+            return Err("position_secs must be a non-negative number".to_string());
+        }
+        if self
+            .segments
+            .is_some_and(|s| !(1..=MAX_OVERLAY_SEGMENTS).contains(&s))
+        {
+            return Err(format!("segments must be between 1 and {}", MAX_OVERLAY_SEGMENTS));
+        }
+        if self
+            .min_confidence
+            .is_some_and(|c| !(0.0..=1.0).contains(&c))
+        {
+            return Err("min_confidence must be between 0 and 1".to_string());
+        }
+        if self.class_list().len() > MAX_OVERLAY_CLASSES {
+            return Err(format!("at most {} classes may be selected", MAX_OVERLAY_CLASSES));
+        }
+        Ok(())
+    }
+
+    /// Selected classes, lowercased as the search index stores them
+    pub fn class_list(&self) -> Vec<String> {
+        class_list(self.classes.as_deref())
+    }
+}
+
+/// Stored detections of the HLS segments around a playback session's
+/// position; body of `GET /v1/playback/sessions/:id/overlays` and of each
+/// `overlays` event of its stream
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlaybackOverlayWindow {
+    pub session_id: String,
+    pub recording_id: String,
+    /// Position the window was picked for
+    pub position_secs: f64,
+    /// Window covered, on segment boundaries, in seconds from the start of
+    /// the recording
+    pub from_secs: f64,
+    pub to_secs: f64,
+    pub segment_secs: f64,
+    /// Unix seconds the recording started at, to map offsets to wall time
+    pub recording_started_at: i64,
+    /// Oldest first; boxes are in pixels of the analysed frame
+    pub detections: Vec<crate::search::RecordingDetection>,
+    /// More detections fell in the window than were returned
+    pub truncated: bool,
+}
+
+// === Recording Access Log ===
+
+/// What was done with a recording
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingAccessAction {
+    /// Watched through a playback session
+    View,
+    /// Recording files or a download link handed out
+    Download,
+    /// Copy of the footage made for use outside the system
+    Export,
+}
+
+impl RecordingAccessAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordingAccessAction::View => "view",
+            RecordingAccessAction::Download => "download",
+            RecordingAccessAction::Export => "export",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "view" => Some(RecordingAccessAction::View),
+            "download" => Some(RecordingAccessAction::Download),
+            "export" => Some(RecordingAccessAction::Export),
+            _ => None,
+        }
+    }
+}
+
+/// Range of a recording, in seconds from its start
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ViewedRange {
+    pub from_secs: f64,
+    pub to_secs: f64,
+}
+
+/// One access to a recording: who, when, which part and how
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordingAccessEntry {
+    pub id: String,
+    pub recording_id: String,
+    pub action: RecordingAccessAction,
+    /// `None` when the request carried no identity
+    pub tenant_id: Option<String>,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    /// Playback session of a view
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub protocol: Option<PlaybackProtocol>,
+    /// Requested range; for a finished view, the extent of what was watched
+    #[serde(default)]
+    pub from_secs: Option<f64>,
+    #[serde(default)]
+    pub to_secs: Option<f64>,
+    /// Ranges actually played, once a view has ended
+    #[serde(default)]
+    pub viewed_ranges: Vec<ViewedRange>,
+    /// Service-specific details, e.g. the kind of export
+    #[serde(default)]
+    pub detail: serde_json::Value,
+    /// Unix seconds
+    pub accessed_at: u64,
+    #[serde(default)]
+    pub ended_at: Option<u64>,
+}
+
+/// Body of `POST /v1/recordings/:id/access-log`, for services that hand out
+/// footage outside a playback session. The user is taken from the request's
+/// identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingAccessReport {
+    pub action: RecordingAccessAction,
+    #[serde(default)]
+    pub from_secs: Option<f64>,
+    #[serde(default)]
+    pub to_secs: Option<f64>,
+    #[serde(default)]
+    pub detail: serde_json::Value,
+}
+
+/// Body of `GET /v1/recordings/:id/access-log`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingAccessLogResponse {
+    pub recording_id: String,
+    /// Newest first
+    pub entries: Vec<RecordingAccessEntry>,
+    pub total: i64,
+}
+
+// === Protocol Fallback ===
+
+/// How a negotiated session is delivered, most to least preferred
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryProtocol {
+    /// WHEP
+    WebRtc,
+    /// Low-Latency HLS, live streams only
+    LlHls,
+    Hls,
+}
+
+impl DeliveryProtocol {
+    /// Order in which protocols are attempted
+    pub const FALLBACK_ORDER: [DeliveryProtocol; 3] =
+        [DeliveryProtocol::WebRtc, DeliveryProtocol::LlHls, DeliveryProtocol::Hls];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryProtocol::WebRtc => "webrtc",
+            DeliveryProtocol::LlHls => "ll_hls",
+            DeliveryProtocol::Hls => "hls",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "webrtc" => Some(DeliveryProtocol::WebRtc),
+            "ll_hls" => Some(DeliveryProtocol::LlHls),
+            "hls" => Some(DeliveryProtocol::Hls),
+            _ => None,
+        }
+    }
+
+    /// Session protocol and low-latency flag serving this delivery
+    pub fn session_protocol(&self) -> (PlaybackProtocol, bool) {
+        match self {
+            DeliveryProtocol::WebRtc => (PlaybackProtocol::WebRtc, false),
+            DeliveryProtocol::LlHls => (PlaybackProtocol::Hls, true),
+            DeliveryProtocol::Hls => (PlaybackProtocol::Hls, false),
+        }
+    }
+}
+
+/// Network conditions measured by the client; unknown values are not held
+/// against any protocol
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct NetworkConditions {
+    /// Round-trip time to the playback service
+    #[serde(default)]
+    pub rtt_ms: Option<u32>,
+    /// Recent packet loss, 0-100
+    #[serde(default)]
+    pub packet_loss_pct: Option<f64>,
+    /// Estimated downlink bandwidth
+    #[serde(default)]
+    pub downlink_kbps: Option<u32>,
+    /// UDP is blocked on the client's network, so ICE cannot connect
+    #[serde(default)]
+    pub udp_blocked: bool,
+}
+
+/// What a client can play and how its network looks, sent with a start
+/// request to negotiate the protocol
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClientReport {
+    /// Protocols the client can play; all of them when omitted
+    #[serde(default = "all_delivery_protocols")]
+    pub supports: Vec<DeliveryProtocol>,
+    #[serde(default)]
+    pub network: NetworkConditions,
+}
+
+impl Default for ClientReport {
+    fn default() -> Self {
+        Self {
+            supports: all_delivery_protocols(),
+            network: NetworkConditions::default(),
+        }
+    }
+}
+
+fn all_delivery_protocols() -> Vec<DeliveryProtocol> {
+    DeliveryProtocol::FALLBACK_ORDER.to_vec()
+}
+
+/// A protocol passed over during negotiation or after a failed attempt
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SkippedDelivery {
+    pub protocol: DeliveryProtocol,
+    pub reason: String,
+    /// Reported by the client after trying it, rather than ruled out up front
+    #[serde(default)]
+    pub attempted: bool,
+}
+
+/// Which protocol a negotiated session is served with and why the
+/// preferred ones were not
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeliveryRecord {
+    pub served: DeliveryProtocol,
+    /// In fallback order
+    #[serde(default)]
+    pub skipped: Vec<SkippedDelivery>,
+    /// What the client reported when the session started
+    #[serde(default)]
+    pub client: ClientReport,
+}
+
+/// Body of `POST /v1/playback/fallback`: the served protocol failed on the
+/// client, move the session to the next one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackFallbackRequest {
+    pub session_id: String,
+    /// Why the protocol failed, e.g. "ice connection failed"
+    pub reason: String,
+    /// Conditions measured since the session started
+    #[serde(default)]
+    pub network: Option<NetworkConditions>,
+}
+
+/// Response to `POST /v1/playback/fallback`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackFallbackResponse {
+    pub session_id: String,
+    pub playback_url: Option<String>,
+    pub delivery: DeliveryRecord,
+}
+
+// === Delivery Latency ===
+
+/// Latest latency measurement of a live session (see `common::latency`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionLatency {
+    pub session_id: String,
+    /// Stream (or, for WebRTC, the WHEP resource) served
+    pub source_id: String,
+    pub protocol: DeliveryProtocol,
+    /// How far the viewer is estimated to run behind the camera: the segment
+    /// age for HLS, the round trip time for WebRTC
+    pub latency_ms: u64,
+    /// HLS: time since the first frame of the newest segment was ingested
+    #[serde(default)]
+    pub segment_age_ms: Option<u64>,
+    /// WebRTC: ICE round trip time to the viewer
+    #[serde(default)]
+    pub rtt_ms: Option<u64>,
+    /// Budget of the session's protocol; none configured means no alerts
+    #[serde(default)]
+    pub budget_ms: Option<u64>,
+    #[serde(default)]
+    pub over_budget: bool,
+    /// Unix seconds
+    pub measured_at: u64,
+}
+
+// === WebRTC Session Stats ===
+
+/// Quality of experience of one WHEP session, measured while it plays and
+/// kept after it closes (`GET /v1/playback/sessions/:id/stats`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct WebRtcSessionStats {
+    pub session_id: String,
+    /// WHEP resource the session plays
+    pub resource_id: String,
+    /// Unix milliseconds
+    pub started_at: u64,
+    /// Unix milliseconds; none while the session is live
+    #[serde(default)]
+    pub ended_at: Option<u64>,
+    /// ICE candidate types of the nominated pair: "host", "srflx", "prflx"
+    /// or "relay"
+    #[serde(default)]
+    pub local_candidate_type: Option<String>,
+    #[serde(default)]
+    pub remote_candidate_type: Option<String>,
+    /// Latest ICE round trip time to the viewer
+    #[serde(default)]
+    pub rtt_ms: Option<u64>,
+    #[serde(default)]
+    pub avg_rtt_ms: Option<u64>,
+    #[serde(default)]
+    pub max_rtt_ms: Option<u64>,
+    pub bytes_sent: u64,
+    /// Send rate over the latest sampling interval
+    #[serde(default)]
+    pub bitrate_kbps: Option<f64>,
+    /// Send rate over the whole session
+    #[serde(default)]
+    pub avg_bitrate_kbps: Option<f64>,
+    /// Packets the viewer reported lost, over all tracks
+    pub packets_lost: u64,
+    /// Latest fraction lost reported by the viewer, 0.0 to 1.0
+    #[serde(default)]
+    pub fraction_lost: Option<f64>,
+    #[serde(default)]
+    pub max_fraction_lost: Option<f64>,
+    /// Latest interarrival jitter reported by the viewer
+    #[serde(default)]
+    pub jitter_ms: Option<f64>,
+    #[serde(default)]
+    pub max_jitter_ms: Option<f64>,
+    /// Transport samples taken
+    pub samples: u64,
+    /// RTCP receiver reports received
+    pub reports: u64,
+}
@@ -0,0 +1,232 @@
+//! Data subject export and erasure (GDPR articles 15 and 17).
+//!
+//! auth-service coordinates both workflows. It resolves the person to a
+//! [`DataSubject`] (their user account plus any face enrollments, names or
+//! plates the operator names), then calls every service holding personal
+//! data at [`EXPORT_PATH`] or [`ERASE_PATH`]. Services answer with a
+//! [`ServiceExport`] or an [`ErasureOutcome`]; auth-service collects the
+//! outcomes into an [`ErasureReport`] and signs it.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Relative path every service answers data subject exports on
+pub const EXPORT_PATH: &str = "v1/privacy/export";
+
+/// Relative path every service answers data subject erasures on
+pub const ERASE_PATH: &str = "v1/privacy/erase";
+
+pub const EXPORT_PERMISSION: &str = "privacy:export";
+pub const ERASE_PERMISSION: &str = "privacy:erase";
+
+/// The person a request is about, by every identifier services key their
+/// data on. Services match the identifiers they know and ignore the rest.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataSubject {
+  pub tenant_id: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub user_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub email: Option<String>,
+  /// Face enrollment ids in facial recognition
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub face_ids: Vec<String>,
+  /// Names the person is enrolled or referenced under
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub names: Vec<String>,
+  /// Licence plates registered to the person
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub plates: Vec<String>,
+}
+
+impl DataSubject {
+  pub fn validate(&self) -> Result<()> {
+    crate::validation::validate_id(&self.tenant_id, "tenant_id")?;
+    let identifiers = self.user_id.iter().chain(&self.email).chain(&self.face_ids).chain(&self.names).chain(&self.plates);
+    let mut any = false;
+    for identifier in identifiers {
+      if identifier.trim().is_empty() || identifier.len() > 255 {
+        bail!("subject identifiers must be 1-255 characters");
+      }
+      any = true;
+    }
+    if !any {
+      bail!("subject needs at least one identifier");
+    }
+    Ok(())
+  }
+
+  /// Whether `name` is one of the subject's names, ignoring case and
+  /// surrounding whitespace
+  pub fn has_name(&self, name: &str) -> bool {
+    let name = name.trim();
+    self.names.iter().any(|n| n.trim().eq_ignore_ascii_case(name))
+  }
+
+  /// Whether `plate` is one of the subject's plates, ignoring case, spaces
+  /// and dashes
+  pub fn has_plate(&self, plate: &str) -> bool {
+    let plate = normalize_plate(plate);
+    self.plates.iter().any(|p| normalize_plate(p) == plate)
+  }
+
+  /// Stable digest of the identifiers, so reports can name the subject
+  /// without repeating the personal data that was erased
+  pub fn fingerprint(&self) -> String {
+    let canonical = serde_json::to_vec(self).unwrap_or_default();
+    hex::encode(Sha256::digest(&canonical))
+  }
+}
+
+/// Plate reduced to upper-case letters and digits, as [`DataSubject::has_plate`]
+/// compares them
+pub fn normalize_plate(plate: &str) -> String {
+  plate
+    .chars()
+    .filter(|c| c.is_ascii_alphanumeric())
+    .map(|c| c.to_ascii_uppercase())
+    .collect()
+}
+
+/// Everything one service holds about a subject, by category
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServiceExport {
+  pub records: BTreeMap<String, Vec<serde_json::Value>>,
+}
+
+impl ServiceExport {
+  pub fn add<T: Serialize>(&mut self, category: &str, items: &[T]) -> Result<()> {
+    let values = items
+      .iter()
+      .map(serde_json::to_value)
+      .collect::<Result<Vec<_>, _>>()
+      .with_context(|| format!("failed to serialize {category}"))?;
+    self.records.entry(category.to_string()).or_default().extend(values);
+    Ok(())
+  }
+}
+
+/// Records one service deleted or anonymized, by category
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureOutcome {
+  #[serde(default)]
+  pub deleted: BTreeMap<String, u64>,
+  #[serde(default)]
+  pub anonymized: BTreeMap<String, u64>,
+}
+
+impl ErasureOutcome {
+  pub fn deleted(mut self, category: &str, count: u64) -> Self {
+    *self.deleted.entry(category.to_string()).or_default() += count;
+    self
+  }
+
+  pub fn anonymized(mut self, category: &str, count: u64) -> Self {
+    *self.anonymized.entry(category.to_string()).or_default() += count;
+    self
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureStatus {
+  Completed,
+  Failed,
+  /// The service is not deployed, so it holds nothing to erase
+  NotConfigured,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceErasure {
+  pub service: String,
+  pub status: ErasureStatus,
+  #[serde(flatten)]
+  pub outcome: ErasureOutcome,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+/// Completion report of an erasure request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureReport {
+  pub request_id: String,
+  pub tenant_id: String,
+  /// [`DataSubject::fingerprint`] of the erased subject
+  pub subject_fingerprint: String,
+  pub requested_by: String,
+  pub requested_at_epoch_secs: u64,
+  pub completed_at_epoch_secs: u64,
+  pub services: Vec<ServiceErasure>,
+}
+
+impl ErasureReport {
+  /// Every deployed service erased its data
+  pub fn is_complete(&self) -> bool {
+    self.services.iter().all(|s| s.status != ErasureStatus::Failed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn subject() -> DataSubject {
+    DataSubject {
+      tenant_id: "acme".to_string(),
+      email: Some("jane@example.com".to_string()),
+      names: vec!["Jane Doe".to_string()],
+      plates: vec!["AB-123 CD".to_string()],
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn subject_needs_an_identifier() {
+    assert!(subject().validate().is_ok());
+    let empty = DataSubject {
+      tenant_id: "acme".to_string(),
+      ..Default::default()
+    };
+    assert!(empty.validate().is_err());
+    let blank = DataSubject {
+      names: vec![" ".to_string()],
+      ..empty
+    };
+    assert!(blank.validate().is_err());
+  }
+
+  #[test]
+  fn names_and_plates_match_loosely() {
+    let subject = subject();
+    assert!(subject.has_name(" jane doe"));
+    assert!(!subject.has_name("Jane"));
+    assert!(subject.has_plate("ab123cd"));
+    assert!(!subject.has_plate("AB123"));
+    assert_eq!(subject.fingerprint(), subject.clone().fingerprint());
+    assert_ne!(subject.fingerprint(), DataSubject::default().fingerprint());
+  }
+
+  #[test]
+  fn report_is_incomplete_when_a_service_failed() {
+    let service = |status| ServiceErasure {
+      service: "ai-service".to_string(),
+      status,
+      outcome: ErasureOutcome::default().deleted("face_enrollments", 1),
+      error: None,
+    };
+    let mut report = ErasureReport {
+      request_id: "r1".to_string(),
+      tenant_id: "acme".to_string(),
+      subject_fingerprint: subject().fingerprint(),
+      requested_by: "admin".to_string(),
+      requested_at_epoch_secs: 1,
+      completed_at_epoch_secs: 2,
+      services: vec![service(ErasureStatus::Completed), service(ErasureStatus::NotConfigured)],
+    };
+    assert!(report.is_complete());
+    report.services.push(service(ErasureStatus::Failed));
+    assert!(!report.is_complete());
+  }
+}
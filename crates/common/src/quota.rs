@@ -0,0 +1,453 @@
+//! Monthly per-tenant API quotas.
+//!
+//! Every request that passes [`quota_middleware`] is counted against the
+//! caller's tenant for the current calendar month (UTC). Once a tenant has
+//! used its allowance, further requests get 429 with a `Retry-After` pointing
+//! at the start of the next month. Counters are kept in a [`UsageStore`]:
+//! [`MemoryUsageStore`] for single-instance deployments, or
+//! [`StateStoreUsageStore`] to share them across replicas through the
+//! coordinator's Postgres StateStore. The same counters back usage reporting
+//! for billing.
+//!
+//! Requests without an [`AuthContext`] (auth middleware not mounted) are not
+//! counted.
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::auth_middleware::AuthContext;
+use crate::state_store::StateStore;
+use crate::validation::safe_unix_timestamp;
+
+/// Request counters of one tenant for one billing period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TenantUsage {
+    pub tenant_id: String,
+    /// Calendar month, `YYYY-MM` (UTC)
+    pub period: String,
+    /// Requests admitted within the quota
+    pub requests: u64,
+    /// Requests rejected because the quota was exhausted
+    pub rejected: u64,
+}
+
+/// Counter change sent to the StateStore API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageDelta {
+    pub tenant_id: String,
+    pub period: String,
+    pub requests: i64,
+    pub rejected: i64,
+}
+
+/// Billing period containing `epoch_secs`, formatted `YYYY-MM`
+pub fn period_at(epoch_secs: u64) -> String {
+    let (year, month) = year_month(epoch_secs);
+    format!("{year:04}-{month:02}")
+}
+
+/// Period of the current time
+pub fn current_period() -> String {
+    period_at(safe_unix_timestamp())
+}
+
+/// Check that `period` is a `YYYY-MM` string
+pub fn validate_period(period: &str) -> Result<()> {
+    let valid = match period.split_once('-') {
+        Some((year, month)) => {
+            year.len() == 4
+                && month.len() == 2
+                && year.bytes().chain(month.bytes()).all(|b| b.is_ascii_digit())
+                && matches!(month.parse::<u32>(), Ok(1..=12))
+        }
+        None => false,
+    };
+    if !valid {
+        bail!("period must be formatted YYYY-MM");
+    }
+    Ok(())
+}
+
+/// Seconds from `epoch_secs` until the next period starts
+pub fn secs_until_next_period(epoch_secs: u64) -> u64 {
+    let (year, month) = year_month(epoch_secs);
+    let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    let next = days_from_civil(year, month) * 86_400;
+    next.saturating_sub(epoch_secs).max(1)
+}
+
+fn year_month(epoch_secs: u64) -> (u64, u64) {
+    // Howard Hinnant's civil_from_days, restricted to dates after 1970
+    let days = epoch_secs / 86_400 + 719_468;
+    let era = days / 146_097;
+    let doe = days % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month)
+}
+
+/// Days since 1970-01-01 of the first day of `year`-`month`
+fn days_from_civil(year: u64, month: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Storage backend for usage counters
+#[async_trait]
+pub trait UsageStore: Send + Sync {
+    /// Add to a tenant's counters (deltas may be negative) and return the
+    /// updated totals
+    async fn add(&self, tenant_id: &str, period: &str, requests: i64, rejected: i64) -> Result<TenantUsage>;
+    /// Counters for `period`, optionally restricted to one tenant
+    async fn list(&self, period: &str, tenant_id: Option<&str>) -> Result<Vec<TenantUsage>>;
+}
+
+/// In-process counters; lost on restart and not shared between replicas
+#[derive(Default)]
+pub struct MemoryUsageStore {
+    entries: RwLock<HashMap<(String, String), TenantUsage>>,
+}
+
+#[async_trait]
+impl UsageStore for MemoryUsageStore {
+    async fn add(&self, tenant_id: &str, period: &str, requests: i64, rejected: i64) -> Result<TenantUsage> {
+        let mut entries = self.entries.write().await;
+        let usage = entries
+            .entry((tenant_id.to_string(), period.to_string()))
+            .or_insert_with(|| TenantUsage {
+                tenant_id: tenant_id.to_string(),
+                period: period.to_string(),
+                requests: 0,
+                rejected: 0,
+            });
+        usage.requests = usage.requests.saturating_add_signed(requests);
+        usage.rejected = usage.rejected.saturating_add_signed(rejected);
+        Ok(usage.clone())
+    }
+
+    async fn list(&self, period: &str, tenant_id: Option<&str>) -> Result<Vec<TenantUsage>> {
+        let entries = self.entries.read().await;
+        let mut usage: Vec<TenantUsage> = entries
+            .values()
+            .filter(|u| u.period == period && tenant_id.is_none_or(|t| u.tenant_id == t))
+            .cloned()
+            .collect();
+        usage.sort_by(|a, b| a.tenant_id.cmp(&b.tenant_id));
+        Ok(usage)
+    }
+}
+
+/// Adapter persisting counters through a [`StateStore`]
+pub struct StateStoreUsageStore {
+    store: Arc<dyn StateStore>,
+}
+
+impl StateStoreUsageStore {
+    pub fn new(store: Arc<dyn StateStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl UsageStore for StateStoreUsageStore {
+    async fn add(&self, tenant_id: &str, period: &str, requests: i64, rejected: i64) -> Result<TenantUsage> {
+        self.store.add_tenant_usage(tenant_id, period, requests, rejected).await
+    }
+
+    async fn list(&self, period: &str, tenant_id: Option<&str>) -> Result<Vec<TenantUsage>> {
+        self.store.list_tenant_usage(period, tenant_id).await
+    }
+}
+
+/// Monthly request allowances
+#[derive(Debug, Clone, Default)]
+pub struct QuotaConfig {
+    /// Allowance for tenants without an override; 0 means unlimited
+    pub monthly_requests: u64,
+    /// Per-tenant allowances; 0 means unlimited
+    pub tenant_overrides: HashMap<String, u64>,
+}
+
+impl QuotaConfig {
+    pub fn new(monthly_requests: u64) -> Self {
+        Self {
+            monthly_requests,
+            tenant_overrides: HashMap::new(),
+        }
+    }
+
+    /// Read `{PREFIX}_MONTHLY_QUOTA` and `{PREFIX}_TENANT_QUOTAS`
+    /// (`tenant-a=100000,tenant-b=0`), falling back to `default_monthly`.
+    /// Malformed override entries are skipped with a warning.
+    pub fn from_env(prefix: &str, default_monthly: u64) -> Self {
+        let monthly_requests = std::env::var(format!("{}_MONTHLY_QUOTA", prefix))
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_monthly);
+        let mut config = Self::new(monthly_requests);
+        if let Ok(overrides) = std::env::var(format!("{}_TENANT_QUOTAS", prefix)) {
+            for entry in overrides.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                match entry.split_once('=').map(|(t, v)| (t.trim(), v.trim().parse::<u64>())) {
+                    Some((tenant, Ok(limit))) if !tenant.is_empty() => {
+                        config.tenant_overrides.insert(tenant.to_string(), limit);
+                    }
+                    _ => warn!(entry, "ignoring malformed {}_TENANT_QUOTAS entry", prefix),
+                }
+            }
+        }
+        config
+    }
+
+    pub fn with_tenant_quota(mut self, tenant_id: impl Into<String>, monthly_requests: u64) -> Self {
+        self.tenant_overrides.insert(tenant_id.into(), monthly_requests);
+        self
+    }
+
+    /// Allowance of `tenant_id`, `None` when unlimited
+    pub fn limit_for(&self, tenant_id: &str) -> Option<u64> {
+        let limit = self
+            .tenant_overrides
+            .get(tenant_id)
+            .copied()
+            .unwrap_or(self.monthly_requests);
+        (limit > 0).then_some(limit)
+    }
+}
+
+/// Outcome of a quota check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaDecision {
+    Allowed { limit: Option<u64>, remaining: Option<u64> },
+    Exceeded { limit: u64, retry_after_secs: u64 },
+}
+
+/// Middleware state: allowances plus the counter store
+#[derive(Clone)]
+pub struct Quota {
+    config: Arc<QuotaConfig>,
+    store: Arc<dyn UsageStore>,
+}
+
+impl Quota {
+    pub fn new(config: QuotaConfig, store: Arc<dyn UsageStore>) -> Self {
+        Self {
+            config: Arc::new(config),
+            store,
+        }
+    }
+
+    /// In-memory counters
+    pub fn in_memory(config: QuotaConfig) -> Self {
+        Self::new(config, Arc::new(MemoryUsageStore::default()))
+    }
+
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    pub fn store(&self) -> &Arc<dyn UsageStore> {
+        &self.store
+    }
+
+    /// Count one request for `tenant_id`. Store failures admit the request.
+    pub async fn record(&self, tenant_id: &str) -> QuotaDecision {
+        self.record_at(tenant_id, safe_unix_timestamp()).await
+    }
+
+    async fn record_at(&self, tenant_id: &str, now: u64) -> QuotaDecision {
+        let limit = self.config.limit_for(tenant_id);
+        let period = period_at(now);
+
+        let usage = match self.store.add(tenant_id, &period, 1, 0).await {
+            Ok(usage) => usage,
+            Err(e) => {
+                warn!(error = %e, tenant_id, "usage store update failed; admitting request");
+                return QuotaDecision::Allowed { limit, remaining: None };
+            }
+        };
+
+        match limit {
+            Some(limit) if usage.requests > limit => {
+                // Move the request from the admitted to the rejected counter
+                if let Err(e) = self.store.add(tenant_id, &period, -1, 1).await {
+                    warn!(error = %e, tenant_id, "failed to record rejected request");
+                }
+                QuotaDecision::Exceeded {
+                    limit,
+                    retry_after_secs: secs_until_next_period(now),
+                }
+            }
+            Some(limit) => QuotaDecision::Allowed {
+                limit: Some(limit),
+                remaining: Some(limit - usage.requests),
+            },
+            None => QuotaDecision::Allowed { limit: None, remaining: None },
+        }
+    }
+}
+
+/// Quota middleware; mount with
+/// `axum::middleware::from_fn_with_state(quota, quota_middleware)` inside
+/// the auth middleware so the caller's tenant is known.
+/// Rejected requests get 429 with a `Retry-After` header.
+pub async fn quota_middleware(State(quota): State<Quota>, req: Request, next: Next) -> Response {
+    let Some(tenant_id) = req.extensions().get::<AuthContext>().map(|ctx| ctx.tenant_id.clone()) else {
+        return next.run(req).await;
+    };
+
+    match quota.record(&tenant_id).await {
+        QuotaDecision::Allowed { limit, remaining } => {
+            let mut response = next.run(req).await;
+            if let (Some(limit), Some(remaining)) = (limit, remaining) {
+                response
+                    .headers_mut()
+                    .insert("x-quota-limit", HeaderValue::from(limit));
+                response
+                    .headers_mut()
+                    .insert("x-quota-remaining", HeaderValue::from(remaining));
+            }
+            response
+        }
+        QuotaDecision::Exceeded {
+            limit,
+            retry_after_secs,
+        } => {
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({ "error": "Monthly API quota exceeded" })),
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+            response
+                .headers_mut()
+                .insert("x-quota-limit", HeaderValue::from(limit));
+            response
+                .headers_mut()
+                .insert("x-quota-remaining", HeaderValue::from(0u64));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    // 2026-10-17T12:00:00Z
+    const OCT_17: u64 = 1_792_238_400;
+
+    #[test]
+    fn test_periods() {
+        assert_eq!(period_at(0), "1970-01");
+        assert_eq!(period_at(OCT_17), "2026-10");
+        // 2024-02-29T23:59:59Z
+        assert_eq!(period_at(1_709_251_199), "2024-02");
+        assert_eq!(period_at(1_709_251_200), "2024-03");
+        // Until 2026-11-01T00:00:00Z
+        assert_eq!(secs_until_next_period(OCT_17), 1_793_491_200 - OCT_17);
+        // December rolls over into January
+        assert_eq!(period_at(OCT_17 + 75 * 86_400 + secs_until_next_period(OCT_17 + 75 * 86_400)), "2027-01");
+
+        assert!(validate_period("2026-10").is_ok());
+        for bad in ["2026-13", "2026-1", "26-10", "2026/10", "abcd-ef", ""] {
+            assert!(validate_period(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_tenant_overrides() {
+        let config = QuotaConfig::new(100)
+            .with_tenant_quota("big", 1_000)
+            .with_tenant_quota("free", 0);
+        assert_eq!(config.limit_for("other"), Some(100));
+        assert_eq!(config.limit_for("big"), Some(1_000));
+        assert_eq!(config.limit_for("free"), None);
+        assert_eq!(QuotaConfig::default().limit_for("other"), None);
+    }
+
+    #[tokio::test]
+    async fn test_quota_counts_per_tenant_and_period() {
+        let quota = Quota::in_memory(QuotaConfig::new(2));
+
+        assert_eq!(
+            quota.record_at("a", OCT_17).await,
+            QuotaDecision::Allowed { limit: Some(2), remaining: Some(1) }
+        );
+        assert_eq!(
+            quota.record_at("a", OCT_17).await,
+            QuotaDecision::Allowed { limit: Some(2), remaining: Some(0) }
+        );
+        assert!(matches!(
+            quota.record_at("a", OCT_17).await,
+            QuotaDecision::Exceeded { limit: 2, .. }
+        ));
+        // Other tenants and the next month are unaffected
+        assert!(matches!(quota.record_at("b", OCT_17).await, QuotaDecision::Allowed { .. }));
+        let november = OCT_17 + secs_until_next_period(OCT_17);
+        assert!(matches!(quota.record_at("a", november).await, QuotaDecision::Allowed { .. }));
+
+        let usage = quota.store().list("2026-10", Some("a")).await.unwrap();
+        assert_eq!(usage[0].requests, 2);
+        assert_eq!(usage[0].rejected, 1);
+        assert_eq!(quota.store().list("2026-10", None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_returns_429_when_exhausted() {
+        let quota = Quota::in_memory(QuotaConfig::new(1));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(quota, quota_middleware));
+
+        let request = |tenant: Option<&str>| {
+            let mut req = Request::builder().uri("/").body(Body::empty()).unwrap();
+            if let Some(tenant) = tenant {
+                req.extensions_mut().insert(AuthContext {
+                    user_id: "u".into(),
+                    tenant_id: tenant.into(),
+                    username: "u".into(),
+                    is_system_admin: false,
+                    roles: vec![],
+                    permissions: vec![],
+                });
+            }
+            req
+        };
+
+        let resp = app.clone().oneshot(request(Some("t"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("x-quota-remaining").unwrap(), "0");
+
+        let resp = app.clone().oneshot(request(Some("t"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().get(header::RETRY_AFTER).is_some());
+
+        // Anonymous requests are not metered
+        let resp = app.oneshot(request(None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,367 @@
+//! Token-bucket rate limiting middleware.
+//!
+//! Each route group gets its own [`RateLimiter`] with a burst capacity and a
+//! refill rate, keyed by client IP, authenticated user, tenant, or API key:
+//!
+//! ```ignore
+//! let login_limit = RateLimiter::new(RateLimitConfig::from_env("AUTH_LOGIN", 10, 0.2, RateLimitKey::Ip));
+//! Router::new().route(
+//!     "/v1/auth/login",
+//!     post(login).layer(middleware::from_fn_with_state(login_limit, rate_limit_middleware)),
+//! );
+//! ```
+//!
+//! Per-IP keying needs the server started with
+//! `into_make_service_with_connect_info::<SocketAddr>()`.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+
+use crate::auth_middleware::AuthContext;
+
+/// Header carrying an API key for [`RateLimitKey::ApiKey`]
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Default upper bound on tracked clients per limiter
+const DEFAULT_MAX_TRACKED_KEYS: usize = 100_000;
+
+/// What identifies a client for rate limiting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// Peer address (or first `X-Forwarded-For` hop when trusted)
+    Ip,
+    /// Authenticated user id; falls back to IP for anonymous requests
+    User,
+    /// Authenticated user's tenant, shared by all of its users; falls back
+    /// to IP for anonymous requests
+    Tenant,
+    /// `X-API-Key` or bearer token; falls back to IP when absent
+    ApiKey,
+}
+
+/// Configuration for one route group
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Maximum burst size; 0 disables limiting
+    pub burst: u32,
+    /// Tokens added per second
+    pub refill_per_sec: f64,
+    pub key: RateLimitKey,
+    /// Use the first `X-Forwarded-For` hop as the client IP (behind a trusted proxy)
+    pub trust_forwarded_for: bool,
+    pub max_tracked_keys: usize,
+}
+
+impl RateLimitConfig {
+    pub fn new(burst: u32, refill_per_sec: f64, key: RateLimitKey) -> Self {
+        Self {
+            burst,
+            refill_per_sec,
+            key,
+            trust_forwarded_for: false,
+            max_tracked_keys: DEFAULT_MAX_TRACKED_KEYS,
+        }
+    }
+
+    /// Read `{PREFIX}_RATE_LIMIT_BURST` and `{PREFIX}_RATE_LIMIT_PER_SEC`, falling
+    /// back to the given defaults. `RATE_LIMIT_TRUST_FORWARDED_FOR=true` enables
+    /// `X-Forwarded-For` handling for every limiter.
+    pub fn from_env(prefix: &str, default_burst: u32, default_per_sec: f64, key: RateLimitKey) -> Self {
+        let burst = std::env::var(format!("{}_RATE_LIMIT_BURST", prefix))
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_burst);
+        let refill_per_sec = std::env::var(format!("{}_RATE_LIMIT_PER_SEC", prefix))
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .unwrap_or(default_per_sec);
+        let trust_forwarded_for = std::env::var("RATE_LIMIT_TRUST_FORWARDED_FOR")
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+
+        Self {
+            trust_forwarded_for,
+            ..Self::new(burst, refill_per_sec, key)
+        }
+    }
+
+    pub fn with_trust_forwarded_for(mut self, trust: bool) -> Self {
+        self.trust_forwarded_for = trust;
+        self
+    }
+
+    pub fn with_max_tracked_keys(mut self, max: usize) -> Self {
+        self.max_tracked_keys = max.max(1);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Outcome of a rate limit check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Allowed { remaining: u32 },
+    Limited { retry_after_secs: u64 },
+}
+
+/// Shared token-bucket state for one route group
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    buckets: Arc<RwLock<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Take one token for `key`
+    pub async fn check(&self, key: &str) -> Decision {
+        self.check_at(key, Instant::now()).await
+    }
+
+    async fn check_at(&self, key: &str, now: Instant) -> Decision {
+        let capacity = f64::from(self.config.burst);
+        let rate = self.config.refill_per_sec;
+        let mut buckets = self.buckets.write().await;
+
+        if buckets.len() >= self.config.max_tracked_keys && !buckets.contains_key(key) {
+            // Drop clients whose bucket has refilled completely; they carry no state
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.last_refill).as_secs_f64() * rate < capacity
+            });
+            if buckets.len() >= self.config.max_tracked_keys {
+                let stalest = buckets
+                    .iter()
+                    .min_by_key(|(_, b)| b.last_refill)
+                    .map(|(k, _)| k.clone());
+                if let Some(stalest) = stalest {
+                    buckets.remove(&stalest);
+                }
+            }
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Decision::Allowed {
+                remaining: bucket.tokens.floor() as u32,
+            }
+        } else {
+            let retry_after_secs = if rate > 0.0 {
+                ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64
+            } else {
+                u64::MAX
+            };
+            Decision::Limited { retry_after_secs }
+        }
+    }
+
+    fn client_key(&self, req: &Request) -> String {
+        match self.config.key {
+            RateLimitKey::Ip => format!("ip:{}", self.client_ip(req)),
+            RateLimitKey::User => match req.extensions().get::<AuthContext>() {
+                Some(ctx) => format!("user:{}", ctx.user_id),
+                None => format!("ip:{}", self.client_ip(req)),
+            },
+            RateLimitKey::Tenant => match req.extensions().get::<AuthContext>() {
+                Some(ctx) => format!("tenant:{}", ctx.tenant_id),
+                None => format!("ip:{}", self.client_ip(req)),
+            },
+            RateLimitKey::ApiKey => {
+                let credential = req
+                    .headers()
+                    .get(API_KEY_HEADER)
+                    .or_else(|| req.headers().get(header::AUTHORIZATION));
+                match credential {
+                    // Hash so raw credentials never sit in memory as map keys
+                    Some(value) => format!("key:{}", hex::encode(Sha256::digest(value.as_bytes()))),
+                    None => format!("ip:{}", self.client_ip(req)),
+                }
+            }
+        }
+    }
+
+    fn client_ip(&self, req: &Request) -> String {
+        if self.config.trust_forwarded_for {
+            let forwarded = req
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .map(str::trim)
+                .filter(|v| !v.is_empty());
+            if let Some(ip) = forwarded {
+                return ip.to_string();
+            }
+        }
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+/// Rate limiting middleware; mount with
+/// `axum::middleware::from_fn_with_state(limiter, rate_limit_middleware)`.
+/// Rejected requests get 429 with a `Retry-After` header.
+pub async fn rate_limit_middleware(
+    State(limiter): State<RateLimiter>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if limiter.config.burst == 0 {
+        return next.run(req).await;
+    }
+
+    let key = limiter.client_key(&req);
+    match limiter.check(&key).await {
+        Decision::Allowed { remaining } => {
+            let mut response = next.run(req).await;
+            response
+                .headers_mut()
+                .insert("x-ratelimit-limit", HeaderValue::from(limiter.config.burst));
+            response
+                .headers_mut()
+                .insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+            response
+        }
+        Decision::Limited { retry_after_secs } => {
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({ "error": "Rate limit exceeded" })),
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_bucket_refills_over_time() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(2, 1.0, RateLimitKey::Ip));
+        let start = Instant::now();
+
+        assert_eq!(limiter.check_at("a", start).await, Decision::Allowed { remaining: 1 });
+        assert_eq!(limiter.check_at("a", start).await, Decision::Allowed { remaining: 0 });
+        assert_eq!(
+            limiter.check_at("a", start).await,
+            Decision::Limited { retry_after_secs: 1 }
+        );
+        // Other clients are unaffected
+        assert_eq!(limiter.check_at("b", start).await, Decision::Allowed { remaining: 1 });
+
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.check_at("a", later).await, Decision::Allowed { remaining: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_tracked_keys_are_bounded() {
+        let limiter = RateLimiter::new(
+            RateLimitConfig::new(5, 0.0, RateLimitKey::Ip).with_max_tracked_keys(2),
+        );
+        let now = Instant::now();
+        for key in ["a", "b", "c"] {
+            limiter.check_at(key, now).await;
+        }
+        assert_eq!(limiter.buckets.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_returns_429_per_api_key() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(1, 0.0, RateLimitKey::ApiKey));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(limiter, rate_limit_middleware));
+
+        let request = |key: &str| {
+            Request::builder()
+                .uri("/")
+                .header(API_KEY_HEADER, key)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let resp = app.clone().oneshot(request("k1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("x-ratelimit-remaining").unwrap(), "0");
+
+        let resp = app.clone().oneshot(request("k1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().get(header::RETRY_AFTER).is_some());
+
+        let resp = app.oneshot(request("k2")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_key_is_shared_by_users_of_a_tenant() {
+        let limiter = RateLimiter::new(RateLimitConfig::new(1, 0.0, RateLimitKey::Tenant));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(limiter, rate_limit_middleware));
+
+        let request = |user: &str, tenant: &str| {
+            let mut req = Request::builder().uri("/").body(Body::empty()).unwrap();
+            req.extensions_mut().insert(AuthContext {
+                user_id: user.into(),
+                tenant_id: tenant.into(),
+                username: user.into(),
+                is_system_admin: false,
+                roles: vec![],
+                permissions: vec![],
+            });
+            req
+        };
+
+        let resp = app.clone().oneshot(request("alice", "t1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = app.clone().oneshot(request("bob", "t1")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let resp = app.oneshot(request("carol", "t2")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
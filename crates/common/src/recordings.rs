@@ -7,6 +7,10 @@ pub struct RecordingConfig {
   pub source_uri: Option<String>,
   pub retention_hours: Option<u32>,
   pub format: Option<RecordingFormat>,
+  /// How the video is written; the recorder node's
+  /// `RECORDING_DEFAULT_ENCODING` when unset
+  #[serde(default)]
+  pub encoding: Option<RecordingEncoding>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -23,6 +27,45 @@ impl Default for RecordingFormat {
   }
 }
 
+/// Whether a recording keeps the camera's bitstream or re-encodes it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "mode")]
+pub enum RecordingEncoding {
+  /// Remux the camera's H.264/H.265 bitstream without decoding it.
+  /// Segments can only be cut on the camera's keyframes.
+  Copy,
+  /// Decode and re-encode to H.264 with AAC audio
+  Transcode {
+    /// libx264 constant rate factor (0-51, lower is better)
+    #[serde(default)]
+    crf: Option<u8>,
+    /// Scale down to at most this height, keeping the aspect ratio
+    #[serde(default)]
+    max_height: Option<u32>,
+  },
+}
+
+impl RecordingEncoding {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      RecordingEncoding::Copy => "copy",
+      RecordingEncoding::Transcode { .. } => "transcode",
+    }
+  }
+
+  /// `copy` or `transcode` (with default quality), as used by settings
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "copy" => Some(RecordingEncoding::Copy),
+      "transcode" => Some(RecordingEncoding::Transcode {
+        crf: None,
+        max_height: None,
+      }),
+      _ => None,
+    }
+  }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum RecordingState {
@@ -91,6 +134,18 @@ pub struct RecordingAiConfig {
   /// JPEG quality (2-31, lower is better)
   #[serde(default = "default_jpeg_quality")]
   pub jpeg_quality: u32,
+  /// Tags added to the recording when a frame matches
+  #[serde(default)]
+  pub auto_tags: Vec<AutoTagRule>,
+}
+
+/// Tag a recording once a detection of `class` reaches `min_confidence`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoTagRule {
+  pub class: String,
+  #[serde(default)]
+  pub min_confidence: f32,
+  pub tag: String,
 }
 
 fn default_capture_interval() -> u64 {
@@ -113,6 +168,16 @@ pub struct RecordingStartRequest {
   /// Optional AI processing configuration
   #[serde(default)]
   pub ai_config: Option<RecordingAiConfig>,
+  /// Store idle segments at reduced quality or drop them, based on the
+  /// source stream's motion activity; forces HLS output
+  #[serde(default)]
+  pub motion_policy: Option<crate::motion::MotionStoragePolicy>,
+  /// Initial tags of the recording in the search index
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// Initial key/value labels of the recording in the search index
+  #[serde(default)]
+  pub labels: std::collections::HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
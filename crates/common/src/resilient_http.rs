@@ -0,0 +1,641 @@
+//! Resilient HTTP client for inter-service calls.
+//!
+//! Wraps `reqwest::Client` with per-attempt timeouts, retries with full-jitter
+//! exponential backoff and a circuit breaker per target service:
+//!
+//! ```ignore
+//! let client = ResilientClient::builder("coordinator").build().await?;
+//! let resp = client.send(|c| c.post(url.clone()).json(&request)).await?;
+//! ```
+//!
+//! Connection failures are always retried since the request never reached the
+//! target. Timeouts, 5xx, 408 and 429 are retried only for idempotent methods
+//! unless [`ResilientClientBuilder::retry_non_idempotent`] is set. After the
+//! last attempt the final response is returned as-is, so callers keep using
+//! `error_for_status()`.
+//!
+//! Every client registers its target in a process-wide table read by
+//! [`target_health`], which services export as metrics.
+
+use anyhow::Result;
+use rand::Rng;
+use reqwest::{Method, StatusCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, warn};
+
+/// Upper bound on targets tracked by [`target_health`]
+const MAX_TRACKED_TARGETS: usize = 256;
+
+/// Retry, timeout and circuit breaker settings
+#[derive(Debug, Clone)]
+pub struct ResilienceConfig {
+    pub connect_timeout: Duration,
+    /// Timeout for each individual attempt
+    pub request_timeout: Duration,
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failures that open the circuit; 0 disables the breaker
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a half-open probe
+    pub open_duration: Duration,
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(3),
+            request_timeout: Duration::from_secs(10),
+            max_retries: 2,
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl ResilienceConfig {
+    /// Defaults overridden by `HTTP_CLIENT_TIMEOUT_SECS`, `HTTP_CLIENT_MAX_RETRIES`,
+    /// `HTTP_CLIENT_BREAKER_THRESHOLD` and `HTTP_CLIENT_BREAKER_OPEN_SECS`
+    pub fn from_env() -> Self {
+        fn env<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+
+        let defaults = Self::default();
+        Self {
+            request_timeout: env::<u64>("HTTP_CLIENT_TIMEOUT_SECS")
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.request_timeout),
+            max_retries: env("HTTP_CLIENT_MAX_RETRIES").unwrap_or(defaults.max_retries).min(10),
+            failure_threshold: env("HTTP_CLIENT_BREAKER_THRESHOLD")
+                .unwrap_or(defaults.failure_threshold),
+            open_duration: env::<u64>("HTTP_CLIENT_BREAKER_OPEN_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.open_duration),
+            ..defaults
+        }
+    }
+
+    /// Full-jitter backoff before retry number `attempt` (0-based)
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        let max_ms = u64::try_from(exp.as_millis()).unwrap_or(u64::MAX);
+        Duration::from_millis(rand::thread_rng().gen_range(0..=max_ms))
+    }
+}
+
+/// Circuit breaker state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// Numeric encoding for gauges: 0 closed, 1 half-open, 2 open
+    pub fn as_gauge(self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        }
+    }
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half_open"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+/// Consecutive-failure circuit breaker with a single half-open probe
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    open_duration: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            threshold,
+            open_duration,
+            inner: Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    /// Whether a request may go out now; `Err` carries the remaining open time
+    pub async fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now()).await
+    }
+
+    async fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        if self.threshold == 0 {
+            return Ok(());
+        }
+        let mut inner = self.inner.lock().await;
+        match inner.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open => {
+                let elapsed = inner
+                    .opened_at
+                    .map(|at| now.saturating_duration_since(at))
+                    .unwrap_or(self.open_duration);
+                if elapsed >= self.open_duration {
+                    inner.state = CircuitState::HalfOpen;
+                    inner.probe_in_flight = true;
+                    Ok(())
+                } else {
+                    Err(self.open_duration - elapsed)
+                }
+            }
+            CircuitState::HalfOpen if !inner.probe_in_flight => {
+                inner.probe_in_flight = true;
+                Ok(())
+            }
+            CircuitState::HalfOpen => Err(Duration::ZERO),
+        }
+    }
+
+    pub async fn record_success(&self) {
+        let mut inner = self.inner.lock().await;
+        inner.state = CircuitState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_in_flight = false;
+    }
+
+    pub async fn record_failure(&self) {
+        self.record_failure_at(Instant::now()).await
+    }
+
+    async fn record_failure_at(&self, now: Instant) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().await;
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        inner.probe_in_flight = false;
+        if inner.state == CircuitState::HalfOpen || inner.consecutive_failures >= self.threshold {
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(now);
+        }
+    }
+
+    pub async fn state(&self) -> CircuitState {
+        self.inner.lock().await.state
+    }
+
+    pub async fn consecutive_failures(&self) -> u32 {
+        self.inner.lock().await.consecutive_failures
+    }
+}
+
+/// Error returned by [`ResilientClient::send`]
+#[derive(Debug)]
+pub enum ResilientError {
+    /// The target's circuit is open; no request was sent
+    CircuitOpen { target: String, retry_in: Duration },
+    /// Transport error on the last attempt
+    Request { target: String, source: reqwest::Error },
+}
+
+impl ResilientError {
+    /// True when the target never received the request (open circuit or
+    /// connection failure), so sending it elsewhere cannot duplicate work
+    pub fn is_unsent(&self) -> bool {
+        match self {
+            ResilientError::CircuitOpen { .. } => true,
+            ResilientError::Request { source, .. } => source.is_connect(),
+        }
+    }
+}
+
+impl fmt::Display for ResilientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResilientError::CircuitOpen { target, retry_in } => write!(
+                f,
+                "circuit open for {} (retry in {}s)",
+                target,
+                retry_in.as_secs()
+            ),
+            ResilientError::Request { target, source } => {
+                write!(f, "request to {} failed: {}", target, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResilientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResilientError::CircuitOpen { .. } => None,
+            ResilientError::Request { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Point-in-time health of one target
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetHealth {
+    pub target: String,
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    pub requests: u64,
+    pub failures: u64,
+    pub retries: u64,
+    pub rejected: u64,
+    pub last_latency_ms: u64,
+}
+
+impl TargetHealth {
+    /// Absolute event counts, labelled for metrics export
+    pub fn event_totals(&self) -> [(&'static str, u64); 4] {
+        [
+            ("attempt", self.requests),
+            ("failure", self.failures),
+            ("retry", self.retries),
+            ("rejected", self.rejected),
+        ]
+    }
+}
+
+#[derive(Debug, Default)]
+struct TargetCounters {
+    requests: AtomicU64,
+    failures: AtomicU64,
+    retries: AtomicU64,
+    rejected: AtomicU64,
+    last_latency_ms: AtomicU64,
+}
+
+struct TargetEntry {
+    breaker: Arc<CircuitBreaker>,
+    counters: Arc<TargetCounters>,
+}
+
+fn registry() -> &'static RwLock<HashMap<String, TargetEntry>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, TargetEntry>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Health of every target a client was built for in this process
+pub async fn target_health() -> Vec<TargetHealth> {
+    let registry = registry().read().await;
+    let mut out = Vec::with_capacity(registry.len());
+    for (target, entry) in registry.iter() {
+        out.push(TargetHealth {
+            target: target.clone(),
+            circuit: entry.breaker.state().await,
+            consecutive_failures: entry.breaker.consecutive_failures().await,
+            requests: entry.counters.requests.load(Ordering::Relaxed),
+            failures: entry.counters.failures.load(Ordering::Relaxed),
+            retries: entry.counters.retries.load(Ordering::Relaxed),
+            rejected: entry.counters.rejected.load(Ordering::Relaxed),
+            last_latency_ms: entry.counters.last_latency_ms.load(Ordering::Relaxed),
+        });
+    }
+    out.sort_by(|a, b| a.target.cmp(&b.target));
+    out
+}
+
+/// Builder for [`ResilientClient`]
+#[derive(Debug, Clone)]
+pub struct ResilientClientBuilder {
+    target: String,
+    config: ResilienceConfig,
+}
+
+impl ResilientClientBuilder {
+    pub fn config(mut self, config: ResilienceConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = timeout;
+        self
+    }
+
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.config.max_retries = retries;
+        self
+    }
+
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.config.base_backoff = base;
+        self.config.max_backoff = max.max(base);
+        self
+    }
+
+    pub fn circuit_breaker(mut self, failure_threshold: u32, open_duration: Duration) -> Self {
+        self.config.failure_threshold = failure_threshold;
+        self.config.open_duration = open_duration;
+        self
+    }
+
+    /// Also retry timeouts and 5xx for POST/PATCH (only for endpoints that
+    /// tolerate duplicates)
+    pub fn retry_non_idempotent(mut self, retry: bool) -> Self {
+        self.config.retry_non_idempotent = retry;
+        self
+    }
+
+    pub async fn build(self) -> Result<ResilientClient> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(self.config.connect_timeout)
+            .timeout(self.config.request_timeout)
+            .build()?;
+
+        // Clients for the same target share one breaker and one set of counters
+        let mut registry = registry().write().await;
+        let (breaker, counters) = match registry.get(&self.target) {
+            Some(entry) => (entry.breaker.clone(), entry.counters.clone()),
+            None => {
+                let breaker = Arc::new(CircuitBreaker::new(
+                    self.config.failure_threshold,
+                    self.config.open_duration,
+                ));
+                let counters = Arc::new(TargetCounters::default());
+                if registry.len() < MAX_TRACKED_TARGETS {
+                    registry.insert(
+                        self.target.clone(),
+                        TargetEntry {
+                            breaker: breaker.clone(),
+                            counters: counters.clone(),
+                        },
+                    );
+                }
+                (breaker, counters)
+            }
+        };
+
+        Ok(ResilientClient {
+            target: self.target,
+            client,
+            config: Arc::new(self.config),
+            breaker,
+            counters,
+        })
+    }
+}
+
+/// `reqwest::Client` with timeouts, retries and a circuit breaker
+#[derive(Clone)]
+pub struct ResilientClient {
+    target: String,
+    client: reqwest::Client,
+    config: Arc<ResilienceConfig>,
+    breaker: Arc<CircuitBreaker>,
+    counters: Arc<TargetCounters>,
+}
+
+impl ResilientClient {
+    /// `target` names the dependency in logs and metrics (e.g. "coordinator")
+    pub fn builder(target: impl Into<String>) -> ResilientClientBuilder {
+        ResilientClientBuilder {
+            target: target.into(),
+            config: ResilienceConfig::from_env(),
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Underlying client, for calls that must bypass retries and the breaker
+    pub fn inner(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    pub async fn circuit_state(&self) -> CircuitState {
+        self.breaker.state().await
+    }
+
+    /// Send the request produced by `build`, retrying per the config. `build`
+    /// is called once per attempt. Inside a [`crate::gateway_identity::scope`]
+    /// the caller's identity headers are attached.
+    pub async fn send<F>(&self, build: F) -> Result<reqwest::Response, ResilientError>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0u32;
+        loop {
+            if let Err(retry_in) = self.breaker.try_acquire().await {
+                self.counters.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(ResilientError::CircuitOpen {
+                    target: self.target.clone(),
+                    retry_in,
+                });
+            }
+
+            let mut request = build(&self.client).build().map_err(|source| ResilientError::Request {
+                target: self.target.clone(),
+                source,
+            })?;
+            if let Some(identity) = crate::gateway_identity::current() {
+                request.headers_mut().extend(identity);
+            }
+            let idempotent = self.config.retry_non_idempotent || is_idempotent(request.method());
+
+            self.counters.requests.fetch_add(1, Ordering::Relaxed);
+            let started = Instant::now();
+            let result = self.client.execute(request).await;
+            self.counters.last_latency_ms.store(
+                u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+                Ordering::Relaxed,
+            );
+
+            let retryable = match &result {
+                Ok(resp) if is_retryable_status(resp.status()) => {
+                    self.record_failure().await;
+                    idempotent
+                }
+                Ok(_) => {
+                    // 4xx is the caller's problem, not a sign the target is down
+                    self.breaker.record_success().await;
+                    false
+                }
+                Err(e) => {
+                    self.record_failure().await;
+                    e.is_connect() || (idempotent && e.is_timeout())
+                }
+            };
+
+            if !retryable || attempt >= self.config.max_retries {
+                return result.map_err(|source| ResilientError::Request {
+                    target: self.target.clone(),
+                    source,
+                });
+            }
+
+            let mut delay = self.config.backoff(attempt);
+            if let Ok(resp) = &result {
+                if let Some(retry_after) = retry_after(resp) {
+                    delay = delay.max(retry_after.min(self.config.max_backoff));
+                }
+            }
+            match &result {
+                Ok(resp) => debug!(target_service = %self.target, status = %resp.status(), attempt, "retrying request"),
+                Err(e) => debug!(target_service = %self.target, error = %e, attempt, "retrying request"),
+            }
+            self.counters.retries.fetch_add(1, Ordering::Relaxed);
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn record_failure(&self) {
+        self.counters.failures.fetch_add(1, Ordering::Relaxed);
+        let before = self.breaker.state().await;
+        self.breaker.record_failure().await;
+        if before != CircuitState::Open && self.breaker.state().await == CircuitState::Open {
+            warn!(target_service = %self.target, "circuit opened after repeated failures");
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    )
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    resp.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_breaker_opens_and_half_opens() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+
+        breaker.record_failure_at(start).await;
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+        breaker.record_failure_at(start).await;
+        assert_eq!(breaker.state().await, CircuitState::Open);
+        assert!(breaker.try_acquire_at(start + Duration::from_secs(1)).await.is_err());
+
+        // One probe after the open period, further requests wait for its outcome
+        let later = start + Duration::from_secs(10);
+        assert!(breaker.try_acquire_at(later).await.is_ok());
+        assert_eq!(breaker.state().await, CircuitState::HalfOpen);
+        assert!(breaker.try_acquire_at(later).await.is_err());
+
+        breaker.record_success().await;
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+        assert!(breaker.try_acquire_at(later).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_reopens() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(5));
+        let start = Instant::now();
+        breaker.record_failure_at(start).await;
+
+        let probe = start + Duration::from_secs(5);
+        assert!(breaker.try_acquire_at(probe).await.is_ok());
+        breaker.record_failure_at(probe).await;
+        assert_eq!(breaker.state().await, CircuitState::Open);
+        assert!(breaker.try_acquire_at(probe + Duration::from_secs(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_zero_threshold_disables_breaker() {
+        let breaker = CircuitBreaker::new(0, Duration::from_secs(5));
+        for _ in 0..10 {
+            breaker.record_failure().await;
+        }
+        assert!(breaker.try_acquire().await.is_ok());
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = ResilienceConfig {
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            ..ResilienceConfig::default()
+        };
+        for attempt in 0..20 {
+            assert!(config.backoff(attempt) <= Duration::from_millis(500));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_rejects_without_sending() {
+        let client = ResilientClient::builder("test-open-circuit")
+            .circuit_breaker(1, Duration::from_secs(60))
+            .max_retries(0)
+            .build()
+            .await
+            .unwrap();
+        client.breaker.record_failure().await;
+
+        let err = client
+            .send(|c| c.get("http://127.0.0.1:9/never"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ResilientError::CircuitOpen { .. }));
+
+        let health = target_health().await;
+        let entry = health.iter().find(|h| h.target == "test-open-circuit").unwrap();
+        assert_eq!(entry.circuit, CircuitState::Open);
+        assert_eq!(entry.requests, 0);
+        assert_eq!(entry.rejected, 1);
+    }
+}
@@ -163,6 +163,43 @@ pub struct ExecutePolicyResponse {
   pub message: String,
 }
 
+/// Impact of a policy on one camera's recordings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CameraRetentionImpact {
+  /// Source stream of the recordings; `None` for recordings without one
+  pub device_id: Option<String>,
+  pub recordings_to_delete: i32,
+  pub bytes_to_free: i64,
+  pub recordings_to_move: i32,
+  pub bytes_to_move: i64,
+  /// Recordings of the camera left after the policy runs (cold ones included)
+  pub recordings_remaining: i32,
+  /// Start of the camera's oldest recording left after the policy runs
+  pub oldest_remaining_at: Option<i64>,
+}
+
+/// Actions a policy would take right now, computed without recording an
+/// execution or touching any file, and kept as the policy's latest preview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPreview {
+  pub id: String,
+  pub policy_id: String,
+  pub recordings_scanned: i32,
+  pub recordings_matched: i32,
+  pub actions: Vec<RetentionAction>,
+  pub recordings_to_delete: i32,
+  /// Bytes the delete actions would free
+  pub bytes_to_free: i64,
+  pub recordings_to_move: i32,
+  /// Bytes the move-to-cold actions would move
+  pub bytes_to_move: i64,
+  /// Cameras with at least one recording deleted or moved
+  pub cameras: Vec<CameraRetentionImpact>,
+  /// Start of the oldest recording left on the node after the policy runs
+  pub oldest_remaining_at: Option<i64>,
+  pub generated_at: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListPoliciesResponse {
   pub policies: Vec<RetentionPolicy>,
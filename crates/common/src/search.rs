@@ -26,6 +26,21 @@ pub struct RecordingIndexEntry {
   pub state: String,
   pub indexed_at: i64,
   pub updated_at: i64,
+  /// Matching AI detections, set when a search filters on `classes`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub detections: Option<DetectionSummary>,
+}
+
+/// AI detections of the searched classes within one recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionSummary {
+  /// Detected objects of the searched classes, summed over frames
+  pub count: i64,
+  /// Frames with at least one matching detection
+  pub frames: i64,
+  pub first_at: i64,
+  pub last_at: i64,
+  pub max_confidence: Option<f32>,
 }
 
 // Event Search Types
@@ -82,6 +97,17 @@ pub struct RecordingSearchQuery {
   pub tags: Option<Vec<String>>, // Match ANY of these tags
   pub labels: Option<HashMap<String, String>>, // Match ALL these labels
 
+  // AI detection filters; with classes set only recordings containing a
+  // matching detection are returned
+  #[serde(default)]
+  pub classes: Option<Vec<String>>, // Match ANY of these classes
+  #[serde(default)]
+  pub detected_after: Option<i64>,
+  #[serde(default)]
+  pub detected_before: Option<i64>,
+  #[serde(default)]
+  pub min_confidence: Option<f32>,
+
   // Pagination
   #[serde(default = "default_offset")]
   pub offset: i32,
@@ -90,7 +116,7 @@ pub struct RecordingSearchQuery {
 
   // Sorting
   #[serde(default = "default_sort_by")]
-  pub sort_by: String, // started_at, duration_secs, file_size_bytes
+  pub sort_by: String, // started_at, duration_secs, file_size_bytes, relevance (detection count)
   #[serde(default = "default_sort_order")]
   pub sort_order: String, // asc, desc
 }
@@ -182,6 +208,79 @@ pub struct ObjectSearchResponse {
   pub limit: i32,
 }
 
+/// One detection box kept in the `boxes` of an `ai_detection` event, in
+/// pixels of the frame that was analysed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionBox {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+  pub confidence: f32,
+}
+
+/// Range of `GET /v1/search/recordings/:recording_id/detections`, in
+/// seconds from the start of the recording
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordingDetectionsQuery {
+  #[serde(default)]
+  pub from_secs: f64,
+  /// End of the range; the end of the recording when unset
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub to_secs: Option<f64>,
+}
+
+/// Stored detections of one class in one analysed frame of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingDetection {
+  /// Seconds from the start of the recording
+  pub offset_secs: f64,
+  pub class: String,
+  /// Size the frame was analysed at; 0 for a dimension that was scaled to
+  /// keep the aspect ratio
+  pub frame_width: u32,
+  pub frame_height: u32,
+  pub boxes: Vec<DetectionBox>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingDetectionsResponse {
+  pub recording_id: String,
+  /// Unix seconds
+  pub started_at: i64,
+  /// Oldest first
+  pub detections: Vec<RecordingDetection>,
+  /// More detections fell in the range than were returned
+  pub truncated: bool,
+}
+
+/// Change to a recording's tags and labels
+/// (`PATCH /v1/search/recordings/:recording_id/metadata`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordingMetadataUpdate {
+  #[serde(default)]
+  pub add_tags: Vec<String>,
+  #[serde(default)]
+  pub remove_tags: Vec<String>,
+  /// Labels to set; `null` removes a label
+  #[serde(default)]
+  pub labels: HashMap<String, Option<String>>,
+}
+
+impl RecordingMetadataUpdate {
+  pub fn is_empty(&self) -> bool {
+    self.add_tags.is_empty() && self.remove_tags.is_empty() && self.labels.is_empty()
+  }
+}
+
+/// Tags and labels of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingMetadataResponse {
+  pub recording_id: String,
+  pub tags: Vec<String>,
+  pub labels: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchStatsResponse {
   pub total_recordings: i64,
@@ -191,6 +290,66 @@ pub struct SearchStatsResponse {
   pub newest_recording: Option<i64>,
 }
 
+/// Kind of a change in the recording change feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingChangeKind {
+  Created,
+  Updated,
+  Deleted,
+}
+
+impl RecordingChangeKind {
+  pub fn parse(kind: &str) -> Option<Self> {
+    match kind {
+      "created" => Some(Self::Created),
+      "updated" => Some(Self::Updated),
+      "deleted" => Some(Self::Deleted),
+      _ => None,
+    }
+  }
+}
+
+/// Most changes one page of the change feed holds
+pub const MAX_CHANGES_LIMIT: i64 = 1000;
+
+/// Page of `GET /v1/recordings/changes`, e.g. `?since=1234&limit=500`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordingChangesQuery {
+  /// `next_cursor` of the previous page; from the beginning when unset
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub since: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub limit: Option<i64>,
+}
+
+/// One change of an indexed recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingChange {
+  /// Position in the feed; opaque, increasing
+  pub cursor: String,
+  pub kind: RecordingChangeKind,
+  pub recording_id: String,
+  /// Unix seconds
+  pub changed_at: i64,
+  /// Index entry as of now rather than as of the change; unset for
+  /// deletions and for recordings deleted since
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub recording: Option<RecordingIndexEntry>,
+}
+
+/// Changes after a cursor, oldest first. Only the latest change of each
+/// recording is kept, so a created or updated change is an upsert; replaying
+/// the feed from the beginning rebuilds the whole index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingChangesResponse {
+  pub changes: Vec<RecordingChange>,
+  /// Cursor to resume from; the cursor passed in when there were no changes
+  pub next_cursor: String,
+  /// More changes follow right away
+  pub has_more: bool,
+}
+
 // Helper functions
 fn default_offset() -> i32 {
   0
@@ -0,0 +1,202 @@
+//! Centrally managed service configuration.
+//!
+//! Operators publish JSON configuration documents per service to the
+//! coordinator (`PUT /v1/config/:service`). Every publish or rollback creates
+//! a new immutable version. Services run a [`ConfigWatcher`], which
+//! long-polls `GET /v1/config/:service?after=<version>` and hands each new
+//! version to the service so it can be applied without a restart.
+
+use crate::resilient_http::ResilientClient;
+use anyhow::{bail, Context, Result};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{env, time::Duration};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+/// Upper bound on a serialized configuration document
+pub const MAX_DOCUMENT_BYTES: usize = 256 * 1024;
+
+/// Longest a watch request may be held open by the coordinator
+pub const MAX_WAIT_SECS: u64 = 60;
+
+/// One published version of a service's configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceConfig {
+  pub service: String,
+  /// Starts at 1 and increases by one per publish or rollback
+  pub version: u64,
+  /// JSON object; its layout is up to the consuming service
+  pub document: Value,
+  /// Unix seconds
+  pub created_at: u64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub created_by: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub comment: Option<String>,
+  /// Version whose document this version restored
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub rollback_of: Option<u64>,
+}
+
+/// Body of `PUT /v1/config/:service`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceConfigUpdate {
+  pub document: Value,
+  #[serde(default)]
+  pub comment: Option<String>,
+  /// Reject the update with 409 unless the latest version is this one
+  /// (0 when no version exists yet)
+  #[serde(default)]
+  pub expected_version: Option<u64>,
+}
+
+/// Body of `POST /v1/config/:service/rollback`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceConfigRollback {
+  /// Version whose document becomes current again
+  pub version: u64,
+  #[serde(default)]
+  pub comment: Option<String>,
+}
+
+/// Documents must be JSON objects of bounded size
+pub fn validate_document(document: &Value) -> Result<()> {
+  if !document.is_object() {
+    bail!("configuration document must be a JSON object");
+  }
+  if serde_json::to_vec(document)?.len() > MAX_DOCUMENT_BYTES {
+    bail!("configuration document exceeds {MAX_DOCUMENT_BYTES} bytes");
+  }
+  Ok(())
+}
+
+/// Follows one service's configuration on the coordinator
+pub struct ConfigWatcher {
+  coordinator: Url,
+  service: String,
+  wait: Duration,
+  client: ResilientClient,
+}
+
+impl ConfigWatcher {
+  pub async fn new(coordinator: Url, service: impl Into<String>, wait: Duration) -> Result<Self> {
+    let wait = wait.min(Duration::from_secs(MAX_WAIT_SECS));
+    // Requests are held open for `wait`, so allow for that on top of the
+    // usual response time
+    let client = ResilientClient::builder("coordinator-config")
+      .request_timeout(wait + Duration::from_secs(10))
+      .build()
+      .await?;
+    Ok(Self {
+      coordinator,
+      service: service.into(),
+      wait,
+      client,
+    })
+  }
+
+  /// Watcher for `service` on `coordinator`, waiting `CONFIG_WATCH_WAIT_SECS`
+  /// per poll; `None` unless `CONFIG_SYNC_ENABLED=true`
+  pub async fn from_env(coordinator: Url, service: &str) -> Result<Option<Self>> {
+    let enabled = env::var("CONFIG_SYNC_ENABLED")
+      .map(|v| v.eq_ignore_ascii_case("true"))
+      .unwrap_or(false);
+    if !enabled {
+      return Ok(None);
+    }
+    let wait = env::var("CONFIG_WATCH_WAIT_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(30);
+    Ok(Some(Self::new(coordinator, service, Duration::from_secs(wait)).await?))
+  }
+
+  pub fn service(&self) -> &str {
+    &self.service
+  }
+
+  /// The first version newer than `after`, waiting up to the watch interval
+  /// for one to be published; `None` when nothing changed
+  pub async fn fetch(&self, after: u64) -> Result<Option<ServiceConfig>> {
+    let url = self
+      .coordinator
+      .join(&format!("v1/config/{}", self.service))
+      .context("invalid config endpoint")?;
+    let query = [
+      ("after", after.to_string()),
+      ("wait_secs", self.wait.as_secs().to_string()),
+    ];
+    let resp = self
+      .client
+      .send(|c| c.get(url.clone()).query(&query))
+      .await
+      .context("config watch request failed")?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+      return Ok(None);
+    }
+    let resp = resp.error_for_status().context("config watch returned error status")?;
+    let config: ServiceConfig = resp.json().await.context("failed to parse service config")?;
+    Ok((config.version > after).then_some(config))
+  }
+
+  /// Follow the configuration in the background. The receiver holds `None`
+  /// until the first version is seen.
+  pub fn spawn(self) -> (watch::Receiver<Option<ServiceConfig>>, JoinHandle<()>) {
+    let (tx, rx) = watch::channel(None);
+    let handle = tokio::spawn(async move {
+      let mut current = 0;
+      loop {
+        match self.fetch(current).await {
+          Ok(Some(config)) => {
+            info!(service = %self.service, version = config.version, "service configuration updated");
+            current = config.version;
+            if tx.send(Some(config)).is_err() {
+              return;
+            }
+          }
+          Ok(None) => debug!(service = %self.service, version = current, "service configuration unchanged"),
+          Err(e) => {
+            warn!(service = %self.service, error = %e, "service configuration watch failed");
+            tokio::time::sleep(Duration::from_secs(5)).await;
+          }
+        }
+        if tx.is_closed() {
+          return;
+        }
+      }
+    });
+    (rx, handle)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn documents_must_be_bounded_objects() {
+    assert!(validate_document(&json!({"log_level": "debug"})).is_ok());
+    assert!(validate_document(&json!(["log_level"])).is_err());
+    let big = "x".repeat(MAX_DOCUMENT_BYTES);
+    assert!(validate_document(&json!({ "blob": big })).is_err());
+  }
+
+  #[test]
+  fn optional_fields_are_omitted() {
+    let config = ServiceConfig {
+      service: "alert-service".into(),
+      version: 1,
+      document: json!({}),
+      created_at: 0,
+      created_by: None,
+      comment: None,
+      rollback_of: None,
+    };
+    let value = serde_json::to_value(&config).unwrap();
+    assert!(value.get("rollback_of").is_none());
+    assert_eq!(serde_json::from_value::<ServiceConfig>(value).unwrap(), config);
+  }
+}
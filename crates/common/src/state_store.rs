@@ -1,9 +1,80 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
 
 use crate::ai_tasks::AiTaskInfo;
+use crate::idempotency::CachedResponse;
+use crate::quota::TenantUsage;
 use crate::recordings::RecordingInfo;
+use crate::service_config::ServiceConfig;
 use crate::streams::StreamInfo;
+use crate::timeline::{TimelineEvent, TimelineQuery};
+
+/// Kind of record in the StateStore, each kept in its own table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Namespace {
+    Streams,
+    Recordings,
+    AiTasks,
+    IdempotencyKeys,
+    TenantApiUsage,
+    ServiceConfigs,
+    TimelineEvents,
+}
+
+impl Namespace {
+    pub const ALL: [Namespace; 7] = [
+        Namespace::Streams,
+        Namespace::Recordings,
+        Namespace::AiTasks,
+        Namespace::IdempotencyKeys,
+        Namespace::TenantApiUsage,
+        Namespace::ServiceConfigs,
+        Namespace::TimelineEvents,
+    ];
+
+    /// Name of the namespace, which is also its table
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Namespace::Streams => "streams",
+            Namespace::Recordings => "recordings",
+            Namespace::AiTasks => "ai_tasks",
+            Namespace::IdempotencyKeys => "idempotency_keys",
+            Namespace::TenantApiUsage => "tenant_api_usage",
+            Namespace::ServiceConfigs => "service_configs",
+            Namespace::TimelineEvents => "timeline_events",
+        }
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Namespace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Namespace::ALL
+            .into_iter()
+            .find(|ns| ns.as_str() == s)
+            .ok_or_else(|| format!("unknown state namespace '{s}'"))
+    }
+}
+
+/// Keys in a namespace and the space they take. Bytes are the backend's
+/// own measure: the table with its indexes on Postgres, the stored
+/// documents on SQLite.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceUsage {
+    pub namespace: Namespace,
+    pub keys: u64,
+    pub bytes: u64,
+}
 
 /// Trait for persistent state storage
 #[async_trait]
@@ -30,6 +101,29 @@ pub trait StateStore: Send + Sync {
     async fn update_ai_task_state(&self, task_id: &str, state: &str, error: Option<&str>) -> Result<()>;
     async fn update_ai_task_stats(&self, task_id: &str, frames_delta: u64, detections_delta: u64) -> Result<()>;
 
+    // Idempotency-Key response cache
+    async fn save_idempotency_record(&self, key: &str, record: &CachedResponse) -> Result<()>;
+    async fn get_idempotency_record(&self, key: &str) -> Result<Option<CachedResponse>>;
+
+    // Per-tenant API usage counters (see common::quota)
+    async fn add_tenant_usage(&self, tenant_id: &str, period: &str, requests: i64, rejected: i64) -> Result<TenantUsage>;
+    async fn list_tenant_usage(&self, period: &str, tenant_id: Option<&str>) -> Result<Vec<TenantUsage>>;
+
+    // Versioned service configuration (see common::service_config). Saving
+    // returns false when the version already exists.
+    async fn save_service_config(&self, config: &ServiceConfig) -> Result<bool>;
+    async fn get_service_config(&self, service: &str, version: Option<u64>) -> Result<Option<ServiceConfig>>;
+    async fn list_service_configs(&self, service: &str) -> Result<Vec<ServiceConfig>>;
+
+    // Cluster event timeline (see common::timeline). Appending skips events
+    // whose id is already stored; purging returns the number removed.
+    async fn append_timeline_events(&self, events: &[TimelineEvent]) -> Result<()>;
+    async fn query_timeline(&self, query: &TimelineQuery) -> Result<Vec<TimelineEvent>>;
+    async fn purge_timeline(&self, before_ms: u64) -> Result<u64>;
+
+    // Key count and size of every namespace
+    async fn namespace_usage(&self) -> Result<Vec<NamespaceUsage>>;
+
     // Health check
     async fn health_check(&self) -> Result<bool>;
 }
@@ -4,9 +4,13 @@ use reqwest::Client;
 use serde::Serialize;
 
 use crate::ai_tasks::AiTaskInfo;
+use crate::idempotency::CachedResponse;
+use crate::quota::{TenantUsage, UsageDelta};
 use crate::recordings::RecordingInfo;
-use crate::state_store::StateStore;
+use crate::service_config::ServiceConfig;
+use crate::state_store::{NamespaceUsage, StateStore};
 use crate::streams::StreamInfo;
+use crate::timeline::{TimelineEvent, TimelineQuery};
 
 /// HTTP client for StateStore API
 #[derive(Clone)]
@@ -240,6 +244,134 @@ impl StateStore for StateStoreClient {
         Ok(())
     }
 
+    async fn save_idempotency_record(&self, key: &str, record: &CachedResponse) -> Result<()> {
+        self.client
+            .put(self.url("/v1/state/idempotency"))
+            .query(&[("key", key)])
+            .json(record)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn get_idempotency_record(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let response = self.client
+            .get(self.url("/v1/state/idempotency"))
+            .query(&[("key", key)])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let record = response.json::<Option<CachedResponse>>().await?;
+        Ok(record)
+    }
+
+    async fn add_tenant_usage(&self, tenant_id: &str, period: &str, requests: i64, rejected: i64) -> Result<TenantUsage> {
+        let response = self.client
+            .put(self.url("/v1/state/usage"))
+            .json(&UsageDelta {
+                tenant_id: tenant_id.to_string(),
+                period: period.to_string(),
+                requests,
+                rejected,
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<TenantUsage>().await?)
+    }
+
+    async fn list_tenant_usage(&self, period: &str, tenant_id: Option<&str>) -> Result<Vec<TenantUsage>> {
+        let mut request = self.client
+            .get(self.url("/v1/state/usage"))
+            .query(&[("period", period)]);
+        if let Some(tenant_id) = tenant_id {
+            request = request.query(&[("tenant_id", tenant_id)]);
+        }
+        let response = request.send().await?.error_for_status()?;
+
+        Ok(response.json::<Vec<TenantUsage>>().await?)
+    }
+
+    async fn save_service_config(&self, config: &ServiceConfig) -> Result<bool> {
+        let response = self.client
+            .post(self.url("/v1/state/configs"))
+            .json(config)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::CONFLICT {
+            return Ok(false);
+        }
+        response.error_for_status()?;
+        Ok(true)
+    }
+
+    async fn get_service_config(&self, service: &str, version: Option<u64>) -> Result<Option<ServiceConfig>> {
+        let mut request = self.client
+            .get(self.url(&format!("/v1/state/configs/{}", service)));
+        if let Some(version) = version {
+            request = request.query(&[("version", version)]);
+        }
+        let response = request.send().await?.error_for_status()?;
+
+        Ok(response.json::<Option<ServiceConfig>>().await?)
+    }
+
+    async fn list_service_configs(&self, service: &str) -> Result<Vec<ServiceConfig>> {
+        let response = self.client
+            .get(self.url(&format!("/v1/state/configs/{}/versions", service)))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<ServiceConfig>>().await?)
+    }
+
+    async fn append_timeline_events(&self, events: &[TimelineEvent]) -> Result<()> {
+        self.client
+            .post(self.url("/v1/state/timeline"))
+            .json(events)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn query_timeline(&self, query: &TimelineQuery) -> Result<Vec<TimelineEvent>> {
+        let response = self.client
+            .get(self.url("/v1/state/timeline"))
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<TimelineEvent>>().await?)
+    }
+
+    async fn purge_timeline(&self, before_ms: u64) -> Result<u64> {
+        let response = self.client
+            .delete(self.url("/v1/state/timeline"))
+            .query(&[("before", before_ms)])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<u64>().await?)
+    }
+
+    async fn namespace_usage(&self) -> Result<Vec<NamespaceUsage>> {
+        let response = self.client
+            .get(self.url("/v1/state/namespaces"))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // Quota and growth fields of the status are not part of the usage
+        Ok(response.json::<Vec<NamespaceUsage>>().await?)
+    }
+
     async fn health_check(&self) -> Result<bool> {
         // Use coordinator health check endpoint
         let response = self.client
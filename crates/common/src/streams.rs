@@ -7,6 +7,69 @@ pub struct StreamConfig {
   pub uri: String,
   pub codec: Option<String>,
   pub container: Option<String>,
+  /// Lower-bitrate stream of the same camera, used instead of `uri` while
+  /// the site's uplink is over its bandwidth budget
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub substream_uri: Option<String>,
+  /// Thermal camera: the single-channel (8- or 16-bit) thermal image is
+  /// transcoded to H.264 in this palette instead of being copied
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub thermal_palette: Option<ThermalPalette>,
+}
+
+/// False-color palette a thermal stream is rendered in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThermalPalette {
+  /// Hot is bright
+  WhiteHot,
+  /// Hot is dark
+  BlackHot,
+  Inferno,
+  Magma,
+  Plasma,
+  Turbo,
+  Viridis,
+}
+
+impl ThermalPalette {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ThermalPalette::WhiteHot => "white_hot",
+      ThermalPalette::BlackHot => "black_hot",
+      ThermalPalette::Inferno => "inferno",
+      ThermalPalette::Magma => "magma",
+      ThermalPalette::Plasma => "plasma",
+      ThermalPalette::Turbo => "turbo",
+      ThermalPalette::Viridis => "viridis",
+    }
+  }
+
+  pub fn parse(s: &str) -> Option<Self> {
+    match s {
+      "white_hot" => Some(ThermalPalette::WhiteHot),
+      "black_hot" => Some(ThermalPalette::BlackHot),
+      "inferno" => Some(ThermalPalette::Inferno),
+      "magma" => Some(ThermalPalette::Magma),
+      "plasma" => Some(ThermalPalette::Plasma),
+      "turbo" => Some(ThermalPalette::Turbo),
+      "viridis" => Some(ThermalPalette::Viridis),
+      _ => None,
+    }
+  }
+
+  /// FFmpeg filter chain turning the thermal image into 8-bit YUV in this
+  /// palette. Intensities are stretched to the scene's range (automatic gain,
+  /// smoothed over a second of frames), which keeps 16-bit radiometric
+  /// sources from rendering as a flat gray.
+  pub fn ffmpeg_filter(&self) -> String {
+    let palette = match self {
+      ThermalPalette::WhiteHot => String::new(),
+      ThermalPalette::BlackHot => ",negate".to_string(),
+      other => format!(",pseudocolor=preset={}", other.as_str()),
+    };
+    format!("format=gbrp16le,normalize=smoothing=25:independence=0,format=gray{palette},format=yuv420p")
+  }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -0,0 +1,300 @@
+//! Tenant isolation.
+//!
+//! Mount after [`crate::auth_middleware::auth_middleware`]; every request must
+//! carry an [`AuthContext`]. Three pieces work together:
+//!
+//! - [`tenancy_middleware`] rewrites the `tenant_id` query parameter so list
+//!   endpoints taking `Query<{ tenant_id: Option<String> }>` only ever see the
+//!   caller's tenant (system admins may still pass any tenant, or none).
+//! - [`Tenant`] is an extractor for handlers; [`Tenant::scope`] forces the
+//!   tenant on body-based queries ([`ScopedQuery`]) and [`Tenant::ensure_owns`]
+//!   checks a loaded resource.
+//! - [`resource_tenancy_middleware`] guards `/:id` routes by resolving the
+//!   owning tenant of the path resource through a [`ResourceTenantResolver`].
+//!
+//! Resources owned by another tenant are reported as 404, not 403, so their
+//! existence is not leaked.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path, Request, State},
+    http::{request::Parts, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::auth_middleware::AuthContext;
+
+/// Query parameter rewritten by [`tenancy_middleware`]
+pub const TENANT_QUERY_PARAM: &str = "tenant_id";
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(serde_json::json!({ "error": "Authentication required" })),
+    )
+        .into_response()
+}
+
+fn not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": "resource not found" })),
+    )
+        .into_response()
+}
+
+/// The authenticated caller's tenant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tenant {
+    pub tenant_id: String,
+    pub is_system_admin: bool,
+}
+
+impl Tenant {
+    pub fn from_auth(ctx: &AuthContext) -> Self {
+        Self {
+            tenant_id: ctx.tenant_id.clone(),
+            is_system_admin: ctx.is_system_admin,
+        }
+    }
+
+    /// Tenant filter for store queries: `None` means "all tenants" and is only
+    /// ever returned for system admins.
+    pub fn filter(&self, requested: Option<&str>) -> Option<String> {
+        if self.is_system_admin {
+            requested.map(str::to_string)
+        } else {
+            Some(self.tenant_id.clone())
+        }
+    }
+
+    pub fn can_access(&self, owner_tenant_id: &str) -> bool {
+        self.is_system_admin || self.tenant_id == owner_tenant_id
+    }
+
+    /// 404 unless the caller may access a resource owned by `owner_tenant_id`
+    pub fn ensure_owns(&self, owner_tenant_id: &str) -> Result<(), Response> {
+        if self.can_access(owner_tenant_id) {
+            Ok(())
+        } else {
+            Err(not_found())
+        }
+    }
+
+    /// Force the tenant filter on a body-based query
+    pub fn scope<Q: ScopedQuery>(&self, query: &mut Q) {
+        let scoped = self.filter(query.tenant_id().as_deref());
+        *query.tenant_id() = scoped;
+    }
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for Tenant
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthContext>()
+            .map(Tenant::from_auth)
+            .ok_or_else(unauthorized)
+    }
+}
+
+/// Request DTOs carrying an optional tenant filter
+pub trait ScopedQuery {
+    fn tenant_id(&mut self) -> &mut Option<String>;
+}
+
+impl ScopedQuery for crate::search::RecordingSearchQuery {
+    fn tenant_id(&mut self) -> &mut Option<String> {
+        &mut self.tenant_id
+    }
+}
+
+impl ScopedQuery for crate::search::EventSearchQuery {
+    fn tenant_id(&mut self) -> &mut Option<String> {
+        &mut self.tenant_id
+    }
+}
+
+impl ScopedQuery for crate::search::ObjectSearchQuery {
+    fn tenant_id(&mut self) -> &mut Option<String> {
+        &mut self.tenant_id
+    }
+}
+
+/// Rewrite `tenant_id` in the query string to the caller's tenant
+fn scoped_uri(uri: &Uri, tenant: &Tenant) -> Option<Uri> {
+    let pairs: Vec<(String, String)> = uri
+        .query()
+        .map(|q| {
+            q.split('&')
+                .filter(|p| !p.is_empty())
+                .map(|p| {
+                    let (k, v) = p.split_once('=').unwrap_or((p, ""));
+                    (k.to_string(), v.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let requested = pairs
+        .iter()
+        .find(|(k, _)| k == TENANT_QUERY_PARAM)
+        .map(|(_, v)| v.as_str());
+    let scoped = tenant.filter(requested);
+    if scoped.as_deref() == requested {
+        return None;
+    }
+
+    let mut query: Vec<String> = pairs
+        .iter()
+        .filter(|(k, _)| k != TENANT_QUERY_PARAM)
+        .map(|(k, v)| if v.is_empty() { k.clone() } else { format!("{}={}", k, v) })
+        .collect();
+    if let Some(tenant_id) = scoped {
+        query.push(format!(
+            "{}={}",
+            TENANT_QUERY_PARAM,
+            url_encode(&tenant_id)
+        ));
+    }
+
+    let path_and_query = if query.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), query.join("&"))
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = path_and_query.parse().ok();
+    Uri::from_parts(parts).ok()
+}
+
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Requires authentication and pins the `tenant_id` query parameter to the
+/// caller's tenant. Mount with `axum::middleware::from_fn(tenancy_middleware)`.
+pub async fn tenancy_middleware(mut req: Request, next: Next) -> Response {
+    let Some(tenant) = req.extensions().get::<AuthContext>().map(Tenant::from_auth) else {
+        return unauthorized();
+    };
+
+    if let Some(uri) = scoped_uri(req.uri(), &tenant) {
+        *req.uri_mut() = uri;
+    }
+    req.extensions_mut().insert(tenant);
+    next.run(req).await
+}
+
+/// Looks up which tenant owns the resource named by the route's path params
+#[async_trait]
+pub trait ResourceTenantResolver: Send + Sync {
+    /// `Ok(None)` when the resource does not exist
+    async fn owner_tenant(&self, params: &HashMap<String, String>) -> Result<Option<String>>;
+}
+
+/// Guard for routes addressing a single resource; mount with
+/// `route_layer(middleware::from_fn_with_state(resolver, resource_tenancy_middleware))`
+/// so path params are available. Missing and foreign resources are both 404.
+pub async fn resource_tenancy_middleware(
+    State(resolver): State<Arc<dyn ResourceTenantResolver>>,
+    Path(params): Path<HashMap<String, String>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(tenant) = req.extensions().get::<AuthContext>().map(Tenant::from_auth) else {
+        return unauthorized();
+    };
+
+    match resolver.owner_tenant(&params).await {
+        Ok(Some(owner)) if tenant.can_access(&owner) => next.run(req).await,
+        Ok(_) => not_found(),
+        Err(e) => {
+            error!(error = %e, "failed to resolve resource tenant");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": "failed to resolve resource owner" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant(id: &str, admin: bool) -> Tenant {
+        Tenant {
+            tenant_id: id.to_string(),
+            is_system_admin: admin,
+        }
+    }
+
+    #[test]
+    fn test_filter_pins_non_admins() {
+        let t = tenant("t1", false);
+        assert_eq!(t.filter(None), Some("t1".to_string()));
+        assert_eq!(t.filter(Some("t2")), Some("t1".to_string()));
+
+        let admin = tenant("root", true);
+        assert_eq!(admin.filter(None), None);
+        assert_eq!(admin.filter(Some("t2")), Some("t2".to_string()));
+    }
+
+    #[test]
+    fn test_scoped_uri_rewrites_tenant_param() {
+        let uri: Uri = "/v1/devices?status=online&tenant_id=other".parse().unwrap();
+        let scoped = scoped_uri(&uri, &tenant("t1", false)).unwrap();
+        assert_eq!(scoped.path(), "/v1/devices");
+        assert_eq!(scoped.query(), Some("status=online&tenant_id=t1"));
+
+        let uri: Uri = "/v1/devices".parse().unwrap();
+        let scoped = scoped_uri(&uri, &tenant("t 1", false)).unwrap();
+        assert_eq!(scoped.query(), Some("tenant_id=t%201"));
+
+        // Already scoped, or admin: untouched
+        let uri: Uri = "/v1/devices?tenant_id=t1".parse().unwrap();
+        assert!(scoped_uri(&uri, &tenant("t1", false)).is_none());
+        assert!(scoped_uri(&uri, &tenant("root", true)).is_none());
+    }
+
+    #[test]
+    fn test_scope_body_query() {
+        let mut query: crate::search::RecordingSearchQuery =
+            serde_json::from_value(serde_json::json!({ "tenant_id": "other" })).unwrap();
+        tenant("t1", false).scope(&mut query);
+        assert_eq!(query.tenant_id.as_deref(), Some("t1"));
+    }
+
+    #[test]
+    fn test_ensure_owns() {
+        let t = tenant("t1", false);
+        assert!(t.ensure_owns("t1").is_ok());
+        assert_eq!(
+            t.ensure_owns("t2").unwrap_err().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert!(tenant("root", true).ensure_owns("t2").is_ok());
+    }
+}
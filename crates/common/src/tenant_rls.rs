@@ -0,0 +1,145 @@
+//! Postgres row-level security (RLS) for tenant-owned tables.
+//!
+//! Migrations of device-manager, alert-service and playback-service enable
+//! RLS on their tables with policies comparing each row's tenant to the
+//! [`TENANT_SETTING`] session setting. Pools built from [`pool_options`] set
+//! it on every connection they hand out, to the tenant of the enclosing
+//! [`scope`], so a query that forgets its `tenant_id` filter still only sees
+//! the caller's rows. [`crate::auth_middleware::auth_middleware`] opens the
+//! scope for every authenticated request.
+//!
+//! Outside a scope (background jobs, internal calls without credentials, and
+//! tasks spawned from a request) the setting is [`ALL_TENANTS`], which the
+//! policies treat as unrestricted. A connection with no setting at all sees
+//! nothing. Superusers and roles with `BYPASSRLS` ignore the policies, so
+//! services must connect as an ordinary role for them to apply.
+
+use axum::{
+    extract::{Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use sqlx::postgres::{PgConnection, PgPoolOptions};
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::auth_middleware::{self, AuthContext, AuthMiddlewareConfig};
+
+/// Session setting read by the RLS policies
+pub const TENANT_SETTING: &str = "app.tenant_id";
+
+/// [`TENANT_SETTING`] value granting access to every tenant's rows
+pub const ALL_TENANTS: &str = "*";
+
+tokio::task_local! {
+    static TENANT: String;
+}
+
+/// Run `fut` with every pooled connection it acquires limited to `tenant`'s
+/// rows. Tasks spawned from `fut` do not inherit the scope.
+pub async fn scope<F: Future>(tenant: String, fut: F) -> F::Output {
+    TENANT.scope(tenant, fut).await
+}
+
+/// Scope for a caller: system administrators see every tenant
+pub fn scope_of(ctx: &AuthContext) -> String {
+    if ctx.is_system_admin {
+        ALL_TENANTS.to_string()
+    } else {
+        ctx.tenant_id.clone()
+    }
+}
+
+/// Tenant of the enclosing [`scope`], or [`ALL_TENANTS`] outside one
+pub fn current() -> String {
+    TENANT
+        .try_with(String::clone)
+        .unwrap_or_else(|_| ALL_TENANTS.to_string())
+}
+
+async fn apply(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT set_config($1, $2, false)")
+        .bind(TENANT_SETTING)
+        .bind(current())
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Pool options that set [`TENANT_SETTING`] whenever a connection is
+/// acquired. Acquisition runs in the caller's task, so the setting follows
+/// the caller's [`scope`]; this costs one round-trip per acquire, which
+/// replaces the default liveness ping.
+pub fn pool_options() -> PgPoolOptions {
+    PgPoolOptions::new()
+        .test_before_acquire(false)
+        .after_connect(|conn, _meta| Box::pin(apply(conn)))
+        .before_acquire(|conn, _meta| Box::pin(async move { apply(conn).await.map(|()| true) }))
+}
+
+/// Scope requests to the caller's tenant in services that accept
+/// unauthenticated calls. Callers presenting credentials are scoped as
+/// [`crate::auth_middleware::auth_middleware`] would scope them and rejected
+/// if the credentials are invalid; calls without credentials are left
+/// unscoped.
+pub async fn tenant_scope_middleware(
+    State(config): State<Arc<AuthMiddlewareConfig>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    if !has_credentials(req.headers()) {
+        return Ok(next.run(req).await);
+    }
+    let ctx = auth_middleware::authenticate(req.headers(), &config.jwt_secret)?;
+    Ok(scope(scope_of(&ctx), next.run(req)).await)
+}
+
+fn has_credentials(headers: &HeaderMap) -> bool {
+    headers.contains_key(crate::gateway_identity::IDENTITY_HEADER)
+        || headers.contains_key(axum::http::header::AUTHORIZATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(is_system_admin: bool) -> AuthContext {
+        AuthContext {
+            user_id: "user-1".into(),
+            tenant_id: "tenant-a".into(),
+            username: "alice".into(),
+            is_system_admin,
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn system_admins_see_every_tenant() {
+        assert_eq!(scope_of(&ctx(false)), "tenant-a");
+        assert_eq!(scope_of(&ctx(true)), ALL_TENANTS);
+    }
+
+    #[tokio::test]
+    async fn scope_applies_only_inside_the_future() {
+        assert_eq!(current(), ALL_TENANTS);
+        let inner = scope("tenant-a".into(), async {
+            tokio::task::yield_now().await;
+            let nested = scope("tenant-b".into(), async { current() }).await;
+            (current(), nested)
+        })
+        .await;
+        assert_eq!(inner, ("tenant-a".to_string(), "tenant-b".to_string()));
+        assert_eq!(current(), ALL_TENANTS);
+    }
+
+    #[tokio::test]
+    async fn spawned_tasks_are_unscoped() {
+        let spawned = scope("tenant-a".into(), async {
+            tokio::spawn(async { current() }).await.unwrap()
+        })
+        .await;
+        assert_eq!(spawned, ALL_TENANTS);
+    }
+}
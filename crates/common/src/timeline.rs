@@ -0,0 +1,498 @@
+//! Cluster-wide event timeline.
+//!
+//! Services describe noteworthy events (device status changes, recording
+//! start/stops, AI detections, alerts, operator actions) as
+//! [`TimelineEvent`]s and hand them to [`record`]. A process-wide
+//! [`TimelinePublisher`] batches them in the background and ships them to the
+//! coordinator (`POST /v1/timeline/events`), which stores them centrally and
+//! serves `GET /v1/timeline` for post-incident reconstruction.
+//!
+//! Recording never blocks the caller: events are queued and dropped (with a
+//! warning) only when the coordinator stays unreachable long enough for the
+//! bounded buffer to fill up.
+
+use crate::resilient_http::ResilientClient;
+use anyhow::{bail, Context, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  collections::{HashMap, VecDeque},
+  env, fmt,
+  str::FromStr,
+  sync::{Mutex, OnceLock},
+  time::{Duration, Instant, SystemTime},
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+/// Most events accepted per ingest request
+pub const MAX_BATCH: usize = 500;
+
+/// Most events returned per query
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+const DEFAULT_QUERY_LIMIT: usize = 200;
+const MAX_SUMMARY_LEN: usize = 512;
+const MAX_FIELD_LEN: usize = 255;
+const MAX_DETAILS_BYTES: usize = 16 * 1024;
+
+/// Events queued in a publisher before the oldest are dropped
+const MAX_PENDING: usize = 10_000;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineEventKind {
+  DeviceStatus,
+  RecordingStarted,
+  RecordingStopped,
+  Detection,
+  Alert,
+  OperatorAction,
+}
+
+impl TimelineEventKind {
+  pub const ALL: [TimelineEventKind; 6] = [
+    TimelineEventKind::DeviceStatus,
+    TimelineEventKind::RecordingStarted,
+    TimelineEventKind::RecordingStopped,
+    TimelineEventKind::Detection,
+    TimelineEventKind::Alert,
+    TimelineEventKind::OperatorAction,
+  ];
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      TimelineEventKind::DeviceStatus => "device_status",
+      TimelineEventKind::RecordingStarted => "recording_started",
+      TimelineEventKind::RecordingStopped => "recording_stopped",
+      TimelineEventKind::Detection => "detection",
+      TimelineEventKind::Alert => "alert",
+      TimelineEventKind::OperatorAction => "operator_action",
+    }
+  }
+}
+
+impl fmt::Display for TimelineEventKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for TimelineEventKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    TimelineEventKind::ALL
+      .into_iter()
+      .find(|kind| kind.as_str() == s)
+      .ok_or_else(|| format!("unknown timeline event kind '{s}'"))
+  }
+}
+
+/// One entry of the timeline
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimelineEvent {
+  /// Assigned by the producer so retried deliveries are stored once
+  pub id: String,
+  /// Unix milliseconds at which the event happened
+  pub timestamp_ms: u64,
+  pub kind: TimelineEventKind,
+  /// Service that observed the event
+  pub source: String,
+  /// Device id of the camera, or the stream id when no device is known
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub camera_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tenant_id: Option<String>,
+  /// User responsible for an operator action
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub actor: Option<String>,
+  pub summary: String,
+  /// Kind-specific JSON object
+  #[serde(default, skip_serializing_if = "Value::is_null")]
+  pub details: Value,
+}
+
+pub fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+impl TimelineEvent {
+  pub fn new(kind: TimelineEventKind, source: &str, summary: impl Into<String>) -> Self {
+    Self {
+      id: uuid::Uuid::new_v4().to_string(),
+      timestamp_ms: now_ms(),
+      kind,
+      source: source.to_string(),
+      camera_id: None,
+      tenant_id: None,
+      actor: None,
+      summary: summary.into(),
+      details: Value::Null,
+    }
+  }
+
+  pub fn at(mut self, timestamp_ms: u64) -> Self {
+    self.timestamp_ms = timestamp_ms;
+    self
+  }
+
+  pub fn camera(mut self, camera_id: impl Into<String>) -> Self {
+    self.camera_id = Some(camera_id.into());
+    self
+  }
+
+  pub fn tenant(mut self, tenant_id: impl Into<String>) -> Self {
+    self.tenant_id = Some(tenant_id.into());
+    self
+  }
+
+  pub fn actor(mut self, actor: impl Into<String>) -> Self {
+    self.actor = Some(actor.into());
+    self
+  }
+
+  pub fn details(mut self, details: Value) -> Self {
+    self.details = details;
+    self
+  }
+
+  /// Reject events that would bloat or corrupt the timeline
+  pub fn validate(&self) -> Result<()> {
+    if self.id.is_empty() || self.id.len() > MAX_FIELD_LEN {
+      bail!("event id must be 1-{MAX_FIELD_LEN} bytes");
+    }
+    if self.source.is_empty() || self.source.len() > MAX_FIELD_LEN {
+      bail!("event source must be 1-{MAX_FIELD_LEN} bytes");
+    }
+    if self.summary.is_empty() || self.summary.len() > MAX_SUMMARY_LEN {
+      bail!("event summary must be 1-{MAX_SUMMARY_LEN} bytes");
+    }
+    for (field, value) in [
+      ("camera_id", &self.camera_id),
+      ("tenant_id", &self.tenant_id),
+      ("actor", &self.actor),
+    ] {
+      if value.as_ref().is_some_and(|v| v.len() > MAX_FIELD_LEN) {
+        bail!("event {field} exceeds {MAX_FIELD_LEN} bytes");
+      }
+    }
+    if !(self.details.is_null() || self.details.is_object()) {
+      bail!("event details must be a JSON object");
+    }
+    if serde_json::to_vec(&self.details)?.len() > MAX_DETAILS_BYTES {
+      bail!("event details exceed {MAX_DETAILS_BYTES} bytes");
+    }
+    Ok(())
+  }
+}
+
+/// Filter for `GET /v1/timeline`. Events come back oldest first; page by
+/// repeating the query with `from` set to the last timestamp seen.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimelineQuery {
+  /// Inclusive lower bound, unix milliseconds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub from: Option<u64>,
+  /// Exclusive upper bound, unix milliseconds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub to: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub camera_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tenant_id: Option<String>,
+  /// Comma-separated event kinds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub kind: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub limit: Option<usize>,
+}
+
+impl TimelineQuery {
+  /// Kinds to include; empty means all
+  pub fn kinds(&self) -> Result<Vec<TimelineEventKind>> {
+    let Some(kinds) = &self.kind else {
+      return Ok(Vec::new());
+    };
+    kinds
+      .split(',')
+      .map(str::trim)
+      .filter(|k| !k.is_empty())
+      .map(|k| k.parse().map_err(anyhow::Error::msg))
+      .collect()
+  }
+
+  pub fn effective_limit(&self) -> usize {
+    self.limit.unwrap_or(DEFAULT_QUERY_LIMIT).clamp(1, MAX_QUERY_LIMIT)
+  }
+
+  /// Whether `event` passes every filter except the limit
+  pub fn matches(&self, event: &TimelineEvent, kinds: &[TimelineEventKind]) -> bool {
+    self.from.is_none_or(|from| event.timestamp_ms >= from)
+      && self.to.is_none_or(|to| event.timestamp_ms < to)
+      && (kinds.is_empty() || kinds.contains(&event.kind))
+      && self
+        .camera_id
+        .as_ref()
+        .is_none_or(|camera| event.camera_id.as_ref() == Some(camera))
+      && self
+        .tenant_id
+        .as_ref()
+        .is_none_or(|tenant| event.tenant_id.as_ref() == Some(tenant))
+  }
+}
+
+/// Ships events to the coordinator in the background
+#[derive(Clone)]
+pub struct TimelinePublisher {
+  tx: mpsc::Sender<TimelineEvent>,
+}
+
+impl TimelinePublisher {
+  /// Publisher delivering to `coordinator`, plus its delivery task
+  pub async fn new(coordinator: Url) -> Result<(Self, JoinHandle<()>)> {
+    let url = coordinator
+      .join("v1/timeline/events")
+      .context("invalid timeline endpoint")?;
+    // Events carry their own ids, so a retried batch is stored once
+    let client = ResilientClient::builder("coordinator-timeline")
+      .retry_non_idempotent(true)
+      .build()
+      .await?;
+    let (tx, rx) = mpsc::channel(MAX_BATCH * 2);
+    let handle = tokio::spawn(deliver(client, url, rx));
+    Ok((Self { tx }, handle))
+  }
+
+  /// Publisher handing events to the returned receiver instead of the
+  /// network, for processes that embed the coordinator
+  pub fn in_process() -> (Self, mpsc::Receiver<TimelineEvent>) {
+    let (tx, rx) = mpsc::channel(MAX_PENDING);
+    (Self { tx }, rx)
+  }
+
+  /// Queue an event without waiting; dropped when the queue is full
+  pub fn publish(&self, event: TimelineEvent) {
+    if let Err(e) = self.tx.try_send(event) {
+      debug!(error = %e, "timeline event dropped");
+    }
+  }
+}
+
+async fn deliver(client: ResilientClient, url: Url, mut rx: mpsc::Receiver<TimelineEvent>) {
+  let mut pending: VecDeque<TimelineEvent> = VecDeque::new();
+  let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+  let mut open = true;
+  while open || !pending.is_empty() {
+    tokio::select! {
+      event = rx.recv(), if open => match event {
+        Some(event) => {
+          pending.push_back(event);
+          if pending.len() < MAX_BATCH {
+            continue;
+          }
+        }
+        None => open = false,
+      },
+      _ = ticker.tick() => {}
+    }
+
+    while !pending.is_empty() {
+      let batch: Vec<_> = pending.iter().take(MAX_BATCH).cloned().collect();
+      let sent = client
+        .send(|c| c.post(url.clone()).json(&batch))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|resp| resp.error_for_status().map_err(anyhow::Error::from));
+      match sent {
+        Ok(_) => {
+          pending.drain(..batch.len());
+        }
+        Err(e) => {
+          warn!(pending = pending.len(), error = %e, "timeline delivery failed");
+          if !open {
+            return;
+          }
+          break;
+        }
+      }
+    }
+
+    if pending.len() > MAX_PENDING {
+      let dropped = pending.len() - MAX_PENDING;
+      pending.drain(..dropped);
+      warn!(dropped, "timeline buffer full, dropped oldest events");
+    }
+  }
+}
+
+static PUBLISHER: OnceLock<TimelinePublisher> = OnceLock::new();
+
+/// Coordinator events are published to when `TIMELINE_ENABLED=true`:
+/// `TIMELINE_URL` if set, else `coordinator` (the service's own coordinator
+/// setting), else `COORDINATOR_URL`
+pub fn coordinator_from_env(coordinator: Option<Url>) -> Result<Option<Url>> {
+  let enabled = env::var("TIMELINE_ENABLED")
+    .map(|v| v.eq_ignore_ascii_case("true"))
+    .unwrap_or(false);
+  if !enabled {
+    return Ok(None);
+  }
+  let from_env = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+  let coordinator = match from_env("TIMELINE_URL") {
+    Some(url) => Url::parse(&url).context("invalid TIMELINE_URL")?,
+    None => match coordinator {
+      Some(url) => url,
+      None => {
+        let url = from_env("COORDINATOR_URL").context("TIMELINE_ENABLED requires TIMELINE_URL or COORDINATOR_URL")?;
+        Url::parse(&url).context("invalid COORDINATOR_URL")?
+      }
+    },
+  };
+  Ok(Some(coordinator))
+}
+
+/// Install the process-wide publisher when `TIMELINE_ENABLED=true`, delivering
+/// to the coordinator picked by [`coordinator_from_env`]
+pub async fn init_from_env(coordinator: Option<Url>) -> Result<()> {
+  let Some(coordinator) = coordinator_from_env(coordinator)? else {
+    return Ok(());
+  };
+  let (publisher, _) = TimelinePublisher::new(coordinator.clone()).await?;
+  if PUBLISHER.set(publisher).is_ok() {
+    info!(coordinator = %coordinator, "publishing events to the timeline");
+  }
+  Ok(())
+}
+
+/// Install `publisher` as the process-wide publisher; returns false if one
+/// is already installed
+pub fn install(publisher: TimelinePublisher) -> bool {
+  PUBLISHER.set(publisher).is_ok()
+}
+
+/// Record an event on the timeline; a no-op unless a publisher is installed
+pub fn record(event: TimelineEvent) {
+  if let Some(publisher) = PUBLISHER.get() {
+    publisher.publish(event);
+  }
+}
+
+static LAST_SAMPLED: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+
+/// Record at most one event per `key` every `every`, for high-volume
+/// sources such as per-frame detections
+pub fn record_sampled(key: &str, every: Duration, event: TimelineEvent) {
+  if PUBLISHER.get().is_some() && sample(key, every) {
+    record(event);
+  }
+}
+
+/// Whether an event for `key` is due, allowing one every `every`; callers
+/// delivering events themselves sample with this like [`record_sampled`]
+pub fn sample(key: &str, every: Duration) -> bool {
+  let now = Instant::now();
+  let mut last = match LAST_SAMPLED.get_or_init(Default::default).lock() {
+    Ok(last) => last,
+    Err(poisoned) => poisoned.into_inner(),
+  };
+  if last.get(key).is_some_and(|at| now.duration_since(*at) < every) {
+    return false;
+  }
+  last.retain(|_, at| now.duration_since(*at) < every);
+  last.insert(key.to_string(), now);
+  true
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::{extract::State, routing::post, Json, Router};
+  use serde_json::json;
+  use std::sync::Arc;
+  use tokio::sync::Mutex as AsyncMutex;
+
+  #[test]
+  fn events_are_validated() {
+    let event = TimelineEvent::new(TimelineEventKind::Alert, "alert-service", "Motion in lobby")
+      .camera("cam-1")
+      .details(json!({"severity": "high"}));
+    assert!(event.validate().is_ok());
+    assert!(event.clone().details(json!([1])).validate().is_err());
+    let mut empty = event.clone();
+    empty.summary.clear();
+    assert!(empty.validate().is_err());
+    assert!(event.camera("x".repeat(MAX_FIELD_LEN + 1)).validate().is_err());
+  }
+
+  #[test]
+  fn queries_filter_by_time_camera_and_kind() {
+    let event = TimelineEvent::new(TimelineEventKind::Detection, "ai-service", "person")
+      .at(1_000)
+      .camera("cam-1");
+    let query = TimelineQuery {
+      from: Some(1_000),
+      to: Some(2_000),
+      camera_id: Some("cam-1".into()),
+      kind: Some("detection, alert".into()),
+      ..Default::default()
+    };
+    let kinds = query.kinds().unwrap();
+    assert_eq!(kinds, [TimelineEventKind::Detection, TimelineEventKind::Alert]);
+    assert!(query.matches(&event, &kinds));
+    assert!(!query.matches(&event.clone().at(2_000), &kinds));
+    assert!(!query.matches(&event.clone().camera("cam-2"), &kinds));
+    assert!(!query.matches(&event, &[TimelineEventKind::Alert]));
+
+    let bad = TimelineQuery {
+      kind: Some("motion".into()),
+      ..Default::default()
+    };
+    assert!(bad.kinds().is_err());
+    assert_eq!(TimelineQuery::default().effective_limit(), DEFAULT_QUERY_LIMIT);
+  }
+
+  #[tokio::test]
+  async fn publisher_delivers_batches() {
+    let received: Arc<AsyncMutex<Vec<TimelineEvent>>> = Arc::default();
+    let app = Router::new()
+      .route(
+        "/v1/timeline/events",
+        post(
+          |State(received): State<Arc<AsyncMutex<Vec<TimelineEvent>>>>, Json(batch): Json<Vec<TimelineEvent>>| async move {
+            received.lock().await.extend(batch);
+          },
+        ),
+      )
+      .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let coordinator = Url::parse(&format!("http://{addr}/")).unwrap();
+    let (publisher, handle) = TimelinePublisher::new(coordinator).await.unwrap();
+    for i in 0..3 {
+      publisher.publish(TimelineEvent::new(TimelineEventKind::OperatorAction, "admin-gateway", format!("action {i}")));
+    }
+    drop(publisher);
+    tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+
+    let received = received.lock().await;
+    let summaries: Vec<_> = received.iter().map(|e| e.summary.as_str()).collect();
+    assert_eq!(summaries, ["action 0", "action 1", "action 2"]);
+  }
+
+  #[tokio::test]
+  async fn in_process_publisher_hands_events_to_receiver() {
+    let (publisher, mut events) = TimelinePublisher::in_process();
+    publisher.publish(TimelineEvent::new(TimelineEventKind::Alert, "alert-service", "Motion in lobby"));
+    drop(publisher);
+    assert_eq!(events.recv().await.unwrap().summary, "Motion in lobby");
+    assert!(events.recv().await.is_none());
+  }
+}
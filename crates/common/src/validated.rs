@@ -0,0 +1,252 @@
+//! Declarative request validation.
+//!
+//! Request DTOs derive [`validator::Validate`] and declare their constraints
+//! next to the fields; handlers take [`ValidatedJson<T>`] instead of
+//! `Json<T>`. Malformed bodies and constraint violations are rejected before
+//! the handler runs, with a consistent 422 body:
+//!
+//! ```json
+//! { "error": "validation failed", "fields": { "primary_uri": ["contains dangerous shell metacharacters"] } }
+//! ```
+//!
+//! The `custom` validators below wrap [`crate::validation`] so declarative and
+//! imperative checks enforce the same rules.
+
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::borrow::Cow;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::validation;
+
+pub use validator;
+
+/// JSON body extractor that runs `Validate::validate` after deserializing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
+
+/// Rejection for [`ValidatedJson`]
+#[derive(Debug)]
+pub enum ValidationRejection {
+    /// Body was not valid JSON or did not match the expected shape
+    Json(JsonRejection),
+    /// Body deserialized but violated declared constraints
+    Invalid(ValidationErrors),
+}
+
+impl IntoResponse for ValidationRejection {
+    fn into_response(self) -> Response {
+        match self {
+            ValidationRejection::Json(rejection) => {
+                // Syntax errors and wrong content type keep their status; shape
+                // errors (missing field, unknown enum value) are 422 like constraints
+                let status = match rejection.status() {
+                    StatusCode::BAD_REQUEST | StatusCode::UNSUPPORTED_MEDIA_TYPE => rejection.status(),
+                    _ => StatusCode::UNPROCESSABLE_ENTITY,
+                };
+                (status, Json(json!({ "error": rejection.body_text() }))).into_response()
+            }
+            ValidationRejection::Invalid(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({
+                    "error": "validation failed",
+                    "fields": field_errors(&errors),
+                })),
+            )
+                .into_response(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ValidationRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(ValidationRejection::Json)?;
+        value.validate().map_err(ValidationRejection::Invalid)?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// Flatten nested validation errors into `{ "a.b[0].c": ["message", ...] }`
+pub fn field_errors(errors: &ValidationErrors) -> Value {
+    let mut out = Map::new();
+    collect_errors(errors, "", &mut out);
+    Value::Object(out)
+}
+
+fn collect_errors(errors: &ValidationErrors, prefix: &str, out: &mut Map<String, Value>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        match kind {
+            ValidationErrorsKind::Field(errs) => {
+                let messages = errs.iter().map(|e| Value::String(message(e))).collect();
+                out.insert(path, Value::Array(messages));
+            }
+            ValidationErrorsKind::Struct(nested) => collect_errors(nested, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_errors(nested, &format!("{}[{}]", path, index), out);
+                }
+            }
+        }
+    }
+}
+
+fn message(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    match error.code.as_ref() {
+        "length" => match (error.params.get("min"), error.params.get("max")) {
+            (Some(min), Some(max)) => format!("length must be between {} and {}", min, max),
+            (Some(min), None) => format!("length must be at least {}", min),
+            (None, Some(max)) => format!("length must be at most {}", max),
+            _ => "invalid length".to_string(),
+        },
+        "range" => match (error.params.get("min"), error.params.get("max")) {
+            (Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+            (Some(min), None) => format!("must be at least {}", min),
+            (None, Some(max)) => format!("must be at most {}", max),
+            _ => "out of range".to_string(),
+        },
+        code => format!("invalid ({})", code),
+    }
+}
+
+fn to_validation_error(code: &'static str, err: anyhow::Error) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.message = Some(Cow::Owned(err.to_string()));
+    error
+}
+
+// ============================================================================
+// Custom validators for `#[validate(custom(function = "..."))]`
+// ============================================================================
+
+/// Stream/device URI: non-empty, bounded, no shell metacharacters
+pub fn uri(value: &str) -> Result<(), ValidationError> {
+    validation::validate_uri(value, "value").map_err(|e| to_validation_error("uri", e))
+}
+
+/// Human-readable name: non-empty and bounded
+pub fn name(value: &str) -> Result<(), ValidationError> {
+    validation::validate_name(value, "value").map_err(|e| to_validation_error("name", e))
+}
+
+/// Identifier: alphanumeric plus `-`/`_`
+pub fn id(value: &str) -> Result<(), ValidationError> {
+    validation::validate_id(value, "value").map_err(|e| to_validation_error("id", e))
+}
+
+/// Each tag is a valid name
+pub fn tags(values: &[String]) -> Result<(), ValidationError> {
+    values.iter().try_for_each(|tag| name(tag))
+}
+
+/// Cron expression with 5 or 6 whitespace-separated fields
+pub fn cron(value: &str) -> Result<(), ValidationError> {
+    let fields = value.split_whitespace().count();
+    if (5..=6).contains(&fields) {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("cron");
+        error.message = Some(Cow::Borrowed("must be a cron expression with 5 or 6 fields"));
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct CreateThing {
+        #[validate(custom(function = "name"))]
+        name: String,
+        #[validate(custom(function = "uri"))]
+        uri: String,
+        #[validate(range(min = 1, max = 60))]
+        interval_secs: Option<i32>,
+    }
+
+    async fn send(body: &str) -> (StatusCode, Value) {
+        let app = Router::new().route(
+            "/",
+            post(|ValidatedJson(thing): ValidatedJson<CreateThing>| async move { thing.name }),
+        );
+        let resp = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_valid_body_passes() {
+        let (status, _) = send(r#"{"name":"cam","uri":"rtsp://host/stream","interval_secs":30}"#).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_constraint_violations_are_422_with_fields() {
+        let (status, body) = send(r#"{"name":"","uri":"rtsp://x;rm -rf","interval_secs":0}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"], "validation failed");
+        assert!(body["fields"]["name"].is_array());
+        assert!(body["fields"]["uri"].is_array());
+        assert!(body["fields"]["interval_secs"][0]
+            .as_str()
+            .is_some_and(|m| m.starts_with("must be between")));
+    }
+
+    #[tokio::test]
+    async fn test_missing_field_is_422() {
+        let (status, body) = send(r#"{"name":"cam"}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_is_400() {
+        let (status, _) = send("{not json").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_cron_validator() {
+        assert!(cron("*/5 * * * *").is_ok());
+        assert!(cron("0 0 * * * *").is_ok());
+        assert!(cron("daily").is_err());
+    }
+}
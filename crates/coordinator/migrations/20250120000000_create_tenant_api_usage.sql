@@ -0,0 +1,11 @@
+-- Monthly per-tenant API request counters (see common::quota)
+CREATE TABLE IF NOT EXISTS tenant_api_usage (
+    tenant_id TEXT NOT NULL,
+    period TEXT NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    rejected BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (tenant_id, period)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tenant_api_usage_period ON tenant_api_usage (period);
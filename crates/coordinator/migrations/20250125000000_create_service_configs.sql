@@ -0,0 +1,11 @@
+-- Versioned service configuration documents (see common::service_config)
+CREATE TABLE IF NOT EXISTS service_configs (
+    service TEXT NOT NULL,
+    version BIGINT NOT NULL,
+    document JSONB NOT NULL,
+    created_at BIGINT NOT NULL,
+    created_by TEXT,
+    comment TEXT,
+    rollback_of BIGINT,
+    PRIMARY KEY (service, version)
+);
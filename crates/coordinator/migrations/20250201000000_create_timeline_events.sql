@@ -0,0 +1,15 @@
+-- Cluster event timeline (see common::timeline)
+CREATE TABLE IF NOT EXISTS timeline_events (
+    id TEXT PRIMARY KEY,
+    timestamp_ms BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    source TEXT NOT NULL,
+    camera_id TEXT,
+    tenant_id TEXT,
+    actor TEXT,
+    summary TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT 'null'
+);
+
+CREATE INDEX IF NOT EXISTS idx_timeline_events_timestamp ON timeline_events(timestamp_ms);
+CREATE INDEX IF NOT EXISTS idx_timeline_events_camera ON timeline_events(camera_id, timestamp_ms) WHERE camera_id IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Per-recording encoding (copy or transcode, see common::recordings::RecordingEncoding);
+-- NULL uses the recorder node's default
+ALTER TABLE recordings ADD COLUMN IF NOT EXISTS encoding JSONB;
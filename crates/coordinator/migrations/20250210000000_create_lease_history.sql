@@ -0,0 +1,18 @@
+-- Append-only history of lease acquisitions, renewals, releases, expiries
+-- and transfers (see common::leases::LeaseHistoryEntry)
+CREATE TABLE IF NOT EXISTS lease_history (
+    sequence BIGSERIAL PRIMARY KEY,
+    occurred_at_ms BIGINT NOT NULL,
+    event TEXT NOT NULL,
+    lease_id TEXT NOT NULL,
+    resource_id TEXT NOT NULL,
+    holder_id TEXT NOT NULL,
+    previous_holder_id TEXT,
+    kind TEXT NOT NULL,
+    expires_at_epoch_secs BIGINT NOT NULL,
+    version BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_lease_history_occurred_at ON lease_history (occurred_at_ms);
+CREATE INDEX IF NOT EXISTS idx_lease_history_resource ON lease_history (resource_id, occurred_at_ms);
+CREATE INDEX IF NOT EXISTS idx_lease_history_holder ON lease_history (holder_id, occurred_at_ms);
@@ -0,0 +1,450 @@
+//! Cluster-wide backup and restore.
+//!
+//! A backup is a single versioned JSON archive holding the rows of every
+//! table owned by the Postgres-backed services (auth, devices, alerts,
+//! retention, playback) plus the coordinator's StateStore tables. Tables are
+//! read inside one `REPEATABLE READ` transaction per database, so components
+//! sharing a database are captured from the same snapshot.
+//!
+//! Restoring replaces the contents of the selected components: all their
+//! tables are truncated and refilled in dependency order inside one
+//! transaction per database, then serial sequences are moved past the
+//! restored ids. The target schema must already be migrated to at least the
+//! version the archive was taken from.
+//!
+//! Both run with the `app.tenant_id` setting at `'*'` so row-level security
+//! policies do not hide any tenant's rows.
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+use std::collections::{BTreeMap, HashSet};
+use tracing::{info, warn};
+
+/// Version of the archive layout; bumped on incompatible changes
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Rows are inserted in batches of this size
+const INSERT_BATCH: usize = 500;
+
+/// A table included in backups
+#[derive(Debug, Clone, Copy)]
+pub struct Table {
+  pub name: &'static str,
+  /// Columns stored as NULL because their values do not survive a restore
+  pub clear: &'static [&'static str],
+}
+
+const fn table(name: &'static str) -> Table {
+  Table { name, clear: &[] }
+}
+
+/// The tables owned by one service, parents before children
+#[derive(Debug, Clone, Copy)]
+pub struct Component {
+  pub name: &'static str,
+  pub tables: &'static [Table],
+}
+
+/// Everything a cluster backup covers, in restore order. Leases and
+/// idempotency keys are short-lived and left out; StateStore rows are saved
+/// without their lease so workers re-acquire after a restore.
+pub const COMPONENTS: &[Component] = &[
+  Component {
+    name: "auth",
+    tables: &[
+      table("tenants"),
+      table("users"),
+      table("roles"),
+      table("permissions"),
+      table("role_permissions"),
+      table("user_roles"),
+      table("api_tokens"),
+      table("oidc_providers"),
+      table("oidc_user_identities"),
+      table("audit_logs"),
+      table("privacy_requests"),
+    ],
+  },
+  Component {
+    name: "devices",
+    tables: &[
+      table("devices"),
+      table("device_health_history"),
+      table("device_events"),
+      table("ptz_presets"),
+      table("ptz_tours"),
+      table("ptz_tour_steps"),
+      table("discovery_scans"),
+      table("discovered_devices"),
+      table("device_configurations"),
+      table("firmware_files"),
+      table("firmware_updates"),
+      table("firmware_update_history"),
+      table("device_clock_drift"),
+    ],
+  },
+  Component {
+    name: "alerts",
+    tables: &[
+      table("alert_rules"),
+      table("alert_actions"),
+      table("alert_events"),
+      table("alert_notifications"),
+      table("alert_suppression_state"),
+    ],
+  },
+  Component {
+    name: "retention",
+    tables: &[
+      table("retention_policies"),
+      table("retention_executions"),
+      table("retention_actions"),
+      table("storage_statistics"),
+      table("recording_index"),
+      table("event_index"),
+      table("search_query_log"),
+      table("archive_policies"),
+      table("archive_jobs"),
+    ],
+  },
+  Component {
+    name: "playback",
+    tables: &[table("playback_sessions")],
+  },
+  Component {
+    name: "state",
+    tables: &[
+      Table { name: "streams", clear: &["lease_id"] },
+      Table { name: "recordings", clear: &["lease_id"] },
+      Table { name: "ai_tasks", clear: &["lease_id"] },
+      table("service_configs"),
+      table("tenant_api_usage"),
+      table("timeline_events"),
+    ],
+  },
+];
+
+/// Look up components by name, keeping restore order; empty selects all
+pub fn select(names: &[String]) -> Result<Vec<&'static Component>> {
+  if let Some(unknown) = names.iter().find(|n| !COMPONENTS.iter().any(|c| c.name == n.as_str())) {
+    let known: Vec<_> = COMPONENTS.iter().map(|c| c.name).collect();
+    bail!("unknown component '{}' (expected one of: {})", unknown, known.join(", "));
+  }
+  Ok(COMPONENTS
+    .iter()
+    .filter(|c| names.is_empty() || names.iter().any(|n| n == c.name))
+    .collect())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupArchive {
+  pub format_version: u32,
+  pub created_at: DateTime<Utc>,
+  /// Version of the tool that wrote the archive
+  pub quadrant_version: String,
+  pub components: Vec<ComponentBackup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentBackup {
+  pub name: String,
+  /// Latest migration applied to the source database
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub schema_version: Option<i64>,
+  /// Tables that existed in the source database
+  pub tables: Vec<TableBackup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableBackup {
+  pub name: String,
+  /// One JSON object per row, keyed by column name
+  pub rows: Vec<Value>,
+}
+
+impl BackupArchive {
+  pub fn new(components: Vec<ComponentBackup>) -> Self {
+    Self {
+      format_version: FORMAT_VERSION,
+      created_at: Utc::now(),
+      quadrant_version: env!("CARGO_PKG_VERSION").to_string(),
+      components,
+    }
+  }
+
+  /// Parse an archive, rejecting layouts this build does not understand
+  pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+    let archive: Self = serde_json::from_slice(bytes).context("invalid backup archive")?;
+    if archive.format_version > FORMAT_VERSION {
+      bail!(
+        "backup archive format {} is newer than supported format {}",
+        archive.format_version,
+        FORMAT_VERSION
+      );
+    }
+    Ok(archive)
+  }
+
+  pub fn component(&self, name: &str) -> Option<&ComponentBackup> {
+    self.components.iter().find(|c| c.name == name)
+  }
+
+  /// Row counts per component and table
+  pub fn manifest(&self) -> BTreeMap<String, BTreeMap<String, usize>> {
+    self
+      .components
+      .iter()
+      .map(|c| {
+        let tables = c.tables.iter().map(|t| (t.name.clone(), t.rows.len())).collect();
+        (c.name.clone(), tables)
+      })
+      .collect()
+  }
+}
+
+fn quote_ident(name: &str) -> String {
+  format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+async fn table_exists(conn: &mut PgConnection, table: &str) -> Result<bool> {
+  let (exists,): (bool,) = sqlx::query_as("SELECT to_regclass($1) IS NOT NULL")
+    .bind(table)
+    .fetch_one(conn)
+    .await?;
+  Ok(exists)
+}
+
+async fn table_columns(conn: &mut PgConnection, table: &str) -> Result<HashSet<String>> {
+  let columns: Vec<(String,)> = sqlx::query_as(
+    "SELECT attname::text FROM pg_attribute WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped",
+  )
+  .bind(table)
+  .fetch_all(conn)
+  .await?;
+  Ok(columns.into_iter().map(|(c,)| c).collect())
+}
+
+/// Lift the row-level security tenant scope for the rest of the transaction
+async fn see_all_tenants(conn: &mut PgConnection) -> Result<()> {
+  sqlx::query("SELECT set_config('app.tenant_id', '*', true)")
+    .execute(conn)
+    .await
+    .context("failed to set tenant scope")?;
+  Ok(())
+}
+
+async fn schema_version(conn: &mut PgConnection) -> Result<Option<i64>> {
+  if !table_exists(conn, "_sqlx_migrations").await? {
+    return Ok(None);
+  }
+  let (version,): (Option<i64>,) = sqlx::query_as("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+    .fetch_one(conn)
+    .await?;
+  Ok(version)
+}
+
+/// Read `components` from one database within a single snapshot
+pub async fn snapshot(pool: &PgPool, components: &[&Component]) -> Result<Vec<ComponentBackup>> {
+  let mut tx = pool.begin().await.context("failed to start backup transaction")?;
+  sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+    .execute(&mut *tx)
+    .await?;
+  see_all_tenants(&mut tx).await?;
+  let schema_version = schema_version(&mut tx).await?;
+
+  let mut backups = Vec::with_capacity(components.len());
+  for component in components {
+    let mut tables = Vec::new();
+    for table in component.tables {
+      if !table_exists(&mut tx, table.name).await? {
+        warn!(component = component.name, table = table.name, "table missing, skipping");
+        continue;
+      }
+      let rows: Vec<(Value,)> = sqlx::query_as(&format!("SELECT to_jsonb(t) FROM {} t", quote_ident(table.name)))
+        .fetch_all(&mut *tx)
+        .await
+        .with_context(|| format!("failed to read table {}", table.name))?;
+      let rows = rows
+        .into_iter()
+        .map(|(mut row,)| {
+          if let Some(row) = row.as_object_mut() {
+            for column in table.clear {
+              row.insert(column.to_string(), Value::Null);
+            }
+          }
+          row
+        })
+        .collect::<Vec<_>>();
+      info!(component = component.name, table = table.name, rows = rows.len(), "table backed up");
+      tables.push(TableBackup {
+        name: table.name.to_string(),
+        rows,
+      });
+    }
+    backups.push(ComponentBackup {
+      name: component.name.to_string(),
+      schema_version,
+      tables,
+    });
+  }
+  tx.commit().await?;
+  Ok(backups)
+}
+
+/// Replace the contents of `components` in one database with the archived
+/// rows. Nothing is changed unless every table restores cleanly. Returns the
+/// number of rows restored per table.
+pub async fn restore(
+  pool: &PgPool,
+  components: &[&Component],
+  archive: &BackupArchive,
+) -> Result<Vec<(&'static str, usize)>> {
+  let mut tx = pool.begin().await.context("failed to start restore transaction")?;
+  see_all_tenants(&mut tx).await?;
+  let target_version = schema_version(&mut tx).await?;
+
+  // Pair every table present in the target with its archived rows
+  let mut plan: Vec<(&Table, &[Value])> = Vec::new();
+  for component in components {
+    let backup = archive
+      .component(component.name)
+      .with_context(|| format!("archive has no '{}' component", component.name))?;
+    if let (Some(source), Some(target)) = (backup.schema_version, target_version)
+      && source > target
+    {
+      bail!(
+        "'{}' was backed up at schema version {}, target database is at {}; run migrations first",
+        component.name,
+        source,
+        target
+      );
+    }
+    for table in component.tables {
+      let rows = backup
+        .tables
+        .iter()
+        .find(|t| t.name == table.name)
+        .map(|t| t.rows.as_slice())
+        .unwrap_or_default();
+      if !table_exists(&mut tx, table.name).await? {
+        if !rows.is_empty() {
+          bail!("table {} does not exist in the target database", table.name);
+        }
+        continue;
+      }
+      plan.push((table, rows));
+    }
+  }
+  if plan.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  // One statement so references between the restored tables are allowed;
+  // references from tables outside the selection make this fail
+  let names: Vec<_> = plan.iter().map(|(t, _)| quote_ident(t.name)).collect();
+  sqlx::query(&format!("TRUNCATE {}", names.join(", ")))
+    .execute(&mut *tx)
+    .await
+    .context("failed to clear tables; restore dependent components together")?;
+
+  let mut restored = Vec::with_capacity(plan.len());
+  for (table, rows) in plan {
+    let columns = table_columns(&mut tx, table.name).await?;
+    if let Some(column) = rows
+      .iter()
+      .filter_map(Value::as_object)
+      .flat_map(|row| row.keys())
+      .find(|column| !columns.contains(column.as_str()))
+    {
+      bail!(
+        "archived column {}.{} does not exist in the target database; run migrations first",
+        table.name,
+        column
+      );
+    }
+    let insert = format!(
+      "INSERT INTO {0} SELECT * FROM jsonb_populate_recordset(NULL::{0}, $1)",
+      quote_ident(table.name)
+    );
+    for batch in rows.chunks(INSERT_BATCH) {
+      sqlx::query(&insert)
+        .bind(Value::Array(batch.to_vec()))
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("failed to restore table {}", table.name))?;
+    }
+    reset_sequences(&mut tx, table.name).await?;
+    info!(table = table.name, rows = rows.len(), "table restored");
+    restored.push((table.name, rows.len()));
+  }
+  tx.commit().await.context("failed to commit restore")?;
+  Ok(restored)
+}
+
+/// Move serial sequences past the largest restored value
+async fn reset_sequences(conn: &mut PgConnection, table: &str) -> Result<()> {
+  let sequences: Vec<(String, String)> = sqlx::query_as(
+    "SELECT attname::text, pg_get_serial_sequence($1, attname) FROM pg_attribute \
+     WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped \
+     AND pg_get_serial_sequence($1, attname) IS NOT NULL",
+  )
+  .bind(table)
+  .fetch_all(&mut *conn)
+  .await?;
+  for (column, sequence) in sequences {
+    sqlx::query(&format!(
+      "SELECT setval($1, COALESCE((SELECT MAX({}) FROM {}), 0) + 1, false)",
+      quote_ident(&column),
+      quote_ident(table)
+    ))
+    .bind(sequence)
+    .execute(&mut *conn)
+    .await?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn tables_are_listed_once() {
+    let mut seen = HashSet::new();
+    for table in COMPONENTS.iter().flat_map(|c| c.tables) {
+      assert!(seen.insert(table.name), "{} listed twice", table.name);
+    }
+  }
+
+  #[test]
+  fn select_keeps_restore_order() {
+    let names = vec!["state".to_string(), "auth".to_string()];
+    let selected: Vec<_> = select(&names).unwrap().iter().map(|c| c.name).collect();
+    assert_eq!(selected, ["auth", "state"]);
+    assert_eq!(select(&[]).unwrap().len(), COMPONENTS.len());
+    assert!(select(&["leases".to_string()]).is_err());
+  }
+
+  #[test]
+  fn archives_round_trip_and_reject_newer_formats() {
+    let archive = BackupArchive::new(vec![ComponentBackup {
+      name: "playback".into(),
+      schema_version: Some(2),
+      tables: vec![TableBackup {
+        name: "playback_sessions".into(),
+        rows: vec![json!({"session_id": "s1"})],
+      }],
+    }]);
+    let bytes = serde_json::to_vec(&archive).unwrap();
+    let parsed = BackupArchive::from_slice(&bytes).unwrap();
+    assert_eq!(parsed.manifest()["playback"]["playback_sessions"], 1);
+
+    let mut newer = serde_json::to_value(&archive).unwrap();
+    newer["format_version"] = json!(FORMAT_VERSION + 1);
+    assert!(BackupArchive::from_slice(&serde_json::to_vec(&newer).unwrap()).is_err());
+  }
+}
@@ -0,0 +1,311 @@
+use crate::error::ApiError;
+use common::bandwidth::{
+  BandwidthBudgets, BandwidthDirective, BandwidthReport, BandwidthRole, UplinkBudget, UplinkStatus,
+  MISSED_REPORTS_EXPIRED,
+};
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  time::{Duration, SystemTime},
+};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Upper bound on reporting nodes; reports from new nodes beyond it are rejected
+pub const MAX_REPORTING_NODES: usize = 10_000;
+
+struct ReportEntry {
+  report: BandwidthReport,
+  received_epoch_secs: u64,
+}
+
+impl ReportEntry {
+  fn is_current_at(&self, now_epoch_secs: u64) -> bool {
+    let expire_after = self.report.interval_secs.max(1).saturating_mul(MISSED_REPORTS_EXPIRED);
+    now_epoch_secs.saturating_sub(self.received_epoch_secs) <= expire_after
+  }
+}
+
+#[derive(Default)]
+struct Totals {
+  ingest_kbps: u64,
+  remote_egress_kbps: u64,
+  local_egress_kbps: u64,
+  playback_nodes: u64,
+  nodes: Vec<String>,
+}
+
+impl Totals {
+  fn usage_kbps(&self) -> u64 {
+    self.ingest_kbps.saturating_add(self.remote_egress_kbps)
+  }
+}
+
+fn now_epoch_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or(Duration::ZERO)
+    .as_secs()
+}
+
+/// Latest bandwidth report of every node and the over-budget state of each
+/// uplink. Like node registrations this is soft state: nodes report every
+/// few seconds, so nothing is persisted and a restarted coordinator is
+/// current again after one round of reports.
+pub struct BandwidthTracker {
+  reports: RwLock<HashMap<String, ReportEntry>>,
+  /// Uplinks currently over budget
+  constrained: RwLock<HashSet<String>>,
+}
+
+impl Default for BandwidthTracker {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl BandwidthTracker {
+  pub fn new() -> Self {
+    Self {
+      reports: RwLock::new(HashMap::new()),
+      constrained: RwLock::new(HashSet::new()),
+    }
+  }
+
+  /// Store a node's report and tell it how to behave on its uplink
+  pub async fn report(
+    &self,
+    report: BandwidthReport,
+    budgets: &BandwidthBudgets,
+  ) -> Result<BandwidthDirective, ApiError> {
+    common::validation::validate_id(&report.node_id, "node_id").map_err(|e| ApiError::bad_request(e.to_string()))?;
+    common::validation::validate_id(&report.uplink, "uplink").map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let now = now_epoch_secs();
+    {
+      let mut reports = self.reports.write().await;
+      reports.retain(|_, entry| entry.is_current_at(now));
+      if !reports.contains_key(&report.node_id) && reports.len() >= MAX_REPORTING_NODES {
+        return Err(ApiError::new(
+          axum::http::StatusCode::SERVICE_UNAVAILABLE,
+          "too many nodes reporting bandwidth",
+        ));
+      }
+      reports.insert(
+        report.node_id.clone(),
+        ReportEntry {
+          report: report.clone(),
+          received_epoch_secs: now,
+        },
+      );
+    }
+
+    let Some(budget) = budgets.get(&report.uplink) else {
+      self.constrained.write().await.remove(&report.uplink);
+      return Ok(BandwidthDirective {
+        uplink: report.uplink,
+        ..Default::default()
+      });
+    };
+    let totals = self.totals(now).await;
+    let empty = Totals::default();
+    let uplink_totals = totals.get(&report.uplink).unwrap_or(&empty);
+    let constrained = self.update_constrained(budget, uplink_totals).await;
+    Ok(directive(budget, uplink_totals, constrained, report.role))
+  }
+
+  /// Usage of every uplink with a budget or a current report, ordered by name
+  pub async fn status(&self, budgets: &BandwidthBudgets) -> Vec<UplinkStatus> {
+    let totals = self.totals(now_epoch_secs()).await;
+    let constrained = self.constrained.read().await;
+    let mut uplinks: BTreeMap<&str, Option<&UplinkBudget>> =
+      budgets.uplinks.iter().map(|b| (b.uplink.as_str(), Some(b))).collect();
+    for uplink in totals.keys() {
+      uplinks.entry(uplink.as_str()).or_insert(None);
+    }
+
+    let empty = Totals::default();
+    uplinks
+      .into_iter()
+      .map(|(uplink, budget)| {
+        let totals = totals.get(uplink).unwrap_or(&empty);
+        UplinkStatus {
+          uplink: uplink.to_string(),
+          limit_kbps: budget.map(|b| b.limit_kbps),
+          ingest_kbps: totals.ingest_kbps,
+          remote_egress_kbps: totals.remote_egress_kbps,
+          local_egress_kbps: totals.local_egress_kbps,
+          usage_kbps: totals.usage_kbps(),
+          constrained: budget.is_some() && constrained.contains(uplink),
+          nodes: totals.nodes.clone(),
+        }
+      })
+      .collect()
+  }
+
+  async fn totals(&self, now_epoch_secs: u64) -> HashMap<String, Totals> {
+    let reports = self.reports.read().await;
+    let mut totals: HashMap<String, Totals> = HashMap::new();
+    for entry in reports.values().filter(|e| e.is_current_at(now_epoch_secs)) {
+      let report = &entry.report;
+      let uplink = totals.entry(report.uplink.clone()).or_default();
+      uplink.ingest_kbps = uplink.ingest_kbps.saturating_add(report.ingest_kbps);
+      uplink.remote_egress_kbps = uplink.remote_egress_kbps.saturating_add(report.remote_egress_kbps);
+      uplink.local_egress_kbps = uplink.local_egress_kbps.saturating_add(report.local_egress_kbps);
+      if report.role == BandwidthRole::Playback {
+        uplink.playback_nodes += 1;
+      }
+      uplink.nodes.push(report.node_id.clone());
+    }
+    for uplink in totals.values_mut() {
+      uplink.nodes.sort();
+    }
+    totals
+  }
+
+  /// Enter the constrained state above the budget and leave it only once
+  /// usage falls below the policy's recovery threshold
+  async fn update_constrained(&self, budget: &UplinkBudget, totals: &Totals) -> bool {
+    let usage = totals.usage_kbps();
+    let recover_below = budget.limit_kbps.saturating_mul(u64::from(budget.policy.recover_below_percent)) / 100;
+    let mut constrained = self.constrained.write().await;
+    let was = constrained.contains(&budget.uplink);
+    let now = if was { usage >= recover_below } else { usage > budget.limit_kbps };
+    if now != was {
+      if now {
+        constrained.insert(budget.uplink.clone());
+        warn!(uplink = %budget.uplink, usage_kbps = usage, limit_kbps = budget.limit_kbps, "uplink over bandwidth budget");
+      } else {
+        constrained.remove(&budget.uplink);
+        info!(uplink = %budget.uplink, usage_kbps = usage, limit_kbps = budget.limit_kbps, "uplink back within bandwidth budget");
+      }
+    }
+    now
+  }
+}
+
+/// What a node in `role` should do on a constrained or normal uplink. Remote
+/// playback gets whatever the budget leaves after camera ingest, split evenly
+/// across the uplink's playback nodes.
+fn directive(budget: &UplinkBudget, totals: &Totals, constrained: bool, role: BandwidthRole) -> BandwidthDirective {
+  let policy = &budget.policy;
+  let remote_playback_limit_kbps = (constrained && policy.throttle_remote_playback && role == BandwidthRole::Playback)
+    .then(|| {
+      let available = budget.limit_kbps.saturating_sub(totals.ingest_kbps);
+      (available / totals.playback_nodes.max(1)).max(policy.min_remote_playback_kbps)
+    });
+  BandwidthDirective {
+    uplink: budget.uplink.clone(),
+    constrained,
+    remote_playback_limit_kbps,
+    prefer_substream: constrained && policy.reduce_substream_quality && role == BandwidthRole::Stream,
+  }
+}
+
+/// The budgets document, or no budgets when it is missing or invalid
+pub fn parse_budgets(document: Option<&serde_json::Value>) -> BandwidthBudgets {
+  let Some(document) = document else {
+    return BandwidthBudgets::default();
+  };
+  match serde_json::from_value::<BandwidthBudgets>(document.clone()) {
+    Ok(budgets) => match budgets.validate() {
+      Ok(()) => budgets,
+      Err(e) => {
+        warn!(error = %e, "invalid bandwidth budgets, ignoring them");
+        BandwidthBudgets::default()
+      }
+    },
+    Err(e) => {
+      warn!(error = %e, "malformed bandwidth budgets, ignoring them");
+      BandwidthBudgets::default()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::bandwidth::BandwidthPolicy;
+
+  fn budgets(limit_kbps: u64) -> BandwidthBudgets {
+    BandwidthBudgets {
+      uplinks: vec![UplinkBudget {
+        uplink: "wan".to_string(),
+        limit_kbps,
+        policy: BandwidthPolicy::default(),
+      }],
+    }
+  }
+
+  fn report(node_id: &str, role: BandwidthRole, ingest_kbps: u64, remote_egress_kbps: u64) -> BandwidthReport {
+    BandwidthReport {
+      node_id: node_id.to_string(),
+      role,
+      uplink: "wan".to_string(),
+      ingest_kbps,
+      remote_egress_kbps,
+      local_egress_kbps: 0,
+      interval_secs: 10,
+    }
+  }
+
+  #[tokio::test]
+  async fn over_budget_throttles_playback_and_prefers_substreams() {
+    let tracker = BandwidthTracker::new();
+    let budgets = budgets(10_000);
+
+    let stream = tracker
+      .report(report("stream-1", BandwidthRole::Stream, 6_000, 0), &budgets)
+      .await
+      .unwrap();
+    assert!(!stream.constrained);
+
+    let playback = tracker
+      .report(report("playback-1", BandwidthRole::Playback, 0, 5_000), &budgets)
+      .await
+      .unwrap();
+    assert!(playback.constrained);
+    assert_eq!(playback.remote_playback_limit_kbps, Some(4_000));
+    assert!(!playback.prefer_substream);
+
+    let stream = tracker
+      .report(report("stream-1", BandwidthRole::Stream, 6_000, 0), &budgets)
+      .await
+      .unwrap();
+    assert!(stream.prefer_substream);
+    assert_eq!(stream.remote_playback_limit_kbps, None);
+  }
+
+  #[tokio::test]
+  async fn recovery_needs_usage_below_threshold() {
+    let tracker = BandwidthTracker::new();
+    let budgets = budgets(10_000);
+
+    assert!(tracker.report(report("stream-1", BandwidthRole::Stream, 11_000, 0), &budgets).await.unwrap().constrained);
+    // Under the limit but above 90% of it: still constrained
+    assert!(tracker.report(report("stream-1", BandwidthRole::Stream, 9_500, 0), &budgets).await.unwrap().constrained);
+    assert!(!tracker.report(report("stream-1", BandwidthRole::Stream, 8_000, 0), &budgets).await.unwrap().constrained);
+  }
+
+  #[tokio::test]
+  async fn uplinks_without_budget_are_reported_unconstrained() {
+    let tracker = BandwidthTracker::new();
+    let none = BandwidthBudgets::default();
+    let directive = tracker
+      .report(report("stream-1", BandwidthRole::Stream, 50_000, 0), &none)
+      .await
+      .unwrap();
+    assert!(!directive.constrained);
+
+    let status = tracker.status(&budgets(100_000)).await;
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].usage_kbps, 50_000);
+    assert_eq!(status[0].nodes, vec!["stream-1".to_string()]);
+  }
+
+  #[test]
+  fn invalid_budgets_are_ignored() {
+    let document = serde_json::json!({ "uplinks": [{ "uplink": "wan", "limit_kbps": 0 }] });
+    assert!(parse_budgets(Some(&document)).uplinks.is_empty());
+    assert!(parse_budgets(None).uplinks.is_empty());
+  }
+}
@@ -0,0 +1,210 @@
+//! Cluster-wide backup and restore for Quadrant VMS
+//!
+//! Captures the Postgres data of every service plus the coordinator's
+//! StateStore into one versioned JSON archive, and restores it. All services
+//! use `DATABASE_URL` unless a component is pointed elsewhere with
+//! `--component-url <component>=<url>`.
+//!
+//! Usage:
+//!   cluster-backup create <path>           - Write a backup archive
+//!   cluster-backup inspect <path>          - Show archive version and row counts
+//!   cluster-backup restore <path> --yes    - Replace current data with the archive
+//!
+//! Components: auth, devices, alerts, retention, playback, state
+
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
+use coordinator::backup::{self, BackupArchive, Component};
+use sqlx::PgPool;
+use std::{fs, path::PathBuf};
+use tracing::info;
+
+#[derive(Parser)]
+#[command(name = "cluster-backup")]
+#[command(about = "Cluster-wide backup and restore for Quadrant VMS", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// Database URL used by every component without an override
+    #[arg(long, env = "DATABASE_URL")]
+    database_url: Option<String>,
+
+    /// Database of one component, as <component>=<url> (repeatable)
+    #[arg(long = "component-url", value_name = "COMPONENT=URL")]
+    component_urls: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Write a backup archive
+    Create {
+        /// Output file path
+        path: PathBuf,
+
+        /// Only back up these components (repeatable; default all)
+        #[arg(long = "component")]
+        components: Vec<String>,
+
+        /// Pretty-print JSON
+        #[arg(long)]
+        pretty: bool,
+    },
+
+    /// Show archive metadata and row counts
+    Inspect {
+        /// Archive file path
+        path: PathBuf,
+    },
+
+    /// Replace the data of the selected components with the archive
+    Restore {
+        /// Archive file path
+        path: PathBuf,
+
+        /// Only restore these components (repeatable; default all in the archive)
+        #[arg(long = "component")]
+        components: Vec<String>,
+
+        /// Confirm that current data may be overwritten
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    telemetry::init();
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Commands::Create {
+            path,
+            components,
+            pretty,
+        } => create(&cli, path, components, *pretty).await?,
+        Commands::Inspect { path } => inspect(&read_archive(path)?)?,
+        Commands::Restore {
+            path,
+            components,
+            yes,
+        } => {
+            if !yes {
+                bail!("restore overwrites current data; pass --yes to confirm");
+            }
+            restore(&cli, path, components).await?
+        }
+    }
+
+    Ok(())
+}
+
+/// Group components by the database they live in, keeping restore order
+fn databases(cli: &Cli, components: Vec<&'static Component>) -> Result<Vec<(String, Vec<&'static Component>)>> {
+    let mut overrides = Vec::new();
+    for entry in &cli.component_urls {
+        let (name, url) = entry
+            .split_once('=')
+            .with_context(|| format!("invalid --component-url '{}', expected <component>=<url>", entry))?;
+        backup::select(&[name.to_string()])?;
+        overrides.push((name, url));
+    }
+
+    let mut groups: Vec<(String, Vec<&'static Component>)> = Vec::new();
+    for component in components {
+        let url = match overrides.iter().find(|(name, _)| *name == component.name) {
+            Some((_, url)) => url.to_string(),
+            None => cli.database_url.clone().with_context(|| {
+                format!(
+                    "no database for '{}': set DATABASE_URL or --component-url {}=<url>",
+                    component.name, component.name
+                )
+            })?,
+        };
+        match groups.iter_mut().find(|(group_url, _)| *group_url == url) {
+            Some((_, group)) => group.push(component),
+            None => groups.push((url, vec![component])),
+        }
+    }
+    Ok(groups)
+}
+
+async fn connect(url: &str) -> Result<PgPool> {
+    PgPool::connect(url).await.context("failed to connect to database")
+}
+
+fn read_archive(path: &PathBuf) -> Result<BackupArchive> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+    BackupArchive::from_slice(&bytes)
+}
+
+async fn create(cli: &Cli, path: &PathBuf, components: &[String], pretty: bool) -> Result<()> {
+    let selected = backup::select(components)?;
+    let mut backups = Vec::new();
+    for (url, group) in databases(cli, selected)? {
+        let pool = connect(&url).await?;
+        backups.extend(backup::snapshot(&pool, &group).await?);
+    }
+    let archive = BackupArchive::new(backups);
+
+    let json = if pretty {
+        serde_json::to_vec_pretty(&archive)?
+    } else {
+        serde_json::to_vec(&archive)?
+    };
+    // Never leave a truncated archive under the final name
+    let partial = path.with_extension("partial");
+    fs::write(&partial, json).context("failed to write backup archive")?;
+    fs::rename(&partial, path).context("failed to move backup archive into place")?;
+
+    info!(path = ?path, components = archive.components.len(), "backup written");
+    println!("{}", serde_json::to_string_pretty(&archive.manifest())?);
+
+    Ok(())
+}
+
+fn inspect(archive: &BackupArchive) -> Result<()> {
+    let summary = serde_json::json!({
+        "format_version": archive.format_version,
+        "created_at": archive.created_at,
+        "quadrant_version": archive.quadrant_version,
+        "schema_versions": archive
+            .components
+            .iter()
+            .map(|c| (c.name.clone(), c.schema_version))
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        "rows": archive.manifest(),
+    });
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+async fn restore(cli: &Cli, path: &PathBuf, components: &[String]) -> Result<()> {
+    let archive = read_archive(path)?;
+    let selected: Vec<_> = if components.is_empty() {
+        backup::select(&[])?
+            .into_iter()
+            .filter(|c| archive.component(c.name).is_some())
+            .collect()
+    } else {
+        backup::select(components)?
+    };
+
+    info!(
+        created_at = %archive.created_at,
+        quadrant_version = %archive.quadrant_version,
+        components = ?selected.iter().map(|c| c.name).collect::<Vec<_>>(),
+        "restoring backup"
+    );
+
+    let mut restored = std::collections::BTreeMap::new();
+    for (url, group) in databases(cli, selected)? {
+        let pool = connect(&url).await?;
+        for (table, rows) in backup::restore(&pool, &group, &archive).await? {
+            restored.insert(table, rows);
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&restored)?);
+
+    Ok(())
+}
@@ -0,0 +1,296 @@
+use crate::error::ApiError;
+use common::{
+  service_config::{ServiceConfig, ServiceConfigRollback, ServiceConfigUpdate, validate_document},
+  state_store::StateStore,
+};
+use serde_json::Value;
+use std::{
+  collections::HashMap,
+  sync::Arc,
+  time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::{Mutex, watch};
+use tracing::info;
+
+/// How often watchers re-read the shared store, which other coordinator
+/// replicas may write to without notifying this one
+const STORE_RECHECK: Duration = Duration::from_secs(5);
+
+const MAX_COMMENT_LEN: usize = 1024;
+
+/// Versioned configuration documents per service. Versions are kept in the
+/// StateStore when one is configured and in memory otherwise; clustered
+/// coordinators need the StateStore to share them.
+pub struct ConfigRegistry {
+  store: Option<Arc<dyn StateStore>>,
+  /// Versions per service, oldest first (memory mode only); the lock also
+  /// serializes publishes on this coordinator
+  memory: Mutex<HashMap<String, Vec<ServiceConfig>>>,
+  /// Bumped on every publish so long-polls wake up
+  changes: watch::Sender<u64>,
+}
+
+fn now_epoch_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or(Duration::ZERO)
+    .as_secs()
+}
+
+fn validate_service(service: &str) -> Result<(), ApiError> {
+  common::validation::validate_id(service, "service")
+    .map_err(|e| ApiError::bad_request(format!("invalid service: {}", e)))
+}
+
+fn validate_comment(comment: Option<&str>) -> Result<(), ApiError> {
+  match comment {
+    Some(comment) if comment.len() > MAX_COMMENT_LEN => Err(ApiError::bad_request(format!(
+      "comment exceeds {MAX_COMMENT_LEN} bytes"
+    ))),
+    _ => Ok(()),
+  }
+}
+
+impl ConfigRegistry {
+  pub fn new(store: Option<Arc<dyn StateStore>>) -> Self {
+    Self {
+      store,
+      memory: Mutex::new(HashMap::new()),
+      changes: watch::Sender::new(0),
+    }
+  }
+
+  /// The latest version, or `version` when given
+  pub async fn get(&self, service: &str, version: Option<u64>) -> Result<Option<ServiceConfig>, ApiError> {
+    validate_service(service)?;
+    if let Some(store) = &self.store {
+      return Ok(store.get_service_config(service, version).await?);
+    }
+    let memory = self.memory.lock().await;
+    let versions = memory.get(service).map(Vec::as_slice).unwrap_or_default();
+    Ok(match version {
+      Some(version) => versions.iter().find(|c| c.version == version).cloned(),
+      None => versions.last().cloned(),
+    })
+  }
+
+  /// All versions, newest first
+  pub async fn history(&self, service: &str) -> Result<Vec<ServiceConfig>, ApiError> {
+    validate_service(service)?;
+    if let Some(store) = &self.store {
+      return Ok(store.list_service_configs(service).await?);
+    }
+    let memory = self.memory.lock().await;
+    Ok(memory
+      .get(service)
+      .map(|versions| versions.iter().rev().cloned().collect())
+      .unwrap_or_default())
+  }
+
+  pub async fn publish(
+    &self,
+    service: &str,
+    update: ServiceConfigUpdate,
+    created_by: Option<String>,
+  ) -> Result<ServiceConfig, ApiError> {
+    validate_service(service)?;
+    validate_document(&update.document).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    validate_comment(update.comment.as_deref())?;
+    self
+      .append(service, update.document, update.expected_version, update.comment, created_by, None)
+      .await
+  }
+
+  /// Publish the document of an earlier version as a new version
+  pub async fn rollback(
+    &self,
+    service: &str,
+    rollback: ServiceConfigRollback,
+    created_by: Option<String>,
+  ) -> Result<ServiceConfig, ApiError> {
+    validate_comment(rollback.comment.as_deref())?;
+    let target = self
+      .get(service, Some(rollback.version))
+      .await?
+      .ok_or_else(|| ApiError::not_found(format!("version {} of '{}' not found", rollback.version, service)))?;
+    self
+      .append(service, target.document, None, rollback.comment, created_by, Some(target.version))
+      .await
+  }
+
+  async fn append(
+    &self,
+    service: &str,
+    document: Value,
+    expected_version: Option<u64>,
+    comment: Option<String>,
+    created_by: Option<String>,
+    rollback_of: Option<u64>,
+  ) -> Result<ServiceConfig, ApiError> {
+    let mut memory = self.memory.lock().await;
+    let latest = match &self.store {
+      Some(store) => store.get_service_config(service, None).await?.map(|c| c.version),
+      None => memory.get(service).and_then(|v| v.last()).map(|c| c.version),
+    }
+    .unwrap_or(0);
+    if let Some(expected) = expected_version.filter(|expected| *expected != latest) {
+      return Err(ApiError::conflict(format!(
+        "expected version {expected} of '{service}', latest is {latest}"
+      )));
+    }
+
+    let config = ServiceConfig {
+      service: service.to_string(),
+      version: latest + 1,
+      document,
+      created_at: now_epoch_secs(),
+      created_by,
+      comment,
+      rollback_of,
+    };
+    match &self.store {
+      // Another coordinator replica may have taken the version meanwhile
+      Some(store) if !store.save_service_config(&config).await? => {
+        return Err(ApiError::conflict(format!(
+          "version {} of '{}' was published concurrently",
+          config.version, service
+        )));
+      }
+      Some(_) => {}
+      None => memory.entry(service.to_string()).or_default().push(config.clone()),
+    }
+    drop(memory);
+
+    info!(
+      service = %config.service,
+      version = config.version,
+      rollback_of = ?config.rollback_of,
+      "service configuration published"
+    );
+    self.changes.send_modify(|generation| *generation += 1);
+    Ok(config)
+  }
+
+  /// The latest version if it is newer than `after`, waiting up to `wait`
+  /// for one to be published
+  pub async fn wait_for_newer(
+    &self,
+    service: &str,
+    after: u64,
+    wait: Duration,
+  ) -> Result<Option<ServiceConfig>, ApiError> {
+    let deadline = Instant::now() + wait;
+    let mut changes = self.changes.subscribe();
+    loop {
+      if let Some(config) = self.get(service, None).await?.filter(|c| c.version > after) {
+        return Ok(Some(config));
+      }
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        return Ok(None);
+      }
+      let recheck = if self.store.is_some() { remaining.min(STORE_RECHECK) } else { remaining };
+      let _ = tokio::time::timeout(recheck, changes.changed()).await;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn update(document: Value, expected_version: Option<u64>) -> ServiceConfigUpdate {
+    ServiceConfigUpdate {
+      document,
+      comment: None,
+      expected_version,
+    }
+  }
+
+  #[tokio::test]
+  async fn publish_versions_and_rollback() {
+    let registry = ConfigRegistry::new(None);
+    let v1 = registry
+      .publish("alert-service", update(json!({"level": "info"}), Some(0)), Some("alice".into()))
+      .await
+      .unwrap();
+    registry
+      .publish("alert-service", update(json!({"level": "debug"}), None), None)
+      .await
+      .unwrap();
+    assert_eq!(v1.version, 1);
+    assert_eq!(v1.created_by.as_deref(), Some("alice"));
+
+    let stale = registry
+      .publish("alert-service", update(json!({}), Some(1)), None)
+      .await
+      .unwrap_err();
+    assert!(stale.to_string().contains("409"));
+
+    let restored = registry
+      .rollback("alert-service", ServiceConfigRollback { version: 1, comment: None }, None)
+      .await
+      .unwrap();
+    assert_eq!(restored.version, 3);
+    assert_eq!(restored.rollback_of, Some(1));
+    assert_eq!(restored.document, json!({"level": "info"}));
+
+    let history: Vec<u64> = registry
+      .history("alert-service")
+      .await
+      .unwrap()
+      .iter()
+      .map(|c| c.version)
+      .collect();
+    assert_eq!(history, [3, 2, 1]);
+    assert_eq!(registry.get("alert-service", Some(2)).await.unwrap().unwrap().document["level"], "debug");
+    assert!(registry.get("ai-service", None).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn rejects_invalid_documents_and_missing_versions() {
+    let registry = ConfigRegistry::new(None);
+    assert!(registry.publish("alert-service", update(json!([1]), None), None).await.is_err());
+    assert!(registry.publish("../etc", update(json!({}), None), None).await.is_err());
+    assert!(
+      registry
+        .rollback("alert-service", ServiceConfigRollback { version: 4, comment: None }, None)
+        .await
+        .is_err()
+    );
+  }
+
+  #[tokio::test]
+  async fn watchers_wake_on_publish() {
+    let registry = Arc::new(ConfigRegistry::new(None));
+    assert!(
+      registry
+        .wait_for_newer("alert-service", 0, Duration::from_millis(10))
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    let waiter = {
+      let registry = registry.clone();
+      tokio::spawn(async move {
+        registry
+          .wait_for_newer("alert-service", 0, Duration::from_secs(10))
+          .await
+          .unwrap()
+      })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    registry
+      .publish("alert-service", update(json!({"level": "warn"}), None), None)
+      .await
+      .unwrap();
+    let config = tokio::time::timeout(Duration::from_secs(2), waiter)
+      .await
+      .unwrap()
+      .unwrap()
+      .unwrap();
+    assert_eq!(config.version, 1);
+  }
+}
@@ -24,6 +24,14 @@ impl ApiError {
     Self::new(StatusCode::BAD_REQUEST, message)
   }
 
+  pub fn not_found(message: impl Into<String>) -> Self {
+    Self::new(StatusCode::NOT_FOUND, message)
+  }
+
+  pub fn conflict(message: impl Into<String>) -> Self {
+    Self::new(StatusCode::CONFLICT, message)
+  }
+
   pub fn internal(message: impl Into<String>) -> Self {
     Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
   }
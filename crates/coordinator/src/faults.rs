@@ -0,0 +1,306 @@
+//! Fault injection for chaos tests (`fault-injection` feature).
+//!
+//! A [`FaultInjector`] sits in front of the coordinator routes and applies a
+//! [`FaultPlan`]: requests delayed by path, and heartbeats dropped so their
+//! senders see the coordinator as unreachable. Lease renewals are the
+//! heartbeats of stream, recorder and AI work, node registrations those of
+//! the nodes, and `/cluster/heartbeat` those of the coordinator leader.
+//! Leases can also be expired on the spot, as if their holder had died.
+//!
+//! The plan is changed in-process or with `/v1/faults`; neither exists in
+//! builds without the feature, so production coordinators cannot be told
+//! to misbehave.
+
+use crate::{error::ApiError, store::LeaseStore};
+use axum::{
+  Json, Router,
+  body::{Body, to_bytes},
+  extract::{Request, State},
+  http::StatusCode,
+  middleware::{self, Next},
+  response::{IntoResponse, Response},
+  routing::{get, post},
+};
+use common::{leases::LeaseRenewRequest, nodes::NodeRegisterRequest};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Largest heartbeat body inspected; larger ones are never dropped
+const MAX_HEARTBEAT_BODY: usize = 64 * 1024;
+
+/// Faults currently injected
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FaultPlan {
+  #[serde(default)]
+  pub delays: Vec<DelayFault>,
+  #[serde(default)]
+  pub dropped_heartbeats: Vec<HeartbeatDrop>,
+}
+
+impl FaultPlan {
+  pub fn is_empty(&self) -> bool {
+    self.delays.is_empty() && self.dropped_heartbeats.is_empty()
+  }
+}
+
+/// Hold requests whose path starts with `path_prefix` before handling them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DelayFault {
+  pub path_prefix: String,
+  pub delay_ms: u64,
+}
+
+/// Which heartbeats a [`HeartbeatDrop`] applies to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HeartbeatKind {
+  /// `POST /v1/leases/renew`
+  Lease,
+  /// `POST /v1/nodes/register`
+  Node,
+  /// `POST /cluster/heartbeat`
+  Cluster,
+}
+
+/// Answer matching heartbeats with 503 instead of handling them. `id`
+/// matches the holder or resource of a lease, the node id of a node and the
+/// leader id of a cluster heartbeat; without it every heartbeat of the kind
+/// is dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HeartbeatDrop {
+  pub kind: HeartbeatKind,
+  #[serde(default)]
+  pub id: Option<String>,
+}
+
+impl HeartbeatDrop {
+  fn matches(&self, kind: HeartbeatKind, ids: &[&str]) -> bool {
+    self.kind == kind && self.id.as_deref().is_none_or(|id| ids.contains(&id))
+  }
+}
+
+/// Sender of a `/cluster/heartbeat`
+#[derive(Deserialize)]
+struct ClusterHeartbeat {
+  leader_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExpireLeaseRequest {
+  pub resource_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExpireLeaseResponse {
+  pub expired: bool,
+}
+
+#[derive(Clone)]
+pub struct FaultInjector {
+  plan: Arc<RwLock<FaultPlan>>,
+  store: Arc<dyn LeaseStore>,
+}
+
+impl FaultInjector {
+  pub fn new(store: Arc<dyn LeaseStore>) -> Self {
+    Self {
+      plan: Arc::new(RwLock::new(FaultPlan::default())),
+      store,
+    }
+  }
+
+  pub async fn plan(&self) -> FaultPlan {
+    self.plan.read().await.clone()
+  }
+
+  /// Replace the injected faults
+  pub async fn set(&self, plan: FaultPlan) {
+    info!(?plan, "injecting coordinator faults");
+    *self.plan.write().await = plan;
+  }
+
+  pub async fn clear(&self) {
+    self.set(FaultPlan::default()).await;
+  }
+
+  /// Expire the lease on `resource_id` now; its holder finds out on its next
+  /// renewal, and anyone may acquire the resource meanwhile
+  pub async fn expire_lease(&self, resource_id: &str) -> anyhow::Result<bool> {
+    let expired = self.store.expire(resource_id).await?;
+    if expired {
+      warn!(resource_id, "lease expired by fault injection");
+    }
+    Ok(expired)
+  }
+
+  fn delay(plan: &FaultPlan, path: &str) -> Duration {
+    let ms = plan
+      .delays
+      .iter()
+      .filter(|d| path.starts_with(&d.path_prefix))
+      .map(|d| d.delay_ms)
+      .max()
+      .unwrap_or(0);
+    Duration::from_millis(ms)
+  }
+
+  /// Whether the heartbeat in `body`, sent to `path`, is dropped
+  async fn drops(&self, plan: &FaultPlan, path: &str, body: &[u8]) -> bool {
+    let drops = |kind: HeartbeatKind, ids: &[&str]| plan.dropped_heartbeats.iter().any(|d| d.matches(kind, ids));
+    match path {
+      "/v1/leases/renew" => {
+        let Ok(request) = serde_json::from_slice::<LeaseRenewRequest>(body) else {
+          return false;
+        };
+        // Renewals only carry the lease id
+        let Ok(leases) = self.store.list(None).await else {
+          return false;
+        };
+        match leases.iter().find(|l| l.lease_id == request.lease_id) {
+          Some(lease) => drops(HeartbeatKind::Lease, &[&lease.holder_id, &lease.resource_id]),
+          None => drops(HeartbeatKind::Lease, &[]),
+        }
+      }
+      "/v1/nodes/register" => serde_json::from_slice::<NodeRegisterRequest>(body)
+        .is_ok_and(|r| drops(HeartbeatKind::Node, &[&r.node_id])),
+      "/cluster/heartbeat" => serde_json::from_slice::<ClusterHeartbeat>(body)
+        .is_ok_and(|r| drops(HeartbeatKind::Cluster, &[&r.leader_id])),
+      _ => false,
+    }
+  }
+}
+
+/// Middleware applying the injected faults to every request
+pub async fn inject(State(faults): State<FaultInjector>, request: Request, next: Next) -> Response {
+  let plan = faults.plan().await;
+  if plan.is_empty() {
+    return next.run(request).await;
+  }
+
+  let path = request.uri().path().to_string();
+  let delay = FaultInjector::delay(&plan, &path);
+  if !delay.is_zero() {
+    tokio::time::sleep(delay).await;
+  }
+
+  if plan.dropped_heartbeats.is_empty() {
+    return next.run(request).await;
+  }
+  let (parts, body) = request.into_parts();
+  let Ok(bytes) = to_bytes(body, MAX_HEARTBEAT_BODY).await else {
+    return ApiError::bad_request("request body too large").into_response();
+  };
+  if faults.drops(&plan, &path, &bytes).await {
+    warn!(path = %path, "heartbeat dropped by fault injection");
+    return ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "heartbeat dropped by fault injection").into_response();
+  }
+  next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+/// Add the `/v1/faults` routes to `app` and inject faults into all of it
+pub fn wrap(app: Router, faults: &FaultInjector) -> Router {
+  app
+    .merge(router(faults.clone()))
+    .layer(middleware::from_fn_with_state(faults.clone(), inject))
+}
+
+/// `/v1/faults` routes controlling the injector
+pub fn router(faults: FaultInjector) -> Router {
+  Router::new()
+    .route("/v1/faults", get(get_faults).put(set_faults).delete(clear_faults))
+    .route("/v1/faults/leases/expire", post(expire_lease))
+    .with_state(faults)
+}
+
+pub const OPENAPI_OPERATIONS: &[(&str, &str, &str, &str)] = &[
+  ("GET", "/v1/faults", "faults", "Faults injected into this coordinator (fault-injection builds)"),
+  ("PUT", "/v1/faults", "faults", "Replace the injected delays and dropped heartbeats"),
+  ("DELETE", "/v1/faults", "faults", "Stop injecting faults"),
+  ("POST", "/v1/faults/leases/expire", "faults", "Expire the lease on a resource now"),
+];
+
+async fn get_faults(State(faults): State<FaultInjector>) -> Json<FaultPlan> {
+  Json(faults.plan().await)
+}
+
+async fn set_faults(State(faults): State<FaultInjector>, Json(plan): Json<FaultPlan>) -> Json<FaultPlan> {
+  faults.set(plan.clone()).await;
+  Json(plan)
+}
+
+async fn clear_faults(State(faults): State<FaultInjector>) -> Json<FaultPlan> {
+  faults.clear().await;
+  Json(FaultPlan::default())
+}
+
+async fn expire_lease(
+  State(faults): State<FaultInjector>,
+  Json(request): Json<ExpireLeaseRequest>,
+) -> Result<Json<ExpireLeaseResponse>, ApiError> {
+  let expired = faults.expire_lease(&request.resource_id).await?;
+  if !expired {
+    return Err(ApiError::not_found(format!("no lease on '{}'", request.resource_id)));
+  }
+  Ok(Json(ExpireLeaseResponse { expired }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::store::MemoryLeaseStore;
+  use common::leases::{LeaseAcquireRequest, LeaseKind};
+
+  #[tokio::test]
+  async fn drops_renewals_of_matching_holder_and_expires_leases() {
+    let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new(10, 60));
+    let faults = FaultInjector::new(store.clone());
+    let lease = store
+      .acquire(LeaseAcquireRequest {
+        resource_id: "cam1".into(),
+        holder_id: "node-a".into(),
+        kind: LeaseKind::Recorder,
+        ttl_secs: 10,
+      })
+      .await
+      .unwrap()
+      .record
+      .unwrap();
+    let renew = serde_json::to_vec(&LeaseRenewRequest {
+      lease_id: lease.lease_id.clone(),
+      ttl_secs: 10,
+    })
+    .unwrap();
+
+    let plan = |id: &str| FaultPlan {
+      delays: vec![DelayFault {
+        path_prefix: "/v1/leases".into(),
+        delay_ms: 250,
+      }],
+      dropped_heartbeats: vec![HeartbeatDrop {
+        kind: HeartbeatKind::Lease,
+        id: Some(id.into()),
+      }],
+    };
+    assert!(faults.drops(&plan("node-a"), "/v1/leases/renew", &renew).await);
+    assert!(faults.drops(&plan("cam1"), "/v1/leases/renew", &renew).await);
+    assert!(!faults.drops(&plan("node-b"), "/v1/leases/renew", &renew).await);
+    assert!(!faults.drops(&plan("node-a"), "/v1/leases/acquire", &renew).await);
+    assert_eq!(FaultInjector::delay(&plan("x"), "/v1/leases/renew"), Duration::from_millis(250));
+    assert!(FaultInjector::delay(&plan("x"), "/v1/nodes").is_zero());
+
+    assert!(faults.expire_lease("cam1").await.unwrap());
+    assert!(!faults.expire_lease("cam1").await.unwrap());
+    let takeover = store
+      .acquire(LeaseAcquireRequest {
+        resource_id: "cam1".into(),
+        holder_id: "node-b".into(),
+        kind: LeaseKind::Recorder,
+        ttl_secs: 10,
+      })
+      .await
+      .unwrap();
+    assert!(takeover.granted);
+  }
+}
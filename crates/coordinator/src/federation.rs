@@ -0,0 +1,287 @@
+use crate::error::ApiError;
+use common::federation::{
+  FederatedAlert, FederatedDevice, FederationHealth, FederationQuery, MISSED_REPORTS_UNREACHABLE, SiteRecord,
+  SiteReport, SiteStatus,
+};
+use std::{
+  collections::HashMap,
+  time::{Duration, SystemTime},
+};
+use tokio::sync::RwLock;
+
+/// Upper bound on federated sites; reports from new sites beyond it are rejected
+pub const MAX_SITES: usize = 256;
+
+struct SiteEntry {
+  report: SiteReport,
+  first_seen_epoch_secs: u64,
+  last_report_epoch_secs: u64,
+}
+
+impl SiteEntry {
+  /// Unreachable once several reports in a row have not arrived
+  fn status_at(&self, now_epoch_secs: u64) -> SiteStatus {
+    let stale_after = self.report.report_interval_secs.saturating_mul(MISSED_REPORTS_UNREACHABLE);
+    if now_epoch_secs.saturating_sub(self.last_report_epoch_secs) > stale_after {
+      SiteStatus::Unreachable
+    } else {
+      SiteStatus::Online
+    }
+  }
+
+  fn record(&self, now_epoch_secs: u64) -> SiteRecord {
+    SiteRecord {
+      site_id: self.report.site_id.clone(),
+      name: self.report.name.clone(),
+      status: self.status_at(now_epoch_secs),
+      playback_url: self.report.playback_url.clone(),
+      version: self.report.version.clone(),
+      first_seen_epoch_secs: self.first_seen_epoch_secs,
+      last_report_epoch_secs: self.last_report_epoch_secs,
+      health: self.report.health.clone(),
+      device_count: self.report.devices.len(),
+      alert_count: self.report.alerts.len(),
+    }
+  }
+}
+
+fn now_epoch_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or(Duration::ZERO)
+    .as_secs()
+}
+
+/// Latest report of every edge site known to this (central) coordinator.
+/// Unlike node registrations, sites are never expired: an unreachable site
+/// stays listed with its last known inventory until an operator removes it.
+/// Sites re-report periodically, so the registry is not persisted.
+pub struct SiteRegistry {
+  sites: RwLock<HashMap<String, SiteEntry>>,
+}
+
+impl Default for SiteRegistry {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl SiteRegistry {
+  pub fn new() -> Self {
+    Self {
+      sites: RwLock::new(HashMap::new()),
+    }
+  }
+
+  /// Store a site's report, replacing the previous one
+  pub async fn report(&self, site_id: &str, report: SiteReport) -> Result<SiteRecord, ApiError> {
+    if report.site_id != site_id {
+      return Err(ApiError::bad_request("site_id in the report does not match the path"));
+    }
+    report.validate().map_err(ApiError::bad_request)?;
+
+    let now = now_epoch_secs();
+    let mut sites = self.sites.write().await;
+    if !sites.contains_key(site_id) && sites.len() >= MAX_SITES {
+      return Err(ApiError::new(
+        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+        "site registry is full",
+      ));
+    }
+    let first_seen_epoch_secs = sites.get(site_id).map(|e| e.first_seen_epoch_secs).unwrap_or(now);
+    let entry = SiteEntry {
+      report,
+      first_seen_epoch_secs,
+      last_report_epoch_secs: now,
+    };
+    let record = entry.record(now);
+    sites.insert(site_id.to_string(), entry);
+    Ok(record)
+  }
+
+  pub async fn remove(&self, site_id: &str) -> bool {
+    self.sites.write().await.remove(site_id).is_some()
+  }
+
+  /// Every site ordered by id
+  pub async fn list(&self) -> Vec<SiteRecord> {
+    let now = now_epoch_secs();
+    let sites = self.sites.read().await;
+    let mut records: Vec<SiteRecord> = sites.values().map(|entry| entry.record(now)).collect();
+    records.sort_by(|a, b| a.site_id.cmp(&b.site_id));
+    records
+  }
+
+  pub async fn get(&self, site_id: &str) -> Option<SiteRecord> {
+    let sites = self.sites.read().await;
+    sites.get(site_id).map(|entry| entry.record(now_epoch_secs()))
+  }
+
+  /// Devices of every site matching `query`, ordered by site then device id
+  pub async fn devices(&self, query: &FederationQuery) -> Vec<FederatedDevice> {
+    let now = now_epoch_secs();
+    let sites = self.sites.read().await;
+    let mut devices: Vec<FederatedDevice> = sites
+      .iter()
+      .flat_map(|(site_id, entry)| {
+        let site_status = entry.status_at(now);
+        entry
+          .report
+          .devices
+          .iter()
+          .filter(move |device| query.matches_device(site_id, device))
+          .map(move |device| FederatedDevice {
+            site_id: site_id.clone(),
+            site_status,
+            as_of_epoch_secs: entry.last_report_epoch_secs,
+            device: device.clone(),
+          })
+      })
+      .collect();
+    devices.sort_by(|a, b| (&a.site_id, &a.device.device_id).cmp(&(&b.site_id, &b.device.device_id)));
+    devices
+  }
+
+  /// Recent alerts of every site matching `query`, newest first
+  pub async fn alerts(&self, query: &FederationQuery) -> Vec<FederatedAlert> {
+    let sites = self.sites.read().await;
+    let mut alerts: Vec<FederatedAlert> = sites
+      .iter()
+      .flat_map(|(site_id, entry)| {
+        entry
+          .report
+          .alerts
+          .iter()
+          .filter(move |alert| query.matches_alert(site_id, alert))
+          .map(move |alert| FederatedAlert {
+            site_id: site_id.clone(),
+            alert: alert.clone(),
+          })
+      })
+      .collect();
+    alerts.sort_by_key(|a| std::cmp::Reverse(a.alert.fired_at_ms));
+    alerts
+  }
+
+  pub async fn health(&self) -> FederationHealth {
+    let mut health = FederationHealth::default();
+    for site in self.list().await {
+      health.sites_total += 1;
+      health.devices_total += site.device_count;
+      health.alerts_total += site.alert_count;
+      match site.status {
+        SiteStatus::Online => {
+          health.sites_online += 1;
+          if site.health.status != "healthy" {
+            health.sites_degraded += 1;
+          }
+        }
+        SiteStatus::Unreachable => health.sites_unreachable += 1,
+      }
+    }
+    health
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::federation::{SiteAlert, SiteDevice, SiteHealth};
+
+  fn report(site_id: &str, devices: &[(&str, &str)], alerts: &[u64]) -> SiteReport {
+    SiteReport {
+      site_id: site_id.to_string(),
+      name: site_id.to_uppercase(),
+      playback_url: None,
+      version: None,
+      report_interval_secs: 30,
+      generated_at_epoch_secs: 0,
+      health: SiteHealth {
+        status: "healthy".into(),
+        ..Default::default()
+      },
+      devices: devices
+        .iter()
+        .map(|(device_id, status)| SiteDevice {
+          device_id: device_id.to_string(),
+          tenant_id: "tenant-a".into(),
+          name: device_id.to_string(),
+          status: status.to_string(),
+          location: None,
+        })
+        .collect(),
+      alerts: alerts
+        .iter()
+        .map(|fired_at_ms| SiteAlert {
+          id: format!("{site_id}-{fired_at_ms}"),
+          tenant_id: Some("tenant-a".into()),
+          camera_id: None,
+          severity: Some("critical".into()),
+          message: "tamper".into(),
+          fired_at_ms: *fired_at_ms,
+        })
+        .collect(),
+    }
+  }
+
+  #[tokio::test]
+  async fn aggregates_devices_and_alerts_across_sites() {
+    let registry = SiteRegistry::new();
+    registry
+      .report("east", report("east", &[("cam-2", "online"), ("cam-1", "offline")], &[1_000]))
+      .await
+      .unwrap();
+    registry
+      .report("west", report("west", &[("cam-1", "online")], &[2_000]))
+      .await
+      .unwrap();
+
+    let all: Vec<_> = registry
+      .devices(&FederationQuery::default())
+      .await
+      .into_iter()
+      .map(|d| format!("{}/{}", d.site_id, d.device.device_id))
+      .collect();
+    assert_eq!(all, ["east/cam-1", "east/cam-2", "west/cam-1"]);
+
+    let online = FederationQuery {
+      status: Some("online".into()),
+      ..Default::default()
+    };
+    assert_eq!(registry.devices(&online).await.len(), 2);
+
+    let alerts = registry.alerts(&FederationQuery::default()).await;
+    assert_eq!(alerts[0].site_id, "west");
+
+    let health = registry.health().await;
+    assert_eq!((health.sites_total, health.sites_online, health.devices_total), (2, 2, 3));
+  }
+
+  #[tokio::test]
+  async fn silent_sites_become_unreachable_but_keep_their_inventory() {
+    let registry = SiteRegistry::new();
+    registry.report("east", report("east", &[("cam-1", "online")], &[])).await.unwrap();
+    registry.sites.write().await.get_mut("east").unwrap().last_report_epoch_secs -= 91;
+
+    let site = registry.get("east").await.unwrap();
+    assert_eq!(site.status, SiteStatus::Unreachable);
+    let devices = registry.devices(&FederationQuery::default()).await;
+    assert_eq!(devices[0].site_status, SiteStatus::Unreachable);
+    assert_eq!(registry.health().await.sites_unreachable, 1);
+
+    // The next report brings it back without resetting first_seen
+    let first_seen = site.first_seen_epoch_secs;
+    let record = registry.report("east", report("east", &[], &[])).await.unwrap();
+    assert_eq!(record.status, SiteStatus::Online);
+    assert_eq!(record.first_seen_epoch_secs, first_seen);
+  }
+
+  #[tokio::test]
+  async fn rejects_mismatched_and_invalid_reports() {
+    let registry = SiteRegistry::new();
+    assert!(registry.report("west", report("east", &[], &[])).await.is_err());
+    assert!(registry.report("../x", report("../x", &[], &[])).await.is_err());
+    assert!(registry.list().await.is_empty());
+    assert!(!registry.remove("east").await);
+  }
+}
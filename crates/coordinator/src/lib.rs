@@ -1,8 +1,20 @@
+pub mod backup;
+pub mod bandwidth;
 pub mod cluster;
 pub mod config;
+pub mod configs;
 pub mod error;
+#[cfg(feature = "fault-injection")]
+pub mod faults;
+pub mod federation;
+pub mod namespaces;
+pub mod nodes;
 pub mod pg_state_store;
+pub mod reconcile;
 pub mod routes;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_state_store;
 pub mod state;
 pub mod state_routes;
 pub mod store;
+pub mod timeline;
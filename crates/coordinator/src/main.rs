@@ -4,9 +4,10 @@ use coordinator::{
   cluster::ClusterManager,
   config::{CoordinatorConfig, LeaseStoreType},
   pg_state_store::PgStateStore,
+  reconcile::{self, ReconcileConfig, Reconciler},
   routes,
   state::CoordinatorState,
-  store::{LeaseStore, MemoryLeaseStore, PostgresLeaseStore},
+  store::{self, LeaseStore, MemoryLeaseStore, PostgresLeaseStore},
 };
 use std::sync::Arc;
 use tokio::net::TcpListener;
@@ -91,7 +92,42 @@ async fn main() -> Result<()> {
     CoordinatorState::new(config.clone(), store, state_store)
   };
 
-  let app = routes::router(state.clone());
+  // Keep the event timeline bounded; TIMELINE_RETENTION_DAYS=0 keeps everything
+  let retention_days = std::env::var("TIMELINE_RETENTION_DAYS")
+    .ok()
+    .and_then(|v| v.parse::<u64>().ok())
+    .unwrap_or(30);
+  if retention_days > 0 {
+    state
+      .timeline()
+      .spawn_retention(std::time::Duration::from_secs(retention_days * 24 * 3600));
+  }
+
+  // Measure StateStore namespaces for quotas and leak alerts
+  if state.state_store().is_some() {
+    state.namespaces().spawn(state.timeline());
+  }
+
+  // Keep the lease history bounded; LEASE_HISTORY_RETENTION_DAYS=0 keeps everything
+  tokio::spawn(store::run_history_pruner(state.store()));
+
+  let mut app = routes::router(state.clone());
+  if let Some(reconcile_config) = ReconcileConfig::from_env()? {
+    let reconciler = Arc::new(Reconciler::new(reconcile_config, state.clone())?);
+    info!(
+      interval_secs = reconciler.config().interval.as_secs(),
+      dry_run = reconciler.config().dry_run,
+      "desired/actual state reconciliation enabled"
+    );
+    reconciler.clone().spawn();
+    app = app.merge(reconcile::router(reconciler));
+  }
+  #[cfg(feature = "fault-injection")]
+  let app = {
+    tracing::warn!("fault injection enabled: /v1/faults can delay requests, drop heartbeats and expire leases");
+    coordinator::faults::wrap(app, &coordinator::faults::FaultInjector::new(state.store()))
+  };
+  let app = routes::versioned(app);
   let listener = TcpListener::bind(bind_addr).await?;
 
   info!(
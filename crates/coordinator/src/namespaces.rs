@@ -0,0 +1,326 @@
+//! StateStore namespace usage, quotas and leak detection.
+//!
+//! Every service keeps its state in the coordinator's StateStore, one
+//! namespace (table) per kind of record. The [`NamespaceMonitor`] measures
+//! each namespace every `STATE_NAMESPACE_INTERVAL_SECS` and:
+//!
+//! - rejects writes that would add keys to a namespace over its quota
+//!   (`STATE_QUOTA_<NAMESPACE>_KEYS` / `_BYTES`, e.g.
+//!   `STATE_QUOTA_AI_TASKS_KEYS`), as of the last measurement; updates of
+//!   existing keys still go through
+//! - flags a leak when a namespace that should stay bounded (streams,
+//!   recordings, AI tasks: one key per live resource) only grew over
+//!   `STATE_LEAK_WINDOW_SECS`, by at least `STATE_LEAK_MIN_KEYS`
+//!
+//! Crossing a quota or starting to leak is logged and recorded on the
+//! timeline as an `alert` event.
+
+use crate::{error::ApiError, timeline::Timeline};
+use axum::http::StatusCode;
+use common::{
+  state_store::{Namespace, NamespaceUsage, StateStore},
+  timeline::{TimelineEvent, TimelineEventKind, now_ms},
+};
+use serde::Serialize;
+use std::{
+  collections::{HashMap, VecDeque},
+  future::Future,
+  sync::Arc,
+  time::Duration,
+};
+use tokio::{sync::RwLock, task::JoinHandle};
+use tracing::{info, warn};
+
+/// Most keys per namespace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NamespaceQuota {
+  pub max_keys: Option<u64>,
+  pub max_bytes: Option<u64>,
+}
+
+impl NamespaceQuota {
+  fn exceeded_by(&self, usage: &NamespaceUsage) -> bool {
+    self.max_keys.is_some_and(|max| usage.keys >= max) || self.max_bytes.is_some_and(|max| usage.bytes >= max)
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct NamespaceConfig {
+  pub interval: Duration,
+  pub quotas: HashMap<Namespace, NamespaceQuota>,
+  pub leak_window: Duration,
+  pub leak_min_keys: u64,
+}
+
+impl Default for NamespaceConfig {
+  fn default() -> Self {
+    Self {
+      interval: Duration::from_secs(60),
+      quotas: HashMap::new(),
+      leak_window: Duration::from_secs(6 * 3600),
+      leak_min_keys: 1000,
+    }
+  }
+}
+
+impl NamespaceConfig {
+  pub fn from_env() -> Self {
+    let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+    let defaults = Self::default();
+    let quotas = Namespace::ALL
+      .into_iter()
+      .filter_map(|namespace| {
+        let prefix = format!("STATE_QUOTA_{}", namespace.as_str().to_uppercase());
+        let quota = NamespaceQuota {
+          max_keys: var(&format!("{prefix}_KEYS")).filter(|v| *v > 0),
+          max_bytes: var(&format!("{prefix}_BYTES")).filter(|v| *v > 0),
+        };
+        (quota != NamespaceQuota::default()).then_some((namespace, quota))
+      })
+      .collect();
+    Self {
+      interval: var("STATE_NAMESPACE_INTERVAL_SECS")
+        .filter(|v| *v > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(defaults.interval),
+      quotas,
+      leak_window: var("STATE_LEAK_WINDOW_SECS")
+        .filter(|v| *v > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(defaults.leak_window),
+      leak_min_keys: var("STATE_LEAK_MIN_KEYS").unwrap_or(defaults.leak_min_keys),
+    }
+  }
+}
+
+/// Namespaces holding one key per live stream, recording or task; the
+/// others grow by design (timeline, config versions, usage periods) or
+/// expire their keys (idempotency)
+fn bounded(namespace: Namespace) -> bool {
+  matches!(namespace, Namespace::Streams | Namespace::Recordings | Namespace::AiTasks)
+}
+
+/// Usage of a namespace as of the last measurement, served at
+/// `GET /v1/state/namespaces`
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceStatus {
+  #[serde(flatten)]
+  pub usage: NamespaceUsage,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_keys: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_bytes: Option<u64>,
+  pub over_quota: bool,
+  /// Keys gained over the leak window (or since the coordinator started),
+  /// negative when the namespace shrank
+  pub growth: i64,
+  pub leaking: bool,
+  pub sampled_at: u64,
+}
+
+/// Key counts of one namespace, oldest first, as (ms, keys)
+type Samples = VecDeque<(u64, u64)>;
+
+pub struct NamespaceMonitor {
+  store: Option<Arc<dyn StateStore>>,
+  config: NamespaceConfig,
+  samples: RwLock<HashMap<Namespace, Samples>>,
+  status: RwLock<HashMap<Namespace, NamespaceStatus>>,
+}
+
+impl NamespaceMonitor {
+  pub fn new(store: Option<Arc<dyn StateStore>>, config: NamespaceConfig) -> Self {
+    Self {
+      store,
+      config,
+      samples: RwLock::new(HashMap::new()),
+      status: RwLock::new(HashMap::new()),
+    }
+  }
+
+  /// Namespaces as of the last measurement, measuring now when none was
+  /// taken yet
+  pub async fn status(&self, timeline: &Timeline) -> Result<Vec<NamespaceStatus>, ApiError> {
+    if self.status.read().await.is_empty() {
+      self.refresh(timeline).await?;
+    }
+    let mut status: Vec<_> = self.status.read().await.values().cloned().collect();
+    status.sort_by_key(|s| Namespace::ALL.iter().position(|ns| *ns == s.usage.namespace));
+    Ok(status)
+  }
+
+  /// Measure every namespace, update the gauges and raise alerts for
+  /// namespaces that crossed their quota or started to leak
+  pub async fn refresh(&self, timeline: &Timeline) -> Result<(), ApiError> {
+    let Some(store) = &self.store else {
+      return Err(ApiError::bad_request("StateStore not configured (use LEASE_STORE_TYPE=postgres)"));
+    };
+    let usage = store.namespace_usage().await?;
+    let now = now_ms();
+    let window_ms = self.config.leak_window.as_millis() as u64;
+
+    let mut alerts = Vec::new();
+    {
+      let mut samples = self.samples.write().await;
+      let mut status = self.status.write().await;
+      for usage in usage {
+        let namespace = usage.namespace;
+        let samples = samples.entry(namespace).or_default();
+        samples.push_back((now, usage.keys));
+        // Keep one sample at or before the start of the window
+        while samples.len() > 2 && samples[1].0 + window_ms <= now {
+          samples.pop_front();
+        }
+
+        let quota = self.config.quotas.get(&namespace).copied().unwrap_or_default();
+        let over_quota = quota.exceeded_by(&usage);
+        let growth = samples.front().map_or(0, |(_, first)| usage.keys as i64 - *first as i64);
+        let leaking = bounded(namespace) && leaking(samples, now, window_ms, self.config.leak_min_keys);
+
+        let label = namespace.as_str();
+        telemetry::metrics::COORDINATOR_STATE_NAMESPACE_KEYS
+          .with_label_values(&[label])
+          .set(usage.keys as i64);
+        telemetry::metrics::COORDINATOR_STATE_NAMESPACE_BYTES
+          .with_label_values(&[label])
+          .set(usage.bytes as i64);
+        telemetry::metrics::COORDINATOR_STATE_NAMESPACE_LEAKING
+          .with_label_values(&[label])
+          .set(i64::from(leaking));
+
+        let previous = status.get(&namespace);
+        if over_quota && !previous.is_some_and(|s| s.over_quota) {
+          warn!(namespace = label, keys = usage.keys, bytes = usage.bytes, "StateStore namespace over its quota, rejecting new keys");
+          alerts.push(format!(
+            "StateStore namespace {} is over its quota ({} keys, {} bytes); new keys are rejected",
+            label, usage.keys, usage.bytes
+          ));
+        } else if !over_quota && previous.is_some_and(|s| s.over_quota) {
+          info!(namespace = label, keys = usage.keys, "StateStore namespace back under its quota");
+        }
+        if leaking && !previous.is_some_and(|s| s.leaking) {
+          warn!(namespace = label, keys = usage.keys, growth, "StateStore namespace keeps growing, a service may be leaking keys");
+          alerts.push(format!(
+            "StateStore namespace {} grew by {} keys to {} without ever shrinking; a service may be leaking keys",
+            label, growth, usage.keys
+          ));
+        }
+
+        status.insert(
+          namespace,
+          NamespaceStatus {
+            usage,
+            max_keys: quota.max_keys,
+            max_bytes: quota.max_bytes,
+            over_quota,
+            growth,
+            leaking,
+            sampled_at: now,
+          },
+        );
+      }
+    }
+
+    if !alerts.is_empty() {
+      let events = alerts
+        .into_iter()
+        .map(|summary| TimelineEvent::new(TimelineEventKind::Alert, "coordinator", summary))
+        .collect();
+      if let Err(e) = timeline.append(events).await {
+        warn!(error = %e, "failed to record StateStore namespace alert");
+      }
+    }
+    Ok(())
+  }
+
+  /// Allow a write to `namespace`; when it is over its quota, only writes
+  /// to keys that already exist (`exists` is only awaited then)
+  pub async fn admit<F>(&self, namespace: Namespace, exists: F) -> Result<(), ApiError>
+  where
+    F: Future<Output = anyhow::Result<bool>>,
+  {
+    let over_quota = self
+      .status
+      .read()
+      .await
+      .get(&namespace)
+      .is_some_and(|s| s.over_quota);
+    if !over_quota || exists.await? {
+      return Ok(());
+    }
+    telemetry::metrics::COORDINATOR_STATE_QUOTA_REJECTIONS
+      .with_label_values(&[namespace.as_str()])
+      .inc();
+    Err(ApiError::new(
+      StatusCode::INSUFFICIENT_STORAGE,
+      format!("StateStore namespace {namespace} is over its quota"),
+    ))
+  }
+
+  /// Measure the namespaces every `STATE_NAMESPACE_INTERVAL_SECS`
+  pub fn spawn(self: Arc<Self>, timeline: Arc<Timeline>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(self.config.interval);
+      loop {
+        ticker.tick().await;
+        if let Err(e) = self.refresh(&timeline).await {
+          warn!(error = %e, "failed to measure StateStore namespaces");
+        }
+      }
+    })
+  }
+}
+
+/// Whether the samples cover the window, never went down and grew by at
+/// least `min_keys`
+fn leaking(samples: &Samples, now: u64, window_ms: u64, min_keys: u64) -> bool {
+  let (Some((first_at, first)), Some((_, last))) = (samples.front(), samples.back()) else {
+    return false;
+  };
+  *first_at + window_ms <= now
+    && samples.iter().zip(samples.iter().skip(1)).all(|(a, b)| b.1 >= a.1)
+    && last.saturating_sub(*first) >= min_keys
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn samples(points: &[(u64, u64)]) -> Samples {
+    points.iter().copied().collect()
+  }
+
+  #[test]
+  fn leaks_need_steady_growth_over_the_whole_window() {
+    let hour = 3_600_000;
+    let growing = samples(&[(0, 100), (hour, 700), (2 * hour, 1400)]);
+    assert!(leaking(&growing, 2 * hour, 2 * hour, 1000));
+    // Window not covered yet
+    assert!(!leaking(&growing, 2 * hour, 3 * hour, 1000));
+    // Not enough growth
+    assert!(!leaking(&growing, 2 * hour, 2 * hour, 2000));
+    // Keys were deleted in between
+    let sawtooth = samples(&[(0, 100), (hour, 1500), (2 * hour, 1400), (3 * hour, 2000)]);
+    assert!(!leaking(&sawtooth, 3 * hour, 3 * hour, 1000));
+  }
+
+  #[test]
+  fn quotas_count_keys_and_bytes() {
+    let usage = NamespaceUsage {
+      namespace: Namespace::AiTasks,
+      keys: 500,
+      bytes: 2048,
+    };
+    assert!(!NamespaceQuota::default().exceeded_by(&usage));
+    let keys = NamespaceQuota {
+      max_keys: Some(500),
+      max_bytes: None,
+    };
+    assert!(keys.exceeded_by(&usage));
+    let bytes = NamespaceQuota {
+      max_keys: Some(10_000),
+      max_bytes: Some(4096),
+    };
+    assert!(!bytes.exceeded_by(&usage));
+  }
+}
@@ -0,0 +1,186 @@
+use crate::error::ApiError;
+use common::nodes::{NodeKind, NodeRecord, NodeRegisterRequest};
+use reqwest::Url;
+use std::{
+  collections::HashMap,
+  time::{Duration, SystemTime},
+};
+use tokio::sync::RwLock;
+
+/// Upper bound on tracked nodes; registrations beyond it are rejected
+pub const MAX_REGISTERED_NODES: usize = 1024;
+
+/// Soft-state registry of stream, recorder and AI nodes. Entries live only as
+/// long as their owners keep re-registering, so nothing is persisted.
+pub struct NodeRegistry {
+  nodes: RwLock<HashMap<String, NodeRecord>>,
+  max_ttl_secs: u64,
+}
+
+fn now_epoch_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or(Duration::ZERO)
+    .as_secs()
+}
+
+impl NodeRegistry {
+  pub fn new(max_ttl_secs: u64) -> Self {
+    Self {
+      nodes: RwLock::new(HashMap::new()),
+      max_ttl_secs: max_ttl_secs.max(1),
+    }
+  }
+
+  /// Insert or refresh a registration
+  pub async fn register(&self, request: NodeRegisterRequest) -> Result<NodeRecord, ApiError> {
+    common::validation::validate_id(&request.node_id, "node_id")
+      .map_err(|e| ApiError::bad_request(format!("invalid node_id: {}", e)))?;
+    let base_url = Url::parse(&request.base_url)
+      .map_err(|e| ApiError::bad_request(format!("invalid base_url: {}", e)))?;
+    if !matches!(base_url.scheme(), "http" | "https") {
+      return Err(ApiError::bad_request("base_url must be http or https"));
+    }
+    if let Some(version) = &request.version {
+      common::validation::validate_id(version, "version")
+        .map_err(|e| ApiError::bad_request(format!("invalid version: {}", e)))?;
+    }
+
+    let now = now_epoch_secs();
+    let ttl = request.ttl_secs.clamp(1, self.max_ttl_secs);
+    let mut nodes = self.nodes.write().await;
+    nodes.retain(|_, record| !record.is_expired_at(now));
+
+    if !nodes.contains_key(&request.node_id) && nodes.len() >= MAX_REGISTERED_NODES {
+      return Err(ApiError::new(
+        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+        "node registry is full",
+      ));
+    }
+
+    // A heartbeat must not cancel an operator's drain
+    let draining = nodes
+      .get(&request.node_id)
+      .is_some_and(|existing| existing.draining);
+
+    // Re-registering under a new kind, URL or version replaces the entry but
+    // keeps the original registration time only when nothing changed
+    let registered_at = nodes
+      .get(&request.node_id)
+      .filter(|existing| {
+        existing.kind == request.kind
+          && existing.base_url == base_url.as_str()
+          && existing.version == request.version
+      })
+      .map(|existing| existing.registered_at_epoch_secs)
+      .unwrap_or(now);
+
+    let record = NodeRecord {
+      node_id: request.node_id.clone(),
+      kind: request.kind,
+      base_url: base_url.to_string(),
+      registered_at_epoch_secs: registered_at,
+      last_seen_epoch_secs: now,
+      expires_at_epoch_secs: now + ttl,
+      version: request.version,
+      draining,
+    };
+    nodes.insert(request.node_id, record.clone());
+    Ok(record)
+  }
+
+  pub async fn deregister(&self, node_id: &str) -> bool {
+    self.nodes.write().await.remove(node_id).is_some()
+  }
+
+  /// Mark a live node as draining or back in rotation; `None` if it is not
+  /// registered
+  pub async fn set_draining(&self, node_id: &str, draining: bool) -> Option<NodeRecord> {
+    let now = now_epoch_secs();
+    let mut nodes = self.nodes.write().await;
+    let record = nodes.get_mut(node_id).filter(|record| !record.is_expired_at(now))?;
+    record.draining = draining;
+    Some(record.clone())
+  }
+
+  /// Live registrations, optionally filtered by kind, ordered by node id
+  pub async fn list(&self, kind: Option<NodeKind>) -> Vec<NodeRecord> {
+    let now = now_epoch_secs();
+    let nodes = self.nodes.read().await;
+    let mut records: Vec<NodeRecord> = nodes
+      .values()
+      .filter(|record| !record.is_expired_at(now))
+      .filter(|record| kind.is_none_or(|k| record.kind == k))
+      .cloned()
+      .collect();
+    records.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+    records
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn request(node_id: &str, kind: NodeKind, ttl_secs: u64) -> NodeRegisterRequest {
+    NodeRegisterRequest {
+      node_id: node_id.to_string(),
+      kind,
+      base_url: format!("http://{node_id}.internal:8080"),
+      ttl_secs,
+      version: None,
+    }
+  }
+
+  #[tokio::test]
+  async fn register_refresh_and_filter() {
+    let registry = NodeRegistry::new(60);
+    let first = registry.register(request("sn-1", NodeKind::Stream, 30)).await.unwrap();
+    registry.register(request("rec-1", NodeKind::Recorder, 30)).await.unwrap();
+    let refreshed = registry.register(request("sn-1", NodeKind::Stream, 30)).await.unwrap();
+
+    assert_eq!(refreshed.registered_at_epoch_secs, first.registered_at_epoch_secs);
+    assert_eq!(refreshed.base_url, "http://sn-1.internal:8080/");
+    assert_eq!(registry.list(None).await.len(), 2);
+
+    let streams = registry.list(Some(NodeKind::Stream)).await;
+    assert_eq!(streams.len(), 1);
+    assert_eq!(streams[0].node_id, "sn-1");
+  }
+
+  #[tokio::test]
+  async fn expired_and_deregistered_nodes_are_hidden() {
+    let registry = NodeRegistry::new(60);
+    registry.register(request("sn-1", NodeKind::Stream, 30)).await.unwrap();
+    registry.register(request("sn-2", NodeKind::Stream, 30)).await.unwrap();
+    registry.nodes.write().await.get_mut("sn-2").unwrap().expires_at_epoch_secs = 0;
+
+    assert_eq!(registry.list(Some(NodeKind::Stream)).await.len(), 1);
+    assert!(registry.deregister("sn-1").await);
+    assert!(!registry.deregister("sn-1").await);
+    assert!(registry.list(None).await.is_empty());
+  }
+
+  #[tokio::test]
+  async fn drain_survives_heartbeats() {
+    let registry = NodeRegistry::new(60);
+    registry.register(request("sn-1", NodeKind::Stream, 30)).await.unwrap();
+    assert!(registry.set_draining("sn-1", true).await.unwrap().draining);
+    assert!(registry.register(request("sn-1", NodeKind::Stream, 30)).await.unwrap().draining);
+
+    assert!(!registry.set_draining("sn-1", false).await.unwrap().draining);
+    assert!(registry.set_draining("sn-2", true).await.is_none());
+  }
+
+  #[tokio::test]
+  async fn rejects_invalid_registrations() {
+    let registry = NodeRegistry::new(60);
+    let mut bad_url = request("sn-1", NodeKind::Stream, 30);
+    bad_url.base_url = "file:///etc/passwd".to_string();
+    assert!(registry.register(bad_url).await.is_err());
+    assert!(registry.register(request("../sn", NodeKind::Stream, 30)).await.is_err());
+    let mut bad_version = request("sn-1", NodeKind::Stream, 30);
+    bad_version.version = Some("1.0/../x".to_string());
+    assert!(registry.register(bad_version).await.is_err());
+  }
+}
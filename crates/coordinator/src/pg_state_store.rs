@@ -1,10 +1,15 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use common::ai_tasks::{AiTaskConfig, AiTaskInfo, AiTaskState};
+use common::idempotency::CachedResponse;
+use common::quota::TenantUsage;
+use common::service_config::ServiceConfig;
 use common::recordings::{RecordingConfig, RecordingFormat, RecordingInfo, RecordingMetadata, RecordingState};
-use common::state_store::StateStore;
+use common::state_store::{Namespace, NamespaceUsage, StateStore};
 use common::streams::{StreamConfig, StreamInfo, StreamState};
-use sqlx::PgPool;
+use common::timeline::{TimelineEvent, TimelineQuery};
+use common::validation::safe_unix_timestamp;
+use sqlx::{PgPool, Row};
 use tracing::warn;
 
 pub struct PgStateStore {
@@ -16,6 +21,33 @@ impl PgStateStore {
         Self { pool }
     }
 
+    fn service_config_from_row(r: &sqlx::postgres::PgRow) -> Result<ServiceConfig> {
+        Ok(ServiceConfig {
+            service: r.try_get("service")?,
+            version: r.try_get::<i64, _>("version")? as u64,
+            document: r.try_get("document")?,
+            created_at: r.try_get::<i64, _>("created_at")? as u64,
+            created_by: r.try_get("created_by")?,
+            comment: r.try_get("comment")?,
+            rollback_of: r.try_get::<Option<i64>, _>("rollback_of")?.map(|v| v as u64),
+        })
+    }
+
+    fn timeline_event_from_row(r: &sqlx::postgres::PgRow) -> Result<TimelineEvent> {
+        let kind: String = r.try_get("kind")?;
+        Ok(TimelineEvent {
+            id: r.try_get("id")?,
+            timestamp_ms: r.try_get::<i64, _>("timestamp_ms")? as u64,
+            kind: kind.parse().map_err(anyhow::Error::msg)?,
+            source: r.try_get("source")?,
+            camera_id: r.try_get("camera_id")?,
+            tenant_id: r.try_get("tenant_id")?,
+            actor: r.try_get("actor")?,
+            summary: r.try_get("summary")?,
+            details: r.try_get("details")?,
+        })
+    }
+
     fn parse_stream_state(s: &str) -> StreamState {
         match s {
             "pending" => StreamState::Pending,
@@ -162,6 +194,8 @@ impl StateStore for PgStateStore {
                 uri: r.uri,
                 codec: Some(r.codec),
                 container: Some(r.container),
+                substream_uri: None,
+                thermal_palette: None,
             },
             state: Self::parse_stream_state(&r.state),
             lease_id: r.lease_id,
@@ -197,6 +231,8 @@ impl StateStore for PgStateStore {
                     uri: r.uri,
                     codec: Some(r.codec),
                     container: Some(r.container),
+                    substream_uri: None,
+                    thermal_palette: None,
                 },
                 state: Self::parse_stream_state(&r.state),
                 lease_id: r.lease_id,
@@ -247,6 +283,12 @@ impl StateStore for PgStateStore {
             Some(RecordingFormat::Mkv) => "mkv",
             None => "mp4",
         };
+        let encoding_json = info
+            .config
+            .encoding
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()?;
 
         let (duration, file_size, resolution, codec_name, bitrate, fps) = if let Some(meta) = &info.metadata {
             (
@@ -266,8 +308,8 @@ impl StateStore for PgStateStore {
             INSERT INTO recordings (recording_id, source_stream_id, source_uri, retention_hours,
                                     format, state, node_id, lease_id, storage_path, last_error,
                                     started_at, stopped_at, duration_secs, file_size_bytes,
-                                    resolution, codec_name, bitrate_kbps, fps)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+                                    resolution, codec_name, bitrate_kbps, fps, encoding)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
             ON CONFLICT (recording_id) DO UPDATE SET
                 source_stream_id = EXCLUDED.source_stream_id,
                 source_uri = EXCLUDED.source_uri,
@@ -285,7 +327,8 @@ impl StateStore for PgStateStore {
                 resolution = EXCLUDED.resolution,
                 codec_name = EXCLUDED.codec_name,
                 bitrate_kbps = EXCLUDED.bitrate_kbps,
-                fps = EXCLUDED.fps
+                fps = EXCLUDED.fps,
+                encoding = EXCLUDED.encoding
             "#,
             &info.config.id,
             info.config.source_stream_id.as_deref(),
@@ -305,6 +348,7 @@ impl StateStore for PgStateStore {
             codec_name.as_deref(),
             bitrate,
             fps,
+            encoding_json,
         )
         .execute(&self.pool)
         .await
@@ -318,7 +362,7 @@ impl StateStore for PgStateStore {
             r#"
             SELECT recording_id, source_stream_id, source_uri, retention_hours, format, state,
                    node_id, lease_id, storage_path, last_error, started_at, stopped_at,
-                   duration_secs, file_size_bytes, resolution, codec_name, bitrate_kbps, fps
+                   duration_secs, file_size_bytes, resolution, codec_name, bitrate_kbps, fps, encoding
             FROM recordings WHERE recording_id = $1
             "#,
             recording_id
@@ -370,6 +414,7 @@ impl StateStore for PgStateStore {
                     source_uri: r.source_uri,
                     retention_hours: r.retention_hours.map(|v| v as u32),
                     format: Some(format),
+                    encoding: r.encoding.and_then(|e| serde_json::from_value(e).ok()),
                 },
                 state: Self::parse_recording_state(&r.state),
                 lease_id: r.lease_id,
@@ -388,7 +433,7 @@ impl StateStore for PgStateStore {
             r#"
             SELECT recording_id, source_stream_id, source_uri, retention_hours, format, state,
                    node_id, lease_id, storage_path, last_error, started_at, stopped_at,
-                   duration_secs, file_size_bytes, resolution, codec_name, bitrate_kbps, fps
+                   duration_secs, file_size_bytes, resolution, codec_name, bitrate_kbps, fps, encoding
             FROM recordings
             WHERE ($1::text IS NULL OR node_id = $1)
             ORDER BY created_at DESC
@@ -443,6 +488,7 @@ impl StateStore for PgStateStore {
                         source_uri: r.source_uri,
                         retention_hours: r.retention_hours.map(|v| v as u32),
                         format: Some(format),
+                        encoding: r.encoding.and_then(|e| serde_json::from_value(e).ok()),
                     },
                     state: Self::parse_recording_state(&r.state),
                     lease_id: r.lease_id,
@@ -576,6 +622,14 @@ impl StateStore for PgStateStore {
                     source_stream_id: r.source_stream_id,
                     source_recording_id: r.source_recording_id,
                     model_config: serde_json::Value::Null,
+                    secondary_plugins: Vec::new(),
+                    pipeline: None,
+                    classes: None,
+                    tracking: None,
+                    zones: None,
+                    dedup: None,
+                    alarm_classes: Vec::new(),
+                    schedule: None,
                     output,
                     frame_config,
                 },
@@ -583,6 +637,7 @@ impl StateStore for PgStateStore {
                 node_id: r.node_id,
                 lease_id: r.lease_id,
                 last_error: r.last_error,
+                last_error_kind: None,
                 started_at: r.started_at.map(|v| v as u64),
                 stopped_at: r.stopped_at.map(|v| v as u64),
                 last_processed_frame: r.last_processed_frame.map(|v| v as u64),
@@ -626,6 +681,14 @@ impl StateStore for PgStateStore {
                         source_stream_id: r.source_stream_id,
                         source_recording_id: r.source_recording_id,
                         model_config: serde_json::Value::Null,
+                        secondary_plugins: Vec::new(),
+                        pipeline: None,
+                        classes: None,
+                        tracking: None,
+                        zones: None,
+                        dedup: None,
+                        alarm_classes: Vec::new(),
+                        schedule: None,
                         output,
                         frame_config,
                     },
@@ -633,6 +696,7 @@ impl StateStore for PgStateStore {
                     node_id: r.node_id,
                     lease_id: r.lease_id,
                     last_error: r.last_error,
+                    last_error_kind: None,
                     started_at: r.started_at.map(|v| v as u64),
                     stopped_at: r.stopped_at.map(|v| v as u64),
                     last_processed_frame: r.last_processed_frame.map(|v| v as u64),
@@ -700,6 +764,260 @@ impl StateStore for PgStateStore {
         Ok(())
     }
 
+    async fn save_idempotency_record(&self, key: &str, record: &CachedResponse) -> Result<()> {
+        // Opportunistically drop expired keys so the table stays bounded
+        sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= $1")
+            .bind(safe_unix_timestamp() as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to purge expired idempotency keys")?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (idempotency_key, fingerprint, status, content_type, body, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (idempotency_key) DO UPDATE SET
+                fingerprint = EXCLUDED.fingerprint,
+                status = EXCLUDED.status,
+                content_type = EXCLUDED.content_type,
+                body = EXCLUDED.body,
+                expires_at = EXCLUDED.expires_at
+            "#,
+        )
+        .bind(key)
+        .bind(&record.fingerprint)
+        .bind(record.status as i32)
+        .bind(record.content_type.as_deref())
+        .bind(&record.body)
+        .bind(record.expires_at_epoch_secs as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save idempotency record")?;
+
+        Ok(())
+    }
+
+    async fn get_idempotency_record(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let row = sqlx::query(
+            r#"
+            SELECT fingerprint, status, content_type, body, expires_at
+            FROM idempotency_keys WHERE idempotency_key = $1 AND expires_at > $2
+            "#,
+        )
+        .bind(key)
+        .bind(safe_unix_timestamp() as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch idempotency record")?;
+
+        row.map(|r| -> Result<CachedResponse> {
+            Ok(CachedResponse {
+                fingerprint: r.try_get("fingerprint")?,
+                status: r.try_get::<i32, _>("status")? as u16,
+                content_type: r.try_get("content_type")?,
+                body: r.try_get("body")?,
+                expires_at_epoch_secs: r.try_get::<i64, _>("expires_at")? as u64,
+            })
+        })
+        .transpose()
+    }
+
+    async fn add_tenant_usage(&self, tenant_id: &str, period: &str, requests: i64, rejected: i64) -> Result<TenantUsage> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO tenant_api_usage (tenant_id, period, requests, rejected)
+            VALUES ($1, $2, GREATEST($3, 0), GREATEST($4, 0))
+            ON CONFLICT (tenant_id, period) DO UPDATE SET
+                requests = GREATEST(tenant_api_usage.requests + $3, 0),
+                rejected = GREATEST(tenant_api_usage.rejected + $4, 0),
+                updated_at = NOW()
+            RETURNING requests, rejected
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(period)
+        .bind(requests)
+        .bind(rejected)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to update tenant usage")?;
+
+        Ok(TenantUsage {
+            tenant_id: tenant_id.to_string(),
+            period: period.to_string(),
+            requests: row.try_get::<i64, _>("requests")? as u64,
+            rejected: row.try_get::<i64, _>("rejected")? as u64,
+        })
+    }
+
+    async fn list_tenant_usage(&self, period: &str, tenant_id: Option<&str>) -> Result<Vec<TenantUsage>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT tenant_id, period, requests, rejected
+            FROM tenant_api_usage
+            WHERE period = $1 AND ($2::TEXT IS NULL OR tenant_id = $2)
+            ORDER BY tenant_id
+            "#,
+        )
+        .bind(period)
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list tenant usage")?;
+
+        rows.into_iter()
+            .map(|r| -> Result<TenantUsage> {
+                Ok(TenantUsage {
+                    tenant_id: r.try_get("tenant_id")?,
+                    period: r.try_get("period")?,
+                    requests: r.try_get::<i64, _>("requests")? as u64,
+                    rejected: r.try_get::<i64, _>("rejected")? as u64,
+                })
+            })
+            .collect()
+    }
+
+    async fn save_service_config(&self, config: &ServiceConfig) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO service_configs (service, version, document, created_at, created_by, comment, rollback_of)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (service, version) DO NOTHING
+            "#,
+        )
+        .bind(&config.service)
+        .bind(config.version as i64)
+        .bind(&config.document)
+        .bind(config.created_at as i64)
+        .bind(config.created_by.as_deref())
+        .bind(config.comment.as_deref())
+        .bind(config.rollback_of.map(|v| v as i64))
+        .execute(&self.pool)
+        .await
+        .context("Failed to save service config")?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn get_service_config(&self, service: &str, version: Option<u64>) -> Result<Option<ServiceConfig>> {
+        let row = sqlx::query(
+            r#"
+            SELECT service, version, document, created_at, created_by, comment, rollback_of
+            FROM service_configs
+            WHERE service = $1 AND ($2::BIGINT IS NULL OR version = $2)
+            ORDER BY version DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(service)
+        .bind(version.map(|v| v as i64))
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get service config")?;
+
+        row.map(|r| Self::service_config_from_row(&r)).transpose()
+    }
+
+    async fn list_service_configs(&self, service: &str) -> Result<Vec<ServiceConfig>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT service, version, document, created_at, created_by, comment, rollback_of
+            FROM service_configs
+            WHERE service = $1
+            ORDER BY version DESC
+            "#,
+        )
+        .bind(service)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list service configs")?;
+
+        rows.iter().map(Self::service_config_from_row).collect()
+    }
+
+    async fn append_timeline_events(&self, events: &[TimelineEvent]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for event in events {
+            sqlx::query(
+                r#"
+                INSERT INTO timeline_events (id, timestamp_ms, kind, source, camera_id, tenant_id, actor, summary, details)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (id) DO NOTHING
+                "#,
+            )
+            .bind(&event.id)
+            .bind(event.timestamp_ms as i64)
+            .bind(event.kind.as_str())
+            .bind(&event.source)
+            .bind(event.camera_id.as_deref())
+            .bind(event.tenant_id.as_deref())
+            .bind(event.actor.as_deref())
+            .bind(&event.summary)
+            .bind(&event.details)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to append timeline event")?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn query_timeline(&self, query: &TimelineQuery) -> Result<Vec<TimelineEvent>> {
+        let kinds: Vec<&str> = query.kinds()?.into_iter().map(|k| k.as_str()).collect();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, timestamp_ms, kind, source, camera_id, tenant_id, actor, summary, details
+            FROM timeline_events
+            WHERE ($1::BIGINT IS NULL OR timestamp_ms >= $1)
+              AND ($2::BIGINT IS NULL OR timestamp_ms < $2)
+              AND ($3::TEXT IS NULL OR camera_id = $3)
+              AND ($4::TEXT IS NULL OR tenant_id = $4)
+              AND (cardinality($5::TEXT[]) = 0 OR kind = ANY($5))
+            ORDER BY timestamp_ms, id
+            LIMIT $6
+            "#,
+        )
+        .bind(query.from.map(|v| v as i64))
+        .bind(query.to.map(|v| v as i64))
+        .bind(query.camera_id.as_deref())
+        .bind(query.tenant_id.as_deref())
+        .bind(&kinds)
+        .bind(query.effective_limit() as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query timeline")?;
+
+        rows.iter().map(Self::timeline_event_from_row).collect()
+    }
+
+    async fn purge_timeline(&self, before_ms: u64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM timeline_events WHERE timestamp_ms < $1")
+            .bind(before_ms as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to purge timeline")?;
+        Ok(result.rows_affected())
+    }
+
+    async fn namespace_usage(&self) -> Result<Vec<NamespaceUsage>> {
+        let mut usage = Vec::with_capacity(Namespace::ALL.len());
+        for namespace in Namespace::ALL {
+            // Table names come from Namespace, not from input
+            let row = sqlx::query(&format!(
+                "SELECT COUNT(*) AS keys, pg_total_relation_size('{table}') AS bytes FROM {table}",
+                table = namespace.as_str()
+            ))
+            .fetch_one(&self.pool)
+            .await
+            .with_context(|| format!("Failed to measure namespace {}", namespace))?;
+            usage.push(NamespaceUsage {
+                namespace,
+                keys: row.try_get::<i64, _>("keys")?.max(0) as u64,
+                bytes: row.try_get::<i64, _>("bytes")?.max(0) as u64,
+            });
+        }
+        Ok(usage)
+    }
+
     async fn health_check(&self) -> Result<bool> {
         sqlx::query("SELECT 1")
             .fetch_one(&self.pool)
@@ -0,0 +1,1121 @@
+//! Reconciliation of desired and actual stream/recording state.
+//!
+//! Desired state is the device-manager inventory: an online device with
+//! `auto_start` should have an active stream, one with `recording_enabled`
+//! an active recording. Actual state is what the nodes persisted to the
+//! StateStore. The two drift apart after crashes, so every
+//! `RECONCILE_INTERVAL_SECS` the leader compares them and sends corrective
+//! starts and stops through the admin-gateway, which takes the leases and
+//! picks a node as for any other request.
+//!
+//! Only resources the reconciler started itself (ids prefixed `auto-`) or
+//! that belong to a deleted device are ever stopped; a stream an operator
+//! started by hand for a device without `auto_start` is left alone.
+//!
+//! device-manager also reports devices coming back from an outage
+//! (`POST /v1/reconcile/devices/:device_id/recovered`); the device's stream
+//! and recording that failed meanwhile are then restarted at once, whether
+//! the reconciler or an operator started them.
+
+use crate::{error::ApiError, state::CoordinatorState};
+use anyhow::{Context, Result, anyhow, bail};
+use axum::{
+  Json, Router,
+  extract::{Path, State},
+  routing::{get, post},
+};
+use common::{
+  nodes::{NodeKind, NodeRecord},
+  recordings::{RecordingConfig, RecordingInfo, RecordingStartRequest, RecordingStartResponse, RecordingState},
+  streams::{StreamConfig, StreamInfo, StreamStartRequest, StreamStartResponse, StreamState},
+  timeline::{TimelineEvent, TimelineEventKind},
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{HashMap, HashSet},
+  env,
+  sync::Arc,
+  time::{Duration, Instant, SystemTime},
+};
+use telemetry::metrics::{COORDINATOR_RECONCILE_ACTIONS, COORDINATOR_RECONCILE_DRIFT, COORDINATOR_RECONCILE_PASSES};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+/// Prefix of the stream ids the reconciler starts
+pub const AUTO_STREAM_PREFIX: &str = "auto-";
+
+/// Prefix of the recording ids the reconciler starts
+pub const AUTO_RECORDING_PREFIX: &str = "auto-rec-";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone)]
+pub struct ReconcileConfig {
+  pub interval: Duration,
+  pub device_manager_url: Url,
+  pub gateway_url: Url,
+  /// Bearer token for device-manager and admin-gateway, e.g. a system
+  /// admin API token
+  pub auth_token: Option<String>,
+  /// Report drift without correcting it
+  pub dry_run: bool,
+  /// Corrections issued per pass; the rest wait for the next pass
+  pub max_actions: usize,
+}
+
+impl ReconcileConfig {
+  /// `None` unless `RECONCILE_ENABLED` is true
+  pub fn from_env() -> Result<Option<Self>> {
+    let enabled = env::var("RECONCILE_ENABLED")
+      .ok()
+      .and_then(|v| v.parse::<bool>().ok())
+      .unwrap_or(false);
+    if !enabled {
+      return Ok(None);
+    }
+
+    let interval_secs = env::var("RECONCILE_INTERVAL_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(60)
+      .max(5);
+    let device_manager_url = env::var("DEVICE_MANAGER_URL")
+      .context("DEVICE_MANAGER_URL required when RECONCILE_ENABLED is true")
+      .and_then(|v| Url::parse(&v).context("invalid DEVICE_MANAGER_URL"))?;
+    let gateway_url = env::var("RECONCILE_GATEWAY_URL")
+      .context("RECONCILE_GATEWAY_URL required when RECONCILE_ENABLED is true")
+      .and_then(|v| Url::parse(&v).context("invalid RECONCILE_GATEWAY_URL"))?;
+    let auth_token = env::var("RECONCILE_AUTH_TOKEN").ok().filter(|v| !v.trim().is_empty());
+    let dry_run = env::var("RECONCILE_DRY_RUN")
+      .ok()
+      .and_then(|v| v.parse::<bool>().ok())
+      .unwrap_or(false);
+    let max_actions = env::var("RECONCILE_MAX_ACTIONS")
+      .ok()
+      .and_then(|v| v.parse::<usize>().ok())
+      .unwrap_or(20);
+
+    Ok(Some(Self {
+      interval: Duration::from_secs(interval_secs),
+      device_manager_url,
+      gateway_url,
+      auth_token,
+      dry_run,
+      max_actions,
+    }))
+  }
+}
+
+/// The fields of a device-manager device that make up desired state
+#[derive(Debug, Clone, Deserialize)]
+pub struct DesiredDevice {
+  pub device_id: String,
+  pub tenant_id: String,
+  pub primary_uri: String,
+  pub status: String,
+  pub auto_start: bool,
+  pub recording_enabled: bool,
+}
+
+impl DesiredDevice {
+  fn wants_stream(&self) -> bool {
+    self.auto_start && self.status == "online"
+  }
+
+  fn wants_recording(&self) -> bool {
+    self.recording_enabled && self.status == "online"
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+  Stream,
+  Recording,
+}
+
+impl ResourceKind {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      ResourceKind::Stream => "stream",
+      ResourceKind::Recording => "recording",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Drift {
+  /// Desired but not active anywhere
+  Missing,
+  /// Recorded as active on a node that is no longer registered
+  Stale,
+  /// Active although its device no longer wants it or was deleted
+  Orphaned,
+  /// Failed while its device was unreachable, which has since recovered
+  Failed,
+}
+
+impl Drift {
+  pub const ALL: [Drift; 4] = [Drift::Missing, Drift::Stale, Drift::Orphaned, Drift::Failed];
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Drift::Missing => "missing",
+      Drift::Stale => "stale",
+      Drift::Orphaned => "orphaned",
+      Drift::Failed => "failed",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorrectionAction {
+  Start,
+  Stop,
+  /// Mark a stale entry as failed in the StateStore; its node is gone, so
+  /// there is nothing to stop
+  MarkFailed,
+}
+
+impl CorrectionAction {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      CorrectionAction::Start => "start",
+      CorrectionAction::Stop => "stop",
+      CorrectionAction::MarkFailed => "mark_failed",
+    }
+  }
+}
+
+/// One step towards the desired state
+#[derive(Debug, Clone, Serialize)]
+pub struct Correction {
+  pub kind: ResourceKind,
+  pub action: CorrectionAction,
+  pub drift: Drift,
+  pub resource_id: String,
+  pub device_id: Option<String>,
+  pub tenant_id: Option<String>,
+  /// Start configuration; not reported since source URIs carry credentials
+  #[serde(skip)]
+  pub stream: Option<StreamConfig>,
+  #[serde(skip)]
+  pub recording: Option<RecordingConfig>,
+}
+
+/// Everything one pass compares
+#[derive(Debug, Clone, Default)]
+pub struct Observed {
+  pub devices: Vec<DesiredDevice>,
+  pub streams: Vec<StreamInfo>,
+  pub recordings: Vec<RecordingInfo>,
+  /// Registered stream nodes; staleness is not judged when none register
+  pub stream_nodes: HashSet<String>,
+  pub recorder_nodes: HashSet<String>,
+}
+
+fn is_stale(node_id: Option<&str>, live: &HashSet<String>) -> bool {
+  !live.is_empty() && node_id.is_some_and(|node| !live.contains(node))
+}
+
+/// Compare desired and actual state; corrections for each device come
+/// out in inventory order, followed by those for deleted devices
+pub fn plan(observed: &Observed, now_epoch_secs: u64) -> Vec<Correction> {
+  let devices: HashMap<&str, &DesiredDevice> = observed
+    .devices
+    .iter()
+    .map(|device| (device.device_id.as_str(), device))
+    .collect();
+  let mut corrections = Vec::new();
+
+  // Streams, grouped by camera
+  let mut device_streams: HashMap<&str, Vec<&StreamInfo>> = HashMap::new();
+  for stream in &observed.streams {
+    if let Some(camera) = stream.config.camera_id.as_deref() {
+      device_streams.entry(camera).or_default().push(stream);
+    }
+  }
+  let stream_cameras: HashMap<&str, &str> = observed
+    .streams
+    .iter()
+    .filter_map(|s| s.config.camera_id.as_deref().map(|camera| (s.config.id.as_str(), camera)))
+    .collect();
+
+  // Recordings map to a device through their source stream or source URI
+  let mut uri_devices: HashMap<&str, &str> = HashMap::new();
+  for device in &observed.devices {
+    uri_devices.insert(device.primary_uri.as_str(), device.device_id.as_str());
+  }
+  for stream in &observed.streams {
+    if let Some(camera) = stream.config.camera_id.as_deref() {
+      uri_devices.entry(stream.config.uri.as_str()).or_insert(camera);
+    }
+  }
+  let mut device_recordings: HashMap<&str, Vec<&RecordingInfo>> = HashMap::new();
+  for recording in &observed.recordings {
+    let device = match (&recording.config.source_stream_id, &recording.config.source_uri) {
+      (Some(stream_id), _) => stream_cameras.get(stream_id.as_str()).copied(),
+      (None, Some(uri)) => uri_devices.get(uri.as_str()).copied(),
+      (None, None) => None,
+    };
+    if let Some(device) = device {
+      device_recordings.entry(device).or_default().push(recording);
+    }
+  }
+
+  let no_streams = Vec::new();
+  let no_recordings = Vec::new();
+  for device in &observed.devices {
+    let id = device.device_id.as_str();
+    let streams = device_streams.get(id).unwrap_or(&no_streams);
+    let recordings = device_recordings.get(id).unwrap_or(&no_recordings);
+
+    let mut live_streams = Vec::new();
+    for stream in streams.iter().filter(|s| s.state.is_active()) {
+      if is_stale(stream.node_id.as_deref(), &observed.stream_nodes) {
+        corrections.push(stream_correction(CorrectionAction::MarkFailed, Drift::Stale, stream, Some(device)));
+      } else {
+        live_streams.push(*stream);
+      }
+    }
+    if device.wants_stream() && live_streams.is_empty() {
+      corrections.push(start_stream(device, streams, now_epoch_secs));
+    } else if !device.auto_start {
+      for stream in live_streams.iter().filter(|s| s.config.id.starts_with(AUTO_STREAM_PREFIX)) {
+        corrections.push(stream_correction(CorrectionAction::Stop, Drift::Orphaned, stream, Some(device)));
+      }
+    }
+
+    let mut live_recordings = Vec::new();
+    for recording in recordings.iter().filter(|r| r.state.is_active()) {
+      if is_stale(recording.node_id.as_deref(), &observed.recorder_nodes) {
+        corrections.push(recording_correction(CorrectionAction::MarkFailed, Drift::Stale, recording, Some(device)));
+      } else {
+        live_recordings.push(*recording);
+      }
+    }
+    if device.wants_recording() && live_recordings.is_empty() {
+      corrections.push(start_recording(device, streams, recordings, now_epoch_secs));
+    } else if !device.recording_enabled {
+      for recording in live_recordings.iter().filter(|r| r.config.id.starts_with(AUTO_RECORDING_PREFIX)) {
+        corrections.push(recording_correction(CorrectionAction::Stop, Drift::Orphaned, recording, Some(device)));
+      }
+    }
+  }
+
+  // Whatever still runs for a deleted device
+  for stream in observed.streams.iter().filter(|s| s.state.is_active()) {
+    let Some(camera) = stream.config.camera_id.as_deref() else {
+      continue;
+    };
+    if devices.contains_key(camera) {
+      continue;
+    }
+    if is_stale(stream.node_id.as_deref(), &observed.stream_nodes) {
+      corrections.push(stream_correction(CorrectionAction::MarkFailed, Drift::Stale, stream, None));
+    } else {
+      corrections.push(stream_correction(CorrectionAction::Stop, Drift::Orphaned, stream, None));
+    }
+  }
+  for recording in observed.recordings.iter().filter(|r| r.state.is_active()) {
+    let Some(camera) = recording
+      .config
+      .source_stream_id
+      .as_deref()
+      .and_then(|stream_id| stream_cameras.get(stream_id))
+    else {
+      continue;
+    };
+    if devices.contains_key(camera) {
+      continue;
+    }
+    if is_stale(recording.node_id.as_deref(), &observed.recorder_nodes) {
+      corrections.push(recording_correction(CorrectionAction::MarkFailed, Drift::Stale, recording, None));
+    } else {
+      corrections.push(recording_correction(CorrectionAction::Stop, Drift::Orphaned, recording, None));
+    }
+  }
+
+  corrections
+}
+
+fn stream_correction(
+  action: CorrectionAction,
+  drift: Drift,
+  stream: &StreamInfo,
+  device: Option<&DesiredDevice>,
+) -> Correction {
+  Correction {
+    kind: ResourceKind::Stream,
+    action,
+    drift,
+    resource_id: stream.config.id.clone(),
+    device_id: stream.config.camera_id.clone(),
+    tenant_id: device.map(|d| d.tenant_id.clone()),
+    stream: None,
+    recording: None,
+  }
+}
+
+fn recording_correction(
+  action: CorrectionAction,
+  drift: Drift,
+  recording: &RecordingInfo,
+  device: Option<&DesiredDevice>,
+) -> Correction {
+  Correction {
+    kind: ResourceKind::Recording,
+    action,
+    drift,
+    resource_id: recording.config.id.clone(),
+    device_id: device.map(|d| d.device_id.clone()),
+    tenant_id: device.map(|d| d.tenant_id.clone()),
+    stream: None,
+    recording: None,
+  }
+}
+
+/// The stream started last for a device; it keeps the source credentials,
+/// which device-manager never returns
+fn last_stream<'a>(streams: &[&'a StreamInfo]) -> Option<&'a StreamInfo> {
+  streams.iter().copied().max_by_key(|s| s.started_at.unwrap_or(0))
+}
+
+fn start_stream(device: &DesiredDevice, streams: &[&StreamInfo], now_epoch_secs: u64) -> Correction {
+  // A dead node may still hold the lease on the usual id
+  let mut id = format!("{}{}", AUTO_STREAM_PREFIX, device.device_id);
+  if streams.iter().any(|s| s.config.id == id && s.state.is_active()) {
+    id = format!("{}-{}", id, now_epoch_secs);
+  }
+  let previous = last_stream(streams);
+  Correction {
+    kind: ResourceKind::Stream,
+    action: CorrectionAction::Start,
+    drift: Drift::Missing,
+    resource_id: id.clone(),
+    device_id: Some(device.device_id.clone()),
+    tenant_id: Some(device.tenant_id.clone()),
+    stream: Some(StreamConfig {
+      id,
+      camera_id: Some(device.device_id.clone()),
+      uri: previous.map_or_else(|| device.primary_uri.clone(), |s| s.config.uri.clone()),
+      codec: previous.and_then(|s| s.config.codec.clone()),
+      container: previous.and_then(|s| s.config.container.clone()),
+      substream_uri: previous.and_then(|s| s.config.substream_uri.clone()),
+      thermal_palette: previous.and_then(|s| s.config.thermal_palette),
+    }),
+    recording: None,
+  }
+}
+
+fn start_recording(
+  device: &DesiredDevice,
+  streams: &[&StreamInfo],
+  recordings: &[&RecordingInfo],
+  now_epoch_secs: u64,
+) -> Correction {
+  // Every recording session is its own file, so ids are never reused
+  let id = format!("{}{}-{}", AUTO_RECORDING_PREFIX, device.device_id, now_epoch_secs);
+  let previous = recordings.iter().max_by_key(|r| r.started_at.unwrap_or(0));
+  let source_uri = last_stream(streams).map_or_else(|| device.primary_uri.clone(), |s| s.config.uri.clone());
+  Correction {
+    kind: ResourceKind::Recording,
+    action: CorrectionAction::Start,
+    drift: Drift::Missing,
+    resource_id: id.clone(),
+    device_id: Some(device.device_id.clone()),
+    tenant_id: Some(device.tenant_id.clone()),
+    stream: None,
+    recording: Some(RecordingConfig {
+      id,
+      source_stream_id: None,
+      source_uri: Some(source_uri),
+      retention_hours: previous.and_then(|r| r.config.retention_hours),
+      format: previous.and_then(|r| r.config.format.clone()),
+      encoding: previous.and_then(|r| r.config.encoding.clone()),
+    }),
+  }
+}
+
+/// Restarts for a device back from an outage: its newest stream and its
+/// newest recording, when they ended in error and nothing replaced them.
+/// Unlike [`plan`] this restarts what operators started by hand too.
+pub fn plan_recovery(observed: &Observed, device_id: &str, now_epoch_secs: u64) -> Vec<Correction> {
+  let Some(device) = observed
+    .devices
+    .iter()
+    .find(|d| d.device_id == device_id && d.status == "online")
+  else {
+    return Vec::new();
+  };
+  let streams: Vec<&StreamInfo> = observed
+    .streams
+    .iter()
+    .filter(|s| s.config.camera_id.as_deref() == Some(device_id))
+    .collect();
+  let mut corrections = Vec::new();
+
+  if !streams.iter().any(|s| s.state.is_active())
+    && let Some(stream) = last_stream(&streams).filter(|s| s.state == StreamState::Error)
+  {
+    corrections.push(Correction {
+      stream: Some(stream.config.clone()),
+      ..stream_correction(CorrectionAction::Start, Drift::Failed, stream, Some(device))
+    });
+  }
+
+  let stream_uris: HashMap<&str, &str> = streams
+    .iter()
+    .map(|s| (s.config.id.as_str(), s.config.uri.as_str()))
+    .collect();
+  let recordings: Vec<&RecordingInfo> = observed
+    .recordings
+    .iter()
+    .filter(|r| match (&r.config.source_stream_id, &r.config.source_uri) {
+      (Some(stream_id), _) => stream_uris.contains_key(stream_id.as_str()),
+      (None, Some(uri)) => uri == &device.primary_uri || stream_uris.values().any(|u| u == uri),
+      (None, None) => false,
+    })
+    .collect();
+  let last_recording = recordings.iter().max_by_key(|r| r.started_at.unwrap_or(0));
+  if !recordings.iter().any(|r| r.state.is_active())
+    && let Some(recording) = last_recording.filter(|r| r.state == RecordingState::Error)
+  {
+    // The source stream may still be starting, so record from the camera
+    let mut config = recording.config.clone();
+    if let Some(uri) = config.source_stream_id.take().and_then(|id| stream_uris.get(id.as_str()).copied()) {
+      config.source_uri = Some(uri.to_string());
+    }
+    config.id = restarted_recording_id(&recording.config.id, now_epoch_secs);
+    corrections.push(Correction {
+      resource_id: config.id.clone(),
+      recording: Some(config),
+      ..recording_correction(CorrectionAction::Start, Drift::Failed, recording, Some(device))
+    });
+  }
+
+  corrections
+}
+
+/// Recording ids are never reused; a restart gets the original id with the
+/// restart time, in place of the time an earlier restart appended
+fn restarted_recording_id(id: &str, now_epoch_secs: u64) -> String {
+  let base = match id.rsplit_once('-') {
+    Some((base, suffix)) if suffix.len() >= 9 && suffix.bytes().all(|b| b.is_ascii_digit()) => base,
+    _ => id,
+  };
+  format!("{}-{}", base, now_epoch_secs)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftCount {
+  pub kind: ResourceKind,
+  pub drift: Drift,
+  pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorrectionOutcome {
+  #[serde(flatten)]
+  pub correction: Correction,
+  /// Not issued: dry run, or over the per-pass limit
+  pub deferred: bool,
+  pub error: Option<String>,
+}
+
+/// Result of one reconcile pass
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconcileReport {
+  pub started_at_epoch_secs: u64,
+  pub duration_ms: u64,
+  pub dry_run: bool,
+  pub devices: usize,
+  pub streams: usize,
+  pub recordings: usize,
+  pub drift: Vec<DriftCount>,
+  pub corrections: Vec<CorrectionOutcome>,
+}
+
+fn now_epoch_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or(Duration::ZERO)
+    .as_secs()
+}
+
+fn drift_counts(corrections: &[Correction]) -> Vec<DriftCount> {
+  let mut counts = Vec::new();
+  for kind in [ResourceKind::Stream, ResourceKind::Recording] {
+    for drift in Drift::ALL {
+      let count = corrections.iter().filter(|c| c.kind == kind && c.drift == drift).count();
+      counts.push(DriftCount { kind, drift, count });
+    }
+  }
+  counts
+}
+
+/// Periodically drives actual state towards desired state
+pub struct Reconciler {
+  config: ReconcileConfig,
+  state: CoordinatorState,
+  client: reqwest::Client,
+  last: RwLock<Option<ReconcileReport>>,
+  /// Keeps the ticker and `POST /v1/reconcile/run` from overlapping
+  pass: Mutex<()>,
+}
+
+impl Reconciler {
+  pub fn new(config: ReconcileConfig, state: CoordinatorState) -> Result<Self> {
+    if state.state_store().is_none() {
+      bail!("reconciliation needs the StateStore (LEASE_STORE_TYPE=postgres)");
+    }
+    let client = reqwest::Client::builder()
+      .timeout(REQUEST_TIMEOUT)
+      .build()
+      .context("failed to build reconciler HTTP client")?;
+    Ok(Self {
+      config,
+      state,
+      client,
+      last: RwLock::new(None),
+      pass: Mutex::new(()),
+    })
+  }
+
+  pub fn config(&self) -> &ReconcileConfig {
+    &self.config
+  }
+
+  pub async fn last_report(&self) -> Option<ReconcileReport> {
+    self.last.read().await.clone()
+  }
+
+  /// Only the leader reconciles, so followers never issue duplicate commands
+  async fn is_leader(&self) -> bool {
+    match self.state.cluster() {
+      Some(cluster) => cluster.is_leader().await,
+      None => true,
+    }
+  }
+
+  pub fn spawn(self: Arc<Self>) {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(self.config.interval);
+      ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+      loop {
+        ticker.tick().await;
+        if !self.is_leader().await {
+          COORDINATOR_RECONCILE_PASSES.with_label_values(&["skipped"]).inc();
+          continue;
+        }
+        match self.run_pass().await {
+          Ok(report) => {
+            let drifted: usize = report.drift.iter().map(|d| d.count).sum();
+            if drifted > 0 {
+              info!(drifted, corrections = report.corrections.len(), "reconcile pass found drift");
+            }
+          }
+          Err(e) => warn!(error = %e, "reconcile pass failed"),
+        }
+      }
+    });
+  }
+
+  async fn observe(&self) -> Result<Observed> {
+    let store = self
+      .state
+      .state_store()
+      .ok_or_else(|| anyhow!("StateStore not configured"))?;
+    let url = self.config.device_manager_url.join("v1/devices")?;
+    let mut request = self.client.get(url);
+    if let Some(token) = &self.config.auth_token {
+      request = request.bearer_auth(token);
+    }
+    // A failed listing must never look like an empty inventory
+    let devices = request
+      .send()
+      .await
+      .context("device-manager unreachable")?
+      .error_for_status()
+      .context("device-manager refused the device listing")?
+      .json::<Vec<DesiredDevice>>()
+      .await
+      .context("invalid device listing")?;
+
+    let nodes = self.state.nodes();
+    let node_ids = |records: Vec<NodeRecord>| -> HashSet<String> { records.into_iter().map(|n| n.node_id).collect() };
+    Ok(Observed {
+      devices,
+      streams: store.list_streams(None).await?,
+      recordings: store.list_recordings(None).await?,
+      stream_nodes: node_ids(nodes.list(Some(NodeKind::Stream)).await),
+      recorder_nodes: node_ids(nodes.list(Some(NodeKind::Recorder)).await),
+    })
+  }
+
+  /// Compare once and issue up to `max_actions` corrections
+  pub async fn run_pass(&self) -> Result<ReconcileReport> {
+    let _pass = self.pass.lock().await;
+    let started = Instant::now();
+    let started_at_epoch_secs = now_epoch_secs();
+
+    let observed = match self.observe().await {
+      Ok(observed) => observed,
+      Err(e) => {
+        COORDINATOR_RECONCILE_PASSES.with_label_values(&["error"]).inc();
+        return Err(e);
+      }
+    };
+    let corrections = plan(&observed, started_at_epoch_secs);
+    let drift = drift_counts(&corrections);
+    for count in &drift {
+      COORDINATOR_RECONCILE_DRIFT
+        .with_label_values(&[count.kind.as_str(), count.drift.as_str()])
+        .set(i64::try_from(count.count).unwrap_or(i64::MAX));
+    }
+
+    let outcomes = self.apply_all(corrections).await;
+
+    COORDINATOR_RECONCILE_PASSES.with_label_values(&["ok"]).inc();
+    let report = ReconcileReport {
+      started_at_epoch_secs,
+      duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+      dry_run: self.config.dry_run,
+      devices: observed.devices.len(),
+      streams: observed.streams.len(),
+      recordings: observed.recordings.len(),
+      drift,
+      corrections: outcomes,
+    };
+    *self.last.write().await = Some(report.clone());
+    Ok(report)
+  }
+
+  /// Restart what failed for a device while it was unreachable
+  pub async fn recover_device(&self, device_id: &str) -> Result<ReconcileReport> {
+    let _pass = self.pass.lock().await;
+    let started = Instant::now();
+    let started_at_epoch_secs = now_epoch_secs();
+
+    let observed = self.observe().await?;
+    let corrections = plan_recovery(&observed, device_id, started_at_epoch_secs);
+    let drift = drift_counts(&corrections);
+    let outcomes = self.apply_all(corrections).await;
+    if !outcomes.is_empty() {
+      info!(device_id = %device_id, restarts = outcomes.len(), "restarting failed resources of recovered device");
+    }
+    Ok(ReconcileReport {
+      started_at_epoch_secs,
+      duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+      dry_run: self.config.dry_run,
+      devices: observed.devices.len(),
+      streams: observed.streams.len(),
+      recordings: observed.recordings.len(),
+      drift,
+      corrections: outcomes,
+    })
+  }
+
+  /// Issue up to `max_actions` corrections, none in a dry run
+  async fn apply_all(&self, corrections: Vec<Correction>) -> Vec<CorrectionOutcome> {
+    let mut outcomes = Vec::with_capacity(corrections.len());
+    for (index, correction) in corrections.into_iter().enumerate() {
+      if self.config.dry_run || index >= self.config.max_actions {
+        outcomes.push(CorrectionOutcome {
+          correction,
+          deferred: true,
+          error: None,
+        });
+        continue;
+      }
+      let result = self.apply(&correction).await;
+      let status = if result.is_ok() { "success" } else { "failure" };
+      COORDINATOR_RECONCILE_ACTIONS
+        .with_label_values(&[correction.kind.as_str(), correction.action.as_str(), status])
+        .inc();
+      let error = match result {
+        Ok(()) => {
+          info!(
+            kind = correction.kind.as_str(),
+            action = correction.action.as_str(),
+            drift = correction.drift.as_str(),
+            resource_id = %correction.resource_id,
+            device_id = ?correction.device_id,
+            "reconciler corrected drift"
+          );
+          self.record_timeline(&correction).await;
+          None
+        }
+        Err(e) => {
+          warn!(
+            kind = correction.kind.as_str(),
+            action = correction.action.as_str(),
+            resource_id = %correction.resource_id,
+            error = %e,
+            "reconciler correction failed"
+          );
+          Some(e.to_string())
+        }
+      };
+      outcomes.push(CorrectionOutcome {
+        correction,
+        deferred: false,
+        error,
+      });
+    }
+    outcomes
+  }
+
+  fn gateway_request(&self, method: reqwest::Method, path: &str) -> Result<reqwest::RequestBuilder> {
+    let url = self.config.gateway_url.join(path)?;
+    let mut request = self.client.request(method, url);
+    if let Some(token) = &self.config.auth_token {
+      request = request.bearer_auth(token);
+    }
+    Ok(request)
+  }
+
+  async fn apply(&self, correction: &Correction) -> Result<()> {
+    let id = &correction.resource_id;
+    match (correction.kind, correction.action) {
+      (ResourceKind::Stream, CorrectionAction::Start) => {
+        let config = correction.stream.clone().context("stream start without config")?;
+        let response: StreamStartResponse = self
+          .gateway_request(reqwest::Method::POST, "v1/streams")?
+          .json(&StreamStartRequest {
+            config,
+            lease_ttl_secs: None,
+          })
+          .send()
+          .await?
+          .error_for_status()?
+          .json()
+          .await?;
+        if !response.accepted {
+          bail!("gateway refused: {}", response.message.unwrap_or_default());
+        }
+      }
+      (ResourceKind::Recording, CorrectionAction::Start) => {
+        let config = correction.recording.clone().context("recording start without config")?;
+        let response: RecordingStartResponse = self
+          .gateway_request(reqwest::Method::POST, "v1/recordings")?
+          .json(&RecordingStartRequest {
+            config,
+            lease_ttl_secs: None,
+            ai_config: None,
+            motion_policy: None,
+            tags: Vec::new(),
+            labels: Default::default(),
+          })
+          .send()
+          .await?
+          .error_for_status()?
+          .json()
+          .await?;
+        if !response.accepted {
+          bail!("gateway refused: {}", response.message.unwrap_or_default());
+        }
+      }
+      (ResourceKind::Stream, CorrectionAction::Stop) => {
+        self
+          .gateway_request(reqwest::Method::DELETE, &format!("v1/streams/{}", id))?
+          .send()
+          .await?
+          .error_for_status()?;
+      }
+      (ResourceKind::Recording, CorrectionAction::Stop) => {
+        self
+          .gateway_request(reqwest::Method::DELETE, &format!("v1/recordings/{}", id))?
+          .send()
+          .await?
+          .error_for_status()?;
+      }
+      (kind, CorrectionAction::MarkFailed) => {
+        let store = self
+          .state
+          .state_store()
+          .ok_or_else(|| anyhow!("StateStore not configured"))?;
+        let message = "node no longer registered (reconciler)";
+        match kind {
+          ResourceKind::Stream => store.update_stream_state(id, "error", Some(message)).await?,
+          ResourceKind::Recording => store.update_recording_state(id, "error", Some(message)).await?,
+        }
+      }
+    }
+    Ok(())
+  }
+
+  async fn record_timeline(&self, correction: &Correction) {
+    let kind = match (correction.kind, correction.action) {
+      (ResourceKind::Recording, CorrectionAction::Start) => TimelineEventKind::RecordingStarted,
+      (ResourceKind::Recording, CorrectionAction::Stop) => TimelineEventKind::RecordingStopped,
+      _ => TimelineEventKind::OperatorAction,
+    };
+    let mut event = TimelineEvent::new(
+      kind,
+      "coordinator",
+      format!(
+        "Reconciler {} {} {} ({})",
+        correction.action.as_str().replace('_', " "),
+        correction.kind.as_str(),
+        correction.resource_id,
+        correction.drift.as_str()
+      ),
+    )
+    .actor("reconciler")
+    .details(serde_json::json!({
+      "resource_id": correction.resource_id,
+      "drift": correction.drift,
+    }));
+    if let Some(device_id) = &correction.device_id {
+      event = event.camera(device_id.clone());
+    }
+    if let Some(tenant_id) = &correction.tenant_id {
+      event = event.tenant(tenant_id.clone());
+    }
+    if let Err(e) = self.state.timeline().append(vec![event]).await {
+      warn!(error = %e, "failed to record reconcile timeline event");
+    }
+  }
+}
+
+/// Documented with the other routes, although only mounted when enabled
+pub const OPENAPI_OPERATIONS: &[(&str, &str, &str, &str)] = &[
+  ("GET", "/v1/reconcile", "reconcile", "Drift and corrections of the last reconcile pass"),
+  ("POST", "/v1/reconcile/run", "reconcile", "Run a reconcile pass now (leader only)"),
+  (
+    "POST",
+    "/v1/reconcile/devices/:device_id/recovered",
+    "reconcile",
+    "Restart the stream and recording that failed while a device was offline (leader only; called by device-manager)",
+  ),
+];
+
+pub fn router(reconciler: Arc<Reconciler>) -> Router {
+  Router::new()
+    .route("/v1/reconcile", get(last_report))
+    .route("/v1/reconcile/run", post(run_now))
+    .route("/v1/reconcile/devices/:device_id/recovered", post(device_recovered))
+    .with_state(reconciler)
+}
+
+async fn last_report(State(reconciler): State<Arc<Reconciler>>) -> Result<Json<ReconcileReport>, ApiError> {
+  reconciler
+    .last_report()
+    .await
+    .map(Json)
+    .ok_or_else(|| ApiError::not_found("no reconcile pass has run yet"))
+}
+
+async fn run_now(State(reconciler): State<Arc<Reconciler>>) -> Result<Json<ReconcileReport>, ApiError> {
+  if !reconciler.is_leader().await {
+    return Err(ApiError::conflict("only the leader reconciles"));
+  }
+  Ok(Json(reconciler.run_pass().await?))
+}
+
+async fn device_recovered(
+  State(reconciler): State<Arc<Reconciler>>,
+  Path(device_id): Path<String>,
+) -> Result<Json<ReconcileReport>, ApiError> {
+  if !reconciler.is_leader().await {
+    return Err(ApiError::conflict("only the leader reconciles"));
+  }
+  Ok(Json(reconciler.recover_device(&device_id).await?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::{recordings::RecordingState, streams::StreamState};
+
+  fn device(id: &str, auto_start: bool, recording_enabled: bool) -> DesiredDevice {
+    DesiredDevice {
+      device_id: id.to_string(),
+      tenant_id: "t1".to_string(),
+      primary_uri: format!("rtsp://{id}.cam/stream"),
+      status: "online".to_string(),
+      auto_start,
+      recording_enabled,
+    }
+  }
+
+  fn stream(id: &str, camera: &str, state: StreamState, node: &str) -> StreamInfo {
+    StreamInfo {
+      config: StreamConfig {
+        id: id.to_string(),
+        camera_id: Some(camera.to_string()),
+        uri: format!("rtsp://user:pw@{camera}.cam/stream"),
+        codec: None,
+        container: None,
+        substream_uri: None,
+        thermal_palette: None,
+      },
+      state,
+      lease_id: None,
+      last_error: None,
+      node_id: Some(node.to_string()),
+      playlist_path: None,
+      output_dir: None,
+      started_at: Some(100),
+      stopped_at: None,
+    }
+  }
+
+  fn recording(id: &str, source_stream: &str, state: RecordingState, node: &str) -> RecordingInfo {
+    RecordingInfo {
+      config: RecordingConfig {
+        id: id.to_string(),
+        source_stream_id: Some(source_stream.to_string()),
+        source_uri: None,
+        retention_hours: Some(72),
+        format: None,
+        encoding: None,
+      },
+      state,
+      lease_id: None,
+      storage_path: None,
+      last_error: None,
+      started_at: Some(100),
+      stopped_at: None,
+      node_id: Some(node.to_string()),
+      metadata: None,
+    }
+  }
+
+  fn nodes(ids: &[&str]) -> HashSet<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+  }
+
+  #[test]
+  fn starts_missing_stream_and_recording_with_last_known_source() {
+    let observed = Observed {
+      devices: vec![device("cam-1", true, true)],
+      streams: vec![stream("auto-cam-1", "cam-1", StreamState::Error, "sn-1")],
+      recordings: vec![recording("rec-old", "auto-cam-1", RecordingState::Stopped, "rec-1")],
+      stream_nodes: nodes(&["sn-1"]),
+      recorder_nodes: nodes(&["rec-1"]),
+    };
+    let corrections = plan(&observed, 500);
+    assert_eq!(corrections.len(), 2);
+
+    let start = &corrections[0];
+    assert_eq!(
+      (start.kind, start.action, start.drift),
+      (ResourceKind::Stream, CorrectionAction::Start, Drift::Missing)
+    );
+    let config = start.stream.as_ref().unwrap();
+    assert_eq!(config.id, "auto-cam-1");
+    assert_eq!(config.uri, "rtsp://user:pw@cam-1.cam/stream");
+
+    let record = &corrections[1];
+    assert_eq!((record.kind, record.action), (ResourceKind::Recording, CorrectionAction::Start));
+    let config = record.recording.as_ref().unwrap();
+    assert_eq!(config.id, "auto-rec-cam-1-500");
+    assert_eq!(config.retention_hours, Some(72));
+  }
+
+  #[test]
+  fn stale_entries_are_failed_and_replaced_under_a_new_id() {
+    let observed = Observed {
+      devices: vec![device("cam-1", true, false)],
+      streams: vec![stream("auto-cam-1", "cam-1", StreamState::Running, "sn-dead")],
+      stream_nodes: nodes(&["sn-1"]),
+      ..Default::default()
+    };
+    let corrections = plan(&observed, 500);
+    assert_eq!(corrections.len(), 2);
+    assert_eq!((corrections[0].action, corrections[0].drift), (CorrectionAction::MarkFailed, Drift::Stale));
+    assert_eq!(corrections[1].resource_id, "auto-cam-1-500");
+
+    // Without registered nodes liveness is unknown, so the stream is trusted
+    let observed = Observed {
+      stream_nodes: HashSet::new(),
+      ..observed
+    };
+    assert!(plan(&observed, 500).is_empty());
+  }
+
+  #[test]
+  fn stops_only_owned_or_deleted_device_resources() {
+    let observed = Observed {
+      devices: vec![device("cam-1", false, false)],
+      streams: vec![
+        stream("auto-cam-1", "cam-1", StreamState::Running, "sn-1"),
+        stream("manual-cam-1", "cam-1", StreamState::Running, "sn-1"),
+        stream("auto-cam-9", "cam-9", StreamState::Running, "sn-1"),
+      ],
+      recordings: vec![
+        recording("auto-rec-cam-1-1", "manual-cam-1", RecordingState::Recording, "rec-1"),
+        recording("rec-cam-9", "auto-cam-9", RecordingState::Recording, "rec-1"),
+      ],
+      stream_nodes: nodes(&["sn-1"]),
+      recorder_nodes: nodes(&["rec-1"]),
+    };
+    let corrections = plan(&observed, 500);
+    assert!(
+      corrections
+        .iter()
+        .all(|c| (c.action, c.drift) == (CorrectionAction::Stop, Drift::Orphaned))
+    );
+    let stopped: Vec<(ResourceKind, &str)> = corrections.iter().map(|c| (c.kind, c.resource_id.as_str())).collect();
+    assert_eq!(
+      stopped,
+      vec![
+        (ResourceKind::Stream, "auto-cam-1"),
+        (ResourceKind::Recording, "auto-rec-cam-1-1"),
+        (ResourceKind::Stream, "auto-cam-9"),
+        (ResourceKind::Recording, "rec-cam-9"),
+      ]
+    );
+  }
+
+  #[test]
+  fn recovery_restarts_the_failed_stream_and_recording() {
+    let mut manual = stream("manual-cam-1", "cam-1", StreamState::Error, "sn-1");
+    manual.started_at = Some(200);
+    let mut failed = recording("rec-cam-1-1700000000", "manual-cam-1", RecordingState::Error, "rec-1");
+    failed.started_at = Some(200);
+    let observed = Observed {
+      devices: vec![device("cam-1", false, false)],
+      streams: vec![stream("old-cam-1", "cam-1", StreamState::Stopped, "sn-1"), manual],
+      recordings: vec![recording("rec-old", "old-cam-1", RecordingState::Stopped, "rec-1"), failed],
+      stream_nodes: nodes(&["sn-1"]),
+      recorder_nodes: nodes(&["rec-1"]),
+    };
+    let corrections = plan_recovery(&observed, "cam-1", 1800000000);
+    assert_eq!(corrections.len(), 2);
+    assert!(corrections.iter().all(|c| (c.action, c.drift) == (CorrectionAction::Start, Drift::Failed)));
+
+    let config = corrections[0].stream.as_ref().unwrap();
+    assert_eq!(config.id, "manual-cam-1");
+    let config = corrections[1].recording.as_ref().unwrap();
+    assert_eq!(config.id, "rec-cam-1-1800000000");
+    assert_eq!(corrections[1].resource_id, config.id);
+    assert_eq!(config.source_stream_id, None);
+    assert_eq!(config.source_uri.as_deref(), Some("rtsp://user:pw@cam-1.cam/stream"));
+
+    // Nothing to do once something runs again, or while still offline
+    let mut running = observed.clone();
+    running.streams.push(stream("auto-cam-1", "cam-1", StreamState::Running, "sn-1"));
+    running.recordings.push(recording("auto-rec-cam-1-5", "auto-cam-1", RecordingState::Recording, "rec-1"));
+    assert!(plan_recovery(&running, "cam-1", 1800000000).is_empty());
+    let mut offline = observed;
+    offline.devices[0].status = "offline".to_string();
+    assert!(plan_recovery(&offline, "cam-1", 1800000000).is_empty());
+  }
+
+  #[test]
+  fn offline_devices_are_not_started() {
+    let mut offline = device("cam-1", true, true);
+    offline.status = "offline".to_string();
+    let observed = Observed {
+      devices: vec![offline],
+      ..Default::default()
+    };
+    assert!(plan(&observed, 500).is_empty());
+  }
+}
@@ -1,18 +1,36 @@
-use crate::{cluster::ClusterStatus, error::ApiError, state::CoordinatorState, state_routes};
+use crate::{bandwidth, cluster::ClusterStatus, error::ApiError, state::CoordinatorState, state_routes};
 use axum::{
   Json, Router,
-  extract::{Query, State},
+  extract::{DefaultBodyLimit, Path, Query, State},
+  http::{HeaderMap, StatusCode},
   middleware,
+  response::{IntoResponse, Response},
   routing::{get, post},
 };
-use common::leases::{
-  LeaseAcquireRequest, LeaseAcquireResponse, LeaseKind, LeaseRecord, LeaseReleaseRequest,
-  LeaseReleaseResponse, LeaseRenewRequest, LeaseRenewResponse,
+use common::{
+  ai_tasks::CameraAnalyticsDocument,
+  bandwidth::{BandwidthBudgets, BandwidthDirective, BandwidthReport, UplinkStatus},
+  federation::{
+    FederatedAlert, FederatedDevice, FederationHealth, FederationQuery, MAX_REPORT_BYTES, SiteRecord, SiteReport,
+  },
+  leases::{
+    LeaseAcquireRequest, LeaseAcquireResponse, LeaseHistoryEntry, LeaseHistoryQuery, LeaseKind,
+    LeaseRecord, LeaseReleaseRequest, LeaseReleaseResponse, LeaseRenewRequest, LeaseRenewResponse,
+  },
+  nodes::{
+    NodeDeregisterRequest, NodeDeregisterResponse, NodeDrainRequest, NodeKind, NodeRecord, NodeRegisterRequest,
+  },
+  openapi::{OpenApiSpec, openapi_routes},
+  service_config::{MAX_WAIT_SECS, ServiceConfig, ServiceConfigRollback, ServiceConfigUpdate},
+  state_store::Namespace,
+  timeline::{TimelineEvent, TimelineQuery},
 };
+use common::api_version::{self, Deprecation};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use telemetry::{trace_http_request, CorrelationIdLayer};
 use tower::ServiceBuilder;
-use tracing::debug;
+use tracing::{debug, info};
 
 pub fn router(state: CoordinatorState) -> Router {
   Router::new()
@@ -23,10 +41,34 @@ pub fn router(state: CoordinatorState) -> Router {
     .route("/v1/leases/acquire", post(acquire_lease))
     .route("/v1/leases/renew", post(renew_lease))
     .route("/v1/leases/release", post(release_lease))
+    .route("/v1/leases/history", get(lease_history))
+    .route("/v1/nodes", get(list_nodes))
+    .route("/v1/nodes/register", post(register_node))
+    .route("/v1/nodes/deregister", post(deregister_node))
+    .route("/v1/nodes/drain", post(drain_node))
+    .route("/v1/config/:service", get(get_service_config).put(publish_service_config))
+    .route("/v1/config/:service/versions", get(list_service_config_versions))
+    .route("/v1/config/:service/versions/:version", get(get_service_config_version))
+    .route("/v1/config/:service/rollback", post(rollback_service_config))
+    .route("/v1/bandwidth", get(bandwidth_status))
+    .route("/v1/bandwidth/reports", post(report_bandwidth))
+    .route("/v1/timeline", get(query_timeline))
+    .route("/v1/timeline/events", post(append_timeline_events))
+    .route("/v1/federation/sites", get(list_sites))
+    .route(
+      "/v1/federation/sites/report",
+      post(report_site).layer(DefaultBodyLimit::max(MAX_REPORT_BYTES)),
+    )
+    .route("/v1/federation/sites/remove", post(remove_site))
+    .route("/v1/federation/sites/:site_id", get(get_site))
+    .route("/v1/federation/devices", get(federated_devices))
+    .route("/v1/federation/alerts", get(federated_alerts))
+    .route("/v1/federation/health", get(federation_health))
     .route("/cluster/status", get(cluster_status))
     .route("/cluster/vote", post(cluster_vote))
     .route("/cluster/heartbeat", post(cluster_heartbeat))
     .merge(state_routes::state_router())
+    .merge(openapi_routes(&openapi()))
     .layer(
       ServiceBuilder::new()
         .layer(middleware::from_fn(trace_http_request))
@@ -35,6 +77,65 @@ pub fn router(state: CoordinatorState) -> Router {
     .with_state(state)
 }
 
+/// Coordinator routes scheduled for removal; each gets `Deprecation` and
+/// `Sunset` headers until it is deleted
+pub const DEPRECATIONS: &[Deprecation] = &[];
+
+/// Add API version discovery, the v2 compatibility shim and deprecation
+/// headers; call after every route is merged in
+pub fn versioned(app: Router) -> Router {
+  api_version::versioned(app, "coordinator", DEPRECATIONS)
+}
+
+/// OpenAPI description of the coordinator API
+pub fn openapi() -> OpenApiSpec {
+  OpenApiSpec::new("coordinator", env!("CARGO_PKG_VERSION"))
+    .with_description("Lease-based job scheduler and cluster state store")
+    .operations(&[
+      ("GET", "/healthz", "health", "Liveness probe"),
+      ("GET", "/readyz", "health", "Readiness probe"),
+      ("GET", "/metrics", "health", "Prometheus metrics"),
+      ("GET", "/api/versions", "health", "Supported API versions and deprecated routes"),
+      ("GET", "/v1/leases", "leases", "List active leases"),
+      ("POST", "/v1/leases/acquire", "leases", "Acquire a lease"),
+      ("POST", "/v1/leases/renew", "leases", "Renew a lease"),
+      ("POST", "/v1/leases/release", "leases", "Release a lease"),
+      ("GET", "/v1/leases/history", "leases", "Lease acquire, renew, release, expire and transfer records by resource_id, holder_id, event and time range, oldest first"),
+      ("GET", "/v1/nodes", "nodes", "List registered stream, recorder and AI nodes"),
+      ("POST", "/v1/nodes/register", "nodes", "Register or refresh a node"),
+      ("POST", "/v1/nodes/deregister", "nodes", "Remove a node registration"),
+      ("POST", "/v1/nodes/drain", "nodes", "Stop (or resume) routing new work to a node"),
+      ("GET", "/v1/config/:service", "config", "Latest service configuration; long-polls with ?after=<version>"),
+      ("PUT", "/v1/config/:service", "config", "Publish a new service configuration version"),
+      ("GET", "/v1/config/:service/versions", "config", "List service configuration versions"),
+      ("GET", "/v1/config/:service/versions/:version", "config", "Get a service configuration version"),
+      ("POST", "/v1/config/:service/rollback", "config", "Republish an earlier service configuration version"),
+      ("GET", "/v1/bandwidth", "bandwidth", "Usage and budget of every uplink"),
+      ("POST", "/v1/bandwidth/reports", "bandwidth", "Report a node's bandwidth usage and get its directive"),
+      ("GET", "/v1/timeline", "timeline", "Cluster events by time range, camera, tenant and kind (oldest first)"),
+      ("POST", "/v1/timeline/events", "timeline", "Append a batch of timeline events"),
+      ("GET", "/v1/federation/sites", "federation", "Edge sites reporting to this central instance"),
+      ("POST", "/v1/federation/sites/report", "federation", "Store the latest report of an edge site"),
+      ("POST", "/v1/federation/sites/remove", "federation", "Forget an edge site"),
+      ("GET", "/v1/federation/sites/:site_id", "federation", "Get an edge site"),
+      ("GET", "/v1/federation/devices", "federation", "Device inventory across sites"),
+      ("GET", "/v1/federation/alerts", "federation", "Recent alerts across sites, newest first"),
+      ("GET", "/v1/federation/health", "federation", "Site, device and alert counts across sites"),
+      ("GET", "/cluster/status", "cluster", "Cluster status"),
+      ("POST", "/cluster/vote", "cluster", "Leader election vote"),
+      ("POST", "/cluster/heartbeat", "cluster", "Leader heartbeat"),
+    ])
+    .operations(state_routes::OPENAPI_OPERATIONS)
+    .operations(crate::reconcile::OPENAPI_OPERATIONS)
+    .operations(FAULT_OPERATIONS)
+}
+
+/// Fault injection routes, in builds that have them
+#[cfg(feature = "fault-injection")]
+const FAULT_OPERATIONS: &[(&str, &str, &str, &str)] = crate::faults::OPENAPI_OPERATIONS;
+#[cfg(not(feature = "fault-injection"))]
+const FAULT_OPERATIONS: &[(&str, &str, &str, &str)] = &[];
+
 async fn healthz() -> &'static str {
   "ok"
 }
@@ -81,6 +182,19 @@ async fn list_leases(
   Ok(Json(records))
 }
 
+async fn lease_history(
+  State(state): State<CoordinatorState>,
+  Query(query): Query<LeaseHistoryQuery>,
+) -> Result<Json<Vec<LeaseHistoryEntry>>, ApiError> {
+  if let (Some(from), Some(to)) = (query.from_epoch_ms, query.to_epoch_ms)
+    && from > to
+  {
+    return Err(ApiError::bad_request("from_epoch_ms must not be after to_epoch_ms"));
+  }
+  let entries = state.store().history(&query).await?;
+  Ok(Json(entries))
+}
+
 /// Forward a request to the leader if this node is a follower
 async fn forward_to_leader<T: Serialize, R: serde::de::DeserializeOwned>(
   state: &CoordinatorState,
@@ -127,6 +241,40 @@ async fn forward_to_leader<T: Serialize, R: serde::de::DeserializeOwned>(
   Ok(result)
 }
 
+/// GET counterpart of [`forward_to_leader`] for reads served only by the leader
+async fn get_from_leader<R: serde::de::DeserializeOwned>(
+  state: &CoordinatorState,
+  path: &str,
+) -> Result<R, ApiError> {
+  let cluster = state
+    .cluster()
+    .ok_or_else(|| ApiError::internal("clustering not enabled"))?;
+  let leader_addr = cluster
+    .leader_addr()
+    .await
+    .ok_or_else(|| ApiError::internal("no leader available"))?;
+
+  let url = format!("http://{}{}", leader_addr, path);
+  debug!(url = %url, "reading from leader");
+
+  let response = reqwest::Client::new()
+    .get(&url)
+    .send()
+    .await
+    .map_err(|e| ApiError::internal(format!("failed to forward request: {}", e)))?;
+  if !response.status().is_success() {
+    return Err(ApiError::internal(format!(
+      "leader returned error: {}",
+      response.status()
+    )));
+  }
+
+  response
+    .json::<R>()
+    .await
+    .map_err(|e| ApiError::internal(format!("failed to parse leader response: {}", e)))
+}
+
 async fn acquire_lease(
   State(state): State<CoordinatorState>,
   Json(request): Json<LeaseAcquireRequest>,
@@ -178,6 +326,335 @@ async fn release_lease(
   Ok(Json(resp))
 }
 
+#[derive(Debug, Deserialize)]
+struct ListNodesQuery {
+  kind: Option<String>,
+}
+
+async fn list_nodes(
+  State(state): State<CoordinatorState>,
+  Query(query): Query<ListNodesQuery>,
+) -> Result<Json<Vec<NodeRecord>>, ApiError> {
+  let kind = match query.kind.as_deref() {
+    None | Some("") => None,
+    Some(kind_str) => Some(
+      kind_str
+        .parse::<NodeKind>()
+        .map_err(|_| ApiError::bad_request(format!("unknown node kind '{}'", kind_str)))?,
+    ),
+  };
+
+  // Registrations live on the leader; followers proxy reads to it
+  if let Some(cluster) = state.cluster() {
+    if !cluster.is_leader().await {
+      let path = match kind {
+        Some(kind) => format!("/v1/nodes?kind={}", kind),
+        None => "/v1/nodes".to_string(),
+      };
+      let records = get_from_leader(&state, &path).await?;
+      return Ok(Json(records));
+    }
+  }
+
+  Ok(Json(state.nodes().list(kind).await))
+}
+
+async fn register_node(
+  State(state): State<CoordinatorState>,
+  Json(request): Json<NodeRegisterRequest>,
+) -> Result<Json<NodeRecord>, ApiError> {
+  if let Some(cluster) = state.cluster() {
+    if !cluster.is_leader().await {
+      let resp = forward_to_leader(&state, "/v1/nodes/register", &request).await?;
+      return Ok(Json(resp));
+    }
+  }
+
+  let record = state.nodes().register(request).await?;
+  debug!(node_id = %record.node_id, kind = %record.kind, "node registration refreshed");
+  Ok(Json(record))
+}
+
+async fn deregister_node(
+  State(state): State<CoordinatorState>,
+  Json(request): Json<NodeDeregisterRequest>,
+) -> Result<Json<NodeDeregisterResponse>, ApiError> {
+  if let Some(cluster) = state.cluster() {
+    if !cluster.is_leader().await {
+      let resp = forward_to_leader(&state, "/v1/nodes/deregister", &request).await?;
+      return Ok(Json(resp));
+    }
+  }
+
+  let removed = state.nodes().deregister(&request.node_id).await;
+  Ok(Json(NodeDeregisterResponse { removed }))
+}
+
+async fn drain_node(
+  State(state): State<CoordinatorState>,
+  Json(request): Json<NodeDrainRequest>,
+) -> Result<Json<NodeRecord>, ApiError> {
+  if let Some(cluster) = state.cluster() {
+    if !cluster.is_leader().await {
+      let resp = forward_to_leader(&state, "/v1/nodes/drain", &request).await?;
+      return Ok(Json(resp));
+    }
+  }
+
+  let record = state
+    .nodes()
+    .set_draining(&request.node_id, request.draining)
+    .await
+    .ok_or_else(|| ApiError::not_found(format!("node {} is not registered", request.node_id)))?;
+  info!(node_id = %record.node_id, draining = record.draining, "node drain state changed");
+  Ok(Json(record))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SiteRemoveRequest {
+  site_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SiteRemoveResponse {
+  removed: bool,
+}
+
+/// Follower that must proxy federation and bandwidth calls to the leader,
+/// which holds the site registry and bandwidth reports like it holds node
+/// registrations
+async fn follower(state: &CoordinatorState) -> bool {
+  match state.cluster() {
+    Some(cluster) => !cluster.is_leader().await,
+    None => false,
+  }
+}
+
+fn with_query(path: &str, query: &FederationQuery) -> Result<String, ApiError> {
+  let query = serde_urlencoded::to_string(query)
+    .map_err(|e| ApiError::internal(format!("failed to encode query: {}", e)))?;
+  Ok(if query.is_empty() { path.to_string() } else { format!("{}?{}", path, query) })
+}
+
+async fn report_site(
+  State(state): State<CoordinatorState>,
+  Json(report): Json<SiteReport>,
+) -> Result<Json<SiteRecord>, ApiError> {
+  if follower(&state).await {
+    return Ok(Json(forward_to_leader(&state, "/v1/federation/sites/report", &report).await?));
+  }
+  let site_id = report.site_id.clone();
+  let record = state.sites().report(&site_id, report).await?;
+  debug!(site_id = %record.site_id, devices = record.device_count, "site report stored");
+  Ok(Json(record))
+}
+
+async fn remove_site(
+  State(state): State<CoordinatorState>,
+  Json(request): Json<SiteRemoveRequest>,
+) -> Result<Json<SiteRemoveResponse>, ApiError> {
+  if follower(&state).await {
+    return Ok(Json(forward_to_leader(&state, "/v1/federation/sites/remove", &request).await?));
+  }
+  let removed = state.sites().remove(&request.site_id).await;
+  Ok(Json(SiteRemoveResponse { removed }))
+}
+
+async fn list_sites(State(state): State<CoordinatorState>) -> Result<Json<Vec<SiteRecord>>, ApiError> {
+  if follower(&state).await {
+    return Ok(Json(get_from_leader(&state, "/v1/federation/sites").await?));
+  }
+  Ok(Json(state.sites().list().await))
+}
+
+async fn get_site(
+  State(state): State<CoordinatorState>,
+  Path(site_id): Path<String>,
+) -> Result<Json<SiteRecord>, ApiError> {
+  common::validation::validate_id(&site_id, "site_id")
+    .map_err(|e| ApiError::bad_request(format!("invalid site_id: {}", e)))?;
+  if follower(&state).await {
+    return Ok(Json(get_from_leader(&state, &format!("/v1/federation/sites/{}", site_id)).await?));
+  }
+  state
+    .sites()
+    .get(&site_id)
+    .await
+    .map(Json)
+    .ok_or_else(|| ApiError::not_found(format!("site '{}' not found", site_id)))
+}
+
+async fn federated_devices(
+  State(state): State<CoordinatorState>,
+  Query(query): Query<FederationQuery>,
+) -> Result<Json<Vec<FederatedDevice>>, ApiError> {
+  if follower(&state).await {
+    return Ok(Json(get_from_leader(&state, &with_query("/v1/federation/devices", &query)?).await?));
+  }
+  Ok(Json(state.sites().devices(&query).await))
+}
+
+async fn federated_alerts(
+  State(state): State<CoordinatorState>,
+  Query(query): Query<FederationQuery>,
+) -> Result<Json<Vec<FederatedAlert>>, ApiError> {
+  if follower(&state).await {
+    return Ok(Json(get_from_leader(&state, &with_query("/v1/federation/alerts", &query)?).await?));
+  }
+  Ok(Json(state.sites().alerts(&query).await))
+}
+
+async fn federation_health(State(state): State<CoordinatorState>) -> Result<Json<FederationHealth>, ApiError> {
+  if follower(&state).await {
+    return Ok(Json(get_from_leader(&state, "/v1/federation/health").await?));
+  }
+  Ok(Json(state.sites().health().await))
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigWatchQuery {
+  /// Return only a version newer than this, waiting for one if needed
+  after: Option<u64>,
+  wait_secs: Option<u64>,
+}
+
+/// User recorded as the author of a change, as forwarded by admin-gateway
+fn config_author(headers: &HeaderMap) -> Option<String> {
+  headers
+    .get(common::gateway_identity::USER_ID_HEADER)
+    .and_then(|v| v.to_str().ok())
+    .map(str::to_string)
+}
+
+async fn get_service_config(
+  State(state): State<CoordinatorState>,
+  Path(service): Path<String>,
+  Query(query): Query<ConfigWatchQuery>,
+) -> Result<Response, ApiError> {
+  let configs = state.configs();
+  let Some(after) = query.after else {
+    return match configs.get(&service, None).await? {
+      Some(config) => Ok(Json(config).into_response()),
+      None => Err(ApiError::not_found(format!("no configuration for '{}'", service))),
+    };
+  };
+
+  let wait = Duration::from_secs(query.wait_secs.unwrap_or(0).min(MAX_WAIT_SECS));
+  match configs.wait_for_newer(&service, after, wait).await? {
+    Some(config) => Ok(Json(config).into_response()),
+    None => Ok(StatusCode::NOT_MODIFIED.into_response()),
+  }
+}
+
+async fn publish_service_config(
+  State(state): State<CoordinatorState>,
+  Path(service): Path<String>,
+  headers: HeaderMap,
+  Json(update): Json<ServiceConfigUpdate>,
+) -> Result<(StatusCode, Json<ServiceConfig>), ApiError> {
+  // The coordinator consumes this document itself, so reject broken budgets
+  // up front rather than ignoring them later
+  if service == common::bandwidth::CONFIG_SERVICE {
+    serde_json::from_value::<BandwidthBudgets>(update.document.clone())
+      .map_err(|e| e.to_string())
+      .and_then(|budgets| budgets.validate())
+      .map_err(|e| ApiError::bad_request(format!("invalid bandwidth budgets: {e}")))?;
+  }
+  // AI nodes skip versions they cannot apply, so refuse them here
+  if service == common::ai_tasks::CAMERA_ANALYTICS_SERVICE {
+    serde_json::from_value::<CameraAnalyticsDocument>(update.document.clone())
+      .map_err(|e| e.to_string())
+      .and_then(|document| document.validate())
+      .map_err(|e| ApiError::bad_request(format!("invalid camera analytics: {e}")))?;
+  }
+  let config = state
+    .configs()
+    .publish(&service, update, config_author(&headers))
+    .await?;
+  Ok((StatusCode::CREATED, Json(config)))
+}
+
+async fn list_service_config_versions(
+  State(state): State<CoordinatorState>,
+  Path(service): Path<String>,
+) -> Result<Json<Vec<ServiceConfig>>, ApiError> {
+  Ok(Json(state.configs().history(&service).await?))
+}
+
+async fn get_service_config_version(
+  State(state): State<CoordinatorState>,
+  Path((service, version)): Path<(String, u64)>,
+) -> Result<Json<ServiceConfig>, ApiError> {
+  state
+    .configs()
+    .get(&service, Some(version))
+    .await?
+    .map(Json)
+    .ok_or_else(|| ApiError::not_found(format!("version {} of '{}' not found", version, service)))
+}
+
+async fn rollback_service_config(
+  State(state): State<CoordinatorState>,
+  Path(service): Path<String>,
+  headers: HeaderMap,
+  Json(rollback): Json<ServiceConfigRollback>,
+) -> Result<(StatusCode, Json<ServiceConfig>), ApiError> {
+  let config = state
+    .configs()
+    .rollback(&service, rollback, config_author(&headers))
+    .await?;
+  Ok((StatusCode::CREATED, Json(config)))
+}
+
+async fn bandwidth_budgets(state: &CoordinatorState) -> Result<BandwidthBudgets, ApiError> {
+  let config = state.configs().get(common::bandwidth::CONFIG_SERVICE, None).await?;
+  Ok(bandwidth::parse_budgets(config.as_ref().map(|c| &c.document)))
+}
+
+async fn report_bandwidth(
+  State(state): State<CoordinatorState>,
+  Json(report): Json<BandwidthReport>,
+) -> Result<Json<BandwidthDirective>, ApiError> {
+  // Usage is summed on the leader so every node of an uplink sees the same state
+  if follower(&state).await {
+    return Ok(Json(forward_to_leader(&state, "/v1/bandwidth/reports", &report).await?));
+  }
+  let budgets = bandwidth_budgets(&state).await?;
+  Ok(Json(state.bandwidth().report(report, &budgets).await?))
+}
+
+async fn bandwidth_status(State(state): State<CoordinatorState>) -> Result<Json<Vec<UplinkStatus>>, ApiError> {
+  if follower(&state).await {
+    return Ok(Json(get_from_leader(&state, "/v1/bandwidth").await?));
+  }
+  let budgets = bandwidth_budgets(&state).await?;
+  Ok(Json(state.bandwidth().status(&budgets).await))
+}
+
+#[derive(Debug, Serialize)]
+struct TimelineAppendResponse {
+  accepted: usize,
+}
+
+async fn append_timeline_events(
+  State(state): State<CoordinatorState>,
+  Json(events): Json<Vec<TimelineEvent>>,
+) -> Result<(StatusCode, Json<TimelineAppendResponse>), ApiError> {
+  state
+    .namespaces()
+    .admit(Namespace::TimelineEvents, async { Ok(false) })
+    .await?;
+  let accepted = state.timeline().append(events).await?;
+  Ok((StatusCode::ACCEPTED, Json(TimelineAppendResponse { accepted })))
+}
+
+async fn query_timeline(
+  State(state): State<CoordinatorState>,
+  Query(query): Query<TimelineQuery>,
+) -> Result<Json<Vec<TimelineEvent>>, ApiError> {
+  Ok(Json(state.timeline().query(&query).await?))
+}
+
 async fn cluster_status(
   State(state): State<CoordinatorState>,
 ) -> Result<Json<ClusterStatus>, ApiError> {
@@ -301,6 +778,72 @@ mod tests {
     assert_eq!(leases[0].resource_id, "cam1");
   }
 
+  #[tokio::test]
+  async fn lease_history_by_resource() {
+    let app = router(test_state());
+    let acquire = |holder: &str| {
+      Request::builder()
+        .method("POST")
+        .uri("/v1/leases/acquire")
+        .header("content-type", "application/json")
+        .body(Body::from(
+          json!({"resource_id": "cam1", "holder_id": holder, "kind": "stream", "ttl_secs": 15}).to_string(),
+        ))
+        .unwrap()
+    };
+    let resp = app.clone().oneshot(acquire("node-a")).await.unwrap();
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let granted: LeaseAcquireResponse = serde_json::from_slice(&bytes).unwrap();
+    let lease_id = granted.record.unwrap().lease_id;
+
+    let release = Request::builder()
+      .method("POST")
+      .uri("/v1/leases/release")
+      .header("content-type", "application/json")
+      .body(Body::from(json!({"lease_id": lease_id}).to_string()))
+      .unwrap();
+    app.clone().oneshot(release).await.unwrap();
+    app.clone().oneshot(acquire("node-b")).await.unwrap();
+
+    let get = |uri: &str| Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap();
+    let resp = app.clone().oneshot(get("/v1/leases/history?resource_id=cam1")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let history: Vec<LeaseHistoryEntry> = serde_json::from_slice(&bytes).unwrap();
+    let events: Vec<_> = history.iter().map(|e| e.event.as_str()).collect();
+    assert_eq!(events, ["acquire", "release", "transfer"]);
+    assert_eq!(history[2].previous_holder_id.as_deref(), Some("node-a"));
+
+    let resp = app
+      .oneshot(get("/v1/leases/history?from_epoch_ms=2000&to_epoch_ms=1000"))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn openapi_document_served() {
+    let app = router(test_state());
+    let resp = app
+      .oneshot(
+        Request::builder()
+          .method("GET")
+          .uri("/openapi.json")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let doc: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(doc["info"]["title"], "coordinator");
+    assert!(doc["paths"]["/v1/leases/acquire"]["post"].is_object());
+    assert!(doc["paths"]["/v1/state/streams/{stream_id}"]["get"].is_object());
+  }
+
   #[tokio::test]
   async fn release_clears_lease() {
     let app = router(test_state());
@@ -368,4 +911,109 @@ mod tests {
     let leases: Vec<LeaseRecord> = serde_json::from_slice(&bytes).unwrap();
     assert!(leases.is_empty());
   }
+
+  #[tokio::test]
+  async fn register_then_list_nodes_by_kind() {
+    let app = router(test_state());
+    for (node_id, kind) in [("sn-1", "stream"), ("rec-1", "recorder")] {
+      let resp = app
+        .clone()
+        .oneshot(
+          Request::builder()
+            .method("POST")
+            .uri("/v1/nodes/register")
+            .header("content-type", "application/json")
+            .body(Body::from(
+              json!({
+                  "node_id": node_id,
+                  "kind": kind,
+                  "base_url": format!("http://{node_id}:8080")
+              })
+              .to_string(),
+            ))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+      assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let resp = app
+      .clone()
+      .oneshot(
+        Request::builder()
+          .method("GET")
+          .uri("/v1/nodes?kind=recorder")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let nodes: Vec<NodeRecord> = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].node_id, "rec-1");
+    assert_eq!(nodes[0].base_url, "http://rec-1:8080/");
+
+    let resp = app
+      .oneshot(
+        Request::builder()
+          .method("GET")
+          .uri("/v1/nodes?kind=pipeline")
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+  }
+
+  #[tokio::test]
+  async fn service_config_publish_and_watch() {
+    let app = router(test_state());
+    let call = |method: &str, uri: &str, body: Option<serde_json::Value>| {
+      let mut req = Request::builder().method(method).uri(uri);
+      if body.is_some() {
+        req = req.header("content-type", "application/json");
+      }
+      let req = req
+        .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+        .unwrap();
+      app.clone().oneshot(req)
+    };
+
+    let resp = call("GET", "/v1/config/alert-service", None).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = call(
+      "PUT",
+      "/v1/config/alert-service",
+      Some(json!({"document": {"log_level": "debug"}, "comment": "verbose"})),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = call("GET", "/v1/config/alert-service?after=0&wait_secs=5", None)
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    let config: ServiceConfig = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(config.version, 1);
+    assert_eq!(config.document["log_level"], "debug");
+
+    let resp = call("GET", "/v1/config/alert-service?after=1", None).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    let resp = call("POST", "/v1/config/alert-service/rollback", Some(json!({"version": 7})))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+  }
 }
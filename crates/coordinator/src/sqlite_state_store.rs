@@ -0,0 +1,624 @@
+//! SQLite-backed state store for single-process deployments.
+//!
+//! Records are kept as JSON documents next to the columns queries filter on,
+//! so the schema needs no migrations beyond the `CREATE TABLE IF NOT EXISTS`
+//! run on connect.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use common::ai_tasks::{AiTaskInfo, AiTaskState};
+use common::idempotency::CachedResponse;
+use common::quota::TenantUsage;
+use common::recordings::{RecordingInfo, RecordingState};
+use common::service_config::ServiceConfig;
+use common::state_store::{Namespace, NamespaceUsage, StateStore};
+use common::streams::{StreamInfo, StreamState};
+use common::timeline::{TimelineEvent, TimelineQuery};
+use common::validation::safe_unix_timestamp;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::{QueryBuilder, Row, Sqlite};
+use std::str::FromStr;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS streams (
+        stream_id TEXT PRIMARY KEY,
+        node_id TEXT,
+        document TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS recordings (
+        recording_id TEXT PRIMARY KEY,
+        node_id TEXT,
+        document TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS ai_tasks (
+        task_id TEXT PRIMARY KEY,
+        node_id TEXT,
+        document TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS idempotency_keys (
+        idempotency_key TEXT PRIMARY KEY,
+        document TEXT NOT NULL,
+        expires_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS tenant_api_usage (
+        tenant_id TEXT NOT NULL,
+        period TEXT NOT NULL,
+        requests INTEGER NOT NULL DEFAULT 0,
+        rejected INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (tenant_id, period)
+    )",
+    "CREATE TABLE IF NOT EXISTS service_configs (
+        service TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        document TEXT NOT NULL,
+        PRIMARY KEY (service, version)
+    )",
+    "CREATE TABLE IF NOT EXISTS timeline_events (
+        id TEXT PRIMARY KEY,
+        timestamp_ms INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        camera_id TEXT,
+        tenant_id TEXT,
+        document TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS timeline_events_timestamp ON timeline_events (timestamp_ms, id)",
+];
+
+/// Tables holding one JSON document per id
+#[derive(Clone, Copy)]
+enum Documents {
+    Streams,
+    Recordings,
+    AiTasks,
+}
+
+impl Documents {
+    fn table(self) -> &'static str {
+        match self {
+            Documents::Streams => "streams",
+            Documents::Recordings => "recordings",
+            Documents::AiTasks => "ai_tasks",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Documents::Streams => "stream_id",
+            Documents::Recordings => "recording_id",
+            Documents::AiTasks => "task_id",
+        }
+    }
+}
+
+pub struct SqliteStateStore {
+    pool: SqlitePool,
+    /// Serializes read-modify-write updates of stored documents
+    updates: Mutex<()>,
+}
+
+impl SqliteStateStore {
+    /// Open (creating if needed) the database at `url`, e.g. `sqlite://data/state.db`
+    pub async fn connect(url: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)
+            .with_context(|| format!("invalid SQLite URL {url}"))?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .context("failed to open SQLite state store")?;
+        for statement in SCHEMA {
+            sqlx::query(statement)
+                .execute(&pool)
+                .await
+                .context("failed to create SQLite schema")?;
+        }
+        Ok(Self {
+            pool,
+            updates: Mutex::new(()),
+        })
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    async fn save_document<T: Serialize>(&self, kind: Documents, id: &str, node_id: Option<&str>, value: &T) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO {table} ({key}, node_id, document) VALUES (?1, ?2, ?3)
+             ON CONFLICT ({key}) DO UPDATE SET node_id = excluded.node_id, document = excluded.document",
+            table = kind.table(),
+            key = kind.key(),
+        );
+        sqlx::query(&sql)
+            .bind(id)
+            .bind(node_id)
+            .bind(serde_json::to_string(value)?)
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("Failed to save {}", kind.table()))?;
+        Ok(())
+    }
+
+    async fn get_document<T: DeserializeOwned>(&self, kind: Documents, id: &str) -> Result<Option<T>> {
+        let sql = format!("SELECT document FROM {} WHERE {} = ?1", kind.table(), kind.key());
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .with_context(|| format!("Failed to fetch from {}", kind.table()))?;
+        row.map(|r| Ok(serde_json::from_str(r.try_get("document")?)?))
+            .transpose()
+    }
+
+    /// Newest first, like the Postgres store
+    async fn list_documents<T: DeserializeOwned>(&self, kind: Documents, node_id: Option<&str>) -> Result<Vec<T>> {
+        let sql = format!(
+            "SELECT document FROM {} WHERE (?1 IS NULL OR node_id = ?1) ORDER BY rowid DESC",
+            kind.table()
+        );
+        let rows = sqlx::query(&sql)
+            .bind(node_id)
+            .fetch_all(&self.pool)
+            .await
+            .with_context(|| format!("Failed to list {}", kind.table()))?;
+        rows.iter()
+            .map(|r| Ok(serde_json::from_str(r.try_get("document")?)?))
+            .collect()
+    }
+
+    async fn delete_document(&self, kind: Documents, id: &str) -> Result<()> {
+        let sql = format!("DELETE FROM {} WHERE {} = ?1", kind.table(), kind.key());
+        sqlx::query(&sql)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("Failed to delete from {}", kind.table()))?;
+        Ok(())
+    }
+
+    /// Unknown states are stored as `error`, as the Postgres store reads them
+    fn parse_state<S: DeserializeOwned>(state: &str, error: S) -> S {
+        serde_json::from_value(serde_json::Value::String(state.to_string())).unwrap_or_else(|_| {
+            warn!("unknown state: {}, defaulting to error", state);
+            error
+        })
+    }
+
+    fn now_ms() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+#[async_trait]
+impl StateStore for SqliteStateStore {
+    async fn save_stream(&self, info: &StreamInfo) -> Result<()> {
+        self.save_document(Documents::Streams, &info.config.id, info.node_id.as_deref(), info)
+            .await
+    }
+
+    async fn get_stream(&self, stream_id: &str) -> Result<Option<StreamInfo>> {
+        self.get_document(Documents::Streams, stream_id).await
+    }
+
+    async fn list_streams(&self, node_id: Option<&str>) -> Result<Vec<StreamInfo>> {
+        self.list_documents(Documents::Streams, node_id).await
+    }
+
+    async fn delete_stream(&self, stream_id: &str) -> Result<()> {
+        self.delete_document(Documents::Streams, stream_id).await
+    }
+
+    async fn update_stream_state(&self, stream_id: &str, state: &str, error: Option<&str>) -> Result<()> {
+        let _guard = self.updates.lock().await;
+        if let Some(mut info) = self.get_stream(stream_id).await? {
+            info.state = Self::parse_state(state, StreamState::Error);
+            info.last_error = error.map(str::to_string);
+            self.save_stream(&info).await?;
+        }
+        Ok(())
+    }
+
+    async fn save_recording(&self, info: &RecordingInfo) -> Result<()> {
+        self.save_document(Documents::Recordings, &info.config.id, info.node_id.as_deref(), info)
+            .await
+    }
+
+    async fn get_recording(&self, recording_id: &str) -> Result<Option<RecordingInfo>> {
+        self.get_document(Documents::Recordings, recording_id).await
+    }
+
+    async fn list_recordings(&self, node_id: Option<&str>) -> Result<Vec<RecordingInfo>> {
+        self.list_documents(Documents::Recordings, node_id).await
+    }
+
+    async fn delete_recording(&self, recording_id: &str) -> Result<()> {
+        self.delete_document(Documents::Recordings, recording_id).await
+    }
+
+    async fn update_recording_state(&self, recording_id: &str, state: &str, error: Option<&str>) -> Result<()> {
+        let _guard = self.updates.lock().await;
+        if let Some(mut info) = self.get_recording(recording_id).await? {
+            info.state = Self::parse_state(state, RecordingState::Error);
+            info.last_error = error.map(str::to_string);
+            self.save_recording(&info).await?;
+        }
+        Ok(())
+    }
+
+    async fn save_ai_task(&self, info: &AiTaskInfo) -> Result<()> {
+        self.save_document(Documents::AiTasks, &info.config.id, info.node_id.as_deref(), info)
+            .await
+    }
+
+    async fn get_ai_task(&self, task_id: &str) -> Result<Option<AiTaskInfo>> {
+        self.get_document(Documents::AiTasks, task_id).await
+    }
+
+    async fn list_ai_tasks(&self, node_id: Option<&str>) -> Result<Vec<AiTaskInfo>> {
+        self.list_documents(Documents::AiTasks, node_id).await
+    }
+
+    async fn delete_ai_task(&self, task_id: &str) -> Result<()> {
+        self.delete_document(Documents::AiTasks, task_id).await
+    }
+
+    async fn update_ai_task_state(&self, task_id: &str, state: &str, error: Option<&str>) -> Result<()> {
+        let _guard = self.updates.lock().await;
+        if let Some(mut info) = self.get_ai_task(task_id).await? {
+            info.state = Self::parse_state(state, AiTaskState::Error);
+            info.last_error = error.map(str::to_string);
+            self.save_ai_task(&info).await?;
+        }
+        Ok(())
+    }
+
+    async fn update_ai_task_stats(&self, task_id: &str, frames_delta: u64, detections_delta: u64) -> Result<()> {
+        let _guard = self.updates.lock().await;
+        if let Some(mut info) = self.get_ai_task(task_id).await? {
+            info.frames_processed += frames_delta;
+            info.detections_made += detections_delta;
+            info.last_processed_frame = Some(Self::now_ms());
+            self.save_ai_task(&info).await?;
+        }
+        Ok(())
+    }
+
+    async fn save_idempotency_record(&self, key: &str, record: &CachedResponse) -> Result<()> {
+        // Opportunistically drop expired keys so the table stays bounded
+        sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= ?1")
+            .bind(safe_unix_timestamp() as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to purge expired idempotency keys")?;
+
+        sqlx::query(
+            "INSERT INTO idempotency_keys (idempotency_key, document, expires_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (idempotency_key) DO UPDATE SET document = excluded.document, expires_at = excluded.expires_at",
+        )
+        .bind(key)
+        .bind(serde_json::to_string(record)?)
+        .bind(record.expires_at_epoch_secs as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save idempotency record")?;
+        Ok(())
+    }
+
+    async fn get_idempotency_record(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let row = sqlx::query("SELECT document FROM idempotency_keys WHERE idempotency_key = ?1 AND expires_at > ?2")
+            .bind(key)
+            .bind(safe_unix_timestamp() as i64)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to fetch idempotency record")?;
+        row.map(|r| Ok(serde_json::from_str(r.try_get("document")?)?))
+            .transpose()
+    }
+
+    async fn add_tenant_usage(&self, tenant_id: &str, period: &str, requests: i64, rejected: i64) -> Result<TenantUsage> {
+        let row = sqlx::query(
+            "INSERT INTO tenant_api_usage (tenant_id, period, requests, rejected)
+             VALUES (?1, ?2, MAX(?3, 0), MAX(?4, 0))
+             ON CONFLICT (tenant_id, period) DO UPDATE SET
+                 requests = MAX(tenant_api_usage.requests + ?3, 0),
+                 rejected = MAX(tenant_api_usage.rejected + ?4, 0)
+             RETURNING requests, rejected",
+        )
+        .bind(tenant_id)
+        .bind(period)
+        .bind(requests)
+        .bind(rejected)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to update tenant usage")?;
+
+        Ok(TenantUsage {
+            tenant_id: tenant_id.to_string(),
+            period: period.to_string(),
+            requests: row.try_get::<i64, _>("requests")? as u64,
+            rejected: row.try_get::<i64, _>("rejected")? as u64,
+        })
+    }
+
+    async fn list_tenant_usage(&self, period: &str, tenant_id: Option<&str>) -> Result<Vec<TenantUsage>> {
+        let rows = sqlx::query(
+            "SELECT tenant_id, period, requests, rejected FROM tenant_api_usage
+             WHERE period = ?1 AND (?2 IS NULL OR tenant_id = ?2)
+             ORDER BY tenant_id",
+        )
+        .bind(period)
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list tenant usage")?;
+
+        rows.into_iter()
+            .map(|r| -> Result<TenantUsage> {
+                Ok(TenantUsage {
+                    tenant_id: r.try_get("tenant_id")?,
+                    period: r.try_get("period")?,
+                    requests: r.try_get::<i64, _>("requests")? as u64,
+                    rejected: r.try_get::<i64, _>("rejected")? as u64,
+                })
+            })
+            .collect()
+    }
+
+    async fn save_service_config(&self, config: &ServiceConfig) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO service_configs (service, version, document) VALUES (?1, ?2, ?3)
+             ON CONFLICT (service, version) DO NOTHING",
+        )
+        .bind(&config.service)
+        .bind(config.version as i64)
+        .bind(serde_json::to_string(config)?)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save service config")?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn get_service_config(&self, service: &str, version: Option<u64>) -> Result<Option<ServiceConfig>> {
+        let row = sqlx::query(
+            "SELECT document FROM service_configs
+             WHERE service = ?1 AND (?2 IS NULL OR version = ?2)
+             ORDER BY version DESC LIMIT 1",
+        )
+        .bind(service)
+        .bind(version.map(|v| v as i64))
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get service config")?;
+        row.map(|r| Ok(serde_json::from_str(r.try_get("document")?)?))
+            .transpose()
+    }
+
+    async fn list_service_configs(&self, service: &str) -> Result<Vec<ServiceConfig>> {
+        let rows = sqlx::query("SELECT document FROM service_configs WHERE service = ?1 ORDER BY version DESC")
+            .bind(service)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list service configs")?;
+        rows.iter()
+            .map(|r| Ok(serde_json::from_str(r.try_get("document")?)?))
+            .collect()
+    }
+
+    async fn append_timeline_events(&self, events: &[TimelineEvent]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for event in events {
+            sqlx::query(
+                "INSERT INTO timeline_events (id, timestamp_ms, kind, camera_id, tenant_id, document)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (id) DO NOTHING",
+            )
+            .bind(&event.id)
+            .bind(event.timestamp_ms as i64)
+            .bind(event.kind.as_str())
+            .bind(event.camera_id.as_deref())
+            .bind(event.tenant_id.as_deref())
+            .bind(serde_json::to_string(event)?)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to append timeline event")?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn query_timeline(&self, query: &TimelineQuery) -> Result<Vec<TimelineEvent>> {
+        let mut sql = QueryBuilder::<Sqlite>::new("SELECT document FROM timeline_events WHERE 1 = 1");
+        if let Some(from) = query.from {
+            sql.push(" AND timestamp_ms >= ").push_bind(from as i64);
+        }
+        if let Some(to) = query.to {
+            sql.push(" AND timestamp_ms < ").push_bind(to as i64);
+        }
+        if let Some(camera_id) = &query.camera_id {
+            sql.push(" AND camera_id = ").push_bind(camera_id);
+        }
+        if let Some(tenant_id) = &query.tenant_id {
+            sql.push(" AND tenant_id = ").push_bind(tenant_id);
+        }
+        let kinds = query.kinds()?;
+        if !kinds.is_empty() {
+            sql.push(" AND kind IN (");
+            let mut separated = sql.separated(", ");
+            for kind in kinds {
+                separated.push_bind(kind.as_str());
+            }
+            sql.push(")");
+        }
+        sql.push(" ORDER BY timestamp_ms, id LIMIT ")
+            .push_bind(query.effective_limit() as i64);
+
+        let rows = sql
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query timeline")?;
+        rows.iter()
+            .map(|r| Ok(serde_json::from_str(r.try_get("document")?)?))
+            .collect()
+    }
+
+    async fn purge_timeline(&self, before_ms: u64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM timeline_events WHERE timestamp_ms < ?1")
+            .bind(before_ms as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to purge timeline")?;
+        Ok(result.rows_affected())
+    }
+
+    async fn namespace_usage(&self) -> Result<Vec<NamespaceUsage>> {
+        let mut usage = Vec::with_capacity(Namespace::ALL.len());
+        for namespace in Namespace::ALL {
+            // Usage counters are the only rows without a document
+            let size = match namespace {
+                Namespace::TenantApiUsage => "LENGTH(tenant_id) + LENGTH(period) + 16",
+                _ => "LENGTH(document)",
+            };
+            let row = sqlx::query(&format!(
+                "SELECT COUNT(*) AS keys, COALESCE(SUM({size}), 0) AS bytes FROM {table}",
+                table = namespace.as_str()
+            ))
+            .fetch_one(&self.pool)
+            .await
+            .with_context(|| format!("failed to measure namespace {namespace}"))?;
+            usage.push(NamespaceUsage {
+                namespace,
+                keys: row.try_get::<i64, _>("keys")?.max(0) as u64,
+                bytes: row.try_get::<i64, _>("bytes")?.max(0) as u64,
+            });
+        }
+        Ok(usage)
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        sqlx::query("SELECT 1")
+            .fetch_one(&self.pool)
+            .await
+            .map(|_| true)
+            .or(Ok(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::streams::StreamConfig;
+    use common::timeline::TimelineEventKind;
+
+    fn stream(id: &str, node_id: &str) -> StreamInfo {
+        StreamInfo {
+            config: StreamConfig {
+                id: id.to_string(),
+                camera_id: None,
+                uri: "rtsp://camera.local/stream".to_string(),
+                codec: Some("h264".to_string()),
+                container: Some("ts".to_string()),
+                substream_uri: None,
+                thermal_palette: None,
+            },
+            state: StreamState::Running,
+            lease_id: None,
+            last_error: None,
+            node_id: Some(node_id.to_string()),
+            playlist_path: None,
+            output_dir: None,
+            started_at: None,
+            stopped_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_and_updates_documents() {
+        let store = SqliteStateStore::connect("sqlite::memory:").await.unwrap();
+        store.save_stream(&stream("lobby", "node-a")).await.unwrap();
+        store.save_stream(&stream("dock", "node-b")).await.unwrap();
+
+        let ids: Vec<_> = store
+            .list_streams(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.config.id)
+            .collect();
+        assert_eq!(ids, ["dock", "lobby"]);
+        assert_eq!(store.list_streams(Some("node-a")).await.unwrap().len(), 1);
+
+        store
+            .update_stream_state("lobby", "bogus", Some("ffmpeg exited"))
+            .await
+            .unwrap();
+        let lobby = store.get_stream("lobby").await.unwrap().unwrap();
+        assert_eq!(lobby.state, StreamState::Error);
+        assert_eq!(lobby.last_error.as_deref(), Some("ffmpeg exited"));
+
+        store.delete_stream("lobby").await.unwrap();
+        assert!(store.get_stream("lobby").await.unwrap().is_none());
+
+        let usage = store.add_tenant_usage("tenant-a", "2026-10", 5, -3).await.unwrap();
+        assert_eq!((usage.requests, usage.rejected), (5, 0));
+        let usage = store.add_tenant_usage("tenant-a", "2026-10", 2, 1).await.unwrap();
+        assert_eq!((usage.requests, usage.rejected), (7, 1));
+    }
+
+    #[tokio::test]
+    async fn filters_timeline_queries() {
+        let store = SqliteStateStore::connect("sqlite::memory:").await.unwrap();
+        let mut events = Vec::new();
+        for (i, kind) in [TimelineEventKind::Alert, TimelineEventKind::OperatorAction, TimelineEventKind::Alert]
+            .into_iter()
+            .enumerate()
+        {
+            let mut event = TimelineEvent::new(kind, "test", format!("event {i}")).camera("cam-1");
+            event.timestamp_ms = 1_000 + i as u64;
+            events.push(event);
+        }
+        store.append_timeline_events(&events).await.unwrap();
+        // Redelivery is ignored
+        store.append_timeline_events(&events[..1]).await.unwrap();
+
+        let alerts = store
+            .query_timeline(&TimelineQuery {
+                kind: Some("alert".to_string()),
+                from: Some(1_001),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].summary, "event 2");
+
+        assert_eq!(store.purge_timeline(1_002).await.unwrap(), 2);
+        assert_eq!(store.query_timeline(&TimelineQuery::default()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn measures_namespaces() {
+        let store = SqliteStateStore::connect("sqlite::memory:").await.unwrap();
+        store.save_stream(&stream("lobby", "node-a")).await.unwrap();
+        store.save_stream(&stream("dock", "node-b")).await.unwrap();
+        store.add_tenant_usage("tenant-a", "2026-10", 1, 0).await.unwrap();
+
+        let usage = store.namespace_usage().await.unwrap();
+        assert_eq!(usage.len(), Namespace::ALL.len());
+        let streams = usage.iter().find(|u| u.namespace == Namespace::Streams).unwrap();
+        assert_eq!(streams.keys, 2);
+        assert!(streams.bytes > 0);
+        let tenants = usage.iter().find(|u| u.namespace == Namespace::TenantApiUsage).unwrap();
+        assert_eq!(tenants.keys, 1);
+        let timeline = usage.iter().find(|u| u.namespace == Namespace::TimelineEvents).unwrap();
+        assert_eq!((timeline.keys, timeline.bytes), (0, 0));
+    }
+}
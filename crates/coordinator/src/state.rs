@@ -1,4 +1,4 @@
-use crate::{cluster::ClusterManager, config::CoordinatorConfig, store::LeaseStore};
+use crate::{bandwidth::BandwidthTracker, cluster::ClusterManager, config::CoordinatorConfig, configs::ConfigRegistry, federation::SiteRegistry, namespaces::{NamespaceConfig, NamespaceMonitor}, nodes::NodeRegistry, store::LeaseStore, timeline::Timeline};
 use common::state_store::StateStore;
 use std::sync::Arc;
 
@@ -12,12 +12,24 @@ struct StateInner {
   store: Arc<dyn LeaseStore>,
   state_store: Option<Arc<dyn StateStore>>,
   cluster: Option<Arc<ClusterManager>>,
+  nodes: Arc<NodeRegistry>,
+  configs: Arc<ConfigRegistry>,
+  timeline: Arc<Timeline>,
+  sites: Arc<SiteRegistry>,
+  bandwidth: Arc<BandwidthTracker>,
+  namespaces: Arc<NamespaceMonitor>,
 }
 
 impl CoordinatorState {
   pub fn new(config: CoordinatorConfig, store: Arc<dyn LeaseStore>, state_store: Option<Arc<dyn StateStore>>) -> Self {
     Self {
       inner: Arc::new(StateInner {
+        nodes: Arc::new(NodeRegistry::new(config.max_ttl_secs)),
+        configs: Arc::new(ConfigRegistry::new(state_store.clone())),
+        timeline: Arc::new(Timeline::new(state_store.clone())),
+        sites: Arc::new(SiteRegistry::new()),
+        bandwidth: Arc::new(BandwidthTracker::new()),
+        namespaces: Arc::new(NamespaceMonitor::new(state_store.clone(), NamespaceConfig::from_env())),
         config,
         store,
         state_store,
@@ -34,6 +46,12 @@ impl CoordinatorState {
   ) -> Self {
     Self {
       inner: Arc::new(StateInner {
+        nodes: Arc::new(NodeRegistry::new(config.max_ttl_secs)),
+        configs: Arc::new(ConfigRegistry::new(state_store.clone())),
+        timeline: Arc::new(Timeline::new(state_store.clone())),
+        sites: Arc::new(SiteRegistry::new()),
+        bandwidth: Arc::new(BandwidthTracker::new()),
+        namespaces: Arc::new(NamespaceMonitor::new(state_store.clone(), NamespaceConfig::from_env())),
         config,
         store,
         state_store,
@@ -57,4 +75,28 @@ impl CoordinatorState {
   pub fn cluster(&self) -> Option<Arc<ClusterManager>> {
     self.inner.cluster.clone()
   }
+
+  pub fn nodes(&self) -> Arc<NodeRegistry> {
+    self.inner.nodes.clone()
+  }
+
+  pub fn configs(&self) -> Arc<ConfigRegistry> {
+    self.inner.configs.clone()
+  }
+
+  pub fn timeline(&self) -> Arc<Timeline> {
+    self.inner.timeline.clone()
+  }
+
+  pub fn sites(&self) -> Arc<SiteRegistry> {
+    self.inner.sites.clone()
+  }
+
+  pub fn bandwidth(&self) -> Arc<BandwidthTracker> {
+    self.inner.bandwidth.clone()
+  }
+
+  pub fn namespaces(&self) -> Arc<NamespaceMonitor> {
+    self.inner.namespaces.clone()
+  }
 }
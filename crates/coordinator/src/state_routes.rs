@@ -1,14 +1,19 @@
-use crate::{error::ApiError, state::CoordinatorState};
+use crate::{error::ApiError, namespaces::NamespaceStatus, state::CoordinatorState};
 use axum::{
     extract::{Path, Query, State},
+    http::StatusCode,
     Json, Router,
     routing::{delete, get, post, put},
 };
 use common::{
     ai_tasks::AiTaskInfo,
+    idempotency::CachedResponse,
+    quota::{TenantUsage, UsageDelta},
     recordings::RecordingInfo,
-    state_store::StateStore,
+    service_config::ServiceConfig,
+    state_store::{Namespace, StateStore},
     streams::StreamInfo,
+    timeline::{TimelineEvent, TimelineQuery},
 };
 use serde::Deserialize;
 
@@ -33,8 +38,56 @@ pub fn state_router() -> Router<CoordinatorState> {
         .route("/v1/state/ai-tasks/:task_id", delete(delete_ai_task))
         .route("/v1/state/ai-tasks/:task_id/state", put(update_ai_task_state))
         .route("/v1/state/ai-tasks/:task_id/stats", put(update_ai_task_stats))
+        // Idempotency-Key response cache
+        .route("/v1/state/idempotency", get(get_idempotency_record))
+        .route("/v1/state/idempotency", put(save_idempotency_record))
+        // Per-tenant API usage counters
+        .route("/v1/state/usage", get(list_tenant_usage))
+        .route("/v1/state/usage", put(add_tenant_usage))
+        // Versioned service configuration
+        .route("/v1/state/configs", post(save_service_config))
+        .route("/v1/state/configs/:service", get(get_service_config))
+        .route("/v1/state/configs/:service/versions", get(list_service_configs))
+        // Cluster event timeline
+        .route(
+            "/v1/state/timeline",
+            get(query_timeline).post(append_timeline_events).delete(purge_timeline),
+        )
+        // Namespace usage and quotas
+        .route("/v1/state/namespaces", get(list_namespaces))
 }
 
+/// OpenAPI operations for the state store routes (method, path, tag, summary)
+pub const OPENAPI_OPERATIONS: &[(&str, &str, &str, &str)] = &[
+    ("POST", "/v1/state/streams", "state", "Save stream state"),
+    ("GET", "/v1/state/streams", "state", "List stream state"),
+    ("GET", "/v1/state/streams/:stream_id", "state", "Get stream state"),
+    ("DELETE", "/v1/state/streams/:stream_id", "state", "Delete stream state"),
+    ("PUT", "/v1/state/streams/:stream_id/state", "state", "Update stream state"),
+    ("POST", "/v1/state/recordings", "state", "Save recording state"),
+    ("GET", "/v1/state/recordings", "state", "List recording state"),
+    ("GET", "/v1/state/recordings/:recording_id", "state", "Get recording state"),
+    ("DELETE", "/v1/state/recordings/:recording_id", "state", "Delete recording state"),
+    ("PUT", "/v1/state/recordings/:recording_id/state", "state", "Update recording state"),
+    ("POST", "/v1/state/ai-tasks", "state", "Save AI task state"),
+    ("GET", "/v1/state/ai-tasks", "state", "List AI task state"),
+    ("GET", "/v1/state/ai-tasks/:task_id", "state", "Get AI task state"),
+    ("DELETE", "/v1/state/ai-tasks/:task_id", "state", "Delete AI task state"),
+    ("PUT", "/v1/state/ai-tasks/:task_id/state", "state", "Update AI task state"),
+    ("PUT", "/v1/state/ai-tasks/:task_id/stats", "state", "Update AI task stats"),
+    ("GET", "/v1/state/idempotency", "state", "Get cached idempotent response"),
+    ("PUT", "/v1/state/idempotency", "state", "Save cached idempotent response"),
+    ("GET", "/v1/state/usage", "state", "List tenant API usage for a period"),
+    ("PUT", "/v1/state/usage", "state", "Add to tenant API usage counters"),
+    ("POST", "/v1/state/configs", "state", "Save a service configuration version"),
+    ("GET", "/v1/state/configs/:service", "state", "Get the latest or a given service configuration version"),
+    ("GET", "/v1/state/configs/:service/versions", "state", "List service configuration versions"),
+    ("POST", "/v1/state/timeline", "state", "Append timeline events"),
+    ("GET", "/v1/state/timeline", "state", "Query timeline events"),
+    ("DELETE", "/v1/state/timeline", "state", "Purge timeline events older than ?before=<ms>"),
+    ("GET", "/v1/state/namespaces", "state", "List namespace usage, quotas and leak alerts"),
+];
+
 // Helper to get state store or return error
 fn get_state_store(state: &CoordinatorState) -> Result<std::sync::Arc<dyn StateStore>, ApiError> {
     state
@@ -49,6 +102,10 @@ async fn save_stream(
     Json(info): Json<StreamInfo>,
 ) -> Result<Json<()>, ApiError> {
     let store = get_state_store(&state)?;
+    state
+        .namespaces()
+        .admit(Namespace::Streams, async { Ok(store.get_stream(&info.config.id).await?.is_some()) })
+        .await?;
     store
         .save_stream(&info)
         .await
@@ -123,6 +180,10 @@ async fn save_recording(
     Json(info): Json<RecordingInfo>,
 ) -> Result<Json<()>, ApiError> {
     let store = get_state_store(&state)?;
+    state
+        .namespaces()
+        .admit(Namespace::Recordings, async { Ok(store.get_recording(&info.config.id).await?.is_some()) })
+        .await?;
     store
         .save_recording(&info)
         .await
@@ -186,6 +247,14 @@ async fn save_ai_task(
     Json(info): Json<AiTaskInfo>,
 ) -> Result<Json<()>, ApiError> {
     let store = get_state_store(&state)?;
+    // Every node applies the task's class filter, so it is checked once here
+    if let Some(classes) = &info.config.classes {
+        classes.validate().map_err(ApiError::bad_request)?;
+    }
+    state
+        .namespaces()
+        .admit(Namespace::AiTasks, async { Ok(store.get_ai_task(&info.config.id).await?.is_some()) })
+        .await?;
     store
         .save_ai_task(&info)
         .await
@@ -260,3 +329,184 @@ async fn update_ai_task_stats(
         .map_err(|e| ApiError::internal(format!("Failed to update AI task stats: {}", e)))?;
     Ok(Json(()))
 }
+
+// ========== Idempotency endpoints ==========
+
+#[derive(Deserialize)]
+struct IdempotencyKeyQuery {
+    key: String,
+}
+
+async fn get_idempotency_record(
+    State(state): State<CoordinatorState>,
+    Query(query): Query<IdempotencyKeyQuery>,
+) -> Result<Json<Option<CachedResponse>>, ApiError> {
+    let store = get_state_store(&state)?;
+    let record = store
+        .get_idempotency_record(&query.key)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to get idempotency record: {}", e)))?;
+    Ok(Json(record))
+}
+
+async fn save_idempotency_record(
+    State(state): State<CoordinatorState>,
+    Query(query): Query<IdempotencyKeyQuery>,
+    Json(record): Json<CachedResponse>,
+) -> Result<Json<()>, ApiError> {
+    let store = get_state_store(&state)?;
+    state
+        .namespaces()
+        .admit(Namespace::IdempotencyKeys, async {
+            Ok(store.get_idempotency_record(&query.key).await?.is_some())
+        })
+        .await?;
+    store
+        .save_idempotency_record(&query.key, &record)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to save idempotency record: {}", e)))?;
+    Ok(Json(()))
+}
+
+// ========== Tenant usage endpoints ==========
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    period: String,
+    tenant_id: Option<String>,
+}
+
+async fn list_tenant_usage(
+    State(state): State<CoordinatorState>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<Vec<TenantUsage>>, ApiError> {
+    let store = get_state_store(&state)?;
+    let usage = store
+        .list_tenant_usage(&query.period, query.tenant_id.as_deref())
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list tenant usage: {}", e)))?;
+    Ok(Json(usage))
+}
+
+async fn add_tenant_usage(
+    State(state): State<CoordinatorState>,
+    Json(delta): Json<UsageDelta>,
+) -> Result<Json<TenantUsage>, ApiError> {
+    let store = get_state_store(&state)?;
+    let usage = store
+        .add_tenant_usage(&delta.tenant_id, &delta.period, delta.requests, delta.rejected)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to update tenant usage: {}", e)))?;
+    Ok(Json(usage))
+}
+
+// ========== Service configuration endpoints ==========
+
+#[derive(Deserialize)]
+struct ServiceConfigQuery {
+    version: Option<u64>,
+}
+
+async fn save_service_config(
+    State(state): State<CoordinatorState>,
+    Json(config): Json<ServiceConfig>,
+) -> Result<StatusCode, ApiError> {
+    let store = get_state_store(&state)?;
+    // Every save adds a version
+    state
+        .namespaces()
+        .admit(Namespace::ServiceConfigs, async { Ok(false) })
+        .await?;
+    let created = store
+        .save_service_config(&config)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to save service config: {}", e)))?;
+    if created {
+        Ok(StatusCode::CREATED)
+    } else {
+        Err(ApiError::conflict(format!(
+            "version {} of '{}' already exists",
+            config.version, config.service
+        )))
+    }
+}
+
+async fn get_service_config(
+    State(state): State<CoordinatorState>,
+    Path(service): Path<String>,
+    Query(query): Query<ServiceConfigQuery>,
+) -> Result<Json<Option<ServiceConfig>>, ApiError> {
+    let store = get_state_store(&state)?;
+    let config = store
+        .get_service_config(&service, query.version)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to get service config: {}", e)))?;
+    Ok(Json(config))
+}
+
+async fn list_service_configs(
+    State(state): State<CoordinatorState>,
+    Path(service): Path<String>,
+) -> Result<Json<Vec<ServiceConfig>>, ApiError> {
+    let store = get_state_store(&state)?;
+    let configs = store
+        .list_service_configs(&service)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to list service configs: {}", e)))?;
+    Ok(Json(configs))
+}
+
+// ========== Timeline endpoints ==========
+
+#[derive(Deserialize)]
+struct TimelinePurgeQuery {
+    before: u64,
+}
+
+async fn append_timeline_events(
+    State(state): State<CoordinatorState>,
+    Json(events): Json<Vec<TimelineEvent>>,
+) -> Result<StatusCode, ApiError> {
+    let store = get_state_store(&state)?;
+    state
+        .namespaces()
+        .admit(Namespace::TimelineEvents, async { Ok(false) })
+        .await?;
+    store
+        .append_timeline_events(&events)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to append timeline events: {}", e)))?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn query_timeline(
+    State(state): State<CoordinatorState>,
+    Query(query): Query<TimelineQuery>,
+) -> Result<Json<Vec<TimelineEvent>>, ApiError> {
+    let store = get_state_store(&state)?;
+    let events = store
+        .query_timeline(&query)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to query timeline: {}", e)))?;
+    Ok(Json(events))
+}
+
+async fn purge_timeline(
+    State(state): State<CoordinatorState>,
+    Query(query): Query<TimelinePurgeQuery>,
+) -> Result<Json<u64>, ApiError> {
+    let store = get_state_store(&state)?;
+    let removed = store
+        .purge_timeline(query.before)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to purge timeline: {}", e)))?;
+    Ok(Json(removed))
+}
+
+// ========== Namespace endpoints ==========
+
+async fn list_namespaces(State(state): State<CoordinatorState>) -> Result<Json<Vec<NamespaceStatus>>, ApiError> {
+    get_state_store(&state)?;
+    let status = state.namespaces().status(&state.timeline()).await?;
+    Ok(Json(status))
+}
@@ -1,24 +1,81 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use common::leases::{
-  LeaseAcquireRequest, LeaseAcquireResponse, LeaseKind, LeaseRecord, LeaseReleaseRequest,
-  LeaseReleaseResponse, LeaseRenewRequest, LeaseRenewResponse,
+  LeaseAcquireRequest, LeaseAcquireResponse, LeaseEvent, LeaseHistoryEntry, LeaseHistoryQuery, LeaseKind,
+  LeaseRecord, LeaseReleaseRequest, LeaseReleaseResponse, LeaseRenewRequest, LeaseRenewResponse,
 };
-use sqlx::{PgPool, postgres::PgPoolOptions};
+use sqlx::{PgPool, Postgres, Row, postgres::PgPoolOptions};
 use std::{
-  collections::HashMap,
-  time::{SystemTime, UNIX_EPOCH},
+  collections::{HashMap, VecDeque},
+  env,
+  sync::Arc,
+  time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::RwLock;
+use tracing::{info, warn};
 use uuid::Uuid;
 
+/// History records kept by the in-memory store; the oldest are dropped first
+pub const MAX_MEMORY_LEASE_HISTORY: usize = 50_000;
+
+/// Default for `LEASE_HISTORY_RETENTION_DAYS`
+pub const DEFAULT_HISTORY_RETENTION_DAYS: u64 = 14;
+
+const HISTORY_PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
 #[async_trait]
 pub trait LeaseStore: Send + Sync {
   async fn acquire(&self, request: LeaseAcquireRequest) -> Result<LeaseAcquireResponse>;
   async fn renew(&self, request: LeaseRenewRequest) -> Result<LeaseRenewResponse>;
   async fn release(&self, request: LeaseReleaseRequest) -> Result<LeaseReleaseResponse>;
   async fn list(&self, kind: Option<LeaseKind>) -> Result<Vec<LeaseRecord>>;
+  /// Lease history matching `query`, oldest first
+  async fn history(&self, query: &LeaseHistoryQuery) -> Result<Vec<LeaseHistoryEntry>>;
+  /// Drop history that occurred before `before_epoch_ms`
+  async fn prune_history(&self, before_epoch_ms: u64) -> Result<u64>;
   async fn health_check(&self) -> Result<bool>;
+  /// End the lease on `resource_id` now, as if its holder had stopped
+  /// renewing it; false when there is none
+  #[cfg(feature = "fault-injection")]
+  async fn expire(&self, resource_id: &str) -> Result<bool>;
+}
+
+/// Prune lease history older than `LEASE_HISTORY_RETENTION_DAYS` once an hour
+pub async fn run_history_pruner(store: Arc<dyn LeaseStore>) {
+  let days = env::var("LEASE_HISTORY_RETENTION_DAYS")
+    .ok()
+    .and_then(|v| v.parse::<u64>().ok())
+    .unwrap_or(DEFAULT_HISTORY_RETENTION_DAYS);
+  if days == 0 {
+    info!("lease history retention disabled");
+    return;
+  }
+  let retention_ms = days.saturating_mul(24 * 3600 * 1000);
+  let mut ticker = tokio::time::interval(HISTORY_PRUNE_INTERVAL);
+  loop {
+    ticker.tick().await;
+    let cutoff = now_epoch_ms().saturating_sub(retention_ms);
+    match store.prune_history(cutoff).await {
+      Ok(0) => {}
+      Ok(removed) => info!(removed, "pruned expired lease history"),
+      Err(e) => warn!(error = %e, "failed to prune lease history"),
+    }
+  }
+}
+
+fn now_epoch_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+/// `transfer` when a different holder than the resource's previous one gets it
+fn grant_event(previous_holder: Option<String>, holder_id: &str) -> (LeaseEvent, Option<String>) {
+  match previous_holder {
+    Some(previous) if previous != holder_id => (LeaseEvent::Transfer, Some(previous)),
+    _ => (LeaseEvent::Acquire, None),
+  }
 }
 
 #[derive(Default)]
@@ -53,12 +110,16 @@ impl MemoryLeaseStore {
     let mut stale = Vec::new();
     for (resource, record) in inner.by_resource.iter() {
       if record.expires_at_epoch_secs <= now {
-        stale.push((resource.clone(), record.lease_id.clone()));
+        stale.push(resource.clone());
       }
     }
-    for (resource, lease_id) in stale {
-      inner.by_resource.remove(&resource);
-      inner.lease_to_resource.remove(&lease_id);
+    for resource in stale {
+      if let Some(record) = inner.by_resource.remove(&resource) {
+        inner.lease_to_resource.remove(&record.lease_id);
+        // Expired when it ran out, not when it was noticed
+        let at_ms = record.expires_at_epoch_secs.saturating_mul(1000);
+        inner.record(LeaseEvent::Expire, &record, None, at_ms);
+      }
     }
   }
 }
@@ -68,6 +129,38 @@ struct StoreInner {
   by_resource: HashMap<String, LeaseRecord>,
   lease_to_resource: HashMap<String, String>,
   version_counter: u64,
+  history: VecDeque<LeaseHistoryEntry>,
+  history_sequence: u64,
+}
+
+impl StoreInner {
+  fn record(&mut self, event: LeaseEvent, record: &LeaseRecord, previous_holder_id: Option<String>, at_ms: u64) {
+    self.history_sequence += 1;
+    self.history.push_back(LeaseHistoryEntry {
+      sequence: self.history_sequence,
+      occurred_at_epoch_ms: at_ms,
+      event,
+      lease_id: record.lease_id.clone(),
+      resource_id: record.resource_id.clone(),
+      holder_id: record.holder_id.clone(),
+      previous_holder_id,
+      kind: record.kind.clone(),
+      expires_at_epoch_secs: record.expires_at_epoch_secs,
+      version: record.version,
+    });
+    while self.history.len() > MAX_MEMORY_LEASE_HISTORY {
+      self.history.pop_front();
+    }
+  }
+
+  fn previous_holder(&self, resource_id: &str) -> Option<String> {
+    self
+      .history
+      .iter()
+      .rev()
+      .find(|entry| entry.resource_id == resource_id)
+      .map(|entry| entry.holder_id.clone())
+  }
 }
 
 #[async_trait]
@@ -84,9 +177,11 @@ impl LeaseStore for MemoryLeaseStore {
         if existing.holder_id == request.holder_id {
           existing.expires_at_epoch_secs = now + ttl;
           existing.version += 1;
+          let record = existing.clone();
+          inner.record(LeaseEvent::Renew, &record, None, now_epoch_ms());
           return Ok(LeaseAcquireResponse {
             granted: true,
-            record: Some(existing.clone()),
+            record: Some(record),
           });
         } else {
           return Ok(LeaseAcquireResponse {
@@ -122,6 +217,8 @@ impl LeaseStore for MemoryLeaseStore {
     inner
       .by_resource
       .insert(request.resource_id, record.clone());
+    let (event, previous_holder) = grant_event(inner.previous_holder(&record.resource_id), &record.holder_id);
+    inner.record(event, &record, previous_holder, now_epoch_ms());
 
     Ok(LeaseAcquireResponse {
       granted: true,
@@ -154,9 +251,11 @@ impl LeaseStore for MemoryLeaseStore {
 
       record.expires_at_epoch_secs = now + ttl;
       record.version += 1;
+      let record = record.clone();
+      inner.record(LeaseEvent::Renew, &record, None, now_epoch_ms());
       return Ok(LeaseRenewResponse {
         renewed: true,
-        record: Some(record.clone()),
+        record: Some(record),
       });
     }
 
@@ -175,7 +274,9 @@ impl LeaseStore for MemoryLeaseStore {
       return Ok(LeaseReleaseResponse { released: false });
     };
 
-    inner.by_resource.remove(&resource_id);
+    if let Some(record) = inner.by_resource.remove(&resource_id) {
+      inner.record(LeaseEvent::Release, &record, None, now_epoch_ms());
+    }
     Ok(LeaseReleaseResponse { released: true })
   }
 
@@ -192,11 +293,43 @@ impl LeaseStore for MemoryLeaseStore {
     Ok(out)
   }
 
+  async fn history(&self, query: &LeaseHistoryQuery) -> Result<Vec<LeaseHistoryEntry>> {
+    // Record expiries that happened since the last call first
+    let mut inner = self.inner.write().await;
+    Self::purge_expired(&mut inner, Self::now_epoch_secs());
+
+    let mut out: Vec<LeaseHistoryEntry> = inner.history.iter().filter(|e| query.matches(e)).cloned().collect();
+    // Expiries are recorded late with their actual time
+    out.sort_by_key(|e| (e.occurred_at_epoch_ms, e.sequence));
+    out.truncate(query.effective_limit());
+    Ok(out)
+  }
+
+  async fn prune_history(&self, before_epoch_ms: u64) -> Result<u64> {
+    let mut inner = self.inner.write().await;
+    let before = inner.history.len();
+    inner.history.retain(|e| e.occurred_at_epoch_ms >= before_epoch_ms);
+    Ok((before - inner.history.len()) as u64)
+  }
+
   async fn health_check(&self) -> Result<bool> {
     // Memory store is always healthy if we can acquire the lock
     let _inner = self.inner.read().await;
     Ok(true)
   }
+
+  #[cfg(feature = "fault-injection")]
+  async fn expire(&self, resource_id: &str) -> Result<bool> {
+    let mut inner = self.inner.write().await;
+    let now = Self::now_epoch_secs();
+    Self::purge_expired(&mut inner, now);
+    let Some(record) = inner.by_resource.get_mut(resource_id) else {
+      return Ok(false);
+    };
+    record.expires_at_epoch_secs = now;
+    Self::purge_expired(&mut inner, now);
+    Ok(true)
+  }
 }
 
 #[cfg(test)]
@@ -334,6 +467,52 @@ mod tests {
     assert!(reacquire.granted);
     assert_eq!(reacquire.record.unwrap().holder_id, "node-b");
   }
+  #[tokio::test]
+  async fn history_records_every_transition() {
+    let store = MemoryLeaseStore::new(5, 60);
+    let acquire = |holder: &str| LeaseAcquireRequest {
+      resource_id: "cam1".into(),
+      holder_id: holder.into(),
+      kind: LeaseKind::Stream,
+      ttl_secs: 5,
+    };
+    let lease = store.acquire(acquire("node-a")).await.unwrap().record.unwrap();
+    store
+      .renew(LeaseRenewRequest {
+        lease_id: lease.lease_id.clone(),
+        ttl_secs: 5,
+      })
+      .await
+      .unwrap();
+    // node-a dies; node-b takes the camera over once the lease expired
+    sleep(Duration::from_secs(6)).await;
+    store.acquire(acquire("node-b")).await.unwrap();
+
+    let history = store.history(&LeaseHistoryQuery::default()).await.unwrap();
+    let events: Vec<_> = history.iter().map(|e| e.event).collect();
+    assert_eq!(
+      events,
+      [LeaseEvent::Acquire, LeaseEvent::Renew, LeaseEvent::Expire, LeaseEvent::Transfer]
+    );
+    assert_eq!(history[2].occurred_at_epoch_ms, history[2].expires_at_epoch_secs * 1000);
+    assert_eq!(history[3].holder_id, "node-b");
+    assert_eq!(history[3].previous_holder_id.as_deref(), Some("node-a"));
+
+    // A holder's records include the resources taken over from it
+    let query = LeaseHistoryQuery {
+      holder_id: Some("node-a".into()),
+      event: Some(LeaseEvent::Transfer),
+      ..Default::default()
+    };
+    assert_eq!(store.history(&query).await.unwrap().len(), 1);
+    let query = LeaseHistoryQuery {
+      to_epoch_ms: Some(history[2].occurred_at_epoch_ms),
+      ..Default::default()
+    };
+    assert_eq!(store.history(&query).await.unwrap().len(), 2);
+
+    assert_eq!(store.prune_history(history[3].occurred_at_epoch_ms).await.unwrap(), 3);
+  }
 }
 
 pub struct PostgresLeaseStore {
@@ -382,11 +561,54 @@ impl PostgresLeaseStore {
 
   async fn purge_expired(&self) -> Result<()> {
     let now = Self::now_epoch_secs() as i64;
-    sqlx::query("DELETE FROM leases WHERE expires_at_epoch_secs <= $1")
-      .bind(now)
-      .execute(&self.pool)
-      .await
-      .context("failed to purge expired leases")?;
+    // Expired when they ran out, not when they were noticed
+    sqlx::query(
+      "WITH expired AS (
+         DELETE FROM leases WHERE expires_at_epoch_secs <= $1
+         RETURNING lease_id, resource_id, holder_id, kind, expires_at_epoch_secs, version
+       )
+       INSERT INTO lease_history
+         (occurred_at_ms, event, lease_id, resource_id, holder_id, kind, expires_at_epoch_secs, version)
+       SELECT expires_at_epoch_secs * 1000, 'expire', lease_id, resource_id, holder_id, kind,
+              expires_at_epoch_secs, version
+       FROM expired"
+    )
+    .bind(now)
+    .execute(&self.pool)
+    .await
+    .context("failed to purge expired leases")?;
+    Ok(())
+  }
+
+  /// Append a history record
+  async fn record<'e, E>(
+    executor: E,
+    event: LeaseEvent,
+    record: &LeaseRecord,
+    previous_holder_id: Option<&str>,
+    at_ms: u64,
+  ) -> Result<()>
+  where
+    E: sqlx::Executor<'e, Database = Postgres>,
+  {
+    sqlx::query(
+      "INSERT INTO lease_history
+         (occurred_at_ms, event, lease_id, resource_id, holder_id, previous_holder_id, kind,
+          expires_at_epoch_secs, version)
+       VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+    )
+    .bind(i64::try_from(at_ms).unwrap_or(i64::MAX))
+    .bind(event.as_str())
+    .bind(&record.lease_id)
+    .bind(&record.resource_id)
+    .bind(&record.holder_id)
+    .bind(previous_holder_id)
+    .bind(record.kind.as_str())
+    .bind(record.expires_at_epoch_secs as i64)
+    .bind(record.version as i64)
+    .execute(executor)
+    .await
+    .context("failed to record lease history")?;
     Ok(())
   }
 }
@@ -430,19 +652,21 @@ impl LeaseStore for PostgresLeaseStore {
           .await
           .context("failed to update lease")?;
 
+          let kind = kind_str.parse().unwrap_or(LeaseKind::Stream);
+          let record = LeaseRecord {
+            lease_id,
+            resource_id: request.resource_id,
+            holder_id,
+            kind,
+            expires_at_epoch_secs: new_expires as u64,
+            version: new_version as u64,
+          };
+          Self::record(&mut *tx, LeaseEvent::Renew, &record, None, now_epoch_ms()).await?;
           tx.commit().await.context("failed to commit transaction")?;
 
-          let kind = kind_str.parse().unwrap_or(LeaseKind::Stream);
           return Ok(LeaseAcquireResponse {
             granted: true,
-            record: Some(LeaseRecord {
-              lease_id,
-              resource_id: request.resource_id,
-              holder_id,
-              kind,
-              expires_at_epoch_secs: new_expires as u64,
-              version: new_version as u64,
-            }),
+            record: Some(record),
           });
         } else {
           tx.rollback().await.ok();
@@ -465,9 +689,26 @@ impl LeaseStore for PostgresLeaseStore {
           .execute(&mut *tx)
           .await
           .context("failed to delete expired lease")?;
+        let expired = LeaseRecord {
+          lease_id,
+          resource_id: request.resource_id.clone(),
+          holder_id,
+          kind: kind_str.parse().unwrap_or(LeaseKind::Stream),
+          expires_at_epoch_secs: expires_at as u64,
+          version: version as u64,
+        };
+        Self::record(&mut *tx, LeaseEvent::Expire, &expired, None, (expires_at as u64).saturating_mul(1000)).await?;
       }
     }
 
+    let previous_holder: Option<String> = sqlx::query_scalar(
+      "SELECT holder_id FROM lease_history WHERE resource_id = $1 ORDER BY sequence DESC LIMIT 1"
+    )
+    .bind(&request.resource_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .context("failed to query lease history")?;
+
     let lease_id = Uuid::new_v4().to_string();
     let expires_at = (now + ttl) as i64;
     let kind_str = request.kind.to_string();
@@ -486,18 +727,21 @@ impl LeaseStore for PostgresLeaseStore {
     .await
     .context("failed to insert new lease")?;
 
+    let record = LeaseRecord {
+      lease_id,
+      resource_id: request.resource_id,
+      holder_id: request.holder_id,
+      kind: request.kind,
+      expires_at_epoch_secs: expires_at as u64,
+      version: 1,
+    };
+    let (event, previous_holder) = grant_event(previous_holder, &record.holder_id);
+    Self::record(&mut *tx, event, &record, previous_holder.as_deref(), now_epoch_ms()).await?;
     tx.commit().await.context("failed to commit transaction")?;
 
     Ok(LeaseAcquireResponse {
       granted: true,
-      record: Some(LeaseRecord {
-        lease_id,
-        resource_id: request.resource_id,
-        holder_id: request.holder_id,
-        kind: request.kind,
-        expires_at_epoch_secs: expires_at as u64,
-        version: 1,
-      }),
+      record: Some(record),
     })
   }
 
@@ -525,11 +769,25 @@ impl LeaseStore for PostgresLeaseStore {
     };
 
     if expires_at as u64 <= now {
-      sqlx::query("DELETE FROM leases WHERE lease_id = $1")
+      let deleted = sqlx::query("DELETE FROM leases WHERE lease_id = $1")
         .bind(&request.lease_id)
         .execute(&self.pool)
         .await
-        .ok();
+        .is_ok_and(|r| r.rows_affected() > 0);
+      if deleted {
+        let expired = LeaseRecord {
+          lease_id: request.lease_id.clone(),
+          resource_id,
+          holder_id,
+          kind: kind_str.parse().unwrap_or(LeaseKind::Stream),
+          expires_at_epoch_secs: expires_at as u64,
+          version: version as u64,
+        };
+        let at_ms = (expires_at as u64).saturating_mul(1000);
+        if let Err(e) = Self::record(&self.pool, LeaseEvent::Expire, &expired, None, at_ms).await {
+          warn!(error = %e, "failed to record lease expiry");
+        }
+      }
 
       return Ok(LeaseRenewResponse {
         renewed: false,
@@ -553,32 +811,47 @@ impl LeaseStore for PostgresLeaseStore {
     .context("failed to renew lease")?;
 
     let kind = kind_str.parse().unwrap_or(LeaseKind::Stream);
+    let record = LeaseRecord {
+      lease_id: request.lease_id,
+      resource_id,
+      holder_id,
+      kind,
+      expires_at_epoch_secs: new_expires as u64,
+      version: new_version as u64,
+    };
+    Self::record(&self.pool, LeaseEvent::Renew, &record, None, now_epoch_ms()).await?;
 
     Ok(LeaseRenewResponse {
       renewed: true,
-      record: Some(LeaseRecord {
-        lease_id: request.lease_id,
-        resource_id,
-        holder_id,
-        kind,
-        expires_at_epoch_secs: new_expires as u64,
-        version: new_version as u64,
-      }),
+      record: Some(record),
     })
   }
 
   async fn release(&self, request: LeaseReleaseRequest) -> Result<LeaseReleaseResponse> {
     self.purge_expired().await?;
 
-    let result = sqlx::query("DELETE FROM leases WHERE lease_id = $1")
-      .bind(&request.lease_id)
-      .execute(&self.pool)
-      .await
-      .context("failed to release lease")?;
+    let released: Option<(String, String, String, i64, i64)> = sqlx::query_as(
+      "DELETE FROM leases WHERE lease_id = $1
+       RETURNING resource_id, holder_id, kind, expires_at_epoch_secs, version"
+    )
+    .bind(&request.lease_id)
+    .fetch_optional(&self.pool)
+    .await
+    .context("failed to release lease")?;
 
-    Ok(LeaseReleaseResponse {
-      released: result.rows_affected() > 0,
-    })
+    let Some((resource_id, holder_id, kind_str, expires_at, version)) = released else {
+      return Ok(LeaseReleaseResponse { released: false });
+    };
+    let record = LeaseRecord {
+      lease_id: request.lease_id,
+      resource_id,
+      holder_id,
+      kind: kind_str.parse().unwrap_or(LeaseKind::Stream),
+      expires_at_epoch_secs: expires_at as u64,
+      version: version as u64,
+    };
+    Self::record(&self.pool, LeaseEvent::Release, &record, None, now_epoch_ms()).await?;
+    Ok(LeaseReleaseResponse { released: true })
   }
 
   async fn list(&self, kind: Option<LeaseKind>) -> Result<Vec<LeaseRecord>> {
@@ -623,6 +896,63 @@ impl LeaseStore for PostgresLeaseStore {
     Ok(out)
   }
 
+  async fn history(&self, query: &LeaseHistoryQuery) -> Result<Vec<LeaseHistoryEntry>> {
+    // Record expiries that happened since the last call first
+    self.purge_expired().await?;
+
+    let to_i64 = |v: u64| i64::try_from(v).unwrap_or(i64::MAX);
+    let rows = sqlx::query(
+      "SELECT sequence, occurred_at_ms, event, lease_id, resource_id, holder_id, previous_holder_id,
+              kind, expires_at_epoch_secs, version
+       FROM lease_history
+       WHERE ($1::BIGINT IS NULL OR occurred_at_ms >= $1)
+         AND ($2::BIGINT IS NULL OR occurred_at_ms < $2)
+         AND ($3::TEXT IS NULL OR resource_id = $3)
+         AND ($4::TEXT IS NULL OR holder_id = $4 OR previous_holder_id = $4)
+         AND ($5::TEXT IS NULL OR event = $5)
+       ORDER BY occurred_at_ms, sequence
+       LIMIT $6"
+    )
+    .bind(query.from_epoch_ms.map(to_i64))
+    .bind(query.to_epoch_ms.map(to_i64))
+    .bind(&query.resource_id)
+    .bind(&query.holder_id)
+    .bind(query.event.map(|e| e.as_str()))
+    .bind(i64::try_from(query.effective_limit()).unwrap_or(i64::MAX))
+    .fetch_all(&self.pool)
+    .await
+    .context("failed to query lease history")?;
+
+    rows
+      .into_iter()
+      .map(|r| -> Result<LeaseHistoryEntry> {
+        let event: String = r.try_get("event")?;
+        let kind: String = r.try_get("kind")?;
+        Ok(LeaseHistoryEntry {
+          sequence: r.try_get::<i64, _>("sequence")?.max(0) as u64,
+          occurred_at_epoch_ms: r.try_get::<i64, _>("occurred_at_ms")?.max(0) as u64,
+          event: event.parse::<LeaseEvent>().map_err(anyhow::Error::msg)?,
+          lease_id: r.try_get("lease_id")?,
+          resource_id: r.try_get("resource_id")?,
+          holder_id: r.try_get("holder_id")?,
+          previous_holder_id: r.try_get("previous_holder_id")?,
+          kind: kind.parse().unwrap_or(LeaseKind::Stream),
+          expires_at_epoch_secs: r.try_get::<i64, _>("expires_at_epoch_secs")?.max(0) as u64,
+          version: r.try_get::<i64, _>("version")?.max(0) as u64,
+        })
+      })
+      .collect()
+  }
+
+  async fn prune_history(&self, before_epoch_ms: u64) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM lease_history WHERE occurred_at_ms < $1")
+      .bind(i64::try_from(before_epoch_ms).unwrap_or(i64::MAX))
+      .execute(&self.pool)
+      .await
+      .context("failed to prune lease history")?;
+    Ok(result.rows_affected())
+  }
+
   async fn health_check(&self) -> Result<bool> {
     // Verify database connectivity with a simple query
     match sqlx::query("SELECT 1").fetch_one(&self.pool).await {
@@ -633,4 +963,19 @@ impl LeaseStore for PostgresLeaseStore {
       }
     }
   }
+
+  #[cfg(feature = "fault-injection")]
+  async fn expire(&self, resource_id: &str) -> Result<bool> {
+    self.purge_expired().await?;
+    let expired = sqlx::query("UPDATE leases SET expires_at_epoch_secs = $2 WHERE resource_id = $1")
+      .bind(resource_id)
+      .bind(Self::now_epoch_secs() as i64)
+      .execute(&self.pool)
+      .await
+      .context("failed to expire lease")?
+      .rows_affected()
+      > 0;
+    self.purge_expired().await?;
+    Ok(expired)
+  }
 }
@@ -0,0 +1,163 @@
+use crate::error::ApiError;
+use common::{
+  state_store::StateStore,
+  timeline::{MAX_BATCH, TimelineEvent, TimelineQuery, now_ms},
+};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{info, warn};
+
+/// Events kept when no StateStore is configured; the oldest go first
+const MEMORY_CAPACITY: usize = 50_000;
+
+/// How often expired events are purged
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Cluster-wide event timeline. Events are kept in the StateStore when one is
+/// configured and in a bounded in-memory buffer otherwise.
+pub struct Timeline {
+  store: Option<Arc<dyn StateStore>>,
+  /// Oldest first by arrival (memory mode only)
+  memory: Mutex<VecDeque<TimelineEvent>>,
+}
+
+impl Timeline {
+  pub fn new(store: Option<Arc<dyn StateStore>>) -> Self {
+    Self {
+      store,
+      memory: Mutex::new(VecDeque::new()),
+    }
+  }
+
+  /// Store a batch from a publisher; the whole batch is rejected if any
+  /// event is invalid
+  pub async fn append(&self, events: Vec<TimelineEvent>) -> Result<usize, ApiError> {
+    if events.len() > MAX_BATCH {
+      return Err(ApiError::bad_request(format!("at most {MAX_BATCH} events per batch")));
+    }
+    for event in &events {
+      event
+        .validate()
+        .map_err(|e| ApiError::bad_request(format!("invalid event {}: {}", event.id, e)))?;
+    }
+    let count = events.len();
+    match &self.store {
+      Some(store) => store.append_timeline_events(&events).await?,
+      None => {
+        let mut memory = self.memory.lock().await;
+        for event in events {
+          if !memory.iter().rev().take(MAX_BATCH * 2).any(|e| e.id == event.id) {
+            memory.push_back(event);
+          }
+        }
+        let excess = memory.len().saturating_sub(MEMORY_CAPACITY);
+        memory.drain(..excess);
+      }
+    }
+    Ok(count)
+  }
+
+  /// Matching events, oldest first
+  pub async fn query(&self, query: &TimelineQuery) -> Result<Vec<TimelineEvent>, ApiError> {
+    let kinds = query.kinds().map_err(|e| ApiError::bad_request(e.to_string()))?;
+    if let Some(store) = &self.store {
+      return Ok(store.query_timeline(query).await?);
+    }
+    let memory = self.memory.lock().await;
+    let mut events: Vec<_> = memory
+      .iter()
+      .filter(|event| query.matches(event, &kinds))
+      .cloned()
+      .collect();
+    events.sort_by(|a, b| (a.timestamp_ms, &a.id).cmp(&(b.timestamp_ms, &b.id)));
+    events.truncate(query.effective_limit());
+    Ok(events)
+  }
+
+  /// Remove events that happened before `before_ms`
+  pub async fn purge(&self, before_ms: u64) -> Result<u64, ApiError> {
+    if let Some(store) = &self.store {
+      return Ok(store.purge_timeline(before_ms).await?);
+    }
+    let mut memory = self.memory.lock().await;
+    let before = memory.len();
+    memory.retain(|event| event.timestamp_ms >= before_ms);
+    Ok((before - memory.len()) as u64)
+  }
+
+  /// Purge events older than `retention` every hour
+  pub fn spawn_retention(self: Arc<Self>, retention: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+      loop {
+        ticker.tick().await;
+        let cutoff = now_ms().saturating_sub(retention.as_millis() as u64);
+        match self.purge(cutoff).await {
+          Ok(0) => {}
+          Ok(removed) => info!(removed, "purged expired timeline events"),
+          Err(e) => warn!(error = %e, "timeline purge failed"),
+        }
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::timeline::TimelineEventKind;
+
+  fn event(kind: TimelineEventKind, at: u64, camera: &str) -> TimelineEvent {
+    TimelineEvent::new(kind, "test", format!("{kind} on {camera}")).at(at).camera(camera)
+  }
+
+  #[tokio::test]
+  async fn query_orders_filters_and_deduplicates() {
+    let timeline = Timeline::new(None);
+    let alert = event(TimelineEventKind::Alert, 3_000, "cam-1");
+    timeline
+      .append(vec![
+        alert.clone(),
+        event(TimelineEventKind::DeviceStatus, 1_000, "cam-1"),
+        event(TimelineEventKind::Detection, 2_000, "cam-2"),
+      ])
+      .await
+      .unwrap();
+    // Retried delivery of the same event
+    timeline.append(vec![alert]).await.unwrap();
+
+    let cam1 = TimelineQuery {
+      camera_id: Some("cam-1".into()),
+      ..Default::default()
+    };
+    let kinds: Vec<_> = timeline.query(&cam1).await.unwrap().iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, [TimelineEventKind::DeviceStatus, TimelineEventKind::Alert]);
+
+    let window = TimelineQuery {
+      from: Some(1_500),
+      limit: Some(1),
+      ..Default::default()
+    };
+    let events = timeline.query(&window).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].timestamp_ms, 2_000);
+
+    assert_eq!(timeline.purge(2_500).await.unwrap(), 2);
+    assert_eq!(timeline.query(&TimelineQuery::default()).await.unwrap().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn rejects_invalid_batches() {
+    let timeline = Timeline::new(None);
+    let mut bad = event(TimelineEventKind::Alert, 1, "cam-1");
+    bad.summary.clear();
+    assert!(timeline.append(vec![event(TimelineEventKind::Alert, 2, "cam-1"), bad]).await.is_err());
+    assert!(timeline.query(&TimelineQuery::default()).await.unwrap().is_empty());
+
+    let unknown_kind = TimelineQuery {
+      kind: Some("motion".into()),
+      ..Default::default()
+    };
+    assert!(timeline.query(&unknown_kind).await.is_err());
+  }
+}
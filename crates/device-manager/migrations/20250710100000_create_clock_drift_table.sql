@@ -0,0 +1,19 @@
+-- Camera clock drift samples from the time-sync checker
+CREATE TABLE IF NOT EXISTS device_clock_drift (
+    sample_id BIGSERIAL PRIMARY KEY,
+    device_id TEXT NOT NULL REFERENCES devices(device_id) ON DELETE CASCADE,
+
+    -- Camera clock minus server clock
+    drift_ms BIGINT NOT NULL,
+    -- Request round trip; the drift is accurate to about half of it plus the
+    -- camera clock's one-second resolution
+    round_trip_ms INT NOT NULL,
+    source TEXT NOT NULL CHECK (source IN ('onvif')),
+    -- Drift was beyond the configured threshold
+    exceeded BOOLEAN NOT NULL,
+
+    measured_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_device_clock_drift_device_id ON device_clock_drift(device_id, measured_at DESC);
+CREATE INDEX idx_device_clock_drift_measured_at ON device_clock_drift(measured_at);
@@ -0,0 +1,60 @@
+-- Row-level security: each connection only sees the rows of the tenant in
+-- the app.tenant_id setting (see common::tenant_rls), or every row when it is
+-- '*'. Connections without the setting see nothing. FORCE applies the
+-- policies to the table owner too; superusers still bypass them.
+
+ALTER TABLE devices ENABLE ROW LEVEL SECURITY;
+ALTER TABLE devices FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON devices
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
+
+-- Per-device tables follow the visibility of their device
+ALTER TABLE device_health_history ENABLE ROW LEVEL SECURITY;
+ALTER TABLE device_health_history FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON device_health_history
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = device_health_history.device_id));
+
+ALTER TABLE device_events ENABLE ROW LEVEL SECURITY;
+ALTER TABLE device_events FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON device_events
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = device_events.device_id));
+
+ALTER TABLE ptz_presets ENABLE ROW LEVEL SECURITY;
+ALTER TABLE ptz_presets FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON ptz_presets
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = ptz_presets.device_id));
+
+ALTER TABLE ptz_tours ENABLE ROW LEVEL SECURITY;
+ALTER TABLE ptz_tours FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON ptz_tours
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = ptz_tours.device_id));
+
+ALTER TABLE ptz_tour_steps ENABLE ROW LEVEL SECURITY;
+ALTER TABLE ptz_tour_steps FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON ptz_tour_steps
+    USING (EXISTS (SELECT 1 FROM ptz_tours t WHERE t.tour_id = ptz_tour_steps.tour_id));
+
+ALTER TABLE device_configurations ENABLE ROW LEVEL SECURITY;
+ALTER TABLE device_configurations FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON device_configurations
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = device_configurations.device_id));
+
+ALTER TABLE firmware_updates ENABLE ROW LEVEL SECURITY;
+ALTER TABLE firmware_updates FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON firmware_updates
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = firmware_updates.device_id));
+
+ALTER TABLE firmware_update_history ENABLE ROW LEVEL SECURITY;
+ALTER TABLE firmware_update_history FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON firmware_update_history
+    USING (EXISTS (SELECT 1 FROM firmware_updates u WHERE u.update_id = firmware_update_history.update_id));
+
+ALTER TABLE device_clock_drift ENABLE ROW LEVEL SECURITY;
+ALTER TABLE device_clock_drift FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON device_clock_drift
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = device_clock_drift.device_id));
+
+-- discovery_scans, discovered_devices and firmware_files are not tenant-owned
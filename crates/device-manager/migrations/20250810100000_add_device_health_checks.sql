@@ -0,0 +1,3 @@
+-- Protocol-level health checks selected per device (DeviceHealthChecks as
+-- JSON); NULL runs the protocol's reachability check
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS health_checks JSONB;
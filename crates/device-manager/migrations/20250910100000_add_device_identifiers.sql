@@ -0,0 +1,9 @@
+-- Hardware identifiers used to detect the same camera being added twice;
+-- stored normalized (lowercase colon-separated MAC, uppercase serial)
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS mac_address TEXT;
+ALTER TABLE devices ADD COLUMN IF NOT EXISTS serial_number TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_devices_tenant_mac ON devices(tenant_id, mac_address)
+    WHERE mac_address IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_devices_tenant_serial ON devices(tenant_id, serial_number)
+    WHERE serial_number IS NOT NULL;
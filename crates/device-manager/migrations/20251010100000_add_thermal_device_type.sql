@@ -0,0 +1,2 @@
+-- Thermal (infrared) cameras
+ALTER TYPE device_type ADD VALUE IF NOT EXISTS 'thermal' AFTER 'camera';
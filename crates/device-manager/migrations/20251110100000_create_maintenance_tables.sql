@@ -0,0 +1,58 @@
+-- Scheduled device maintenance (reboots) and the history of its runs
+CREATE TABLE IF NOT EXISTS maintenance_tasks (
+    task_id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('reboot')),
+
+    -- One device, or every device of the tenant matching manufacturer/model
+    device_id TEXT REFERENCES devices(device_id) ON DELETE CASCADE,
+    manufacturer TEXT,
+    model TEXT,
+
+    -- Cron expression with seconds, read in time_zone
+    schedule_cron TEXT NOT NULL,
+    time_zone TEXT NOT NULL DEFAULT 'UTC',
+    -- How long a device may stay unreachable after the action before it
+    -- counts as offline
+    window_secs INT NOT NULL DEFAULT 600,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    next_run_at TIMESTAMPTZ,
+    last_run_at TIMESTAMPTZ,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_maintenance_tasks_tenant_id ON maintenance_tasks(tenant_id);
+CREATE INDEX idx_maintenance_tasks_due ON maintenance_tasks(next_run_at) WHERE enabled;
+
+CREATE TABLE IF NOT EXISTS maintenance_runs (
+    run_id BIGSERIAL PRIMARY KEY,
+    task_id TEXT REFERENCES maintenance_tasks(task_id) ON DELETE SET NULL,
+    device_id TEXT NOT NULL REFERENCES devices(device_id) ON DELETE CASCADE,
+    action TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('running', 'succeeded', 'failed', 'skipped')),
+    error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    window_ends_at TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_maintenance_runs_device_id ON maintenance_runs(device_id, started_at DESC);
+CREATE INDEX idx_maintenance_runs_running ON maintenance_runs(run_id) WHERE status = 'running';
+
+-- Row-level security as in 20250720100000: tasks belong to their tenant,
+-- runs follow the visibility of their device
+ALTER TABLE maintenance_tasks ENABLE ROW LEVEL SECURITY;
+ALTER TABLE maintenance_tasks FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON maintenance_tasks
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
+
+ALTER TABLE maintenance_runs ENABLE ROW LEVEL SECURITY;
+ALTER TABLE maintenance_runs FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON maintenance_runs
+    USING (EXISTS (SELECT 1 FROM devices d WHERE d.device_id = maintenance_runs.device_id));
@@ -0,0 +1,386 @@
+//! Camera clock drift monitoring.
+//!
+//! Recording timestamps and analytics events come from the camera's clock,
+//! so a camera that has drifted away from server time files footage under
+//! the wrong time. The checker periodically reads the clock of every online
+//! ONVIF device with `GetSystemDateAndTime`, stores the drift against server
+//! time, and raises a timeline alert when it moves beyond the threshold.
+
+use crate::store::DeviceStore;
+use crate::types::Device;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use common::timeline::{self, TimelineEvent, TimelineEventKind};
+use serde_json::json;
+use std::sync::atomic::{AtomicI32, AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+/// Drift read with ONVIF `GetSystemDateAndTime`
+pub const SOURCE_ONVIF: &str = "onvif";
+
+pub(crate) const GET_SYSTEM_DATE_AND_TIME: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope"
+            xmlns:tds="http://www.onvif.org/ver10/device/wsdl">
+  <s:Body>
+    <tds:GetSystemDateAndTime/>
+  </s:Body>
+</s:Envelope>"#;
+
+/// One clock reading of a camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockReading {
+    /// Camera clock minus server clock; positive when the camera is ahead
+    pub drift_ms: i64,
+    pub round_trip_ms: i32,
+}
+
+pub struct ClockSyncChecker {
+    store: Arc<DeviceStore>,
+    client: reqwest::Client,
+    check_interval_secs: u64,
+    threshold_ms: AtomicI64,
+    history_days: AtomicI32,
+}
+
+impl ClockSyncChecker {
+    pub fn new(
+        store: Arc<DeviceStore>,
+        timeout_secs: u64,
+        check_interval_secs: u64,
+        threshold_ms: i64,
+        history_days: i32,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout_secs))
+            .build()
+            .unwrap_or_default();
+        Self {
+            store,
+            client,
+            check_interval_secs,
+            threshold_ms: AtomicI64::new(threshold_ms),
+            history_days: AtomicI32::new(history_days),
+        }
+    }
+
+    /// Change the drift threshold and history retention of a running checker
+    pub fn reconfigure(&self, threshold_ms: i64, history_days: i32) {
+        self.threshold_ms.store(threshold_ms, Ordering::Relaxed);
+        self.history_days.store(history_days, Ordering::Relaxed);
+    }
+
+    /// Start the clock check loop
+    pub async fn start(&self) {
+        info!(
+            interval_secs = self.check_interval_secs,
+            threshold_ms = self.threshold_ms.load(Ordering::Relaxed),
+            "clock sync checker started"
+        );
+
+        loop {
+            if let Err(e) = self.run_clock_checks().await {
+                error!("clock check cycle failed: {}", e);
+            }
+
+            sleep(Duration::from_secs(self.check_interval_secs)).await;
+        }
+    }
+
+    /// Check the clocks of all online ONVIF devices
+    async fn run_clock_checks(&self) -> Result<()> {
+        let purged = self
+            .store
+            .cleanup_old_clock_drift(self.history_days.load(Ordering::Relaxed))
+            .await?;
+        if purged > 0 {
+            debug!(purged, "purged old clock drift samples");
+        }
+
+        let devices = self.store.get_devices_for_clock_check().await?;
+        if devices.is_empty() {
+            return Ok(());
+        }
+
+        let mut tasks = Vec::new();
+
+        for device in devices {
+            let store = Arc::clone(&self.store);
+            let client = self.client.clone();
+            let threshold_ms = self.threshold_ms.load(Ordering::Relaxed);
+
+            let task = tokio::spawn(async move {
+                if let Err(e) = Self::check_device_clock(device, store, client, threshold_ms).await
+                {
+                    warn!("failed to check device clock: {}", e);
+                }
+            });
+
+            tasks.push(task);
+
+            // Limit concurrency to avoid overwhelming the system
+            if tasks.len() >= 10 {
+                for task in tasks.drain(..) {
+                    let _ = task.await;
+                }
+            }
+        }
+
+        for task in tasks {
+            let _ = task.await;
+        }
+
+        Ok(())
+    }
+
+    /// Read one device's clock, store the sample and alert on threshold
+    /// crossings
+    async fn check_device_clock(
+        device: Device,
+        store: Arc<DeviceStore>,
+        client: reqwest::Client,
+        threshold_ms: i64,
+    ) -> Result<()> {
+        let reading = read_clock(&client, &device.primary_uri)
+            .await
+            .with_context(|| format!("device {}", device.device_id))?;
+        let exceeded = exceeds(reading.drift_ms, threshold_ms);
+
+        let was_exceeded = store
+            .get_clock_drift_history(&device.device_id, 1)
+            .await?
+            .first()
+            .is_some_and(|s| s.exceeded);
+        store
+            .record_clock_drift(
+                &device.device_id,
+                reading.drift_ms,
+                reading.round_trip_ms,
+                SOURCE_ONVIF,
+                exceeded,
+            )
+            .await?;
+
+        telemetry::metrics::DEVICE_CLOCK_DRIFT_MS
+            .with_label_values(&[&device.device_id])
+            .set(reading.drift_ms);
+
+        if exceeded != was_exceeded {
+            if exceeded {
+                warn!(
+                    device_id = %device.device_id,
+                    device_name = %device.name,
+                    drift_ms = reading.drift_ms,
+                    "device clock drift beyond threshold"
+                );
+            } else {
+                info!(
+                    device_id = %device.device_id,
+                    device_name = %device.name,
+                    drift_ms = reading.drift_ms,
+                    "device clock back in sync"
+                );
+            }
+            timeline::record(drift_event(&device, reading, threshold_ms, exceeded));
+        }
+
+        Ok(())
+    }
+}
+
+fn exceeds(drift_ms: i64, threshold_ms: i64) -> bool {
+    drift_ms.abs() > threshold_ms
+}
+
+/// Timeline event for a clock drifting past the threshold or recovering
+fn drift_event(
+    device: &Device,
+    reading: ClockReading,
+    threshold_ms: i64,
+    exceeded: bool,
+) -> TimelineEvent {
+    let (kind, summary) = if exceeded {
+        let direction = if reading.drift_ms > 0 {
+            "ahead of"
+        } else {
+            "behind"
+        };
+        (
+            TimelineEventKind::Alert,
+            format!(
+                "{} clock is {:.1}s {} server time",
+                device.name,
+                reading.drift_ms.abs() as f64 / 1000.0,
+                direction
+            ),
+        )
+    } else {
+        (
+            TimelineEventKind::DeviceStatus,
+            format!("{} clock is back in sync", device.name),
+        )
+    };
+    TimelineEvent::new(kind, "device-manager", summary)
+        .camera(device.device_id.clone())
+        .tenant(device.tenant_id.clone())
+        .details(json!({
+            "severity": if exceeded { "warning" } else { "info" },
+            "drift_ms": reading.drift_ms,
+            "round_trip_ms": reading.round_trip_ms,
+            "threshold_ms": threshold_ms,
+            "source": SOURCE_ONVIF,
+        }))
+}
+
+/// Read a camera's clock with ONVIF `GetSystemDateAndTime`, which devices
+/// must answer without authentication. Server time is taken halfway
+/// through the round trip.
+pub async fn read_clock(client: &reqwest::Client, uri: &str) -> Result<ClockReading> {
+    let device_service_url = if uri.contains("/onvif/device_service") {
+        uri.to_string()
+    } else {
+        format!("{}/onvif/device_service", uri.trim_end_matches('/'))
+    };
+
+    let sent_at = Utc::now();
+    let started = Instant::now();
+    let response = client
+        .post(&device_service_url)
+        .header("Content-Type", "application/soap+xml; charset=utf-8")
+        .body(GET_SYSTEM_DATE_AND_TIME)
+        .send()
+        .await
+        .context("GetSystemDateAndTime request failed")?;
+    let round_trip = started.elapsed();
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "GetSystemDateAndTime returned HTTP {}",
+            response.status()
+        ));
+    }
+    let body = response.text().await?;
+    let camera_time = parse_utc_date_time(&body)?;
+
+    Ok(drift(camera_time, sent_at, round_trip))
+}
+
+fn drift(camera_time: DateTime<Utc>, sent_at: DateTime<Utc>, round_trip: Duration) -> ClockReading {
+    let round_trip_ms = i64::try_from(round_trip.as_millis()).unwrap_or(i64::MAX);
+    let server_time = sent_at + chrono::Duration::milliseconds(round_trip_ms / 2);
+    ClockReading {
+        drift_ms: (camera_time - server_time).num_milliseconds(),
+        round_trip_ms: i32::try_from(round_trip_ms).unwrap_or(i32::MAX),
+    }
+}
+
+/// The `UTCDateTime` of a `GetSystemDateAndTime` response
+pub fn parse_utc_date_time(xml: &str) -> Result<DateTime<Utc>> {
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut in_utc = false;
+    let mut current_tag = String::new();
+    let mut fields = [None::<u32>; 6];
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                if name == "UTCDateTime" {
+                    in_utc = true;
+                }
+                current_tag = name;
+            }
+            Ok(Event::End(e)) => {
+                if e.local_name().as_ref() == b"UTCDateTime" {
+                    in_utc = false;
+                }
+                current_tag.clear();
+            }
+            Ok(Event::Text(e)) if in_utc => {
+                let index = match current_tag.as_str() {
+                    "Year" => 0,
+                    "Month" => 1,
+                    "Day" => 2,
+                    "Hour" => 3,
+                    "Minute" => 4,
+                    "Second" => 5,
+                    _ => continue,
+                };
+                let text = e.unescape().unwrap_or_default();
+                fields[index] = text.trim().parse().ok();
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(anyhow!("invalid GetSystemDateAndTime response: {}", e)),
+            _ => {}
+        }
+    }
+
+    let [Some(year), Some(month), Some(day), Some(hour), Some(minute), Some(second)] = fields
+    else {
+        return Err(anyhow!("GetSystemDateAndTime response has no UTCDateTime"));
+    };
+    NaiveDate::from_ymd_opt(year as i32, month, day)
+        .and_then(|date| date.and_hms_opt(hour, minute, second))
+        .map(|t| t.and_utc())
+        .ok_or_else(|| anyhow!("GetSystemDateAndTime response has an invalid UTCDateTime"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const RESPONSE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<SOAP-ENV:Envelope xmlns:SOAP-ENV="http://www.w3.org/2003/05/soap-envelope"
+    xmlns:tds="http://www.onvif.org/ver10/device/wsdl" xmlns:tt="http://www.onvif.org/ver10/schema">
+  <SOAP-ENV:Body>
+    <tds:GetSystemDateAndTimeResponse>
+      <tds:SystemDateAndTime>
+        <tt:DateTimeType>NTP</tt:DateTimeType>
+        <tt:DaylightSavings>true</tt:DaylightSavings>
+        <tt:TimeZone><tt:TZ>CET-1CEST,M3.5.0,M10.5.0/3</tt:TZ></tt:TimeZone>
+        <tt:UTCDateTime>
+          <tt:Time><tt:Hour>9</tt:Hour><tt:Minute>30</tt:Minute><tt:Second>15</tt:Second></tt:Time>
+          <tt:Date><tt:Year>2025</tt:Year><tt:Month>7</tt:Month><tt:Day>10</tt:Day></tt:Date>
+        </tt:UTCDateTime>
+        <tt:LocalDateTime>
+          <tt:Time><tt:Hour>11</tt:Hour><tt:Minute>30</tt:Minute><tt:Second>15</tt:Second></tt:Time>
+          <tt:Date><tt:Year>2025</tt:Year><tt:Month>7</tt:Month><tt:Day>10</tt:Day></tt:Date>
+        </tt:LocalDateTime>
+      </tds:SystemDateAndTime>
+    </tds:GetSystemDateAndTimeResponse>
+  </SOAP-ENV:Body>
+</SOAP-ENV:Envelope>"#;
+
+    #[test]
+    fn test_parse_utc_date_time_ignores_local_time() {
+        let parsed = parse_utc_date_time(RESPONSE).unwrap();
+        assert_eq!(parsed, Utc.with_ymd_and_hms(2025, 7, 10, 9, 30, 15).unwrap());
+
+        let local_only = RESPONSE.replace("UTCDateTime", "Other");
+        assert!(parse_utc_date_time(&local_only).is_err());
+    }
+
+    #[test]
+    fn test_drift_uses_round_trip_midpoint() {
+        let sent_at = Utc.with_ymd_and_hms(2025, 7, 10, 9, 30, 0).unwrap();
+        let camera = Utc.with_ymd_and_hms(2025, 7, 10, 9, 30, 5).unwrap();
+
+        let reading = drift(camera, sent_at, Duration::from_millis(400));
+        assert_eq!(reading.drift_ms, 4_800);
+        assert_eq!(reading.round_trip_ms, 400);
+        assert!(exceeds(reading.drift_ms, 2_000));
+
+        let behind = drift(sent_at, camera, Duration::ZERO);
+        assert_eq!(behind.drift_ms, -5_000);
+        assert!(exceeds(behind.drift_ms, 2_000));
+        assert!(!exceeds(1_500, 2_000));
+    }
+}
@@ -1,9 +1,10 @@
-use crate::firmware_storage::calculate_checksum;
+use crate::firmware_storage::{calculate_checksum, UploadError};
 use crate::state::DeviceManagerState;
 use crate::types::*;
 use axum::{
+    body::Bytes,
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
@@ -101,6 +102,138 @@ pub async fn upload_firmware_file(
     Ok((StatusCode::CREATED, Json(firmware_file)))
 }
 
+/// Header carrying the SHA-256 (hex) of an appended chunk
+pub const CHUNK_CHECKSUM_HEADER: &str = "x-chunk-sha256";
+
+fn upload_error(e: UploadError) -> (StatusCode, Json<serde_json::Value>) {
+    let status = match &e {
+        UploadError::NotFound => StatusCode::NOT_FOUND,
+        UploadError::OffsetMismatch { .. } => StatusCode::CONFLICT,
+        UploadError::ChunkChecksum | UploadError::ImageChecksum { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        UploadError::Invalid(_) => StatusCode::BAD_REQUEST,
+        UploadError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    if status == StatusCode::INTERNAL_SERVER_ERROR {
+        error!("firmware upload failed: {:#}", e);
+    } else {
+        warn!("firmware upload refused: {}", e);
+    }
+    let mut body = json!({"error": e.to_string()});
+    if let UploadError::OffsetMismatch { expected, .. } = e {
+        body["expected_offset"] = json!(expected);
+    }
+    (status, Json(body))
+}
+
+/// Open a chunked firmware upload session
+pub async fn create_firmware_upload(
+    State(state): State<DeviceManagerState>,
+    Json(req): Json<InitFirmwareUploadRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    info!(
+        "opening firmware upload: {} {} v{} ({} bytes)",
+        req.manufacturer, req.model, req.firmware_version, req.total_size
+    );
+    let session = state
+        .firmware_storage
+        .create_upload(&req)
+        .await
+        .map_err(upload_error)?;
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// Get a firmware upload session; `received_bytes` is the offset to resume from
+pub async fn get_firmware_upload(
+    State(state): State<DeviceManagerState>,
+    Path(upload_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let session = state
+        .firmware_storage
+        .get_upload(&upload_id)
+        .await
+        .map_err(upload_error)?;
+    Ok((StatusCode::OK, Json(session)))
+}
+
+/// Append a raw chunk at `?offset=`, checked against the `x-chunk-sha256` header
+pub async fn append_firmware_chunk(
+    State(state): State<DeviceManagerState>,
+    Path(upload_id): Path<String>,
+    Query(query): Query<FirmwareChunkQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let chunk_checksum = headers
+        .get(CHUNK_CHECKSUM_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("{} header is required", CHUNK_CHECKSUM_HEADER)})),
+            )
+        })?;
+    let session = state
+        .firmware_storage
+        .append_chunk(&upload_id, query.offset, &body, chunk_checksum)
+        .await
+        .map_err(upload_error)?;
+    Ok((StatusCode::OK, Json(session)))
+}
+
+/// Verify a fully received upload and add it to the firmware catalog
+pub async fn complete_firmware_upload(
+    State(state): State<DeviceManagerState>,
+    Path(upload_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    info!("completing firmware upload: {}", upload_id);
+    let (session, file_path) = state
+        .firmware_storage
+        .complete_upload(&upload_id)
+        .await
+        .map_err(upload_error)?;
+
+    let firmware_file = state
+        .store
+        .create_firmware_file(
+            &session.manufacturer,
+            &session.model,
+            &session.firmware_version,
+            &file_path,
+            i64::try_from(session.total_size).unwrap_or(i64::MAX),
+            &session.checksum,
+            session.release_notes.as_deref(),
+            session.release_date,
+            session.min_device_version.as_deref(),
+            session.compatible_models.as_deref(),
+            None, // uploaded_by - would come from auth context
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to create firmware file record: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "failed to create firmware file record", "details": e.to_string()})),
+            )
+        })?;
+
+    info!("firmware file uploaded in chunks: {}", firmware_file.file_id);
+
+    Ok((StatusCode::CREATED, Json(firmware_file)))
+}
+
+/// Abort a firmware upload and drop the data received so far
+pub async fn abort_firmware_upload(
+    State(state): State<DeviceManagerState>,
+    Path(upload_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    state
+        .firmware_storage
+        .abort_upload(&upload_id)
+        .await
+        .map_err(upload_error)?;
+    Ok((StatusCode::OK, Json(json!({"message": "firmware upload aborted"}))))
+}
+
 /// List firmware files
 pub async fn list_firmware_files(
     State(state): State<DeviceManagerState>,
@@ -1,20 +1,64 @@
+use crate::types::{FirmwareUploadSession, InitFirmwareUploadRequest};
 use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
 use sha2::{Digest, Sha256};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
 use tracing::{debug, info, warn};
 
+/// Largest image accepted through an upload session (4 GiB)
+pub const MAX_UPLOAD_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+/// Largest chunk accepted in one append (16 MiB)
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+/// Upload sessions open at once; abandoned ones are collected by
+/// [`FirmwareStorage::cleanup_stale_uploads`]
+pub const MAX_UPLOAD_SESSIONS: usize = 64;
+/// Directory under the storage root holding upload sessions
+const UPLOADS_DIR: &str = ".uploads";
+
+/// Why a chunked upload operation was refused
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    #[error("upload session not found")]
+    NotFound,
+    #[error("chunk starts at {offset}, expected {expected}")]
+    OffsetMismatch { offset: u64, expected: u64 },
+    #[error("chunk checksum mismatch")]
+    ChunkChecksum,
+    #[error("image checksum mismatch: expected {expected}, got {actual}")]
+    ImageChecksum { expected: String, actual: String },
+    #[error("{0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Storage(#[from] anyhow::Error),
+}
+
+impl From<std::io::Error> for UploadError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Storage(e.into())
+    }
+}
+
 /// Manages firmware file storage and validation
 #[derive(Clone)]
 pub struct FirmwareStorage {
     storage_root: PathBuf,
+    /// Serializes changes to upload sessions
+    uploads: Arc<Mutex<()>>,
 }
 
 impl FirmwareStorage {
     pub fn new(storage_root: impl Into<PathBuf>) -> Result<Self> {
         let storage_root = storage_root.into();
-        Ok(Self { storage_root })
+        Ok(Self {
+            storage_root,
+            uploads: Arc::new(Mutex::new(())),
+        })
     }
 
     /// Initialize storage directory
@@ -183,6 +227,10 @@ impl FirmwareStorage {
                 };
 
                 if metadata.is_dir() {
+                    // Upload sessions have their own expiry
+                    if path.file_name().is_some_and(|name| name == UPLOADS_DIR) {
+                        continue;
+                    }
                     stack.push(path);
                 } else if metadata.is_file() {
                     let modified = match metadata.modified() {
@@ -216,6 +264,325 @@ impl FirmwareStorage {
     }
 }
 
+impl FirmwareStorage {
+    fn uploads_dir(&self) -> PathBuf {
+        self.storage_root.join(UPLOADS_DIR)
+    }
+
+    fn session_path(&self, upload_id: &str) -> PathBuf {
+        self.uploads_dir().join(format!("{}.json", upload_id))
+    }
+
+    fn part_path(&self, upload_id: &str) -> PathBuf {
+        self.uploads_dir().join(format!("{}.part", upload_id))
+    }
+
+    async fn load_session(&self, upload_id: &str) -> Result<FirmwareUploadSession, UploadError> {
+        // Upload ids are generated here; anything else cannot name a session
+        if uuid::Uuid::parse_str(upload_id).is_err() {
+            return Err(UploadError::NotFound);
+        }
+        match fs::read(self.session_path(upload_id)).await {
+            Ok(data) => Ok(serde_json::from_slice(&data).context("corrupt upload session")?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(UploadError::NotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the session file through a rename so a crash never leaves it torn
+    async fn save_session(&self, session: &FirmwareUploadSession) -> Result<()> {
+        let path = self.session_path(&session.upload_id);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(session)?)
+            .await
+            .context("failed to write upload session")?;
+        fs::rename(&tmp, &path)
+            .await
+            .context("failed to write upload session")?;
+        Ok(())
+    }
+
+    async fn session_count(&self) -> Result<usize> {
+        let mut count = 0;
+        let mut entries = fs::read_dir(self.uploads_dir()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().extension().is_some_and(|ext| ext == "json") {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Open a chunked upload session for an image of `total_size` bytes
+    pub async fn create_upload(&self, req: &InitFirmwareUploadRequest) -> Result<FirmwareUploadSession, UploadError> {
+        for (value, field) in [
+            (&req.manufacturer, "manufacturer"),
+            (&req.model, "model"),
+            (&req.firmware_version, "firmware_version"),
+        ] {
+            common::validation::validate_name(value, field).map_err(|e| UploadError::Invalid(e.to_string()))?;
+        }
+        if req.total_size == 0 || req.total_size > MAX_UPLOAD_SIZE {
+            return Err(UploadError::Invalid(format!(
+                "total_size must be between 1 and {} bytes",
+                MAX_UPLOAD_SIZE
+            )));
+        }
+        if !is_sha256_hex(&req.checksum) {
+            return Err(UploadError::Invalid("checksum must be a hex SHA-256".to_string()));
+        }
+
+        let _guard = self.uploads.lock().await;
+        fs::create_dir_all(self.uploads_dir())
+            .await
+            .context("failed to create upload directory")?;
+        if self.session_count().await? >= MAX_UPLOAD_SESSIONS {
+            return Err(UploadError::Invalid(format!(
+                "too many open upload sessions (max {})",
+                MAX_UPLOAD_SESSIONS
+            )));
+        }
+
+        let now = Utc::now();
+        let session = FirmwareUploadSession {
+            upload_id: uuid::Uuid::new_v4().to_string(),
+            manufacturer: req.manufacturer.clone(),
+            model: req.model.clone(),
+            firmware_version: req.firmware_version.clone(),
+            total_size: req.total_size,
+            checksum: req.checksum.to_ascii_lowercase(),
+            received_bytes: 0,
+            chunk_count: 0,
+            release_notes: req.release_notes.clone(),
+            release_date: req.release_date,
+            min_device_version: req.min_device_version.clone(),
+            compatible_models: req.compatible_models.clone(),
+            created_at: now,
+            updated_at: now,
+        };
+        fs::File::create(self.part_path(&session.upload_id))
+            .await
+            .context("failed to create upload file")?;
+        self.save_session(&session).await?;
+        info!(
+            "opened firmware upload {} for {} {} v{} ({} bytes)",
+            session.upload_id, session.manufacturer, session.model, session.firmware_version, session.total_size
+        );
+        Ok(session)
+    }
+
+    /// Get an upload session, e.g. to find the offset to resume from
+    pub async fn get_upload(&self, upload_id: &str) -> Result<FirmwareUploadSession, UploadError> {
+        self.load_session(upload_id).await
+    }
+
+    /// Append a chunk at `offset`, which must be where the upload stands. A
+    /// chunk that was already received (a retry after a lost response) is
+    /// accepted without writing it again.
+    pub async fn append_chunk(
+        &self,
+        upload_id: &str,
+        offset: u64,
+        data: &[u8],
+        chunk_checksum: &str,
+    ) -> Result<FirmwareUploadSession, UploadError> {
+        if data.is_empty() || data.len() > MAX_CHUNK_SIZE {
+            return Err(UploadError::Invalid(format!(
+                "chunks must be between 1 and {} bytes",
+                MAX_CHUNK_SIZE
+            )));
+        }
+        if !calculate_checksum(data).eq_ignore_ascii_case(chunk_checksum) {
+            return Err(UploadError::ChunkChecksum);
+        }
+
+        let _guard = self.uploads.lock().await;
+        let mut session = self.load_session(upload_id).await?;
+        let end = offset.saturating_add(data.len() as u64);
+        if end <= session.received_bytes {
+            debug!("firmware upload {}: chunk at {} already received", upload_id, offset);
+            return Ok(session);
+        }
+        if offset != session.received_bytes {
+            return Err(UploadError::OffsetMismatch {
+                offset,
+                expected: session.received_bytes,
+            });
+        }
+        if end > session.total_size {
+            return Err(UploadError::Invalid(format!(
+                "chunk ends at {}, past total_size {}",
+                end, session.total_size
+            )));
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(self.part_path(upload_id))
+            .await
+            .context("failed to open upload file")?;
+        // A crash between writing data and the session leaves extra bytes;
+        // cut back to what the session records before appending
+        file.set_len(offset).await?;
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        file.write_all(data).await.context("failed to write chunk")?;
+        file.sync_all().await.context("failed to sync chunk")?;
+
+        session.received_bytes = end;
+        session.chunk_count = session.chunk_count.saturating_add(1);
+        session.updated_at = Utc::now();
+        self.save_session(&session).await?;
+        Ok(session)
+    }
+
+    /// Verify the whole image against the session checksum and move it into
+    /// the catalog layout. Returns the session with the relative path of the
+    /// stored file; the session is gone afterwards.
+    pub async fn complete_upload(&self, upload_id: &str) -> Result<(FirmwareUploadSession, String), UploadError> {
+        let _guard = self.uploads.lock().await;
+        let session = self.load_session(upload_id).await?;
+        if session.received_bytes != session.total_size {
+            return Err(UploadError::Invalid(format!(
+                "upload incomplete: {} of {} bytes received",
+                session.received_bytes, session.total_size
+            )));
+        }
+
+        let part = self.part_path(upload_id);
+        let actual = checksum_file(&part).await?;
+        if actual != session.checksum {
+            return Err(UploadError::ImageChecksum {
+                expected: session.checksum.clone(),
+                actual,
+            });
+        }
+
+        let subdir = self
+            .storage_root
+            .join(sanitize_filename(&session.manufacturer))
+            .join(sanitize_filename(&session.model));
+        fs::create_dir_all(&subdir)
+            .await
+            .context("failed to create firmware subdirectory")?;
+        let file_path = subdir.join(format!(
+            "{}_{}.bin",
+            session.upload_id,
+            sanitize_filename(&session.firmware_version)
+        ));
+        fs::rename(&part, &file_path)
+            .await
+            .context("failed to move uploaded firmware into place")?;
+        let _ = fs::remove_file(self.session_path(upload_id)).await;
+
+        let relative_path = file_path
+            .strip_prefix(&self.storage_root)
+            .context("failed to get relative path")?
+            .to_string_lossy()
+            .to_string();
+        info!(
+            "completed firmware upload {}: {} (checksum: {})",
+            upload_id, relative_path, session.checksum
+        );
+        Ok((session, relative_path))
+    }
+
+    /// Drop an upload session and the data received so far
+    pub async fn abort_upload(&self, upload_id: &str) -> Result<(), UploadError> {
+        let _guard = self.uploads.lock().await;
+        self.load_session(upload_id).await?;
+        let _ = fs::remove_file(self.part_path(upload_id)).await;
+        fs::remove_file(self.session_path(upload_id))
+            .await
+            .context("failed to remove upload session")?;
+        info!("aborted firmware upload {}", upload_id);
+        Ok(())
+    }
+
+    /// Remove upload sessions without a chunk for `max_idle`, and data
+    /// files whose session is gone
+    pub async fn cleanup_stale_uploads(&self, max_idle: Duration) -> Result<usize> {
+        let _guard = self.uploads.lock().await;
+        let cutoff = Utc::now() - chrono::Duration::from_std(max_idle).unwrap_or(chrono::Duration::MAX);
+        let mut entries = match fs::read_dir(self.uploads_dir()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut removed = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let Some(upload_id) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+                continue;
+            };
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+            let stale = match extension {
+                "json" => match self.load_session(&upload_id).await {
+                    Ok(session) => session.updated_at < cutoff,
+                    // Unreadable sessions can never complete
+                    Err(_) => true,
+                },
+                "part" => path.exists() && !self.session_path(&upload_id).exists(),
+                _ => false,
+            };
+            if !stale {
+                continue;
+            }
+            if extension == "json" {
+                let _ = fs::remove_file(self.part_path(&upload_id)).await;
+                removed += 1;
+                info!("removed abandoned firmware upload {}", upload_id);
+            }
+            if let Err(e) = fs::remove_file(&path).await {
+                warn!("failed to remove {:?}: {}", path, e);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Idle time after which an upload session is abandoned, by default
+pub const DEFAULT_UPLOAD_TTL_SECS: u64 = 86_400;
+
+/// How often abandoned upload sessions are looked for
+pub const UPLOAD_CLEANUP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Remove upload sessions idle for longer than `ttl_secs` every
+/// [`UPLOAD_CLEANUP_INTERVAL`]; `ttl_secs` may be changed while running
+pub fn spawn_upload_cleanup(storage: Arc<FirmwareStorage>, ttl_secs: Arc<AtomicU64>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPLOAD_CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let ttl = Duration::from_secs(ttl_secs.load(Ordering::Relaxed));
+            match storage.cleanup_stale_uploads(ttl).await {
+                Ok(0) => {}
+                Ok(removed) => info!("removed {} abandoned firmware uploads", removed),
+                Err(e) => warn!("firmware upload cleanup failed: {:#}", e),
+            }
+        }
+    });
+}
+
+/// SHA-256 of a file, read in chunks so large images are not held in memory
+async fn checksum_file(path: &std::path::Path) -> Result<String> {
+    let mut file = fs::File::open(path).await.context("failed to open uploaded firmware")?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(buf.get(..n).unwrap_or_default());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
 /// Calculate SHA-256 checksum of data
 pub fn calculate_checksum(data: &[u8]) -> String {
     let mut hasher = Sha256::new();
@@ -291,6 +658,86 @@ mod tests {
         assert_ne!(checksum1, checksum3);
     }
 
+    fn upload_request(data: &[u8]) -> InitFirmwareUploadRequest {
+        InitFirmwareUploadRequest {
+            manufacturer: "acme".to_string(),
+            model: "cam1".to_string(),
+            firmware_version: "2.0.0".to_string(),
+            total_size: data.len() as u64,
+            checksum: calculate_checksum(data),
+            release_notes: None,
+            release_date: None,
+            min_device_version: None,
+            compatible_models: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_resumes_and_completes() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FirmwareStorage::new(temp_dir.path()).unwrap();
+        storage.init().await.unwrap();
+
+        let data = b"chunked firmware image data";
+        let session = storage.create_upload(&upload_request(data)).await.unwrap();
+        let id = session.upload_id.clone();
+
+        let (first, rest) = data.split_at(10);
+        storage.append_chunk(&id, 0, first, &calculate_checksum(first)).await.unwrap();
+        // A retried chunk is accepted once, a gap is refused
+        let retried = storage.append_chunk(&id, 0, first, &calculate_checksum(first)).await.unwrap();
+        assert_eq!(retried.received_bytes, 10);
+        assert_eq!(retried.chunk_count, 1);
+        let gap = storage.append_chunk(&id, 12, rest, &calculate_checksum(rest)).await;
+        assert!(matches!(gap, Err(UploadError::OffsetMismatch { expected: 10, .. })));
+        let corrupt = storage.append_chunk(&id, 10, rest, &calculate_checksum(first)).await;
+        assert!(matches!(corrupt, Err(UploadError::ChunkChecksum)));
+
+        // Resume from the offset the session reports
+        let offset = storage.get_upload(&id).await.unwrap().received_bytes;
+        storage.append_chunk(&id, offset, rest, &calculate_checksum(rest)).await.unwrap();
+
+        let (session, path) = storage.complete_upload(&id).await.unwrap();
+        assert_eq!(session.total_size, data.len() as u64);
+        assert_eq!(storage.read_file(&path).await.unwrap(), data);
+        storage.validate_file(&path, &session.checksum).await.unwrap();
+        assert!(matches!(storage.get_upload(&id).await, Err(UploadError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_upload_with_wrong_image_checksum_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FirmwareStorage::new(temp_dir.path()).unwrap();
+        storage.init().await.unwrap();
+
+        let data = b"firmware";
+        let mut req = upload_request(data);
+        req.checksum = calculate_checksum(b"something else");
+        let session = storage.create_upload(&req).await.unwrap();
+        storage
+            .append_chunk(&session.upload_id, 0, data, &calculate_checksum(data))
+            .await
+            .unwrap();
+
+        let result = storage.complete_upload(&session.upload_id).await;
+        assert!(matches!(result, Err(UploadError::ImageChecksum { .. })));
+        // The session stays so the client can abort or inspect it
+        assert!(storage.get_upload(&session.upload_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stale_uploads_are_collected() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FirmwareStorage::new(temp_dir.path()).unwrap();
+        storage.init().await.unwrap();
+
+        let session = storage.create_upload(&upload_request(b"abandoned")).await.unwrap();
+        assert_eq!(storage.cleanup_stale_uploads(Duration::from_secs(3600)).await.unwrap(), 0);
+        assert_eq!(storage.cleanup_stale_uploads(Duration::ZERO).await.unwrap(), 1);
+        assert!(matches!(storage.get_upload(&session.upload_id).await, Err(UploadError::NotFound)));
+        assert!(!storage.part_path(&session.upload_id).exists());
+    }
+
     #[test]
     fn test_sanitize_filename() {
         assert_eq!(sanitize_filename("normal.txt"), "normal.txt");
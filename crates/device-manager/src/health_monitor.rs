@@ -1,6 +1,10 @@
 use crate::prober::DeviceProber;
+use crate::recovery::RecoveryHook;
 use crate::store::DeviceStore;
-use crate::types::{Device, DeviceStatus};
+use crate::types::{Device, DeviceStatus, HealthCheckOutcome};
+use common::timeline::{self, TimelineEvent, TimelineEventKind};
+use serde_json::json;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
@@ -9,8 +13,9 @@ use tracing::{error, info, warn};
 pub struct HealthMonitor {
     store: Arc<DeviceStore>,
     prober: Arc<DeviceProber>,
-    check_interval_secs: u64,
-    max_consecutive_failures: i32,
+    check_interval_secs: AtomicU64,
+    max_consecutive_failures: AtomicI32,
+    recovery: Option<Arc<RecoveryHook>>,
 }
 
 impl HealthMonitor {
@@ -23,11 +28,26 @@ impl HealthMonitor {
         Self {
             store,
             prober,
-            check_interval_secs,
-            max_consecutive_failures,
+            check_interval_secs: AtomicU64::new(check_interval_secs),
+            max_consecutive_failures: AtomicI32::new(max_consecutive_failures),
+            recovery: None,
         }
     }
 
+    /// Restart a device's failed streams and recordings when it recovers
+    pub fn with_recovery_hook(mut self, hook: Arc<RecoveryHook>) -> Self {
+        self.recovery = Some(hook);
+        self
+    }
+
+    /// Change the check interval and failure threshold of a running monitor;
+    /// the new interval applies after the current wait
+    pub fn reconfigure(&self, check_interval_secs: u64, max_consecutive_failures: i32) {
+        self.check_interval_secs.store(check_interval_secs, Ordering::Relaxed);
+        self.max_consecutive_failures
+            .store(max_consecutive_failures, Ordering::Relaxed);
+    }
+
     /// Start the health monitoring loop
     pub async fn start(&self) {
         info!("health monitor started");
@@ -37,7 +57,10 @@ impl HealthMonitor {
                 error!("health check cycle failed: {}", e);
             }
 
-            sleep(Duration::from_secs(self.check_interval_secs)).await;
+            sleep(Duration::from_secs(
+                self.check_interval_secs.load(Ordering::Relaxed),
+            ))
+            .await;
         }
     }
 
@@ -57,10 +80,12 @@ impl HealthMonitor {
         for device in devices {
             let store = Arc::clone(&self.store);
             let prober = Arc::clone(&self.prober);
-            let max_failures = self.max_consecutive_failures;
+            let max_failures = self.max_consecutive_failures.load(Ordering::Relaxed);
+            let recovery = self.recovery.clone();
 
             let task = tokio::spawn(async move {
-                if let Err(e) = Self::check_device_health(device, store, prober, max_failures).await
+                if let Err(e) =
+                    Self::check_device_health(device, store, prober, max_failures, recovery).await
                 {
                     error!("failed to check device health: {}", e);
                 }
@@ -90,6 +115,7 @@ impl HealthMonitor {
         store: Arc<DeviceStore>,
         prober: Arc<DeviceProber>,
         max_consecutive_failures: i32,
+        recovery: Option<Arc<RecoveryHook>>,
     ) -> anyhow::Result<()> {
         let device_id = &device.device_id;
         let username = device.username.as_deref();
@@ -99,10 +125,19 @@ impl HealthMonitor {
             .and_then(|enc| store.decrypt_password(enc).ok());
         let password = password_decrypted.as_deref();
 
-        // Perform health check
-        let (is_healthy, response_time_ms, error_message) = prober
-            .health_check(&device.primary_uri, &device.protocol, username, password)
-            .await?;
+        // Run the device's selected checks; it is healthy only if all pass
+        let checks = store.get_health_checks(device_id).await?.unwrap_or_default();
+        let outcomes = prober
+            .run_health_checks(&device, &checks, username, password)
+            .await;
+        let is_healthy = outcomes.iter().all(|o| o.success);
+        let response_time_ms: u64 = outcomes.iter().map(|o| o.latency_ms).sum();
+        let failures: Vec<String> = outcomes
+            .iter()
+            .filter(|o| !o.success)
+            .map(|o| format!("{}: {}", o.check.as_str(), o.error.as_deref().unwrap_or("failed")))
+            .collect();
+        let error_message = (!failures.is_empty()).then(|| failures.join("; "));
 
         // Determine new status
         let new_status = if is_healthy {
@@ -113,6 +148,13 @@ impl HealthMonitor {
             DeviceStatus::Offline
         };
 
+        // A scheduled reboot may have started while the checks ran
+        if let Some(current) = store.get_device(device_id).await? {
+            if current.status == DeviceStatus::Maintenance {
+                return Ok(());
+            }
+        }
+
         // Update device status
         store
             .update_health_status(
@@ -120,9 +162,20 @@ impl HealthMonitor {
                 new_status.clone(),
                 Some(response_time_ms as i32),
                 error_message.clone(),
+                &outcomes,
             )
             .await?;
 
+        if new_status != device.status {
+            timeline::record(status_event(
+                &device,
+                &new_status,
+                response_time_ms,
+                error_message.as_deref(),
+                &outcomes,
+            ));
+        }
+
         // Log result
         match new_status {
             DeviceStatus::Online => {
@@ -134,6 +187,12 @@ impl HealthMonitor {
                         "device came online"
                     );
                 }
+                // Back from an outage, not from maintenance or provisioning
+                if matches!(device.status, DeviceStatus::Offline | DeviceStatus::Error) {
+                    if let Some(hook) = &recovery {
+                        hook.device_recovered(device_id);
+                    }
+                }
             }
             DeviceStatus::Offline => {
                 warn!(
@@ -159,3 +218,28 @@ impl HealthMonitor {
         Ok(())
     }
 }
+
+/// Timeline event for a device status transition
+pub(crate) fn status_event(
+    device: &Device,
+    new_status: &DeviceStatus,
+    response_time_ms: u64,
+    error: Option<&str>,
+    checks: &[HealthCheckOutcome],
+) -> TimelineEvent {
+    let to = json!(new_status);
+    TimelineEvent::new(
+        TimelineEventKind::DeviceStatus,
+        "device-manager",
+        format!("{} is {}", device.name, to.as_str().unwrap_or_default()),
+    )
+    .camera(device.device_id.clone())
+    .tenant(device.tenant_id.clone())
+    .details(json!({
+        "from": device.status,
+        "to": to,
+        "error": error,
+        "response_time_ms": response_time_ms,
+        "checks": checks,
+    }))
+}
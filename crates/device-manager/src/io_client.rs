@@ -0,0 +1,245 @@
+use crate::types::*;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use quick_xml::escape::escape;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Trait for digital output (relay) operations
+#[async_trait]
+pub trait IoClient: Send + Sync {
+    /// Relay outputs of the device with their settings
+    async fn list_relay_outputs(&self) -> Result<Vec<RelayOutput>>;
+
+    /// Set a relay output active or inactive
+    async fn set_relay_output_state(&self, token: &str, state: RelayState) -> Result<()>;
+}
+
+/// ONVIF DeviceIO client; relay outputs are part of the device service
+pub struct OnvifIoClient {
+    device_service_url: String,
+    username: Option<String>,
+    password: Option<String>,
+    http_client: reqwest::Client,
+}
+
+impl OnvifIoClient {
+    pub fn new(device_uri: &str, username: Option<String>, password: Option<String>) -> Result<Self> {
+        let device_service_url = if device_uri.contains("/onvif/device_service") {
+            device_uri.to_string()
+        } else {
+            format!("{}/onvif/device_service", device_uri.trim_end_matches('/'))
+        };
+        let http_client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?;
+
+        Ok(Self {
+            device_service_url,
+            username,
+            password,
+            http_client,
+        })
+    }
+
+    async fn send_onvif_request(&self, soap_body: &str) -> Result<String> {
+        let envelope = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope"
+            xmlns:tds="http://www.onvif.org/ver10/device/wsdl"
+            xmlns:tt="http://www.onvif.org/ver10/schema">
+  <s:Body>
+    {}
+  </s:Body>
+</s:Envelope>"#,
+            soap_body
+        );
+
+        debug!("sending ONVIF device IO request to {}", self.device_service_url);
+
+        let mut request = self
+            .http_client
+            .post(&self.device_service_url)
+            .header("Content-Type", "application/soap+xml; charset=utf-8")
+            .body(envelope);
+
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            request = request.basic_auth(username, Some(password));
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(anyhow!("ONVIF request failed: {} - {}", status, body));
+        }
+
+        Ok(body)
+    }
+}
+
+#[async_trait]
+impl IoClient for OnvifIoClient {
+    async fn list_relay_outputs(&self) -> Result<Vec<RelayOutput>> {
+        let body = self.send_onvif_request("<tds:GetRelayOutputs/>").await?;
+        parse_relay_outputs(&body)
+    }
+
+    async fn set_relay_output_state(&self, token: &str, state: RelayState) -> Result<()> {
+        let soap_body = format!(
+            r#"<tds:SetRelayOutputState>
+      <tds:RelayOutputToken>{}</tds:RelayOutputToken>
+      <tds:LogicalState>{}</tds:LogicalState>
+    </tds:SetRelayOutputState>"#,
+            escape(token),
+            state.as_onvif()
+        );
+        self.send_onvif_request(&soap_body).await?;
+        Ok(())
+    }
+}
+
+/// The `RelayOutputs` of a `GetRelayOutputs` response
+pub fn parse_relay_outputs(xml: &str) -> Result<Vec<RelayOutput>> {
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut outputs = Vec::new();
+    let mut current: Option<RelayOutput> = None;
+    let mut current_tag = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                if name == "RelayOutputs" {
+                    let token = e
+                        .attributes()
+                        .flatten()
+                        .find(|a| a.key.local_name().as_ref() == b"token")
+                        .and_then(|a| a.unescape_value().ok())
+                        .map(|v| v.to_string())
+                        .unwrap_or_default();
+                    current = Some(RelayOutput {
+                        token,
+                        mode: None,
+                        idle_state: None,
+                        delay_time: None,
+                    });
+                }
+                current_tag = name;
+            }
+            Ok(Event::End(e)) => {
+                if e.local_name().as_ref() == b"RelayOutputs" {
+                    outputs.extend(current.take().filter(|o| !o.token.is_empty()));
+                }
+                current_tag.clear();
+            }
+            Ok(Event::Text(e)) => {
+                let Some(output) = current.as_mut() else {
+                    continue;
+                };
+                let text = e.unescape().unwrap_or_default();
+                let text = text.trim();
+                match current_tag.as_str() {
+                    "Mode" => {
+                        output.mode = match text {
+                            "Monostable" => Some(RelayMode::Monostable),
+                            "Bistable" => Some(RelayMode::Bistable),
+                            _ => None,
+                        }
+                    }
+                    "IdleState" => {
+                        output.idle_state = match text {
+                            "open" => Some(RelayIdleState::Open),
+                            "closed" => Some(RelayIdleState::Closed),
+                            _ => None,
+                        }
+                    }
+                    "DelayTime" => output.delay_time = Some(text.to_string()),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(anyhow!("invalid GetRelayOutputs response: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok(outputs)
+}
+
+/// Factory for creating IO clients based on device protocol. Unlike imaging,
+/// there is no mock fallback: an operator opening a door must not be told it
+/// opened when nothing was sent
+pub fn create_io_client(
+    protocol: &ConnectionProtocol,
+    device_uri: &str,
+    username: Option<String>,
+    password: Option<String>,
+) -> Result<Arc<dyn IoClient>> {
+    match protocol {
+        ConnectionProtocol::Onvif => Ok(Arc::new(OnvifIoClient::new(device_uri, username, password)?)),
+        other => Err(anyhow!("relay outputs are not supported over {:?}; use ONVIF", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<SOAP-ENV:Envelope xmlns:SOAP-ENV="http://www.w3.org/2003/05/soap-envelope"
+    xmlns:tds="http://www.onvif.org/ver10/device/wsdl" xmlns:tt="http://www.onvif.org/ver10/schema">
+  <SOAP-ENV:Body>
+    <tds:GetRelayOutputsResponse>
+      <tds:RelayOutputs token="AlarmOut_0">
+        <tt:Properties>
+          <tt:Mode>Monostable</tt:Mode>
+          <tt:DelayTime>PT5S</tt:DelayTime>
+          <tt:IdleState>open</tt:IdleState>
+        </tt:Properties>
+      </tds:RelayOutputs>
+      <tds:RelayOutputs token="AlarmOut_1">
+        <tt:Properties>
+          <tt:Mode>Bistable</tt:Mode>
+          <tt:DelayTime>PT0S</tt:DelayTime>
+          <tt:IdleState>closed</tt:IdleState>
+        </tt:Properties>
+      </tds:RelayOutputs>
+    </tds:GetRelayOutputsResponse>
+  </SOAP-ENV:Body>
+</SOAP-ENV:Envelope>"#;
+
+    #[test]
+    fn test_parse_relay_outputs() {
+        let outputs = parse_relay_outputs(RESPONSE).unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                RelayOutput {
+                    token: "AlarmOut_0".to_string(),
+                    mode: Some(RelayMode::Monostable),
+                    idle_state: Some(RelayIdleState::Open),
+                    delay_time: Some("PT5S".to_string()),
+                },
+                RelayOutput {
+                    token: "AlarmOut_1".to_string(),
+                    mode: Some(RelayMode::Bistable),
+                    idle_state: Some(RelayIdleState::Closed),
+                    delay_time: Some("PT0S".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relay_outputs_need_onvif() {
+        assert!(create_io_client(&ConnectionProtocol::Rtsp, "rtsp://10.0.0.5/stream", None, None).is_err());
+        assert!(create_io_client(&ConnectionProtocol::Onvif, "http://10.0.0.5", None, None).is_ok());
+    }
+}
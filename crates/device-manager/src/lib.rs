@@ -1,27 +1,36 @@
+pub mod clock_sync;
 pub mod discovery;
+pub mod duplicates;
 pub mod firmware_client;
 pub mod firmware_executor;
 pub mod firmware_routes;
 pub mod firmware_storage;
 pub mod health_monitor;
 pub mod imaging_client;
+pub mod io_client;
+pub mod maintenance;
 pub mod prober;
 pub mod ptz_client;
 pub mod ptz_routes;
+pub mod recovery;
 pub mod routes_simple;
 pub mod state;
 pub mod store;
 pub mod tour_executor;
 pub mod types;
 
+pub use clock_sync::ClockSyncChecker;
 pub use discovery::OnvifDiscoveryClient;
 pub use firmware_client::{create_firmware_client, FirmwareClient};
 pub use firmware_executor::FirmwareExecutor;
 pub use firmware_storage::FirmwareStorage;
 pub use health_monitor::HealthMonitor;
 pub use imaging_client::{create_imaging_client, ImagingClient};
+pub use io_client::{create_io_client, IoClient};
+pub use maintenance::MaintenanceScheduler;
 pub use prober::DeviceProber;
 pub use ptz_client::{create_ptz_client, PtzClient};
+pub use recovery::RecoveryHook;
 pub use routes_simple as routes;
 pub use state::DeviceManagerState;
 pub use store::DeviceStore;
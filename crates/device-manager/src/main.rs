@@ -1,52 +1,72 @@
 use anyhow::{Context, Result};
 use device_manager::{
     DeviceManagerState, DeviceProber, DeviceStore, FirmwareExecutor, FirmwareStorage,
-    HealthMonitor, OnvifDiscoveryClient, TourExecutor,
+    ClockSyncChecker, HealthMonitor, MaintenanceScheduler, OnvifDiscoveryClient, RecoveryHook,
+    TourExecutor,
 };
+use common::config_reload::{settings_routes, ConfigReloader, Setting};
+use device_manager::firmware_storage::{spawn_upload_cleanup, DEFAULT_UPLOAD_TTL_SECS};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tracing::info;
 
+/// Settings read at startup; live ones are applied to the health monitor,
+/// clock sync checker and firmware upload cleanup while running
+const SETTINGS: &[Setting] = &[
+    Setting::restart("DEVICE_MANAGER_ADDR"),
+    Setting::restart("PROBE_TIMEOUT_SECS"),
+    Setting::live("HEALTH_CHECK_INTERVAL_SECS"),
+    Setting::live("MAX_CONSECUTIVE_FAILURES"),
+    Setting::restart("CLOCK_SYNC_INTERVAL_SECS"),
+    Setting::live("CLOCK_DRIFT_THRESHOLD_MS"),
+    Setting::live("CLOCK_DRIFT_HISTORY_DAYS"),
+    Setting::restart("MAINTENANCE_CHECK_INTERVAL_SECS"),
+    Setting::restart("PTZ_TIMEOUT_SECS"),
+    Setting::restart("DISCOVERY_TIMEOUT_SECS"),
+    Setting::restart("FIRMWARE_STORAGE_ROOT"),
+    Setting::live("FIRMWARE_UPLOAD_TTL_SECS"),
+];
+
 #[tokio::main]
 async fn main() -> Result<()> {
     telemetry::init();
 
-    // Load configuration from environment
-    let bind_addr: std::net::SocketAddr = std::env::var("DEVICE_MANAGER_ADDR")
-        .unwrap_or_else(|_| "127.0.0.1:8084".to_string())
-        .parse()
-        .context("invalid bind address")?;
-
     let database_url = std::env::var("DATABASE_URL")
         .context("DATABASE_URL environment variable required")?;
 
-    let probe_timeout_secs = std::env::var("PROBE_TIMEOUT_SECS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(10);
+    // Remaining configuration comes from the environment and CONFIG_FILE;
+    // live settings are re-applied on SIGHUP or central config changes
+    let reloader = ConfigReloader::start("device-manager", SETTINGS).await?;
+    let settings = reloader.current();
 
-    let health_check_interval_secs = std::env::var("HEALTH_CHECK_INTERVAL_SECS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(30);
-
-    let max_consecutive_failures = std::env::var("MAX_CONSECUTIVE_FAILURES")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(3);
-
-    let ptz_timeout_secs = std::env::var("PTZ_TIMEOUT_SECS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(10);
+    let bind_addr: std::net::SocketAddr = settings
+        .get("DEVICE_MANAGER_ADDR")
+        .unwrap_or("127.0.0.1:8084")
+        .parse()
+        .context("invalid bind address")?;
 
-    let discovery_timeout_secs = std::env::var("DISCOVERY_TIMEOUT_SECS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(5);
+    let probe_timeout_secs = settings.get_or("PROBE_TIMEOUT_SECS", 10);
+    let health_check_interval_secs = settings.get_or("HEALTH_CHECK_INTERVAL_SECS", 30);
+    let max_consecutive_failures = settings.get_or("MAX_CONSECUTIVE_FAILURES", 3);
+    let clock_sync_interval_secs: u64 = settings.get_or("CLOCK_SYNC_INTERVAL_SECS", 300);
+    let clock_drift_threshold_ms: i64 = settings.get_or("CLOCK_DRIFT_THRESHOLD_MS", 2000);
+    let clock_drift_history_days = settings.get_or("CLOCK_DRIFT_HISTORY_DAYS", 30);
+    let maintenance_check_interval_secs: u64 = settings.get_or(
+        "MAINTENANCE_CHECK_INTERVAL_SECS",
+        device_manager::maintenance::DEFAULT_CHECK_INTERVAL_SECS,
+    );
+    let ptz_timeout_secs = settings.get_or("PTZ_TIMEOUT_SECS", 10);
+    let discovery_timeout_secs = settings.get_or("DISCOVERY_TIMEOUT_SECS", 5);
+    let firmware_storage_root = settings
+        .get("FIRMWARE_STORAGE_ROOT")
+        .unwrap_or("./data/firmware")
+        .to_string();
+    let firmware_upload_ttl_secs = Arc::new(AtomicU64::new(
+        settings.get_or("FIRMWARE_UPLOAD_TTL_SECS", DEFAULT_UPLOAD_TTL_SECS),
+    ));
 
-    let firmware_storage_root = std::env::var("FIRMWARE_STORAGE_ROOT")
-        .unwrap_or_else(|_| "./data/firmware".to_string());
+    common::timeline::init_from_env(None).await?;
 
     // Initialize store
     info!("connecting to database");
@@ -72,6 +92,12 @@ async fn main() -> Result<()> {
         .await
         .context("failed to initialize firmware storage")?;
 
+    // Collect chunked uploads abandoned by their clients
+    spawn_upload_cleanup(
+        Arc::clone(&firmware_storage),
+        Arc::clone(&firmware_upload_ttl_secs),
+    );
+
     // Initialize firmware executor
     let firmware_executor = Arc::new(FirmwareExecutor::new(
         (*store).clone(),
@@ -88,20 +114,84 @@ async fn main() -> Result<()> {
         Arc::clone(&firmware_storage),
     );
 
-    // Start health monitor in background
-    let health_monitor = HealthMonitor::new(
+    // Start health monitor in background; recovered devices get their
+    // failed streams and recordings restarted by the coordinator
+    let mut health_monitor = HealthMonitor::new(
         Arc::clone(&store),
         Arc::clone(&prober),
         health_check_interval_secs,
         max_consecutive_failures,
     );
+    let recovery_hook = RecoveryHook::from_env()?.map(Arc::new);
+    if let Some(hook) = &recovery_hook {
+        info!("restarting failed streams and recordings of recovered devices");
+        health_monitor = health_monitor.with_recovery_hook(Arc::clone(hook));
+    }
+    let health_monitor = Arc::new(health_monitor);
+
+    let monitor = Arc::clone(&health_monitor);
+    tokio::spawn(async move {
+        monitor.start().await;
+    });
+
+    // Start clock sync checker in background; an interval of 0 disables it
+    let clock_sync = (clock_sync_interval_secs > 0).then(|| {
+        Arc::new(ClockSyncChecker::new(
+            Arc::clone(&store),
+            probe_timeout_secs,
+            clock_sync_interval_secs,
+            clock_drift_threshold_ms,
+            clock_drift_history_days,
+        ))
+    });
 
+    if let Some(clock_sync) = &clock_sync {
+        let checker = Arc::clone(clock_sync);
+        tokio::spawn(async move {
+            checker.start().await;
+        });
+    }
+
+    // Start scheduled maintenance in background; an interval of 0 disables it
+    if maintenance_check_interval_secs > 0 {
+        let mut scheduler = MaintenanceScheduler::new(
+            Arc::clone(&store),
+            Arc::clone(&prober),
+            maintenance_check_interval_secs,
+        );
+        if let Some(hook) = &recovery_hook {
+            scheduler = scheduler.with_recovery_hook(Arc::clone(hook));
+        }
+        let scheduler = Arc::new(scheduler);
+        tokio::spawn(async move {
+            scheduler.start().await;
+        });
+    }
+
+    // Apply reloaded settings to the background workers
+    let mut changes = reloader.subscribe();
     tokio::spawn(async move {
-        health_monitor.start().await;
+        while changes.changed().await.is_ok() {
+            let settings = changes.borrow_and_update().clone();
+            health_monitor.reconfigure(
+                settings.get_or("HEALTH_CHECK_INTERVAL_SECS", 30),
+                settings.get_or("MAX_CONSECUTIVE_FAILURES", 3),
+            );
+            if let Some(clock_sync) = &clock_sync {
+                clock_sync.reconfigure(
+                    settings.get_or("CLOCK_DRIFT_THRESHOLD_MS", 2000),
+                    settings.get_or("CLOCK_DRIFT_HISTORY_DAYS", 30),
+                );
+            }
+            firmware_upload_ttl_secs.store(
+                settings.get_or("FIRMWARE_UPLOAD_TTL_SECS", DEFAULT_UPLOAD_TTL_SECS),
+                Ordering::Relaxed,
+            );
+        }
     });
 
     // Create router
-    let app = device_manager::routes::router(state);
+    let app = device_manager::routes::router(state).merge(settings_routes(reloader));
 
     // Start server
     let listener = TcpListener::bind(bind_addr).await?;
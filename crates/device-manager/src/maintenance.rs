@@ -0,0 +1,357 @@
+//! Scheduled device maintenance.
+//!
+//! Some camera models only stay healthy when rebooted regularly. A
+//! maintenance task reboots one device, or every device of a manufacturer
+//! and model, with ONVIF `SystemReboot` on a cron schedule. For the task's
+//! window each device is put into `maintenance` status, which the health
+//! monitor skips, so the reboot raises no offline events; the device is
+//! polled until its health checks pass again. A device not back when the
+//! window ends is marked offline and monitored (and alerted on) as usual.
+//! Every run is kept in the device's maintenance history.
+
+use crate::firmware_client::{FirmwareClient, OnvifFirmwareClient};
+use crate::health_monitor::status_event;
+use crate::prober::DeviceProber;
+use crate::recovery::RecoveryHook;
+use crate::store::DeviceStore;
+use crate::types::*;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use common::timeline::{self, TimelineEvent, TimelineEventKind};
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use tracing::{debug, error, info, warn};
+
+/// Default of `MAINTENANCE_CHECK_INTERVAL_SECS`
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 30;
+
+/// Time a rebooting device gets to go down before it is polled
+const REBOOT_GRACE: Duration = Duration::from_secs(30);
+
+/// How often a device in its window is checked for being back
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Check a task before it is stored
+pub fn validate(req: &MaintenanceTaskRequest) -> Result<(), String> {
+    let blank = |v: &Option<String>| v.as_deref().is_none_or(|v| v.trim().is_empty());
+    if blank(&req.device_id) && blank(&req.manufacturer) && blank(&req.model) {
+        return Err("a maintenance task needs a device_id, manufacturer or model".to_string());
+    }
+    next_run(&req.schedule_cron, &req.time_zone, Utc::now())?;
+    Ok(())
+}
+
+/// First time after `after` that `schedule_cron` (with seconds, e.g.
+/// `0 0 3 * * Sun`) matches in `time_zone`; `None` when it never will
+pub fn next_run(
+    schedule_cron: &str,
+    time_zone: &str,
+    after: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, String> {
+    let schedule = cron::Schedule::from_str(schedule_cron)
+        .map_err(|e| format!("invalid schedule_cron '{}': {}", schedule_cron, e))?;
+    let tz = chrono_tz::Tz::from_str(time_zone)
+        .map_err(|_| format!("unknown time_zone '{}'", time_zone))?;
+    Ok(schedule
+        .after(&after.with_timezone(&tz))
+        .next()
+        .map(|at| at.with_timezone(&Utc)))
+}
+
+pub struct MaintenanceScheduler {
+    store: Arc<DeviceStore>,
+    prober: Arc<DeviceProber>,
+    check_interval_secs: u64,
+    recovery: Option<Arc<RecoveryHook>>,
+}
+
+impl MaintenanceScheduler {
+    pub fn new(store: Arc<DeviceStore>, prober: Arc<DeviceProber>, check_interval_secs: u64) -> Self {
+        Self {
+            store,
+            prober,
+            check_interval_secs,
+            recovery: None,
+        }
+    }
+
+    /// Restart a device's failed streams and recordings when it is back
+    /// from its reboot
+    pub fn with_recovery_hook(mut self, hook: Arc<RecoveryHook>) -> Self {
+        self.recovery = Some(hook);
+        self
+    }
+
+    /// Start the scheduling loop
+    pub async fn start(self: Arc<Self>) {
+        info!(interval_secs = self.check_interval_secs, "maintenance scheduler started");
+
+        match self.store.recover_maintenance_runs().await {
+            Ok(0) => {}
+            Ok(interrupted) => warn!(interrupted, "maintenance runs interrupted by a restart marked failed"),
+            Err(e) => error!("failed to recover maintenance runs: {}", e),
+        }
+
+        loop {
+            if let Err(e) = self.run_due_tasks().await {
+                error!("maintenance cycle failed: {}", e);
+            }
+
+            sleep(Duration::from_secs(self.check_interval_secs)).await;
+        }
+    }
+
+    /// Start the runs of every task that is due
+    async fn run_due_tasks(self: &Arc<Self>) -> Result<()> {
+        let now = Utc::now();
+        for task in self.store.due_maintenance_tasks(now).await? {
+            let Some(due_at) = task.next_run_at else {
+                continue;
+            };
+            let next = match next_run(&task.schedule_cron, &task.time_zone, now) {
+                Ok(next) => next,
+                Err(e) => {
+                    warn!(task_id = %task.task_id, "maintenance task disabled by its schedule: {}", e);
+                    None
+                }
+            };
+            if !self.store.advance_maintenance_task(&task.task_id, due_at, next).await? {
+                debug!(task_id = %task.task_id, "maintenance task changed before its run");
+                continue;
+            }
+
+            let devices = self.store.maintenance_task_devices(&task).await?;
+            info!(
+                task_id = %task.task_id,
+                task_name = %task.name,
+                devices = devices.len(),
+                next_run_at = ?next,
+                "running maintenance task"
+            );
+            let task = Arc::new(task);
+            for device in devices {
+                let scheduler = Arc::clone(self);
+                let task = Arc::clone(&task);
+                tokio::spawn(async move {
+                    let device_id = device.device_id.clone();
+                    if let Err(e) = scheduler.maintain_device(&task, device).await {
+                        error!(task_id = %task.task_id, device_id = %device_id, "maintenance run failed: {}", e);
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a task's action on one device and wait for it within the window
+    async fn maintain_device(&self, task: &MaintenanceTask, device: Device) -> Result<()> {
+        let window = Duration::from_secs(task.window_secs.max(0) as u64);
+        let window_ends_at = Utc::now() + chrono::Duration::seconds(i64::from(task.window_secs));
+
+        let skipped = if !matches!(device.protocol, ConnectionProtocol::Onvif) {
+            Some("SystemReboot needs an ONVIF device")
+        } else if !self.store.enter_maintenance(&device.device_id).await? {
+            Some("device is already in maintenance or provisioning")
+        } else {
+            None
+        };
+        if let Some(reason) = skipped {
+            info!(task_id = %task.task_id, device_id = %device.device_id, reason, "maintenance skipped");
+            self.store
+                .create_maintenance_run(
+                    Some(&task.task_id),
+                    &device.device_id,
+                    task.action,
+                    MaintenanceRunStatus::Skipped,
+                    Some(reason),
+                    window_ends_at,
+                )
+                .await?;
+            count_run(task.action, MaintenanceRunStatus::Skipped);
+            return Ok(());
+        }
+
+        let run = self
+            .store
+            .create_maintenance_run(
+                Some(&task.task_id),
+                &device.device_id,
+                task.action,
+                MaintenanceRunStatus::Running,
+                None,
+                window_ends_at,
+            )
+            .await?;
+        timeline::record(maintenance_event(&device, task, window_ends_at));
+
+        let started = Instant::now();
+        let password = device
+            .password_encrypted
+            .as_ref()
+            .and_then(|enc| self.store.decrypt_password(enc).ok());
+        let action_error = match OnvifFirmwareClient::new(
+            device.primary_uri.clone(),
+            device.username.clone(),
+            password.clone(),
+            device.device_id.clone(),
+        ) {
+            Ok(client) => client.reboot().await.err(),
+            Err(e) => Some(e),
+        }
+        .map(|e| format!("SystemReboot failed: {:#}", e));
+        if let Some(e) = &action_error {
+            warn!(device_id = %device.device_id, error = %e, "maintenance action failed");
+        }
+
+        // Poll until the device passes its health checks or the window ends
+        let checks = self.store.get_health_checks(&device.device_id).await?.unwrap_or_default();
+        if action_error.is_none() {
+            sleep(REBOOT_GRACE.min(window)).await;
+        }
+        let outcomes = loop {
+            let outcomes = self
+                .prober
+                .run_health_checks(&device, &checks, device.username.as_deref(), password.as_deref())
+                .await;
+            if outcomes.iter().all(|o| o.success) || started.elapsed() >= window {
+                break outcomes;
+            }
+            sleep(POLL_INTERVAL.min(window.saturating_sub(started.elapsed()))).await;
+        };
+
+        let back = outcomes.iter().all(|o| o.success);
+        let response_time_ms: u64 = outcomes.iter().map(|o| o.latency_ms).sum();
+        let (new_status, error_message) = if back {
+            (DeviceStatus::Online, None)
+        } else {
+            let error = format!("not back {}s after scheduled maintenance", task.window_secs);
+            (DeviceStatus::Offline, Some(error))
+        };
+        self.store
+            .update_health_status(
+                &device.device_id,
+                new_status.clone(),
+                Some(response_time_ms as i32),
+                error_message.clone(),
+                &outcomes,
+            )
+            .await?;
+        let in_maintenance = Device {
+            status: DeviceStatus::Maintenance,
+            ..device
+        };
+        timeline::record(status_event(
+            &in_maintenance,
+            &new_status,
+            response_time_ms,
+            error_message.as_deref(),
+            &outcomes,
+        ));
+
+        let (status, error) = match (back, action_error) {
+            (true, None) => (MaintenanceRunStatus::Succeeded, None),
+            (_, Some(e)) => (MaintenanceRunStatus::Failed, Some(e)),
+            (false, None) => (MaintenanceRunStatus::Failed, error_message),
+        };
+        self.store
+            .finish_maintenance_run(run.run_id, status, error.as_deref())
+            .await?;
+        count_run(task.action, status);
+
+        let device_id = &in_maintenance.device_id;
+        if back {
+            info!(device_id = %device_id, status = status.as_str(), "device back from maintenance");
+            // Streams and recordings dropped with the reboot
+            if let Some(hook) = &self.recovery {
+                hook.device_recovered(device_id);
+            }
+        } else {
+            warn!(device_id = %device_id, window_secs = task.window_secs, "device not back from maintenance");
+        }
+        Ok(())
+    }
+}
+
+fn count_run(action: MaintenanceAction, status: MaintenanceRunStatus) {
+    let action = match action {
+        MaintenanceAction::Reboot => "reboot",
+    };
+    telemetry::metrics::DEVICE_MAINTENANCE_RUNS
+        .with_label_values(&[action, status.as_str()])
+        .inc();
+}
+
+/// Timeline event for a device going into its maintenance window
+fn maintenance_event(device: &Device, task: &MaintenanceTask, window_ends_at: DateTime<Utc>) -> TimelineEvent {
+    TimelineEvent::new(
+        TimelineEventKind::DeviceStatus,
+        "device-manager",
+        format!("{} is in maintenance ({})", device.name, task.name),
+    )
+    .camera(device.device_id.clone())
+    .tenant(device.tenant_id.clone())
+    .details(json!({
+        "from": device.status,
+        "to": DeviceStatus::Maintenance,
+        "task_id": task.task_id,
+        "action": task.action,
+        "window_ends_at": window_ends_at,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn request() -> MaintenanceTaskRequest {
+        MaintenanceTaskRequest {
+            name: "Weekly reboot".to_string(),
+            action: MaintenanceAction::Reboot,
+            device_id: None,
+            manufacturer: Some("Acme".to_string()),
+            model: Some("X100".to_string()),
+            schedule_cron: "0 0 3 * * Sun".to_string(),
+            time_zone: "Europe/Berlin".to_string(),
+            window_secs: 600,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_next_run_in_time_zone() {
+        // Saturday 2025-03-29 12:00 UTC; Berlin switches to summer time on
+        // Sunday at 02:00, so 03:00 local is 01:00 UTC
+        let after = Utc.with_ymd_and_hms(2025, 3, 29, 12, 0, 0).unwrap();
+        let next = next_run("0 0 3 * * Sun", "Europe/Berlin", after).unwrap();
+        assert_eq!(next, Some(Utc.with_ymd_and_hms(2025, 3, 30, 1, 0, 0).unwrap()));
+
+        // The week after, from the run itself
+        let following = next_run("0 0 3 * * Sun", "Europe/Berlin", next.unwrap()).unwrap();
+        assert_eq!(following, Some(Utc.with_ymd_and_hms(2025, 4, 6, 1, 0, 0).unwrap()));
+
+        assert!(next_run("0 3 * * Sun", "UTC", after).is_err());
+        assert!(next_run("0 0 3 * * Sun", "Mars/Olympus", after).is_err());
+    }
+
+    #[test]
+    fn test_validate_needs_target() {
+        assert!(validate(&request()).is_ok());
+
+        let untargeted = MaintenanceTaskRequest {
+            manufacturer: None,
+            model: Some(" ".to_string()),
+            ..request()
+        };
+        assert!(validate(&untargeted).unwrap_err().contains("device_id"));
+
+        let bad_schedule = MaintenanceTaskRequest {
+            schedule_cron: "weekly".to_string(),
+            ..request()
+        };
+        assert!(validate(&bad_schedule).is_err());
+    }
+}
@@ -1,11 +1,20 @@
-use crate::types::{ConnectionProtocol, ProbeResult};
-use anyhow::{Context, Result};
+use crate::clock_sync::{parse_utc_date_time, GET_SYSTEM_DATE_AND_TIME};
+use crate::types::{
+    ConnectionProtocol, Device, DeviceHealthChecks, HealthCheckKind, HealthCheckOutcome, ProbeResult,
+};
+use anyhow::{bail, Context, Result};
 use std::collections::HashMap;
 use std::process::Stdio;
 use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
 use tokio::process::Command;
 use tokio::time::timeout;
 
+const DEFAULT_RTSP_PORT: u16 = 554;
+const MAX_RTSP_HEAD_BYTES: usize = 16 * 1024;
+const MAX_RTSP_BODY_BYTES: usize = 64 * 1024;
+
 pub struct DeviceProber {
     timeout_secs: u64,
 }
@@ -531,4 +540,486 @@ impl DeviceProber {
             Err(_) => Ok((false, elapsed, Some("Health check timeout".to_string()))),
         }
     }
+
+    /// Run the health checks selected for a device, in order, each bounded
+    /// by the probe timeout. The reachability check runs when none are
+    /// selected.
+    pub async fn run_health_checks(
+        &self,
+        device: &Device,
+        checks: &DeviceHealthChecks,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) -> Vec<HealthCheckOutcome> {
+        let selected: &[HealthCheckKind] = if checks.checks.is_empty() {
+            &[HealthCheckKind::Reachability]
+        } else {
+            &checks.checks
+        };
+
+        let mut outcomes = Vec::with_capacity(selected.len());
+        for &check in selected {
+            let start = Instant::now();
+            let result = timeout(
+                Duration::from_secs(self.timeout_secs),
+                self.run_health_check(check, device, checks, username, password),
+            )
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("{} timed out", check.as_str())));
+
+            outcomes.push(HealthCheckOutcome {
+                check,
+                success: result.is_ok(),
+                latency_ms: start.elapsed().as_millis() as u64,
+                error: result.err().map(|e| format!("{:#}", e)),
+            });
+        }
+        outcomes
+    }
+
+    async fn run_health_check(
+        &self,
+        check: HealthCheckKind,
+        device: &Device,
+        checks: &DeviceHealthChecks,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) -> Result<()> {
+        match check {
+            HealthCheckKind::Reachability => {
+                let (healthy, _, error_message) = self
+                    .health_check(&device.primary_uri, &device.protocol, username, password)
+                    .await?;
+                if !healthy {
+                    bail!(error_message.unwrap_or_else(|| "device unreachable".to_string()));
+                }
+            }
+            HealthCheckKind::RtspOptions | HealthCheckKind::RtspDescribe => {
+                let uri = checks.rtsp_uri.as_deref().unwrap_or(&device.primary_uri);
+                if !uri.starts_with("rtsp://") {
+                    bail!("no RTSP URI configured");
+                }
+                let method = if check == HealthCheckKind::RtspDescribe {
+                    "DESCRIBE"
+                } else {
+                    "OPTIONS"
+                };
+
+                let response = rtsp_request(uri, method, username, password).await?;
+                if response.status != 200 {
+                    bail!("RTSP {} returned {} {}", method, response.status, response.reason);
+                }
+                if check == HealthCheckKind::RtspDescribe && !sdp_has_video(&response.body) {
+                    bail!("SDP has no video track");
+                }
+            }
+            HealthCheckKind::OnvifDateTime => {
+                let url = match &checks.onvif_url {
+                    Some(url) => url.clone(),
+                    None => onvif_device_service_url(&device.primary_uri)?,
+                };
+
+                // GetSystemDateAndTime is callable without credentials
+                let body = reqwest::Client::new()
+                    .post(&url)
+                    .header("Content-Type", "application/soap+xml; charset=utf-8")
+                    .body(GET_SYSTEM_DATE_AND_TIME)
+                    .send()
+                    .await
+                    .context("GetSystemDateAndTime request failed")?
+                    .error_for_status()
+                    .context("GetSystemDateAndTime rejected")?
+                    .text()
+                    .await
+                    .context("failed to read response")?;
+                parse_utc_date_time(&body).context("response has no UTCDateTime")?;
+            }
+            HealthCheckKind::HttpSnapshot => {
+                let url = match (&checks.snapshot_url, &device.protocol) {
+                    (Some(url), _) => url.as_str(),
+                    (None, ConnectionProtocol::Http) => device.primary_uri.as_str(),
+                    _ => bail!("no snapshot URL configured"),
+                };
+
+                let mut request = reqwest::Client::new().get(url);
+                if let Some(user) = username {
+                    request = request.basic_auth(user, password);
+                }
+                let response = request
+                    .send()
+                    .await
+                    .context("snapshot request failed")?
+                    .error_for_status()
+                    .context("snapshot rejected")?;
+
+                let content_type = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("")
+                    .to_ascii_lowercase();
+                if !content_type.starts_with("image/") {
+                    bail!("snapshot is not an image (content type {:?})", content_type);
+                }
+                let image = response.bytes().await.context("failed to read snapshot")?;
+                if image.is_empty() {
+                    bail!("snapshot is empty");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ONVIF device service on the device's host; RTSP URIs map to HTTP on
+/// the default port
+fn onvif_device_service_url(uri: &str) -> Result<String> {
+    if uri.contains("/onvif/device_service") {
+        return Ok(uri.to_string());
+    }
+    let url = reqwest::Url::parse(uri).context("invalid device URI")?;
+    match url.scheme() {
+        "http" | "https" => Ok(format!("{}/onvif/device_service", uri.trim_end_matches('/'))),
+        _ => {
+            let host = url.host_str().context("device URI has no host")?;
+            Ok(format!("http://{}/onvif/device_service", host))
+        }
+    }
+}
+
+/// Status line, headers and body of an RTSP response
+#[derive(Debug, Default)]
+struct RtspResponse {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl RtspResponse {
+    fn headers_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn header<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Send an RTSP request over a fresh connection, answering one Basic or
+/// Digest challenge. Credentials in the URI are used when none are given.
+async fn rtsp_request(
+    uri: &str,
+    method: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> Result<RtspResponse> {
+    let mut url = reqwest::Url::parse(uri).context("invalid RTSP URI")?;
+    let host = url
+        .host_str()
+        .context("RTSP URI has no host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port().unwrap_or(DEFAULT_RTSP_PORT);
+    let username = username
+        .map(str::to_string)
+        .or_else(|| Some(url.username().to_string()).filter(|u| !u.is_empty()));
+    let password = password
+        .map(str::to_string)
+        .or_else(|| url.password().map(str::to_string));
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    let request_uri = url.to_string();
+
+    let stream = TcpStream::connect((host.as_str(), port))
+        .await
+        .with_context(|| format!("failed to connect to {}:{}", host, port))?;
+    let mut stream = BufReader::new(stream);
+
+    let response = send_rtsp_request(&mut stream, method, &request_uri, 1, None).await?;
+    if response.status != 401 {
+        return Ok(response);
+    }
+    let (Some(user), Some(pass)) = (username.as_deref(), password.as_deref()) else {
+        return Ok(response);
+    };
+    let challenges: Vec<AuthChallenge> = response
+        .headers_named("WWW-Authenticate")
+        .filter_map(AuthChallenge::parse)
+        .collect();
+    let Some(challenge) = challenges
+        .iter()
+        .find(|c| matches!(c, AuthChallenge::Digest { .. }))
+        .or(challenges.first())
+    else {
+        return Ok(response);
+    };
+
+    let cnonce = uuid::Uuid::new_v4().simple().to_string();
+    let authorization = challenge.authorization(method, &request_uri, user, pass, &cnonce);
+    send_rtsp_request(&mut stream, method, &request_uri, 2, Some(&authorization)).await
+}
+
+async fn send_rtsp_request(
+    stream: &mut BufReader<TcpStream>,
+    method: &str,
+    uri: &str,
+    cseq: u32,
+    authorization: Option<&str>,
+) -> Result<RtspResponse> {
+    let mut request = format!(
+        "{} {} RTSP/1.0\r\nCSeq: {}\r\nUser-Agent: quadrant-vms\r\n",
+        method, uri, cseq
+    );
+    if method == "DESCRIBE" {
+        request.push_str("Accept: application/sdp\r\n");
+    }
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Authorization: {}\r\n", authorization));
+    }
+    request.push_str("\r\n");
+    stream
+        .get_mut()
+        .write_all(request.as_bytes())
+        .await
+        .context("failed to send RTSP request")?;
+
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if stream
+            .read_line(&mut line)
+            .await
+            .context("failed to read RTSP response")?
+            == 0
+        {
+            bail!("RTSP server closed the connection");
+        }
+        if line.trim().is_empty() {
+            if head.is_empty() {
+                continue;
+            }
+            break;
+        }
+        head.push_str(&line);
+        if head.len() > MAX_RTSP_HEAD_BYTES {
+            bail!("RTSP response headers too large");
+        }
+    }
+
+    let mut response = parse_rtsp_head(&head).context("malformed RTSP response")?;
+    let length = response
+        .header("Content-Length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_RTSP_BODY_BYTES {
+        bail!("RTSP response body too large ({} bytes)", length);
+    }
+    let mut body = vec![0u8; length];
+    stream
+        .read_exact(&mut body)
+        .await
+        .context("failed to read RTSP response body")?;
+    response.body = String::from_utf8_lossy(&body).into_owned();
+
+    Ok(response)
+}
+
+/// Parse the status line and headers of an RTSP response
+fn parse_rtsp_head(head: &str) -> Option<RtspResponse> {
+    let mut lines = head.lines();
+    let mut status_line = lines.next()?.splitn(3, ' ');
+    if !status_line.next()?.starts_with("RTSP/") {
+        return None;
+    }
+    let status = status_line.next()?.parse().ok()?;
+    let reason = status_line.next().unwrap_or("").trim().to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    Some(RtspResponse {
+        status,
+        reason,
+        headers,
+        body: String::new(),
+    })
+}
+
+fn sdp_has_video(sdp: &str) -> bool {
+    sdp.lines().any(|line| line.trim_start().starts_with("m=video"))
+}
+
+/// `WWW-Authenticate` challenge from an RTSP server
+#[derive(Debug, PartialEq)]
+enum AuthChallenge {
+    Basic,
+    Digest {
+        realm: String,
+        nonce: String,
+        qop: Option<String>,
+        opaque: Option<String>,
+    },
+}
+
+impl AuthChallenge {
+    fn parse(header: &str) -> Option<Self> {
+        let header = header.trim();
+        let (scheme, params) = header.split_once(' ').unwrap_or((header, ""));
+        if scheme.eq_ignore_ascii_case("basic") {
+            return Some(AuthChallenge::Basic);
+        }
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let mut params = parse_auth_params(params);
+        Some(AuthChallenge::Digest {
+            realm: params.remove("realm")?,
+            nonce: params.remove("nonce")?,
+            qop: params.remove("qop"),
+            opaque: params.remove("opaque"),
+        })
+    }
+
+    /// `Authorization` header answering the challenge. Digest uses
+    /// qop=auth when the server offers it (RFC 2617) and the RFC 2069 form
+    /// most cameras expect otherwise.
+    fn authorization(&self, method: &str, uri: &str, username: &str, password: &str, cnonce: &str) -> String {
+        use base64::{engine::general_purpose, Engine as _};
+
+        match self {
+            AuthChallenge::Basic => format!(
+                "Basic {}",
+                general_purpose::STANDARD.encode(format!("{}:{}", username, password))
+            ),
+            AuthChallenge::Digest {
+                realm,
+                nonce,
+                qop,
+                opaque,
+            } => {
+                let ha1 = md5_hex(&format!("{}:{}:{}", username, realm, password));
+                let ha2 = md5_hex(&format!("{}:{}", method, uri));
+                let offers_auth = qop
+                    .as_deref()
+                    .is_some_and(|q| q.split(',').any(|v| v.trim() == "auth"));
+
+                let mut header = if offers_auth {
+                    let response = md5_hex(&format!("{}:{}:00000001:{}:auth:{}", ha1, nonce, cnonce, ha2));
+                    format!(
+                        r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", qop=auth, nc=00000001, cnonce="{}", response="{}""#,
+                        username, realm, nonce, uri, cnonce, response
+                    )
+                } else {
+                    let response = md5_hex(&format!("{}:{}:{}", ha1, nonce, ha2));
+                    format!(
+                        r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", response="{}""#,
+                        username, realm, nonce, uri, response
+                    )
+                };
+                if let Some(opaque) = opaque {
+                    header.push_str(&format!(r#", opaque="{}""#, opaque));
+                }
+                header
+            }
+        }
+    }
+}
+
+/// Parse `key=value` and `key="quoted, value"` pairs of an auth header
+fn parse_auth_params(params: &str) -> HashMap<String, String> {
+    let mut parsed = HashMap::new();
+    let mut rest = params.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let after = after.trim_start();
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match after.find(',') {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            },
+        };
+        parsed.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+    parsed
+}
+
+fn md5_hex(input: &str) -> String {
+    format!("{:x}", md5::compute(input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rtsp_head() {
+        let head = "RTSP/1.0 401 Unauthorized\r\nCSeq: 1\r\n\
+                    WWW-Authenticate: Basic realm=\"cam\"\r\n\
+                    WWW-Authenticate: Digest realm=\"cam\", nonce=\"4d2f\", stale=FALSE\r\n";
+        let response = parse_rtsp_head(head).unwrap();
+        assert_eq!(response.status, 401);
+        assert_eq!(response.reason, "Unauthorized");
+        assert_eq!(response.header("cseq"), Some("1"));
+
+        let challenges: Vec<_> = response
+            .headers_named("WWW-Authenticate")
+            .filter_map(AuthChallenge::parse)
+            .collect();
+        assert_eq!(challenges[0], AuthChallenge::Basic);
+        assert_eq!(
+            challenges[1],
+            AuthChallenge::Digest {
+                realm: "cam".into(),
+                nonce: "4d2f".into(),
+                qop: None,
+                opaque: None,
+            }
+        );
+
+        assert!(parse_rtsp_head("HTTP/1.1 200 OK\r\n").is_none());
+        assert!(sdp_has_video("v=0\r\nm=audio 0 RTP/AVP 0\r\nm=video 0 RTP/AVP 96\r\n"));
+        assert!(!sdp_has_video("v=0\r\nm=audio 0 RTP/AVP 0\r\n"));
+    }
+
+    #[test]
+    fn test_digest_authorization() {
+        // RFC 2617 section 3.5 example
+        let challenge = AuthChallenge::parse(
+            r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        )
+        .unwrap();
+        let header = challenge.authorization("GET", "/dir/index.html", "Mufasa", "Circle Of Life", "0a4f113b");
+        assert!(header.contains(r#"response="6629fae49393a05397450978507c4ef1""#));
+        assert!(header.ends_with(r#"opaque="5ccc069c403ebaf9f0171e9517f40e41""#));
+
+        assert_eq!(
+            AuthChallenge::Basic.authorization("DESCRIBE", "rtsp://cam/", "admin", "secret", ""),
+            "Basic YWRtaW46c2VjcmV0"
+        );
+    }
+
+    #[test]
+    fn test_onvif_device_service_url() {
+        assert_eq!(
+            onvif_device_service_url("rtsp://admin:pw@10.0.0.5:554/stream1").unwrap(),
+            "http://10.0.0.5/onvif/device_service"
+        );
+        assert_eq!(
+            onvif_device_service_url("http://10.0.0.5:8080").unwrap(),
+            "http://10.0.0.5:8080/onvif/device_service"
+        );
+    }
 }
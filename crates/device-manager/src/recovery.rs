@@ -0,0 +1,83 @@
+//! Recovery hook: when the health monitor sees a device come back online
+//! (offline or error → online), the coordinator is asked to restart the
+//! streams and recordings that failed while the device was unreachable
+//! (`POST /v1/reconcile/devices/:device_id/recovered`), instead of waiting
+//! for an operator.
+
+use anyhow::{Context, Result};
+use reqwest::{StatusCode, Url};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+pub struct RecoveryHook {
+    client: reqwest::Client,
+    coordinator_url: Url,
+}
+
+impl RecoveryHook {
+    pub fn new(coordinator_url: Url) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("failed to build recovery hook HTTP client")?;
+        Ok(Self {
+            client,
+            coordinator_url,
+        })
+    }
+
+    /// `None` unless `DEVICE_RECOVERY_HOOK_URL` (the coordinator) is set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(url) = std::env::var("DEVICE_RECOVERY_HOOK_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let url = Url::parse(&url).context("invalid DEVICE_RECOVERY_HOOK_URL")?;
+        Self::new(url).map(Some)
+    }
+
+    /// Tell the coordinator in the background; retried a few times while it
+    /// is unreachable
+    pub fn device_recovered(self: &Arc<Self>, device_id: &str) {
+        let hook = Arc::clone(self);
+        let device_id = device_id.to_string();
+        tokio::spawn(async move {
+            for attempt in 1..=ATTEMPTS {
+                match hook.notify(&device_id).await {
+                    Ok(()) => {
+                        info!(device_id = %device_id, "requested restart of failed streams and recordings");
+                        return;
+                    }
+                    Err(e) if attempt < ATTEMPTS => {
+                        warn!(device_id = %device_id, attempt, error = %e, "recovery hook failed, retrying");
+                        tokio::time::sleep(RETRY_DELAY * attempt).await;
+                    }
+                    Err(e) => {
+                        warn!(device_id = %device_id, error = %e, "recovery hook failed, giving up");
+                    }
+                }
+            }
+        });
+    }
+
+    async fn notify(&self, device_id: &str) -> Result<()> {
+        let url = self.coordinator_url.join(&format!(
+            "v1/reconcile/devices/{}/recovered",
+            device_id
+        ))?;
+        let response = self.client.post(url).send().await?;
+        // Without reconciliation the coordinator has no such route
+        if response.status() == StatusCode::NOT_FOUND {
+            anyhow::bail!("coordinator does not reconcile (RECONCILE_ENABLED)");
+        }
+        response.error_for_status()?;
+        Ok(())
+    }
+}
@@ -1,41 +1,52 @@
 // Simplified routes with JWT authentication
+use crate::duplicates::{self, normalize_identifiers, normalize_mac, DeviceIdentity};
 use crate::imaging_client::create_imaging_client;
+use crate::io_client::create_io_client;
+use crate::maintenance;
 use crate::ptz_client::create_ptz_client;
 use crate::state::DeviceManagerState;
 use crate::types::*;
 use axum::{
-    extract::{Path, Query, State},
+    extract::{DefaultBodyLimit, Path, Query, State},
     http::StatusCode,
+    middleware,
     response::IntoResponse,
     routing::{delete, get, post, put},
     Json, Router,
 };
 use chrono::Utc;
-use common::auth_middleware::RequireAuth;
+use common::auth_middleware::{auth_middleware, AuthMiddlewareConfig, RequireAuth};
+use common::idempotency::{idempotency_middleware, Idempotency};
+use common::openapi::{openapi_routes, OpenApiSpec};
+use common::tenancy::{
+    resource_tenancy_middleware, tenancy_middleware, ResourceTenantResolver, Tenant,
+};
+use common::validated::ValidatedJson;
 use serde_json::json;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{error, info};
 
 pub fn router(state: DeviceManagerState) -> Router {
-    Router::new()
-        .route("/health", get(health))
-        .route("/readyz", get(readyz))
-        .route("/metrics", get(metrics))
-        .route("/v1/devices", post(create_device))
-        .route("/v1/devices", get(list_devices))
+    let idempotent = middleware::from_fn_with_state(Idempotency::in_memory(), idempotency_middleware);
+    let device_owner: Arc<dyn ResourceTenantResolver> = state.store.clone();
+
+    // Routes addressing a single device: 404 unless it belongs to the caller's tenant
+    let device_routes = Router::new()
         .route("/v1/devices/:device_id", get(get_device))
         .route("/v1/devices/:device_id", put(update_device))
         .route("/v1/devices/:device_id", delete(delete_device))
         .route("/v1/devices/:device_id/probe", post(probe_device))
         .route("/v1/devices/:device_id/health", get(get_device_health))
         .route("/v1/devices/:device_id/health/history", get(get_health_history))
-        .route("/v1/devices/batch", put(batch_update_devices))
-        // Discovery routes
-        .route("/v1/discovery/scan", post(start_discovery_scan))
-        .route("/v1/discovery/scans", get(list_discovery_scans))
-        .route("/v1/discovery/scans/:scan_id", get(get_discovery_scan))
-        .route("/v1/discovery/scans/:scan_id/devices", get(get_discovered_devices))
-        .route("/v1/discovery/scans/:scan_id/cancel", post(cancel_discovery_scan))
+        .route(
+            "/v1/devices/:device_id/health/checks",
+            get(get_health_checks).put(set_health_checks),
+        )
+        .route("/v1/devices/:device_id/clock-drift", get(get_clock_drift_history))
+        .route("/v1/devices/:device_id/maintenance", get(get_maintenance_runs))
+        .route("/v1/devices/:device_id/duplicates", get(get_device_duplicates))
+        .route("/v1/devices/:device_id/merge", post(merge_devices))
         // PTZ Control routes
         .route("/v1/devices/:device_id/ptz/move", post(ptz_move))
         .route("/v1/devices/:device_id/ptz/stop", post(ptz_stop))
@@ -68,21 +79,150 @@ pub fn router(state: DeviceManagerState) -> Router {
         .route("/v1/devices/:device_id/configuration", get(get_current_configuration))
         .route("/v1/devices/:device_id/configuration/history", get(get_configuration_history))
         .route("/v1/devices/:device_id/configuration/:config_id", get(get_configuration_by_id))
+        // Relay output routes (ONVIF DeviceIO)
+        .route("/v1/devices/:device_id/io/relays", get(list_relay_outputs))
+        .route("/v1/devices/:device_id/io/relays/:token", post(set_relay_output))
+        .route("/v1/devices/:device_id/firmware/update", post(crate::firmware_routes::initiate_firmware_update))
+        .route("/v1/devices/:device_id/firmware/updates", get(crate::firmware_routes::list_device_firmware_updates))
+        .route_layer(middleware::from_fn_with_state(
+            device_owner,
+            resource_tenancy_middleware,
+        ));
+
+    let api_routes = Router::new()
+        .route("/v1/devices", post(create_device).layer(idempotent.clone()))
+        .route("/v1/devices", get(list_devices))
+        .route("/v1/devices/batch", put(batch_update_devices))
+        .route("/v1/clock-drift", get(list_clock_drift))
+        .route("/v1/maintenance/tasks", post(create_maintenance_task))
+        .route("/v1/maintenance/tasks", get(list_maintenance_tasks))
+        .route("/v1/maintenance/tasks/:task_id", get(get_maintenance_task))
+        .route("/v1/maintenance/tasks/:task_id", put(update_maintenance_task))
+        .route("/v1/maintenance/tasks/:task_id", delete(delete_maintenance_task))
+        // Discovery routes
+        .route("/v1/discovery/scan", post(start_discovery_scan).layer(idempotent))
+        .route("/v1/discovery/scans", get(list_discovery_scans))
+        .route("/v1/discovery/scans/:scan_id", get(get_discovery_scan))
+        .route("/v1/discovery/scans/:scan_id/devices", get(get_discovered_devices))
+        .route("/v1/discovery/scans/:scan_id/cancel", post(cancel_discovery_scan))
         // Firmware Management routes
         .route("/v1/firmware/files", post(crate::firmware_routes::upload_firmware_file))
         .route("/v1/firmware/files", get(crate::firmware_routes::list_firmware_files))
         .route("/v1/firmware/files/:file_id", get(crate::firmware_routes::get_firmware_file))
         .route("/v1/firmware/files/:file_id/verify", post(crate::firmware_routes::verify_firmware_file))
         .route("/v1/firmware/files/:file_id", delete(crate::firmware_routes::delete_firmware_file))
+        .route("/v1/firmware/uploads", post(crate::firmware_routes::create_firmware_upload))
+        .route("/v1/firmware/uploads/:upload_id", get(crate::firmware_routes::get_firmware_upload))
+        .route("/v1/firmware/uploads/:upload_id", delete(crate::firmware_routes::abort_firmware_upload))
+        .route(
+            "/v1/firmware/uploads/:upload_id/chunks",
+            put(crate::firmware_routes::append_firmware_chunk)
+                .layer(DefaultBodyLimit::max(crate::firmware_storage::MAX_CHUNK_SIZE)),
+        )
+        .route("/v1/firmware/uploads/:upload_id/complete", post(crate::firmware_routes::complete_firmware_upload))
         .route("/v1/firmware/updates", get(crate::firmware_routes::list_firmware_updates))
         .route("/v1/firmware/updates/:update_id", get(crate::firmware_routes::get_firmware_update))
         .route("/v1/firmware/updates/:update_id/history", get(crate::firmware_routes::get_firmware_update_history))
         .route("/v1/firmware/updates/:update_id/cancel", post(crate::firmware_routes::cancel_firmware_update))
-        .route("/v1/devices/:device_id/firmware/update", post(crate::firmware_routes::initiate_firmware_update))
-        .route("/v1/devices/:device_id/firmware/updates", get(crate::firmware_routes::list_device_firmware_updates))
+        .merge(device_routes)
+        .layer(middleware::from_fn(tenancy_middleware))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(AuthMiddlewareConfig::from_env()),
+            auth_middleware,
+        ));
+
+    Router::new()
+        .route("/health", get(health))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .merge(api_routes)
+        .merge(openapi_routes(&openapi()))
         .with_state(state)
 }
 
+/// OpenAPI description of the device-manager API
+pub fn openapi() -> OpenApiSpec {
+    OpenApiSpec::new("device-manager", env!("CARGO_PKG_VERSION"))
+        .with_description("Camera and device management, PTZ, discovery and firmware")
+        .operations(&[
+            ("GET", "/health", "health", "Liveness probe"),
+            ("GET", "/readyz", "health", "Readiness probe"),
+            ("GET", "/metrics", "health", "Prometheus metrics"),
+            ("POST", "/v1/devices", "devices", "Create device"),
+            ("GET", "/v1/devices", "devices", "List devices"),
+            ("GET", "/v1/devices/:device_id", "devices", "Get device"),
+            ("PUT", "/v1/devices/:device_id", "devices", "Update device"),
+            ("DELETE", "/v1/devices/:device_id", "devices", "Delete device"),
+            ("POST", "/v1/devices/:device_id/probe", "devices", "Probe device"),
+            ("GET", "/v1/devices/:device_id/health", "devices", "Get device health"),
+            ("GET", "/v1/devices/:device_id/health/history", "devices", "Get health history"),
+            ("GET", "/v1/devices/:device_id/health/checks", "devices", "Get selected health checks"),
+            ("PUT", "/v1/devices/:device_id/health/checks", "devices", "Select health checks"),
+            ("GET", "/v1/devices/:device_id/clock-drift", "devices", "Get clock drift history"),
+            ("GET", "/v1/devices/:device_id/duplicates", "devices", "List devices duplicating a device"),
+            ("POST", "/v1/devices/:device_id/merge", "devices", "Merge a duplicate into a device"),
+            ("GET", "/v1/clock-drift", "devices", "List latest clock drift per device"),
+            ("GET", "/v1/devices/:device_id/maintenance", "maintenance", "Get maintenance history"),
+            ("POST", "/v1/maintenance/tasks", "maintenance", "Create maintenance task"),
+            ("GET", "/v1/maintenance/tasks", "maintenance", "List maintenance tasks"),
+            ("GET", "/v1/maintenance/tasks/:task_id", "maintenance", "Get maintenance task"),
+            ("PUT", "/v1/maintenance/tasks/:task_id", "maintenance", "Update maintenance task"),
+            ("DELETE", "/v1/maintenance/tasks/:task_id", "maintenance", "Delete maintenance task"),
+            ("PUT", "/v1/devices/batch", "devices", "Batch update devices"),
+            ("POST", "/v1/discovery/scan", "discovery", "Start discovery scan"),
+            ("GET", "/v1/discovery/scans", "discovery", "List discovery scans"),
+            ("GET", "/v1/discovery/scans/:scan_id", "discovery", "Get discovery scan"),
+            ("GET", "/v1/discovery/scans/:scan_id/devices", "discovery", "Get discovered devices"),
+            ("POST", "/v1/discovery/scans/:scan_id/cancel", "discovery", "Cancel discovery scan"),
+            ("POST", "/v1/devices/:device_id/ptz/move", "ptz", "PTZ move"),
+            ("POST", "/v1/devices/:device_id/ptz/stop", "ptz", "PTZ stop"),
+            ("POST", "/v1/devices/:device_id/ptz/zoom", "ptz", "PTZ zoom"),
+            ("POST", "/v1/devices/:device_id/ptz/absolute", "ptz", "PTZ goto absolute"),
+            ("POST", "/v1/devices/:device_id/ptz/home", "ptz", "PTZ goto home"),
+            ("GET", "/v1/devices/:device_id/ptz/status", "ptz", "PTZ get status"),
+            ("GET", "/v1/devices/:device_id/ptz/capabilities", "ptz", "PTZ get capabilities"),
+            ("POST", "/v1/devices/:device_id/ptz/presets", "ptz", "Create PTZ preset"),
+            ("GET", "/v1/devices/:device_id/ptz/presets", "ptz", "List PTZ presets"),
+            ("GET", "/v1/devices/:device_id/ptz/presets/:preset_id", "ptz", "Get PTZ preset"),
+            ("PUT", "/v1/devices/:device_id/ptz/presets/:preset_id", "ptz", "Update PTZ preset"),
+            ("DELETE", "/v1/devices/:device_id/ptz/presets/:preset_id", "ptz", "Delete PTZ preset"),
+            ("POST", "/v1/devices/:device_id/ptz/presets/:preset_id/goto", "ptz", "Goto PTZ preset"),
+            ("POST", "/v1/devices/:device_id/ptz/tours", "ptz", "Create PTZ tour"),
+            ("GET", "/v1/devices/:device_id/ptz/tours", "ptz", "List PTZ tours"),
+            ("GET", "/v1/devices/:device_id/ptz/tours/:tour_id", "ptz", "Get PTZ tour"),
+            ("PUT", "/v1/devices/:device_id/ptz/tours/:tour_id", "ptz", "Update PTZ tour"),
+            ("DELETE", "/v1/devices/:device_id/ptz/tours/:tour_id", "ptz", "Delete PTZ tour"),
+            ("POST", "/v1/devices/:device_id/ptz/tours/:tour_id/steps", "ptz", "Add PTZ tour step"),
+            ("DELETE", "/v1/devices/:device_id/ptz/tours/:tour_id/steps/:step_id", "ptz", "Delete PTZ tour step"),
+            ("POST", "/v1/devices/:device_id/ptz/tours/:tour_id/start", "ptz", "Start PTZ tour"),
+            ("POST", "/v1/devices/:device_id/ptz/tours/:tour_id/stop", "ptz", "Stop PTZ tour"),
+            ("POST", "/v1/devices/:device_id/ptz/tours/:tour_id/pause", "ptz", "Pause PTZ tour"),
+            ("POST", "/v1/devices/:device_id/ptz/tours/:tour_id/resume", "ptz", "Resume PTZ tour"),
+            ("POST", "/v1/devices/:device_id/configuration", "configuration", "Configure camera"),
+            ("GET", "/v1/devices/:device_id/configuration", "configuration", "Get current configuration"),
+            ("GET", "/v1/devices/:device_id/configuration/history", "configuration", "Get configuration history"),
+            ("GET", "/v1/devices/:device_id/configuration/:config_id", "configuration", "Get configuration by ID"),
+            ("GET", "/v1/devices/:device_id/io/relays", "io", "List relay outputs"),
+            ("POST", "/v1/devices/:device_id/io/relays/:token", "io", "Set relay output state"),
+            ("POST", "/v1/firmware/files", "firmware", "Upload firmware file"),
+            ("GET", "/v1/firmware/files", "firmware", "List firmware files"),
+            ("GET", "/v1/firmware/files/:file_id", "firmware", "Get firmware file"),
+            ("POST", "/v1/firmware/files/:file_id/verify", "firmware", "Verify firmware file"),
+            ("DELETE", "/v1/firmware/files/:file_id", "firmware", "Delete firmware file"),
+            ("POST", "/v1/firmware/uploads", "firmware", "Open chunked firmware upload"),
+            ("GET", "/v1/firmware/uploads/:upload_id", "firmware", "Get firmware upload"),
+            ("DELETE", "/v1/firmware/uploads/:upload_id", "firmware", "Abort firmware upload"),
+            ("PUT", "/v1/firmware/uploads/:upload_id/chunks", "firmware", "Append firmware upload chunk"),
+            ("POST", "/v1/firmware/uploads/:upload_id/complete", "firmware", "Complete firmware upload"),
+            ("GET", "/v1/firmware/updates", "firmware", "List firmware updates"),
+            ("GET", "/v1/firmware/updates/:update_id", "firmware", "Get firmware update"),
+            ("GET", "/v1/firmware/updates/:update_id/history", "firmware", "Get firmware update history"),
+            ("POST", "/v1/firmware/updates/:update_id/cancel", "firmware", "Cancel firmware update"),
+            ("POST", "/v1/devices/:device_id/firmware/update", "firmware", "Initiate firmware update"),
+            ("GET", "/v1/devices/:device_id/firmware/updates", "firmware", "List device firmware updates"),
+        ])
+}
+
 async fn health() -> impl IntoResponse {
     Json(json!({"status": "ok"}))
 }
@@ -116,7 +256,7 @@ async fn metrics() -> impl IntoResponse {
 async fn create_device(
     State(state): State<DeviceManagerState>,
     RequireAuth(auth_ctx): RequireAuth,
-    Json(req): Json<CreateDeviceRequest>,
+    ValidatedJson(req): ValidatedJson<CreateDeviceRequest>,
 ) -> impl IntoResponse {
     // Check permission
     if !auth_ctx.has_permission("device:create") {
@@ -130,6 +270,19 @@ async fn create_device(
     // Extract tenant_id from auth context
     let tenant_id = &auth_ctx.tenant_id;
 
+    if let Some(response) = invalid_mac_address(req.mac_address.as_deref()) {
+        return response;
+    }
+    if !req.allow_duplicate {
+        let identity = DeviceIdentity::of_request(
+            std::iter::once(req.primary_uri.as_str()).chain(req.secondary_uri.as_deref()),
+            &normalize_identifiers(req.mac_address.as_deref(), req.serial_number.as_deref()),
+        );
+        if let Some(response) = duplicate_conflict(&state, tenant_id, &identity, None).await {
+            return response;
+        }
+    }
+
     match state.store.create_device(tenant_id, req).await {
         Ok(device) => {
             info!(
@@ -153,8 +306,10 @@ async fn create_device(
 
 async fn list_devices(
     State(state): State<DeviceManagerState>,
-    Query(query): Query<DeviceListQuery>,
+    tenant: Tenant,
+    Query(mut query): Query<DeviceListQuery>,
 ) -> impl IntoResponse {
+    query.tenant_id = tenant.filter(query.tenant_id.as_deref());
     match state.store.list_devices(query).await {
         Ok(devices) => (StatusCode::OK, Json(devices)).into_response(),
         Err(e) => {
@@ -193,8 +348,41 @@ async fn get_device(
 async fn update_device(
     State(state): State<DeviceManagerState>,
     Path(device_id): Path<String>,
-    Json(req): Json<UpdateDeviceRequest>,
+    ValidatedJson(req): ValidatedJson<UpdateDeviceRequest>,
 ) -> impl IntoResponse {
+    if let Some(response) = invalid_mac_address(req.mac_address.as_deref()) {
+        return response;
+    }
+    // Only the new URIs and identifiers are checked, so a device that
+    // already had a duplicate can still be renamed
+    let identity = DeviceIdentity::of_request(
+        req.primary_uri.iter().chain(req.secondary_uri.iter()).map(String::as_str),
+        &normalize_identifiers(req.mac_address.as_deref(), req.serial_number.as_deref()),
+    );
+    if !req.allow_duplicate && identity != DeviceIdentity::default() {
+        let tenant_id = match state.store.get_device_tenant(&device_id).await {
+            Ok(Some(tenant_id)) => tenant_id,
+            Ok(None) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({"error": "device not found"})),
+                )
+                    .into_response()
+            }
+            Err(e) => {
+                error!("failed to get device: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response();
+            }
+        };
+        if let Some(response) = duplicate_conflict(&state, &tenant_id, &identity, Some(&device_id)).await {
+            return response;
+        }
+    }
+
     match state.store.update_device(&device_id, req).await {
         Ok(device) => {
             info!(
@@ -235,6 +423,198 @@ async fn delete_device(
     }
 }
 
+/// 400 for a MAC address without 12 hex digits
+fn invalid_mac_address(mac_address: Option<&str>) -> Option<axum::response::Response> {
+    let mac_address = mac_address?;
+    if normalize_mac(mac_address).is_some() {
+        return None;
+    }
+    Some(
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "mac_address must have 12 hex digits"})),
+        )
+            .into_response(),
+    )
+}
+
+/// 409 listing the tenant's devices `identity` duplicates, if any
+async fn duplicate_conflict(
+    state: &DeviceManagerState,
+    tenant_id: &str,
+    identity: &DeviceIdentity,
+    exclude_device_id: Option<&str>,
+) -> Option<axum::response::Response> {
+    match state.store.find_duplicates(tenant_id, identity, exclude_device_id).await {
+        Ok(duplicates) if duplicates.is_empty() => None,
+        Ok(duplicates) => {
+            info!(
+                tenant_id = %tenant_id,
+                duplicates = duplicates.len(),
+                "rejected duplicate device"
+            );
+            Some(
+                (
+                    StatusCode::CONFLICT,
+                    Json(json!({
+                        "error": "device duplicates an existing device",
+                        "duplicates": duplicates,
+                    })),
+                )
+                    .into_response(),
+            )
+        }
+        Err(e) => {
+            error!("failed to check for duplicate devices: {}", e);
+            Some(
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response(),
+            )
+        }
+    }
+}
+
+async fn get_device_duplicates(
+    State(state): State<DeviceManagerState>,
+    Path(device_id): Path<String>,
+) -> impl IntoResponse {
+    let device = match state.store.get_device(&device_id).await {
+        Ok(Some(device)) => device,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "device not found"})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("failed to get device: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+
+    let result = match state.store.get_device_identifiers(&device_id).await {
+        Ok(identifiers) => {
+            let identifiers = identifiers.unwrap_or_default();
+            let identity = DeviceIdentity::of_request(
+                std::iter::once(device.primary_uri.as_str()).chain(device.secondary_uri.as_deref()),
+                &identifiers,
+            );
+            state
+                .store
+                .find_duplicates(&device.tenant_id, &identity, Some(&device_id))
+                .await
+                .map(|duplicates| (identifiers, duplicates))
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((identifiers, duplicates)) => (
+            StatusCode::OK,
+            Json(json!({
+                "device_id": device_id,
+                "mac_address": identifiers.mac_address,
+                "serial_number": identifiers.serial_number,
+                "duplicates": duplicates,
+            })),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("failed to find duplicate devices: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn merge_devices(
+    State(state): State<DeviceManagerState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(device_id): Path<String>,
+    Json(req): Json<MergeDevicesRequest>,
+) -> impl IntoResponse {
+    // The duplicate is deleted
+    if !auth_ctx.has_permission("device:delete") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+    if req.duplicate_device_id == device_id {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "a device cannot be merged into itself"})),
+        )
+            .into_response();
+    }
+
+    // Both devices must belong to the same tenant; a foreign duplicate is
+    // reported like a missing one
+    let owners = match (
+        state.store.get_device_tenant(&device_id).await,
+        state.store.get_device_tenant(&req.duplicate_device_id).await,
+    ) {
+        (Ok(owner), Ok(duplicate_owner)) => (owner, duplicate_owner),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("failed to get device: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+    match owners {
+        (Some(owner), Some(duplicate_owner)) if owner == duplicate_owner => {}
+        (None, _) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "device not found"})),
+            )
+                .into_response()
+        }
+        _ => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "duplicate device not found"})),
+            )
+                .into_response()
+        }
+    }
+
+    match state.store.merge_devices(&device_id, &req.duplicate_device_id).await {
+        Ok(result) => {
+            info!(
+                device_id = %device_id,
+                merged_device_id = %req.duplicate_device_id,
+                user_id = %auth_ctx.user_id,
+                "devices merged"
+            );
+            (StatusCode::OK, Json(result)).into_response()
+        }
+        Err(e) => {
+            error!("failed to merge devices: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
 async fn probe_device(
     State(state): State<DeviceManagerState>,
     Path(device_id): Path<String>,
@@ -336,14 +716,378 @@ async fn get_health_history(
     }
 }
 
+async fn get_health_checks(
+    State(state): State<DeviceManagerState>,
+    Path(device_id): Path<String>,
+) -> impl IntoResponse {
+    match state.store.get_health_checks(&device_id).await {
+        Ok(checks) => (StatusCode::OK, Json(checks.unwrap_or_default())).into_response(),
+        Err(e) => {
+            error!("failed to get health checks: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn set_health_checks(
+    State(state): State<DeviceManagerState>,
+    Path(device_id): Path<String>,
+    Json(mut checks): Json<DeviceHealthChecks>,
+) -> impl IntoResponse {
+    let mut selected = Vec::with_capacity(checks.checks.len());
+    for check in checks.checks.drain(..) {
+        if !selected.contains(&check) {
+            selected.push(check);
+        }
+    }
+    checks.checks = selected;
+
+    for (field, url, schemes) in [
+        ("rtsp_uri", &checks.rtsp_uri, &["rtsp"][..]),
+        ("onvif_url", &checks.onvif_url, &["http", "https"][..]),
+        ("snapshot_url", &checks.snapshot_url, &["http", "https"][..]),
+    ] {
+        let Some(url) = url else { continue };
+        let valid = reqwest::Url::parse(url)
+            .map(|u| schemes.contains(&u.scheme()) && u.has_host())
+            .unwrap_or(false);
+        if !valid {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("{} must be a {} URL", field, schemes.join("/"))})),
+            )
+                .into_response();
+        }
+    }
+
+    match state.store.set_health_checks(&device_id, &checks).await {
+        Ok(()) => {
+            info!(device_id = %device_id, checks = ?checks.checks, "health checks updated");
+            (StatusCode::OK, Json(checks)).into_response()
+        }
+        Err(e) => {
+            error!("failed to update health checks: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn get_clock_drift_history(
+    State(state): State<DeviceManagerState>,
+    Path(device_id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let limit = query
+        .get("limit")
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(100);
+
+    match state.store.get_clock_drift_history(&device_id, limit).await {
+        Ok(history) => (StatusCode::OK, Json(history)).into_response(),
+        Err(e) => {
+            error!("failed to get clock drift history: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn list_clock_drift(
+    State(state): State<DeviceManagerState>,
+    tenant: Tenant,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let tenant_id = tenant.filter(query.get("tenant_id").map(String::as_str));
+    let exceeded_only = query.get("exceeded").is_some_and(|v| v == "true");
+
+    match state
+        .store
+        .list_clock_drift(tenant_id.as_deref(), exceeded_only)
+        .await
+    {
+        Ok(drift) => (StatusCode::OK, Json(drift)).into_response(),
+        Err(e) => {
+            error!("failed to list clock drift: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn get_maintenance_runs(
+    State(state): State<DeviceManagerState>,
+    Path(device_id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let limit = query
+        .get("limit")
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(100);
+
+    match state.store.get_maintenance_runs(&device_id, limit).await {
+        Ok(runs) => (StatusCode::OK, Json(runs)).into_response(),
+        Err(e) => {
+            error!("failed to get maintenance runs: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Validate a maintenance task of `tenant_id` and compute its first run
+async fn check_maintenance_task(
+    state: &DeviceManagerState,
+    tenant_id: &str,
+    req: &MaintenanceTaskRequest,
+) -> Result<Option<chrono::DateTime<Utc>>, axum::response::Response> {
+    let bad_request = |error: String| {
+        (StatusCode::BAD_REQUEST, Json(json!({"error": error}))).into_response()
+    };
+    maintenance::validate(req).map_err(bad_request)?;
+
+    // A foreign device is reported like a missing one
+    if let Some(device_id) = &req.device_id {
+        match state.store.get_device_tenant(device_id).await {
+            Ok(Some(owner)) if owner == tenant_id => {}
+            Ok(_) => return Err(bad_request(format!("device {} not found", device_id))),
+            Err(e) => {
+                error!("failed to get device: {}", e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response());
+            }
+        }
+    }
+
+    maintenance::next_run(&req.schedule_cron, &req.time_zone, Utc::now()).map_err(bad_request)
+}
+
+/// A maintenance task of the caller's tenant; foreign tasks are not found
+async fn find_maintenance_task(
+    state: &DeviceManagerState,
+    tenant: &Tenant,
+    task_id: &str,
+) -> Result<MaintenanceTask, axum::response::Response> {
+    match state.store.get_maintenance_task(task_id).await {
+        Ok(Some(task)) if tenant.can_access(&task.tenant_id) => Ok(task),
+        Ok(_) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "maintenance task not found"})),
+        )
+            .into_response()),
+        Err(e) => {
+            error!("failed to get maintenance task: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response())
+        }
+    }
+}
+
+async fn create_maintenance_task(
+    State(state): State<DeviceManagerState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    ValidatedJson(req): ValidatedJson<MaintenanceTaskRequest>,
+) -> impl IntoResponse {
+    // Maintenance reboots devices
+    if !auth_ctx.has_permission("device:configure") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let tenant_id = &auth_ctx.tenant_id;
+    let next_run_at = match check_maintenance_task(&state, tenant_id, &req).await {
+        Ok(next_run_at) => next_run_at,
+        Err(response) => return response,
+    };
+
+    match state
+        .store
+        .create_maintenance_task(tenant_id, &req, next_run_at)
+        .await
+    {
+        Ok(task) => {
+            info!(
+                task_id = %task.task_id,
+                task_name = %task.name,
+                next_run_at = ?task.next_run_at,
+                "maintenance task created"
+            );
+            (StatusCode::CREATED, Json(task)).into_response()
+        }
+        Err(e) => {
+            error!("failed to create maintenance task: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn list_maintenance_tasks(
+    State(state): State<DeviceManagerState>,
+    tenant: Tenant,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let tenant_id = tenant.filter(query.get("tenant_id").map(String::as_str));
+
+    match state.store.list_maintenance_tasks(tenant_id.as_deref()).await {
+        Ok(tasks) => (StatusCode::OK, Json(tasks)).into_response(),
+        Err(e) => {
+            error!("failed to list maintenance tasks: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn get_maintenance_task(
+    State(state): State<DeviceManagerState>,
+    tenant: Tenant,
+    Path(task_id): Path<String>,
+) -> impl IntoResponse {
+    match find_maintenance_task(&state, &tenant, &task_id).await {
+        Ok(task) => (StatusCode::OK, Json(task)).into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn update_maintenance_task(
+    State(state): State<DeviceManagerState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    tenant: Tenant,
+    Path(task_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<MaintenanceTaskRequest>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("device:configure") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    let task = match find_maintenance_task(&state, &tenant, &task_id).await {
+        Ok(task) => task,
+        Err(response) => return response,
+    };
+    // The schedule restarts from now, so re-enabling a task does not run
+    // the occurrences it missed
+    let next_run_at = match check_maintenance_task(&state, &task.tenant_id, &req).await {
+        Ok(next_run_at) => next_run_at,
+        Err(response) => return response,
+    };
+
+    match state
+        .store
+        .update_maintenance_task(&task_id, &req, next_run_at)
+        .await
+    {
+        Ok(Some(task)) => (StatusCode::OK, Json(task)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "maintenance task not found"})),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("failed to update maintenance task: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn delete_maintenance_task(
+    State(state): State<DeviceManagerState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    tenant: Tenant,
+    Path(task_id): Path<String>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("device:configure") {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "permission denied"})),
+        )
+            .into_response();
+    }
+
+    if let Err(response) = find_maintenance_task(&state, &tenant, &task_id).await {
+        return response;
+    }
+
+    // Runs in progress finish; their history stays with the device
+    match state.store.delete_maintenance_task(&task_id).await {
+        Ok(true) => (StatusCode::NO_CONTENT, Json(json!({}))).into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "maintenance task not found"})),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("failed to delete maintenance task: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    }
+}
+
 async fn batch_update_devices(
     State(state): State<DeviceManagerState>,
+    tenant: Tenant,
     Json(req): Json<BatchUpdateRequest>,
 ) -> impl IntoResponse {
     let mut succeeded = Vec::new();
     let mut failed = HashMap::new();
 
     for device_id in req.device_ids {
+        // Foreign devices are reported exactly like missing ones
+        match state.store.get_device_tenant(&device_id).await {
+            Ok(Some(owner)) if tenant.can_access(&owner) => {}
+            Ok(_) => {
+                failed.insert(device_id, "device not found".to_string());
+                continue;
+            }
+            Err(e) => {
+                failed.insert(device_id, e.to_string());
+                continue;
+            }
+        }
+
         match state.store.update_device(&device_id, req.update.clone()).await {
             Ok(_) => succeeded.push(device_id),
             Err(e) => {
@@ -795,11 +1539,32 @@ async fn get_discovery_scan(
 
 async fn get_discovered_devices(
     State(state): State<DeviceManagerState>,
+    tenant: Tenant,
     Path(scan_id): Path<String>,
 ) -> impl IntoResponse {
-    match state.store.list_discovered_devices(&scan_id).await {
-        Ok(devices) => {
+    let discovered = match state.store.list_discovered_devices(&scan_id).await {
+        Ok(devices) => state
+            .store
+            .device_identities(&tenant.tenant_id)
+            .await
+            .map(|existing| (devices, existing)),
+        Err(e) => Err(e),
+    };
+
+    match discovered {
+        Ok((devices, existing)) => {
             info!(scan_id = %scan_id, count = devices.len(), "listed discovered devices");
+            // Each discovered device lists the caller's devices it already is
+            let devices: Vec<serde_json::Value> = devices
+                .into_iter()
+                .map(|device| {
+                    let duplicates =
+                        duplicates::find(&DeviceIdentity::of_discovered(&device), &existing, None);
+                    let mut value = json!(device);
+                    value["duplicates"] = json!(duplicates);
+                    value
+                })
+                .collect();
             (StatusCode::OK, Json(json!({"devices": devices}))).into_response()
         }
         Err(e) => {
@@ -1031,3 +1796,122 @@ async fn get_configuration_by_id(
         }
     }
 }
+
+// Relay Output Handlers
+
+/// Longest pulse device-manager times itself
+const MAX_RELAY_PULSE_SECS: u32 = 3600;
+
+async fn get_device_and_create_io_client(
+    state: &DeviceManagerState,
+    device_id: &str,
+) -> Result<Arc<dyn crate::io_client::IoClient>, axum::response::Response> {
+    let device = match state.store.get_device(device_id).await {
+        Ok(Some(device)) => device,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, Json(json!({"error": "device not found"}))).into_response()),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response()),
+    };
+
+    let username = device.username.clone();
+    let password = device.password_encrypted.as_ref().and_then(|enc| state.store.decrypt_password(enc).ok());
+
+    create_io_client(&device.protocol, &device.primary_uri, username, password)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response())
+}
+
+/// List the relay outputs of a device
+async fn list_relay_outputs(
+    State(state): State<DeviceManagerState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path(device_id): Path<String>,
+) -> impl IntoResponse {
+    if !auth_ctx.has_permission("device:read") {
+        return (StatusCode::FORBIDDEN, Json(json!({"error": "permission denied"}))).into_response();
+    }
+
+    let io_client = match get_device_and_create_io_client(&state, &device_id).await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+
+    match io_client.list_relay_outputs().await {
+        Ok(outputs) => (StatusCode::OK, Json(outputs)).into_response(),
+        Err(e) => {
+            error!(device_id = %device_id, error = %e, "failed to list relay outputs");
+            (StatusCode::BAD_GATEWAY, Json(json!({"error": e.to_string()}))).into_response()
+        }
+    }
+}
+
+/// Set a relay output active or inactive, optionally only for a pulse
+async fn set_relay_output(
+    State(state): State<DeviceManagerState>,
+    RequireAuth(auth_ctx): RequireAuth,
+    Path((device_id, token)): Path<(String, String)>,
+    Json(req): Json<SetRelayOutputRequest>,
+) -> impl IntoResponse {
+    // Outputs drive sirens and door strikes, so they have their own permission
+    if !auth_ctx.has_permission("device:output") {
+        return (StatusCode::FORBIDDEN, Json(json!({"error": "permission denied"}))).into_response();
+    }
+    let pulse_secs = match req.duration_secs {
+        Some(_) if req.state == RelayState::Inactive => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "duration_secs only applies to state active"})),
+            )
+                .into_response();
+        }
+        Some(secs) if secs == 0 || secs > MAX_RELAY_PULSE_SECS => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("duration_secs must be between 1 and {}", MAX_RELAY_PULSE_SECS)})),
+            )
+                .into_response();
+        }
+        other => other,
+    };
+
+    let io_client = match get_device_and_create_io_client(&state, &device_id).await {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+
+    info!(
+        device_id = %device_id,
+        token = %token,
+        state = req.state.as_onvif(),
+        duration_secs = ?pulse_secs,
+        user = %auth_ctx.username,
+        "setting relay output"
+    );
+
+    if let Err(e) = io_client.set_relay_output_state(&token, req.state).await {
+        error!(device_id = %device_id, token = %token, error = %e, "failed to set relay output");
+        return (StatusCode::BAD_GATEWAY, Json(json!({"error": e.to_string()}))).into_response();
+    }
+
+    let resets_at = pulse_secs.map(|secs| {
+        let device_id = device_id.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(secs as u64)).await;
+            match io_client.set_relay_output_state(&token, RelayState::Inactive).await {
+                Ok(()) => info!(device_id = %device_id, token = %token, "relay output pulse ended"),
+                Err(e) => error!(device_id = %device_id, token = %token, error = %e, "failed to end relay output pulse"),
+            }
+        });
+        Utc::now() + chrono::Duration::seconds(secs as i64)
+    });
+
+    (
+        StatusCode::OK,
+        Json(SetRelayOutputResponse {
+            device_id,
+            token,
+            state: req.state,
+            resets_at,
+        }),
+    )
+        .into_response()
+}
@@ -1,7 +1,11 @@
+use crate::duplicates::{self, normalize_identifiers, DeviceIdentity};
 use crate::types::*;
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use common::tenancy::ResourceTenantResolver;
+use common::tenant_rls;
 use sqlx::PgPool;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -11,7 +15,8 @@ pub struct DeviceStore {
 
 impl DeviceStore {
     pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = PgPool::connect(database_url)
+        let pool = tenant_rls::pool_options()
+            .connect(database_url)
             .await
             .context("failed to connect to database")?;
 
@@ -43,6 +48,7 @@ impl DeviceStore {
 
         // Encrypt password if provided (simple placeholder - should use proper encryption)
         let password_encrypted = req.password.map(|p| self.encrypt_password(&p));
+        let identifiers = normalize_identifiers(req.mac_address.as_deref(), req.serial_number.as_deref());
 
         let device = sqlx::query_as!(
             Device,
@@ -102,6 +108,10 @@ impl DeviceStore {
         .await
         .context("failed to create device")?;
 
+        if identifiers != DeviceIdentifiers::default() {
+            self.set_device_identifiers(&device_id, &identifiers).await?;
+        }
+
         // Log creation event
         self.log_event(&device_id, "created", None, None, None).await?;
 
@@ -140,6 +150,15 @@ impl DeviceStore {
         Ok(device)
     }
 
+    /// Get the owning tenant of a device, `None` if it does not exist
+    pub async fn get_device_tenant(&self, device_id: &str) -> Result<Option<String>> {
+        sqlx::query_scalar::<_, String>("SELECT tenant_id FROM devices WHERE device_id = $1")
+            .bind(device_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("failed to fetch device tenant")
+    }
+
     /// List devices with optional filters
     pub async fn list_devices(&self, query: DeviceListQuery) -> Result<Vec<Device>> {
         let mut sql = String::from(
@@ -227,6 +246,7 @@ impl DeviceStore {
             .context("device not found")?;
 
         let password_encrypted = req.password.map(|p| self.encrypt_password(&p));
+        let identifiers = normalize_identifiers(req.mac_address.as_deref(), req.serial_number.as_deref());
 
         let device = sqlx::query_as!(
             Device,
@@ -295,6 +315,10 @@ impl DeviceStore {
         .await
         .context("failed to update device")?;
 
+        if identifiers != DeviceIdentifiers::default() {
+            self.set_device_identifiers(device_id, &identifiers).await?;
+        }
+
         // Log update event
         self.log_event(device_id, "updated", None, None, None).await?;
 
@@ -318,6 +342,7 @@ impl DeviceStore {
         status: DeviceStatus,
         response_time_ms: Option<i32>,
         error_message: Option<String>,
+        checks: &[HealthCheckOutcome],
     ) -> Result<()> {
         let now = Utc::now();
 
@@ -348,19 +373,21 @@ impl DeviceStore {
             anyhow::bail!("device not found");
         }
 
-        // Insert health history record
-        sqlx::query!(
+        // Insert health history record, with per-check latency in metadata
+        let metadata = (!checks.is_empty()).then(|| serde_json::json!({ "checks": checks }));
+        sqlx::query(
             r#"
             INSERT INTO device_health_history
-            (device_id, status, response_time_ms, error_message, checked_at)
-            VALUES ($1, $2, $3, $4, $5)
+            (device_id, status, response_time_ms, error_message, metadata, checked_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
             "#,
-            device_id,
-            status as DeviceStatus,
-            response_time_ms,
-            error_message,
-            now,
         )
+        .bind(device_id)
+        .bind(status)
+        .bind(response_time_ms)
+        .bind(error_message)
+        .bind(metadata)
+        .bind(now)
         .execute(&self.pool)
         .await
         .context("failed to insert health history")?;
@@ -400,6 +427,582 @@ impl DeviceStore {
         Ok(history)
     }
 
+    /// Online ONVIF devices, whose clocks the time-sync checker reads
+    pub async fn get_devices_for_clock_check(&self) -> Result<Vec<Device>> {
+        sqlx::query_as::<_, Device>(
+            "SELECT * FROM devices WHERE protocol = 'onvif' AND status = 'online' ORDER BY device_id",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to fetch devices for clock check")
+    }
+
+    /// Get the health checks selected for a device, `None` when the device
+    /// runs the default reachability check
+    pub async fn get_health_checks(&self, device_id: &str) -> Result<Option<DeviceHealthChecks>> {
+        let row: Option<(Option<serde_json::Value>,)> =
+            sqlx::query_as("SELECT health_checks FROM devices WHERE device_id = $1")
+                .bind(device_id)
+                .fetch_optional(&self.pool)
+                .await
+                .context("failed to fetch health checks")?;
+
+        match row.and_then(|(checks,)| checks) {
+            Some(value) => Ok(Some(
+                serde_json::from_value(value).context("invalid stored health checks")?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Select the health checks for a device
+    pub async fn set_health_checks(&self, device_id: &str, checks: &DeviceHealthChecks) -> Result<()> {
+        let result = sqlx::query(
+            "UPDATE devices SET health_checks = $2, updated_at = NOW() WHERE device_id = $1",
+        )
+        .bind(device_id)
+        .bind(serde_json::to_value(checks)?)
+        .execute(&self.pool)
+        .await
+        .context("failed to update health checks")?;
+
+        if result.rows_affected() == 0 {
+            anyhow::bail!("device not found");
+        }
+
+        Ok(())
+    }
+
+    /// Get the stored hardware identifiers of a device
+    pub async fn get_device_identifiers(&self, device_id: &str) -> Result<Option<DeviceIdentifiers>> {
+        sqlx::query_as::<_, DeviceIdentifiers>(
+            "SELECT mac_address, serial_number FROM devices WHERE device_id = $1",
+        )
+        .bind(device_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to fetch device identifiers")
+    }
+
+    /// Store the hardware identifiers of a device; `None` keeps the current one
+    pub async fn set_device_identifiers(&self, device_id: &str, identifiers: &DeviceIdentifiers) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE devices
+            SET mac_address = COALESCE($2, mac_address),
+                serial_number = COALESCE($3, serial_number),
+                updated_at = NOW()
+            WHERE device_id = $1
+            "#,
+        )
+        .bind(device_id)
+        .bind(&identifiers.mac_address)
+        .bind(&identifiers.serial_number)
+        .execute(&self.pool)
+        .await
+        .context("failed to update device identifiers")?;
+
+        Ok(())
+    }
+
+    /// Identities of all devices of a tenant, with their IDs and names
+    pub async fn device_identities(&self, tenant_id: &str) -> Result<Vec<(String, String, DeviceIdentity)>> {
+        // URIs are normalized in Rust, so devices are compared outside the query
+        let rows: Vec<(String, String, String, Option<String>, Option<String>, Option<String>)> =
+            sqlx::query_as(
+                r#"
+                SELECT device_id, name, primary_uri, secondary_uri, mac_address, serial_number
+                FROM devices
+                WHERE tenant_id = $1
+                ORDER BY created_at
+                "#,
+            )
+            .bind(tenant_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("failed to fetch device identities")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(device_id, name, primary_uri, secondary_uri, mac_address, serial_number)| {
+                let identity = DeviceIdentity::of_device(
+                    std::iter::once(primary_uri.as_str()).chain(secondary_uri.as_deref()),
+                    &DeviceIdentifiers { mac_address, serial_number },
+                );
+                (device_id, name, identity)
+            })
+            .collect())
+    }
+
+    /// Devices of a tenant that are the same camera as `identity`, other
+    /// than `exclude_device_id`
+    pub async fn find_duplicates(
+        &self,
+        tenant_id: &str,
+        identity: &DeviceIdentity,
+        exclude_device_id: Option<&str>,
+    ) -> Result<Vec<DuplicateDevice>> {
+        let devices = self.device_identities(tenant_id).await?;
+        Ok(duplicates::find(identity, &devices, exclude_device_id))
+    }
+
+    /// Merge `duplicate_id` into `device_id`: its health history, events,
+    /// PTZ presets and tours, configurations, firmware updates, clock drift
+    /// samples and maintenance tasks and runs move over, identifiers and URIs
+    /// the device lacks are taken over, and the duplicate is deleted, all in
+    /// one transaction
+    pub async fn merge_devices(&self, device_id: &str, duplicate_id: &str) -> Result<DeviceMergeResult> {
+        let mut tx = self.pool.begin().await?;
+        let mut moved = HashMap::new();
+
+        for table in [
+            "device_health_history",
+            "device_events",
+            "device_configurations",
+            "firmware_updates",
+            "device_clock_drift",
+            "maintenance_tasks",
+            "maintenance_runs",
+        ] {
+            let result = sqlx::query(&format!(
+                "UPDATE {} SET device_id = $1 WHERE device_id = $2",
+                table
+            ))
+            .bind(device_id)
+            .bind(duplicate_id)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("failed to move {}", table))?;
+            moved.insert(table.to_string(), result.rows_affected());
+        }
+
+        // Preset and tour names are unique per device; clashing ones are
+        // suffixed with the duplicate's name
+        for table in ["ptz_presets", "ptz_tours"] {
+            let result = sqlx::query(&format!(
+                r#"
+                UPDATE {table} AS moved
+                SET device_id = $1,
+                    name = CASE
+                        WHEN EXISTS (SELECT 1 FROM {table} kept WHERE kept.device_id = $1 AND kept.name = moved.name)
+                        THEN moved.name || ' (' || (SELECT name FROM devices WHERE device_id = $2) || ')'
+                        ELSE moved.name
+                    END
+                WHERE moved.device_id = $2
+                "#
+            ))
+            .bind(device_id)
+            .bind(duplicate_id)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("failed to move {}", table))?;
+            moved.insert(table.to_string(), result.rows_affected());
+        }
+
+        sqlx::query("UPDATE discovered_devices SET imported_device_id = $1 WHERE imported_device_id = $2")
+            .bind(device_id)
+            .bind(duplicate_id)
+            .execute(&mut *tx)
+            .await
+            .context("failed to move discovery imports")?;
+
+        let merged = sqlx::query(
+            r#"
+            UPDATE devices AS kept
+            SET mac_address = COALESCE(kept.mac_address, dup.mac_address),
+                serial_number = COALESCE(kept.serial_number, dup.serial_number),
+                secondary_uri = COALESCE(kept.secondary_uri, dup.secondary_uri),
+                manufacturer = COALESCE(kept.manufacturer, dup.manufacturer),
+                model = COALESCE(kept.model, dup.model),
+                firmware_version = COALESCE(kept.firmware_version, dup.firmware_version),
+                last_seen_at = GREATEST(kept.last_seen_at, dup.last_seen_at),
+                updated_at = NOW()
+            FROM devices AS dup
+            WHERE kept.device_id = $1 AND dup.device_id = $2
+            "#,
+        )
+        .bind(device_id)
+        .bind(duplicate_id)
+        .execute(&mut *tx)
+        .await
+        .context("failed to merge device records")?;
+        if merged.rows_affected() == 0 {
+            anyhow::bail!("device not found");
+        }
+
+        sqlx::query("DELETE FROM devices WHERE device_id = $1")
+            .bind(duplicate_id)
+            .execute(&mut *tx)
+            .await
+            .context("failed to delete merged device")?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO device_events (device_id, event_type, old_value, metadata)
+            VALUES ($1, 'merged', $2, $3)
+            "#,
+        )
+        .bind(device_id)
+        .bind(duplicate_id)
+        .bind(serde_json::to_value(&moved)?)
+        .execute(&mut *tx)
+        .await
+        .context("failed to log merge event")?;
+
+        tx.commit().await?;
+
+        Ok(DeviceMergeResult {
+            device_id: device_id.to_string(),
+            merged_device_id: duplicate_id.to_string(),
+            moved,
+        })
+    }
+
+    /// Record a clock drift sample
+    pub async fn record_clock_drift(
+        &self,
+        device_id: &str,
+        drift_ms: i64,
+        round_trip_ms: i32,
+        source: &str,
+        exceeded: bool,
+    ) -> Result<ClockDriftSample> {
+        sqlx::query_as::<_, ClockDriftSample>(
+            r#"
+            INSERT INTO device_clock_drift (device_id, drift_ms, round_trip_ms, source, exceeded)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING sample_id, device_id, drift_ms, round_trip_ms, source, exceeded, measured_at
+            "#,
+        )
+        .bind(device_id)
+        .bind(drift_ms)
+        .bind(round_trip_ms)
+        .bind(source)
+        .bind(exceeded)
+        .fetch_one(&self.pool)
+        .await
+        .context("failed to record clock drift")
+    }
+
+    /// Clock drift samples of a device, newest first
+    pub async fn get_clock_drift_history(
+        &self,
+        device_id: &str,
+        limit: i64,
+    ) -> Result<Vec<ClockDriftSample>> {
+        sqlx::query_as::<_, ClockDriftSample>(
+            r#"
+            SELECT sample_id, device_id, drift_ms, round_trip_ms, source, exceeded, measured_at
+            FROM device_clock_drift
+            WHERE device_id = $1
+            ORDER BY measured_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(device_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to fetch clock drift history")
+    }
+
+    /// Latest clock drift sample of every device, optionally limited to one
+    /// tenant, largest drift first
+    pub async fn list_clock_drift(
+        &self,
+        tenant_id: Option<&str>,
+        exceeded_only: bool,
+    ) -> Result<Vec<DeviceClockDrift>> {
+        sqlx::query_as::<_, DeviceClockDrift>(
+            r#"
+            SELECT * FROM (
+                SELECT DISTINCT ON (c.device_id)
+                    c.device_id, d.name, c.drift_ms, c.exceeded, c.measured_at
+                FROM device_clock_drift c
+                JOIN devices d ON d.device_id = c.device_id
+                WHERE $1::TEXT IS NULL OR d.tenant_id = $1
+                ORDER BY c.device_id, c.measured_at DESC
+            ) latest
+            WHERE NOT $2 OR exceeded
+            ORDER BY ABS(drift_ms) DESC
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(exceeded_only)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list clock drift")
+    }
+
+    /// Delete clock drift samples older than `days`
+    pub async fn cleanup_old_clock_drift(&self, days: i32) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM device_clock_drift WHERE measured_at < NOW() - INTERVAL '1 day' * $1",
+        )
+        .bind(f64::from(days))
+        .execute(&self.pool)
+        .await
+        .context("failed to clean up clock drift samples")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Create a maintenance task of `tenant_id`
+    pub async fn create_maintenance_task(
+        &self,
+        tenant_id: &str,
+        req: &MaintenanceTaskRequest,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<MaintenanceTask> {
+        sqlx::query_as::<_, MaintenanceTask>(
+            r#"
+            INSERT INTO maintenance_tasks (
+                task_id, tenant_id, name, action, device_id, manufacturer, model,
+                schedule_cron, time_zone, window_secs, enabled, next_run_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(tenant_id)
+        .bind(&req.name)
+        .bind(req.action)
+        .bind(&req.device_id)
+        .bind(&req.manufacturer)
+        .bind(&req.model)
+        .bind(&req.schedule_cron)
+        .bind(&req.time_zone)
+        .bind(req.window_secs)
+        .bind(req.enabled)
+        .bind(next_run_at)
+        .fetch_one(&self.pool)
+        .await
+        .context("failed to create maintenance task")
+    }
+
+    pub async fn get_maintenance_task(&self, task_id: &str) -> Result<Option<MaintenanceTask>> {
+        sqlx::query_as::<_, MaintenanceTask>("SELECT * FROM maintenance_tasks WHERE task_id = $1")
+            .bind(task_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("failed to fetch maintenance task")
+    }
+
+    /// Maintenance tasks, optionally of one tenant, by name
+    pub async fn list_maintenance_tasks(&self, tenant_id: Option<&str>) -> Result<Vec<MaintenanceTask>> {
+        sqlx::query_as::<_, MaintenanceTask>(
+            "SELECT * FROM maintenance_tasks WHERE $1::TEXT IS NULL OR tenant_id = $1 ORDER BY name, task_id",
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to list maintenance tasks")
+    }
+
+    /// Replace a maintenance task's settings; `None` when it does not exist
+    pub async fn update_maintenance_task(
+        &self,
+        task_id: &str,
+        req: &MaintenanceTaskRequest,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<MaintenanceTask>> {
+        sqlx::query_as::<_, MaintenanceTask>(
+            r#"
+            UPDATE maintenance_tasks
+            SET name = $2, action = $3, device_id = $4, manufacturer = $5, model = $6,
+                schedule_cron = $7, time_zone = $8, window_secs = $9, enabled = $10,
+                next_run_at = $11, updated_at = NOW()
+            WHERE task_id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(task_id)
+        .bind(&req.name)
+        .bind(req.action)
+        .bind(&req.device_id)
+        .bind(&req.manufacturer)
+        .bind(&req.model)
+        .bind(&req.schedule_cron)
+        .bind(&req.time_zone)
+        .bind(req.window_secs)
+        .bind(req.enabled)
+        .bind(next_run_at)
+        .fetch_optional(&self.pool)
+        .await
+        .context("failed to update maintenance task")
+    }
+
+    /// Delete a maintenance task; its runs stay in the device history
+    pub async fn delete_maintenance_task(&self, task_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM maintenance_tasks WHERE task_id = $1")
+            .bind(task_id)
+            .execute(&self.pool)
+            .await
+            .context("failed to delete maintenance task")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enabled maintenance tasks whose next run is due
+    pub async fn due_maintenance_tasks(&self, now: DateTime<Utc>) -> Result<Vec<MaintenanceTask>> {
+        sqlx::query_as::<_, MaintenanceTask>(
+            "SELECT * FROM maintenance_tasks WHERE enabled AND next_run_at <= $1 ORDER BY next_run_at",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to fetch due maintenance tasks")
+    }
+
+    /// Move a task from the run due at `due_at` on to `next_run_at`. False
+    /// when the task changed in between, and the run must not happen.
+    pub async fn advance_maintenance_task(
+        &self,
+        task_id: &str,
+        due_at: DateTime<Utc>,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE maintenance_tasks
+            SET next_run_at = $3, last_run_at = NOW()
+            WHERE task_id = $1 AND next_run_at = $2
+            "#,
+        )
+        .bind(task_id)
+        .bind(due_at)
+        .bind(next_run_at)
+        .execute(&self.pool)
+        .await
+        .context("failed to advance maintenance task")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Devices a maintenance task applies to: its device, or the tenant's
+    /// devices of its manufacturer and model (case-insensitive)
+    pub async fn maintenance_task_devices(&self, task: &MaintenanceTask) -> Result<Vec<Device>> {
+        sqlx::query_as::<_, Device>(
+            r#"
+            SELECT * FROM devices
+            WHERE tenant_id = $1
+                AND ($2::TEXT IS NULL OR device_id = $2)
+                AND ($3::TEXT IS NULL OR LOWER(manufacturer) = LOWER($3))
+                AND ($4::TEXT IS NULL OR LOWER(model) = LOWER($4))
+            ORDER BY device_id
+            "#,
+        )
+        .bind(&task.tenant_id)
+        .bind(&task.device_id)
+        .bind(&task.manufacturer)
+        .bind(&task.model)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to fetch maintenance task devices")
+    }
+
+    /// Put a device into maintenance, which the health monitor skips. False
+    /// when it already is in maintenance or still provisioning.
+    pub async fn enter_maintenance(&self, device_id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE devices SET status = 'maintenance', updated_at = NOW()
+            WHERE device_id = $1 AND status NOT IN ('maintenance', 'provisioning')
+            "#,
+        )
+        .bind(device_id)
+        .execute(&self.pool)
+        .await
+        .context("failed to put device into maintenance")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record a maintenance run; runs other than `running` are complete
+    pub async fn create_maintenance_run(
+        &self,
+        task_id: Option<&str>,
+        device_id: &str,
+        action: MaintenanceAction,
+        status: MaintenanceRunStatus,
+        error: Option<&str>,
+        window_ends_at: DateTime<Utc>,
+    ) -> Result<MaintenanceRun> {
+        sqlx::query_as::<_, MaintenanceRun>(
+            r#"
+            INSERT INTO maintenance_runs (task_id, device_id, action, status, error, window_ends_at, completed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $4 = 'running' THEN NULL ELSE NOW() END)
+            RETURNING *
+            "#,
+        )
+        .bind(task_id)
+        .bind(device_id)
+        .bind(action)
+        .bind(status)
+        .bind(error)
+        .bind(window_ends_at)
+        .fetch_one(&self.pool)
+        .await
+        .context("failed to record maintenance run")
+    }
+
+    pub async fn finish_maintenance_run(
+        &self,
+        run_id: i64,
+        status: MaintenanceRunStatus,
+        error: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE maintenance_runs SET status = $2, error = $3, completed_at = NOW() WHERE run_id = $1",
+        )
+        .bind(run_id)
+        .bind(status)
+        .bind(error)
+        .execute(&self.pool)
+        .await
+        .context("failed to finish maintenance run")?;
+        Ok(())
+    }
+
+    /// Maintenance runs of a device, newest first
+    pub async fn get_maintenance_runs(&self, device_id: &str, limit: i64) -> Result<Vec<MaintenanceRun>> {
+        sqlx::query_as::<_, MaintenanceRun>(
+            "SELECT * FROM maintenance_runs WHERE device_id = $1 ORDER BY started_at DESC, run_id DESC LIMIT $2",
+        )
+        .bind(device_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("failed to fetch maintenance runs")
+    }
+
+    /// Fail the runs a restart interrupted and hand their devices back to
+    /// the health monitor, which checks them right away
+    pub async fn recover_maintenance_runs(&self) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let devices: Vec<String> = sqlx::query_scalar(
+            r#"
+            UPDATE maintenance_runs
+            SET status = 'failed', error = 'interrupted by a restart', completed_at = NOW()
+            WHERE status = 'running'
+            RETURNING device_id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .context("failed to fail interrupted maintenance runs")?;
+        sqlx::query(
+            r#"
+            UPDATE devices SET status = 'offline', last_health_check_at = NULL, updated_at = NOW()
+            WHERE device_id = ANY($1) AND status = 'maintenance'
+            "#,
+        )
+        .bind(&devices)
+        .execute(&mut *tx)
+        .await
+        .context("failed to end interrupted maintenance")?;
+        tx.commit().await?;
+        Ok(devices.len() as u64)
+    }
+
     /// Get devices requiring health check
     pub async fn get_devices_needing_health_check(&self) -> Result<Vec<Device>> {
         let devices = sqlx::query_as!(
@@ -1833,6 +2436,16 @@ impl DeviceStore {
     }
 }
 
+#[async_trait::async_trait]
+impl ResourceTenantResolver for DeviceStore {
+    async fn owner_tenant(&self, params: &HashMap<String, String>) -> Result<Option<String>> {
+        match params.get("device_id") {
+            Some(device_id) => self.get_device_tenant(device_id).await,
+            None => Ok(None),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
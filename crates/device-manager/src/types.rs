@@ -2,12 +2,16 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
+use validator::Validate;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "device_type", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum DeviceType {
     Camera,
+    /// Thermal (infrared) camera; its streams are transcoded in a false-color
+    /// palette and radiometric models feed the `thermal_analytics` plugin
+    Thermal,
     Nvr,
     Encoder,
     Other,
@@ -86,49 +90,91 @@ pub struct Device {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct CreateDeviceRequest {
+    #[validate(custom(function = "common::validated::name"))]
     pub name: String,
     pub device_type: DeviceType,
+    #[validate(length(max = 255))]
     pub manufacturer: Option<String>,
+    #[validate(length(max = 255))]
     pub model: Option<String>,
+    #[validate(custom(function = "common::validated::uri"))]
     pub primary_uri: String,
+    #[validate(custom(function = "common::validated::uri"))]
     pub secondary_uri: Option<String>,
     pub protocol: ConnectionProtocol,
+    #[validate(length(max = 255))]
     pub username: Option<String>,
+    #[validate(length(max = 255))]
     pub password: Option<String>,
+    #[validate(length(max = 255))]
     pub location: Option<String>,
+    #[validate(length(max = 255))]
     pub zone: Option<String>,
+    #[validate(length(max = 50), custom(function = "common::validated::tags"))]
     pub tags: Option<Vec<String>>,
+    #[validate(length(max = 4096))]
     pub description: Option<String>,
+    #[validate(range(min = 10, max = 86400))]
     pub health_check_interval_secs: Option<i32>,
     pub auto_start: Option<bool>,
     pub recording_enabled: Option<bool>,
     pub ai_enabled: Option<bool>,
     pub metadata: Option<JsonValue>,
+    /// MAC address, checked with the serial number and endpoints against
+    /// the tenant's other devices
+    #[validate(length(max = 64))]
+    pub mac_address: Option<String>,
+    #[validate(length(max = 255))]
+    pub serial_number: Option<String>,
+    /// Create the device even if it duplicates an existing one
+    #[serde(default)]
+    pub allow_duplicate: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct UpdateDeviceRequest {
+    #[validate(custom(function = "common::validated::name"))]
     pub name: Option<String>,
+    #[validate(length(max = 255))]
     pub manufacturer: Option<String>,
+    #[validate(length(max = 255))]
     pub model: Option<String>,
+    #[validate(length(max = 255))]
     pub firmware_version: Option<String>,
+    #[validate(custom(function = "common::validated::uri"))]
     pub primary_uri: Option<String>,
+    #[validate(custom(function = "common::validated::uri"))]
     pub secondary_uri: Option<String>,
+    #[validate(length(max = 255))]
     pub username: Option<String>,
+    #[validate(length(max = 255))]
     pub password: Option<String>,
+    #[validate(length(max = 255))]
     pub location: Option<String>,
+    #[validate(length(max = 255))]
     pub zone: Option<String>,
+    #[validate(length(max = 50), custom(function = "common::validated::tags"))]
     pub tags: Option<Vec<String>>,
+    #[validate(length(max = 4096))]
     pub description: Option<String>,
+    #[validate(length(max = 4096))]
     pub notes: Option<String>,
+    #[validate(range(min = 10, max = 86400))]
     pub health_check_interval_secs: Option<i32>,
     pub auto_start: Option<bool>,
     pub recording_enabled: Option<bool>,
     pub ai_enabled: Option<bool>,
     pub status: Option<DeviceStatus>,
     pub metadata: Option<JsonValue>,
+    #[validate(length(max = 64))]
+    pub mac_address: Option<String>,
+    #[validate(length(max = 255))]
+    pub serial_number: Option<String>,
+    /// Apply a new URI or identifier even if it duplicates another device
+    #[serde(default)]
+    pub allow_duplicate: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -142,6 +188,125 @@ pub struct DeviceHealthHistory {
     pub checked_at: DateTime<Utc>,
 }
 
+/// Protocol-level health check the health monitor can run against a device
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthCheckKind {
+    /// Basic reachability for the device protocol (ffprobe for RTSP, GET for HTTP)
+    Reachability,
+    /// RTSP OPTIONS: the RTSP server answers requests
+    RtspOptions,
+    /// RTSP DESCRIBE: the server hands out an SDP with a video track
+    RtspDescribe,
+    /// ONVIF GetSystemDateAndTime: the device service answers SOAP calls
+    OnvifDateTime,
+    /// HTTP snapshot: the camera serves a still image
+    HttpSnapshot,
+}
+
+impl HealthCheckKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthCheckKind::Reachability => "reachability",
+            HealthCheckKind::RtspOptions => "rtsp_options",
+            HealthCheckKind::RtspDescribe => "rtsp_describe",
+            HealthCheckKind::OnvifDateTime => "onvif_date_time",
+            HealthCheckKind::HttpSnapshot => "http_snapshot",
+        }
+    }
+}
+
+/// Health checks selected for a device. All selected checks must pass for
+/// the device to count as online; with none selected the reachability
+/// check runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DeviceHealthChecks {
+    #[serde(default)]
+    pub checks: Vec<HealthCheckKind>,
+    /// RTSP URI for the RTSP checks, defaults to the primary URI
+    pub rtsp_uri: Option<String>,
+    /// ONVIF device service URL, defaults to `/onvif/device_service` on the
+    /// primary URI's host
+    pub onvif_url: Option<String>,
+    /// Snapshot URL, defaults to the primary URI of HTTP devices
+    pub snapshot_url: Option<String>,
+}
+
+/// Result of one health check, kept under `checks` in the health history
+/// metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheckOutcome {
+    pub check: HealthCheckKind,
+    pub success: bool,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Stored hardware identifiers of a device, normalized
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, sqlx::FromRow)]
+pub struct DeviceIdentifiers {
+    pub mac_address: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+/// What two devices have in common that marks them as the same camera
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    MacAddress,
+    SerialNumber,
+    /// Same normalized primary or secondary URI
+    Endpoint,
+    /// Same host; only used for discovered devices, which have no stream URI
+    Host,
+}
+
+/// Existing device another device (or a discovered one) duplicates
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DuplicateDevice {
+    pub device_id: String,
+    pub name: String,
+    pub matched_on: Vec<DuplicateReason>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeDevicesRequest {
+    /// Device whose history moves onto the addressed device; it is deleted
+    pub duplicate_device_id: String,
+}
+
+/// Outcome of a merge: rows moved per table from the duplicate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceMergeResult {
+    pub device_id: String,
+    pub merged_device_id: String,
+    pub moved: HashMap<String, u64>,
+}
+
+/// One camera clock check by the time-sync checker
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ClockDriftSample {
+    pub sample_id: i64,
+    pub device_id: String,
+    /// Camera clock minus server clock; positive when the camera is ahead
+    pub drift_ms: i64,
+    pub round_trip_ms: i32,
+    pub source: String,
+    /// Drift was beyond the alert threshold
+    pub exceeded: bool,
+    pub measured_at: DateTime<Utc>,
+}
+
+/// Latest clock check of a device
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DeviceClockDrift {
+    pub device_id: String,
+    pub name: String,
+    pub drift_ms: i64,
+    pub exceeded: bool,
+    pub measured_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct DeviceEvent {
     pub event_id: i64,
@@ -507,6 +672,69 @@ pub struct ConfigurationHistoryQuery {
     pub offset: Option<i64>,
 }
 
+// Relay Output Types (ONVIF DeviceIO)
+
+/// `Monostable` outputs fall back to their idle state after `delay_time`;
+/// `Bistable` ones stay where they are set
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RelayMode {
+    Monostable,
+    Bistable,
+}
+
+/// Electrical state of an inactive output
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RelayIdleState {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RelayState {
+    Active,
+    Inactive,
+}
+
+impl RelayState {
+    /// `tt:RelayLogicalState` value
+    pub fn as_onvif(&self) -> &'static str {
+        match self {
+            RelayState::Active => "active",
+            RelayState::Inactive => "inactive",
+        }
+    }
+}
+
+/// Digital output of a device (siren, door strike, light)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RelayOutput {
+    pub token: String,
+    pub mode: Option<RelayMode>,
+    pub idle_state: Option<RelayIdleState>,
+    /// ONVIF duration, e.g. `PT5S`, after which a monostable output resets
+    pub delay_time: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetRelayOutputRequest {
+    pub state: RelayState,
+    /// Set the output back to inactive after this many seconds; for pulsing
+    /// bistable outputs (a monostable one resets by itself)
+    pub duration_secs: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetRelayOutputResponse {
+    pub device_id: String,
+    pub token: String,
+    pub state: RelayState,
+    /// When device-manager sets the output back to inactive
+    pub resets_at: Option<DateTime<Utc>>,
+}
+
 // Firmware Update Types
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq)]
@@ -643,6 +871,50 @@ pub struct UploadFirmwareFileRequest {
     pub metadata: Option<JsonValue>,
 }
 
+/// Start of a chunked firmware upload (`POST /v1/firmware/uploads`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitFirmwareUploadRequest {
+    pub manufacturer: String,
+    pub model: String,
+    pub firmware_version: String,
+    /// Size of the whole image in bytes
+    pub total_size: u64,
+    /// SHA-256 (hex) of the whole image, checked on completion
+    pub checksum: String,
+    pub release_notes: Option<String>,
+    pub release_date: Option<DateTime<Utc>>,
+    pub min_device_version: Option<String>,
+    pub compatible_models: Option<Vec<String>>,
+}
+
+/// A chunked firmware upload in progress. Chunks are appended at
+/// `received_bytes`, so a client that lost its connection reads the session
+/// and resumes from there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirmwareUploadSession {
+    pub upload_id: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub firmware_version: String,
+    pub total_size: u64,
+    pub checksum: String,
+    pub received_bytes: u64,
+    pub chunk_count: u32,
+    pub release_notes: Option<String>,
+    pub release_date: Option<DateTime<Utc>>,
+    pub min_device_version: Option<String>,
+    pub compatible_models: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Query of a chunk append (`PUT /v1/firmware/uploads/:upload_id/chunks`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct FirmwareChunkQuery {
+    /// Byte offset of the chunk in the image
+    pub offset: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FirmwareUpdateProgressReport {
     pub update_id: String,
@@ -669,3 +941,116 @@ pub struct FirmwareFileListQuery {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
 }
+
+// Maintenance Types
+
+/// What a maintenance task does to its devices
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenanceAction {
+    /// ONVIF `SystemReboot`
+    #[default]
+    Reboot,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenanceRunStatus {
+    /// Action sent; waiting for the device to come back within the window
+    Running,
+    Succeeded,
+    Failed,
+    /// The device could not be maintained (not ONVIF, already in maintenance)
+    Skipped,
+}
+
+impl MaintenanceRunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaintenanceRunStatus::Running => "running",
+            MaintenanceRunStatus::Succeeded => "succeeded",
+            MaintenanceRunStatus::Failed => "failed",
+            MaintenanceRunStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// Scheduled maintenance of one device, or of every device of the tenant
+/// matching a manufacturer and model
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MaintenanceTask {
+    pub task_id: String,
+    pub tenant_id: String,
+    pub name: String,
+    pub action: MaintenanceAction,
+    pub device_id: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    /// Cron expression with seconds, e.g. `0 0 3 * * Sun`
+    pub schedule_cron: String,
+    /// IANA time zone the schedule is read in
+    pub time_zone: String,
+    /// How long a device may stay unreachable after the action before it
+    /// counts as offline
+    pub window_secs: i32,
+    pub enabled: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Create or replace a maintenance task
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct MaintenanceTaskRequest {
+    #[validate(custom(function = "common::validated::name"))]
+    pub name: String,
+    #[serde(default)]
+    pub action: MaintenanceAction,
+    #[validate(length(max = 255))]
+    pub device_id: Option<String>,
+    #[validate(length(max = 255))]
+    pub manufacturer: Option<String>,
+    #[validate(length(max = 255))]
+    pub model: Option<String>,
+    #[validate(length(max = 255))]
+    pub schedule_cron: String,
+    #[serde(default = "default_maintenance_time_zone")]
+    #[validate(length(max = 64))]
+    pub time_zone: String,
+    #[serde(default = "default_maintenance_window_secs")]
+    #[validate(range(min = 60, max = 86400))]
+    pub window_secs: i32,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_maintenance_time_zone() -> String {
+    "UTC".to_string()
+}
+
+fn default_maintenance_window_secs() -> i32 {
+    600
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// One run of a maintenance task on a device
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MaintenanceRun {
+    pub run_id: i64,
+    /// `None` once the task was deleted
+    pub task_id: Option<String>,
+    pub device_id: String,
+    pub action: MaintenanceAction,
+    pub status: MaintenanceRunStatus,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// Offline alerts of the device are held back until then
+    pub window_ends_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
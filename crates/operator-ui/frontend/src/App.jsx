@@ -6,6 +6,7 @@ import Devices from './pages/Devices';
 import Streams from './pages/Streams';
 import Recordings from './pages/Recordings';
 import AiTasks from './pages/AiTasks';
+import CameraAnalytics from './pages/CameraAnalytics';
 import Alerts from './pages/Alerts';
 import Incidents from './pages/Incidents';
 
@@ -18,6 +19,7 @@ function Sidebar() {
     { path: '/streams', label: 'Live Streams', icon: '🎥' },
     { path: '/recordings', label: 'Recordings', icon: '📼' },
     { path: '/ai', label: 'AI Tasks', icon: '🤖' },
+    { path: '/analytics', label: 'Camera Analytics', icon: '📐' },
     { path: '/alerts', label: 'Alerts', icon: '🔔' },
     { path: '/incidents', label: 'Incidents', icon: '🚨' },
   ];
@@ -63,6 +65,7 @@ function App() {
             <Route path="/streams" element={<Streams />} />
             <Route path="/recordings" element={<Recordings />} />
             <Route path="/ai" element={<AiTasks />} />
+            <Route path="/analytics" element={<CameraAnalytics />} />
             <Route path="/alerts" element={<Alerts />} />
             <Route path="/incidents" element={<Incidents />} />
           </Routes>
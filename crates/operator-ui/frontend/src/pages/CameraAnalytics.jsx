@@ -0,0 +1,268 @@
+import React, { useEffect, useState } from 'react';
+import { api } from '../services/api';
+
+// Zones and tripwires are kept in frame pixels; the drawing area maps onto
+// a frame of this size
+const FRAME_WIDTH = 1920;
+const FRAME_HEIGHT = 1080;
+
+const inputStyle = {
+  padding: '10px',
+  borderRadius: '6px',
+  border: '1px solid #2d3748',
+  backgroundColor: '#1a1f2e',
+  color: '#e8eaed',
+};
+
+const empty = { zones: [], tripwires: [], alarm_classes: [] };
+
+function CameraAnalytics() {
+  const [devices, setDevices] = useState([]);
+  const [configured, setConfigured] = useState([]);
+  const [cameraId, setCameraId] = useState('');
+  const [analytics, setAnalytics] = useState(empty);
+  const [mode, setMode] = useState('zone');
+  const [points, setPoints] = useState([]);
+  const [classInput, setClassInput] = useState('');
+  const [message, setMessage] = useState(null);
+  const [error, setError] = useState(null);
+
+  useEffect(() => {
+    loadCameras();
+  }, []);
+
+  const loadCameras = async () => {
+    try {
+      const [deviceList, entries] = await Promise.all([api.getDevices(), api.getCameraAnalytics()]);
+      setDevices(Array.isArray(deviceList) ? deviceList : []);
+      setConfigured(Array.isArray(entries) ? entries : []);
+      setError(null);
+    } catch (err) {
+      setError('Failed to load cameras');
+      console.error(err);
+    }
+  };
+
+  const selectCamera = (id) => {
+    setCameraId(id);
+    setPoints([]);
+    setMessage(null);
+    const entry = configured.find((c) => c.camera_id === id);
+    setAnalytics(
+      entry
+        ? { zones: entry.zones || [], tripwires: entry.tripwires || [], alarm_classes: entry.alarm_classes || [] }
+        : empty
+    );
+  };
+
+  // Clicks add corners of the zone being drawn, or the two ends of a tripwire
+  const addPoint = (e) => {
+    const rect = e.currentTarget.getBoundingClientRect();
+    const point = {
+      x: Math.round(((e.clientX - rect.left) / rect.width) * FRAME_WIDTH),
+      y: Math.round(((e.clientY - rect.top) / rect.height) * FRAME_HEIGHT),
+    };
+    if (mode === 'tripwire' && points.length === 1) {
+      const id = `tripwire-${analytics.tripwires.length + 1}`;
+      setAnalytics({ ...analytics, tripwires: [...analytics.tripwires, { id, from: points[0], to: point }] });
+      setPoints([]);
+    } else {
+      setPoints([...points, point]);
+    }
+  };
+
+  const finishZone = () => {
+    if (points.length < 3) return;
+    const id = `zone-${analytics.zones.length + 1}`;
+    setAnalytics({ ...analytics, zones: [...analytics.zones, { id, polygon: points }] });
+    setPoints([]);
+  };
+
+  const removeShape = (kind, id) => {
+    setAnalytics({ ...analytics, [kind]: analytics[kind].filter((shape) => shape.id !== id) });
+  };
+
+  const addClass = (e) => {
+    e.preventDefault();
+    const name = classInput.trim();
+    if (name && !analytics.alarm_classes.includes(name)) {
+      setAnalytics({ ...analytics, alarm_classes: [...analytics.alarm_classes, name] });
+    }
+    setClassInput('');
+  };
+
+  const save = async () => {
+    try {
+      const entry = await api.saveCameraAnalytics(cameraId, analytics);
+      setMessage(`Saved as version ${entry.version}`);
+      setError(null);
+      loadCameras();
+    } catch (err) {
+      setError(err.message);
+    }
+  };
+
+  const remove = async () => {
+    await api.deleteCameraAnalytics(cameraId);
+    setAnalytics(empty);
+    setMessage('Camera analytics removed');
+    loadCameras();
+  };
+
+  const cameraIds = [
+    ...new Set([...devices.map((d) => d.id), ...configured.map((c) => c.camera_id)]),
+  ];
+
+  return (
+    <div>
+      <div className="header">
+        <h2>Camera Analytics</h2>
+        <div className="header-actions">
+          <select value={cameraId} onChange={(e) => selectCamera(e.target.value)} style={inputStyle}>
+            <option value="">Select a camera...</option>
+            {cameraIds.map((id) => (
+              <option key={id} value={id}>
+                {devices.find((d) => d.id === id)?.name || id}
+              </option>
+            ))}
+          </select>
+          <button className="btn btn-secondary" onClick={loadCameras}>
+            Refresh
+          </button>
+        </div>
+      </div>
+      <div className="content">
+        {error && <div className="error">{error}</div>}
+        {message && <div className="card">{message}</div>}
+
+        {cameraId && (
+          <>
+            <div className="card">
+              <div className="card-header">
+                <h3 className="card-title">Zones and tripwires</h3>
+                <div style={{ display: 'flex', gap: '10px' }}>
+                  <button
+                    className={`btn ${mode === 'zone' ? 'btn-primary' : 'btn-secondary'}`}
+                    onClick={() => { setMode('zone'); setPoints([]); }}
+                  >
+                    Draw zone
+                  </button>
+                  <button
+                    className={`btn ${mode === 'tripwire' ? 'btn-primary' : 'btn-secondary'}`}
+                    onClick={() => { setMode('tripwire'); setPoints([]); }}
+                  >
+                    Draw tripwire
+                  </button>
+                  {mode === 'zone' && (
+                    <button className="btn btn-secondary" onClick={finishZone} disabled={points.length < 3}>
+                      Close zone
+                    </button>
+                  )}
+                </div>
+              </div>
+              <svg
+                viewBox={`0 0 ${FRAME_WIDTH} ${FRAME_HEIGHT}`}
+                onClick={addPoint}
+                style={{ width: '100%', backgroundColor: '#0f1320', cursor: 'crosshair' }}
+              >
+                {analytics.zones.map((zone) => (
+                  <polygon
+                    key={zone.id}
+                    points={zone.polygon.map((p) => `${p.x},${p.y}`).join(' ')}
+                    fill="rgba(66, 153, 225, 0.25)"
+                    stroke="#4299e1"
+                    strokeWidth="4"
+                  />
+                ))}
+                {analytics.tripwires.map((wire) => (
+                  <line
+                    key={wire.id}
+                    x1={wire.from.x}
+                    y1={wire.from.y}
+                    x2={wire.to.x}
+                    y2={wire.to.y}
+                    stroke="#f6ad55"
+                    strokeWidth="6"
+                  />
+                ))}
+                <polyline
+                  points={points.map((p) => `${p.x},${p.y}`).join(' ')}
+                  fill="none"
+                  stroke="#48bb78"
+                  strokeWidth="4"
+                  strokeDasharray="12 8"
+                />
+                {points.map((p, idx) => (
+                  <circle key={idx} cx={p.x} cy={p.y} r="8" fill="#48bb78" />
+                ))}
+              </svg>
+              <table className="table">
+                <tbody>
+                  {[...analytics.zones.map((z) => ['zones', z]), ...analytics.tripwires.map((t) => ['tripwires', t])].map(
+                    ([kind, shape]) => (
+                      <tr key={`${kind}-${shape.id}`}>
+                        <td>{shape.id}</td>
+                        <td>{kind === 'zones' ? `Zone, ${shape.polygon.length} corners` : 'Tripwire (tasks with tracking only)'}</td>
+                        <td>
+                          <button className="btn btn-secondary" onClick={() => removeShape(kind, shape.id)}>
+                            Remove
+                          </button>
+                        </td>
+                      </tr>
+                    )
+                  )}
+                </tbody>
+              </table>
+            </div>
+
+            <div className="card">
+              <div className="card-header">
+                <h3 className="card-title">Alarm classes</h3>
+              </div>
+              <p>Only these classes raise alerts; with none, every class does.</p>
+              <form onSubmit={addClass} style={{ display: 'flex', gap: '10px', marginBottom: '10px' }}>
+                <input
+                  type="text"
+                  value={classInput}
+                  onChange={(e) => setClassInput(e.target.value)}
+                  placeholder="Class, e.g. person"
+                  style={inputStyle}
+                />
+                <button type="submit" className="btn btn-secondary">
+                  Add
+                </button>
+              </form>
+              <div style={{ display: 'flex', gap: '10px', flexWrap: 'wrap' }}>
+                {analytics.alarm_classes.map((name) => (
+                  <span
+                    key={name}
+                    className="badge info"
+                    style={{ cursor: 'pointer' }}
+                    onClick={() =>
+                      setAnalytics({ ...analytics, alarm_classes: analytics.alarm_classes.filter((c) => c !== name) })
+                    }
+                  >
+                    {name} ✕
+                  </span>
+                ))}
+              </div>
+            </div>
+
+            <div style={{ display: 'flex', gap: '10px' }}>
+              <button className="btn btn-primary" onClick={save}>
+                Save
+              </button>
+              {configured.some((c) => c.camera_id === cameraId) && (
+                <button className="btn btn-secondary" onClick={remove}>
+                  Remove camera analytics
+                </button>
+              )}
+            </div>
+          </>
+        )}
+      </div>
+    </div>
+  );
+}
+
+export default CameraAnalytics;
@@ -39,6 +39,23 @@ export const api = {
     return fetch(`${API_BASE}/ai/detections${query ? '?' + query : ''}`).then(r => r.json());
   },
 
+  // Camera analytics (zones, tripwires and alarm classes per camera)
+  getCameraAnalytics: () => fetch(`${API_BASE}/analytics/cameras`).then(r => r.json()),
+  getCameraAnalyticsFor: (cameraId) =>
+    fetch(`${API_BASE}/analytics/cameras/${cameraId}`).then(r => r.json()),
+  saveCameraAnalytics: (cameraId, analytics) =>
+    fetch(`${API_BASE}/analytics/cameras/${cameraId}`, {
+      method: 'PUT',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(analytics),
+    }).then(async r => {
+      const body = await r.json();
+      if (!r.ok) throw new Error(body.error || 'Failed to save camera analytics');
+      return body;
+    }),
+  deleteCameraAnalytics: (cameraId) =>
+    fetch(`${API_BASE}/analytics/cameras/${cameraId}`, { method: 'DELETE' }).then(r => r.json()),
+
   // Alerts
   getAlerts: (params) => {
     const query = new URLSearchParams(params).toString();
@@ -75,4 +92,26 @@ export const api = {
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify(note),
     }).then(r => r.json()),
+
+  // Co-browsing sessions (participants follow them with wsClient.joinSession)
+  getCoBrowseSessions: () => fetch(`${API_BASE}/cobrowse/sessions`).then(r => r.json()),
+  createCoBrowseSession: (session) =>
+    fetch(`${API_BASE}/cobrowse/sessions`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(session),
+    }).then(r => r.json()),
+  getCoBrowseSession: (id) => fetch(`${API_BASE}/cobrowse/sessions/${id}`).then(r => r.json()),
+  controlCoBrowseSession: (id, ownerToken, control) =>
+    fetch(`${API_BASE}/cobrowse/sessions/${id}/control`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ owner_token: ownerToken, ...control }),
+    }).then(r => r.json()),
+  endCoBrowseSession: (id, ownerToken) =>
+    fetch(`${API_BASE}/cobrowse/sessions/${id}/end`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ owner_token: ownerToken }),
+    }),
 };
@@ -55,6 +55,8 @@ export class WebSocketClient {
   handleMessage(message) {
     if (message.type === 'update') {
       this.notifyListeners(message.topic, message.data);
+    } else if (message.type === 'session') {
+      this.notifyListeners(`session:${message.session_id}`, message.event);
     } else if (message.type === 'error') {
       console.error('WebSocket error:', message.message);
     }
@@ -95,6 +97,26 @@ export class WebSocketClient {
     }
   }
 
+  // Follow a co-browsing session; `callback` gets a snapshot, then the
+  // owner's controls and participant changes until the session ends
+  joinSession(sessionId, operator, callback) {
+    const topic = `session:${sessionId}`;
+    if (!this.listeners.has(topic)) {
+      this.listeners.set(topic, new Set());
+    }
+    this.listeners.get(topic).add(callback);
+    this.send({ type: 'join_session', session_id: sessionId, operator });
+
+    return () => {
+      this.listeners.delete(topic);
+      this.send({ type: 'leave_session', session_id: sessionId });
+    };
+  }
+
+  controlSession(sessionId, ownerToken, control) {
+    this.send({ type: 'session_control', session_id: sessionId, owner_token: ownerToken, control });
+  }
+
   notifyListeners(topic, data) {
     const listeners = this.listeners.get(topic);
     if (listeners) {
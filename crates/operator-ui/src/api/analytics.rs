@@ -0,0 +1,104 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde_json::Value;
+
+use crate::state::AppState;
+
+/// Gateway refusals carry the reason, e.g. which zone is invalid; pass it on
+async fn gateway_error(response: reqwest::Response) -> (StatusCode, Json<Value>) {
+    let status = response.status();
+    let body = response
+        .json::<Value>()
+        .await
+        .ok()
+        .filter(|body| body.get("error").is_some())
+        .unwrap_or_else(|| serde_json::json!({"error": "Admin gateway error"}));
+    (status, Json(body))
+}
+
+fn unavailable() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({"error": "Admin gateway unavailable"})),
+    )
+}
+
+pub async fn list_camera_analytics(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Value>>, (StatusCode, Json<Value>)> {
+    let url = format!("{}/v1/analytics/cameras", state.config.admin_gateway_url);
+
+    match state.http_client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => {
+            match response.json::<Vec<Value>>().await {
+                Ok(cameras) => Ok(Json(cameras)),
+                Err(_) => Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"error": "Failed to parse response"})),
+                )),
+            }
+        }
+        Ok(response) => Err(gateway_error(response).await),
+        Err(_) => Err(unavailable()),
+    }
+}
+
+pub async fn get_camera_analytics(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let url = format!("{}/v1/analytics/cameras/{}", state.config.admin_gateway_url, id);
+
+    match state.http_client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => {
+            match response.json::<Value>().await {
+                Ok(camera) => Ok(Json(camera)),
+                Err(_) => Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"error": "Failed to parse response"})),
+                )),
+            }
+        }
+        Ok(response) => Err(gateway_error(response).await),
+        Err(_) => Err(unavailable()),
+    }
+}
+
+pub async fn save_camera_analytics(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(analytics): Json<Value>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let url = format!("{}/v1/analytics/cameras/{}", state.config.admin_gateway_url, id);
+
+    match state.http_client.put(&url).json(&analytics).send().await {
+        Ok(response) if response.status().is_success() => {
+            match response.json::<Value>().await {
+                Ok(camera) => Ok(Json(camera)),
+                Err(_) => Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"error": "Failed to parse response"})),
+                )),
+            }
+        }
+        Ok(response) => Err(gateway_error(response).await),
+        Err(_) => Err(unavailable()),
+    }
+}
+
+pub async fn delete_camera_analytics(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let url = format!("{}/v1/analytics/cameras/{}", state.config.admin_gateway_url, id);
+
+    match state.http_client.delete(&url).send().await {
+        Ok(response) if response.status().is_success() => Ok(Json(serde_json::json!({
+            "success": true,
+            "message": "Camera analytics removed"
+        }))),
+        Ok(response) => Err(gateway_error(response).await),
+        Err(_) => Err(unavailable()),
+    }
+}
@@ -0,0 +1,133 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cobrowse::{CoBrowseError, CoBrowseSession, PlaybackControl, PlaybackSourceKind, PlaybackState};
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    pub title: String,
+    pub owner: String,
+    pub incident_id: Option<String>,
+    pub source_kind: PlaybackSourceKind,
+    pub source_id: String,
+    #[serde(default)]
+    pub position_ms: i64,
+    #[serde(default)]
+    pub paused: bool,
+    pub rate: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ControlRequest {
+    pub owner_token: String,
+    #[serde(flatten)]
+    pub control: PlaybackControl,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EndSessionRequest {
+    pub owner_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateSessionResponse {
+    pub session: CoBrowseSession,
+    /// Needed to control playback and end the session; only returned here
+    pub owner_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub session: CoBrowseSession,
+}
+
+fn error_response(err: CoBrowseError) -> (StatusCode, Json<Value>) {
+    let status = match err {
+        CoBrowseError::NotFound => StatusCode::NOT_FOUND,
+        CoBrowseError::NotOwner => StatusCode::FORBIDDEN,
+        CoBrowseError::Full => StatusCode::CONFLICT,
+        CoBrowseError::InvalidControl(_) => StatusCode::BAD_REQUEST,
+    };
+    (status, Json(serde_json::json!({"error": err.to_string()})))
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<CoBrowseSession>>, (StatusCode, Json<Value>)> {
+    let store = state.cobrowse_store.read().await;
+    let sessions = store.list().into_iter().cloned().collect();
+    Ok(Json(sessions))
+}
+
+pub async fn create_session(
+    State(state): State<AppState>,
+    Json(req): Json<CreateSessionRequest>,
+) -> Result<Json<CreateSessionResponse>, (StatusCode, Json<Value>)> {
+    if req.owner.trim().is_empty() || req.source_id.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": "owner and source_id are required"})),
+        ));
+    }
+    let rate = req.rate.unwrap_or(1.0);
+    if !(0.1..=16.0).contains(&rate) {
+        return Err(error_response(CoBrowseError::InvalidControl(
+            "rate must be between 0.1 and 16".to_string(),
+        )));
+    }
+    let playback = PlaybackState {
+        source_kind: req.source_kind,
+        source_id: req.source_id,
+        position_ms: req.position_ms,
+        paused: req.paused,
+        rate,
+        updated_at: chrono::Utc::now(),
+    };
+
+    let mut store = state.cobrowse_store.write().await;
+    let (session, owner_token) = store.create(req.title, req.owner, req.incident_id, playback);
+
+    Ok(Json(CreateSessionResponse { session, owner_token }))
+}
+
+pub async fn get_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionResponse>, (StatusCode, Json<Value>)> {
+    let store = state.cobrowse_store.read().await;
+
+    match store.get(&id) {
+        Some(session) => Ok(Json(SessionResponse {
+            session: session.clone(),
+        })),
+        None => Err(error_response(CoBrowseError::NotFound)),
+    }
+}
+
+pub async fn control_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<ControlRequest>,
+) -> Result<Json<PlaybackState>, (StatusCode, Json<Value>)> {
+    let mut store = state.cobrowse_store.write().await;
+    store
+        .control(&id, &req.owner_token, req.control)
+        .map(Json)
+        .map_err(error_response)
+}
+
+pub async fn end_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<EndSessionRequest>,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let mut store = state.cobrowse_store.write().await;
+    store
+        .end(&id, &req.owner_token)
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(error_response)
+}
@@ -1,5 +1,7 @@
 pub mod ai;
 pub mod alerts;
+pub mod analytics;
+pub mod cobrowse;
 pub mod dashboard;
 pub mod devices;
 pub mod health;
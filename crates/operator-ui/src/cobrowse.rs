@@ -0,0 +1,310 @@
+//! Co-browsing sessions for investigations.
+//!
+//! An investigator shares what they are watching (a live stream or a
+//! recording, the timeline position, paused or playing, the rate) as a
+//! session. Operators joining it over the WebSocket channel get the current
+//! state and then every change the owner makes. Only the owner, who holds the
+//! token returned when the session was created, controls playback.
+//!
+//! Positions are timeline milliseconds. While playing, followers advance the
+//! position from `updated_at` at `rate` themselves, so only changes are sent.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Sessions kept at once; idle ones are dropped first
+pub const MAX_SESSIONS: usize = 256;
+/// Operators in one session, the owner included
+pub const MAX_PARTICIPANTS: usize = 64;
+/// Sessions without a change for this long are dropped
+const SESSION_IDLE_HOURS: i64 = 12;
+/// Events buffered per session for slow participants
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackSourceKind {
+    Stream,
+    Recording,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackState {
+    pub source_kind: PlaybackSourceKind,
+    /// Stream or recording id
+    pub source_id: String,
+    /// Timeline position at `updated_at`
+    pub position_ms: i64,
+    pub paused: bool,
+    pub rate: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PlaybackState {
+    /// Position now, advanced from `updated_at` while playing
+    pub fn current_position_ms(&self) -> i64 {
+        if self.paused {
+            return self.position_ms;
+        }
+        let elapsed = (Utc::now() - self.updated_at).num_milliseconds().max(0);
+        self.position_ms + (elapsed as f64 * self.rate) as i64
+    }
+
+    /// Apply an owner's control; the position is settled first so a pause or
+    /// rate change keeps the frame everyone is looking at
+    pub fn apply(&mut self, control: &PlaybackControl) -> Result<(), CoBrowseError> {
+        let position_ms = self.current_position_ms();
+        match control {
+            PlaybackControl::Seek { position_ms } => self.position_ms = *position_ms,
+            PlaybackControl::Pause { position_ms: at } => {
+                self.position_ms = at.unwrap_or(position_ms);
+                self.paused = true;
+            }
+            PlaybackControl::Play => {
+                self.position_ms = position_ms;
+                self.paused = false;
+            }
+            PlaybackControl::Rate { rate } => {
+                if !(0.1..=16.0).contains(rate) {
+                    return Err(CoBrowseError::InvalidControl(
+                        "rate must be between 0.1 and 16".to_string(),
+                    ));
+                }
+                self.position_ms = position_ms;
+                self.rate = *rate;
+            }
+            PlaybackControl::Load {
+                source_kind,
+                source_id,
+                position_ms,
+            } => {
+                if source_id.is_empty() {
+                    return Err(CoBrowseError::InvalidControl("source_id is required".to_string()));
+                }
+                self.source_kind = *source_kind;
+                self.source_id = source_id.clone();
+                self.position_ms = *position_ms;
+            }
+        }
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+}
+
+/// A change of the shared playback, made by the owner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlaybackControl {
+    Seek { position_ms: i64 },
+    Pause { position_ms: Option<i64> },
+    Play,
+    Rate { rate: f64 },
+    /// Switch to another stream or recording
+    Load {
+        source_kind: PlaybackSourceKind,
+        source_id: String,
+        position_ms: i64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoBrowseSession {
+    pub id: String,
+    pub title: String,
+    pub owner: String,
+    /// Incident under investigation, if any
+    pub incident_id: Option<String>,
+    pub playback: PlaybackState,
+    /// Operators currently joined
+    pub participants: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Sent to everyone joined to a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// Full state, sent on join and after a participant missed events
+    Snapshot { session: CoBrowseSession },
+    Control {
+        control: PlaybackControl,
+        playback: PlaybackState,
+    },
+    Joined { operator: String },
+    Left { operator: String },
+    Ended,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CoBrowseError {
+    #[error("session not found")]
+    NotFound,
+    #[error("only the session owner controls playback")]
+    NotOwner,
+    #[error("session has {MAX_PARTICIPANTS} participants")]
+    Full,
+    #[error("invalid control: {0}")]
+    InvalidControl(String),
+}
+
+struct SessionEntry {
+    session: CoBrowseSession,
+    owner_token: String,
+    events: broadcast::Sender<SessionEvent>,
+}
+
+impl SessionEntry {
+    fn is_owner(&self, token: &str) -> bool {
+        let (a, b) = (self.owner_token.as_bytes(), token.as_bytes());
+        a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+}
+
+#[derive(Default)]
+pub struct CoBrowseStore {
+    sessions: HashMap<String, SessionEntry>,
+}
+
+impl CoBrowseStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a session; returns it with the owner token
+    pub fn create(
+        &mut self,
+        title: String,
+        owner: String,
+        incident_id: Option<String>,
+        playback: PlaybackState,
+    ) -> (CoBrowseSession, String) {
+        self.prune();
+        let session = CoBrowseSession {
+            id: Uuid::new_v4().to_string(),
+            title,
+            owner,
+            incident_id,
+            playback,
+            participants: Vec::new(),
+            created_at: Utc::now(),
+        };
+        let owner_token = Uuid::new_v4().simple().to_string();
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        self.sessions.insert(
+            session.id.clone(),
+            SessionEntry {
+                session: session.clone(),
+                owner_token: owner_token.clone(),
+                events,
+            },
+        );
+        (session, owner_token)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&CoBrowseSession> {
+        self.sessions.get(id).map(|entry| &entry.session)
+    }
+
+    pub fn list(&self) -> Vec<&CoBrowseSession> {
+        let mut sessions: Vec<&CoBrowseSession> =
+            self.sessions.values().map(|entry| &entry.session).collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        sessions
+    }
+
+    /// Add an operator and subscribe them to the session's events
+    pub fn join(
+        &mut self,
+        id: &str,
+        operator: &str,
+    ) -> Result<(CoBrowseSession, broadcast::Receiver<SessionEvent>), CoBrowseError> {
+        let entry = self.sessions.get_mut(id).ok_or(CoBrowseError::NotFound)?;
+        if !entry.session.participants.iter().any(|p| p == operator) {
+            if entry.session.participants.len() >= MAX_PARTICIPANTS {
+                return Err(CoBrowseError::Full);
+            }
+            entry.session.participants.push(operator.to_string());
+            let _ = entry.events.send(SessionEvent::Joined {
+                operator: operator.to_string(),
+            });
+        }
+        Ok((entry.session.clone(), entry.events.subscribe()))
+    }
+
+    pub fn leave(&mut self, id: &str, operator: &str) {
+        let Some(entry) = self.sessions.get_mut(id) else {
+            return;
+        };
+        let before = entry.session.participants.len();
+        entry.session.participants.retain(|p| p != operator);
+        if entry.session.participants.len() != before {
+            let _ = entry.events.send(SessionEvent::Left {
+                operator: operator.to_string(),
+            });
+        }
+    }
+
+    /// Apply an owner's control and send it to the participants
+    pub fn control(
+        &mut self,
+        id: &str,
+        owner_token: &str,
+        control: PlaybackControl,
+    ) -> Result<PlaybackState, CoBrowseError> {
+        let entry = self.sessions.get_mut(id).ok_or(CoBrowseError::NotFound)?;
+        if !entry.is_owner(owner_token) {
+            return Err(CoBrowseError::NotOwner);
+        }
+        entry.session.playback.apply(&control)?;
+        let playback = entry.session.playback.clone();
+        let _ = entry.events.send(SessionEvent::Control {
+            control,
+            playback: playback.clone(),
+        });
+        Ok(playback)
+    }
+
+    /// End a session; joined operators get `ended`
+    pub fn end(&mut self, id: &str, owner_token: &str) -> Result<(), CoBrowseError> {
+        let entry = self.sessions.get(id).ok_or(CoBrowseError::NotFound)?;
+        if !entry.is_owner(owner_token) {
+            return Err(CoBrowseError::NotOwner);
+        }
+        if let Some(entry) = self.sessions.remove(id) {
+            let _ = entry.events.send(SessionEvent::Ended);
+        }
+        Ok(())
+    }
+
+    /// Drop idle sessions, then the oldest ones while at capacity
+    fn prune(&mut self) {
+        let cutoff = Utc::now() - chrono::Duration::hours(SESSION_IDLE_HOURS);
+        let idle: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, e)| e.session.playback.updated_at < cutoff)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in idle {
+            if let Some(entry) = self.sessions.remove(&id) {
+                let _ = entry.events.send(SessionEvent::Ended);
+            }
+        }
+        while self.sessions.len() >= MAX_SESSIONS {
+            let Some(oldest) = self
+                .sessions
+                .values()
+                .min_by_key(|e| e.session.playback.updated_at)
+                .map(|e| e.session.id.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.sessions.remove(&oldest) {
+                let _ = entry.events.send(SessionEvent::Ended);
+            }
+        }
+    }
+}
@@ -3,6 +3,7 @@ use reqwest::Client;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::cobrowse::CoBrowseStore;
 use crate::config::Config;
 use crate::incident::IncidentStore;
 
@@ -11,6 +12,7 @@ pub struct AppState {
     pub config: Config,
     pub http_client: Client,
     pub incident_store: Arc<RwLock<IncidentStore>>,
+    pub cobrowse_store: Arc<RwLock<CoBrowseStore>>,
 }
 
 impl AppState {
@@ -20,11 +22,13 @@ impl AppState {
             .build()?;
 
         let incident_store = Arc::new(RwLock::new(IncidentStore::new()));
+        let cobrowse_store = Arc::new(RwLock::new(CoBrowseStore::new()));
 
         Ok(Self {
             config,
             http_client,
             incident_store,
+            cobrowse_store,
         })
     }
 }
@@ -7,12 +7,21 @@ use axum::{
 };
 use futures::{sink::SinkExt, stream::StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
 use tokio::time;
 use tracing::{error, info};
 
+use crate::cobrowse::{PlaybackControl, SessionEvent};
 use crate::state::AppState;
 
+/// Messages queued per connection before updates are dropped
+const OUTGOING_BUFFER: usize = 64;
+/// Co-browsing sessions one connection can follow at once
+const MAX_JOINED_SESSIONS: usize = 8;
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum WsMessage {
@@ -22,6 +31,22 @@ pub enum WsMessage {
     Unsubscribe { topics: Vec<String> },
     Update { topic: String, data: serde_json::Value },
     Error { message: String },
+    /// Follow a co-browsing session
+    JoinSession { session_id: String, operator: String },
+    LeaveSession { session_id: String },
+    /// Owner's playback change, same as `POST /api/cobrowse/sessions/:id/control`
+    SessionControl {
+        session_id: String,
+        owner_token: String,
+        control: PlaybackControl,
+    },
+    /// Event of a joined session
+    Session { session_id: String, event: SessionEvent },
+}
+
+struct JoinedSession {
+    operator: String,
+    forwarder: JoinHandle<()>,
 }
 
 pub async fn ws_handler(
@@ -33,25 +58,36 @@ pub async fn ws_handler(
 
 async fn handle_socket(socket: WebSocket, state: AppState) {
     let (mut sender, mut receiver) = socket.split();
+    let (outgoing, mut outgoing_rx) = mpsc::channel::<WsMessage>(OUTGOING_BUFFER);
+
+    // Single writer for dashboard updates and session events
+    let send_task = tokio::spawn(async move {
+        while let Some(msg) = outgoing_rx.recv().await {
+            if let Ok(json) = serde_json::to_string(&msg) {
+                if sender.send(Message::Text(json)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
 
     // Spawn a task to send periodic updates
+    let update_state = state.clone();
+    let update_outgoing = outgoing.clone();
     let mut update_interval = time::interval(Duration::from_secs(5));
-    let send_task = tokio::spawn(async move {
+    let update_task = tokio::spawn(async move {
         loop {
             update_interval.tick().await;
 
             // Send dashboard stats update
-            match fetch_dashboard_update(&state).await {
+            match fetch_dashboard_update(&update_state).await {
                 Ok(update) => {
                     let msg = WsMessage::Update {
                         topic: "dashboard".to_string(),
                         data: serde_json::to_value(update).unwrap_or_default(),
                     };
-
-                    if let Ok(json) = serde_json::to_string(&msg) {
-                        if sender.send(Message::Text(json)).await.is_err() {
-                            break;
-                        }
+                    if update_outgoing.send(msg).await.is_err() {
+                        break;
                     }
                 }
                 Err(e) => {
@@ -62,39 +98,126 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     });
 
     // Handle incoming messages
-    let recv_task = tokio::spawn(async move {
-        while let Some(Ok(msg)) = receiver.next().await {
-            match msg {
-                Message::Text(text) => {
-                    if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                        match ws_msg {
-                            WsMessage::Ping => {
-                                info!("Received ping");
+    let mut joined: HashMap<String, JoinedSession> = HashMap::new();
+    while let Some(Ok(msg)) = receiver.next().await {
+        match msg {
+            Message::Text(text) => {
+                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
+                    match ws_msg {
+                        WsMessage::Ping => {
+                            info!("Received ping");
+                        }
+                        WsMessage::Subscribe { topics } => {
+                            info!("Client subscribed to topics: {:?}", topics);
+                        }
+                        WsMessage::Unsubscribe { topics } => {
+                            info!("Client unsubscribed from topics: {:?}", topics);
+                        }
+                        WsMessage::JoinSession { session_id, operator } => {
+                            if let Err(message) =
+                                join_session(&state, &outgoing, &mut joined, session_id, operator).await
+                            {
+                                let _ = outgoing.try_send(WsMessage::Error { message });
                             }
-                            WsMessage::Subscribe { topics } => {
-                                info!("Client subscribed to topics: {:?}", topics);
+                        }
+                        WsMessage::LeaveSession { session_id } => {
+                            if let Some(session) = joined.remove(&session_id) {
+                                session.forwarder.abort();
+                                state.cobrowse_store.write().await.leave(&session_id, &session.operator);
                             }
-                            WsMessage::Unsubscribe { topics } => {
-                                info!("Client unsubscribed from topics: {:?}", topics);
+                        }
+                        WsMessage::SessionControl {
+                            session_id,
+                            owner_token,
+                            control,
+                        } => {
+                            let result = state
+                                .cobrowse_store
+                                .write()
+                                .await
+                                .control(&session_id, &owner_token, control);
+                            if let Err(e) = result {
+                                let _ = outgoing.try_send(WsMessage::Error { message: e.to_string() });
                             }
-                            _ => {}
                         }
+                        _ => {}
                     }
                 }
-                Message::Close(_) => {
-                    info!("Client disconnected");
-                    break;
-                }
-                _ => {}
             }
+            Message::Close(_) => {
+                info!("Client disconnected");
+                break;
+            }
+            _ => {}
         }
-    });
+    }
+
+    update_task.abort();
+    send_task.abort();
+    let mut store = state.cobrowse_store.write().await;
+    for (session_id, session) in joined {
+        session.forwarder.abort();
+        store.leave(&session_id, &session.operator);
+    }
+}
 
-    // Wait for either task to finish
-    tokio::select! {
-        _ = send_task => {},
-        _ = recv_task => {},
+/// Join a session and forward its events, starting with a snapshot
+async fn join_session(
+    state: &AppState,
+    outgoing: &mpsc::Sender<WsMessage>,
+    joined: &mut HashMap<String, JoinedSession>,
+    session_id: String,
+    operator: String,
+) -> Result<(), String> {
+    if operator.trim().is_empty() {
+        return Err("operator is required".to_string());
     }
+    if joined.contains_key(&session_id) {
+        return Ok(());
+    }
+    if joined.len() >= MAX_JOINED_SESSIONS {
+        return Err(format!("at most {} sessions can be joined", MAX_JOINED_SESSIONS));
+    }
+    let (session, mut events) = state
+        .cobrowse_store
+        .write()
+        .await
+        .join(&session_id, &operator)
+        .map_err(|e| e.to_string())?;
+    info!(session_id = %session_id, operator = %operator, "operator joined co-browsing session");
+
+    let store = state.cobrowse_store.clone();
+    let outgoing = outgoing.clone();
+    let id = session_id.clone();
+    let forwarder = tokio::spawn(async move {
+        let mut next = Some(SessionEvent::Snapshot { session });
+        loop {
+            let event = match next.take() {
+                Some(event) => event,
+                None => match events.recv().await {
+                    Ok(event) => event,
+                    // Missed events: resync from the current state
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        match store.read().await.get(&id).cloned() {
+                            Some(session) => SessionEvent::Snapshot { session },
+                            None => SessionEvent::Ended,
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => SessionEvent::Ended,
+                },
+            };
+            let ended = matches!(event, SessionEvent::Ended);
+            let msg = WsMessage::Session {
+                session_id: id.clone(),
+                event,
+            };
+            if outgoing.send(msg).await.is_err() || ended {
+                break;
+            }
+        }
+    });
+    joined.insert(session_id, JoinedSession { operator, forwarder });
+    Ok(())
 }
 
 async fn fetch_dashboard_update(state: &AppState) -> anyhow::Result<serde_json::Value> {
@@ -0,0 +1,19 @@
+-- Owning tenant, taken from the app.tenant_id setting of the connection that
+-- creates the session (see common::tenant_rls). Sessions started without a
+-- tenant scope have none and are only visible to unscoped connections.
+ALTER TABLE playback_sessions
+ADD COLUMN tenant_id TEXT DEFAULT NULLIF(current_setting('app.tenant_id', true), '*');
+
+CREATE INDEX idx_playback_sessions_tenant ON playback_sessions(tenant_id);
+
+-- Row-level security: each connection only sees the sessions of the tenant
+-- in app.tenant_id, or every session when it is '*'. Connections without the
+-- setting see nothing. FORCE applies the policy to the table owner too;
+-- superusers still bypass it.
+ALTER TABLE playback_sessions ENABLE ROW LEVEL SECURITY;
+ALTER TABLE playback_sessions FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON playback_sessions
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
@@ -0,0 +1,81 @@
+-- Four-eyes authorization (see common::approvals)
+
+-- Streams/recordings whose playback or export needs a second approver
+CREATE TABLE IF NOT EXISTS approval_rules (
+    rule_id VARCHAR(255) PRIMARY KEY,
+    tenant_id TEXT, -- NULL: applies to every tenant
+    source_pattern VARCHAR(255) NOT NULL, -- stream/recording id, trailing '*' as wildcard
+    actions TEXT[] NOT NULL, -- playback, export
+    description TEXT,
+    created_by VARCHAR(255) NOT NULL,
+    created_at_ms BIGINT NOT NULL
+);
+
+CREATE INDEX idx_approval_rules_tenant ON approval_rules(tenant_id);
+
+-- Held playback/export calls and their decisions
+CREATE TABLE IF NOT EXISTS approval_requests (
+    approval_id VARCHAR(255) PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    action VARCHAR(20) NOT NULL,
+    source_id VARCHAR(255) NOT NULL,
+    details JSONB NOT NULL DEFAULT 'null',
+    reason TEXT,
+    requested_by VARCHAR(255) NOT NULL,
+    requested_by_name VARCHAR(255) NOT NULL,
+    -- pending, approved, rejected, used, expired
+    status VARCHAR(20) NOT NULL,
+    decided_by VARCHAR(255),
+    decided_by_name VARCHAR(255),
+    decision_note TEXT,
+    created_at_ms BIGINT NOT NULL,
+    decided_at_ms BIGINT,
+    expires_at_ms BIGINT NOT NULL
+);
+
+CREATE INDEX idx_approval_requests_tenant ON approval_requests(tenant_id, created_at_ms DESC);
+CREATE INDEX idx_approval_requests_requester ON approval_requests(requested_by, status);
+
+-- Audit trail: requested, approved, rejected, used, denied
+CREATE TABLE IF NOT EXISTS approval_events (
+    id BIGSERIAL PRIMARY KEY,
+    approval_id VARCHAR(255) NOT NULL REFERENCES approval_requests(approval_id) ON DELETE CASCADE,
+    tenant_id TEXT NOT NULL, -- copied from the request
+    event VARCHAR(20) NOT NULL,
+    actor VARCHAR(255) NOT NULL,
+    note TEXT,
+    at_ms BIGINT NOT NULL
+);
+
+CREATE INDEX idx_approval_events_approval ON approval_events(approval_id, at_ms);
+
+-- Row-level security, as for playback_sessions (0003). Global rules are
+-- visible to every tenant.
+ALTER TABLE approval_rules ENABLE ROW LEVEL SECURITY;
+ALTER TABLE approval_rules FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON approval_rules
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id IS NULL
+        OR tenant_id = current_setting('app.tenant_id', true)
+    )
+    WITH CHECK (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
+
+ALTER TABLE approval_requests ENABLE ROW LEVEL SECURITY;
+ALTER TABLE approval_requests FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON approval_requests
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
+
+ALTER TABLE approval_events ENABLE ROW LEVEL SECURITY;
+ALTER TABLE approval_events FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON approval_events
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
@@ -0,0 +1,35 @@
+-- Who viewed, downloaded or exported which recording, and which part of it
+CREATE TABLE IF NOT EXISTS recording_access_log (
+    id VARCHAR(255) PRIMARY KEY,
+    recording_id VARCHAR(255) NOT NULL,
+    action VARCHAR(20) NOT NULL, -- view, download, export
+    -- Tenant of the user; anonymous access falls back to the connection's
+    -- tenant scope as for playback_sessions (0003)
+    tenant_id TEXT DEFAULT NULLIF(current_setting('app.tenant_id', true), '*'),
+    user_id VARCHAR(255),
+    username VARCHAR(255),
+    session_id VARCHAR(255),
+    protocol VARCHAR(20),
+    from_secs DOUBLE PRECISION,
+    to_secs DOUBLE PRECISION,
+    viewed_ranges JSONB NOT NULL DEFAULT '[]',
+    detail JSONB NOT NULL DEFAULT 'null',
+    accessed_at BIGINT NOT NULL,
+    ended_at BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_recording_access_log_recording ON recording_access_log(recording_id, accessed_at DESC);
+CREATE INDEX idx_recording_access_log_session ON recording_access_log(session_id) WHERE session_id IS NOT NULL;
+CREATE INDEX idx_recording_access_log_user ON recording_access_log(user_id, accessed_at DESC);
+CREATE INDEX idx_recording_access_log_tenant ON recording_access_log(tenant_id);
+
+-- Row-level security, as for playback_sessions (0003): tenants only see the
+-- access of their own users
+ALTER TABLE recording_access_log ENABLE ROW LEVEL SECURITY;
+ALTER TABLE recording_access_log FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON recording_access_log
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
@@ -0,0 +1,6 @@
+-- Negotiated protocol of a playback session: what was served, what was
+-- skipped or failed on the client, and the client's report
+ALTER TABLE playback_sessions
+ADD COLUMN delivery JSONB;
+
+CREATE INDEX idx_playback_sessions_delivery_served ON playback_sessions((delivery->>'served')) WHERE delivery IS NOT NULL;
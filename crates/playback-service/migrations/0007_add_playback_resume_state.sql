@@ -0,0 +1,10 @@
+-- State a restarted or replacement playback node needs to resume a session:
+-- low-latency delivery of sessions started with a fixed protocol, and the
+-- view tracker (played ranges and the position playback runs from)
+ALTER TABLE playback_sessions
+ADD COLUMN low_latency BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN view_state JSONB;
+
+-- Sessions a restarted node resumes
+CREATE INDEX idx_playback_sessions_node_active ON playback_sessions(node_id, updated_at)
+    WHERE state IN ('pending', 'starting', 'playing', 'paused', 'seeking');
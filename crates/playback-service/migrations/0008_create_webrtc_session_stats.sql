@@ -0,0 +1,27 @@
+-- Quality of experience of closed WHEP sessions: ICE candidate types, round
+-- trip time, bitrate, packet loss and jitter
+CREATE TABLE IF NOT EXISTS webrtc_session_stats (
+    session_id VARCHAR(255) PRIMARY KEY,
+    resource_id VARCHAR(255) NOT NULL,
+    -- Tenant of the viewer that opened the session, as for
+    -- recording_access_log (0005)
+    tenant_id TEXT DEFAULT NULLIF(current_setting('app.tenant_id', true), '*'),
+    started_at BIGINT NOT NULL,
+    ended_at BIGINT,
+    local_candidate_type VARCHAR(20),
+    remote_candidate_type VARCHAR(20),
+    stats JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_webrtc_session_stats_resource ON webrtc_session_stats(resource_id, started_at DESC);
+CREATE INDEX idx_webrtc_session_stats_tenant ON webrtc_session_stats(tenant_id);
+
+-- Row-level security, as for playback_sessions (0003)
+ALTER TABLE webrtc_session_stats ENABLE ROW LEVEL SECURITY;
+ALTER TABLE webrtc_session_stats FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON webrtc_session_stats
+    USING (
+        current_setting('app.tenant_id', true) = '*'
+        OR tenant_id = current_setting('app.tenant_id', true)
+    );
@@ -0,0 +1,201 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use common::approvals::*;
+use common::auth_middleware::{require_permission, AuthContext};
+use common::tenancy::Tenant;
+use common::timeline::now_ms;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::approvals::Approvals;
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({ "error": message.into() }))).into_response()
+}
+
+fn internal_error(e: anyhow::Error) -> Response {
+    error!(error = %e, "approval store error");
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, "approval store error")
+}
+
+/// Load a request of the caller's tenant; other tenants' are reported missing
+async fn load_request(approvals: &Approvals, tenant: &Tenant, approval_id: &str) -> Result<ApprovalRequest, Response> {
+    match approvals.gate.store().get_request(approval_id).await {
+        Ok(Some(request)) if tenant.can_access(&request.tenant_id) => Ok(request),
+        Ok(_) => Err(error_response(StatusCode::NOT_FOUND, "approval not found")),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+/// Mark a stream or recording as needing a second approver
+pub async fn create_rule(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<AuthContext>,
+    tenant: Tenant,
+    Json(req): Json<CreateApprovalRuleRequest>,
+) -> Result<Json<ApprovalRule>, Response> {
+    require_permission(&auth, MANAGE_PERMISSION)?;
+    req.validate()
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+
+    let rule = ApprovalRule {
+        id: uuid::Uuid::new_v4().to_string(),
+        tenant_id: tenant.filter(req.tenant_id.as_deref()),
+        source_pattern: req.source_pattern.trim().to_string(),
+        actions: req.actions,
+        description: req.description,
+        created_by: auth.username.clone(),
+        created_at_ms: now_ms(),
+    };
+    approvals.gate.store().create_rule(&rule).await.map_err(internal_error)?;
+    info!(rule_id = %rule.id, source_pattern = %rule.source_pattern, actor = %auth.username, "approval rule created");
+    Ok(Json(rule))
+}
+
+/// Rules applying to the caller's tenant, including global ones
+pub async fn list_rules(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<AuthContext>,
+    tenant: Tenant,
+) -> Result<Json<ListApprovalRulesResponse>, Response> {
+    require_permission(&auth, MANAGE_PERMISSION)?;
+    let rules = approvals
+        .gate
+        .store()
+        .list_rules(tenant.filter(None).as_deref())
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(ListApprovalRulesResponse { rules }))
+}
+
+/// Remove a rule. Global rules can only be removed by system admins.
+pub async fn delete_rule(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<AuthContext>,
+    tenant: Tenant,
+    Path(rule_id): Path<String>,
+) -> Result<StatusCode, Response> {
+    require_permission(&auth, MANAGE_PERMISSION)?;
+    let store = approvals.gate.store();
+    let rule = match store.get_rule(&rule_id).await.map_err(internal_error)? {
+        Some(rule) => rule,
+        None => return Err(error_response(StatusCode::NOT_FOUND, "rule not found")),
+    };
+    match rule.tenant_id.as_deref() {
+        Some(owner) if tenant.can_access(owner) => {}
+        None if tenant.is_system_admin => {}
+        None => return Err(error_response(StatusCode::FORBIDDEN, "global rules can only be removed by system admins")),
+        Some(_) => return Err(error_response(StatusCode::NOT_FOUND, "rule not found")),
+    }
+
+    if !store.delete_rule(&rule_id).await.map_err(internal_error)? {
+        return Err(error_response(StatusCode::NOT_FOUND, "rule not found"));
+    }
+    info!(rule_id = %rule_id, actor = %auth.username, "approval rule deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListApprovalsQuery {
+    pub status: Option<ApprovalStatus>,
+    pub tenant_id: Option<String>,
+}
+
+/// Approval requests of the caller's tenant, newest first
+pub async fn list_approvals(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    tenant: Tenant,
+    Query(query): Query<ListApprovalsQuery>,
+) -> Result<Json<ListApprovalRequestsResponse>, Response> {
+    // Stored statuses lag behind expiry, so filter on the effective one
+    let now = now_ms();
+    let requests = approvals
+        .gate
+        .store()
+        .list_requests(tenant.filter(query.tenant_id.as_deref()).as_deref(), None)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|request| request.current(now))
+        .filter(|request| query.status.is_none_or(|status| request.status == status))
+        .collect();
+    Ok(Json(ListApprovalRequestsResponse { approvals: requests }))
+}
+
+pub async fn get_approval(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    tenant: Tenant,
+    Path(approval_id): Path<String>,
+) -> Result<Json<ApprovalRequest>, Response> {
+    let request = load_request(&approvals, &tenant, &approval_id).await?;
+    Ok(Json(request.current(now_ms())))
+}
+
+/// Audit trail of a request
+pub async fn list_approval_events(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    tenant: Tenant,
+    Path(approval_id): Path<String>,
+) -> Result<Json<ListApprovalEventsResponse>, Response> {
+    load_request(&approvals, &tenant, &approval_id).await?;
+    let events = approvals
+        .gate
+        .store()
+        .list_events(&approval_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(ListApprovalEventsResponse { events }))
+}
+
+pub async fn approve(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(approval_id): Path<String>,
+    body: Option<Json<ApprovalDecisionRequest>>,
+) -> Result<Json<ApprovalRequest>, Response> {
+    decide(&approvals, &auth, &approval_id, true, body).await
+}
+
+pub async fn reject(
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(approval_id): Path<String>,
+    body: Option<Json<ApprovalDecisionRequest>>,
+) -> Result<Json<ApprovalRequest>, Response> {
+    decide(&approvals, &auth, &approval_id, false, body).await
+}
+
+async fn decide(
+    approvals: &Approvals,
+    auth: &AuthContext,
+    approval_id: &str,
+    approve: bool,
+    body: Option<Json<ApprovalDecisionRequest>>,
+) -> Result<Json<ApprovalRequest>, Response> {
+    require_permission(auth, APPROVE_PERMISSION)?;
+    load_request(approvals, &Tenant::from_auth(auth), approval_id).await?;
+
+    let note = body.and_then(|Json(body)| body.note);
+    match approvals
+        .gate
+        .decide(auth, approval_id, approve, note)
+        .await
+        .map_err(internal_error)?
+    {
+        Ok(request) => Ok(Json(request)),
+        Err(DecisionError::NotFound) => Err(error_response(StatusCode::NOT_FOUND, "approval not found")),
+        Err(DecisionError::OwnRequest) => Err(error_response(
+            StatusCode::FORBIDDEN,
+            "requests must be decided by someone other than the requester",
+        )),
+        Err(DecisionError::NotPending(status)) => Err(error_response(
+            StatusCode::CONFLICT,
+            format!("approval is {}", status.as_str()),
+        )),
+    }
+}
@@ -1,33 +1,64 @@
+pub mod approval_routes;
 pub mod routes;
 pub mod webrtc_routes;
 
 use axum::{
+    middleware,
     routing::{delete, get, post},
-    Router,
+    Extension, Router,
 };
+use common::auth_middleware::{auth_middleware, AuthMiddlewareConfig};
+use common::openapi::{openapi_routes, OpenApiSpec};
+use common::tenant_rls;
 use std::sync::Arc;
 
+use crate::approvals::Approvals;
 use crate::cache::EdgeCache;
 use crate::playback::PlaybackManager;
 use crate::webrtc::{WebRtcPeerManager, WhepHandler};
 use routes::*;
 
-pub fn create_router(manager: Arc<PlaybackManager>, cache: Arc<EdgeCache>) -> Router {
+pub fn create_router(manager: Arc<PlaybackManager>, cache: Arc<EdgeCache>, approvals: Arc<Approvals>) -> Router {
+    let auth_config = Arc::new(AuthMiddlewareConfig::from_env());
+
     // Create WebRTC peer manager and WHEP handler
     let peer_manager = Arc::new(WebRtcPeerManager::new());
     let whep_handler = Arc::new(WhepHandler::new(peer_manager.clone()));
 
+    // Measure the delivery latency of live sessions and WHEP peers
+    manager.latency().clone().spawn(manager.clone(), peer_manager.clone());
+    // Sample the QoE of WHEP peers and keep it when their connection ends
+    peer_manager.clone().spawn_stats(manager.clone());
+
     // Create app state tuple for WebRTC routes
     let webrtc_state = (manager.clone(), whep_handler);
 
+    // Four-eyes approval API; always authenticated
+    let approval_routes = Router::new()
+        .route("/v1/approvals", get(approval_routes::list_approvals))
+        .route("/v1/approvals/rules", get(approval_routes::list_rules).post(approval_routes::create_rule))
+        .route("/v1/approvals/rules/:rule_id", delete(approval_routes::delete_rule))
+        .route("/v1/approvals/:approval_id", get(approval_routes::get_approval))
+        .route("/v1/approvals/:approval_id/events", get(approval_routes::list_approval_events))
+        .route("/v1/approvals/:approval_id/approve", post(approval_routes::approve))
+        .route("/v1/approvals/:approval_id/reject", post(approval_routes::reject))
+        .layer(middleware::from_fn_with_state(auth_config.clone(), auth_middleware));
+
     Router::new()
+        .merge(openapi_routes(&openapi()))
         .route("/healthz", get(healthz))
         .route("/readyz", get(readyz))
         .route("/v1/playback/start", post(start_playback))
         .route("/v1/playback/stop", post(stop_playback))
+        .route("/v1/playback/fallback", post(fallback_playback))
         .route("/v1/playback/seek", post(seek_playback))
         .route("/v1/playback/control", post(control_playback))
         .route("/v1/playback/sessions", get(list_playback_sessions))
+        .route("/v1/playback/sessions/:session_id", get(get_playback_session))
+        .route("/v1/playback/sessions/:session_id/latency", get(get_session_latency))
+        .route("/v1/playback/sessions/:session_id/stats", get(get_session_stats))
+        .route("/v1/playback/sessions/:session_id/overlays", get(get_session_overlays))
+        .route("/v1/playback/sessions/:session_id/overlays/stream", get(stream_session_overlays))
         .route("/ll-hls/streams/:stream_id/playlist.m3u8", get(serve_ll_hls_playlist))
         // DVR endpoints
         .route("/v1/dvr/window", post(get_dvr_window))
@@ -35,6 +66,8 @@ pub fn create_router(manager: Arc<PlaybackManager>, cache: Arc<EdgeCache>) -> Ro
         .route("/v1/dvr/jump_to_live", post(jump_to_live))
         // Time-axis preview endpoint
         .route("/v1/preview/time_axis", post(get_time_axis_preview))
+        .route("/v1/recordings/:recording_id/clip", get(export_clip))
+        .route("/v1/recordings/:recording_id/access-log", get(get_access_log).post(report_access))
         .with_state(manager)
         // WebRTC WHEP endpoints (with separate state)
         .nest("/whep",
@@ -47,4 +80,55 @@ pub fn create_router(manager: Arc<PlaybackManager>, cache: Arc<EdgeCache>) -> Ro
         // Cache metrics endpoint
         .route("/metrics/cache", get(crate::cache::cache_metrics))
         .with_state(cache)
+        .merge(approval_routes)
+        .layer(Extension(approvals))
+        .layer(Extension(peer_manager))
+        .layer(Extension(auth_config.clone()))
+        // Sessions are stored under the tenant of callers relayed by the
+        // admin-gateway
+        .layer(middleware::from_fn_with_state(
+            auth_config,
+            tenant_rls::tenant_scope_middleware,
+        ))
+}
+
+/// OpenAPI description of the playback-service API
+pub fn openapi() -> OpenApiSpec {
+    OpenApiSpec::new("playback-service", env!("CARGO_PKG_VERSION"))
+        .with_description("HLS, LL-HLS, RTSP and WebRTC playback delivery")
+        .operations(&[
+            ("GET", "/healthz", "health", "Liveness probe"),
+            ("GET", "/readyz", "health", "Readiness probe"),
+            ("POST", "/v1/playback/start", "playback", "Start playback session"),
+            ("POST", "/v1/playback/stop", "playback", "Stop playback session"),
+            ("POST", "/v1/playback/fallback", "playback", "Move a session to the next protocol after a client-side failure"),
+            ("POST", "/v1/playback/seek", "playback", "Seek playback session"),
+            ("POST", "/v1/playback/control", "playback", "Pause/resume playback session"),
+            ("GET", "/v1/playback/sessions", "playback", "List playback sessions"),
+            ("GET", "/v1/playback/sessions/:session_id", "playback", "Get a playback session, resuming it on this node if another node served it"),
+            ("GET", "/v1/playback/sessions/:session_id/latency", "playback", "Latest delivery latency of a live playback or WHEP session against its protocol's budget"),
+"            ("GET", "/v1/playback/sessions/:session_id/stats", "playback", "ICE candidate types, round trip time, bitrate, packet loss and jitter of a live or closed WHEP session"),
+            ("GET", "/v1/playback/sessions/:session_id/overlays", "playback", "Stored AI detections of the HLS segments around a recording session's position"),
+            ("GET", "/v1/playback/sessions/:session_id/overlays/stream", "playback", "Server-sent stream of stored AI detections ahead of a recording session's position"),
+            ("GET", "/ll-hls/streams/:stream_id/playlist.m3u8", "playback", "LL-HLS playlist"),
+            ("POST", "/v1/dvr/window", "dvr", "Get DVR window"),
+            ("POST", "/v1/dvr/seek", "dvr", "Seek within DVR window"),
+            ("POST", "/v1/dvr/jump_to_live", "dvr", "Jump back to live edge"),
+            ("POST", "/v1/preview/time_axis", "preview", "Time-axis preview thumbnails"),
+            ("GET", "/v1/recordings/:recording_id/clip", "export", "Export a time range of a recording as MP4, optionally with AI detection overlays"),
+            ("GET", "/v1/recordings/:recording_id/access-log", "access-log", "Who viewed, downloaded or exported a recording"),
+            ("POST", "/v1/recordings/:recording_id/access-log", "access-log", "Log a download or export of a recording"),
+            ("POST", "/whep/stream/:stream_id", "webrtc", "WHEP offer for live stream"),
+            ("POST", "/whep/recording/:recording_id", "webrtc", "WHEP offer for recording"),
+            ("DELETE", "/whep/session/:session_id", "webrtc", "Close WHEP session"),
+            ("GET", "/metrics/cache", "health", "Edge cache metrics"),
+            ("GET", "/v1/approvals", "approvals", "List approval requests"),
+            ("GET", "/v1/approvals/rules", "approvals", "List four-eyes rules"),
+            ("POST", "/v1/approvals/rules", "approvals", "Require a second approver for a source"),
+            ("DELETE", "/v1/approvals/rules/:rule_id", "approvals", "Delete four-eyes rule"),
+            ("GET", "/v1/approvals/:approval_id", "approvals", "Get approval request"),
+            ("GET", "/v1/approvals/:approval_id/events", "approvals", "Approval audit trail"),
+            ("POST", "/v1/approvals/:approval_id/approve", "approvals", "Approve a held playback or export"),
+            ("POST", "/v1/approvals/:approval_id/reject", "approvals", "Reject a held playback or export"),
+        ])
 }
@@ -1,16 +1,26 @@
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
-    http::StatusCode,
-    Json,
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension, Json,
 };
+use common::approvals::ApprovalAction;
+use common::auth_middleware::{authenticate, AuthContext, AuthMiddlewareConfig};
 use common::playback::*;
 use serde::Deserialize;
+use std::convert::Infallible;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use crate::playback::{BlockingParams, PlaybackManager};
-use crate::preview::{generate_time_axis_preview, PreviewConfig};
+use crate::approvals::Approvals;
+use crate::playback::{BlockingParams, NoPlayableProtocol, NotInFallbackChain, PlaybackManager};
+use crate::preview::{find_recording_path, generate_time_axis_preview, PreviewConfig};
+use crate::webrtc::WebRtcPeerManager;
 
 pub async fn healthz() -> &'static str {
     "ok"
@@ -22,21 +32,97 @@ pub async fn readyz() -> &'static str {
 
 pub async fn start_playback(
     State(manager): State<Arc<PlaybackManager>>,
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<Arc<AuthMiddlewareConfig>>,
+    headers: HeaderMap,
     Json(req): Json<PlaybackStartRequest>,
-) -> Result<Json<PlaybackStartResponse>, StatusCode> {
+) -> Result<Json<PlaybackStartResponse>, Response> {
     info!(session_id = %req.config.session_id, source = %req.config.source_id, "start playback request");
 
-    match manager.start(req.config.clone()).await {
-        Ok(info) => Ok(Json(PlaybackStartResponse {
-            accepted: true,
-            session_id: info.config.session_id,
-            lease_id: info.lease_id,
-            playback_url: info.playback_url,
-            message: Some("Playback session started".to_string()),
-        })),
+    approvals
+        .check(&headers, ApprovalAction::Playback, &req.config.source_id, serde_json::Value::Null)
+        .await?;
+
+    let started = match req.negotiate.clone() {
+        Some(client) => manager.start_negotiated(req.config.clone(), client).await,
+        None => manager.start(req.config.clone()).await,
+    };
+    match started {
+        Ok(info) => {
+            if info.config.source_type == PlaybackSourceType::Recording {
+                let mut entry = access_entry(
+                    caller(&auth, &headers).as_ref(),
+                    &info.config.source_id,
+                    RecordingAccessAction::View,
+                );
+                entry.session_id = Some(info.config.session_id.clone());
+                entry.protocol = Some(info.config.protocol.clone());
+                entry.from_secs = info.config.start_time_secs.or(Some(0.0));
+                manager.record_access(entry).await;
+            }
+            Ok(Json(PlaybackStartResponse {
+                accepted: true,
+                session_id: info.config.session_id,
+                lease_id: info.lease_id,
+                playback_url: info.playback_url,
+                message: Some("Playback session started".to_string()),
+                delivery: info.delivery,
+            }))
+        }
+        Err(e) if e.is::<NoPlayableProtocol>() => {
+            warn!("playback refused: {}", e);
+            Err(no_playable_protocol(e))
+        }
         Err(e) => {
             error!("failed to start playback: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+fn no_playable_protocol(e: anyhow::Error) -> Response {
+    let skipped = e
+        .downcast_ref::<NoPlayableProtocol>()
+        .map(|err| err.skipped.clone())
+        .unwrap_or_default();
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(serde_json::json!({ "error": e.to_string(), "skipped": skipped })),
+    )
+        .into_response()
+}
+
+/// The client could not play the protocol it was served; move the session
+/// to the next one in the fallback chain
+pub async fn fallback_playback(
+    State(manager): State<Arc<PlaybackManager>>,
+    Json(req): Json<PlaybackFallbackRequest>,
+) -> Result<Json<PlaybackFallbackResponse>, Response> {
+    info!(session_id = %req.session_id, reason = %req.reason, "playback fallback request");
+    let session_id = req.session_id.clone();
+
+    match manager.fall_back(req).await {
+        Ok(Some(info)) => match info.delivery {
+            Some(delivery) => Ok(Json(PlaybackFallbackResponse {
+                session_id,
+                playback_url: info.playback_url,
+                delivery,
+            })),
+            None => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        },
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) if e.is::<NoPlayableProtocol>() => {
+            warn!(session_id = %session_id, "no protocol left to fall back to: {}", e);
+            Err(no_playable_protocol(e))
+        }
+        Err(e) if e.is::<NotInFallbackChain>() => Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response()),
+        Err(e) => {
+            error!(session_id = %session_id, "playback fallback failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
         }
     }
 }
@@ -116,6 +202,283 @@ pub async fn list_playback_sessions(
     Json(PlaybackListResponse { sessions })
 }
 
+/// A session by id, taking it over from the store when another node served
+/// it; players re-read `playback_url` here after a failover
+pub async fn get_playback_session(
+    State(manager): State<Arc<PlaybackManager>>,
+    Path(session_id): Path<String>,
+) -> Result<Json<PlaybackInfo>, StatusCode> {
+    manager.get(&session_id).await.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Latest delivery latency of a live session; WHEP session ids are accepted
+/// too. 404 until the session has been measured (recordings never are).
+pub async fn get_session_latency(
+    State(manager): State<Arc<PlaybackManager>>,
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionLatency>, StatusCode> {
+    manager
+        .latency()
+        .get(&session_id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Quality of experience of a WHEP session: measured so far while it is
+/// live, its stored summary once it closed
+pub async fn get_session_stats(
+    State(manager): State<Arc<PlaybackManager>>,
+    Extension(peers): Extension<Arc<WebRtcPeerManager>>,
+    Path(session_id): Path<String>,
+) -> Result<Json<WebRtcSessionStats>, StatusCode> {
+    if let Some(stats) = peers.stats(&session_id).await {
+        return Ok(Json(stats));
+    }
+    match manager.webrtc_stats(&session_id).await {
+        Ok(Some(stats)) => Ok(Json(stats)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!(session_id = %session_id, error = %e, "failed to load WebRTC session stats");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// === Playback Overlays ===
+
+/// How often an overlay stream checks where the player is
+const OVERLAY_STREAM_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Stored AI detections of the HLS segments around a recording session's
+/// position, for the player to draw over the video. The player should pass
+/// its own `position_secs`; the session's estimate drifts with buffering.
+pub async fn get_session_overlays(
+    State(manager): State<Arc<PlaybackManager>>,
+    Extension(auth): Extension<Arc<AuthMiddlewareConfig>>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Query(query): Query<PlaybackOverlayQuery>,
+) -> Result<Json<PlaybackOverlayWindow>, Response> {
+    query
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    let (info, position) = overlay_session(&manager, &session_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let position = query.position_secs.or(position).unwrap_or(0.0);
+    let window = crate::overlay::segment_window(position, &query);
+    overlay_window(&info, position, &window, &query, caller(&auth, &headers).as_ref(), &auth)
+        .await
+        .map(Json)
+        .map_err(IntoResponse::into_response)
+}
+
+/// Follow a recording session and send an `overlays` server-sent event with
+/// the stored detections of the next segments before the player reaches
+/// them. Nothing is sent while paused; a seek starts a new window. The
+/// stream ends with the session.
+pub async fn stream_session_overlays(
+    State(manager): State<Arc<PlaybackManager>>,
+    Extension(auth): Extension<Arc<AuthMiddlewareConfig>>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Query(query): Query<PlaybackOverlayQuery>,
+) -> Result<Response, Response> {
+    query
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    overlay_session(&manager, &session_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    // The relayed identity is short-lived, so it is issued again per lookup
+    let caller = caller(&auth, &headers);
+
+    let stream = futures::stream::unfold(None::<ClipExportQuery>, move |sent| {
+        let manager = manager.clone();
+        let auth = auth.clone();
+        let caller = caller.clone();
+        let session_id = session_id.clone();
+        let query = query.clone();
+        async move {
+            loop {
+                let (info, position) = manager.position(&session_id).await?;
+                if !info.state.is_active() {
+                    return None;
+                }
+                let next = (info.state != PlaybackState::Paused)
+                    .then(|| crate::overlay::next_window(position.unwrap_or(0.0), sent.as_ref(), &query))
+                    .flatten()
+                    .filter(|window| info.duration_secs.is_none_or(|d| window.start_secs < d));
+                if let Some(window) = next {
+                    let position = position.unwrap_or(0.0);
+                    match overlay_window(&info, position, &window, &query, caller.as_ref(), &auth).await {
+                        Ok(body) => {
+                            let event = Event::default()
+                                .event("overlays")
+                                .json_data(&body)
+                                .unwrap_or_else(|_| Event::default().comment("unserializable overlay window"));
+                            return Some((Ok::<_, Infallible>(event), Some(window)));
+                        }
+                        Err((_, e)) => warn!(session_id = %session_id, error = %e, "overlay lookup failed, retrying"),
+                    }
+                }
+                tokio::time::sleep(OVERLAY_STREAM_POLL).await;
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()).into_response())
+}
+
+/// A recording session and its estimated position
+async fn overlay_session(
+    manager: &PlaybackManager,
+    session_id: &str,
+) -> Result<(PlaybackInfo, Option<f64>), (StatusCode, String)> {
+    let (info, position) = manager
+        .position(session_id)
+        .await
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Session not found: {}", session_id)))?;
+    if info.config.source_type != PlaybackSourceType::Recording {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "overlays are only available for recording sessions".to_string(),
+        ));
+    }
+    Ok((info, position))
+}
+
+/// Stored detections of `window` of the session's recording
+async fn overlay_window(
+    info: &PlaybackInfo,
+    position_secs: f64,
+    window: &ClipExportQuery,
+    query: &PlaybackOverlayQuery,
+    caller: Option<&AuthContext>,
+    auth: &AuthMiddlewareConfig,
+) -> Result<PlaybackOverlayWindow, (StatusCode, String)> {
+    let recording_id = &info.config.source_id;
+    let (recorder_url, identity) = detection_source(caller, auth)?;
+    let detections = crate::overlay::fetch_detections(&recorder_url, identity, recording_id, window)
+        .await
+        .map_err(|e| {
+            error!(recording_id = %recording_id, error = %e, "failed to fetch detections for playback overlay");
+            (StatusCode::BAD_GATEWAY, "failed to fetch stored detections".to_string())
+        })?;
+
+    Ok(PlaybackOverlayWindow {
+        session_id: info.config.session_id.clone(),
+        recording_id: recording_id.clone(),
+        position_secs,
+        from_secs: window.start_secs,
+        to_secs: window.end_secs,
+        segment_secs: crate::overlay::SEGMENT_SECS,
+        recording_started_at: detections.started_at,
+        detections: crate::overlay::select(detections.detections, query),
+        truncated: detections.truncated,
+    })
+}
+
+// === Recording Access Log ===
+
+const DEFAULT_ACCESS_LOG_LIMIT: i64 = 100;
+const MAX_ACCESS_LOG_LIMIT: i64 = 1000;
+
+/// Identity of the caller, if it presented credentials; invalid ones were
+/// already refused by the tenant scope middleware
+pub(crate) fn caller(auth: &AuthMiddlewareConfig, headers: &HeaderMap) -> Option<AuthContext> {
+    authenticate(headers, &auth.jwt_secret).ok()
+}
+
+/// New access log entry for the caller, accessed now
+pub(crate) fn access_entry(
+    caller: Option<&AuthContext>,
+    recording_id: &str,
+    action: RecordingAccessAction,
+) -> RecordingAccessEntry {
+    RecordingAccessEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        recording_id: recording_id.to_string(),
+        action,
+        tenant_id: caller.map(|ctx| ctx.tenant_id.clone()),
+        user_id: caller.map(|ctx| ctx.user_id.clone()),
+        username: caller.map(|ctx| ctx.username.clone()),
+        session_id: None,
+        protocol: None,
+        from_secs: None,
+        to_secs: None,
+        viewed_ranges: Vec::new(),
+        detail: serde_json::Value::Null,
+        accessed_at: common::validation::safe_unix_timestamp(),
+        ended_at: None,
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AccessLogQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Who viewed, downloaded or exported a recording. Tenants only see their
+/// own users' access; system admins see all of it.
+pub async fn get_access_log(
+    State(manager): State<Arc<PlaybackManager>>,
+    Extension(auth): Extension<Arc<AuthMiddlewareConfig>>,
+    headers: HeaderMap,
+    Path(recording_id): Path<String>,
+    Query(query): Query<AccessLogQuery>,
+) -> Result<Json<RecordingAccessLogResponse>, StatusCode> {
+    if caller(&auth, &headers).is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_ACCESS_LOG_LIMIT)
+        .clamp(1, MAX_ACCESS_LOG_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    match manager.access_log(&recording_id, limit, offset).await {
+        Ok(Some((entries, total))) => Ok(Json(RecordingAccessLogResponse {
+            recording_id,
+            entries,
+            total,
+        })),
+        Ok(None) => Err(StatusCode::SERVICE_UNAVAILABLE),
+        Err(e) => {
+            error!(recording_id = %recording_id, error = %e, "failed to read access log");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Log a download or export handed out by another service, attributed to
+/// the request's identity
+pub async fn report_access(
+    State(manager): State<Arc<PlaybackManager>>,
+    Extension(auth): Extension<Arc<AuthMiddlewareConfig>>,
+    headers: HeaderMap,
+    Path(recording_id): Path<String>,
+    Json(report): Json<RecordingAccessReport>,
+) -> StatusCode {
+    let Some(ctx) = caller(&auth, &headers) else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    if report.action == RecordingAccessAction::View {
+        // Views are logged by the playback sessions themselves
+        return StatusCode::BAD_REQUEST;
+    }
+    info!(recording_id = %recording_id, action = report.action.as_str(), user = %ctx.username, "recording access reported");
+
+    let mut entry = access_entry(Some(&ctx), &recording_id, report.action);
+    entry.from_secs = report.from_secs;
+    entry.to_secs = report.to_secs;
+    entry.detail = report.detail;
+    manager.record_access(entry).await;
+    StatusCode::CREATED
+}
+
 /// Query parameters for LL-HLS playlist requests
 #[derive(Debug, Deserialize)]
 pub struct LlHlsPlaylistQuery {
@@ -250,3 +613,169 @@ pub async fn get_time_axis_preview(
         }
     }
 }
+
+// === Clip Export ===
+
+/// Export a time range of a recording as an MP4 download, optionally with
+/// the stored AI detections drawn on
+pub async fn export_clip(
+    State(manager): State<Arc<PlaybackManager>>,
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<Arc<AuthMiddlewareConfig>>,
+    headers: HeaderMap,
+    Path(recording_id): Path<String>,
+    Query(query): Query<ClipExportQuery>,
+    Query(overlay): Query<ClipOverlayQuery>,
+) -> Result<Response, Response> {
+    common::validation::validate_id(&recording_id, "recording_id")
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())?;
+    query
+        .validate()
+        .and_then(|()| overlay.validate())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+
+    // An approval covers only the range it was requested for
+    approvals
+        .check(
+            &headers,
+            ApprovalAction::Export,
+            &recording_id,
+            serde_json::json!({ "start_secs": query.start_secs, "end_secs": query.end_secs }),
+        )
+        .await?;
+
+    let caller = caller(&auth, &headers);
+    let recording = clip_source(&recording_id).map_err(IntoResponse::into_response)?;
+    let (clip, boxes_drawn) = if overlay.overlays {
+        let (clip, boxes) = export_overlay_clip(&recording, &recording_id, &query, &overlay, caller.as_ref(), &auth)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        (clip, Some(boxes))
+    } else {
+        let clip = crate::clip::export_clip(&recording, &query)
+            .await
+            .map_err(|e| clip_failed(&recording_id, e).into_response())?;
+        (clip, None)
+    };
+    let response = clip_download(&clip, &recording_id, &query)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let mut entry = access_entry(caller.as_ref(), &recording_id, RecordingAccessAction::Export);
+    entry.from_secs = Some(query.start_secs);
+    entry.to_secs = Some(query.end_secs);
+    entry.detail = match boxes_drawn {
+        Some(boxes) => serde_json::json!({ "format": "mp4", "overlays": true, "boxes_drawn": boxes }),
+        None => serde_json::json!({ "format": "mp4" }),
+    };
+    manager.record_access(entry).await;
+    Ok(response)
+}
+
+/// How long the identity relayed to recorder-node for a detection lookup
+/// stays valid
+const OVERLAY_IDENTITY_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Recording file a clip is cut from
+fn clip_source(recording_id: &str) -> Result<PathBuf, (StatusCode, String)> {
+    let storage_root = std::env::var("RECORDING_STORAGE_ROOT")
+        .unwrap_or_else(|_| "./data/recordings".to_string());
+    find_recording_path(&PathBuf::from(storage_root), recording_id)
+        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))
+}
+
+fn clip_failed(recording_id: &str, e: anyhow::Error) -> (StatusCode, String) {
+    error!(recording_id = %recording_id, error = %e, "clip export failed");
+    (StatusCode::INTERNAL_SERVER_ERROR, "clip export failed".to_string())
+}
+
+/// Recorder-node (`RECORDER_SERVICE_URL`) holding the stored detections, and
+/// the identity headers to look them up as `caller`
+fn detection_source(
+    caller: Option<&AuthContext>,
+    auth: &AuthMiddlewareConfig,
+) -> Result<(String, HeaderMap), (StatusCode, String)> {
+    let recorder_url = std::env::var("RECORDER_SERVICE_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "overlays need RECORDER_SERVICE_URL".to_string(),
+            )
+        })?;
+    let identity = match caller {
+        Some(ctx) => common::gateway_identity::headers(ctx, &auth.jwt_secret, OVERLAY_IDENTITY_TTL)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        None => HeaderMap::new(),
+    };
+    Ok((recorder_url, identity))
+}
+
+/// Export with the detections stored by recorder-node (`RECORDER_SERVICE_URL`)
+/// drawn on; returns the clip and the number of boxes drawn
+async fn export_overlay_clip(
+    recording: &std::path::Path,
+    recording_id: &str,
+    query: &ClipExportQuery,
+    overlay: &ClipOverlayQuery,
+    caller: Option<&AuthContext>,
+    auth: &AuthMiddlewareConfig,
+) -> Result<(PathBuf, usize), (StatusCode, String)> {
+    let (recorder_url, identity) = detection_source(caller, auth)?;
+    let detections = crate::overlay::fetch_detections(&recorder_url, identity, recording_id, query)
+        .await
+        .map_err(|e| {
+            error!(recording_id = %recording_id, error = %e, "failed to fetch detections for overlay");
+            (StatusCode::BAD_GATEWAY, "failed to fetch stored detections".to_string())
+        })?;
+    if detections.truncated {
+        warn!(recording_id = %recording_id, "too many stored detections, overlay is incomplete");
+    }
+
+    let source = recording.display().to_string();
+    let video_size = tokio::task::spawn_blocking(move || common::frame_extractor::probe_frame_dimensions(&source))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| clip_failed(recording_id, e))?;
+    let boxes = crate::overlay::plan(&detections.detections, query, overlay, video_size);
+    let font_file = std::env::var("OVERLAY_FONT_FILE").ok().filter(|f| !f.is_empty());
+    let graph = crate::overlay::filtergraph(
+        &boxes,
+        overlay.show_labels(),
+        font_file.as_deref().map(std::path::Path::new),
+    );
+
+    let clip = crate::clip::export_overlay_clip(recording, query, &graph)
+        .await
+        .map_err(|e| clip_failed(recording_id, e))?;
+    Ok((clip, boxes.len()))
+}
+
+/// Stream an exported clip as an attachment. The clip is unlinked right
+/// away; the open handle keeps the data readable.
+async fn clip_download(
+    clip: &std::path::Path,
+    recording_id: &str,
+    query: &ClipExportQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let file = tokio::fs::File::open(clip).await;
+    let _ = tokio::fs::remove_file(clip).await;
+    let file = file.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let filename = format!(
+        "{}-{}-{}.mp4",
+        recording_id, query.start_secs as u64, query.end_secs as u64
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "video/mp4".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+    )
+        .into_response())
+}
@@ -1,11 +1,18 @@
 use axum::{
     extract::{Path, State},
     http::{HeaderMap, StatusCode},
-    Json,
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
+use common::approvals::ApprovalAction;
+use common::auth_middleware::AuthMiddlewareConfig;
+use common::playback::{PlaybackProtocol, RecordingAccessAction};
+use common::tenant_rls;
 use std::sync::Arc;
 use tracing::{error, info};
 
+use super::routes::{access_entry, caller};
+use crate::approvals::Approvals;
 use crate::playback::PlaybackManager;
 use crate::webrtc::{WhepHandler, WhepOffer, WhepAnswer};
 
@@ -17,11 +24,17 @@ type AppState = (Arc<PlaybackManager>, Arc<WhepHandler>);
 /// Returns: { "sdp": "...", "session_id": "...", "session_url": "..." }
 pub async fn whep_stream(
     State((manager, whep)): State<AppState>,
+    Extension(approvals): Extension<Arc<Approvals>>,
+    request_headers: HeaderMap,
     Path(stream_id): Path<String>,
     Json(offer): Json<WhepOffer>,
-) -> Result<(StatusCode, HeaderMap, Json<WhepAnswer>), StatusCode> {
+) -> Result<(StatusCode, HeaderMap, Json<WhepAnswer>), Response> {
     info!(stream_id = %stream_id, "WHEP request for stream");
 
+    approvals
+        .check(&request_headers, ApprovalAction::Playback, &stream_id, serde_json::Value::Null)
+        .await?;
+
     // Get base URL from environment
     let base_url = std::env::var("PLAYBACK_SERVICE_URL")
         .unwrap_or_else(|_| "http://localhost:8087".to_string());
@@ -45,7 +58,7 @@ pub async fn whep_stream(
         }
         Err(e) => {
             error!(stream_id = %stream_id, error = %e, "failed to handle WHEP offer for stream");
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
         }
     }
 }
@@ -56,11 +69,18 @@ pub async fn whep_stream(
 /// Returns: { "sdp": "...", "session_id": "...", "session_url": "..." }
 pub async fn whep_recording(
     State((manager, whep)): State<AppState>,
+    Extension(approvals): Extension<Arc<Approvals>>,
+    Extension(auth): Extension<Arc<AuthMiddlewareConfig>>,
+    request_headers: HeaderMap,
     Path(recording_id): Path<String>,
     Json(offer): Json<WhepOffer>,
-) -> Result<(StatusCode, HeaderMap, Json<WhepAnswer>), StatusCode> {
+) -> Result<(StatusCode, HeaderMap, Json<WhepAnswer>), Response> {
     info!(recording_id = %recording_id, "WHEP request for recording");
 
+    approvals
+        .check(&request_headers, ApprovalAction::Playback, &recording_id, serde_json::Value::Null)
+        .await?;
+
     // Get base URL from environment
     let base_url = std::env::var("PLAYBACK_SERVICE_URL")
         .unwrap_or_else(|_| "http://localhost:8087".to_string());
@@ -79,12 +99,22 @@ pub async fn whep_recording(
                 "application/json".parse().unwrap(),
             );
 
+            let mut entry = access_entry(
+                caller(&auth, &request_headers).as_ref(),
+                &recording_id,
+                RecordingAccessAction::View,
+            );
+            entry.session_id = Some(answer.session_id.clone());
+            entry.protocol = Some(PlaybackProtocol::WebRtc);
+            entry.from_secs = Some(0.0);
+            manager.record_access(entry).await;
+
             info!(recording_id = %recording_id, session_id = %answer.session_id, "WHEP session created for recording");
             Ok((StatusCode::CREATED, headers, Json(answer)))
         }
         Err(e) => {
             error!(recording_id = %recording_id, error = %e, "failed to handle WHEP offer for recording");
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
         }
     }
 }
@@ -98,7 +128,12 @@ pub async fn whep_delete_session(
     info!(session_id = %session_id, "deleting WHEP session");
 
     match whep.delete_session(&session_id).await {
-        Ok(_) => {
+        Ok(closed) => {
+            // Kept under the tenant of the viewer that opened the session
+            tenant_rls::scope(closed.tenant, manager.save_webrtc_stats(&closed.stats)).await;
+            // Closes the access entry of a recording session; WHEP reports
+            // no positions, so only the end time is known
+            manager.end_access(&session_id, &[]).await;
             info!(session_id = %session_id, "WHEP session deleted");
             StatusCode::NO_CONTENT
         }
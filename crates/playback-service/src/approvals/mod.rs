@@ -0,0 +1,118 @@
+//! Four-eyes authorization for playback and export (see
+//! [`common::approvals`]). Gated handlers call [`Approvals::check`] before
+//! serving; calls held for a second approver get `202 Accepted` with the
+//! pending request and are retried once it has been approved.
+
+pub mod store;
+
+pub use store::PostgresApprovalStore;
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use common::approvals::{
+    ApprovalAction, ApprovalGate, ApprovalRequiredResponse, ApprovalStore, GateDecision, APPROVAL_HEADER,
+};
+use common::auth_middleware::{authenticate, AuthMiddlewareConfig};
+use common::gateway_identity::IDENTITY_HEADER;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::error;
+
+/// Optional header explaining why a gated call is needed, stored on the
+/// approval request
+pub const REASON_HEADER: &str = "x-approval-reason";
+
+pub struct Approvals {
+    pub gate: ApprovalGate,
+    auth: Arc<AuthMiddlewareConfig>,
+}
+
+impl Approvals {
+    pub fn new(store: Arc<dyn ApprovalStore>, auth: Arc<AuthMiddlewareConfig>) -> Self {
+        Self {
+            gate: ApprovalGate::from_env(store, "playback-service"),
+            auth,
+        }
+    }
+
+    /// Let the call through, or return the response holding or refusing it
+    pub async fn check(
+        &self,
+        headers: &HeaderMap,
+        action: ApprovalAction,
+        source_id: &str,
+        details: Value,
+    ) -> Result<(), Response> {
+        let has_credentials = headers.contains_key(IDENTITY_HEADER)
+            || headers.contains_key(axum::http::header::AUTHORIZATION);
+        if !has_credentials {
+            // Anonymous callers cannot be held for approval, so anything a
+            // rule covers needs credentials
+            let rules = self.gate.store().list_rules(None).await.map_err(internal_error)?;
+            if rules.iter().any(|rule| rule.matches(action, source_id)) {
+                return Err((
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({ "error": "Authentication required for this source" })),
+                )
+                    .into_response());
+            }
+            return Ok(());
+        }
+
+        let caller = authenticate(headers, &self.auth.jwt_secret)?;
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let decision = self
+            .gate
+            .authorize(
+                &caller,
+                action,
+                source_id,
+                details,
+                header(APPROVAL_HEADER),
+                header(REASON_HEADER).map(str::to_string),
+            )
+            .await
+            .map_err(internal_error)?;
+
+        match decision {
+            GateDecision::Allowed => Ok(()),
+            GateDecision::Pending(approval) => Err((
+                StatusCode::ACCEPTED,
+                Json(ApprovalRequiredResponse {
+                    approval_required: true,
+                    message: format!(
+                        "{} of {} requires a second approver; retry once approval {} is granted",
+                        action.as_str(),
+                        source_id,
+                        approval.id
+                    ),
+                    approval,
+                }),
+            )
+                .into_response()),
+            GateDecision::Denied(reason) => Err((
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({ "error": reason })),
+            )
+                .into_response()),
+        }
+    }
+}
+
+fn internal_error(e: anyhow::Error) -> Response {
+    error!(error = %e, "approval check failed");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": "approval check failed" })),
+    )
+        .into_response()
+}
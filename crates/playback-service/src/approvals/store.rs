@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use common::approvals::*;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+/// Approval rules, requests and audit trail in Postgres, so every replica
+/// sees the same decisions
+pub struct PostgresApprovalStore {
+    pool: PgPool,
+}
+
+impl PostgresApprovalStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn ms(value: i64) -> u64 {
+    u64::try_from(value).unwrap_or(0)
+}
+
+fn db_ms(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+fn row_to_rule(row: PgRow) -> Result<ApprovalRule> {
+    let actions: Vec<String> = row.try_get("actions")?;
+    Ok(ApprovalRule {
+        id: row.try_get("rule_id")?,
+        tenant_id: row.try_get("tenant_id")?,
+        source_pattern: row.try_get("source_pattern")?,
+        actions: actions
+            .iter()
+            .map(|a| a.parse().map_err(|e: String| anyhow!(e)))
+            .collect::<Result<_>>()?,
+        description: row.try_get("description")?,
+        created_by: row.try_get("created_by")?,
+        created_at_ms: ms(row.try_get("created_at_ms")?),
+    })
+}
+
+fn row_to_request(row: PgRow) -> Result<ApprovalRequest> {
+    let action: String = row.try_get("action")?;
+    let status: String = row.try_get("status")?;
+    Ok(ApprovalRequest {
+        id: row.try_get("approval_id")?,
+        tenant_id: row.try_get("tenant_id")?,
+        action: action.parse().map_err(|e: String| anyhow!(e))?,
+        source_id: row.try_get("source_id")?,
+        details: row.try_get("details")?,
+        reason: row.try_get("reason")?,
+        requested_by: row.try_get("requested_by")?,
+        requested_by_name: row.try_get("requested_by_name")?,
+        status: status.parse().map_err(|e: String| anyhow!(e))?,
+        decided_by: row.try_get("decided_by")?,
+        decided_by_name: row.try_get("decided_by_name")?,
+        decision_note: row.try_get("decision_note")?,
+        created_at_ms: ms(row.try_get("created_at_ms")?),
+        decided_at_ms: row.try_get::<Option<i64>, _>("decided_at_ms")?.map(ms),
+        expires_at_ms: ms(row.try_get("expires_at_ms")?),
+    })
+}
+
+#[async_trait]
+impl ApprovalStore for PostgresApprovalStore {
+    async fn create_rule(&self, rule: &ApprovalRule) -> Result<()> {
+        let actions: Vec<&str> = rule.actions.iter().map(|a| a.as_str()).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO approval_rules
+                (rule_id, tenant_id, source_pattern, actions, description, created_by, created_at_ms)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(&rule.id)
+        .bind(&rule.tenant_id)
+        .bind(&rule.source_pattern)
+        .bind(&actions)
+        .bind(&rule.description)
+        .bind(&rule.created_by)
+        .bind(db_ms(rule.created_at_ms))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_rule(&self, rule_id: &str) -> Result<Option<ApprovalRule>> {
+        let row = sqlx::query("SELECT * FROM approval_rules WHERE rule_id = $1")
+            .bind(rule_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(row_to_rule).transpose()
+    }
+
+    async fn list_rules(&self, tenant_id: Option<&str>) -> Result<Vec<ApprovalRule>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM approval_rules
+            WHERE $1::text IS NULL OR tenant_id IS NULL OR tenant_id = $1
+            ORDER BY source_pattern
+            "#,
+        )
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter().map(row_to_rule).collect()
+    }
+
+    async fn delete_rule(&self, rule_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM approval_rules WHERE rule_id = $1")
+            .bind(rule_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn create_request(&self, request: &ApprovalRequest) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO approval_requests (
+                approval_id, tenant_id, action, source_id, details, reason,
+                requested_by, requested_by_name, status, created_at_ms, expires_at_ms
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(&request.id)
+        .bind(&request.tenant_id)
+        .bind(request.action.as_str())
+        .bind(&request.source_id)
+        .bind(&request.details)
+        .bind(&request.reason)
+        .bind(&request.requested_by)
+        .bind(&request.requested_by_name)
+        .bind(request.status.as_str())
+        .bind(db_ms(request.created_at_ms))
+        .bind(db_ms(request.expires_at_ms))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_request(&self, approval_id: &str) -> Result<Option<ApprovalRequest>> {
+        let row = sqlx::query("SELECT * FROM approval_requests WHERE approval_id = $1")
+            .bind(approval_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(row_to_request).transpose()
+    }
+
+    async fn list_requests(&self, tenant_id: Option<&str>, status: Option<ApprovalStatus>) -> Result<Vec<ApprovalRequest>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM approval_requests
+            WHERE ($1::text IS NULL OR tenant_id = $1)
+              AND ($2::text IS NULL OR status = $2)
+            ORDER BY created_at_ms DESC
+            LIMIT 1000
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(status.map(|s| s.as_str()))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter().map(row_to_request).collect()
+    }
+
+    async fn open_requests(&self, user_id: &str) -> Result<Vec<ApprovalRequest>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM approval_requests
+            WHERE requested_by = $1 AND status IN ('pending', 'approved')
+            ORDER BY created_at_ms DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter().map(row_to_request).collect()
+    }
+
+    async fn update_request(&self, request: &ApprovalRequest, expected: ApprovalStatus) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE approval_requests
+            SET status = $3, decided_by = $4, decided_by_name = $5, decision_note = $6,
+                decided_at_ms = $7, expires_at_ms = $8
+            WHERE approval_id = $1 AND status = $2
+            "#,
+        )
+        .bind(&request.id)
+        .bind(expected.as_str())
+        .bind(request.status.as_str())
+        .bind(&request.decided_by)
+        .bind(&request.decided_by_name)
+        .bind(&request.decision_note)
+        .bind(request.decided_at_ms.map(db_ms))
+        .bind(db_ms(request.expires_at_ms))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn add_event(&self, event: &ApprovalEvent) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO approval_events (approval_id, tenant_id, event, actor, note, at_ms)
+            SELECT approval_id, tenant_id, $2, $3, $4, $5
+            FROM approval_requests WHERE approval_id = $1
+            "#,
+        )
+        .bind(&event.approval_id)
+        .bind(&event.event)
+        .bind(&event.actor)
+        .bind(&event.note)
+        .bind(db_ms(event.at_ms))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_events(&self, approval_id: &str) -> Result<Vec<ApprovalEvent>> {
+        let rows = sqlx::query(
+            "SELECT approval_id, event, actor, note, at_ms FROM approval_events WHERE approval_id = $1 ORDER BY at_ms, id",
+        )
+        .bind(approval_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(ApprovalEvent {
+                    approval_id: row.try_get("approval_id")?,
+                    event: row.try_get("event")?,
+                    actor: row.try_get("actor")?,
+                    note: row.try_get("note")?,
+                    at_ms: ms(row.try_get("at_ms")?),
+                })
+            })
+            .collect()
+    }
+}
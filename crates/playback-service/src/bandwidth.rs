@@ -0,0 +1,222 @@
+//! Viewing egress metering and remote playback throttling.
+//!
+//! Every HLS response is counted as local or remote egress, depending on
+//! whether the viewer is on a private network. The totals are reported to the
+//! coordinator against the site's uplink budget; while the uplink is over
+//! budget the coordinator hands back a remote playback limit, and remote
+//! requests beyond it get `503` with `Retry-After` so players back off or
+//! drop to a lower rendition. Local viewers are never throttled.
+
+use async_trait::async_trait;
+use axum::{
+    body::HttpBody,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use common::bandwidth::{kbps, BandwidthDirective, BandwidthSource};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Window over which the remote limit is enforced
+const THROTTLE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Counts HLS egress and enforces the coordinator's remote playback limit
+#[derive(Debug)]
+pub struct EgressMeter {
+    remote_bytes: AtomicU64,
+    local_bytes: AtomicU64,
+    /// Remote playback limit in kbit/s; 0 is unlimited
+    remote_limit_kbps: AtomicU64,
+    /// Start of the current throttle window and remote bytes sent in it
+    window: Mutex<(Instant, u64)>,
+}
+
+impl Default for EgressMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EgressMeter {
+    pub fn new() -> Self {
+        Self {
+            remote_bytes: AtomicU64::new(0),
+            local_bytes: AtomicU64::new(0),
+            remote_limit_kbps: AtomicU64::new(0),
+            window: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    pub fn set_remote_limit_kbps(&self, limit: Option<u64>) {
+        self.remote_limit_kbps
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Whether a remote viewer may be sent another response now
+    fn admit_remote(&self, now: Instant) -> bool {
+        let limit_kbps = self.remote_limit_kbps.load(Ordering::Relaxed);
+        if limit_kbps == 0 {
+            return true;
+        }
+        let Ok(mut window) = self.window.lock() else {
+            return true;
+        };
+        if now.duration_since(window.0) >= THROTTLE_WINDOW {
+            *window = (now, 0);
+        }
+        let allowed_bytes = limit_kbps * 1000 / 8 * THROTTLE_WINDOW.as_secs();
+        window.1 < allowed_bytes
+    }
+
+    fn record(&self, remote: bool, bytes: u64) {
+        if remote {
+            self.remote_bytes.fetch_add(bytes, Ordering::Relaxed);
+            if let Ok(mut window) = self.window.lock() {
+                window.1 += bytes;
+            }
+        } else {
+            self.local_bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Bytes sent since the previous call, as `(remote, local)`
+    fn take(&self) -> (u64, u64) {
+        (
+            self.remote_bytes.swap(0, Ordering::Relaxed),
+            self.local_bytes.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
+/// Reports the meter's egress and applies the remote playback limit
+pub struct PlaybackBandwidth(pub Arc<EgressMeter>);
+
+#[async_trait]
+impl BandwidthSource for PlaybackBandwidth {
+    async fn sample(&self, interval: Duration) -> (u64, u64, u64) {
+        let (remote, local) = self.0.take();
+        (0, kbps(remote, interval), kbps(local, interval))
+    }
+
+    async fn apply(&self, directive: &BandwidthDirective) {
+        self.0
+            .set_remote_limit_kbps(directive.remote_playback_limit_kbps);
+    }
+}
+
+fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_loopback() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                // fc00::/7 unique local, fe80::/10 link local
+                || (ip.segments()[0] & 0xfe00) == 0xfc00
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// The viewer's address. `X-Forwarded-For` is only trusted from a proxy on
+/// the local network, so remote viewers cannot claim to be local.
+fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+    let peer = peer?;
+    if !is_local(peer) {
+        return Some(peer);
+    }
+    let forwarded = headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    Some(forwarded.unwrap_or(peer))
+}
+
+/// Middleware metering `/hls` responses; needs the server to provide
+/// `ConnectInfo<SocketAddr>`, otherwise every viewer counts as remote
+pub async fn egress_layer(
+    State(meter): State<Arc<EgressMeter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !req.uri().path().starts_with("/hls/") {
+        return next.run(req).await;
+    }
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let remote = !client_ip(peer, req.headers()).is_some_and(is_local);
+
+    if remote && !meter.admit_remote(Instant::now()) {
+        let mut response = (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "remote playback bandwidth limit reached",
+        )
+            .into_response();
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(THROTTLE_WINDOW.as_secs()),
+        );
+        return response;
+    }
+
+    let response = next.run(req).await;
+    let bytes = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .or_else(|| response.body().size_hint().exact())
+        .unwrap_or(0);
+    meter.record(remote, bytes);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forwarded_for_only_trusted_from_local_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("10.1.2.3, 203.0.113.9"),
+        );
+
+        let proxy: IpAddr = "127.0.0.1".parse().unwrap();
+        assert_eq!(
+            client_ip(Some(proxy), &headers),
+            Some("10.1.2.3".parse().unwrap())
+        );
+
+        let outsider: IpAddr = "198.51.100.7".parse().unwrap();
+        assert_eq!(client_ip(Some(outsider), &headers), Some(outsider));
+        assert!(!is_local(outsider));
+        assert!(is_local("fd00::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_remote_limit_applies_per_window() {
+        let meter = EgressMeter::new();
+        let start = Instant::now();
+        assert!(meter.admit_remote(start));
+
+        // 800 kbit/s over a 5 s window allows 500 kB
+        meter.set_remote_limit_kbps(Some(800));
+        meter.record(true, 400_000);
+        assert!(meter.admit_remote(start));
+        meter.record(true, 200_000);
+        assert!(!meter.admit_remote(start));
+        assert!(meter.admit_remote(start + THROTTLE_WINDOW));
+
+        meter.set_remote_limit_kbps(None);
+        meter.record(true, 10_000_000);
+        assert!(meter.admit_remote(start + THROTTLE_WINDOW));
+        assert_eq!(meter.take(), (10_600_000, 0));
+    }
+}
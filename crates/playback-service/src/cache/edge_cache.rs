@@ -1,5 +1,5 @@
 use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock as SyncRwLock};
 use std::time::{Duration, Instant};
 use bytes::Bytes;
 use tokio::sync::RwLock;
@@ -57,7 +57,7 @@ impl CachedItem {
 
 /// LRU-based edge cache for HLS content
 pub struct EdgeCache {
-    config: CacheConfig,
+    config: SyncRwLock<CacheConfig>,
     /// Cache storage: path -> cached item
     items: Arc<RwLock<HashMap<String, CachedItem>>>,
     /// LRU queue: maintains access order
@@ -80,7 +80,7 @@ pub struct CacheStats {
 impl EdgeCache {
     pub fn new(config: CacheConfig) -> Self {
         Self {
-            config,
+            config: SyncRwLock::new(config),
             items: Arc::new(RwLock::new(HashMap::new())),
             lru_queue: Arc::new(RwLock::new(VecDeque::new())),
             current_size: Arc::new(RwLock::new(0)),
@@ -88,9 +88,32 @@ impl EdgeCache {
         }
     }
 
+    /// Current configuration
+    pub fn config(&self) -> CacheConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Replace the configuration of a running cache. Items over the new
+    /// limits are evicted; disabling the cache drops everything.
+    pub async fn reconfigure(&self, config: CacheConfig) {
+        let enabled = config.enabled;
+        *self
+            .config
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = config;
+        if enabled {
+            self.evict(0, 0).await;
+        } else {
+            self.clear().await;
+        }
+    }
+
     /// Get item from cache
     pub async fn get(&self, key: &str) -> Option<CachedItem> {
-        if !self.config.enabled {
+        if !self.config().enabled {
             return None;
         }
 
@@ -142,14 +165,14 @@ impl EdgeCache {
 
     /// Insert item into cache
     pub async fn insert(&self, key: String, item: CachedItem) {
-        if !self.config.enabled {
+        if !self.config().enabled {
             return;
         }
 
         let item_size = item.size;
 
         // Evict items if necessary
-        self.evict_if_needed(item_size).await;
+        self.evict(1, item_size).await;
 
         let mut items = self.items.write().await;
         let mut queue = self.lru_queue.write().await;
@@ -168,15 +191,17 @@ impl EdgeCache {
         stats.inserts += 1;
     }
 
-    /// Evict items based on LRU policy
-    async fn evict_if_needed(&self, incoming_size: usize) {
+    /// Evict items based on LRU policy until `incoming_items` more items of
+    /// `incoming_size` bytes fit
+    async fn evict(&self, incoming_items: usize, incoming_size: usize) {
+        let config = self.config();
         let mut queue = self.lru_queue.write().await;
         let mut items = self.items.write().await;
         let mut size = self.current_size.write().await;
         let mut stats = self.stats.write().await;
 
         // Evict items if max_items exceeded
-        while queue.len() >= self.config.max_items && !queue.is_empty() {
+        while queue.len() + incoming_items > config.max_items && !queue.is_empty() {
             if let Some(key) = queue.pop_front() {
                 if let Some(item) = items.remove(&key) {
                     *size = size.saturating_sub(item.size);
@@ -186,8 +211,8 @@ impl EdgeCache {
         }
 
         // Evict items if size limit exceeded
-        if self.config.max_size_bytes > 0 {
-            while *size + incoming_size > self.config.max_size_bytes && !queue.is_empty() {
+        if config.max_size_bytes > 0 {
+            while *size + incoming_size > config.max_size_bytes && !queue.is_empty() {
                 if let Some(key) = queue.pop_front() {
                     if let Some(item) = items.remove(&key) {
                         *size = size.saturating_sub(item.size);
@@ -226,10 +251,11 @@ impl EdgeCache {
 
     /// Get TTL for given file path
     pub fn get_ttl_for_path(&self, path: &str) -> Duration {
+        let config = self.config();
         if path.ends_with(".m3u8") {
-            self.config.playlist_ttl
+            config.playlist_ttl
         } else {
-            self.config.segment_ttl
+            config.segment_ttl
         }
     }
 
@@ -405,4 +431,41 @@ mod tests {
         cache.insert("test.ts".to_string(), item).await;
         assert!(cache.get("test.ts").await.is_none());
     }
+
+    #[tokio::test]
+    async fn test_cache_reconfigure() {
+        let cache = EdgeCache::new(CacheConfig::default());
+
+        for i in 0..3 {
+            let item = CachedItem {
+                data: Bytes::from("test"),
+                content_type: "video/mp2t".to_string(),
+                cached_at: Instant::now(),
+                ttl: Duration::from_secs(10),
+                size: 4,
+                etag: format!("\"tag{}\"", i),
+            };
+            cache.insert(format!("seg{}.ts", i), item).await;
+        }
+
+        // Shrinking evicts the least recently used items
+        cache
+            .reconfigure(CacheConfig {
+                max_items: 1,
+                segment_ttl: Duration::from_secs(5),
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(cache.item_count().await, 1);
+        assert!(cache.get("seg2.ts").await.is_some());
+        assert_eq!(cache.get_ttl_for_path("seg3.ts"), Duration::from_secs(5));
+
+        cache
+            .reconfigure(CacheConfig {
+                enabled: false,
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(cache.item_count().await, 0);
+    }
 }
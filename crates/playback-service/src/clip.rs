@@ -0,0 +1,180 @@
+//! Clip export: cut a time range out of a recording without re-encoding,
+//! or re-encoded with AI detection overlays drawn on (see [`crate::overlay`])
+
+use anyhow::{Context, Result};
+use common::playback::ClipExportQuery;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tracing::info;
+
+/// ffmpeg gets this long per clip before it is killed
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// ffmpeg arguments copying `[start, end)` of `input` into an MP4 at `output`
+fn ffmpeg_args(input: &Path, output: &Path, query: &ClipExportQuery) -> Vec<String> {
+    vec![
+        "-loglevel".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        format!("{:.3}", query.start_secs),
+        "-i".to_string(),
+        input.display().to_string(),
+        "-t".to_string(),
+        format!("{:.3}", query.end_secs - query.start_secs),
+        "-c".to_string(),
+        "copy".to_string(),
+        "-movflags".to_string(),
+        "+faststart".to_string(),
+        "-y".to_string(),
+        output.display().to_string(),
+    ]
+}
+
+/// ffmpeg arguments re-encoding `[start, end)` of `input` into an MP4 at
+/// `output` through the filtergraph in `script`, which ends in `[out]`.
+/// Audio is copied when there is any.
+fn overlay_ffmpeg_args(input: &Path, output: &Path, script: &Path, query: &ClipExportQuery) -> Vec<String> {
+    [
+        "-loglevel",
+        "error",
+        "-ss",
+        &format!("{:.3}", query.start_secs),
+        "-i",
+        &input.display().to_string(),
+        "-t",
+        &format!("{:.3}", query.end_secs - query.start_secs),
+        "-filter_complex_script",
+        &script.display().to_string(),
+        "-map",
+        "[out]",
+        "-map",
+        "0:a?",
+        "-c:v",
+        "libx264",
+        "-preset",
+        "veryfast",
+        "-crf",
+        "20",
+        "-pix_fmt",
+        "yuv420p",
+        "-c:a",
+        "copy",
+        "-movflags",
+        "+faststart",
+        "-y",
+        &output.display().to_string(),
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+/// Write the requested range of `recording` to a temporary MP4 and return
+/// its path; the caller removes it
+pub async fn export_clip(recording: &Path, query: &ClipExportQuery) -> Result<PathBuf> {
+    let output = std::env::temp_dir().join(format!("clip-{}.mp4", uuid::Uuid::new_v4()));
+    run_ffmpeg(ffmpeg_args(recording, &output, query), &output).await?;
+
+    info!(
+        recording = %recording.display(),
+        start_secs = query.start_secs,
+        end_secs = query.end_secs,
+        "clip exported"
+    );
+    Ok(output)
+}
+
+/// Like [`export_clip`], with `filtergraph` (from
+/// [`crate::overlay::filtergraph`]) applied to the video
+pub async fn export_overlay_clip(
+    recording: &Path,
+    query: &ClipExportQuery,
+    filtergraph: &str,
+) -> Result<PathBuf> {
+    let id = uuid::Uuid::new_v4();
+    let output = std::env::temp_dir().join(format!("clip-{}.mp4", id));
+    // Thousands of boxes overflow the argument length limit, so the graph
+    // is passed as a file
+    let script = std::env::temp_dir().join(format!("clip-{}.filter", id));
+    tokio::fs::write(&script, filtergraph)
+        .await
+        .context("failed to write overlay filtergraph")?;
+    let result = run_ffmpeg(overlay_ffmpeg_args(recording, &output, &script, query), &output).await;
+    let _ = tokio::fs::remove_file(&script).await;
+    result?;
+
+    info!(
+        recording = %recording.display(),
+        start_secs = query.start_secs,
+        end_secs = query.end_secs,
+        "overlay clip exported"
+    );
+    Ok(output)
+}
+
+/// Run ffmpeg to produce `output`, which is removed again on failure
+async fn run_ffmpeg(args: Vec<String>, output: &Path) -> Result<()> {
+    let status = tokio::time::timeout(
+        EXPORT_TIMEOUT,
+        tokio::process::Command::new("ffmpeg")
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .status(),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("clip export exceeded {}s", EXPORT_TIMEOUT.as_secs()))?
+    .context("failed to execute ffmpeg")?;
+
+    if !status.success() {
+        let _ = tokio::fs::remove_file(output).await;
+        anyhow::bail!("ffmpeg exited with error: {:?}", status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_range_validation() {
+        let query = |start_secs, end_secs| ClipExportQuery { start_secs, end_secs };
+        assert!(query(10.0, 40.0).validate().is_ok());
+        assert!(query(-1.0, 40.0).validate().is_err());
+        assert!(query(40.0, 40.0).validate().is_err());
+        assert!(query(0.0, f64::NAN).validate().is_err());
+        assert!(query(0.0, 7200.0).validate().is_err());
+    }
+
+    #[test]
+    fn test_ffmpeg_args_copy_the_range() {
+        let args = ffmpeg_args(
+            Path::new("/data/rec-1.mp4"),
+            Path::new("/tmp/clip.mp4"),
+            &ClipExportQuery { start_secs: 10.0, end_secs: 40.5 },
+        );
+        let joined = args.join(" ");
+        assert!(joined.contains("-ss 10.000 -i /data/rec-1.mp4 -t 30.500 -c copy"));
+        assert!(joined.ends_with("/tmp/clip.mp4"));
+    }
+
+    #[test]
+    fn test_overlay_args_reencode_through_the_script() {
+        let args = overlay_ffmpeg_args(
+            Path::new("/data/rec-1.mp4"),
+            Path::new("/tmp/clip.mp4"),
+            Path::new("/tmp/clip.filter"),
+            &ClipExportQuery { start_secs: 10.0, end_secs: 40.5 },
+        );
+        let joined = args.join(" ");
+        assert!(joined.contains("-ss 10.000 -i /data/rec-1.mp4 -t 30.500"));
+        assert!(joined.contains("-filter_complex_script /tmp/clip.filter -map [out] -map 0:a?"));
+        assert!(joined.contains("-c:v libx264"));
+        assert!(!joined.contains("-c copy"));
+        assert!(joined.ends_with("/tmp/clip.mp4"));
+    }
+}
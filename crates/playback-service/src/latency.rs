@@ -0,0 +1,380 @@
+//! Delivery latency of live playback sessions.
+//!
+//! Stream nodes stamp every HLS segment with the time its first frame was
+//! ingested (see `common::latency`). Every few seconds each live session is
+//! measured the way it is delivered:
+//! - HLS and LL-HLS: the age of the newest segment of the session's stream,
+//!   the least a player at the live edge can run behind the camera
+//! - WebRTC (WHEP): the ICE round trip time to the viewer
+//!
+//! Measurements are served per session (`/v1/playback/sessions/:id` and
+//! `/v1/playback/sessions/:id/latency`) and per stream as
+//! `playback_service_delivery_latency_seconds`. A session going over its
+//! protocol's budget raises a `latency_budget` alert once; it alerts again
+//! only after getting back under the budget.
+
+use anyhow::{Context, Result};
+use common::auth_middleware::AuthContext;
+use common::config_reload::Settings;
+use common::gateway_identity;
+use common::latency::{live_edge, now_ms};
+use common::playback::{DeliveryProtocol, PlaybackProtocol, PlaybackSourceType, SessionLatency};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::playback::PlaybackManager;
+use crate::webrtc::WebRtcPeerManager;
+
+const MEASURE_INTERVAL: Duration = Duration::from_secs(5);
+
+const IDENTITY_TTL: Duration = Duration::from_secs(60);
+
+/// Latency budgets per delivery protocol; a protocol without one is
+/// measured but never alerts
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyBudgets {
+    pub webrtc_ms: Option<u64>,
+    pub ll_hls_ms: Option<u64>,
+    pub hls_ms: Option<u64>,
+}
+
+impl LatencyBudgets {
+    /// Budgets from the `PLAYBACK_LATENCY_BUDGET_*_MS` settings
+    pub fn from_settings(settings: &Settings) -> Self {
+        let budget = |name: &str| settings.get_or(name, 0u64);
+        let set = |ms: u64| (ms > 0).then_some(ms);
+        Self {
+            webrtc_ms: set(budget("PLAYBACK_LATENCY_BUDGET_WEBRTC_MS")),
+            ll_hls_ms: set(budget("PLAYBACK_LATENCY_BUDGET_LL_HLS_MS")),
+            hls_ms: set(budget("PLAYBACK_LATENCY_BUDGET_HLS_MS")),
+        }
+    }
+
+    pub fn budget(&self, protocol: DeliveryProtocol) -> Option<u64> {
+        match protocol {
+            DeliveryProtocol::WebRtc => self.webrtc_ms,
+            DeliveryProtocol::LlHls => self.ll_hls_ms,
+            DeliveryProtocol::Hls => self.hls_ms,
+        }
+    }
+}
+
+/// One measurement, before it is held against a budget
+#[derive(Debug, Clone)]
+struct Sample {
+    session_id: String,
+    source_id: String,
+    protocol: DeliveryProtocol,
+    latency_ms: u64,
+    segment_age_ms: Option<u64>,
+    rtt_ms: Option<u64>,
+}
+
+/// Raises `latency_budget` alerts through the alert service
+struct LatencyAlerter {
+    client: reqwest::Client,
+    trigger_url: String,
+    identity: AuthContext,
+    jwt_secret: String,
+}
+
+impl LatencyAlerter {
+    /// Alerting is enabled when `ALERT_SERVICE_URL`, `JWT_SECRET` and
+    /// `LATENCY_ALERT_TENANT_ID` are set
+    fn from_env() -> Option<Self> {
+        let base = std::env::var("ALERT_SERVICE_URL").ok()?;
+        let jwt_secret = std::env::var("JWT_SECRET").ok()?;
+        let tenant_id = std::env::var("LATENCY_ALERT_TENANT_ID").ok()?;
+        Some(Self {
+            client: reqwest::Client::new(),
+            trigger_url: format!("{}/v1/trigger", base.trim_end_matches('/')),
+            identity: AuthContext {
+                user_id: "playback-service".into(),
+                tenant_id,
+                username: "playback-service".into(),
+                is_system_admin: false,
+                roles: Vec::new(),
+                permissions: Vec::new(),
+            },
+            jwt_secret,
+        })
+    }
+
+    async fn raise(&self, latency: &SessionLatency) -> Result<()> {
+        let budget_ms = latency.budget_ms.unwrap_or_default();
+        let message = format!(
+            "{} session {} of {} is {} ms behind, over its {} ms budget",
+            latency.protocol.as_str(),
+            latency.session_id,
+            latency.source_id,
+            latency.latency_ms,
+            budget_ms
+        );
+        let headers = gateway_identity::headers(&self.identity, &self.jwt_secret, IDENTITY_TTL)?;
+        self.client
+            .post(&self.trigger_url)
+            .headers(headers)
+            .json(&json!({
+                "trigger_type": "latency_budget",
+                "message": message,
+                "context": {
+                    "session_id": latency.session_id,
+                    "stream_id": latency.source_id,
+                    "protocol": latency.protocol.as_str(),
+                    "latency_ms": latency.latency_ms,
+                    "budget_ms": budget_ms,
+                    "segment_age_ms": latency.segment_age_ms,
+                    "rtt_ms": latency.rtt_ms,
+                },
+            }))
+            .send()
+            .await
+            .context("alert service unreachable")?
+            .error_for_status()
+            .context("alert service rejected trigger")?;
+        Ok(())
+    }
+}
+
+/// Latest latency of every live session served by this node
+pub struct LatencyMonitor {
+    budgets: std::sync::RwLock<LatencyBudgets>,
+    /// HLS directories of the streams, written by the stream nodes
+    hls_root: PathBuf,
+    latest: RwLock<HashMap<String, SessionLatency>>,
+    alerter: Option<LatencyAlerter>,
+}
+
+impl LatencyMonitor {
+    pub fn new(hls_root: PathBuf) -> Self {
+        Self {
+            budgets: std::sync::RwLock::new(LatencyBudgets::default()),
+            hls_root,
+            latest: RwLock::new(HashMap::new()),
+            alerter: LatencyAlerter::from_env(),
+        }
+    }
+
+    /// Judge measurements from now on against new budgets
+    pub fn reconfigure(&self, budgets: LatencyBudgets) {
+        *self.budgets.write().unwrap_or_else(|e| e.into_inner()) = budgets;
+    }
+
+    fn budgets(&self) -> LatencyBudgets {
+        self.budgets.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Latest measurement of a session (playback or WHEP session id)
+    pub async fn get(&self, session_id: &str) -> Option<SessionLatency> {
+        self.latest.read().await.get(session_id).cloned()
+    }
+
+    /// Measure the live sessions of `manager` and the WebRTC peers every few
+    /// seconds
+    pub fn spawn(self: Arc<Self>, manager: Arc<PlaybackManager>, peers: Arc<WebRtcPeerManager>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(MEASURE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                self.measure(&manager, &peers).await;
+            }
+        });
+    }
+
+    async fn measure(&self, manager: &PlaybackManager, peers: &WebRtcPeerManager) {
+        let now = now_ms();
+        let mut samples = Vec::new();
+        // Age of each stream's newest segment, read once per round
+        let mut ages: HashMap<String, Option<u64>> = HashMap::new();
+        for info in manager.list().await {
+            let config = &info.config;
+            // WebRTC sessions are measured through their WHEP peer; RTSP is
+            // not delivered by this service
+            let protocol = match config.protocol {
+                PlaybackProtocol::Hls if config.low_latency => DeliveryProtocol::LlHls,
+                PlaybackProtocol::Hls => DeliveryProtocol::Hls,
+                _ => continue,
+            };
+            if !info.state.is_active() || config.source_type != PlaybackSourceType::Stream {
+                continue;
+            }
+            let age = match ages.get(&config.source_id) {
+                Some(age) => *age,
+                None => {
+                    let age = self.segment_age_ms(&config.source_id, now).await;
+                    ages.insert(config.source_id.clone(), age);
+                    age
+                }
+            };
+            if let Some(age) = age {
+                samples.push(Sample {
+                    session_id: config.session_id.clone(),
+                    source_id: config.source_id.clone(),
+                    protocol,
+                    latency_ms: age,
+                    segment_age_ms: Some(age),
+                    rtt_ms: None,
+                });
+            }
+        }
+        for (session_id, resource_id, rtt_ms) in peers.round_trip_times().await {
+            samples.push(Sample {
+                session_id,
+                source_id: resource_id,
+                protocol: DeliveryProtocol::WebRtc,
+                latency_ms: rtt_ms,
+                segment_age_ms: None,
+                rtt_ms: Some(rtt_ms),
+            });
+        }
+
+        for latency in self.record(samples, now / 1000).await {
+            warn!(
+                session_id = %latency.session_id,
+                stream_id = %latency.source_id,
+                protocol = latency.protocol.as_str(),
+                latency_ms = latency.latency_ms,
+                budget_ms = latency.budget_ms,
+                "playback session over its latency budget"
+            );
+            if let Some(alerter) = &self.alerter {
+                if let Err(e) = alerter.raise(&latency).await {
+                    warn!(error = %e, "failed to raise latency budget alert");
+                }
+            }
+        }
+    }
+
+    /// Time since the first frame of the stream's newest segment was
+    /// ingested; `None` while the stream has no stamped segments
+    async fn segment_age_ms(&self, stream_id: &str, now: u64) -> Option<u64> {
+        let playlist = tokio::fs::read_to_string(self.hls_root.join(stream_id).join("index.m3u8"))
+            .await
+            .ok()?;
+        live_edge(&playlist).map(|segment| now.saturating_sub(segment.ingest_ms))
+    }
+
+    /// Replace the measurements with a new round (sessions without a sample
+    /// are gone) and export them; returns the sessions that went over their
+    /// budget in this round
+    async fn record(&self, samples: Vec<Sample>, measured_at: u64) -> Vec<SessionLatency> {
+        let budgets = self.budgets();
+        let mut latest = self.latest.write().await;
+        let mut next = HashMap::with_capacity(samples.len());
+        let mut exceeded = Vec::new();
+        for sample in samples {
+            let budget_ms = budgets.budget(sample.protocol);
+            let over_budget = budget_ms.is_some_and(|budget| sample.latency_ms > budget);
+            let latency = SessionLatency {
+                session_id: sample.session_id,
+                source_id: sample.source_id,
+                protocol: sample.protocol,
+                latency_ms: sample.latency_ms,
+                segment_age_ms: sample.segment_age_ms,
+                rtt_ms: sample.rtt_ms,
+                budget_ms,
+                over_budget,
+                measured_at,
+            };
+            let was_over = latest.get(&latency.session_id).is_some_and(|l| l.over_budget);
+            if over_budget && !was_over {
+                telemetry::metrics::PLAYBACK_SERVICE_LATENCY_BUDGET_EXCEEDED
+                    .with_label_values(&[latency.protocol.as_str()])
+                    .inc();
+                exceeded.push(latency.clone());
+            }
+            next.insert(latency.session_id.clone(), latency);
+        }
+
+        // Worst session per stream and protocol
+        let mut worst: HashMap<(&str, &str), u64> = HashMap::new();
+        for latency in next.values() {
+            let entry = worst
+                .entry((latency.source_id.as_str(), latency.protocol.as_str()))
+                .or_default();
+            *entry = (*entry).max(latency.latency_ms);
+        }
+        let gone: HashSet<(&str, &str)> = latest
+            .values()
+            .map(|l| (l.source_id.as_str(), l.protocol.as_str()))
+            .filter(|key| !worst.contains_key(key))
+            .collect();
+        for (stream_id, protocol) in gone {
+            let _ = telemetry::metrics::PLAYBACK_SERVICE_DELIVERY_LATENCY
+                .remove_label_values(&[stream_id, protocol]);
+        }
+        for ((stream_id, protocol), ms) in &worst {
+            debug!(stream_id, protocol, latency_ms = ms, "delivery latency");
+            telemetry::metrics::PLAYBACK_SERVICE_DELIVERY_LATENCY
+                .with_label_values(&[stream_id, protocol])
+                .set(*ms as f64 / 1000.0);
+        }
+
+        *latest = next;
+        exceeded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(session_id: &str, protocol: DeliveryProtocol, latency_ms: u64) -> Sample {
+        Sample {
+            session_id: session_id.to_string(),
+            source_id: "lobby".to_string(),
+            protocol,
+            latency_ms,
+            segment_age_ms: Some(latency_ms),
+            rtt_ms: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn alerts_once_per_excursion_over_budget() {
+        let monitor = LatencyMonitor::new(PathBuf::from("/nonexistent"));
+        monitor.reconfigure(LatencyBudgets {
+            hls_ms: Some(8000),
+            ..Default::default()
+        });
+
+        let exceeded = monitor
+            .record(
+                vec![sample("a", DeliveryProtocol::Hls, 9000), sample("b", DeliveryProtocol::LlHls, 9000)],
+                1,
+            )
+            .await;
+        // LL-HLS has no budget: measured, never over
+        assert_eq!(exceeded.iter().map(|l| l.session_id.as_str()).collect::<Vec<_>>(), ["a"]);
+        assert_eq!(monitor.get("b").await.map(|l| (l.over_budget, l.budget_ms)), Some((false, None)));
+
+        // Still over: no second alert
+        assert!(monitor.record(vec![sample("a", DeliveryProtocol::Hls, 9500)], 2).await.is_empty());
+        assert!(monitor.get("b").await.is_none());
+
+        // Back under, then over again
+        assert!(monitor.record(vec![sample("a", DeliveryProtocol::Hls, 4000)], 3).await.is_empty());
+        assert!(!monitor.get("a").await.unwrap().over_budget);
+        assert_eq!(monitor.record(vec![sample("a", DeliveryProtocol::Hls, 8001)], 4).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn hls_sessions_are_as_old_as_their_newest_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("lobby")).unwrap();
+        std::fs::write(
+            dir.path().join("lobby/index.m3u8"),
+            "#EXTM3U\n#EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:00.000+0000\n#EXTINF:2.0,\na.ts\n#EXTINF:2.0,\nb.ts\n",
+        )
+        .unwrap();
+        let monitor = LatencyMonitor::new(dir.path().to_path_buf());
+        let now = 1_718_157_600_000 + 5_500;
+        assert_eq!(monitor.segment_age_ms("lobby", now).await, Some(3_500));
+        assert_eq!(monitor.segment_age_ms("gone", now).await, None);
+    }
+}
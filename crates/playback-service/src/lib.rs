@@ -1,5 +1,10 @@
 pub mod api;
+pub mod approvals;
+pub mod bandwidth;
 pub mod cache;
+pub mod clip;
+pub mod latency;
+pub mod overlay;
 pub mod playback;
 pub mod preview;
 pub mod webrtc;
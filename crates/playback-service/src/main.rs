@@ -1,8 +1,16 @@
 use anyhow::Result;
-use playback_service::{api, cache, playback};
+use playback_service::{api, approvals, bandwidth, cache, latency, playback};
+use approvals::{Approvals, PostgresApprovalStore};
+use bandwidth::{EgressMeter, PlaybackBandwidth};
 use cache::{CacheConfig, EdgeCache};
-use playback::{PlaybackManager, PlaybackStore};
-use sqlx::postgres::PgPoolOptions;
+use common::approvals::{ApprovalStore, MemoryApprovalStore};
+use common::auth_middleware::AuthMiddlewareConfig;
+use common::bandwidth::{BandwidthReporter, BandwidthRole};
+use common::config_reload::{settings_routes, ConfigReloader, Setting, Settings};
+use common::tenant_rls;
+use latency::LatencyBudgets;
+use playback::{FallbackPolicy, PlaybackManager, PlaybackStore};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
@@ -11,6 +19,24 @@ use tower_http::services::ServeDir;
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
+/// Settings read through the reloader; the edge cache, protocol fallback and
+/// latency budget ones apply live
+const SETTINGS: &[Setting] = &[
+    Setting::restart("LL_HLS_ENABLED"),
+    Setting::live("PLAYBACK_WEBRTC_MAX_RTT_MS"),
+    Setting::live("PLAYBACK_WEBRTC_MAX_LOSS_PCT"),
+    Setting::live("PLAYBACK_LL_HLS_MAX_RTT_MS"),
+    Setting::live("PLAYBACK_LOW_LATENCY_MIN_KBPS"),
+    Setting::live("PLAYBACK_LATENCY_BUDGET_WEBRTC_MS"),
+    Setting::live("PLAYBACK_LATENCY_BUDGET_LL_HLS_MS"),
+    Setting::live("PLAYBACK_LATENCY_BUDGET_HLS_MS"),
+    Setting::live("EDGE_CACHE_ENABLED"),
+    Setting::live("EDGE_CACHE_MAX_ITEMS"),
+    Setting::live("EDGE_CACHE_MAX_SIZE_MB"),
+    Setting::live("EDGE_CACHE_PLAYLIST_TTL_SECS"),
+    Setting::live("EDGE_CACHE_SEGMENT_TTL_SECS"),
+];
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
@@ -39,69 +65,30 @@ async fn main() -> Result<()> {
     let recording_storage_root = std::env::var("RECORDING_STORAGE_ROOT")
         .unwrap_or_else(|_| "./data/recordings".to_string());
 
+    // Settings that can change while running (see SETTINGS)
+    let reloader = ConfigReloader::start("playback-service", SETTINGS).await?;
+    let settings = reloader.current();
+
     // LL-HLS configuration
-    let ll_hls_enabled = std::env::var("LL_HLS_ENABLED")
-        .unwrap_or_else(|_| "false".to_string())
-        .parse::<bool>()
-        .unwrap_or(false);
+    let ll_hls_enabled = settings.get_or("LL_HLS_ENABLED", false);
 
     if ll_hls_enabled {
         info!("LL-HLS (Low-Latency HLS) support enabled");
     }
 
     // Edge cache configuration
-    let cache_enabled = std::env::var("EDGE_CACHE_ENABLED")
-        .unwrap_or_else(|_| "true".to_string())
-        .parse::<bool>()
-        .unwrap_or(true);
-
-    let cache_max_items = std::env::var("EDGE_CACHE_MAX_ITEMS")
-        .unwrap_or_else(|_| "10000".to_string())
-        .parse::<usize>()
-        .unwrap_or(10000);
-
-    let cache_max_size_mb = std::env::var("EDGE_CACHE_MAX_SIZE_MB")
-        .unwrap_or_else(|_| "1024".to_string())
-        .parse::<usize>()
-        .unwrap_or(1024);
-
-    let cache_playlist_ttl_secs = std::env::var("EDGE_CACHE_PLAYLIST_TTL_SECS")
-        .unwrap_or_else(|_| "2".to_string())
-        .parse::<u64>()
-        .unwrap_or(2);
-
-    let cache_segment_ttl_secs = std::env::var("EDGE_CACHE_SEGMENT_TTL_SECS")
-        .unwrap_or_else(|_| "60".to_string())
-        .parse::<u64>()
-        .unwrap_or(60);
-
-    let cache_config = CacheConfig {
-        max_items: cache_max_items,
-        max_size_bytes: cache_max_size_mb * 1024 * 1024,
-        playlist_ttl: Duration::from_secs(cache_playlist_ttl_secs),
-        segment_ttl: Duration::from_secs(cache_segment_ttl_secs),
-        enabled: cache_enabled,
-    };
+    let cache_config = edge_cache_config(&settings);
+    log_cache_config(&cache_config);
+    let edge_cache = Arc::new(EdgeCache::new(cache_config));
 
-    let edge_cache = Arc::new(EdgeCache::new(cache_config.clone()));
-
-    if cache_enabled {
-        info!(
-            "Edge cache enabled: max_items={}, max_size={}MB, playlist_ttl={}s, segment_ttl={}s",
-            cache_max_items,
-            cache_max_size_mb,
-            cache_playlist_ttl_secs,
-            cache_segment_ttl_secs
-        );
-    } else {
-        info!("Edge cache disabled");
-    }
+    // Operator actions (approvals) go to the cluster timeline
+    common::timeline::init_from_env(None).await?;
 
     // Initialize database connection if DATABASE_URL is provided
-    let store = if let Ok(database_url) = std::env::var("DATABASE_URL") {
+    let pool = if let Ok(database_url) = std::env::var("DATABASE_URL") {
         info!("Connecting to database: {}", database_url);
 
-        let pool = PgPoolOptions::new()
+        let pool = tenant_rls::pool_options()
             .max_connections(5)
             .connect(&database_url)
             .await?;
@@ -112,22 +99,71 @@ async fn main() -> Result<()> {
         //     .run(&pool)
         //     .await?;
 
-        Some(Arc::new(PlaybackStore::new(pool)))
+        Some(pool)
     } else {
         info!("DATABASE_URL not set, running without persistent storage");
         None
     };
+    let store = pool.clone().map(|pool| Arc::new(PlaybackStore::new(pool)));
 
-    // Create playback manager
-    let manager = Arc::new(PlaybackManager::new(
-        store,
-        node_id.clone(),
-        hls_base_url,
-        rtsp_base_url,
+    // Four-eyes approvals; rules and requests are shared through the
+    // database when there is one
+    let approval_store: Arc<dyn ApprovalStore> = match pool {
+        Some(pool) => Arc::new(PostgresApprovalStore::new(pool)),
+        None => Arc::new(MemoryApprovalStore::new()),
+    };
+    let approvals = Arc::new(Approvals::new(
+        approval_store,
+        Arc::new(AuthMiddlewareConfig::from_env()),
     ));
 
+    // Create playback manager
+    let manager = Arc::new(
+        PlaybackManager::new(store, node_id.clone(), hls_base_url, rtsp_base_url)
+            .with_fallback_policy(FallbackPolicy::from_settings(&settings, ll_hls_enabled))
+            .with_latency_budgets(LatencyBudgets::from_settings(&settings)),
+    );
+
+    // Pick up the sessions this node served before it restarted; sessions of
+    // other nodes are taken over when their clients show up here
+    match manager.restore().await {
+        Ok(0) => {}
+        Ok(resumed) => info!("Resumed {} playback sessions", resumed),
+        Err(e) => error!("Failed to resume playback sessions: {}", e),
+    }
+
+    // Resize the edge cache and apply new protocol fallback thresholds and
+    // latency budgets when settings are reloaded
+    let mut changes = reloader.subscribe();
+    let reloaded_cache = edge_cache.clone();
+    let reloaded_manager = manager.clone();
+    tokio::spawn(async move {
+        while changes.changed().await.is_ok() {
+            let settings = changes.borrow_and_update().clone();
+            let cache_config = edge_cache_config(&settings);
+            log_cache_config(&cache_config);
+            reloaded_cache.reconfigure(cache_config).await;
+            reloaded_manager
+                .reconfigure_fallback(FallbackPolicy::from_settings(&settings, ll_hls_enabled));
+            reloaded_manager
+                .latency()
+                .reconfigure(LatencyBudgets::from_settings(&settings));
+        }
+    });
+
+    // Meter viewing egress and report it against the site's uplink budget
+    let egress_meter = Arc::new(EgressMeter::new());
+    match BandwidthReporter::from_env(BandwidthRole::Playback, &node_id).await {
+        Ok(Some(reporter)) => {
+            reporter.spawn(PlaybackBandwidth(egress_meter.clone()));
+            info!("Bandwidth reporting enabled");
+        }
+        Ok(None) => {}
+        Err(e) => error!("Bandwidth reporting disabled: {}", e),
+    }
+
     // Create API router
-    let api_router = api::create_router(manager.clone(), edge_cache.clone());
+    let api_router = api::create_router(manager.clone(), edge_cache.clone(), approvals);
 
     // Create file serving router for HLS files
     let hls_serve_dir = ServeDir::new(&hls_root);
@@ -136,12 +172,17 @@ async fn main() -> Result<()> {
     // Combine routes
     let app = axum::Router::new()
         .nest("/api", api_router)
+        .merge(settings_routes(reloader))
         .nest_service("/hls/streams", hls_serve_dir)
         .nest_service("/hls/recordings", recording_serve_dir)
         .layer(axum::middleware::from_fn_with_state(
             edge_cache.clone(),
             cache::middleware::cache_layer,
         ))
+        .layer(axum::middleware::from_fn_with_state(
+            egress_meter,
+            bandwidth::egress_layer,
+        ))
         .layer(CorsLayer::permissive());
 
     // Bind and serve
@@ -151,7 +192,36 @@ async fn main() -> Result<()> {
     info!("Recording files served from: {}", recording_storage_root);
 
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
+
+/// Edge cache configuration from the `EDGE_CACHE_*` settings
+fn edge_cache_config(settings: &Settings) -> CacheConfig {
+    CacheConfig {
+        max_items: settings.get_or("EDGE_CACHE_MAX_ITEMS", 10000),
+        max_size_bytes: settings.get_or("EDGE_CACHE_MAX_SIZE_MB", 1024usize) * 1024 * 1024,
+        playlist_ttl: Duration::from_secs(settings.get_or("EDGE_CACHE_PLAYLIST_TTL_SECS", 2)),
+        segment_ttl: Duration::from_secs(settings.get_or("EDGE_CACHE_SEGMENT_TTL_SECS", 60)),
+        enabled: settings.get_or("EDGE_CACHE_ENABLED", true),
+    }
+}
+
+fn log_cache_config(config: &CacheConfig) {
+    if config.enabled {
+        info!(
+            "Edge cache enabled: max_items={}, max_size={}MB, playlist_ttl={}s, segment_ttl={}s",
+            config.max_items,
+            config.max_size_bytes / (1024 * 1024),
+            config.playlist_ttl.as_secs(),
+            config.segment_ttl.as_secs()
+        );
+    } else {
+        info!("Edge cache disabled");
+    }
+}
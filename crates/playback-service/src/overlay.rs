@@ -0,0 +1,349 @@
+//! AI detection overlays for clip exports and recording playback.
+//!
+//! The boxes come from the detections recorder-node stored in its search
+//! index while the recording was made; nothing is analysed again. For clip
+//! exports they are drawn with FFmpeg's `drawbox`/`drawtext` filters, so an
+//! overlay clip is re-encoded rather than copied. The recording itself is
+//! never modified. During playback they are handed to the player instead,
+//! a few HLS segments at a time, for it to draw over the video.
+
+use anyhow::{Context, Result};
+use common::playback::{ClipExportQuery, ClipOverlayQuery, PlaybackOverlayQuery};
+use common::resilient_http::ResilientClient;
+use common::search::{RecordingDetection, RecordingDetectionsQuery, RecordingDetectionsResponse};
+use reqwest::header::HeaderMap;
+use std::path::Path;
+
+/// Upper bound on boxes drawn in one clip
+pub const MAX_OVERLAY_BOXES: usize = 5000;
+
+/// Recorder frame capture samples every 2 seconds by default, so a box
+/// stays up that long unless the request says otherwise
+const DEFAULT_HOLD_SECS: f64 = 2.0;
+
+const MAX_LABEL_LEN: usize = 32;
+
+/// Box colors, picked per class so a class keeps its color across the clip
+const PALETTE: &[&str] = &["red", "yellow", "lime", "cyan", "magenta", "orange", "deepskyblue", "white"];
+
+/// One drawn box, in video pixels, and the stretch of clip time it covers
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub label: String,
+    pub color: &'static str,
+    pub start_secs: f64,
+    pub end_secs: f64,
+}
+
+/// Boxes of `detections` that fall in `range`, scaled from the analysed
+/// frame to a video of `video_size` and timed from the start of the clip.
+/// At most [`MAX_OVERLAY_BOXES`] are returned.
+pub fn plan(
+    detections: &[RecordingDetection],
+    range: &ClipExportQuery,
+    overlay: &ClipOverlayQuery,
+    video_size: (u32, u32),
+) -> Vec<OverlayBox> {
+    let (video_w, video_h) = video_size;
+    let classes = overlay.class_list();
+    let hold_secs = overlay.hold_secs.unwrap_or(DEFAULT_HOLD_SECS);
+    let length_secs = range.end_secs - range.start_secs;
+
+    let mut boxes = Vec::new();
+    for detection in detections {
+        if !classes.is_empty() && !classes.contains(&detection.class) {
+            continue;
+        }
+        let at_secs = detection.offset_secs - range.start_secs;
+        if !(0.0..length_secs).contains(&at_secs) {
+            continue;
+        }
+        let (scale_x, scale_y) = scale((detection.frame_width, detection.frame_height), video_size);
+        let color = class_color(&detection.class);
+        for b in &detection.boxes {
+            if overlay.min_confidence.is_some_and(|min| b.confidence < min) {
+                continue;
+            }
+            let x = (f64::from(b.x) * scale_x) as u32;
+            let y = (f64::from(b.y) * scale_y) as u32;
+            if b.width == 0 || b.height == 0 || x >= video_w || y >= video_h {
+                continue;
+            }
+            if boxes.len() == MAX_OVERLAY_BOXES {
+                return boxes;
+            }
+            boxes.push(OverlayBox {
+                x,
+                y,
+                width: ((f64::from(b.width) * scale_x) as u32).clamp(1, video_w - x),
+                height: ((f64::from(b.height) * scale_y) as u32).clamp(1, video_h - y),
+                label: format!("{} {:.2}", sanitize_label(&detection.class), b.confidence),
+                color,
+                start_secs: at_secs,
+                end_secs: (at_secs + hold_secs).min(length_secs),
+            });
+        }
+    }
+    boxes
+}
+
+/// Factors from analysed-frame pixels to video pixels. A zero frame
+/// dimension was scaled to keep the aspect ratio; with no frame size the
+/// boxes are taken as video pixels.
+fn scale(frame: (u32, u32), video: (u32, u32)) -> (f64, f64) {
+    let factor = |frame: u32, video: u32| (frame > 0).then(|| f64::from(video) / f64::from(frame));
+    match (factor(frame.0, video.0), factor(frame.1, video.1)) {
+        (Some(x), Some(y)) => (x, y),
+        (Some(x), None) => (x, x),
+        (None, Some(y)) => (y, y),
+        (None, None) => (1.0, 1.0),
+    }
+}
+
+fn class_color(class: &str) -> &'static str {
+    let index = class
+        .bytes()
+        .fold(0usize, |acc, b| acc.wrapping_mul(31).wrapping_add(usize::from(b)));
+    PALETTE[index % PALETTE.len()]
+}
+
+/// Labels go into the filtergraph, so only characters with no meaning to
+/// FFmpeg's filter or drawtext syntax are kept
+fn sanitize_label(class: &str) -> String {
+    class
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ' '))
+        .take(MAX_LABEL_LEN)
+        .collect()
+}
+
+/// FFmpeg filtergraph drawing every box on input `[0:v]`, ending in `[out]`.
+/// Each box (and its label) is only enabled while its time window is active.
+pub fn filtergraph(boxes: &[OverlayBox], show_labels: bool, font_file: Option<&Path>) -> String {
+    if boxes.is_empty() {
+        return "[0:v]null[out]".to_string();
+    }
+    let font = font_file
+        .map(|path| format!("fontfile='{}':", path.display()))
+        .unwrap_or_default();
+    let mut filters = Vec::with_capacity(boxes.len() * 2);
+    for b in boxes {
+        let enable = format!("enable='between(t,{:.3},{:.3})'", b.start_secs, b.end_secs);
+        filters.push(format!(
+            "drawbox=x={}:y={}:w={}:h={}:color={}@0.9:t=3:{}",
+            b.x, b.y, b.width, b.height, b.color, enable
+        ));
+        if show_labels {
+            filters.push(format!(
+                "drawtext={}text='{}':x={}:y={}:fontsize=16:fontcolor={}:box=1:boxcolor=black@0.6:boxborderw=3:{}",
+                font,
+                b.label,
+                b.x,
+                b.y.saturating_sub(22),
+                b.color,
+                enable
+            ));
+        }
+    }
+    format!("[0:v]{}[out]", filters.join(","))
+}
+
+/// Length of the HLS segments recorder-node writes (`-hls_time 2`); overlay
+/// windows of playback sessions start and end on their boundaries
+pub const SEGMENT_SECS: f64 = 2.0;
+
+const DEFAULT_OVERLAY_SEGMENTS: u32 = 3;
+
+/// Window of whole segments starting with the one playing at `position_secs`
+pub fn segment_window(position_secs: f64, query: &PlaybackOverlayQuery) -> ClipExportQuery {
+    let segments = query.segments.unwrap_or(DEFAULT_OVERLAY_SEGMENTS);
+    let start_secs = (position_secs.max(0.0) / SEGMENT_SECS).floor() * SEGMENT_SECS;
+    ClipExportQuery {
+        start_secs,
+        end_secs: start_secs + f64::from(segments) * SEGMENT_SECS,
+    }
+}
+
+/// Window a stream should send next, given the one it `sent` last: the
+/// segments past it, once the player is within one segment of its end, or
+/// a fresh window when the player moved out of it (seek). `None` while the
+/// player is still covered.
+pub fn next_window(
+    position_secs: f64,
+    sent: Option<&ClipExportQuery>,
+    query: &PlaybackOverlayQuery,
+) -> Option<ClipExportQuery> {
+    let window = segment_window(position_secs, query);
+    match sent {
+        Some(sent) if (sent.start_secs..sent.end_secs).contains(&position_secs) => {
+            (window.end_secs > sent.end_secs && position_secs >= sent.end_secs - SEGMENT_SECS).then_some(
+                ClipExportQuery {
+                    start_secs: sent.end_secs,
+                    end_secs: window.end_secs,
+                },
+            )
+        }
+        _ => Some(window),
+    }
+}
+
+/// Detections of the selected classes, without the boxes below the
+/// confidence threshold; detections left without boxes are dropped
+pub fn select(detections: Vec<RecordingDetection>, query: &PlaybackOverlayQuery) -> Vec<RecordingDetection> {
+    let classes = query.class_list();
+    detections
+        .into_iter()
+        .filter(|d| classes.is_empty() || classes.contains(&d.class))
+        .filter_map(|mut d| {
+            if let Some(min) = query.min_confidence {
+                d.boxes.retain(|b| b.confidence >= min);
+            }
+            (!d.boxes.is_empty()).then_some(d)
+        })
+        .collect()
+}
+
+/// Stored detections of `range` from the recorder at `base_url`, requested
+/// with `identity` so the recorder scopes them to the caller's tenant
+pub async fn fetch_detections(
+    base_url: &str,
+    identity: HeaderMap,
+    recording_id: &str,
+    range: &ClipExportQuery,
+) -> Result<RecordingDetectionsResponse> {
+    let client = ResilientClient::builder("recorder-node").build().await?;
+    let url = format!(
+        "{}/v1/search/recordings/{}/detections",
+        base_url.trim_end_matches('/'),
+        recording_id
+    );
+    let query = RecordingDetectionsQuery {
+        from_secs: range.start_secs,
+        to_secs: Some(range.end_secs),
+    };
+    client
+        .send(|c| c.get(&url).headers(identity.clone()).query(&query))
+        .await
+        .context("failed to reach recorder-node")?
+        .error_for_status()
+        .context("detection lookup failed")?
+        .json()
+        .await
+        .context("invalid detection response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::search::DetectionBox;
+
+    fn detection(class: &str, offset_secs: f64, frame: (u32, u32), confidence: f32) -> RecordingDetection {
+        RecordingDetection {
+            offset_secs,
+            class: class.to_string(),
+            frame_width: frame.0,
+            frame_height: frame.1,
+            boxes: vec![DetectionBox { x: 64, y: 36, width: 128, height: 72, confidence }],
+        }
+    }
+
+    #[test]
+    fn test_boxes_are_scaled_and_timed_from_clip_start() {
+        let range = ClipExportQuery { start_secs: 10.0, end_secs: 20.0 };
+        let detections = vec![
+            detection("person", 5.0, (640, 0), 0.9),
+            detection("person", 12.5, (640, 0), 0.9),
+            detection("car", 19.0, (0, 0), 0.4),
+        ];
+
+        let boxes = plan(&detections, &range, &ClipOverlayQuery::default(), (1280, 720));
+        assert_eq!(boxes.len(), 2);
+        assert_eq!((boxes[0].x, boxes[0].y, boxes[0].width, boxes[0].height), (128, 72, 256, 144));
+        assert_eq!((boxes[0].start_secs, boxes[0].end_secs), (2.5, 4.5));
+        assert_eq!(boxes[0].label, "person 0.90");
+        // Unscaled, and held only until the clip ends
+        assert_eq!((boxes[1].x, boxes[1].width), (64, 128));
+        assert_eq!(boxes[1].end_secs, 10.0);
+    }
+
+    #[test]
+    fn test_class_and_confidence_filters() {
+        let range = ClipExportQuery { start_secs: 0.0, end_secs: 60.0 };
+        let detections = vec![
+            detection("person", 1.0, (0, 0), 0.9),
+            detection("car", 2.0, (0, 0), 0.9),
+            detection("person", 3.0, (0, 0), 0.3),
+        ];
+        let overlay = ClipOverlayQuery {
+            overlays: true,
+            classes: Some("Person".to_string()),
+            min_confidence: Some(0.5),
+            ..Default::default()
+        };
+
+        let boxes = plan(&detections, &range, &overlay, (1280, 720));
+        assert_eq!(boxes.len(), 1);
+        assert_eq!(boxes[0].start_secs, 1.0);
+    }
+
+    #[test]
+    fn test_playback_windows_follow_segments() {
+        let query = PlaybackOverlayQuery::default();
+        let window = segment_window(7.3, &query);
+        assert_eq!((window.start_secs, window.end_secs), (6.0, 12.0));
+
+        // Covered until the player reaches the last segment sent
+        assert_eq!(next_window(9.9, Some(&window), &query), None);
+        let next = next_window(10.1, Some(&window), &query).unwrap();
+        assert_eq!((next.start_secs, next.end_secs), (12.0, 16.0));
+
+        // A seek out of the window starts over
+        let seeked = next_window(41.0, Some(&window), &query).unwrap();
+        assert_eq!((seeked.start_secs, seeked.end_secs), (40.0, 46.0));
+        assert_eq!(next_window(3.0, None, &query).map(|w| w.start_secs), Some(2.0));
+    }
+
+    #[test]
+    fn test_select_filters_classes_and_boxes() {
+        let detections = vec![
+            detection("person", 1.0, (0, 0), 0.9),
+            detection("car", 2.0, (0, 0), 0.9),
+            detection("person", 3.0, (0, 0), 0.3),
+        ];
+        let query = PlaybackOverlayQuery {
+            classes: Some("person".to_string()),
+            min_confidence: Some(0.5),
+            ..Default::default()
+        };
+
+        let selected = select(detections, &query);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].offset_secs, 1.0);
+    }
+
+    #[test]
+    fn test_filtergraph_enables_boxes_in_their_window() {
+        let boxes = vec![OverlayBox {
+            x: 10,
+            y: 5,
+            width: 100,
+            height: 50,
+            label: sanitize_label("person':x=0,drawtext"),
+            color: "red",
+            start_secs: 1.0,
+            end_secs: 3.0,
+        }];
+
+        let graph = filtergraph(&boxes, true, None);
+        assert!(graph.starts_with("[0:v]drawbox=x=10:y=5:w=100:h=50:color=red@0.9"));
+        assert!(graph.contains("enable='between(t,1.000,3.000)'"));
+        assert!(graph.contains("text='personx0drawtext'"));
+        assert!(graph.ends_with("[out]"));
+        assert!(!filtergraph(&boxes, false, None).contains("drawtext"));
+        assert_eq!(filtergraph(&[], true, None), "[0:v]null[out]");
+    }
+}
@@ -0,0 +1,163 @@
+use common::playback::ViewedRange;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the epoch with sub-second precision, for view tracking
+pub fn now_secs() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+/// Tracks which parts of a recording a playback session actually played.
+///
+/// Playback has no position reports from the player, so the position is
+/// extrapolated from wall time and speed between the controls the session
+/// receives (pause, resume and seek); each seek starts a new range. It is
+/// persisted with the session, so wall times have to be comparable between
+/// playback nodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewTracker {
+    ranges: Vec<ViewedRange>,
+    /// Position and wall time playback last started from, while playing
+    playing_from: Option<(f64, f64)>,
+    /// Position while paused
+    position: f64,
+    speed: f64,
+}
+
+impl ViewTracker {
+    pub fn start(position_secs: f64, speed: f64, now: f64) -> Self {
+        Self {
+            ranges: Vec::new(),
+            playing_from: Some((position_secs, now)),
+            position: position_secs,
+            speed: if speed > 0.0 { speed } else { 1.0 },
+        }
+    }
+
+    /// Tracker for a session persisted without one: nothing is known to be
+    /// played yet, and playback goes on from the last stored position
+    pub fn resume_untracked(position_secs: f64, speed: f64, paused: bool, now: f64) -> Self {
+        let mut tracker = Self::start(position_secs, speed, now);
+        if paused {
+            tracker.playing_from = None;
+        }
+        tracker
+    }
+
+    pub fn position(&self, now: f64) -> f64 {
+        match self.playing_from {
+            Some((from, at)) => from + (now - at).max(0.0) * self.speed,
+            None => self.position,
+        }
+    }
+
+    pub fn pause(&mut self, now: f64) {
+        if let Some((from, _)) = self.playing_from {
+            self.position = self.position(now);
+            self.push(from, self.position);
+            self.playing_from = None;
+        }
+    }
+
+    pub fn resume(&mut self, now: f64) {
+        if self.playing_from.is_none() {
+            self.playing_from = Some((self.position, now));
+        }
+    }
+
+    pub fn seek(&mut self, position_secs: f64, now: f64) {
+        let playing = self.playing_from.is_some();
+        self.pause(now);
+        self.position = position_secs;
+        if playing {
+            self.resume(now);
+        }
+    }
+
+    /// Played ranges, clamped to `duration_secs`, sorted and merged
+    pub fn finish(mut self, now: f64, duration_secs: Option<f64>) -> Vec<ViewedRange> {
+        self.pause(now);
+        let limit = duration_secs.unwrap_or(f64::MAX);
+        let mut ranges: Vec<ViewedRange> = self
+            .ranges
+            .into_iter()
+            .map(|r| ViewedRange {
+                from_secs: r.from_secs.clamp(0.0, limit),
+                to_secs: r.to_secs.clamp(0.0, limit),
+            })
+            .filter(|r| r.to_secs > r.from_secs)
+            .collect();
+        ranges.sort_by(|a, b| a.from_secs.total_cmp(&b.from_secs));
+
+        let mut merged: Vec<ViewedRange> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.from_secs <= last.to_secs => {
+                    last.to_secs = last.to_secs.max(range.to_secs);
+                }
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    fn push(&mut self, from_secs: f64, to_secs: f64) {
+        if to_secs > from_secs {
+            self.ranges.push(ViewedRange { from_secs, to_secs });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(from_secs: f64, to_secs: f64) -> ViewedRange {
+        ViewedRange { from_secs, to_secs }
+    }
+
+    #[test]
+    fn test_view_ranges_follow_controls() {
+        // Play 10s from 30s, pause 60s, resume for 5s, seek back to 35s
+        // (overlapping what was seen) and play 20s at double speed
+        let mut tracker = ViewTracker::start(30.0, 1.0, 1000.0);
+        tracker.pause(1010.0);
+        assert_eq!(tracker.position(1070.0), 40.0);
+        tracker.resume(1070.0);
+        tracker.seek(35.0, 1075.0);
+        tracker.speed = 2.0;
+        let ranges = tracker.finish(1085.0, None);
+        assert_eq!(ranges, vec![range(30.0, 55.0)]);
+    }
+
+    #[test]
+    fn test_view_ranges_are_clamped_to_duration() {
+        let mut tracker = ViewTracker::start(0.0, 1.0, 0.0);
+        tracker.seek(100.0, 5.0);
+        // Left running well past the end of a 110s recording
+        let ranges = tracker.finish(600.0, Some(110.0));
+        assert_eq!(ranges, vec![range(0.0, 5.0), range(100.0, 110.0)]);
+    }
+
+    #[test]
+    fn test_view_tracking_survives_a_node_change() {
+        // Played 20s from 0s on one node, which then went away while playing
+        let mut tracker = ViewTracker::start(0.0, 1.0, 1000.0);
+        tracker.seek(60.0, 1020.0);
+        let persisted = serde_json::to_string(&tracker).unwrap();
+
+        // Another node picks the session up 10s later
+        let mut resumed: ViewTracker = serde_json::from_str(&persisted).unwrap();
+        assert_eq!(resumed.position(1030.0), 70.0);
+        resumed.pause(1035.0);
+        let ranges = resumed.finish(1100.0, None);
+        assert_eq!(ranges, vec![range(0.0, 20.0), range(60.0, 75.0)]);
+
+        let paused = ViewTracker::resume_untracked(42.0, 1.0, true, 2000.0);
+        assert_eq!(paused.position(2100.0), 42.0);
+        assert!(paused.finish(2100.0, None).is_empty());
+    }
+}
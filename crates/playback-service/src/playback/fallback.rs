@@ -0,0 +1,302 @@
+//! Protocol negotiation for playback sessions.
+//!
+//! A client that asks for negotiation instead of a fixed protocol reports
+//! what it can play and how its network looks. Protocols are tried in
+//! [`DeliveryProtocol::FALLBACK_ORDER`]: WebRTC (WHEP) when UDP gets through
+//! and the path is clean enough for real-time delivery, LL-HLS for live
+//! streams while the round trip still suits blocking playlist reloads, and
+//! plain HLS otherwise. When the served protocol then fails on the client
+//! (ICE never connects, the player stalls), the client reports it and the
+//! session moves on to the next protocol that is not ruled out.
+
+use common::playback::{
+    ClientReport, DeliveryProtocol, DeliveryRecord, NetworkConditions, PlaybackProtocol,
+    PlaybackSourceType, SkippedDelivery,
+};
+use common::config_reload::Settings;
+
+/// No protocol in the fallback chain can serve the client
+#[derive(Debug, Clone)]
+pub struct NoPlayableProtocol {
+    pub skipped: Vec<SkippedDelivery>,
+}
+
+impl std::fmt::Display for NoPlayableProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no playable protocol")?;
+        for (i, skipped) in self.skipped.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{} ({})", sep, skipped.protocol.as_str(), skipped.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NoPlayableProtocol {}
+
+/// The session's protocol is outside the fallback chain (RTSP)
+#[derive(Debug, Clone)]
+pub struct NotInFallbackChain;
+
+impl std::fmt::Display for NotInFallbackChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RTSP sessions have no protocol fallback")
+    }
+}
+
+impl std::error::Error for NotInFallbackChain {}
+
+/// Thresholds deciding which protocols a client's network can carry
+#[derive(Debug, Clone, PartialEq)]
+pub struct FallbackPolicy {
+    /// LL-HLS playlists are served by this node (`LL_HLS_ENABLED`)
+    pub ll_hls_enabled: bool,
+    pub webrtc_max_rtt_ms: u32,
+    pub webrtc_max_loss_pct: f64,
+    pub ll_hls_max_rtt_ms: u32,
+    pub low_latency_min_kbps: u32,
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        Self {
+            ll_hls_enabled: false,
+            webrtc_max_rtt_ms: 300,
+            webrtc_max_loss_pct: 5.0,
+            ll_hls_max_rtt_ms: 1000,
+            low_latency_min_kbps: 1500,
+        }
+    }
+}
+
+impl FallbackPolicy {
+    /// Thresholds from the `PLAYBACK_*` settings, defaults for unset ones
+    pub fn from_settings(settings: &Settings, ll_hls_enabled: bool) -> Self {
+        let defaults = Self::default();
+        Self {
+            ll_hls_enabled,
+            webrtc_max_rtt_ms: settings.get_or("PLAYBACK_WEBRTC_MAX_RTT_MS", defaults.webrtc_max_rtt_ms),
+            webrtc_max_loss_pct: settings
+                .get_or("PLAYBACK_WEBRTC_MAX_LOSS_PCT", defaults.webrtc_max_loss_pct),
+            ll_hls_max_rtt_ms: settings.get_or("PLAYBACK_LL_HLS_MAX_RTT_MS", defaults.ll_hls_max_rtt_ms),
+            low_latency_min_kbps: settings
+                .get_or("PLAYBACK_LOW_LATENCY_MIN_KBPS", defaults.low_latency_min_kbps),
+        }
+    }
+
+    /// Why `protocol` cannot serve this client, or `None` when it can
+    pub fn rule_out(
+        &self,
+        protocol: DeliveryProtocol,
+        source_type: &PlaybackSourceType,
+        client: &ClientReport,
+    ) -> Option<String> {
+        if !client.supports.contains(&protocol) {
+            return Some("not supported by client".to_string());
+        }
+        let network = &client.network;
+        let slow_downlink = network
+            .downlink_kbps
+            .filter(|kbps| *kbps < self.low_latency_min_kbps);
+
+        match protocol {
+            DeliveryProtocol::WebRtc => {
+                if network.udp_blocked {
+                    return Some("UDP blocked on client network".to_string());
+                }
+                let max_rtt = self.webrtc_max_rtt_ms;
+                if let Some(rtt) = network.rtt_ms.filter(|rtt| *rtt > max_rtt) {
+                    return Some(format!("rtt {}ms above {}ms", rtt, max_rtt));
+                }
+                let max_loss = self.webrtc_max_loss_pct;
+                if let Some(loss) = network.packet_loss_pct.filter(|loss| *loss > max_loss) {
+                    return Some(format!("packet loss {}% above {}%", loss, max_loss));
+                }
+                slow_downlink.map(|kbps| format!("downlink {}kbps too slow for real-time delivery", kbps))
+            }
+            DeliveryProtocol::LlHls => {
+                if *source_type != PlaybackSourceType::Stream {
+                    return Some("only live streams have LL-HLS playlists".to_string());
+                }
+                if !self.ll_hls_enabled {
+                    return Some("LL-HLS disabled on this node".to_string());
+                }
+                let max_rtt = self.ll_hls_max_rtt_ms;
+                if let Some(rtt) = network.rtt_ms.filter(|rtt| *rtt > max_rtt) {
+                    return Some(format!("rtt {}ms above {}ms", rtt, max_rtt));
+                }
+                slow_downlink.map(|kbps| format!("downlink {}kbps too slow for partial segments", kbps))
+            }
+            DeliveryProtocol::Hls => None,
+        }
+    }
+
+    /// Pick the protocol of a new session
+    pub fn negotiate(
+        &self,
+        source_type: &PlaybackSourceType,
+        client: ClientReport,
+    ) -> Result<DeliveryRecord, NoPlayableProtocol> {
+        self.first_playable(&DeliveryProtocol::FALLBACK_ORDER, source_type, client, Vec::new())
+    }
+
+    /// Move a session on after `record.served` failed on the client.
+    /// Fresh network measurements replace the ones reported at start.
+    pub fn fall_back(
+        &self,
+        source_type: &PlaybackSourceType,
+        record: &DeliveryRecord,
+        reason: &str,
+        network: Option<NetworkConditions>,
+    ) -> Result<DeliveryRecord, NoPlayableProtocol> {
+        let mut client = record.client.clone();
+        if let Some(network) = network {
+            client.network = network;
+        }
+        let mut skipped = record.skipped.clone();
+        skipped.push(SkippedDelivery {
+            protocol: record.served,
+            reason: reason.to_string(),
+            attempted: true,
+        });
+
+        let remaining: Vec<DeliveryProtocol> = DeliveryProtocol::FALLBACK_ORDER
+            .iter()
+            .skip_while(|p| **p != record.served)
+            .skip(1)
+            .copied()
+            .collect();
+        self.first_playable(&remaining, source_type, client, skipped)
+    }
+
+    fn first_playable(
+        &self,
+        candidates: &[DeliveryProtocol],
+        source_type: &PlaybackSourceType,
+        client: ClientReport,
+        mut skipped: Vec<SkippedDelivery>,
+    ) -> Result<DeliveryRecord, NoPlayableProtocol> {
+        for protocol in candidates {
+            match self.rule_out(*protocol, source_type, &client) {
+                Some(reason) => skipped.push(SkippedDelivery {
+                    protocol: *protocol,
+                    reason,
+                    attempted: false,
+                }),
+                None => {
+                    return Ok(DeliveryRecord {
+                        served: *protocol,
+                        skipped,
+                        client,
+                    })
+                }
+            }
+        }
+        Err(NoPlayableProtocol { skipped })
+    }
+}
+
+/// Delivery a session without a negotiation record is served with, so that
+/// it can still fall back; RTSP is outside the fallback chain
+pub fn current_delivery(protocol: &PlaybackProtocol, low_latency: bool) -> Option<DeliveryProtocol> {
+    match protocol {
+        PlaybackProtocol::WebRtc => Some(DeliveryProtocol::WebRtc),
+        PlaybackProtocol::Hls if low_latency => Some(DeliveryProtocol::LlHls),
+        PlaybackProtocol::Hls => Some(DeliveryProtocol::Hls),
+        PlaybackProtocol::Rtsp => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> FallbackPolicy {
+        FallbackPolicy {
+            ll_hls_enabled: true,
+            ..FallbackPolicy::default()
+        }
+    }
+
+    fn client(network: NetworkConditions) -> ClientReport {
+        ClientReport {
+            network,
+            ..ClientReport::default()
+        }
+    }
+
+    #[test]
+    fn prefers_webrtc_on_a_clean_network() {
+        let record = policy()
+            .negotiate(&PlaybackSourceType::Stream, ClientReport::default())
+            .unwrap();
+        assert_eq!(record.served, DeliveryProtocol::WebRtc);
+        assert!(record.skipped.is_empty());
+    }
+
+    #[test]
+    fn falls_back_on_reported_conditions() {
+        let blocked = client(NetworkConditions {
+            udp_blocked: true,
+            ..NetworkConditions::default()
+        });
+        let record = policy().negotiate(&PlaybackSourceType::Stream, blocked).unwrap();
+        assert_eq!(record.served, DeliveryProtocol::LlHls);
+        assert_eq!(record.skipped.len(), 1);
+        assert!(!record.skipped[0].attempted);
+
+        let far = client(NetworkConditions {
+            rtt_ms: Some(1500),
+            ..NetworkConditions::default()
+        });
+        let record = policy().negotiate(&PlaybackSourceType::Stream, far).unwrap();
+        assert_eq!(record.served, DeliveryProtocol::Hls);
+        assert_eq!(record.skipped.len(), 2);
+
+        // Recordings have no LL-HLS playlist
+        let blocked = client(NetworkConditions {
+            udp_blocked: true,
+            ..NetworkConditions::default()
+        });
+        let record = policy().negotiate(&PlaybackSourceType::Recording, blocked).unwrap();
+        assert_eq!(record.served, DeliveryProtocol::Hls);
+    }
+
+    #[test]
+    fn respects_client_capabilities() {
+        let webrtc_only = ClientReport {
+            supports: vec![DeliveryProtocol::WebRtc],
+            network: NetworkConditions {
+                udp_blocked: true,
+                ..NetworkConditions::default()
+            },
+        };
+        let err = policy().negotiate(&PlaybackSourceType::Stream, webrtc_only).unwrap_err();
+        assert_eq!(err.skipped.len(), 3);
+        assert!(err.to_string().starts_with("no playable protocol: webrtc"));
+    }
+
+    #[test]
+    fn falls_back_after_a_failed_attempt() {
+        let policy = policy();
+        let record = policy
+            .negotiate(&PlaybackSourceType::Stream, ClientReport::default())
+            .unwrap();
+
+        let record = policy
+            .fall_back(&PlaybackSourceType::Stream, &record, "ice connection failed", None)
+            .unwrap();
+        assert_eq!(record.served, DeliveryProtocol::LlHls);
+        assert!(record.skipped[0].attempted);
+
+        let record = policy
+            .fall_back(&PlaybackSourceType::Stream, &record, "stalled", None)
+            .unwrap();
+        assert_eq!(record.served, DeliveryProtocol::Hls);
+        assert_eq!(record.skipped.len(), 2);
+
+        assert!(policy
+            .fall_back(&PlaybackSourceType::Stream, &record, "stalled", None)
+            .is_err());
+    }
+}
@@ -6,21 +6,29 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
+use super::access::{now_secs, ViewTracker};
 use super::dvr::DvrBufferManager;
+use super::fallback::{current_delivery, FallbackPolicy, NotInFallbackChain};
 use super::ll_hls::{BlockingParams, HlsVariant, LlHlsConfig, LlHlsPlaylistGenerator};
 use super::store::PlaybackStore;
+use crate::latency::{LatencyBudgets, LatencyMonitor};
 
 // Maximum concurrent playback sessions to prevent OOM
 const MAX_CONCURRENT_SESSIONS: usize = 10000;
 
+// Persisted sessions untouched for longer are not resumed
+const DEFAULT_RESUME_WINDOW_SECS: i64 = 86400;
+
 /// In-memory playback session data
 struct SessionData {
     info: PlaybackInfo,
     cancel_token: CancellationToken,
     /// DVR buffer manager (only for DVR-enabled sessions)
     dvr_manager: Option<Arc<DvrBufferManager>>,
+    /// Ranges played so far (only for recordings)
+    view: Option<ViewTracker>,
 }
 
 /// Playback session manager
@@ -33,6 +41,12 @@ pub struct PlaybackManager {
     recording_storage_root: PathBuf,
     stream_hls_root: PathBuf,
     ll_hls_generator: Arc<LlHlsPlaylistGenerator>,
+    /// Protocol negotiation thresholds; replaced when settings are reloaded
+    fallback: std::sync::RwLock<FallbackPolicy>,
+    /// How recently a persisted session must have been touched to resume it
+    resume_window_secs: i64,
+    /// Delivery latency of the live sessions
+    latency: Arc<LatencyMonitor>,
 }
 
 impl PlaybackManager {
@@ -57,6 +71,11 @@ impl PlaybackManager {
             stream_hls_root.clone(),
         ));
 
+        let resume_window_secs = std::env::var("PLAYBACK_SESSION_RESUME_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RESUME_WINDOW_SECS);
+
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             store,
@@ -64,13 +83,127 @@ impl PlaybackManager {
             hls_base_url,
             rtsp_base_url,
             recording_storage_root,
+            latency: Arc::new(LatencyMonitor::new(stream_hls_root.clone())),
             stream_hls_root,
             ll_hls_generator,
+            fallback: std::sync::RwLock::new(FallbackPolicy::default()),
+            resume_window_secs,
         }
     }
 
+    pub fn with_fallback_policy(self, policy: FallbackPolicy) -> Self {
+        self.reconfigure_fallback(policy);
+        self
+    }
+
+    /// Apply new negotiation thresholds to sessions started or falling back
+    /// from now on
+    pub fn reconfigure_fallback(&self, policy: FallbackPolicy) {
+        *self.fallback.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    pub fn with_latency_budgets(self, budgets: LatencyBudgets) -> Self {
+        self.latency.reconfigure(budgets);
+        self
+    }
+
+    pub fn latency(&self) -> &Arc<LatencyMonitor> {
+        &self.latency
+    }
+
+    fn fallback_policy(&self) -> FallbackPolicy {
+        self.fallback.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     /// Start a new playback session
     pub async fn start(&self, config: PlaybackConfig) -> Result<PlaybackInfo> {
+        self.start_session(config, None).await
+    }
+
+    /// Start a session with the first protocol of the fallback chain that
+    /// `client` can play; `config.protocol` and `config.low_latency` are
+    /// replaced by the negotiated ones
+    pub async fn start_negotiated(
+        &self,
+        mut config: PlaybackConfig,
+        client: ClientReport,
+    ) -> Result<PlaybackInfo> {
+        let delivery = match self.fallback_policy().negotiate(&config.source_type, client) {
+            Ok(delivery) => delivery,
+            Err(e) => {
+                telemetry::metrics::PLAYBACK_SERVICE_SESSION_REJECTIONS
+                    .with_label_values(&["protocol"])
+                    .inc();
+                return Err(e.into());
+            }
+        };
+        (config.protocol, config.low_latency) = delivery.served.session_protocol();
+        info!(
+            session_id = %config.session_id,
+            served = delivery.served.as_str(),
+            skipped = delivery.skipped.len(),
+            "playback protocol negotiated"
+        );
+        telemetry::metrics::PLAYBACK_SERVICE_PROTOCOL_SERVED
+            .with_label_values(&[delivery.served.as_str()])
+            .inc();
+        self.start_session(config, Some(delivery)).await
+    }
+
+    /// Move a session to the next protocol after the served one failed on
+    /// the client. `None` when the session does not exist.
+    pub async fn fall_back(&self, request: PlaybackFallbackRequest) -> Result<Option<PlaybackInfo>> {
+        self.ensure_loaded(&request.session_id).await;
+        let mut sessions = self.sessions.write().await;
+        let Some(session_data) = sessions.get_mut(&request.session_id) else {
+            return Ok(None);
+        };
+        let info = &mut session_data.info;
+
+        // Sessions started with a fixed protocol fall back from that one
+        let current = match &info.delivery {
+            Some(delivery) => delivery.clone(),
+            None => DeliveryRecord {
+                served: current_delivery(&info.config.protocol, info.config.low_latency)
+                    .ok_or(NotInFallbackChain)?,
+                skipped: Vec::new(),
+                client: ClientReport::default(),
+            },
+        };
+        let delivery = self.fallback_policy().fall_back(
+            &info.config.source_type,
+            &current,
+            &request.reason,
+            request.network,
+        )?;
+
+        let mut config = info.config.clone();
+        (config.protocol, config.low_latency) = delivery.served.session_protocol();
+        info.playback_url = Some(self.generate_playback_url(&config)?);
+        info.config = config;
+        warn!(
+            session_id = %request.session_id,
+            from = current.served.as_str(),
+            to = delivery.served.as_str(),
+            reason = %request.reason,
+            "playback protocol fell back"
+        );
+        telemetry::metrics::PLAYBACK_SERVICE_PROTOCOL_FALLBACKS
+            .with_label_values(&[current.served.as_str(), delivery.served.as_str()])
+            .inc();
+        info.delivery = Some(delivery);
+
+        if let Some(store) = &self.store {
+            store.save(info).await?;
+        }
+        Ok(Some(info.clone()))
+    }
+
+    async fn start_session(
+        &self,
+        config: PlaybackConfig,
+        delivery: Option<DeliveryRecord>,
+    ) -> Result<PlaybackInfo> {
         info!(session_id = %config.session_id, source = %config.source_id, "starting playback session");
 
         // Check concurrent session limit
@@ -109,6 +242,8 @@ impl PlaybackManager {
                 .as_secs()),
             stopped_at: None,
             dvr_window: None, // Will be set later if DVR is enabled
+            delivery,
+            latency: None,
         };
 
         // For recordings, get duration
@@ -125,9 +260,6 @@ impl PlaybackManager {
 
         // Update state to playing
         info.state = PlaybackState::Playing;
-        if let Some(store) = &self.store {
-            store.save(&info).await?;
-        }
 
         // Create DVR manager if DVR is enabled
         let dvr_manager = if let Some(ref dvr_cfg) = config.dvr {
@@ -157,17 +289,27 @@ impl PlaybackManager {
             None
         };
 
+        let view = (config.source_type == PlaybackSourceType::Recording).then(|| {
+            ViewTracker::start(
+                config.start_time_secs.unwrap_or(0.0),
+                config.speed.unwrap_or(1.0),
+                now_secs(),
+            )
+        });
+
+        let session = SessionData {
+            info: info.clone(),
+            cancel_token: CancellationToken::new(),
+            dvr_manager,
+            view,
+        };
+        if let Some(store) = &self.store {
+            persist(store, &session).await?;
+        }
+
         // Store in memory
-        let cancel_token = CancellationToken::new();
         let mut sessions = self.sessions.write().await;
-        sessions.insert(
-            config.session_id.clone(),
-            SessionData {
-                info: info.clone(),
-                cancel_token,
-                dvr_manager,
-            },
-        );
+        sessions.insert(config.session_id.clone(), session);
 
         if let Some(url) = &info.playback_url {
             info!(session_id = %config.session_id, url = %url, "playback session started");
@@ -181,6 +323,7 @@ impl PlaybackManager {
     pub async fn stop(&self, session_id: &str) -> Result<bool> {
         info!(session_id = %session_id, "stopping playback session");
 
+        self.ensure_loaded(session_id).await;
         let mut sessions = self.sessions.write().await;
         if let Some(session_data) = sessions.remove(session_id) {
             // Cancel any background tasks
@@ -194,6 +337,12 @@ impl PlaybackManager {
             if let Some(store) = &self.store {
                 store.save(&info).await?;
             }
+            drop(sessions);
+
+            if let Some(view) = session_data.view {
+                let ranges = view.finish(now_secs(), info.duration_secs);
+                self.end_access(session_id, &ranges).await;
+            }
 
             Ok(true)
         } else {
@@ -205,6 +354,7 @@ impl PlaybackManager {
     pub async fn seek(&self, session_id: &str, position_secs: f64) -> Result<f64> {
         info!(session_id = %session_id, position = %position_secs, "seeking playback");
 
+        self.ensure_loaded(session_id).await;
         let mut sessions = self.sessions.write().await;
         if let Some(session_data) = sessions.get_mut(session_id) {
             // Only allow seeking for recordings
@@ -220,11 +370,14 @@ impl PlaybackManager {
             }
 
             // Update position
+            if let Some(view) = &mut session_data.view {
+                view.seek(position_secs, now_secs());
+            }
             session_data.info.current_position_secs = Some(position_secs);
             session_data.info.state = PlaybackState::Playing;
 
             if let Some(store) = &self.store {
-                store.save(&session_data.info).await?;
+                persist(store, session_data).await?;
             }
 
             Ok(position_secs)
@@ -245,14 +398,215 @@ impl PlaybackManager {
 
     /// List all active sessions
     pub async fn list(&self) -> Vec<PlaybackInfo> {
-        let sessions = self.sessions.read().await;
-        sessions.values().map(|s| s.info.clone()).collect()
+        let mut infos: Vec<PlaybackInfo> = {
+            let sessions = self.sessions.read().await;
+            sessions.values().map(|s| s.info.clone()).collect()
+        };
+        for info in &mut infos {
+            info.latency = self.latency.get(&info.config.session_id).await;
+        }
+        infos
     }
 
     /// Get a specific session
     pub async fn get(&self, session_id: &str) -> Option<PlaybackInfo> {
+        self.ensure_loaded(session_id).await;
+        let mut info = {
+            let sessions = self.sessions.read().await;
+            sessions.get(session_id).map(|s| s.info.clone())?
+        };
+        info.latency = self.latency.get(session_id).await;
+        Some(info)
+    }
+
+    /// A session and its current position; for recordings the position is
+    /// extrapolated from the session's controls (see [`ViewTracker`])
+    pub async fn position(&self, session_id: &str) -> Option<(PlaybackInfo, Option<f64>)> {
+        self.ensure_loaded(session_id).await;
         let sessions = self.sessions.read().await;
-        sessions.get(session_id).map(|s| s.info.clone())
+        sessions.get(session_id).map(|s| {
+            let position = match &s.view {
+                Some(view) => Some(view.position(now_secs())),
+                None => s.info.current_position_secs,
+            };
+            (s.info.clone(), position)
+        })
+    }
+
+    // === Session Resumption ===
+
+    /// Resume the sessions this node was serving before it restarted (which
+    /// takes a fixed `NODE_ID`); returns how many were resumed
+    pub async fn restore(&self) -> Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let mut resumed = 0;
+        for (info, view) in store.list_resumable(&self.node_id, self.resume_window_secs).await? {
+            let session_id = info.config.session_id.clone();
+            match self.resume(info, view).await {
+                Ok(()) => resumed += 1,
+                Err(e) => warn!(session_id = %session_id, error = %e, "failed to resume playback session"),
+            }
+        }
+        Ok(resumed)
+    }
+
+    /// Load a session this node does not hold from the store. Clients only
+    /// carry the session id, so the gateway may route them to any playback
+    /// node after the one serving them restarted or went away.
+    async fn ensure_loaded(&self, session_id: &str) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if self.sessions.read().await.contains_key(session_id) {
+            return;
+        }
+        match store.get_resumable(session_id, self.resume_window_secs).await {
+            Ok(Some((info, view))) => {
+                if let Err(e) = self.resume(info, view).await {
+                    warn!(session_id = %session_id, error = %e, "failed to take over playback session");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!(session_id = %session_id, error = %e, "failed to look up persisted playback session"),
+        }
+    }
+
+    /// Serve a persisted session from this node: its playback URL points
+    /// here, DVR segments are rescanned and view tracking carries on
+    async fn resume(&self, mut info: PlaybackInfo, view: Option<ViewTracker>) -> Result<()> {
+        let session_id = info.config.session_id.clone();
+        let reason = if info.node_id.as_deref() == Some(self.node_id.as_str()) {
+            "restart"
+        } else {
+            "takeover"
+        };
+        info.node_id = Some(self.node_id.clone());
+        info.playback_url = Some(self.generate_playback_url(&info.config)?);
+
+        let dvr_manager = match &info.config.dvr {
+            Some(dvr_cfg) if dvr_cfg.enabled && info.config.source_type == PlaybackSourceType::Stream => {
+                let manager = Arc::new(DvrBufferManager::new(
+                    info.config.source_id.clone(),
+                    self.stream_hls_root.join(&info.config.source_id),
+                    dvr_cfg.buffer_window_secs,
+                ));
+                if let Err(e) = manager.scan_segments().await {
+                    warn!(session_id = %session_id, error = %e, "Failed to scan DVR segments");
+                }
+                let current_pos = info.dvr_window.as_ref().and_then(|w| w.current_position);
+                if let Ok(window) = manager.get_window(current_pos).await {
+                    info.dvr_window = Some(window);
+                }
+                Some(manager)
+            }
+            _ => None,
+        };
+
+        let view = (info.config.source_type == PlaybackSourceType::Recording).then(|| {
+            view.unwrap_or_else(|| {
+                ViewTracker::resume_untracked(
+                    info.current_position_secs
+                        .or(info.config.start_time_secs)
+                        .unwrap_or(0.0),
+                    info.config.speed.unwrap_or(1.0),
+                    info.state == PlaybackState::Paused,
+                    now_secs(),
+                )
+            })
+        });
+        let session = SessionData {
+            info,
+            cancel_token: CancellationToken::new(),
+            dvr_manager,
+            view,
+        };
+
+        let mut sessions = self.sessions.write().await;
+        if sessions.contains_key(&session_id) {
+            return Ok(());
+        }
+        if sessions.len() >= MAX_CONCURRENT_SESSIONS {
+            telemetry::metrics::PLAYBACK_SERVICE_SESSION_REJECTIONS
+                .with_label_values(&["capacity"])
+                .inc();
+            return Err(anyhow!(
+                "Maximum concurrent playback sessions ({}) exceeded. Cannot resume session.",
+                MAX_CONCURRENT_SESSIONS
+            ));
+        }
+        if let Some(store) = &self.store {
+            persist(store, &session).await?;
+        }
+        sessions.insert(session_id.clone(), session);
+
+        info!(session_id = %session_id, reason, "playback session resumed");
+        telemetry::metrics::PLAYBACK_SERVICE_SESSIONS_RESUMED
+            .with_label_values(&[reason])
+            .inc();
+        Ok(())
+    }
+
+    // === Recording Access Log ===
+
+    /// Append to the recording access log; access is never refused because
+    /// the log is unavailable
+    pub async fn record_access(&self, entry: RecordingAccessEntry) {
+        let Some(store) = &self.store else {
+            debug!(recording_id = %entry.recording_id, action = entry.action.as_str(), "no store, access not logged");
+            return;
+        };
+        if let Err(e) = store.record_access(&entry).await {
+            error!(recording_id = %entry.recording_id, error = %e, "failed to log recording access");
+        }
+    }
+
+    /// Close the view entry of `session_id` with the ranges it played
+    pub async fn end_access(&self, session_id: &str, viewed_ranges: &[ViewedRange]) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let ended_at = common::validation::safe_unix_timestamp();
+        if let Err(e) = store.end_access(session_id, viewed_ranges, ended_at).await {
+            error!(session_id = %session_id, error = %e, "failed to close recording access entry");
+        }
+    }
+
+    /// Access log of a recording, or `None` without persistent storage
+    pub async fn access_log(
+        &self,
+        recording_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Option<(Vec<RecordingAccessEntry>, i64)>> {
+        match &self.store {
+            Some(store) => Ok(Some(store.list_access(recording_id, limit, offset).await?)),
+            None => Ok(None),
+        }
+    }
+
+    // === WebRTC Session Stats ===
+
+    /// Keep the summary of a closed WebRTC session; without a store it is
+    /// only logged
+    pub async fn save_webrtc_stats(&self, stats: &WebRtcSessionStats) {
+        let Some(store) = &self.store else {
+            debug!(session_id = %stats.session_id, "no store, WebRTC stats not kept");
+            return;
+        };
+        if let Err(e) = store.save_webrtc_stats(stats).await {
+            error!(session_id = %stats.session_id, error = %e, "failed to save WebRTC session stats");
+        }
+    }
+
+    /// Summary of a closed WebRTC session, or `None` without persistent
+    /// storage or when unknown
+    pub async fn webrtc_stats(&self, session_id: &str) -> Result<Option<WebRtcSessionStats>> {
+        match &self.store {
+            Some(store) => store.get_webrtc_stats(session_id).await,
+            None => Ok(None),
+        }
     }
 
     // Helper methods
@@ -280,6 +634,12 @@ impl PlaybackManager {
 
     fn generate_playback_url(&self, config: &PlaybackConfig) -> Result<String> {
         match config.protocol {
+            PlaybackProtocol::Hls if config.low_latency && config.source_type == PlaybackSourceType::Stream => {
+                // LL-HLS playlists are generated by this service
+                let base_url = std::env::var("PLAYBACK_SERVICE_URL")
+                    .unwrap_or_else(|_| "http://localhost:8087".to_string());
+                Ok(format!("{}/api/ll-hls/streams/{}/playlist.m3u8", base_url, config.source_id))
+            }
             PlaybackProtocol::Hls => {
                 match config.source_type {
                     PlaybackSourceType::Stream => {
@@ -371,11 +731,19 @@ impl PlaybackManager {
     }
 
     async fn update_state(&self, session_id: &str, state: PlaybackState) -> Result<()> {
+        self.ensure_loaded(session_id).await;
         let mut sessions = self.sessions.write().await;
         if let Some(session_data) = sessions.get_mut(session_id) {
+            if let Some(view) = &mut session_data.view {
+                match state {
+                    PlaybackState::Paused => view.pause(now_secs()),
+                    PlaybackState::Playing => view.resume(now_secs()),
+                    _ => {}
+                }
+            }
             session_data.info.state = state;
             if let Some(store) = &self.store {
-                store.save(&session_data.info).await?;
+                persist(store, session_data).await?;
             }
             Ok(())
         } else {
@@ -414,6 +782,7 @@ impl PlaybackManager {
 
     /// Get DVR window information for a session
     pub async fn get_dvr_window(&self, session_id: &str) -> Result<DvrWindowInfo> {
+        self.ensure_loaded(session_id).await;
         let sessions = self.sessions.read().await;
         if let Some(session_data) = sessions.get(session_id) {
             if let Some(dvr_manager) = &session_data.dvr_manager {
@@ -439,6 +808,7 @@ impl PlaybackManager {
 
     /// Seek to a specific timestamp in DVR buffer
     pub async fn dvr_seek(&self, request: DvrSeekRequest) -> Result<DvrSeekResponse> {
+        self.ensure_loaded(&request.session_id).await;
         let mut sessions = self.sessions.write().await;
         if let Some(session_data) = sessions.get_mut(&request.session_id) {
             if let Some(dvr_manager) = &session_data.dvr_manager {
@@ -515,6 +885,7 @@ impl PlaybackManager {
 
     /// Jump to live edge (exit DVR mode, return to live)
     pub async fn jump_to_live(&self, session_id: &str) -> Result<DvrSeekResponse> {
+        self.ensure_loaded(session_id).await;
         let mut sessions = self.sessions.write().await;
         if let Some(session_data) = sessions.get_mut(session_id) {
             if let Some(dvr_manager) = &session_data.dvr_manager {
@@ -554,6 +925,7 @@ impl PlaybackManager {
 
     /// Update DVR buffer window size
     pub async fn set_dvr_buffer_limit(&self, session_id: &str, buffer_secs: f64) -> Result<()> {
+        self.ensure_loaded(session_id).await;
         let sessions = self.sessions.read().await;
         if let Some(session_data) = sessions.get(session_id) {
             if let Some(dvr_manager) = &session_data.dvr_manager {
@@ -568,3 +940,11 @@ impl PlaybackManager {
         }
     }
 }
+
+/// Save a session with its view tracker, if it has one
+async fn persist(store: &PlaybackStore, session: &SessionData) -> Result<()> {
+    match &session.view {
+        Some(view) => store.save_with_view(&session.info, view).await,
+        None => store.save(&session.info).await,
+    }
+}
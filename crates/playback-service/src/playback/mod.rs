@@ -1,9 +1,12 @@
+pub mod access;
 pub mod dvr;
+pub mod fallback;
 pub mod ll_hls;
 pub mod manager;
 pub mod store;
 
 pub use dvr::DvrBufferManager;
+pub use fallback::{FallbackPolicy, NoPlayableProtocol, NotInFallbackChain};
 pub use ll_hls::{BlockingParams, LlHlsConfig, LlHlsPlaylistGenerator};
 pub use manager::PlaybackManager;
 pub use store::PlaybackStore;
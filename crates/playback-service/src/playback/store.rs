@@ -3,6 +3,8 @@ use common::playback::*;
 use sqlx::{PgPool, Row};
 use tracing::{error, info};
 
+use super::access::ViewTracker;
+
 /// Database store for playback sessions
 pub struct PlaybackStore {
     pool: PgPool,
@@ -15,6 +17,16 @@ impl PlaybackStore {
 
     /// Save or update a playback session
     pub async fn save(&self, session: &PlaybackInfo) -> Result<()> {
+        self.upsert(session, None).await
+    }
+
+    /// Save or update a playback session along with its view tracker, so
+    /// another node can carry on tracking what is watched
+    pub async fn save_with_view(&self, session: &PlaybackInfo, view: &ViewTracker) -> Result<()> {
+        self.upsert(session, Some(view)).await
+    }
+
+    async fn upsert(&self, session: &PlaybackInfo, view: Option<&ViewTracker>) -> Result<()> {
         let state_str = match session.state {
             PlaybackState::Pending => "pending",
             PlaybackState::Starting => "starting",
@@ -31,11 +43,13 @@ impl PlaybackStore {
             PlaybackSourceType::Recording => "recording",
         };
 
-        let protocol_str = match session.config.protocol {
-            PlaybackProtocol::Hls => "hls",
-            PlaybackProtocol::Rtsp => "rtsp",
-            PlaybackProtocol::WebRtc => "webrtc",
-        };
+        let protocol_str = protocol_to_str(&session.config.protocol);
+        let delivery = session
+            .delivery
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let view_state = view.map(serde_json::to_string).transpose()?;
 
         // Extract DVR fields
         let (dvr_enabled, dvr_rewind_limit, dvr_buffer_window) =
@@ -64,9 +78,11 @@ impl PlaybackStore {
                 duration_secs, start_time_secs, speed, last_error,
                 started_at, stopped_at,
                 dvr_enabled, dvr_rewind_limit_secs, dvr_buffer_window_secs,
-                dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21)
+                dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position,
+                delivery, low_latency, view_state
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22::jsonb, $23, $24::jsonb)
             ON CONFLICT (session_id) DO UPDATE SET
+                protocol = EXCLUDED.protocol,
                 state = EXCLUDED.state,
                 lease_id = EXCLUDED.lease_id,
                 node_id = EXCLUDED.node_id,
@@ -83,7 +99,10 @@ impl PlaybackStore {
                 dvr_buffer_window_secs = EXCLUDED.dvr_buffer_window_secs,
                 dvr_earliest_timestamp = EXCLUDED.dvr_earliest_timestamp,
                 dvr_latest_timestamp = EXCLUDED.dvr_latest_timestamp,
-                dvr_current_position = EXCLUDED.dvr_current_position
+                dvr_current_position = EXCLUDED.dvr_current_position,
+                delivery = EXCLUDED.delivery,
+                low_latency = EXCLUDED.low_latency,
+                view_state = COALESCE(EXCLUDED.view_state, playback_sessions.view_state)
             "#,
         )
         .bind(&session.config.session_id)
@@ -107,6 +126,9 @@ impl PlaybackStore {
         .bind(dvr_earliest)
         .bind(dvr_latest)
         .bind(dvr_current)
+        .bind(delivery)
+        .bind(session.config.low_latency)
+        .bind(view_state)
         .execute(&self.pool)
         .await?;
 
@@ -122,7 +144,8 @@ impl PlaybackStore {
                    duration_secs, start_time_secs, speed, last_error,
                    started_at, stopped_at,
                    dvr_enabled, dvr_rewind_limit_secs, dvr_buffer_window_secs,
-                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position
+                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position,
+                   delivery::text AS delivery, low_latency
             FROM playback_sessions
             WHERE session_id = $1
             "#,
@@ -146,7 +169,8 @@ impl PlaybackStore {
                    duration_secs, start_time_secs, speed, last_error,
                    started_at, stopped_at,
                    dvr_enabled, dvr_rewind_limit_secs, dvr_buffer_window_secs,
-                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position
+                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position,
+                   delivery::text AS delivery, low_latency
             FROM playback_sessions
             WHERE state IN ('pending', 'starting', 'playing', 'paused', 'seeking')
             ORDER BY created_at DESC
@@ -162,6 +186,67 @@ impl PlaybackStore {
         Ok(sessions)
     }
 
+    /// An active session touched within the last `max_age_secs`, with its
+    /// view tracker, for a node taking it over
+    pub async fn get_resumable(
+        &self,
+        session_id: &str,
+        max_age_secs: i64,
+    ) -> Result<Option<(PlaybackInfo, Option<ViewTracker>)>> {
+        let row = sqlx::query(
+            r#"
+            SELECT session_id, source_type, source_id, protocol, state,
+                   lease_id, node_id, playback_url, current_position_secs,
+                   duration_secs, start_time_secs, speed, last_error,
+                   started_at, stopped_at,
+                   dvr_enabled, dvr_rewind_limit_secs, dvr_buffer_window_secs,
+                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position,
+                   delivery::text AS delivery, low_latency, view_state::text AS view_state
+            FROM playback_sessions
+            WHERE session_id = $1
+              AND state IN ('pending', 'starting', 'playing', 'paused', 'seeking')
+              AND updated_at > NOW() - make_interval(secs => $2)
+            "#,
+        )
+        .bind(session_id)
+        .bind(max_age_secs as f64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(row_to_resumable).transpose()
+    }
+
+    /// Active sessions of `node_id` touched within the last `max_age_secs`,
+    /// with their view trackers, for the node resuming after a restart
+    pub async fn list_resumable(
+        &self,
+        node_id: &str,
+        max_age_secs: i64,
+    ) -> Result<Vec<(PlaybackInfo, Option<ViewTracker>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT session_id, source_type, source_id, protocol, state,
+                   lease_id, node_id, playback_url, current_position_secs,
+                   duration_secs, start_time_secs, speed, last_error,
+                   started_at, stopped_at,
+                   dvr_enabled, dvr_rewind_limit_secs, dvr_buffer_window_secs,
+                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position,
+                   delivery::text AS delivery, low_latency, view_state::text AS view_state
+            FROM playback_sessions
+            WHERE node_id = $1
+              AND state IN ('pending', 'starting', 'playing', 'paused', 'seeking')
+              AND updated_at > NOW() - make_interval(secs => $2)
+            ORDER BY created_at
+            "#,
+        )
+        .bind(node_id)
+        .bind(max_age_secs as f64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_resumable).collect()
+    }
+
     /// List playback sessions by node_id
     pub async fn list_by_node(&self, node_id: &str) -> Result<Vec<PlaybackInfo>> {
         let rows = sqlx::query(
@@ -171,7 +256,8 @@ impl PlaybackStore {
                    duration_secs, start_time_secs, speed, last_error,
                    started_at, stopped_at,
                    dvr_enabled, dvr_rewind_limit_secs, dvr_buffer_window_secs,
-                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position
+                   dvr_earliest_timestamp, dvr_latest_timestamp, dvr_current_position,
+                   delivery::text AS delivery, low_latency
             FROM playback_sessions
             WHERE node_id = $1
             ORDER BY created_at DESC
@@ -196,6 +282,195 @@ impl PlaybackStore {
             .await?;
         Ok(())
     }
+
+    /// Append an entry to the recording access log
+    pub async fn record_access(&self, entry: &RecordingAccessEntry) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO recording_access_log (
+                id, recording_id, action, tenant_id, user_id, username,
+                session_id, protocol, from_secs, to_secs, viewed_ranges,
+                detail, accessed_at, ended_at
+            ) VALUES (
+                $1, $2, $3, COALESCE($4, NULLIF(current_setting('app.tenant_id', true), '*')),
+                $5, $6, $7, $8, $9, $10, $11::jsonb, $12::jsonb, $13, $14
+            )
+            "#,
+        )
+        .bind(&entry.id)
+        .bind(&entry.recording_id)
+        .bind(entry.action.as_str())
+        .bind(entry.tenant_id.as_deref())
+        .bind(entry.user_id.as_deref())
+        .bind(entry.username.as_deref())
+        .bind(entry.session_id.as_deref())
+        .bind(entry.protocol.as_ref().map(protocol_to_str))
+        .bind(entry.from_secs)
+        .bind(entry.to_secs)
+        .bind(serde_json::to_string(&entry.viewed_ranges)?)
+        .bind(serde_json::to_string(&entry.detail)?)
+        .bind(entry.accessed_at as i64)
+        .bind(entry.ended_at.map(|t| t as i64))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Close the view entry of a playback session with what was watched
+    pub async fn end_access(
+        &self,
+        session_id: &str,
+        viewed_ranges: &[ViewedRange],
+        ended_at: u64,
+    ) -> Result<()> {
+        let from_secs = viewed_ranges.first().map(|r| r.from_secs);
+        let to_secs = viewed_ranges.last().map(|r| r.to_secs);
+        sqlx::query(
+            r#"
+            UPDATE recording_access_log
+            SET viewed_ranges = $2::jsonb,
+                from_secs = COALESCE($3, from_secs),
+                to_secs = COALESCE($4, to_secs),
+                ended_at = $5
+            WHERE session_id = $1 AND action = 'view' AND ended_at IS NULL
+            "#,
+        )
+        .bind(session_id)
+        .bind(serde_json::to_string(viewed_ranges)?)
+        .bind(from_secs)
+        .bind(to_secs)
+        .bind(ended_at as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Access log of a recording, newest first, with the total entry count.
+    /// Row-level security limits it to the connection's tenant.
+    pub async fn list_access(
+        &self,
+        recording_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<RecordingAccessEntry>, i64)> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, recording_id, action, tenant_id, user_id, username,
+                   session_id, protocol, from_secs, to_secs,
+                   viewed_ranges::text AS viewed_ranges, detail::text AS detail,
+                   accessed_at, ended_at, COUNT(*) OVER () AS total
+            FROM recording_access_log
+            WHERE recording_id = $1
+            ORDER BY accessed_at DESC, created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(recording_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let total = match rows.first() {
+            Some(row) => row.try_get("total")?,
+            None => 0,
+        };
+        let mut entries = Vec::with_capacity(rows.len());
+        for row in rows {
+            entries.push(row_to_access_entry(row)?);
+        }
+        Ok((entries, total))
+    }
+
+    /// Store the summary of a closed WebRTC session
+    pub async fn save_webrtc_stats(&self, stats: &WebRtcSessionStats) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO webrtc_session_stats (
+                session_id, resource_id, started_at, ended_at,
+                local_candidate_type, remote_candidate_type, stats
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7::jsonb)
+            ON CONFLICT (session_id) DO UPDATE SET
+                ended_at = EXCLUDED.ended_at,
+                local_candidate_type = EXCLUDED.local_candidate_type,
+                remote_candidate_type = EXCLUDED.remote_candidate_type,
+                stats = EXCLUDED.stats
+            "#,
+        )
+        .bind(&stats.session_id)
+        .bind(&stats.resource_id)
+        .bind(stats.started_at as i64)
+        .bind(stats.ended_at.map(|t| t as i64))
+        .bind(stats.local_candidate_type.as_deref())
+        .bind(stats.remote_candidate_type.as_deref())
+        .bind(serde_json::to_string(stats)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Summary of a closed WebRTC session, limited to the connection's tenant
+    pub async fn get_webrtc_stats(&self, session_id: &str) -> Result<Option<WebRtcSessionStats>> {
+        let row = sqlx::query("SELECT stats::text AS stats FROM webrtc_session_stats WHERE session_id = $1")
+            .bind(session_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => {
+                let stats: String = row.try_get("stats")?;
+                Ok(Some(serde_json::from_str(&stats)?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn protocol_to_str(protocol: &PlaybackProtocol) -> &'static str {
+    match protocol {
+        PlaybackProtocol::Hls => "hls",
+        PlaybackProtocol::Rtsp => "rtsp",
+        PlaybackProtocol::WebRtc => "webrtc",
+    }
+}
+
+fn row_to_access_entry(row: sqlx::postgres::PgRow) -> Result<RecordingAccessEntry> {
+    let action: String = row.try_get("action")?;
+    let protocol: Option<String> = row.try_get("protocol")?;
+    let viewed_ranges: String = row.try_get("viewed_ranges")?;
+    let detail: String = row.try_get("detail")?;
+
+    Ok(RecordingAccessEntry {
+        id: row.try_get("id")?,
+        recording_id: row.try_get("recording_id")?,
+        action: RecordingAccessAction::parse(&action)
+            .ok_or_else(|| anyhow::anyhow!("unknown access action: {}", action))?,
+        tenant_id: row.try_get("tenant_id")?,
+        user_id: row.try_get("user_id")?,
+        username: row.try_get("username")?,
+        session_id: row.try_get("session_id")?,
+        protocol: protocol.and_then(|p| match p.as_str() {
+            "hls" => Some(PlaybackProtocol::Hls),
+            "rtsp" => Some(PlaybackProtocol::Rtsp),
+            "webrtc" => Some(PlaybackProtocol::WebRtc),
+            _ => None,
+        }),
+        from_secs: row.try_get("from_secs")?,
+        to_secs: row.try_get("to_secs")?,
+        viewed_ranges: serde_json::from_str(&viewed_ranges)?,
+        detail: serde_json::from_str(&detail)?,
+        accessed_at: row.try_get::<i64, _>("accessed_at")? as u64,
+        ended_at: row
+            .try_get::<Option<i64>, _>("ended_at")?
+            .map(|t| t as u64),
+    })
+}
+
+fn row_to_resumable(row: sqlx::postgres::PgRow) -> Result<(PlaybackInfo, Option<ViewTracker>)> {
+    let view = row
+        .try_get::<Option<String>, _>("view_state")?
+        .map(|v| serde_json::from_str(&v))
+        .transpose()?;
+    Ok((row_to_playback_info(row)?, view))
 }
 
 fn row_to_playback_info(row: sqlx::postgres::PgRow) -> Result<PlaybackInfo> {
@@ -229,6 +504,11 @@ fn row_to_playback_info(row: sqlx::postgres::PgRow) -> Result<PlaybackInfo> {
         _ => PlaybackState::Pending,
     };
 
+    let delivery: Option<DeliveryRecord> = row
+        .try_get::<Option<String>, _>("delivery")?
+        .map(|d| serde_json::from_str(&d))
+        .transpose()?;
+
     let session_id: String = row.try_get("session_id")?;
     let source_id: String = row.try_get("source_id")?;
     let start_time_secs: Option<f64> = row.try_get("start_time_secs").ok();
@@ -288,7 +568,10 @@ fn row_to_playback_info(row: sqlx::postgres::PgRow) -> Result<PlaybackInfo> {
             protocol,
             start_time_secs,
             speed,
-            low_latency: false, // Default to false for database rows
+            low_latency: row.try_get("low_latency").unwrap_or(false)
+                || delivery
+                    .as_ref()
+                    .is_some_and(|d| d.served == DeliveryProtocol::LlHls),
             dvr,
         },
         state,
@@ -309,5 +592,7 @@ fn row_to_playback_info(row: sqlx::postgres::PgRow) -> Result<PlaybackInfo> {
             .flatten()
             .map(|t| t as u64),
         dvr_window,
+        delivery,
+        latency: None,
     })
 }
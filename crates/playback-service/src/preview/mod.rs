@@ -128,7 +128,7 @@ pub fn generate_time_axis_preview(
 }
 
 /// Find the recording file path from the recording ID
-fn find_recording_path(storage_root: &Path, recording_id: &str) -> Result<PathBuf> {
+pub(crate) fn find_recording_path(storage_root: &Path, recording_id: &str) -> Result<PathBuf> {
     // Try different possible file extensions
     let extensions = ["mp4", "mkv", "m3u8"];
 
@@ -1,5 +1,6 @@
 mod peer;
+mod qoe;
 mod whep;
 
-pub use peer::WebRtcPeerManager;
+pub use peer::{ClosedPeer, WebRtcPeerManager};
 pub use whep::{WhepHandler, WhepOffer, WhepAnswer};
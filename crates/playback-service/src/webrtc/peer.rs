@@ -1,19 +1,83 @@
 use anyhow::{anyhow, Result};
+use common::latency::now_ms;
+use common::playback::WebRtcSessionStats;
+use common::tenant_rls;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::RwLock;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::RTCPeerConnection;
-use webrtc::rtp_transceiver::rtp_sender::RTCRtpSender;
+use webrtc::rtcp::packet::Packet;
+use webrtc::rtcp::receiver_report::ReceiverReport;
+use webrtc::stats::{StatsReport, StatsReportType};
 use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
 use webrtc::track::track_local::TrackLocal;
 
+use super::qoe::SessionQoe;
+use crate::playback::PlaybackManager;
+
 /// WebRTC peer connection data
 struct PeerData {
     connection: Arc<RTCPeerConnection>,
     resource_id: String,
     video_track: Option<Arc<TrackLocalStaticRTP>>,
     audio_track: Option<Arc<TrackLocalStaticRTP>>,
+    /// Shared with the RTCP reader, which records the viewer's reports
+    qoe: Arc<Mutex<SessionQoe>>,
+    /// Tenant scope of the request that opened the session, to store its
+    /// summary under
+    tenant: String,
+}
+
+/// A peer taken out of the manager, with the summary of its session
+pub struct ClosedPeer {
+    pub tenant: String,
+    pub stats: WebRtcSessionStats,
+}
+
+/// How often the transport of each peer is sampled
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Clock rates of the tracks `add_peer` creates, to convert RTCP jitter
+const VIDEO_CLOCK_RATE: u32 = 90000;
+const AUDIO_CLOCK_RATE: u32 = 48000;
+
+/// Record the reception reports of the receiver reports among `packets`
+fn record_reports(qoe: &Mutex<SessionQoe>, packets: &[Box<dyn Packet + Send + Sync>], clock_rate: u32) {
+    for packet in packets {
+        if let Some(report) = packet.as_any().downcast_ref::<ReceiverReport>() {
+            let mut qoe = qoe.lock().unwrap_or_else(|e| e.into_inner());
+            for block in &report.reports {
+                qoe.record_report(block.ssrc, clock_rate, block.fraction_lost, block.total_lost, block.jitter);
+            }
+        }
+    }
+}
+
+/// Bytes sent, round trip time and candidate types of the nominated ICE
+/// candidate pair
+fn transport_sample(report: &StatsReport) -> Option<(u64, Option<u64>, Option<String>, Option<String>)> {
+    let pair = report.reports.values().find_map(|stats| match stats {
+        StatsReportType::CandidatePair(pair) if pair.nominated => Some(pair),
+        _ => None,
+    })?;
+    let rtt_ms = (pair.current_round_trip_time > 0.0).then(|| (pair.current_round_trip_time * 1000.0).round() as u64);
+    let candidate_type = |id: &str| {
+        report.reports.get(id).and_then(|stats| match stats {
+            StatsReportType::LocalCandidate(candidate) | StatsReportType::RemoteCandidate(candidate) => {
+                Some(candidate.candidate_type.to_string())
+            }
+            _ => None,
+        })
+    };
+    Some((
+        pair.bytes_sent,
+        rtt_ms,
+        candidate_type(&pair.local_candidate_id),
+        candidate_type(&pair.remote_candidate_id),
+    ))
 }
 
 /// Manages WebRTC peer connections
@@ -72,22 +136,32 @@ impl WebRtcPeerManager {
             .add_track(Arc::clone(&audio_track) as Arc<dyn TrackLocal + Send + Sync>)
             .await?;
 
-        // Read RTCP packets (required for WebRTC to work properly)
+        let qoe = Arc::new(Mutex::new(SessionQoe::new(session_id, resource_id, now_ms())));
+
+        // Read RTCP packets (required for WebRTC to work properly); the
+        // viewer's receiver reports carry its packet loss and jitter
+        let rtcp_qoe = qoe.clone();
         tokio::spawn(async move {
             let mut rtcp_buf_video = vec![0u8; 1500];
             let mut rtcp_buf_audio = vec![0u8; 1500];
             loop {
                 tokio::select! {
                     result = rtp_sender_video.read(&mut rtcp_buf_video) => {
-                        if let Err(e) = result {
-                            warn!("video RTCP read error: {}", e);
-                            break;
+                        match result {
+                            Ok((packets, _)) => record_reports(&rtcp_qoe, &packets, VIDEO_CLOCK_RATE),
+                            Err(e) => {
+                                warn!("video RTCP read error: {}", e);
+                                break;
+                            }
                         }
                     }
                     result = rtp_sender_audio.read(&mut rtcp_buf_audio) => {
-                        if let Err(e) = result {
-                            warn!("audio RTCP read error: {}", e);
-                            break;
+                        match result {
+                            Ok((packets, _)) => record_reports(&rtcp_qoe, &packets, AUDIO_CLOCK_RATE),
+                            Err(e) => {
+                                warn!("audio RTCP read error: {}", e);
+                                break;
+                            }
                         }
                     }
                 }
@@ -100,6 +174,8 @@ impl WebRtcPeerManager {
             resource_id: resource_id.to_string(),
             video_track: Some(video_track),
             audio_track: Some(audio_track),
+            qoe,
+            tenant: tenant_rls::current(),
         };
 
         let mut peers = self.peers.write().await;
@@ -109,23 +185,100 @@ impl WebRtcPeerManager {
         Ok(())
     }
 
-    /// Remove a peer connection
-    pub async fn remove_peer(&self, session_id: &str) -> Result<()> {
+    /// Remove a peer connection and return the summary of its session
+    pub async fn remove_peer(&self, session_id: &str) -> Result<ClosedPeer> {
         info!(session_id = %session_id, "removing WebRTC peer");
 
-        let mut peers = self.peers.write().await;
-        if let Some(peer_data) = peers.remove(session_id) {
+        let peer_data = self.peers.write().await.remove(session_id);
+        if let Some(peer_data) = peer_data {
+            // Take a last transport sample before the connection goes away
+            let report = peer_data.connection.get_stats().await;
+            let closed = Self::close_peer(&peer_data, Some(&report));
             // Close the peer connection
             if let Err(e) = peer_data.connection.close().await {
                 error!(session_id = %session_id, error = %e, "failed to close peer connection");
             }
             info!(session_id = %session_id, "WebRTC peer removed");
-            Ok(())
+            Ok(closed)
         } else {
             Err(anyhow!("Peer not found: {}", session_id))
         }
     }
 
+    fn close_peer(peer_data: &PeerData, report: Option<&StatsReport>) -> ClosedPeer {
+        let now = now_ms();
+        let mut qoe = peer_data.qoe.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((bytes_sent, rtt_ms, local, remote)) = report.and_then(transport_sample) {
+            qoe.record_transport(now, bytes_sent, rtt_ms, local, remote);
+        }
+        ClosedPeer {
+            tenant: peer_data.tenant.clone(),
+            stats: qoe.close(now),
+        }
+    }
+
+    /// Measurements of a live session so far
+    pub async fn stats(&self, session_id: &str) -> Option<WebRtcSessionStats> {
+        let peers = self.peers.read().await;
+        let peer_data = peers.get(session_id)?;
+        let stats = peer_data.qoe.lock().unwrap_or_else(|e| e.into_inner()).summary();
+        Some(stats)
+    }
+
+    /// Sample the transport of every peer, and take out the peers whose
+    /// connection failed or closed without the viewer deleting the session
+    pub async fn sample(&self) -> Vec<ClosedPeer> {
+        let peers: Vec<(String, Arc<RTCPeerConnection>, Arc<Mutex<SessionQoe>>)> = {
+            let peers = self.peers.read().await;
+            peers
+                .iter()
+                .map(|(id, p)| (id.clone(), p.connection.clone(), p.qoe.clone()))
+                .collect()
+        };
+        let mut gone = Vec::new();
+        for (session_id, connection, qoe) in peers {
+            let report = connection.get_stats().await;
+            if let Some((bytes_sent, rtt_ms, local, remote)) = transport_sample(&report) {
+                qoe.lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .record_transport(now_ms(), bytes_sent, rtt_ms, local, remote);
+            }
+            if matches!(
+                connection.connection_state(),
+                RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed
+            ) {
+                gone.push(session_id);
+            }
+        }
+
+        let mut closed = Vec::with_capacity(gone.len());
+        for session_id in gone {
+            let peer_data = self.peers.write().await.remove(&session_id);
+            if let Some(peer_data) = peer_data {
+                info!(session_id = %session_id, "WebRTC peer connection ended, removing peer");
+                if let Err(e) = peer_data.connection.close().await {
+                    debug!(session_id = %session_id, error = %e, "failed to close ended peer connection");
+                }
+                closed.push(Self::close_peer(&peer_data, None));
+            }
+        }
+        closed
+    }
+
+    /// Sample every peer every few seconds and keep the summaries of the
+    /// sessions that ended on their own
+    pub fn spawn_stats(self: Arc<Self>, manager: Arc<PlaybackManager>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(STATS_INTERVAL);
+            loop {
+                ticker.tick().await;
+                for closed in self.sample().await {
+                    tenant_rls::scope(closed.tenant, manager.save_webrtc_stats(&closed.stats)).await;
+                }
+            }
+        });
+    }
+
     /// Get peer connection
     pub async fn get_peer(&self, session_id: &str) -> Option<Arc<RTCPeerConnection>> {
         let peers = self.peers.read().await;
@@ -144,6 +297,35 @@ impl WebRtcPeerManager {
         peers.get(session_id).and_then(|p| p.audio_track.clone())
     }
 
+    /// Round trip time to each viewer in milliseconds, with the session's
+    /// resource id, from the nominated ICE candidate pair; peers that have
+    /// not measured one yet are left out
+    pub async fn round_trip_times(&self) -> Vec<(String, String, u64)> {
+        let peers: Vec<(String, String, Arc<RTCPeerConnection>)> = {
+            let peers = self.peers.read().await;
+            peers
+                .iter()
+                .map(|(id, p)| (id.clone(), p.resource_id.clone(), p.connection.clone()))
+                .collect()
+        };
+        let mut rtts = Vec::with_capacity(peers.len());
+        for (session_id, resource_id, connection) in peers {
+            let report = connection.get_stats().await;
+            let rtt_secs = report
+                .reports
+                .values()
+                .filter_map(|stats| match stats {
+                    StatsReportType::CandidatePair(pair) if pair.nominated => Some(pair.current_round_trip_time),
+                    _ => None,
+                })
+                .fold(0.0_f64, f64::max);
+            if rtt_secs > 0.0 {
+                rtts.push((session_id, resource_id, (rtt_secs * 1000.0).round() as u64));
+            }
+        }
+        rtts
+    }
+
     /// List all active sessions
     pub async fn list_sessions(&self) -> Vec<String> {
         let peers = self.peers.read().await;
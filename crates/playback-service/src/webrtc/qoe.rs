@@ -0,0 +1,155 @@
+//! Quality of experience of a WHEP session.
+//!
+//! [`SessionQoe`] folds what the peer connection measures into a
+//! [`WebRtcSessionStats`] summary: transport samples taken from the nominated
+//! ICE candidate pair (bytes sent, round trip time, candidate types) and the
+//! RTCP receiver reports the viewer sends for each track (packet loss and
+//! jitter). It does no I/O, so the peer manager decides when to sample.
+
+use common::playback::WebRtcSessionStats;
+use std::collections::HashMap;
+
+/// Running measurements of one session
+#[derive(Debug, Clone)]
+pub struct SessionQoe {
+    stats: WebRtcSessionStats,
+    /// Time and byte count of the previous transport sample
+    last_sample: Option<(u64, u64)>,
+    rtt_total_ms: u64,
+    rtt_samples: u64,
+    /// Cumulative loss the viewer last reported per SSRC
+    lost_by_ssrc: HashMap<u32, u32>,
+}
+
+impl SessionQoe {
+    /// `started_at` in unix milliseconds
+    pub fn new(session_id: &str, resource_id: &str, started_at: u64) -> Self {
+        Self {
+            stats: WebRtcSessionStats {
+                session_id: session_id.to_string(),
+                resource_id: resource_id.to_string(),
+                started_at,
+                ..Default::default()
+            },
+            last_sample: None,
+            rtt_total_ms: 0,
+            rtt_samples: 0,
+            lost_by_ssrc: HashMap::new(),
+        }
+    }
+
+    /// Record a sample of the nominated candidate pair taken at `at_ms`
+    pub fn record_transport(
+        &mut self,
+        at_ms: u64,
+        bytes_sent: u64,
+        rtt_ms: Option<u64>,
+        local_candidate_type: Option<String>,
+        remote_candidate_type: Option<String>,
+    ) {
+        let stats = &mut self.stats;
+        stats.samples += 1;
+        if let Some((last_at, last_bytes)) = self.last_sample {
+            if at_ms > last_at {
+                let bits = bytes_sent.saturating_sub(last_bytes) as f64 * 8.0;
+                // Bits per millisecond are kilobits per second
+                stats.bitrate_kbps = Some(bits / (at_ms - last_at) as f64);
+            }
+        }
+        if at_ms > stats.started_at {
+            stats.avg_bitrate_kbps = Some(bytes_sent as f64 * 8.0 / (at_ms - stats.started_at) as f64);
+        }
+        self.last_sample = Some((at_ms, bytes_sent));
+        stats.bytes_sent = stats.bytes_sent.max(bytes_sent);
+
+        if let Some(rtt) = rtt_ms {
+            self.rtt_total_ms += rtt;
+            self.rtt_samples += 1;
+            stats.rtt_ms = Some(rtt);
+            stats.avg_rtt_ms = Some(self.rtt_total_ms / self.rtt_samples);
+            stats.max_rtt_ms = Some(stats.max_rtt_ms.map_or(rtt, |max| max.max(rtt)));
+        }
+        if local_candidate_type.is_some() {
+            stats.local_candidate_type = local_candidate_type;
+        }
+        if remote_candidate_type.is_some() {
+            stats.remote_candidate_type = remote_candidate_type;
+        }
+    }
+
+    /// Record one reception report block of an RTCP receiver report.
+    /// `fraction_lost` is the RTCP fixed point fraction (of 256) and `jitter`
+    /// is in units of the track's `clock_rate`.
+    pub fn record_report(&mut self, ssrc: u32, clock_rate: u32, fraction_lost: u8, total_lost: u32, jitter: u32) {
+        let stats = &mut self.stats;
+        stats.reports += 1;
+
+        self.lost_by_ssrc.insert(ssrc, total_lost);
+        stats.packets_lost = self.lost_by_ssrc.values().map(|lost| *lost as u64).sum();
+
+        let fraction = fraction_lost as f64 / 256.0;
+        stats.fraction_lost = Some(fraction);
+        stats.max_fraction_lost = Some(stats.max_fraction_lost.map_or(fraction, |max| max.max(fraction)));
+
+        if clock_rate > 0 {
+            let jitter_ms = jitter as f64 * 1000.0 / clock_rate as f64;
+            stats.jitter_ms = Some(jitter_ms);
+            stats.max_jitter_ms = Some(stats.max_jitter_ms.map_or(jitter_ms, |max| max.max(jitter_ms)));
+        }
+    }
+
+    /// Measurements so far
+    pub fn summary(&self) -> WebRtcSessionStats {
+        self.stats.clone()
+    }
+
+    /// Final summary of a session that ended at `at_ms`
+    pub fn close(&self, at_ms: u64) -> WebRtcSessionStats {
+        WebRtcSessionStats {
+            ended_at: Some(at_ms),
+            ..self.summary()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_bitrate_and_rtt_from_transport_samples() {
+        let mut qoe = SessionQoe::new("s1", "camera-1", 10_000);
+        qoe.record_transport(15_000, 500_000, Some(40), Some("host".into()), Some("srflx".into()));
+        qoe.record_transport(20_000, 1_500_000, Some(80), None, None);
+
+        let stats = qoe.summary();
+        assert_eq!(stats.samples, 2);
+        assert_eq!(stats.bytes_sent, 1_500_000);
+        // 1 MB in 5 s, and 1.5 MB in 10 s
+        assert_eq!(stats.bitrate_kbps, Some(1600.0));
+        assert_eq!(stats.avg_bitrate_kbps, Some(1200.0));
+        assert_eq!((stats.rtt_ms, stats.avg_rtt_ms, stats.max_rtt_ms), (Some(80), Some(60), Some(80)));
+        assert_eq!(stats.local_candidate_type.as_deref(), Some("host"));
+        assert_eq!(stats.remote_candidate_type.as_deref(), Some("srflx"));
+        assert_eq!(stats.ended_at, None);
+    }
+
+    #[test]
+    fn sums_loss_over_tracks_and_converts_jitter() {
+        let mut qoe = SessionQoe::new("s1", "camera-1", 0);
+        // Video at 90 kHz, audio at 48 kHz
+        qoe.record_report(1, 90_000, 64, 10, 900);
+        qoe.record_report(2, 48_000, 0, 3, 480);
+        // Totals are cumulative per track, not increments
+        qoe.record_report(1, 90_000, 0, 12, 1_800);
+
+        let stats = qoe.close(30_000);
+        assert_eq!(stats.reports, 3);
+        assert_eq!(stats.packets_lost, 15);
+        assert_eq!(stats.fraction_lost, Some(0.0));
+        assert_eq!(stats.max_fraction_lost, Some(0.25));
+        assert_eq!(stats.jitter_ms, Some(20.0));
+        assert_eq!(stats.max_jitter_ms, Some(20.0));
+        assert_eq!(stats.ended_at, Some(30_000));
+    }
+}
@@ -12,7 +12,7 @@ use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
 use interceptor::registry::Registry;
 
-use super::peer::WebRtcPeerManager;
+use super::peer::{ClosedPeer, WebRtcPeerManager};
 
 /// WHEP (WebRTC-HTTP Egress Protocol) handler
 ///
@@ -105,7 +105,7 @@ impl WhepHandler {
     }
 
     /// Handle session deletion
-    pub async fn delete_session(&self, session_id: &str) -> Result<()> {
+    pub async fn delete_session(&self, session_id: &str) -> Result<ClosedPeer> {
         info!(session_id = %session_id, "deleting WHEP session");
         self.peer_manager.remove_peer(session_id).await
     }
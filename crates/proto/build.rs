@@ -0,0 +1,19 @@
+use std::env;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Prefer a system protoc when PROTOC is set, otherwise use the vendored binary
+    if env::var_os("PROTOC").is_none() {
+        env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .compile_protos(
+            &["proto/quadrant/v1/leases.proto", "proto/quadrant/v1/ai.proto"],
+            &["proto"],
+        )?;
+
+    println!("cargo:rerun-if-changed=proto");
+    Ok(())
+}
@@ -0,0 +1,466 @@
+//! Shared gRPC contracts for internal Quadrant VMS services.
+//!
+//! The `.proto` definitions live in `proto/quadrant/v1` and are compiled by
+//! `build.rs` into tonic clients and servers. Conversions to and from the
+//! `common` REST types are provided so services can expose both transports
+//! from the same handlers.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use common::{ai_tasks, leases};
+
+pub mod v1 {
+    tonic::include_proto!("quadrant.v1");
+}
+
+pub use v1::ai_service_client::AiServiceClient;
+pub use v1::ai_service_server::{AiService, AiServiceServer};
+pub use v1::lease_service_client::LeaseServiceClient;
+pub use v1::lease_service_server::{LeaseService, LeaseServiceServer};
+
+/// Errors raised when a protobuf message cannot be mapped to a `common` type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    MissingField(&'static str),
+    InvalidEnum(&'static str, i32),
+    InvalidJson(&'static str),
+    InvalidFrameData,
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::MissingField(field) => write!(f, "missing field '{field}'"),
+            ConversionError::InvalidEnum(field, value) => {
+                write!(f, "invalid value {value} for '{field}'")
+            }
+            ConversionError::InvalidJson(field) => write!(f, "field '{field}' is not valid JSON"),
+            ConversionError::InvalidFrameData => f.write_str("frame data is not valid base64"),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl From<ConversionError> for tonic::Status {
+    fn from(err: ConversionError) -> Self {
+        tonic::Status::invalid_argument(err.to_string())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Leases
+// ---------------------------------------------------------------------------
+
+impl From<leases::LeaseKind> for v1::LeaseKind {
+    fn from(kind: leases::LeaseKind) -> Self {
+        match kind {
+            leases::LeaseKind::Stream => v1::LeaseKind::Stream,
+            leases::LeaseKind::Recorder => v1::LeaseKind::Recorder,
+            leases::LeaseKind::Pipeline => v1::LeaseKind::Pipeline,
+            leases::LeaseKind::Ai => v1::LeaseKind::Ai,
+        }
+    }
+}
+
+fn lease_kind_from_i32(value: i32) -> Result<leases::LeaseKind, ConversionError> {
+    match v1::LeaseKind::try_from(value) {
+        Ok(v1::LeaseKind::Stream) => Ok(leases::LeaseKind::Stream),
+        Ok(v1::LeaseKind::Recorder) => Ok(leases::LeaseKind::Recorder),
+        Ok(v1::LeaseKind::Pipeline) => Ok(leases::LeaseKind::Pipeline),
+        Ok(v1::LeaseKind::Ai) => Ok(leases::LeaseKind::Ai),
+        Ok(v1::LeaseKind::Unspecified) | Err(_) => Err(ConversionError::InvalidEnum("kind", value)),
+    }
+}
+
+impl From<leases::LeaseRecord> for v1::LeaseRecord {
+    fn from(record: leases::LeaseRecord) -> Self {
+        Self {
+            lease_id: record.lease_id,
+            resource_id: record.resource_id,
+            holder_id: record.holder_id,
+            kind: v1::LeaseKind::from(record.kind) as i32,
+            expires_at_epoch_secs: record.expires_at_epoch_secs,
+            version: record.version,
+        }
+    }
+}
+
+impl TryFrom<v1::LeaseRecord> for leases::LeaseRecord {
+    type Error = ConversionError;
+
+    fn try_from(record: v1::LeaseRecord) -> Result<Self, Self::Error> {
+        Ok(Self {
+            lease_id: record.lease_id,
+            resource_id: record.resource_id,
+            holder_id: record.holder_id,
+            kind: lease_kind_from_i32(record.kind)?,
+            expires_at_epoch_secs: record.expires_at_epoch_secs,
+            version: record.version,
+        })
+    }
+}
+
+impl From<leases::LeaseAcquireRequest> for v1::AcquireLeaseRequest {
+    fn from(req: leases::LeaseAcquireRequest) -> Self {
+        Self {
+            resource_id: req.resource_id,
+            holder_id: req.holder_id,
+            kind: v1::LeaseKind::from(req.kind) as i32,
+            ttl_secs: req.ttl_secs,
+        }
+    }
+}
+
+impl TryFrom<v1::AcquireLeaseRequest> for leases::LeaseAcquireRequest {
+    type Error = ConversionError;
+
+    fn try_from(req: v1::AcquireLeaseRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            resource_id: req.resource_id,
+            holder_id: req.holder_id,
+            kind: lease_kind_from_i32(req.kind)?,
+            ttl_secs: req.ttl_secs,
+        })
+    }
+}
+
+impl From<leases::LeaseAcquireResponse> for v1::AcquireLeaseResponse {
+    fn from(resp: leases::LeaseAcquireResponse) -> Self {
+        Self {
+            granted: resp.granted,
+            record: resp.record.map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<v1::AcquireLeaseResponse> for leases::LeaseAcquireResponse {
+    type Error = ConversionError;
+
+    fn try_from(resp: v1::AcquireLeaseResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            granted: resp.granted,
+            record: resp.record.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+impl From<leases::LeaseRenewRequest> for v1::RenewLeaseRequest {
+    fn from(req: leases::LeaseRenewRequest) -> Self {
+        Self {
+            lease_id: req.lease_id,
+            ttl_secs: req.ttl_secs,
+        }
+    }
+}
+
+impl From<v1::RenewLeaseRequest> for leases::LeaseRenewRequest {
+    fn from(req: v1::RenewLeaseRequest) -> Self {
+        Self {
+            lease_id: req.lease_id,
+            ttl_secs: req.ttl_secs,
+        }
+    }
+}
+
+impl From<leases::LeaseRenewResponse> for v1::RenewLeaseResponse {
+    fn from(resp: leases::LeaseRenewResponse) -> Self {
+        Self {
+            renewed: resp.renewed,
+            record: resp.record.map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<v1::RenewLeaseResponse> for leases::LeaseRenewResponse {
+    type Error = ConversionError;
+
+    fn try_from(resp: v1::RenewLeaseResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            renewed: resp.renewed,
+            record: resp.record.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+impl From<leases::LeaseReleaseRequest> for v1::ReleaseLeaseRequest {
+    fn from(req: leases::LeaseReleaseRequest) -> Self {
+        Self {
+            lease_id: req.lease_id,
+        }
+    }
+}
+
+impl From<v1::ReleaseLeaseRequest> for leases::LeaseReleaseRequest {
+    fn from(req: v1::ReleaseLeaseRequest) -> Self {
+        Self {
+            lease_id: req.lease_id,
+        }
+    }
+}
+
+impl From<leases::LeaseReleaseResponse> for v1::ReleaseLeaseResponse {
+    fn from(resp: leases::LeaseReleaseResponse) -> Self {
+        Self {
+            released: resp.released,
+        }
+    }
+}
+
+impl From<v1::ReleaseLeaseResponse> for leases::LeaseReleaseResponse {
+    fn from(resp: v1::ReleaseLeaseResponse) -> Self {
+        Self {
+            released: resp.released,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// AI
+// ---------------------------------------------------------------------------
+
+fn json_to_string(value: Option<serde_json::Value>) -> Option<String> {
+    value.map(|v| v.to_string())
+}
+
+fn string_to_json(
+    value: Option<String>,
+    field: &'static str,
+) -> Result<Option<serde_json::Value>, ConversionError> {
+    value
+        .map(|s| serde_json::from_str(&s).map_err(|_| ConversionError::InvalidJson(field)))
+        .transpose()
+}
+
+impl From<ai_tasks::BoundingBox> for v1::BoundingBox {
+    fn from(bbox: ai_tasks::BoundingBox) -> Self {
+        Self {
+            x: bbox.x,
+            y: bbox.y,
+            width: bbox.width,
+            height: bbox.height,
+        }
+    }
+}
+
+impl From<v1::BoundingBox> for ai_tasks::BoundingBox {
+    fn from(bbox: v1::BoundingBox) -> Self {
+        Self {
+            x: bbox.x,
+            y: bbox.y,
+            width: bbox.width,
+            height: bbox.height,
+        }
+    }
+}
+
+impl From<ai_tasks::Detection> for v1::Detection {
+    fn from(det: ai_tasks::Detection) -> Self {
+        Self {
+            class: det.class,
+            confidence: det.confidence,
+            bbox: Some(det.bbox.into()),
+            metadata_json: json_to_string(det.metadata),
+        }
+    }
+}
+
+impl TryFrom<v1::Detection> for ai_tasks::Detection {
+    type Error = ConversionError;
+
+    fn try_from(det: v1::Detection) -> Result<Self, Self::Error> {
+        Ok(Self {
+            class: det.class,
+            confidence: det.confidence,
+            bbox: det.bbox.ok_or(ConversionError::MissingField("bbox"))?.into(),
+            metadata: string_to_json(det.metadata_json, "metadata_json")?,
+        })
+    }
+}
+
+/// REST frames carry base64; gRPC frames carry raw bytes
+impl TryFrom<ai_tasks::VideoFrame> for v1::VideoFrame {
+    type Error = ConversionError;
+
+    fn try_from(frame: ai_tasks::VideoFrame) -> Result<Self, Self::Error> {
+        let data = BASE64
+            .decode(frame.data.as_bytes())
+            .map_err(|_| ConversionError::InvalidFrameData)?;
+        Ok(Self {
+            source_id: frame.source_id,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+            data,
+        })
+    }
+}
+
+impl From<v1::VideoFrame> for ai_tasks::VideoFrame {
+    fn from(frame: v1::VideoFrame) -> Self {
+        Self {
+            source_id: frame.source_id,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+            data: BASE64.encode(&frame.data),
+            audio: None,
+        }
+    }
+}
+
+impl From<ai_tasks::AiResult> for v1::AiResult {
+    fn from(result: ai_tasks::AiResult) -> Self {
+        Self {
+            task_id: result.task_id,
+            timestamp: result.timestamp,
+            plugin_type: result.plugin_type,
+            detections: result.detections.into_iter().map(Into::into).collect(),
+            confidence: result.confidence,
+            processing_time_ms: result.processing_time_ms,
+            metadata_json: json_to_string(result.metadata),
+        }
+    }
+}
+
+impl TryFrom<v1::AiResult> for ai_tasks::AiResult {
+    type Error = ConversionError;
+
+    fn try_from(result: v1::AiResult) -> Result<Self, Self::Error> {
+        Ok(Self {
+            task_id: result.task_id,
+            timestamp: result.timestamp,
+            plugin_type: result.plugin_type,
+            detections: result
+                .detections
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            confidence: result.confidence,
+            processing_time_ms: result.processing_time_ms,
+            metadata: string_to_json(result.metadata_json, "metadata_json")?,
+        })
+    }
+}
+
+impl From<ai_tasks::PluginInfo> for v1::PluginInfo {
+    fn from(info: ai_tasks::PluginInfo) -> Self {
+        Self {
+            id: info.id,
+            name: info.name,
+            description: info.description,
+            version: info.version,
+            config_schema_json: json_to_string(info.config_schema),
+            supported_formats: info.supported_formats,
+            requires_gpu: info.requires_gpu,
+        }
+    }
+}
+
+impl TryFrom<v1::PluginInfo> for ai_tasks::PluginInfo {
+    type Error = ConversionError;
+
+    fn try_from(info: v1::PluginInfo) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: info.id,
+            name: info.name,
+            description: info.description,
+            version: info.version,
+            config_schema: string_to_json(info.config_schema_json, "config_schema_json")?,
+            supported_formats: info.supported_formats,
+            requires_gpu: info.requires_gpu,
+            backend: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_record_roundtrip() {
+        let record = leases::LeaseRecord {
+            lease_id: "lease-1".into(),
+            resource_id: "stream-1".into(),
+            holder_id: "node-a".into(),
+            kind: leases::LeaseKind::Recorder,
+            expires_at_epoch_secs: 1_700_000_000,
+            version: 3,
+        };
+
+        let wire: v1::LeaseRecord = record.clone().into();
+        assert_eq!(wire.kind, v1::LeaseKind::Recorder as i32);
+
+        let back: leases::LeaseRecord = wire.try_into().unwrap();
+        assert_eq!(back.lease_id, record.lease_id);
+        assert_eq!(back.kind, leases::LeaseKind::Recorder);
+        assert_eq!(back.version, 3);
+    }
+
+    #[test]
+    fn test_unspecified_lease_kind_rejected() {
+        let req = v1::AcquireLeaseRequest {
+            resource_id: "r".into(),
+            holder_id: "h".into(),
+            kind: v1::LeaseKind::Unspecified as i32,
+            ttl_secs: 30,
+        };
+        let err = leases::LeaseAcquireRequest::try_from(req).unwrap_err();
+        assert_eq!(err, ConversionError::InvalidEnum("kind", 0));
+    }
+
+    #[test]
+    fn test_video_frame_base64_roundtrip() {
+        let frame = ai_tasks::VideoFrame {
+            source_id: "cam-1".into(),
+            timestamp: 1,
+            sequence: 2,
+            width: 640,
+            height: 480,
+            format: "jpeg".into(),
+            data: BASE64.encode([0xff, 0xd8, 0xff]),
+            audio: None,
+        };
+
+        let wire = v1::VideoFrame::try_from(frame.clone()).unwrap();
+        assert_eq!(wire.data, vec![0xff, 0xd8, 0xff]);
+
+        let back = ai_tasks::VideoFrame::from(wire);
+        assert_eq!(back.data, frame.data);
+    }
+
+    #[test]
+    fn test_ai_result_metadata_json() {
+        let result = ai_tasks::AiResult {
+            task_id: "t".into(),
+            timestamp: 5,
+            plugin_type: "yolov8".into(),
+            detections: vec![ai_tasks::Detection {
+                class: "person".into(),
+                confidence: 0.9,
+                bbox: ai_tasks::BoundingBox {
+                    x: 1,
+                    y: 2,
+                    width: 3,
+                    height: 4,
+                },
+                metadata: Some(serde_json::json!({"track_id": 7})),
+            }],
+            confidence: Some(0.9),
+            processing_time_ms: Some(12),
+            metadata: None,
+        };
+
+        let wire: v1::AiResult = result.into();
+        let back = ai_tasks::AiResult::try_from(wire).unwrap();
+        assert_eq!(back.detections.len(), 1);
+        assert_eq!(
+            back.detections[0].metadata,
+            Some(serde_json::json!({"track_id": 7}))
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Alert rules, actions and events via alert-service
+
+use alert_service::types::{
+    AlertAction, AlertEvent, AlertRule, CreateAlertActionRequest, CreateAlertRuleRequest,
+    TriggerAlertRequest, UpdateAlertRuleRequest,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::service::{path, Service};
+
+/// Result of [`AlertsClient::trigger`]
+#[derive(Debug, Clone, Deserialize)]
+pub struct TriggerAlertResponse {
+    pub fired_count: usize,
+    pub events: Vec<AlertEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertsClient {
+    service: Service,
+}
+
+impl AlertsClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    pub async fn create_rule(&self, request: &CreateAlertRuleRequest) -> Result<AlertRule> {
+        self.service.post("v1/rules", request).await
+    }
+
+    pub async fn list_rules(&self, enabled_only: bool) -> Result<Vec<AlertRule>> {
+        self.service
+            .get_query("v1/rules", &[("enabled_only", enabled_only)])
+            .await
+    }
+
+    pub async fn get_rule(&self, rule_id: Uuid) -> Result<AlertRule> {
+        self.service
+            .get(&path(&["v1", "rules", &rule_id.to_string()]))
+            .await
+    }
+
+    pub async fn update_rule(
+        &self,
+        rule_id: Uuid,
+        request: &UpdateAlertRuleRequest,
+    ) -> Result<AlertRule> {
+        self.service
+            .put(&path(&["v1", "rules", &rule_id.to_string()]), request)
+            .await
+    }
+
+    pub async fn delete_rule(&self, rule_id: Uuid) -> Result<()> {
+        self.service
+            .delete_no_content(&path(&["v1", "rules", &rule_id.to_string()]))
+            .await
+    }
+
+    pub async fn create_action(
+        &self,
+        rule_id: Uuid,
+        request: &CreateAlertActionRequest,
+    ) -> Result<AlertAction> {
+        self.service
+            .post(&path(&["v1", "rules", &rule_id.to_string(), "actions"]), request)
+            .await
+    }
+
+    pub async fn list_actions(&self, rule_id: Uuid) -> Result<Vec<AlertAction>> {
+        self.service
+            .get(&path(&["v1", "rules", &rule_id.to_string(), "actions"]))
+            .await
+    }
+
+    pub async fn delete_action(&self, action_id: Uuid) -> Result<()> {
+        self.service
+            .delete_no_content(&path(&["v1", "actions", &action_id.to_string()]))
+            .await
+    }
+
+    pub async fn list_events(&self, limit: i64, offset: i64) -> Result<Vec<AlertEvent>> {
+        self.service
+            .get_query("v1/events", &[("limit", limit), ("offset", offset)])
+            .await
+    }
+
+    pub async fn get_event(&self, event_id: Uuid) -> Result<AlertEvent> {
+        self.service
+            .get(&path(&["v1", "events", &event_id.to_string()]))
+            .await
+    }
+
+    pub async fn trigger(&self, request: &TriggerAlertRequest) -> Result<TriggerAlertResponse> {
+        self.service.post("v1/trigger", request).await
+    }
+}
@@ -0,0 +1,106 @@
+//! Login, users, tenants and API tokens via auth-service
+
+use auth_service::models::{
+    CreateApiTokenRequest, CreateApiTokenResponse, CreateTenantRequest, CreateUserRequest,
+    JwtClaims, LoginRequest, LoginResponse, Tenant, UpdateUserRequest, User,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::service::{path, Service};
+
+#[derive(Serialize)]
+struct VerifyTokenRequest<'a> {
+    token: &'a str,
+}
+
+/// Result of [`AuthClient::verify`]
+#[derive(Debug, Deserialize)]
+pub struct VerifyTokenResponse {
+    pub valid: bool,
+    pub claims: Option<JwtClaims>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthClient {
+    service: Service,
+}
+
+impl AuthClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    /// Exchange credentials for a JWT; use [`crate::QuadrantClient::with_token`]
+    /// with `access_token` for subsequent calls
+    pub async fn login(&self, request: &LoginRequest) -> Result<LoginResponse> {
+        self.service.post("v1/auth/login", request).await
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<VerifyTokenResponse> {
+        self.service
+            .post("v1/auth/verify", &VerifyTokenRequest { token })
+            .await
+    }
+
+    pub async fn list_users(&self, tenant_id: Option<&str>) -> Result<Vec<User>> {
+        match tenant_id {
+            Some(tenant_id) => {
+                self.service
+                    .get_query("v1/users", &[("tenant_id", tenant_id)])
+                    .await
+            }
+            None => self.service.get("v1/users").await,
+        }
+    }
+
+    pub async fn create_user(&self, request: &CreateUserRequest) -> Result<User> {
+        self.service.post("v1/users", request).await
+    }
+
+    pub async fn get_user(&self, user_id: &str) -> Result<User> {
+        self.service.get(&path(&["v1", "users", user_id])).await
+    }
+
+    pub async fn update_user(&self, user_id: &str, request: &UpdateUserRequest) -> Result<User> {
+        self.service
+            .put(&path(&["v1", "users", user_id]), request)
+            .await
+    }
+
+    pub async fn delete_user(&self, user_id: &str) -> Result<()> {
+        self.service
+            .delete_no_content(&path(&["v1", "users", user_id]))
+            .await
+    }
+
+    pub async fn create_api_token(
+        &self,
+        user_id: &str,
+        request: &CreateApiTokenRequest,
+    ) -> Result<CreateApiTokenResponse> {
+        self.service
+            .post(&path(&["v1", "users", user_id, "tokens"]), request)
+            .await
+    }
+
+    pub async fn revoke_api_token(&self, token_id: &str) -> Result<()> {
+        let _: serde_json::Value = self
+            .service
+            .post(&path(&["v1", "tokens", token_id, "revoke"]), &serde_json::json!({}))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list_tenants(&self) -> Result<Vec<Tenant>> {
+        self.service.get("v1/tenants").await
+    }
+
+    pub async fn create_tenant(&self, request: &CreateTenantRequest) -> Result<Tenant> {
+        self.service.post("v1/tenants", request).await
+    }
+
+    pub async fn get_tenant(&self, tenant_id: &str) -> Result<Tenant> {
+        self.service.get(&path(&["v1", "tenants", tenant_id])).await
+    }
+}
@@ -0,0 +1,156 @@
+//! Cameras and devices via device-manager
+
+use device_manager::types::{
+    BatchUpdateRequest, BatchUpdateResponse, CreateDeviceRequest, Device, DeviceHealthHistory,
+    DeviceListQuery, DeviceStatus, DeviceType, ProbeResult, PtzMoveRequest, PtzPreset, PtzStatus,
+    PtzStopRequest, RelayOutput, SetRelayOutputRequest, SetRelayOutputResponse, UpdateDeviceRequest,
+};
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::service::{path, Service};
+
+/// Query-string form of [`DeviceListQuery`]; `tags` is not expressible as a
+/// flat query parameter and is not sent
+#[derive(Serialize)]
+struct ListParams<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<&'a DeviceStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_type: Option<&'a DeviceType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zone: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DevicesClient {
+    service: Service,
+}
+
+impl DevicesClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    /// Create a device; pass an idempotency key to make retries safe
+    pub async fn create(
+        &self,
+        request: &CreateDeviceRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<Device> {
+        self.service
+            .post_idempotent("v1/devices", request, idempotency_key)
+            .await
+    }
+
+    pub async fn list(&self, query: &DeviceListQuery) -> Result<Vec<Device>> {
+        let params = ListParams {
+            tenant_id: query.tenant_id.as_deref(),
+            status: query.status.as_ref(),
+            device_type: query.device_type.as_ref(),
+            zone: query.zone.as_deref(),
+            limit: query.limit,
+            offset: query.offset,
+        };
+        self.service.get_query("v1/devices", &params).await
+    }
+
+    pub async fn get(&self, device_id: &str) -> Result<Device> {
+        self.service.get(&path(&["v1", "devices", device_id])).await
+    }
+
+    pub async fn update(&self, device_id: &str, request: &UpdateDeviceRequest) -> Result<Device> {
+        self.service
+            .put(&path(&["v1", "devices", device_id]), request)
+            .await
+    }
+
+    pub async fn delete(&self, device_id: &str) -> Result<()> {
+        self.service
+            .delete_no_content(&path(&["v1", "devices", device_id]))
+            .await
+    }
+
+    pub async fn batch_update(&self, request: &BatchUpdateRequest) -> Result<BatchUpdateResponse> {
+        self.service.put("v1/devices/batch", request).await
+    }
+
+    pub async fn probe(&self, device_id: &str) -> Result<ProbeResult> {
+        self.service
+            .post(&path(&["v1", "devices", device_id, "probe"]), &serde_json::json!({}))
+            .await
+    }
+
+    /// Current health summary (`status`, `last_seen_at`, `consecutive_failures`, ...)
+    pub async fn health(&self, device_id: &str) -> Result<serde_json::Value> {
+        self.service
+            .get(&path(&["v1", "devices", device_id, "health"]))
+            .await
+    }
+
+    pub async fn health_history(
+        &self,
+        device_id: &str,
+        limit: Option<i64>,
+    ) -> Result<Vec<DeviceHealthHistory>> {
+        self.service
+            .get_query(
+                &path(&["v1", "devices", device_id, "health", "history"]),
+                &[("limit", limit.unwrap_or(100))],
+            )
+            .await
+    }
+
+    pub async fn ptz_move(&self, device_id: &str, request: &PtzMoveRequest) -> Result<()> {
+        let _: serde_json::Value = self
+            .service
+            .post(&path(&["v1", "devices", device_id, "ptz", "move"]), request)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn ptz_stop(&self, device_id: &str, request: &PtzStopRequest) -> Result<()> {
+        let _: serde_json::Value = self
+            .service
+            .post(&path(&["v1", "devices", device_id, "ptz", "stop"]), request)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn ptz_status(&self, device_id: &str) -> Result<PtzStatus> {
+        self.service
+            .get(&path(&["v1", "devices", device_id, "ptz", "status"]))
+            .await
+    }
+
+    pub async fn ptz_presets(&self, device_id: &str) -> Result<Vec<PtzPreset>> {
+        self.service
+            .get(&path(&["v1", "devices", device_id, "ptz", "presets"]))
+            .await
+    }
+
+    /// Relay outputs (sirens, door strikes) of an ONVIF device
+    pub async fn relay_outputs(&self, device_id: &str) -> Result<Vec<RelayOutput>> {
+        self.service
+            .get(&path(&["v1", "devices", device_id, "io", "relays"]))
+            .await
+    }
+
+    /// Switch a relay output; needs the `device:output` permission
+    pub async fn set_relay_output(
+        &self,
+        device_id: &str,
+        token: &str,
+        request: &SetRelayOutputRequest,
+    ) -> Result<SetRelayOutputResponse> {
+        self.service
+            .post(&path(&["v1", "devices", device_id, "io", "relays", token]), request)
+            .await
+    }
+}
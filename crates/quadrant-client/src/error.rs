@@ -0,0 +1,43 @@
+use reqwest::StatusCode;
+
+/// Errors returned by the client
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// No base URL was configured for the service being called
+    #[error("{0} endpoint is not configured")]
+    NotConfigured(&'static str),
+
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+
+    /// Transport failure or undecodable response body
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The service answered with a non-success status
+    #[error("{status}: {message}")]
+    Api { status: StatusCode, message: String },
+}
+
+impl ClientError {
+    /// HTTP status for [`ClientError::Api`] errors
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClientError::Api { status, .. } => Some(*status),
+            ClientError::Http(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    /// The resource or cursor expired, e.g. a change feed cursor older than
+    /// the feed's retention
+    pub fn is_gone(&self) -> bool {
+        self.status() == Some(StatusCode::GONE)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
@@ -0,0 +1,392 @@
+//! Typed async client for the Quadrant VMS HTTP APIs.
+//!
+//! Request and response types are the services' own DTOs (`common` for
+//! streams, recordings and playback; the service crates for devices, alerts
+//! and auth, behind the matching cargo features), so the client cannot drift
+//! from the handlers.
+//!
+//! ```ignore
+//! let client = QuadrantClient::builder()
+//!     .gateway("http://localhost:8081")?
+//!     .device_manager("http://localhost:8088")?
+//!     .token(jwt)
+//!     .build()?;
+//!
+//! let device = client.devices()?.get("cam-1").await?;
+//! let streams = client.streams()?.list().await?;
+//! ```
+
+pub mod error;
+pub mod nodes;
+pub mod playback;
+pub mod recording_sync;
+pub mod recordings;
+pub mod retention;
+mod service;
+pub mod streams;
+
+#[cfg(feature = "alerts")]
+pub mod alerts;
+#[cfg(feature = "auth")]
+pub mod auth;
+#[cfg(feature = "devices")]
+pub mod devices;
+
+pub use common::api_version::ApiVersion;
+pub use error::{ClientError, Result};
+
+use reqwest::Url;
+use service::Service;
+use std::time::Duration;
+
+/// Default per-request timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Builder for [`QuadrantClient`]; only services with a base URL can be called
+#[derive(Debug, Clone, Default)]
+pub struct QuadrantClientBuilder {
+    gateway: Option<Url>,
+    coordinator: Option<Url>,
+    recorder: Option<Url>,
+    device_manager: Option<Url>,
+    playback: Option<Url>,
+    alert_service: Option<Url>,
+    auth_service: Option<Url>,
+    token: Option<String>,
+    timeout: Option<Duration>,
+}
+
+fn parse_base(url: &str) -> Result<Url> {
+    // A trailing slash keeps `Url::join` from dropping a path prefix
+    let normalized = if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
+    };
+    Url::parse(&normalized).map_err(|e| ClientError::InvalidUrl(format!("{}: {}", url, e)))
+}
+
+impl QuadrantClientBuilder {
+    /// admin-gateway (streams and recordings)
+    pub fn gateway(mut self, url: &str) -> Result<Self> {
+        self.gateway = Some(parse_base(url)?);
+        Ok(self)
+    }
+
+    /// coordinator (node registrations)
+    pub fn coordinator(mut self, url: &str) -> Result<Self> {
+        self.coordinator = Some(parse_base(url)?);
+        Ok(self)
+    }
+
+    /// recorder-node (retention policies, recording change feed)
+    pub fn recorder(mut self, url: &str) -> Result<Self> {
+        self.recorder = Some(parse_base(url)?);
+        Ok(self)
+    }
+
+    pub fn device_manager(mut self, url: &str) -> Result<Self> {
+        self.device_manager = Some(parse_base(url)?);
+        Ok(self)
+    }
+
+    pub fn playback(mut self, url: &str) -> Result<Self> {
+        self.playback = Some(parse_base(url)?);
+        Ok(self)
+    }
+
+    pub fn alert_service(mut self, url: &str) -> Result<Self> {
+        self.alert_service = Some(parse_base(url)?);
+        Ok(self)
+    }
+
+    pub fn auth_service(mut self, url: &str) -> Result<Self> {
+        self.auth_service = Some(parse_base(url)?);
+        Ok(self)
+    }
+
+    /// Bearer token (JWT or API token) sent with every request
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<QuadrantClient> {
+        let http = reqwest::Client::builder()
+            .timeout(self.timeout.unwrap_or(DEFAULT_TIMEOUT))
+            .user_agent(concat!("quadrant-client/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(QuadrantClient {
+            http,
+            config: self,
+        })
+    }
+}
+
+/// Entry point; hands out per-service clients
+#[derive(Debug, Clone)]
+pub struct QuadrantClient {
+    http: reqwest::Client,
+    config: QuadrantClientBuilder,
+}
+
+impl QuadrantClient {
+    pub fn builder() -> QuadrantClientBuilder {
+        QuadrantClientBuilder::default()
+    }
+
+    /// Same endpoints with a different bearer token (e.g. after login)
+    pub fn with_token(&self, token: impl Into<String>) -> Self {
+        let mut client = self.clone();
+        client.config.token = Some(token.into());
+        client
+    }
+
+    fn service(&self, name: &'static str, base: &Option<Url>) -> Result<Service> {
+        let base = base.clone().ok_or(ClientError::NotConfigured(name))?;
+        Ok(Service::new(
+            name,
+            base,
+            self.http.clone(),
+            self.config.token.clone(),
+        ))
+    }
+
+    pub fn streams(&self) -> Result<streams::StreamsClient> {
+        Ok(streams::StreamsClient::new(
+            self.service("admin-gateway", &self.config.gateway)?,
+        ))
+    }
+
+    pub fn recordings(&self) -> Result<recordings::RecordingsClient> {
+        Ok(recordings::RecordingsClient::new(
+            self.service("admin-gateway", &self.config.gateway)?,
+        ))
+    }
+
+    pub fn nodes(&self) -> Result<nodes::NodesClient> {
+        Ok(nodes::NodesClient::new(
+            self.service("coordinator", &self.config.coordinator)?,
+        ))
+    }
+
+    pub fn retention(&self) -> Result<retention::RetentionClient> {
+        Ok(retention::RetentionClient::new(
+            self.service("recorder-node", &self.config.recorder)?,
+        ))
+    }
+
+    pub fn recording_sync(&self) -> Result<recording_sync::RecordingSyncClient> {
+        Ok(recording_sync::RecordingSyncClient::new(
+            self.service("recorder-node", &self.config.recorder)?,
+        ))
+    }
+
+    pub fn playback(&self) -> Result<playback::PlaybackClient> {
+        Ok(playback::PlaybackClient::new(
+            self.service("playback-service", &self.config.playback)?,
+        ))
+    }
+
+    #[cfg(feature = "devices")]
+    pub fn devices(&self) -> Result<devices::DevicesClient> {
+        Ok(devices::DevicesClient::new(
+            self.service("device-manager", &self.config.device_manager)?,
+        ))
+    }
+
+    #[cfg(feature = "alerts")]
+    pub fn alerts(&self) -> Result<alerts::AlertsClient> {
+        Ok(alerts::AlertsClient::new(
+            self.service("alert-service", &self.config.alert_service)?,
+        ))
+    }
+
+    #[cfg(feature = "auth")]
+    pub fn auth(&self) -> Result<auth::AuthClient> {
+        Ok(auth::AuthClient::new(
+            self.service("auth-service", &self.config.auth_service)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::{delete, get},
+        Json, Router,
+    };
+    use common::streams::{StreamState, StreamStopResponse};
+    use serde_json::json;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_typed_response_and_bearer_token() {
+        let app = Router::new().route(
+            "/v1/streams",
+            get(|headers: HeaderMap| async move {
+                assert_eq!(headers["authorization"], "Bearer secret");
+                Json(json!([{
+                    "config": { "id": "cam-1", "uri": "rtsp://h/s" },
+                    "state": "running",
+                    "lease_id": null,
+                    "last_error": null,
+                }]))
+            }),
+        );
+        let base = serve(app).await;
+
+        let client = QuadrantClient::builder()
+            .gateway(&base)
+            .unwrap()
+            .token("secret")
+            .build()
+            .unwrap();
+        let streams = client.streams().unwrap().list().await.unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].config.id, "cam-1");
+        assert_eq!(streams[0].state, StreamState::Running);
+    }
+
+    #[tokio::test]
+    async fn test_error_body_is_surfaced() {
+        let app = Router::new().route(
+            "/v1/streams/:id",
+            delete(|| async {
+                (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({ "error": "stream not found" })),
+                )
+            }),
+        );
+        let base = serve(app).await;
+
+        let client = QuadrantClient::builder().gateway(&base).unwrap().build().unwrap();
+        let err: ClientError = client
+            .streams()
+            .unwrap()
+            .stop("missing")
+            .await
+            .map(|_: StreamStopResponse| ())
+            .unwrap_err();
+        assert!(err.is_not_found());
+        assert_eq!(err.to_string(), "404 Not Found: stream not found");
+    }
+
+    #[tokio::test]
+    async fn test_drain_posts_node_and_flag() {
+        let app = Router::new().route(
+            "/v1/nodes/drain",
+            axum::routing::post(|Json(body): Json<serde_json::Value>| async move {
+                assert_eq!(body, json!({ "node_id": "rec-1", "draining": true }));
+                Json(json!({
+                    "node_id": "rec-1",
+                    "kind": "recorder",
+                    "base_url": "http://rec-1:8085",
+                    "registered_at_epoch_secs": 1,
+                    "last_seen_epoch_secs": 2,
+                    "expires_at_epoch_secs": 3,
+                    "draining": true,
+                }))
+            }),
+        );
+        let base = serve(app).await;
+
+        let client = QuadrantClient::builder().coordinator(&base).unwrap().build().unwrap();
+        let node = client.nodes().unwrap().drain("rec-1").await.unwrap();
+        assert!(node.draining);
+    }
+
+    #[tokio::test]
+    async fn test_api_version_negotiation() {
+        let versioned = common::api_version::versioned(Router::new(), "coordinator", &[]);
+        let client = QuadrantClient::builder()
+            .coordinator(&serve(versioned).await)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(client.nodes().unwrap().api_version().await.unwrap(), ApiVersion::V2);
+
+        // A coordinator from before versioning has no /api/versions
+        let client = QuadrantClient::builder()
+            .coordinator(&serve(Router::new()).await)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(client.nodes().unwrap().api_version().await.unwrap(), ApiVersion::V1);
+    }
+
+    #[tokio::test]
+    async fn test_change_feed_follows_cursors_until_caught_up() {
+        let app = Router::new().route(
+            "/v1/recordings/changes",
+            get(|axum::extract::RawQuery(query): axum::extract::RawQuery| async move {
+                let change = |cursor: &str, kind: &str| {
+                    json!({
+                        "cursor": cursor,
+                        "kind": kind,
+                        "recording_id": format!("rec-{}", cursor),
+                        "changed_at": 1_700_000_000,
+                    })
+                };
+                match query.as_deref() {
+                    Some("limit=2") => Json(json!({
+                        "changes": [change("1", "created"), change("2", "updated")],
+                        "next_cursor": "2",
+                        "has_more": true,
+                    })),
+                    Some("since=2&limit=2") => Json(json!({
+                        "changes": [change("5", "deleted")],
+                        "next_cursor": "5",
+                        "has_more": false,
+                    })),
+                    other => panic!("unexpected query {:?}", other),
+                }
+            }),
+        );
+        let client = QuadrantClient::builder()
+            .recorder(&serve(app).await)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let mut feed = client.recording_sync().unwrap().feed(None).page_size(2);
+        let first = feed.next_page().await.unwrap().unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(feed.cursor(), Some("2"));
+        let second = feed.next_page().await.unwrap().unwrap();
+        assert_eq!(second[0].kind, common::search::RecordingChangeKind::Deleted);
+        assert!(second[0].recording.is_none());
+        assert!(feed.next_page().await.unwrap().is_none());
+        assert_eq!(feed.cursor(), Some("5"));
+    }
+
+    #[test]
+    fn test_unconfigured_service() {
+        let client = QuadrantClient::builder().build().unwrap();
+        assert!(matches!(
+            client.playback(),
+            Err(ClientError::NotConfigured("playback-service"))
+        ));
+    }
+
+    #[test]
+    fn test_path_segments_are_encoded() {
+        assert_eq!(service::path(&["v1", "streams", "a/../b"]), "/v1/streams/a%2F..%2Fb");
+    }
+}
@@ -0,0 +1,53 @@
+//! Node registrations and drain via the coordinator
+
+use common::api_version::ApiVersion;
+use common::nodes::{NodeDrainRequest, NodeKind, NodeRecord};
+
+use crate::error::Result;
+use crate::service::Service;
+
+#[derive(Debug, Clone)]
+pub struct NodesClient {
+    service: Service,
+}
+
+impl NodesClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    /// API version the coordinator and this client agree on
+    pub async fn api_version(&self) -> Result<ApiVersion> {
+        self.service.negotiate().await
+    }
+
+    /// Registered nodes, optionally only those of one kind
+    pub async fn list(&self, kind: Option<NodeKind>) -> Result<Vec<NodeRecord>> {
+        match kind {
+            Some(kind) => {
+                self.service
+                    .get_query("v1/nodes", &[("kind", kind.to_string())])
+                    .await
+            }
+            None => self.service.get("v1/nodes").await,
+        }
+    }
+
+    /// Stop routing new work to a node; work already on it keeps running
+    pub async fn drain(&self, node_id: &str) -> Result<NodeRecord> {
+        self.set_draining(node_id, true).await
+    }
+
+    /// Route new work to a drained node again
+    pub async fn undrain(&self, node_id: &str) -> Result<NodeRecord> {
+        self.set_draining(node_id, false).await
+    }
+
+    async fn set_draining(&self, node_id: &str, draining: bool) -> Result<NodeRecord> {
+        let request = NodeDrainRequest {
+            node_id: node_id.to_string(),
+            draining,
+        };
+        self.service.post("v1/nodes/drain", &request).await
+    }
+}
@@ -0,0 +1,154 @@
+//! Playback sessions and DVR via playback-service
+
+use common::playback::{
+    ClipExportQuery, ClipOverlayQuery, DvrJumpToLiveRequest, DvrSeekRequest, DvrSeekResponse,
+    DvrWindowInfo, DvrWindowRequest, NetworkConditions,
+    PlaybackAction, PlaybackControlRequest, PlaybackControlResponse, PlaybackFallbackRequest,
+    PlaybackFallbackResponse, PlaybackInfo, PlaybackListResponse,
+    PlaybackSeekRequest, PlaybackSeekResponse, PlaybackStartRequest, PlaybackStartResponse,
+    PlaybackStopRequest, PlaybackStopResponse, SessionLatency, TimeAxisPreviewRequest,
+    TimeAxisPreviewResponse,
+};
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::service::{path, Service};
+
+#[derive(Debug, Clone)]
+pub struct PlaybackClient {
+    service: Service,
+}
+
+impl PlaybackClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    pub async fn start(&self, request: &PlaybackStartRequest) -> Result<PlaybackStartResponse> {
+        self.service.post("v1/playback/start", request).await
+    }
+
+    pub async fn stop(&self, session_id: &str) -> Result<PlaybackStopResponse> {
+        let request = PlaybackStopRequest {
+            session_id: session_id.to_string(),
+        };
+        self.service.post("v1/playback/stop", &request).await
+    }
+
+    /// Report that the served protocol failed and move a negotiated session
+    /// to the next one
+    pub async fn fallback(
+        &self,
+        session_id: &str,
+        reason: &str,
+        network: Option<NetworkConditions>,
+    ) -> Result<PlaybackFallbackResponse> {
+        let request = PlaybackFallbackRequest {
+            session_id: session_id.to_string(),
+            reason: reason.to_string(),
+            network,
+        };
+        self.service.post("v1/playback/fallback", &request).await
+    }
+
+    pub async fn seek(&self, session_id: &str, position_secs: f64) -> Result<PlaybackSeekResponse> {
+        let request = PlaybackSeekRequest {
+            session_id: session_id.to_string(),
+            position_secs,
+        };
+        self.service.post("v1/playback/seek", &request).await
+    }
+
+    pub async fn control(
+        &self,
+        session_id: &str,
+        action: PlaybackAction,
+    ) -> Result<PlaybackControlResponse> {
+        let request = PlaybackControlRequest {
+            session_id: session_id.to_string(),
+            action,
+        };
+        self.service.post("v1/playback/control", &request).await
+    }
+
+    pub async fn list_sessions(&self) -> Result<PlaybackListResponse> {
+        self.service.get("v1/playback/sessions").await
+    }
+
+    /// A session, resumed by whichever node serves the request if the one
+    /// that started it went away; re-read `playback_url` after a failover
+    pub async fn session(&self, session_id: &str) -> Result<PlaybackInfo> {
+        self.service
+            .get(&path(&["v1", "playback", "sessions", session_id]))
+            .await
+    }
+
+    /// Latest delivery latency of a live session or WHEP session
+    pub async fn session_latency(&self, session_id: &str) -> Result<SessionLatency> {
+        self.service
+            .get(&path(&["v1", "playback", "sessions", session_id, "latency"]))
+            .await
+    }
+
+    pub async fn dvr_window(&self, session_id: &str) -> Result<DvrWindowInfo> {
+        let request = DvrWindowRequest {
+            session_id: session_id.to_string(),
+        };
+        self.service.post("v1/dvr/window", &request).await
+    }
+
+    pub async fn dvr_seek(&self, request: &DvrSeekRequest) -> Result<DvrSeekResponse> {
+        self.service.post("v1/dvr/seek", request).await
+    }
+
+    pub async fn jump_to_live(&self, session_id: &str) -> Result<DvrSeekResponse> {
+        let request = DvrJumpToLiveRequest {
+            session_id: session_id.to_string(),
+        };
+        self.service.post("v1/dvr/jump_to_live", &request).await
+    }
+
+    pub async fn time_axis_preview(
+        &self,
+        request: &TimeAxisPreviewRequest,
+    ) -> Result<TimeAxisPreviewResponse> {
+        self.service.post("v1/preview/time_axis", request).await
+    }
+
+    /// Export `[start_secs, end_secs)` of a recording as MP4; read the body
+    /// with [`reqwest::Response::chunk`] to stream it to disk
+    pub async fn export_clip(
+        &self,
+        recording_id: &str,
+        query: &ClipExportQuery,
+    ) -> Result<reqwest::Response> {
+        self.service
+            .get_response(&path(&["v1", "recordings", recording_id, "clip"]), query)
+            .await
+    }
+
+    /// Like [`Self::export_clip`], with the AI detections stored for the
+    /// range drawn onto the video
+    pub async fn export_clip_with_overlays(
+        &self,
+        recording_id: &str,
+        query: &ClipExportQuery,
+        overlay: &ClipOverlayQuery,
+    ) -> Result<reqwest::Response> {
+        self.service
+            .get_response(
+                &path(&["v1", "recordings", recording_id, "clip"]),
+                &OverlayClipQuery { range: query, overlay },
+            )
+            .await
+    }
+}
+
+/// Query string of an overlay clip export
+#[derive(Serialize)]
+struct OverlayClipQuery<'a> {
+    #[serde(flatten)]
+    range: &'a ClipExportQuery,
+    #[serde(flatten)]
+    overlay: &'a ClipOverlayQuery,
+}
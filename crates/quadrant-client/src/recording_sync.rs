@@ -0,0 +1,87 @@
+//! Incremental mirroring of the recording index via recorder-node
+
+use common::search::{RecordingChange, RecordingChangesQuery, RecordingChangesResponse};
+
+use crate::error::Result;
+use crate::service::Service;
+
+#[derive(Debug, Clone)]
+pub struct RecordingSyncClient {
+    service: Service,
+}
+
+impl RecordingSyncClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    /// One page of changes after `since` (from the beginning when `None`).
+    /// A 410 Gone error ([`crate::ClientError::is_gone`]) means the cursor
+    /// is too old: mirror again from the beginning.
+    pub async fn changes(
+        &self,
+        since: Option<&str>,
+        limit: Option<i64>,
+    ) -> Result<RecordingChangesResponse> {
+        let query = RecordingChangesQuery {
+            since: since.map(str::to_string),
+            limit,
+        };
+        self.service.get_query("v1/recordings/changes", &query).await
+    }
+
+    /// Pages through the changes after `since` until caught up
+    pub fn feed(&self, since: Option<String>) -> RecordingChangeFeed {
+        RecordingChangeFeed {
+            client: self.clone(),
+            cursor: since,
+            limit: None,
+            caught_up: false,
+        }
+    }
+}
+
+/// Cursor-following reader of the change feed. Apply each page, then
+/// persist [`RecordingChangeFeed::cursor`] so the next sync resumes there.
+///
+/// ```ignore
+/// let mut feed = client.recording_sync()?.feed(saved_cursor);
+/// while let Some(changes) = feed.next_page().await? {
+///     mirror.apply(&changes)?;
+///     save_cursor(feed.cursor());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordingChangeFeed {
+    client: RecordingSyncClient,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    caught_up: bool,
+}
+
+impl RecordingChangeFeed {
+    /// Changes per page; the service default when unset
+    pub fn page_size(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Next page of changes, oldest first; `None` once caught up
+    pub async fn next_page(&mut self) -> Result<Option<Vec<RecordingChange>>> {
+        if self.caught_up {
+            return Ok(None);
+        }
+        let page = self.client.changes(self.cursor.as_deref(), self.limit).await?;
+        self.cursor = Some(page.next_cursor);
+        self.caught_up = !page.has_more;
+        if page.changes.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(page.changes))
+    }
+
+    /// Cursor after the last page returned
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+}
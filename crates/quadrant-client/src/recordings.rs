@@ -0,0 +1,40 @@
+//! Recordings via admin-gateway
+
+use common::recordings::{
+    RecordingInfo, RecordingStartRequest, RecordingStartResponse, RecordingStopResponse,
+};
+
+use crate::error::Result;
+use crate::service::{path, Service};
+
+#[derive(Debug, Clone)]
+pub struct RecordingsClient {
+    service: Service,
+}
+
+impl RecordingsClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    pub async fn list(&self) -> Result<Vec<RecordingInfo>> {
+        self.service.get("v1/recordings").await
+    }
+
+    /// Start a recording; pass an idempotency key to make retries safe
+    pub async fn start(
+        &self,
+        request: &RecordingStartRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<RecordingStartResponse> {
+        self.service
+            .post_idempotent("v1/recordings", request, idempotency_key)
+            .await
+    }
+
+    pub async fn stop(&self, recording_id: &str) -> Result<RecordingStopResponse> {
+        self.service
+            .delete(&path(&["v1", "recordings", recording_id]))
+            .await
+    }
+}
@@ -0,0 +1,74 @@
+//! Retention policies and dry-runs via recorder-node
+
+use common::retention::{
+    ExecutePolicyResponse, ListExecutionsResponse, ListPoliciesResponse, RetentionExecution,
+    RetentionPolicy, RetentionPreview,
+};
+
+use crate::error::Result;
+use crate::service::{path, Service};
+
+#[derive(Debug, Clone)]
+pub struct RetentionClient {
+    service: Service,
+}
+
+impl RetentionClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    pub async fn list_policies(&self) -> Result<Vec<RetentionPolicy>> {
+        let resp: ListPoliciesResponse = self.service.get("v1/retention/policies").await?;
+        Ok(resp.policies)
+    }
+
+    pub async fn get_policy(&self, policy_id: &str) -> Result<RetentionPolicy> {
+        self.service
+            .get(&path(&["v1", "retention", "policies", policy_id]))
+            .await
+    }
+
+    /// What executing the policy now would do, without touching anything;
+    /// kept as the policy's latest preview
+    pub async fn preview(&self, policy_id: &str) -> Result<RetentionPreview> {
+        self.service
+            .post(
+                &path(&["v1", "retention", "policies", policy_id, "preview"]),
+                &serde_json::json!({}),
+            )
+            .await
+    }
+
+    /// The policy's latest preview
+    pub async fn latest_preview(&self, policy_id: &str) -> Result<RetentionPreview> {
+        self.service
+            .get(&path(&["v1", "retention", "policies", policy_id, "preview"]))
+            .await
+    }
+
+    /// Run the policy now; the returned execution id can be looked up in
+    /// [`RetentionClient::list_executions`]
+    pub async fn execute(&self, policy_id: &str) -> Result<ExecutePolicyResponse> {
+        self.service
+            .post(
+                &path(&["v1", "retention", "policies", policy_id, "execute"]),
+                &serde_json::json!({}),
+            )
+            .await
+    }
+
+    pub async fn list_executions(&self, policy_id: &str) -> Result<Vec<RetentionExecution>> {
+        let resp: ListExecutionsResponse = self
+            .service
+            .get(&path(&[
+                "v1",
+                "retention",
+                "policies",
+                policy_id,
+                "executions",
+            ]))
+            .await?;
+        Ok(resp.executions)
+    }
+}
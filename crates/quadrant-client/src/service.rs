@@ -0,0 +1,162 @@
+use common::api_version::{self, ApiVersion, VersionsInfo, VERSIONS_PATH};
+use reqwest::{header, Method, RequestBuilder, Response, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{ClientError, Result};
+
+/// HTTP plumbing shared by the per-service clients
+#[derive(Debug, Clone)]
+pub(crate) struct Service {
+    name: &'static str,
+    base: Url,
+    http: reqwest::Client,
+    token: Option<String>,
+}
+
+impl Service {
+    pub(crate) fn new(
+        name: &'static str,
+        base: Url,
+        http: reqwest::Client,
+        token: Option<String>,
+    ) -> Self {
+        Self {
+            name,
+            base,
+            http,
+            token,
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        let url = self
+            .base
+            .join(path.trim_start_matches('/'))
+            .map_err(|e| ClientError::InvalidUrl(format!("{}{}: {}", self.base, path, e)))?;
+        let builder = self.http.request(method, url);
+        Ok(match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        })
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> Result<Response> {
+        let resp = builder.send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+
+        // Services answer `{"error": "..."}`; fall back to the raw body
+        let body = resp.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(str::to_string))
+            .unwrap_or_else(|| {
+                if body.is_empty() {
+                    format!("{} request failed", self.name)
+                } else {
+                    body
+                }
+            });
+        Err(ClientError::Api { status, message })
+    }
+
+    /// Newest API version both this client and the service speak; v1 for
+    /// services from before `GET /api/versions`
+    pub(crate) async fn negotiate(&self) -> Result<ApiVersion> {
+        let resp = self.request(Method::GET, VERSIONS_PATH)?.send().await?;
+        if !resp.status().is_success() {
+            return Ok(ApiVersion::V1);
+        }
+        let info: VersionsInfo = resp.json().await?;
+        Ok(api_version::pick(&info.versions()).unwrap_or(ApiVersion::V1))
+    }
+
+    pub(crate) async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let builder = self.request(Method::GET, path)?;
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    pub(crate) async fn get_query<Q: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &Q,
+    ) -> Result<T> {
+        let builder = self.request(Method::GET, path)?.query(query);
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    /// GET handing back the response itself, for bodies too large to buffer
+    pub(crate) async fn get_response<Q: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        query: &Q,
+    ) -> Result<Response> {
+        let builder = self.request(Method::GET, path)?.query(query);
+        self.send(builder).await
+    }
+
+    pub(crate) async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T> {
+        let builder = self.request(Method::POST, path)?.json(body);
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    /// POST with an `Idempotency-Key` so retries replay the first response
+    pub(crate) async fn post_idempotent<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+        idempotency_key: Option<&str>,
+    ) -> Result<T> {
+        let mut builder = self.request(Method::POST, path)?.json(body);
+        if let Some(key) = idempotency_key {
+            builder = builder.header("idempotency-key", key);
+        }
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    pub(crate) async fn put<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T> {
+        let builder = self.request(Method::PUT, path)?.json(body);
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    pub(crate) async fn delete<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let builder = self.request(Method::DELETE, path)?;
+        Ok(self.send(builder).await?.json().await?)
+    }
+
+    /// DELETE where the service answers 204 or an ignorable body
+    pub(crate) async fn delete_no_content(&self, path: &str) -> Result<()> {
+        let builder = self
+            .request(Method::DELETE, path)?
+            .header(header::ACCEPT, "application/json");
+        self.send(builder).await?;
+        Ok(())
+    }
+}
+
+/// Join path segments, percent-encoding each one so ids cannot escape the path
+pub(crate) fn path(segments: &[&str]) -> String {
+    let mut out = String::new();
+    for segment in segments {
+        out.push('/');
+        for b in segment.bytes() {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    out.push(b as char)
+                }
+                _ => out.push_str(&format!("%{:02X}", b)),
+            }
+        }
+    }
+    out
+}
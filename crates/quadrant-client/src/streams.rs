@@ -0,0 +1,38 @@
+//! Live streams via admin-gateway
+
+use common::streams::{StreamInfo, StreamStartRequest, StreamStartResponse, StreamStopResponse};
+
+use crate::error::Result;
+use crate::service::{path, Service};
+
+#[derive(Debug, Clone)]
+pub struct StreamsClient {
+    service: Service,
+}
+
+impl StreamsClient {
+    pub(crate) fn new(service: Service) -> Self {
+        Self { service }
+    }
+
+    pub async fn list(&self) -> Result<Vec<StreamInfo>> {
+        self.service.get("v1/streams").await
+    }
+
+    /// Start a stream; pass an idempotency key to make retries safe
+    pub async fn start(
+        &self,
+        request: &StreamStartRequest,
+        idempotency_key: Option<&str>,
+    ) -> Result<StreamStartResponse> {
+        self.service
+            .post_idempotent("v1/streams", request, idempotency_key)
+            .await
+    }
+
+    pub async fn stop(&self, stream_id: &str) -> Result<StreamStopResponse> {
+        self.service
+            .delete(&path(&["v1", "streams", stream_id]))
+            .await
+    }
+}
@@ -0,0 +1,72 @@
+//! Parsing for `devices import`
+
+use anyhow::{bail, Context, Result};
+use device_manager::types::CreateDeviceRequest;
+
+/// Parse a JSON array of device create requests
+pub fn parse_devices(contents: &str) -> Result<Vec<CreateDeviceRequest>> {
+    let requests: Vec<CreateDeviceRequest> = serde_json::from_str(contents)
+        .context("expected a JSON array of device create requests")?;
+    if requests.is_empty() {
+        bail!("no devices in file");
+    }
+    let mut names = std::collections::HashSet::new();
+    for request in &requests {
+        if !names.insert(request.name.as_str()) {
+            bail!("device '{}' appears more than once", request.name);
+        }
+    }
+    Ok(requests)
+}
+
+/// Idempotency key derived from the device's name and primary URI, so
+/// importing the same file twice replays the first responses instead of
+/// creating duplicates
+pub fn idempotency_key(request: &CreateDeviceRequest) -> String {
+    // FNV-1a: stable across builds, unlike `DefaultHasher`
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in request
+        .name
+        .bytes()
+        .chain([0])
+        .chain(request.primary_uri.bytes())
+    {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("quadrantctl-device-{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICES: &str = r#"[
+        {"name": "lobby", "device_type": "camera", "primary_uri": "rtsp://10.0.0.5/stream", "protocol": "rtsp"},
+        {"name": "dock", "device_type": "camera", "primary_uri": "rtsp://10.0.0.6/stream", "protocol": "rtsp"}
+    ]"#;
+
+    #[test]
+    fn test_parse_devices() {
+        let requests = parse_devices(DEVICES).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].name, "dock");
+
+        assert!(parse_devices("[]").is_err());
+        let duplicate = DEVICES.replace("dock", "lobby");
+        assert!(parse_devices(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_idempotency_key_is_stable_per_device() {
+        let requests = parse_devices(DEVICES).unwrap();
+        assert_eq!(
+            idempotency_key(&requests[0]),
+            idempotency_key(&requests[0].clone())
+        );
+        assert_ne!(idempotency_key(&requests[0]), idempotency_key(&requests[1]));
+        assert!(
+            idempotency_key(&requests[0]).len() <= common::idempotency::MAX_IDEMPOTENCY_KEY_LENGTH
+        );
+    }
+}
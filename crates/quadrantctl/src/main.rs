@@ -0,0 +1,724 @@
+//! Admin CLI for Quadrant VMS
+//!
+//! Talks to the running services over their HTTP APIs through
+//! `quadrant-client`. Every service URL can be given as a flag or through the
+//! matching `QUADRANT_*` environment variable; only the services a command
+//! needs have to be set.
+//!
+//! Usage:
+//!   quadrantctl nodes list [--kind recorder]        - Registered nodes
+//!   quadrantctl nodes drain <node-id>               - Stop routing new work to a node
+//!   quadrantctl nodes undrain <node-id>             - Route new work to it again
+//!   quadrantctl devices import <file.json>          - Add devices from a JSON array
+//!   quadrantctl streams start <id> <uri>            - Start a live stream
+//!   quadrantctl streams stop <id>                   - Stop a live stream
+//!   quadrantctl recordings start <id> --stream <id> - Start a recording
+//!   quadrantctl recordings stop <id>                - Stop a recording
+//!   quadrantctl retention preview <policy-id>       - Dry-run a retention policy
+//!   quadrantctl alerts tail                         - Follow alert events
+//!   quadrantctl clips export <recording-id> --start 60 --end 120 -o clip.mp4 [--overlays]
+
+mod import;
+
+use alert_service::types::AlertEvent;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use common::nodes::NodeKind;
+use common::playback::{ClipExportQuery, ClipOverlayQuery};
+use common::recordings::{RecordingConfig, RecordingEncoding, RecordingStartRequest};
+use common::streams::{StreamConfig, StreamStartRequest, ThermalPalette};
+use quadrant_client::QuadrantClient;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Parser)]
+#[command(name = "quadrantctl")]
+#[command(about = "Admin CLI for Quadrant VMS", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// admin-gateway base URL (streams and recordings)
+    #[arg(long, env = "QUADRANT_GATEWAY_URL", global = true)]
+    gateway: Option<String>,
+
+    /// coordinator base URL (nodes)
+    #[arg(long, env = "QUADRANT_COORDINATOR_URL", global = true)]
+    coordinator: Option<String>,
+
+    /// device-manager base URL
+    #[arg(long, env = "QUADRANT_DEVICE_MANAGER_URL", global = true)]
+    device_manager: Option<String>,
+
+    /// recorder-node base URL (retention)
+    #[arg(long, env = "QUADRANT_RECORDER_URL", global = true)]
+    recorder: Option<String>,
+
+    /// playback-service base URL (clip export)
+    #[arg(long, env = "QUADRANT_PLAYBACK_URL", global = true)]
+    playback: Option<String>,
+
+    /// alert-service base URL
+    #[arg(long, env = "QUADRANT_ALERT_SERVICE_URL", global = true)]
+    alert_service: Option<String>,
+
+    /// Bearer token (JWT or API token)
+    #[arg(long, env = "QUADRANT_TOKEN", global = true, hide_env_values = true)]
+    token: Option<String>,
+
+    /// Print raw JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Stream, recorder and AI nodes
+    Nodes {
+        #[command(subcommand)]
+        command: NodesCommand,
+    },
+
+    /// Cameras and other devices
+    Devices {
+        #[command(subcommand)]
+        command: DevicesCommand,
+    },
+
+    /// Live streams
+    Streams {
+        #[command(subcommand)]
+        command: StreamsCommand,
+    },
+
+    /// Recordings
+    Recordings {
+        #[command(subcommand)]
+        command: RecordingsCommand,
+    },
+
+    /// Retention policies
+    Retention {
+        #[command(subcommand)]
+        command: RetentionCommand,
+    },
+
+    /// Alert events
+    Alerts {
+        #[command(subcommand)]
+        command: AlertsCommand,
+    },
+
+    /// Recording clips
+    Clips {
+        #[command(subcommand)]
+        command: ClipsCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum NodesCommand {
+    /// List registered nodes
+    List {
+        /// Only nodes of this kind (stream, recorder, ai)
+        #[arg(long)]
+        kind: Option<NodeKind>,
+    },
+
+    /// Stop routing new streams and recordings to a node
+    Drain { node_id: String },
+
+    /// Route new work to a drained node again
+    Undrain { node_id: String },
+}
+
+#[derive(Subcommand)]
+enum DevicesCommand {
+    /// Add every device in a JSON array of create requests
+    Import {
+        /// File holding `[{"name": ..., "device_type": ..., ...}, ...]`
+        path: PathBuf,
+
+        /// Validate the file without creating anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum StreamsCommand {
+    /// List live streams
+    List,
+
+    /// Start a live stream
+    Start {
+        id: String,
+        uri: String,
+
+        /// Camera the stream belongs to
+        #[arg(long)]
+        camera_id: Option<String>,
+
+        /// Lower-quality substream used while the site's uplink is over budget
+        #[arg(long)]
+        substream: Option<String>,
+
+        /// Thermal camera: render in this palette (white_hot, black_hot,
+        /// inferno, magma, plasma, turbo, viridis)
+        #[arg(long, value_parser = parse_thermal_palette)]
+        thermal_palette: Option<ThermalPalette>,
+    },
+
+    /// Stop a live stream
+    Stop { id: String },
+}
+
+fn parse_thermal_palette(s: &str) -> std::result::Result<ThermalPalette, String> {
+    ThermalPalette::parse(s).ok_or_else(|| format!("unknown thermal palette '{}'", s))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EncodingArg {
+    Copy,
+    Transcode,
+}
+
+impl From<EncodingArg> for RecordingEncoding {
+    fn from(encoding: EncodingArg) -> Self {
+        match encoding {
+            EncodingArg::Copy => RecordingEncoding::Copy,
+            EncodingArg::Transcode => RecordingEncoding::Transcode {
+                crf: None,
+                max_height: None,
+            },
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum RecordingsCommand {
+    /// List recordings
+    List,
+
+    /// Start a recording from a running stream or a source URI
+    Start {
+        id: String,
+
+        /// Stream to record
+        #[arg(long, conflicts_with = "uri", required_unless_present = "uri")]
+        stream: Option<String>,
+
+        /// Source URI to record directly
+        #[arg(long)]
+        uri: Option<String>,
+
+        #[arg(long)]
+        retention_hours: Option<u32>,
+
+        /// Keep the camera's bitstream (copy) or re-encode it; the recorder
+        /// node's default when omitted
+        #[arg(long, value_enum)]
+        encoding: Option<EncodingArg>,
+    },
+
+    /// Stop a recording
+    Stop { id: String },
+}
+
+#[derive(Subcommand)]
+enum RetentionCommand {
+    /// List retention policies
+    List,
+
+    /// Show what a policy would delete or move, without changing anything
+    Preview { policy_id: String },
+}
+
+#[derive(Subcommand)]
+enum AlertsCommand {
+    /// Print new alert events as they fire
+    Tail {
+        /// Events to show before following
+        #[arg(long, short = 'n', default_value_t = 10)]
+        lines: usize,
+
+        /// Seconds between polls
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum ClipsCommand {
+    /// Export part of a recording as MP4
+    Export {
+        recording_id: String,
+
+        /// Start, in seconds from the beginning of the recording
+        #[arg(long)]
+        start: f64,
+
+        /// End, in seconds from the beginning of the recording
+        #[arg(long)]
+        end: f64,
+
+        /// Output file
+        #[arg(long, short = 'o')]
+        output: PathBuf,
+
+        /// Draw the stored AI detections onto the clip
+        #[arg(long)]
+        overlays: bool,
+
+        /// Comma-separated detection classes to draw (with --overlays)
+        #[arg(long, requires = "overlays")]
+        classes: Option<String>,
+
+        /// Leave out boxes below this confidence (with --overlays)
+        #[arg(long, requires = "overlays")]
+        min_confidence: Option<f32>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let client = client(&cli)?;
+
+    match &cli.command {
+        Commands::Nodes { command } => nodes(&cli, &client, command).await,
+        Commands::Devices { command } => devices(&cli, &client, command).await,
+        Commands::Streams { command } => streams(&cli, &client, command).await,
+        Commands::Recordings { command } => recordings(&cli, &client, command).await,
+        Commands::Retention { command } => retention(&cli, &client, command).await,
+        Commands::Alerts { command } => alerts(&client, command).await,
+        Commands::Clips { command } => clips(&client, command).await,
+    }
+}
+
+fn client(cli: &Cli) -> Result<QuadrantClient> {
+    // The playback service cuts the whole clip before it starts sending
+    let timeout = match cli.command {
+        Commands::Clips { .. } => Duration::from_secs(600),
+        _ => Duration::from_secs(60),
+    };
+    let mut builder = QuadrantClient::builder().timeout(timeout);
+    if let Some(url) = &cli.gateway {
+        builder = builder.gateway(url)?;
+    }
+    if let Some(url) = &cli.coordinator {
+        builder = builder.coordinator(url)?;
+    }
+    if let Some(url) = &cli.device_manager {
+        builder = builder.device_manager(url)?;
+    }
+    if let Some(url) = &cli.recorder {
+        builder = builder.recorder(url)?;
+    }
+    if let Some(url) = &cli.playback {
+        builder = builder.playback(url)?;
+    }
+    if let Some(url) = &cli.alert_service {
+        builder = builder.alert_service(url)?;
+    }
+    if let Some(token) = &cli.token {
+        builder = builder.token(token);
+    }
+    Ok(builder.build()?)
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Wire name of a unit enum variant, e.g. `move_to_cold`
+fn label(value: &impl serde::Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => "-".to_string(),
+    }
+}
+
+/// Print rows as left-aligned columns under a header
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(header.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+async fn nodes(cli: &Cli, client: &QuadrantClient, command: &NodesCommand) -> Result<()> {
+    let nodes = client.nodes()?;
+    match command {
+        NodesCommand::List { kind } => {
+            let records = nodes.list(*kind).await?;
+            if cli.json {
+                return print_json(&records);
+            }
+            let rows: Vec<Vec<String>> = records
+                .iter()
+                .map(|node| {
+                    vec![
+                        node.node_id.clone(),
+                        node.kind.to_string(),
+                        node.base_url.clone(),
+                        node.version.clone().unwrap_or_else(|| "-".to_string()),
+                        if node.draining { "draining" } else { "active" }.to_string(),
+                    ]
+                })
+                .collect();
+            print_table(&["NODE", "KIND", "URL", "VERSION", "STATE"], &rows);
+        }
+        NodesCommand::Drain { node_id } => {
+            nodes.drain(node_id).await?;
+            println!("{node_id} draining; it gets no new streams or recordings");
+        }
+        NodesCommand::Undrain { node_id } => {
+            nodes.undrain(node_id).await?;
+            println!("{node_id} accepting new work");
+        }
+    }
+    Ok(())
+}
+
+async fn devices(cli: &Cli, client: &QuadrantClient, command: &DevicesCommand) -> Result<()> {
+    match command {
+        DevicesCommand::Import { path, dry_run } => {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {:?}", path))?;
+            let requests = import::parse_devices(&contents)?;
+            if *dry_run {
+                println!("{} devices valid", requests.len());
+                return Ok(());
+            }
+
+            let devices = client.devices()?;
+            let mut created = Vec::new();
+            let mut failed = 0;
+            for request in &requests {
+                // Re-running an interrupted import replays the earlier responses
+                let key = import::idempotency_key(request);
+                match devices.create(request, Some(&key)).await {
+                    Ok(device) => {
+                        if !cli.json {
+                            println!("created {} ({})", device.device_id, device.name);
+                        }
+                        created.push(device);
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        eprintln!("failed to create {}: {}", request.name, e);
+                    }
+                }
+            }
+            if cli.json {
+                print_json(&created)?;
+            }
+            if failed > 0 {
+                bail!("{} of {} devices failed", failed, requests.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn streams(cli: &Cli, client: &QuadrantClient, command: &StreamsCommand) -> Result<()> {
+    let streams = client.streams()?;
+    match command {
+        StreamsCommand::List => {
+            let infos = streams.list().await?;
+            if cli.json {
+                return print_json(&infos);
+            }
+            let rows: Vec<Vec<String>> = infos
+                .iter()
+                .map(|info| {
+                    vec![
+                        info.config.id.clone(),
+                        label(&info.state),
+                        info.config.uri.clone(),
+                        info.last_error.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            print_table(&["STREAM", "STATE", "URI", "ERROR"], &rows);
+        }
+        StreamsCommand::Start {
+            id,
+            uri,
+            camera_id,
+            substream,
+            thermal_palette,
+        } => {
+            let request = StreamStartRequest {
+                config: StreamConfig {
+                    id: id.clone(),
+                    camera_id: camera_id.clone(),
+                    uri: uri.clone(),
+                    codec: None,
+                    container: None,
+                    substream_uri: substream.clone(),
+                    thermal_palette: *thermal_palette,
+                },
+                lease_ttl_secs: None,
+            };
+            let key = format!("quadrantctl-stream-{}", uuid::Uuid::new_v4());
+            print_json(&streams.start(&request, Some(&key)).await?)?;
+        }
+        StreamsCommand::Stop { id } => print_json(&streams.stop(id).await?)?,
+    }
+    Ok(())
+}
+
+async fn recordings(cli: &Cli, client: &QuadrantClient, command: &RecordingsCommand) -> Result<()> {
+    let recordings = client.recordings()?;
+    match command {
+        RecordingsCommand::List => {
+            let infos = recordings.list().await?;
+            if cli.json {
+                return print_json(&infos);
+            }
+            let rows: Vec<Vec<String>> = infos
+                .iter()
+                .map(|info| {
+                    vec![
+                        info.config.id.clone(),
+                        label(&info.state),
+                        info.config
+                            .source_stream_id
+                            .clone()
+                            .or_else(|| info.config.source_uri.clone())
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            print_table(&["RECORDING", "STATE", "SOURCE"], &rows);
+        }
+        RecordingsCommand::Start {
+            id,
+            stream,
+            uri,
+            retention_hours,
+            encoding,
+        } => {
+            let request = RecordingStartRequest {
+                config: RecordingConfig {
+                    id: id.clone(),
+                    source_stream_id: stream.clone(),
+                    source_uri: uri.clone(),
+                    retention_hours: *retention_hours,
+                    format: None,
+                    encoding: encoding.map(Into::into),
+                },
+                lease_ttl_secs: None,
+                ai_config: None,
+                motion_policy: None,
+                tags: Vec::new(),
+                labels: Default::default(),
+            };
+            let key = format!("quadrantctl-recording-{}", uuid::Uuid::new_v4());
+            print_json(&recordings.start(&request, Some(&key)).await?)?;
+        }
+        RecordingsCommand::Stop { id } => print_json(&recordings.stop(id).await?)?,
+    }
+    Ok(())
+}
+
+async fn retention(cli: &Cli, client: &QuadrantClient, command: &RetentionCommand) -> Result<()> {
+    let retention = client.retention()?;
+    match command {
+        RetentionCommand::List => {
+            let policies = retention.list_policies().await?;
+            if cli.json {
+                return print_json(&policies);
+            }
+            let rows: Vec<Vec<String>> = policies
+                .iter()
+                .map(|policy| {
+                    vec![
+                        policy.id.clone(),
+                        policy.name.clone(),
+                        if policy.enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                        .to_string(),
+                    ]
+                })
+                .collect();
+            print_table(&["POLICY", "NAME", "STATE"], &rows);
+        }
+        RetentionCommand::Preview { policy_id } => {
+            let preview = retention.preview(policy_id).await?;
+            if cli.json {
+                return print_json(&preview);
+            }
+            let rows: Vec<Vec<String>> = preview
+                .actions
+                .iter()
+                .map(|action| {
+                    vec![
+                        action.recording_id.clone(),
+                        label(&action.action_type),
+                        action
+                            .recording_size_bytes
+                            .map(|b| b.to_string())
+                            .unwrap_or_else(|| "-".to_string()),
+                    ]
+                })
+                .collect();
+            print_table(&["RECORDING", "ACTION", "BYTES"], &rows);
+            println!();
+            let cameras: Vec<Vec<String>> = preview
+                .cameras
+                .iter()
+                .map(|camera| {
+                    vec![
+                        camera.device_id.clone().unwrap_or_else(|| "-".to_string()),
+                        camera.recordings_to_delete.to_string(),
+                        camera.recordings_to_move.to_string(),
+                        camera.recordings_remaining.to_string(),
+                        camera
+                            .oldest_remaining_at
+                            .map(|t| t.to_string())
+                            .unwrap_or_else(|| "-".to_string()),
+                    ]
+                })
+                .collect();
+            print_table(&["CAMERA", "DELETE", "MOVE", "REMAINING", "OLDEST REMAINING"], &cameras);
+            println!(
+                "\n{} recordings scanned, {} affected: {} bytes freed, {} bytes moved (dry run)",
+                preview.recordings_scanned,
+                preview.actions.len(),
+                preview.bytes_to_free,
+                preview.bytes_to_move,
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn alerts(client: &QuadrantClient, command: &AlertsCommand) -> Result<()> {
+    match command {
+        AlertsCommand::Tail { lines, interval } => {
+            let alerts = client.alerts()?;
+            // Events come newest first; remember the last page so only
+            // events that were not on it are printed
+            let page = alerts.list_events(100, 0).await?;
+            for event in page.iter().take(*lines).rev() {
+                print_event(event);
+            }
+            let mut seen: HashSet<uuid::Uuid> = page.iter().map(|e| e.id).collect();
+
+            let mut ticker = tokio::time::interval(Duration::from_secs((*interval).max(1)));
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = tokio::signal::ctrl_c() => return Ok(()),
+                }
+                let page = match alerts.list_events(100, 0).await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        eprintln!("poll failed: {e}");
+                        continue;
+                    }
+                };
+                for event in page.iter().rev().filter(|e| !seen.contains(&e.id)) {
+                    print_event(event);
+                }
+                seen = page.iter().map(|e| e.id).collect();
+            }
+        }
+    }
+}
+
+fn print_event(event: &AlertEvent) {
+    println!(
+        "{} {:<8} {} {}{}",
+        event.fired_at.format("%Y-%m-%d %H:%M:%S"),
+        label(&event.severity),
+        label(&event.trigger_type),
+        event.message,
+        if event.suppressed {
+            " (suppressed)"
+        } else {
+            ""
+        },
+    );
+}
+
+async fn clips(client: &QuadrantClient, command: &ClipsCommand) -> Result<()> {
+    match command {
+        ClipsCommand::Export {
+            recording_id,
+            start,
+            end,
+            output,
+            overlays,
+            classes,
+            min_confidence,
+        } => {
+            let query = ClipExportQuery {
+                start_secs: *start,
+                end_secs: *end,
+            };
+            query.validate().map_err(anyhow::Error::msg)?;
+            let playback = client.playback()?;
+            let resp = if *overlays {
+                let overlay = ClipOverlayQuery {
+                    overlays: true,
+                    classes: classes.clone(),
+                    min_confidence: *min_confidence,
+                    ..Default::default()
+                };
+                overlay.validate().map_err(anyhow::Error::msg)?;
+                playback
+                    .export_clip_with_overlays(recording_id, &query, &overlay)
+                    .await?
+            } else {
+                playback.export_clip(recording_id, &query).await?
+            };
+            let bytes = write_clip(resp, output).await?;
+            println!("wrote {} bytes to {}", bytes, output.display());
+        }
+    }
+    Ok(())
+}
+
+/// Stream the clip to `<output>.partial` and move it into place once complete
+async fn write_clip(mut resp: reqwest::Response, output: &Path) -> Result<u64> {
+    let partial = output.with_extension("partial");
+    let mut file = tokio::fs::File::create(&partial)
+        .await
+        .with_context(|| format!("failed to create {:?}", partial))?;
+    let mut written = 0u64;
+    while let Some(chunk) = resp.chunk().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await?;
+    drop(file);
+    tokio::fs::rename(&partial, output)
+        .await
+        .context("failed to move clip into place")?;
+    Ok(written)
+}
@@ -0,0 +1,63 @@
+-- Archive Policies Table
+CREATE TABLE IF NOT EXISTS archive_policies (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    tenant_id UUID, -- NULL: applies to every tenant's recordings
+    name VARCHAR(255) NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+
+    -- Selector (JSON), one of:
+    -- {"kind": "flagged"} - recordings tagged 'flagged'
+    -- {"kind": "event_based", "event_types": ["ai_detection"]} - recordings with indexed events
+    -- {"kind": "older_than", "days": 30} - recordings started more than N days ago
+    selector JSONB NOT NULL,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    UNIQUE(tenant_id, name)
+);
+
+CREATE INDEX idx_archive_policies_tenant ON archive_policies(tenant_id);
+
+-- Archive Jobs Table (one upload per recording, retried in place)
+CREATE TABLE IF NOT EXISTS archive_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    policy_id UUID REFERENCES archive_policies(id) ON DELETE SET NULL,
+    recording_id VARCHAR(255) NOT NULL UNIQUE,
+    tenant_id UUID,
+
+    -- Destination: s3, azure, gcs
+    backend VARCHAR(20) NOT NULL,
+    source_path VARCHAR(512) NOT NULL,
+    object_key VARCHAR(1024) NOT NULL,
+
+    -- Status: pending, uploading, completed, failed
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+
+    -- Resumable multipart progress: parts is [{"part_number": 1, "tag": "..."}]
+    bytes_total BIGINT NOT NULL DEFAULT 0,
+    bytes_uploaded BIGINT NOT NULL DEFAULT 0,
+    part_size_bytes BIGINT NOT NULL,
+    upload_id VARCHAR(1024),
+    parts JSONB NOT NULL DEFAULT '[]',
+
+    location VARCHAR(1024),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    error_message TEXT,
+
+    -- Recorder node holding the recording file, the only one that uploads it
+    node_id VARCHAR(255) NOT NULL,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_archive_jobs_node_status ON archive_jobs(node_id, status);
+CREATE INDEX idx_archive_jobs_tenant ON archive_jobs(tenant_id);
+CREATE INDEX idx_archive_jobs_policy ON archive_jobs(policy_id);
+
+-- Where the archived copy of a recording lives
+ALTER TABLE recording_index ADD COLUMN IF NOT EXISTS archive_location VARCHAR(1024);
+ALTER TABLE recording_index ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_recording_index_archived ON recording_index(archived_at);
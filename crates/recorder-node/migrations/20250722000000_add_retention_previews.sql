@@ -0,0 +1,18 @@
+-- Retention Previews Table (what a policy would do, computed without acting)
+CREATE TABLE IF NOT EXISTS retention_previews (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    policy_id UUID NOT NULL REFERENCES retention_policies(id) ON DELETE CASCADE,
+
+    -- Totals, duplicated from the report for listing
+    recordings_to_delete INTEGER NOT NULL DEFAULT 0,
+    bytes_to_free BIGINT NOT NULL DEFAULT 0,
+    recordings_to_move INTEGER NOT NULL DEFAULT 0,
+    bytes_to_move BIGINT NOT NULL DEFAULT 0,
+
+    -- Full impact report (common::retention::RetentionPreview)
+    report JSONB NOT NULL,
+
+    generated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_retention_previews_policy ON retention_previews(policy_id, generated_at DESC);
@@ -0,0 +1,57 @@
+-- Recording Changes Table (change feed of the recording index, read through
+-- GET /v1/recordings/changes; seq is the cursor)
+CREATE TABLE IF NOT EXISTS recording_changes (
+    seq BIGSERIAL PRIMARY KEY,
+    recording_id VARCHAR(255) NOT NULL,
+    tenant_id UUID,
+
+    -- created, updated, deleted
+    kind VARCHAR(16) NOT NULL,
+
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_recording_changes_recording ON recording_changes(recording_id, seq);
+CREATE INDEX idx_recording_changes_changed_at ON recording_changes(changed_at);
+
+-- Newest pruned deletion; cursors before it may have missed one
+CREATE TABLE IF NOT EXISTS recording_changes_horizon (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    pruned_through BIGINT NOT NULL
+);
+
+-- Existing recordings enter the feed as created
+INSERT INTO recording_changes (recording_id, tenant_id, kind, changed_at)
+SELECT recording_id, tenant_id, 'created', indexed_at
+FROM recording_index
+ORDER BY indexed_at, recording_id;
+
+CREATE OR REPLACE FUNCTION record_recording_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- Writers take turns until they commit, so changes become visible in
+    -- seq order and a reader never skips one that commits late
+    PERFORM pg_advisory_xact_lock(hashtext('recording_changes'));
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO recording_changes (recording_id, tenant_id, kind)
+        VALUES (OLD.recording_id, OLD.tenant_id, 'deleted');
+        RETURN OLD;
+    END IF;
+    INSERT INTO recording_changes (recording_id, tenant_id, kind)
+    VALUES (NEW.recording_id, NEW.tenant_id, CASE WHEN TG_OP = 'INSERT' THEN 'created' ELSE 'updated' END);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER recording_index_changes
+    AFTER INSERT OR DELETE ON recording_index
+    FOR EACH ROW
+    EXECUTE FUNCTION record_recording_change();
+
+-- updated_at and the search vector are rewritten on every update; only
+-- updates that change something else are changes
+CREATE TRIGGER recording_index_changes_update
+    AFTER UPDATE ON recording_index
+    FOR EACH ROW
+    WHEN (to_jsonb(OLD) - 'updated_at' - 'search_vector' IS DISTINCT FROM to_jsonb(NEW) - 'updated_at' - 'search_vector')
+    EXECUTE FUNCTION record_recording_change();
@@ -1,5 +1,6 @@
 mod routes;
 
 pub use routes::{
-    get_thumbnail, get_thumbnail_grid, healthz, list_recordings, start_recording, stop_recording,
+    get_capacity, get_thumbnail, get_thumbnail_grid, healthz, list_recordings, readyz,
+    start_recording, stop_recording,
 };
@@ -9,6 +9,7 @@ use std::path::PathBuf;
 use tracing::{error, info};
 
 use crate::recording::manager::RECORDING_MANAGER;
+use crate::storage::capacity::{self, CapacityStatus};
 use crate::recording::thumbnail_generator::{
     find_recording_path, generate_recording_thumbnail, generate_recording_thumbnail_grid,
     ThumbnailConfig,
@@ -18,15 +19,33 @@ pub async fn healthz() -> &'static str {
   "ok"
 }
 
+/// Not ready once the node has started draining for shutdown
+pub async fn readyz() -> (StatusCode, &'static str) {
+  if RECORDING_MANAGER.is_draining() {
+    (StatusCode::SERVICE_UNAVAILABLE, "draining")
+  } else {
+    (StatusCode::OK, "ready")
+  }
+}
+
 pub async fn list_recordings() -> Json<RecordingListResponse> {
   let recordings = RECORDING_MANAGER.list().await;
   Json(RecordingListResponse { recordings })
 }
 
+/// Recordings volume as of the last capacity check
+pub async fn get_capacity() -> Result<Json<CapacityStatus>, StatusCode> {
+  capacity::status().await.map(Json).ok_or(StatusCode::SERVICE_UNAVAILABLE)
+}
+
 pub async fn start_recording(
   Json(req): Json<RecordingStartRequest>,
 ) -> Result<Json<RecordingStartResponse>, StatusCode> {
   info!(id = %req.config.id, "start recording request");
+  if let Err(e) = crate::search::api::validate_metadata(&crate::search::indexer::initial_metadata(&req)) {
+    tracing::warn!(id = %req.config.id, error = %e, "invalid recording tags");
+    return Err(StatusCode::BAD_REQUEST);
+  }
 
   match RECORDING_MANAGER.start(req).await {
     Ok(response) => Ok(Json(response)),
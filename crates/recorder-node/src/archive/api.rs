@@ -0,0 +1,245 @@
+use axum::{
+  extract::{Path, Query, State},
+  http::StatusCode,
+  Json,
+};
+use common::archive::*;
+use common::tenancy::Tenant;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::store::ArchiveStore;
+use super::uploader::Archiver;
+
+pub struct ArchiveApiState {
+  pub store: Arc<dyn ArchiveStore>,
+  pub archiver: Arc<Archiver>,
+}
+
+/// Load a policy visible to the caller. Global policies (no tenant) are
+/// readable by everyone but only system admins may modify them; policies of
+/// other tenants are reported as missing.
+async fn load_policy(
+  state: &ArchiveApiState,
+  tenant: &Tenant,
+  policy_id: &str,
+  modify: bool,
+) -> Result<ArchivePolicy, StatusCode> {
+  let policy = match state.store.get_policy(policy_id).await {
+    Ok(Some(policy)) => policy,
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!(error = %e, "failed to get archive policy");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  };
+
+  match policy.tenant_id.as_deref() {
+    Some(owner) if tenant.can_access(owner) => Ok(policy),
+    None if !modify || tenant.is_system_admin => Ok(policy),
+    None => Err(StatusCode::FORBIDDEN),
+    Some(_) => Err(StatusCode::NOT_FOUND),
+  }
+}
+
+/// Recordings without a tenant are only visible to system admins
+fn can_see_recording(tenant: &Tenant, owner: Option<&str>) -> bool {
+  match owner {
+    Some(owner) => tenant.can_access(owner),
+    None => tenant.is_system_admin,
+  }
+}
+
+/// Load a job whose recording is visible to the caller
+async fn load_job(state: &ArchiveApiState, tenant: &Tenant, job_id: &str) -> Result<ArchiveJob, StatusCode> {
+  match state.store.get_job(job_id).await {
+    Ok(Some(job)) if can_see_recording(tenant, job.tenant_id.as_deref()) => Ok(job),
+    Ok(_) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!(error = %e, "failed to get archive job");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Create a new archive policy
+pub async fn create_policy(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Json(mut req): Json<CreateArchivePolicyRequest>,
+) -> Result<Json<ArchivePolicy>, StatusCode> {
+  req.tenant_id = tenant.filter(req.tenant_id.as_deref());
+  info!(
+    policy_name = %req.name,
+    selector = ?req.selector,
+    "creating archive policy"
+  );
+
+  match state.store.create_policy(req).await {
+    Ok(policy) => {
+      info!(policy_id = %policy.id, "archive policy created");
+      Ok(Json(policy))
+    }
+    Err(e) => {
+      error!(error = %e, "failed to create archive policy");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Get a specific archive policy
+pub async fn get_policy(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Path(policy_id): Path<String>,
+) -> Result<Json<ArchivePolicy>, StatusCode> {
+  load_policy(&state, &tenant, &policy_id, false).await.map(Json)
+}
+
+/// List archive policies
+pub async fn list_policies(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+) -> Result<Json<ListArchivePoliciesResponse>, StatusCode> {
+  match state.store.list_policies(tenant.filter(None).as_deref()).await {
+    Ok(policies) => Ok(Json(ListArchivePoliciesResponse { policies })),
+    Err(e) => {
+      error!(error = %e, "failed to list archive policies");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Update an archive policy
+pub async fn update_policy(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Path(policy_id): Path<String>,
+  Json(req): Json<UpdateArchivePolicyRequest>,
+) -> Result<Json<ArchivePolicy>, StatusCode> {
+  info!(policy_id = %policy_id, "updating archive policy");
+  load_policy(&state, &tenant, &policy_id, true).await?;
+
+  match state.store.update_policy(&policy_id, req).await {
+    Ok(policy) => {
+      info!(policy_id = %policy.id, "archive policy updated");
+      Ok(Json(policy))
+    }
+    Err(e) => {
+      error!(policy_id = %policy_id, error = %e, "failed to update archive policy");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Delete an archive policy; its jobs and archived copies are kept
+pub async fn delete_policy(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Path(policy_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+  info!(policy_id = %policy_id, "deleting archive policy");
+  load_policy(&state, &tenant, &policy_id, true).await?;
+
+  match state.store.delete_policy(&policy_id).await {
+    Ok(true) => {
+      info!(policy_id = %policy_id, "archive policy deleted");
+      Ok(StatusCode::NO_CONTENT)
+    }
+    Ok(false) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!(policy_id = %policy_id, error = %e, "failed to delete archive policy");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Enqueue the recordings a policy selects on this node and start uploading
+pub async fn run_policy(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Path(policy_id): Path<String>,
+) -> Result<Json<ArchiveRunResponse>, StatusCode> {
+  info!(policy_id = %policy_id, "running archive policy");
+  let policy = load_policy(&state, &tenant, &policy_id, true).await?;
+
+  match state.archiver.enqueue_policy(&policy).await {
+    Ok(jobs_created) => {
+      state.archiver.wake();
+      Ok(Json(ArchiveRunResponse { policy_id, jobs_created }))
+    }
+    Err(e) => {
+      error!(policy_id = %policy_id, error = %e, "failed to run archive policy");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListJobsQuery {
+  pub status: Option<ArchiveJobStatus>,
+}
+
+/// List archive jobs, optionally by status
+pub async fn list_jobs(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Query(query): Query<ListJobsQuery>,
+) -> Result<Json<ListArchiveJobsResponse>, StatusCode> {
+  match state.store.list_jobs(tenant.filter(None).as_deref(), query.status).await {
+    Ok(jobs) => Ok(Json(ListArchiveJobsResponse { jobs })),
+    Err(e) => {
+      error!(error = %e, "failed to list archive jobs");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Get a specific archive job
+pub async fn get_job(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Path(job_id): Path<String>,
+) -> Result<Json<ArchiveJob>, StatusCode> {
+  load_job(&state, &tenant, &job_id).await.map(Json)
+}
+
+/// Retry a failed archive job from scratch
+pub async fn retry_job(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Path(job_id): Path<String>,
+) -> Result<Json<ArchiveJob>, StatusCode> {
+  info!(job_id = %job_id, "retrying archive job");
+  load_job(&state, &tenant, &job_id).await?;
+
+  match state.store.retry_job(&job_id).await {
+    Ok(Some(job)) => {
+      state.archiver.wake();
+      Ok(Json(job))
+    }
+    // Only failed jobs can be retried
+    Ok(None) => Err(StatusCode::CONFLICT),
+    Err(e) => {
+      error!(job_id = %job_id, error = %e, "failed to retry archive job");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Where a recording's archived copy is stored
+pub async fn get_recording_location(
+  State(state): State<Arc<ArchiveApiState>>,
+  tenant: Tenant,
+  Path(recording_id): Path<String>,
+) -> Result<Json<ArchiveLocation>, StatusCode> {
+  match state.store.archive_location(&recording_id).await {
+    Ok(Some(location)) if can_see_recording(&tenant, location.tenant_id.as_deref()) => Ok(Json(location)),
+    Ok(_) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!(recording_id = %recording_id, error = %e, "failed to get archive location");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
@@ -0,0 +1,7 @@
+pub mod store;
+pub mod target;
+pub mod uploader;
+pub mod api;
+
+pub use store::{ArchiveStore, PostgresArchiveStore};
+pub use uploader::{ArchiveConfig, Archiver};
@@ -0,0 +1,406 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use common::archive::*;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+/// Recording a policy selected for archiving
+#[derive(Debug, Clone)]
+pub struct ArchiveCandidate {
+  pub recording_id: String,
+  pub tenant_id: Option<String>,
+  pub storage_path: String,
+}
+
+#[async_trait]
+pub trait ArchiveStore: Send + Sync {
+  // Policy CRUD
+  async fn create_policy(&self, req: CreateArchivePolicyRequest) -> Result<ArchivePolicy>;
+  async fn get_policy(&self, policy_id: &str) -> Result<Option<ArchivePolicy>>;
+  async fn list_policies(&self, tenant_id: Option<&str>) -> Result<Vec<ArchivePolicy>>;
+  async fn update_policy(&self, policy_id: &str, req: UpdateArchivePolicyRequest) -> Result<ArchivePolicy>;
+  async fn delete_policy(&self, policy_id: &str) -> Result<bool>;
+
+  /// Finished single-file recordings matching `policy` that have no archive
+  /// job yet, oldest first
+  async fn select_candidates(&self, policy: &ArchivePolicy, limit: i64) -> Result<Vec<ArchiveCandidate>>;
+
+  // Jobs
+  /// Insert `job`; `false` when the recording already has one
+  async fn create_job(&self, job: &ArchiveJob) -> Result<bool>;
+  /// Persist status and progress
+  async fn save_job(&self, job: &ArchiveJob) -> Result<()>;
+  async fn get_job(&self, job_id: &str) -> Result<Option<ArchiveJob>>;
+  async fn list_jobs(&self, tenant_id: Option<&str>, status: Option<ArchiveJobStatus>) -> Result<Vec<ArchiveJob>>;
+  /// Up to `limit` unfinished jobs of `node_id` (pending, interrupted, or
+  /// failed fewer than `max_attempts` times), least attempted first
+  async fn pending_jobs(&self, node_id: &str, max_attempts: i32, limit: i64) -> Result<Vec<ArchiveJob>>;
+  /// Reset a failed job so it is retried from scratch
+  async fn retry_job(&self, job_id: &str) -> Result<Option<ArchiveJob>>;
+
+  // Recording index
+  async fn mark_archived(&self, recording_id: &str, location: &str) -> Result<()>;
+  async fn archive_location(&self, recording_id: &str) -> Result<Option<ArchiveLocation>>;
+}
+
+pub struct PostgresArchiveStore {
+  pool: PgPool,
+}
+
+impl PostgresArchiveStore {
+  pub fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  fn map_policy_row(row: PgRow) -> Result<ArchivePolicy> {
+    let selector: serde_json::Value = row.try_get("selector")?;
+    let created_at: chrono::DateTime<chrono::Utc> = row.try_get("created_at")?;
+    let updated_at: chrono::DateTime<chrono::Utc> = row.try_get("updated_at")?;
+
+    Ok(ArchivePolicy {
+      id: row.try_get::<Uuid, _>("id")?.to_string(),
+      tenant_id: row
+        .try_get::<Option<Uuid>, _>("tenant_id")?
+        .map(|u| u.to_string()),
+      name: row.try_get("name")?,
+      enabled: row.try_get("enabled")?,
+      selector: serde_json::from_value(selector)?,
+      created_at: Some(created_at.timestamp()),
+      updated_at: Some(updated_at.timestamp()),
+    })
+  }
+
+  fn map_job_row(row: PgRow) -> Result<ArchiveJob> {
+    let backend: String = row.try_get("backend")?;
+    let status: String = row.try_get("status")?;
+    let parts: serde_json::Value = row.try_get("parts")?;
+    let created_at: chrono::DateTime<chrono::Utc> = row.try_get("created_at")?;
+    let updated_at: chrono::DateTime<chrono::Utc> = row.try_get("updated_at")?;
+
+    Ok(ArchiveJob {
+      id: row.try_get::<Uuid, _>("id")?.to_string(),
+      policy_id: row
+        .try_get::<Option<Uuid>, _>("policy_id")?
+        .map(|u| u.to_string()),
+      recording_id: row.try_get("recording_id")?,
+      tenant_id: row
+        .try_get::<Option<Uuid>, _>("tenant_id")?
+        .map(|u| u.to_string()),
+      node_id: row.try_get("node_id")?,
+      backend: backend.parse().map_err(|e: String| anyhow!(e))?,
+      source_path: row.try_get("source_path")?,
+      object_key: row.try_get("object_key")?,
+      status: status.parse().map_err(|e: String| anyhow!(e))?,
+      bytes_total: row.try_get("bytes_total")?,
+      bytes_uploaded: row.try_get("bytes_uploaded")?,
+      part_size_bytes: row.try_get("part_size_bytes")?,
+      upload_id: row.try_get("upload_id")?,
+      parts: serde_json::from_value(parts)?,
+      location: row.try_get("location")?,
+      attempts: row.try_get("attempts")?,
+      error_message: row.try_get("error_message")?,
+      created_at: created_at.timestamp(),
+      updated_at: updated_at.timestamp(),
+    })
+  }
+}
+
+fn parse_tenant(tenant_id: Option<&str>) -> Result<Option<Uuid>> {
+  tenant_id.map(Uuid::parse_str).transpose().map_err(Into::into)
+}
+
+#[async_trait]
+impl ArchiveStore for PostgresArchiveStore {
+  async fn create_policy(&self, req: CreateArchivePolicyRequest) -> Result<ArchivePolicy> {
+    let row = sqlx::query(
+      r#"
+      INSERT INTO archive_policies (id, tenant_id, name, enabled, selector)
+      VALUES ($1, $2, $3, $4, $5)
+      RETURNING *
+      "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(parse_tenant(req.tenant_id.as_deref())?)
+    .bind(&req.name)
+    .bind(req.enabled)
+    .bind(serde_json::to_value(&req.selector)?)
+    .fetch_one(&self.pool)
+    .await?;
+
+    Self::map_policy_row(row)
+  }
+
+  async fn get_policy(&self, policy_id: &str) -> Result<Option<ArchivePolicy>> {
+    let uuid = Uuid::parse_str(policy_id)?;
+    let row = sqlx::query("SELECT * FROM archive_policies WHERE id = $1")
+      .bind(uuid)
+      .fetch_optional(&self.pool)
+      .await?;
+
+    row.map(Self::map_policy_row).transpose()
+  }
+
+  async fn list_policies(&self, tenant_id: Option<&str>) -> Result<Vec<ArchivePolicy>> {
+    let rows = sqlx::query(
+      "SELECT * FROM archive_policies WHERE $1::uuid IS NULL OR tenant_id = $1 ORDER BY name",
+    )
+    .bind(parse_tenant(tenant_id)?)
+    .fetch_all(&self.pool)
+    .await?;
+
+    rows.into_iter().map(Self::map_policy_row).collect()
+  }
+
+  async fn update_policy(&self, policy_id: &str, req: UpdateArchivePolicyRequest) -> Result<ArchivePolicy> {
+    let uuid = Uuid::parse_str(policy_id)?;
+    let selector = req.selector.as_ref().map(serde_json::to_value).transpose()?;
+    let row = sqlx::query(
+      r#"
+      UPDATE archive_policies
+      SET name = COALESCE($2, name),
+          enabled = COALESCE($3, enabled),
+          selector = COALESCE($4, selector),
+          updated_at = NOW()
+      WHERE id = $1
+      RETURNING *
+      "#,
+    )
+    .bind(uuid)
+    .bind(&req.name)
+    .bind(req.enabled)
+    .bind(selector)
+    .fetch_optional(&self.pool)
+    .await?
+    .ok_or_else(|| anyhow!("policy not found"))?;
+
+    Self::map_policy_row(row)
+  }
+
+  async fn delete_policy(&self, policy_id: &str) -> Result<bool> {
+    let uuid = Uuid::parse_str(policy_id)?;
+    let result = sqlx::query("DELETE FROM archive_policies WHERE id = $1")
+      .bind(uuid)
+      .execute(&self.pool)
+      .await?;
+
+    Ok(result.rows_affected() > 0)
+  }
+
+  async fn select_candidates(&self, policy: &ArchivePolicy, limit: i64) -> Result<Vec<ArchiveCandidate>> {
+    // HLS recordings are a playlist plus segments, not a single object
+    let base = r#"
+      SELECT r.recording_id, r.tenant_id, r.storage_path
+      FROM recording_index r
+      WHERE r.archive_location IS NULL
+        AND r.stopped_at IS NOT NULL
+        AND r.storage_path IS NOT NULL
+        AND r.storage_path NOT LIKE '%.m3u8'
+        AND ($1::uuid IS NULL OR r.tenant_id = $1)
+        AND NOT EXISTS (SELECT 1 FROM archive_jobs j WHERE j.recording_id = r.recording_id)
+    "#;
+    let tenant = parse_tenant(policy.tenant_id.as_deref())?;
+
+    let rows = match &policy.selector {
+      ArchiveSelector::Flagged => {
+        sqlx::query(&format!("{base} AND $3 = ANY(r.tags) ORDER BY r.started_at LIMIT $2"))
+          .bind(tenant)
+          .bind(limit)
+          .bind(FLAGGED_TAG)
+          .fetch_all(&self.pool)
+          .await?
+      }
+      ArchiveSelector::EventBased { event_types } => {
+        sqlx::query(&format!(
+          "{base} AND EXISTS (
+            SELECT 1 FROM event_index e
+            WHERE e.recording_id = r.recording_id
+              AND (cardinality($3::text[]) = 0 OR e.event_type = ANY($3))
+          )
+          ORDER BY r.started_at LIMIT $2"
+        ))
+        .bind(tenant)
+        .bind(limit)
+        .bind(event_types)
+        .fetch_all(&self.pool)
+        .await?
+      }
+      ArchiveSelector::OlderThan { days } => {
+        sqlx::query(&format!(
+          "{base} AND r.started_at < NOW() - make_interval(days => $3) ORDER BY r.started_at LIMIT $2"
+        ))
+        .bind(tenant)
+        .bind(limit)
+        .bind(i32::try_from(*days).unwrap_or(i32::MAX))
+        .fetch_all(&self.pool)
+        .await?
+      }
+    };
+
+    rows
+      .into_iter()
+      .map(|row| {
+        Ok(ArchiveCandidate {
+          recording_id: row.try_get("recording_id")?,
+          tenant_id: row
+            .try_get::<Option<Uuid>, _>("tenant_id")?
+            .map(|u| u.to_string()),
+          storage_path: row.try_get("storage_path")?,
+        })
+      })
+      .collect()
+  }
+
+  async fn create_job(&self, job: &ArchiveJob) -> Result<bool> {
+    let result = sqlx::query(
+      r#"
+      INSERT INTO archive_jobs
+        (id, policy_id, recording_id, tenant_id, node_id, backend, source_path, object_key,
+         status, part_size_bytes)
+      VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+      ON CONFLICT (recording_id) DO NOTHING
+      "#,
+    )
+    .bind(Uuid::parse_str(&job.id)?)
+    .bind(job.policy_id.as_deref().map(Uuid::parse_str).transpose()?)
+    .bind(&job.recording_id)
+    .bind(parse_tenant(job.tenant_id.as_deref())?)
+    .bind(&job.node_id)
+    .bind(job.backend.as_str())
+    .bind(&job.source_path)
+    .bind(&job.object_key)
+    .bind(job.status.as_str())
+    .bind(job.part_size_bytes)
+    .execute(&self.pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+  }
+
+  async fn save_job(&self, job: &ArchiveJob) -> Result<()> {
+    sqlx::query(
+      r#"
+      UPDATE archive_jobs
+      SET status = $2, bytes_total = $3, bytes_uploaded = $4, part_size_bytes = $5,
+          upload_id = $6, parts = $7, location = $8, attempts = $9, error_message = $10,
+          updated_at = NOW()
+      WHERE id = $1
+      "#,
+    )
+    .bind(Uuid::parse_str(&job.id)?)
+    .bind(job.status.as_str())
+    .bind(job.bytes_total)
+    .bind(job.bytes_uploaded)
+    .bind(job.part_size_bytes)
+    .bind(&job.upload_id)
+    .bind(serde_json::to_value(&job.parts)?)
+    .bind(&job.location)
+    .bind(job.attempts)
+    .bind(&job.error_message)
+    .execute(&self.pool)
+    .await?;
+
+    Ok(())
+  }
+
+  async fn get_job(&self, job_id: &str) -> Result<Option<ArchiveJob>> {
+    let uuid = Uuid::parse_str(job_id)?;
+    let row = sqlx::query("SELECT * FROM archive_jobs WHERE id = $1")
+      .bind(uuid)
+      .fetch_optional(&self.pool)
+      .await?;
+
+    row.map(Self::map_job_row).transpose()
+  }
+
+  async fn list_jobs(&self, tenant_id: Option<&str>, status: Option<ArchiveJobStatus>) -> Result<Vec<ArchiveJob>> {
+    let rows = sqlx::query(
+      r#"
+      SELECT * FROM archive_jobs
+      WHERE ($1::uuid IS NULL OR tenant_id = $1)
+        AND ($2::text IS NULL OR status = $2)
+      ORDER BY created_at DESC
+      LIMIT 1000
+      "#,
+    )
+    .bind(parse_tenant(tenant_id)?)
+    .bind(status.map(|s| s.as_str()))
+    .fetch_all(&self.pool)
+    .await?;
+
+    rows.into_iter().map(Self::map_job_row).collect()
+  }
+
+  async fn pending_jobs(&self, node_id: &str, max_attempts: i32, limit: i64) -> Result<Vec<ArchiveJob>> {
+    let rows = sqlx::query(
+      r#"
+      SELECT * FROM archive_jobs
+      WHERE node_id = $1
+        AND (status IN ('pending', 'uploading') OR (status = 'failed' AND attempts < $2))
+      ORDER BY attempts, created_at
+      LIMIT $3
+      "#,
+    )
+    .bind(node_id)
+    .bind(max_attempts)
+    .bind(limit)
+    .fetch_all(&self.pool)
+    .await?;
+
+    rows.into_iter().map(Self::map_job_row).collect()
+  }
+
+  async fn retry_job(&self, job_id: &str) -> Result<Option<ArchiveJob>> {
+    let row = sqlx::query(
+      r#"
+      UPDATE archive_jobs
+      SET status = 'pending', attempts = 0, error_message = NULL, upload_id = NULL,
+          parts = '[]', bytes_uploaded = 0, updated_at = NOW()
+      WHERE id = $1 AND status = 'failed'
+      RETURNING *
+      "#,
+    )
+    .bind(Uuid::parse_str(job_id)?)
+    .fetch_optional(&self.pool)
+    .await?;
+
+    row.map(Self::map_job_row).transpose()
+  }
+
+  async fn mark_archived(&self, recording_id: &str, location: &str) -> Result<()> {
+    sqlx::query(
+      "UPDATE recording_index SET archive_location = $2, archived_at = NOW(), updated_at = NOW()
+       WHERE recording_id = $1",
+    )
+    .bind(recording_id)
+    .bind(location)
+    .execute(&self.pool)
+    .await?;
+
+    Ok(())
+  }
+
+  async fn archive_location(&self, recording_id: &str) -> Result<Option<ArchiveLocation>> {
+    let row = sqlx::query(
+      "SELECT recording_id, tenant_id, archive_location, archived_at FROM recording_index
+       WHERE recording_id = $1 AND archive_location IS NOT NULL",
+    )
+    .bind(recording_id)
+    .fetch_optional(&self.pool)
+    .await?;
+
+    row
+      .map(|row| {
+        let archived_at: Option<chrono::DateTime<chrono::Utc>> = row.try_get("archived_at")?;
+        Ok(ArchiveLocation {
+          recording_id: row.try_get("recording_id")?,
+          tenant_id: row
+            .try_get::<Option<Uuid>, _>("tenant_id")?
+            .map(|u| u.to_string()),
+          location: row.try_get("archive_location")?,
+          archived_at: archived_at.map(|t| t.timestamp()).unwrap_or_default(),
+        })
+      })
+      .transpose()
+  }
+}
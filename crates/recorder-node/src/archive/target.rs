@@ -0,0 +1,261 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region};
+use aws_credential_types::Credentials;
+use aws_sdk_s3::{
+  config::Builder as S3ConfigBuilder,
+  primitives::ByteStream,
+  types::{CompletedMultipartUpload, CompletedPart},
+  Client,
+};
+use base64::Engine;
+use common::archive::{ArchiveBackend, ArchivedPart};
+use reqwest::Url;
+
+/// Object storage receiving archived recordings through multipart uploads
+#[async_trait]
+pub trait ArchiveTarget: Send + Sync {
+  fn backend(&self) -> ArchiveBackend;
+
+  /// Start a multipart upload of `key`, returning its upload id
+  async fn begin(&self, key: &str) -> Result<String>;
+
+  /// Store part `part_number` (from 1), returning the tag that identifies it
+  /// when the upload is completed
+  async fn upload_part(&self, key: &str, upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<String>;
+
+  /// Assemble the uploaded parts into the object, returning its location
+  async fn complete(&self, key: &str, upload_id: &str, parts: &[ArchivedPart]) -> Result<String>;
+}
+
+/// S3, or Google Cloud Storage through its S3-compatible XML API (with HMAC
+/// keys)
+pub struct S3Target {
+  client: Client,
+  bucket: String,
+  backend: ArchiveBackend,
+}
+
+impl S3Target {
+  pub async fn new(
+    backend: ArchiveBackend,
+    endpoint: Option<&str>,
+    region: &str,
+    access_key: &str,
+    secret_key: &str,
+    bucket: String,
+  ) -> Self {
+    let base = aws_config::defaults(BehaviorVersion::latest())
+      .region(Region::new(region.to_string()))
+      .load()
+      .await;
+
+    let mut conf = S3ConfigBuilder::from(&base)
+      .region(Region::new(region.to_string()))
+      .credentials_provider(Credentials::new(access_key, secret_key, None, None, "static"));
+    if let Some(endpoint) = endpoint {
+      conf = conf.endpoint_url(endpoint).force_path_style(true);
+    }
+
+    Self {
+      client: Client::from_conf(conf.build()),
+      bucket,
+      backend,
+    }
+  }
+}
+
+#[async_trait]
+impl ArchiveTarget for S3Target {
+  fn backend(&self) -> ArchiveBackend {
+    self.backend
+  }
+
+  async fn begin(&self, key: &str) -> Result<String> {
+    let upload = self
+      .client
+      .create_multipart_upload()
+      .bucket(&self.bucket)
+      .key(key)
+      .send()
+      .await
+      .context("failed to start multipart upload")?;
+    upload
+      .upload_id()
+      .map(str::to_string)
+      .ok_or_else(|| anyhow!("multipart upload started without an upload id"))
+  }
+
+  async fn upload_part(&self, key: &str, upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<String> {
+    let part = self
+      .client
+      .upload_part()
+      .bucket(&self.bucket)
+      .key(key)
+      .upload_id(upload_id)
+      .part_number(part_number)
+      .body(ByteStream::from(data))
+      .send()
+      .await
+      .with_context(|| format!("failed to upload part {part_number}"))?;
+    part
+      .e_tag()
+      .map(str::to_string)
+      .ok_or_else(|| anyhow!("part {part_number} uploaded without an ETag"))
+  }
+
+  async fn complete(&self, key: &str, upload_id: &str, parts: &[ArchivedPart]) -> Result<String> {
+    let parts = parts
+      .iter()
+      .map(|part| {
+        CompletedPart::builder()
+          .part_number(part.part_number)
+          .e_tag(&part.tag)
+          .build()
+      })
+      .collect();
+    self
+      .client
+      .complete_multipart_upload()
+      .bucket(&self.bucket)
+      .key(key)
+      .upload_id(upload_id)
+      .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+      .send()
+      .await
+      .context("failed to complete multipart upload")?;
+
+    let scheme = match self.backend {
+      ArchiveBackend::Gcs => "gs",
+      _ => "s3",
+    };
+    Ok(format!("{scheme}://{}/{key}", self.bucket))
+  }
+}
+
+/// Azure Blob Storage block blobs, authorized with a SAS token. Parts are
+/// staged as blocks and committed with a block list; uncommitted blocks are
+/// kept by Azure for a week, so an upload can resume within that time.
+pub struct AzureTarget {
+  http: reqwest::Client,
+  container_url: Url,
+  sas_token: String,
+}
+
+const AZURE_API_VERSION: &str = "2021-08-06";
+
+impl AzureTarget {
+  /// `account_url` is e.g. `https://account.blob.core.windows.net`
+  pub fn new(account_url: &str, container: &str, sas_token: &str) -> Result<Self> {
+    let container_url = Url::parse(&format!("{}/{container}/", account_url.trim_end_matches('/')))
+      .context("invalid Azure account URL")?;
+    Ok(Self {
+      http: reqwest::Client::new(),
+      container_url,
+      sas_token: sas_token.trim_start_matches('?').to_string(),
+    })
+  }
+
+  fn blob_url(&self, key: &str, params: &[(&str, &str)]) -> Result<Url> {
+    let mut url = self.container_url.join(key).context("invalid blob name")?;
+    url.set_query(Some(&self.sas_token));
+    url.query_pairs_mut().extend_pairs(params);
+    Ok(url)
+  }
+}
+
+/// Block ids must have the same length within a blob
+fn block_id(part_number: i32) -> String {
+  base64::engine::general_purpose::STANDARD.encode(format!("{part_number:08}"))
+}
+
+fn block_list(parts: &[ArchivedPart]) -> String {
+  let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?><BlockList>"#);
+  for part in parts {
+    xml.push_str(&format!("<Latest>{}</Latest>", part.tag));
+  }
+  xml.push_str("</BlockList>");
+  xml
+}
+
+#[async_trait]
+impl ArchiveTarget for AzureTarget {
+  fn backend(&self) -> ArchiveBackend {
+    ArchiveBackend::Azure
+  }
+
+  async fn begin(&self, _key: &str) -> Result<String> {
+    // Blocks are staged against the blob itself; there is no upload session
+    Ok("blocks".to_string())
+  }
+
+  async fn upload_part(&self, key: &str, _upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<String> {
+    let id = block_id(part_number);
+    let url = self.blob_url(key, &[("comp", "block"), ("blockid", &id)])?;
+    self
+      .http
+      .put(url)
+      .header("x-ms-version", AZURE_API_VERSION)
+      .body(data)
+      .send()
+      .await
+      .with_context(|| format!("failed to upload block {part_number}"))?
+      .error_for_status()
+      .with_context(|| format!("block {part_number} rejected"))?;
+    Ok(id)
+  }
+
+  async fn complete(&self, key: &str, _upload_id: &str, parts: &[ArchivedPart]) -> Result<String> {
+    let url = self.blob_url(key, &[("comp", "blocklist")])?;
+    self
+      .http
+      .put(url)
+      .header("x-ms-version", AZURE_API_VERSION)
+      .header("content-type", "application/xml")
+      .body(block_list(parts))
+      .send()
+      .await
+      .context("failed to commit block list")?
+      .error_for_status()
+      .context("block list rejected")?;
+
+    let mut location = self.container_url.join(key).context("invalid blob name")?;
+    location.set_query(None);
+    Ok(location.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn block_ids_have_a_fixed_length() {
+    assert_eq!(block_id(1).len(), block_id(12_345).len());
+    assert_ne!(block_id(1), block_id(2));
+  }
+
+  #[test]
+  fn block_list_commits_parts_in_order() {
+    let parts = vec![
+      ArchivedPart { part_number: 1, tag: block_id(1) },
+      ArchivedPart { part_number: 2, tag: block_id(2) },
+    ];
+    let xml = block_list(&parts);
+    assert!(xml.ends_with(&format!(
+      "<BlockList><Latest>{}</Latest><Latest>{}</Latest></BlockList>",
+      block_id(1),
+      block_id(2)
+    )));
+  }
+
+  #[test]
+  fn blob_urls_carry_the_sas_token() {
+    let target = AzureTarget::new("https://acct.blob.core.windows.net/", "archive", "?sv=2022&sig=abc").unwrap();
+    let url = target.blob_url("t1/rec-1/recording.mp4", &[("comp", "blocklist")]).unwrap();
+    assert_eq!(
+      url.as_str(),
+      "https://acct.blob.core.windows.net/archive/t1/rec-1/recording.mp4?sv=2022&sig=abc&comp=blocklist"
+    );
+  }
+}
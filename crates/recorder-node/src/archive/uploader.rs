@@ -0,0 +1,600 @@
+use anyhow::{anyhow, Context, Result};
+use common::archive::*;
+use std::collections::HashSet;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::store::{ArchiveCandidate, ArchiveStore};
+use super::target::{ArchiveTarget, AzureTarget, S3Target};
+
+/// Smallest part S3 accepts (other than the last one)
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Recordings enqueued per policy and run
+const CANDIDATES_PER_RUN: i64 = 500;
+
+/// Jobs fetched at a time
+const JOBS_PER_BATCH: i64 = 20;
+
+/// Archiver settings, from `ARCHIVE_*` variables
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+  pub backend: ArchiveBackend,
+  /// Bucket, or container for Azure
+  pub bucket: String,
+  pub prefix: String,
+  pub endpoint: Option<String>,
+  pub region: String,
+  pub access_key: String,
+  pub secret_key: String,
+  pub azure_account: Option<String>,
+  pub azure_sas_token: String,
+  /// Upload rate limit in kbit/s, 0 for none
+  pub max_bandwidth_kbps: u64,
+  pub part_size_bytes: u64,
+  pub interval: Duration,
+  pub max_attempts: i32,
+}
+
+impl ArchiveConfig {
+  /// `None` (archiving disabled) unless `ARCHIVE_BACKEND` is set
+  pub fn from_env() -> Result<Option<Self>> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+    let number = |name: &str, default: u64| var(name).and_then(|v| v.parse::<u64>().ok()).unwrap_or(default);
+
+    let Some(backend) = var("ARCHIVE_BACKEND") else {
+      return Ok(None);
+    };
+    let backend: ArchiveBackend = backend.parse().map_err(|e: String| anyhow!(e))?;
+    let bucket = var("ARCHIVE_BUCKET").context("ARCHIVE_BUCKET is required with ARCHIVE_BACKEND")?;
+
+    Ok(Some(Self {
+      backend,
+      bucket,
+      prefix: var("ARCHIVE_PREFIX").unwrap_or_else(|| "recordings".to_string()),
+      endpoint: var("ARCHIVE_ENDPOINT"),
+      region: var("ARCHIVE_REGION").unwrap_or_else(|| "us-east-1".to_string()),
+      access_key: var("ARCHIVE_ACCESS_KEY").unwrap_or_default(),
+      secret_key: var("ARCHIVE_SECRET_KEY").unwrap_or_default(),
+      azure_account: var("ARCHIVE_AZURE_ACCOUNT"),
+      azure_sas_token: var("ARCHIVE_AZURE_SAS_TOKEN").unwrap_or_default(),
+      max_bandwidth_kbps: number("ARCHIVE_MAX_BANDWIDTH_KBPS", 0),
+      part_size_bytes: (number("ARCHIVE_PART_SIZE_MB", 8) * 1024 * 1024).max(MIN_PART_SIZE),
+      interval: Duration::from_secs(number("ARCHIVE_INTERVAL_SECS", 3600).max(10)),
+      max_attempts: i32::try_from(number("ARCHIVE_MAX_ATTEMPTS", 5)).unwrap_or(i32::MAX),
+    }))
+  }
+
+  pub async fn target(&self) -> Result<Arc<dyn ArchiveTarget>> {
+    Ok(match self.backend {
+      ArchiveBackend::S3 => Arc::new(
+        S3Target::new(
+          self.backend,
+          self.endpoint.as_deref(),
+          &self.region,
+          &self.access_key,
+          &self.secret_key,
+          self.bucket.clone(),
+        )
+        .await,
+      ),
+      ArchiveBackend::Gcs => Arc::new(
+        S3Target::new(
+          self.backend,
+          Some(self.endpoint.as_deref().unwrap_or("https://storage.googleapis.com")),
+          // GCS ignores the region of its S3-compatible API
+          "auto",
+          &self.access_key,
+          &self.secret_key,
+          self.bucket.clone(),
+        )
+        .await,
+      ),
+      ArchiveBackend::Azure => {
+        let account_url = match (&self.endpoint, &self.azure_account) {
+          (Some(endpoint), _) => endpoint.clone(),
+          (None, Some(account)) => format!("https://{account}.blob.core.windows.net"),
+          (None, None) => return Err(anyhow!("ARCHIVE_AZURE_ACCOUNT or ARCHIVE_ENDPOINT is required for Azure")),
+        };
+        Arc::new(AzureTarget::new(&account_url, &self.bucket, &self.azure_sas_token)?)
+      }
+    })
+  }
+}
+
+/// Spreads uploads out so they average at most `kbps`, shared by every
+/// upload of the archiver
+pub struct Throttle {
+  kbps: u64,
+  next: Mutex<Instant>,
+}
+
+impl Throttle {
+  pub fn new(kbps: u64) -> Self {
+    Self {
+      kbps,
+      next: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// Wait until `bytes` more may be sent
+  pub async fn acquire(&self, bytes: usize) {
+    if self.kbps == 0 {
+      return;
+    }
+    let cost = Duration::from_secs_f64(bytes as f64 * 8.0 / (self.kbps as f64 * 1000.0));
+    let start = {
+      let mut next = self.next.lock().await;
+      let start = (*next).max(Instant::now());
+      *next = start + cost;
+      start
+    };
+    tokio::time::sleep_until(start).await;
+  }
+}
+
+/// Object key of a recording's archived copy
+fn object_key(prefix: &str, candidate: &ArchiveCandidate) -> String {
+  let file_name = Path::new(&candidate.storage_path)
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_else(|| "recording".to_string());
+  let tenant = candidate.tenant_id.as_deref().unwrap_or("global");
+  format!(
+    "{}/{tenant}/{}/{file_name}",
+    prefix.trim_end_matches('/'),
+    candidate.recording_id
+  )
+}
+
+/// Enqueues recordings selected by archive policies and uploads them
+pub struct Archiver {
+  store: Arc<dyn ArchiveStore>,
+  target: Arc<dyn ArchiveTarget>,
+  throttle: Throttle,
+  node_id: String,
+  prefix: String,
+  part_size_bytes: u64,
+  max_attempts: i32,
+  wake: Notify,
+}
+
+impl Archiver {
+  pub fn new(
+    store: Arc<dyn ArchiveStore>,
+    target: Arc<dyn ArchiveTarget>,
+    config: &ArchiveConfig,
+    node_id: String,
+  ) -> Self {
+    Self {
+      store,
+      target,
+      throttle: Throttle::new(config.max_bandwidth_kbps),
+      node_id,
+      prefix: config.prefix.clone(),
+      part_size_bytes: config.part_size_bytes,
+      max_attempts: config.max_attempts,
+      wake: Notify::new(),
+    }
+  }
+
+  /// Start the next pass now instead of at the next interval
+  pub fn wake(&self) {
+    self.wake.notify_one();
+  }
+
+  /// Create jobs for the recordings `policy` selects that are stored on this
+  /// node; returns how many
+  pub async fn enqueue_policy(&self, policy: &ArchivePolicy) -> Result<usize> {
+    let candidates = self.store.select_candidates(policy, CANDIDATES_PER_RUN).await?;
+    let now = chrono::Utc::now().timestamp();
+    let mut created = 0;
+    for candidate in candidates {
+      // Recordings live on the node that recorded them, which enqueues them
+      if !Path::new(&candidate.storage_path).exists() {
+        continue;
+      }
+      let job = ArchiveJob {
+        id: Uuid::new_v4().to_string(),
+        policy_id: Some(policy.id.clone()),
+        recording_id: candidate.recording_id.clone(),
+        tenant_id: candidate.tenant_id.clone(),
+        node_id: self.node_id.clone(),
+        backend: self.target.backend(),
+        object_key: object_key(&self.prefix, &candidate),
+        source_path: candidate.storage_path,
+        status: ArchiveJobStatus::Pending,
+        bytes_total: 0,
+        bytes_uploaded: 0,
+        part_size_bytes: i64::try_from(self.part_size_bytes)?,
+        upload_id: None,
+        parts: Vec::new(),
+        location: None,
+        attempts: 0,
+        error_message: None,
+        created_at: now,
+        updated_at: now,
+      };
+      if self.store.create_job(&job).await? {
+        created += 1;
+      }
+    }
+    info!(policy_id = %policy.id, jobs_created = created, "archive policy evaluated");
+    Ok(created)
+  }
+
+  /// Upload this node's unfinished jobs, trying each once; returns how many
+  /// completed
+  pub async fn process_jobs(&self) -> Result<usize> {
+    let mut attempted = HashSet::new();
+    let mut completed = 0;
+    loop {
+      let jobs = self
+        .store
+        .pending_jobs(&self.node_id, self.max_attempts, JOBS_PER_BATCH)
+        .await?;
+      let jobs: Vec<_> = jobs
+        .into_iter()
+        .filter(|job| !attempted.contains(&job.id))
+        .collect();
+      if jobs.is_empty() {
+        return Ok(completed);
+      }
+      for mut job in jobs {
+        attempted.insert(job.id.clone());
+        match self.upload(&mut job).await {
+          Ok(()) => completed += 1,
+          Err(e) => {
+            job.status = ArchiveJobStatus::Failed;
+            job.attempts += 1;
+            job.error_message = Some(format!("{e:#}"));
+            warn!(
+              job_id = %job.id,
+              recording_id = %job.recording_id,
+              attempts = job.attempts,
+              error = %e,
+              "archive upload failed"
+            );
+            self.store.save_job(&job).await?;
+          }
+        }
+      }
+    }
+  }
+
+  /// Upload `job`, continuing after the last part the backend stored
+  async fn upload(&self, job: &mut ArchiveJob) -> Result<()> {
+    let mut file = File::open(&job.source_path)
+      .await
+      .with_context(|| format!("cannot open {}", job.source_path))?;
+    let size = file.metadata().await?.len();
+    let size_i64 = i64::try_from(size)?;
+
+    // Start over if there is no upload yet or the file changed since
+    let upload_id = match &job.upload_id {
+      Some(upload_id) if job.bytes_total == size_i64 && job.part_size_bytes > 0 => upload_id.clone(),
+      _ => {
+        let upload_id = self.target.begin(&job.object_key).await?;
+        job.upload_id = Some(upload_id.clone());
+        job.parts.clear();
+        job.bytes_total = size_i64;
+        job.bytes_uploaded = 0;
+        job.part_size_bytes = i64::try_from(self.part_size_bytes)?;
+        upload_id
+      }
+    };
+    job.status = ArchiveJobStatus::Uploading;
+    self.store.save_job(job).await?;
+
+    let part_size = u64::try_from(job.part_size_bytes)?;
+    let mut offset = job.parts.len() as u64 * part_size;
+    if offset > 0 {
+      info!(job_id = %job.id, offset, size, "resuming archive upload");
+    }
+    file.seek(SeekFrom::Start(offset)).await?;
+
+    // At least one part, so empty recordings still produce an object
+    while offset < size || job.parts.is_empty() {
+      let len = usize::try_from(part_size.min(size - offset))?;
+      let mut data = vec![0; len];
+      file.read_exact(&mut data).await?;
+
+      self.throttle.acquire(len).await;
+      let part_number = i32::try_from(job.parts.len() + 1)?;
+      let tag = self
+        .target
+        .upload_part(&job.object_key, &upload_id, part_number, data)
+        .await?;
+      job.parts.push(ArchivedPart { part_number, tag });
+      offset += len as u64;
+      job.bytes_uploaded = i64::try_from(offset)?;
+      self.store.save_job(job).await?;
+    }
+
+    let location = self
+      .target
+      .complete(&job.object_key, &upload_id, &job.parts)
+      .await?;
+    self.store.mark_archived(&job.recording_id, &location).await?;
+    job.location = Some(location.clone());
+    job.status = ArchiveJobStatus::Completed;
+    job.error_message = None;
+    self.store.save_job(job).await?;
+
+    info!(
+      job_id = %job.id,
+      recording_id = %job.recording_id,
+      bytes = size,
+      location = %location,
+      "recording archived"
+    );
+    Ok(())
+  }
+
+  /// Evaluate every enabled policy, then upload what is queued
+  pub async fn run_once(&self) -> Result<usize> {
+    for policy in self.store.list_policies(None).await? {
+      if !policy.enabled {
+        continue;
+      }
+      if let Err(e) = self.enqueue_policy(&policy).await {
+        error!(policy_id = %policy.id, error = %e, "failed to evaluate archive policy");
+      }
+    }
+    self.process_jobs().await
+  }
+
+  pub fn spawn(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(interval);
+      loop {
+        tokio::select! {
+          _ = ticker.tick() => {}
+          _ = self.wake.notified() => {}
+        }
+        match self.run_once().await {
+          Ok(0) => {}
+          Ok(completed) => info!(completed, "archive pass finished"),
+          Err(e) => error!(error = %e, "archive pass failed"),
+        }
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use async_trait::async_trait;
+  use std::collections::HashMap;
+
+  fn candidate(tenant_id: Option<&str>, storage_path: &str) -> ArchiveCandidate {
+    ArchiveCandidate {
+      recording_id: "rec-1".to_string(),
+      tenant_id: tenant_id.map(str::to_string),
+      storage_path: storage_path.to_string(),
+    }
+  }
+
+  #[test]
+  fn object_keys_group_by_tenant_and_recording() {
+    assert_eq!(
+      object_key("archive/", &candidate(Some("t1"), "/data/rec-1/recording.mp4")),
+      "archive/t1/rec-1/recording.mp4"
+    );
+    assert_eq!(
+      object_key("archive", &candidate(None, "/data/rec-1/recording.mkv")),
+      "archive/global/rec-1/recording.mkv"
+    );
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn throttle_spreads_uploads_over_time() {
+    let throttle = Throttle::new(80); // 10 kB/s
+    let started = Instant::now();
+    throttle.acquire(10_000).await;
+    throttle.acquire(10_000).await;
+    throttle.acquire(10_000).await;
+    // The first part goes out at once, each following one a second later
+    assert_eq!(started.elapsed(), Duration::from_secs(2));
+
+    let unlimited = Throttle::new(0);
+    let started = Instant::now();
+    unlimited.acquire(usize::MAX).await;
+    assert_eq!(started.elapsed(), Duration::ZERO);
+  }
+
+  /// Target keeping parts in memory, failing once on `fail_on_part`
+  #[derive(Default)]
+  struct MemoryTarget {
+    parts: std::sync::Mutex<HashMap<i32, Vec<u8>>>,
+    fail_on_part: std::sync::Mutex<Option<i32>>,
+    begun: std::sync::Mutex<u32>,
+  }
+
+  #[async_trait]
+  impl ArchiveTarget for MemoryTarget {
+    fn backend(&self) -> ArchiveBackend {
+      ArchiveBackend::S3
+    }
+
+    async fn begin(&self, _key: &str) -> Result<String> {
+      *self.begun.lock().unwrap() += 1;
+      Ok("upload-1".to_string())
+    }
+
+    async fn upload_part(&self, _key: &str, _upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<String> {
+      if self.fail_on_part.lock().unwrap().take_if(|n| *n == part_number).is_some() {
+        return Err(anyhow!("connection reset"));
+      }
+      self.parts.lock().unwrap().insert(part_number, data);
+      Ok(format!("etag-{part_number}"))
+    }
+
+    async fn complete(&self, key: &str, _upload_id: &str, parts: &[ArchivedPart]) -> Result<String> {
+      assert_eq!(parts.len(), self.parts.lock().unwrap().len());
+      Ok(format!("s3://bucket/{key}"))
+    }
+  }
+
+  /// Store holding a single job, as the database would between attempts
+  #[derive(Default)]
+  struct SingleJobStore {
+    job: std::sync::Mutex<Option<ArchiveJob>>,
+    archived: std::sync::Mutex<Option<String>>,
+  }
+
+  #[async_trait]
+  impl ArchiveStore for SingleJobStore {
+    async fn create_policy(&self, _req: CreateArchivePolicyRequest) -> Result<ArchivePolicy> {
+      Err(anyhow!("unsupported"))
+    }
+    async fn get_policy(&self, _policy_id: &str) -> Result<Option<ArchivePolicy>> {
+      Ok(None)
+    }
+    async fn list_policies(&self, _tenant_id: Option<&str>) -> Result<Vec<ArchivePolicy>> {
+      Ok(Vec::new())
+    }
+    async fn update_policy(&self, _policy_id: &str, _req: UpdateArchivePolicyRequest) -> Result<ArchivePolicy> {
+      Err(anyhow!("unsupported"))
+    }
+    async fn delete_policy(&self, _policy_id: &str) -> Result<bool> {
+      Ok(false)
+    }
+    async fn select_candidates(&self, _policy: &ArchivePolicy, _limit: i64) -> Result<Vec<ArchiveCandidate>> {
+      Ok(Vec::new())
+    }
+    async fn create_job(&self, job: &ArchiveJob) -> Result<bool> {
+      *self.job.lock().unwrap() = Some(job.clone());
+      Ok(true)
+    }
+    async fn save_job(&self, job: &ArchiveJob) -> Result<()> {
+      *self.job.lock().unwrap() = Some(job.clone());
+      Ok(())
+    }
+    async fn get_job(&self, _job_id: &str) -> Result<Option<ArchiveJob>> {
+      Ok(self.job.lock().unwrap().clone())
+    }
+    async fn list_jobs(&self, _tenant_id: Option<&str>, _status: Option<ArchiveJobStatus>) -> Result<Vec<ArchiveJob>> {
+      Ok(self.job.lock().unwrap().iter().cloned().collect())
+    }
+    async fn pending_jobs(&self, _node_id: &str, max_attempts: i32, _limit: i64) -> Result<Vec<ArchiveJob>> {
+      Ok(self
+        .job
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|job| match job.status {
+          ArchiveJobStatus::Completed => false,
+          ArchiveJobStatus::Failed => job.attempts < max_attempts,
+          _ => true,
+        })
+        .cloned()
+        .collect())
+    }
+    async fn retry_job(&self, _job_id: &str) -> Result<Option<ArchiveJob>> {
+      Ok(None)
+    }
+    async fn mark_archived(&self, _recording_id: &str, location: &str) -> Result<()> {
+      *self.archived.lock().unwrap() = Some(location.to_string());
+      Ok(())
+    }
+    async fn archive_location(&self, _recording_id: &str) -> Result<Option<ArchiveLocation>> {
+      Ok(None)
+    }
+  }
+
+  #[tokio::test]
+  async fn interrupted_uploads_resume_after_the_last_stored_part() {
+    let dir = std::env::temp_dir().join(format!("archive-test-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("recording.mp4");
+    let contents: Vec<u8> = (0..(MIN_PART_SIZE * 2 + 1000)).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &contents).unwrap();
+
+    let store = Arc::new(SingleJobStore::default());
+    let target = Arc::new(MemoryTarget::default());
+    *target.fail_on_part.lock().unwrap() = Some(2);
+    let config = ArchiveConfig {
+      backend: ArchiveBackend::S3,
+      bucket: "bucket".to_string(),
+      prefix: "archive".to_string(),
+      endpoint: None,
+      region: "us-east-1".to_string(),
+      access_key: String::new(),
+      secret_key: String::new(),
+      azure_account: None,
+      azure_sas_token: String::new(),
+      max_bandwidth_kbps: 0,
+      part_size_bytes: MIN_PART_SIZE,
+      interval: Duration::from_secs(60),
+      max_attempts: 3,
+    };
+    let archiver = Archiver::new(store.clone(), target.clone(), &config, "rec-node-1".to_string());
+
+    let policy = ArchivePolicy {
+      id: Uuid::new_v4().to_string(),
+      tenant_id: None,
+      name: "flagged".to_string(),
+      enabled: true,
+      selector: ArchiveSelector::Flagged,
+      created_at: None,
+      updated_at: None,
+    };
+    let job = ArchiveJob {
+      id: Uuid::new_v4().to_string(),
+      policy_id: Some(policy.id.clone()),
+      recording_id: "rec-1".to_string(),
+      tenant_id: None,
+      node_id: "rec-node-1".to_string(),
+      backend: ArchiveBackend::S3,
+      source_path: path.to_string_lossy().to_string(),
+      object_key: object_key("archive", &candidate(None, &path.to_string_lossy())),
+      status: ArchiveJobStatus::Pending,
+      bytes_total: 0,
+      bytes_uploaded: 0,
+      part_size_bytes: i64::try_from(MIN_PART_SIZE).unwrap(),
+      upload_id: None,
+      parts: Vec::new(),
+      location: None,
+      attempts: 0,
+      error_message: None,
+      created_at: 0,
+      updated_at: 0,
+    };
+    store.create_job(&job).await.unwrap();
+
+    // Part 2 fails: part 1 is kept and the job is left for a retry
+    assert_eq!(archiver.process_jobs().await.unwrap(), 0);
+    let failed = store.get_job(&job.id).await.unwrap().unwrap();
+    assert_eq!(failed.status, ArchiveJobStatus::Failed);
+    assert_eq!(failed.parts.len(), 1);
+    assert_eq!(failed.bytes_uploaded, i64::try_from(MIN_PART_SIZE).unwrap());
+
+    // The retry continues with part 2 on the same upload
+    assert_eq!(archiver.process_jobs().await.unwrap(), 1);
+    let done = store.get_job(&job.id).await.unwrap().unwrap();
+    assert_eq!(done.status, ArchiveJobStatus::Completed);
+    assert_eq!(done.bytes_uploaded, i64::try_from(contents.len()).unwrap());
+    assert_eq!(*target.begun.lock().unwrap(), 1);
+    assert_eq!(
+      store.archived.lock().unwrap().as_deref(),
+      Some("s3://bucket/archive/global/rec-1/recording.mp4")
+    );
+
+    let parts = target.parts.lock().unwrap();
+    let uploaded: Vec<u8> = (1..=3).flat_map(|n| parts[&n].clone()).collect();
+    assert_eq!(uploaded, contents);
+    drop(parts);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -4,8 +4,8 @@ use common::leases::{
   LeaseAcquireRequest, LeaseAcquireResponse, LeaseReleaseRequest, LeaseReleaseResponse,
   LeaseRenewRequest, LeaseRenewResponse,
 };
+use common::resilient_http::ResilientClient;
 use reqwest::Url;
-use std::time::Duration;
 use tracing::instrument;
 
 #[async_trait]
@@ -17,15 +17,12 @@ pub trait CoordinatorClient: Send + Sync {
 
 pub struct HttpCoordinatorClient {
   base: Url,
-  client: reqwest::Client,
+  client: ResilientClient,
 }
 
 impl HttpCoordinatorClient {
-  pub fn new(base: Url) -> Result<Self> {
-    let client = reqwest::Client::builder()
-      .connect_timeout(Duration::from_secs(3))
-      .timeout(Duration::from_secs(10))
-      .build()?;
+  pub async fn new(base: Url) -> Result<Self> {
+    let client = ResilientClient::builder("coordinator").build().await?;
     Ok(Self { base, client })
   }
 
@@ -41,9 +38,7 @@ impl CoordinatorClient for HttpCoordinatorClient {
     let url = self.endpoint("v1/leases/acquire")?;
     let resp = self
       .client
-      .post(url)
-      .json(request)
-      .send()
+      .send(|c| c.post(url.clone()).json(request))
       .await
       .context("coordinator acquire request failed")?;
     let resp = resp
@@ -62,9 +57,7 @@ impl CoordinatorClient for HttpCoordinatorClient {
     let url = self.endpoint("v1/leases/renew")?;
     let resp = self
       .client
-      .post(url)
-      .json(request)
-      .send()
+      .send(|c| c.post(url.clone()).json(request))
       .await
       .context("coordinator renew request failed")?;
     let resp = resp
@@ -83,9 +76,7 @@ impl CoordinatorClient for HttpCoordinatorClient {
     let url = self.endpoint("v1/leases/release")?;
     let resp = self
       .client
-      .post(url)
-      .json(request)
-      .send()
+      .send(|c| c.post(url.clone()).json(request))
       .await
       .context("coordinator release request failed")?;
     let resp = resp
@@ -1,6 +1,170 @@
+use axum::{extract::DefaultBodyLimit, middleware, routing::delete, routing::get, routing::post, routing::put, Router};
+use common::api_version::{self, Deprecation};
+use common::auth_middleware::{auth_middleware, AuthMiddlewareConfig};
+use archive::api::ArchiveApiState;
+use common::tenancy::tenancy_middleware;
+use retention::api::RetentionApiState;
+use search::api::SearchApiState;
+use std::sync::Arc;
+
 pub mod api;
+pub mod archive;
 pub mod coordinator;
+pub mod onvif;
 pub mod recording;
 pub mod retention;
 pub mod search;
 pub mod storage;
+
+/// Recorder routes scheduled for removal
+pub const DEPRECATIONS: &[Deprecation] = &[];
+
+/// Add API version discovery, the v2 compatibility shim and deprecation
+/// headers; call after the optional routers are merged in
+pub fn versioned(app: Router) -> Router {
+  api_version::versioned(app, "recorder-node", DEPRECATIONS)
+}
+
+/// Recording API of a recorder node; retention routes are added by the
+/// binary when a database is configured
+pub fn router() -> Router {
+  Router::new()
+    .route("/healthz", get(api::healthz))
+    .route("/readyz", get(api::readyz))
+    .route("/metrics", get(|| async {
+      for health in common::resilient_http::target_health().await {
+        telemetry::metrics::set_upstream_health(
+          &health.target,
+          health.circuit.as_gauge(),
+          i64::from(health.consecutive_failures),
+          i64::try_from(health.last_latency_ms).unwrap_or(i64::MAX),
+          &health.event_totals(),
+        );
+      }
+      telemetry::metrics::encode_metrics().unwrap_or_else(|e| format!("Error: {}", e))
+    }))
+    .route("/recordings", get(api::list_recordings))
+    .route("/start", post(api::start_recording))
+    .route("/stop", post(api::stop_recording))
+    .route("/thumbnail", get(api::get_thumbnail))
+    .route("/thumbnail/grid", get(api::get_thumbnail_grid))
+    .route("/v1/storage/capacity", get(api::get_capacity))
+}
+
+/// Retention policy API, authenticated and tenant-scoped
+pub fn retention_router(state: Arc<RetentionApiState>) -> Router {
+  Router::new()
+    .route("/v1/retention/policies", post(retention::api::create_policy))
+    .route("/v1/retention/policies", get(retention::api::list_policies))
+    .route("/v1/retention/policies/:policy_id", get(retention::api::get_policy))
+    .route("/v1/retention/policies/:policy_id", put(retention::api::update_policy))
+    .route("/v1/retention/policies/:policy_id", delete(retention::api::delete_policy))
+    .route("/v1/retention/policies/:policy_id/execute", post(retention::api::execute_policy))
+    .route(
+      "/v1/retention/policies/:policy_id/preview",
+      get(retention::api::get_preview).post(retention::api::preview_policy),
+    )
+    .route("/v1/retention/execute", post(retention::api::execute_all_policies))
+    .route("/v1/retention/executions", get(retention::api::list_all_executions))
+    .route("/v1/retention/executions/:execution_id", get(retention::api::get_execution))
+    .route("/v1/retention/policies/:policy_id/executions", get(retention::api::list_executions))
+    .route("/v1/retention/executions/:execution_id/actions", get(retention::api::list_actions))
+    .route("/v1/retention/storage/stats", get(retention::api::get_storage_stats))
+    .layer(middleware::from_fn(tenancy_middleware))
+    .layer(middleware::from_fn_with_state(
+      Arc::new(AuthMiddlewareConfig::from_env()),
+      auth_middleware,
+    ))
+    .with_state(state)
+}
+
+/// Cloud archive API, authenticated and tenant-scoped
+pub fn archive_router(state: Arc<ArchiveApiState>) -> Router {
+  Router::new()
+    .route("/v1/archive/policies", post(archive::api::create_policy))
+    .route("/v1/archive/policies", get(archive::api::list_policies))
+    .route("/v1/archive/policies/:policy_id", get(archive::api::get_policy))
+    .route("/v1/archive/policies/:policy_id", put(archive::api::update_policy))
+    .route("/v1/archive/policies/:policy_id", delete(archive::api::delete_policy))
+    .route("/v1/archive/policies/:policy_id/run", post(archive::api::run_policy))
+    .route("/v1/archive/jobs", get(archive::api::list_jobs))
+    .route("/v1/archive/jobs/:job_id", get(archive::api::get_job))
+    .route("/v1/archive/jobs/:job_id/retry", post(archive::api::retry_job))
+    .route("/v1/archive/recordings/:recording_id", get(archive::api::get_recording_location))
+    .layer(middleware::from_fn(tenancy_middleware))
+    .layer(middleware::from_fn_with_state(
+      Arc::new(AuthMiddlewareConfig::from_env()),
+      auth_middleware,
+    ))
+    .with_state(state)
+}
+
+/// Recording and event search API, authenticated and tenant-scoped
+pub fn search_router(state: Arc<SearchApiState>) -> Router {
+  Router::new()
+    .route("/v1/search/recordings", get(search::api::find_recordings))
+    .route("/v1/search/recordings", post(search::api::search_recordings))
+    .route(
+      "/v1/search/recordings/:recording_id/detections",
+      get(search::api::recording_detections),
+    )
+    .route(
+      "/v1/search/recordings/:recording_id/metadata",
+      get(search::api::get_recording_metadata).patch(search::api::update_recording_metadata),
+    )
+    .route("/v1/search/events", post(search::api::search_events))
+    .route("/v1/search/objects", post(search::api::search_objects))
+    .route("/v1/search/stats", get(search::api::get_search_stats))
+    .route("/v1/search/reindex", post(search::api::reindex_recordings))
+    .route("/v1/recordings/changes", get(search::api::recording_changes))
+    .layer(middleware::from_fn(tenancy_middleware))
+    .layer(middleware::from_fn_with_state(
+      Arc::new(AuthMiddlewareConfig::from_env()),
+      auth_middleware,
+    ))
+    .with_state(state)
+}
+
+/// Import of external video files, authenticated and tenant-scoped; the
+/// upload limit is `RECORDING_IMPORT_MAX_BYTES` instead of axum's default
+pub fn import_router() -> Router {
+  Router::new()
+    .route("/v1/recordings/import", post(recording::import::import_recording))
+    .layer(DefaultBodyLimit::disable())
+    .layer(middleware::from_fn(tenancy_middleware))
+    .layer(middleware::from_fn_with_state(
+      Arc::new(AuthMiddlewareConfig::from_env()),
+      auth_middleware,
+    ))
+}
+
+/// Time-lapse jobs over the recording index, authenticated and
+/// tenant-scoped
+pub fn timelapse_router(timelapser: Arc<recording::timelapse::Timelapser>) -> Router {
+  Router::new()
+    .route(
+      "/v1/timelapses",
+      get(recording::timelapse::list_timelapses).post(recording::timelapse::create_timelapse),
+    )
+    .route(
+      "/v1/timelapses/:id",
+      get(recording::timelapse::get_timelapse).delete(recording::timelapse::delete_timelapse),
+    )
+    .route("/v1/timelapses/:id/output", get(recording::timelapse::download_timelapse))
+    .layer(middleware::from_fn(tenancy_middleware))
+    .layer(middleware::from_fn_with_state(
+      Arc::new(AuthMiddlewareConfig::from_env()),
+      auth_middleware,
+    ))
+    .with_state(timelapser)
+}
+
+/// ONVIF Profile G device, search and replay services; clients authenticate
+/// with WS-Security instead of the API's bearer tokens
+pub fn onvif_router(service: Arc<onvif::OnvifService>) -> Router {
+  Router::new()
+    .route("/onvif/device_service", post(onvif::device_service))
+    .route("/onvif/search_service", post(onvif::search_service))
+    .route("/onvif/replay_service", post(onvif::replay_service))
+    .with_state(service)
+}
@@ -1,22 +1,25 @@
-use axum::{middleware, routing::get, routing::post, routing::delete, routing::put, Router};
+use axum::middleware;
+use common::nodes::{self, NodeAnnouncer, NodeKind};
 use common::state_store::StateStore;
 use common::state_store_client::StateStoreClient;
 use std::sync::Arc;
 use telemetry::{trace_http_request, TracingConfig};
 use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
 use tower::ServiceBuilder;
 use tracing::{info, warn};
 
-mod api;
-mod coordinator;
-mod recording;
-mod retention;
-mod storage;
-
-use coordinator::HttpCoordinatorClient;
-use recording::manager::RECORDING_MANAGER;
-use retention::{PostgresRetentionStore, RetentionExecutor};
-use retention::api::RetentionApiState;
+use recorder_node::archive::{self, ArchiveConfig, Archiver, PostgresArchiveStore};
+use recorder_node::archive::api::ArchiveApiState;
+use recorder_node::coordinator::HttpCoordinatorClient;
+use recorder_node::onvif::{OnvifConfig, OnvifService};
+use recorder_node::recording::manager::RECORDING_MANAGER;
+use recorder_node::recording::timelapse::Timelapser;
+use recorder_node::retention::{self, PostgresRetentionStore, RetentionExecutor};
+use recorder_node::retention::api::RetentionApiState;
+use recorder_node::search::{self, PostgresSearchStore, SearchIndexer};
+use recorder_node::search::api::SearchApiState;
+use recorder_node::storage::capacity::{self, CapacityConfig, CapacityGuard};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -33,13 +36,20 @@ async fn main() -> anyhow::Result<()> {
   }
 
   // Initialize coordinator client if configured
+  let mut registration = None;
   if let Ok(coordinator_url) = std::env::var("COORDINATOR_URL") {
     let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| "recorder-node".to_string());
     info!(coordinator_url = %coordinator_url, node_id = %node_id, "initializing coordinator client");
 
     let base = reqwest::Url::parse(&coordinator_url)?;
-    let client = Arc::new(HttpCoordinatorClient::new(base)?);
-    RECORDING_MANAGER.set_coordinator(client, node_id).await;
+    let client = Arc::new(HttpCoordinatorClient::new(base).await?);
+    RECORDING_MANAGER.set_coordinator(client, node_id.clone()).await;
+
+    // Register with the coordinator so gateways route recordings to this node
+    if let Some(announcer) = NodeAnnouncer::from_env(NodeKind::Recorder, &node_id).await? {
+      let heartbeat = announcer.clone().spawn();
+      registration = Some((announcer, heartbeat));
+    }
 
     // Initialize StateStore client if enabled
     let state_store_enabled = std::env::var("ENABLE_STATE_STORE")
@@ -61,16 +71,10 @@ async fn main() -> anyhow::Result<()> {
     info!("COORDINATOR_URL not set, running without lease management");
   }
 
-  let mut app = Router::new()
-    .route("/healthz", get(api::healthz))
-    .route("/metrics", get(|| async {
-      telemetry::metrics::encode_metrics().unwrap_or_else(|e| format!("Error: {}", e))
-    }))
-    .route("/recordings", get(api::list_recordings))
-    .route("/start", post(api::start_recording))
-    .route("/stop", post(api::stop_recording))
-    .route("/thumbnail", get(api::get_thumbnail))
-    .route("/thumbnail/grid", get(api::get_thumbnail_grid));
+  let mut app = recorder_node::router().merge(recorder_node::import_router());
+
+  // Recordings index, where recordings are locked against emergency retention
+  let mut index_pool = None;
 
   // Initialize retention system if DATABASE_URL is set
   if let Ok(database_url) = std::env::var("DATABASE_URL") {
@@ -92,7 +96,7 @@ async fn main() -> anyhow::Result<()> {
     //   .await?;
 
     // Initialize retention store and executor
-    let retention_store = Arc::new(PostgresRetentionStore::new(pool));
+    let retention_store = Arc::new(PostgresRetentionStore::new(pool.clone()));
     let retention_executor = Arc::new(RetentionExecutor::new(
       Arc::clone(&retention_store) as Arc<dyn retention::store::RetentionStore>,
       recording_storage_root,
@@ -103,30 +107,68 @@ async fn main() -> anyhow::Result<()> {
       executor: retention_executor,
     });
 
-    // Add retention routes
-    let retention_routes = Router::new()
-      .route("/v1/retention/policies", post(retention::api::create_policy))
-      .route("/v1/retention/policies", get(retention::api::list_policies))
-      .route("/v1/retention/policies/:policy_id", get(retention::api::get_policy))
-      .route("/v1/retention/policies/:policy_id", put(retention::api::update_policy))
-      .route("/v1/retention/policies/:policy_id", delete(retention::api::delete_policy))
-      .route("/v1/retention/policies/:policy_id/execute", post(retention::api::execute_policy))
-      .route("/v1/retention/execute", post(retention::api::execute_all_policies))
-      .route("/v1/retention/executions", get(retention::api::list_all_executions))
-      .route("/v1/retention/executions/:execution_id", get(retention::api::get_execution))
-      .route("/v1/retention/policies/:policy_id/executions", get(retention::api::list_executions))
-      .route("/v1/retention/executions/:execution_id/actions", get(retention::api::list_actions))
-      .route("/v1/retention/storage/stats", get(retention::api::get_storage_stats))
-      .with_state(retention_state);
-
-    app = app.merge(retention_routes);
+    app = app.merge(recorder_node::retention_router(retention_state));
     info!("retention system initialized successfully");
+
+    index_pool = Some(pool.clone());
+
+    // Recording search; frame capture indexes AI detections from here on
+    let search_store = Arc::new(PostgresSearchStore::new(pool.clone())) as Arc<dyn search::SearchStore>;
+    let search_indexer = Arc::new(SearchIndexer::new(Arc::clone(&search_store)));
+    search::indexer::install(Arc::clone(&search_indexer));
+    app = app.merge(recorder_node::search_router(Arc::new(SearchApiState {
+      store: Arc::clone(&search_store),
+      indexer: search_indexer,
+    })));
+    app = app.merge(recorder_node::timelapse_router(Arc::new(Timelapser::from_env(Arc::clone(&search_store)))));
+    tokio::spawn(search::changes::run_change_compactor(Arc::clone(&search_store)));
+
+    // ONVIF Profile G search and replay over the same index
+    if let Some(config) = OnvifConfig::from_env()? {
+      if config.credentials.is_none() {
+        warn!("ONVIF_USERNAME not set, ONVIF services accept unauthenticated requests");
+      }
+      info!(replay_base_url = %config.replay_base_url, "ONVIF Profile G services enabled");
+      app = app.merge(recorder_node::onvif_router(Arc::new(OnvifService::new(config, search_store))));
+    }
+
+    // Cloud archive, enabled by ARCHIVE_BACKEND
+    if let Some(config) = ArchiveConfig::from_env()? {
+      let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| "recorder-node".to_string());
+      let archive_store = Arc::new(PostgresArchiveStore::new(pool)) as Arc<dyn archive::ArchiveStore>;
+      let archiver = Arc::new(Archiver::new(
+        Arc::clone(&archive_store),
+        config.target().await?,
+        &config,
+        node_id,
+      ));
+      Arc::clone(&archiver).spawn(config.interval);
+
+      app = app.merge(recorder_node::archive_router(Arc::new(ArchiveApiState {
+        store: archive_store,
+        archiver,
+      })));
+      info!(backend = %config.backend, bucket = %config.bucket, "cloud archive enabled");
+    }
   } else {
-    info!("DATABASE_URL not set, retention system disabled");
+    info!("DATABASE_URL not set, retention, search and ONVIF disabled");
   }
 
+  // Keep headroom on the recordings volume and delete old footage before it fills up
+  let capacity_config = CapacityConfig::from_env();
+  info!(
+    root = %capacity_config.root.display(),
+    min_free_pct = capacity_config.min_free_pct,
+    emergency_free_pct = capacity_config.emergency_free_pct,
+    "recordings volume capacity guard enabled"
+  );
+  let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| "recorder-node".to_string());
+  let capacity_guard = Arc::new(CapacityGuard::new(capacity_config, node_id, index_pool));
+  capacity::install(Arc::clone(&capacity_guard));
+  capacity_guard.spawn();
+
   // Add HTTP tracing middleware
-  let app = app.layer(
+  let app = recorder_node::versioned(app).layer(
     ServiceBuilder::new()
       .layer(middleware::from_fn(trace_http_request))
   );
@@ -134,10 +176,59 @@ async fn main() -> anyhow::Result<()> {
   let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 8085));
   let listener = TcpListener::bind(addr).await?;
   info!(%addr, "recorder-node started");
-  axum::serve(listener, app).await?;
+  axum::serve(listener, app)
+    .with_graceful_shutdown(async move {
+      shutdown_signal().await;
+      drain(registration).await;
+    })
+    .await?;
 
   // Shutdown tracing provider
   telemetry::shutdown_tracing();
 
   Ok(())
 }
+
+async fn shutdown_signal() {
+  let ctrl_c = async {
+    let _ = tokio::signal::ctrl_c().await;
+  };
+
+  #[cfg(unix)]
+  let terminate = async {
+    use tokio::signal::unix::{SignalKind, signal};
+    if let Ok(mut sigterm) = signal(SignalKind::terminate()) {
+      let _ = sigterm.recv().await;
+    }
+  };
+
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  tokio::select! {
+      _ = ctrl_c => {},
+      _ = terminate => {},
+  }
+
+  info!("shutdown signal received");
+}
+
+/// Leave the gateways' routing, finalize active recordings and hand their
+/// leases back to the coordinator; the API keeps serving (and refusing new
+/// recordings) until this returns
+async fn drain(registration: Option<(NodeAnnouncer, JoinHandle<()>)>) {
+  if let Some((announcer, _)) = &registration {
+    if let Err(e) = announcer.drain().await {
+      warn!(error = %e, "failed to mark node as draining");
+    }
+  }
+
+  RECORDING_MANAGER.drain(nodes::shutdown_grace()).await;
+
+  if let Some((announcer, heartbeat)) = registration {
+    heartbeat.abort();
+    if let Err(e) = announcer.deregister().await {
+      warn!(error = %e, "failed to deregister node");
+    }
+  }
+}
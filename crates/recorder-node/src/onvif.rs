@@ -0,0 +1,886 @@
+//! ONVIF Profile G exposure of stored recordings
+//!
+//! ONVIF clients and NVR consoles search and replay quadrant footage without
+//! a custom integration:
+//!
+//! - `POST /onvif/device_service`: `GetSystemDateAndTime`,
+//!   `GetDeviceInformation`, `GetServices` and `GetCapabilities`, so clients
+//!   can discover the two services below
+//! - `POST /onvif/search_service`: `GetServiceCapabilities`,
+//!   `GetRecordingSummary`, `GetRecordingInformation`, `FindRecordings`,
+//!   `GetRecordingSearchResults`, `GetSearchState` and `EndSearch`
+//! - `POST /onvif/replay_service`: `GetServiceCapabilities`,
+//!   `GetReplayConfiguration` and `GetReplayUri`
+//!
+//! Recordings come from the search index: the recording token is the
+//! recording id and the source token the device id. Replay URIs point at the
+//! RTSP server playback sessions use (`RTSP_BASE_URL/recordings/{id}`).
+//! `FindRecordings` runs the whole search up front; the search session only
+//! pages the results until it is ended or its keep-alive time passes.
+//!
+//! With `ONVIF_USERNAME` and `ONVIF_PASSWORD` set, every action except the
+//! ONVIF pre-auth ones needs a WS-Security UsernameToken (PasswordDigest or
+//! PasswordText).
+
+use crate::search::store::{SearchStore, MAX_SEARCH_LIMIT};
+use anyhow::{bail, Result};
+use axum::{
+  extract::State,
+  http::{header, HeaderMap, HeaderValue, StatusCode},
+  response::{IntoResponse, Response},
+};
+use base64::Engine;
+use chrono::{DateTime, Datelike, SecondsFormat, Timelike, Utc};
+use common::search::{RecordingIndexEntry, RecordingSearchQuery};
+use sha1::{Digest, Sha1};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, error};
+
+/// Open search sessions; `FindRecordings` beyond it is refused
+pub const MAX_SEARCH_SESSIONS: usize = 32;
+
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(60);
+const MAX_KEEP_ALIVE: Duration = Duration::from_secs(600);
+const DEFAULT_MAX_RESULTS: usize = 100;
+
+/// Largest clock difference a UsernameToken `Created` may show
+const MAX_TOKEN_SKEW_SECS: i64 = 300;
+
+/// Replay session timeout advertised to clients
+const REPLAY_SESSION_TIMEOUT: &str = "PT60S";
+
+/// Actions ONVIF clients call before they have credentials
+const PRE_AUTH_ACTIONS: [&str; 4] = [
+  "GetSystemDateAndTime",
+  "GetServices",
+  "GetCapabilities",
+  "GetServiceCapabilities",
+];
+
+const DEVICE_NAMESPACE: &str = "http://www.onvif.org/ver10/device/wsdl";
+const SEARCH_NAMESPACE: &str = "http://www.onvif.org/ver10/search/wsdl";
+const REPLAY_NAMESPACE: &str = "http://www.onvif.org/ver10/replay/wsdl";
+
+#[derive(Debug, Clone)]
+pub struct OnvifConfig {
+  /// Base URL clients reach this node at, for service addresses; taken from
+  /// the request's `Host` header when unset
+  pub service_url: Option<String>,
+  /// RTSP server replay URIs point at
+  pub replay_base_url: String,
+  /// Only recordings of this tenant are visible
+  pub tenant_id: Option<String>,
+  /// UsernameToken credentials; services are open when unset
+  pub credentials: Option<(String, String)>,
+}
+
+impl OnvifConfig {
+  /// `None` unless `ONVIF_ENABLED=true`. Reads `ONVIF_SERVICE_URL`,
+  /// `RTSP_BASE_URL`, `ONVIF_TENANT_ID`, `ONVIF_USERNAME` and `ONVIF_PASSWORD`
+  pub fn from_env() -> Result<Option<Self>> {
+    let enabled = std::env::var("ONVIF_ENABLED")
+      .map(|v| v.eq_ignore_ascii_case("true"))
+      .unwrap_or(false);
+    if !enabled {
+      return Ok(None);
+    }
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+    let service_url = var("ONVIF_SERVICE_URL").map(|url| url.trim_end_matches('/').to_string());
+    if let Some(url) = &service_url {
+      common::validation::validate_uri(url, "ONVIF_SERVICE_URL")?;
+    }
+    let replay_base_url = var("RTSP_BASE_URL")
+      .unwrap_or_else(|| "rtsp://localhost:8554".to_string())
+      .trim_end_matches('/')
+      .to_string();
+    common::validation::validate_uri(&replay_base_url, "RTSP_BASE_URL")?;
+    let tenant_id = var("ONVIF_TENANT_ID");
+    if let Some(tenant_id) = &tenant_id {
+      common::validation::parse_uuid(tenant_id, "ONVIF_TENANT_ID")?;
+    }
+    let credentials = match (var("ONVIF_USERNAME"), var("ONVIF_PASSWORD")) {
+      (Some(username), Some(password)) => Some((username, password)),
+      (None, None) => None,
+      _ => bail!("ONVIF_USERNAME and ONVIF_PASSWORD must be set together"),
+    };
+
+    Ok(Some(Self {
+      service_url,
+      replay_base_url,
+      tenant_id,
+      credentials,
+    }))
+  }
+}
+
+/// Recordings matched by `FindRecordings` and not yet fetched
+struct SearchSession {
+  results: VecDeque<RecordingIndexEntry>,
+  keep_alive: Duration,
+  expires_at: Instant,
+}
+
+/// Open search sessions, by search token
+#[derive(Default)]
+pub struct SearchSessions {
+  sessions: Mutex<HashMap<String, SearchSession>>,
+}
+
+impl SearchSessions {
+  /// Token of a new session, or `None` when too many are open
+  pub async fn create(&self, results: Vec<RecordingIndexEntry>, keep_alive: Duration) -> Option<String> {
+    let now = Instant::now();
+    let mut sessions = self.sessions.lock().await;
+    sessions.retain(|_, s| s.expires_at > now);
+    if sessions.len() >= MAX_SEARCH_SESSIONS {
+      return None;
+    }
+    let token = uuid::Uuid::new_v4().to_string();
+    sessions.insert(
+      token.clone(),
+      SearchSession {
+        results: results.into(),
+        keep_alive,
+        expires_at: now + keep_alive,
+      },
+    );
+    Some(token)
+  }
+
+  /// Up to `max` further results and whether the search is completed
+  pub async fn next_page(&self, token: &str, max: usize) -> Option<(Vec<RecordingIndexEntry>, bool)> {
+    let now = Instant::now();
+    let mut sessions = self.sessions.lock().await;
+    let session = sessions.get_mut(token).filter(|s| s.expires_at > now)?;
+    session.expires_at = now + session.keep_alive;
+    let count = max.min(session.results.len());
+    let page: Vec<RecordingIndexEntry> = session.results.drain(..count).collect();
+    Some((page, session.results.is_empty()))
+  }
+
+  /// Whether the search is completed
+  pub async fn completed(&self, token: &str) -> Option<bool> {
+    let now = Instant::now();
+    let mut sessions = self.sessions.lock().await;
+    let session = sessions.get_mut(token).filter(|s| s.expires_at > now)?;
+    session.expires_at = now + session.keep_alive;
+    Some(session.results.is_empty())
+  }
+
+  pub async fn end(&self, token: &str) -> bool {
+    let now = Instant::now();
+    let mut sessions = self.sessions.lock().await;
+    sessions.remove(token).is_some_and(|s| s.expires_at > now)
+  }
+}
+
+/// `tt:SearchScope` of a `FindRecordings` request
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchScope {
+  /// Unix seconds
+  pub earliest: Option<i64>,
+  pub latest: Option<i64>,
+  /// Device ids
+  pub sources: Vec<String>,
+  /// Recording ids
+  pub recordings: Vec<String>,
+}
+
+impl SearchScope {
+  pub fn parse(body: &str) -> Self {
+    let scope = element_section(body, "Scope").unwrap_or_default();
+    let time = |name: &str| {
+      element_text(scope, name)
+        .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+        .map(|t| t.timestamp())
+    };
+    Self {
+      earliest: time("EarliestTime"),
+      latest: time("LatestTime"),
+      sources: element_texts(scope, "Token").into_iter().map(str::to_string).collect(),
+      recordings: element_texts(scope, "IncludedRecordings")
+        .into_iter()
+        .map(str::to_string)
+        .collect(),
+    }
+  }
+
+  /// Whether a recording overlaps the scope's time window
+  fn overlaps(&self, entry: &RecordingIndexEntry) -> bool {
+    self.latest.is_none_or(|latest| entry.started_at < latest)
+      && self
+        .earliest
+        .is_none_or(|earliest| entry.stopped_at.is_none_or(|stopped| stopped >= earliest))
+  }
+}
+
+/// Search and replay services over the recording search index
+pub struct OnvifService {
+  config: OnvifConfig,
+  store: Arc<dyn SearchStore>,
+  searches: SearchSessions,
+}
+
+impl OnvifService {
+  pub fn new(config: OnvifConfig, store: Arc<dyn SearchStore>) -> Self {
+    Self {
+      config,
+      store,
+      searches: SearchSessions::default(),
+    }
+  }
+
+  pub fn config(&self) -> &OnvifConfig {
+    &self.config
+  }
+
+  fn authorized(&self, body: &str) -> bool {
+    let Some((username, password)) = &self.config.credentials else {
+      return true;
+    };
+    if PRE_AUTH_ACTIONS.iter().any(|action| has_element(body, action)) {
+      return true;
+    }
+    element_section(body, "UsernameToken")
+      .is_some_and(|token| check_username_token(token, username, password, Utc::now()))
+  }
+
+  fn service_url(&self, headers: &HeaderMap) -> String {
+    if let Some(url) = &self.config.service_url {
+      return url.clone();
+    }
+    let host = headers
+      .get(header::HOST)
+      .and_then(|h| h.to_str().ok())
+      .unwrap_or("localhost:8085");
+    format!("http://{}", host)
+  }
+
+  fn query(&self, device_id: Option<String>, limit: usize) -> RecordingSearchQuery {
+    RecordingSearchQuery {
+      query: None,
+      tenant_id: self.config.tenant_id.clone(),
+      device_id,
+      zone: None,
+      state: None,
+      started_after: None,
+      started_before: None,
+      stopped_after: None,
+      stopped_before: None,
+      min_duration_secs: None,
+      max_duration_secs: None,
+      tags: None,
+      labels: None,
+      classes: None,
+      detected_after: None,
+      detected_before: None,
+      min_confidence: None,
+      offset: 0,
+      limit: i32::try_from(limit).unwrap_or(MAX_SEARCH_LIMIT),
+      sort_by: "started_at".to_string(),
+      sort_order: "desc".to_string(),
+    }
+  }
+
+  /// Index entry of a recording visible to ONVIF clients
+  async fn recording(&self, recording_id: &str) -> Result<Option<RecordingIndexEntry>> {
+    if common::validation::validate_id(recording_id, "RecordingToken").is_err() {
+      return Ok(None);
+    }
+    let entry = self.store.recording_entry(recording_id).await?;
+    Ok(entry.filter(|e| self.config.tenant_id.is_none() || e.tenant_id == self.config.tenant_id))
+  }
+
+  /// Recordings in `scope`, newest first and at most `max_matches`
+  async fn find(&self, scope: &SearchScope, max_matches: usize) -> Result<Vec<RecordingIndexEntry>> {
+    let mut found = Vec::new();
+    if !scope.recordings.is_empty() {
+      for recording_id in scope.recordings.iter().take(max_matches) {
+        if let Some(entry) = self.recording(recording_id).await? {
+          let source_matches = scope.sources.is_empty()
+            || entry.device_id.as_ref().is_some_and(|d| scope.sources.contains(d));
+          if source_matches && scope.overlaps(&entry) {
+            found.push(entry);
+          }
+        }
+      }
+    } else {
+      let devices: Vec<Option<String>> = if scope.sources.is_empty() {
+        vec![None]
+      } else {
+        scope.sources.iter().map(|s| Some(s.clone())).collect()
+      };
+      for device_id in devices {
+        let mut query = self.query(device_id, max_matches);
+        // A recording overlaps the window when it starts before its end and
+        // stops after its start
+        query.started_before = scope.latest;
+        query.stopped_after = scope.earliest;
+        found.extend(self.store.search_recordings(&query).await?.recordings);
+      }
+    }
+    found.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.recording_id.cmp(&b.recording_id)));
+    found.truncate(max_matches);
+    Ok(found)
+  }
+
+  /// Number of recordings and the time span they cover
+  async fn summary(&self) -> Result<(i64, Option<i64>, Option<i64>)> {
+    let mut oldest = self.query(None, 1);
+    oldest.sort_order = "asc".to_string();
+    let oldest = self.store.search_recordings(&oldest).await?;
+    let Some(first) = oldest.recordings.first() else {
+      return Ok((0, None, None));
+    };
+
+    let mut active = self.query(None, 1);
+    active.state = Some("Recording".to_string());
+    let data_until = if self.store.search_recordings(&active).await?.total > 0 {
+      Some(Utc::now().timestamp())
+    } else {
+      let mut latest = self.query(None, 1);
+      latest.sort_by = "stopped_at".to_string();
+      let latest = self.store.search_recordings(&latest).await?;
+      latest.recordings.first().and_then(|r| r.stopped_at)
+    };
+    Ok((oldest.total, Some(first.started_at), data_until))
+  }
+}
+
+/// Verify a `wsse:UsernameToken`: PasswordDigest is
+/// `Base64(SHA1(nonce + created + password))` and must be created within
+/// [`MAX_TOKEN_SKEW_SECS`] of `now`; PasswordText is compared as is
+pub fn check_username_token(token: &str, username: &str, password: &str, now: DateTime<Utc>) -> bool {
+  if element_text(token, "Username") != Some(username) {
+    return false;
+  }
+  let Some(given) = element_text(token, "Password") else {
+    return false;
+  };
+  if !token.contains("#PasswordDigest") {
+    return constant_time_eq(given.as_bytes(), password.as_bytes());
+  }
+
+  let (Some(nonce), Some(created)) = (element_text(token, "Nonce"), element_text(token, "Created")) else {
+    return false;
+  };
+  let fresh = DateTime::parse_from_rfc3339(created)
+    .is_ok_and(|t| (now.timestamp() - t.timestamp()).abs() <= MAX_TOKEN_SKEW_SECS);
+  let Ok(nonce) = base64::engine::general_purpose::STANDARD.decode(nonce) else {
+    return false;
+  };
+  let mut hasher = Sha1::new();
+  hasher.update(&nonce);
+  hasher.update(created.as_bytes());
+  hasher.update(password.as_bytes());
+  let expected = base64::engine::general_purpose::STANDARD.encode(hasher.finalize());
+  fresh && constant_time_eq(given.as_bytes(), expected.as_bytes())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn utc(secs: i64) -> String {
+  DateTime::<Utc>::from_timestamp(secs, 0)
+    .unwrap_or_else(Utc::now)
+    .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn escape(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&apos;")
+}
+
+/// ONVIF status of an indexed recording state
+fn recording_status(state: &str) -> &'static str {
+  match state {
+    "Pending" | "Starting" => "Initiated",
+    "Recording" => "Recording",
+    "Paused" | "Stopping" | "Stopped" | "Error" => "Stopped",
+    _ => "Unknown",
+  }
+}
+
+/// `tt:RecordingInformation` content of an indexed recording, wrapped in
+/// `element`
+pub fn recording_information(entry: &RecordingIndexEntry, element: &str) -> String {
+  let source_id = entry.device_id.as_deref().unwrap_or(&entry.recording_id);
+  let name = entry.device_name.as_deref().unwrap_or(source_id);
+  let data_from = utc(entry.started_at);
+  let data_to = entry.stopped_at.map(utc).unwrap_or_else(|| utc(Utc::now().timestamp()));
+
+  let mut tracks = format!(
+    "<tt:Track><tt:TrackToken>VIDEO001</tt:TrackToken><tt:TrackType>Video</tt:TrackType><tt:Description>{}</tt:Description><tt:DataFrom>{data_from}</tt:DataFrom><tt:DataTo>{data_to}</tt:DataTo></tt:Track>",
+    escape(entry.video_codec.as_deref().unwrap_or_default()),
+  );
+  if let Some(audio_codec) = &entry.audio_codec {
+    tracks.push_str(&format!(
+      "<tt:Track><tt:TrackToken>AUDIO001</tt:TrackToken><tt:TrackType>Audio</tt:TrackType><tt:Description>{}</tt:Description><tt:DataFrom>{data_from}</tt:DataFrom><tt:DataTo>{data_to}</tt:DataTo></tt:Track>",
+      escape(audio_codec),
+    ));
+  }
+
+  format!(
+    "<{element}><tt:RecordingToken>{}</tt:RecordingToken><tt:Source><tt:SourceId>{}</tt:SourceId><tt:Name>{}</tt:Name><tt:Location>{}</tt:Location><tt:Description>{}</tt:Description><tt:Address>{}</tt:Address></tt:Source><tt:EarliestRecording>{data_from}</tt:EarliestRecording><tt:LatestRecording>{data_to}</tt:LatestRecording><tt:Content>{}</tt:Content>{tracks}<tt:RecordingStatus>{}</tt:RecordingStatus></{element}>",
+    escape(&entry.recording_id),
+    escape(source_id),
+    escape(name),
+    escape(entry.location.as_deref().unwrap_or_default()),
+    escape(entry.zone.as_deref().unwrap_or_default()),
+    escape(source_id),
+    escape(name),
+    recording_status(&entry.state),
+  )
+}
+
+fn soap_envelope(body: &str) -> String {
+  format!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope"
+            xmlns:tt="http://www.onvif.org/ver10/schema"
+            xmlns:tds="{DEVICE_NAMESPACE}"
+            xmlns:tse="{SEARCH_NAMESPACE}"
+            xmlns:trp="{REPLAY_NAMESPACE}"
+            xmlns:ter="http://www.onvif.org/ver10/error">
+  <s:Body>
+    {}
+  </s:Body>
+</s:Envelope>"#,
+    body
+  )
+}
+
+fn soap_response(status: StatusCode, body: &str) -> Response {
+  let mut response = (status, soap_envelope(body)).into_response();
+  response.headers_mut().insert(
+    header::CONTENT_TYPE,
+    HeaderValue::from_static("application/soap+xml; charset=utf-8"),
+  );
+  response
+}
+
+fn soap_fault(status: StatusCode, reason: &str) -> Response {
+  soap_response(
+    status,
+    &format!(
+      r#"<s:Fault><s:Code><s:Value>{}</s:Value></s:Code><s:Reason><s:Text xml:lang="en">{}</s:Text></s:Reason></s:Fault>"#,
+      if status.is_server_error() { "s:Receiver" } else { "s:Sender" },
+      escape(reason),
+    ),
+  )
+}
+
+fn not_authorized() -> Response {
+  soap_response(
+    StatusCode::BAD_REQUEST,
+    r#"<s:Fault><s:Code><s:Value>s:Sender</s:Value><s:Subcode><s:Value>ter:NotAuthorized</s:Value></s:Subcode></s:Code><s:Reason><s:Text xml:lang="en">sender not authorized</s:Text></s:Reason></s:Fault>"#,
+  )
+}
+
+fn internal_error(action: &str, e: anyhow::Error) -> Response {
+  error!(action, error = %e, "ONVIF request failed");
+  soap_fault(StatusCode::INTERNAL_SERVER_ERROR, "recording index unavailable")
+}
+
+/// Texts of all elements with the given local name, any prefix
+fn element_texts<'a>(xml: &'a str, local_name: &str) -> Vec<&'a str> {
+  let mut texts = Vec::new();
+  let mut rest = xml;
+  while let Some(start) = rest.find('<') {
+    rest = &rest[start + 1..];
+    let Some(end) = rest.find('>') else {
+      break;
+    };
+    let tag = &rest[..end];
+    let name = tag.split_whitespace().next().unwrap_or_default();
+    let name = name.rsplit(':').next().unwrap_or(name);
+    rest = &rest[end + 1..];
+    if name == local_name && !tag.starts_with('/') && !tag.ends_with('/') {
+      if let Some(close) = rest.find('<') {
+        let text = rest[..close].trim();
+        if !text.is_empty() {
+          texts.push(text);
+        }
+      }
+    }
+  }
+  texts
+}
+
+/// Text of the first element with the given local name, any prefix
+fn element_text<'a>(xml: &'a str, local_name: &str) -> Option<&'a str> {
+  element_texts(xml, local_name).into_iter().next()
+}
+
+/// Inner XML of the first element with the given local name, any prefix
+fn element_section<'a>(xml: &'a str, local_name: &str) -> Option<&'a str> {
+  let mut offset = 0;
+  while let Some(start) = xml[offset..].find('<') {
+    let open = offset + start + 1;
+    let end = open + xml[open..].find('>')?;
+    let tag = &xml[open..end];
+    let qualified = tag.split_whitespace().next().unwrap_or_default();
+    let name = qualified.rsplit(':').next().unwrap_or(qualified);
+    if name == local_name && !tag.starts_with('/') && !tag.ends_with('/') {
+      let content = &xml[end + 1..];
+      let close = content.find(&format!("</{qualified}>"))?;
+      return Some(&content[..close]);
+    }
+    offset = end + 1;
+  }
+  None
+}
+
+fn has_element(xml: &str, local_name: &str) -> bool {
+  xml.contains(&format!(":{local_name}")) || xml.contains(&format!("<{local_name}"))
+}
+
+/// Parse an `xs:duration` such as `PT10S` or `PT1M30S`; days and larger units
+/// are not used by ONVIF clients for these timeouts
+pub fn parse_duration(value: &str) -> Option<Duration> {
+  let rest = value.trim().strip_prefix("PT")?;
+  let mut secs = 0.0;
+  let mut number = String::new();
+  for c in rest.chars() {
+    match c {
+      '0'..='9' | '.' => number.push(c),
+      'H' | 'M' | 'S' => {
+        let n: f64 = number.parse().ok()?;
+        number.clear();
+        secs += n * match c {
+          'H' => 3600.0,
+          'M' => 60.0,
+          _ => 1.0,
+        };
+      }
+      _ => return None,
+    }
+  }
+  if !number.is_empty() {
+    return None;
+  }
+  Some(Duration::from_secs_f64(secs))
+}
+
+fn count(body: &str, element: &str, default: usize) -> usize {
+  element_text(body, element)
+    .and_then(|v| v.parse::<usize>().ok())
+    .unwrap_or(default)
+    .clamp(1, MAX_SEARCH_LIMIT as usize)
+}
+
+/// `POST /onvif/device_service`
+pub async fn device_service(
+  State(onvif): State<Arc<OnvifService>>,
+  headers: HeaderMap,
+  body: String,
+) -> Response {
+  if !onvif.authorized(&body) {
+    return not_authorized();
+  }
+  let base = onvif.service_url(&headers);
+  if has_element(&body, "GetSystemDateAndTime") {
+    let now = Utc::now();
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<tds:GetSystemDateAndTimeResponse><tds:SystemDateAndTime><tt:DateTimeType>NTP</tt:DateTimeType><tt:DaylightSavings>false</tt:DaylightSavings><tt:TimeZone><tt:TZ>UTC0</tt:TZ></tt:TimeZone><tt:UTCDateTime><tt:Time><tt:Hour>{}</tt:Hour><tt:Minute>{}</tt:Minute><tt:Second>{}</tt:Second></tt:Time><tt:Date><tt:Year>{}</tt:Year><tt:Month>{}</tt:Month><tt:Day>{}</tt:Day></tt:Date></tt:UTCDateTime></tds:SystemDateAndTime></tds:GetSystemDateAndTimeResponse>",
+        now.hour(),
+        now.minute(),
+        now.second(),
+        now.year(),
+        now.month(),
+        now.day(),
+      ),
+    );
+  }
+  if has_element(&body, "GetDeviceInformation") {
+    let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| "recorder-node".to_string());
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<tds:GetDeviceInformationResponse><tds:Manufacturer>Quadrant VMS</tds:Manufacturer><tds:Model>recorder-node</tds:Model><tds:FirmwareVersion>{}</tds:FirmwareVersion><tds:SerialNumber>{}</tds:SerialNumber><tds:HardwareId>recorder-node</tds:HardwareId></tds:GetDeviceInformationResponse>",
+        env!("CARGO_PKG_VERSION"),
+        escape(&node_id),
+      ),
+    );
+  }
+  if has_element(&body, "GetServices") {
+    let services: String = [
+      (DEVICE_NAMESPACE, "device_service"),
+      (SEARCH_NAMESPACE, "search_service"),
+      (REPLAY_NAMESPACE, "replay_service"),
+    ]
+    .iter()
+    .map(|(namespace, path)| {
+      format!(
+        "<tds:Service><tds:Namespace>{namespace}</tds:Namespace><tds:XAddr>{}/onvif/{path}</tds:XAddr><tds:Version><tt:Major>2</tt:Major><tt:Minor>40</tt:Minor></tds:Version></tds:Service>",
+        escape(&base),
+      )
+    })
+    .collect();
+    return soap_response(
+      StatusCode::OK,
+      &format!("<tds:GetServicesResponse>{services}</tds:GetServicesResponse>"),
+    );
+  }
+  if has_element(&body, "GetCapabilities") {
+    let base = escape(&base);
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<tds:GetCapabilitiesResponse><tds:Capabilities><tt:Device><tt:XAddr>{base}/onvif/device_service</tt:XAddr></tt:Device><tt:Extension><tt:Search><tt:XAddr>{base}/onvif/search_service</tt:XAddr><tt:MetadataSearch>false</tt:MetadataSearch></tt:Search><tt:Replay><tt:XAddr>{base}/onvif/replay_service</tt:XAddr></tt:Replay></tt:Extension></tds:Capabilities></tds:GetCapabilitiesResponse>"
+      ),
+    );
+  }
+  soap_fault(StatusCode::BAD_REQUEST, "unsupported device service action")
+}
+
+/// `POST /onvif/search_service`
+pub async fn search_service(State(onvif): State<Arc<OnvifService>>, body: String) -> Response {
+  if !onvif.authorized(&body) {
+    return not_authorized();
+  }
+  if has_element(&body, "GetServiceCapabilities") {
+    return soap_response(
+      StatusCode::OK,
+      r#"<tse:GetServiceCapabilitiesResponse><tse:Capabilities MetadataSearch="false" GeneralStartEvents="false"/></tse:GetServiceCapabilitiesResponse>"#,
+    );
+  }
+  if has_element(&body, "GetRecordingSummary") {
+    let (total, data_from, data_until) = match onvif.summary().await {
+      Ok(summary) => summary,
+      Err(e) => return internal_error("GetRecordingSummary", e),
+    };
+    let now = Utc::now().timestamp();
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<tse:GetRecordingSummaryResponse><tse:Summary><tt:DataFrom>{}</tt:DataFrom><tt:DataUntil>{}</tt:DataUntil><tt:NumberRecordings>{total}</tt:NumberRecordings></tse:Summary></tse:GetRecordingSummaryResponse>",
+        utc(data_from.unwrap_or(now)),
+        utc(data_until.unwrap_or(now)),
+      ),
+    );
+  }
+  if has_element(&body, "GetRecordingInformation") {
+    let token = element_text(&body, "RecordingToken").unwrap_or_default();
+    return match onvif.recording(token).await {
+      Ok(Some(entry)) => soap_response(
+        StatusCode::OK,
+        &format!(
+          "<tse:GetRecordingInformationResponse>{}</tse:GetRecordingInformationResponse>",
+          recording_information(&entry, "tse:RecordingInformation"),
+        ),
+      ),
+      Ok(None) => soap_fault(StatusCode::NOT_FOUND, "unknown recording token"),
+      Err(e) => internal_error("GetRecordingInformation", e),
+    };
+  }
+  if has_element(&body, "FindRecordings") {
+    let scope = SearchScope::parse(&body);
+    let max_matches = count(&body, "MaxMatches", MAX_SEARCH_LIMIT as usize);
+    let keep_alive = element_text(&body, "KeepAliveTime")
+      .and_then(parse_duration)
+      .unwrap_or(DEFAULT_KEEP_ALIVE)
+      .min(MAX_KEEP_ALIVE);
+    let results = match onvif.find(&scope, max_matches).await {
+      Ok(results) => results,
+      Err(e) => return internal_error("FindRecordings", e),
+    };
+    debug!(matches = results.len(), "ONVIF recording search");
+    let Some(token) = onvif.searches.create(results, keep_alive).await else {
+      return soap_fault(StatusCode::SERVICE_UNAVAILABLE, "too many open searches");
+    };
+    return soap_response(
+      StatusCode::OK,
+      &format!("<tse:FindRecordingsResponse><tse:SearchToken>{token}</tse:SearchToken></tse:FindRecordingsResponse>"),
+    );
+  }
+  let token = element_text(&body, "SearchToken").unwrap_or_default();
+  if has_element(&body, "GetRecordingSearchResults") {
+    // Results are complete when the search starts, so MinResults and
+    // WaitTime never have to be waited for
+    let max_results = count(&body, "MaxResults", DEFAULT_MAX_RESULTS);
+    let Some((page, completed)) = onvif.searches.next_page(token, max_results).await else {
+      return soap_fault(StatusCode::NOT_FOUND, "unknown or expired search token");
+    };
+    let results: String = page
+      .iter()
+      .map(|entry| recording_information(entry, "tt:RecordingInformation"))
+      .collect();
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<tse:GetRecordingSearchResultsResponse><tse:ResultList><tt:SearchState>{}</tt:SearchState>{results}</tse:ResultList></tse:GetRecordingSearchResultsResponse>",
+        if completed { "Completed" } else { "Searching" },
+      ),
+    );
+  }
+  if has_element(&body, "GetSearchState") {
+    let Some(completed) = onvif.searches.completed(token).await else {
+      return soap_fault(StatusCode::NOT_FOUND, "unknown or expired search token");
+    };
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<tse:GetSearchStateResponse><tse:State>{}</tse:State></tse:GetSearchStateResponse>",
+        if completed { "Completed" } else { "Searching" },
+      ),
+    );
+  }
+  if has_element(&body, "EndSearch") {
+    if !onvif.searches.end(token).await {
+      return soap_fault(StatusCode::NOT_FOUND, "unknown or expired search token");
+    }
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<tse:EndSearchResponse><tse:Endpoint>{}</tse:Endpoint></tse:EndSearchResponse>",
+        utc(Utc::now().timestamp()),
+      ),
+    );
+  }
+  soap_fault(StatusCode::BAD_REQUEST, "unsupported search service action")
+}
+
+/// `POST /onvif/replay_service`
+pub async fn replay_service(State(onvif): State<Arc<OnvifService>>, body: String) -> Response {
+  if !onvif.authorized(&body) {
+    return not_authorized();
+  }
+  if has_element(&body, "GetServiceCapabilities") {
+    return soap_response(
+      StatusCode::OK,
+      r#"<trp:GetServiceCapabilitiesResponse><trp:Capabilities ReversePlayback="false" SessionTimeoutRange="60 60" RTP_RTSP_TCP="true"/></trp:GetServiceCapabilitiesResponse>"#,
+    );
+  }
+  if has_element(&body, "GetReplayConfiguration") {
+    return soap_response(
+      StatusCode::OK,
+      &format!(
+        "<trp:GetReplayConfigurationResponse><trp:Configuration><tt:SessionTimeout>{REPLAY_SESSION_TIMEOUT}</tt:SessionTimeout></trp:Configuration></trp:GetReplayConfigurationResponse>"
+      ),
+    );
+  }
+  if has_element(&body, "GetReplayUri") {
+    let token = element_text(&body, "RecordingToken").unwrap_or_default();
+    return match onvif.recording(token).await {
+      Ok(Some(entry)) => soap_response(
+        StatusCode::OK,
+        &format!(
+          "<trp:GetReplayUriResponse><trp:Uri>{}/recordings/{}</trp:Uri></trp:GetReplayUriResponse>",
+          escape(&onvif.config.replay_base_url),
+          escape(&entry.recording_id),
+        ),
+      ),
+      Ok(None) => soap_fault(StatusCode::NOT_FOUND, "unknown recording token"),
+      Err(e) => internal_error("GetReplayUri", e),
+    };
+  }
+  soap_fault(StatusCode::BAD_REQUEST, "unsupported replay service action")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(recording_id: &str, started_at: i64, stopped_at: Option<i64>) -> RecordingIndexEntry {
+    serde_json::from_value(serde_json::json!({
+      "id": uuid::Uuid::new_v4().to_string(),
+      "recording_id": recording_id,
+      "tenant_id": null,
+      "device_id": "cam-1",
+      "device_name": "Lobby <east>",
+      "zone": "lobby",
+      "location": null,
+      "started_at": started_at,
+      "stopped_at": stopped_at,
+      "duration_secs": null,
+      "resolution": null,
+      "video_codec": "h264",
+      "audio_codec": null,
+      "file_size_bytes": null,
+      "storage_path": null,
+      "state": if stopped_at.is_some() { "Stopped" } else { "Recording" },
+      "indexed_at": 0,
+      "updated_at": 0,
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn parses_find_recordings_scope() {
+    let body = r#"<s:Body><tse:FindRecordings><tse:Scope><tt:EarliestTime>2024-06-12T10:00:00Z</tt:EarliestTime><tt:IncludedSources><tt:Token>cam-1</tt:Token></tt:IncludedSources><tt:IncludedSources><tt:Token>cam-2</tt:Token></tt:IncludedSources><tt:IncludedRecordings>rec-1</tt:IncludedRecordings></tse:Scope><tse:MaxMatches>10</tse:MaxMatches><tse:KeepAliveTime>PT30S</tse:KeepAliveTime></tse:FindRecordings></s:Body>"#;
+    let scope = SearchScope::parse(body);
+    assert_eq!(
+      scope,
+      SearchScope {
+        earliest: Some(1_718_186_400),
+        latest: None,
+        sources: vec!["cam-1".to_string(), "cam-2".to_string()],
+        recordings: vec!["rec-1".to_string()],
+      }
+    );
+    assert_eq!(count(body, "MaxMatches", 500), 10);
+    assert_eq!(element_text(body, "KeepAliveTime").and_then(parse_duration), Some(Duration::from_secs(30)));
+
+    // Overlap with the window: ongoing recordings always reach past its start
+    assert!(scope.overlaps(&entry("a", 1_718_100_000, None)));
+    assert!(!scope.overlaps(&entry("b", 1_718_100_000, Some(1_718_100_600))));
+  }
+
+  #[test]
+  fn verifies_username_tokens() {
+    let now = DateTime::parse_from_rfc3339("2010-09-16T07:52:00Z").unwrap().with_timezone(&Utc);
+    let token = r#"<wsse:Username>user</wsse:Username><wsse:Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest">tuOSpGlFlIXsozq4HFNeeGeFLEI=</wsse:Password><wsse:Nonce>LKqI6G/AikKCQrN0zqZFlg==</wsse:Nonce><wsu:Created>2010-09-16T07:50:45Z</wsu:Created>"#;
+    assert!(check_username_token(token, "user", "userpassword", now));
+    assert!(!check_username_token(token, "user", "wrong", now));
+    assert!(!check_username_token(token, "admin", "userpassword", now));
+
+    // Replayed long after it was created
+    let later = now + chrono::Duration::hours(1);
+    assert!(!check_username_token(token, "user", "userpassword", later));
+
+    let text = r#"<wsse:Username>user</wsse:Username><wsse:Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordText">password</wsse:Password>"#;
+    assert!(check_username_token(text, "user", "password", now));
+  }
+
+  #[test]
+  fn recording_information_follows_schema_order() {
+    let xml = recording_information(&entry("rec-1", 1_718_186_400, Some(1_718_190_000)), "tt:RecordingInformation");
+    assert!(xml.starts_with("<tt:RecordingInformation><tt:RecordingToken>rec-1</tt:RecordingToken><tt:Source><tt:SourceId>cam-1</tt:SourceId><tt:Name>Lobby &lt;east&gt;</tt:Name>"));
+    assert!(xml.contains("<tt:EarliestRecording>2024-06-12T10:00:00Z</tt:EarliestRecording><tt:LatestRecording>2024-06-12T11:00:00Z</tt:LatestRecording>"));
+    assert!(xml.contains("<tt:TrackType>Video</tt:TrackType><tt:Description>h264</tt:Description>"));
+    assert!(!xml.contains("AUDIO001"));
+    assert!(xml.ends_with("<tt:RecordingStatus>Stopped</tt:RecordingStatus></tt:RecordingInformation>"));
+  }
+
+  #[tokio::test]
+  async fn search_sessions_page_results() {
+    let sessions = SearchSessions::default();
+    let results = (0..5).map(|i| entry(&format!("rec-{}", i), i, None)).collect();
+    let token = sessions.create(results, DEFAULT_KEEP_ALIVE).await.unwrap();
+
+    let (page, completed) = sessions.next_page(&token, 3).await.unwrap();
+    assert_eq!(page.len(), 3);
+    assert!(!completed);
+    assert_eq!(sessions.completed(&token).await, Some(false));
+
+    let (page, completed) = sessions.next_page(&token, 3).await.unwrap();
+    assert_eq!(page.len(), 2);
+    assert!(completed);
+
+    assert!(sessions.end(&token).await);
+    assert!(sessions.next_page(&token, 3).await.is_none());
+    assert!(!sessions.end(&token).await);
+  }
+}
@@ -1,13 +1,15 @@
 //! Frame capture and AI integration for active recordings
 //!
 //! This module handles periodic frame extraction from active recordings
-//! and submits them to the AI service for processing.
+//! and submits them to the AI service for processing. Detections are
+//! written to the search index so recordings can be found by content.
 
 use anyhow::{Context, Result};
 use base64::Engine;
+use common::ai_tasks::{AiResult, VideoFrame};
 use common::frame_extractor;
+use common::recordings::AutoTagRule;
 use reqwest::Client;
-use serde_json::json;
 use std::time::Duration;
 use tokio::time;
 use tokio_util::sync::CancellationToken;
@@ -28,6 +30,8 @@ pub struct FrameCaptureConfig {
     pub frame_height: u32,
     /// JPEG quality (2-31, lower is better)
     pub jpeg_quality: u32,
+    /// Tags added to the recording when a frame matches
+    pub auto_tags: Vec<AutoTagRule>,
 }
 
 impl Default for FrameCaptureConfig {
@@ -39,6 +43,7 @@ impl Default for FrameCaptureConfig {
             frame_width: 640,
             frame_height: 0, // auto-scale
             jpeg_quality: 5,
+            auto_tags: Vec::new(),
         }
     }
 }
@@ -56,7 +61,7 @@ impl Default for FrameCaptureConfig {
 pub fn start_frame_capture(
     recording_id: String,
     source_uri: String,
-    config: FrameCaptureConfig,
+    mut config: FrameCaptureConfig,
     cancel_token: CancellationToken,
 ) {
     tokio::spawn(async move {
@@ -100,21 +105,39 @@ pub fn start_frame_capture(
                             );
 
                             // Submit frame to AI service
-                            if let Err(e) = submit_frame_to_ai(
+                            match submit_frame_to_ai(
                                 &client,
-                                &config.ai_service_url,
-                                &config.ai_task_id,
+                                &mut config,
+                                &recording_id,
                                 frame_seq,
                                 jpeg_data,
                             )
                             .await
                             {
-                                warn!(
-                                    recording_id = %recording_id,
-                                    frame_seq = frame_seq,
-                                    error = %e,
-                                    "failed to submit frame to AI service"
-                                );
+                                Ok(result) => {
+                                    crate::search::indexer::index_detections(
+                                        &recording_id,
+                                        &result,
+                                        (config.frame_width, config.frame_height),
+                                    )
+                                    .await;
+                                    if !config.auto_tags.is_empty() {
+                                        crate::search::indexer::apply_auto_tags(
+                                            &recording_id,
+                                            &result,
+                                            &config.auto_tags,
+                                        )
+                                        .await;
+                                    }
+                                }
+                                Err(e) => {
+                                    warn!(
+                                        recording_id = %recording_id,
+                                        frame_seq = frame_seq,
+                                        error = %e,
+                                        "failed to submit frame to AI service"
+                                    );
+                                }
                             }
                         }
                         Err(e) => {
@@ -134,30 +157,37 @@ pub fn start_frame_capture(
     });
 }
 
-/// Submit a frame to the AI service
+/// Submit a frame to the AI service and return its detections. A sharded
+/// AI service redirects frames to the node holding the task; later frames
+/// are sent straight to that node.
 async fn submit_frame_to_ai(
     client: &Client,
-    ai_service_url: &str,
-    task_id: &str,
+    config: &mut FrameCaptureConfig,
+    recording_id: &str,
     frame_seq: u64,
     jpeg_data: Vec<u8>,
-) -> Result<()> {
-    let base64_data = base64::engine::general_purpose::STANDARD.encode(&jpeg_data);
-
-    let url = format!("{}/v1/tasks/{}/frames", ai_service_url, task_id);
-
-    let payload = json!({
-        "frame_data": base64_data,
-        "sequence_number": frame_seq,
-        "timestamp_ms": std::time::SystemTime::now()
+) -> Result<AiResult> {
+    let task_id = config.ai_task_id.clone();
+    let path = format!("/v1/tasks/{}/frames", task_id);
+    let url = format!("{}{}", config.ai_service_url, path);
+
+    let frame = VideoFrame {
+        source_id: recording_id.to_string(),
+        timestamp: std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis() as u64,
-    });
+        sequence: frame_seq,
+        width: config.frame_width,
+        height: config.frame_height,
+        format: "jpeg".to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(&jpeg_data),
+        audio: None,
+    };
 
     let response = client
         .post(&url)
-        .json(&payload)
+        .json(&frame)
         .send()
         .await
         .context("failed to send frame to AI service")?;
@@ -168,9 +198,26 @@ async fn submit_frame_to_ai(
         anyhow::bail!("AI service returned error {}: {}", status, body);
     }
 
-    debug!(task_id = %task_id, frame_seq = frame_seq, "frame submitted to AI service");
+    if response.url().as_str() != url {
+        if let Some(owner) = response.url().as_str().strip_suffix(&path) {
+            debug!(task_id = %task_id, ai_service_url = %owner, "AI task served by another node");
+            config.ai_service_url = owner.to_string();
+        }
+    }
+
+    let result: AiResult = response
+        .json()
+        .await
+        .context("invalid AI service response")?;
+
+    debug!(
+        task_id = %task_id,
+        frame_seq = frame_seq,
+        detections = result.detections.len(),
+        "frame submitted to AI service"
+    );
 
-    Ok(())
+    Ok(result)
 }
 
 #[cfg(test)]
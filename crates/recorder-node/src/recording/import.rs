@@ -0,0 +1,370 @@
+//! Import of external footage (phone video, exports of other NVRs)
+//!
+//! `POST /v1/recordings/import` takes the video file as the raw request
+//! body, e.g. `?camera_id=lobby-cam&case_id=case-2025-118&recorded_at=1755252000`.
+//! The upload is probed with ffprobe and normalized to MP4 by the recording
+//! pipeline: H.264/H.265 is remuxed, anything else transcoded to H.264. The
+//! result is stored and listed like a native recording of the camera, and
+//! indexed under the importing tenant with the `imported` tag and `case`,
+//! `origin` and `original_filename` labels, so search, playback and
+//! exports find it the same way.
+
+use anyhow::{anyhow, Context, Result};
+use axum::{
+  body::Body,
+  extract::Query,
+  http::StatusCode,
+  Json,
+};
+use common::recordings::*;
+use common::search::RecordingMetadataUpdate;
+use common::tenancy::Tenant;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info, warn};
+
+use super::manager::RECORDING_MANAGER;
+use super::pipeline::{self, RecordingPipeline};
+
+/// Largest upload accepted when `RECORDING_IMPORT_MAX_BYTES` is not set
+const DEFAULT_MAX_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Name of the upload in the recording's directory until it is normalized
+const UPLOAD_FILE: &str = "upload";
+
+/// Tag every imported recording carries in the search index
+pub const IMPORTED_TAG: &str = "imported";
+
+/// Largest upload accepted, from `RECORDING_IMPORT_MAX_BYTES`
+pub fn max_bytes() -> u64 {
+  std::env::var("RECORDING_IMPORT_MAX_BYTES")
+    .ok()
+    .and_then(|value| value.parse().ok())
+    .filter(|bytes| *bytes > 0)
+    .unwrap_or(DEFAULT_MAX_BYTES)
+}
+
+/// Query string of an import; a camera, a case or both is required
+#[derive(Debug, Default, Deserialize)]
+pub struct ImportParams {
+  /// Recording ID; `import-<uuid>` when unset
+  pub id: Option<String>,
+  /// Camera the footage belongs to, as the stream ID of its recordings
+  pub camera_id: Option<String>,
+  /// Investigation the footage belongs to, set as the `case` label
+  pub case_id: Option<String>,
+  /// Unix seconds the footage starts at; the upload time when unset
+  pub recorded_at: Option<u64>,
+  /// Name of the file on the system it came from
+  pub filename: Option<String>,
+  /// Comma-separated extra tags
+  pub tag: Option<String>,
+}
+
+impl ImportParams {
+  /// Recording ID, after checking the camera and case IDs
+  fn validate(&self) -> Result<String> {
+    if self.camera_id.is_none() && self.case_id.is_none() {
+      return Err(anyhow!("camera_id or case_id required"));
+    }
+    if let Some(camera_id) = &self.camera_id {
+      common::validation::validate_id(camera_id, "camera_id")?;
+    }
+    if let Some(case_id) = &self.case_id {
+      common::validation::validate_id(case_id, "case_id")?;
+    }
+    let id = self
+      .id
+      .clone()
+      .unwrap_or_else(|| format!("import-{}", uuid::Uuid::new_v4()));
+    common::validation::validate_id(&id, "recording_id")?;
+    crate::search::api::validate_metadata(&self.metadata()).map_err(|e| anyhow!(e))?;
+    Ok(id)
+  }
+
+  /// Tags and labels of the imported recording in the search index
+  fn metadata(&self) -> RecordingMetadataUpdate {
+    let mut add_tags = vec![IMPORTED_TAG.to_string()];
+    add_tags.extend(
+      self
+        .tag
+        .iter()
+        .flat_map(|t| t.split(','))
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty()),
+    );
+    add_tags.sort();
+    add_tags.dedup();
+
+    let mut labels = std::collections::HashMap::new();
+    labels.insert("origin".to_string(), Some("import".to_string()));
+    if let Some(case_id) = &self.case_id {
+      labels.insert("case".to_string(), Some(case_id.clone()));
+    }
+    // Only the file name; clients may send a full path
+    if let Some(filename) = self
+      .filename
+      .as_deref()
+      .and_then(|name| name.rsplit(['/', '\\']).next())
+      .filter(|name| !name.is_empty())
+    {
+      labels.insert("original_filename".to_string(), Some(filename.to_string()));
+    }
+    RecordingMetadataUpdate {
+      add_tags,
+      labels,
+      ..Default::default()
+    }
+  }
+
+  fn config(&self, id: String) -> RecordingConfig {
+    RecordingConfig {
+      id,
+      source_stream_id: self.camera_id.clone(),
+      source_uri: None,
+      retention_hours: None,
+      format: Some(RecordingFormat::Mp4),
+      // Remux what browsers play, transcode the rest
+      encoding: Some(RecordingEncoding::Copy),
+    }
+  }
+}
+
+/// Accept an uploaded video file as a recording; it is normalized in the
+/// background and listed as `pending` until then
+pub async fn import_recording(
+  tenant: Tenant,
+  Query(params): Query<ImportParams>,
+  body: Body,
+) -> Result<(StatusCode, Json<RecordingInfo>), StatusCode> {
+  let id = params.validate().map_err(|e| {
+    info!(error = %e, "rejected recording import");
+    StatusCode::BAD_REQUEST
+  })?;
+  if RECORDING_MANAGER.is_draining() {
+    return Err(StatusCode::SERVICE_UNAVAILABLE);
+  }
+  if let Some(reason) = crate::storage::capacity::refusal(0) {
+    warn!(id = %id, reason = %reason, "refusing recording import, disk space low");
+    telemetry::metrics::RECORDER_NODE_RECORDING_REJECTIONS
+      .with_label_values(&["disk_full"])
+      .inc();
+    return Err(StatusCode::INSUFFICIENT_STORAGE);
+  }
+
+  let config = params.config(id.clone());
+  let dir = RecordingPipeline::new(config.clone())
+    .output_path()
+    .parent()
+    .map(Path::to_path_buf)
+    .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+  let upload = dir.join(UPLOAD_FILE);
+  // The pipeline reads the upload; the recording does not keep its path
+  let pipeline = RecordingPipeline::new(RecordingConfig {
+    source_uri: Some(upload.to_string_lossy().to_string()),
+    ..config.clone()
+  });
+
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs();
+  let info = RecordingInfo {
+    config,
+    state: RecordingState::Pending,
+    lease_id: None,
+    storage_path: None,
+    last_error: None,
+    started_at: Some(params.recorded_at.unwrap_or(now)),
+    stopped_at: None,
+    node_id: None,
+    metadata: None,
+  };
+  // Registered before the upload is written, so an import cannot overwrite
+  // another one of the same ID
+  let info = RECORDING_MANAGER.register(info).await.map_err(|e| {
+    info!(id = %id, error = %e, "rejected recording import");
+    StatusCode::CONFLICT
+  })?;
+
+  let saved = async {
+    fs::create_dir_all(&dir).await.context("failed to create recording directory")?;
+    save_upload(body, &upload, max_bytes()).await
+  }
+  .await;
+  let rejection = match saved {
+    Ok(Some(bytes)) if bytes > 0 => {
+      info!(id = %id, bytes, camera_id = ?params.camera_id, case_id = ?params.case_id, "recording import received");
+      None
+    }
+    Ok(Some(_)) => Some(StatusCode::BAD_REQUEST),
+    Ok(None) => Some(StatusCode::PAYLOAD_TOO_LARGE),
+    Err(e) => {
+      error!(id = %id, error = %e, "failed to receive recording import");
+      Some(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  };
+  if let Some(status) = rejection {
+    info!(id = %id, status = %status, "recording import not accepted");
+    let _ = fs::remove_dir_all(&dir).await;
+    RECORDING_MANAGER.update(&id, |info| info.state = RecordingState::Error).await;
+    RECORDING_MANAGER.forget(&id).await;
+    return Err(status);
+  }
+
+  let metadata = params.metadata();
+  let tenant_id = Some(tenant.tenant_id.clone());
+  tokio::spawn(async move {
+    let result = normalize(pipeline, &upload).await;
+    finish(&id, result, tenant_id, &metadata).await;
+  });
+
+  Ok((StatusCode::ACCEPTED, Json(info)))
+}
+
+/// Write a request body to `path`; `Ok(None)` once it exceeds `max_bytes`
+async fn save_upload(body: Body, path: &Path, max_bytes: u64) -> Result<Option<u64>> {
+  let mut file = fs::File::create(path).await.context("failed to create upload file")?;
+  let mut stream = body.into_data_stream();
+  let mut written = 0u64;
+  while let Some(chunk) = stream.next().await {
+    let chunk = chunk.context("upload interrupted")?;
+    written += chunk.len() as u64;
+    if written > max_bytes {
+      return Ok(None);
+    }
+    file.write_all(&chunk).await.context("failed to write upload")?;
+  }
+  file.flush().await.context("failed to write upload")?;
+  Ok(Some(written))
+}
+
+/// Probe the upload and write it as the recording's MP4, returning its
+/// path and metadata; the upload is removed either way
+async fn normalize(mut pipeline: RecordingPipeline, upload: &Path) -> Result<(String, Option<RecordingMetadata>)> {
+  let result = async {
+    if std::env::var("MOCK_RECORDING").is_err() {
+      let codec = pipeline::probe_video_codec(&upload.to_string_lossy())
+        .await
+        .context("upload is not a video file")?;
+      info!(path = ?upload, codec = %codec, "normalizing imported recording");
+    }
+    let encoding = pipeline.resolve_encoding().await.clone();
+    telemetry::metrics::RECORDER_NODE_PIPELINE_ENCODINGS
+      .with_label_values(&[encoding.as_str()])
+      .inc();
+    pipeline.run().await
+  }
+  .await;
+  let _ = fs::remove_file(upload).await;
+  result?;
+
+  let storage_path = pipeline.output_path().to_string_lossy().to_string();
+  // A normalized file whose metadata cannot be read still plays
+  match pipeline.extract_metadata().await {
+    Ok(metadata) => Ok((storage_path, Some(metadata))),
+    Err(e) => {
+      warn!(path = %storage_path, error = %e, "metadata extraction of imported recording failed");
+      Ok((storage_path, None))
+    }
+  }
+}
+
+/// Mark the import stopped, or failed, and index it
+async fn finish(
+  id: &str,
+  result: Result<(String, Option<RecordingMetadata>)>,
+  tenant_id: Option<String>,
+  update: &RecordingMetadataUpdate,
+) {
+  let status = if result.is_ok() { "success" } else { "error" };
+  telemetry::metrics::RECORDER_NODE_IMPORTS.with_label_values(&[status]).inc();
+
+  let info = RECORDING_MANAGER
+    .update(id, |info| match result {
+      Ok((storage_path, metadata)) => {
+        info.state = RecordingState::Stopped;
+        info.storage_path = Some(storage_path);
+        info.stopped_at = info
+          .started_at
+          .map(|start| start + metadata.as_ref().and_then(|m| m.duration_secs).unwrap_or(0));
+        info.metadata = metadata;
+      }
+      Err(e) => {
+        warn!(id = %info.config.id, error = %e, "recording import failed");
+        info.state = RecordingState::Error;
+        info.last_error = Some(format!("{:#}", e));
+      }
+    })
+    .await;
+
+  if let Some(info) = info.filter(|info| info.state == RecordingState::Stopped) {
+    info!(id = %id, metadata = ?info.metadata, "recording import finished");
+    crate::search::indexer::index_import(&info, tenant_id, update).await;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(query: &str) -> ImportParams {
+    serde_json::from_value(serde_json::Value::Object(
+      query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| {
+          let value = match k {
+            "recorded_at" => serde_json::json!(v.parse::<u64>().unwrap()),
+            _ => serde_json::json!(v),
+          };
+          (k.to_string(), value)
+        })
+        .collect(),
+    ))
+    .unwrap()
+  }
+
+  #[test]
+  fn imports_need_a_camera_or_case() {
+    assert!(params("filename=clip.mp4").validate().is_err());
+    assert!(params("camera_id=../etc").validate().is_err());
+    assert!(params("case_id=cases/1").validate().is_err());
+
+    let id = params("case_id=case-118").validate().unwrap();
+    assert!(id.starts_with("import-"));
+    assert_eq!(params("id=phone-1&camera_id=lobby").validate().unwrap(), "phone-1");
+  }
+
+  #[test]
+  fn imports_are_tagged_and_labelled() {
+    let params = params("camera_id=lobby&case_id=case-118&filename=C:\\exports\\door.avi&tag=witness, imported");
+    let update = params.metadata();
+    assert_eq!(update.add_tags, vec!["imported", "witness"]);
+    assert_eq!(update.labels["case"].as_deref(), Some("case-118"));
+    assert_eq!(update.labels["origin"].as_deref(), Some("import"));
+    assert_eq!(update.labels["original_filename"].as_deref(), Some("door.avi"));
+
+    let config = params.config("import-1".to_string());
+    assert_eq!(config.source_stream_id.as_deref(), Some("lobby"));
+    assert_eq!(config.format, Some(RecordingFormat::Mp4));
+    assert_eq!(config.encoding, Some(RecordingEncoding::Copy));
+  }
+
+  #[tokio::test]
+  async fn uploads_are_capped() {
+    let dir = std::env::temp_dir().join(format!("recorder-import-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).await.unwrap();
+    let path = dir.join(UPLOAD_FILE);
+
+    assert_eq!(save_upload(Body::from(vec![7u8; 1000]), &path, 1000).await.unwrap(), Some(1000));
+    assert_eq!(fs::read(&path).await.unwrap().len(), 1000);
+    assert_eq!(save_upload(Body::from(vec![7u8; 1001]), &path, 1000).await.unwrap(), None);
+
+    fs::remove_dir_all(&dir).await.unwrap();
+  }
+}
@@ -6,13 +6,16 @@ use common::{
 };
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
 use super::frame_capturer::{self, FrameCaptureConfig};
+use super::motion_storage::MotionStorage;
 use super::pipeline::RecordingPipeline;
 use crate::coordinator::CoordinatorClient;
 
@@ -23,14 +26,21 @@ lazy_static! {
   pub static ref RECORDING_MANAGER: RecordingManager = RecordingManager::new();
 }
 
+/// A running pipeline: its stop signal and the task running it
+struct PipelineHandle {
+  stop: CancellationToken,
+  task: JoinHandle<()>,
+}
+
 pub struct RecordingManager {
   recordings: Arc<RwLock<HashMap<String, RecordingInfo>>>,
-  pipelines: Arc<RwLock<HashMap<String, RecordingPipeline>>>,
+  pipelines: Arc<RwLock<HashMap<String, PipelineHandle>>>,
   renewals: Arc<RwLock<HashMap<String, CancellationToken>>>,
   frame_capturers: Arc<RwLock<HashMap<String, CancellationToken>>>,
   coordinator: Arc<RwLock<Option<Arc<dyn CoordinatorClient>>>>,
   node_id: Arc<RwLock<Option<String>>>,
   state_store: Arc<RwLock<Option<Arc<dyn StateStore>>>>,
+  draining: AtomicBool,
 }
 
 impl RecordingManager {
@@ -43,13 +53,17 @@ impl RecordingManager {
       coordinator: Arc::new(RwLock::new(None)),
       node_id: Arc::new(RwLock::new(None)),
       state_store: Arc::new(RwLock::new(None)),
+      draining: AtomicBool::new(false),
     }
   }
 
   /// Clear all recordings and state (for testing only)
   pub async fn clear(&self) {
     self.recordings.write().await.clear();
-    self.pipelines.write().await.clear();
+    let pipelines = self.pipelines.write().await.drain().collect::<Vec<_>>();
+    for (_, handle) in pipelines {
+      handle.stop.cancel();
+    }
     let renewals = self.renewals.write().await.drain().collect::<Vec<_>>();
     for (_, token) in renewals {
       token.cancel();
@@ -60,6 +74,7 @@ impl RecordingManager {
     }
     *self.coordinator.write().await = None;
     *self.node_id.write().await = None;
+    self.draining.store(false, Ordering::Relaxed);
   }
 
   pub async fn set_coordinator(&self, coordinator: Arc<dyn CoordinatorClient>, node_id: String) {
@@ -96,9 +111,15 @@ impl RecordingManager {
     Ok(())
   }
 
-  pub async fn start(&self, req: RecordingStartRequest) -> Result<RecordingStartResponse> {
+  pub async fn start(&self, mut req: RecordingStartRequest) -> Result<RecordingStartResponse> {
     let id = req.config.id.clone();
 
+    // Idle segments can only be reduced or dropped in a segmented recording
+    if req.motion_policy.is_some() && req.config.format != Some(RecordingFormat::Hls) {
+      info!(id = %id, "motion policy set, recording as HLS");
+      req.config.format = Some(RecordingFormat::Hls);
+    }
+
     // Validate recording ID
     common::validation::validate_id(&id, "recording_id")?;
 
@@ -111,6 +132,17 @@ impl RecordingManager {
       return Err(anyhow!("source_stream_id or source_uri required"));
     }
 
+    if self.is_draining() {
+      telemetry::metrics::RECORDER_NODE_RECORDING_REJECTIONS
+        .with_label_values(&["draining"])
+        .inc();
+      return Ok(RecordingStartResponse {
+        accepted: false,
+        lease_id: None,
+        message: Some("recorder node is shutting down".to_string()),
+      });
+    }
+
     let recordings = self.recordings.read().await;
     if recordings.contains_key(&id) {
       return Ok(RecordingStartResponse {
@@ -134,7 +166,20 @@ impl RecordingManager {
         )),
       });
     }
+    // Keep headroom on the recordings volume for the running recordings
+    let running = recordings.values().filter(|info| info.state.is_active()).count();
     drop(recordings);
+    if let Some(reason) = crate::storage::capacity::refusal(running) {
+      warn!(id = %id, reason = %reason, "refusing recording, disk space low");
+      telemetry::metrics::RECORDER_NODE_RECORDING_REJECTIONS
+        .with_label_values(&["disk_full"])
+        .inc();
+      return Ok(RecordingStartResponse {
+        accepted: false,
+        lease_id: None,
+        message: Some(reason),
+      });
+    }
 
     // Attempt to acquire lease if coordinator is configured
     let lease_id = if let Some(coordinator) = self.coordinator.read().await.clone() {
@@ -206,10 +251,8 @@ impl RecordingManager {
     // Persist initial state
     self.persist_recording(&info).await;
 
-    let pipeline = RecordingPipeline::new(req.config.clone());
-    let mut pipelines = self.pipelines.write().await;
-    pipelines.insert(id.clone(), pipeline);
-    drop(pipelines);
+    let mut pipeline = RecordingPipeline::new(req.config.clone());
+    let stop = pipeline.stop_token();
 
     // Start frame capture if AI config is provided
     if let Some(ai_cfg) = &req.ai_config {
@@ -227,6 +270,7 @@ impl RecordingManager {
         frame_width: ai_cfg.frame_width,
         frame_height: ai_cfg.frame_height,
         jpeg_quality: ai_cfg.jpeg_quality,
+        auto_tags: ai_cfg.auto_tags.clone(),
       };
 
       info!(
@@ -246,15 +290,40 @@ impl RecordingManager {
       capturers.insert(id.clone(), cancel_token);
     }
 
+    // Tags and labels the recording was started with
+    let metadata = crate::search::indexer::initial_metadata(&req);
+    if !metadata.is_empty() {
+      let id = id.clone();
+      tokio::spawn(async move { crate::search::indexer::tag_recording(&id, &metadata).await });
+    }
+
+    let motion_storage = match (&req.motion_policy, pipeline.output_path().parent()) {
+      (Some(policy), Some(dir)) => MotionStorage::start(
+        id.clone(),
+        req.config.source_stream_id.clone(),
+        dir.to_path_buf(),
+        policy.clone(),
+      ),
+      _ => None,
+    };
+
     let recordings_clone = Arc::clone(&self.recordings);
-    let pipelines_clone = Arc::clone(&self.pipelines);
     let state_store_clone = Arc::clone(&self.state_store);
-
-    tokio::spawn(async move {
+    let task_id = id.clone();
+
+    // The task owns the pipeline, so stopping one recording never waits on
+    // another; `stop` signals it through the token and awaits the task
+    let task = tokio::spawn(async move {
+      let id = task_id;
+      let encoding = pipeline.resolve_encoding().await.clone();
+      telemetry::metrics::RECORDER_NODE_PIPELINE_ENCODINGS
+        .with_label_values(&[encoding.as_str()])
+        .inc();
       let info_to_persist = {
         let mut recordings = recordings_clone.write().await;
         if let Some(info) = recordings.get_mut(&id) {
           info.state = RecordingState::Recording;
+          info.config.encoding = Some(encoding);
           Some(info.clone())
         } else {
           None
@@ -270,54 +339,60 @@ impl RecordingManager {
 
       info!(id = %id, "recording pipeline started");
 
-      let mut pipelines = pipelines_clone.write().await;
-      if let Some(pipeline) = pipelines.get_mut(&id) {
-        // Store output path
-        let output_path = pipeline.output_path().to_string_lossy().to_string();
+      // Store output path
+      let output_path = pipeline.output_path().to_string_lossy().to_string();
+      let mut recordings = recordings_clone.write().await;
+      if let Some(info) = recordings.get_mut(&id) {
+        info.storage_path = Some(output_path);
+      }
+      drop(recordings);
+
+      // Run pipeline
+      let result = pipeline.run().await;
+      if let Some(motion_storage) = motion_storage {
+        motion_storage.finish().await;
+      }
+      if let Err(e) = result {
+        warn!(id = %id, error = %e, "recording pipeline failed");
         let mut recordings = recordings_clone.write().await;
         if let Some(info) = recordings.get_mut(&id) {
-          info.storage_path = Some(output_path);
+          info.state = RecordingState::Error;
+          info.last_error = Some(e.to_string());
         }
-        drop(recordings);
-
-        // Run pipeline
-        if let Err(e) = pipeline.run().await {
-          warn!(id = %id, error = %e, "recording pipeline failed");
-          let mut recordings = recordings_clone.write().await;
-          if let Some(info) = recordings.get_mut(&id) {
-            info.state = RecordingState::Error;
-            info.last_error = Some(e.to_string());
-          }
-        } else {
-          // Extract metadata after successful recording
-          info!(id = %id, "recording completed, extracting metadata");
-          match pipeline.extract_metadata().await {
-            Ok(metadata) => {
-              info!(id = %id, metadata = ?metadata, "metadata extraction successful");
-              // Store metadata in RecordingInfo
-              let info_to_persist = {
-                let mut recordings = recordings_clone.write().await;
-                if let Some(info) = recordings.get_mut(&id) {
-                  info.metadata = Some(metadata);
-                  Some(info.clone())
-                } else {
-                  None
-                }
-              };
-              // Persist metadata
-              if let (Some(info), Some(store)) = (info_to_persist, state_store_clone.read().await.as_ref()) {
-                if let Err(e) = store.save_recording(&info).await {
-                  warn!(recording_id = %info.config.id, error = %e, "failed to persist recording metadata");
-                }
+      } else {
+        // Extract metadata after successful recording
+        info!(id = %id, "recording completed, extracting metadata");
+        match pipeline.extract_metadata().await {
+          Ok(metadata) => {
+            info!(id = %id, metadata = ?metadata, "metadata extraction successful");
+            // Store metadata in RecordingInfo
+            let info_to_persist = {
+              let mut recordings = recordings_clone.write().await;
+              if let Some(info) = recordings.get_mut(&id) {
+                info.metadata = Some(metadata);
+                Some(info.clone())
+              } else {
+                None
+              }
+            };
+            // Persist metadata
+            if let (Some(info), Some(store)) = (info_to_persist, state_store_clone.read().await.as_ref()) {
+              if let Err(e) = store.save_recording(&info).await {
+                warn!(recording_id = %info.config.id, error = %e, "failed to persist recording metadata");
               }
-            }
-            Err(e) => {
-              warn!(id = %id, error = %e, "metadata extraction failed");
             }
           }
+          Err(e) => {
+            warn!(id = %id, error = %e, "metadata extraction failed");
+          }
         }
       }
     });
+    self
+      .pipelines
+      .write()
+      .await
+      .insert(id.clone(), PipelineHandle { stop, task });
 
     Ok(RecordingStartResponse {
       accepted: true,
@@ -365,12 +440,14 @@ impl RecordingManager {
       token.cancel();
     }
 
-    // Stop the pipeline
-    let mut pipelines = self.pipelines.write().await;
-    if let Some(mut pipeline) = pipelines.remove(id) {
-      pipeline.stop().await?;
+    // Stop the pipeline and wait for the recording to be finalized
+    let handle = self.pipelines.write().await.remove(id);
+    if let Some(handle) = handle {
+      handle.stop.cancel();
+      if let Err(e) = handle.task.await {
+        warn!(id = %id, error = %e, "recording task failed");
+      }
     }
-    drop(pipelines);
 
     // Release the lease if we have one
     if let Some(lease_id) = lease_id {
@@ -404,6 +481,75 @@ impl RecordingManager {
     Ok(true)
   }
 
+  /// Whether [`Self::drain`] has started; new recordings are refused
+  pub fn is_draining(&self) -> bool {
+    self.draining.load(Ordering::Relaxed)
+  }
+
+  /// Shut down gracefully: refuse new recordings, finalize the active ones
+  /// and release their leases so the coordinator can hand them to another
+  /// node. Recordings still finalizing after `grace` have their leases
+  /// released anyway, and are cut off when the process exits.
+  pub async fn drain(&self, grace: Duration) {
+    self.draining.store(true, Ordering::Relaxed);
+
+    let active: Vec<String> = self
+      .recordings
+      .read()
+      .await
+      .values()
+      .filter(|info| info.state.is_active())
+      .map(|info| info.config.id.clone())
+      .collect();
+    info!(recordings = active.len(), grace_secs = grace.as_secs(), "draining recorder node");
+
+    // Signal every pipeline first so they all finalize concurrently
+    for handle in self.pipelines.read().await.values() {
+      handle.stop.cancel();
+    }
+
+    let stopped = tokio::time::timeout(grace, async {
+      for id in &active {
+        if let Err(e) = self.stop(id).await {
+          warn!(id = %id, error = %e, "failed to stop recording while draining");
+        }
+      }
+    })
+    .await;
+    if stopped.is_err() {
+      warn!("grace period elapsed before every recording was finalized");
+    }
+
+    // Failed recordings and those still finalizing keep their leases until
+    // released here
+    let leftover: Vec<(String, String)> = self
+      .recordings
+      .read()
+      .await
+      .values()
+      .filter(|info| info.state != RecordingState::Stopped)
+      .filter_map(|info| Some((info.config.id.clone(), info.lease_id.clone()?)))
+      .collect();
+    let coordinator = self.coordinator.read().await.clone();
+    for (id, lease_id) in &leftover {
+      self.cancel_lease_renewal(id).await;
+      if let Some(coordinator) = &coordinator {
+        info!(id = %id, lease_id = %lease_id, "releasing recorder lease");
+        let release_req = LeaseReleaseRequest {
+          lease_id: lease_id.clone(),
+        };
+        if let Err(e) = coordinator.release(&release_req).await {
+          warn!(id = %id, error = %e, "failed to release lease");
+        }
+      }
+    }
+    info!(
+      recordings = active.len(),
+      released_leases = leftover.len(),
+      "recorder node drained"
+    );
+  }
+
   pub async fn list(&self) -> Vec<RecordingInfo> {
     let recordings = self.recordings.read().await;
     recordings.values().cloned().collect()
@@ -414,6 +560,54 @@ impl RecordingManager {
     recordings.get(id).cloned()
   }
 
+  /// Add a recording this node did not record, such as an import; fails
+  /// when the ID is taken
+  pub async fn register(&self, mut info: RecordingInfo) -> Result<RecordingInfo> {
+    common::validation::validate_id(&info.config.id, "recording_id")?;
+    info.node_id = self.node_id.read().await.clone();
+    {
+      let mut recordings = self.recordings.write().await;
+      if recordings.contains_key(&info.config.id) {
+        return Err(anyhow!("recording {} already exists", info.config.id));
+      }
+      recordings.insert(info.config.id.clone(), info.clone());
+    }
+    self.persist_recording(&info).await;
+    Ok(info)
+  }
+
+  /// Change a recording and persist it; `None` when it is unknown
+  pub async fn update(&self, id: &str, change: impl FnOnce(&mut RecordingInfo)) -> Option<RecordingInfo> {
+    let info = {
+      let mut recordings = self.recordings.write().await;
+      let info = recordings.get_mut(id)?;
+      change(info);
+      info.clone()
+    };
+    self.persist_recording(&info).await;
+    Some(info)
+  }
+
+  /// Drop a stopped recording whose footage was deleted; running ones are
+  /// kept
+  pub async fn forget(&self, id: &str) {
+    let removed = {
+      let mut recordings = self.recordings.write().await;
+      match recordings.get(id) {
+        Some(info) if !info.state.is_active() => recordings.remove(id).is_some(),
+        _ => false,
+      }
+    };
+    if !removed {
+      return;
+    }
+    if let Some(store) = self.state_store.read().await.as_ref() {
+      if let Err(e) = store.delete_recording(id).await {
+        warn!(recording_id = %id, error = %e, "failed to delete recording state");
+      }
+    }
+  }
+
   async fn start_lease_renewal(&self, recording_id: String, lease_id: String, ttl_secs: u64) {
     let token = CancellationToken::new();
     {
@@ -424,6 +618,7 @@ impl RecordingManager {
     }
 
     let recordings = Arc::clone(&self.recordings);
+    let pipelines = Arc::clone(&self.pipelines);
     let coordinator = self.coordinator.clone();
     let state_store = Arc::clone(&self.state_store);
     let interval_secs = ttl_secs / 2;
@@ -444,7 +639,7 @@ impl RecordingManager {
             let coordinator_guard = coordinator.read().await;
             if let Some(coordinator) = coordinator_guard.as_ref() {
               match coordinator.renew(&req).await {
-                Ok(_) => {
+                Ok(resp) if resp.renewed => {
                   let info_to_persist = {
                     let mut recordings = recordings.write().await;
                     if let Some(entry) = recordings.get_mut(&recording_id) {
@@ -464,6 +659,30 @@ impl RecordingManager {
                     }
                   }
                 }
+                Ok(_) => {
+                  // Expired or taken over: another recorder may have the
+                  // recording now, so this one stops writing it
+                  warn!(id = %recording_id, "recorder lease lost, stopping recording");
+                  if let Some(handle) = pipelines.write().await.remove(&recording_id) {
+                    handle.stop.cancel();
+                  }
+                  let info_to_persist = {
+                    let mut recordings = recordings.write().await;
+                    if let Some(entry) = recordings.get_mut(&recording_id) {
+                      entry.state = RecordingState::Error;
+                      entry.last_error = Some("lease lost to another recorder".to_string());
+                      Some(entry.clone())
+                    } else {
+                      None
+                    }
+                  };
+                  if let (Some(info), Some(store)) = (info_to_persist, state_store.read().await.as_ref()) {
+                    if let Err(e) = store.save_recording(&info).await {
+                      warn!(recording_id = %info.config.id, error = %e, "failed to persist recording state");
+                    }
+                  }
+                  break;
+                }
                 Err(err) => {
                   warn!(id = %recording_id, error = %err, "lease renewal failed");
                   let info_to_persist = {
@@ -515,12 +734,16 @@ mod tests {
       source_uri: Some("rtsp://example.com/stream".to_string()),
       retention_hours: Some(24),
       format: Some(RecordingFormat::Mp4),
+      encoding: None,
     };
 
     let req = RecordingStartRequest {
       config,
       lease_ttl_secs: Some(60),
       ai_config: None,
+      motion_policy: None,
+      tags: Vec::new(),
+      labels: Default::default(),
     };
 
     let response = manager.start(req).await.unwrap();
@@ -536,4 +759,39 @@ mod tests {
     let info = manager.get("test-rec-1").await.unwrap();
     assert_eq!(info.state, RecordingState::Stopped);
   }
+
+  fn start_request(id: &str) -> RecordingStartRequest {
+    RecordingStartRequest {
+      config: RecordingConfig {
+        id: id.to_string(),
+        source_stream_id: None,
+        source_uri: Some("rtsp://example.com/stream".to_string()),
+        retention_hours: None,
+        format: Some(RecordingFormat::Mp4),
+        encoding: None,
+      },
+      lease_ttl_secs: None,
+      ai_config: None,
+      motion_policy: None,
+      tags: Vec::new(),
+      labels: Default::default(),
+    }
+  }
+
+  #[tokio::test]
+  async fn drain_stops_recordings_and_refuses_new_ones() {
+    let manager = RecordingManager::new();
+    assert!(manager.start(start_request("drain-1")).await.unwrap().accepted);
+    assert!(manager.start(start_request("drain-2")).await.unwrap().accepted);
+
+    manager.drain(Duration::from_secs(10)).await;
+    assert!(manager.is_draining());
+    for info in manager.list().await {
+      assert!(!info.state.is_active(), "{} still {:?}", info.config.id, info.state);
+    }
+
+    let refused = manager.start(start_request("drain-3")).await.unwrap();
+    assert!(!refused.accepted);
+    assert!(manager.get("drain-3").await.is_none());
+  }
 }
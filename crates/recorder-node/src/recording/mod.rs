@@ -1,4 +1,7 @@
 pub mod frame_capturer;
+pub mod import;
 pub mod manager;
+pub mod motion_storage;
 pub mod pipeline;
 pub mod thumbnail_generator;
+pub mod timelapse;
@@ -0,0 +1,462 @@
+//! Motion-only storage of HLS recordings.
+//!
+//! While a recording with a [`MotionStoragePolicy`] runs, every finished
+//! segment is matched against the motion activity the source stream's node
+//! published for the same wall-clock span. Segments with motion, and those
+//! within the pre/post roll of it, stay as recorded; idle ones are
+//! re-encoded at the idle bitrate or deleted. Segments the stream node has
+//! no activity for are kept. When the recording stops the playlist is
+//! rewritten without the dropped segments, with discontinuities wherever
+//! segments were dropped or the quality changes.
+
+use anyhow::{anyhow, Context, Result};
+use common::motion::{IdleSegmentAction, MotionActivityResponse, MotionStoragePolicy, SegmentActivity};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How one recorded segment is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentDecision {
+  Full,
+  Reduced,
+  Dropped,
+}
+
+impl SegmentDecision {
+  fn as_str(&self) -> &'static str {
+    match self {
+      SegmentDecision::Full => "full",
+      SegmentDecision::Reduced => "reduced",
+      SegmentDecision::Dropped => "dropped",
+    }
+  }
+}
+
+/// A segment of the recording's playlist, in wall-clock milliseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedSegment {
+  pub name: String,
+  pub start_ms: u64,
+  pub end_ms: u64,
+}
+
+/// Background task applying the policy to one recording
+pub struct MotionStorage {
+  finish: CancellationToken,
+  task: JoinHandle<()>,
+}
+
+impl MotionStorage {
+  /// Start applying `policy` to the segments written to `dir`. Without a
+  /// source stream or an activity URL there is nothing to match against and
+  /// every segment is kept.
+  pub fn start(
+    recording_id: String,
+    stream_id: Option<String>,
+    dir: PathBuf,
+    policy: MotionStoragePolicy,
+  ) -> Option<Self> {
+    let Some(stream_id) = stream_id else {
+      warn!(id = %recording_id, "motion policy needs a source_stream_id, keeping all segments");
+      return None;
+    };
+    let Some(activity_url) = policy
+      .activity_url
+      .clone()
+      .or_else(|| std::env::var("MOTION_ACTIVITY_URL").ok())
+    else {
+      warn!(id = %recording_id, "no motion activity URL configured, keeping all segments");
+      return None;
+    };
+
+    info!(
+      id = %recording_id,
+      stream_id = %stream_id,
+      idle_action = policy.idle_action.as_str(),
+      "motion-only storage enabled"
+    );
+    let finish = CancellationToken::new();
+    let worker = Worker {
+      recording_id,
+      stream_id,
+      dir,
+      activity_url: activity_url.trim_end_matches('/').to_string(),
+      policy,
+      client: reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new()),
+      decisions: HashMap::new(),
+    };
+    let task = tokio::spawn(worker.run(finish.clone()));
+    Some(Self { finish, task })
+  }
+
+  /// Decide the remaining segments and rewrite the playlist; call once the
+  /// pipeline has finalized the recording
+  pub async fn finish(self) {
+    self.finish.cancel();
+    let _ = self.task.await;
+  }
+}
+
+struct Worker {
+  recording_id: String,
+  stream_id: String,
+  dir: PathBuf,
+  activity_url: String,
+  policy: MotionStoragePolicy,
+  client: reqwest::Client,
+  decisions: HashMap<String, SegmentDecision>,
+}
+
+impl Worker {
+  async fn run(mut self, finish: CancellationToken) {
+    loop {
+      let finishing = tokio::select! {
+        _ = finish.cancelled() => true,
+        _ = tokio::time::sleep(POLL_INTERVAL) => false,
+      };
+      if let Err(e) = self.pass(finishing).await {
+        warn!(id = %self.recording_id, error = %e, "motion storage pass failed");
+      }
+      if finishing {
+        break;
+      }
+    }
+    if let Err(e) = self.rewrite_playlist().await {
+      warn!(id = %self.recording_id, error = %e, "failed to rewrite motion-only playlist");
+    }
+  }
+
+  /// Decide every undecided segment whose pre/post roll the stream node has
+  /// analysed; when finishing, decide all of them
+  async fn pass(&mut self, finishing: bool) -> Result<()> {
+    let Ok(playlist) = tokio::fs::read_to_string(self.dir.join("index.m3u8")).await else {
+      return Ok(());
+    };
+    let pending: Vec<RecordedSegment> = parse_recorded_playlist(&playlist)
+      .into_iter()
+      .filter(|s| !self.decisions.contains_key(&s.name))
+      .collect();
+    let Some(first) = pending.first() else {
+      return Ok(());
+    };
+
+    let pre_roll_ms = u64::from(self.policy.pre_roll_secs) * 1000;
+    let post_roll_ms = u64::from(self.policy.post_roll_secs) * 1000;
+    let activity = self.fetch_activity(first.start_ms.saturating_sub(pre_roll_ms)).await;
+    let analysed_until = activity.iter().map(|a| a.end_ms).max().unwrap_or(0);
+
+    for segment in pending {
+      if !finishing && analysed_until < segment.end_ms + post_roll_ms {
+        break;
+      }
+      let decision = decide(&segment, &activity, &self.policy);
+      let decision = match self.apply(&segment, decision).await {
+        Ok(()) => decision,
+        Err(e) => {
+          warn!(id = %self.recording_id, segment = %segment.name, error = %e, "keeping segment at full quality");
+          SegmentDecision::Full
+        }
+      };
+      debug!(id = %self.recording_id, segment = %segment.name, decision = decision.as_str(), "segment stored");
+      telemetry::metrics::RECORDER_NODE_MOTION_SEGMENTS
+        .with_label_values(&[decision.as_str()])
+        .inc();
+      self.decisions.insert(segment.name, decision);
+    }
+    Ok(())
+  }
+
+  /// Activity ending after `since_ms`; empty if the stream node has none
+  async fn fetch_activity(&self, since_ms: u64) -> Vec<SegmentActivity> {
+    let url = format!("{}/streams/{}/motion", self.activity_url, self.stream_id);
+    let response = self
+      .client
+      .get(&url)
+      .query(&[("since_ms", since_ms)])
+      .send()
+      .await
+      .and_then(|r| r.error_for_status());
+    match response {
+      Ok(response) => match response.json::<MotionActivityResponse>().await {
+        Ok(body) => body.segments,
+        Err(e) => {
+          warn!(id = %self.recording_id, error = %e, "invalid motion activity response");
+          Vec::new()
+        }
+      },
+      Err(e) => {
+        debug!(id = %self.recording_id, error = %e, "no motion activity available");
+        Vec::new()
+      }
+    }
+  }
+
+  async fn apply(&self, segment: &RecordedSegment, decision: SegmentDecision) -> Result<()> {
+    let path = self.dir.join(&segment.name);
+    match decision {
+      SegmentDecision::Full => Ok(()),
+      SegmentDecision::Reduced => reencode(&path, self.policy.idle_bitrate_kbps).await,
+      SegmentDecision::Dropped => tokio::fs::remove_file(&path)
+        .await
+        .with_context(|| format!("failed to delete {}", path.display())),
+    }
+  }
+
+  async fn rewrite_playlist(&self) -> Result<()> {
+    if !self.decisions.values().any(|d| *d != SegmentDecision::Full) {
+      return Ok(());
+    }
+    let path = self.dir.join("index.m3u8");
+    let playlist = tokio::fs::read_to_string(&path).await?;
+    let tmp = self.dir.join("index.m3u8.tmp");
+    tokio::fs::write(&tmp, prune_playlist(&playlist, &self.decisions)).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+
+    let dropped = self
+      .decisions
+      .values()
+      .filter(|d| **d == SegmentDecision::Dropped)
+      .count();
+    let reduced = self
+      .decisions
+      .values()
+      .filter(|d| **d == SegmentDecision::Reduced)
+      .count();
+    info!(id = %self.recording_id, dropped, reduced, "motion-only playlist written");
+    Ok(())
+  }
+}
+
+/// Full quality when the stream had motion within the pre/post roll of the
+/// segment or was not analysed for it; otherwise the policy's idle action
+pub fn decide(
+  segment: &RecordedSegment,
+  activity: &[SegmentActivity],
+  policy: &MotionStoragePolicy,
+) -> SegmentDecision {
+  let overlaps = |a: &SegmentActivity, from: u64, to: u64| a.start_ms < to && a.end_ms > from;
+  let analysed = activity
+    .iter()
+    .any(|a| overlaps(a, segment.start_ms, segment.end_ms));
+  if !analysed {
+    return SegmentDecision::Full;
+  }
+
+  let from = segment
+    .start_ms
+    .saturating_sub(u64::from(policy.pre_roll_secs) * 1000);
+  let to = segment.end_ms + u64::from(policy.post_roll_secs) * 1000;
+  if activity.iter().any(|a| a.motion && overlaps(a, from, to)) {
+    return SegmentDecision::Full;
+  }
+  match policy.idle_action {
+    IdleSegmentAction::Keep => SegmentDecision::Full,
+    IdleSegmentAction::Reduce => SegmentDecision::Reduced,
+    IdleSegmentAction::Drop => SegmentDecision::Dropped,
+  }
+}
+
+/// Segments of an HLS recording playlist written with
+/// `-hls_flags program_date_time`
+pub fn parse_recorded_playlist(playlist: &str) -> Vec<RecordedSegment> {
+  let mut segments = Vec::new();
+  let mut next_start: Option<u64> = None;
+  let mut duration_ms = None;
+  for line in playlist.lines().map(str::trim) {
+    if let Some(date) = line.strip_prefix("#EXT-X-PROGRAM-DATE-TIME:") {
+      if let Ok(at) = chrono::DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f%z") {
+        next_start = u64::try_from(at.timestamp_millis()).ok();
+      }
+    } else if let Some(rest) = line.strip_prefix("#EXTINF:") {
+      duration_ms = rest
+        .split(',')
+        .next()
+        .and_then(|d| d.parse::<f64>().ok())
+        .map(|d| (d * 1000.0) as u64);
+    } else if !line.is_empty() && !line.starts_with('#') {
+      if let (Some(start_ms), Some(duration_ms)) = (next_start, duration_ms.take()) {
+        segments.push(RecordedSegment {
+          name: line.to_string(),
+          start_ms,
+          end_ms: start_ms + duration_ms,
+        });
+        next_start = Some(start_ms + duration_ms);
+      }
+    }
+  }
+  segments
+}
+
+/// Remove dropped segments from `playlist`, marking a discontinuity where
+/// segments were removed or the stored quality changes
+pub fn prune_playlist(playlist: &str, decisions: &HashMap<String, SegmentDecision>) -> String {
+  let mut out = Vec::new();
+  let mut segment_tags: Vec<&str> = Vec::new();
+  let mut previous: Option<SegmentDecision> = None;
+  let mut gap = false;
+  let dropped: HashSet<&str> = decisions
+    .iter()
+    .filter(|(_, d)| **d == SegmentDecision::Dropped)
+    .map(|(name, _)| name.as_str())
+    .collect();
+
+  for line in playlist.lines() {
+    let trimmed = line.trim();
+    if trimmed.starts_with("#EXTINF:")
+      || trimmed.starts_with("#EXT-X-PROGRAM-DATE-TIME:")
+      || trimmed == "#EXT-X-DISCONTINUITY"
+    {
+      segment_tags.push(line);
+    } else if trimmed.is_empty() || trimmed.starts_with('#') {
+      out.push(line);
+    } else if dropped.contains(trimmed) {
+      segment_tags.clear();
+      gap = true;
+    } else {
+      let decision = decisions.get(trimmed).copied().unwrap_or(SegmentDecision::Full);
+      let changed = previous.is_some_and(|p| p != decision);
+      let marked = segment_tags.iter().any(|l| l.trim() == "#EXT-X-DISCONTINUITY");
+      if previous.is_some() && (gap || changed) && !marked {
+        out.push("#EXT-X-DISCONTINUITY");
+      }
+      out.append(&mut segment_tags);
+      out.push(line);
+      previous = Some(decision);
+      gap = false;
+    }
+  }
+
+  let mut text = out.join("\n");
+  text.push('\n');
+  text
+}
+
+/// Re-encode a segment in place at `kbps`, keeping its timestamps
+async fn reencode(path: &Path, kbps: u32) -> Result<()> {
+  let tmp = path.with_extension("reduced.ts");
+  let rate = format!("{}k", kbps);
+  let output = Command::new("ffmpeg")
+    .arg("-v")
+    .arg("error")
+    .arg("-y")
+    .arg("-i")
+    .arg(path)
+    .args(["-c:v", "libx264", "-preset", "veryfast", "-b:v", &rate, "-maxrate", &rate])
+    .args(["-bufsize", &format!("{}k", kbps.saturating_mul(2))])
+    .args(["-c:a", "copy", "-copyts", "-muxdelay", "0", "-f", "mpegts"])
+    .arg(&tmp)
+    .output()
+    .await
+    .context("failed to spawn ffmpeg")?;
+  if !output.status.success() {
+    let _ = tokio::fs::remove_file(&tmp).await;
+    return Err(anyhow!(
+      "ffmpeg exited with {}: {}",
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  tokio::fs::rename(&tmp, path).await?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn activity(start_ms: u64, end_ms: u64, motion: bool) -> SegmentActivity {
+    SegmentActivity {
+      segment: format!("segment_{}.ts", start_ms),
+      start_ms,
+      end_ms,
+      score: if motion { 0.2 } else { 0.0 },
+      motion,
+    }
+  }
+
+  fn segment(start_ms: u64) -> RecordedSegment {
+    RecordedSegment {
+      name: format!("segment_{}.ts", start_ms),
+      start_ms,
+      end_ms: start_ms + 2000,
+    }
+  }
+
+  #[test]
+  fn playlist_segments_follow_program_date_time() {
+    let playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:2\n\
+      #EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:00.000+0000\n#EXTINF:2.000000,\nsegment_00000.ts\n\
+      #EXTINF:1.500000,\nsegment_00001.ts\n";
+    let segments = parse_recorded_playlist(playlist);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].start_ms, 1_718_157_600_000);
+    assert_eq!(segments[0].end_ms, 1_718_157_602_000);
+    assert_eq!(segments[1].start_ms, 1_718_157_602_000);
+    assert_eq!(segments[1].end_ms, 1_718_157_603_500);
+  }
+
+  #[test]
+  fn idle_segments_follow_policy_outside_roll() {
+    let policy = MotionStoragePolicy {
+      idle_action: IdleSegmentAction::Drop,
+      pre_roll_secs: 2,
+      post_roll_secs: 2,
+      ..Default::default()
+    };
+    let activity = vec![
+      activity(0, 2000, false),
+      activity(2000, 4000, false),
+      activity(4000, 6000, false),
+      activity(6000, 8000, true),
+      activity(8000, 10000, false),
+    ];
+
+    // Well before the motion
+    assert_eq!(decide(&segment(0), &activity, &policy), SegmentDecision::Dropped);
+    // Within the pre roll, the motion segment itself, and the post roll
+    assert_eq!(decide(&segment(4000), &activity, &policy), SegmentDecision::Full);
+    assert_eq!(decide(&segment(6000), &activity, &policy), SegmentDecision::Full);
+    assert_eq!(decide(&segment(8000), &activity, &policy), SegmentDecision::Full);
+    // Never analysed by the stream node
+    assert_eq!(decide(&segment(20000), &activity, &policy), SegmentDecision::Full);
+
+    let reduce = MotionStoragePolicy {
+      idle_action: IdleSegmentAction::Reduce,
+      ..policy
+    };
+    assert_eq!(decide(&segment(0), &activity, &reduce), SegmentDecision::Reduced);
+  }
+
+  #[test]
+  fn pruned_playlist_marks_gaps_and_quality_changes() {
+    let playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:2\n\
+      #EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:00.000+0000\n#EXTINF:2.0,\na.ts\n\
+      #EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:02.000+0000\n#EXTINF:2.0,\nb.ts\n\
+      #EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:04.000+0000\n#EXTINF:2.0,\nc.ts\n\
+      #EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:06.000+0000\n#EXTINF:2.0,\nd.ts\n#EXT-X-ENDLIST\n";
+    let decisions = HashMap::from([
+      ("a.ts".to_string(), SegmentDecision::Full),
+      ("b.ts".to_string(), SegmentDecision::Dropped),
+      ("c.ts".to_string(), SegmentDecision::Full),
+      ("d.ts".to_string(), SegmentDecision::Reduced),
+    ]);
+
+    let pruned = prune_playlist(playlist, &decisions);
+    assert!(!pruned.contains("b.ts"));
+    assert!(!pruned.contains("02:00:02"));
+    assert_eq!(pruned.matches("#EXT-X-DISCONTINUITY").count(), 2);
+    assert!(pruned.contains("#EXT-X-DISCONTINUITY\n#EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:04.000+0000"));
+    assert!(pruned.contains("#EXT-X-DISCONTINUITY\n#EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:06.000+0000"));
+    assert!(pruned.ends_with("d.ts\n#EXT-X-ENDLIST\n"));
+  }
+}
@@ -1,27 +1,101 @@
 use anyhow::{anyhow, Context, Result};
-use common::recordings::{RecordingConfig, RecordingFormat, RecordingMetadata};
+use common::ffmpeg_progress::{self, ProgressSample, PROGRESS_ARGS};
+use common::recordings::{RecordingConfig, RecordingEncoding, RecordingFormat, RecordingMetadata};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::time::Duration;
 use tokio::fs;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+/// How long FFmpeg gets to write its trailer after being asked to quit
+const FINALIZE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time ffprobe gets to read the source's stream info before copy mode is
+/// assumed to work
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Video codecs copy mode writes as they come from the camera
+const COPYABLE_CODECS: &[&str] = &["h264", "hevc"];
+
+/// libx264 quality when a transcoding recording does not set one
+const DEFAULT_CRF: u8 = 23;
+
+/// Encoding for recordings that do not choose one, from
+/// `RECORDING_DEFAULT_ENCODING` (`copy` or `transcode`)
+pub fn default_encoding() -> RecordingEncoding {
+  std::env::var("RECORDING_DEFAULT_ENCODING")
+    .ok()
+    .and_then(|value| RecordingEncoding::parse(&value))
+    .unwrap_or(RecordingEncoding::Copy)
+}
+
 pub struct RecordingPipeline {
   config: RecordingConfig,
   output_path: PathBuf,
   process: Option<Child>,
-  stopped: bool,
+  stop: CancellationToken,
+  encoding: RecordingEncoding,
+  /// Video codec reported by the source, once probed
+  source_codec: Option<String>,
 }
 
 impl RecordingPipeline {
   pub fn new(config: RecordingConfig) -> Self {
     let output_path = Self::generate_output_path(&config);
+    let encoding = config.encoding.clone().unwrap_or_else(default_encoding);
     Self {
       config,
       output_path,
       process: None,
-      stopped: false,
+      stop: CancellationToken::new(),
+      encoding,
+      source_codec: None,
+    }
+  }
+
+  /// Encoding the pipeline runs with
+  pub fn encoding(&self) -> &RecordingEncoding {
+    &self.encoding
+  }
+
+  /// Check that copy mode can carry the source's video before
+  /// [`RecordingPipeline::run`]. A camera sending anything but H.264/H.265
+  /// (MJPEG, MPEG-4) is transcoded instead; a source that cannot be probed
+  /// is recorded in copy mode and left to ffmpeg.
+  pub async fn resolve_encoding(&mut self) -> &RecordingEncoding {
+    if self.encoding != RecordingEncoding::Copy || std::env::var("MOCK_RECORDING").is_ok() {
+      return &self.encoding;
     }
+    let Some(source_uri) = self.config.source_uri.clone() else {
+      return &self.encoding;
+    };
+
+    match probe_video_codec(&source_uri).await {
+      Ok(codec) if COPYABLE_CODECS.contains(&codec.as_str()) => {
+        info!(id = %self.config.id, codec = %codec, "recording camera bitstream without transcoding");
+        self.source_codec = Some(codec);
+      }
+      Ok(codec) => {
+        warn!(id = %self.config.id, codec = %codec, "source codec cannot be copied, transcoding to H.264");
+        self.encoding = RecordingEncoding::Transcode {
+          crf: None,
+          max_height: None,
+        };
+        self.source_codec = Some(codec);
+      }
+      Err(e) => {
+        warn!(id = %self.config.id, error = %e, "could not probe source codec, recording in copy mode");
+      }
+    }
+    &self.encoding
+  }
+
+  /// Token that stops the pipeline from outside the task running it; the
+  /// recording is finalized before [`Self::run`] returns
+  pub fn stop_token(&self) -> CancellationToken {
+    self.stop.clone()
   }
 
   fn generate_output_path(config: &RecordingConfig) -> PathBuf {
@@ -80,18 +154,33 @@ impl RecordingPipeline {
 
     // Build FFmpeg command based on output format
     let format = self.config.format.as_ref().unwrap_or(&RecordingFormat::Mp4);
-    let args = self.build_ffmpeg_args(source_uri, format)?;
+    let mut args = self.build_ffmpeg_args(source_uri, format)?;
+    args.splice(0..0, PROGRESS_ARGS.iter().map(|a| a.to_string()));
 
     info!(id = %self.config.id, args = ?args, "launching ffmpeg");
 
     // Spawn FFmpeg process
-    let child = Command::new("ffmpeg")
+    // stdin stays open so ffmpeg can be asked to quit cleanly; stdout
+    // carries its progress reports
+    let mut child = Command::new("ffmpeg")
       .args(&args)
-      .stdout(Stdio::null())
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
       .stderr(Stdio::piped())
       .spawn()
       .context("failed to spawn ffmpeg")?;
 
+    if let Some(stdout) = child.stdout.take() {
+      let id = self.config.id.clone();
+      let spawned =
+        ffmpeg_progress::spawn_reader(format!("progress-{}", id), stdout, move |sample| {
+          record_progress(&id, sample)
+        });
+      if let Err(e) = spawned {
+        warn!(id = %self.config.id, error = %e, "failed to read ffmpeg progress");
+      }
+    }
+
     // Store process handle
     self.process = Some(child);
 
@@ -114,14 +203,48 @@ impl RecordingPipeline {
     let mut args = vec![];
 
     // Input options
+    if self.encoding == RecordingEncoding::Copy {
+      // Cameras often omit timestamps that the muxer needs when nothing is
+      // re-encoded
+      args.push("-fflags".to_string());
+      args.push("+genpts".to_string());
+    }
     args.push("-i".to_string());
     args.push(source_uri.to_string());
 
-    // Codec settings - copy streams when possible for efficiency
-    args.push("-c:v".to_string());
-    args.push("copy".to_string());
-    args.push("-c:a".to_string());
-    args.push("copy".to_string());
+    match &self.encoding {
+      RecordingEncoding::Copy => {
+        // Remux the camera's bitstream; no decoding
+        args.push("-c:v".to_string());
+        args.push("copy".to_string());
+        args.push("-c:a".to_string());
+        args.push("copy".to_string());
+        if *format == RecordingFormat::Mp4 && self.source_codec.as_deref() == Some("hevc") {
+          // Tag H.265 the way browsers and Apple players expect in MP4
+          args.push("-tag:v".to_string());
+          args.push("hvc1".to_string());
+        }
+      }
+      RecordingEncoding::Transcode { crf, max_height } => {
+        args.push("-c:v".to_string());
+        args.push("libx264".to_string());
+        args.push("-preset".to_string());
+        args.push("veryfast".to_string());
+        args.push("-crf".to_string());
+        args.push(crf.unwrap_or(DEFAULT_CRF).min(51).to_string());
+        if let Some(height) = max_height {
+          args.push("-vf".to_string());
+          args.push(format!("scale=-2:'min(ih,{})'", height));
+        }
+        if *format == RecordingFormat::Hls {
+          // Keyframes on the segment boundaries
+          args.push("-force_key_frames".to_string());
+          args.push("expr:gte(t,n_forced*2)".to_string());
+        }
+        args.push("-c:a".to_string());
+        args.push("aac".to_string());
+      }
+    }
 
     // Format-specific options
     match format {
@@ -140,6 +263,9 @@ impl RecordingPipeline {
         args.push("2".to_string()); // 2 second segments
         args.push("-hls_list_size".to_string());
         args.push("0".to_string()); // Keep all segments
+        // Wall-clock segment times, used to match motion activity
+        args.push("-hls_flags".to_string());
+        args.push("program_date_time".to_string());
         args.push("-hls_segment_filename".to_string());
         let segment_pattern = self
           .output_path
@@ -170,20 +296,18 @@ impl RecordingPipeline {
   }
 
   async fn monitor_process(&mut self) -> Result<()> {
-    let process = self
-      .process
-      .as_mut()
-      .ok_or_else(|| anyhow!("no process running"))?;
-
     // Poll process status
     loop {
-      if self.stopped {
-        // Kill process if stopped
-        let _ = process.kill();
-        let _ = process.wait();
+      if self.stop.is_cancelled() {
+        self.finalize().await;
         return Ok(());
       }
 
+      let process = self
+        .process
+        .as_mut()
+        .ok_or_else(|| anyhow!("no process running"))?;
+
       // Check if process is still running
       match process.try_wait() {
         Ok(Some(status)) => {
@@ -195,7 +319,10 @@ impl RecordingPipeline {
         }
         Ok(None) => {
           // Process still running
-          tokio::time::sleep(Duration::from_millis(500)).await;
+          tokio::select! {
+            _ = self.stop.cancelled() => {}
+            _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+          }
         }
         Err(e) => {
           return Err(anyhow!("failed to check process status: {}", e));
@@ -204,33 +331,41 @@ impl RecordingPipeline {
     }
   }
 
-  pub async fn stop(&mut self) -> Result<()> {
-    info!(id = %self.config.id, "stopping recording pipeline");
-    self.stopped = true;
+  /// Ask FFmpeg to quit so it flushes the current segment and writes the
+  /// container trailer, killing it if it does not exit in time
+  async fn finalize(&mut self) {
+    let Some(mut process) = self.process.take() else {
+      return;
+    };
 
-    if let Some(mut process) = self.process.take() {
-      // Send SIGTERM to FFmpeg for graceful shutdown
-      let _ = process.kill();
+    // 'q' on stdin is FFmpeg's graceful quit; a killed MP4 has no moov atom
+    if let Some(mut stdin) = process.stdin.take() {
+      let _ = stdin.write_all(b"q");
+    }
 
-      // Wait for process to terminate
-      match tokio::time::timeout(Duration::from_secs(5), async {
-        loop {
-          if let Ok(Some(_)) = process.try_wait() {
-            break;
-          }
-          tokio::time::sleep(Duration::from_millis(100)).await;
-        }
-      })
-      .await
-      {
-        Ok(_) => info!(id = %self.config.id, "ffmpeg terminated gracefully"),
-        Err(_) => {
-          warn!(id = %self.config.id, "ffmpeg did not terminate, forcing kill");
-          let _ = process.kill();
+    match tokio::time::timeout(FINALIZE_TIMEOUT, async {
+      loop {
+        match process.try_wait() {
+          Ok(None) => tokio::time::sleep(Duration::from_millis(100)).await,
+          _ => break,
         }
       }
+    })
+    .await
+    {
+      Ok(_) => info!(id = %self.config.id, "ffmpeg finalized recording"),
+      Err(_) => {
+        warn!(id = %self.config.id, "ffmpeg did not finalize in time, forcing kill");
+        let _ = process.kill();
+        let _ = process.wait();
+      }
     }
+  }
 
+  pub async fn stop(&mut self) -> Result<()> {
+    info!(id = %self.config.id, "stopping recording pipeline");
+    self.stop.cancel();
+    self.finalize().await;
     Ok(())
   }
 
@@ -344,6 +479,68 @@ impl RecordingPipeline {
   }
 }
 
+/// Name of the source's first video codec, as ffprobe reports it
+pub(crate) async fn probe_video_codec(source_uri: &str) -> Result<String> {
+  let mut command = tokio::process::Command::new("ffprobe");
+  command.args(["-v", "error"]);
+  if source_uri.starts_with("rtsp://") || source_uri.starts_with("rtsps://") {
+    command.args(["-rtsp_transport", "tcp"]);
+  }
+  let output = tokio::time::timeout(
+    PROBE_TIMEOUT,
+    command
+      .args(["-select_streams", "v:0", "-show_entries", "stream=codec_name", "-of", "csv=p=0"])
+      .arg(source_uri)
+      .kill_on_drop(true)
+      .output(),
+  )
+  .await
+  .context("ffprobe timed out")?
+  .context("failed to run ffprobe")?;
+
+  if !output.status.success() {
+    return Err(anyhow!("ffprobe failed: {}", output.status));
+  }
+  let codec = String::from_utf8_lossy(&output.stdout).trim().to_string();
+  if codec.is_empty() {
+    return Err(anyhow!("source has no video stream"));
+  }
+  Ok(codec)
+}
+
+/// Export a recording's pipeline progress; `None` once its ffmpeg is gone
+fn record_progress(id: &str, sample: Option<&ProgressSample>) {
+  use telemetry::metrics::{
+    RECORDER_NODE_PIPELINE_FPS, RECORDER_NODE_PIPELINE_FRAMES, RECORDER_NODE_PIPELINE_LAG,
+    RECORDER_NODE_PIPELINE_SPEED,
+  };
+
+  let Some(sample) = sample else {
+    let _ = RECORDER_NODE_PIPELINE_SPEED.remove_label_values(&[id]);
+    let _ = RECORDER_NODE_PIPELINE_FPS.remove_label_values(&[id]);
+    let _ = RECORDER_NODE_PIPELINE_LAG.remove_label_values(&[id]);
+    for outcome in ["dropped", "duplicated"] {
+      let _ = RECORDER_NODE_PIPELINE_FRAMES.remove_label_values(&[id, outcome]);
+    }
+    return;
+  };
+  if let Some(speed) = sample.progress.speed {
+    RECORDER_NODE_PIPELINE_SPEED.with_label_values(&[id]).set(speed);
+  }
+  RECORDER_NODE_PIPELINE_FPS
+    .with_label_values(&[id])
+    .set(sample.progress.fps);
+  RECORDER_NODE_PIPELINE_LAG
+    .with_label_values(&[id])
+    .set(sample.lag_secs);
+  RECORDER_NODE_PIPELINE_FRAMES
+    .with_label_values(&[id, "dropped"])
+    .inc_by(sample.new_drop_frames);
+  RECORDER_NODE_PIPELINE_FRAMES
+    .with_label_values(&[id, "duplicated"])
+    .inc_by(sample.new_dup_frames);
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -356,6 +553,7 @@ mod tests {
       source_uri: Some("rtsp://example.com/stream".to_string()),
       retention_hours: None,
       format: Some(RecordingFormat::Mp4),
+      encoding: None,
     };
     let path = RecordingPipeline::generate_output_path(&config);
     assert!(path.to_string_lossy().contains("test-rec-1"));
@@ -370,6 +568,7 @@ mod tests {
       source_uri: Some("rtsp://example.com/stream".to_string()),
       retention_hours: None,
       format: Some(RecordingFormat::Hls),
+      encoding: None,
     };
     let path = RecordingPipeline::generate_output_path(&config);
     assert!(path.to_string_lossy().contains("test-rec-2"));
@@ -384,6 +583,7 @@ mod tests {
       source_uri: Some("rtsp://example.com/stream".to_string()),
       retention_hours: None,
       format: Some(RecordingFormat::Mp4),
+      encoding: None,
     };
     let pipeline = RecordingPipeline::new(config);
     let args = pipeline
@@ -406,6 +606,7 @@ mod tests {
       source_uri: Some("rtsp://example.com/stream".to_string()),
       retention_hours: None,
       format: Some(RecordingFormat::Hls),
+      encoding: None,
     };
     let pipeline = RecordingPipeline::new(config);
     let args = pipeline
@@ -416,5 +617,57 @@ mod tests {
     assert!(joined.contains("-i rtsp://example.com/stream"));
     assert!(joined.contains("-f hls"));
     assert!(joined.contains("-hls_time 2"));
+    assert!(joined.contains("-hls_flags program_date_time"));
+  }
+
+  #[test]
+  fn test_build_ffmpeg_args_copy_hevc_mp4() {
+    let config = RecordingConfig {
+      id: "test-rec-5".to_string(),
+      source_stream_id: None,
+      source_uri: Some("rtsp://example.com/stream".to_string()),
+      retention_hours: None,
+      format: Some(RecordingFormat::Mp4),
+      encoding: Some(RecordingEncoding::Copy),
+    };
+    let mut pipeline = RecordingPipeline::new(config);
+    pipeline.source_codec = Some("hevc".to_string());
+    let args = pipeline
+      .build_ffmpeg_args("rtsp://example.com/stream", &RecordingFormat::Mp4)
+      .unwrap();
+
+    let joined = args.join(" ");
+    assert!(joined.starts_with("-fflags +genpts -i rtsp://example.com/stream"));
+    assert!(joined.contains("-c:v copy"));
+    assert!(joined.contains("-tag:v hvc1"));
+    assert!(!joined.contains("libx264"));
+  }
+
+  #[test]
+  fn test_build_ffmpeg_args_transcode_hls() {
+    let config = RecordingConfig {
+      id: "test-rec-6".to_string(),
+      source_stream_id: None,
+      source_uri: Some("rtsp://example.com/stream".to_string()),
+      retention_hours: None,
+      format: Some(RecordingFormat::Hls),
+      encoding: Some(RecordingEncoding::Transcode {
+        crf: Some(28),
+        max_height: Some(720),
+      }),
+    };
+    let pipeline = RecordingPipeline::new(config);
+    assert_eq!(pipeline.encoding().as_str(), "transcode");
+    let args = pipeline
+      .build_ffmpeg_args("rtsp://example.com/stream", &RecordingFormat::Hls)
+      .unwrap();
+
+    let joined = args.join(" ");
+    assert!(joined.contains("-c:v libx264"));
+    assert!(joined.contains("-crf 28"));
+    assert!(joined.contains("scale=-2:'min(ih,720)'"));
+    assert!(joined.contains("-force_key_frames"));
+    assert!(joined.contains("-c:a aac"));
+    assert!(!joined.contains("-c:v copy"));
   }
 }
@@ -0,0 +1,777 @@
+//! Time-lapse videos of a camera over days or months
+//!
+//! `POST /v1/timelapses` queues a job for a camera and a time range, e.g.
+//! `{"camera_id": "site-north", "from": 1754006400, "to": 1756684800,
+//! "speedup": 3600, "windows": [{"days": ["mon", "tue", "wed", "thu", "fri"],
+//! "start": "07:00", "end": "18:00"}], "utc_offset_minutes": 120}`.
+//! The camera's recordings are found in the search index, cut down to the
+//! schedule windows (nights and weekends left out), and sampled at
+//! `fps / speedup` frames per second of footage; the frames are encoded to
+//! an MP4 playing at `fps`. Gaps without footage are skipped, not padded.
+//!
+//! Jobs run one at a time and are tracked in memory like anonymization
+//! jobs; videos stay in `TIMELAPSE_OUTPUT_DIR` until the job is deleted.
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::{
+  body::Body,
+  extract::{Path as RoutePath, State},
+  http::{header, StatusCode},
+  response::{IntoResponse, Response},
+  Json,
+};
+use chrono::{Datelike, NaiveTime, Timelike, Weekday};
+use common::search::{RecordingIndexEntry, RecordingSearchQuery};
+use common::tenancy::Tenant;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs;
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{error, info, warn};
+
+use crate::search::store::{SearchStore, MAX_SEARCH_LIMIT};
+
+/// ffmpeg gets this long per recording before it is killed
+const FFMPEG_TIMEOUT: Duration = Duration::from_secs(900);
+
+/// Longest range one job may cover
+const MAX_RANGE_SECS: i64 = 366 * 86_400;
+
+/// Most frames one video may have, about an hour at 30 fps
+const MAX_FRAMES: i64 = 108_000;
+
+const MAX_SPEEDUP: u32 = 604_800;
+const MAX_FPS: u32 = 60;
+
+/// With a frame sampled this rarely, only keyframes are decoded
+const KEYFRAME_ONLY_INTERVAL_SECS: f64 = 10.0;
+
+fn default_speedup() -> u32 {
+  600
+}
+
+/// Hours of the day footage is taken from, in the request's UTC offset
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleWindow {
+  /// Weekdays (`mon`, `tuesday`, ...); every day when empty
+  #[serde(default)]
+  pub days: Vec<String>,
+  /// `HH:MM`, inclusive
+  pub start: String,
+  /// `HH:MM`, exclusive and after `start`; `24:00` is the end of the day
+  pub end: String,
+}
+
+impl ScheduleWindow {
+  /// Weekdays and the window as seconds from local midnight
+  fn parse(&self) -> Result<(Vec<Weekday>, i64, i64), String> {
+    let days = self
+      .days
+      .iter()
+      .map(|day| day.parse::<Weekday>().map_err(|_| format!("unknown weekday '{}'", day)))
+      .collect::<Result<Vec<_>, _>>()?;
+    let start = seconds_of_day(&self.start)?;
+    let end = seconds_of_day(&self.end)?;
+    if end <= start {
+      return Err(format!(
+        "window {}-{} must end after it starts; split overnight windows in two",
+        self.start, self.end
+      ));
+    }
+    Ok((days, start, end))
+  }
+}
+
+fn seconds_of_day(time: &str) -> Result<i64, String> {
+  if time == "24:00" {
+    return Ok(86_400);
+  }
+  NaiveTime::parse_from_str(time, "%H:%M")
+    .map(|t| i64::from(t.num_seconds_from_midnight()))
+    .map_err(|_| format!("time '{}' is not HH:MM", time))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelapseRequest {
+  /// Camera, as the stream ID of its recordings
+  pub camera_id: String,
+  /// Range in Unix seconds
+  pub from: i64,
+  pub to: i64,
+  /// Seconds of footage per second of video
+  #[serde(default = "default_speedup")]
+  pub speedup: u32,
+  /// Only footage inside one of these windows is used; all of it when empty
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub windows: Vec<ScheduleWindow>,
+  /// Offset of the site's local time the windows are in, e.g. 120 for UTC+2
+  #[serde(default)]
+  pub utc_offset_minutes: i32,
+  /// Frame rate of the video; `TIMELAPSE_FPS` when unset
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub fps: Option<u32>,
+  /// Frame height in pixels, width following the aspect ratio; the
+  /// footage's own size when unset
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub height: Option<u32>,
+}
+
+impl TimelapseRequest {
+  pub fn validate(&self, default_fps: u32) -> Result<(), String> {
+    common::validation::validate_id(&self.camera_id, "camera_id").map_err(|e| e.to_string())?;
+    if self.to <= self.from {
+      return Err("to must be after from".to_string());
+    }
+    if self.to - self.from > MAX_RANGE_SECS {
+      return Err(format!("time-lapses are limited to {} days", MAX_RANGE_SECS / 86_400));
+    }
+    if !(2..=MAX_SPEEDUP).contains(&self.speedup) {
+      return Err(format!("speedup must be between 2 and {}", MAX_SPEEDUP));
+    }
+    if let Some(fps) = self.fps {
+      if !(1..=MAX_FPS).contains(&fps) {
+        return Err(format!("fps must be between 1 and {}", MAX_FPS));
+      }
+    }
+    if let Some(height) = self.height {
+      if !(144..=2160).contains(&height) || height % 2 != 0 {
+        return Err("height must be an even number between 144 and 2160".to_string());
+      }
+    }
+    if !(-720..=840).contains(&self.utc_offset_minutes) {
+      return Err("utc_offset_minutes must be between -720 and 840".to_string());
+    }
+    for window in &self.windows {
+      window.parse()?;
+    }
+    let frames = (self.to - self.from) * i64::from(self.fps.unwrap_or(default_fps)) / i64::from(self.speedup);
+    if frames > MAX_FRAMES {
+      return Err(format!(
+        "the range would give up to {} frames, more than {}; raise speedup or shorten the range",
+        frames, MAX_FRAMES
+      ));
+    }
+    Ok(())
+  }
+
+  /// Parts of the range inside the schedule windows, sorted and disjoint
+  fn ranges(&self) -> Vec<(i64, i64)> {
+    if self.windows.is_empty() {
+      return vec![(self.from, self.to)];
+    }
+    let windows: Vec<_> = self.windows.iter().filter_map(|w| w.parse().ok()).collect();
+    let offset = i64::from(self.utc_offset_minutes) * 60;
+    let first_day = (self.from + offset).div_euclid(86_400);
+    let last_day = (self.to + offset).div_euclid(86_400);
+
+    let mut ranges = Vec::new();
+    for day in first_day..=last_day {
+      let midnight = day * 86_400;
+      let Some(weekday) = chrono::DateTime::from_timestamp(midnight, 0).map(|d| d.weekday()) else {
+        continue;
+      };
+      for (days, start, end) in &windows {
+        if !days.is_empty() && !days.contains(&weekday) {
+          continue;
+        }
+        let start = (midnight + start - offset).max(self.from);
+        let end = (midnight + end - offset).min(self.to);
+        if start < end {
+          ranges.push((start, end));
+        }
+      }
+    }
+    merge(ranges)
+  }
+}
+
+fn merge(mut ranges: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+  ranges.sort();
+  let mut merged: Vec<(i64, i64)> = Vec::new();
+  for (start, end) in ranges {
+    match merged.last_mut() {
+      Some(last) if start <= last.1 => last.1 = last.1.max(end),
+      _ => merged.push((start, end)),
+    }
+  }
+  merged
+}
+
+/// Part of a recording to sample
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+  recording_id: String,
+  path: String,
+  /// Seconds from the start of the recording
+  offset_secs: i64,
+  duration_secs: i64,
+}
+
+/// Cut recordings, sorted by start, down to `ranges`; time covered by more
+/// than one recording is taken from the first
+fn segments(recordings: &[RecordingIndexEntry], ranges: &[(i64, i64)]) -> Vec<Segment> {
+  let mut segments = Vec::new();
+  let mut covered_until = i64::MIN;
+  for recording in recordings {
+    let Some(path) = &recording.storage_path else {
+      continue;
+    };
+    // Recordings still being written have no end yet
+    let Some(stopped_at) = recording
+      .stopped_at
+      .or_else(|| recording.duration_secs.map(|d| recording.started_at + i64::from(d)))
+    else {
+      continue;
+    };
+    for &(from, to) in ranges {
+      let start = recording.started_at.max(from).max(covered_until);
+      let end = stopped_at.min(to);
+      if start < end {
+        segments.push(Segment {
+          recording_id: recording.recording_id.clone(),
+          path: path.clone(),
+          offset_secs: start - recording.started_at,
+          duration_secs: end - start,
+        });
+      }
+    }
+    covered_until = covered_until.max(stopped_at);
+  }
+  segments
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelapseState {
+  Queued,
+  Running,
+  Completed,
+  Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelapseJob {
+  pub id: String,
+  pub tenant_id: String,
+  #[serde(flatten)]
+  pub request: TimelapseRequest,
+  pub state: TimelapseState,
+  /// Recording parts inside the range and windows, and how many are sampled
+  pub segments_total: u64,
+  pub segments_processed: u64,
+  pub frames: u64,
+  /// Seconds of footage sampled
+  pub footage_secs: i64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub output_bytes: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+  /// Unix timestamps in milliseconds
+  pub created_at: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub completed_at: Option<u64>,
+}
+
+/// Runs time-lapse jobs one at a time and keeps track of them
+pub struct Timelapser {
+  store: Arc<dyn SearchStore>,
+  output_dir: PathBuf,
+  default_fps: u32,
+  jobs: RwLock<HashMap<String, TimelapseJob>>,
+  running: Semaphore,
+}
+
+impl Timelapser {
+  /// Configured from `TIMELAPSE_OUTPUT_DIR` and `TIMELAPSE_FPS`
+  pub fn from_env(store: Arc<dyn SearchStore>) -> Self {
+    let output_dir = std::env::var("TIMELAPSE_OUTPUT_DIR").unwrap_or_else(|_| "./data/timelapses".to_string());
+    let default_fps = std::env::var("TIMELAPSE_FPS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .filter(|fps| (1..=MAX_FPS).contains(fps))
+      .unwrap_or(30);
+    Self::new(store, PathBuf::from(output_dir), default_fps)
+  }
+
+  pub fn new(store: Arc<dyn SearchStore>, output_dir: PathBuf, default_fps: u32) -> Self {
+    Self {
+      store,
+      output_dir,
+      default_fps,
+      jobs: RwLock::new(HashMap::new()),
+      running: Semaphore::new(1),
+    }
+  }
+
+  /// Queue a job for the tenant's recordings of the camera; it runs in the
+  /// background
+  pub async fn submit(self: &Arc<Self>, tenant_id: String, request: TimelapseRequest) -> Result<TimelapseJob> {
+    request.validate(self.default_fps).map_err(|e| anyhow!(e))?;
+    let job = TimelapseJob {
+      id: uuid::Uuid::new_v4().to_string(),
+      tenant_id,
+      request,
+      state: TimelapseState::Queued,
+      segments_total: 0,
+      segments_processed: 0,
+      frames: 0,
+      footage_secs: 0,
+      output_bytes: None,
+      error: None,
+      created_at: now_ms(),
+      completed_at: None,
+    };
+    self.jobs.write().await.insert(job.id.clone(), job.clone());
+
+    let timelapser = self.clone();
+    let id = job.id.clone();
+    tokio::spawn(async move { timelapser.run(&id).await });
+    Ok(job)
+  }
+
+  pub async fn get(&self, id: &str) -> Option<TimelapseJob> {
+    self.jobs.read().await.get(id).cloned()
+  }
+
+  /// Newest first
+  pub async fn list(&self) -> Vec<TimelapseJob> {
+    let mut jobs: Vec<_> = self.jobs.read().await.values().cloned().collect();
+    jobs.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+    jobs
+  }
+
+  /// Video of a completed job
+  pub async fn output(&self, id: &str) -> Option<PathBuf> {
+    let job = self.get(id).await?;
+    (job.state == TimelapseState::Completed).then(|| self.output_path(id))
+  }
+
+  /// Forget a finished job and delete its video
+  pub async fn remove(&self, id: &str) -> Result<bool> {
+    let mut jobs = self.jobs.write().await;
+    match jobs.get(id).map(|job| job.state) {
+      None => return Ok(false),
+      Some(TimelapseState::Queued | TimelapseState::Running) => bail!("job '{}' has not finished", id),
+      Some(_) => {}
+    }
+    jobs.remove(id);
+    match fs::remove_file(self.output_path(id)).await {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).context("failed to delete time-lapse"),
+      _ => Ok(true),
+    }
+  }
+
+  fn output_path(&self, id: &str) -> PathBuf {
+    self.output_dir.join(format!("{}.mp4", id))
+  }
+
+  async fn update(&self, id: &str, f: impl FnOnce(&mut TimelapseJob)) -> Option<TimelapseJob> {
+    let mut jobs = self.jobs.write().await;
+    let job = jobs.get_mut(id)?;
+    f(job);
+    Some(job.clone())
+  }
+
+  async fn run(&self, id: &str) {
+    let Ok(_permit) = self.running.acquire().await else {
+      return;
+    };
+    let Some(job) = self.update(id, |job| job.state = TimelapseState::Running).await else {
+      return;
+    };
+
+    let work_dir = std::env::temp_dir().join(format!("timelapse-{}", id));
+    let result = self.generate(&job, &work_dir).await;
+    let _ = fs::remove_dir_all(&work_dir).await;
+
+    let status = if result.is_ok() { "success" } else { "error" };
+    telemetry::metrics::RECORDER_NODE_TIMELAPSES.with_label_values(&[status]).inc();
+    let job = self
+      .update(id, |job| {
+        job.completed_at = Some(now_ms());
+        match &result {
+          Ok(bytes) => {
+            job.state = TimelapseState::Completed;
+            job.output_bytes = Some(*bytes);
+          }
+          Err(e) => {
+            job.state = TimelapseState::Failed;
+            job.error = Some(format!("{:#}", e));
+          }
+        }
+      })
+      .await;
+
+    match (result, job) {
+      (Ok(_), Some(job)) => info!(
+        job_id = %id,
+        camera_id = %job.request.camera_id,
+        frames = job.frames,
+        footage_secs = job.footage_secs,
+        "time-lapse completed"
+      ),
+      (Err(e), _) => error!(job_id = %id, error = %e, "time-lapse failed"),
+      (Ok(_), None) => {}
+    }
+  }
+
+  /// Recordings of the job's camera overlapping its range, oldest first
+  async fn recordings(&self, job: &TimelapseJob) -> Result<Vec<RecordingIndexEntry>> {
+    let mut query = RecordingSearchQuery {
+      query: None,
+      tenant_id: Some(job.tenant_id.clone()),
+      device_id: Some(job.request.camera_id.clone()),
+      zone: None,
+      state: None,
+      started_after: None,
+      started_before: Some(job.request.to),
+      stopped_after: Some(job.request.from),
+      stopped_before: None,
+      min_duration_secs: None,
+      max_duration_secs: None,
+      tags: None,
+      labels: None,
+      classes: None,
+      detected_after: None,
+      detected_before: None,
+      min_confidence: None,
+      offset: 0,
+      limit: MAX_SEARCH_LIMIT,
+      sort_by: "started_at".to_string(),
+      sort_order: "asc".to_string(),
+    };
+    let mut recordings = Vec::new();
+    loop {
+      let page = self.store.search_recordings(&query).await?;
+      let count = page.recordings.len() as i32;
+      recordings.extend(page.recordings);
+      query.offset += count;
+      if count < query.limit || i64::from(query.offset) >= page.total {
+        return Ok(recordings);
+      }
+    }
+  }
+
+  /// Produce the video and return its size
+  async fn generate(&self, job: &TimelapseJob, work_dir: &Path) -> Result<u64> {
+    let request = &job.request;
+    let recordings = self.recordings(job).await.context("failed to search recordings")?;
+    let segments = segments(&recordings, &request.ranges());
+    if segments.is_empty() {
+      bail!("no recordings of camera '{}' in the range and windows", request.camera_id);
+    }
+    self
+      .update(&job.id, |job| job.segments_total = segments.len() as u64)
+      .await;
+
+    let frames_dir = work_dir.join("frames");
+    fs::create_dir_all(&frames_dir).await.context("failed to create work directory")?;
+    let fps = request.fps.unwrap_or(self.default_fps);
+    // One frame per this many seconds of footage
+    let interval = f64::from(request.speedup) / f64::from(fps);
+    let mut filter = format!("fps=fps={}/{}", fps, request.speedup);
+    if let Some(height) = request.height {
+      filter.push_str(&format!(",scale=-2:{}", height));
+    }
+
+    for (index, segment) in segments.iter().enumerate() {
+      let pattern = frames_dir.join(format!("{:05}_%06d.jpg", index));
+      let mut args = Vec::new();
+      if interval >= KEYFRAME_ONLY_INTERVAL_SECS {
+        args.extend(["-skip_frame".to_string(), "nokey".to_string()]);
+      }
+      args.extend([
+        "-ss".to_string(),
+        segment.offset_secs.to_string(),
+        "-t".to_string(),
+        segment.duration_secs.to_string(),
+        "-i".to_string(),
+        segment.path.clone(),
+        "-an".to_string(),
+        "-vf".to_string(),
+        filter.clone(),
+        "-q:v".to_string(),
+        "3".to_string(),
+        pattern.display().to_string(),
+      ]);
+      // A missing or damaged recording leaves a gap, not a failed job
+      if let Err(e) = ffmpeg(&args).await {
+        warn!(job_id = %job.id, recording_id = %segment.recording_id, error = %e, "skipping recording in time-lapse");
+      }
+      let frames = count_frames(&frames_dir).await?;
+      self
+        .update(&job.id, |job| {
+          job.segments_processed = index as u64 + 1;
+          job.frames = frames;
+          job.footage_secs += segment.duration_secs;
+        })
+        .await;
+    }
+
+    // The image sequence demuxer needs numbers without gaps
+    let sequence_dir = work_dir.join("sequence");
+    fs::create_dir_all(&sequence_dir).await?;
+    let mut frames = Vec::new();
+    let mut entries = fs::read_dir(&frames_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+      frames.push(entry.path());
+    }
+    frames.sort();
+    if frames.is_empty() {
+      bail!("no frames could be read from the recordings");
+    }
+    for (index, frame) in frames.iter().enumerate() {
+      fs::rename(frame, sequence_dir.join(format!("{:06}.jpg", index))).await?;
+    }
+
+    fs::create_dir_all(&self.output_dir)
+      .await
+      .context("failed to create output directory")?;
+    let output = self.output_path(&job.id);
+    ffmpeg(&[
+      "-y".to_string(),
+      "-framerate".to_string(),
+      fps.to_string(),
+      "-i".to_string(),
+      sequence_dir.join("%06d.jpg").display().to_string(),
+      "-c:v".to_string(),
+      "libx264".to_string(),
+      "-pix_fmt".to_string(),
+      "yuv420p".to_string(),
+      // H.264 in yuv420p needs even dimensions
+      "-vf".to_string(),
+      "scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string(),
+      "-movflags".to_string(),
+      "+faststart".to_string(),
+      output.display().to_string(),
+    ])
+    .await
+    .context("failed to encode time-lapse")?;
+
+    Ok(fs::metadata(&output).await?.len())
+  }
+}
+
+async fn count_frames(dir: &Path) -> Result<u64> {
+  let mut count = 0;
+  let mut entries = fs::read_dir(dir).await?;
+  while entries.next_entry().await?.is_some() {
+    count += 1;
+  }
+  Ok(count)
+}
+
+async fn ffmpeg(args: &[String]) -> Result<()> {
+  let output = tokio::time::timeout(
+    FFMPEG_TIMEOUT,
+    tokio::process::Command::new("ffmpeg")
+      .args(["-v", "error"])
+      .args(args)
+      .stdin(Stdio::null())
+      .kill_on_drop(true)
+      .output(),
+  )
+  .await
+  .map_err(|_| anyhow!("ffmpeg exceeded {}s", FFMPEG_TIMEOUT.as_secs()))?
+  .context("failed to spawn ffmpeg")?;
+  if !output.status.success() {
+    bail!(
+      "ffmpeg exited with {}: {}",
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    );
+  }
+  Ok(())
+}
+
+fn now_ms() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_millis() as u64
+}
+
+/// Load a job of the caller's tenant
+async fn load_job(timelapser: &Timelapser, tenant: &Tenant, id: &str) -> Result<TimelapseJob, StatusCode> {
+  match timelapser.get(id).await {
+    Some(job) if tenant.can_access(&job.tenant_id) => Ok(job),
+    _ => Err(StatusCode::NOT_FOUND),
+  }
+}
+
+/// Queue a time-lapse of a camera
+pub async fn create_timelapse(
+  State(timelapser): State<Arc<Timelapser>>,
+  tenant: Tenant,
+  Json(request): Json<TimelapseRequest>,
+) -> Response {
+  match timelapser.submit(tenant.tenant_id.clone(), request).await {
+    Ok(job) => {
+      info!(job_id = %job.id, camera_id = %job.request.camera_id, "time-lapse queued");
+      (StatusCode::ACCEPTED, Json(job)).into_response()
+    }
+    Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+  }
+}
+
+/// Jobs of the caller's tenant, newest first
+pub async fn list_timelapses(State(timelapser): State<Arc<Timelapser>>, tenant: Tenant) -> Json<Vec<TimelapseJob>> {
+  let jobs = timelapser.list().await;
+  Json(jobs.into_iter().filter(|job| tenant.can_access(&job.tenant_id)).collect())
+}
+
+pub async fn get_timelapse(
+  State(timelapser): State<Arc<Timelapser>>,
+  tenant: Tenant,
+  RoutePath(id): RoutePath<String>,
+) -> Result<Json<TimelapseJob>, StatusCode> {
+  load_job(&timelapser, &tenant, &id).await.map(Json)
+}
+
+/// Forget a finished job and delete its video; 409 while it runs
+pub async fn delete_timelapse(
+  State(timelapser): State<Arc<Timelapser>>,
+  tenant: Tenant,
+  RoutePath(id): RoutePath<String>,
+) -> StatusCode {
+  if let Err(status) = load_job(&timelapser, &tenant, &id).await {
+    return status;
+  }
+  match timelapser.remove(&id).await {
+    Ok(true) => StatusCode::NO_CONTENT,
+    Ok(false) => StatusCode::NOT_FOUND,
+    Err(e) => {
+      info!(job_id = %id, error = %e, "time-lapse not deleted");
+      StatusCode::CONFLICT
+    }
+  }
+}
+
+/// Download the video of a completed job
+pub async fn download_timelapse(
+  State(timelapser): State<Arc<Timelapser>>,
+  tenant: Tenant,
+  RoutePath(id): RoutePath<String>,
+) -> Result<Response, StatusCode> {
+  let job = load_job(&timelapser, &tenant, &id).await?;
+  let path = timelapser.output(&id).await.ok_or(StatusCode::NOT_FOUND)?;
+  let file = fs::File::open(&path).await.map_err(|e| {
+    error!(job_id = %id, error = %e, "failed to open time-lapse");
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+  Ok(
+    (
+      [
+        (header::CONTENT_TYPE, "video/mp4".to_string()),
+        (
+          header::CONTENT_DISPOSITION,
+          format!("attachment; filename=\"timelapse-{}-{}.mp4\"", job.request.camera_id, id),
+        ),
+      ],
+      Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+    )
+      .into_response(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn request(windows: Vec<ScheduleWindow>) -> TimelapseRequest {
+    serde_json::from_value(serde_json::json!({
+      "camera_id": "site-north",
+      // Monday 2025-09-01 00:00 UTC to Monday 2025-09-08 00:00 UTC
+      "from": 1756684800,
+      "to": 1757289600,
+      "windows": windows,
+    }))
+    .unwrap()
+  }
+
+  fn window(days: &[&str], start: &str, end: &str) -> ScheduleWindow {
+    ScheduleWindow {
+      days: days.iter().map(|d| d.to_string()).collect(),
+      start: start.to_string(),
+      end: end.to_string(),
+    }
+  }
+
+  fn recording(id: &str, started_at: i64, stopped_at: Option<i64>) -> RecordingIndexEntry {
+    serde_json::from_value(serde_json::json!({
+      "id": id, "recording_id": id, "tenant_id": null, "device_id": "site-north",
+      "device_name": null, "zone": null, "location": null,
+      "started_at": started_at, "stopped_at": stopped_at, "duration_secs": null,
+      "resolution": null, "video_codec": null, "audio_codec": null, "file_size_bytes": null,
+      "storage_path": format!("/recordings/{}/recording.mp4", id),
+      "state": "Stopped", "indexed_at": 0, "updated_at": 0,
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn requests_are_validated() {
+    assert!(request(vec![]).validate(30).is_ok());
+    assert_eq!(request(vec![]).speedup, 600);
+
+    let mut bad = request(vec![window(&["mon"], "18:00", "07:00")]);
+    assert!(bad.validate(30).unwrap_err().contains("overnight"));
+    bad.windows = vec![window(&["someday"], "07:00", "18:00")];
+    assert!(bad.validate(30).is_err());
+
+    // A week at 60x and 30 fps is 302,400 frames
+    let mut long = request(vec![]);
+    long.speedup = 60;
+    assert!(long.validate(30).unwrap_err().contains("raise speedup"));
+    long.speedup = 3600;
+    assert!(long.validate(30).is_ok());
+    long.height = Some(721);
+    assert!(long.validate(30).is_err());
+  }
+
+  #[test]
+  fn windows_select_local_working_hours() {
+    let mut request = request(vec![window(&["mon", "Friday"], "07:00", "18:00")]);
+    request.utc_offset_minutes = 120;
+    let monday = 1756684800;
+    let friday = monday + 4 * 86_400;
+    // 07:00-18:00 at UTC+2 is 05:00-16:00 UTC
+    assert_eq!(
+      request.ranges(),
+      vec![
+        (monday + 5 * 3600, monday + 16 * 3600),
+        (friday + 5 * 3600, friday + 16 * 3600),
+      ]
+    );
+
+    // Overlapping windows merge, the range bounds them
+    request.windows = vec![window(&[], "06:00", "12:00"), window(&[], "10:00", "24:00")];
+    request.utc_offset_minutes = 0;
+    request.to = monday + 8 * 3600;
+    assert_eq!(request.ranges(), vec![(monday + 6 * 3600, monday + 8 * 3600)]);
+  }
+
+  #[test]
+  fn recordings_are_cut_to_ranges_without_overlap() {
+    let recordings = vec![
+      recording("a", 0, Some(1000)),
+      // Overlaps the end of "a"
+      recording("b", 900, Some(2000)),
+      // Still recording
+      recording("c", 2000, None),
+    ];
+    let ranges = vec![(500, 950), (1500, 3000)];
+    let cut = segments(&recordings, &ranges);
+    assert_eq!(
+      cut.iter()
+        .map(|s| (s.recording_id.as_str(), s.offset_secs, s.duration_secs))
+        .collect::<Vec<_>>(),
+      vec![("a", 500, 450), ("b", 600, 500)]
+    );
+    assert_eq!(cut[0].path, "/recordings/a/recording.mp4");
+  }
+}
@@ -4,6 +4,7 @@ use axum::{
   Json,
 };
 use common::retention::*;
+use common::tenancy::Tenant;
 use std::sync::Arc;
 use tracing::{error, info};
 
@@ -15,11 +16,48 @@ pub struct RetentionApiState {
   pub executor: Arc<RetentionExecutor>,
 }
 
+/// Cross-tenant operations are reserved for system admins
+fn require_system_admin(tenant: &Tenant) -> Result<(), StatusCode> {
+  if tenant.is_system_admin {
+    Ok(())
+  } else {
+    Err(StatusCode::FORBIDDEN)
+  }
+}
+
+/// Load a policy visible to the caller. Global policies (no tenant) are
+/// readable by everyone but only system admins may modify them; policies of
+/// other tenants are reported as missing.
+async fn load_policy(
+  state: &RetentionApiState,
+  tenant: &Tenant,
+  policy_id: &str,
+  modify: bool,
+) -> Result<RetentionPolicy, StatusCode> {
+  let policy = match state.store.get_policy(policy_id).await {
+    Ok(Some(policy)) => policy,
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!(error = %e, "failed to get retention policy");
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  };
+
+  match policy.tenant_id.as_deref() {
+    Some(owner) if tenant.can_access(owner) => Ok(policy),
+    None if !modify || tenant.is_system_admin => Ok(policy),
+    None => Err(StatusCode::FORBIDDEN),
+    Some(_) => Err(StatusCode::NOT_FOUND),
+  }
+}
+
 /// Create a new retention policy
 pub async fn create_policy(
   State(state): State<Arc<RetentionApiState>>,
-  Json(req): Json<CreateRetentionPolicyRequest>,
+  tenant: Tenant,
+  Json(mut req): Json<CreateRetentionPolicyRequest>,
 ) -> Result<Json<RetentionPolicy>, StatusCode> {
+  req.tenant_id = tenant.filter(req.tenant_id.as_deref());
   info!(
     policy_name = %req.name,
     policy_type = ?req.policy_type,
@@ -41,23 +79,18 @@ pub async fn create_policy(
 /// Get a specific retention policy
 pub async fn get_policy(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
   Path(policy_id): Path<String>,
 ) -> Result<Json<RetentionPolicy>, StatusCode> {
-  match state.store.get_policy(&policy_id).await {
-    Ok(Some(policy)) => Ok(Json(policy)),
-    Ok(None) => Err(StatusCode::NOT_FOUND),
-    Err(e) => {
-      error!(error = %e, "failed to get retention policy");
-      Err(StatusCode::INTERNAL_SERVER_ERROR)
-    }
-  }
+  load_policy(&state, &tenant, &policy_id, false).await.map(Json)
 }
 
 /// List all retention policies
 pub async fn list_policies(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
 ) -> Result<Json<ListPoliciesResponse>, StatusCode> {
-  match state.store.list_policies(None).await {
+  match state.store.list_policies(tenant.filter(None).as_deref()).await {
     Ok(policies) => Ok(Json(ListPoliciesResponse { policies })),
     Err(e) => {
       error!(error = %e, "failed to list retention policies");
@@ -69,10 +102,12 @@ pub async fn list_policies(
 /// Update a retention policy
 pub async fn update_policy(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
   Path(policy_id): Path<String>,
   Json(req): Json<UpdateRetentionPolicyRequest>,
 ) -> Result<Json<RetentionPolicy>, StatusCode> {
   info!(policy_id = %policy_id, "updating retention policy");
+  load_policy(&state, &tenant, &policy_id, true).await?;
 
   match state.store.update_policy(&policy_id, req).await {
     Ok(policy) => {
@@ -89,9 +124,11 @@ pub async fn update_policy(
 /// Delete a retention policy
 pub async fn delete_policy(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
   Path(policy_id): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
   info!(policy_id = %policy_id, "deleting retention policy");
+  load_policy(&state, &tenant, &policy_id, true).await?;
 
   match state.store.delete_policy(&policy_id).await {
     Ok(true) => {
@@ -109,9 +146,11 @@ pub async fn delete_policy(
 /// Execute a specific retention policy
 pub async fn execute_policy(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
   Path(policy_id): Path<String>,
 ) -> Result<Json<ExecutePolicyResponse>, StatusCode> {
   info!(policy_id = %policy_id, "executing retention policy");
+  load_policy(&state, &tenant, &policy_id, true).await?;
 
   match state.executor.execute_policy(&policy_id).await {
     Ok(execution) => {
@@ -132,10 +171,47 @@ pub async fn execute_policy(
   }
 }
 
+/// Show what a retention policy would do without executing it
+pub async fn preview_policy(
+  State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
+  Path(policy_id): Path<String>,
+) -> Result<Json<RetentionPreview>, StatusCode> {
+  info!(policy_id = %policy_id, "previewing retention policy");
+  load_policy(&state, &tenant, &policy_id, false).await?;
+
+  match state.executor.preview_policy(&policy_id).await {
+    Ok(preview) => Ok(Json(preview)),
+    Err(e) => {
+      error!(policy_id = %policy_id, error = %e, "failed to preview retention policy");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Latest preview of a retention policy
+pub async fn get_preview(
+  State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
+  Path(policy_id): Path<String>,
+) -> Result<Json<RetentionPreview>, StatusCode> {
+  load_policy(&state, &tenant, &policy_id, false).await?;
+  match state.store.latest_preview(&policy_id).await {
+    Ok(Some(preview)) => Ok(Json(preview)),
+    Ok(None) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!(policy_id = %policy_id, error = %e, "failed to get retention preview");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
 /// Execute all enabled retention policies
 pub async fn execute_all_policies(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
 ) -> Result<Json<ListExecutionsResponse>, StatusCode> {
+  require_system_admin(&tenant)?;
   info!("executing all enabled retention policies");
 
   match state.executor.execute_all_policies().await {
@@ -156,23 +232,37 @@ pub async fn execute_all_policies(
 /// Get a specific retention execution
 pub async fn get_execution(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
   Path(execution_id): Path<String>,
 ) -> Result<Json<RetentionExecution>, StatusCode> {
-  match state.store.get_execution(&execution_id).await {
-    Ok(Some(execution)) => Ok(Json(execution)),
-    Ok(None) => Err(StatusCode::NOT_FOUND),
+  load_execution(&state, &tenant, &execution_id).await.map(Json)
+}
+
+/// Load an execution whose policy is visible to the caller
+async fn load_execution(
+  state: &RetentionApiState,
+  tenant: &Tenant,
+  execution_id: &str,
+) -> Result<RetentionExecution, StatusCode> {
+  let execution = match state.store.get_execution(execution_id).await {
+    Ok(Some(execution)) => execution,
+    Ok(None) => return Err(StatusCode::NOT_FOUND),
     Err(e) => {
       error!(error = %e, "failed to get retention execution");
-      Err(StatusCode::INTERNAL_SERVER_ERROR)
+      return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
-  }
+  };
+  load_policy(state, tenant, &execution.policy_id, false).await?;
+  Ok(execution)
 }
 
 /// List retention executions for a policy
 pub async fn list_executions(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
   Path(policy_id): Path<String>,
 ) -> Result<Json<ListExecutionsResponse>, StatusCode> {
+  load_policy(&state, &tenant, &policy_id, false).await?;
   match state.store.list_executions(Some(&policy_id)).await {
     Ok(executions) => Ok(Json(ListExecutionsResponse { executions })),
     Err(e) => {
@@ -185,7 +275,9 @@ pub async fn list_executions(
 /// List all retention executions
 pub async fn list_all_executions(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
 ) -> Result<Json<ListExecutionsResponse>, StatusCode> {
+  require_system_admin(&tenant)?;
   match state.store.list_executions(None).await {
     Ok(executions) => Ok(Json(ListExecutionsResponse { executions })),
     Err(e) => {
@@ -198,8 +290,10 @@ pub async fn list_all_executions(
 /// List retention actions for an execution
 pub async fn list_actions(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
   Path(execution_id): Path<String>,
 ) -> Result<Json<ListActionsResponse>, StatusCode> {
+  load_execution(&state, &tenant, &execution_id).await?;
   match state.store.list_actions(&execution_id).await {
     Ok(actions) => Ok(Json(ListActionsResponse { actions })),
     Err(e) => {
@@ -212,8 +306,9 @@ pub async fn list_actions(
 /// Get storage statistics
 pub async fn get_storage_stats(
   State(state): State<Arc<RetentionApiState>>,
+  tenant: Tenant,
 ) -> Result<Json<StorageStatsResponse>, StatusCode> {
-  match state.store.get_storage_stats(None, None).await {
+  match state.store.get_storage_stats(tenant.filter(None).as_deref(), None).await {
     Ok(statistics) => Ok(Json(StorageStatsResponse { statistics })),
     Err(e) => {
       error!(error = %e, "failed to get storage statistics");
@@ -30,6 +30,10 @@ pub trait RetentionStore: Send + Sync {
   async fn update_action(&self, action: &RetentionAction) -> Result<()>;
   async fn list_actions(&self, execution_id: &str) -> Result<Vec<RetentionAction>>;
 
+  // Previews
+  async fn save_preview(&self, preview: &RetentionPreview) -> Result<()>;
+  async fn latest_preview(&self, policy_id: &str) -> Result<Option<RetentionPreview>>;
+
   // Storage statistics
   async fn update_storage_stats(&self, stats: &StorageStatistics) -> Result<()>;
   async fn get_storage_stats(
@@ -543,6 +547,53 @@ impl RetentionStore for PostgresRetentionStore {
     rows.into_iter().map(Self::map_action_row).collect()
   }
 
+  async fn save_preview(&self, preview: &RetentionPreview) -> Result<()> {
+    let id = Uuid::parse_str(&preview.id)?;
+    let policy_uuid = Uuid::parse_str(&preview.policy_id)?;
+    let generated_at = chrono::DateTime::from_timestamp(preview.generated_at, 0);
+
+    sqlx::query(
+      r#"
+      INSERT INTO retention_previews
+        (id, policy_id, recordings_to_delete, bytes_to_free, recordings_to_move,
+         bytes_to_move, report, generated_at)
+      VALUES ($1, $2, $3, $4, $5, $6, $7, COALESCE($8, NOW()))
+      "#,
+    )
+    .bind(id)
+    .bind(policy_uuid)
+    .bind(preview.recordings_to_delete)
+    .bind(preview.bytes_to_free)
+    .bind(preview.recordings_to_move)
+    .bind(preview.bytes_to_move)
+    .bind(serde_json::to_value(preview)?)
+    .bind(generated_at)
+    .execute(&self.pool)
+    .await?;
+
+    Ok(())
+  }
+
+  async fn latest_preview(&self, policy_id: &str) -> Result<Option<RetentionPreview>> {
+    use sqlx::Row;
+
+    let uuid = Uuid::parse_str(policy_id)?;
+    let row = sqlx::query(
+      "SELECT report FROM retention_previews WHERE policy_id = $1 ORDER BY generated_at DESC LIMIT 1",
+    )
+    .bind(uuid)
+    .fetch_optional(&self.pool)
+    .await?;
+
+    match row {
+      Some(r) => {
+        let report: serde_json::Value = r.try_get("report")?;
+        Ok(Some(serde_json::from_value(report)?))
+      }
+      None => Ok(None),
+    }
+  }
+
   async fn update_storage_stats(&self, stats: &StorageStatistics) -> Result<()> {
     let tenant_uuid = stats
       .tenant_id
@@ -1,9 +1,17 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+  extract::{Path, Query, State},
+  http::StatusCode,
+  Json,
+};
 use common::search::*;
+use common::tenancy::Tenant;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{error, info};
 use super::store::SearchStore;
 use super::indexer::SearchIndexer;
+use super::changes;
 
 pub struct SearchApiState {
   pub store: Arc<dyn SearchStore>,
@@ -12,9 +20,11 @@ pub struct SearchApiState {
 
 pub async fn search_recordings(
   State(state): State<Arc<SearchApiState>>,
-  Json(query): Json<RecordingSearchQuery>,
+  tenant: Tenant,
+  Json(mut query): Json<RecordingSearchQuery>,
 ) -> Result<Json<RecordingSearchResponse>, StatusCode> {
   info!("searching recordings");
+  tenant.scope(&mut query);
   match state.store.search_recordings(&query).await {
     Ok(response) => Ok(Json(response)),
     Err(e) => {
@@ -24,11 +34,103 @@ pub async fn search_recordings(
   }
 }
 
+/// Query string of `GET /v1/search/recordings`, e.g.
+/// `?class=person&device_id=camera-3&from=1718157600&to=1718164800`.
+/// With `class` set, `from`/`to` bound the detections rather than the
+/// recording and results rank by detection count unless `sort_by` is given.
+#[derive(Debug, Default, Deserialize)]
+pub struct RecordingSearchParams {
+  /// Comma-separated detection classes, any of which must appear
+  pub class: Option<String>,
+  pub q: Option<String>,
+  pub device_id: Option<String>,
+  pub zone: Option<String>,
+  pub state: Option<String>,
+  /// Comma-separated tags, any of which must be set
+  pub tag: Option<String>,
+  /// Comma-separated `key:value` labels, all of which must be set
+  pub label: Option<String>,
+  /// Unix seconds
+  pub from: Option<i64>,
+  pub to: Option<i64>,
+  pub min_confidence: Option<f32>,
+  pub offset: Option<i32>,
+  pub limit: Option<i32>,
+  pub sort_by: Option<String>,
+  pub sort_order: Option<String>,
+}
+
+impl RecordingSearchParams {
+  fn into_query(self) -> RecordingSearchQuery {
+    let classes: Vec<String> = self
+      .class
+      .iter()
+      .flat_map(|c| c.split(','))
+      .map(|c| c.trim().to_lowercase())
+      .filter(|c| !c.is_empty())
+      .collect();
+    let by_detection = !classes.is_empty();
+    let tags: Vec<String> = self
+      .tag
+      .iter()
+      .flat_map(|t| t.split(','))
+      .map(|t| t.trim().to_string())
+      .filter(|t| !t.is_empty())
+      .collect();
+    let labels: HashMap<String, String> = self
+      .label
+      .iter()
+      .flat_map(|l| l.split(','))
+      .filter_map(|l| l.split_once(':'))
+      .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+      .filter(|(key, _)| !key.is_empty())
+      .collect();
+
+    RecordingSearchQuery {
+      query: self.q,
+      tenant_id: None,
+      device_id: self.device_id,
+      zone: self.zone,
+      state: self.state,
+      // A recording overlaps the window when it starts before its end and
+      // stops after its start
+      started_after: None,
+      started_before: if by_detection { None } else { self.to },
+      stopped_after: if by_detection { None } else { self.from },
+      stopped_before: None,
+      min_duration_secs: None,
+      max_duration_secs: None,
+      tags: (!tags.is_empty()).then_some(tags),
+      labels: (!labels.is_empty()).then_some(labels),
+      classes: by_detection.then_some(classes),
+      detected_after: if by_detection { self.from } else { None },
+      detected_before: if by_detection { self.to } else { None },
+      min_confidence: self.min_confidence,
+      offset: self.offset.unwrap_or(0),
+      limit: self.limit.unwrap_or(50),
+      sort_by: self.sort_by.unwrap_or_else(|| {
+        if by_detection { "relevance" } else { "started_at" }.to_string()
+      }),
+      sort_order: self.sort_order.unwrap_or_else(|| "desc".to_string()),
+    }
+  }
+}
+
+pub async fn find_recordings(
+  state: State<Arc<SearchApiState>>,
+  tenant: Tenant,
+  Query(params): Query<RecordingSearchParams>,
+) -> Result<Json<RecordingSearchResponse>, StatusCode> {
+  search_recordings(state, tenant, Json(params.into_query())).await
+}
+
 pub async fn search_events(
   State(state): State<Arc<SearchApiState>>,
-  Json(query): Json<EventSearchQuery>,
+  tenant: Tenant,
+  Json(mut query): Json<EventSearchQuery>,
 ) -> Result<Json<EventSearchResponse>, StatusCode> {
   info!("searching events");
+  tenant.scope(&mut query);
   match state.store.search_events(&query).await {
     Ok(response) => Ok(Json(response)),
     Err(e) => {
@@ -40,9 +142,11 @@ pub async fn search_events(
 
 pub async fn search_objects(
   State(state): State<Arc<SearchApiState>>,
-  Json(query): Json<ObjectSearchQuery>,
+  tenant: Tenant,
+  Json(mut query): Json<ObjectSearchQuery>,
 ) -> Result<Json<ObjectSearchResponse>, StatusCode> {
   info!(object_type = %query.object_type, "searching objects");
+  tenant.scope(&mut query);
   match state.store.search_objects(&query).await {
     Ok(response) => Ok(Json(response)),
     Err(e) => {
@@ -52,9 +156,228 @@ pub async fn search_objects(
   }
 }
 
+/// Upper bound on detection events returned for one range
+const MAX_RECORDING_DETECTIONS: usize = 20_000;
+
+/// Stored AI detections of a recording, timed from its start, for
+/// playback-service to draw on clip exports
+pub async fn recording_detections(
+  State(state): State<Arc<SearchApiState>>,
+  tenant: Tenant,
+  Path(recording_id): Path<String>,
+  Query(query): Query<RecordingDetectionsQuery>,
+) -> Result<Json<RecordingDetectionsResponse>, StatusCode> {
+  common::validation::validate_id(&recording_id, "recording_id").map_err(|_| StatusCode::BAD_REQUEST)?;
+  let valid_end = query.to_secs.is_none_or(|to| to.is_finite() && to > query.from_secs);
+  if !query.from_secs.is_finite() || query.from_secs < 0.0 || !valid_end {
+    return Err(StatusCode::BAD_REQUEST);
+  }
+
+  let started_at = state
+    .store
+    .recording_started_at(&recording_id, tenant.filter(None).as_deref())
+    .await
+    .map_err(|e| {
+      error!(recording_id = %recording_id, error = %e, "failed to look up recording");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+  // Events are stored at whole seconds; the exact frame time is applied below
+  let from = started_at + query.from_secs.floor() as i64;
+  let to = query.to_secs.map(|to| started_at + to.ceil() as i64 + 1);
+  let mut events = state
+    .store
+    .recording_detections(&recording_id, from, to, MAX_RECORDING_DETECTIONS as i64 + 1)
+    .await
+    .map_err(|e| {
+      error!(recording_id = %recording_id, error = %e, "failed to read detections");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+  let truncated = events.len() > MAX_RECORDING_DETECTIONS;
+  events.truncate(MAX_RECORDING_DETECTIONS);
+
+  let detections = events
+    .iter()
+    .filter_map(|event| recording_detection(event, started_at))
+    .filter(|d| d.offset_secs >= query.from_secs && query.to_secs.is_none_or(|to| d.offset_secs < to))
+    .collect();
+  Ok(Json(RecordingDetectionsResponse {
+    recording_id,
+    started_at,
+    detections,
+    truncated,
+  }))
+}
+
+/// Boxes of an `ai_detection` event, timed from `started_at` (Unix seconds).
+/// Events indexed before boxes were kept have none and are skipped.
+fn recording_detection(event: &EventIndexEntry, started_at: i64) -> Option<RecordingDetection> {
+  let class = event.detected_objects.first()?.clone();
+  let boxes = serde_json::from_value(event.event_data.get("boxes")?.clone()).ok()?;
+  let timestamp_ms = event
+    .event_data
+    .get("timestamp_ms")
+    .and_then(|v| v.as_i64())
+    .filter(|ms| *ms > 0)
+    .unwrap_or(event.occurred_at.saturating_mul(1000));
+  let dimension = |key: &str| {
+    event
+      .event_data
+      .get(key)
+      .and_then(|v| v.as_u64())
+      .and_then(|v| u32::try_from(v).ok())
+      .unwrap_or(0)
+  };
+  Some(RecordingDetection {
+    offset_secs: (timestamp_ms - started_at.saturating_mul(1000)) as f64 / 1000.0,
+    class,
+    frame_width: dimension("frame_width"),
+    frame_height: dimension("frame_height"),
+    boxes,
+  })
+}
+
+/// Most tags or labels one update may carry
+const MAX_METADATA_ENTRIES: usize = 100;
+/// Longest tag or label key
+const MAX_TAG_LENGTH: usize = 64;
+const MAX_LABEL_VALUE_LENGTH: usize = 256;
+
+/// Reject empty or oversized tags and labels; commas are refused in tags
+/// and label keys since the query string splits on them
+pub fn validate_metadata(update: &RecordingMetadataUpdate) -> Result<(), String> {
+  let name = |kind: &str, value: &str| -> Result<(), String> {
+    if value.trim().is_empty() || value.trim() != value {
+      return Err(format!("{} '{}' is empty or has surrounding whitespace", kind, value));
+    }
+    if value.chars().count() > MAX_TAG_LENGTH || value.contains(',') || value.chars().any(char::is_control) {
+      return Err(format!("{} '{}' is longer than {} characters or contains commas", kind, value, MAX_TAG_LENGTH));
+    }
+    Ok(())
+  };
+  if update.add_tags.len() + update.remove_tags.len() > MAX_METADATA_ENTRIES || update.labels.len() > MAX_METADATA_ENTRIES {
+    return Err(format!("at most {} tags and {} labels per update", MAX_METADATA_ENTRIES, MAX_METADATA_ENTRIES));
+  }
+  for tag in update.add_tags.iter().chain(&update.remove_tags) {
+    name("tag", tag)?;
+  }
+  for (key, value) in &update.labels {
+    name("label", key)?;
+    if value.as_ref().is_some_and(|v| v.chars().count() > MAX_LABEL_VALUE_LENGTH) {
+      return Err(format!("label '{}' is longer than {} characters", key, MAX_LABEL_VALUE_LENGTH));
+    }
+  }
+  Ok(())
+}
+
+fn metadata_response(entry: RecordingIndexEntry) -> Json<RecordingMetadataResponse> {
+  Json(RecordingMetadataResponse {
+    recording_id: entry.recording_id,
+    tags: entry.tags,
+    labels: entry.labels,
+  })
+}
+
+/// Tags and labels of a recording
+pub async fn get_recording_metadata(
+  State(state): State<Arc<SearchApiState>>,
+  tenant: Tenant,
+  Path(recording_id): Path<String>,
+) -> Result<Json<RecordingMetadataResponse>, StatusCode> {
+  common::validation::validate_id(&recording_id, "recording_id").map_err(|_| StatusCode::BAD_REQUEST)?;
+  // Running recordings enter the index here, so they can be read before
+  // their first detection or tag
+  if let Err(e) = state.indexer.ensure_indexed(&recording_id).await {
+    error!(recording_id = %recording_id, error = %e, "failed to index recording");
+    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+  }
+  let entry = state
+    .store
+    .recording_entry(&recording_id)
+    .await
+    .map_err(|e| {
+      error!(recording_id = %recording_id, error = %e, "failed to look up recording");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::NOT_FOUND)?;
+  match (&entry.tenant_id, tenant.filter(None)) {
+    (Some(owner), Some(_)) if !tenant.can_access(owner) => return Err(StatusCode::NOT_FOUND),
+    (None, Some(_)) if !tenant.is_system_admin => return Err(StatusCode::NOT_FOUND),
+    _ => {}
+  }
+  Ok(metadata_response(entry))
+}
+
+/// Add or remove tags and set or remove labels of a recording
+pub async fn update_recording_metadata(
+  State(state): State<Arc<SearchApiState>>,
+  tenant: Tenant,
+  Path(recording_id): Path<String>,
+  Json(update): Json<RecordingMetadataUpdate>,
+) -> Result<Json<RecordingMetadataResponse>, StatusCode> {
+  common::validation::validate_id(&recording_id, "recording_id").map_err(|_| StatusCode::BAD_REQUEST)?;
+  if let Err(e) = validate_metadata(&update) {
+    info!(recording_id = %recording_id, error = %e, "rejected recording metadata update");
+    return Err(StatusCode::BAD_REQUEST);
+  }
+  match state
+    .indexer
+    .update_metadata(&recording_id, tenant.filter(None).as_deref(), &update)
+    .await
+  {
+    Ok(Some(entry)) => {
+      info!(recording_id = %recording_id, tags = ?entry.tags, "updated recording metadata");
+      Ok(metadata_response(entry))
+    }
+    Ok(None) => Err(StatusCode::NOT_FOUND),
+    Err(e) => {
+      error!(recording_id = %recording_id, error = %e, "failed to update recording metadata");
+      Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+/// Changes of the recording index after the `since` cursor, for tools
+/// mirroring it incrementally. 410 Gone when a deletion after the cursor was
+/// already dropped from the feed: the mirror has to start over.
+pub async fn recording_changes(
+  State(state): State<Arc<SearchApiState>>,
+  tenant: Tenant,
+  Query(query): Query<RecordingChangesQuery>,
+) -> Result<Json<RecordingChangesResponse>, StatusCode> {
+  let since = changes::parse_cursor(query.since.as_deref()).map_err(|_| StatusCode::BAD_REQUEST)?;
+  let limit = query.limit.unwrap_or(100);
+  if !(1..=MAX_CHANGES_LIMIT).contains(&limit) {
+    return Err(StatusCode::BAD_REQUEST);
+  }
+  let response = state
+    .store
+    .recording_changes(since, tenant.filter(None).as_deref(), limit)
+    .await
+    .map_err(|e| {
+      error!(error = %e, "failed to read recording changes");
+      StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+  // Read after the changes, so a compaction in between is noticed
+  let horizon = state.store.recording_changes_horizon().await.map_err(|e| {
+    error!(error = %e, "failed to read recording change horizon");
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+  if changes::cursor_expired(since, horizon) {
+    info!(since, horizon, "recording change cursor expired");
+    return Err(StatusCode::GONE);
+  }
+  Ok(Json(response))
+}
+
 pub async fn reindex_recordings(
   State(state): State<Arc<SearchApiState>>,
+  tenant: Tenant,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
+  if !tenant.is_system_admin {
+    return Err(StatusCode::FORBIDDEN);
+  }
   info!("reindexing all recordings");
   match state.indexer.index_all_recordings().await {
     Ok(count) => Ok(Json(serde_json::json!({
@@ -79,3 +402,121 @@ pub async fn get_search_stats(
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn class_params_filter_and_rank_by_detections() {
+    let query = RecordingSearchParams {
+      class: Some("Person, car,".to_string()),
+      device_id: Some("camera-3".to_string()),
+      from: Some(7_200),
+      to: Some(14_400),
+      ..Default::default()
+    }
+    .into_query();
+
+    assert_eq!(query.classes, Some(vec!["person".to_string(), "car".to_string()]));
+    assert_eq!(query.detected_after, Some(7_200));
+    assert_eq!(query.detected_before, Some(14_400));
+    assert_eq!(query.stopped_after, None);
+    assert_eq!(query.sort_by, "relevance");
+    assert_eq!(query.limit, 50);
+  }
+
+  #[test]
+  fn time_params_without_class_match_overlapping_recordings() {
+    let query = RecordingSearchParams {
+      from: Some(7_200),
+      to: Some(14_400),
+      ..Default::default()
+    }
+    .into_query();
+
+    assert_eq!(query.classes, None);
+    assert_eq!(query.stopped_after, Some(7_200));
+    assert_eq!(query.started_before, Some(14_400));
+    assert_eq!(query.sort_by, "started_at");
+  }
+
+  #[test]
+  fn tag_and_label_params_filter_recordings() {
+    let query = RecordingSearchParams {
+      tag: Some("case-123, reviewed,".to_string()),
+      label: Some("case:123,reviewer: alice,broken".to_string()),
+      ..Default::default()
+    }
+    .into_query();
+
+    assert_eq!(query.tags, Some(vec!["case-123".to_string(), "reviewed".to_string()]));
+    let labels = query.labels.unwrap();
+    assert_eq!(labels.len(), 2);
+    assert_eq!(labels["reviewer"], "alice");
+
+    let untagged = RecordingSearchParams::default().into_query();
+    assert_eq!((untagged.tags, untagged.labels), (None, None));
+  }
+
+  #[test]
+  fn metadata_updates_are_validated() {
+    let update = |tags: &[&str], labels: &[(&str, Option<&str>)]| RecordingMetadataUpdate {
+      add_tags: tags.iter().map(|t| t.to_string()).collect(),
+      remove_tags: vec![],
+      labels: labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.map(str::to_string)))
+        .collect(),
+    };
+    assert!(validate_metadata(&update(&["case-123", "reviewed"], &[("case", Some("123")), ("old", None)])).is_ok());
+    assert!(validate_metadata(&update(&[""], &[])).is_err());
+    assert!(validate_metadata(&update(&[" padded"], &[])).is_err());
+    assert!(validate_metadata(&update(&["a,b"], &[])).is_err());
+    assert!(validate_metadata(&update(&[&"x".repeat(65)], &[])).is_err());
+    assert!(validate_metadata(&update(&[], &[("case", Some(&"x".repeat(257)))])).is_err());
+  }
+
+  #[test]
+  fn detections_are_timed_from_recording_start() {
+    let event = EventIndexEntry {
+      id: "1".to_string(),
+      event_id: "rec-1:1700000012500:person".to_string(),
+      tenant_id: None,
+      event_type: "ai_detection".to_string(),
+      recording_id: Some("rec-1".to_string()),
+      occurred_at: 1_700_000_012,
+      duration_secs: None,
+      device_id: None,
+      device_name: None,
+      zone: None,
+      event_data: serde_json::from_value(serde_json::json!({
+        "timestamp_ms": 1_700_000_012_500u64,
+        "frame_width": 640,
+        "frame_height": 0,
+        "boxes": [{ "x": 10, "y": 20, "width": 30, "height": 40, "confidence": 0.8 }],
+      }))
+      .unwrap(),
+      detected_objects: vec!["person".to_string()],
+      object_count: Some(1),
+      max_confidence: Some(0.8),
+      snapshot_path: None,
+      thumbnail_data: None,
+      severity: None,
+      tags: vec![],
+      indexed_at: 0,
+      updated_at: 0,
+    };
+
+    let detection = recording_detection(&event, 1_700_000_000).unwrap();
+    assert_eq!(detection.offset_secs, 12.5);
+    assert_eq!(detection.class, "person");
+    assert_eq!((detection.frame_width, detection.frame_height), (640, 0));
+    assert_eq!(detection.boxes[0].width, 30);
+
+    // Indexed before boxes were kept
+    let mut old = event;
+    old.event_data.remove("boxes");
+    assert!(recording_detection(&old, 1_700_000_000).is_none());
+  }
+}
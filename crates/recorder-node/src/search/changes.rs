@@ -0,0 +1,88 @@
+//! Change feed of the recording index (`GET /v1/recordings/changes`).
+//!
+//! A trigger on `recording_index` appends every insert, update and delete to
+//! `recording_changes`, whose sequence number is the cursor. Writers are
+//! serialized until they commit, so changes become visible in cursor order
+//! and a reader paging with `since` never skips one. The compactor keeps only
+//! the latest change of each recording and drops deletions after
+//! `RECORDING_CHANGES_RETENTION_DAYS`; readers resuming from before a
+//! dropped deletion get 410 Gone and have to start over.
+
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use super::store::SearchStore;
+
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+const COMPACT_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Position in the feed of a `since` cursor; the beginning when unset
+pub fn parse_cursor(cursor: Option<&str>) -> Result<i64, String> {
+  match cursor.map(str::trim).filter(|c| !c.is_empty()) {
+    None => Ok(0),
+    Some(cursor) => cursor
+      .parse::<i64>()
+      .ok()
+      .filter(|seq| *seq >= 0)
+      .ok_or_else(|| format!("invalid cursor '{}'", cursor)),
+  }
+}
+
+/// Whether a reader at `since` may have missed a deletion dropped from the
+/// feed; starting from the beginning never misses anything that matters
+pub fn cursor_expired(since: i64, horizon: i64) -> bool {
+  since > 0 && since < horizon
+}
+
+/// Compact the change feed every hour; `RECORDING_CHANGES_RETENTION_DAYS`
+/// (default 30) is how long deletions stay in it, 0 keeps them forever
+pub async fn run_change_compactor(store: Arc<dyn SearchStore>) {
+  let days = env::var("RECORDING_CHANGES_RETENTION_DAYS")
+    .ok()
+    .and_then(|v| v.parse::<u64>().ok())
+    .unwrap_or(DEFAULT_RETENTION_DAYS);
+  let retention_secs = if days == 0 { None } else { Some(days.saturating_mul(24 * 3600)) };
+  let mut ticker = tokio::time::interval(COMPACT_INTERVAL);
+  loop {
+    ticker.tick().await;
+    let deletions_before = match retention_secs {
+      Some(secs) => now_secs().saturating_sub(secs) as i64,
+      None => 0,
+    };
+    match store.compact_recording_changes(deletions_before).await {
+      Ok(0) => {}
+      Ok(removed) => info!(removed, "compacted recording change feed"),
+      Err(e) => warn!(error = %e, "failed to compact recording change feed"),
+    }
+  }
+}
+
+fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cursors_are_non_negative_sequence_numbers() {
+    assert_eq!(parse_cursor(None), Ok(0));
+    assert_eq!(parse_cursor(Some("")), Ok(0));
+    assert_eq!(parse_cursor(Some("1234")), Ok(1234));
+    assert!(parse_cursor(Some("-1")).is_err());
+    assert!(parse_cursor(Some("abc")).is_err());
+  }
+
+  #[test]
+  fn only_readers_behind_a_dropped_deletion_start_over() {
+    assert!(!cursor_expired(0, 500));
+    assert!(cursor_expired(499, 500));
+    assert!(!cursor_expired(500, 500));
+    assert!(!cursor_expired(10, 0));
+  }
+}
@@ -1,17 +1,181 @@
 use anyhow::Result;
+use common::ai_tasks::AiResult;
+use common::recordings::{AutoTagRule, RecordingInfo, RecordingStartRequest};
 use common::search::*;
-use std::sync::Arc;
-use tracing::info;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
 use crate::recording::manager::RECORDING_MANAGER;
 use super::store::SearchStore;
 
+static INDEXER: OnceLock<Arc<SearchIndexer>> = OnceLock::new();
+
+/// Index AI detections from frame capture through `indexer`
+pub fn install(indexer: Arc<SearchIndexer>) {
+  let _ = INDEXER.set(indexer);
+}
+
+/// Record the detections of one captured frame, if search is enabled.
+/// `frame_size` is the size the frame was captured at (0 = scaled to keep
+/// the aspect ratio), which the boxes are relative to.
+pub async fn index_detections(recording_id: &str, result: &AiResult, frame_size: (u32, u32)) {
+  let Some(indexer) = INDEXER.get() else {
+    return;
+  };
+  if let Err(e) = indexer.index_detections(recording_id, result, frame_size).await {
+    warn!(recording_id = %recording_id, error = %e, "failed to index detections");
+  }
+}
+
+/// Apply a recording's `auto_tags` rules to one analysed frame, if search
+/// is enabled
+pub async fn apply_auto_tags(recording_id: &str, result: &AiResult, rules: &[AutoTagRule]) {
+  let Some(indexer) = INDEXER.get() else {
+    return;
+  };
+  if let Err(e) = indexer.apply_auto_tags(recording_id, result, rules).await {
+    warn!(recording_id = %recording_id, error = %e, "failed to auto-tag recording");
+  }
+}
+
+/// Give a recording the tags and labels it was started with, if search is
+/// enabled
+pub async fn tag_recording(recording_id: &str, update: &RecordingMetadataUpdate) {
+  let Some(indexer) = INDEXER.get() else {
+    return;
+  };
+  if let Err(e) = indexer.update_metadata(recording_id, None, update).await {
+    warn!(recording_id = %recording_id, error = %e, "failed to tag recording");
+  }
+}
+
+/// Index an imported recording under the tenant that imported it, if
+/// search is enabled
+pub async fn index_import(info: &RecordingInfo, tenant_id: Option<String>, update: &RecordingMetadataUpdate) {
+  let Some(indexer) = INDEXER.get() else {
+    return;
+  };
+  if let Err(e) = indexer.index_import(info, tenant_id, update).await {
+    warn!(recording_id = %info.config.id, error = %e, "failed to index imported recording");
+  }
+}
+
+/// Remove a recording whose footage was deleted from the index, if search
+/// is enabled; the change feed reports it as deleted
+pub async fn remove_recording(recording_id: &str) {
+  let Some(indexer) = INDEXER.get() else {
+    return;
+  };
+  if let Err(e) = indexer.remove_recording(recording_id).await {
+    warn!(recording_id = %recording_id, error = %e, "failed to remove recording from index");
+  }
+}
+
+/// Tags and labels a recording is started with, as an index update
+pub fn initial_metadata(req: &RecordingStartRequest) -> RecordingMetadataUpdate {
+  RecordingMetadataUpdate {
+    add_tags: req.tags.clone(),
+    labels: req
+      .labels
+      .iter()
+      .map(|(key, value)| (key.clone(), Some(value.clone())))
+      .collect(),
+    ..Default::default()
+  }
+}
+
 pub struct SearchIndexer {
   store: Arc<dyn SearchStore>,
+  /// Recordings already in the index, so detections can be joined to them
+  indexed: Mutex<HashSet<String>>,
+  /// (recording, tag) pairs already added by auto-tag rules
+  auto_tagged: Mutex<HashSet<(String, String)>>,
 }
 
 impl SearchIndexer {
   pub fn new(store: Arc<dyn SearchStore>) -> Self {
-    Self { store }
+    Self {
+      store,
+      indexed: Mutex::new(HashSet::new()),
+      auto_tagged: Mutex::new(HashSet::new()),
+    }
+  }
+
+  /// Index a recording of this node unless it already is; recordings only
+  /// enter the index on their first detection or tag otherwise
+  pub async fn ensure_indexed(&self, recording_id: &str) -> Result<Option<RecordingInfo>> {
+    let recording = RECORDING_MANAGER.get(recording_id).await;
+    if let Some(rec) = &recording {
+      let first = self.indexed.lock().await.insert(recording_id.to_string());
+      if first {
+        if let Err(e) = self.store.index_recording(&recording_entry(rec)).await {
+          self.indexed.lock().await.remove(recording_id);
+          return Err(e);
+        }
+      }
+    }
+    Ok(recording)
+  }
+
+  /// Drop a recording from the index
+  pub async fn remove_recording(&self, recording_id: &str) -> Result<()> {
+    self.indexed.lock().await.remove(recording_id);
+    self.auto_tagged.lock().await.retain(|(id, _)| id != recording_id);
+    if self.store.remove_recording(recording_id).await? {
+      info!(recording_id = %recording_id, "removed deleted recording from index");
+    }
+    Ok(())
+  }
+
+  /// Change a recording's tags and labels; `None` when the recording is
+  /// neither indexed nor running on this node, or belongs to another tenant
+  pub async fn update_metadata(
+    &self,
+    recording_id: &str,
+    tenant_id: Option<&str>,
+    update: &RecordingMetadataUpdate,
+  ) -> Result<Option<RecordingIndexEntry>> {
+    self.ensure_indexed(recording_id).await?;
+    self.store.update_recording_metadata(recording_id, tenant_id, update).await
+  }
+
+  /// Add the tags of the rules the frame matches, each once per recording
+  pub async fn apply_auto_tags(&self, recording_id: &str, result: &AiResult, rules: &[AutoTagRule]) -> Result<()> {
+    let mut tags = matched_tags(rules, result);
+    {
+      let applied = self.auto_tagged.lock().await;
+      tags.retain(|tag| !applied.contains(&(recording_id.to_string(), tag.clone())));
+    }
+    if tags.is_empty() {
+      return Ok(());
+    }
+    let update = RecordingMetadataUpdate {
+      add_tags: tags.clone(),
+      ..Default::default()
+    };
+    if self.update_metadata(recording_id, None, &update).await?.is_some() {
+      let mut applied = self.auto_tagged.lock().await;
+      applied.extend(tags.into_iter().map(|tag| (recording_id.to_string(), tag)));
+    }
+    Ok(())
+  }
+
+  /// Index a finished import with its tenant, tags and labels
+  pub async fn index_import(
+    &self,
+    info: &RecordingInfo,
+    tenant_id: Option<String>,
+    update: &RecordingMetadataUpdate,
+  ) -> Result<()> {
+    let mut entry = recording_entry(info);
+    entry.tenant_id = tenant_id;
+    self.store.index_recording(&entry).await?;
+    self.indexed.lock().await.insert(info.config.id.clone());
+    if !update.is_empty() {
+      self.store.update_recording_metadata(&info.config.id, None, update).await?;
+    }
+    Ok(())
   }
 
   pub async fn index_all_recordings(&self) -> Result<usize> {
@@ -19,36 +183,215 @@ impl SearchIndexer {
     let mut indexed = 0;
 
     for rec in recordings {
-      let entry = RecordingIndexEntry {
+      self.store.index_recording(&recording_entry(&rec)).await?;
+      self.indexed.lock().await.insert(rec.config.id.clone());
+      indexed += 1;
+    }
+
+    info!(count = indexed, "indexed recordings");
+    Ok(indexed)
+  }
+
+  /// Write one `ai_detection` event per detected class in the frame, so a
+  /// class search sums object counts without unpacking event data. The
+  /// boxes are kept in `event_data` for overlay exports.
+  pub async fn index_detections(
+    &self,
+    recording_id: &str,
+    result: &AiResult,
+    frame_size: (u32, u32),
+  ) -> Result<()> {
+    if result.detections.is_empty() {
+      return Ok(());
+    }
+    let recording = self.ensure_indexed(recording_id).await?;
+    let device_id = recording.and_then(|rec| rec.config.source_stream_id);
+
+    for entry in detection_entries(recording_id, device_id, result, frame_size) {
+      self.store.index_event(&entry).await?;
+    }
+    Ok(())
+  }
+}
+
+/// Tags of the rules matched by a detection in the frame, deduplicated
+fn matched_tags(rules: &[AutoTagRule], result: &AiResult) -> Vec<String> {
+  let mut tags: Vec<String> = rules
+    .iter()
+    .filter(|rule| {
+      result.detections.iter().any(|d| {
+        d.class.eq_ignore_ascii_case(&rule.class) && d.confidence >= rule.min_confidence
+      })
+    })
+    .map(|rule| rule.tag.clone())
+    .collect();
+  tags.sort();
+  tags.dedup();
+  tags
+}
+
+fn recording_entry(rec: &RecordingInfo) -> RecordingIndexEntry {
+  RecordingIndexEntry {
+    id: uuid::Uuid::new_v4().to_string(),
+    recording_id: rec.config.id.clone(),
+    tenant_id: None,
+    device_id: rec.config.source_stream_id.clone(),
+    device_name: None,
+    zone: None,
+    location: None,
+    started_at: rec.started_at.unwrap_or(0) as i64,
+    stopped_at: rec.stopped_at.map(|t| t as i64),
+    duration_secs: rec.metadata.as_ref().and_then(|m| m.duration_secs.map(|d| d as i32)),
+    resolution: rec.metadata.as_ref().and_then(|m| {
+      m.resolution.map(|(w, h)| format!("{}x{}", w, h))
+    }),
+    video_codec: rec.metadata.as_ref().and_then(|m| m.video_codec.clone()),
+    audio_codec: rec.metadata.as_ref().and_then(|m| m.audio_codec.clone()),
+    file_size_bytes: rec.metadata.as_ref().and_then(|m| m.file_size_bytes.map(|s| s as i64)),
+    storage_path: rec.storage_path.clone(),
+    tags: vec![],
+    labels: HashMap::new(),
+    state: format!("{:?}", rec.state),
+    indexed_at: chrono::Utc::now().timestamp(),
+    updated_at: chrono::Utc::now().timestamp(),
+    detections: None,
+  }
+}
+
+fn detection_entries(
+  recording_id: &str,
+  device_id: Option<String>,
+  result: &AiResult,
+  (frame_width, frame_height): (u32, u32),
+) -> Vec<EventIndexEntry> {
+  let now = chrono::Utc::now().timestamp();
+  let occurred_at = if result.timestamp > 0 {
+    (result.timestamp / 1000) as i64
+  } else {
+    now
+  };
+
+  // class -> (count, max confidence, boxes), ordered for stable event ids
+  let mut classes: BTreeMap<String, (i32, f32, Vec<DetectionBox>)> = BTreeMap::new();
+  for detection in &result.detections {
+    let entry = classes
+      .entry(detection.class.to_lowercase())
+      .or_insert((0, 0.0, Vec::new()));
+    entry.0 += 1;
+    entry.1 = entry.1.max(detection.confidence);
+    entry.2.push(DetectionBox {
+      x: detection.bbox.x,
+      y: detection.bbox.y,
+      width: detection.bbox.width,
+      height: detection.bbox.height,
+      confidence: detection.confidence,
+    });
+  }
+
+  classes
+    .into_iter()
+    .map(|(class, (count, confidence, boxes))| {
+      let mut event_data = HashMap::new();
+      event_data.insert("task_id".to_string(), serde_json::json!(result.task_id));
+      event_data.insert("plugin_type".to_string(), serde_json::json!(result.plugin_type));
+      event_data.insert("timestamp_ms".to_string(), serde_json::json!(result.timestamp));
+      event_data.insert("frame_width".to_string(), serde_json::json!(frame_width));
+      event_data.insert("frame_height".to_string(), serde_json::json!(frame_height));
+      event_data.insert("boxes".to_string(), serde_json::json!(boxes));
+      EventIndexEntry {
         id: uuid::Uuid::new_v4().to_string(),
-        recording_id: rec.config.id.clone(),
+        event_id: format!("{}:{}:{}", recording_id, result.timestamp, class),
         tenant_id: None,
-        device_id: rec.config.source_stream_id.clone(),
+        event_type: "ai_detection".to_string(),
+        recording_id: Some(recording_id.to_string()),
+        occurred_at,
+        duration_secs: None,
+        device_id: device_id.clone(),
         device_name: None,
         zone: None,
-        location: None,
-        started_at: rec.started_at.unwrap_or(0) as i64,
-        stopped_at: rec.stopped_at.map(|t| t as i64),
-        duration_secs: rec.metadata.as_ref().and_then(|m| m.duration_secs.map(|d| d as i32)),
-        resolution: rec.metadata.as_ref().and_then(|m| {
-          m.resolution.map(|(w, h)| format!("{}x{}", w, h))
-        }),
-        video_codec: rec.metadata.as_ref().and_then(|m| m.video_codec.clone()),
-        audio_codec: rec.metadata.as_ref().and_then(|m| m.audio_codec.clone()),
-        file_size_bytes: rec.metadata.as_ref().and_then(|m| m.file_size_bytes.map(|s| s as i64)),
-        storage_path: rec.storage_path.clone(),
+        event_data,
+        detected_objects: vec![class],
+        object_count: Some(count),
+        max_confidence: Some(confidence),
+        snapshot_path: None,
+        thumbnail_data: None,
+        severity: None,
         tags: vec![],
-        labels: std::collections::HashMap::new(),
-        state: format!("{:?}", rec.state),
-        indexed_at: chrono::Utc::now().timestamp(),
-        updated_at: chrono::Utc::now().timestamp(),
-      };
+        indexed_at: now,
+        updated_at: now,
+      }
+    })
+    .collect()
+}
 
-      self.store.index_recording(&entry).await?;
-      indexed += 1;
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::ai_tasks::{BoundingBox, Detection};
+
+  fn detection(class: &str, confidence: f32) -> Detection {
+    Detection {
+      class: class.to_string(),
+      confidence,
+      bbox: BoundingBox { x: 0, y: 0, width: 10, height: 10 },
+      metadata: None,
     }
+  }
 
-    info!(count = indexed, "indexed recordings");
-    Ok(indexed)
+  fn result(detections: Vec<Detection>) -> AiResult {
+    AiResult {
+      task_id: "task-1".to_string(),
+      timestamp: 1_700_000_123_456,
+      plugin_type: "object_detection".to_string(),
+      detections,
+      confidence: None,
+      processing_time_ms: None,
+      metadata: None,
+    }
+  }
+
+  #[test]
+  fn detections_are_indexed_per_class() {
+    let result = result(vec![detection("person", 0.7), detection("car", 0.6), detection("Person", 0.9)]);
+
+    let entries = detection_entries("rec-1", Some("camera-3".to_string()), &result, (640, 0));
+    assert_eq!(entries.len(), 2);
+
+    let person = entries.iter().find(|e| e.detected_objects == ["person"]).unwrap();
+    assert_eq!(person.object_count, Some(2));
+    assert_eq!(person.max_confidence, Some(0.9));
+    assert_eq!(person.occurred_at, 1_700_000_123);
+    assert_eq!(person.event_type, "ai_detection");
+    assert_eq!(person.recording_id.as_deref(), Some("rec-1"));
+    assert_eq!(person.device_id.as_deref(), Some("camera-3"));
+    assert_eq!(person.event_id, "rec-1:1700000123456:person");
+    assert_eq!(person.event_data["frame_width"], 640);
+    let boxes: Vec<DetectionBox> = serde_json::from_value(person.event_data["boxes"].clone()).unwrap();
+    assert_eq!(boxes.len(), 2);
+    assert_eq!(boxes[1].confidence, 0.9);
+
+    let car = entries.iter().find(|e| e.detected_objects == ["car"]).unwrap();
+    assert_eq!(car.object_count, Some(1));
+  }
+
+  #[test]
+  fn auto_tags_follow_class_and_confidence() {
+    let rule = |class: &str, min_confidence: f32, tag: &str| AutoTagRule {
+      class: class.to_string(),
+      min_confidence,
+      tag: tag.to_string(),
+    };
+    let rules = vec![
+      rule("person", 0.8, "person-seen"),
+      rule("car", 0.0, "vehicle"),
+      rule("truck", 0.0, "vehicle"),
+      rule("dog", 0.0, "animal"),
+    ];
+
+    let frame = result(vec![detection("Person", 0.85), detection("car", 0.4), detection("truck", 0.9)]);
+    assert_eq!(matched_tags(&rules, &frame), vec!["person-seen", "vehicle"]);
+
+    let weak = result(vec![detection("person", 0.5)]);
+    assert!(matched_tags(&rules, &weak).is_empty());
   }
 }
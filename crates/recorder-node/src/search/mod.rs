@@ -1,6 +1,7 @@
 pub mod store;
 pub mod indexer;
 pub mod api;
+pub mod changes;
 
 pub use store::{SearchStore, PostgresSearchStore};
 pub use indexer::SearchIndexer;
@@ -1,9 +1,13 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use common::search::*;
-use sqlx::PgPool;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Largest page a search returns
+pub const MAX_SEARCH_LIMIT: i32 = 500;
+
 #[async_trait]
 pub trait SearchStore: Send + Sync {
   async fn index_recording(&self, entry: &RecordingIndexEntry) -> Result<()>;
@@ -12,6 +16,48 @@ pub trait SearchStore: Send + Sync {
   async fn search_events(&self, query: &EventSearchQuery) -> Result<EventSearchResponse>;
   async fn search_objects(&self, query: &ObjectSearchQuery) -> Result<ObjectSearchResponse>;
   async fn get_search_stats(&self) -> Result<SearchStatsResponse>;
+  /// Start of an indexed recording in Unix seconds; `None` when it is not
+  /// indexed or, with `tenant_id` set, belongs to another tenant
+  async fn recording_started_at(&self, recording_id: &str, tenant_id: Option<&str>) -> Result<Option<i64>>;
+  /// Index entry of one recording, without detection summary
+  async fn recording_entry(&self, recording_id: &str) -> Result<Option<RecordingIndexEntry>>;
+  /// Add or remove tags and set or remove labels of an indexed recording;
+  /// `None` when it is not indexed or, with `tenant_id` set, belongs to
+  /// another tenant
+  async fn update_recording_metadata(
+    &self,
+    recording_id: &str,
+    tenant_id: Option<&str>,
+    update: &RecordingMetadataUpdate,
+  ) -> Result<Option<RecordingIndexEntry>>;
+  /// `ai_detection` events of a recording that occurred in `[from, to)`
+  /// (Unix seconds, no end when `to` is unset), oldest first and at most
+  /// `limit`
+  async fn recording_detections(
+    &self,
+    recording_id: &str,
+    from: i64,
+    to: Option<i64>,
+    limit: i64,
+  ) -> Result<Vec<EventIndexEntry>>;
+  /// Changes of the index after cursor `since`, oldest first and at most
+  /// `limit`; with `tenant_id` set, only changes of that tenant's recordings
+  async fn recording_changes(
+    &self,
+    since: i64,
+    tenant_id: Option<&str>,
+    limit: i64,
+  ) -> Result<RecordingChangesResponse>;
+  /// Cursor of the newest deletion dropped from the change feed; readers
+  /// resuming before it may have missed a deletion
+  async fn recording_changes_horizon(&self) -> Result<i64>;
+  /// Drop changes superseded by a later change of the same recording, and
+  /// deletions from before `deletions_before` (Unix seconds); returns how
+  /// many changes were dropped
+  async fn compact_recording_changes(&self, deletions_before: i64) -> Result<u64>;
+  /// Remove a recording whose footage is gone; `false` when it was not
+  /// indexed
+  async fn remove_recording(&self, recording_id: &str) -> Result<bool>;
 }
 
 pub struct PostgresSearchStore {
@@ -45,11 +91,16 @@ impl SearchStore for PostgresSearchStore {
          file_size_bytes, storage_path, tags, labels, state)
       VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
       ON CONFLICT (recording_id) DO UPDATE SET
+        tenant_id = COALESCE(recording_index.tenant_id, EXCLUDED.tenant_id),
         device_name = EXCLUDED.device_name,
         zone = EXCLUDED.zone,
         stopped_at = EXCLUDED.stopped_at,
         duration_secs = EXCLUDED.duration_secs,
         file_size_bytes = EXCLUDED.file_size_bytes,
+        resolution = COALESCE(EXCLUDED.resolution, recording_index.resolution),
+        video_codec = COALESCE(EXCLUDED.video_codec, recording_index.video_codec),
+        audio_codec = COALESCE(EXCLUDED.audio_codec, recording_index.audio_codec),
+        storage_path = COALESCE(EXCLUDED.storage_path, recording_index.storage_path),
         state = EXCLUDED.state,
         updated_at = NOW()
       "#,
@@ -94,7 +145,8 @@ impl SearchStore for PostgresSearchStore {
         (id, event_id, tenant_id, event_type, recording_id, occurred_at, duration_secs,
          device_id, device_name, zone, event_data, detected_objects, object_count,
          max_confidence, snapshot_path, thumbnail_data, severity, tags)
-      VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+      VALUES ($1, $2, COALESCE($3, (SELECT tenant_id FROM recording_index WHERE recording_id = $5)),
+        $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
       "#,
     )
     .bind(id)
@@ -122,27 +174,90 @@ impl SearchStore for PostgresSearchStore {
   }
 
   async fn search_recordings(&self, query: &RecordingSearchQuery) -> Result<RecordingSearchResponse> {
-    let mut sql = "SELECT * FROM recording_index WHERE 1=1".to_string();
-    let mut count_sql = "SELECT COUNT(*) FROM recording_index WHERE 1=1".to_string();
+    let tenant_id = common::validation::parse_uuid_optional(query.tenant_id.as_deref(), "tenant_id")?;
+    let classes = query.classes.clone().unwrap_or_default();
+    let labels_json = query.labels.as_ref().map(serde_json::to_value).transpose()?;
+    let ts = |t: Option<i64>| t.and_then(|t| chrono::DateTime::from_timestamp(t, 0));
 
-    // Build WHERE clauses (simplified - production would use parameterized queries properly)
-    if query.tenant_id.is_some() {
-      sql.push_str(" AND tenant_id = $tenant_id");
-      count_sql.push_str(" AND tenant_id = $tenant_id");
-    }
-    if query.device_id.is_some() {
-      sql.push_str(" AND device_id = $device_id");
-      count_sql.push_str(" AND device_id = $device_id");
-    }
-    if query.state.is_some() {
-      sql.push_str(" AND state = $state");
-      count_sql.push_str(" AND state = $state");
-    }
+    // Detections of the searched classes are aggregated per recording; with
+    // no classes the aggregate is empty and only recording filters apply
+    let sql = format!(
+      r#"
+      WITH hits AS (
+        SELECT e.recording_id,
+          SUM(COALESCE(e.object_count, 1))::BIGINT AS detection_count,
+          COUNT(DISTINCT e.occurred_at)::BIGINT AS detection_frames,
+          MIN(e.occurred_at) AS first_detected_at,
+          MAX(e.occurred_at) AS last_detected_at,
+          MAX(e.max_confidence)::REAL AS detection_confidence
+        FROM event_index e
+        WHERE e.event_type = 'ai_detection'
+          AND e.detected_objects && $1::text[]
+          AND ($2::timestamptz IS NULL OR e.occurred_at >= $2)
+          AND ($3::timestamptz IS NULL OR e.occurred_at < $3)
+          AND ($4::real IS NULL OR e.max_confidence >= $4)
+          AND ($5::uuid IS NULL OR e.tenant_id IS NULL OR e.tenant_id = $5)
+        GROUP BY e.recording_id
+      )
+      SELECT r.*, h.detection_count, h.detection_frames, h.first_detected_at,
+        h.last_detected_at, h.detection_confidence, COUNT(*) OVER () AS total_count
+      FROM recording_index r
+      LEFT JOIN hits h ON h.recording_id = r.recording_id
+      WHERE (cardinality($1::text[]) = 0 OR h.recording_id IS NOT NULL)
+        AND ($5::uuid IS NULL OR r.tenant_id = $5)
+        AND ($6::text IS NULL OR r.device_id = $6)
+        AND ($7::text IS NULL OR r.zone = $7)
+        AND ($8::text IS NULL OR r.state = $8)
+        AND ($9::timestamptz IS NULL OR r.started_at >= $9)
+        AND ($10::timestamptz IS NULL OR r.started_at < $10)
+        AND ($11::timestamptz IS NULL OR r.stopped_at IS NULL OR r.stopped_at >= $11)
+        AND ($12::timestamptz IS NULL OR r.stopped_at < $12)
+        AND ($13::int IS NULL OR r.duration_secs >= $13)
+        AND ($14::int IS NULL OR r.duration_secs <= $14)
+        AND ($15::text[] IS NULL OR r.tags && $15)
+        AND ($16::jsonb IS NULL OR r.labels @> $16)
+        AND ($17::text IS NULL OR r.search_vector @@ plainto_tsquery('english', $17))
+      ORDER BY {}
+      LIMIT $18 OFFSET $19
+      "#,
+      order_clause(&query.sort_by, &query.sort_order),
+    );
+
+    let rows = sqlx::query(&sql)
+      .bind(&classes)
+      .bind(ts(query.detected_after))
+      .bind(ts(query.detected_before))
+      .bind(query.min_confidence)
+      .bind(tenant_id)
+      .bind(&query.device_id)
+      .bind(&query.zone)
+      .bind(&query.state)
+      .bind(ts(query.started_after))
+      .bind(ts(query.started_before))
+      .bind(ts(query.stopped_after))
+      .bind(ts(query.stopped_before))
+      .bind(query.min_duration_secs)
+      .bind(query.max_duration_secs)
+      .bind(&query.tags)
+      .bind(labels_json)
+      .bind(&query.query)
+      .bind(i64::from(query.limit.clamp(1, MAX_SEARCH_LIMIT)))
+      .bind(i64::from(query.offset.max(0)))
+      .fetch_all(&self.pool)
+      .await?;
+
+    let total = match rows.first() {
+      Some(row) => row.try_get("total_count")?,
+      None => 0,
+    };
+    let recordings = rows
+      .into_iter()
+      .map(map_recording_row)
+      .collect::<Result<Vec<_>>>()?;
 
-    // For simplicity, returning empty results - full implementation would build dynamic query
     Ok(RecordingSearchResponse {
-      recordings: vec![],
-      total: 0,
+      recordings,
+      total,
       offset: query.offset,
       limit: query.limit,
     })
@@ -185,4 +300,325 @@ impl SearchStore for PostgresSearchStore {
       newest_recording: None,
     })
   }
+
+  async fn recording_started_at(&self, recording_id: &str, tenant_id: Option<&str>) -> Result<Option<i64>> {
+    let tenant_id = common::validation::parse_uuid_optional(tenant_id, "tenant_id")?;
+    let started_at: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(
+      "SELECT started_at FROM recording_index WHERE recording_id = $1 AND ($2::uuid IS NULL OR tenant_id = $2)",
+    )
+    .bind(recording_id)
+    .bind(tenant_id)
+    .fetch_optional(&self.pool)
+    .await?;
+    Ok(started_at.map(|t| t.timestamp()))
+  }
+
+  async fn recording_entry(&self, recording_id: &str) -> Result<Option<RecordingIndexEntry>> {
+    let row = sqlx::query(
+      r#"
+      SELECT r.*, NULL::BIGINT AS detection_count, NULL::BIGINT AS detection_frames,
+        NULL::timestamptz AS first_detected_at, NULL::timestamptz AS last_detected_at,
+        NULL::REAL AS detection_confidence
+      FROM recording_index r
+      WHERE r.recording_id = $1
+      "#,
+    )
+    .bind(recording_id)
+    .fetch_optional(&self.pool)
+    .await?;
+    row.map(map_recording_row).transpose()
+  }
+
+  async fn update_recording_metadata(
+    &self,
+    recording_id: &str,
+    tenant_id: Option<&str>,
+    update: &RecordingMetadataUpdate,
+  ) -> Result<Option<RecordingIndexEntry>> {
+    let tenant_id = common::validation::parse_uuid_optional(tenant_id, "tenant_id")?;
+    let removed_labels: Vec<String> = update
+      .labels
+      .iter()
+      .filter(|(_, value)| value.is_none())
+      .map(|(key, _)| key.clone())
+      .collect();
+    let set_labels: HashMap<&String, &String> = update
+      .labels
+      .iter()
+      .filter_map(|(key, value)| value.as_ref().map(|value| (key, value)))
+      .collect();
+
+    // Tags stay a sorted set; the search vector trigger picks them up
+    let row = sqlx::query(
+      r#"
+      UPDATE recording_index SET
+        tags = ARRAY(
+          SELECT DISTINCT t FROM unnest(COALESCE(tags, '{}'::text[]) || $3::text[]) AS t
+          WHERE t <> ALL($4::text[])
+          ORDER BY t
+        ),
+        labels = (COALESCE(labels, '{}'::jsonb) - $5::text[]) || $6::jsonb
+      WHERE recording_id = $1 AND ($2::uuid IS NULL OR tenant_id = $2)
+      RETURNING *, NULL::BIGINT AS detection_count, NULL::BIGINT AS detection_frames,
+        NULL::timestamptz AS first_detected_at, NULL::timestamptz AS last_detected_at,
+        NULL::REAL AS detection_confidence
+      "#,
+    )
+    .bind(recording_id)
+    .bind(tenant_id)
+    .bind(&update.add_tags)
+    .bind(&update.remove_tags)
+    .bind(removed_labels)
+    .bind(serde_json::to_value(set_labels)?)
+    .fetch_optional(&self.pool)
+    .await?;
+    row.map(map_recording_row).transpose()
+  }
+
+  async fn recording_detections(
+    &self,
+    recording_id: &str,
+    from: i64,
+    to: Option<i64>,
+    limit: i64,
+  ) -> Result<Vec<EventIndexEntry>> {
+    let rows = sqlx::query(
+      r#"
+      SELECT * FROM event_index
+      WHERE recording_id = $1
+        AND event_type = 'ai_detection'
+        AND occurred_at >= $2
+        AND ($3::timestamptz IS NULL OR occurred_at < $3)
+      ORDER BY occurred_at, event_id
+      LIMIT $4
+      "#,
+    )
+    .bind(recording_id)
+    .bind(chrono::DateTime::from_timestamp(from, 0))
+    .bind(to.and_then(|t| chrono::DateTime::from_timestamp(t, 0)))
+    .bind(limit)
+    .fetch_all(&self.pool)
+    .await?;
+    rows.into_iter().map(map_event_row).collect()
+  }
+
+  async fn recording_changes(
+    &self,
+    since: i64,
+    tenant_id: Option<&str>,
+    limit: i64,
+  ) -> Result<RecordingChangesResponse> {
+    let tenant_id = common::validation::parse_uuid_optional(tenant_id, "tenant_id")?;
+    // One extra row tells whether more changes follow
+    let rows = sqlx::query(
+      r#"
+      SELECT c.seq AS change_seq, c.kind AS change_kind, c.recording_id AS change_recording_id,
+        c.changed_at AS change_at, r.id IS NOT NULL AS indexed, r.*,
+        NULL::BIGINT AS detection_count, NULL::BIGINT AS detection_frames,
+        NULL::timestamptz AS first_detected_at, NULL::timestamptz AS last_detected_at,
+        NULL::REAL AS detection_confidence
+      FROM recording_changes c
+      LEFT JOIN recording_index r ON r.recording_id = c.recording_id AND c.kind <> 'deleted'
+      WHERE c.seq > $1
+        AND ($2::uuid IS NULL OR COALESCE(r.tenant_id, c.tenant_id) = $2)
+      ORDER BY c.seq
+      LIMIT $3
+      "#,
+    )
+    .bind(since)
+    .bind(tenant_id)
+    .bind(limit + 1)
+    .fetch_all(&self.pool)
+    .await?;
+
+    let has_more = rows.len() as i64 > limit;
+    let mut changes = Vec::with_capacity(rows.len());
+    for row in rows.into_iter().take(limit as usize) {
+      let seq: i64 = row.try_get("change_seq")?;
+      let kind: String = row.try_get("change_kind")?;
+      let changed_at: chrono::DateTime<chrono::Utc> = row.try_get("change_at")?;
+      changes.push(RecordingChange {
+        cursor: seq.to_string(),
+        kind: RecordingChangeKind::parse(&kind)
+          .ok_or_else(|| anyhow::anyhow!("unknown recording change kind '{}'", kind))?,
+        recording_id: row.try_get("change_recording_id")?,
+        changed_at: changed_at.timestamp(),
+        recording: if row.try_get("indexed")? { Some(map_recording_row(row)?) } else { None },
+      });
+    }
+    let next_cursor = changes.last().map_or_else(|| since.to_string(), |c| c.cursor.clone());
+    Ok(RecordingChangesResponse {
+      changes,
+      next_cursor,
+      has_more,
+    })
+  }
+
+  async fn recording_changes_horizon(&self) -> Result<i64> {
+    let horizon: Option<i64> = sqlx::query_scalar("SELECT pruned_through FROM recording_changes_horizon")
+      .fetch_optional(&self.pool)
+      .await?;
+    Ok(horizon.unwrap_or(0))
+  }
+
+  async fn compact_recording_changes(&self, deletions_before: i64) -> Result<u64> {
+    let superseded = sqlx::query(
+      r#"
+      DELETE FROM recording_changes c
+      WHERE EXISTS (
+        SELECT 1 FROM recording_changes n
+        WHERE n.recording_id = c.recording_id AND n.seq > c.seq
+      )
+      "#,
+    )
+    .execute(&self.pool)
+    .await?
+    .rows_affected();
+
+    // The horizon moves past every dropped deletion, so readers behind it
+    // know to start over
+    let deletions: i64 = sqlx::query_scalar(
+      r#"
+      WITH pruned AS (
+        DELETE FROM recording_changes
+        WHERE kind = 'deleted' AND changed_at < $1
+        RETURNING seq
+      ), horizon AS (
+        INSERT INTO recording_changes_horizon (pruned_through)
+        SELECT MAX(seq) FROM pruned HAVING COUNT(*) > 0
+        ON CONFLICT (id) DO UPDATE
+          SET pruned_through = GREATEST(recording_changes_horizon.pruned_through, EXCLUDED.pruned_through)
+      )
+      SELECT COUNT(*) FROM pruned
+      "#,
+    )
+    .bind(chrono::DateTime::from_timestamp(deletions_before, 0))
+    .fetch_one(&self.pool)
+    .await?;
+
+    Ok(superseded + deletions as u64)
+  }
+
+  async fn remove_recording(&self, recording_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM recording_index WHERE recording_id = $1")
+      .bind(recording_id)
+      .execute(&self.pool)
+      .await?;
+    Ok(result.rows_affected() > 0)
+  }
+}
+
+/// ORDER BY for a recording search. Columns are whitelisted since they are
+/// spliced into the statement; relevance ranks by matching detections.
+fn order_clause(sort_by: &str, sort_order: &str) -> String {
+  let direction = if sort_order.eq_ignore_ascii_case("asc") { "ASC" } else { "DESC" };
+  let column = match sort_by {
+    "relevance" => None,
+    "stopped_at" => Some("r.stopped_at"),
+    "duration_secs" => Some("r.duration_secs"),
+    "file_size_bytes" => Some("r.file_size_bytes"),
+    _ => Some("r.started_at"),
+  };
+  match column {
+    Some(column) => format!("{} {} NULLS LAST, r.recording_id", column, direction),
+    None => format!(
+      "h.detection_count {} NULLS LAST, h.detection_confidence DESC NULLS LAST, r.started_at DESC, r.recording_id",
+      direction
+    ),
+  }
+}
+
+fn map_recording_row(row: sqlx::postgres::PgRow) -> Result<RecordingIndexEntry> {
+  let timestamp = |column: &str| -> Result<Option<i64>> {
+    Ok(row
+      .try_get::<Option<chrono::DateTime<chrono::Utc>>, _>(column)?
+      .map(|t| t.timestamp()))
+  };
+  let labels: Option<serde_json::Value> = row.try_get("labels")?;
+  let detection_count: Option<i64> = row.try_get("detection_count")?;
+  let detections = match detection_count {
+    Some(count) => Some(DetectionSummary {
+      count,
+      frames: row.try_get::<Option<i64>, _>("detection_frames")?.unwrap_or(0),
+      first_at: timestamp("first_detected_at")?.unwrap_or(0),
+      last_at: timestamp("last_detected_at")?.unwrap_or(0),
+      max_confidence: row.try_get("detection_confidence")?,
+    }),
+    None => None,
+  };
+
+  Ok(RecordingIndexEntry {
+    id: row.try_get::<Uuid, _>("id")?.to_string(),
+    recording_id: row.try_get("recording_id")?,
+    tenant_id: row.try_get::<Option<Uuid>, _>("tenant_id")?.map(|u| u.to_string()),
+    device_id: row.try_get("device_id")?,
+    device_name: row.try_get("device_name")?,
+    zone: row.try_get("zone")?,
+    location: row.try_get("location")?,
+    started_at: timestamp("started_at")?.unwrap_or(0),
+    stopped_at: timestamp("stopped_at")?,
+    duration_secs: row.try_get("duration_secs")?,
+    resolution: row.try_get("resolution")?,
+    video_codec: row.try_get("video_codec")?,
+    audio_codec: row.try_get("audio_codec")?,
+    file_size_bytes: row.try_get("file_size_bytes")?,
+    storage_path: row.try_get("storage_path")?,
+    tags: row.try_get::<Option<Vec<String>>, _>("tags")?.unwrap_or_default(),
+    labels: labels.map(serde_json::from_value).transpose()?.unwrap_or_default(),
+    state: row.try_get("state")?,
+    indexed_at: timestamp("indexed_at")?.unwrap_or(0),
+    updated_at: timestamp("updated_at")?.unwrap_or(0),
+    detections,
+  })
+}
+
+fn map_event_row(row: sqlx::postgres::PgRow) -> Result<EventIndexEntry> {
+  let timestamp = |column: &str| -> Result<Option<i64>> {
+    Ok(row
+      .try_get::<Option<chrono::DateTime<chrono::Utc>>, _>(column)?
+      .map(|t| t.timestamp()))
+  };
+  let event_data: Option<serde_json::Value> = row.try_get("event_data")?;
+  // FLOAT column, written from an f32
+  let max_confidence: Option<f64> = row.try_get("max_confidence")?;
+
+  Ok(EventIndexEntry {
+    id: row.try_get::<Uuid, _>("id")?.to_string(),
+    event_id: row.try_get("event_id")?,
+    tenant_id: row.try_get::<Option<Uuid>, _>("tenant_id")?.map(|u| u.to_string()),
+    event_type: row.try_get("event_type")?,
+    recording_id: row.try_get("recording_id")?,
+    occurred_at: timestamp("occurred_at")?.unwrap_or(0),
+    duration_secs: row.try_get("duration_secs")?,
+    device_id: row.try_get("device_id")?,
+    device_name: row.try_get("device_name")?,
+    zone: row.try_get("zone")?,
+    event_data: event_data.map(serde_json::from_value).transpose()?.unwrap_or_default(),
+    detected_objects: row.try_get::<Option<Vec<String>>, _>("detected_objects")?.unwrap_or_default(),
+    object_count: row.try_get("object_count")?,
+    max_confidence: max_confidence.map(|c| c as f32),
+    snapshot_path: row.try_get("snapshot_path")?,
+    thumbnail_data: row.try_get("thumbnail_data")?,
+    severity: row.try_get("severity")?,
+    tags: row.try_get::<Option<Vec<String>>, _>("tags")?.unwrap_or_default(),
+    indexed_at: timestamp("indexed_at")?.unwrap_or(0),
+    updated_at: timestamp("updated_at")?.unwrap_or(0),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn relevance_ranks_by_detection_count() {
+    assert!(order_clause("relevance", "desc").starts_with("h.detection_count DESC"));
+    assert!(order_clause("duration_secs", "asc").starts_with("r.duration_secs ASC"));
+  }
+
+  #[test]
+  fn unknown_sort_columns_fall_back_to_start_time() {
+    assert!(order_clause("started_at", "desc").starts_with("r.started_at DESC"));
+    assert!(order_clause("1; DROP TABLE recording_index", "sideways").starts_with("r.started_at DESC"));
+  }
 }
@@ -0,0 +1,684 @@
+//! Disk-full protection for the recordings volume.
+//!
+//! FFmpeg fails mid-recording when the volume fills up, and an MP4 cut off
+//! that way has no index and cannot be played. So the volume under
+//! `RECORDINGS_ROOT` is watched instead:
+//!
+//! - every running recording holds a reservation of headroom
+//!   (`RECORDING_RESERVE_MB`); a new recording is refused when, after its
+//!   own reservation, free space would fall below `RECORDING_MIN_FREE_PCT`
+//! - below `RECORDING_EMERGENCY_FREE_PCT` the oldest stopped recordings are
+//!   deleted until `RECORDING_EMERGENCY_TARGET_PCT` is free again, without
+//!   waiting for retention policies. Recordings tagged `locked` (or labelled
+//!   `locked=true`) in the recordings index are never deleted this way
+//! - crossing `RECORDING_CAPACITY_WARN_PCT` or a lower threshold raises a
+//!   `storage_capacity` alert, well before writes start failing
+//!
+//! Reservations are bookkeeping, nothing is written ahead: a camera's
+//! footage grows at its bitrate, and the reservation covers what running
+//! recordings write before the next check.
+
+use anyhow::{Context, Result};
+use common::auth_middleware::AuthContext;
+use common::gateway_identity;
+use common::recordings::{RecordingInfo, RecordingState};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::recording::manager::RECORDING_MANAGER;
+
+const IDENTITY_TTL: Duration = Duration::from_secs(60);
+
+static GUARD: OnceLock<Arc<CapacityGuard>> = OnceLock::new();
+
+/// Protect the recordings volume through `guard`
+pub fn install(guard: Arc<CapacityGuard>) {
+  let _ = GUARD.set(guard);
+}
+
+/// Why a new recording cannot start now, if the guard refuses it.
+/// `running` is the number of recordings already writing.
+pub fn refusal(running: usize) -> Option<String> {
+  let guard = GUARD.get()?;
+  match disk_usage(&guard.config.root) {
+    Ok(usage) => admit(&guard.config, &usage, running).err(),
+    Err(e) => {
+      // Let the recording fail on its own rather than refuse on a guess
+      warn!(error = %e, "failed to measure recordings volume");
+      None
+    }
+  }
+}
+
+/// Current state of the volume, once the guard has checked it
+pub async fn status() -> Option<CapacityStatus> {
+  GUARD.get()?.status.lock().await.clone()
+}
+
+#[derive(Debug, Clone)]
+pub struct CapacityConfig {
+  /// Directory recordings are written to
+  pub root: PathBuf,
+  /// Headroom each running recording holds
+  pub reserve_bytes: u64,
+  /// Free space, in percent, below which new recordings are refused
+  pub min_free_pct: f64,
+  /// Free space below which emergency retention starts
+  pub emergency_free_pct: f64,
+  /// Free space emergency retention frees up to
+  pub emergency_target_pct: f64,
+  /// Free space below which capacity alerts are raised
+  pub warn_free_pct: f64,
+  pub interval: Duration,
+}
+
+impl Default for CapacityConfig {
+  fn default() -> Self {
+    Self {
+      root: PathBuf::from("./data/recordings"),
+      reserve_bytes: 1024 * 1024 * 1024,
+      min_free_pct: 5.0,
+      emergency_free_pct: 8.0,
+      emergency_target_pct: 12.0,
+      warn_free_pct: 15.0,
+      interval: Duration::from_secs(30),
+    }
+  }
+}
+
+impl CapacityConfig {
+  /// Reads `RECORDINGS_ROOT`, `RECORDING_RESERVE_MB`,
+  /// `RECORDING_MIN_FREE_PCT`, `RECORDING_EMERGENCY_FREE_PCT`,
+  /// `RECORDING_EMERGENCY_TARGET_PCT`, `RECORDING_CAPACITY_WARN_PCT` and
+  /// `RECORDING_CAPACITY_CHECK_SECS`. Thresholds are raised where needed to
+  /// keep them in order: refuse <= emergency <= target, emergency <= warn.
+  pub fn from_env() -> Self {
+    let defaults = Self::default();
+    let pct = |name: &str, default: f64| {
+      std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| (0.0..100.0).contains(v))
+        .unwrap_or(default)
+    };
+    let min_free_pct = pct("RECORDING_MIN_FREE_PCT", defaults.min_free_pct);
+    let emergency_free_pct = pct("RECORDING_EMERGENCY_FREE_PCT", defaults.emergency_free_pct);
+    let emergency_target_pct = pct("RECORDING_EMERGENCY_TARGET_PCT", defaults.emergency_target_pct);
+    let warn_free_pct = pct("RECORDING_CAPACITY_WARN_PCT", defaults.warn_free_pct);
+    let emergency_free_pct = if emergency_free_pct > 0.0 {
+      emergency_free_pct.max(min_free_pct)
+    } else {
+      0.0
+    };
+    Self {
+      root: std::env::var("RECORDINGS_ROOT")
+        .map(PathBuf::from)
+        .unwrap_or(defaults.root),
+      reserve_bytes: std::env::var("RECORDING_RESERVE_MB")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|mb| mb * 1024 * 1024)
+        .unwrap_or(defaults.reserve_bytes),
+      min_free_pct,
+      emergency_free_pct,
+      emergency_target_pct: emergency_target_pct.max(emergency_free_pct),
+      warn_free_pct: warn_free_pct.max(emergency_free_pct),
+      interval: std::env::var("RECORDING_CAPACITY_CHECK_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|secs| Duration::from_secs(secs.max(5)))
+        .unwrap_or(defaults.interval),
+    }
+  }
+}
+
+/// Size and free space of a volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DiskUsage {
+  pub total_bytes: u64,
+  /// Space available to this process, without the root-reserved blocks
+  pub available_bytes: u64,
+}
+
+impl DiskUsage {
+  pub fn free_pct(&self) -> f64 {
+    if self.total_bytes == 0 {
+      return 0.0;
+    }
+    self.available_bytes as f64 * 100.0 / self.total_bytes as f64
+  }
+
+  /// Bytes to free for `pct` percent of the volume to be available
+  pub fn bytes_short_of(&self, pct: f64) -> u64 {
+    let wanted = (self.total_bytes as f64 * pct / 100.0) as u64;
+    wanted.saturating_sub(self.available_bytes)
+  }
+}
+
+/// Measure the volume holding `path`; a root not created yet is measured
+/// at its nearest existing parent
+pub fn disk_usage(path: &Path) -> Result<DiskUsage> {
+  let existing = path
+    .ancestors()
+    .find(|p| p.exists())
+    .unwrap_or_else(|| Path::new("."));
+  let stat = rustix::fs::statvfs(existing)
+    .with_context(|| format!("statvfs {}", existing.display()))?;
+  Ok(DiskUsage {
+    total_bytes: stat.f_blocks.saturating_mul(stat.f_frsize),
+    available_bytes: stat.f_bavail.saturating_mul(stat.f_frsize),
+  })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapacityLevel {
+  Ok,
+  /// Below the warning threshold
+  Low,
+  /// Below the emergency threshold; old footage is being deleted
+  Critical,
+  /// Below the floor; new recordings are refused
+  Full,
+}
+
+impl CapacityLevel {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CapacityLevel::Ok => "ok",
+      CapacityLevel::Low => "low",
+      CapacityLevel::Critical => "critical",
+      CapacityLevel::Full => "full",
+    }
+  }
+
+  pub fn of(config: &CapacityConfig, usage: &DiskUsage) -> Self {
+    let free = usage.free_pct();
+    if free < config.min_free_pct {
+      CapacityLevel::Full
+    } else if free < config.emergency_free_pct {
+      CapacityLevel::Critical
+    } else if free < config.warn_free_pct {
+      CapacityLevel::Low
+    } else {
+      CapacityLevel::Ok
+    }
+  }
+
+  fn as_gauge(&self) -> i64 {
+    *self as i64
+  }
+}
+
+/// Check that one more recording fits next to the `running` ones
+pub fn admit(config: &CapacityConfig, usage: &DiskUsage, running: usize) -> Result<(), String> {
+  let reserved = config.reserve_bytes.saturating_mul(running as u64 + 1);
+  let floor = (usage.total_bytes as f64 * config.min_free_pct / 100.0) as u64;
+  let left = usage.available_bytes.saturating_sub(reserved);
+  if left < floor {
+    return Err(format!(
+      "recordings volume has {} MB free; reserving {} MB for this and {} running recording(s) would leave less than the {}% floor",
+      usage.available_bytes / (1024 * 1024),
+      reserved / (1024 * 1024),
+      running,
+      config.min_free_pct,
+    ));
+  }
+  Ok(())
+}
+
+/// A stopped recording emergency retention may delete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Footage {
+  pub recording_id: String,
+  /// The recording's own directory under the root
+  pub dir: PathBuf,
+  pub started_at: u64,
+  pub bytes: u64,
+}
+
+/// The recording's directory, if emergency retention may delete it: the
+/// recording has stopped, is not locked and lives in its own directory
+/// under `root`
+pub fn deletable_dir(root: &Path, info: &RecordingInfo, locked: &HashSet<String>) -> Option<PathBuf> {
+  if !matches!(info.state, RecordingState::Stopped | RecordingState::Error) {
+    return None;
+  }
+  if locked.contains(&info.config.id) {
+    return None;
+  }
+  let dir = Path::new(info.storage_path.as_deref()?).parent()?;
+  (dir.file_name()? == info.config.id.as_str() && dir.parent()? == root).then(|| dir.to_path_buf())
+}
+
+/// Oldest footage first, until `bytes_needed` would be freed
+pub fn plan_emergency(mut footage: Vec<Footage>, bytes_needed: u64) -> Vec<Footage> {
+  footage.sort_by_key(|f| f.started_at);
+  let mut freed = 0u64;
+  footage
+    .into_iter()
+    .take_while(|f| {
+      let take = freed < bytes_needed;
+      freed = freed.saturating_add(f.bytes);
+      take
+    })
+    .collect()
+}
+
+/// Outcome of one emergency retention pass
+#[derive(Debug, Clone, Serialize)]
+pub struct EmergencyReport {
+  pub at_epoch_secs: u64,
+  pub bytes_needed: u64,
+  pub bytes_freed: u64,
+  pub deleted: Vec<String>,
+  /// Stopped recordings passed over because they are locked
+  pub locked_skipped: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapacityStatus {
+  pub root: PathBuf,
+  pub total_bytes: u64,
+  pub available_bytes: u64,
+  pub free_pct: f64,
+  pub level: CapacityLevel,
+  pub running_recordings: usize,
+  pub reserved_bytes: u64,
+  /// Whether a new recording would be accepted now
+  pub accepting: bool,
+  pub checked_at_epoch_secs: u64,
+  pub last_emergency: Option<EmergencyReport>,
+}
+
+/// Raises `storage_capacity` alerts through the alert service
+struct CapacityAlerter {
+  client: reqwest::Client,
+  trigger_url: String,
+  identity: AuthContext,
+  jwt_secret: String,
+}
+
+impl CapacityAlerter {
+  /// Alerting is enabled when `ALERT_SERVICE_URL`, `JWT_SECRET` and
+  /// `CAPACITY_ALERT_TENANT_ID` are set
+  fn from_env() -> Option<Self> {
+    let base = std::env::var("ALERT_SERVICE_URL").ok()?;
+    let jwt_secret = std::env::var("JWT_SECRET").ok()?;
+    let tenant_id = std::env::var("CAPACITY_ALERT_TENANT_ID").ok()?;
+    Some(Self {
+      client: reqwest::Client::new(),
+      trigger_url: format!("{}/v1/trigger", base.trim_end_matches('/')),
+      identity: AuthContext {
+        user_id: "recorder-node".into(),
+        tenant_id,
+        username: "recorder-node".into(),
+        is_system_admin: false,
+        roles: Vec::new(),
+        permissions: Vec::new(),
+      },
+      jwt_secret,
+    })
+  }
+
+  async fn raise(&self, node_id: &str, status: &CapacityStatus) -> Result<()> {
+    let message = format!(
+      "recordings volume on {} is {}: {:.1}% free",
+      node_id,
+      status.level.as_str(),
+      status.free_pct
+    );
+    let headers = gateway_identity::headers(&self.identity, &self.jwt_secret, IDENTITY_TTL)?;
+    self
+      .client
+      .post(&self.trigger_url)
+      .headers(headers)
+      .json(&json!({
+      "trigger_type": "storage_capacity",
+      "message": message,
+      "context": {
+        "node_id": node_id,
+        "level": status.level.as_str(),
+        "free_pct": status.free_pct,
+        "available_bytes": status.available_bytes,
+        "total_bytes": status.total_bytes,
+        "accepting": status.accepting,
+      },
+    }))
+      .send()
+      .await
+      .context("alert service unreachable")?
+      .error_for_status()
+      .context("alert service rejected trigger")?;
+    Ok(())
+  }
+}
+
+/// Watches the recordings volume and deletes old footage when it runs low
+pub struct CapacityGuard {
+  config: CapacityConfig,
+  node_id: String,
+  /// Recordings index, for locks; without it no recording is locked
+  pool: Option<PgPool>,
+  alerter: Option<CapacityAlerter>,
+  status: Mutex<Option<CapacityStatus>>,
+}
+
+impl CapacityGuard {
+  pub fn new(config: CapacityConfig, node_id: String, pool: Option<PgPool>) -> Self {
+    Self {
+      config,
+      node_id,
+      pool,
+      alerter: CapacityAlerter::from_env(),
+      status: Mutex::new(None),
+    }
+  }
+
+  pub fn config(&self) -> &CapacityConfig {
+    &self.config
+  }
+
+  /// Check the volume every interval
+  pub fn spawn(self: Arc<Self>) {
+    tokio::spawn(async move {
+      let mut ticker = tokio::time::interval(self.config.interval);
+      loop {
+        ticker.tick().await;
+        if let Err(e) = self.check().await {
+          warn!(error = %e, "recordings volume check failed");
+        }
+      }
+    });
+  }
+
+  /// Measure the volume, run emergency retention when it is needed and
+  /// alert when the level got worse
+  pub async fn check(&self) -> Result<CapacityStatus> {
+    let mut usage = disk_usage(&self.config.root)?;
+    let mut last_emergency = None;
+    if self.config.emergency_free_pct > 0.0 && usage.free_pct() < self.config.emergency_free_pct {
+      match self.emergency_retention(&usage).await {
+        Ok(report) => {
+          usage = disk_usage(&self.config.root)?;
+          last_emergency = Some(report);
+        }
+        Err(e) => error!(error = %e, "emergency retention skipped"),
+      }
+    }
+
+    let running = RECORDING_MANAGER
+      .list()
+      .await
+      .iter()
+      .filter(|info| info.state.is_active())
+      .count();
+    let level = CapacityLevel::of(&self.config, &usage);
+    let mut status = CapacityStatus {
+      root: self.config.root.clone(),
+      total_bytes: usage.total_bytes,
+      available_bytes: usage.available_bytes,
+      free_pct: usage.free_pct(),
+      level,
+      running_recordings: running,
+      reserved_bytes: self.config.reserve_bytes.saturating_mul(running as u64),
+      accepting: admit(&self.config, &usage, running).is_ok(),
+      checked_at_epoch_secs: now_secs(),
+      last_emergency: None,
+    };
+
+    use telemetry::metrics::{
+      RECORDER_NODE_DISK_AVAILABLE_BYTES, RECORDER_NODE_DISK_CAPACITY_LEVEL,
+      RECORDER_NODE_DISK_TOTAL_BYTES,
+    };
+    RECORDER_NODE_DISK_TOTAL_BYTES.set(i64::try_from(usage.total_bytes).unwrap_or(i64::MAX));
+    RECORDER_NODE_DISK_AVAILABLE_BYTES.set(i64::try_from(usage.available_bytes).unwrap_or(i64::MAX));
+    RECORDER_NODE_DISK_CAPACITY_LEVEL.set(level.as_gauge());
+
+    let previous = {
+      let mut current = self.status.lock().await;
+      let previous = current.take();
+      status.last_emergency =
+        last_emergency.or_else(|| previous.as_ref().and_then(|p| p.last_emergency.clone()));
+      *current = Some(status.clone());
+      previous.map(|p| p.level).unwrap_or(CapacityLevel::Ok)
+    };
+
+    if level > previous {
+      warn!(
+        level = level.as_str(),
+        free_pct = status.free_pct,
+        available_bytes = status.available_bytes,
+        "recordings volume running out of space"
+      );
+      if let Some(alerter) = &self.alerter {
+        if let Err(e) = alerter.raise(&self.node_id, &status).await {
+          warn!(error = %e, "failed to raise capacity alert");
+        }
+      }
+    } else if level < previous {
+      info!(level = level.as_str(), free_pct = status.free_pct, "recordings volume recovered");
+    }
+    Ok(status)
+  }
+
+  /// Recording ids locked in the recordings index
+  async fn locked(&self) -> Result<HashSet<String>> {
+    let Some(pool) = &self.pool else {
+      return Ok(HashSet::new());
+    };
+    let ids: Vec<String> = sqlx::query_scalar(
+      "SELECT recording_id FROM recording_index \
+       WHERE 'locked' = ANY(tags) OR labels->>'locked' = 'true'",
+    )
+    .fetch_all(pool)
+    .await
+    .context("failed to read locked recordings")?;
+    Ok(ids.into_iter().collect())
+  }
+
+  /// Delete the oldest unlocked stopped recordings until the target is free
+  async fn emergency_retention(&self, usage: &DiskUsage) -> Result<EmergencyReport> {
+    // Without the locks nothing is deleted: better refused recordings than
+    // lost evidence
+    let locked = self.locked().await?;
+    let bytes_needed = usage.bytes_short_of(self.config.emergency_target_pct);
+
+    let recordings = RECORDING_MANAGER.list().await;
+    let mut locked_skipped = 0;
+    let mut footage = Vec::new();
+    for info in &recordings {
+      if locked.contains(&info.config.id) && !info.state.is_active() {
+        locked_skipped += 1;
+      }
+      let Some(dir) = deletable_dir(&self.config.root, info, &locked) else {
+        continue;
+      };
+      let bytes = dir_size(&dir).await;
+      if bytes > 0 {
+        footage.push(Footage {
+          recording_id: info.config.id.clone(),
+          dir,
+          started_at: info.started_at.unwrap_or(0),
+          bytes,
+        });
+      }
+    }
+
+    let mut report = EmergencyReport {
+      at_epoch_secs: now_secs(),
+      bytes_needed,
+      bytes_freed: 0,
+      deleted: Vec::new(),
+      locked_skipped,
+    };
+    for footage in plan_emergency(footage, bytes_needed) {
+      match tokio::fs::remove_dir_all(&footage.dir).await {
+        Ok(()) => {
+          RECORDING_MANAGER.forget(&footage.recording_id).await;
+          crate::search::indexer::remove_recording(&footage.recording_id).await;
+          report.bytes_freed += footage.bytes;
+          report.deleted.push(footage.recording_id);
+        }
+        Err(e) => {
+          error!(recording_id = %footage.recording_id, error = %e, "failed to delete recording");
+        }
+      }
+    }
+
+    telemetry::metrics::RECORDER_NODE_EMERGENCY_DELETIONS.inc_by(report.deleted.len() as u64);
+    telemetry::metrics::RECORDER_NODE_EMERGENCY_FREED_BYTES.inc_by(report.bytes_freed);
+    if report.bytes_freed < bytes_needed {
+      error!(
+        bytes_needed,
+        bytes_freed = report.bytes_freed,
+        deleted = report.deleted.len(),
+        locked_skipped,
+        "emergency retention could not free enough space"
+      );
+    } else {
+      warn!(
+        bytes_freed = report.bytes_freed,
+        deleted = ?report.deleted,
+        "emergency retention deleted the oldest recordings"
+      );
+    }
+    Ok(report)
+  }
+}
+
+/// Bytes of the files in `dir`, recursively
+async fn dir_size(dir: &Path) -> u64 {
+  let mut total = 0;
+  let mut pending = vec![dir.to_path_buf()];
+  while let Some(dir) = pending.pop() {
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+      continue;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+      match entry.metadata().await {
+        Ok(meta) if meta.is_dir() => pending.push(entry.path()),
+        Ok(meta) => total += meta.len(),
+        Err(_) => {}
+      }
+    }
+  }
+  total
+}
+
+fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::recordings::RecordingConfig;
+
+  const GIB: u64 = 1024 * 1024 * 1024;
+
+  fn config() -> CapacityConfig {
+    CapacityConfig {
+      root: PathBuf::from("/data/recordings"),
+      reserve_bytes: GIB,
+      ..CapacityConfig::default()
+    }
+  }
+
+  fn usage(free_gib: u64) -> DiskUsage {
+    DiskUsage {
+      total_bytes: 100 * GIB,
+      available_bytes: free_gib * GIB,
+    }
+  }
+
+  fn recording(id: &str, state: RecordingState, started_at: u64) -> RecordingInfo {
+    RecordingInfo {
+      config: RecordingConfig {
+        id: id.to_string(),
+        source_stream_id: None,
+        source_uri: Some("rtsp://example.com/stream".to_string()),
+        retention_hours: None,
+        format: None,
+        encoding: None,
+      },
+      state,
+      lease_id: None,
+      storage_path: Some(format!("/data/recordings/{}/recording.mp4", id)),
+      last_error: None,
+      started_at: Some(started_at),
+      stopped_at: None,
+      node_id: None,
+      metadata: None,
+    }
+  }
+
+  #[test]
+  fn levels_follow_the_thresholds() {
+    let config = config();
+    assert_eq!(CapacityLevel::of(&config, &usage(40)), CapacityLevel::Ok);
+    assert_eq!(CapacityLevel::of(&config, &usage(14)), CapacityLevel::Low);
+    assert_eq!(CapacityLevel::of(&config, &usage(7)), CapacityLevel::Critical);
+    assert_eq!(CapacityLevel::of(&config, &usage(4)), CapacityLevel::Full);
+    assert_eq!(usage(7).bytes_short_of(config.emergency_target_pct), 5 * GIB);
+  }
+
+  #[test]
+  fn running_recordings_reserve_headroom() {
+    let config = config();
+    // 10 GiB free, 5 GiB floor: room for four reservations next to this one
+    assert!(admit(&config, &usage(10), 4).is_ok());
+    let err = admit(&config, &usage(10), 5).unwrap_err();
+    assert!(err.contains("5 running"));
+    assert!(admit(&config, &usage(4), 0).is_err());
+  }
+
+  #[test]
+  fn emergency_retention_spares_running_and_locked_recordings() {
+    let root = Path::new("/data/recordings");
+    let locked: HashSet<String> = ["evidence".to_string()].into();
+
+    let stopped = recording("old", RecordingState::Stopped, 100);
+    assert_eq!(
+      deletable_dir(root, &stopped, &locked),
+      Some(PathBuf::from("/data/recordings/old"))
+    );
+    let running = recording("live", RecordingState::Recording, 50);
+    assert!(deletable_dir(root, &running, &locked).is_none());
+    let evidence = recording("evidence", RecordingState::Stopped, 10);
+    assert!(deletable_dir(root, &evidence, &locked).is_none());
+
+    // Never anything outside the recording's own directory
+    let mut stray = recording("stray", RecordingState::Stopped, 10);
+    stray.storage_path = Some("/data/recordings/recording.mp4".to_string());
+    assert!(deletable_dir(root, &stray, &locked).is_none());
+  }
+
+  #[test]
+  fn deletes_oldest_footage_first_until_enough_is_freed() {
+    let footage = |id: &str, started_at: u64| Footage {
+      recording_id: id.to_string(),
+      dir: PathBuf::from(format!("/data/recordings/{}", id)),
+      started_at,
+      bytes: 2 * GIB,
+    };
+    let plan = plan_emergency(
+      vec![footage("c", 300), footage("a", 100), footage("b", 200)],
+      3 * GIB,
+    );
+    let ids: Vec<&str> = plan.iter().map(|f| f.recording_id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "b"]);
+
+    assert!(plan_emergency(vec![footage("a", 100)], 0).is_empty());
+  }
+}
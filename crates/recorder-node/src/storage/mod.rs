@@ -1 +1,2 @@
+pub mod capacity;
 pub mod indexer;
@@ -1,3 +1,4 @@
+use common::streams::ThermalPalette;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -8,6 +9,12 @@ pub struct StartRequest {
   pub codec: String, // "h264" | "h265" | "hevc" | "h265+"
   #[serde(default = "default_container")]
   pub container: String, // "ts" | "fmp4"
+  /// Used instead of `uri` while the uplink is over its bandwidth budget
+  #[serde(default)]
+  pub substream_uri: Option<String>,
+  /// Thermal camera: transcode in this palette instead of copying
+  #[serde(default)]
+  pub thermal_palette: Option<ThermalPalette>,
 }
 pub fn default_codec() -> String {
   "h264".into()
@@ -30,6 +37,10 @@ pub struct StartQuery {
   pub codec: String,
   #[serde(default = "default_container")]
   pub container: String,
+  #[serde(default)]
+  pub substream_uri: Option<String>,
+  #[serde(default)]
+  pub thermal_palette: Option<ThermalPalette>,
 }
 
 #[derive(Deserialize)]
@@ -37,6 +48,12 @@ pub struct StopQuery {
   pub id: String,
 }
 
+#[derive(Deserialize)]
+pub struct MotionQuery {
+  /// Only segments ending after this wall-clock time (Unix milliseconds)
+  pub since_ms: Option<u64>,
+}
+
 #[derive(Serialize)]
 pub struct StreamDto {
   pub id: String,
@@ -46,4 +63,59 @@ pub struct StreamDto {
   pub running: bool,
   pub playlist: String,
   pub output_dir: String,
+  /// From the newest segment's first frame being ingested until the segment
+  /// was written; unset until a stamped segment exists
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ingest_latency_ms: Option<u64>,
+}
+
+/// One cell of a mosaic: a stream running on this node or a source URI
+#[derive(Deserialize, Serialize)]
+pub struct MosaicTileDto {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub stream_id: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StartMosaicRequest {
+  pub id: String,
+  /// Tiles in row-major order
+  pub tiles: Vec<MosaicTileDto>,
+  /// Tiles per row; a square grid when unset
+  #[serde(default)]
+  pub columns: Option<u32>,
+  #[serde(default = "default_tile_width")]
+  pub tile_width: u32,
+  #[serde(default = "default_tile_height")]
+  pub tile_height: u32,
+  #[serde(default = "default_mosaic_fps")]
+  pub fps: u32,
+  #[serde(default = "default_mosaic_bitrate_kbps")]
+  pub bitrate_kbps: u32,
+}
+pub fn default_tile_width() -> u32 {
+  crate::mosaic::DEFAULT_TILE_WIDTH
+}
+pub fn default_tile_height() -> u32 {
+  crate::mosaic::DEFAULT_TILE_HEIGHT
+}
+pub fn default_mosaic_fps() -> u32 {
+  crate::mosaic::DEFAULT_FPS
+}
+pub fn default_mosaic_bitrate_kbps() -> u32 {
+  crate::mosaic::DEFAULT_BITRATE_KBPS
+}
+
+#[derive(Serialize)]
+pub struct MosaicDto {
+  pub id: String,
+  pub tiles: Vec<MosaicTileDto>,
+  pub columns: u32,
+  pub rows: u32,
+  pub width: u32,
+  pub height: u32,
+  pub running: bool,
+  pub playlist: String,
 }
@@ -1,8 +1,19 @@
 use axum::http::StatusCode;
-use axum::{extract::Query, response::IntoResponse, Json};
+use axum::{
+  extract::{Path, Query},
+  response::IntoResponse,
+  Json,
+};
+use common::motion::MotionActivityResponse;
 use tracing::info;
 
-use super::{StartQuery, StartRequest, StopQuery, StopRequest, StreamDto};
+use super::{
+  MosaicDto, MosaicTileDto, MotionQuery, StartMosaicRequest, StartQuery, StartRequest, StopQuery, StopRequest,
+  StreamDto,
+};
+use crate::mosaic::{self, MosaicSpec, TileSource};
+use crate::motion;
+use crate::ingest::{self, SourceKind};
 use crate::stream::{self, Codec, Container};
 use common::validation;
 
@@ -11,7 +22,11 @@ pub async fn healthz() -> impl IntoResponse {
 }
 
 pub async fn readyz() -> impl IntoResponse {
-  (StatusCode::OK, "ready")
+  if stream::is_draining() {
+    (StatusCode::SERVICE_UNAVAILABLE, "draining")
+  } else {
+    (StatusCode::OK, "ready")
+  }
 }
 
 pub async fn list_streams() -> impl IntoResponse {
@@ -19,6 +34,7 @@ pub async fn list_streams() -> impl IntoResponse {
   let out: Vec<StreamDto> = list
     .into_iter()
     .map(|s| StreamDto {
+      ingest_latency_ms: crate::latency::ingest_latency_ms(&s.id),
       id: s.id,
       uri: s.uri,
       codec: s.codec,
@@ -31,6 +47,34 @@ pub async fn list_streams() -> impl IntoResponse {
   (StatusCode::OK, Json(out))
 }
 
+/// GET /streams/:id/motion - Motion activity of analysed segments
+pub async fn stream_motion(Path(id): Path<String>, Query(q): Query<MotionQuery>) -> impl IntoResponse {
+  match motion::activity(&id, q.since_ms.unwrap_or(0)).await {
+    Some(segments) => (
+      StatusCode::OK,
+      Json(MotionActivityResponse { stream_id: id, segments }),
+    )
+      .into_response(),
+    None => (StatusCode::NOT_FOUND, "no motion activity for stream").into_response(),
+  }
+}
+
+/// 403 for a source outside the node's ingest allowlist
+fn ingest_refused(e: anyhow::Error) -> (StatusCode, String) {
+  telemetry::metrics::STREAM_NODE_STREAM_REJECTIONS
+    .with_label_values(&["ingest_acl"])
+    .inc();
+  tracing::warn!(error = %e, "source refused by ingest allowlist");
+  (StatusCode::FORBIDDEN, format!("source not allowed: {e}"))
+}
+
+async fn check_ingest(uris: &[&str]) -> Result<(), (StatusCode, String)> {
+  for uri in uris {
+    ingest::acl().check(uri).await.map_err(ingest_refused)?;
+  }
+  Ok(())
+}
+
 /// POST /start - Start a stream (recommended)
 pub async fn start_stream(Json(req): Json<StartRequest>) -> impl IntoResponse {
   // Validate inputs
@@ -40,6 +84,13 @@ pub async fn start_stream(Json(req): Json<StartRequest>) -> impl IntoResponse {
   if let Err(e) = validation::validate_uri(&req.uri, "source_uri") {
     return (StatusCode::BAD_REQUEST, format!("invalid source_uri: {e}"));
   }
+  if let Some(Err(e)) = req.substream_uri.as_deref().map(|uri| validation::validate_uri(uri, "substream_uri")) {
+    return (StatusCode::BAD_REQUEST, format!("invalid substream_uri: {e}"));
+  }
+  let sources: Vec<&str> = std::iter::once(req.uri.as_str()).chain(req.substream_uri.as_deref()).collect();
+  if let Err(rejection) = check_ingest(&sources).await {
+    return rejection;
+  }
 
   let codec = match req.codec.to_lowercase().as_str() {
     "h265" | "hevc" | "h265+" => Codec::H265,
@@ -52,10 +103,16 @@ pub async fn start_stream(Json(req): Json<StartRequest>) -> impl IntoResponse {
   let spec = stream::StreamSpec {
     id: req.id.clone(),
     uri: req.uri.clone(),
+    substream_uri: req.substream_uri.clone(),
     codec,
     container,
+    thermal_palette: req.thermal_palette,
   };
 
+  if stream::is_draining() {
+    return (StatusCode::SERVICE_UNAVAILABLE, "stream node is shutting down".to_string());
+  }
+
   match stream::start_stream(&spec).await {
     Ok(_) => {
       info!(id=%req.id, "stream started");
@@ -77,6 +134,13 @@ pub async fn start_stream_api(Query(q): Query<StartQuery>) -> impl IntoResponse
   if let Err(e) = validation::validate_uri(&q.uri, "source_uri") {
     return (StatusCode::BAD_REQUEST, format!("invalid source_uri: {e}"));
   }
+  if let Some(Err(e)) = q.substream_uri.as_deref().map(|uri| validation::validate_uri(uri, "substream_uri")) {
+    return (StatusCode::BAD_REQUEST, format!("invalid substream_uri: {e}"));
+  }
+  let sources: Vec<&str> = std::iter::once(q.uri.as_str()).chain(q.substream_uri.as_deref()).collect();
+  if let Err(rejection) = check_ingest(&sources).await {
+    return rejection;
+  }
 
   let codec = match q.codec.to_lowercase().as_str() {
     "h265" | "hevc" | "h265+" => Codec::H265,
@@ -89,10 +153,16 @@ pub async fn start_stream_api(Query(q): Query<StartQuery>) -> impl IntoResponse
   let spec = stream::StreamSpec {
     id: q.id.clone(),
     uri: q.uri.clone(),
+    substream_uri: q.substream_uri.clone(),
     codec,
     container,
+    thermal_palette: q.thermal_palette,
   };
 
+  if stream::is_draining() {
+    return (StatusCode::SERVICE_UNAVAILABLE, "stream node is shutting down".to_string());
+  }
+
   match stream::start_stream(&spec).await {
     Ok(_) => {
       info!(id=%q.id, "stream started");
@@ -142,3 +212,98 @@ pub async fn stop_stream_api(Query(q): Query<StopQuery>) -> impl IntoResponse {
     }
   }
 }
+
+/// GET /mosaics - Running preview mosaics
+pub async fn list_mosaics() -> impl IntoResponse {
+  let out: Vec<MosaicDto> = mosaic::list_mosaics()
+    .await
+    .into_iter()
+    .map(|m| MosaicDto {
+      id: m.id,
+      tiles: m
+        .tiles
+        .into_iter()
+        .map(|tile| match tile {
+          TileSource::Stream(id) => MosaicTileDto { stream_id: Some(id), uri: None },
+          TileSource::Uri(uri) => MosaicTileDto { stream_id: None, uri: Some(uri) },
+        })
+        .collect(),
+      columns: m.columns,
+      rows: m.rows,
+      width: m.width,
+      height: m.height,
+      running: m.running,
+      playlist: m.playlist.to_string_lossy().to_string(),
+    })
+    .collect();
+  (StatusCode::OK, Json(out))
+}
+
+/// POST /mosaics - Composite camera streams into one video wall stream
+pub async fn start_mosaic(Json(req): Json<StartMosaicRequest>) -> impl IntoResponse {
+  if let Err(e) = validation::validate_id(&req.id, "mosaic_id") {
+    return (StatusCode::BAD_REQUEST, format!("invalid mosaic_id: {e}"));
+  }
+  let mut tiles = Vec::with_capacity(req.tiles.len());
+  for tile in req.tiles {
+    let source = match (tile.stream_id, tile.uri) {
+      (Some(id), None) => validation::validate_id(&id, "stream_id").map(|_| TileSource::Stream(id)),
+      (None, Some(uri)) => validation::validate_uri(&uri, "source_uri").map(|_| TileSource::Uri(uri)),
+      _ => {
+        return (StatusCode::BAD_REQUEST, "each tile needs either stream_id or uri".to_string());
+      }
+    };
+    match source {
+      Ok(TileSource::Uri(uri)) => {
+        // Tiles are always pulled; a camera pushing in must be started as a stream
+        match ingest::acl().check(&uri).await {
+          Ok(SourceKind::Pull) => tiles.push(TileSource::Uri(uri)),
+          Ok(SourceKind::Push) => {
+            return (StatusCode::BAD_REQUEST, "mosaic tiles cannot be push sources".to_string());
+          }
+          Err(e) => return ingest_refused(e),
+        }
+      }
+      Ok(source) => tiles.push(source),
+      Err(e) => return (StatusCode::BAD_REQUEST, format!("invalid tile: {e}")),
+    }
+  }
+  let spec = MosaicSpec {
+    id: req.id.clone(),
+    tiles,
+    columns: req.columns,
+    tile_width: req.tile_width,
+    tile_height: req.tile_height,
+    fps: req.fps,
+    bitrate_kbps: req.bitrate_kbps,
+  };
+  if let Err(e) = spec.validate() {
+    return (StatusCode::BAD_REQUEST, format!("invalid mosaic: {e}"));
+  }
+
+  if stream::is_draining() {
+    return (StatusCode::SERVICE_UNAVAILABLE, "stream node is shutting down".to_string());
+  }
+
+  match mosaic::start_mosaic(&spec).await {
+    Ok(_) => {
+      info!(id=%req.id, "mosaic started");
+      (StatusCode::OK, "started".to_string())
+    }
+    Err(e) => {
+      tracing::error!(?e, "mosaic start failed");
+      (StatusCode::INTERNAL_SERVER_ERROR, format!("error: {e}"))
+    }
+  }
+}
+
+/// DELETE /mosaics/:id - Stop a mosaic
+pub async fn stop_mosaic(Path(id): Path<String>) -> impl IntoResponse {
+  match mosaic::stop_mosaic(&id).await {
+    Ok(_) => {
+      info!(id=%id, "mosaic stopped");
+      (StatusCode::OK, "stopped".to_string())
+    }
+    Err(e) => (StatusCode::NOT_FOUND, format!("error: {e}")),
+  }
+}
@@ -0,0 +1,21 @@
+//! Bandwidth reporting: camera ingest goes to the coordinator, and while the
+//! uplink is over budget cameras with a substream are switched to it
+
+use async_trait::async_trait;
+use common::bandwidth::{BandwidthDirective, BandwidthSource};
+use std::time::Duration;
+
+use crate::stream;
+
+pub struct StreamBandwidth;
+
+#[async_trait]
+impl BandwidthSource for StreamBandwidth {
+  async fn sample(&self, _interval: Duration) -> (u64, u64, u64) {
+    (stream::ingest_kbps().await, 0, 0)
+  }
+
+  async fn apply(&self, directive: &BandwidthDirective) {
+    stream::set_prefer_substream(directive.prefer_substream).await;
+  }
+}
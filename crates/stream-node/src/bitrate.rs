@@ -0,0 +1,137 @@
+//! Ingest bitrate samples for anomaly detection.
+//!
+//! Every interval the bytes of the segments each stream finished are turned
+//! into a `bitrate_kbps` sample and sent to alert-service
+//! (`POST /v1/anomalies/samples`), which learns each camera's normal bitrate
+//! per hour of day. A covered or blinded lens leaves the encoder almost
+//! nothing to send, so the bitrate collapses. Stream nodes have no tenants;
+//! samples are reported for `ANOMALY_TENANT_ID` and the stream id is the
+//! camera id, as in AI tasks' `source_stream_id`.
+
+use crate::stream::{self, segment_bytes_since};
+use anyhow::{Context, Result};
+use common::auth_middleware::AuthContext;
+use common::gateway_identity;
+use serde_json::{json, Value};
+use std::{
+  collections::{HashMap, HashSet},
+  path::PathBuf,
+  time::{Duration, SystemTime},
+};
+use tracing::{debug, info, warn};
+
+const IDENTITY_TTL: Duration = Duration::from_secs(60);
+
+struct BitrateReporter {
+  client: reqwest::Client,
+  samples_url: String,
+  identity: AuthContext,
+  jwt_secret: String,
+  interval: Duration,
+}
+
+impl BitrateReporter {
+  /// Reporting is enabled when `ANOMALY_REPORT_INTERVAL_SECS`,
+  /// `ALERT_SERVICE_URL`, `JWT_SECRET` and `ANOMALY_TENANT_ID` are set
+  fn from_env() -> Option<Self> {
+    let interval = std::env::var("ANOMALY_REPORT_INTERVAL_SECS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .filter(|secs| *secs > 0)?;
+    let base = std::env::var("ALERT_SERVICE_URL").ok()?;
+    let jwt_secret = std::env::var("JWT_SECRET").ok()?;
+    let tenant_id = std::env::var("ANOMALY_TENANT_ID").ok()?;
+    Some(Self {
+      client: reqwest::Client::new(),
+      samples_url: format!("{}/v1/anomalies/samples", base.trim_end_matches('/')),
+      identity: AuthContext {
+        user_id: "stream-node".into(),
+        tenant_id,
+        username: "stream-node".into(),
+        is_system_admin: false,
+        roles: Vec::new(),
+        permissions: Vec::new(),
+      },
+      jwt_secret,
+      interval: Duration::from_secs(interval.max(10)),
+    })
+  }
+
+  async fn report(&self, samples: Vec<Value>) -> Result<()> {
+    debug!(samples = samples.len(), "reporting stream bitrates");
+    let headers = gateway_identity::headers(&self.identity, &self.jwt_secret, IDENTITY_TTL)?;
+    self
+      .client
+      .post(&self.samples_url)
+      .headers(headers)
+      .json(&json!({ "samples": samples }))
+      .send()
+      .await
+      .context("alert service unreachable")?
+      .error_for_status()
+      .context("alert service rejected samples")?;
+    Ok(())
+  }
+}
+
+/// Report stream bitrates to alert-service when configured
+pub fn start_reporting() {
+  let Some(reporter) = BitrateReporter::from_env() else {
+    return;
+  };
+  info!(interval = ?reporter.interval, "stream bitrate reporting enabled");
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(reporter.interval);
+    ticker.tick().await;
+    let mut since = SystemTime::now();
+    // Streams running at the previous tick; only they ran a full window
+    let mut previous: HashSet<String> = HashSet::new();
+    loop {
+      ticker.tick().await;
+      let from = std::mem::replace(&mut since, SystemTime::now());
+      let streams: Vec<(String, PathBuf)> = stream::list_streams()
+        .await
+        .into_iter()
+        .filter(|s| s.running)
+        .map(|s| (s.id, s.output_dir))
+        .collect();
+      let measured: Vec<(String, PathBuf)> = streams
+        .iter()
+        .filter(|(id, _)| previous.contains(id))
+        .cloned()
+        .collect();
+      previous = streams.into_iter().map(|(id, _)| id).collect();
+
+      let bytes = tokio::task::spawn_blocking(move || {
+        measured
+          .into_iter()
+          .map(|(id, dir)| (id, segment_bytes_since(&dir, from)))
+          .collect::<HashMap<String, u64>>()
+      })
+      .await
+      .unwrap_or_default();
+      let samples = bitrate_samples(bytes, reporter.interval);
+      if samples.is_empty() {
+        continue;
+      }
+      if let Err(e) = reporter.report(samples).await {
+        warn!(error = %e, "failed to report stream bitrates");
+      }
+    }
+  });
+}
+
+/// `bitrate_kbps` samples from the segment bytes each stream wrote in a window
+fn bitrate_samples(bytes: HashMap<String, u64>, interval: Duration) -> Vec<Value> {
+  let secs = interval.as_secs_f64().max(1.0);
+  bytes
+    .into_iter()
+    .map(|(stream_id, bytes)| {
+      json!({
+        "camera_id": stream_id,
+        "metric": "bitrate_kbps",
+        "value": bytes as f64 * 8.0 / 1000.0 / secs,
+      })
+    })
+    .collect()
+}
@@ -0,0 +1,357 @@
+//! Ingest allowlist: which sources this node may pull from or accept pushes from
+//!
+//! Anything that can reach the node's API can ask it to open a source, so
+//! every source URI is checked before FFmpeg sees it:
+//! - `STREAM_INGEST_ALLOWED_SCHEMES` limits the protocols (RTSP, RTMP, SRT
+//!   and HTTP by default; never `file:` or other local protocols)
+//! - `STREAM_INGEST_ALLOWED_CIDRS` limits pulled sources to camera address
+//!   ranges; hostnames are resolved and every address must be allowed
+//! - push sources (`listen=1`, SRT `mode=listener`) are refused unless
+//!   `STREAM_INGEST_PUSH_CA_FILE` is set, in which case they must use a TLS
+//!   protocol and the pushing camera must present a certificate issued by
+//!   that CA
+
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+const DEFAULT_SCHEMES: &[&str] = &["rtsp", "rtsps", "rtmp", "rtmps", "srt", "http", "https"];
+
+/// Protocols that can carry client certificates when listening
+const TLS_SCHEMES: &[&str] = &["rtsps", "rtmps", "tls"];
+
+static ACL: OnceCell<IngestAcl> = OnceCell::new();
+
+/// Load the node's ingest allowlist; call once at startup
+pub fn init() -> Result<()> {
+  let acl = IngestAcl::from_env()?;
+  ACL
+    .set(acl)
+    .map_err(|_| anyhow!("ingest allowlist already initialized"))
+}
+
+/// The node's ingest allowlist; allows any pulled source until `init` runs
+pub fn acl() -> &'static IngestAcl {
+  ACL.get_or_init(IngestAcl::default)
+}
+
+/// An IP range in CIDR notation (`10.20.0.0/16`, `fd00::/8`, or a bare address)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cidr {
+  addr: IpAddr,
+  prefix: u8,
+}
+
+impl Cidr {
+  pub fn parse(s: &str) -> Result<Self> {
+    let (addr, prefix) = match s.split_once('/') {
+      Some((addr, prefix)) => (addr, Some(prefix)),
+      None => (s, None),
+    };
+    let addr: IpAddr = addr
+      .trim()
+      .parse()
+      .map_err(|_| anyhow!("invalid address in CIDR '{}'", s))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+      Some(p) => p
+        .trim()
+        .parse::<u8>()
+        .ok()
+        .filter(|p| *p <= max)
+        .ok_or_else(|| anyhow!("invalid prefix length in CIDR '{}'", s))?,
+      None => max,
+    };
+    Ok(Self { addr, prefix })
+  }
+
+  pub fn contains(&self, ip: IpAddr) -> bool {
+    // IPv4-mapped IPv6 addresses match IPv4 ranges
+    let ip = match ip {
+      IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+      v4 => v4,
+    };
+    match (self.addr, ip) {
+      (IpAddr::V4(net), IpAddr::V4(ip)) => {
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+        u32::from(net) & mask == u32::from(ip) & mask
+      }
+      (IpAddr::V6(net), IpAddr::V6(ip)) => {
+        let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+        u128::from(net) & mask == u128::from(ip) & mask
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Server certificate and client CA for push ingest
+#[derive(Clone, Debug)]
+pub struct PushTls {
+  pub ca_file: PathBuf,
+  pub cert_file: Option<PathBuf>,
+  pub key_file: Option<PathBuf>,
+}
+
+/// How a source reaches the node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+  /// The node connects out to the camera
+  Pull,
+  /// The node listens and the camera connects in
+  Push,
+}
+
+#[derive(Clone, Debug)]
+pub struct IngestAcl {
+  schemes: Vec<String>,
+  cidrs: Vec<Cidr>,
+  push: Option<PushTls>,
+}
+
+impl Default for IngestAcl {
+  fn default() -> Self {
+    Self {
+      schemes: DEFAULT_SCHEMES.iter().map(|s| s.to_string()).collect(),
+      cidrs: Vec::new(),
+      push: None,
+    }
+  }
+}
+
+fn list_var(name: &str) -> Option<Vec<String>> {
+  let value = std::env::var(name).ok()?;
+  let items: Vec<String> = value
+    .split(',')
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .collect();
+  (!items.is_empty()).then_some(items)
+}
+
+fn path_var(name: &str) -> Option<PathBuf> {
+  std::env::var(name)
+    .ok()
+    .filter(|v| !v.trim().is_empty())
+    .map(PathBuf::from)
+}
+
+impl IngestAcl {
+  pub fn from_env() -> Result<Self> {
+    let mut acl = Self::default();
+    if let Some(schemes) = list_var("STREAM_INGEST_ALLOWED_SCHEMES") {
+      acl.schemes = schemes.into_iter().map(|s| s.to_ascii_lowercase()).collect();
+    }
+    if let Some(cidrs) = list_var("STREAM_INGEST_ALLOWED_CIDRS") {
+      acl.cidrs = cidrs
+        .iter()
+        .map(|c| Cidr::parse(c))
+        .collect::<Result<_>>()?;
+    }
+    if let Some(ca_file) = path_var("STREAM_INGEST_PUSH_CA_FILE") {
+      let push = PushTls {
+        ca_file,
+        cert_file: path_var("STREAM_INGEST_PUSH_CERT_FILE"),
+        key_file: path_var("STREAM_INGEST_PUSH_KEY_FILE"),
+      };
+      if push.cert_file.is_none() || push.key_file.is_none() {
+        bail!("STREAM_INGEST_PUSH_CA_FILE needs STREAM_INGEST_PUSH_CERT_FILE and STREAM_INGEST_PUSH_KEY_FILE");
+      }
+      acl.push = Some(push);
+    }
+    Ok(acl)
+  }
+
+  pub fn with_schemes(mut self, schemes: &[&str]) -> Self {
+    self.schemes = schemes.iter().map(|s| s.to_string()).collect();
+    self
+  }
+
+  pub fn with_cidrs(mut self, cidrs: Vec<Cidr>) -> Self {
+    self.cidrs = cidrs;
+    self
+  }
+
+  pub fn with_push(mut self, push: PushTls) -> Self {
+    self.push = Some(push);
+    self
+  }
+
+  /// Check a source URI, resolving its host when address ranges are set;
+  /// the error says why the source was refused
+  pub async fn check(&self, uri: &str) -> Result<SourceKind> {
+    let parsed = parse_uri(uri)?;
+    if !self.schemes.iter().any(|s| *s == parsed.scheme) {
+      bail!("protocol '{}' is not allowed for ingest", parsed.scheme);
+    }
+
+    if is_push(&parsed.scheme, uri) {
+      if self.push.is_none() {
+        bail!("push ingest is disabled (STREAM_INGEST_PUSH_CA_FILE)");
+      }
+      if !TLS_SCHEMES.contains(&parsed.scheme.as_str()) {
+        bail!("push ingest requires a TLS protocol for client certificates, got '{}'", parsed.scheme);
+      }
+      return Ok(SourceKind::Push);
+    }
+
+    if self.cidrs.is_empty() {
+      return Ok(SourceKind::Pull);
+    }
+    let addrs: Vec<IpAddr> = match parsed.host.parse::<IpAddr>() {
+      Ok(ip) => vec![ip],
+      Err(_) => tokio::net::lookup_host((parsed.host.as_str(), parsed.port.unwrap_or(0)))
+        .await
+        .map_err(|e| anyhow!("cannot resolve source host '{}': {}", parsed.host, e))?
+        .map(|addr| addr.ip())
+        .collect(),
+    };
+    if addrs.is_empty() {
+      bail!("source host '{}' has no addresses", parsed.host);
+    }
+    // Every address must be allowed, or the name could be re-pointed later
+    if let Some(denied) = addrs.iter().find(|ip| !self.cidrs.iter().any(|c| c.contains(**ip))) {
+      bail!("source address {} is outside the allowed camera ranges", denied);
+    }
+    Ok(SourceKind::Pull)
+  }
+
+  /// FFmpeg input options for a checked source: push sources demand and
+  /// verify a client certificate
+  pub fn input_args(&self, uri: &str) -> Vec<String> {
+    let Some(push) = &self.push else {
+      return Vec::new();
+    };
+    let Ok(parsed) = parse_uri(uri) else {
+      return Vec::new();
+    };
+    if !is_push(&parsed.scheme, uri) {
+      return Vec::new();
+    }
+    let mut args = vec![
+      "-tls_verify".to_string(),
+      "1".to_string(),
+      "-ca_file".to_string(),
+      push.ca_file.to_string_lossy().to_string(),
+    ];
+    if let (Some(cert), Some(key)) = (&push.cert_file, &push.key_file) {
+      args.extend([
+        "-cert_file".to_string(),
+        cert.to_string_lossy().to_string(),
+        "-key_file".to_string(),
+        key.to_string_lossy().to_string(),
+      ]);
+    }
+    args
+  }
+}
+
+struct ParsedUri {
+  scheme: String,
+  host: String,
+  port: Option<u16>,
+}
+
+fn parse_uri(uri: &str) -> Result<ParsedUri> {
+  let (scheme, rest) = uri
+    .split_once("://")
+    .ok_or_else(|| anyhow!("source URI has no protocol"))?;
+  let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+  let host_port = authority.rsplit_once('@').map(|(_, h)| h).unwrap_or(authority);
+  let (host, port) = if let Some(v6) = host_port.strip_prefix('[') {
+    let (host, after) = v6
+      .split_once(']')
+      .ok_or_else(|| anyhow!("invalid IPv6 host in source URI"))?;
+    (host, after.strip_prefix(':'))
+  } else {
+    match host_port.rsplit_once(':') {
+      Some((host, port)) => (host, Some(port)),
+      None => (host_port, None),
+    }
+  };
+  if host.is_empty() {
+    bail!("source URI has no host");
+  }
+  let port = match port {
+    Some(p) => Some(p.parse().map_err(|_| anyhow!("invalid port in source URI"))?),
+    None => None,
+  };
+  Ok(ParsedUri {
+    scheme: scheme.to_ascii_lowercase(),
+    host: host.to_string(),
+    port,
+  })
+}
+
+/// Whether the URI asks FFmpeg to listen for the camera to connect in
+fn is_push(scheme: &str, uri: &str) -> bool {
+  let Some((_, query)) = uri.split_once('?') else {
+    return false;
+  };
+  query.split('&').any(|pair| match pair.split_once('=') {
+    Some(("listen", value)) => value != "0",
+    Some(("mode", value)) => scheme == "srt" && value == "listener",
+    None => pair == "listen",
+    _ => false,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cidrs(list: &[&str]) -> Vec<Cidr> {
+    list.iter().map(|c| Cidr::parse(c).unwrap()).collect()
+  }
+
+  #[test]
+  fn cidr_matches_ranges() {
+    let net = Cidr::parse("10.20.0.0/16").unwrap();
+    assert!(net.contains("10.20.3.4".parse().unwrap()));
+    assert!(net.contains("::ffff:10.20.3.4".parse().unwrap()));
+    assert!(!net.contains("10.21.0.1".parse().unwrap()));
+    assert!(Cidr::parse("0.0.0.0/0").unwrap().contains("8.8.8.8".parse().unwrap()));
+    assert!(Cidr::parse("fd00::/8").unwrap().contains("fd12::1".parse().unwrap()));
+    assert!(Cidr::parse("192.168.1.10").unwrap().contains("192.168.1.10".parse().unwrap()));
+    assert!(Cidr::parse("10.0.0.0/33").is_err());
+    assert!(Cidr::parse("camera.local/24").is_err());
+  }
+
+  #[tokio::test]
+  async fn rejects_unknown_protocols_and_addresses() {
+    let acl = IngestAcl::default().with_cidrs(cidrs(&["10.20.0.0/16", "fd00::/8"]));
+    assert_eq!(
+      acl.check("rtsp://admin:pw@10.20.1.5:554/Streaming/101").await.unwrap(),
+      SourceKind::Pull
+    );
+    assert!(acl.check("rtsp://[fd00::5]/live").await.is_ok());
+    assert!(acl.check("rtsp://192.168.1.5/live").await.is_err());
+    assert!(acl.check("rtsp://localhost/live").await.is_err());
+    assert!(acl.check("file:///etc/passwd").await.is_err());
+    assert!(acl.check("no-protocol").await.is_err());
+
+    let any_host = IngestAcl::default().with_schemes(&["rtsp"]);
+    assert!(any_host.check("rtsp://camera.example/live").await.is_ok());
+    assert!(any_host.check("http://10.20.1.5/stream.m3u8").await.is_err());
+  }
+
+  #[tokio::test]
+  async fn push_sources_need_client_certificates() {
+    let uri = "rtmps://0.0.0.0:1936/live/gate?listen=1";
+    assert!(IngestAcl::default().check(uri).await.is_err());
+
+    let acl = IngestAcl::default().with_push(PushTls {
+      ca_file: "/certs/cameras-ca.pem".into(),
+      cert_file: Some("/certs/node.pem".into()),
+      key_file: Some("/certs/node.key".into()),
+    });
+    assert_eq!(acl.check(uri).await.unwrap(), SourceKind::Push);
+    assert!(acl.check("rtmp://0.0.0.0/live?listen=1").await.is_err());
+    assert!(acl.check("srt://0.0.0.0:9000?mode=listener").await.is_err());
+
+    let args = acl.input_args(uri).join(" ");
+    assert!(args.contains("-tls_verify 1 -ca_file /certs/cameras-ca.pem"));
+    assert!(acl.input_args("rtsp://10.20.1.5/live").is_empty());
+  }
+}
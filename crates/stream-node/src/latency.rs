@@ -0,0 +1,106 @@
+//! Ingest latency of running streams.
+//!
+//! Pipelines stamp each segment with the time its first frame was ingested
+//! (see `common::latency`). Every few seconds the newest segment of each
+//! running stream is compared with the time FFmpeg finished writing it: one
+//! segment duration plus however far the pipeline runs behind. Exported as
+//! `stream_ingest_latency_seconds` and in `GET /streams`; playback-service
+//! measures the rest of the way to the viewer.
+
+use crate::metrics::STREAM_INGEST_LATENCY_SECONDS;
+use crate::stream;
+use common::latency::live_edge;
+use once_cell::sync::Lazy;
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::Mutex,
+  time::{Duration, UNIX_EPOCH},
+};
+use tracing::debug;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+static LATENCY: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Latest ingest latency of a running stream, once it has a stamped segment
+pub fn ingest_latency_ms(stream_id: &str) -> Option<u64> {
+  LATENCY.lock().unwrap_or_else(|e| e.into_inner()).get(stream_id).copied()
+}
+
+/// Measure the ingest latency of running streams in the background
+pub fn start_measuring() {
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    loop {
+      ticker.tick().await;
+      let streams: Vec<(String, PathBuf)> = stream::list_streams()
+        .await
+        .into_iter()
+        .filter(|s| s.running)
+        .map(|s| (s.id, s.output_dir))
+        .collect();
+      let measured = tokio::task::spawn_blocking(move || {
+        streams
+          .into_iter()
+          .filter_map(|(id, dir)| publish_latency_ms(&dir).map(|ms| (id, ms)))
+          .collect::<HashMap<String, u64>>()
+      })
+      .await
+      .unwrap_or_default();
+      record(measured);
+    }
+  });
+}
+
+fn record(measured: HashMap<String, u64>) {
+  let mut latest = LATENCY.lock().unwrap_or_else(|e| e.into_inner());
+  for id in latest.keys().filter(|id| !measured.contains_key(*id)) {
+    let _ = STREAM_INGEST_LATENCY_SECONDS.remove_label_values(&[id]);
+  }
+  for (id, ms) in &measured {
+    debug!(id = %id, latency_ms = ms, "stream ingest latency");
+    STREAM_INGEST_LATENCY_SECONDS
+      .with_label_values(&[id])
+      .set(*ms as f64 / 1000.0);
+  }
+  *latest = measured;
+}
+
+/// Time between the newest segment's first frame being ingested and the
+/// segment being written
+fn publish_latency_ms(dir: &Path) -> Option<u64> {
+  let playlist = std::fs::read_to_string(dir.join("index.m3u8")).ok()?;
+  let segment = live_edge(&playlist)?;
+  let written = std::fs::metadata(dir.join(&segment.name)).ok()?.modified().ok()?;
+  let written_ms = written.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+  Some(written_ms.saturating_sub(segment.ingest_ms))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn latency_runs_from_ingest_to_segment_write() {
+    let dir = std::env::temp_dir().join(format!("stream-node-latency-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+      dir.join("index.m3u8"),
+      "#EXTM3U\n#EXT-X-PROGRAM-DATE-TIME:2024-06-12T02:00:00.000+0000\n#EXTINF:2.000000,\nsegment_00003.ts\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("segment_00003.ts"), b"ts").unwrap();
+    let written_ms = std::fs::metadata(dir.join("segment_00003.ts"))
+      .unwrap()
+      .modified()
+      .unwrap()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_millis() as u64;
+
+    assert_eq!(publish_latency_ms(&dir), Some(written_ms - 1_718_157_600_000));
+    assert_eq!(publish_latency_ms(&dir.join("missing")), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,63 @@
+use axum::{
+  middleware,
+  routing::{delete, get, post},
+  Router,
+};
+use common::api_version::{self, Deprecation};
+use telemetry::trace_http_request;
+use tower::ServiceBuilder;
+
+pub mod api;
+pub mod bandwidth;
+pub mod bitrate;
+pub mod compat;
+pub mod config;
+pub mod ingest;
+pub mod latency;
+pub mod metrics;
+pub mod mosaic;
+pub mod motion;
+pub mod storage;
+pub mod stream;
+
+/// Legacy GET start/stop, replaced by `POST /start` and `DELETE /stop`
+/// (deprecated 2026-10-16, sunset 2027-04-16)
+pub const DEPRECATIONS: &[Deprecation] = &[
+  Deprecation {
+    method: "GET",
+    path: "/start",
+    deprecated_at: 1_792_108_800,
+    sunset: 1_807_833_600,
+    successor: Some("/start"),
+  },
+  Deprecation {
+    method: "GET",
+    path: "/stop",
+    deprecated_at: 1_792_108_800,
+    sunset: 1_807_833_600,
+    successor: Some("/stop"),
+  },
+];
+
+/// HTTP API of a stream node
+pub fn router() -> Router {
+  let router = Router::new()
+    .route("/healthz", get(api::healthz))
+    .route("/readyz", get(api::readyz))
+    .route("/streams", get(api::list_streams))
+    .route("/streams/:id/motion", get(api::stream_motion))
+    .route("/mosaics", get(api::list_mosaics).post(api::start_mosaic))
+    .route("/mosaics/:id", delete(api::stop_mosaic))
+    // Recommended REST endpoints with proper HTTP methods
+    .route("/start", post(api::start_stream))
+    .route("/stop", delete(api::stop_stream))
+    // Legacy GET endpoints (deprecated but maintained for compatibility)
+    .route("/start", get(api::start_stream_api))
+    .route("/stop", get(api::stop_stream_api))
+    .route("/metrics", get(|| async { metrics::render() }))
+    .layer(
+      ServiceBuilder::new()
+        .layer(middleware::from_fn(trace_http_request))
+    );
+  api_version::versioned(router, "stream-node", DEPRECATIONS)
+}
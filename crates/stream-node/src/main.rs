@@ -1,17 +1,10 @@
-use axum::{middleware, routing::{delete, get, post}, Router};
-use telemetry::{trace_http_request, TracingConfig};
+use common::bandwidth::{BandwidthReporter, BandwidthRole};
+use common::nodes::{self, NodeAnnouncer, NodeKind};
+use stream_node::config::Config;
+use telemetry::TracingConfig;
 use tokio::net::TcpListener;
-use tower::ServiceBuilder;
-use tracing::info;
-
-mod api;
-mod compat;
-mod config;
-mod metrics;
-mod storage;
-mod stream;
-
-use config::Config;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -29,29 +22,86 @@ async fn main() -> anyhow::Result<()> {
 
   // Load configuration
   let config = Config::from_env()?;
+  // Refuse sources outside the camera allowlist before serving the API
+  stream_node::ingest::init()?;
 
-  let app = Router::new()
-    .route("/healthz", get(api::healthz))
-    .route("/readyz", get(api::readyz))
-    .route("/streams", get(api::list_streams))
-    // Recommended REST endpoints with proper HTTP methods
-    .route("/start", post(api::start_stream))
-    .route("/stop", delete(api::stop_stream))
-    // Legacy GET endpoints (deprecated but maintained for compatibility)
-    .route("/start", get(api::start_stream_api))
-    .route("/stop", get(api::stop_stream_api))
-    .route("/metrics", get(|| async { metrics::render() }))
-    .layer(
-      ServiceBuilder::new()
-        .layer(middleware::from_fn(trace_http_request))
-    );
+  let app = stream_node::router();
 
   let listener = TcpListener::bind(&config.bind_addr).await?;
   info!(addr = %config.bind_addr, "stream-node started");
-  axum::serve(listener, app).await?;
+
+  // Register with the coordinator so gateways route streams to this node
+  let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| "stream-node".to_string());
+  let mut registration = None;
+  if let Some(announcer) = NodeAnnouncer::from_env(NodeKind::Stream, &node_id).await? {
+    let heartbeat = announcer.clone().spawn();
+    registration = Some((announcer, heartbeat));
+  }
+
+  // Report camera ingest against the uplink's bandwidth budget
+  if let Some(reporter) = BandwidthReporter::from_env(BandwidthRole::Stream, &node_id).await? {
+    reporter.spawn(stream_node::bandwidth::StreamBandwidth);
+  }
+
+  // Report stream bitrates to alert-service for anomaly detection
+  stream_node::bitrate::start_reporting();
+
+  // Measure how far each stream's newest segment runs behind its camera
+  stream_node::latency::start_measuring();
+
+  axum::serve(listener, app)
+    .with_graceful_shutdown(async move {
+      shutdown_signal().await;
+      drain(registration).await;
+    })
+    .await?;
 
   // Shutdown tracing provider
   telemetry::shutdown_tracing();
 
   Ok(())
 }
+
+async fn shutdown_signal() {
+  let ctrl_c = async {
+    let _ = tokio::signal::ctrl_c().await;
+  };
+
+  #[cfg(unix)]
+  let terminate = async {
+    use tokio::signal::unix::{SignalKind, signal};
+    if let Ok(mut sigterm) = signal(SignalKind::terminate()) {
+      let _ = sigterm.recv().await;
+    }
+  };
+
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  tokio::select! {
+      _ = ctrl_c => {},
+      _ = terminate => {},
+  }
+
+  info!("shutdown signal received");
+}
+
+/// Leave the gateways' routing and finalize running streams; the API keeps
+/// serving (and refusing new streams) until this returns
+async fn drain(registration: Option<(NodeAnnouncer, JoinHandle<()>)>) {
+  if let Some((announcer, _)) = &registration {
+    if let Err(e) = announcer.drain().await {
+      warn!(error = %e, "failed to mark node as draining");
+    }
+  }
+
+  let grace = nodes::shutdown_grace();
+  tokio::join!(stream_node::stream::drain(grace), stream_node::mosaic::drain(grace));
+
+  if let Some((announcer, heartbeat)) = registration {
+    heartbeat.abort();
+    if let Err(e) = announcer.deregister().await {
+      warn!(error = %e, "failed to deregister node");
+    }
+  }
+}
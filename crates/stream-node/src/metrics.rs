@@ -1,5 +1,8 @@
+use common::ffmpeg_progress::ProgressSample;
 use once_cell::sync::Lazy;
-use prometheus::{Encoder, IntCounter, IntGauge, Registry, TextEncoder};
+use prometheus::{
+  Encoder, GaugeVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
 
 pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
 
@@ -9,6 +12,12 @@ pub static STREAMS_RUNNING: Lazy<IntGauge> = Lazy::new(|| {
   g
 });
 
+pub static MOSAICS_RUNNING: Lazy<IntGauge> = Lazy::new(|| {
+  let g = IntGauge::new("mosaics_running", "Number of running preview mosaics").unwrap();
+  REGISTRY.register(Box::new(g.clone())).ok();
+  g
+});
+
 pub static FFMPEG_CRASHES_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
   let c = IntCounter::new("ffmpeg_crashes_total", "Total FFmpeg pipeline crashes").unwrap();
   REGISTRY.register(Box::new(c.clone())).ok();
@@ -21,6 +30,109 @@ pub static FFMPEG_RESTARTS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
   c
 });
 
+pub static MOTION_SEGMENTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+  let c = IntCounterVec::new(
+    Opts::new("motion_segments_total", "Stream segments analysed for motion"),
+    &["activity"],
+  )
+  .unwrap();
+  REGISTRY.register(Box::new(c.clone())).ok();
+  c
+});
+
+pub static FFMPEG_PIPELINE_SPEED: Lazy<GaugeVec> = Lazy::new(|| {
+  let g = GaugeVec::new(
+    Opts::new("ffmpeg_pipeline_speed", "FFmpeg processing speed against real time"),
+    &["stream_id"],
+  )
+  .unwrap();
+  REGISTRY.register(Box::new(g.clone())).ok();
+  g
+});
+
+pub static FFMPEG_PIPELINE_FPS: Lazy<GaugeVec> = Lazy::new(|| {
+  let g = GaugeVec::new(
+    Opts::new("ffmpeg_pipeline_fps", "Frames per second FFmpeg writes"),
+    &["stream_id"],
+  )
+  .unwrap();
+  REGISTRY.register(Box::new(g.clone())).ok();
+  g
+});
+
+pub static FFMPEG_PIPELINE_LAG_SECONDS: Lazy<GaugeVec> = Lazy::new(|| {
+  let g = GaugeVec::new(
+    Opts::new(
+      "ffmpeg_pipeline_lag_seconds",
+      "Seconds of video backed up in front of the encoder",
+    ),
+    &["stream_id"],
+  )
+  .unwrap();
+  REGISTRY.register(Box::new(g.clone())).ok();
+  g
+});
+
+pub static FFMPEG_DROPPED_FRAMES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+  let c = IntCounterVec::new(
+    Opts::new("ffmpeg_dropped_frames_total", "Frames FFmpeg dropped"),
+    &["stream_id"],
+  )
+  .unwrap();
+  REGISTRY.register(Box::new(c.clone())).ok();
+  c
+});
+
+pub static FFMPEG_DUP_FRAMES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+  let c = IntCounterVec::new(
+    Opts::new("ffmpeg_dup_frames_total", "Frames FFmpeg duplicated"),
+    &["stream_id"],
+  )
+  .unwrap();
+  REGISTRY.register(Box::new(c.clone())).ok();
+  c
+});
+
+pub static STREAM_INGEST_LATENCY_SECONDS: Lazy<GaugeVec> = Lazy::new(|| {
+  let g = GaugeVec::new(
+    Opts::new(
+      "stream_ingest_latency_seconds",
+      "Time from the first frame of a stream's newest segment reaching the node until the segment was published",
+    ),
+    &["stream_id"],
+  )
+  .unwrap();
+  REGISTRY.register(Box::new(g.clone())).ok();
+  g
+});
+
+/// Export a stream's pipeline progress; `None` once its FFmpeg is gone
+pub fn record_pipeline_progress(stream_id: &str, sample: Option<&ProgressSample>) {
+  let Some(sample) = sample else {
+    let _ = FFMPEG_PIPELINE_SPEED.remove_label_values(&[stream_id]);
+    let _ = FFMPEG_PIPELINE_FPS.remove_label_values(&[stream_id]);
+    let _ = FFMPEG_PIPELINE_LAG_SECONDS.remove_label_values(&[stream_id]);
+    let _ = FFMPEG_DROPPED_FRAMES_TOTAL.remove_label_values(&[stream_id]);
+    let _ = FFMPEG_DUP_FRAMES_TOTAL.remove_label_values(&[stream_id]);
+    return;
+  };
+  if let Some(speed) = sample.progress.speed {
+    FFMPEG_PIPELINE_SPEED.with_label_values(&[stream_id]).set(speed);
+  }
+  FFMPEG_PIPELINE_FPS
+    .with_label_values(&[stream_id])
+    .set(sample.progress.fps);
+  FFMPEG_PIPELINE_LAG_SECONDS
+    .with_label_values(&[stream_id])
+    .set(sample.lag_secs);
+  FFMPEG_DROPPED_FRAMES_TOTAL
+    .with_label_values(&[stream_id])
+    .inc_by(sample.new_drop_frames);
+  FFMPEG_DUP_FRAMES_TOTAL
+    .with_label_values(&[stream_id])
+    .inc_by(sample.new_dup_frames);
+}
+
 pub fn render() -> String {
   let mut buf = Vec::new();
   let encoder = TextEncoder::new();
@@ -0,0 +1,492 @@
+//! Preview mosaics for video walls.
+//!
+//! A mosaic composites several camera streams into one low-bitrate HLS
+//! stream at `hls_root()/mosaics/<id>/index.m3u8`, so a wall display decodes
+//! a single stream instead of one per camera. Tiles name a stream running on
+//! this node (its substream is pulled when it has one) or a source URI.
+//! FFmpeg scales every input into its tile, keeping the aspect ratio, and
+//! lays the tiles out row by row with `xstack`; cells without a tile stay
+//! black. Crashed mosaics restart with the same backoff as stream pipelines.
+
+use crate::metrics::{record_pipeline_progress, FFMPEG_CRASHES_TOTAL, FFMPEG_RESTARTS_TOTAL, MOSAICS_RUNNING};
+use crate::stream::{self, calculate_restart_delay, hls_root, readiness_timeout, wait_for_hls_ready};
+use anyhow::{anyhow, bail, Result};
+use common::ffmpeg_progress::{self, PROGRESS_ARGS};
+use once_cell::sync::Lazy;
+use std::{
+  collections::HashMap,
+  fs,
+  io::Write,
+  path::PathBuf,
+  process::{Child, Command, Stdio},
+  time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// Tiles of one mosaic
+pub const MAX_TILES: usize = 36;
+/// Mosaics transcode every tile, so far fewer fit on a node than streams
+const MAX_CONCURRENT_MOSAICS: usize = 16;
+const MAX_RESTART_ATTEMPTS: u32 = 5;
+
+pub const DEFAULT_TILE_WIDTH: u32 = 480;
+pub const DEFAULT_TILE_HEIGHT: u32 = 270;
+pub const DEFAULT_FPS: u32 = 10;
+pub const DEFAULT_BITRATE_KBPS: u32 = 2000;
+
+/// Where a tile's video comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TileSource {
+  /// A stream running on this node
+  Stream(String),
+  /// A camera or stream URI pulled directly
+  Uri(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct MosaicSpec {
+  pub id: String,
+  pub tiles: Vec<TileSource>,
+  /// Tiles per row; the smallest square grid that fits the tiles when unset
+  pub columns: Option<u32>,
+  pub tile_width: u32,
+  pub tile_height: u32,
+  pub fps: u32,
+  pub bitrate_kbps: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct MosaicStatus {
+  pub id: String,
+  pub tiles: Vec<TileSource>,
+  pub columns: u32,
+  pub rows: u32,
+  pub width: u32,
+  pub height: u32,
+  pub running: bool,
+  pub playlist: PathBuf,
+}
+
+struct MosaicEntry {
+  child: Child,
+  status: MosaicStatus,
+  spec: MosaicSpec,
+  restart_count: u32,
+  monitor_handle: Option<JoinHandle<()>>,
+}
+
+static REGISTRY: Lazy<Mutex<HashMap<String, MosaicEntry>>> =
+  Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl MosaicSpec {
+  pub fn validate(&self) -> Result<()> {
+    if self.tiles.is_empty() || self.tiles.len() > MAX_TILES {
+      bail!("a mosaic needs 1 to {} tiles", MAX_TILES);
+    }
+    if let Some(columns) = self.columns {
+      if columns == 0 || columns as usize > self.tiles.len() {
+        bail!("columns must be between 1 and the number of tiles");
+      }
+    }
+    if !(64..=1920).contains(&self.tile_width) || !(64..=1080).contains(&self.tile_height) {
+      bail!("tile size must be between 64x64 and 1920x1080");
+    }
+    // yuv420p needs even dimensions
+    if self.tile_width % 2 != 0 || self.tile_height % 2 != 0 {
+      bail!("tile width and height must be even");
+    }
+    if !(1..=30).contains(&self.fps) {
+      bail!("fps must be between 1 and 30");
+    }
+    if !(100..=20_000).contains(&self.bitrate_kbps) {
+      bail!("bitrate_kbps must be between 100 and 20000");
+    }
+    Ok(())
+  }
+
+  /// Columns and rows of the grid
+  pub fn grid(&self) -> (u32, u32) {
+    let tiles = self.tiles.len().max(1) as u32;
+    let columns = self
+      .columns
+      .unwrap_or_else(|| (1..=tiles).find(|c| c * c >= tiles).unwrap_or(tiles));
+    (columns, tiles.div_ceil(columns))
+  }
+}
+
+/// Directory a mosaic writes its playlist and segments to
+pub fn output_dir(id: &str) -> PathBuf {
+  hls_root().join("mosaics").join(id)
+}
+
+/// `xstack` layout placing `tiles` tiles of `width`x`height` row by row in
+/// `columns` columns
+pub fn xstack_layout(tiles: usize, columns: u32, width: u32, height: u32) -> String {
+  (0..tiles as u32)
+    .map(|i| format!("{}_{}", (i % columns) * width, (i / columns) * height))
+    .collect::<Vec<_>>()
+    .join("|")
+}
+
+/// Build FFmpeg arguments compositing `sources` (resolved tile URIs, in
+/// tile order) into one H.264 HLS stream
+pub fn build_mosaic_args(spec: &MosaicSpec, sources: &[String], playlist: &str, segment: &str) -> Vec<String> {
+  let mut args: Vec<String> = Vec::new();
+
+  for source in sources {
+    if source.starts_with("rtsp://") || source.starts_with("rtsps://") {
+      args.push("-rtsp_transport".into());
+      args.push("tcp".into());
+    }
+    args.push("-i".into());
+    args.push(source.clone());
+  }
+
+  // Fit each input into its tile, letterboxed, at the mosaic's frame rate
+  let (w, h) = (spec.tile_width, spec.tile_height);
+  let mut filter: Vec<String> = sources
+    .iter()
+    .enumerate()
+    .map(|(i, _)| {
+      let label = if sources.len() == 1 { "out".to_string() } else { format!("t{i}") };
+      format!(
+        "[{i}:v]scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={}[{label}]",
+        spec.fps
+      )
+    })
+    .collect();
+  if sources.len() > 1 {
+    let (columns, _) = spec.grid();
+    let inputs: String = (0..sources.len()).map(|i| format!("[t{i}]")).collect();
+    filter.push(format!(
+      "{inputs}xstack=inputs={}:layout={}:fill=black[out]",
+      sources.len(),
+      xstack_layout(sources.len(), columns, w, h)
+    ));
+  }
+  args.push("-filter_complex".into());
+  args.push(filter.join(";"));
+  args.push("-map".into());
+  args.push("[out]".into());
+  args.push("-an".into());
+
+  // Low-bitrate H.264 with a keyframe at every segment boundary
+  let bitrate = spec.bitrate_kbps;
+  for arg in [
+    "-c:v", "libx264", "-preset", "veryfast", "-tune", "zerolatency", "-pix_fmt", "yuv420p",
+  ] {
+    args.push(arg.into());
+  }
+  args.push("-b:v".into());
+  args.push(format!("{bitrate}k"));
+  args.push("-maxrate".into());
+  args.push(format!("{bitrate}k"));
+  args.push("-bufsize".into());
+  args.push(format!("{}k", bitrate * 2));
+  args.push("-g".into());
+  args.push((spec.fps * 2).to_string());
+  args.push("-sc_threshold".into());
+  args.push("0".into());
+
+  for arg in ["-f", "hls", "-hls_time", "2", "-hls_list_size", "5", "-hls_flags", "delete_segments"] {
+    args.push(arg.into());
+  }
+  args.push("-hls_segment_filename".into());
+  args.push(segment.to_string());
+  args.push(playlist.to_string());
+
+  args
+}
+
+/// URIs of the tiles, in order; fails when a tile names a stream that is
+/// not running on this node
+async fn resolve_sources(tiles: &[TileSource]) -> Result<Vec<String>> {
+  let mut sources = Vec::with_capacity(tiles.len());
+  for tile in tiles {
+    match tile {
+      TileSource::Uri(uri) => sources.push(uri.clone()),
+      TileSource::Stream(id) => match stream::preview_source(id).await {
+        Some(uri) => sources.push(uri),
+        None => bail!("stream '{}' is not running on this node", id),
+      },
+    }
+  }
+  Ok(sources)
+}
+
+pub async fn start_mosaic(spec: &MosaicSpec) -> Result<()> {
+  spec.validate()?;
+  {
+    let reg = REGISTRY.lock().await;
+    if reg.contains_key(&spec.id) {
+      return Err(anyhow!("mosaic '{}' already running", spec.id));
+    }
+    if reg.len() >= MAX_CONCURRENT_MOSAICS {
+      return Err(anyhow!(
+        "Maximum concurrent mosaics ({}) exceeded. Cannot start new mosaic.",
+        MAX_CONCURRENT_MOSAICS
+      ));
+    }
+  }
+  launch(spec, 0).await
+}
+
+/// Spawn a mosaic's pipeline and register it once its playlist is ready.
+/// A restart (`restart_count` > 0) replaces the crashed entry, and is
+/// dropped when the mosaic was stopped while it warmed up.
+async fn launch(spec: &MosaicSpec, restart_count: u32) -> Result<()> {
+  if stream::is_draining() {
+    return Err(anyhow!("stream node is shutting down"));
+  }
+  let sources = resolve_sources(&spec.tiles).await?;
+
+  let out_dir = output_dir(&spec.id);
+  fs::create_dir_all(&out_dir)?;
+  let playlist = out_dir.join("index.m3u8");
+  let segment = out_dir.join("segment_%05d.ts");
+
+  let mut args = build_mosaic_args(
+    spec,
+    &sources,
+    playlist.to_str().ok_or_else(|| anyhow!("bad playlist path"))?,
+    segment.to_str().ok_or_else(|| anyhow!("bad segment path"))?,
+  );
+  args.splice(0..0, PROGRESS_ARGS.iter().map(|a| a.to_string()));
+  info!(id=%spec.id, tiles = sources.len(), args=?args, "starting mosaic pipeline");
+
+  // stdin stays open so FFmpeg can be asked to quit cleanly on drain
+  let mut child = Command::new("ffmpeg")
+    .args(&args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::inherit())
+    .spawn()?;
+  if let Some(stdout) = child.stdout.take() {
+    let label = format!("mosaic/{}", spec.id);
+    let spawned = ffmpeg_progress::spawn_reader(format!("progress-{}", label), stdout, move |sample| {
+      record_pipeline_progress(&label, sample)
+    });
+    if let Err(e) = spawned {
+      warn!(id=%spec.id, error=%e, "failed to read FFmpeg progress");
+    }
+  }
+
+  let ready = wait_for_hls_ready(&out_dir, readiness_timeout()).await;
+  let mut reg = REGISTRY.lock().await;
+  let refused = if stream::is_draining() {
+    Some(anyhow!("stream node is shutting down"))
+  } else if !ready {
+    Some(anyhow!("mosaic '{}' produced no HLS in time", spec.id))
+  } else if (restart_count > 0) != reg.contains_key(&spec.id) {
+    Some(anyhow!("mosaic '{}' was started or stopped meanwhile", spec.id))
+  } else {
+    None
+  };
+  if let Some(e) = refused {
+    let _ = child.kill();
+    let _ = child.wait();
+    return Err(e);
+  }
+
+  let (columns, rows) = spec.grid();
+  let status = MosaicStatus {
+    id: spec.id.clone(),
+    tiles: spec.tiles.clone(),
+    columns,
+    rows,
+    width: columns * spec.tile_width,
+    height: rows * spec.tile_height,
+    running: true,
+    playlist,
+  };
+  reg.insert(
+    spec.id.clone(),
+    MosaicEntry {
+      child,
+      status,
+      spec: spec.clone(),
+      restart_count,
+      monitor_handle: Some(spawn_monitor_task(spec.id.clone())),
+    },
+  );
+  if restart_count == 0 {
+    MOSAICS_RUNNING.inc();
+  }
+  info!(id=%spec.id, columns, rows, "mosaic ready");
+  Ok(())
+}
+
+/// Watch a mosaic's FFmpeg process and start it again with exponential
+/// backoff when it exits. The crashed entry stays registered (not running)
+/// until a restart replaces it.
+fn spawn_monitor_task(id: String) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    loop {
+      tokio::time::sleep(Duration::from_secs(5)).await;
+
+      let restart = {
+        let mut reg = REGISTRY.lock().await;
+        let Some(entry) = reg.get_mut(&id) else {
+          return;
+        };
+        match entry.child.try_wait() {
+          Ok(Some(exit_status)) => {
+            error!(id = %id, exit_code = ?exit_status.code(), restart_count = entry.restart_count, "mosaic pipeline crashed");
+            FFMPEG_CRASHES_TOTAL.inc();
+            entry.status.running = false;
+            if entry.restart_count >= MAX_RESTART_ATTEMPTS {
+              warn!(id = %id, max_attempts = MAX_RESTART_ATTEMPTS, "Maximum restart attempts reached, giving up");
+              return;
+            }
+            entry.restart_count += 1;
+            Some((entry.spec.clone(), entry.restart_count))
+          }
+          Ok(None) => None,
+          Err(e) => {
+            warn!(id = %id, error = %e, "Failed to check process status");
+            None
+          }
+        }
+      };
+
+      if let Some((spec, attempt)) = restart {
+        let delay = calculate_restart_delay(attempt - 1);
+        info!(id = %id, attempt, delay_secs = delay.as_secs(), "Scheduling mosaic pipeline restart");
+        FFMPEG_RESTARTS_TOTAL.inc();
+        tokio::time::sleep(delay).await;
+        match launch(&spec, attempt).await {
+          // The new entry brings its own monitor
+          Ok(()) => return,
+          Err(e) => error!(id = %id, error = %e, "Failed to restart mosaic pipeline"),
+        }
+      }
+    }
+  })
+}
+
+pub async fn stop_mosaic(id: &str) -> Result<()> {
+  let mut reg = REGISTRY.lock().await;
+  let Some(mut entry) = reg.remove(id) else {
+    return Err(anyhow!("mosaic '{}' not found", id));
+  };
+  let _ = entry.child.kill();
+  let _ = entry.child.wait();
+  if let Some(handle) = entry.monitor_handle {
+    handle.abort();
+  }
+  MOSAICS_RUNNING.dec();
+  Ok(())
+}
+
+pub async fn list_mosaics() -> Vec<MosaicStatus> {
+  let mut reg = REGISTRY.lock().await;
+  let mut list: Vec<MosaicStatus> = reg
+    .values_mut()
+    .map(|entry| {
+      entry.status.running = matches!(entry.child.try_wait(), Ok(None));
+      entry.status.clone()
+    })
+    .collect();
+  list.sort_by(|a, b| a.id.cmp(&b.id));
+  list
+}
+
+/// Ask every mosaic pipeline to quit; pipelines still running after
+/// `grace` are killed
+pub async fn drain(grace: Duration) {
+  let entries: Vec<(String, MosaicEntry)> = REGISTRY.lock().await.drain().collect();
+  let deadline = Instant::now() + grace;
+  let mut children = Vec::with_capacity(entries.len());
+  for (id, mut entry) in entries {
+    if let Some(handle) = entry.monitor_handle.take() {
+      handle.abort();
+    }
+    if let Some(mut stdin) = entry.child.stdin.take() {
+      let _ = stdin.write_all(b"q");
+    }
+    children.push((id, entry));
+  }
+
+  for (id, mut entry) in children {
+    loop {
+      match entry.child.try_wait() {
+        Ok(None) if Instant::now() < deadline => tokio::time::sleep(Duration::from_millis(100)).await,
+        Ok(None) => {
+          warn!(id = %id, "mosaic FFmpeg did not finish in time, killing it");
+          let _ = entry.child.kill();
+          let _ = entry.child.wait();
+          break;
+        }
+        _ => break,
+      }
+    }
+    MOSAICS_RUNNING.dec();
+    info!(id = %id, "mosaic finalized");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn spec(tiles: usize, columns: Option<u32>) -> MosaicSpec {
+    MosaicSpec {
+      id: "wall".into(),
+      tiles: (0..tiles).map(|i| TileSource::Uri(format!("rtsp://cam{i}/sub"))).collect(),
+      columns,
+      tile_width: DEFAULT_TILE_WIDTH,
+      tile_height: DEFAULT_TILE_HEIGHT,
+      fps: DEFAULT_FPS,
+      bitrate_kbps: DEFAULT_BITRATE_KBPS,
+    }
+  }
+
+  #[test]
+  fn grid_defaults_to_smallest_square() {
+    assert_eq!(spec(1, None).grid(), (1, 1));
+    assert_eq!(spec(4, None).grid(), (2, 2));
+    assert_eq!(spec(5, None).grid(), (3, 2));
+    assert_eq!(spec(9, None).grid(), (3, 3));
+    assert_eq!(spec(6, Some(6)).grid(), (6, 1));
+    assert_eq!(spec(7, Some(2)).grid(), (2, 4));
+  }
+
+  #[test]
+  fn layout_places_tiles_row_by_row() {
+    assert_eq!(xstack_layout(3, 2, 480, 270), "0_0|480_0|0_270");
+  }
+
+  #[test]
+  fn args_composite_every_source() {
+    let spec = spec(3, None);
+    let sources: Vec<String> = vec!["rtsp://a".into(), "rtsp://b".into(), "http://c/index.m3u8".into()];
+    let args = build_mosaic_args(&spec, &sources, "/m/index.m3u8", "/m/segment_%05d.ts");
+    let joined = args.join(" ");
+    assert_eq!(args.iter().filter(|a| *a == "-i").count(), 3);
+    assert_eq!(args.iter().filter(|a| *a == "-rtsp_transport").count(), 2);
+    assert!(joined.contains("xstack=inputs=3:layout=0_0|480_0|0_270:fill=black[out]"));
+    assert!(joined.contains("-b:v 2000k"));
+    assert!(joined.ends_with("/m/segment_%05d.ts /m/index.m3u8"));
+  }
+
+  #[test]
+  fn single_tile_skips_xstack() {
+    let args = build_mosaic_args(&spec(1, None), &["rtsp://a".into()], "/p.m3u8", "/s_%05d.ts");
+    let joined = args.join(" ");
+    assert!(!joined.contains("xstack"));
+    assert!(joined.contains("fps=10[out]"));
+  }
+
+  #[test]
+  fn validate_rejects_bad_specs() {
+    assert!(spec(4, None).validate().is_ok());
+    assert!(spec(0, None).validate().is_err());
+    assert!(spec(MAX_TILES + 1, None).validate().is_err());
+    assert!(spec(4, Some(5)).validate().is_err());
+    let mut odd = spec(4, None);
+    odd.tile_width = 481;
+    assert!(odd.validate().is_err());
+  }
+}
@@ -0,0 +1,269 @@
+//! Lightweight motion detection on the HLS segments of running streams.
+//!
+//! Each finished segment is decoded at a few frames per second into tiny
+//! grayscale thumbnails and consecutive thumbnails are differenced on the
+//! CPU; a segment has motion when enough pixels changed between any two of
+//! them. Results are kept per stream and served at `GET /streams/:id/motion`
+//! for recorders storing motion-only recordings.
+
+use crate::metrics::MOTION_SEGMENTS_TOTAL;
+use anyhow::{anyhow, Result};
+use common::motion::SegmentActivity;
+use once_cell::sync::Lazy;
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  path::{Path, PathBuf},
+  process::Stdio,
+  time::{Duration, UNIX_EPOCH},
+};
+use tokio::{process::Command, sync::Mutex, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+/// Thumbnail size frames are differenced at
+const SAMPLE_WIDTH: usize = 64;
+const SAMPLE_HEIGHT: usize = 36;
+
+/// Segments of activity kept per stream (an hour of 2 s segments)
+const MAX_SEGMENTS_KEPT: usize = 1800;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct MotionConfig {
+  /// Frames sampled per second of video
+  pub sample_fps: u32,
+  /// Luma change for a pixel to count as changed
+  pub pixel_delta: u8,
+  /// Fraction of changed pixels that counts as motion
+  pub threshold: f32,
+}
+
+impl Default for MotionConfig {
+  fn default() -> Self {
+    Self {
+      sample_fps: 2,
+      pixel_delta: 25,
+      threshold: 0.02,
+    }
+  }
+}
+
+impl MotionConfig {
+  /// `None` unless `STREAM_MOTION_DETECTION` is enabled
+  pub fn from_env() -> Option<Self> {
+    let enabled = std::env::var("STREAM_MOTION_DETECTION")
+      .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+      .unwrap_or(false);
+    if !enabled {
+      return None;
+    }
+    let defaults = Self::default();
+    let parse = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok());
+    Some(Self {
+      sample_fps: parse("STREAM_MOTION_SAMPLE_FPS")
+        .map(|v| v.clamp(1.0, 10.0) as u32)
+        .unwrap_or(defaults.sample_fps),
+      pixel_delta: parse("STREAM_MOTION_PIXEL_DELTA")
+        .map(|v| v.clamp(1.0, 255.0) as u8)
+        .unwrap_or(defaults.pixel_delta),
+      threshold: parse("STREAM_MOTION_THRESHOLD")
+        .map(|v| v.clamp(0.0, 1.0) as f32)
+        .unwrap_or(defaults.threshold),
+    })
+  }
+}
+
+static CONFIG: Lazy<Option<MotionConfig>> = Lazy::new(MotionConfig::from_env);
+
+static ACTIVITY: Lazy<Mutex<HashMap<String, VecDeque<SegmentActivity>>>> =
+  Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Start analysing the segments of `stream_id` if detection is enabled
+pub fn spawn(stream_id: String, dir: PathBuf) -> Option<JoinHandle<()>> {
+  let config = CONFIG.clone()?;
+  Some(tokio::spawn(async move {
+    info!(id = %stream_id, ?config, "motion detection started");
+    // Kept across restarts so recorders see no gap in the history
+    ACTIVITY.lock().await.entry(stream_id.clone()).or_default();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut previous: Option<Vec<u8>> = None;
+
+    loop {
+      tokio::time::sleep(POLL_INTERVAL).await;
+      let Ok(playlist) = tokio::fs::read_to_string(dir.join("index.m3u8")).await else {
+        continue;
+      };
+      let segments = parse_playlist(&playlist);
+      // Forget segments FFmpeg has rotated out of the playlist
+      seen.retain(|name| segments.iter().any(|(s, _)| s == name));
+
+      for (name, duration_secs) in segments {
+        if !seen.insert(name.clone()) {
+          continue;
+        }
+        let path = dir.join(&name);
+        let frames = match sample_frames(&path, &config).await {
+          Ok(frames) => frames,
+          Err(e) => {
+            warn!(id = %stream_id, segment = %name, error = %e, "failed to sample segment");
+            continue;
+          }
+        };
+        let score = motion_score(previous.as_deref(), &frames, config.pixel_delta);
+        if let Some(last) = frames.last() {
+          previous = Some(last.clone());
+        }
+        let motion = score >= config.threshold;
+        let end_ms = segment_end_ms(&path).await;
+        let activity = SegmentActivity {
+          segment: name,
+          start_ms: end_ms.saturating_sub((duration_secs * 1000.0) as u64),
+          end_ms,
+          score,
+          motion,
+        };
+        debug!(id = %stream_id, ?activity, "segment analysed");
+        MOTION_SEGMENTS_TOTAL
+          .with_label_values(&[if motion { "motion" } else { "idle" }])
+          .inc();
+        record(&stream_id, activity).await;
+      }
+    }
+  }))
+}
+
+async fn record(stream_id: &str, activity: SegmentActivity) {
+  let mut all = ACTIVITY.lock().await;
+  let segments = all.entry(stream_id.to_string()).or_default();
+  segments.push_back(activity);
+  while segments.len() > MAX_SEGMENTS_KEPT {
+    segments.pop_front();
+  }
+}
+
+/// Analysed segments of `stream_id` ending after `since_ms`, oldest first.
+/// `None` when the stream has never been analysed on this node.
+pub async fn activity(stream_id: &str, since_ms: u64) -> Option<Vec<SegmentActivity>> {
+  let all = ACTIVITY.lock().await;
+  let segments = all.get(stream_id)?;
+  Some(segments.iter().filter(|s| s.end_ms > since_ms).cloned().collect())
+}
+
+/// Segment file names and durations listed in an HLS media playlist
+fn parse_playlist(playlist: &str) -> Vec<(String, f64)> {
+  let mut segments = Vec::new();
+  let mut duration = None;
+  for line in playlist.lines().map(str::trim) {
+    if let Some(rest) = line.strip_prefix("#EXTINF:") {
+      duration = rest.split(',').next().and_then(|d| d.parse::<f64>().ok());
+    } else if !line.is_empty() && !line.starts_with('#') {
+      if let Some(duration) = duration.take() {
+        segments.push((line.to_string(), duration));
+      }
+    }
+  }
+  segments
+}
+
+/// Decode `path` into grayscale thumbnails at the sample rate
+async fn sample_frames(path: &Path, config: &MotionConfig) -> Result<Vec<Vec<u8>>> {
+  let filter = format!(
+    "fps={},scale={}:{},format=gray",
+    config.sample_fps, SAMPLE_WIDTH, SAMPLE_HEIGHT
+  );
+  let output = Command::new("ffmpeg")
+    .arg("-v")
+    .arg("error")
+    .arg("-i")
+    .arg(path)
+    .args(["-an", "-vf", &filter, "-f", "rawvideo", "-"])
+    .stdin(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .await?;
+  if !output.status.success() {
+    return Err(anyhow!(
+      "ffmpeg exited with {}: {}",
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  Ok(
+    output
+      .stdout
+      .chunks_exact(SAMPLE_WIDTH * SAMPLE_HEIGHT)
+      .map(<[u8]>::to_vec)
+      .collect(),
+  )
+}
+
+/// Largest fraction of changed pixels between consecutive frames, starting
+/// from the last frame of the previous segment
+fn motion_score(previous: Option<&[u8]>, frames: &[Vec<u8>], pixel_delta: u8) -> f32 {
+  let mut last = previous;
+  let mut score = 0.0f32;
+  for frame in frames {
+    if let Some(last) = last {
+      score = score.max(changed_fraction(last, frame, pixel_delta));
+    }
+    last = Some(frame.as_slice());
+  }
+  score
+}
+
+fn changed_fraction(a: &[u8], b: &[u8], pixel_delta: u8) -> f32 {
+  let len = a.len().min(b.len());
+  if len == 0 {
+    return 0.0;
+  }
+  let changed = a
+    .iter()
+    .zip(b)
+    .filter(|(x, y)| x.abs_diff(**y) >= pixel_delta)
+    .count();
+  changed as f32 / len as f32
+}
+
+/// FFmpeg writes a segment until it closes it, so its mtime is its end
+async fn segment_end_ms(path: &Path) -> u64 {
+  tokio::fs::metadata(path)
+    .await
+    .and_then(|m| m.modified())
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn playlist_lists_segments_with_durations() {
+    let playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:7\n\
+      #EXTINF:2.002000,\nsegment_00007.ts\n#EXTINF:1.968000,\nsegment_00008.ts\n";
+    assert_eq!(
+      parse_playlist(playlist),
+      vec![
+        ("segment_00007.ts".to_string(), 2.002),
+        ("segment_00008.ts".to_string(), 1.968)
+      ]
+    );
+  }
+
+  #[test]
+  fn frame_differences_score_motion() {
+    let still = vec![100u8; 100];
+    let mut moved = still.clone();
+    moved[..10].fill(200);
+    // Small noise below the pixel delta is ignored
+    let noisy: Vec<u8> = still.iter().map(|p| p + 5).collect();
+
+    assert_eq!(motion_score(None, &[still.clone(), noisy.clone()], 25), 0.0);
+    assert!((motion_score(None, &[still.clone(), moved.clone()], 25) - 0.1).abs() < 1e-6);
+    // The previous segment's last frame counts for the first frame
+    assert!((motion_score(Some(&moved), std::slice::from_ref(&still), 25) - 0.1).abs() < 1e-6);
+    assert_eq!(motion_score(None, &[still], 25), 0.0);
+  }
+}
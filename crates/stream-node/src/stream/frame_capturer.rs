@@ -1,15 +1,18 @@
 //! Frame capture and AI integration for active streams
 //!
 //! This module handles periodic frame extraction from active video streams
-//! and submits them to the AI service for processing.
+//! and submits them to the AI service for processing. When the AI node is
+//! saturated it answers with a suggested delay (or `429` with
+//! `Retry-After`), and the next frame is held back that long instead of
+//! queuing behind the plugins.
 
 use anyhow::{Context, Result};
 use base64::Engine;
+use common::ai_tasks::{FrameBackpressure, VideoFrame};
 use common::frame_extractor;
-use reqwest::Client;
-use serde_json::json;
+use reqwest::{Client, StatusCode};
 use std::time::Duration;
-use tokio::time;
+use tokio::time::{self, Instant};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
@@ -74,7 +77,8 @@ pub fn start_frame_capture(
             .build()
             .unwrap_or_else(|_| Client::new());
 
-        let mut interval = time::interval(Duration::from_secs(config.capture_interval_secs));
+        let capture_interval = Duration::from_secs(config.capture_interval_secs);
+        let mut next_capture = Instant::now();
         let mut frame_seq = 0u64;
 
         loop {
@@ -83,8 +87,9 @@ pub fn start_frame_capture(
                     info!(stream_id = %stream_id, "frame capture cancelled");
                     break;
                 }
-                _ = interval.tick() => {
+                _ = time::sleep_until(next_capture) => {
                     frame_seq += 1;
+                    next_capture += capture_interval;
 
                     // Extract frame from stream
                     match frame_extractor::extract_frame_jpeg(
@@ -102,21 +107,35 @@ pub fn start_frame_capture(
                             );
 
                             // Submit frame to AI service
-                            if let Err(e) = submit_frame_to_ai(
+                            match submit_frame_to_ai(
                                 &client,
-                                &config.ai_service_url,
-                                &config.ai_task_id,
+                                &config,
+                                &stream_id,
                                 frame_seq,
                                 jpeg_data,
                             )
                             .await
                             {
-                                warn!(
-                                    stream_id = %stream_id,
-                                    frame_seq = frame_seq,
-                                    error = %e,
-                                    "failed to submit frame to AI service"
-                                );
+                                Ok(Some(backpressure)) => {
+                                    next_capture = paced(next_capture, Instant::now(), &backpressure);
+                                    if backpressure.next_frame_delay_ms > 0 {
+                                        debug!(
+                                            stream_id = %stream_id,
+                                            queue_depth = backpressure.queue_depth,
+                                            delay_ms = backpressure.next_frame_delay_ms,
+                                            "AI service asked to slow down"
+                                        );
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(e) => {
+                                    warn!(
+                                        stream_id = %stream_id,
+                                        frame_seq = frame_seq,
+                                        error = %e,
+                                        "failed to submit frame to AI service"
+                                    );
+                                }
                             }
                         }
                         Err(e) => {
@@ -136,35 +155,54 @@ pub fn start_frame_capture(
     });
 }
 
-/// Submit a frame to the AI service
+/// When the next frame is captured: on schedule, unless the AI service
+/// asked to wait longer from `now`
+fn paced(next_capture: Instant, now: Instant, backpressure: &FrameBackpressure) -> Instant {
+    next_capture.max(now + backpressure.next_frame_delay())
+}
+
+/// Submit a frame to the AI service; returns the load it reported. A
+/// frame shed on a saturated node is not an error, only a reason to wait
 #[allow(dead_code)]
 async fn submit_frame_to_ai(
     client: &Client,
-    ai_service_url: &str,
-    task_id: &str,
+    config: &FrameCaptureConfig,
+    stream_id: &str,
     frame_seq: u64,
     jpeg_data: Vec<u8>,
-) -> Result<()> {
-    let base64_data = base64::engine::general_purpose::STANDARD.encode(&jpeg_data);
+) -> Result<Option<FrameBackpressure>> {
+    let task_id = &config.ai_task_id;
+    let url = format!("{}/v1/tasks/{}/frames", config.ai_service_url, task_id);
 
-    let url = format!("{}/v1/tasks/{}/frames", ai_service_url, task_id);
-
-    let payload = json!({
-        "frame_data": base64_data,
-        "sequence_number": frame_seq,
-        "timestamp_ms": std::time::SystemTime::now()
+    let frame = VideoFrame {
+        source_id: stream_id.to_string(),
+        timestamp: std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis() as u64,
-    });
+        sequence: frame_seq,
+        width: config.frame_width,
+        height: config.frame_height,
+        format: "jpeg".to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(&jpeg_data),
+        audio: None,
+    };
 
     let response = client
         .post(&url)
-        .json(&payload)
+        .json(&frame)
         .send()
         .await
         .context("failed to send frame to AI service")?;
 
+    let backpressure = FrameBackpressure::from_headers(response.headers());
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        debug!(task_id = %task_id, frame_seq = frame_seq, "frame shed by saturated AI service");
+        return Ok(Some(backpressure.unwrap_or(FrameBackpressure {
+            queue_depth: 0,
+            next_frame_delay_ms: 1000,
+        })));
+    }
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
@@ -173,7 +211,7 @@ async fn submit_frame_to_ai(
 
     debug!(task_id = %task_id, frame_seq = frame_seq, "frame submitted to AI service");
 
-    Ok(())
+    Ok(backpressure)
 }
 
 #[cfg(test)]
@@ -188,4 +226,17 @@ mod tests {
         assert_eq!(config.frame_height, 0);
         assert_eq!(config.jpeg_quality, 5);
     }
+
+    #[test]
+    fn test_backpressure_delays_the_next_frame() {
+        let now = Instant::now();
+        let next = now + Duration::from_secs(1);
+        let delay = |ms| FrameBackpressure {
+            queue_depth: 4,
+            next_frame_delay_ms: ms,
+        };
+        // Shorter than the interval: keep the schedule
+        assert_eq!(paced(next, now, &delay(200)), next);
+        assert_eq!(paced(next, now, &delay(2500)), now + Duration::from_millis(2500));
+    }
 }
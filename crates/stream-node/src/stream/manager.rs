@@ -1,15 +1,21 @@
-use super::{build_pipeline_args, hls_root, Codec, Container};
+use super::{apply_thermal_palette, build_pipeline_args, hls_root, Codec, Container};
 use crate::compat;
-use crate::metrics::{FFMPEG_CRASHES_TOTAL, FFMPEG_RESTARTS_TOTAL, STREAMS_RUNNING};
+use crate::metrics::{
+  record_pipeline_progress, FFMPEG_CRASHES_TOTAL, FFMPEG_RESTARTS_TOTAL, STREAMS_RUNNING,
+};
 use crate::storage::{self, S3Config as UploaderConfig};
 use anyhow::{anyhow, Result};
+use common::ffmpeg_progress::{self, PROGRESS_ARGS};
+use common::streams::ThermalPalette;
 use once_cell::sync::Lazy;
 use std::{
   collections::HashMap,
   fs,
-  path::PathBuf,
+  io::Write,
+  path::{Path, PathBuf},
   process::{Child, Command, Stdio},
-  time::{Duration, Instant},
+  sync::atomic::{AtomicBool, Ordering},
+  time::{Duration, Instant, SystemTime},
 };
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
@@ -27,8 +33,12 @@ const MAX_RESTART_DELAY_SECS: u64 = 60;
 pub struct StreamSpec {
   pub id: String,
   pub uri: String,
+  /// Lower-bitrate alternative pulled while the uplink is over budget
+  pub substream_uri: Option<String>,
   pub codec: Codec,
   pub container: Container,
+  /// Thermal camera rendered in this palette (transcoded)
+  pub thermal_palette: Option<ThermalPalette>,
 }
 
 #[derive(Clone, Debug)]
@@ -49,12 +59,32 @@ struct StreamEntry {
   upload_handle: Option<JoinHandle<()>>,
   restart_count: u32,
   monitor_handle: Option<JoinHandle<()>>,
+  motion_handle: Option<JoinHandle<()>>,
 }
 
 static REGISTRY: Lazy<Mutex<HashMap<String, StreamEntry>>> =
   Lazy::new(|| Mutex::new(HashMap::new()));
 
-fn readiness_timeout() -> Duration {
+/// Set by the coordinator's bandwidth directive
+static PREFER_SUBSTREAM: AtomicBool = AtomicBool::new(false);
+
+/// Set once the node starts shutting down; no stream may start after it
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+/// Window over which recent segment sizes approximate the ingest rate
+const INGEST_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+impl StreamSpec {
+  /// The URI to pull right now
+  fn source_uri(&self) -> &str {
+    match &self.substream_uri {
+      Some(substream) if PREFER_SUBSTREAM.load(Ordering::Relaxed) => substream,
+      _ => &self.uri,
+    }
+  }
+}
+
+pub(crate) fn readiness_timeout() -> Duration {
   std::env::var("HLS_READY_TIMEOUT_SECS")
     .ok()
     .and_then(|v| v.parse::<u64>().ok())
@@ -63,7 +93,7 @@ fn readiness_timeout() -> Duration {
 }
 
 /// Calculate exponential backoff delay for restart attempts
-fn calculate_restart_delay(attempt: u32) -> Duration {
+pub(crate) fn calculate_restart_delay(attempt: u32) -> Duration {
   let delay_secs = INITIAL_RESTART_DELAY_SECS * 2u64.pow(attempt);
   Duration::from_secs(delay_secs.min(MAX_RESTART_DELAY_SECS))
 }
@@ -154,6 +184,12 @@ fn spawn_monitor_task(stream_id: String) -> JoinHandle<()> {
 }
 
 pub async fn start_stream(spec_req: &StreamSpec) -> Result<()> {
+  if is_draining() {
+    telemetry::metrics::STREAM_NODE_STREAM_REJECTIONS
+      .with_label_values(&["draining"])
+      .inc();
+    return Err(anyhow!("stream node is shutting down"));
+  }
   {
     let reg = REGISTRY.lock().await;
     if reg.contains_key(&spec_req.id) {
@@ -172,7 +208,8 @@ pub async fn start_stream(spec_req: &StreamSpec) -> Result<()> {
     }
   }
 
-  let pr = compat::probe::probe(&spec_req.uri)
+  let source_uri = spec_req.source_uri().to_string();
+  let pr = compat::probe::probe(&source_uri)
     .await
     .unwrap_or_default();
 
@@ -216,10 +253,10 @@ pub async fn start_stream(spec_req: &StreamSpec) -> Result<()> {
     let latency = tuned.latency_ms;
     let parse_opts = tuned.parse_opts.clone();
 
-    let args = build_pipeline_args(
+    let mut args = build_pipeline_args(
       &codec,
       &container,
-      &spec_req.uri,
+      &source_uri,
       latency,
       &parse_opts,
       playlist
@@ -230,20 +267,47 @@ pub async fn start_stream(spec_req: &StreamSpec) -> Result<()> {
         .ok_or_else(|| anyhow!("bad segment path"))?,
     );
 
+    if let Some(palette) = spec_req.thermal_palette {
+      apply_thermal_palette(&mut args, palette);
+    }
+
+    // Push sources verify the camera's client certificate
+    args.splice(0..0, crate::ingest::acl().input_args(&source_uri));
+    args.splice(0..0, PROGRESS_ARGS.iter().map(|a| a.to_string()));
+
     info!(id=%spec_req.id, preset=%tuned.name, args=?args, "trying FFmpeg pipeline");
 
+    // stdin stays open so FFmpeg can be asked to quit cleanly on stop;
+    // stdout carries its progress reports
     match Command::new("ffmpeg")
       .args(&args)
-      .stdout(Stdio::inherit())
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
       .stderr(Stdio::inherit())
       .spawn()
     {
       Ok(mut child) => {
+        if let Some(stdout) = child.stdout.take() {
+          let id = spec_req.id.clone();
+          let spawned =
+            ffmpeg_progress::spawn_reader(format!("progress-{}", id), stdout, move |sample| {
+              record_pipeline_progress(&id, sample)
+            });
+          if let Err(e) = spawned {
+            warn!(id=%spec_req.id, error=%e, "failed to read FFmpeg progress");
+          }
+        }
         let ok = wait_for_hls_ready(&out_dir, readiness_timeout()).await;
+        if is_draining() {
+          // The node began draining while this pipeline warmed up
+          let _ = child.kill();
+          let _ = child.wait();
+          return Err(anyhow!("stream node is shutting down"));
+        }
         if ok {
           let status = StreamStatus {
             id: spec_req.id.clone(),
-            uri: spec_req.uri.clone(),
+            uri: source_uri.clone(),
             codec: match codec {
               Codec::H264 => "h264".into(),
               Codec::H265 => "h265".into(),
@@ -269,6 +333,7 @@ pub async fn start_stream(spec_req: &StreamSpec) -> Result<()> {
 
           // Spawn monitor task for automatic restart
           let monitor_handle = spawn_monitor_task(spec_req.id.clone());
+          let motion_handle = crate::motion::spawn(spec_req.id.clone(), out_dir.clone());
 
           {
             let mut reg = REGISTRY.lock().await;
@@ -280,12 +345,15 @@ pub async fn start_stream(spec_req: &StreamSpec) -> Result<()> {
                 spec: StreamSpec {
                   id: spec_req.id.clone(),
                   uri: spec_req.uri.clone(),
+                  substream_uri: spec_req.substream_uri.clone(),
                   codec,
                   container,
+                  thermal_palette: spec_req.thermal_palette,
                 },
                 upload_handle: Some(upload_handle),
                 restart_count: 0,
                 monitor_handle: Some(monitor_handle),
+                motion_handle,
               },
             );
           }
@@ -323,7 +391,7 @@ async fn restart_stream_internal(spec: &StreamSpec) -> Result<()> {
   start_stream(spec).await
 }
 
-async fn wait_for_hls_ready(dir: &PathBuf, timeout: Duration) -> bool {
+pub(crate) async fn wait_for_hls_ready(dir: &PathBuf, timeout: Duration) -> bool {
   use std::fs;
 
   let deadline = Instant::now() + timeout;
@@ -370,6 +438,9 @@ pub async fn stop_stream(id: &str) -> Result<()> {
       handle.abort();
       info!(id=%id, "monitor task cancelled");
     }
+    if let Some(handle) = entry.motion_handle {
+      handle.abort();
+    }
 
     STREAMS_RUNNING.dec();
     Ok(())
@@ -378,6 +449,122 @@ pub async fn stop_stream(id: &str) -> Result<()> {
   }
 }
 
+/// Source a preview tile pulls for a running stream: its substream when it
+/// has one, otherwise its main stream
+pub async fn preview_source(id: &str) -> Option<String> {
+  let reg = REGISTRY.lock().await;
+  reg
+    .get(id)
+    .map(|entry| entry.spec.substream_uri.clone().unwrap_or_else(|| entry.spec.uri.clone()))
+}
+
+/// Whether [`drain`] has started; new streams are refused
+pub fn is_draining() -> bool {
+  DRAINING.load(Ordering::Relaxed)
+}
+
+/// Shut down gracefully: refuse new streams and ask every FFmpeg pipeline to
+/// quit, so it flushes its last segment and closes the playlist. Pipelines
+/// still running after `grace` are killed.
+pub async fn drain(grace: Duration) {
+  DRAINING.store(true, Ordering::Relaxed);
+  let entries: Vec<(String, StreamEntry)> = REGISTRY.lock().await.drain().collect();
+  info!(streams = entries.len(), grace_secs = grace.as_secs(), "draining stream node");
+
+  let deadline = Instant::now() + grace;
+  let mut children = Vec::with_capacity(entries.len());
+  for (id, mut entry) in entries {
+    // Keep the monitor from restarting the pipeline once it exits
+    if let Some(handle) = entry.monitor_handle.take() {
+      handle.abort();
+    }
+    if let Some(handle) = entry.motion_handle.take() {
+      handle.abort();
+    }
+    // 'q' on stdin is FFmpeg's graceful quit
+    if let Some(mut stdin) = entry.child.stdin.take() {
+      let _ = stdin.write_all(b"q");
+    }
+    children.push((id, entry));
+  }
+
+  for (id, mut entry) in children {
+    loop {
+      match entry.child.try_wait() {
+        Ok(None) if Instant::now() < deadline => tokio::time::sleep(Duration::from_millis(100)).await,
+        Ok(None) => {
+          warn!(id = %id, "FFmpeg did not finish in time, killing it");
+          let _ = entry.child.kill();
+          let _ = entry.child.wait();
+          break;
+        }
+        _ => break,
+      }
+    }
+    if let Some(handle) = entry.upload_handle {
+      handle.abort();
+    }
+    STREAMS_RUNNING.dec();
+    info!(id = %id, "stream finalized");
+  }
+}
+
+/// Switch streams that have a substream between it and their main stream.
+/// Affected pipelines restart in the background; streams without a
+/// substream are left alone.
+pub async fn set_prefer_substream(prefer: bool) {
+  if PREFER_SUBSTREAM.swap(prefer, Ordering::Relaxed) == prefer {
+    return;
+  }
+  let specs: Vec<StreamSpec> = {
+    let reg = REGISTRY.lock().await;
+    reg
+      .values()
+      .filter(|entry| entry.spec.substream_uri.is_some() && entry.status.uri != entry.spec.source_uri())
+      .map(|entry| entry.spec.clone())
+      .collect()
+  };
+  info!(prefer_substream = prefer, streams = specs.len(), "switching stream quality");
+  for spec in specs {
+    tokio::spawn(async move {
+      if let Err(e) = restart_stream_internal(&spec).await {
+        error!(id = %spec.id, error = %e, "failed to switch stream quality");
+      }
+    });
+  }
+}
+
+/// Approximate camera ingest in kbit/s, from the segments written during the
+/// last few seconds (segments are stream copies of the input)
+pub async fn ingest_kbps() -> u64 {
+  let dirs: Vec<PathBuf> = {
+    let reg = REGISTRY.lock().await;
+    reg.values().map(|entry| entry.status.output_dir.clone()).collect()
+  };
+  let since = SystemTime::now() - INGEST_RATE_WINDOW;
+  let bytes = dirs.iter().map(|dir| segment_bytes_since(dir, since)).sum();
+  common::bandwidth::kbps(bytes, INGEST_RATE_WINDOW)
+}
+
+/// Bytes of the segments in `dir` written at or after `since`
+pub fn segment_bytes_since(dir: &Path, since: SystemTime) -> u64 {
+  let Ok(entries) = fs::read_dir(dir) else {
+    return 0;
+  };
+  let mut bytes = 0u64;
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let is_segment = matches!(path.extension().and_then(|e| e.to_str()), Some("ts" | "m4s"));
+    let Ok(meta) = entry.metadata() else {
+      continue;
+    };
+    if is_segment && meta.modified().is_ok_and(|modified| modified >= since) {
+      bytes += meta.len();
+    }
+  }
+  bytes
+}
+
 pub async fn list_streams() -> Vec<StreamStatus> {
   let mut reg = REGISTRY.lock().await;
   let mut to_remove = vec![];
@@ -399,6 +586,9 @@ pub async fn list_streams() -> Vec<StreamStatus> {
       if let Some(handle) = entry.monitor_handle {
         handle.abort();
       }
+      if let Some(handle) = entry.motion_handle {
+        handle.abort();
+      }
       STREAMS_RUNNING.dec();
     }
   }
@@ -1,3 +1,4 @@
+use common::streams::ThermalPalette;
 use std::path::PathBuf;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -30,6 +31,8 @@ pub fn hls_root() -> PathBuf {
 /// - Copies video codec (no re-encoding)
 /// - Generates HLS playlist with 2-second segments
 /// - Keeps last 5 segments in playlist
+/// - Stamps every segment with its ingest time (`#EXT-X-PROGRAM-DATE-TIME`)
+///   for latency measurement (see `common::latency`)
 pub fn build_pipeline_args(
   _codec: &Codec, // Not used in FFmpeg (codec is copied as-is)
   container: &Container,
@@ -84,11 +87,11 @@ pub fn build_pipeline_args(
   match container {
     Container::Ts => {
       // Standard TS segments
-      args.push("delete_segments".into());
+      args.push("delete_segments+program_date_time".into());
     }
     Container::Fmp4 => {
       // Fragmented MP4 segments (fMP4)
-      args.push("delete_segments+independent_segments".into());
+      args.push("delete_segments+independent_segments+program_date_time".into());
       args.push("-hls_segment_type".into());
       args.push("fmp4".into());
     }
@@ -100,6 +103,34 @@ pub fn build_pipeline_args(
   args
 }
 
+/// Transcode instead of copying for thermal cameras, whose single-channel
+/// (often raw 16-bit) video cannot be copied into HLS: the image is rendered
+/// in `palette` and encoded as low-latency H.264. Thermal cameras carry no
+/// audio worth keeping.
+pub fn apply_thermal_palette(args: &mut Vec<String>, palette: ThermalPalette) {
+  let Some(pos) = args.iter().position(|a| a == "-c:v") else {
+    return;
+  };
+  // -c:v copy -c:a copy
+  args.splice(
+    pos..(pos + 4).min(args.len()),
+    [
+      "-vf".to_string(),
+      palette.ffmpeg_filter(),
+      "-c:v".into(),
+      "libx264".into(),
+      "-preset".into(),
+      "veryfast".into(),
+      "-tune".into(),
+      "zerolatency".into(),
+      // A keyframe per 2 s segment at up to 30 fps
+      "-force_key_frames".into(),
+      "expr:gte(t,n_forced*2)".into(),
+      "-an".into(),
+    ],
+  );
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -127,6 +158,7 @@ mod tests {
     assert!(joined.contains("-hls_segment_filename"));
     assert!(joined.contains("/seg_%05d.ts"));
     assert!(joined.contains("/p.m3u8"));
+    assert!(joined.contains("-hls_flags delete_segments+program_date_time"));
   }
 
   #[test]
@@ -146,4 +178,24 @@ mod tests {
     assert!(joined.contains("-hls_segment_type"));
     assert!(joined.contains("fmp4"));
   }
+
+  #[test]
+  fn thermal_streams_are_transcoded_in_their_palette() {
+    let mut args = build_pipeline_args(
+      &Codec::H264,
+      &Container::Ts,
+      "rtsp://thermal",
+      0,
+      &[],
+      "/p.m3u8",
+      "/seg_%05d.ts",
+    );
+    apply_thermal_palette(&mut args, ThermalPalette::Inferno);
+    let joined = args.join(" ");
+    assert!(!joined.contains("copy"));
+    assert!(joined.contains("pseudocolor=preset=inferno"));
+    assert!(joined.contains("-c:v libx264"));
+    assert!(joined.contains("-an -f hls"));
+    assert!(joined.ends_with("/p.m3u8"));
+  }
 }
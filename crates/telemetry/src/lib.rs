@@ -1,3 +1,6 @@
+// The metrics `lazy_static!` block expands one level per metric
+#![recursion_limit = "256"]
+
 use tracing_subscriber::{fmt, EnvFilter};
 
 pub mod correlation;
@@ -1,7 +1,7 @@
 use lazy_static::lazy_static;
 use prometheus::{
-    Counter, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
-    IntGaugeVec, Opts, Registry,
+    Counter, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts, Registry,
 };
 
 lazy_static! {
@@ -71,6 +71,97 @@ lazy_static! {
         metric
     };
 
+    pub static ref COORDINATOR_RECONCILE_DRIFT: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "coordinator_reconcile_drift",
+                "Streams/recordings whose actual state differed from the desired state in the last reconciliation pass",
+            ),
+            &["resource", "drift"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref COORDINATOR_RECONCILE_ACTIONS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "coordinator_reconcile_actions_total",
+                "Corrective start/stop commands issued by the reconciler",
+            ),
+            &["resource", "action", "status"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref COORDINATOR_RECONCILE_PASSES: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "coordinator_reconcile_passes_total",
+                "Reconciliation passes by outcome (ok, skipped, error)",
+            ),
+            &["status"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref COORDINATOR_STATE_NAMESPACE_KEYS: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "coordinator_state_namespace_keys",
+                "Keys stored in each StateStore namespace",
+            ),
+            &["namespace"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref COORDINATOR_STATE_NAMESPACE_BYTES: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "coordinator_state_namespace_bytes",
+                "Space taken by each StateStore namespace",
+            ),
+            &["namespace"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref COORDINATOR_STATE_NAMESPACE_LEAKING: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "coordinator_state_namespace_leaking",
+                "1 while a StateStore namespace grew steadily over the leak window",
+            ),
+            &["namespace"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref COORDINATOR_STATE_QUOTA_REJECTIONS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "coordinator_state_quota_rejections_total",
+                "StateStore writes rejected because their namespace was over its quota",
+            ),
+            &["namespace"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
     // ==== Stream Node Metrics ====
     pub static ref STREAM_NODE_ACTIVE_STREAMS: IntGauge = {
         let metric = IntGauge::new("stream_node_active_streams", "Number of active streams")
@@ -208,6 +299,19 @@ lazy_static! {
         metric
     };
 
+    pub static ref RECORDER_NODE_PIPELINE_ENCODINGS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "recorder_node_pipelines_started_total",
+                "Recording pipelines started, by whether they copy or transcode the video",
+            ),
+            &["encoding"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
     pub static ref RECORDER_NODE_RECORDING_REJECTIONS: IntCounterVec = {
         let metric = IntCounterVec::new(
             Opts::new(
@@ -221,6 +325,132 @@ lazy_static! {
         metric
     };
 
+    pub static ref RECORDER_NODE_MOTION_SEGMENTS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "recorder_node_motion_segments_total",
+                "Segments of motion-only recordings by how they were stored",
+            ),
+            &["decision"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_IMPORTS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "recorder_node_imports_total",
+                "Imported video files by whether they could be normalized",
+            ),
+            &["status"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_TIMELAPSES: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "recorder_node_timelapses_total",
+                "Time-lapse jobs by whether they produced a video",
+            ),
+            &["status"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_PIPELINE_SPEED: GaugeVec = {
+        let metric = GaugeVec::new(
+            Opts::new(
+                "recorder_node_pipeline_speed",
+                "FFmpeg processing speed against real time, per recording",
+            ),
+            &["recording_id"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_PIPELINE_FPS: GaugeVec = {
+        let metric = GaugeVec::new(
+            Opts::new(
+                "recorder_node_pipeline_fps",
+                "Frames per second FFmpeg writes, per recording",
+            ),
+            &["recording_id"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_PIPELINE_LAG: GaugeVec = {
+        let metric = GaugeVec::new(
+            Opts::new(
+                "recorder_node_pipeline_lag_seconds",
+                "Seconds of video backed up in front of the encoder, per recording",
+            ),
+            &["recording_id"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_PIPELINE_FRAMES: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "recorder_node_pipeline_frames_total",
+                "Frames FFmpeg dropped or duplicated, per recording",
+            ),
+            &["recording_id", "outcome"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_DISK_TOTAL_BYTES: IntGauge = {
+        let metric = IntGauge::new("recorder_node_disk_total_bytes", "Size of the recordings volume")
+            .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_DISK_AVAILABLE_BYTES: IntGauge = {
+        let metric = IntGauge::new("recorder_node_disk_available_bytes", "Space available on the recordings volume")
+            .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_DISK_CAPACITY_LEVEL: IntGauge = {
+        let metric = IntGauge::new("recorder_node_disk_capacity_level", "Recordings volume level: 0 ok, 1 low, 2 critical, 3 full")
+            .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_EMERGENCY_DELETIONS: IntCounter = {
+        let metric = IntCounter::new("recorder_node_emergency_deletions_total", "Recordings deleted by emergency retention")
+            .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref RECORDER_NODE_EMERGENCY_FREED_BYTES: IntCounter = {
+        let metric = IntCounter::new("recorder_node_emergency_freed_bytes_total", "Bytes freed by emergency retention")
+            .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
     // ==== Playback Service Metrics ====
     pub static ref PLAYBACK_SERVICE_ACTIVE_SESSIONS: IntGauge = {
         let metric = IntGauge::new("playback_service_active_sessions", "Number of active playback sessions")
@@ -255,6 +485,71 @@ lazy_static! {
         metric
     };
 
+    pub static ref PLAYBACK_SERVICE_PROTOCOL_SERVED: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "playback_service_protocol_served_total",
+                "Negotiated playback sessions by the protocol they started with",
+            ),
+            &["protocol"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref PLAYBACK_SERVICE_PROTOCOL_FALLBACKS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "playback_service_protocol_fallbacks_total",
+                "Playback sessions moved to another protocol after a client-reported failure",
+            ),
+            &["from", "to"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref PLAYBACK_SERVICE_SESSIONS_RESUMED: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "playback_service_sessions_resumed_total",
+                "Persisted playback sessions resumed after a node restart or taken over from another node",
+            ),
+            &["reason"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref PLAYBACK_SERVICE_DELIVERY_LATENCY: GaugeVec = {
+        let metric = GaugeVec::new(
+            Opts::new(
+                "playback_service_delivery_latency_seconds",
+                "Highest delivery latency of a stream's live sessions: HLS segment age or WebRTC round trip time",
+            ),
+            &["stream_id", "protocol"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref PLAYBACK_SERVICE_LATENCY_BUDGET_EXCEEDED: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "playback_service_latency_budget_exceeded_total",
+                "Live sessions whose delivery latency went over their protocol's budget",
+            ),
+            &["protocol"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
     pub static ref PLAYBACK_SERVICE_BYTES_SERVED: Counter = {
         let metric = Counter::new(
             "playback_service_bytes_served_total",
@@ -318,6 +613,45 @@ lazy_static! {
         metric
     };
 
+    pub static ref ADMIN_GATEWAY_CANARY_WEIGHT: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "admin_gateway_canary_weight_percent",
+                "Share of new requests routed to the canary version (0 after a rollback)",
+            ),
+            &["kind", "version"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref ADMIN_GATEWAY_CANARY_REQUESTS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "admin_gateway_canary_requests_total",
+                "Node calls under a canary rule by group (canary, baseline) and outcome",
+            ),
+            &["kind", "group", "outcome"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref ADMIN_GATEWAY_CANARY_ROLLBACKS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "admin_gateway_canary_rollbacks_total",
+                "Automatic canary rollbacks triggered by error rate",
+            ),
+            &["kind"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
     // ==== AI Service Metrics ====
     pub static ref AI_SERVICE_ACTIVE_TASKS: IntGauge = {
         let metric = IntGauge::new("ai_service_active_tasks", "Number of active AI tasks")
@@ -418,6 +752,91 @@ lazy_static! {
         metric
     };
 
+    pub static ref AI_SERVICE_GPU_MEMORY_USED: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "ai_service_gpu_memory_used_bytes",
+                "GPU memory in use on the device, by all processes",
+            ),
+            &["device_id"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_GPU_MEMORY_TOTAL: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "ai_service_gpu_memory_total_bytes",
+                "GPU memory installed on the device",
+            ),
+            &["device_id"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_GPU_PROCESS_MEMORY: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "ai_service_gpu_process_memory_bytes",
+                "GPU memory allocated by this AI service on the device",
+            ),
+            &["device_id"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_PLUGIN_QUEUE_DEPTH: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "ai_service_plugin_queue_depth",
+                "Frames submitted to a plugin that are waiting for or in inference",
+            ),
+            &["plugin_type"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_SCHEDULER_IN_FLIGHT: IntGauge = {
+        let metric = IntGauge::new(
+            "ai_service_scheduler_in_flight",
+            "Frames holding an inference slot",
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_SCHEDULER_WAITING: IntGauge = {
+        let metric = IntGauge::new(
+            "ai_service_scheduler_waiting",
+            "Frames waiting for an inference slot",
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_FRAMES_SHED: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_frames_shed_total",
+                "Frames rejected because the AI node was saturated",
+            ),
+            &["priority", "reason"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
     pub static ref AI_SERVICE_INFERENCE_TIME: HistogramVec = {
         let metric = HistogramVec::new(
             HistogramOpts::new(
@@ -431,6 +850,214 @@ lazy_static! {
         REGISTRY.register(Box::new(metric.clone())).ok();
         metric
     };
+
+    pub static ref AI_SERVICE_BATCH_SIZE: HistogramVec = {
+        let metric = HistogramVec::new(
+            HistogramOpts::new(
+                "ai_service_batch_size",
+                "Frames run through a plugin in one batch",
+            )
+            .buckets(vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0]),
+            &["plugin_type"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_TRACK_EVENTS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_track_events_total",
+                "Object track lifecycle events (created, updated, lost)",
+            ),
+            &["plugin_type", "event"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_ZONE_EVENTS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_zone_events_total",
+                "Zone and tripwire events (entered, exited, crossed)",
+            ),
+            &["plugin_type", "event"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_DETECTIONS_DEDUPLICATED: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_detections_deduplicated_total",
+                "Detections dropped as repeats of an object reported within the task's dedup window",
+            ),
+            &["plugin_type"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_DETECTIONS_FILTERED: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_detections_filtered_total",
+                "Detections dropped by the class allow and deny lists of their task",
+            ),
+            &["plugin_type"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_PLUGIN_ERRORS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_plugin_errors_total",
+                "Frames failed by AI plugins, by error kind (recoverable, fatal, config, resource)",
+            ),
+            &["plugin_type", "kind"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_PLUGIN_RESTARTS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_plugin_restarts_total",
+                "AI plugin restarts after failures (restarted, gave_up)",
+            ),
+            &["plugin_type", "outcome"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_OUTBOX_PENDING: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "ai_service_outbox_pending",
+                "Task states and detection events buffered on disk for the coordinator",
+            ),
+            &["kind"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_OUTBOX_DROPPED: IntCounter = {
+        let metric = IntCounter::new(
+            "ai_service_outbox_dropped_total",
+            "Buffered detection events dropped because the outbox was full",
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref AI_SERVICE_DETECTIONS_STORED: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "ai_service_detections_stored_total",
+                "Detections written to the detection history, or dropped while its store was unreachable",
+            ),
+            &["outcome"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    // ==== Device Manager Metrics ====
+    pub static ref DEVICE_CLOCK_DRIFT_MS: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "device_clock_drift_ms",
+                "Camera clock minus server clock at the last time-sync check, in milliseconds",
+            ),
+            &["device_id"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref DEVICE_MAINTENANCE_RUNS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "device_maintenance_runs_total",
+                "Scheduled maintenance runs on devices by action and outcome",
+            ),
+            &["action", "status"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    // ==== Upstream Dependency Metrics (common::resilient_http) ====
+    pub static ref UPSTREAM_CIRCUIT_STATE: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "upstream_circuit_state",
+                "Circuit breaker state per dependency (0 closed, 1 half-open, 2 open)",
+            ),
+            &["target"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref UPSTREAM_CONSECUTIVE_FAILURES: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "upstream_consecutive_failures",
+                "Consecutive failed calls per dependency",
+            ),
+            &["target"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref UPSTREAM_LAST_LATENCY_MS: IntGaugeVec = {
+        let metric = IntGaugeVec::new(
+            Opts::new(
+                "upstream_last_latency_ms",
+                "Latency of the most recent call per dependency in milliseconds",
+            ),
+            &["target"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
+
+    pub static ref UPSTREAM_HTTP_EVENTS: IntCounterVec = {
+        let metric = IntCounterVec::new(
+            Opts::new(
+                "upstream_http_events_total",
+                "Calls to dependencies by event (attempt, failure, retry, rejected)",
+            ),
+            &["target", "event"],
+        )
+        .expect("metric can be created");
+        REGISTRY.register(Box::new(metric.clone())).ok();
+        metric
+    };
 }
 
 /// Helper function to encode metrics for Prometheus scraping
@@ -445,6 +1072,31 @@ pub fn encode_metrics() -> Result<String, prometheus::Error> {
     })
 }
 
+/// Mirror a dependency health snapshot into the upstream metrics. `totals`
+/// are absolute counts (e.g. `[("attempt", 10), ("failure", 2)]`); counters
+/// are advanced by the difference since the last call.
+pub fn set_upstream_health(
+    target: &str,
+    circuit_state: i64,
+    consecutive_failures: i64,
+    last_latency_ms: i64,
+    totals: &[(&str, u64)],
+) {
+    UPSTREAM_CIRCUIT_STATE
+        .with_label_values(&[target])
+        .set(circuit_state);
+    UPSTREAM_CONSECUTIVE_FAILURES
+        .with_label_values(&[target])
+        .set(consecutive_failures);
+    UPSTREAM_LAST_LATENCY_MS
+        .with_label_values(&[target])
+        .set(last_latency_ms);
+    for (event, total) in totals {
+        let counter = UPSTREAM_HTTP_EVENTS.with_label_values(&[target, event]);
+        counter.inc_by(total.saturating_sub(counter.get()));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -535,6 +1187,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_upstream_health_mirrors_totals() {
+        set_upstream_health("test-target", 2, 3, 15, &[("attempt", 5)]);
+        set_upstream_health("test-target", 0, 0, 12, &[("attempt", 8)]);
+        assert_eq!(
+            UPSTREAM_HTTP_EVENTS
+                .with_label_values(&["test-target", "attempt"])
+                .get(),
+            8
+        );
+        assert_eq!(
+            UPSTREAM_CIRCUIT_STATE
+                .with_label_values(&["test-target"])
+                .get(),
+            0
+        );
+    }
+
     #[test]
     fn test_encode_metrics_succeeds() {
         // Just verify that encoding doesn't panic
@@ -1,11 +1,11 @@
 /// Integration tests for AI service
 use ai_service::{
     api, plugin::mock_detector::MockDetectorPlugin, plugin::registry::PluginRegistry,
-    AiServiceState,
+    plugin::PluginError, AiServiceState,
 };
 use common::ai_tasks::{
-    AiFrameConfig, AiOutputConfig, AiTaskConfig, AiTaskStartRequest, PluginListResponse,
-    VideoFrame,
+    AiFrameConfig, AiOutputConfig, AiTaskConfig, AiTaskStartRequest, AiTaskState,
+    PluginErrorKind, PluginListResponse, TaskZones, VideoFrame,
 };
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -80,10 +80,19 @@ async fn test_start_task() {
         model_config: serde_json::json!({
             "confidence_threshold": 0.7
         }),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
         frame_config: AiFrameConfig {
             frame_interval: 1,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         },
         output: AiOutputConfig {
             output_type: "file".to_string(),
@@ -121,10 +130,19 @@ async fn test_start_task_with_invalid_plugin() {
         source_stream_id: Some("stream-123".to_string()),
         source_recording_id: None,
         model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
         frame_config: AiFrameConfig {
             frame_interval: 1,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         },
         output: AiOutputConfig {
             output_type: "file".to_string(),
@@ -162,10 +180,19 @@ async fn test_list_tasks() {
         source_stream_id: Some("stream-123".to_string()),
         source_recording_id: None,
         model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
         frame_config: AiFrameConfig {
             frame_interval: 1,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         },
         output: AiOutputConfig {
             output_type: "file".to_string(),
@@ -201,10 +228,19 @@ async fn test_get_task() {
         source_stream_id: Some("stream-123".to_string()),
         source_recording_id: None,
         model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
         frame_config: AiFrameConfig {
             frame_interval: 1,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         },
         output: AiOutputConfig {
             output_type: "file".to_string(),
@@ -240,10 +276,19 @@ async fn test_stop_task() {
         source_stream_id: Some("stream-123".to_string()),
         source_recording_id: None,
         model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
         frame_config: AiFrameConfig {
             frame_interval: 1,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         },
         output: AiOutputConfig {
             output_type: "file".to_string(),
@@ -325,10 +370,19 @@ async fn test_submit_frame() {
         source_stream_id: Some("stream-123".to_string()),
         source_recording_id: None,
         model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
         frame_config: AiFrameConfig {
             frame_interval: 1,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         },
         output: AiOutputConfig {
             output_type: "file".to_string(),
@@ -352,6 +406,7 @@ async fn test_submit_frame() {
         height: 480,
         format: "jpeg".to_string(),
         data: base64_data,
+        audio: None,
     };
 
     // Submit frame
@@ -391,6 +446,7 @@ async fn test_submit_frame_to_nonexistent_task() {
         height: 480,
         format: "jpeg".to_string(),
         data: base64_data,
+        audio: None,
     };
 
     // Submit frame to non-existent task
@@ -402,3 +458,404 @@ async fn test_submit_frame_to_nonexistent_task() {
 
     assert_eq!(response.status_code(), 400);
 }
+
+/// Fails frames by `format`: "bad" as recoverable, "broken" as fatal and
+/// "misconfigured" as a config error; counts initializations
+#[derive(Default)]
+struct FailingPlugin {
+    inits: usize,
+}
+
+#[async_trait::async_trait]
+impl ai_service::plugin::AiPlugin for FailingPlugin {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn id(&self) -> &'static str {
+        "failing"
+    }
+
+    fn name(&self) -> &'static str {
+        "Failing"
+    }
+
+    fn description(&self) -> &'static str {
+        "Fails frames on request"
+    }
+
+    fn version(&self) -> &'static str {
+        "1.0.0"
+    }
+
+    async fn init(&mut self, _config: serde_json::Value) -> anyhow::Result<()> {
+        self.inits += 1;
+        Ok(())
+    }
+
+    async fn process_frame(&self, frame: &VideoFrame) -> anyhow::Result<common::ai_tasks::AiResult> {
+        Err(match frame.format.as_str() {
+            "bad" => PluginError::recoverable("Failed to load image"),
+            "broken" => PluginError::inference("Non-zero status code returned while running Conv node"),
+            _ => PluginError::config("No output tensor found"),
+        }
+        .into())
+    }
+}
+
+#[tokio::test]
+async fn test_plugin_failures_by_kind() {
+    let registry = PluginRegistry::new();
+    let plugin = Arc::new(RwLock::new(FailingPlugin::default()));
+    registry
+        .register_with_config(plugin.clone(), serde_json::json!({}))
+        .await
+        .unwrap();
+    let state = AiServiceState::new("test-node".to_string(), registry);
+    let server = axum_test::TestServer::new(api::router(state.clone())).unwrap();
+
+    let task_config = AiTaskConfig {
+        id: "failing-task".to_string(),
+        plugin_type: "failing".to_string(),
+        source_stream_id: Some("stream-123".to_string()),
+        source_recording_id: None,
+        model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
+        frame_config: AiFrameConfig {
+            frame_interval: 1,
+            max_fps: None,
+            skip_seconds: 0,
+            motion_gate: None,
+        },
+        output: AiOutputConfig {
+            output_type: "file".to_string(),
+            config: serde_json::json!({ "path": "/tmp/test.json" }),
+        },
+    };
+    state.start_task(task_config, Some(60)).await.unwrap();
+    let frame = |format: &str| VideoFrame {
+        source_id: "stream-123".to_string(),
+        timestamp: 1234567890,
+        sequence: 1,
+        width: 640,
+        height: 480,
+        format: format.to_string(),
+        data: String::new(),
+        audio: None,
+    };
+
+    // A bad frame only fails itself
+    let response = server.post("/v1/tasks/failing-task/frames").json(&frame("bad")).await;
+    assert_eq!(response.status_code(), 400);
+    assert_eq!(response.json::<serde_json::Value>()["error_kind"], "recoverable");
+    let task = state.get_task("failing-task").await.unwrap();
+    assert_eq!(task.state, AiTaskState::Processing);
+    assert_eq!(task.last_error_kind, Some(PluginErrorKind::Recoverable));
+    assert_eq!(plugin.read().await.inits, 0);
+
+    // A fatal failure restarts the plugin and keeps the task running
+    let response = server.post("/v1/tasks/failing-task/frames").json(&frame("broken")).await;
+    assert_eq!(response.status_code(), 503);
+    assert_eq!(response.json::<serde_json::Value>()["error_kind"], "fatal");
+    let task = state.get_task("failing-task").await.unwrap();
+    assert_eq!(task.state, AiTaskState::Processing);
+    assert_eq!(task.last_error_kind, Some(PluginErrorKind::Fatal));
+    assert_eq!(plugin.read().await.inits, 1);
+
+    // A config error fails the task
+    let response = server
+        .post("/v1/tasks/failing-task/frames")
+        .json(&frame("misconfigured"))
+        .await;
+    assert_eq!(response.status_code(), 422);
+    let task = state.get_task("failing-task").await.unwrap();
+    assert_eq!(task.state, AiTaskState::Error);
+    assert_eq!(task.last_error_kind, Some(PluginErrorKind::Config));
+    assert!(task.last_error.unwrap().contains("No output tensor found"));
+}
+
+#[tokio::test]
+async fn test_task_zones_api() {
+    let (app, state) = setup_test_service().await;
+    let server = axum_test::TestServer::new(app).unwrap();
+
+    let task_config = AiTaskConfig {
+        id: "zones-task".to_string(),
+        plugin_type: "mock_object_detector".to_string(),
+        source_stream_id: Some("stream-123".to_string()),
+        source_recording_id: None,
+        model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
+        frame_config: AiFrameConfig::default(),
+        output: AiOutputConfig {
+            output_type: "file".to_string(),
+            config: serde_json::json!({ "path": "/tmp/test.json" }),
+        },
+    };
+    state.start_task(task_config, Some(60)).await.unwrap();
+
+    let zones = serde_json::json!({
+        "zones": [{
+            "id": "dock",
+            "name": "Loading dock",
+            "polygon": [{"x": 0, "y": 0}, {"x": 320, "y": 0}, {"x": 320, "y": 240}, {"x": 0, "y": 240}],
+            "classes": ["person"]
+        }]
+    });
+    let response = server.put("/v1/tasks/zones-task/zones").json(&zones).await;
+    assert_eq!(response.status_code(), 200);
+    let stored: TaskZones = server.get("/v1/tasks/zones-task/zones").await.json();
+    assert_eq!(stored.zones.len(), 1);
+    assert_eq!(stored.zones[0].name.as_deref(), Some("Loading dock"));
+
+    // Tripwires need tracking, which this task does not have
+    let tripwire = serde_json::json!({
+        "tripwires": [{"id": "gate", "from": {"x": 0, "y": 100}, "to": {"x": 640, "y": 100}}]
+    });
+    let response = server.put("/v1/tasks/zones-task/zones").json(&tripwire).await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = server.delete("/v1/tasks/zones-task/zones").await;
+    assert_eq!(response.status_code(), 200);
+    assert!(state.get_task("zones-task").await.unwrap().config.zones.is_none());
+
+    let response = server.get("/v1/tasks/missing/zones").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_face_enrollment_api() {
+    use ai_service::plugin::facial_recognition::FacialRecognitionPlugin;
+    use axum_test::multipart::{MultipartForm, Part};
+
+    let registry = PluginRegistry::new();
+    registry
+        .register(Arc::new(RwLock::new(FacialRecognitionPlugin::new())))
+        .await
+        .unwrap();
+    let state = AiServiceState::new("test-node".to_string(), registry);
+    let server = axum_test::TestServer::new(api::router(state)).unwrap();
+
+    // Plugin info stays reachable next to the face routes
+    let response = server.get("/v1/plugins/facial_recognition").await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = server.get("/v1/plugins/facial_recognition/faces").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.json::<serde_json::Value>()["count"], 0);
+
+    let without_image = MultipartForm::new().add_text("name", "Jane Doe");
+    let response = server
+        .post("/v1/plugins/facial_recognition/faces")
+        .multipart(without_image)
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let not_an_image = MultipartForm::new()
+        .add_text("name", "Jane Doe")
+        .add_part("image", Part::bytes(b"not an image".to_vec()).file_name("jane.jpg"));
+    let response = server
+        .post("/v1/plugins/facial_recognition/faces")
+        .multipart(not_an_image)
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let bad_metadata = MultipartForm::new()
+        .add_text("name", "Jane Doe")
+        .add_text("metadata", "{not json");
+    let response = server
+        .post("/v1/plugins/facial_recognition/faces")
+        .multipart(bad_metadata)
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = server.get("/v1/plugins/facial_recognition/faces/missing").await;
+    assert_eq!(response.status_code(), 404);
+    let response = server
+        .patch("/v1/plugins/facial_recognition/faces/missing")
+        .json(&serde_json::json!({"name": "Jane Roe"}))
+        .await;
+    assert_eq!(response.status_code(), 404);
+    let response = server
+        .patch("/v1/plugins/facial_recognition/faces/missing")
+        .json(&serde_json::json!({"name": " "}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+    let response = server.delete("/v1/plugins/facial_recognition/faces/missing").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_lpr_watchlist_api() {
+    use ai_service::plugin::lpr::LprPlugin;
+
+    let registry = PluginRegistry::new();
+    registry
+        .register(Arc::new(RwLock::new(LprPlugin::new())))
+        .await
+        .unwrap();
+    let state = AiServiceState::new("test-node".to_string(), registry);
+    let server = axum_test::TestServer::new(api::router(state)).unwrap();
+
+    let response = server.get("/v1/plugins/lpr/watchlists").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.json::<serde_json::Value>()["count"], 0);
+
+    let response = server
+        .post("/v1/plugins/lpr/watchlists")
+        .json(&serde_json::json!({
+            "list_id": "stolen",
+            "name": "Stolen vehicles",
+            "category": "stolen",
+            "plates": ["ab-123 c", "XY987"]
+        }))
+        .await;
+    assert_eq!(response.status_code(), 201);
+    let list: serde_json::Value = response.json();
+    assert_eq!(list["plates"], serde_json::json!(["AB123C", "XY987"]));
+
+    let response = server
+        .post("/v1/plugins/lpr/watchlists")
+        .json(&serde_json::json!({"list_id": "stolen", "name": "Again"}))
+        .await;
+    assert_eq!(response.status_code(), 409);
+    let response = server
+        .post("/v1/plugins/lpr/watchlists")
+        .json(&serde_json::json!({"name": "Far", "max_edit_distance": 9}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+    let response = server
+        .post("/v1/plugins/lpr/watchlists")
+        .json(&serde_json::json!({"name": "Bad plate", "plates": ["--"]}))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = server
+        .patch("/v1/plugins/lpr/watchlists/stolen")
+        .json(&serde_json::json!({"name": "Stolen", "max_edit_distance": 2}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.json::<serde_json::Value>()["max_edit_distance"], 2);
+
+    let response = server
+        .post("/v1/plugins/lpr/watchlists/stolen/plates")
+        .json(&serde_json::json!({"plates": ["KL 555"]}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let response = server.delete("/v1/plugins/lpr/watchlists/stolen/plates/xy987").await;
+    assert_eq!(response.status_code(), 204);
+    let response = server.delete("/v1/plugins/lpr/watchlists/stolen/plates/XY987").await;
+    assert_eq!(response.status_code(), 404);
+
+    let response = server.get("/v1/plugins/lpr/watchlists/stolen").await;
+    assert_eq!(response.status_code(), 200);
+    let list: serde_json::Value = response.json();
+    assert_eq!(list["name"], "Stolen");
+    assert_eq!(list["plates"], serde_json::json!(["AB123C", "KL555"]));
+
+    let response = server.delete("/v1/plugins/lpr/watchlists/stolen").await;
+    assert_eq!(response.status_code(), 204);
+    let response = server.get("/v1/plugins/lpr/watchlists/stolen").await;
+    assert_eq!(response.status_code(), 404);
+    let response = server
+        .patch("/v1/plugins/lpr/watchlists/stolen")
+        .json(&serde_json::json!({"name": "Stolen"}))
+        .await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_detection_history_api() {
+    use ai_service::detections::{DetectionPage, DetectionRecorder, MemoryDetectionStore};
+
+    let (app, state) = setup_test_service().await;
+    let server = axum_test::TestServer::new(app).unwrap();
+
+    // Disabled until a recorder is set
+    let response = server.get("/v1/detections").await;
+    assert_eq!(response.status_code(), 503);
+
+    let recorder = Arc::new(DetectionRecorder::new(Arc::new(MemoryDetectionStore::new(100))));
+    state.set_detections(Arc::clone(&recorder));
+
+    let task_config = AiTaskConfig {
+        id: "task-history".to_string(),
+        plugin_type: "mock_object_detector".to_string(),
+        source_stream_id: Some("camera-7".to_string()),
+        source_recording_id: None,
+        model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
+        frame_config: AiFrameConfig {
+            frame_interval: 1,
+            max_fps: None,
+            skip_seconds: 0,
+            motion_gate: None,
+        },
+        output: AiOutputConfig {
+            output_type: "file".to_string(),
+            config: serde_json::json!({ "tenant_id": "tenant-a" }),
+        },
+    };
+    state.start_task(task_config, Some(60)).await.unwrap();
+
+    let jpeg_data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46];
+    let frame = VideoFrame {
+        source_id: "camera-7".to_string(),
+        timestamp: 1_700_000_000_000,
+        sequence: 1,
+        width: 640,
+        height: 480,
+        format: "jpeg".to_string(),
+        data: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &jpeg_data),
+        audio: None,
+    };
+    let response = server.post("/v1/tasks/task-history/frames").json(&frame).await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(recorder.flush().await.unwrap(), 2);
+
+    let response = server
+        .get("/v1/detections?task_id=task-history&from_ms=1700000000000&to_ms=1700000000001")
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let page: DetectionPage = response.json();
+    assert_eq!(page.total, 2);
+    let detection = &page.detections[0].record;
+    assert_eq!(detection.camera_id.as_deref(), Some("camera-7"));
+    assert_eq!(detection.tenant_id.as_deref(), Some("tenant-a"));
+
+    let classes: Vec<String> = page.detections.iter().map(|d| d.record.class.clone()).collect();
+    let response = server
+        .get(&format!("/v1/detections?class={}&limit=1", classes[0]))
+        .await;
+    let page: DetectionPage = response.json();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.detections[0].record.class, classes[0]);
+
+    let response = server.get("/v1/detections?from_ms=5&to_ms=1").await;
+    assert_eq!(response.status_code(), 400);
+}
@@ -1,4 +1,6 @@
-use alert_service::{create_router, AlertStore, AppState, Notifier, RuleEngine, Severity, TriggerType};
+use alert_service::{
+    create_router, AlertStore, AnomalyConfig, AnomalyDetector, AppState, Notifier, RuleEngine, Severity, TriggerType,
+};
 use anyhow::Result;
 use axum_test::TestServer;
 use serde_json::json;
@@ -39,11 +41,14 @@ async fn create_test_server() -> Result<TestServer> {
     let store = AlertStore::new(pool);
     let engine = Arc::new(RuleEngine::new(store.clone()));
     let notifier = Arc::new(Notifier::new(store.clone()));
+    let anomalies = Arc::new(AnomalyDetector::new(store.clone(), AnomalyConfig::default()));
 
     let state = AppState {
         store,
         engine,
         notifier,
+        anomalies,
+        evidence: None,
     };
 
     let app = create_router(state);
@@ -565,3 +570,54 @@ async fn test_multiple_notification_channels() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_tenant_notification_channels() -> Result<()> {
+    let pool = setup_test_db().await?;
+    let store = AlertStore::new(pool);
+    let secrets = alert_service::secrets::SecretCipher::new("integration-test-key");
+
+    let tenant_a = uuid::Uuid::new_v4();
+    let tenant_b = uuid::Uuid::new_v4();
+    let email = alert_service::ActionType::Email;
+    let settings = json!({
+        "smtp_host": "smtp.customer-a.example",
+        "smtp_port": 465,
+        "smtp_username": "alerts",
+        "from_address": "alerts@customer-a.example",
+        "from_name": "Customer A Security"
+    });
+
+    // Creating a channel needs its secret
+    assert!(store
+        .upsert_notification_channel(tenant_a, &email, &settings, None, None)
+        .await
+        .is_err());
+
+    let sealed = secrets.encrypt("smtp-password")?;
+    let created = store
+        .upsert_notification_channel(tenant_a, &email, &settings, Some(&sealed), None)
+        .await?;
+    assert!(created.enabled);
+    assert_eq!(created.channel_type, email);
+
+    // Updating without a secret keeps the stored one
+    let updated = store
+        .upsert_notification_channel(tenant_a, &email, &settings, None, Some(false))
+        .await?;
+    assert!(!updated.enabled);
+    assert_eq!(secrets.decrypt(&updated.secret_encrypted)?, "smtp-password");
+
+    // Secrets are never serialized
+    let body = serde_json::to_value(&updated)?;
+    assert!(body.get("secret_encrypted").is_none());
+
+    // Channels belong to one tenant
+    assert!(store.get_notification_channel(tenant_b, &email).await?.is_none());
+    assert_eq!(store.list_notification_channels(tenant_a).await?.len(), 1);
+
+    assert!(store.delete_notification_channel(tenant_a, &email).await?);
+    assert!(store.get_notification_channel(tenant_a, &email).await?.is_none());
+
+    Ok(())
+}
@@ -91,6 +91,7 @@ async fn test_spatial_anomaly_detection_with_zones() {
             ]
         })
         .to_string(),
+        audio: None,
     };
 
     let result = plugin.read().await.process_frame(&frame).await.unwrap();
@@ -156,6 +157,7 @@ async fn test_temporal_anomaly_detection() {
                 ]
             })
             .to_string(),
+            audio: None,
         };
 
         plugin.read().await.process_frame(&frame).await.unwrap();
@@ -185,6 +187,7 @@ async fn test_temporal_anomaly_detection() {
                 .collect::<Vec<_>>()
         })
         .to_string(),
+        audio: None,
     };
 
     let result = plugin
@@ -258,6 +261,7 @@ async fn test_combined_spatial_and_temporal_anomalies() {
                 ]
             })
             .to_string(),
+            audio: None,
         };
 
         plugin.read().await.process_frame(&frame).await.unwrap();
@@ -314,6 +318,7 @@ async fn test_combined_spatial_and_temporal_anomalies() {
             ]
         })
         .to_string(),
+        audio: None,
     };
 
     let result = plugin
@@ -384,6 +389,7 @@ async fn test_no_anomalies_on_normal_activity() {
                 ]
             })
             .to_string(),
+            audio: None,
         };
 
         let result = plugin.read().await.process_frame(&frame).await.unwrap();
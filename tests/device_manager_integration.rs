@@ -78,6 +78,9 @@ async fn test_device_crud_operations() -> Result<()> {
         recording_enabled: Some(true),
         ai_enabled: Some(false),
         metadata: None,
+        mac_address: None,
+        serial_number: None,
+        allow_duplicate: false,
     };
 
     // Note: In a real integration test, we would need proper auth setup
@@ -121,6 +124,9 @@ async fn test_device_store_operations() -> Result<()> {
         recording_enabled: Some(false),
         ai_enabled: Some(false),
         metadata: None,
+        mac_address: None,
+        serial_number: None,
+        allow_duplicate: false,
     };
 
     let device = store.create_device("test-tenant", create_req).await?;
@@ -155,6 +161,9 @@ async fn test_device_store_operations() -> Result<()> {
         ai_enabled: None,
         status: None,
         metadata: None,
+        mac_address: None,
+        serial_number: None,
+        allow_duplicate: false,
     };
 
     let updated = store.update_device(&device.device_id, update_req).await?;
@@ -215,6 +224,9 @@ async fn test_device_health_tracking() -> Result<()> {
         recording_enabled: None,
         ai_enabled: None,
         metadata: None,
+        mac_address: None,
+        serial_number: None,
+        allow_duplicate: false,
     };
 
     let device = store.create_device("test-tenant-health", create_req).await?;
@@ -226,6 +238,7 @@ async fn test_device_health_tracking() -> Result<()> {
             device_manager::DeviceStatus::Online,
             Some(250),
             None,
+            &[],
         )
         .await?;
 
@@ -241,6 +254,7 @@ async fn test_device_health_tracking() -> Result<()> {
             device_manager::DeviceStatus::Offline,
             Some(5000),
             Some("Connection timeout".to_string()),
+            &[],
         )
         .await?;
 
@@ -0,0 +1,174 @@
+//! Central side of multi-site federation: an edge site reports to the central
+//! gateway, operators read the aggregated views and playback is proxied to
+//! the site's playback service.
+
+use admin_gateway::{
+    config::GatewayConfig,
+    coordinator::HttpCoordinatorClient,
+    routes as gateway_routes,
+    routing::RoutingTable,
+    state::AppState,
+    worker::{HttpRecorderClient, HttpWorkerClient, RecorderClient, WorkerClient},
+};
+use anyhow::Result;
+use axum::{extract::Path, http::header, routing::get, Router};
+use common::federation::{SiteDevice, SiteHealth, SiteReport};
+use coordinator::{
+    config::{CoordinatorConfig, LeaseStoreType},
+    routes as coordinator_routes,
+    state::CoordinatorState,
+    store::{LeaseStore, MemoryLeaseStore},
+};
+use reqwest::{Client, StatusCode, Url};
+use serde_json::Value;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+const TOKEN: &str = "federation-secret";
+
+fn coordinator_state() -> CoordinatorState {
+    let cfg = CoordinatorConfig {
+        bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        default_ttl_secs: 15,
+        max_ttl_secs: 60,
+        store_type: LeaseStoreType::Memory,
+        database_url: None,
+        cluster_enabled: false,
+        node_id: None,
+        peer_addrs: vec![],
+        election_timeout_ms: 5000,
+        heartbeat_interval_ms: 1000,
+    };
+    let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new(cfg.default_ttl_secs, cfg.max_ttl_secs));
+    CoordinatorState::new(cfg, store, None)
+}
+
+async fn spawn_router(router: Router) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+    let addr = listener.local_addr()?;
+    let handle = tokio::spawn(async move {
+        axum::serve(listener, router.into_make_service())
+            .await
+            .expect("server failed");
+    });
+    Ok((addr, handle))
+}
+
+async fn central_gateway(coordinator: SocketAddr) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let config = GatewayConfig {
+        bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        coordinator_base_url: Url::parse(&format!("http://{coordinator}"))?,
+        node_id: "central-gateway".to_string(),
+        worker_base_url: Url::parse("http://worker.local/")?,
+        recorder_base_url: Url::parse("http://recorder.local/")?,
+        node_discovery_interval_secs: 0,
+        node_unhealthy_threshold: 3,
+        overview_timeout_ms: 2000,
+        auth: None,
+        device_manager_base_url: None,
+        ai_service_base_url: None,
+        playback_base_url: None,
+        alert_service_base_url: None,
+        federation_token: Some(TOKEN.to_string()),
+    };
+    let routing = Arc::new(RoutingTable::new(3));
+    let state = AppState::new(
+        config.clone(),
+        Arc::new(HttpCoordinatorClient::new(config.coordinator_base_url.clone()).await?),
+        Arc::new(HttpWorkerClient::new(routing.clone())) as Arc<dyn WorkerClient>,
+        Arc::new(HttpRecorderClient::new(routing.clone())) as Arc<dyn RecorderClient>,
+        routing,
+    );
+    spawn_router(gateway_routes::router(state)).await
+}
+
+fn report(playback: SocketAddr) -> SiteReport {
+    SiteReport {
+        site_id: "warehouse-east".to_string(),
+        name: "Warehouse East".to_string(),
+        playback_url: Some(format!("http://{playback}/")),
+        version: None,
+        report_interval_secs: 30,
+        generated_at_epoch_secs: 0,
+        health: SiteHealth {
+            status: "healthy".to_string(),
+            ..Default::default()
+        },
+        devices: vec![SiteDevice {
+            device_id: "cam-1".to_string(),
+            tenant_id: "tenant-a".to_string(),
+            name: "Dock door".to_string(),
+            status: "online".to_string(),
+            location: None,
+        }],
+        alerts: vec![],
+    }
+}
+
+#[tokio::test]
+async fn edge_reports_are_aggregated_and_playback_is_proxied() -> Result<()> {
+    let playback = Router::new().route(
+        "/v1/playback/sessions/:id/index.m3u8",
+        get(|Path(id): Path<String>| async move {
+            ([(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")], format!("#EXTM3U\n# {id}\n"))
+        }),
+    );
+    let (playback_addr, playback_task) = spawn_router(playback).await?;
+    let (coordinator_addr, coordinator_task) = spawn_router(coordinator_routes::router(coordinator_state())).await?;
+    let (gateway_addr, gateway_task) = central_gateway(coordinator_addr).await?;
+
+    let client = Client::new();
+    let base = format!("http://{gateway_addr}");
+    let report_url = format!("{base}/v1/federation/sites/warehouse-east");
+
+    let rejected = client
+        .put(&report_url)
+        .bearer_auth("wrong")
+        .json(&report(playback_addr))
+        .send()
+        .await?;
+    assert_eq!(rejected.status(), StatusCode::UNAUTHORIZED);
+
+    let accepted = client
+        .put(&report_url)
+        .bearer_auth(TOKEN)
+        .json(&report(playback_addr))
+        .send()
+        .await?;
+    assert!(accepted.status().is_success());
+    let site: Value = accepted.json().await?;
+    assert_eq!(site["status"], "online");
+
+    let devices: Value = client
+        .get(format!("{base}/v1/federation/devices?status=online"))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(devices[0]["site_id"], "warehouse-east");
+    assert_eq!(devices[0]["device_id"], "cam-1");
+
+    let health: Value = client.get(format!("{base}/v1/federation/health")).send().await?.json().await?;
+    assert_eq!(health["sites_online"], 1);
+
+    let playlist = client
+        .get(format!(
+            "{base}/v1/federation/sites/warehouse-east/playback/v1/playback/sessions/s1/index.m3u8"
+        ))
+        .send()
+        .await?;
+    assert!(playlist.status().is_success());
+    assert_eq!(playlist.headers()[header::CONTENT_TYPE], "application/vnd.apple.mpegurl");
+    assert_eq!(playlist.text().await?, "#EXTM3U\n# s1\n");
+
+    let unknown = client
+        .get(format!("{base}/v1/federation/sites/unknown/playback/v1/playback/sessions"))
+        .send()
+        .await?;
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+    gateway_task.abort();
+    coordinator_task.abort();
+    playback_task.abort();
+    Ok(())
+}
@@ -16,6 +16,7 @@ use admin_gateway::{
     config::GatewayConfig,
     coordinator::HttpCoordinatorClient,
     routes as gateway_routes,
+    routing::RoutingTable,
     state::AppState,
     worker::{RecorderClient, WorkerClient},
 };
@@ -177,7 +178,7 @@ async fn test_full_pipeline_stream_and_ai_task() -> Result<()> {
     let ai_coordinator_client = Arc::new(
         ai_service::coordinator::HttpCoordinatorClient::new(
             reqwest::Url::parse(&coordinator_url)?
-        )?
+        ).await?
     ) as Arc<dyn ai_service::coordinator::CoordinatorClient>;
 
     let ai_state = AiServiceState::with_coordinator(
@@ -199,10 +200,19 @@ async fn test_full_pipeline_stream_and_ai_task() -> Result<()> {
         node_id: "gateway-e2e-test".to_string(),
         worker_base_url: reqwest::Url::parse("http://stream-worker.local/")?,
         recorder_base_url: reqwest::Url::parse("http://recorder-worker.local/")?,
+        node_discovery_interval_secs: 0,
+        node_unhealthy_threshold: 3,
+        overview_timeout_ms: 2000,
+        auth: None,
+        device_manager_base_url: None,
+        ai_service_base_url: None,
+        playback_base_url: None,
+        alert_service_base_url: None,
+        federation_token: None,
     };
 
     let coordinator_client =
-        Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone())?);
+        Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone()).await?);
     let worker_client = stream_worker.clone() as Arc<dyn WorkerClient>;
     let recorder_client = recorder_worker.clone() as Arc<dyn RecorderClient>;
 
@@ -211,6 +221,7 @@ async fn test_full_pipeline_stream_and_ai_task() -> Result<()> {
         coordinator_client,
         worker_client,
         recorder_client,
+        Arc::new(RoutingTable::new(3)),
     );
     let gateway_router = gateway_routes::router(app_state);
     let (gateway_addr, gateway_task) = spawn_router(gateway_router).await?;
@@ -262,10 +273,19 @@ async fn test_full_pipeline_stream_and_ai_task() -> Result<()> {
         source_stream_id: Some("stream-e2e-1".to_string()),
         source_recording_id: None,
         model_config: serde_json::json!({}),
+        secondary_plugins: Vec::new(),
+        pipeline: None,
+        classes: None,
+        tracking: None,
+        zones: None,
+        dedup: None,
+        alarm_classes: Vec::new(),
+        schedule: None,
         frame_config: common::ai_tasks::AiFrameConfig {
             frame_interval: 2,
             max_fps: None,
             skip_seconds: 0,
+            motion_gate: None,
         },
         output: AiOutputConfig {
             output_type: "file".to_string(),
@@ -388,10 +408,19 @@ async fn test_full_pipeline_recording_with_ai() -> Result<()> {
         node_id: "gateway-rec-e2e-test".to_string(),
         worker_base_url: reqwest::Url::parse("http://stream-worker.local/")?,
         recorder_base_url: reqwest::Url::parse("http://recorder-worker.local/")?,
+        node_discovery_interval_secs: 0,
+        node_unhealthy_threshold: 3,
+        overview_timeout_ms: 2000,
+        auth: None,
+        device_manager_base_url: None,
+        ai_service_base_url: None,
+        playback_base_url: None,
+        alert_service_base_url: None,
+        federation_token: None,
     };
 
     let coordinator_client =
-        Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone())?);
+        Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone()).await?);
     let worker_client = stream_worker.clone() as Arc<dyn WorkerClient>;
     let recorder_client = recorder_worker.clone() as Arc<dyn RecorderClient>;
 
@@ -400,6 +429,7 @@ async fn test_full_pipeline_recording_with_ai() -> Result<()> {
         coordinator_client,
         worker_client,
         recorder_client,
+        Arc::new(RoutingTable::new(3)),
     );
     let gateway_router = gateway_routes::router(app_state);
     let (gateway_addr, gateway_task) = spawn_router(gateway_router).await?;
@@ -419,6 +449,7 @@ async fn test_full_pipeline_recording_with_ai() -> Result<()> {
             source_uri: Some("rtsp://example.com/camera1".to_string()),
             retention_hours: Some(24),
             format: Some(RecordingFormat::Mp4),
+            encoding: None,
         },
         lease_ttl_secs: Some(60),
         ai_config: Some(RecordingAiConfig {
@@ -428,7 +459,11 @@ async fn test_full_pipeline_recording_with_ai() -> Result<()> {
             frame_width: 1280,
             frame_height: 720,
             jpeg_quality: 90,
+            auto_tags: Vec::new(),
         }),
+        motion_policy: None,
+        tags: Vec::new(),
+        labels: Default::default(),
     };
 
     let rec_resp = client
@@ -603,15 +638,25 @@ async fn test_multi_service_health_checks() -> Result<()> {
         node_id: "health-test-gateway".to_string(),
         worker_base_url: reqwest::Url::parse("http://worker.local/")?,
         recorder_base_url: reqwest::Url::parse("http://recorder.local/")?,
+        node_discovery_interval_secs: 0,
+        node_unhealthy_threshold: 3,
+        overview_timeout_ms: 2000,
+        auth: None,
+        device_manager_base_url: None,
+        ai_service_base_url: None,
+        playback_base_url: None,
+        alert_service_base_url: None,
+        federation_token: None,
     };
 
     let coordinator_client =
-        Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone())?);
+        Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone()).await?);
     let app_state = AppState::new(
         gateway_cfg,
         coordinator_client,
         stream_worker as Arc<dyn WorkerClient>,
         recorder_worker as Arc<dyn RecorderClient>,
+        Arc::new(RoutingTable::new(3)),
     );
     let gateway_router = gateway_routes::router(app_state);
     let (gateway_addr, gateway_task) = spawn_router(gateway_router).await?;
@@ -2,6 +2,7 @@ use admin_gateway::{
     config::GatewayConfig,
     coordinator::HttpCoordinatorClient,
     routes as gateway_routes,
+    routing::RoutingTable,
     state::AppState,
     worker::{RecorderClient, WorkerClient},
 };
@@ -123,11 +124,26 @@ async fn gateway_and_coordinator_end_to_end() -> Result<()> {
         node_id: "gateway-test".to_string(),
         worker_base_url: reqwest::Url::parse("http://worker.local/")?,
         recorder_base_url: reqwest::Url::parse("http://recorder.local/")?,
+        node_discovery_interval_secs: 0,
+        node_unhealthy_threshold: 3,
+        overview_timeout_ms: 2000,
+        auth: None,
+        device_manager_base_url: None,
+        ai_service_base_url: None,
+        playback_base_url: None,
+        alert_service_base_url: None,
+        federation_token: None,
     };
-    let coordinator_client = Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone())?);
+    let coordinator_client = Arc::new(HttpCoordinatorClient::new(gateway_cfg.coordinator_base_url.clone()).await?);
     let worker_client = worker.clone() as Arc<dyn WorkerClient>;
     let recorder_client = Arc::new(StubRecorder::new()) as Arc<dyn RecorderClient>;
-    let app_state = AppState::new(gateway_cfg.clone(), coordinator_client, worker_client, recorder_client);
+    let app_state = AppState::new(
+        gateway_cfg.clone(),
+        coordinator_client,
+        worker_client,
+        recorder_client,
+        Arc::new(RoutingTable::new(3)),
+    );
     let gateway_router = gateway_routes::router(app_state);
     let (gateway_addr, gateway_task) = spawn_router(gateway_router).await?;
 
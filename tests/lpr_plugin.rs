@@ -100,6 +100,7 @@ async fn test_lpr_plugin_process_frame_fails_when_uninitialized() {
         format: "png".to_string(),
         width: 640,
         height: 480,
+        audio: None,
     };
 
     // Processing should fail when plugin is not initialized
@@ -103,4 +103,60 @@ mod tests {
         assert!(body["incident"].is_object());
         assert_eq!(body["incident"]["title"], "Test Incident");
     }
+
+    #[tokio::test]
+    #[ignore] // Run only when operator-ui service is running
+    async fn test_cobrowse_session_owner_controls_playback() {
+        if !is_operator_ui_running().await {
+            println!("Operator UI not running, skipping test");
+            return;
+        }
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post("http://localhost:8090/api/cobrowse/sessions")
+            .json(&serde_json::json!({
+                "title": "Loading dock review",
+                "owner": "investigator",
+                "source_kind": "recording",
+                "source_id": "rec-1",
+                "position_ms": 60000,
+                "paused": true
+            }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+        let session_id = body["session"]["id"].as_str().expect("session id").to_string();
+        let owner_token = body["owner_token"].as_str().expect("owner token").to_string();
+        let control_url = format!("http://localhost:8090/api/cobrowse/sessions/{}/control", session_id);
+
+        let response = client
+            .post(&control_url)
+            .json(&serde_json::json!({"owner_token": "not-the-owner", "action": "play"}))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = client
+            .post(&control_url)
+            .json(&serde_json::json!({"owner_token": owner_token, "action": "seek", "position_ms": 90000}))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let playback: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+        assert_eq!(playback["position_ms"], 90000);
+        assert_eq!(playback["paused"], true);
+
+        let response = client
+            .post(format!("http://localhost:8090/api/cobrowse/sessions/{}/end", session_id))
+            .json(&serde_json::json!({"owner_token": owner_token}))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
 }
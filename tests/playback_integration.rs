@@ -55,6 +55,7 @@ async fn test_playback_session_lifecycle() -> Result<()> {
     let start_req = PlaybackStartRequest {
         config: config.clone(),
         lease_ttl_secs: Some(300),
+        negotiate: None,
     };
 
     let resp = client
@@ -114,6 +115,7 @@ async fn test_playback_seek() -> Result<()> {
     let start_req = PlaybackStartRequest {
         config,
         lease_ttl_secs: Some(300),
+        negotiate: None,
     };
 
     let resp = client
@@ -181,6 +183,7 @@ async fn test_playback_controls() -> Result<()> {
     let start_req = PlaybackStartRequest {
         config,
         lease_ttl_secs: Some(300),
+        negotiate: None,
     };
 
     let resp = client
@@ -321,6 +324,7 @@ async fn test_ll_hls_playback_config() -> Result<()> {
     let start_req = PlaybackStartRequest {
         config,
         lease_ttl_secs: Some(300),
+        negotiate: None,
     };
 
     let resp = client
@@ -76,6 +76,7 @@ async fn test_recorder_submits_frames_to_ai_service() -> Result<()> {
         source_uri: Some("rtsp://example.com/stream".to_string()),
         retention_hours: Some(24),
         format: Some(RecordingFormat::Mp4),
+        encoding: None,
     };
 
     let ai_config = RecordingAiConfig {
@@ -85,12 +86,16 @@ async fn test_recorder_submits_frames_to_ai_service() -> Result<()> {
         frame_width: 320,
         frame_height: 240,
         jpeg_quality: 10,
+        auto_tags: Vec::new(),
     };
 
     let req = RecordingStartRequest {
         config,
         lease_ttl_secs: Some(30),
         ai_config: Some(ai_config),
+        motion_policy: None,
+        tags: Vec::new(),
+        labels: Default::default(),
     };
 
     let response = RECORDING_MANAGER.start(req).await?;
@@ -130,12 +135,16 @@ async fn test_recorder_without_ai_config() -> Result<()> {
         source_uri: Some("rtsp://example.com/stream".to_string()),
         retention_hours: Some(24),
         format: Some(RecordingFormat::Mp4),
+        encoding: None,
     };
 
     let req = RecordingStartRequest {
         config,
         lease_ttl_secs: Some(30),
         ai_config: None, // No AI processing
+        motion_policy: None,
+        tags: Vec::new(),
+        labels: Default::default(),
     };
 
     let response = RECORDING_MANAGER.start(req).await?;
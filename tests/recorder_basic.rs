@@ -8,6 +8,7 @@ async fn test_recording_types_serialization() {
     source_uri: None,
     retention_hours: Some(48),
     format: Some(RecordingFormat::Mp4),
+    encoding: None,
   };
 
   let json = serde_json::to_string(&config).unwrap();
@@ -36,12 +37,16 @@ async fn test_recording_start_request() {
     source_uri: Some("rtsp://camera.local/stream".to_string()),
     retention_hours: Some(24),
     format: None,
+    encoding: None,
   };
 
   let request = RecordingStartRequest {
     config: config.clone(),
     lease_ttl_secs: Some(120),
     ai_config: None,
+    motion_policy: None,
+    tags: Vec::new(),
+    labels: Default::default(),
   };
 
   assert_eq!(request.config.id, "test-rec");
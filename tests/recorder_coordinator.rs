@@ -69,7 +69,7 @@ async fn recorder_acquires_and_releases_lease() -> Result<()> {
 
   // Initialize recorder manager with coordinator
   let base = reqwest::Url::parse(&coordinator_url)?;
-  let client = Arc::new(HttpCoordinatorClient::new(base)?);
+  let client = Arc::new(HttpCoordinatorClient::new(base).await?);
   RECORDING_MANAGER
     .set_coordinator(client.clone(), "recorder-test".to_string())
     .await;
@@ -82,12 +82,16 @@ async fn recorder_acquires_and_releases_lease() -> Result<()> {
     source_uri: Some("rtsp://example.com/stream".to_string()),
     retention_hours: Some(24),
     format: Some(RecordingFormat::Mp4),
+    encoding: None,
   };
 
   let req = RecordingStartRequest {
     config,
     lease_ttl_secs: Some(30),
     ai_config: None,
+    motion_policy: None,
+    tags: Vec::new(),
+    labels: Default::default(),
   };
 
   let response = RECORDING_MANAGER.start(req).await?;
@@ -164,7 +168,7 @@ async fn recorder_lease_conflict() -> Result<()> {
 
   // Initialize recorder manager with coordinator
   let base = reqwest::Url::parse(&coordinator_url)?;
-  let client = Arc::new(HttpCoordinatorClient::new(base)?);
+  let client = Arc::new(HttpCoordinatorClient::new(base).await?);
   RECORDING_MANAGER
     .set_coordinator(client.clone(), "recorder-test".to_string())
     .await;
@@ -177,12 +181,16 @@ async fn recorder_lease_conflict() -> Result<()> {
     source_uri: Some("rtsp://example.com/stream".to_string()),
     retention_hours: Some(24),
     format: Some(RecordingFormat::Mp4),
+    encoding: None,
   };
 
   let req1 = RecordingStartRequest {
     config: config1,
     lease_ttl_secs: Some(30),
     ai_config: None,
+    motion_policy: None,
+    tags: Vec::new(),
+    labels: Default::default(),
   };
 
   let response1 = RECORDING_MANAGER.start(req1).await?;
@@ -195,12 +203,16 @@ async fn recorder_lease_conflict() -> Result<()> {
     source_uri: Some("rtsp://example.com/stream2".to_string()),
     retention_hours: Some(24),
     format: Some(RecordingFormat::Mp4),
+    encoding: None,
   };
 
   let req2 = RecordingStartRequest {
     config: config2,
     lease_ttl_secs: Some(30),
     ai_config: None,
+    motion_policy: None,
+    tags: Vec::new(),
+    labels: Default::default(),
   };
 
   let response2 = RECORDING_MANAGER.start(req2).await?;
@@ -233,7 +245,7 @@ async fn recorder_lease_renewal() -> Result<()> {
 
   // Initialize recorder manager with coordinator
   let base = reqwest::Url::parse(&coordinator_url)?;
-  let client = Arc::new(HttpCoordinatorClient::new(base)?);
+  let client = Arc::new(HttpCoordinatorClient::new(base).await?);
   RECORDING_MANAGER
     .set_coordinator(client.clone(), "recorder-test".to_string())
     .await;
@@ -246,12 +258,16 @@ async fn recorder_lease_renewal() -> Result<()> {
     source_uri: Some("rtsp://example.com/stream".to_string()),
     retention_hours: Some(24),
     format: Some(RecordingFormat::Mp4),
+    encoding: None,
   };
 
   let req = RecordingStartRequest {
     config,
     lease_ttl_secs: Some(2),
     ai_config: None,
+    motion_policy: None,
+    tags: Vec::new(),
+    labels: Default::default(),
   };
 
   let response = RECORDING_MANAGER.start(req).await?;
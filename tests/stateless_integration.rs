@@ -89,6 +89,8 @@ async fn test_state_store_save_retrieve_stream() -> Result<()> {
             uri: "rtsp://test.local/stream".to_string(),
             codec: None,
             container: None,
+            substream_uri: None,
+            thermal_palette: None,
         },
         state: StreamState::Running,
         lease_id: Some("test-lease-123".to_string()),
@@ -136,6 +138,7 @@ async fn test_state_store_save_retrieve_recording() -> Result<()> {
             source_uri: Some("rtsp://test.local/stream".to_string()),
             retention_hours: Some(24),
             format: Some(RecordingFormat::Mp4),
+            encoding: None,
         },
         state: RecordingState::Recording,
         lease_id: Some("test-lease-456".to_string()),
@@ -184,6 +187,8 @@ async fn test_state_store_list_streams_by_node() -> Result<()> {
             uri: "rtsp://test.local/stream1".to_string(),
             codec: None,
             container: None,
+            substream_uri: None,
+            thermal_palette: None,
         },
         state: StreamState::Running,
         lease_id: Some("lease-1".to_string()),
@@ -202,6 +207,8 @@ async fn test_state_store_list_streams_by_node() -> Result<()> {
             uri: "rtsp://test.local/stream2".to_string(),
             codec: None,
             container: None,
+            substream_uri: None,
+            thermal_palette: None,
         },
         state: StreamState::Running,
         lease_id: Some("lease-2".to_string()),
@@ -251,6 +258,8 @@ async fn test_state_store_update_stream_state() -> Result<()> {
             uri: "rtsp://test.local/stream".to_string(),
             codec: None,
             container: None,
+            substream_uri: None,
+            thermal_palette: None,
         },
         state: StreamState::Pending,
         lease_id: Some("test-lease-123".to_string()),
@@ -308,6 +317,8 @@ async fn test_orphan_detection() -> Result<()> {
             uri: "rtsp://test.local/stream".to_string(),
             codec: None,
             container: None,
+            substream_uri: None,
+            thermal_palette: None,
         },
         state: StreamState::Error,
         lease_id: Some("orphan-lease-123".to_string()),
@@ -328,6 +339,8 @@ async fn test_orphan_detection() -> Result<()> {
             uri: "rtsp://test.local/stream2".to_string(),
             codec: None,
             container: None,
+            substream_uri: None,
+            thermal_palette: None,
         },
         state: StreamState::Running,
         lease_id: Some("active-lease-456".to_string()),
@@ -382,6 +395,8 @@ async fn test_state_store_http_api() -> Result<()> {
             uri: "rtsp://test.local/stream".to_string(),
             codec: None,
             container: None,
+            substream_uri: None,
+            thermal_palette: None,
         },
         state: StreamState::Running,
         lease_id: Some("http-test-lease".to_string()),
@@ -471,6 +486,8 @@ async fn test_state_persistence_across_restart() -> Result<()> {
                 uri: "rtsp://test.local/stream".to_string(),
                 codec: None,
                 container: None,
+                substream_uri: None,
+                thermal_palette: None,
             },
             state: StreamState::Running,
             lease_id: Some("persistent-lease".to_string()),
@@ -535,6 +552,8 @@ async fn test_bootstrap_recovery() -> Result<()> {
                 uri: format!("rtsp://test.local/stream-{}", stream_id),
                 codec: None,
                 container: None,
+                substream_uri: None,
+                thermal_palette: None,
             },
             state: StreamState::Running,
             lease_id: Some(format!("lease-{}", stream_id)),
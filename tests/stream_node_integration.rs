@@ -14,6 +14,8 @@ async fn test_stream_config_serialization() -> Result<()> {
         uri: "rtsp://example.com/stream".to_string(),
         codec: Some("h264".to_string()),
         container: Some("ts".to_string()),
+        substream_uri: None,
+        thermal_palette: None,
     };
 
     let serialized = serde_json::to_string(&config)?;
@@ -36,6 +38,8 @@ async fn test_stream_start_request_serialization() -> Result<()> {
             uri: "rtsp://example.com/stream".to_string(),
             codec: Some("h264".to_string()),
             container: Some("fmp4".to_string()),
+            substream_uri: None,
+            thermal_palette: None,
         },
         lease_ttl_secs: Some(60),
     };
@@ -68,6 +72,8 @@ async fn test_stream_config_with_camera() {
         uri: "rtsp://camera.local/stream".to_string(),
         codec: Some("h265".to_string()),
         container: Some("ts".to_string()),
+        substream_uri: None,
+        thermal_palette: None,
     };
 
     assert!(config.camera_id.is_some());
@@ -83,6 +89,8 @@ async fn test_stream_config_minimal() {
         uri: "rtsp://camera.local/stream".to_string(),
         codec: None,
         container: None,
+        substream_uri: None,
+        thermal_palette: None,
     };
 
     assert!(config.camera_id.is_none());